@@ -1,7 +1,11 @@
 pub mod errors;
 pub mod options;
+pub mod replay;
 pub mod run;
+pub mod safe_mode;
+pub mod startup;
 pub mod state;
+pub mod sync_once;
 pub mod upgrade;
 
 pub use self::errors::UpgradeErr;
@@ -1,12 +1,28 @@
 // standard crates
+use std::sync::Arc;
 use std::time::Duration;
 
 // internal crates
-use crate::deploy::fsm;
-use crate::network::BackendUrl;
+use crate::app::{safe_mode, startup};
+use crate::cache::admission;
+use crate::cell;
+use crate::crypt::{digest, keystore::PrivateKey};
+use crate::deploy::{fsm, hooks, pause, reboot, schedule, signature, space, trash, versions};
+use crate::http::{priority, record::Recorder};
+use crate::logs;
+use crate::network::{egress, BackendUrl};
+#[cfg(feature = "server")]
 use crate::server;
 use crate::storage::{Capacities, Layout};
-use crate::workers::{mqtt, poller, token_refresh::TokenRefreshWorkerOptions};
+use crate::sync::{backoff, event_log, history, network, warming};
+#[cfg(feature = "mqtt")]
+use crate::workers::mqtt;
+#[cfg(feature = "telemetry")]
+use crate::workers::telemetry;
+use crate::workers::{
+    cache_audit, drift, journal, long_poll, notifications, poller, supervisor,
+    token_refresh::TokenRefreshWorkerOptions, updater, wear,
+};
 
 #[derive(Debug, Clone, Copy)]
 pub struct LifecycleOptions {
@@ -32,48 +48,202 @@ impl Default for LifecycleOptions {
 #[derive(Debug, Default)]
 pub struct StorageOptions {
     pub layout: Layout,
+    /// The device's private key. The PEM file in the layout's auth directory if unset.
+    pub private_key: Option<PrivateKey>,
     pub capacities: Capacities,
+    pub content_admission: admission::Policy,
+    /// The most bytes the config instance content cache may take up.
+    pub content_max_bytes: Option<usize>,
+    /// The algorithm digests of newly cached content are computed with.
+    pub content_digest_algorithm: digest::Algorithm,
+}
+
+impl StorageOptions {
+    pub fn private_key(&self) -> PrivateKey {
+        self.private_key
+            .clone()
+            .unwrap_or_else(|| self.layout.auth().private_key().into())
+    }
 }
 
 #[derive(Debug)]
 pub struct AppOptions {
     pub lifecycle: LifecycleOptions,
+    pub safe_mode: safe_mode::Status,
+    pub startup: startup::Options,
 
     pub storage: StorageOptions,
     pub token_refresh_worker: TokenRefreshWorkerOptions,
     pub dpl_retry_policy: fsm::RetryPolicy,
+    pub dpl_reboot: reboot::Options,
+    pub dpl_hooks: hooks::Options,
+    pub dpl_validate_schemas: bool,
+    /// Files removed with deployments are unlinked immediately if unset.
+    pub trash: Option<trash::Trash>,
+    /// Config instances are only deployed as versioned releases under a deployment
+    /// directory if set.
+    pub dpl_versions: Option<versions::Versions>,
+    /// Content is downloaded and deployed regardless of free disk space if unset.
+    pub dpl_space: Option<space::Guard>,
+    /// Release signatures are verified before deploying if set.
+    pub dpl_signatures: Option<signature::Options>,
+    /// Deployments are applied at any time if empty.
+    pub dpl_windows: schedule::Windows,
+    /// Deployments are never paused if unset.
+    pub dpl_pause: Option<pause::Switch>,
 
     pub backend_base_url: BackendUrl,
+    pub http_scheduling: priority::Options,
+    /// Records every exchange with the backend for `replay`.
+    pub http_recorder: Option<Arc<Recorder>>,
 
+    /// Ignored, with a warning, when the agent is built without the server feature.
     pub enable_socket_server: bool,
+    /// Lets the socket server change the log level at runtime.
+    pub log_level: logs::LevelControl,
+    #[cfg(feature = "server")]
     pub server: server::Options,
+    /// Serves the metrics on a TCP listener at this address as well as on the socket.
+    /// Ignored, with a warning, when the agent is built without the server feature.
+    pub metrics_addr: Option<String>,
+
+    /// A cell controller proxies the backend for its peers; a cell peer sends its
+    /// backend requests to the controller and doesn't run the MQTT worker, since the
+    /// broker isn't reachable from the cell's subnet.
+    pub cell: cell::Options,
 
+    /// The proxy and CA bundle the HTTP client reaches the backend with. The MQTT
+    /// client trusts the CA bundle but doesn't use the proxy.
+    pub egress: egress::Options,
+
+    /// Ignored, with a warning, when the agent is built without the mqtt feature.
     pub enable_mqtt_worker: bool,
+    #[cfg(feature = "mqtt")]
     pub mqtt_worker: mqtt::Options,
 
     pub enable_poller: bool,
     pub poller: poller::Options,
+
+    /// Long polls the backend for sync requests, for networks where MQTT is blocked.
+    pub enable_long_poll: bool,
+    pub long_poll: long_poll::Options,
+
+    /// Persisting the history is skipped in low wear mode.
+    pub sync_history: history::Options,
+    /// Persisting the events is skipped in low wear mode.
+    pub sync_events: event_log::Options,
+    /// Persisting the progress is skipped in low wear mode.
+    pub content_warming: warming::Options,
+    pub sync_backoff: backoff::Policies,
+    /// The device's network is reported with each sync, if enabled.
+    pub sync_network: network::Options,
+
+    pub enable_cache_audit: bool,
+    pub cache_audit: cache_audit::Options,
+
+    /// Redeploys deployments whose files no longer hold their deployed content.
+    pub enable_drift_detection: bool,
+    pub drift_detection: drift::Options,
+
+    pub journal_worker: journal::Options,
+
+    /// Limits the agent's writes to flash storage: events aren't persisted and the
+    /// storage wear totals are flushed less often.
+    pub low_wear_mode: bool,
+    pub wear_worker: wear::Options,
+
+    /// The notifications worker only runs if at least one sink is configured.
+    pub notifications: notifications::Options,
+
+    /// Ignored, with a warning, when the agent is built without the telemetry feature.
+    pub enable_telemetry: bool,
+    #[cfg(feature = "telemetry")]
+    pub telemetry: telemetry::Options,
+
+    pub enable_updater: bool,
+    pub updater: updater::Options,
+
+    /// Runs and supervises the customer application, even in safe mode.
+    pub enable_supervisor: bool,
+    pub supervisor: supervisor::Options,
+
+    /// Uploads the reports of past crashes once the agent syncs, even in safe mode.
+    pub enable_crash_reports: bool,
 }
 
 impl Default for AppOptions {
     fn default() -> Self {
         Self {
             lifecycle: LifecycleOptions::default(),
+            safe_mode: safe_mode::Status::default(),
+            startup: startup::Options::default(),
 
             storage: StorageOptions::default(),
             token_refresh_worker: TokenRefreshWorkerOptions::default(),
             dpl_retry_policy: fsm::RetryPolicy::default(),
+            dpl_reboot: reboot::Options::default(),
+            dpl_hooks: hooks::Options::default(),
+            dpl_validate_schemas: true,
+            trash: None,
+            dpl_versions: None,
+            dpl_space: None,
+            dpl_signatures: None,
+            dpl_windows: schedule::Windows::default(),
+            dpl_pause: None,
 
             backend_base_url: BackendUrl::default(),
+            http_scheduling: priority::Options::default(),
+            http_recorder: None,
 
             enable_socket_server: true,
+            log_level: logs::LevelControl::default(),
+            #[cfg(feature = "server")]
             server: server::Options::default(),
+            metrics_addr: None,
+
+            cell: cell::Options::default(),
+            egress: egress::Options::default(),
 
             enable_mqtt_worker: true,
+            #[cfg(feature = "mqtt")]
             mqtt_worker: mqtt::Options::default(),
 
             enable_poller: true,
             poller: poller::Options::default(),
+
+            enable_long_poll: false,
+            long_poll: long_poll::Options::default(),
+
+            sync_history: history::Options::default(),
+            sync_events: event_log::Options::default(),
+            content_warming: warming::Options::default(),
+            sync_backoff: backoff::Policies::default(),
+            sync_network: network::Options::default(),
+
+            enable_cache_audit: true,
+            cache_audit: cache_audit::Options::default(),
+
+            enable_drift_detection: true,
+            drift_detection: drift::Options::default(),
+
+            journal_worker: journal::Options::default(),
+
+            low_wear_mode: false,
+            wear_worker: wear::Options::default(),
+
+            notifications: notifications::Options::default(),
+
+            enable_telemetry: false,
+            #[cfg(feature = "telemetry")]
+            telemetry: telemetry::Options::default(),
+
+            enable_updater: false,
+            updater: updater::Options::default(),
+
+            enable_supervisor: false,
+            supervisor: supervisor::Options::default(),
+
+            enable_crash_reports: true,
         }
     }
 }
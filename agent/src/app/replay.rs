@@ -0,0 +1,136 @@
+// Reproduces a field-reported reconciliation bug by running a single sync against a
+// capture recorded with `run --record` instead of the backend. The sync runs against
+// its own data directory so that nothing on the machine replaying it is touched.
+
+// standard crates
+use std::fmt::Write;
+
+// internal crates
+use crate::deploy::apply;
+use crate::events;
+use crate::http::record::{Capture, Replayer, Usage};
+use crate::models;
+use crate::server::errors::ServerErr;
+use crate::storage::{self, Capacities, Layout};
+use crate::sync::{deployments, SyncErr};
+
+// external crates
+use serde::Serialize;
+
+/// Requests are replayed against this base URL. Captures store paths relative to the
+/// backend they were recorded against so any base URL works.
+pub const BASE_URL: &str = "http://replay.invalid";
+
+/// Used when the data directory doesn't identify a device.
+pub const DEVICE_ID: &str = "replay";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    /// Write config instances to their filepaths. Deployments are only reconciled
+    /// with the capture otherwise.
+    pub apply: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub usage: Usage,
+    /// Why the sync failed, empty if it succeeded.
+    pub errors: Vec<String>,
+    /// The deployments the sync left behind, by id.
+    pub deployments: Vec<models::Deployment>,
+}
+
+impl Report {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if self.errors.is_empty() {
+            out.push_str("Sync succeeded\n");
+        } else {
+            out.push_str("Sync failed:\n");
+            for error in &self.errors {
+                let _ = writeln!(out, "  {error}");
+            }
+        }
+        let _ = writeln!(
+            out,
+            "Served {} recorded responses ({} unused)",
+            self.usage.served, self.usage.unused
+        );
+        for miss in &self.usage.misses {
+            let _ = writeln!(out, "  no recorded response for {miss}");
+        }
+        let _ = writeln!(out, "Deployments: {}", self.deployments.len());
+        for dpl in &self.deployments {
+            let _ = writeln!(
+                out,
+                "  {} {} (error: {}, attempts: {})",
+                dpl.id,
+                dpl.activity_status.as_str(),
+                dpl.error_status.as_str(),
+                dpl.attempts
+            );
+        }
+        out
+    }
+}
+
+pub async fn run(layout: &Layout, capture: Capture, opts: Options) -> Result<Report, ServerErr> {
+    let device_id = storage::resolve_device_id(layout)
+        .await
+        .unwrap_or_else(|_| DEVICE_ID.to_string());
+    let (storage, storage_handle) =
+        storage::Storage::init(layout, Capacities::default(), device_id).await?;
+    let (event_hub, event_hub_handle) = events::EventHub::spawn(
+        layout.events_log_file(),
+        events::hub::SpawnOptions {
+            persist: false,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let replayer = Replayer::new(BASE_URL, capture);
+    let sync_storage = deployments::Storage {
+        deployments: storage.deployments.as_ref(),
+        cfg_insts: storage.cfg_insts.as_ref(),
+        releases: storage.releases.as_ref(),
+        git_commits: storage.git_commits.as_ref(),
+        journal: storage.journal.as_ref(),
+        locks: storage.locks.as_ref(),
+    };
+    let result = deployments::sync(
+        &deployments::SyncArgs {
+            http_client: &replayer,
+            storage: &sync_storage,
+            opts: &apply::DeployOpts {
+                safe_mode: !opts.apply,
+                ..Default::default()
+            },
+            token: DEVICE_ID,
+            event_hub: &event_hub,
+            warming: &Default::default(),
+            list_validators: &Default::default(),
+            audit_anchor: None,
+        },
+        &mut Default::default(),
+    )
+    .await;
+
+    let mut deployments = storage.deployments.values().await?;
+    deployments.sort_by(|a, b| a.id.cmp(&b.id));
+
+    event_hub.shutdown().await?;
+    storage.shutdown().await?;
+    let _ = event_hub_handle.await;
+    storage_handle.await;
+
+    Ok(Report {
+        usage: replayer.usage(),
+        errors: match result {
+            Ok(_) => Vec::new(),
+            Err(SyncErr::SyncErrors(e)) => e.errors.iter().map(|e| e.to_string()).collect(),
+            Err(e) => vec![e.to_string()],
+        },
+        deployments,
+    })
+}
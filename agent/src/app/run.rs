@@ -1,5 +1,6 @@
 // standard crates
 use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
@@ -8,25 +9,73 @@ use std::time::{Duration, SystemTime};
 use crate::activity;
 use crate::app::{
     options::{AppOptions, LifecycleOptions},
+    startup::{self, Component, Startup},
     state::AppState,
 };
 use crate::authn::{self, TokenManagerExt};
+use crate::cell;
+use crate::clock;
+use crate::deploy::{apply, created_dirs, signature, versions};
+use crate::diagnostics::crash;
+use crate::events;
+use crate::filesys;
 use crate::http;
-use crate::server::{self, errors::*, serve::serve};
+use crate::metrics::workers::{instrument, Worker};
+use crate::network::egress;
+#[cfg(feature = "mqtt")]
+use crate::notifications::MqttMessage;
+use crate::notifications::{webhook, MqttOutbox};
+use crate::platform::systemd;
+use crate::server::errors::*;
+#[cfg(feature = "server")]
+use crate::server::{self, serve::serve};
+use crate::sync::{event_log, history, state, warming};
 use crate::trace;
+use crate::updater::{launch, Slots};
+#[cfg(feature = "mqtt")]
+use crate::workers::mqtt;
+#[cfg(feature = "telemetry")]
+use crate::workers::telemetry;
 use crate::workers::{
-    mqtt, poller,
+    cache_audit, crash_reports, drift, journal, long_poll, notifications, poller, supervisor,
     token_refresh::{run_token_refresh_worker, TokenRefreshWorkerOptions},
+    updater, watchdog, wear,
 };
 
 // external crates
-use tokio::sync::broadcast;
+use chrono::Utc;
+use futures::FutureExt;
+use tokio::sync::{broadcast, mpsc};
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+const MQTT_OUTBOX_CAPACITY: usize = 64;
 
 pub async fn run(
     options: AppOptions,
     shutdown_signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), ServerErr> {
+    // a panic is reported as an unclean exit rather than unwinding out of main. The
+    // panic hook has written the crash report by the time it's caught.
+    match AssertUnwindSafe(run_impl(options, shutdown_signal))
+        .catch_unwind()
+        .await
+    {
+        Ok(result) => result,
+        Err(payload) => {
+            let msg = crash::panic_message(payload.as_ref());
+            error!("The agent panicked: {msg}");
+            Err(ServerErr::PanickedErr(PanickedErr {
+                msg,
+                trace: trace!(),
+            }))
+        }
+    }
+}
+
+async fn run_impl(
+    options: AppOptions,
+    shutdown_signal: impl Future<Output = ()> + Send + 'static,
 ) -> Result<(), ServerErr> {
     info!("Initializing miru agent...");
 
@@ -34,16 +83,29 @@ pub async fn run(
     let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
         tokio::sync::broadcast::channel(1);
     let mut shutdown_manager = ShutdownManager::new(shutdown_tx.clone(), options.lifecycle);
+    let notifier = systemd::Notifier::from_env();
 
     // initialize the app (and shutdown if failures occur)
     let app_state = match init(&options, shutdown_tx.clone(), &mut shutdown_manager).await {
         Ok(state) => state,
         Err(e) => {
             error!("Failed to start server: {}", e);
+            notifier.notify(systemd::STOPPING);
             shutdown_manager.shutdown().await?;
             return Err(e);
         }
     };
+    notifier.notify(systemd::READY);
+    if let Some(timeout) = systemd::watchdog_timeout_from_env() {
+        init_watchdog_worker(
+            watchdog::Options::from_timeout(timeout),
+            notifier.clone(),
+            app_state.clone(),
+            &mut shutdown_manager,
+            shutdown_tx.subscribe(),
+        )
+        .await?;
+    }
 
     // if the app is not persistent, wait for ctrl-c, an idle timeout, or max runtime
     // reached to trigger a shutdown
@@ -63,6 +125,9 @@ pub async fn run(
             _ = await_max_runtime(options.lifecycle.max_runtime) => {
                 info!("Max runtime ({:?}) reached, shutting down...", options.lifecycle.max_runtime);
             }
+            _ = launch::restart_requested() => {
+                info!("Restart requested, shutting down...");
+            }
         }
     }
     // if the app is persistent, wait for ctrl-c to trigger a shutdown
@@ -71,10 +136,14 @@ pub async fn run(
             _ = shutdown_signal => {
                 info!("Shutdown signal received, shutting down...");
             }
+            _ = launch::restart_requested() => {
+                info!("Restart requested, shutting down...");
+            }
         }
     }
 
     // shutdown the server
+    notifier.notify(systemd::STOPPING);
     drop(shutdown_tx);
     shutdown_manager.shutdown().await
 }
@@ -112,69 +181,370 @@ async fn init(
     shutdown_tx: broadcast::Sender<()>,
     shutdown_manager: &mut ShutdownManager,
 ) -> Result<Arc<AppState>, ServerErr> {
-    let app_state = init_app_state(options, shutdown_manager).await?;
-
-    init_token_refresh_worker(
-        app_state.token_mngr.clone(),
-        options.token_refresh_worker.clone(),
-        shutdown_manager,
-        shutdown_tx.subscribe(),
-    )
-    .await?;
+    let safe_mode = options.safe_mode.active;
+    if safe_mode {
+        warn!(
+            "Starting in safe mode after {} crashes, deployments will not be applied and nonessential workers are disabled",
+            options.safe_mode.recent_crashes
+        );
+    }
 
+    #[cfg(not(feature = "server"))]
     if options.enable_socket_server {
-        init_socket_server(
-            options,
-            app_state.clone(),
-            shutdown_manager,
-            shutdown_tx.clone(),
-            shutdown_tx.subscribe(),
-        )
-        .await?;
+        warn!("The socket server is enabled but the agent was built without it");
+    }
+    #[cfg(not(feature = "server"))]
+    if let Some(addr) = &options.metrics_addr {
+        warn!("The metrics listener at {addr} is configured but the agent was built without the server feature");
+    }
+    #[cfg(not(feature = "server"))]
+    if let cell::Options::Controller { listen_addr, .. } = &options.cell {
+        warn!("The cell proxy at {listen_addr} is configured but the agent was built without the server feature");
+    }
+    #[cfg(not(feature = "mqtt"))]
+    if options.enable_mqtt_worker {
+        warn!("The MQTT worker is enabled but the agent was built without it");
+    }
+    #[cfg(not(feature = "telemetry"))]
+    if options.enable_telemetry {
+        warn!("The telemetry worker is enabled but the agent was built without it");
+    }
+    #[cfg(not(feature = "telemetry"))]
+    if options.dpl_space.is_some() {
+        warn!("The disk guard is enabled but the agent was built without telemetry, so free space isn't checked");
     }
 
-    if options.enable_poller {
-        init_poller_worker(
-            options.poller.clone(),
-            app_state.clone(),
-            shutdown_manager,
-            shutdown_tx.subscribe(),
+    let mut startup = Startup::new(options.startup.clone(), startup::enabled(options));
+    let Some(app_state) = startup
+        .start(
+            Component::AppState,
+            init_app_state(options, shutdown_manager),
         )
-        .await?;
+        .await
+    else {
+        return Err(startup.into_err());
+    };
+
+    // notifications for mqtt sinks are published by the mqtt worker since it owns
+    // the broker connection
+    let (mqtt_outbox, mqtt_outbox_rx) = mpsc::channel(MQTT_OUTBOX_CAPACITY);
+    #[cfg_attr(not(feature = "mqtt"), allow(unused_mut, unused_variables))]
+    let mut mqtt_outbox_rx = Some(mqtt_outbox_rx);
+
+    for component in startup.stages().into_iter().flatten() {
+        match component {
+            Component::AppState => {}
+            Component::TokenRefresh => {
+                let init = init_token_refresh_worker(
+                    app_state.token_mngr.clone(),
+                    app_state.event_hub.clone(),
+                    options.token_refresh_worker.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::SocketServer => {
+                #[cfg(feature = "server")]
+                {
+                    let init = init_socket_server(
+                        options,
+                        app_state.clone(),
+                        shutdown_manager,
+                        shutdown_tx.clone(),
+                        shutdown_tx.subscribe(),
+                    );
+                    startup.start(component, init).await;
+                }
+            }
+            Component::MetricsListener =>
+            {
+                #[cfg(feature = "server")]
+                if let Some(addr) = &options.metrics_addr {
+                    let init =
+                        init_metrics_listener(addr, shutdown_manager, shutdown_tx.subscribe());
+                    startup.start(component, init).await;
+                }
+            }
+            Component::CellProxy => {
+                #[cfg(feature = "server")]
+                if let cell::Options::Controller { listen_addr, peers } = &options.cell {
+                    let init = init_cell_proxy(
+                        listen_addr,
+                        options.backend_base_url.as_str(),
+                        peers.clone(),
+                        &options.egress,
+                        shutdown_manager,
+                        shutdown_tx.subscribe(),
+                    );
+                    startup.start(component, init).await;
+                }
+            }
+            // status updates are queued even in safe mode so the journal is always drained
+            Component::Journal => {
+                let init = init_journal_worker(
+                    options.journal_worker.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Wear => {
+                let init = init_wear_worker(
+                    options.wear_worker.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Poller => {
+                let init = init_poller_worker(
+                    options.poller.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::LongPoll => {
+                let init = init_long_poll_worker(
+                    options.long_poll.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Mqtt =>
+            {
+                #[cfg(feature = "mqtt")]
+                if let Some(mqtt_outbox_rx) = mqtt_outbox_rx.take() {
+                    let init = init_mqtt_worker(
+                        options.mqtt_worker.clone(),
+                        app_state.clone(),
+                        mqtt_outbox_rx,
+                        shutdown_manager,
+                        shutdown_tx.subscribe(),
+                    );
+                    startup.start(component, init).await;
+                }
+            }
+            Component::CacheAudit => {
+                let init = init_cache_audit_worker(
+                    options.cache_audit.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Drift => {
+                let init = init_drift_worker(
+                    options.drift_detection.clone(),
+                    options.dpl_versions.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Notifications => {
+                let init = init_notifications_worker(
+                    options.notifications.clone(),
+                    &options.egress,
+                    options.storage.layout.auth().webhook_keys(),
+                    app_state.clone(),
+                    mqtt_outbox.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Telemetry => {
+                #[cfg(feature = "telemetry")]
+                {
+                    let init = init_telemetry_worker(
+                        options.telemetry.clone(),
+                        app_state.clone(),
+                        mqtt_outbox.clone(),
+                        shutdown_manager,
+                        shutdown_tx.subscribe(),
+                    );
+                    startup.start(component, init).await;
+                }
+            }
+            Component::Updater => {
+                let init = init_updater_worker(
+                    options.updater.clone(),
+                    Slots::new(options.storage.layout.updates_dir()),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::Supervisor => {
+                let init = init_supervisor_worker(
+                    options.supervisor.clone(),
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+            Component::CrashReports => {
+                let init = init_crash_reports_worker(
+                    crash_reports::Options {
+                        layout: options.storage.layout.crash(),
+                    },
+                    app_state.clone(),
+                    shutdown_manager,
+                    shutdown_tx.subscribe(),
+                );
+                startup.start(component, init).await;
+            }
+        }
     }
+    startup.finish()?;
 
-    if options.enable_mqtt_worker {
-        init_mqtt_worker(
-            options.mqtt_worker.clone(),
-            app_state.clone(),
-            shutdown_manager,
-            shutdown_tx.subscribe(),
-        )
-        .await?;
+    // report safe mode once the notifications worker is listening for it
+    if safe_mode {
+        match events::EventArgs::safe_mode(options.safe_mode.recent_crashes) {
+            Ok(event) => app_state.event_hub.try_publish(event).await,
+            Err(e) => error!("failed to build safe mode event: {e}"),
+        }
     }
 
     Ok(app_state)
 }
 
-async fn init_app_state(
+pub(super) async fn init_app_state(
     options: &AppOptions,
     shutdown_manager: &mut ShutdownManager,
 ) -> Result<Arc<AppState>, ServerErr> {
     let (app_state, app_state_handle) = AppState::init(
         &options.storage.layout,
+        options.storage.private_key(),
         options.storage.capacities,
-        Arc::new(http::Client::new(options.backend_base_url.as_str())?),
-        options.dpl_retry_policy,
+        Arc::new(http_client(options)?),
+        apply::DeployOpts {
+            retry_policy: options.dpl_retry_policy,
+            reboot: options.dpl_reboot.clone(),
+            hooks: options.dpl_hooks.clone(),
+            validate_schemas: options.dpl_validate_schemas,
+            safe_mode: options.safe_mode.active,
+            trash: options.trash.clone(),
+            versions: options.dpl_versions.clone(),
+            created_dirs: Some(created_dirs::CreatedDirs::new(
+                options.storage.layout.created_dirs(),
+            )),
+            space: options.dpl_space.clone(),
+            signatures: options
+                .dpl_signatures
+                .as_ref()
+                .map(signature::Verifier::new),
+            windows: options.dpl_windows.clone(),
+            pause: options.dpl_pause.clone(),
+        },
+        events::hub::SpawnOptions {
+            persist: !options.low_wear_mode,
+            ..Default::default()
+        },
     )
     .await?;
+    app_state
+        .storage
+        .cfg_insts
+        .content
+        .set_admission_policy(options.storage.content_admission.clone())
+        .await?;
+    app_state
+        .storage
+        .cfg_insts
+        .content
+        .set_max_bytes(options.storage.content_max_bytes)
+        .await?;
+    app_state
+        .storage
+        .cfg_insts
+        .content
+        .set_digest_algorithm(options.storage.content_digest_algorithm)
+        .await?;
+    // syncs only verify the content they read, so the whole cache is verified once
+    // here and the next sync downloads whatever was corrupted again
+    match app_state.storage.cfg_insts.content.verify_all().await {
+        Ok(corrupted) if !corrupted.is_empty() => {
+            warn!("Deleted corrupted content for config instances: {corrupted:?}");
+        }
+        Ok(_) => {}
+        Err(e) => error!("unable to verify the config instance content: {e}"),
+    }
+    // entries outlive the retention while no deployment is removed as well
+    if let Some(trash) = &options.trash {
+        trash.expire(Utc::now()).await;
+    }
+    let mut history_opts = options.sync_history.clone();
+    if options.low_wear_mode {
+        history_opts.file = None;
+    }
+    app_state
+        .syncer
+        .set_sync_history(history::History::load(history_opts).await)
+        .await?;
+    let mut events_opts = options.sync_events.clone();
+    if options.low_wear_mode {
+        events_opts.file = None;
+    }
+    app_state
+        .syncer
+        .set_event_log(event_log::Log::load(events_opts).await)
+        .await?;
+    let mut warming_opts = options.content_warming.clone();
+    if options.low_wear_mode {
+        warming_opts.file = None;
+    }
+    app_state
+        .syncer
+        .set_content_warming(warming::Warming::load(warming_opts).await)
+        .await?;
+    app_state
+        .syncer
+        .set_backoff_policies(options.sync_backoff)
+        .await?;
+    if !options.low_wear_mode {
+        match state::open(options.storage.layout.sync_state()).await {
+            Ok(file) => app_state.syncer.set_state_file(file).await?,
+            Err(e) => error!("unable to persist the sync state: {e}"),
+        }
+    }
+    app_state
+        .syncer
+        .set_network_report(options.sync_network.clone())
+        .await?;
     let app_state = Arc::new(app_state);
     shutdown_manager.with_app_state(app_state.clone(), Box::pin(app_state_handle))?;
 
     Ok(app_state)
 }
 
+fn http_client(options: &AppOptions) -> Result<http::Client, ServerErr> {
+    let mut client = http::Client::new(options.backend_base_url.as_str())?
+        .with_scheduling(options.http_scheduling)
+        .with_egress(&options.egress)?;
+    if let Some(upstream) = options.cell.upstream() {
+        client = client.with_upstream(upstream.clone());
+    }
+    Ok(match &options.http_recorder {
+        Some(recorder) => client.with_recorder(recorder.clone()),
+        None => client,
+    })
+}
+
 async fn init_token_refresh_worker(
     token_mngr: Arc<authn::TokenManager>,
+    event_hub: events::EventHub,
     options: TokenRefreshWorkerOptions,
     shutdown_manager: &mut ShutdownManager,
     mut shutdown_rx: broadcast::Receiver<()>,
@@ -187,17 +557,18 @@ async fn init_token_refresh_worker(
     }
 
     // start the refresh worker
-    let token_refresh_handle = tokio::spawn(async move {
+    let token_refresh_handle = tokio::spawn(instrument(Worker::TokenRefresh, async move {
         run_token_refresh_worker(
             &options,
             token_mngr.as_ref(),
+            &event_hub,
             |wait| tokio::time::sleep(wait),
             Box::pin(async move {
                 let _ = shutdown_rx.recv().await;
             }),
         )
         .await;
-    });
+    }));
     shutdown_manager.register_handle(
         |mgr| &mut mgr.token_refresh_worker_handle,
         "token_refresh_handle",
@@ -206,7 +577,7 @@ async fn init_token_refresh_worker(
     Ok(())
 }
 
-async fn refresh_if_expired(token_mngr: &authn::TokenManager) -> Result<(), ServerErr> {
+pub(super) async fn refresh_if_expired(token_mngr: &authn::TokenManager) -> Result<(), ServerErr> {
     let token = token_mngr.get_token().await?;
     if token.is_expired() {
         token_mngr.refresh_token().await?;
@@ -224,19 +595,21 @@ async fn init_poller_worker(
 
     let syncer = app_state.syncer.clone();
     let device_stor = app_state.storage.device.clone();
+    let metrics = app_state.poller_metrics.clone();
 
-    let poller_handle = tokio::spawn(async move {
+    let poller_handle = tokio::spawn(instrument(Worker::Poller, async move {
         poller::run(
             &options,
             syncer.as_ref(),
             device_stor.as_ref(),
+            metrics.as_ref(),
             tokio::time::sleep,
             Box::pin(async move {
                 let _ = shutdown_rx.recv().await;
             }),
         )
         .await;
-    });
+    }));
     shutdown_manager.register_handle(
         |mgr| &mut mgr.poller_worker_handle,
         "poller_handle",
@@ -245,9 +618,46 @@ async fn init_poller_worker(
     Ok(())
 }
 
+async fn init_long_poll_worker(
+    options: long_poll::Options,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing long poll worker...");
+
+    let http_client = app_state.http_client.clone();
+    let token_mngr = app_state.token_mngr.clone();
+    let syncer = app_state.syncer.clone();
+    let device_stor = app_state.storage.device.clone();
+
+    let long_poll_handle = tokio::spawn(instrument(Worker::LongPoll, async move {
+        long_poll::run(
+            &options,
+            http_client.as_ref(),
+            token_mngr.as_ref(),
+            syncer.as_ref(),
+            device_stor.as_ref(),
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.long_poll_worker_handle,
+        "long_poll_handle",
+        long_poll_handle,
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "mqtt")]
 async fn init_mqtt_worker(
     options: mqtt::Options,
     app_state: Arc<AppState>,
+    outbox: mpsc::Receiver<MqttMessage>,
     shutdown_manager: &mut ShutdownManager,
     mut shutdown_rx: broadcast::Receiver<()>,
 ) -> Result<(), ServerErr> {
@@ -257,19 +667,20 @@ async fn init_mqtt_worker(
     let syncer = app_state.syncer.clone();
     let device_stor = app_state.storage.device.clone();
 
-    let mqtt_handle = tokio::spawn(async move {
+    let mqtt_handle = tokio::spawn(instrument(Worker::Mqtt, async move {
         mqtt::run(
             &options,
             token_mngr.as_ref(),
             syncer.as_ref(),
             device_stor.as_ref(),
+            outbox,
             tokio::time::sleep,
             Box::pin(async move {
                 let _ = shutdown_rx.recv().await;
             }),
         )
         .await;
-    });
+    }));
     shutdown_manager.register_handle(
         |mgr| &mut mgr.mqtt_worker_handle,
         "mqtt_handle",
@@ -278,6 +689,344 @@ async fn init_mqtt_worker(
     Ok(())
 }
 
+async fn init_cache_audit_worker(
+    options: cache_audit::Options,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing cache audit worker...");
+
+    let cache_audit_handle = tokio::spawn(instrument(Worker::CacheAudit, async move {
+        let deps = cache_audit::Deps {
+            http_client: app_state.http_client.as_ref(),
+            token_mngr: app_state.token_mngr.as_ref(),
+            syncer: app_state.syncer.as_ref(),
+            deployments: app_state.storage.deployments.as_ref(),
+        };
+        cache_audit::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.cache_audit_worker_handle,
+        "cache_audit_handle",
+        cache_audit_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_drift_worker(
+    options: drift::Options,
+    versions: Option<versions::Versions>,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing drift detection worker...");
+
+    let drift_handle = tokio::spawn(instrument(Worker::Drift, async move {
+        let storage = apply::Storage {
+            deployments: app_state.storage.deployments.as_ref(),
+            cfg_insts: app_state.storage.cfg_insts.as_ref(),
+            releases: app_state.storage.releases.as_ref(),
+        };
+        let deps = drift::Deps {
+            syncer: app_state.syncer.as_ref(),
+            storage: &storage,
+            versions: versions.as_ref(),
+        };
+        drift::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.drift_worker_handle,
+        "drift_handle",
+        drift_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_journal_worker(
+    options: journal::Options,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing journal worker...");
+
+    let journal_handle = tokio::spawn(instrument(Worker::Journal, async move {
+        let deps = journal::Deps {
+            http_client: app_state.http_client.as_ref(),
+            token_mngr: app_state.token_mngr.as_ref(),
+            journal: app_state.storage.journal.as_ref(),
+        };
+        journal::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.journal_worker_handle,
+        "journal_handle",
+        journal_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_wear_worker(
+    options: wear::Options,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing storage wear worker...");
+
+    let wear_handle = tokio::spawn(instrument(Worker::Wear, async move {
+        let deps = wear::Deps {
+            meter: app_state.storage.wear.as_ref(),
+        };
+        wear::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.wear_worker_handle,
+        "wear_handle",
+        wear_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_updater_worker(
+    options: updater::Options,
+    slots: Slots,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing updater worker...");
+
+    let updater_handle = tokio::spawn(instrument(Worker::Updater, async move {
+        let deps = updater::Deps {
+            http_client: app_state.http_client.as_ref(),
+            token_mngr: app_state.token_mngr.as_ref(),
+            syncer: app_state.syncer.as_ref(),
+            slots: &slots,
+            clock: clock::global(),
+        };
+        updater::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            launch::request_restart,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.updater_worker_handle,
+        "updater_handle",
+        updater_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_supervisor_worker(
+    options: supervisor::Options,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing supervisor worker...");
+
+    let events = app_state.event_hub.subscribe();
+    let supervisor_handle = tokio::spawn(instrument(Worker::Supervisor, async move {
+        supervisor::run(
+            &options,
+            tokio::time::sleep,
+            events,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.supervisor_worker_handle,
+        "supervisor_handle",
+        supervisor_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_crash_reports_worker(
+    options: crash_reports::Options,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing crash reports worker...");
+
+    let crash_reports_handle = tokio::spawn(instrument(Worker::CrashReports, async move {
+        let deps = crash_reports::Deps {
+            http_client: app_state.http_client.as_ref(),
+            token_mngr: app_state.token_mngr.as_ref(),
+            syncer: app_state.syncer.as_ref(),
+            device_stor: app_state.storage.device.as_ref(),
+            journal: app_state.storage.journal.as_ref(),
+        };
+        crash_reports::run(
+            &options,
+            &deps,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.crash_reports_worker_handle,
+        "crash_reports_handle",
+        crash_reports_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_watchdog_worker(
+    options: watchdog::Options,
+    notifier: systemd::Notifier,
+    app_state: Arc<AppState>,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing watchdog worker...");
+
+    let watchdog_handle = tokio::spawn(instrument(Worker::Watchdog, async move {
+        let deps = watchdog::Deps {
+            syncer: app_state.syncer.as_ref(),
+            token_mngr: app_state.token_mngr.as_ref(),
+            notifier: &notifier,
+        };
+        watchdog::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.watchdog_worker_handle,
+        "watchdog_handle",
+        watchdog_handle,
+    )?;
+    Ok(())
+}
+
+async fn init_notifications_worker(
+    options: notifications::Options,
+    egress: &egress::Options,
+    webhook_keys: filesys::File,
+    app_state: Arc<AppState>,
+    mqtt_outbox: MqttOutbox,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing notifications worker...");
+
+    let events = app_state.event_hub.subscribe();
+    let webhook_client = webhook::Client::with_egress(egress)?;
+    let notifications_handle = tokio::spawn(instrument(Worker::Notifications, async move {
+        let deps = notifications::Deps {
+            webhook_client: &webhook_client,
+            mqtt_outbox: &mqtt_outbox,
+            webhook_keys: &webhook_keys,
+        };
+        notifications::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            events,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.notifications_worker_handle,
+        "notifications_handle",
+        notifications_handle,
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "telemetry")]
+async fn init_telemetry_worker(
+    options: telemetry::Options,
+    app_state: Arc<AppState>,
+    mqtt_outbox: MqttOutbox,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing telemetry worker...");
+
+    let telemetry_handle = tokio::spawn(instrument(Worker::Telemetry, async move {
+        let deps = telemetry::Deps {
+            http_client: app_state.http_client.as_ref(),
+            token_mngr: app_state.token_mngr.as_ref(),
+            mqtt_outbox: &mqtt_outbox,
+            device_stor: app_state.storage.device.as_ref(),
+            journal: app_state.storage.journal.as_ref(),
+        };
+        telemetry::run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+    }));
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.telemetry_worker_handle,
+        "telemetry_handle",
+        telemetry_handle,
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "server")]
 async fn init_socket_server(
     options: &AppOptions,
     app_state: Arc<AppState>,
@@ -296,7 +1045,20 @@ async fn init_socket_server(
         app_state.activity_tracker.clone(),
         app_state.event_hub.clone(),
         shutdown_tx.clone(),
-    );
+    )
+    .with_safe_mode(options.safe_mode.active)
+    .with_low_wear_mode(options.low_wear_mode)
+    .with_log_level(options.log_level.clone())
+    .with_health(server::health::Probes {
+        mqtt_enabled: options.enable_mqtt_worker && cfg!(feature = "mqtt"),
+        poller_enabled: options.enable_poller && !options.safe_mode.active,
+        // the longest the poller waits between runs
+        poll_interval_secs: options.poller.poll_interval_secs + options.poller.jitter_secs,
+        poller: app_state.poller_metrics.clone(),
+        data_dir: Some(options.storage.layout.root()),
+    })
+    .with_versions(options.dpl_versions.clone())
+    .with_pause(options.dpl_pause.clone());
     let server_handle = serve(&options.server, Arc::new(server_state), async move {
         let _ = shutdown_rx.recv().await;
     })
@@ -306,13 +1068,80 @@ async fn init_socket_server(
     Ok(())
 }
 
+#[cfg(feature = "server")]
+async fn init_metrics_listener(
+    addr: &str,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing metrics listener on {addr}...");
+
+    // the metrics are only for monitoring, so failing to serve them doesn't stop the
+    // agent
+    let server_handle = match server::serve::serve_metrics(addr, async move {
+        let _ = shutdown_rx.recv().await;
+    })
+    .await
+    {
+        Ok(server_handle) => server_handle,
+        Err(e) => {
+            error!("Failed to start the metrics listener: {e}");
+            return Ok(());
+        }
+    };
+    let metrics_handle = tokio::spawn(async move {
+        match server_handle.await {
+            Ok(Err(e)) => error!("Metrics listener failed: {e}"),
+            Err(e) => error!("Metrics listener panicked: {e}"),
+            Ok(Ok(())) => {}
+        }
+    });
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.metrics_listener_handle,
+        "metrics_listener_handle",
+        metrics_handle,
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+async fn init_cell_proxy(
+    addr: &str,
+    backend_base_url: &str,
+    peers: cell::Peers,
+    egress: &egress::Options,
+    shutdown_manager: &mut ShutdownManager,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<(), ServerErr> {
+    info!("Initializing cell proxy on {addr}...");
+
+    let state = Arc::new(cell::proxy::State::new(backend_base_url, peers, egress)?);
+    let server_handle = cell::proxy::serve(addr, state, async move {
+        let _ = shutdown_rx.recv().await;
+    })
+    .await?;
+    let proxy_handle = tokio::spawn(async move {
+        match server_handle.await {
+            Ok(Err(e)) => error!("Cell proxy failed: {e}"),
+            Err(e) => error!("Cell proxy panicked: {e}"),
+            Ok(Ok(())) => {}
+        }
+    });
+    shutdown_manager.register_handle(
+        |mgr| &mut mgr.cell_proxy_handle,
+        "cell_proxy_handle",
+        proxy_handle,
+    )?;
+    Ok(())
+}
+
 // ================================= SHUTDOWN ===================================== //
 struct AppStateShutdownParams {
     state: Arc<AppState>,
     state_handle: Pin<Box<dyn Future<Output = ()> + Send>>,
 }
 
-struct ShutdownManager {
+pub(super) struct ShutdownManager {
     // shutdown transmitter
     shutdown_tx: broadcast::Sender<()>,
     lifecycle_options: LifecycleOptions,
@@ -320,8 +1149,21 @@ struct ShutdownManager {
     // server components requiring shutdown
     app_state: Option<AppStateShutdownParams>,
     socket_server_handle: Option<JoinHandle<Result<(), ServerErr>>>,
+    metrics_listener_handle: Option<JoinHandle<()>>,
+    cell_proxy_handle: Option<JoinHandle<()>>,
     poller_worker_handle: Option<JoinHandle<()>>,
+    long_poll_worker_handle: Option<JoinHandle<()>>,
     mqtt_worker_handle: Option<JoinHandle<()>>,
+    cache_audit_worker_handle: Option<JoinHandle<()>>,
+    drift_worker_handle: Option<JoinHandle<()>>,
+    journal_worker_handle: Option<JoinHandle<()>>,
+    wear_worker_handle: Option<JoinHandle<()>>,
+    notifications_worker_handle: Option<JoinHandle<()>>,
+    telemetry_worker_handle: Option<JoinHandle<()>>,
+    updater_worker_handle: Option<JoinHandle<()>>,
+    supervisor_worker_handle: Option<JoinHandle<()>>,
+    watchdog_worker_handle: Option<JoinHandle<()>>,
+    crash_reports_worker_handle: Option<JoinHandle<()>>,
     token_refresh_worker_handle: Option<JoinHandle<()>>,
 }
 
@@ -332,8 +1174,21 @@ impl ShutdownManager {
             lifecycle_options,
             app_state: None,
             socket_server_handle: None,
+            metrics_listener_handle: None,
+            cell_proxy_handle: None,
             poller_worker_handle: None,
+            long_poll_worker_handle: None,
             mqtt_worker_handle: None,
+            cache_audit_worker_handle: None,
+            drift_worker_handle: None,
+            journal_worker_handle: None,
+            wear_worker_handle: None,
+            notifications_worker_handle: None,
+            telemetry_worker_handle: None,
+            updater_worker_handle: None,
+            supervisor_worker_handle: None,
+            watchdog_worker_handle: None,
+            crash_reports_worker_handle: None,
             token_refresh_worker_handle: None,
         }
     }
@@ -380,6 +1235,7 @@ impl ShutdownManager {
         Ok(())
     }
 
+    #[cfg(feature = "server")]
     pub fn with_socket_server_handle(
         &mut self,
         socket_server_handle: JoinHandle<Result<(), ServerErr>>,
@@ -448,7 +1304,19 @@ impl ShutdownManager {
             info!("Poller worker handle not found, skipping poller worker shutdown...");
         }
 
-        // 3. mqtt
+        // 3. long poll
+        if let Some(long_poll_worker_handle) = self.long_poll_worker_handle.take() {
+            long_poll_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Long poll worker handle not found, skipping long poll worker shutdown...");
+        }
+
+        // 4. mqtt
         if let Some(mqtt_worker_handle) = self.mqtt_worker_handle.take() {
             mqtt_worker_handle.await.map_err(|e| {
                 ServerErr::JoinHandleErr(JoinHandleErr {
@@ -460,7 +1328,131 @@ impl ShutdownManager {
             info!("MQTT worker handle not found, skipping MQTT worker shutdown...");
         }
 
-        // 4. server
+        // 5. cache audit
+        if let Some(cache_audit_worker_handle) = self.cache_audit_worker_handle.take() {
+            cache_audit_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Cache audit worker handle not found, skipping cache audit worker shutdown...");
+        }
+
+        // 6. drift detection
+        if let Some(drift_worker_handle) = self.drift_worker_handle.take() {
+            drift_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Drift detection worker handle not found, skipping drift detection worker shutdown...");
+        }
+
+        // 7. journal
+        if let Some(journal_worker_handle) = self.journal_worker_handle.take() {
+            journal_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Journal worker handle not found, skipping journal worker shutdown...");
+        }
+
+        // 8. storage wear
+        if let Some(wear_worker_handle) = self.wear_worker_handle.take() {
+            wear_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Storage wear worker handle not found, skipping storage wear worker shutdown...");
+        }
+
+        // 9. notifications
+        if let Some(notifications_worker_handle) = self.notifications_worker_handle.take() {
+            notifications_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!(
+                "Notifications worker handle not found, skipping notifications worker shutdown..."
+            );
+        }
+
+        // 10. telemetry
+        if let Some(telemetry_worker_handle) = self.telemetry_worker_handle.take() {
+            telemetry_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Telemetry worker handle not found, skipping telemetry worker shutdown...");
+        }
+
+        // 11. updater
+        if let Some(updater_worker_handle) = self.updater_worker_handle.take() {
+            updater_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Updater worker handle not found, skipping updater worker shutdown...");
+        }
+
+        // 12. supervisor, which stops the application
+        if let Some(supervisor_worker_handle) = self.supervisor_worker_handle.take() {
+            supervisor_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Supervisor worker handle not found, skipping supervisor worker shutdown...");
+        }
+
+        // 13. watchdog
+        if let Some(watchdog_worker_handle) = self.watchdog_worker_handle.take() {
+            watchdog_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Watchdog worker handle not found, skipping watchdog worker shutdown...");
+        }
+
+        // 14. crash reports
+        if let Some(crash_reports_worker_handle) = self.crash_reports_worker_handle.take() {
+            crash_reports_worker_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!(
+                "Crash reports worker handle not found, skipping crash reports worker shutdown..."
+            );
+        }
+
+        // 15. server
         if let Some(socket_server_handle) = self.socket_server_handle.take() {
             socket_server_handle.await.map_err(|e| {
                 ServerErr::JoinHandleErr(JoinHandleErr {
@@ -472,7 +1464,31 @@ impl ShutdownManager {
             info!("Socket server handle not found, skipping socket server shutdown...");
         }
 
-        // 5. app state
+        // 16. metrics listener
+        if let Some(metrics_listener_handle) = self.metrics_listener_handle.take() {
+            metrics_listener_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Metrics listener handle not found, skipping metrics listener shutdown...");
+        }
+
+        // 17. cell proxy
+        if let Some(cell_proxy_handle) = self.cell_proxy_handle.take() {
+            cell_proxy_handle.await.map_err(|e| {
+                ServerErr::JoinHandleErr(JoinHandleErr {
+                    source: Box::new(e),
+                    trace: trace!(),
+                })
+            })?;
+        } else {
+            info!("Cell proxy handle not found, skipping cell proxy shutdown...");
+        }
+
+        // 18. app state
         if let Some(app_state) = self.app_state.take() {
             app_state.state.shutdown().await?;
             app_state.state_handle.await;
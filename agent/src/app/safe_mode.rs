@@ -0,0 +1,77 @@
+// internal crates
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// How many unclean exits within the window start the agent in safe mode. Safe
+    /// mode is disabled if zero.
+    pub max_crashes: u32,
+    pub window: TimeDelta,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_crashes: 5,
+            window: TimeDelta::minutes(10),
+        }
+    }
+}
+
+/// Whether the agent is running in safe mode. In safe mode deployments are not
+/// applied and only the workers needed to keep the local API, diagnostics, and
+/// reporting available are started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Status {
+    pub active: bool,
+    /// Unclean exits within the window, excluding the current start.
+    pub recent_crashes: u32,
+}
+
+/// The starts which haven't (yet) been followed by a clean shutdown. The record is
+/// cleared on every clean shutdown so any start remaining in it when the agent
+/// starts again ended in a crash.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashRecord {
+    pub unclean_starts: Vec<DateTime<Utc>>,
+}
+
+/// Records the current start and determines whether to start in safe mode.
+pub async fn record_start(
+    file: &filesys::File,
+    options: &Options,
+    now: DateTime<Utc>,
+) -> Result<Status, filesys::FileSysErr> {
+    let mut record = if file.exists() {
+        file.read_json::<CrashRecord>().await.unwrap_or_else(|e| {
+            warn!("crash record is unreadable, resetting it: {e}");
+            CrashRecord::default()
+        })
+    } else {
+        CrashRecord::default()
+    };
+    record
+        .unclean_starts
+        .retain(|started_at| now - *started_at < options.window);
+
+    let recent_crashes = record.unclean_starts.len() as u32;
+    let status = Status {
+        active: options.max_crashes > 0 && recent_crashes >= options.max_crashes,
+        recent_crashes,
+    };
+
+    record.unclean_starts.push(now);
+    file.write_json(&record, WriteOptions::OVERWRITE_ATOMIC)
+        .await?;
+    Ok(status)
+}
+
+/// Clears the crash record so the current run doesn't count as a crash.
+pub async fn record_clean_shutdown(file: &filesys::File) -> Result<(), filesys::FileSysErr> {
+    file.delete().await
+}
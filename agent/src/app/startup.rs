@@ -0,0 +1,376 @@
+// standard crates
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+// internal crates
+use crate::app::options::AppOptions;
+use crate::cell;
+use crate::server::errors::{ServerErr, StartupFailed};
+use crate::trace;
+
+// external crates
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+// ================================== COMPONENTS =================================== //
+/// The parts of the agent `app::run` starts. Components are started in their
+/// declaration order within a stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    /// The storage, caches, token manager and syncer every worker shares.
+    AppState,
+    TokenRefresh,
+    SocketServer,
+    MetricsListener,
+    /// The cell controller's proxy for its peers.
+    CellProxy,
+    Journal,
+    Wear,
+    Poller,
+    LongPoll,
+    Mqtt,
+    CacheAudit,
+    /// Redeploys deployments whose files were changed outside the agent.
+    Drift,
+    Notifications,
+    /// Reports the host's resource usage to the backend.
+    Telemetry,
+    Updater,
+    /// The customer application the agent is paired with.
+    Supervisor,
+    /// Uploads the reports of the agent's past crashes.
+    CrashReports,
+}
+
+impl Component {
+    pub const ALL: [Component; 17] = [
+        Component::AppState,
+        Component::TokenRefresh,
+        Component::SocketServer,
+        Component::MetricsListener,
+        Component::CellProxy,
+        Component::Journal,
+        Component::Wear,
+        Component::Poller,
+        Component::LongPoll,
+        Component::Mqtt,
+        Component::CacheAudit,
+        Component::Drift,
+        Component::Notifications,
+        Component::Telemetry,
+        Component::Updater,
+        Component::Supervisor,
+        Component::CrashReports,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Component::AppState => "app_state",
+            Component::TokenRefresh => "token_refresh",
+            Component::SocketServer => "socket_server",
+            Component::MetricsListener => "metrics_listener",
+            Component::CellProxy => "cell_proxy",
+            Component::Journal => "journal",
+            Component::Wear => "wear",
+            Component::Poller => "poller",
+            Component::LongPoll => "long_poll",
+            Component::Mqtt => "mqtt",
+            Component::CacheAudit => "cache_audit",
+            Component::Drift => "drift",
+            Component::Notifications => "notifications",
+            Component::Telemetry => "telemetry",
+            Component::Updater => "updater",
+            Component::Supervisor => "supervisor",
+            Component::CrashReports => "crash_reports",
+        }
+    }
+
+    /// The components which must be ready before this one starts. Dependencies which
+    /// aren't enabled are ignored.
+    pub fn dependencies(&self) -> &'static [Component] {
+        match self {
+            Component::AppState | Component::MetricsListener | Component::CellProxy => &[],
+            Component::TokenRefresh
+            | Component::SocketServer
+            | Component::Wear
+            | Component::Drift
+            | Component::Supervisor => &[Component::AppState],
+            // workers which talk to the backend wait for an expired token to be refreshed
+            Component::Journal
+            | Component::Poller
+            | Component::LongPoll
+            | Component::Mqtt
+            | Component::CacheAudit
+            | Component::Updater
+            | Component::CrashReports => &[Component::AppState, Component::TokenRefresh],
+            // notifications for mqtt sinks are published by the mqtt worker
+            Component::Notifications => &[Component::AppState, Component::Mqtt],
+            // samples are published over http or by the mqtt worker
+            Component::Telemetry => &[
+                Component::AppState,
+                Component::TokenRefresh,
+                Component::Mqtt,
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The components the options enable. Safe mode disables the nonessential workers and
+/// components the agent was built without are never enabled.
+pub fn enabled(options: &AppOptions) -> BTreeSet<Component> {
+    let safe_mode = options.safe_mode.active;
+    let mut enabled = BTreeSet::from([
+        Component::AppState,
+        Component::TokenRefresh,
+        Component::Journal,
+        Component::Wear,
+    ]);
+    let server = cfg!(feature = "server");
+    let optional = [
+        (
+            Component::SocketServer,
+            options.enable_socket_server && server,
+        ),
+        (
+            Component::MetricsListener,
+            options.metrics_addr.is_some() && server,
+        ),
+        (
+            Component::CellProxy,
+            matches!(options.cell, cell::Options::Controller { .. }) && server,
+        ),
+        (Component::Poller, options.enable_poller && !safe_mode),
+        (Component::LongPoll, options.enable_long_poll && !safe_mode),
+        (
+            Component::Mqtt,
+            options.enable_mqtt_worker
+                && cfg!(feature = "mqtt")
+                && options.cell.upstream().is_none(),
+        ),
+        (
+            Component::CacheAudit,
+            options.enable_cache_audit && !safe_mode,
+        ),
+        (
+            Component::Drift,
+            options.enable_drift_detection && !safe_mode,
+        ),
+        (
+            Component::Notifications,
+            !options.notifications.sinks.is_empty(),
+        ),
+        (
+            Component::Telemetry,
+            options.enable_telemetry && cfg!(feature = "telemetry") && !safe_mode,
+        ),
+        // kept in safe mode so a version on trial which lands in it is rolled back
+        (Component::Updater, options.enable_updater),
+        // the application keeps running whatever state the agent is in
+        (Component::Supervisor, options.enable_supervisor),
+        // crash loops are what lands the agent in safe mode
+        (Component::CrashReports, options.enable_crash_reports),
+    ];
+    enabled.extend(
+        optional
+            .into_iter()
+            .filter(|(_, is_enabled)| *is_enabled)
+            .map(|(component, _)| component),
+    );
+    enabled
+}
+
+/// Groups the enabled components into stages which only depend on components in
+/// earlier stages.
+pub fn stages(enabled: &BTreeSet<Component>) -> Vec<Vec<Component>> {
+    let mut staged = BTreeSet::new();
+    let mut remaining: Vec<Component> = enabled.iter().copied().collect();
+    let mut stages = Vec::new();
+    while !remaining.is_empty() {
+        let (stage, rest): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|component| {
+            component
+                .dependencies()
+                .iter()
+                .all(|dep| !enabled.contains(dep) || staged.contains(dep))
+        });
+        // the graph is acyclic so this can't happen, but never loop forever
+        if stage.is_empty() {
+            stages.push(rest);
+            break;
+        }
+        staged.extend(stage.iter().copied());
+        stages.push(stage);
+        remaining = rest;
+    }
+    stages
+}
+
+// ==================================== OPTIONS ==================================== //
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How long each component may take to become ready.
+    pub timeout: Duration,
+    /// Overrides `timeout` for individual components.
+    pub timeouts: BTreeMap<Component, Duration>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            timeouts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Options {
+    pub fn timeout(&self, component: Component) -> Duration {
+        self.timeouts
+            .get(&component)
+            .copied()
+            .unwrap_or(self.timeout)
+    }
+}
+
+// ==================================== STARTUP ==================================== //
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Ready,
+    Failed(String),
+    TimedOut(Duration),
+    /// Not started since this dependency didn't become ready.
+    Skipped(Component),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub component: Component,
+    pub status: Status,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            Status::Ready => write!(f, "{} ready after {:?}", self.component, self.elapsed),
+            Status::Failed(e) => write!(f, "{} failed: {e}", self.component),
+            Status::TimedOut(timeout) => {
+                write!(f, "{} timed out after {timeout:?}", self.component)
+            }
+            Status::Skipped(dep) => write!(f, "{} skipped since {dep} isn't ready", self.component),
+        }
+    }
+}
+
+/// Starts the enabled components stage by stage, recording whether each became
+/// ready. A component whose dependency isn't ready is skipped rather than started.
+#[derive(Debug)]
+pub struct Startup {
+    options: Options,
+    enabled: BTreeSet<Component>,
+    outcomes: Vec<Outcome>,
+}
+
+impl Startup {
+    pub fn new(options: Options, enabled: BTreeSet<Component>) -> Self {
+        Self {
+            options,
+            enabled,
+            outcomes: Vec::new(),
+        }
+    }
+
+    pub fn stages(&self) -> Vec<Vec<Component>> {
+        stages(&self.enabled)
+    }
+
+    pub fn outcomes(&self) -> &[Outcome] {
+        &self.outcomes
+    }
+
+    pub fn is_ready(&self, component: Component) -> bool {
+        self.outcomes
+            .iter()
+            .any(|o| o.component == component && o.status == Status::Ready)
+    }
+
+    /// Runs a component's initialization unless one of its dependencies isn't ready,
+    /// returning its output if it became ready within its timeout.
+    pub async fn start<T, Fut>(&mut self, component: Component, init: Fut) -> Option<T>
+    where
+        Fut: Future<Output = Result<T, ServerErr>>,
+    {
+        let blocked_by = component
+            .dependencies()
+            .iter()
+            .find(|dep| self.enabled.contains(dep) && !self.is_ready(**dep));
+        if let Some(dep) = blocked_by {
+            warn!("Not starting {component} since {dep} isn't ready");
+            self.record(component, Status::Skipped(*dep), Duration::ZERO);
+            return None;
+        }
+
+        let started_at = Instant::now();
+        let timeout = self.options.timeout(component);
+        let (status, output) = match tokio::time::timeout(timeout, init).await {
+            Ok(Ok(output)) => (Status::Ready, Some(output)),
+            Ok(Err(e)) => {
+                error!("Failed to start {component}: {e}");
+                (Status::Failed(e.to_string()), None)
+            }
+            Err(_) => {
+                error!("Timed out after {timeout:?} starting {component}");
+                (Status::TimedOut(timeout), None)
+            }
+        };
+        self.record(component, status, started_at.elapsed());
+        output
+    }
+
+    fn record(&mut self, component: Component, status: Status, elapsed: Duration) {
+        self.outcomes.push(Outcome {
+            component,
+            status,
+            elapsed,
+        });
+    }
+
+    /// Returns an error listing every component which didn't become ready.
+    pub fn finish(self) -> Result<(), ServerErr> {
+        if self
+            .outcomes
+            .iter()
+            .all(|outcome| outcome.status == Status::Ready)
+        {
+            let elapsed: Duration = self.outcomes.iter().map(|o| o.elapsed).sum();
+            info!(
+                "Started {} components in {:?}",
+                self.outcomes.len(),
+                elapsed
+            );
+            return Ok(());
+        }
+        Err(self.into_err())
+    }
+
+    pub fn into_err(self) -> ServerErr {
+        let failures = self
+            .outcomes
+            .into_iter()
+            .filter(|outcome| outcome.status != Status::Ready)
+            .collect();
+        ServerErr::StartupFailed(StartupFailed {
+            failures,
+            trace: trace!(),
+        })
+    }
+}
@@ -6,13 +6,15 @@ use std::sync::Arc;
 use crate::activity;
 use crate::authn::{self, token_mngr::TokenFile, TokenManagerExt};
 use crate::cooldown;
-use crate::deploy::{apply, fsm};
+use crate::crypt::keystore::PrivateKey;
+use crate::deploy::apply;
 use crate::events;
 use crate::filesys::PathExt;
 use crate::http;
 use crate::server;
 use crate::storage;
 use crate::sync::{self, syncer::SyncerArgs, SyncerExt};
+use crate::workers::poller;
 
 #[derive(Clone, Debug)]
 pub struct AppState {
@@ -22,19 +24,21 @@ pub struct AppState {
     pub token_mngr: Arc<authn::TokenManager>,
     pub activity_tracker: Arc<activity::Tracker>,
     pub event_hub: events::EventHub,
+    pub poller_metrics: Arc<poller::Metrics>,
 }
 
 impl AppState {
     pub async fn init(
         layout: &storage::Layout,
+        private_key: PrivateKey,
         capacities: storage::Capacities,
         http_client: Arc<http::Client>,
-        dpl_retry_policy: fsm::RetryPolicy,
+        deploy_opts: apply::DeployOpts,
+        event_opts: events::hub::SpawnOptions,
     ) -> Result<(Self, impl Future<Output = ()>), server::ServerErr> {
         // storage layout stuff
         let auth_dir = layout.auth();
-        let private_key_file = auth_dir.private_key();
-        private_key_file.assert_exists()?;
+        private_key.assert_exists()?;
         let public_key_file = auth_dir.public_key();
         public_key_file.assert_exists()?;
 
@@ -53,14 +57,14 @@ impl AppState {
             64,
             http_client.clone(),
             token_file,
-            private_key_file,
+            private_key,
             public_key_file,
         )?;
         let token_mngr = Arc::new(token_mngr);
 
         // initialize the event hub
         let (event_hub, event_hub_handle) =
-            events::EventHub::spawn(layout.events_log_file(), Default::default()).await?;
+            events::EventHub::spawn(layout.events_log_file(), event_opts).await?;
 
         // initialize the syncer
         let (syncer, syncer_handle) = sync::Syncer::spawn(
@@ -69,9 +73,7 @@ impl AppState {
                 storage: storage.clone(),
                 http_client: http_client.clone(),
                 token_mngr: token_mngr.clone(),
-                deploy_opts: apply::DeployOpts {
-                    retry_policy: dpl_retry_policy,
-                },
+                deploy_opts,
                 backoff: cooldown::Backoff {
                     base_secs: 1,
                     growth_factor: 2,
@@ -99,6 +101,7 @@ impl AppState {
                 token_mngr,
                 activity_tracker,
                 event_hub,
+                poller_metrics: Arc::new(poller::Metrics::default()),
             },
             shutdown_handle,
         ))
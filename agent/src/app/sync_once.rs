@@ -0,0 +1,146 @@
+// Runs a single full sync and exits, for minimal deployments which drive the agent
+// from cron or a pipeline instead of running it as a daemon. The app state is
+// initialized as it is for `run`, without any of the workers, and the outcome is
+// reported in a form scripts can act on.
+
+// standard crates
+use std::time::Instant;
+
+// internal crates
+use crate::app::{
+    options::AppOptions,
+    run::{init_app_state, refresh_if_expired, ShutdownManager},
+    state::AppState,
+};
+use crate::models::{DplActivity, DplErrStatus};
+use crate::sync::{history, SyncErr, SyncerExt};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    /// The syncer is cooling down after earlier failures and didn't sync.
+    Cooldown,
+    Failure,
+}
+
+impl Outcome {
+    /// The process exit status for the outcome. A cooldown exits with `EX_TEMPFAIL` so
+    /// schedulers can tell it apart from a failure and retry later.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::Cooldown => 75,
+            Outcome::Failure => 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeploymentStatus {
+    pub id: String,
+    pub activity_status: DplActivity,
+    pub error_status: DplErrStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub outcome: Outcome,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Why the sync failed, empty if it succeeded.
+    pub errors: Vec<String>,
+    /// The earliest the syncer would sync again.
+    pub next_sync_at: Option<DateTime<Utc>>,
+    /// The cached deployments after the sync.
+    pub deployments: Vec<DeploymentStatus>,
+}
+
+impl Report {
+    /// A report for a sync which couldn't be attempted, e.g. because the device isn't
+    /// activated.
+    pub fn failed(error: String) -> Self {
+        Self {
+            outcome: Outcome::Failure,
+            started_at: Utc::now(),
+            duration_ms: 0,
+            errors: vec![error],
+            next_sync_at: None,
+            deployments: Vec::new(),
+        }
+    }
+}
+
+pub async fn run(options: &AppOptions) -> Report {
+    let started_at = Utc::now();
+    let started = Instant::now();
+
+    let (shutdown_tx, _shutdown_rx) = broadcast::channel(1);
+    let mut shutdown_manager = ShutdownManager::new(shutdown_tx, options.lifecycle);
+    let mut report = match init_app_state(options, &mut shutdown_manager).await {
+        Ok(app_state) => sync(&app_state).await,
+        Err(e) => Report::failed(e.to_string()),
+    };
+    if let Err(e) = shutdown_manager.shutdown().await {
+        error!("Failed to shut down after syncing: {e}");
+    }
+
+    report.started_at = started_at;
+    report.duration_ms = history::millis(started.elapsed());
+    report
+}
+
+async fn sync(app_state: &AppState) -> Report {
+    if let Err(e) = refresh_if_expired(&app_state.token_mngr).await {
+        error!("Failed to refresh expired token: {e}");
+    }
+    let result = app_state.syncer.sync().await;
+
+    let outcome = match &result {
+        Ok(()) => Outcome::Success,
+        Err(SyncErr::InCooldownErr(_)) => Outcome::Cooldown,
+        Err(_) => Outcome::Failure,
+    };
+    let errors = match result {
+        Ok(()) => Vec::new(),
+        Err(SyncErr::SyncErrors(e)) => e.errors.iter().map(|e| e.to_string()).collect(),
+        Err(e) => vec![e.to_string()],
+    };
+    let next_sync_at = match app_state.syncer.get_sync_state().await {
+        Ok(state) => Some(state.cooldown_ends_at),
+        Err(e) => {
+            error!("Failed to read the syncer's state: {e}");
+            None
+        }
+    };
+    let mut deployments = match app_state.storage.deployments.values().await {
+        Ok(deployments) => deployments,
+        Err(e) => {
+            error!("Failed to read the cached deployments: {e}");
+            Vec::new()
+        }
+    };
+    deployments.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Report {
+        outcome,
+        started_at: Utc::now(),
+        duration_ms: 0,
+        errors,
+        next_sync_at,
+        deployments: deployments
+            .into_iter()
+            .map(|dpl| DeploymentStatus {
+                id: dpl.id,
+                activity_status: dpl.activity_status,
+                error_status: dpl.error_status,
+            })
+            .collect(),
+    }
+}
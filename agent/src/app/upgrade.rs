@@ -6,12 +6,14 @@ use std::time::Duration;
 use crate::app::errors::UpgradeErr;
 use crate::authn::{self, token::Token};
 use crate::cooldown;
-use crate::filesys::PathExt;
+use crate::crypt::keystore::PrivateKey;
+use crate::filesys::{PathExt, WriteOptions};
 use crate::http::{self, ClientI};
 use crate::models;
-use crate::storage::{self, Layout, Settings};
+use crate::storage::{self, migrations::Migration, Layout, Settings};
 
 // external crates
+use serde_json::{Map, Value};
 use tracing::{error, info, warn};
 
 pub struct Outcome {
@@ -19,20 +21,35 @@ pub struct Outcome {
     pub attempts: u32,
 }
 
+/// What chose the running version, which decides how a version change is reconciled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// The agent's package was installed or upgraded.
+    Package,
+    /// The self-updater switched versions, installing one on trial or rolling one
+    /// back. The versions it switches between share the device's state.
+    Updater,
+}
+
 /// Reconcile on-disk state with the running version. No-op if the marker matches;
-/// otherwise wipes per-version state and rebootstraps from the backend. Blocks
-/// indefinitely on network failure to avoid leaving a half-wiped device.
+/// otherwise wipes per-version state and rebootstraps from the backend, unless the
+/// self-updater switched versions, which only moves the marker. Blocks indefinitely
+/// on network failure to avoid leaving a half-wiped device. The storage migrations run
+/// last, on the state the reset keeps, so the reset never undoes them.
 pub async fn reconcile<F, Fut, HTTPClientT: ClientI>(
     layout: &Layout,
+    private_key: &PrivateKey,
     http_client: &HTTPClientT,
     version: &str,
+    trigger: Trigger,
+    migrations: &[Migration],
     sleep_fn: F,
 ) -> Result<Outcome, UpgradeErr>
 where
     F: Fn(Duration) -> Fut,
     Fut: Future<Output = ()> + Send,
 {
-    validate_layout(layout).await?;
+    validate_layout(layout, private_key).await?;
 
     let backoff = cooldown::Backoff {
         base_secs: 1,
@@ -41,25 +58,33 @@ where
     };
     let mut attempts: u32 = 0;
 
-    loop {
+    let outcome = loop {
         if !needs_upgrade(layout, version).await {
-            return Ok(Outcome {
+            break Outcome {
                 upgraded: false,
                 attempts,
-            });
+            };
+        }
+        if trigger == Trigger::Updater {
+            info!("self-updater switched to version '{version}', keeping the agent state");
+            storage::agent_version::write(&layout.agent_version(), version).await?;
+            break Outcome {
+                upgraded: true,
+                attempts,
+            };
         }
         info!("resetting miru agent state to use version '{}'", version);
 
-        match reconcile_impl(http_client, layout, version).await {
+        match reconcile_impl(http_client, layout, private_key, version).await {
             Ok(_) => {
                 info!(
                     "upgrade: resetting storage state for version '{}' complete",
                     version
                 );
-                return Ok(Outcome {
+                break Outcome {
                     upgraded: true,
                     attempts,
-                });
+                };
             }
             Err(e) => {
                 warn!("updating agent version storage failed: {e}");
@@ -69,7 +94,11 @@ where
                 sleep_fn(Duration::from_secs(wait as u64)).await;
             }
         }
-    }
+    };
+
+    // bring the kept state up to date with this version's layout
+    storage::migrations::apply(layout, migrations, version).await?;
+    Ok(outcome)
 }
 
 pub async fn needs_upgrade(layout: &Layout, cur_version: &str) -> bool {
@@ -97,33 +126,63 @@ pub async fn needs_upgrade(layout: &Layout, cur_version: &str) -> bool {
     }
 }
 
-pub async fn validate_layout(layout: &Layout) -> Result<(), UpgradeErr> {
-    let auth_dir = layout.auth();
-    auth_dir.private_key().assert_exists()?;
-    auth_dir.public_key().assert_exists()?;
+/// Whether the settings resolved after an upgrade stop verifying release signatures
+/// which the settings resolved before it verified. Only the device's settings may turn
+/// off the verification, never an upgrade.
+pub fn drops_release_signing(before: &Settings, after: &Settings) -> bool {
+    before.release_signing.verifier_options().is_some()
+        && after.release_signing.verifier_options().is_none()
+}
+
+pub async fn validate_layout(layout: &Layout, private_key: &PrivateKey) -> Result<(), UpgradeErr> {
+    if let Some(private_key_file) = private_key.file() {
+        private_key_file.assert_exists()?;
+    }
+    layout.auth().public_key().assert_exists()?;
     Ok(())
 }
 
 pub async fn reconcile_impl<HTTPClientT: ClientI>(
     http_client: &HTTPClientT,
     layout: &Layout,
+    private_key: &PrivateKey,
     version: &str,
 ) -> Result<(), UpgradeErr> {
-    let token = issue_token(http_client, layout).await?;
+    let token = issue_token(http_client, layout, private_key).await?;
     let device = fetch_device(http_client, &token).await?;
-    storage::setup::reset(layout, &device, &Settings::default(), version).await?;
+    keep_settings(layout).await?;
+    storage::setup::reset_state(layout, &device, version).await?;
     update_device(http_client, &device, version, &token).await?;
     Ok(())
 }
 
+/// Keeps the settings the device ran with before the upgrade, since they name where
+/// the private key is kept, unless they can't be read, which resets them to the
+/// defaults. They're kept as written so the storage migrations can still rename or
+/// re-encode their fields.
+async fn keep_settings(layout: &Layout) -> Result<(), UpgradeErr> {
+    let settings_file = layout.settings();
+    if settings_file.exists() {
+        match settings_file.read_json::<Map<String, Value>>().await {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                warn!("unable to read the settings file, resetting it to the defaults: {e}")
+            }
+        }
+    }
+    settings_file
+        .write_json(&Settings::default(), WriteOptions::OVERWRITE_ATOMIC)
+        .await?;
+    Ok(())
+}
+
 async fn issue_token<HTTPClientT: ClientI>(
     http_client: &HTTPClientT,
     layout: &Layout,
+    private_key: &PrivateKey,
 ) -> Result<Token, UpgradeErr> {
-    let auth_dir = layout.auth();
-    let private_key_file = auth_dir.private_key();
-    let public_key_file = auth_dir.public_key();
-    let token = authn::issue_token(http_client, &private_key_file, &public_key_file).await?;
+    let public_key_file = layout.auth().public_key();
+    let token = authn::issue_token(http_client, private_key, &public_key_file).await?;
     Ok(token)
 }
 
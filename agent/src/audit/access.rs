@@ -0,0 +1,430 @@
+// standard crates
+use std::fmt;
+
+// internal crates
+use crate::crypt::digest::{Algorithm, Digest};
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::error;
+
+/// The previous hash of the first access sealed into the chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// ================================== OPTIONS ====================================== //
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// The number of accesses kept. The oldest accesses are dropped first.
+    pub max_entries: usize,
+    /// Repeated reads of a config instance by the same client within this window are
+    /// folded into one access so a client polling its config doesn't flood the log.
+    pub dedup_window: TimeDelta,
+    /// The minimum time between writes of the log to disk. Accesses recorded since
+    /// the last write are persisted on the next write or when storage shuts down.
+    pub persist_interval: TimeDelta,
+    /// Accesses still being folded into are sealed into the hash chain once they have
+    /// been open this long, so a client polling its config is still covered.
+    pub max_open: TimeDelta,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            dedup_window: TimeDelta::minutes(1),
+            persist_interval: TimeDelta::minutes(5),
+            max_open: TimeDelta::minutes(5),
+        }
+    }
+}
+
+// ================================== ACCESSES ===================================== //
+/// The local process which made a request. The peer credentials are only known for
+/// requests made over the unix socket; requests over loopback record the address.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Client {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub pid: Option<i32>,
+    pub addr: Option<String>,
+}
+
+/// One or more reads of a config instance by a client.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Access {
+    pub client: Client,
+    pub config_instance_id: String,
+    pub filepath: String,
+    pub first_read_at: DateTime<Utc>,
+    pub last_read_at: DateTime<Utc>,
+    pub reads: u64,
+    /// Set once the access is sealed into the hash chain, after which no more reads
+    /// are folded into it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seal: Option<Seal>,
+}
+
+/// An access's link in the hash chain. The hash covers every field of the access
+/// along with its sequence number and the hash of the access sealed before it, so an
+/// access which is edited, removed or inserted after the fact breaks the chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seal {
+    pub seq: u64,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// The fields a seal's hash covers, in a fixed order.
+#[derive(Serialize)]
+struct Sealed<'a> {
+    seq: u64,
+    prev_hash: &'a str,
+    client: &'a Client,
+    config_instance_id: &'a str,
+    filepath: &'a str,
+    first_read_at: DateTime<Utc>,
+    last_read_at: DateTime<Utc>,
+    reads: u64,
+}
+
+fn chain_hash(access: &Access, seq: u64, prev_hash: &str) -> String {
+    let sealed = Sealed {
+        seq,
+        prev_hash,
+        client: &access.client,
+        config_instance_id: &access.config_instance_id,
+        filepath: &access.filepath,
+        first_read_at: access.first_read_at,
+        last_read_at: access.last_read_at,
+        reads: access.reads,
+    };
+    // serializing plain data to JSON can't fail
+    let json = serde_json::to_vec(&sealed).unwrap_or_default();
+    Digest::compute(Algorithm::Sha256, &json).hex().to_string()
+}
+
+/// The head of the hash chain. Anchors sent to the backend let a later verification
+/// tell whether the accesses sealed up to then were changed on the device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anchor {
+    pub seq: u64,
+    pub hash: String,
+}
+
+impl fmt::Display for Anchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.seq, self.hash)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorStatus {
+    /// The access at the anchor's sequence number has the anchor's hash.
+    Matches,
+    /// The access is missing or has another hash.
+    Mismatch,
+    /// The access has since been dropped from the log to make room.
+    Dropped,
+}
+
+/// The result of checking the hash chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Verification {
+    /// The number of sealed accesses checked.
+    pub sealed: usize,
+    pub head: Option<Anchor>,
+    /// The sequence number of the first sealed access whose hash or link to the
+    /// previous access doesn't check out.
+    pub broken_at: Option<u64>,
+    /// How the anchor the log was verified against, if any, compares.
+    pub anchor: Option<AnchorStatus>,
+}
+
+impl Verification {
+    pub fn is_intact(&self) -> bool {
+        self.broken_at.is_none() && self.anchor != Some(AnchorStatus::Mismatch)
+    }
+}
+
+/// Filters for querying the access log. Unset filters match every access.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Query {
+    pub config_instance_id: Option<String>,
+    pub uid: Option<u32>,
+    /// Only accesses last read at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    fn matches(&self, access: &Access) -> bool {
+        self.config_instance_id
+            .as_ref()
+            .is_none_or(|id| *id == access.config_instance_id)
+            && self.uid.is_none_or(|uid| access.client.uid == Some(uid))
+            && self.since.is_none_or(|since| access.last_read_at >= since)
+    }
+}
+
+#[derive(Debug)]
+struct Entries {
+    /// The sealed accesses in chain order followed by the open ones, so the accesses
+    /// dropped to make room are always the start of the chain.
+    accesses: Vec<Access>,
+    head: Option<Anchor>,
+    dirty: bool,
+    last_persisted_at: DateTime<Utc>,
+}
+
+/// Records which local clients read which config instances so security reviews can
+/// verify which processes consumed sensitive configuration. Accesses are sealed into
+/// a hash chain once no more reads can be folded into them, and the head of the
+/// chain is anchored with the backend on each sync, so accesses changed on a
+/// compromised device are detectable afterwards.
+#[derive(Debug)]
+pub struct AccessLog {
+    file: filesys::File,
+    options: Options,
+    inner: Mutex<Entries>,
+}
+
+impl AccessLog {
+    /// Loads the log from its file. An unreadable log is discarded so the agent always
+    /// comes up.
+    pub async fn init(file: filesys::File, options: Options) -> Self {
+        let mut accesses = if file.exists() {
+            match file.read_json::<Vec<Access>>().await {
+                Ok(accesses) => accesses,
+                Err(e) => {
+                    error!("discarding unreadable config access log: {e}");
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        accesses.sort_by_key(|a| (a.seal.is_none(), a.seal.as_ref().map(|s| s.seq)));
+        let head = accesses
+            .iter()
+            .filter_map(|a| a.seal.as_ref())
+            .next_back()
+            .map(|seal| Anchor {
+                seq: seal.seq,
+                hash: seal.hash.clone(),
+            });
+        Self {
+            file,
+            options,
+            inner: Mutex::new(Entries {
+                accesses,
+                head,
+                dirty: false,
+                // the first access is persisted immediately
+                last_persisted_at: DateTime::<Utc>::UNIX_EPOCH,
+            }),
+        }
+    }
+
+    pub async fn record(&self, client: &Client, config_instance_id: &str, filepath: &str) {
+        self.record_at(client, config_instance_id, filepath, Utc::now())
+            .await
+    }
+
+    pub async fn record_at(
+        &self,
+        client: &Client,
+        config_instance_id: &str,
+        filepath: &str,
+        now: DateTime<Utc>,
+    ) {
+        let mut inner = self.inner.lock().await;
+        self.seal_locked(&mut inner, now);
+        let window_start = now - self.options.dedup_window;
+        let recent = inner.accesses.iter_mut().rev().find(|a| {
+            a.seal.is_none()
+                && a.client == *client
+                && a.config_instance_id == config_instance_id
+                && a.last_read_at >= window_start
+        });
+        match recent {
+            Some(access) => {
+                access.last_read_at = now;
+                access.reads += 1;
+            }
+            None => {
+                inner.accesses.push(Access {
+                    client: client.clone(),
+                    config_instance_id: config_instance_id.to_string(),
+                    filepath: filepath.to_string(),
+                    first_read_at: now,
+                    last_read_at: now,
+                    reads: 1,
+                    seal: None,
+                });
+                let overflow = inner
+                    .accesses
+                    .len()
+                    .saturating_sub(self.options.max_entries);
+                inner.accesses.drain(..overflow);
+            }
+        }
+        inner.dirty = true;
+
+        if now - inner.last_persisted_at >= self.options.persist_interval {
+            self.persist_locked(&mut inner, now).await;
+        }
+    }
+
+    /// The accesses matching the query, most recently read first.
+    pub async fn query(&self, query: &Query) -> Vec<Access> {
+        let inner = self.inner.lock().await;
+        let mut accesses: Vec<Access> = inner
+            .accesses
+            .iter()
+            .filter(|access| query.matches(access))
+            .cloned()
+            .collect();
+        accesses.sort_by_key(|a| std::cmp::Reverse(a.last_read_at));
+        if let Some(limit) = query.limit {
+            accesses.truncate(limit);
+        }
+        accesses
+    }
+
+    /// Seals the accesses which are due and returns the head of the hash chain, if
+    /// any access has been sealed. Newly sealed accesses are written to disk first so
+    /// an anchor never refers to accesses a crash could lose.
+    pub async fn anchor(&self) -> Option<Anchor> {
+        let mut inner = self.inner.lock().await;
+        let now = Utc::now();
+        if self.seal_locked(&mut inner, now) {
+            self.persist_locked(&mut inner, now).await;
+        }
+        inner.head.clone()
+    }
+
+    /// Checks the hash chain and, if given, that the anchor sent to the backend still
+    /// matches the log.
+    pub async fn verify(&self, anchor: Option<&Anchor>) -> Verification {
+        let inner = self.inner.lock().await;
+        let mut broken_at = None;
+        let mut sealed = 0;
+        let mut prev: Option<&Seal> = None;
+        let chain = inner
+            .accesses
+            .iter()
+            .filter_map(|a| a.seal.as_ref().map(|seal| (a, seal)));
+        for (access, seal) in chain {
+            sealed += 1;
+            let linked = match prev {
+                // the accesses before the first one kept may have been dropped
+                None => seal.seq > 0 || seal.prev_hash == GENESIS_HASH,
+                Some(prev) => seal.seq == prev.seq + 1 && seal.prev_hash == prev.hash,
+            };
+            if !linked || chain_hash(access, seal.seq, &seal.prev_hash) != seal.hash {
+                broken_at = Some(seal.seq);
+                break;
+            }
+            prev = Some(seal);
+        }
+
+        let anchor = anchor.map(|anchor| {
+            let first = inner.accesses.first().and_then(|a| a.seal.as_ref());
+            match inner
+                .accesses
+                .iter()
+                .filter_map(|a| a.seal.as_ref())
+                .find(|seal| seal.seq == anchor.seq)
+            {
+                Some(seal) if seal.hash == anchor.hash => AnchorStatus::Matches,
+                None if first.is_some_and(|first| anchor.seq < first.seq) => AnchorStatus::Dropped,
+                _ => AnchorStatus::Mismatch,
+            }
+        });
+
+        Verification {
+            sealed,
+            head: inner.head.clone(),
+            broken_at,
+            anchor,
+        }
+    }
+
+    /// Seals, in the order they were recorded, the open accesses no more reads can be
+    /// folded into and those open longer than `max_open`. Returns whether any were.
+    fn seal_locked(&self, inner: &mut Entries, now: DateTime<Utc>) -> bool {
+        let window_start = now - self.options.dedup_window;
+        let first_open = inner
+            .accesses
+            .iter()
+            .take_while(|a| a.seal.is_some())
+            .count();
+        let mut sealed_prefix = first_open;
+        let mut sealed_any = false;
+        for i in first_open..inner.accesses.len() {
+            let access = &inner.accesses[i];
+            if access.last_read_at >= window_start
+                && now - access.first_read_at < self.options.max_open
+            {
+                continue;
+            }
+            let mut access = inner.accesses.remove(i);
+            let seq = inner.head.as_ref().map_or(0, |head| head.seq + 1);
+            let prev_hash = inner
+                .head
+                .as_ref()
+                .map_or(GENESIS_HASH.to_string(), |head| head.hash.clone());
+            let hash = chain_hash(&access, seq, &prev_hash);
+            inner.head = Some(Anchor {
+                seq,
+                hash: hash.clone(),
+            });
+            access.seal = Some(Seal {
+                seq,
+                prev_hash,
+                hash,
+            });
+            // moving the access to the end of the sealed ones leaves the next open
+            // access at the next index
+            inner.accesses.insert(sealed_prefix, access);
+            sealed_prefix += 1;
+            sealed_any = true;
+        }
+        if sealed_any {
+            inner.dirty = true;
+        }
+        sealed_any
+    }
+
+    /// Writes any accesses recorded since the last write to disk.
+    pub async fn persist(&self) -> Result<(), filesys::FileSysErr> {
+        let mut inner = self.inner.lock().await;
+        self.seal_locked(&mut inner, Utc::now());
+        if !inner.dirty {
+            return Ok(());
+        }
+        self.file
+            .write_json(&inner.accesses, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        inner.dirty = false;
+        inner.last_persisted_at = Utc::now();
+        Ok(())
+    }
+
+    async fn persist_locked(&self, inner: &mut Entries, now: DateTime<Utc>) {
+        // a failed write is retried after the interval rather than on every access
+        inner.last_persisted_at = now;
+        match self
+            .file
+            .write_json(&inner.accesses, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+        {
+            Ok(()) => inner.dirty = false,
+            Err(e) => error!("failed to persist config access log: {e}"),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod access;
+
+pub use self::access::{
+    Access, AccessLog, Anchor, AnchorStatus, Client, Options, Query, Seal, Verification,
+};
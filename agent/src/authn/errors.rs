@@ -22,6 +22,15 @@ pub struct SerdeErr {
 
 impl crate::errors::Error for SerdeErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("key rotation error: {msg}")]
+pub struct KeyRotationErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for KeyRotationErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("failed to send actor message: {source}")]
 pub struct SendActorMessageErr {
@@ -68,6 +77,8 @@ pub enum AuthnErr {
     #[error(transparent)]
     SerdeErr(SerdeErr),
     #[error(transparent)]
+    KeyRotationErr(KeyRotationErr),
+    #[error(transparent)]
     SendActorMessageErr(SendActorMessageErr),
     #[error(transparent)]
     ReceiveActorMessageErr(ReceiveActorMessageErr),
@@ -99,6 +110,7 @@ crate::impl_error!(AuthnErr {
     FileSysErr,
     HTTPErr,
     SerdeErr,
+    KeyRotationErr,
     SendActorMessageErr,
     ReceiveActorMessageErr,
     MockError,
@@ -3,7 +3,8 @@ use crate::authn::{
     errors::{AuthnErr, SerdeErr, TimestampConversionErr},
     token::Token,
 };
-use crate::crypt::{base64, rsa};
+use crate::clock;
+use crate::crypt::{base64, keystore::PrivateKey, rsa};
 use crate::filesys::file::File;
 use crate::http::{self, devices};
 use crate::trace;
@@ -12,6 +13,7 @@ use crate::trace;
 use chrono::{DateTime, Duration, Utc};
 use serde::Serialize;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
 #[derive(Serialize)]
 struct JwtHeader {
@@ -29,11 +31,11 @@ struct JwtPayload {
 
 pub async fn issue_token(
     http_client: &impl http::ClientI,
-    private_key_file: &File,
+    private_key: &PrivateKey,
     public_key_file: &File,
 ) -> Result<Token, AuthnErr> {
-    // build the self-signed JWT
-    let jwt = mint_jwt(private_key_file, public_key_file).await?;
+    // build the self-signed JWT, which is a credential until it expires
+    let jwt = Zeroizing::new(mint_jwt(private_key, public_key_file).await?);
 
     // send the token request
     let params = devices::IssueTokenParams { token: &jwt };
@@ -60,7 +62,10 @@ pub async fn issue_token(
 /// by this fingerprint and verifies the signature with the stored public key. The
 /// payload contains a unique `jti`, the current `iat`, and an `exp` two minutes in the
 /// future.
-pub async fn mint_jwt(private_key_file: &File, public_key_file: &File) -> Result<String, AuthnErr> {
+pub async fn mint_jwt(
+    private_key: &PrivateKey,
+    public_key_file: &File,
+) -> Result<String, AuthnErr> {
     // load the public key and compute its canonical fingerprint
     let public_key = rsa::read_public_key(public_key_file).await?;
     let kid = rsa::fingerprint(&public_key)?;
@@ -71,7 +76,8 @@ pub async fn mint_jwt(private_key_file: &File, public_key_file: &File) -> Result
         typ: "JWT",
         kid,
     };
-    let now = Utc::now();
+    // the backend checks the token's times against its own clock
+    let now = clock::now();
     let exp = now + Duration::minutes(2);
     let payload = JwtPayload {
         jti: Uuid::new_v4().to_string(),
@@ -81,7 +87,7 @@ pub async fn mint_jwt(private_key_file: &File, public_key_file: &File) -> Result
 
     // serialize header and payload, base64url-no-pad-encode, then join with '.'
     let signing_input = format!("{}.{}", encode_part(&header)?, encode_part(&payload)?);
-    let signature = rsa::sign_rs512(private_key_file, signing_input.as_bytes()).await?;
+    let signature = private_key.sign_rs512(signing_input.as_bytes()).await?;
     Ok(format!(
         "{signing_input}.{}",
         base64::encode_bytes_url_safe_no_pad(&signature),
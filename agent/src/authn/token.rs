@@ -1,9 +1,11 @@
 // internal crates
+use crate::clock;
 use crate::models::Patch;
 
 // external crates
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 #[derive(Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub struct Token {
@@ -22,7 +24,21 @@ impl std::fmt::Debug for Token {
 
 impl Token {
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.expires_at < clock::now()
+    }
+}
+
+// tokens are shared as `Arc<Token>` over the token manager's channel, so the token is
+// wiped once the last copy anywhere is dropped
+impl Zeroize for Token {
+    fn zeroize(&mut self) {
+        self.token.zeroize();
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        self.zeroize();
     }
 }
 
@@ -40,9 +56,16 @@ impl Updates {
     }
 }
 
+impl Drop for Updates {
+    fn drop(&mut self) {
+        self.token.zeroize();
+    }
+}
+
 impl Patch<Updates> for Token {
-    fn patch(&mut self, patch: Updates) {
-        if let Some(token) = patch.token {
+    fn patch(&mut self, mut patch: Updates) {
+        if let Some(token) = patch.token.take() {
+            self.token.zeroize();
             self.token = token;
         }
         if let Some(expires_at) = patch.expires_at {
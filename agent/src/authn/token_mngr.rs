@@ -1,17 +1,31 @@
+// Issues and caches the device's token. Every token issued is also published to
+// subscribers (see `TokenManagerExt::subscribe`) so long-lived connections, such as
+// the MQTT worker's, can switch to it before the old one is rejected.
+//
+// The manager also rotates the device's key pair. A new pair is generated beside the
+// current one, registered with the backend and then swapped in. The previous pair is
+// kept (as `<name>.old`) until a token is issued with the new one, which is the
+// backend's confirmation of the rotation. If the backend rejects the new key instead,
+// the previous pair is restored.
+
 // standard crates
 use std::sync::Arc;
 
 // internal crates
 use crate::authn::{errors::*, issue::issue_token, token, token::Token};
-use crate::filesys::{cached_file::SingleThreadCachedFile, file::File, path::PathExt};
-use crate::http;
+use crate::crypt::{keystore::PrivateKey, rsa};
+use crate::errors::{Error, HTTPCode};
+use crate::filesys::{
+    cached_file::SingleThreadCachedFile, file::File, path::PathExt, CopyOptions, Overwrite,
+};
+use crate::http::{self, devices};
 use crate::trace;
 
 // external crates
 use tokio::sync::mpsc::{self, Receiver, Sender};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, watch};
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 macro_rules! dispatch {
     ($op:expr, $respond_to:expr, $msg:expr) => {{
@@ -24,65 +38,255 @@ macro_rules! dispatch {
 
 pub type TokenFile = SingleThreadCachedFile<Token, token::Updates>;
 
+/// The size of the keys generated when rotating the device's key pair.
+const ROTATED_KEY_BITS: u32 = 4096;
+
 // =================================== TRAIT ======================================= //
 #[allow(async_fn_in_trait)]
 pub trait TokenManagerExt: Send + Sync {
     async fn shutdown(&self) -> Result<(), AuthnErr>;
     async fn get_token(&self) -> Result<Arc<Token>, AuthnErr>;
     async fn refresh_token(&self) -> Result<(), AuthnErr>;
+    /// Receives every token issued from now on. The receiver's current value is the
+    /// token at the time of subscribing and it closes once the manager shuts down.
+    fn subscribe(&self) -> watch::Receiver<Arc<Token>>;
 }
 
 // ======================== SINGLE THREADED IMPLEMENTATION ========================= //
 pub(crate) struct SingleThreadTokenManager<HTTPClientT: http::ClientI> {
     http_client: Arc<HTTPClientT>,
     token_file: TokenFile,
-    private_key_file: File,
+    private_key: PrivateKey,
     public_key_file: File,
+    rotations: watch::Sender<Arc<Token>>,
 }
 
 impl<HTTPClientT: http::ClientI> SingleThreadTokenManager<HTTPClientT> {
     pub(crate) fn new(
         http_client: Arc<HTTPClientT>,
         token_file: TokenFile,
-        private_key_file: File,
+        private_key: PrivateKey,
         public_key_file: File,
     ) -> Result<Self, AuthnErr> {
         token_file.file.assert_exists()?;
-        private_key_file.assert_exists()?;
+        private_key.assert_exists()?;
         public_key_file.assert_exists()?;
+        let (rotations, _) = watch::channel(token_file.cached());
         Ok(Self {
             http_client,
             token_file,
-            private_key_file,
+            private_key,
             public_key_file,
+            rotations,
         })
     }
 
+    fn subscribe(&self) -> watch::Receiver<Arc<Token>> {
+        self.rotations.subscribe()
+    }
+
     async fn get_token(&self) -> Arc<Token> {
         // get the token
         self.token_file.read().await
     }
 
     async fn refresh_token(&mut self) -> Result<(), AuthnErr> {
-        // attempt to issue a new token
-        let token = self.issue_token().await?;
+        // attempt to issue a new token, falling back to the previous key pair if the
+        // backend rejects a rotated one
+        let token = match self.issue_token().await {
+            Ok(token) => token,
+            Err(e) if is_auth_rejected(&e) && self.has_previous_keys() => {
+                warn!("backend rejected the rotated key pair, restoring the previous one: {e}");
+                self.restore_previous_keys().await?;
+                self.issue_token().await?
+            }
+            Err(e) => return Err(e),
+        };
+
+        // a token issued with the current key pair confirms any pending rotation
+        self.discard_previous_keys().await?;
 
         // update the token file
         self.token_file.write(token).await?;
 
+        // notify the subscribers
+        self.rotations.send_replace(self.token_file.read().await);
+
+        Ok(())
+    }
+
+    async fn rotate_key(&mut self) -> Result<String, AuthnErr> {
+        let Some(private_key_file) = self.private_key.file().cloned() else {
+            return Err(AuthnErr::KeyRotationErr(KeyRotationErr {
+                msg: "keys kept in hardware can't be rotated by the agent".to_string(),
+                trace: trace!(),
+            }));
+        };
+        if self.has_previous_keys() {
+            return Err(AuthnErr::KeyRotationErr(KeyRotationErr {
+                msg: "the previous key rotation has not been confirmed yet".to_string(),
+                trace: trace!(),
+            }));
+        }
+
+        // the new key is registered with the current token so it must be valid
+        if self.token_file.read().await.is_expired() {
+            self.refresh_token().await?;
+        }
+        let token = self.token_file.read().await;
+
+        // generate the new key pair beside the current one
+        let new_private_key_file = sibling(&private_key_file, "new")?;
+        let new_public_key_file = sibling(&self.public_key_file, "new")?;
+        rsa::gen_key_pair(
+            ROTATED_KEY_BITS,
+            &new_private_key_file,
+            &new_public_key_file,
+            Overwrite::Allow,
+        )
+        .await?;
+
+        // register the new public key with the backend
+        let registered = match register_key(
+            self.http_client.as_ref(),
+            &new_public_key_file,
+            &token.token,
+        )
+        .await
+        {
+            Ok(fingerprint) => fingerprint,
+            Err(e) => {
+                let _ = new_private_key_file.delete().await;
+                let _ = new_public_key_file.delete().await;
+                return Err(e);
+            }
+        };
+
+        // keep the current key pair until the rotation is confirmed and swap in the
+        // new one (renames are atomic so the key files are never partially written)
+        private_key_file
+            .copy_to(
+                &sibling(&private_key_file, "old")?,
+                CopyOptions::OVERWRITE_SYNC,
+            )
+            .await?;
+        self.public_key_file
+            .copy_to(
+                &sibling(&self.public_key_file, "old")?,
+                CopyOptions::OVERWRITE_SYNC,
+            )
+            .await?;
+        new_private_key_file
+            .move_to(&private_key_file, Overwrite::Allow)
+            .await?;
+        new_public_key_file
+            .move_to(&self.public_key_file, Overwrite::Allow)
+            .await?;
+        info!("rotated the device key pair to {registered}");
+
+        // confirm the rotation by issuing a token with the new key pair. Network
+        // errors leave the rotation pending for the next refresh to confirm.
+        if let Err(e) = self.refresh_token().await {
+            if !e.is_network_conn_err() {
+                return Err(e);
+            }
+            warn!("unable to confirm the key rotation, will retry on the next refresh: {e}");
+            return Ok(registered);
+        }
+
+        // the refresh restores the previous key pair if the backend rejected the new one
+        let current = rsa::fingerprint(&rsa::read_public_key(&self.public_key_file).await?)?;
+        if current != registered {
+            return Err(AuthnErr::KeyRotationErr(KeyRotationErr {
+                msg: format!("backend rejected key '{registered}', kept key '{current}'"),
+                trace: trace!(),
+            }));
+        }
+        Ok(registered)
+    }
+
+    fn has_previous_keys(&self) -> bool {
+        let Some(private_key_file) = self.private_key.file() else {
+            return false;
+        };
+        sibling(private_key_file, "old").is_ok_and(|f| f.exists())
+            && sibling(&self.public_key_file, "old").is_ok_and(|f| f.exists())
+    }
+
+    async fn restore_previous_keys(&self) -> Result<(), AuthnErr> {
+        let Some(private_key_file) = self.private_key.file() else {
+            return Ok(());
+        };
+        sibling(private_key_file, "old")?
+            .move_to(private_key_file, Overwrite::Allow)
+            .await?;
+        sibling(&self.public_key_file, "old")?
+            .move_to(&self.public_key_file, Overwrite::Allow)
+            .await?;
+        Ok(())
+    }
+
+    async fn discard_previous_keys(&self) -> Result<(), AuthnErr> {
+        let Some(private_key_file) = self.private_key.file() else {
+            return Ok(());
+        };
+        for file in [private_key_file, &self.public_key_file] {
+            let old = sibling(file, "old")?;
+            if old.exists() {
+                old.delete().await?;
+            }
+        }
         Ok(())
     }
 
     async fn issue_token(&self) -> Result<Token, AuthnErr> {
         issue_token(
             self.http_client.as_ref(),
-            &self.private_key_file,
+            &self.private_key,
             &self.public_key_file,
         )
         .await
     }
 }
 
+/// The file beside `file` with `suffix` appended to its name.
+fn sibling(file: &File, suffix: &str) -> Result<File, AuthnErr> {
+    Ok(file.parent()?.file(&format!("{}.{suffix}", file.name()?)))
+}
+
+fn is_auth_rejected(e: &AuthnErr) -> bool {
+    matches!(
+        e.http_status(),
+        HTTPCode::UNAUTHORIZED | HTTPCode::FORBIDDEN
+    )
+}
+
+/// Registers the public key with the backend and returns its fingerprint after
+/// checking that it matches the one the backend computed.
+async fn register_key(
+    http_client: &impl http::ClientI,
+    public_key_file: &File,
+    token: &str,
+) -> Result<String, AuthnErr> {
+    let fingerprint = rsa::fingerprint(&rsa::read_public_key(public_key_file).await?)?;
+    let public_key_pem = public_key_file.read_string().await?;
+    let params = devices::RegisterKeyParams {
+        public_key_pem: &public_key_pem,
+        token,
+    };
+    let registered = devices::register_key(http_client, params).await?;
+    if registered.fingerprint != fingerprint {
+        return Err(AuthnErr::KeyRotationErr(KeyRotationErr {
+            msg: format!(
+                "backend registered key '{}' instead of '{fingerprint}'",
+                registered.fingerprint
+            ),
+            trace: trace!(),
+        }));
+    }
+    Ok(fingerprint)
+}
+
 // ========================= MULTI-THREADED IMPLEMENTATION ========================= //
 pub(crate) enum Command {
     GetToken {
@@ -91,6 +295,9 @@ pub(crate) enum Command {
     RefreshToken {
         respond_to: oneshot::Sender<Result<(), AuthnErr>>,
     },
+    RotateKey {
+        respond_to: oneshot::Sender<Result<String, AuthnErr>>,
+    },
     Shutdown {
         respond_to: oneshot::Sender<Result<(), AuthnErr>>,
     },
@@ -125,6 +332,13 @@ impl<HTTPClientT: http::ClientI> Worker<HTTPClientT> {
                         "Actor failed to refresh token"
                     );
                 }
+                Command::RotateKey { respond_to } => {
+                    dispatch!(
+                        self.token_mngr.rotate_key().await,
+                        respond_to,
+                        "Actor failed to rotate key"
+                    );
+                }
             }
         }
     }
@@ -133,6 +347,7 @@ impl<HTTPClientT: http::ClientI> Worker<HTTPClientT> {
 #[derive(Debug)]
 pub struct TokenManager {
     sender: Sender<Command>,
+    rotations: watch::Receiver<Arc<Token>>,
 }
 
 impl TokenManager {
@@ -140,21 +355,25 @@ impl TokenManager {
         buffer_size: usize,
         http_client: Arc<HTTPClientT>,
         token_file: TokenFile,
-        private_key_file: File,
+        private_key: PrivateKey,
         public_key_file: File,
     ) -> Result<(Self, JoinHandle<()>), AuthnErr> {
         let (sender, receiver) = mpsc::channel(buffer_size);
+        let token_mngr =
+            SingleThreadTokenManager::new(http_client, token_file, private_key, public_key_file)?;
+        let rotations = token_mngr.subscribe();
         let worker = Worker {
-            token_mngr: SingleThreadTokenManager::new(
-                http_client,
-                token_file,
-                private_key_file,
-                public_key_file,
-            )?,
+            token_mngr,
             receiver,
         };
         let worker_handle = tokio::spawn(worker.run());
-        Ok((Self { sender }, worker_handle))
+        Ok((Self { sender, rotations }, worker_handle))
+    }
+
+    /// Rotates the device's key pair, returning the new public key's fingerprint.
+    pub async fn rotate_key(&self) -> Result<String, AuthnErr> {
+        self.send_command("rotate_key", |tx| Command::RotateKey { respond_to: tx })
+            .await?
     }
 
     async fn send_command<R>(
@@ -200,6 +419,10 @@ impl TokenManagerExt for TokenManager {
         })
         .await?
     }
+
+    fn subscribe(&self) -> watch::Receiver<Arc<Token>> {
+        self.rotations.clone()
+    }
 }
 
 impl TokenManagerExt for Arc<TokenManager> {
@@ -214,4 +437,8 @@ impl TokenManagerExt for Arc<TokenManager> {
     async fn refresh_token(&self) -> Result<(), AuthnErr> {
         self.as_ref().refresh_token().await
     }
+
+    fn subscribe(&self) -> watch::Receiver<Arc<Token>> {
+        self.as_ref().subscribe()
+    }
 }
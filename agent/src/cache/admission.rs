@@ -0,0 +1,119 @@
+// Admission policy for caches whose entries vary wildly in size. Entries the policy
+// doesn't admit are still written, since the agent may need them, but are marked
+// probationary so that pruning evicts them before any admitted entry. A probationary
+// entry is promoted once it has been read often enough to earn its space.
+
+// standard crates
+use std::collections::{BTreeMap, HashMap};
+
+// external crates
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// Always admitted, whatever the entry's size.
+    Always,
+    /// Never admitted, however often the entry is read.
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Admit,
+    Probation,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    /// Entries larger than this are only admitted once they've been accessed
+    /// `min_accesses` times. No threshold admits entries of any size.
+    pub max_entry_bytes: Option<usize>,
+    pub min_accesses: u32,
+    /// Rules by class (e.g. config type name) which take precedence over the size
+    /// threshold.
+    pub rules: BTreeMap<String, Rule>,
+    /// How many accesses are counted before every count is halved, so that entries
+    /// which were popular long ago don't stay admitted forever. Also bounds how many
+    /// keys are tracked.
+    pub window: usize,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_entry_bytes: None,
+            min_accesses: 3,
+            rules: BTreeMap::new(),
+            window: 1000,
+        }
+    }
+}
+
+impl Policy {
+    pub fn decide(&self, class: Option<&str>, bytes: usize, accesses: u32) -> Decision {
+        if let Some(rule) = class.and_then(|class| self.rules.get(class)) {
+            return match rule {
+                Rule::Always => Decision::Admit,
+                Rule::Never => Decision::Probation,
+            };
+        }
+        match self.max_entry_bytes {
+            Some(max) if bytes > max && accesses < self.min_accesses => Decision::Probation,
+            _ => Decision::Admit,
+        }
+    }
+}
+
+/// Counts how often each key is accessed, with counts aged over the policy's window.
+#[derive(Debug, Default)]
+pub struct Frequencies {
+    window: usize,
+    accesses: usize,
+    counts: HashMap<String, u32>,
+}
+
+impl Frequencies {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            accesses: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Records an access to the key and returns its count, including this access.
+    pub fn record(&mut self, key: &str) -> u32 {
+        self.accesses += 1;
+        if self.accesses > self.window {
+            self.age();
+        }
+        let count = self.counts.entry(key.to_string()).or_default();
+        *count = count.saturating_add(1);
+        *count
+    }
+
+    pub fn count(&self, key: &str) -> u32 {
+        self.counts.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn forget(&mut self, key: &str) {
+        self.counts.remove(key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    fn age(&mut self) {
+        self.accesses = 0;
+        self.counts.retain(|_, count| {
+            *count /= 2;
+            *count > 0
+        });
+    }
+}
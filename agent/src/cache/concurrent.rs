@@ -4,10 +4,12 @@ use std::fmt::Debug;
 
 // internal crates
 use crate::cache::{
+    admission,
     entry::CacheEntry,
     errors::{CacheErr, ReceiveActorMessageErr, SendActorMessageErr},
     single_thread::{CacheKey, CacheValue, SingleThreadCache},
 };
+use crate::crypt::digest;
 use crate::filesys::Overwrite;
 use crate::trace;
 
@@ -68,6 +70,7 @@ where
     Write {
         key: K,
         value: V,
+        class: Option<String>,
         is_dirty: IsDirty<K, V>,
         overwrite: Overwrite,
         respond_to: oneshot::Sender<Result<(), CacheErr>>,
@@ -82,12 +85,37 @@ where
         key: K,
         respond_to: oneshot::Sender<Result<(), CacheErr>>,
     },
+    ReadMany {
+        keys: Vec<K>,
+        respond_to: oneshot::Sender<Result<HashMap<K, V>, CacheErr>>,
+    },
+    WriteMany {
+        entries: Vec<(K, V)>,
+        is_dirty: IsDirty<K, V>,
+        overwrite: Overwrite,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
+    DeleteMany {
+        keys: Vec<K>,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
+    Pin {
+        key: K,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
+    Unpin {
+        key: K,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
     Prune {
         respond_to: oneshot::Sender<Result<(), CacheErr>>,
     },
     Size {
         respond_to: oneshot::Sender<Result<usize, CacheErr>>,
     },
+    SizeBytes {
+        respond_to: oneshot::Sender<Result<usize, CacheErr>>,
+    },
     Entries {
         respond_to: oneshot::Sender<Result<Vec<CacheEntry<K, V>>, CacheErr>>,
     },
@@ -131,6 +159,21 @@ where
     GetDirtyEntries {
         respond_to: oneshot::Sender<Result<Vec<CacheEntry<K, V>>, CacheErr>>,
     },
+    SetAdmissionPolicy {
+        policy: admission::Policy,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
+    SetMaxBytes {
+        max_bytes: Option<usize>,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
+    SetDigestAlgorithm {
+        algorithm: digest::Algorithm,
+        respond_to: oneshot::Sender<Result<(), CacheErr>>,
+    },
+    VerifyAll {
+        respond_to: oneshot::Sender<Result<Vec<K>, CacheErr>>,
+    },
 }
 
 // =================================== WORKER ====================================== //
@@ -194,13 +237,14 @@ where
                 Command::Write {
                     key,
                     value,
+                    class,
                     is_dirty,
                     overwrite,
                     respond_to,
                 } => {
                     dispatch!(
                         self,
-                        write(key, value, is_dirty, overwrite),
+                        write_with_class(key, value, class, is_dirty, overwrite),
                         respond_to,
                         "Actor failed to write cache entry"
                     );
@@ -226,12 +270,65 @@ where
                         "Actor failed to delete cache entry"
                     );
                 }
+                Command::ReadMany { keys, respond_to } => {
+                    dispatch!(
+                        self,
+                        read_many(&keys),
+                        respond_to,
+                        "Actor failed to read cache entries"
+                    );
+                }
+                Command::WriteMany {
+                    entries,
+                    is_dirty,
+                    overwrite,
+                    respond_to,
+                } => {
+                    dispatch!(
+                        self,
+                        write_many(entries, is_dirty, overwrite),
+                        respond_to,
+                        "Actor failed to write cache entries"
+                    );
+                }
+                Command::DeleteMany { keys, respond_to } => {
+                    dispatch!(
+                        self,
+                        delete_many(&keys),
+                        respond_to,
+                        "Actor failed to delete cache entries"
+                    );
+                }
+                Command::Pin { key, respond_to } => {
+                    dispatch!(
+                        self,
+                        pin(&key),
+                        respond_to,
+                        "Actor failed to pin cache entry"
+                    );
+                }
+                Command::Unpin { key, respond_to } => {
+                    dispatch!(
+                        self,
+                        unpin(&key),
+                        respond_to,
+                        "Actor failed to unpin cache entry"
+                    );
+                }
                 Command::Prune { respond_to } => {
                     dispatch!(self, prune(), respond_to, "Actor failed to prune cache");
                 }
                 Command::Size { respond_to } => {
                     dispatch!(self, size(), respond_to, "Actor failed to get cache size");
                 }
+                Command::SizeBytes { respond_to } => {
+                    dispatch!(
+                        self,
+                        size_bytes(),
+                        respond_to,
+                        "Actor failed to get cache size in bytes"
+                    );
+                }
                 Command::Entries { respond_to } => {
                     dispatch!(
                         self,
@@ -336,6 +433,44 @@ where
                         "Actor failed to get dirty entries"
                     );
                 }
+                Command::SetAdmissionPolicy { policy, respond_to } => {
+                    dispatch!(
+                        self,
+                        set_admission_policy(policy),
+                        respond_to,
+                        "Actor failed to set cache admission policy"
+                    );
+                }
+                Command::SetMaxBytes {
+                    max_bytes,
+                    respond_to,
+                } => {
+                    dispatch!(
+                        self,
+                        set_max_bytes(max_bytes),
+                        respond_to,
+                        "Actor failed to set cache byte limit"
+                    );
+                }
+                Command::SetDigestAlgorithm {
+                    algorithm,
+                    respond_to,
+                } => {
+                    dispatch!(
+                        self,
+                        set_digest_algorithm(algorithm),
+                        respond_to,
+                        "Actor failed to set cache digest algorithm"
+                    );
+                }
+                Command::VerifyAll { respond_to } => {
+                    dispatch!(
+                        self,
+                        verify_all(),
+                        respond_to,
+                        "Actor failed to verify cache entries"
+                    );
+                }
             }
         }
     }
@@ -439,12 +574,28 @@ where
         is_dirty: F,
         overwrite: Overwrite,
     ) -> Result<(), CacheErr>
+    where
+        F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync + 'static,
+    {
+        self.write_with_class(key, value, None, is_dirty, overwrite)
+            .await
+    }
+
+    pub async fn write_with_class<F>(
+        &self,
+        key: K,
+        value: V,
+        class: Option<String>,
+        is_dirty: F,
+        overwrite: Overwrite,
+    ) -> Result<(), CacheErr>
     where
         F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync + 'static,
     {
         self.send_command(|tx| Command::Write {
             key,
             value,
+            class,
             is_dirty: Box::new(is_dirty),
             overwrite,
             respond_to: tx,
@@ -473,6 +624,61 @@ where
         .await?
     }
 
+    /// Reads many entries in a single round trip to the actor, returning the values
+    /// of those which exist.
+    pub async fn read_many(&self, keys: Vec<K>) -> Result<HashMap<K, V>, CacheErr> {
+        self.send_command(|tx| Command::ReadMany {
+            keys,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Writes many entries in a single round trip to the actor. The entries before
+    /// the first which can't be written stay written.
+    pub async fn write_many<F>(
+        &self,
+        entries: Vec<(K, V)>,
+        is_dirty: F,
+        overwrite: Overwrite,
+    ) -> Result<(), CacheErr>
+    where
+        F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync + 'static,
+    {
+        self.send_command(|tx| Command::WriteMany {
+            entries,
+            is_dirty: Box::new(is_dirty),
+            overwrite,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    pub async fn delete_many(&self, keys: Vec<K>) -> Result<(), CacheErr> {
+        self.send_command(|tx| Command::DeleteMany {
+            keys,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Protects an entry from pruning until it's unpinned.
+    pub async fn pin(&self, key: K) -> Result<(), CacheErr> {
+        self.send_command(|tx| Command::Pin {
+            key,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    pub async fn unpin(&self, key: K) -> Result<(), CacheErr> {
+        self.send_command(|tx| Command::Unpin {
+            key,
+            respond_to: tx,
+        })
+        .await?
+    }
+
     pub async fn prune(&self) -> Result<(), CacheErr> {
         self.send_command(|tx| Command::Prune { respond_to: tx })
             .await?
@@ -483,6 +689,11 @@ where
             .await?
     }
 
+    pub async fn size_bytes(&self) -> Result<usize, CacheErr> {
+        self.send_command(|tx| Command::SizeBytes { respond_to: tx })
+            .await?
+    }
+
     pub async fn entries(&self) -> Result<Vec<CacheEntry<K, V>>, CacheErr> {
         self.send_command(|tx| Command::Entries { respond_to: tx })
             .await?
@@ -585,6 +796,41 @@ where
         .await?
     }
 
+    pub async fn set_admission_policy(&self, policy: admission::Policy) -> Result<(), CacheErr> {
+        self.send_command(|tx| Command::SetAdmissionPolicy {
+            policy,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Sets the most bytes the cache's entries may take up, pruning it if it's over
+    /// the new limit.
+    pub async fn set_max_bytes(&self, max_bytes: Option<usize>) -> Result<(), CacheErr> {
+        self.send_command(|tx| Command::SetMaxBytes {
+            max_bytes,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Sets the algorithm digests of written entries are computed with. Entries
+    /// already written keep their digests, which are still verified with the
+    /// algorithm they name.
+    pub async fn set_digest_algorithm(&self, algorithm: digest::Algorithm) -> Result<(), CacheErr> {
+        self.send_command(|tx| Command::SetDigestAlgorithm {
+            algorithm,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Deletes corrupted entries, returning their keys.
+    pub async fn verify_all(&self) -> Result<Vec<K>, CacheErr> {
+        self.send_command(|tx| Command::VerifyAll { respond_to: tx })
+            .await?
+    }
+
     pub async fn get_dirty_entries(&self) -> Result<Vec<CacheEntry<K, V>>, CacheErr> {
         self.send_command(|tx| Command::GetDirtyEntries { respond_to: tx })
             .await?
@@ -4,11 +4,13 @@ use std::fmt::Debug;
 
 // internal crates
 use crate::cache::{
+    admission::{self, Decision},
     concurrent::{Command, ConcurrentCache, ConcurrentCacheKey, ConcurrentCacheValue, Worker},
-    entry::CacheEntry,
-    errors::{CacheErr, CannotOverwriteCacheElement},
+    entry::{self, CacheEntry, Probation},
+    errors::{CacheErr, CannotOverwriteCacheElement, CorruptedCacheElement},
     single_thread::{CacheKey, CacheValue, SingleThreadCache},
 };
+use crate::crypt::digest;
 use crate::filesys::{dir::Dir, file, file::File, path::PathExt, Atomic, Overwrite, WriteOptions};
 use crate::trace;
 
@@ -16,6 +18,7 @@ use crate::trace;
 use futures::future::try_join_all;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tracing::debug;
 
 #[derive(Debug)]
 pub struct SingleThreadDirCache<K, V>
@@ -25,6 +28,10 @@ where
 {
     dir: Dir,
     capacity: usize,
+    max_bytes: Option<usize>,
+    admission: admission::Policy,
+    frequencies: admission::Frequencies,
+    digest_algorithm: digest::Algorithm,
     _phantom: std::marker::PhantomData<K>,
     _phantom2: std::marker::PhantomData<V>,
 }
@@ -37,9 +44,14 @@ where
     pub async fn new(dir: Dir, capacity: usize) -> Result<Self, CacheErr> {
         dir.create_if_absent().await?;
 
+        let admission = admission::Policy::default();
         Ok(Self {
             dir,
             capacity,
+            max_bytes: None,
+            frequencies: admission::Frequencies::new(admission.window),
+            admission,
+            digest_algorithm: digest::Algorithm::default(),
             _phantom: std::marker::PhantomData,
             _phantom2: std::marker::PhantomData,
         })
@@ -50,6 +62,12 @@ where
         filename = file::sanitize_filename(&filename);
         self.dir.file(&filename)
     }
+
+    fn decide(&self, entry: &CacheEntry<K, V>, class: Option<&str>) -> Decision {
+        let bytes = serde_json::to_vec(&entry.value).map_or(0, |bytes| bytes.len());
+        let accesses = self.frequencies.count(&entry.key.to_string());
+        self.admission.decide(class, bytes, accesses)
+    }
 }
 
 impl<K, V> SingleThreadCache<K, V> for SingleThreadDirCache<K, V>
@@ -65,6 +83,19 @@ where
 
         let entry = entry_file.read_json::<CacheEntry<K, V>>().await?;
 
+        // values on disk can rot (e.g. on SD cards) so refuse to serve a corrupted one
+        if let Some(expected) = &entry.digest {
+            let actual = entry::digest(expected.algorithm(), &entry.value);
+            if *expected != actual {
+                return Err(CacheErr::CorruptedCacheElement(CorruptedCacheElement {
+                    key: key.to_string(),
+                    expected: expected.to_string(),
+                    actual: actual.to_string(),
+                    trace: trace!(),
+                }));
+            }
+        }
+
         Ok(Some(entry))
     }
 
@@ -109,6 +140,26 @@ where
         Ok(self.capacity)
     }
 
+    async fn max_bytes(&self) -> Result<Option<usize>, CacheErr> {
+        Ok(self.max_bytes)
+    }
+
+    async fn set_max_bytes(&mut self, max_bytes: Option<usize>) -> Result<(), CacheErr> {
+        self.max_bytes = max_bytes;
+        self.prune_bytes(None).await
+    }
+
+    /// The total size of the entry files, read from their metadata rather than
+    /// parsing every entry.
+    async fn size_bytes(&self) -> Result<usize, CacheErr> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let files = self.dir.files().await?;
+        let sizes = try_join_all(files.iter().map(File::size)).await?;
+        Ok(sizes.into_iter().map(|size| size as usize).sum())
+    }
+
     async fn prune_invalid_entries(&self) -> Result<(), CacheErr> {
         let files = self.dir.files().await?;
         let futures = files.into_iter().map(|file| async move {
@@ -149,6 +200,40 @@ where
         let entries = self.entries().await?;
         Ok(entries.into_iter().map(|e| (e.key, e.value)).collect())
     }
+
+    async fn set_admission_policy(&mut self, policy: admission::Policy) -> Result<(), CacheErr> {
+        self.frequencies = admission::Frequencies::new(policy.window);
+        self.admission = policy;
+        Ok(())
+    }
+
+    async fn set_digest_algorithm(&mut self, algorithm: digest::Algorithm) -> Result<(), CacheErr> {
+        self.digest_algorithm = algorithm;
+        Ok(())
+    }
+
+    fn on_write(&mut self, entry: &mut CacheEntry<K, V>, class: Option<String>) {
+        entry.digest = Some(entry::digest(self.digest_algorithm, &entry.value));
+        entry.probation = match self.decide(entry, class.as_deref()) {
+            Decision::Admit => None,
+            Decision::Probation => Some(Probation { class }),
+        };
+    }
+
+    fn on_access(&mut self, entry: &mut CacheEntry<K, V>) {
+        self.frequencies.record(&entry.key.to_string());
+        let Some(probation) = &entry.probation else {
+            return;
+        };
+        if self.decide(entry, probation.class.as_deref()) == Decision::Admit {
+            debug!("admitting cache entry '{}'", entry.key.to_string());
+            entry.probation = None;
+        }
+    }
+
+    fn on_delete(&mut self, key: &K) {
+        self.frequencies.forget(&key.to_string());
+    }
 }
 
 pub type DirCache<K, V> = ConcurrentCache<SingleThreadDirCache<K, V>, K, V>;
@@ -2,6 +2,9 @@
 use std::cmp::Eq;
 use std::fmt::Debug;
 
+// internal crates
+use crate::crypt::digest::{Algorithm, Digest};
+
 // external crates
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -17,4 +20,52 @@ where
     pub is_dirty: bool,
     pub created_at: DateTime<Utc>,
     pub last_accessed: DateTime<Utc>,
+    /// Set while the cache's admission policy hasn't admitted the entry, which makes it
+    /// the first to go when the cache is pruned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probation: Option<Probation>,
+    /// The digest of the value, set by caches which verify their entries on read.
+    /// Entries written before digests were recorded have none and are trusted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<Digest>,
+    /// Pinned entries are never pruned, however the cache is bounded.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+}
+
+impl<K, V> CacheEntry<K, V>
+where
+    K: ToString + Serialize,
+    V: Clone + Serialize,
+{
+    pub fn is_probationary(&self) -> bool {
+        self.probation.is_some()
+    }
+
+    /// The size of the entry as cache files store it, which is what byte limits
+    /// bound.
+    pub fn bytes(&self) -> usize {
+        serde_json::to_vec_pretty(self).map_or(0, |bytes| bytes.len())
+    }
+
+    /// Whether the value still matches the digest it was written with, using the
+    /// algorithm the digest was computed with.
+    pub fn is_intact(&self) -> bool {
+        self.digest
+            .as_ref()
+            .is_none_or(|expected| *expected == digest(expected.algorithm(), &self.value))
+    }
+}
+
+/// The digest of the value's JSON serialization, which is how it's stored.
+pub fn digest<V: Serialize>(algorithm: Algorithm, value: &V) -> Digest {
+    let bytes = serde_json::to_vec(value).unwrap_or_default();
+    Digest::compute(algorithm, &bytes)
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd)]
+pub struct Probation {
+    /// The admission class the entry was written with, e.g. its config type name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
 }
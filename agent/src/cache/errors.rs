@@ -38,6 +38,17 @@ pub struct CannotOverwriteCacheElement {
 
 impl crate::errors::Error for CannotOverwriteCacheElement {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("cache element '{key}' is corrupted: expected digest {expected} but found {actual}")]
+pub struct CorruptedCacheElement {
+    pub key: String,
+    pub expected: String,
+    pub actual: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for CorruptedCacheElement {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("failed to send actor message: {source:?}")]
 pub struct SendActorMessageErr {
@@ -65,6 +76,8 @@ pub enum CacheErr {
     #[error(transparent)]
     CannotOverwriteCacheElement(CannotOverwriteCacheElement),
     #[error(transparent)]
+    CorruptedCacheElement(CorruptedCacheElement),
+    #[error(transparent)]
     FileSysErr(filesys::FileSysErr),
     #[error(transparent)]
     FoundTooManyCacheElements(FoundTooManyCacheElements),
@@ -83,6 +96,7 @@ impl From<filesys::FileSysErr> for CacheErr {
 crate::impl_error!(CacheErr {
     CacheElementNotFound,
     CannotOverwriteCacheElement,
+    CorruptedCacheElement,
     FileSysErr,
     FoundTooManyCacheElements,
     SendActorMessageErr,
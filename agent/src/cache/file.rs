@@ -9,12 +9,35 @@ use crate::cache::{
     errors::{CacheErr, CannotOverwriteCacheElement},
     single_thread::{CacheKey, CacheValue, SingleThreadCache},
 };
-use crate::filesys::{file::File, path::PathExt, Overwrite, WriteOptions};
+use crate::filesys::{file::File, path::PathExt, AppendOptions, Overwrite, WriteOptions};
 use crate::trace;
 
 // external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+/// An entry moved out of a cache file by the startup integrity check, as written to
+/// the file's `.corrupt` sidecar (one JSON object per line).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quarantined {
+    pub quarantined_at: DateTime<Utc>,
+    /// None if the file as a whole couldn't be parsed.
+    pub key: Option<String>,
+    pub error: String,
+    /// The entry as it was persisted, or the whole file as a string.
+    pub raw: Value,
+}
+
+/// The sidecar corrupt entries of a cache file are moved to, e.g. `deployments.json.corrupt`.
+pub fn quarantine_file(file: &File) -> File {
+    let mut path = file.path().clone().into_os_string();
+    path.push(".corrupt");
+    File::new(path)
+}
 
 #[derive(Debug)]
 pub struct SingleThreadFileCache<K, V>
@@ -40,12 +63,90 @@ where
                 .await?;
         }
 
-        Ok(Self {
+        let cache = Self {
             file,
             capacity,
             _phantom: std::marker::PhantomData,
             _phantom2: std::marker::PhantomData,
-        })
+        };
+        cache.check_integrity().await?;
+        Ok(cache)
+    }
+
+    /// Moves the persisted entries which can't be parsed to the `.corrupt` sidecar and
+    /// compacts the file to the rest, so a truncated write costs the entries it
+    /// damaged rather than the whole cache.
+    async fn check_integrity(&self) -> Result<(), CacheErr> {
+        let raw = self.file.read_bytes().await?;
+        let (cache, quarantined) = match serde_json::from_slice::<Map<String, Value>>(&raw) {
+            Ok(entries) => Self::parse_entries(entries),
+            Err(e) => {
+                error!(
+                    "cache file {} can't be parsed, starting it empty: {e}",
+                    self.file
+                );
+                let whole_file = Quarantined {
+                    quarantined_at: Utc::now(),
+                    key: None,
+                    error: e.to_string(),
+                    raw: Value::String(String::from_utf8_lossy(&raw).into_owned()),
+                };
+                (HashMap::new(), vec![whole_file])
+            }
+        };
+
+        if !quarantined.is_empty() {
+            warn!(
+                "moving {} corrupt entries of cache file {} to {}",
+                quarantined.len(),
+                self.file,
+                quarantine_file(&self.file)
+            );
+            let mut lines = Vec::new();
+            for entry in &quarantined {
+                if let Ok(line) = serde_json::to_vec(entry) {
+                    lines.extend(line);
+                    lines.push(b'\n');
+                }
+            }
+            quarantine_file(&self.file)
+                .append_bytes(&lines, AppendOptions::SYNC)
+                .await?;
+        }
+
+        // rewrite the file if anything was dropped, including duplicate keys
+        let compacted_len = serde_json::to_vec_pretty(&cache).map_or(0, |bytes| bytes.len());
+        if !quarantined.is_empty() || compacted_len != raw.len() {
+            self.write_cache(&cache).await?;
+        }
+        Ok(())
+    }
+
+    fn parse_entries(
+        entries: Map<String, Value>,
+    ) -> (HashMap<K, CacheEntry<K, V>>, Vec<Quarantined>) {
+        let mut cache = HashMap::new();
+        let mut quarantined = Vec::new();
+        for (key, raw) in entries {
+            let error = match serde_json::from_value::<CacheEntry<K, V>>(raw.clone()) {
+                Ok(entry) if entry.key.to_string() == key => {
+                    cache.insert(entry.key.clone(), entry);
+                    continue;
+                }
+                Ok(entry) => format!(
+                    "entry is filed under the wrong key '{}'",
+                    entry.key.to_string()
+                ),
+                Err(e) => e.to_string(),
+            };
+            quarantined.push(Quarantined {
+                quarantined_at: Utc::now(),
+                key: Some(key),
+                error,
+                raw,
+            });
+        }
+        (cache, quarantined)
     }
 
     async fn read_cache(&self) -> Result<HashMap<K, CacheEntry<K, V>>, CacheErr> {
@@ -1,3 +1,4 @@
+pub mod admission;
 pub mod concurrent;
 pub mod dir;
 pub mod entry;
@@ -6,6 +7,6 @@ pub mod file;
 pub mod single_thread;
 
 pub use self::dir::{DirCache, SingleThreadDirCache};
-pub use self::entry::CacheEntry;
+pub use self::entry::{CacheEntry, Probation};
 pub use self::errors::CacheErr;
 pub use self::file::{FileCache, SingleThreadFileCache};
@@ -5,17 +5,21 @@ use std::hash::Hash;
 
 // internal crates
 use crate::cache::{
+    admission,
     entry::CacheEntry,
     errors::{CacheElementNotFound, CacheErr, FoundTooManyCacheElements},
 };
+use crate::config::units;
+use crate::crypt::digest;
 use crate::filesys::Overwrite;
+use crate::metrics;
 use crate::trace;
 
 // external crates
 use chrono::{DateTime, Utc};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use tracing::info;
+use tracing::{info, warn};
 
 pub trait CacheKey: Debug + Clone + ToString + Serialize + DeserializeOwned + Eq + Hash {}
 
@@ -48,6 +52,22 @@ where
 
     async fn capacity(&self) -> Result<usize, CacheErr>;
 
+    /// The most bytes the cache's entries may take up. No limit bounds the cache by
+    /// its capacity alone.
+    async fn max_bytes(&self) -> Result<Option<usize>, CacheErr> {
+        Ok(None)
+    }
+
+    async fn set_max_bytes(&mut self, _max_bytes: Option<usize>) -> Result<(), CacheErr> {
+        Ok(())
+    }
+
+    /// The bytes the cache's entries take up.
+    async fn size_bytes(&self) -> Result<usize, CacheErr> {
+        let entries = self.entries().await?;
+        Ok(entries.iter().map(CacheEntry::bytes).sum())
+    }
+
     async fn entries(&self) -> Result<Vec<CacheEntry<K, V>>, CacheErr>;
 
     async fn values(&self) -> Result<Vec<V>, CacheErr>;
@@ -56,6 +76,30 @@ where
 
     async fn value_map(&self) -> Result<HashMap<K, V>, CacheErr>;
 
+    // ------------------------------- ADMISSION HOOKS --------------------------------- //
+    // caches without an admission policy admit every entry
+
+    async fn set_admission_policy(&mut self, _policy: admission::Policy) -> Result<(), CacheErr> {
+        Ok(())
+    }
+
+    /// Sets the algorithm digests of written entries are computed with, for caches
+    /// which verify their entries on read. Entries already written keep theirs.
+    async fn set_digest_algorithm(
+        &mut self,
+        _algorithm: digest::Algorithm,
+    ) -> Result<(), CacheErr> {
+        Ok(())
+    }
+
+    /// Decides whether a written entry is admitted or put on probation.
+    fn on_write(&mut self, _entry: &mut CacheEntry<K, V>, _class: Option<String>) {}
+
+    /// Records an access to an entry, promoting it if it has earned admission.
+    fn on_access(&mut self, _entry: &mut CacheEntry<K, V>) {}
+
+    fn on_delete(&mut self, _key: &K) {}
+
     // -------------------------------- TRAIT METHODS ---------------------------------- //
     async fn set_last_accessed(
         &mut self,
@@ -68,17 +112,46 @@ where
     }
 
     async fn read_entry_optional(&mut self, key: &K) -> Result<Option<CacheEntry<K, V>>, CacheErr> {
+        let entry = self.access_entry(key).await?;
+        let metrics = metrics::global();
+        let cache = [metrics::type_label::<V>()];
+        match entry {
+            Some(_) => metrics.cache_hits.inc(&cache),
+            None => metrics.cache_misses.inc(&cache),
+        }
+        Ok(entry)
+    }
+
+    /// Reads an entry, counting the read as an access, without recording a cache hit
+    /// or miss. Used when writing, since a write isn't a lookup.
+    async fn access_entry(&mut self, key: &K) -> Result<Option<CacheEntry<K, V>>, CacheErr> {
         let mut entry = match self.read_entry_impl(key).await? {
             Some(entry) => entry,
             None => return Ok(None),
         };
 
         // update the last accessed time
+        self.on_access(&mut entry);
         self.set_last_accessed(&mut entry, Utc::now()).await?;
 
         Ok(Some(entry))
     }
 
+    /// Like `access_entry`, but treats a corrupted entry as absent so writing over it
+    /// repairs it.
+    async fn access_entry_for_write(
+        &mut self,
+        key: &K,
+    ) -> Result<Option<CacheEntry<K, V>>, CacheErr> {
+        match self.access_entry(key).await {
+            Err(CacheErr::CorruptedCacheElement(e)) => {
+                warn!("overwriting corrupted cache entry: {e}");
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+
     async fn read_entry(&mut self, key: &K) -> Result<CacheEntry<K, V>, CacheErr> {
         let result = self.read_entry_optional(key).await?;
         match result {
@@ -109,6 +182,8 @@ where
     ) -> Result<(), CacheErr> {
         self.prune().await?;
         self.write_entry_impl(entry, overwrite).await?;
+        // the written entry may have pushed the cache over its byte limit
+        self.prune_bytes(Some(&entry.key)).await?;
         Ok(())
     }
 
@@ -122,25 +197,49 @@ where
     where
         F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync,
     {
-        // if the entry already exists, keep the original created_at time
-        let (created_at, last_accessed, is_dirty) = match self.read_entry_optional(&key).await? {
-            Some(existing_entry) => (
-                existing_entry.created_at,
-                Utc::now(),
-                is_dirty(Some(&existing_entry), &value),
-            ),
-            None => {
-                let now = Utc::now();
-                (now, now, is_dirty(None, &value))
-            }
-        };
-        let entry = CacheEntry {
+        self.write_with_class(key, value, None, is_dirty, overwrite)
+            .await
+    }
+
+    /// Writes an entry whose admission class (e.g. its config type name) the cache's
+    /// admission policy may have a rule for.
+    async fn write_with_class<F>(
+        &mut self,
+        key: K,
+        value: V,
+        class: Option<String>,
+        is_dirty: F,
+        overwrite: Overwrite,
+    ) -> Result<(), CacheErr>
+    where
+        F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync,
+    {
+        // if the entry already exists, keep the original created_at time, class and pin
+        let (created_at, last_accessed, is_dirty, class, pinned) =
+            match self.access_entry_for_write(&key).await? {
+                Some(existing_entry) => (
+                    existing_entry.created_at,
+                    Utc::now(),
+                    is_dirty(Some(&existing_entry), &value),
+                    class.or(existing_entry.probation.and_then(|p| p.class)),
+                    existing_entry.pinned,
+                ),
+                None => {
+                    let now = Utc::now();
+                    (now, now, is_dirty(None, &value), class, false)
+                }
+            };
+        let mut entry = CacheEntry {
             key,
             value,
             created_at,
             last_accessed,
             is_dirty,
+            probation: None,
+            digest: None,
+            pinned,
         };
+        self.on_write(&mut entry, class);
 
         // write the entry
         self.write_entry(&entry, overwrite).await?;
@@ -151,7 +250,7 @@ where
     where
         F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync,
     {
-        if self.read_entry_optional(&key).await?.is_some() {
+        if self.access_entry_for_write(&key).await?.is_some() {
             return Ok(());
         }
         self.write(key, value, is_dirty, Overwrite::Allow).await
@@ -159,10 +258,95 @@ where
 
     async fn delete(&mut self, key: &K) -> Result<(), CacheErr> {
         self.delete_entry_impl(key).await?;
+        self.on_delete(key);
+        Ok(())
+    }
+
+    /// Reads the entries with the given keys, returning the values of those which
+    /// exist. Corrupted entries are left out like absent ones so that a single bad
+    /// entry doesn't fail the whole batch; writing over them repairs them.
+    async fn read_many(&mut self, keys: &[K]) -> Result<HashMap<K, V>, CacheErr> {
+        let mut values = HashMap::with_capacity(keys.len());
+        for key in keys {
+            match self.read_optional(key).await {
+                Ok(Some(value)) => {
+                    values.insert(key.clone(), value);
+                }
+                Ok(None) => {}
+                Err(CacheErr::CorruptedCacheElement(e)) => {
+                    warn!("skipping corrupted cache entry: {e}");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Writes the entries in order, judging each with the same `is_dirty`. Stops at
+    /// the first entry which can't be written, keeping the entries written before it.
+    async fn write_many<F>(
+        &mut self,
+        entries: Vec<(K, V)>,
+        is_dirty: F,
+        overwrite: Overwrite,
+    ) -> Result<(), CacheErr>
+    where
+        F: Fn(Option<&CacheEntry<K, V>>, &V) -> bool + Send + Sync,
+    {
+        for (key, value) in entries {
+            self.write_with_class(key, value, None, &is_dirty, overwrite)
+                .await?;
+        }
         Ok(())
     }
 
+    async fn delete_many(&mut self, keys: &[K]) -> Result<(), CacheErr> {
+        for key in keys {
+            self.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Protects an entry from pruning until it's unpinned. Deleting the entry, or
+    /// finding it corrupted, still removes it.
+    async fn pin(&mut self, key: &K) -> Result<(), CacheErr> {
+        self.set_pinned(key, true).await
+    }
+
+    /// Lets an entry be pruned again. Unpinning an absent entry does nothing.
+    async fn unpin(&mut self, key: &K) -> Result<(), CacheErr> {
+        self.set_pinned(key, false).await
+    }
+
+    async fn set_pinned(&mut self, key: &K, pinned: bool) -> Result<(), CacheErr> {
+        let mut entry = match self.read_entry_impl(key).await? {
+            Some(entry) => entry,
+            None if !pinned => return Ok(()),
+            None => {
+                return Err(CacheErr::CacheElementNotFound(CacheElementNotFound {
+                    msg: format!(
+                        "Unable to pin missing cache entry with key: '{}'",
+                        key.to_string()
+                    ),
+                    trace: trace!(),
+                }))
+            }
+        };
+        if entry.pinned == pinned {
+            return Ok(());
+        }
+        // pinning isn't an access so the entry's last accessed time is kept
+        entry.pinned = pinned;
+        self.write_entry_impl(&entry, Overwrite::Allow).await
+    }
+
     async fn prune(&mut self) -> Result<(), CacheErr> {
+        self.prune_entries().await?;
+        self.prune_bytes(None).await
+    }
+
+    /// Deletes entries until the cache is within its capacity.
+    async fn prune_entries(&mut self) -> Result<(), CacheErr> {
         let capacity = self.capacity().await?;
 
         // check if there are too many files
@@ -181,19 +365,80 @@ where
         // prune the invalid entries first
         self.prune_invalid_entries().await?;
 
-        // prune by last accessed time
+        // prune probationary entries first, then by last accessed time
         let mut entries = self.entries().await?;
         if entries.len() <= capacity {
             return Ok(());
         }
-        entries.sort_by_key(|entry| entry.last_accessed);
-        let num_delete = entries.len() - capacity;
+        let total = entries.len();
+        let num_delete = total - capacity;
+        entries.retain(|entry| !entry.pinned);
+        if entries.len() < num_delete {
+            warn!(
+                "{} pinned entries keep cache {} over its capacity of {capacity}",
+                total - entries.len(),
+                std::any::type_name::<V>()
+            );
+        }
+        entries.sort_by_key(|entry| (!entry.is_probationary(), entry.last_accessed));
         for entry in entries.into_iter().take(num_delete) {
             self.delete(&entry.key).await?;
         }
         Ok(())
     }
 
+    /// Deletes entries until the cache is within its byte limit, in the same order
+    /// as `prune_entries`. Pinned entries and the entry with the `keep` key are
+    /// spared, so an entry larger than the limit is still cached until the next one
+    /// is written.
+    async fn prune_bytes(&mut self, keep: Option<&K>) -> Result<(), CacheErr> {
+        let Some(max_bytes) = self.max_bytes().await? else {
+            return Ok(());
+        };
+        let mut size = self.size_bytes().await?;
+        if size <= max_bytes {
+            return Ok(());
+        }
+
+        info!(
+            "Pruning cache {} from {} to {}...",
+            std::any::type_name::<V>(),
+            units::format_size(size as u64),
+            units::format_size(max_bytes as u64)
+        );
+
+        let mut entries = self.entries().await?;
+        entries.sort_by_key(|entry| (!entry.is_probationary(), entry.last_accessed));
+        for entry in entries {
+            if size <= max_bytes {
+                break;
+            }
+            if entry.pinned || keep == Some(&entry.key) {
+                continue;
+            }
+            self.delete(&entry.key).await?;
+            size = size.saturating_sub(entry.bytes());
+        }
+        Ok(())
+    }
+
+    /// Deletes the entries whose values no longer match their digests, along with any
+    /// which can't be parsed, returning the keys of the deleted entries which could be.
+    async fn verify_all(&mut self) -> Result<Vec<K>, CacheErr> {
+        self.prune_invalid_entries().await?;
+
+        let mut corrupted = Vec::new();
+        for entry in self.entries().await? {
+            if entry.is_intact() {
+                continue;
+            }
+            warn!("deleting corrupted cache entry '{}'", entry.key.to_string());
+            self.delete(&entry.key).await?;
+            corrupted.push(entry.key);
+        }
+        Ok(corrupted)
+    }
+
     async fn find_entries_where<F>(&mut self, filter: F) -> Result<Vec<CacheEntry<K, V>>, CacheErr>
     where
         F: Fn(&CacheEntry<K, V>) -> bool,
@@ -0,0 +1,194 @@
+// Device groups for multi-robot cells, where the robots sit on an isolated subnet and
+// only the cell controller can reach the backend. The controller's agent serves its
+// peers a proxy for the subset of the backend's API agents sync with and forwards it
+// to the backend; the peers' agents send their backend requests to the proxy instead.
+// Each peer authenticates to the proxy with its own token, on top of the device token
+// the backend authenticates it with.
+
+#[cfg(feature = "server")]
+pub mod proxy;
+
+// internal crates
+use crate::crypt::digest::Digest;
+use crate::storage::settings;
+
+// external crates
+use openssl::memcmp;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Identifies the peer a request to the proxy comes from.
+pub const PEER_HEADER: &str = "Miru-Cell-Peer";
+/// Authenticates the peer a request to the proxy comes from.
+pub const TOKEN_HEADER: &str = "Miru-Cell-Token";
+
+/// The part an agent plays in its cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Talks to the backend directly and serves no peers.
+    #[default]
+    Standalone,
+    /// Proxies the backend for the cell's peers.
+    Controller,
+    /// Reaches the backend through the cell's controller.
+    Peer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Standalone => "standalone",
+            Role::Controller => "controller",
+            Role::Peer => "peer",
+        }
+    }
+}
+
+/// A peer allowed to use the controller's proxy. Only a digest of its token is kept
+/// on the controller.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerCredential {
+    pub id: String,
+    pub token_digest: Digest,
+}
+
+/// The peers allowed to use the controller's proxy.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Peers {
+    credentials: Vec<PeerCredential>,
+}
+
+impl Peers {
+    pub fn new(credentials: Vec<PeerCredential>) -> Self {
+        Self { credentials }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+
+    /// Whether the token is the peer's. Unknown peers are never authenticated and the
+    /// token's digest is compared in constant time.
+    pub fn authenticate(&self, id: &str, token: &str) -> bool {
+        let Some(credential) = self.credentials.iter().find(|c| c.id == id) else {
+            return false;
+        };
+        let expected = credential.token_digest.hex().as_bytes();
+        let digest = Digest::compute(credential.token_digest.algorithm(), token.as_bytes());
+        let actual = digest.hex().as_bytes();
+        expected.len() == actual.len() && memcmp::eq(expected, actual)
+    }
+}
+
+/// The controller a peer reaches the backend through.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Upstream {
+    /// Base URL of the controller's proxy, e.g. "http://10.0.0.1:8443".
+    pub url: String,
+    pub peer_id: String,
+    pub token: String,
+}
+
+impl std::fmt::Debug for Upstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Upstream")
+            .field("url", &self.url)
+            .field("peer_id", &self.peer_id)
+            .field("token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// The cell settings, validated for the agent's role.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Options {
+    #[default]
+    Standalone,
+    Controller {
+        /// Address the proxy listens on, e.g. "10.0.0.1:8443".
+        listen_addr: String,
+        peers: Peers,
+    },
+    Peer(Upstream),
+}
+
+impl Options {
+    /// Validates the settings for the configured role. Settings missing something the
+    /// role needs fall back to standalone, with a warning, so a misconfigured cell
+    /// doesn't keep the agent from starting.
+    pub fn new(settings: &settings::Cell) -> Self {
+        let role = settings.role;
+        match Self::for_role(settings) {
+            Ok(options) => options,
+            Err(msg) => {
+                warn!("ignoring the cell {} settings: {msg}", role.as_str());
+                Options::Standalone
+            }
+        }
+    }
+
+    fn for_role(settings: &settings::Cell) -> Result<Self, String> {
+        match settings.role {
+            Role::Standalone => Ok(Options::Standalone),
+            Role::Controller => {
+                let listen_addr = settings
+                    .listen_addr
+                    .clone()
+                    .ok_or("a controller needs a listen_addr")?;
+                if settings.peers.is_empty() {
+                    warn!("the cell controller has no peers, so its proxy rejects every request");
+                }
+                Ok(Options::Controller {
+                    listen_addr,
+                    peers: Peers::new(settings.peers.clone()),
+                })
+            }
+            Role::Peer => {
+                let url = settings
+                    .controller_url
+                    .as_deref()
+                    .ok_or("a peer needs a controller_url")?;
+                let url = parse_controller_url(url)?;
+                let peer_id = settings.peer_id.clone().ok_or("a peer needs a peer_id")?;
+                let token = settings.token.clone().ok_or("a peer needs a token")?;
+                Ok(Options::Peer(Upstream {
+                    url,
+                    peer_id,
+                    token,
+                }))
+            }
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        match self {
+            Options::Standalone => Role::Standalone,
+            Options::Controller { .. } => Role::Controller,
+            Options::Peer(_) => Role::Peer,
+        }
+    }
+
+    pub fn upstream(&self) -> Option<&Upstream> {
+        match self {
+            Options::Peer(upstream) => Some(upstream),
+            _ => None,
+        }
+    }
+}
+
+/// The controller is on the cell's private subnet, so unlike the backend's URL it may
+/// be any host and plain http.
+fn parse_controller_url(raw: &str) -> Result<String, String> {
+    let url = url::Url::parse(raw).map_err(|e| format!("invalid controller_url: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "controller_url scheme `{}` not allowed",
+            url.scheme()
+        ));
+    }
+    if url.host_str().is_none() {
+        return Err("controller_url must contain a host".into());
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
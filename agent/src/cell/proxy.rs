@@ -0,0 +1,264 @@
+// The cell controller's proxy. Peers send it the requests their agents would send the
+// backend; a request is forwarded once the peer is authenticated and the request is
+// one an agent syncs with, and the backend's response is streamed back. The peer's
+// own device token authenticates it to the backend, so the controller never acts on
+// the backend with its own credentials on a peer's behalf.
+
+// standard crates
+use std::future::Future;
+use std::sync::Arc;
+
+// internal crates
+use crate::cell::{Peers, PEER_HEADER, TOKEN_HEADER};
+use crate::http::{errors::BuildReqwestErr, HTTPErr};
+use crate::metrics::workers::{instrument, Worker};
+use crate::network::egress;
+use crate::server::errors::{BindCellProxyErr, RunAxumServerErr, ServerErr};
+use crate::trace;
+use backend_api::models::{Error as BackendError, ErrorResponse};
+
+// external crates
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State as AxumState},
+    http::{HeaderMap, HeaderName, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Bodies of requests agents send the backend are small JSON documents.
+const MAX_REQUEST_BODY_BYTES: usize = 1024 * 1024;
+
+/// The requests peers may send, as methods and paths relative to the backend's base
+/// URL. `*` matches a single path segment.
+const ALLOWED: &[(Method, &str)] = &[
+    (Method::POST, "/devices/token"),
+    (Method::GET, "/device"),
+    (Method::PATCH, "/devices/*"),
+    (Method::GET, "/devices/*/sync"),
+    (Method::GET, "/deployments"),
+    (Method::GET, "/deployments/*"),
+    (Method::PATCH, "/deployments/*"),
+    (Method::GET, "/config_instances/*/content"),
+    (Method::GET, "/config_instances/*/content/patch"),
+    (Method::GET, "/releases/*"),
+    (Method::GET, "/git_commits/*"),
+];
+
+/// Whether peers may send the request. Paths with percent-encoded characters are
+/// refused outright: the backend decodes them, so `%2e%2e` would match a `*` here and
+/// be normalized to `..` upstream. No path an agent syncs with needs encoding.
+pub fn allowed(method: &Method, path: &str) -> bool {
+    if path.contains('%') {
+        return false;
+    }
+    let segments: Vec<&str> = path.split('/').collect();
+    if segments[1..]
+        .iter()
+        .any(|s| s.is_empty() || *s == "." || *s == "..")
+    {
+        return false;
+    }
+    ALLOWED.iter().any(|(allowed_method, pattern)| {
+        let pattern: Vec<&str> = pattern.split('/').collect();
+        allowed_method == method
+            && pattern.len() == segments.len()
+            && pattern
+                .iter()
+                .zip(&segments)
+                .all(|(p, s)| *p == "*" || p == s)
+    })
+}
+
+/// Request headers the backend needs. The peer's proxy credentials and the
+/// connection's own headers aren't forwarded.
+fn forwarded_request_header(name: &HeaderName) -> bool {
+    let name = name.as_str();
+    matches!(
+        name,
+        "authorization"
+            | "content-type"
+            | "accept"
+            | "if-none-match"
+            | "if-modified-since"
+            | "range"
+            | "if-range"
+    ) || (name.starts_with("miru-") && !name.starts_with("miru-cell-"))
+}
+
+fn forwarded_response_header(name: &HeaderName) -> bool {
+    !matches!(
+        name.as_str(),
+        "connection" | "keep-alive" | "transfer-encoding" | "upgrade" | "content-length"
+    )
+}
+
+pub struct State {
+    client: reqwest::Client,
+    backend_base_url: String,
+    peers: Peers,
+}
+
+impl State {
+    /// Forwards to the backend the way the controller's own agent reaches it: through
+    /// the egress proxy, trusting the CA bundle and presenting the client certificate.
+    pub fn new(
+        backend_base_url: &str,
+        peers: Peers,
+        egress: &egress::Options,
+    ) -> Result<Self, ServerErr> {
+        let client = egress
+            .configure(reqwest::Client::builder())?
+            .build()
+            .map_err(|e| {
+                ServerErr::from(HTTPErr::BuildReqwestErr(BuildReqwestErr {
+                    source: e,
+                    trace: trace!(),
+                }))
+            })?;
+        Ok(Self {
+            client,
+            backend_base_url: backend_base_url.trim_end_matches('/').to_string(),
+            peers,
+        })
+    }
+}
+
+pub fn routes(state: Arc<State>) -> Router {
+    Router::new().fallback(forward).with_state(state)
+}
+
+async fn forward(AxumState(state): AxumState<Arc<State>>, req: Request) -> Response {
+    // each connection is served on its own task, so requests are attributed to the
+    // proxy individually
+    instrument(Worker::CellProxy, forward_impl(state, req)).await
+}
+
+async fn forward_impl(state: Arc<State>, req: Request) -> Response {
+    let (parts, body) = req.into_parts();
+    let Some(peer) = authenticate(&state.peers, &parts.headers) else {
+        return error(
+            StatusCode::UNAUTHORIZED,
+            "unauthenticated",
+            "the request isn't from a known cell peer",
+        );
+    };
+    if !allowed(&parts.method, parts.uri.path()) {
+        warn!(
+            "Rejected {} {} from cell peer {peer}",
+            parts.method,
+            parts.uri.path()
+        );
+        return error(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "cell peers may only send the requests agents sync with",
+        );
+    }
+    let body = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => {
+            return error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                "the request body is too large",
+            )
+        }
+    };
+
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| parts.uri.path());
+    let url = format!("{}{}", state.backend_base_url, path_and_query);
+    debug!(
+        "Forwarding {} {path_and_query} for cell peer {peer}",
+        parts.method
+    );
+    let mut upstream = state.client.request(parts.method, url).body(body);
+    for (name, value) in parts.headers.iter() {
+        if forwarded_request_header(name) {
+            upstream = upstream.header(name, value);
+        }
+    }
+    let resp = match upstream.send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            warn!("Failed to forward a request for cell peer {peer}: {e}");
+            return error(
+                StatusCode::BAD_GATEWAY,
+                "bad_gateway",
+                "the cell controller couldn't reach the backend",
+            );
+        }
+    };
+
+    let mut builder = Response::builder().status(resp.status());
+    for (name, value) in resp.headers() {
+        if forwarded_response_header(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    // streamed so large config instances aren't buffered on the controller
+    let chunks = futures::stream::unfold(Some(resp), |resp| async move {
+        let mut resp = resp?;
+        match resp.chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(resp))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    builder
+        .body(Body::from_stream(chunks))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+/// The id of the peer the headers authenticate, if any.
+fn authenticate(peers: &Peers, headers: &HeaderMap) -> Option<String> {
+    let id = headers.get(PEER_HEADER)?.to_str().ok()?;
+    let token = headers.get(TOKEN_HEADER)?.to_str().ok()?;
+    peers.authenticate(id, token).then(|| id.to_string())
+}
+
+/// An error in the backend's format, so peers handle it like one from the backend.
+fn error(status: StatusCode, code: &str, message: &str) -> Response {
+    let body = ErrorResponse::new(BackendError::new(
+        code.to_string(),
+        Default::default(),
+        message.to_string(),
+    ));
+    (status, Json(body)).into_response()
+}
+
+/// Serves the proxy on a TCP listener on the cell's subnet.
+pub async fn serve(
+    addr: &str,
+    state: Arc<State>,
+    shutdown_signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<Result<(), ServerErr>>, ServerErr> {
+    let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
+        ServerErr::BindCellProxyErr(BindCellProxyErr {
+            addr: addr.to_string(),
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    let app = routes(state);
+
+    let server_handle = tokio::task::spawn(instrument(Worker::CellProxy, async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_signal)
+            .await
+            .map_err(|e| {
+                ServerErr::RunAxumServerErr(RunAxumServerErr {
+                    source: e,
+                    trace: trace!(),
+                })
+            })
+    }));
+
+    Ok(server_handle)
+}
@@ -0,0 +1,48 @@
+// external crates
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CliErr {
+    #[error("unknown command '{command}'{}", did_you_mean(suggestion))]
+    UnknownCommand {
+        command: String,
+        suggestion: Option<&'static str>,
+    },
+    #[error("unknown flag '--{flag}' for '{command}'{}", did_you_mean(suggestion))]
+    UnknownFlag {
+        command: &'static str,
+        flag: String,
+        suggestion: Option<&'static str>,
+    },
+    #[error("flag '--{flag}' requires a value")]
+    MissingValue { flag: &'static str },
+    #[error("flag '--{flag}' doesn't take a value")]
+    UnexpectedValue { flag: &'static str },
+    #[error("invalid value '{value}' for '--{flag}': {msg}")]
+    InvalidValue {
+        flag: &'static str,
+        value: String,
+        msg: String,
+    },
+    #[error("flag '--{flag}' can't be combined with '--{other}'")]
+    ConflictingFlags {
+        flag: &'static str,
+        other: &'static str,
+    },
+    #[error("unexpected argument '{arg}' for '{command}'")]
+    UnexpectedArg { command: &'static str, arg: String },
+    #[error("'{command}' requires the <{name}> argument")]
+    MissingArg {
+        command: &'static str,
+        name: &'static str,
+    },
+}
+
+impl crate::errors::Error for CliErr {}
+
+fn did_you_mean(suggestion: &Option<&'static str>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(" (did you mean '{suggestion}'?)"),
+        None => String::new(),
+    }
+}
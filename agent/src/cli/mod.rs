@@ -1,77 +1,905 @@
-#[derive(Debug, Default)]
-pub struct Args {
-    pub display_version: bool,
-    pub provision_args: Option<ProvisionArgs>,
-    pub reprovision_args: Option<ReprovisionArgs>,
-}
-
-impl Args {
-    pub fn parse(inputs: &[String]) -> Self {
-        let mut args = Self::default();
-        for input in inputs.iter().skip(1) {
-            match input.trim_start_matches('-') {
-                "version" => args.display_version = true,
-                "provision" => args.provision_args = Some(ProvisionArgs::parse(inputs)),
-                "reprovision" => args.reprovision_args = Some(ReprovisionArgs::parse(inputs)),
-                _ => {}
-            }
-        }
-        args
-    }
+pub mod errors;
+pub mod spec;
+pub mod status;
+
+pub use self::errors::CliErr;
+
+// standard crates
+use std::fmt::Write;
+use std::path::PathBuf;
+
+// internal crates
+use self::spec::{suggest, Flag, Matches, Positional, Spec};
+use crate::config::compat;
+use crate::storage::fsck::Severity;
+use crate::version;
+
+pub const BINARY: &str = "miru-agent";
+
+// ================================== COMMANDS ===================================== //
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Run(RunArgs),
+    Activate(ProvisionArgs),
+    Reprovision(ReprovisionArgs),
+    #[cfg(feature = "installer")]
+    Install(InstallArgs),
+    #[cfg(feature = "installer")]
+    Uninstall(UninstallArgs),
+    Status(StatusArgs),
+    SyncHistory(SyncHistoryArgs),
+    Version,
+    ConfigSources(RunArgs),
+    SupportBundle(SupportBundleArgs),
+    Fsck(FsckArgs),
+    Replay(ReplayArgs),
+    WebhookKeys(WebhookKeysArgs),
+    SyncOnce(RunArgs),
+    Trash(TrashArgs),
+    Deployments(DeploymentsArgs),
+    /// Print the given help text.
+    Help(String),
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RunArgs {
+    /// `--set=<field.path>=<value>` overrides of settings fields.
+    pub settings_overrides: Vec<(String, String)>,
+    pub data_dir: Option<PathBuf>,
+    /// Where to record the agent's exchanges with the backend for later replay.
+    pub record: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct ProvisionArgs {
     pub backend_host: Option<String>,
     pub mqtt_broker_host: Option<String>,
     pub device_name: Option<String>,
+    /// The provisioning token or an activation URL carrying it. Read from the
+    /// environment if unset.
+    pub token: Option<String>,
+    pub data_dir: Option<PathBuf>,
 }
 
-impl ProvisionArgs {
-    pub fn parse(inputs: &[String]) -> Self {
-        let mut args = Self::default();
-        for input in inputs.iter().skip(1) {
-            if let Some((key, value)) = input.split_once('=') {
-                let value = if value.is_empty() {
-                    None
-                } else {
-                    Some(value.to_string())
-                };
-                match key.trim_start_matches('-') {
-                    "backend-host" => args.backend_host = value,
-                    "mqtt-broker-host" => args.mqtt_broker_host = value,
-                    "device-name" => args.device_name = value,
-                    _ => {}
-                }
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReprovisionArgs {
+    pub backend_host: Option<String>,
+    pub mqtt_broker_host: Option<String>,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[cfg(feature = "installer")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct InstallArgs {
+    /// Where to write the service definition. Printed to stdout if unset.
+    pub output: Option<PathBuf>,
+    /// The data directory the installed service runs the agent with.
+    pub data_dir: Option<PathBuf>,
+    /// Sandbox the systemd service.
+    pub hardening: bool,
+    /// Extra paths the sandboxed service may write to.
+    pub writable_paths: Vec<PathBuf>,
+    /// The systemd restart policy. The platform's default if unset.
+    pub restart: Option<String>,
+    pub watchdog_sec: Option<u32>,
+    /// Where to write a tmpfiles.d entry for the log directory.
+    pub tmpfiles: Option<PathBuf>,
+}
+
+#[cfg(feature = "installer")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UninstallArgs {
+    /// Only lists what would be removed unless set.
+    pub yes: bool,
+    pub keep_logs: bool,
+    /// Leaves the device registered with the backend.
+    pub keep_registration: bool,
+    /// Removes the local state even if the device couldn't be deregistered.
+    pub force: bool,
+    /// The service to stop. The platform's default if unset.
+    pub service: Option<String>,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StatusArgs {
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncHistoryArgs {
+    pub json: bool,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SupportBundleArgs {
+    pub dir: String,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FsckArgs {
+    pub fix: bool,
+    /// The least severe findings to print.
+    pub level: Severity,
+    pub json: bool,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReplayArgs {
+    /// The capture recorded with `run --record`.
+    pub capture: PathBuf,
+    pub apply: bool,
+    pub json: bool,
+    /// Replays into a new temporary directory if unset.
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct WebhookKeysArgs {
+    /// The webhook destination, as configured in the notification sink.
+    pub url: String,
+    pub rotate: bool,
+    /// How long the keys replaced by a rotation keep signing.
+    pub grace_secs: i64,
+    pub revoke: bool,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum TrashAction {
+    #[default]
+    List,
+    Restore {
+        entry: String,
+        /// Restores over a file written since the entry was trashed.
+        force: bool,
+    },
+    /// Purges every entry if none is given.
+    Purge { entry: Option<String> },
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TrashArgs {
+    pub action: TrashAction,
+    pub json: bool,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum DeploymentsAction {
+    #[default]
+    Status,
+    Pause {
+        reason: Option<String>,
+    },
+    Resume,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DeploymentsArgs {
+    pub action: DeploymentsAction,
+    pub json: bool,
+    pub data_dir: Option<PathBuf>,
+}
+
+pub const DEFAULT_WEBHOOK_KEY_GRACE_SECS: i64 = 24 * 60 * 60;
+
+impl Default for FsckArgs {
+    fn default() -> Self {
+        Self {
+            fix: false,
+            level: Severity::Warning,
+            json: false,
+            data_dir: None,
+        }
+    }
+}
+
+const SET_FLAG: Flag = Flag {
+    name: "set",
+    aliases: &[],
+    value: Some("FIELD=VALUE"),
+    help: "Override a settings field, e.g. --set=log_level=debug (repeatable)",
+};
+const DATA_DIR_FLAG: Flag = Flag {
+    name: "data-dir",
+    aliases: &["config"],
+    value: Some("DIR"),
+    help: "Keep the agent's state, settings, logs and socket under this directory",
+};
+const BACKEND_HOST_FLAG: Flag = Flag {
+    name: "backend-host",
+    aliases: &[],
+    value: Some("URL"),
+    help: "The backend to activate the device against",
+};
+const MQTT_BROKER_HOST_FLAG: Flag = Flag {
+    name: "mqtt-broker-host",
+    aliases: &[],
+    value: Some("HOST"),
+    help: "The MQTT broker the device connects to",
+};
+
+const RUN: Spec = Spec {
+    name: "run",
+    aliases: &[],
+    about: "Run the agent (the default when no command is given)",
+    positional: None,
+    flags: &[
+        SET_FLAG,
+        DATA_DIR_FLAG,
+        Flag {
+            name: "record",
+            aliases: &[],
+            value: Some("FILE"),
+            help: "Record every exchange with the backend, sanitized, for 'replay'",
+        },
+    ],
+};
+const ACTIVATE: Spec = Spec {
+    name: "activate",
+    aliases: &["provision"],
+    about: "Activate this device with a provisioning token (or MIRU_PROVISIONING_TOKEN)",
+    positional: None,
+    flags: &[
+        BACKEND_HOST_FLAG,
+        MQTT_BROKER_HOST_FLAG,
+        Flag {
+            name: "device-name",
+            aliases: &[],
+            value: Some("NAME"),
+            help: "The name to activate the device as (defaults to the hostname)",
+        },
+        Flag {
+            name: "token",
+            aliases: &["activation-url"],
+            value: Some("TOKEN|URL"),
+            help: "The provisioning token, or the activation URL it came in",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+const REPROVISION: Spec = Spec {
+    name: "reprovision",
+    aliases: &[],
+    about: "Replace this device's credentials while keeping its identity",
+    positional: None,
+    flags: &[BACKEND_HOST_FLAG, MQTT_BROKER_HOST_FLAG, DATA_DIR_FLAG],
+};
+#[cfg(feature = "installer")]
+const INSTALL: Spec = Spec {
+    name: "install",
+    aliases: &["service-definition"],
+    about: "Render the definition for running the agent as a service on this platform",
+    positional: None,
+    flags: &[
+        Flag {
+            name: "output",
+            aliases: &[],
+            value: Some("PATH"),
+            help: "Write the definition to a file instead of stdout",
+        },
+        Flag {
+            name: "hardening",
+            aliases: &["harden"],
+            value: None,
+            help: "Sandbox the systemd service, leaving only the agent's directories writable",
+        },
+        Flag {
+            name: "writable-path",
+            aliases: &[],
+            value: Some("PATH"),
+            help: "Let the sandboxed service write to this path, e.g. where configs are deployed (repeatable)",
+        },
+        Flag {
+            name: "restart",
+            aliases: &[],
+            value: Some("POLICY"),
+            help: "When systemd restarts the agent, e.g. always (defaults to on-failure)",
+        },
+        Flag {
+            name: "watchdog",
+            aliases: &["watchdog-sec"],
+            value: Some("SECS"),
+            help: "Have systemd restart the agent if it stops sending keep-alives for SECS",
+        },
+        Flag {
+            name: "tmpfiles",
+            aliases: &[],
+            value: Some("PATH"),
+            help: "Also write a tmpfiles.d entry creating the log directory, e.g. /etc/tmpfiles.d/miru.conf",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+#[cfg(feature = "installer")]
+const UNINSTALL: Spec = Spec {
+    name: "uninstall",
+    aliases: &["reset", "factory-reset"],
+    about: "Stop the service, deregister the device and remove its deployed configs and state",
+    positional: None,
+    flags: &[
+        Flag {
+            name: "yes",
+            aliases: &[],
+            value: None,
+            help: "Remove everything listed (without it, only lists what would be removed)",
+        },
+        Flag {
+            name: "keep-logs",
+            aliases: &[],
+            value: None,
+            help: "Leave the log directory in place",
+        },
+        Flag {
+            name: "keep-registration",
+            aliases: &[],
+            value: None,
+            help: "Leave the device registered with the backend",
+        },
+        Flag {
+            name: "force",
+            aliases: &[],
+            value: None,
+            help: "Remove the local state even if the device couldn't be deregistered",
+        },
+        Flag {
+            name: "service",
+            aliases: &[],
+            value: Some("NAME"),
+            help: "The service to stop (defaults to the name the package installs)",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+const STATUS: Spec = Spec {
+    name: "status",
+    aliases: &[],
+    about: "Show whether the device is activated and what the agent last recorded",
+    positional: None,
+    flags: &[DATA_DIR_FLAG],
+};
+const SYNC_HISTORY: Spec = Spec {
+    name: "sync-history",
+    aliases: &[],
+    about: "Show the syncer's recent events (requires sync_history.persist)",
+    positional: None,
+    flags: &[
+        Flag {
+            name: "json",
+            aliases: &[],
+            value: None,
+            help: "Print the events as JSON",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+const VERSION: Spec = Spec {
+    name: "version",
+    aliases: &[],
+    about: "Print the agent's version",
+    positional: None,
+    flags: &[],
+};
+const CONFIG_SOURCES: Spec = Spec {
+    name: "config-sources",
+    aliases: &[],
+    about: "Show the resolved settings and where each value came from",
+    positional: None,
+    flags: &[SET_FLAG, DATA_DIR_FLAG],
+};
+const SUPPORT_BUNDLE: Spec = Spec {
+    name: "support-bundle",
+    aliases: &[],
+    about: "Write a redacted support bundle of the agent's state and logs",
+    positional: Some(Positional {
+        name: "DIR",
+        help: "The directory to write the bundle to",
+    }),
+    flags: &[DATA_DIR_FLAG],
+};
+const FSCK: Spec = Spec {
+    name: "fsck",
+    aliases: &[],
+    about: "Check the agent's caches for broken references and optionally repair them",
+    positional: None,
+    flags: &[
+        Flag {
+            name: "fix",
+            aliases: &[],
+            value: None,
+            help: "Repair what can be repaired (stop the agent first)",
+        },
+        Flag {
+            name: "level",
+            aliases: &[],
+            value: Some("LEVEL"),
+            help: "The least severe findings to print: info, warning or error (default: warning)",
+        },
+        Flag {
+            name: "json",
+            aliases: &[],
+            value: None,
+            help: "Print every finding as JSON",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+const REPLAY: Spec = Spec {
+    name: "replay",
+    aliases: &[],
+    about: "Sync offline against backend exchanges recorded with 'run --record'",
+    positional: Some(Positional {
+        name: "FILE",
+        help: "The recorded capture",
+    }),
+    flags: &[
+        Flag {
+            name: "apply",
+            aliases: &[],
+            value: None,
+            help: "Write config instances to their filepaths instead of only reconciling",
+        },
+        Flag {
+            name: "json",
+            aliases: &[],
+            value: None,
+            help: "Print the report as JSON",
+        },
+        Flag {
+            name: "data-dir",
+            aliases: &[],
+            value: Some("DIR"),
+            help: "Replay into this directory instead of a new temporary one",
+        },
+    ],
+};
+const WEBHOOK_KEYS: Spec = Spec {
+    name: "webhook-keys",
+    aliases: &[],
+    about: "List, rotate or revoke the keys signing a notification webhook",
+    positional: Some(Positional {
+        name: "URL",
+        help: "The webhook's URL",
+    }),
+    flags: &[
+        Flag {
+            name: "rotate",
+            aliases: &[],
+            value: None,
+            help: "Add a new key and print its secret; older keys retire after the grace period",
+        },
+        Flag {
+            name: "grace",
+            aliases: &[],
+            value: Some("SECONDS"),
+            help: "How long replaced keys keep signing (default: 86400)",
+        },
+        Flag {
+            name: "revoke",
+            aliases: &[],
+            value: None,
+            help: "Remove every key so the webhook is sent unsigned",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+
+const SYNC_ONCE: Spec = Spec {
+    name: "sync-once",
+    aliases: &[],
+    about: "Sync once, print the result as JSON and exit (0: synced, 1: failed, 75: cooling down)",
+    positional: None,
+    flags: &[SET_FLAG, DATA_DIR_FLAG],
+};
+
+const TRASH: Spec = Spec {
+    name: "trash",
+    aliases: &[],
+    about: "List, restore or purge the files removed with deployments",
+    positional: Some(Positional {
+        name: "ACTION",
+        help: "list (the default), restore or purge",
+    }),
+    flags: &[
+        Flag {
+            name: "entry",
+            aliases: &[],
+            value: Some("ID"),
+            help: "The entry to restore or purge (purge empties the trash without one)",
+        },
+        Flag {
+            name: "force",
+            aliases: &[],
+            value: None,
+            help: "Restore over a file written since the entry was trashed",
+        },
+        Flag {
+            name: "json",
+            aliases: &[],
+            value: None,
+            help: "Print the entries as JSON",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+
+const DEPLOYMENTS: Spec = Spec {
+    name: "deployments",
+    aliases: &[],
+    about: "Pause or resume deployments, e.g. during an incident, while the agent keeps syncing",
+    positional: Some(Positional {
+        name: "ACTION",
+        help: "status (the default), pause or resume",
+    }),
+    flags: &[
+        Flag {
+            name: "reason",
+            aliases: &[],
+            value: Some("TEXT"),
+            help: "Why deployments are paused",
+        },
+        Flag {
+            name: "json",
+            aliases: &[],
+            value: None,
+            help: "Print whether deployments are paused as JSON",
+        },
+        DATA_DIR_FLAG,
+    ],
+};
+
+const COMMANDS: &[Spec] = &[
+    RUN,
+    ACTIVATE,
+    REPROVISION,
+    #[cfg(feature = "installer")]
+    INSTALL,
+    #[cfg(feature = "installer")]
+    UNINSTALL,
+    STATUS,
+    SYNC_HISTORY,
+    VERSION,
+    CONFIG_SOURCES,
+    SUPPORT_BUNDLE,
+    FSCK,
+    REPLAY,
+    WEBHOOK_KEYS,
+    SYNC_ONCE,
+    TRASH,
+    DEPLOYMENTS,
+];
+
+// =================================== PARSING ===================================== //
+/// Parses the process arguments, the first of which is the binary. The agent is run
+/// when no command is given. For backwards compatibility commands may also be given
+/// as flags, e.g. `--version` or `--support-bundle=<DIR>`.
+pub fn parse(inputs: &[String]) -> Result<Command, CliErr> {
+    let args = inputs.get(1..).unwrap_or_default();
+    let Some(first) = args.first() else {
+        return Ok(Command::Run(RunArgs::default()));
+    };
+
+    match first.as_str() {
+        "-h" | "--help" => return Ok(Command::Help(help())),
+        "help" => {
+            return match args.get(1) {
+                Some(name) => Ok(Command::Help(find(name)?.help(BINARY))),
+                None => Ok(Command::Help(help())),
+            };
+        }
+        _ => {}
+    }
+
+    // legacy: commands given as flags
+    let mut rest = args[1..].to_vec();
+    let name = match first.strip_prefix("--") {
+        Some(flag) => {
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (flag, None),
+            };
+            if !COMMANDS.iter().any(|spec| spec.matches(name)) {
+                // flags without a command are flags of the run command
+                return build(&RUN, RUN.parse(args)?);
             }
+            if let Some(value) = value {
+                rest.insert(0, value.to_string());
+            }
+            name
         }
-        args
+        None => first.as_str(),
+    };
+
+    let spec = find(name)?;
+    if let Some(rename) = compat::find(compat::CLI_COMMANDS, name) {
+        compat::record(compat::Deprecation::new(compat::Kind::Command, rename));
+    }
+    let matches = spec.parse(&rest)?;
+    if matches.help {
+        return Ok(Command::Help(spec.help(BINARY)));
     }
+    build(spec, matches)
 }
 
-#[derive(Debug, Default)]
-pub struct ReprovisionArgs {
-    pub backend_host: Option<String>,
-    pub mqtt_broker_host: Option<String>,
+fn find(name: &str) -> Result<&'static Spec, CliErr> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.matches(name))
+        .ok_or_else(|| CliErr::UnknownCommand {
+            command: name.to_string(),
+            suggestion: suggest(name, COMMANDS.iter().map(|spec| spec.name)),
+        })
 }
 
-impl ReprovisionArgs {
-    pub fn parse(inputs: &[String]) -> Self {
-        let mut args = Self::default();
-        for input in inputs.iter().skip(1) {
-            if let Some((key, value)) = input.split_once('=') {
-                let value = if value.is_empty() {
-                    None
-                } else {
-                    Some(value.to_string())
-                };
-                match key.trim_start_matches('-') {
-                    "backend-host" => args.backend_host = value,
-                    "mqtt-broker-host" => args.mqtt_broker_host = value,
-                    _ => {}
-                }
+fn build(spec: &Spec, matches: Matches) -> Result<Command, CliErr> {
+    if matches.help {
+        return Ok(Command::Help(spec.help(BINARY)));
+    }
+    let data_dir = data_dir(&matches)?;
+    let command = match spec.name {
+        "run" => Command::Run(run_args(&matches, data_dir)?),
+        "activate" => Command::Activate(ProvisionArgs {
+            backend_host: non_empty(matches.value("backend-host")),
+            mqtt_broker_host: non_empty(matches.value("mqtt-broker-host")),
+            device_name: non_empty(matches.value("device-name")),
+            token: non_empty(matches.value("token")),
+            data_dir,
+        }),
+        "reprovision" => Command::Reprovision(ReprovisionArgs {
+            backend_host: non_empty(matches.value("backend-host")),
+            mqtt_broker_host: non_empty(matches.value("mqtt-broker-host")),
+            data_dir,
+        }),
+        #[cfg(feature = "installer")]
+        "install" => Command::Install(install_args(&matches, data_dir)?),
+        #[cfg(feature = "installer")]
+        "uninstall" => Command::Uninstall(UninstallArgs {
+            yes: matches.is_set("yes"),
+            keep_logs: matches.is_set("keep-logs"),
+            keep_registration: matches.is_set("keep-registration"),
+            force: matches.is_set("force"),
+            service: non_empty(matches.value("service")),
+            data_dir,
+        }),
+        "status" => Command::Status(StatusArgs { data_dir }),
+        "sync-history" => Command::SyncHistory(SyncHistoryArgs {
+            json: matches.is_set("json"),
+            data_dir,
+        }),
+        "version" => Command::Version,
+        "config-sources" => Command::ConfigSources(run_args(&matches, data_dir)?),
+        "sync-once" => Command::SyncOnce(run_args(&matches, data_dir)?),
+        "support-bundle" => match non_empty(matches.positional.as_deref()) {
+            Some(dir) => Command::SupportBundle(SupportBundleArgs { dir, data_dir }),
+            None => {
+                return Err(CliErr::MissingArg {
+                    command: spec.name,
+                    name: "DIR",
+                })
+            }
+        },
+        "fsck" => Command::Fsck(FsckArgs {
+            fix: matches.is_set("fix"),
+            level: level(&matches)?,
+            json: matches.is_set("json"),
+            data_dir,
+        }),
+        "replay" => match non_empty(matches.positional.as_deref()) {
+            Some(capture) => Command::Replay(ReplayArgs {
+                capture: PathBuf::from(capture),
+                apply: matches.is_set("apply"),
+                json: matches.is_set("json"),
+                data_dir,
+            }),
+            None => {
+                return Err(CliErr::MissingArg {
+                    command: spec.name,
+                    name: "FILE",
+                })
             }
+        },
+        "webhook-keys" => {
+            let Some(url) = non_empty(matches.positional.as_deref()) else {
+                return Err(CliErr::MissingArg {
+                    command: spec.name,
+                    name: "URL",
+                });
+            };
+            if matches.is_set("rotate") && matches.is_set("revoke") {
+                return Err(CliErr::ConflictingFlags {
+                    flag: "rotate",
+                    other: "revoke",
+                });
+            }
+            Command::WebhookKeys(WebhookKeysArgs {
+                url,
+                rotate: matches.is_set("rotate"),
+                grace_secs: grace_secs(&matches)?,
+                revoke: matches.is_set("revoke"),
+                data_dir,
+            })
         }
-        args
+        "trash" => {
+            let entry = non_empty(matches.value("entry"));
+            let action = match matches.positional.as_deref() {
+                None | Some("list") => TrashAction::List,
+                Some("restore") => TrashAction::Restore {
+                    entry: entry.ok_or(CliErr::MissingValue { flag: "entry" })?,
+                    force: matches.is_set("force"),
+                },
+                Some("purge") => TrashAction::Purge { entry },
+                Some(arg) => {
+                    return Err(CliErr::UnexpectedArg {
+                        command: spec.name,
+                        arg: arg.to_string(),
+                    })
+                }
+            };
+            Command::Trash(TrashArgs {
+                action,
+                json: matches.is_set("json"),
+                data_dir,
+            })
+        }
+        "deployments" => {
+            let action = match matches.positional.as_deref() {
+                None | Some("status") => DeploymentsAction::Status,
+                Some("pause") => DeploymentsAction::Pause {
+                    reason: non_empty(matches.value("reason")),
+                },
+                Some("resume") => DeploymentsAction::Resume,
+                Some(arg) => {
+                    return Err(CliErr::UnexpectedArg {
+                        command: spec.name,
+                        arg: arg.to_string(),
+                    })
+                }
+            };
+            Command::Deployments(DeploymentsArgs {
+                action,
+                json: matches.is_set("json"),
+                data_dir,
+            })
+        }
+        _ => unreachable!("command '{}' is not handled", spec.name),
+    };
+    Ok(command)
+}
+
+/// The `--data-dir` flag, made absolute so that it still refers to the same directory
+/// when written into a service definition.
+fn data_dir(matches: &Matches) -> Result<Option<PathBuf>, CliErr> {
+    let Some(dir) = non_empty(matches.value("data-dir")) else {
+        return Ok(None);
+    };
+    std::path::absolute(&dir)
+        .map(Some)
+        .map_err(|e| CliErr::InvalidValue {
+            flag: "data-dir",
+            value: dir,
+            msg: e.to_string(),
+        })
+}
+
+fn level(matches: &Matches) -> Result<Severity, CliErr> {
+    let Some(value) = matches.value("level") else {
+        return Ok(Severity::Warning);
+    };
+    Severity::parse(value).ok_or_else(|| CliErr::InvalidValue {
+        flag: "level",
+        value: value.to_string(),
+        msg: "expected info, warning or error".to_string(),
+    })
+}
+
+fn grace_secs(matches: &Matches) -> Result<i64, CliErr> {
+    let Some(value) = matches.value("grace") else {
+        return Ok(DEFAULT_WEBHOOK_KEY_GRACE_SECS);
+    };
+    value
+        .parse::<i64>()
+        .ok()
+        .filter(|secs| *secs >= 0)
+        .ok_or_else(|| CliErr::InvalidValue {
+            flag: "grace",
+            value: value.to_string(),
+            msg: "expected a number of seconds".to_string(),
+        })
+}
+
+#[cfg(feature = "installer")]
+fn install_args(matches: &Matches, data_dir: Option<PathBuf>) -> Result<InstallArgs, CliErr> {
+    let restart = non_empty(matches.value("restart"));
+    if let Some(policy) = &restart {
+        if !crate::platform::RESTART_POLICIES.contains(&policy.as_str()) {
+            return Err(CliErr::InvalidValue {
+                flag: "restart",
+                value: policy.clone(),
+                msg: format!(
+                    "expected one of {}",
+                    crate::platform::RESTART_POLICIES.join(", ")
+                ),
+            });
+        }
+    }
+    let watchdog_sec = match matches.value("watchdog") {
+        Some(value) => Some(
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| CliErr::InvalidValue {
+                    flag: "watchdog",
+                    value: value.to_string(),
+                    msg: "expected a positive number of seconds".to_string(),
+                })?,
+        ),
+        None => None,
+    };
+    Ok(InstallArgs {
+        output: non_empty(matches.value("output")).map(PathBuf::from),
+        data_dir,
+        hardening: matches.is_set("hardening"),
+        writable_paths: matches
+            .values("writable-path")
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        restart,
+        watchdog_sec,
+        tmpfiles: non_empty(matches.value("tmpfiles")).map(PathBuf::from),
+    })
+}
+
+fn run_args(matches: &Matches, data_dir: Option<PathBuf>) -> Result<RunArgs, CliErr> {
+    let mut args = RunArgs {
+        data_dir,
+        record: non_empty(matches.value("record")).map(PathBuf::from),
+        ..Default::default()
+    };
+    for value in matches.values("set") {
+        let Some((field, field_value)) = value.split_once('=') else {
+            return Err(CliErr::InvalidValue {
+                flag: "set",
+                value: value.to_string(),
+                msg: "expected <FIELD>=<VALUE>".to_string(),
+            });
+        };
+        if field.is_empty() {
+            return Err(CliErr::InvalidValue {
+                flag: "set",
+                value: value.to_string(),
+                msg: "the field is empty".to_string(),
+            });
+        }
+        args.settings_overrides
+            .push((field.to_string(), field_value.to_string()));
+    }
+    Ok(args)
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// The top-level `--help` output.
+pub fn help() -> String {
+    let mut out = format!(
+        "Miru Agent {}\n\nUsage: {BINARY} [COMMAND] [FLAGS]\n\nCommands:\n",
+        version::VERSION
+    );
+    for spec in COMMANDS {
+        let _ = writeln!(out, "  {:<18}{}", spec.name, spec.about);
     }
+    let _ = writeln!(out, "  {:<18}Print help for a command", "help <COMMAND>");
+    let _ = write!(
+        out,
+        "\nRun '{BINARY} <COMMAND> --help' for the flags of a command.\n"
+    );
+    out
 }
@@ -0,0 +1,181 @@
+// standard crates
+use std::fmt::Write;
+
+// internal crates
+use crate::cli::errors::CliErr;
+use crate::config::compat;
+
+/// A `--name` or `--name=<VALUE>` flag accepted by a command.
+pub struct Flag {
+    pub name: &'static str,
+    /// Other names the flag may be given as. Values are always reported under `name`.
+    pub aliases: &'static [&'static str],
+    /// The placeholder shown in the help output for flags which take a value. Flags
+    /// without one are switches.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// A positional argument accepted by a command.
+pub struct Positional {
+    pub name: &'static str,
+    pub help: &'static str,
+}
+
+pub struct Spec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub about: &'static str,
+    pub positional: Option<Positional>,
+    pub flags: &'static [Flag],
+}
+
+/// The flags and positional argument given to a command.
+#[derive(Debug, Default)]
+pub struct Matches {
+    flags: Vec<(&'static str, Option<String>)>,
+    pub positional: Option<String>,
+    pub help: bool,
+}
+
+impl Matches {
+    pub fn is_set(&self, name: &str) -> bool {
+        self.flags.iter().any(|(flag, _)| *flag == name)
+    }
+
+    /// The value of a flag. The last value wins if the flag was given more than once.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).into_iter().last()
+    }
+
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.flags
+            .iter()
+            .filter(|(flag, _)| *flag == name)
+            .filter_map(|(_, value)| value.as_deref())
+            .collect()
+    }
+}
+
+impl Spec {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    /// Parses the arguments following the command name. Unknown flags and unexpected
+    /// arguments are rejected rather than ignored so that typos surface immediately.
+    pub fn parse(&self, args: &[String]) -> Result<Matches, CliErr> {
+        let mut matches = Matches::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                matches.help = true;
+                continue;
+            }
+            let Some(flag) = arg.strip_prefix("--") else {
+                if self.positional.is_none() || matches.positional.is_some() {
+                    return Err(CliErr::UnexpectedArg {
+                        command: self.name,
+                        arg: arg.clone(),
+                    });
+                }
+                matches.positional = Some(arg.clone());
+                continue;
+            };
+
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let Some(spec) = self
+                .flags
+                .iter()
+                .find(|f| f.name == name || f.aliases.contains(&name))
+            else {
+                return Err(CliErr::UnknownFlag {
+                    command: self.name,
+                    flag: name.to_string(),
+                    suggestion: suggest(name, self.flags.iter().map(|f| f.name)),
+                });
+            };
+            if let Some(rename) = compat::find(compat::CLI_FLAGS, name) {
+                compat::record(compat::Deprecation::new(compat::Kind::Flag, rename));
+            }
+            let value = match (spec.value, inline_value) {
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => match args.next() {
+                    Some(value) if !value.starts_with("--") => Some(value.clone()),
+                    _ => return Err(CliErr::MissingValue { flag: spec.name }),
+                },
+                (None, Some(_)) => return Err(CliErr::UnexpectedValue { flag: spec.name }),
+                (None, None) => None,
+            };
+            matches.flags.push((spec.name, value));
+        }
+        Ok(matches)
+    }
+
+    /// Renders the command's `--help` output.
+    pub fn help(&self, binary: &str) -> String {
+        let mut out = format!("{}\n\nUsage: {binary} {}", self.about, self.name);
+        if !self.flags.is_empty() {
+            out.push_str(" [FLAGS]");
+        }
+        if let Some(positional) = &self.positional {
+            let _ = write!(out, " <{}>", positional.name);
+        }
+        out.push('\n');
+        if !self.aliases.is_empty() {
+            let _ = writeln!(out, "Aliases: {}", self.aliases.join(", "));
+        }
+
+        if let Some(positional) = &self.positional {
+            let _ = write!(
+                out,
+                "\nArguments:\n  {:<28}{}\n",
+                positional.name, positional.help
+            );
+        }
+
+        out.push_str("\nFlags:\n");
+        for flag in self.flags {
+            let usage = match flag.value {
+                Some(value) => format!("--{}=<{value}>", flag.name),
+                None => format!("--{}", flag.name),
+            };
+            let _ = write!(out, "  {usage:<28}{}", flag.help);
+            if !flag.aliases.is_empty() {
+                let aliases: Vec<String> = flag.aliases.iter().map(|a| format!("--{a}")).collect();
+                let _ = write!(out, " (alias: {})", aliases.join(", "));
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(out, "  {:<28}Print help", "-h, --help");
+        out
+    }
+}
+
+/// Suggests the closest candidate to a mistyped name, if any is close enough to be a
+/// likely typo.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,90 @@
+// standard crates
+use std::fmt;
+
+// internal crates
+use crate::crypt::keystore::PrivateKey;
+use crate::deploy::pause;
+use crate::diagnostics::crash;
+use crate::filesys::PathExt;
+use crate::models;
+use crate::storage::{self, wear::Usage, Layout};
+
+/// What `status` reports about the agent. It is read from the files the agent leaves
+/// on disk so it works whether or not the agent is running.
+#[derive(Debug, Default)]
+pub struct Status {
+    pub activated: bool,
+    pub device: Option<models::Device>,
+    pub wear: Option<Usage>,
+    pub last_crash: Option<crash::Report>,
+    pub deployments: pause::Status,
+}
+
+impl Status {
+    pub async fn read(layout: &Layout, private_key: &PrivateKey) -> Self {
+        let activated = storage::assert_activated(layout, private_key).await.is_ok();
+
+        let device_file = layout.device();
+        let device = if device_file.exists() {
+            device_file.read_json::<models::Device>().await.ok()
+        } else {
+            None
+        };
+
+        let wear_file = layout.wear();
+        let wear = if wear_file.exists() {
+            wear_file.read_json::<Usage>().await.ok()
+        } else {
+            None
+        };
+
+        Self {
+            activated,
+            device,
+            wear,
+            last_crash: crash::last_crash(&layout.crash()).await,
+            deployments: pause::Switch::new(layout.deployments_paused())
+                .status()
+                .await,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let activated = if self.activated { "yes" } else { "no" };
+        writeln!(f, "Activated:      {activated}")?;
+        match &self.device {
+            Some(device) => {
+                writeln!(f, "Device ID:      {}", device.id)?;
+                writeln!(f, "Device name:    {}", device.name)?;
+                writeln!(f, "Device status:  {}", device.status.as_str())?;
+                writeln!(f, "Last synced at: {}", device.last_synced_at.to_rfc3339())?;
+            }
+            None => writeln!(f, "Device:         unknown")?,
+        }
+        match &self.wear {
+            Some(wear) => writeln!(
+                f,
+                "Bytes written:  {} since {}",
+                wear.total_bytes(),
+                wear.since.to_rfc3339()
+            )?,
+            None => writeln!(f, "Bytes written:  unknown")?,
+        }
+        match (self.deployments.paused, &self.deployments.reason) {
+            (false, _) => writeln!(f, "Deployments:    running")?,
+            (true, Some(reason)) => writeln!(f, "Deployments:    paused ({reason})")?,
+            (true, None) => writeln!(f, "Deployments:    paused")?,
+        }
+        match &self.last_crash {
+            Some(report) => write!(
+                f,
+                "Last crash:     {} ({})",
+                report.crashed_at.to_rfc3339(),
+                report.message
+            ),
+            None => write!(f, "Last crash:     none"),
+        }
+    }
+}
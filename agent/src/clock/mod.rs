@@ -0,0 +1,167 @@
+// The device's clock can't be trusted: a device with a dead RTC battery boots with its
+// clock years off and keeps it until NTP, if there is any, corrects it. Tokens then
+// look expired (or never expire), the JWTs the agent mints are rejected and sync
+// cooldowns end immediately or never. The backend's clock is the reference instead:
+// each response's `Date` header is compared to the local time, and the difference is
+// kept as a skew which authn and the syncer add to the local time. Like the metrics,
+// the skew lives in a process-wide clock so it's measured and applied where it's
+// needed without threading a handle through every call.
+
+// standard crates
+use std::sync::atomic::{AtomicI64, Ordering};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::{HeaderMap, DATE};
+use tracing::{info, warn};
+
+/// Skews within this are noise: the `Date` header only has a resolution of a second
+/// and the request's latency blurs it further.
+pub const TOLERANCE: TimeDelta = TimeDelta::seconds(2);
+
+/// Skews beyond this are logged and reported as degrading the clock's health.
+pub const LARGE_SKEW: TimeDelta = TimeDelta::seconds(60);
+
+const NEVER: i64 = i64::MIN;
+
+#[derive(Debug)]
+pub struct Clock {
+    /// The backend's time minus the local time, in milliseconds.
+    skew_ms: AtomicI64,
+    /// When the skew was last measured, in milliseconds since the epoch.
+    checked_at_ms: AtomicI64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Status {
+    pub skew: TimeDelta,
+    /// Unset until a response from the backend was received.
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+impl Status {
+    pub fn is_large(&self) -> bool {
+        self.skew.abs() >= LARGE_SKEW
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub const fn new() -> Self {
+        Self {
+            skew_ms: AtomicI64::new(0),
+            checked_at_ms: AtomicI64::new(NEVER),
+        }
+    }
+
+    /// The local time corrected by the skew.
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.skew()
+    }
+
+    pub fn skew(&self) -> TimeDelta {
+        TimeDelta::milliseconds(self.skew_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn status(&self) -> Status {
+        let checked_at = match self.checked_at_ms.load(Ordering::Relaxed) {
+            NEVER => None,
+            ms => DateTime::from_timestamp_millis(ms),
+        };
+        Status {
+            skew: self.skew(),
+            checked_at,
+        }
+    }
+
+    /// Measures the skew from the backend's time, given by a response received at
+    /// `received_at` to a request sent at `sent_at` (both local times). The backend is
+    /// taken to have answered halfway through the request. Skews within the
+    /// [TOLERANCE] are ignored, as are changes to the skew within it.
+    pub fn observe(
+        &self,
+        backend_time: DateTime<Utc>,
+        sent_at: DateTime<Utc>,
+        received_at: DateTime<Utc>,
+    ) {
+        let latency = (received_at - sent_at).max(TimeDelta::zero());
+        let local_time = sent_at + latency / 2;
+        let measured = backend_time - local_time;
+        self.checked_at_ms.store(
+            (received_at + measured).timestamp_millis(),
+            Ordering::Relaxed,
+        );
+
+        let previous = self.skew();
+        if (measured - previous).abs() < TOLERANCE {
+            return;
+        }
+        let skew = if measured.abs() < TOLERANCE {
+            TimeDelta::zero()
+        } else {
+            measured
+        };
+        self.skew_ms
+            .store(skew.num_milliseconds(), Ordering::Relaxed);
+
+        if skew.abs() >= LARGE_SKEW {
+            warn!(
+                "the device's clock is {}s {} the backend's, correcting timestamps by it",
+                skew.num_seconds().abs(),
+                if skew < TimeDelta::zero() {
+                    "ahead of"
+                } else {
+                    "behind"
+                },
+            );
+        } else if previous.abs() >= LARGE_SKEW {
+            info!(
+                "the device's clock is within {}s of the backend's again",
+                skew.num_seconds().abs().max(TOLERANCE.num_seconds())
+            );
+        }
+    }
+
+    /// Measures the skew from a response's `Date` header, if it has a valid one.
+    pub fn observe_headers(
+        &self,
+        headers: &HeaderMap,
+        sent_at: DateTime<Utc>,
+        received_at: DateTime<Utc>,
+    ) {
+        let Some(backend_time) = headers
+            .get(DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(parse_http_date)
+        else {
+            return;
+        };
+        self.observe(backend_time, sent_at, received_at);
+    }
+}
+
+/// Parses an HTTP date (RFC 9110 §5.6.7), e.g. `Sun, 06 Nov 1994 08:49:37 GMT`. The
+/// header is truncated to the second so half a second is added, the middle of the
+/// second it names.
+pub fn parse_http_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.with_timezone(&Utc) + TimeDelta::milliseconds(500))
+}
+
+static CLOCK: Clock = Clock::new();
+
+/// The agent's clock.
+pub fn global() -> &'static Clock {
+    &CLOCK
+}
+
+/// The local time corrected by the skew from the backend's clock.
+pub fn now() -> DateTime<Utc> {
+    CLOCK.now()
+}
@@ -0,0 +1,219 @@
+// Settings fields, CLI flags and commands are renamed as the agent evolves. The old
+// names keep working so that fleets can upgrade agents before rewriting their
+// settings files and service definitions; each use of one is recorded once per
+// process, logged as a structured warning and reported by the health endpoint.
+
+// standard crates
+use std::fmt;
+use std::sync::Mutex;
+
+// internal crates
+use crate::config::resolve::{insert_path, Source};
+
+// external crates
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::warn;
+
+// ================================== RENAMES ====================================== //
+/// A deprecated name and the name which replaced it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rename {
+    pub old: &'static str,
+    pub new: &'static str,
+    /// The agent version which deprecated the old name.
+    pub since: &'static str,
+}
+
+/// Renamed settings fields, as `.` separated field paths.
+pub const SETTINGS_FIELDS: &[Rename] = &[
+    Rename {
+        old: "enable_mqtt",
+        new: "enable_mqtt_worker",
+        since: "v0.7.0",
+    },
+    Rename {
+        old: "reboot.allowed",
+        new: "reboot.authorized",
+        since: "v0.8.0",
+    },
+    Rename {
+        old: "safe_mode.crash_window_secs",
+        new: "safe_mode.window_secs",
+        since: "v0.8.0",
+    },
+];
+
+/// Renamed flags, without their leading `--`. The old names remain aliases of the
+/// new flags in the command specs.
+pub const CLI_FLAGS: &[Rename] = &[Rename {
+    old: "config",
+    new: "data-dir",
+    since: "v0.8.0",
+}];
+
+/// Renamed commands. The old names remain aliases of the new commands in the
+/// command specs.
+pub const CLI_COMMANDS: &[Rename] = &[
+    Rename {
+        old: "provision",
+        new: "activate",
+        since: "v0.8.0",
+    },
+    Rename {
+        old: "service-definition",
+        new: "install",
+        since: "v0.9.0",
+    },
+];
+
+pub fn find(renames: &'static [Rename], old: &str) -> Option<&'static Rename> {
+    renames.iter().find(|rename| rename.old == old)
+}
+
+// ================================= DEPRECATIONS ================================== //
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Setting,
+    Flag,
+    Command,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Setting => "setting",
+            Kind::Flag => "flag",
+            Kind::Command => "command",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A deprecated name the agent was given.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    pub kind: Kind,
+    pub name: String,
+    pub replacement: String,
+    pub since: String,
+    /// Where a deprecated setting was given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+impl Deprecation {
+    pub fn new(kind: Kind, rename: &Rename) -> Self {
+        Self {
+            kind,
+            name: rename.old.to_string(),
+            replacement: rename.new.to_string(),
+            since: rename.since.to_string(),
+            source: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: Source) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the {} '{}' is deprecated since {}, use '{}' instead",
+            self.kind, self.name, self.since, self.replacement
+        )?;
+        if let Some(source) = self.source {
+            write!(f, " (given by the {source} source)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The deprecations recorded by this process and whether each has been logged.
+static RECORDED: Mutex<Vec<(Deprecation, bool)>> = Mutex::new(Vec::new());
+
+/// Records a deprecation, logging it unless the same deprecated name was already
+/// recorded. Deprecations recorded before logging is initialized (e.g. while parsing
+/// the command line) are logged by `warn_pending`.
+pub fn record(deprecation: Deprecation) {
+    let mut recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    if recorded
+        .iter()
+        .any(|(d, _)| d.kind == deprecation.kind && d.name == deprecation.name)
+    {
+        return;
+    }
+    let logged = tracing::dispatcher::has_been_set();
+    if logged {
+        log(&deprecation);
+    }
+    recorded.push((deprecation, logged));
+}
+
+/// Logs the recorded deprecations which haven't been logged yet.
+pub fn warn_pending() {
+    let mut recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    for (deprecation, logged) in recorded.iter_mut().filter(|(_, logged)| !*logged) {
+        log(deprecation);
+        *logged = true;
+    }
+}
+
+/// Every deprecation recorded by this process, in the order first recorded.
+pub fn recorded() -> Vec<Deprecation> {
+    let recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    recorded.iter().map(|(d, _)| d.clone()).collect()
+}
+
+fn log(deprecation: &Deprecation) {
+    warn!(
+        kind = %deprecation.kind,
+        name = %deprecation.name,
+        replacement = %deprecation.replacement,
+        since = %deprecation.since,
+        source = deprecation.source.map(|s| s.as_str()),
+        "{deprecation}"
+    );
+}
+
+// =================================== SETTINGS ==================================== //
+/// Moves the values of renamed fields in a layer to their new paths, returning the
+/// deprecations found. A value given under the new path takes precedence over one
+/// given under the old.
+pub fn migrate_settings(values: &mut Map<String, Value>, source: Source) -> Vec<Deprecation> {
+    let mut deprecations = Vec::new();
+    for rename in SETTINGS_FIELDS {
+        let Some(value) = remove_path(values, rename.old) else {
+            continue;
+        };
+        if get_path(values, rename.new).is_none() {
+            insert_path(values, rename.new, value);
+        }
+        deprecations.push(Deprecation::new(Kind::Setting, rename).with_source(source));
+    }
+    deprecations
+}
+
+fn get_path<'a>(map: &'a Map<String, Value>, field: &str) -> Option<&'a Value> {
+    match field.split_once('.') {
+        Some((head, rest)) => get_path(map.get(head)?.as_object()?, rest),
+        None => map.get(field),
+    }
+}
+
+fn remove_path(map: &mut Map<String, Value>, field: &str) -> Option<Value> {
+    match field.split_once('.') {
+        Some((head, rest)) => remove_path(map.get_mut(head)?.as_object_mut()?, rest),
+        None => map.remove(field),
+    }
+}
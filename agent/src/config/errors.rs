@@ -0,0 +1,96 @@
+// internal crates
+use crate::config::resolve::Source;
+use crate::errors::Trace;
+use crate::filesys;
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown settings field: {field}")]
+pub struct UnknownFieldErr {
+    pub field: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for UnknownFieldErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid value '{value}' for settings field '{field}': expected {expected}")]
+pub struct InvalidValueErr {
+    pub field: String,
+    pub value: String,
+    pub expected: &'static str,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidValueErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid {kind} '{value}': expected {expected}")]
+pub struct ParseUnitErr {
+    pub value: String,
+    pub kind: &'static str,
+    pub expected: &'static str,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ParseUnitErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("settings provided by the {layer} source must be a JSON object")]
+pub struct InvalidLayerErr {
+    pub layer: Source,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidLayerErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("settings field '{field}' can't be set remotely")]
+pub struct RemoteFieldErr {
+    pub field: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for RemoteFieldErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("serialization error: {source}")]
+pub struct SerdeErr {
+    pub source: serde_json::Error,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for SerdeErr {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigErr {
+    #[error(transparent)]
+    UnknownFieldErr(UnknownFieldErr),
+    #[error(transparent)]
+    InvalidValueErr(InvalidValueErr),
+    #[error(transparent)]
+    ParseUnitErr(ParseUnitErr),
+    #[error(transparent)]
+    InvalidLayerErr(InvalidLayerErr),
+    #[error(transparent)]
+    RemoteFieldErr(RemoteFieldErr),
+    #[error(transparent)]
+    SerdeErr(SerdeErr),
+    #[error(transparent)]
+    FileSysErr(filesys::FileSysErr),
+}
+
+impl From<filesys::FileSysErr> for ConfigErr {
+    fn from(e: filesys::FileSysErr) -> Self {
+        Self::FileSysErr(e)
+    }
+}
+
+crate::impl_error!(ConfigErr {
+    UnknownFieldErr,
+    InvalidValueErr,
+    ParseUnitErr,
+    InvalidLayerErr,
+    RemoteFieldErr,
+    SerdeErr,
+    FileSysErr,
+});
@@ -0,0 +1,9 @@
+pub mod compat;
+pub mod errors;
+pub mod remote;
+pub mod resolve;
+pub mod units;
+
+pub use self::compat::Deprecation;
+pub use self::errors::ConfigErr;
+pub use self::resolve::{load, load_remote, resolve, Layer, Resolved, Source, ENV_PREFIX};
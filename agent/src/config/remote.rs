@@ -0,0 +1,100 @@
+// Remote settings take precedence over every other source, so a remote may only set
+// the fields listed here: the log level and the tuning of intervals, retries and
+// retention. Fields which run commands (hooks, the supervisor, reboots), choose who
+// the agent trusts or talks to (the backend, the broker, release signing, keys) or
+// where it writes (the deployment directory) can only be set on the device.
+
+// internal crates
+use crate::config::errors::{ConfigErr, RemoteFieldErr};
+use crate::trace;
+
+// external crates
+use serde_json::{Map, Value};
+use tracing::warn;
+
+/// The fields a remote may set, by path. A section (e.g. `sync_backoff`) allows
+/// each of its fields.
+pub const FIELDS: &[&str] = &[
+    "log_level",
+    "poller.interval_secs",
+    "poller.jitter_secs",
+    "poller.maintenance_windows",
+    "long_poll.wait_secs",
+    "long_poll.min_interval_secs",
+    "wear.low_wear_mode",
+    "sync_history.capacity",
+    "sync_history.persist",
+    "sync_backoff",
+    "content_warming",
+    "trash.retention_secs",
+    "trash.max_bytes",
+    "load_shedding",
+    "telemetry.interval_secs",
+    "telemetry.retention",
+    "crash_reports.log_lines",
+];
+
+/// Errors on the first field in `values` a remote may not set.
+pub fn check(values: &Map<String, Value>) -> Result<(), ConfigErr> {
+    let mut rejected = Vec::new();
+    retain(&mut values.clone(), "", &mut rejected);
+    match rejected.into_iter().next() {
+        Some(field) => Err(ConfigErr::RemoteFieldErr(RemoteFieldErr {
+            field,
+            trace: trace!(),
+        })),
+        None => Ok(()),
+    }
+}
+
+/// Drops the fields in `values` a remote may not set with a warning, for remote
+/// settings written before a field was taken off the list.
+pub fn filter(mut values: Map<String, Value>) -> Map<String, Value> {
+    let mut rejected = Vec::new();
+    retain(&mut values, "", &mut rejected);
+    for field in rejected {
+        warn!("ignoring remote setting '{field}' since it can't be set remotely");
+    }
+    values
+}
+
+fn retain(values: &mut Map<String, Value>, prefix: &str, rejected: &mut Vec<String>) {
+    values.retain(|key, value| {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        if is_allowed(&path) {
+            return true;
+        }
+        match value {
+            Value::Object(child) if is_section(&path) => {
+                retain(child, &path, rejected);
+                true
+            }
+            _ => {
+                rejected.push(path);
+                false
+            }
+        }
+    });
+}
+
+fn is_allowed(path: &str) -> bool {
+    FIELDS.iter().any(|field| {
+        path == *field
+            || path
+                .strip_prefix(field)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Whether some allowed field lies under the path.
+fn is_section(path: &str) -> bool {
+    FIELDS.iter().any(|field| {
+        field
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
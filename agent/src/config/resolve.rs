@@ -0,0 +1,405 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::fmt;
+
+// internal crates
+use crate::config::compat::{self, Deprecation};
+use crate::config::errors::{
+    ConfigErr, InvalidLayerErr, InvalidValueErr, SerdeErr, UnknownFieldErr,
+};
+use crate::config::remote;
+use crate::diagnostics::redact::Redactor;
+use crate::filesys::{File, PathExt};
+use crate::storage::Settings;
+use crate::trace;
+
+// external crates
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::warn;
+
+/// Prefix of the environment variables which override settings fields. The
+/// rest of the variable name is the upper-cased field path with `.` replaced
+/// by `_` (e.g. `MIRU_AGENT_BACKEND_BASE_URL` for `backend.base_url`).
+pub const ENV_PREFIX: &str = "MIRU_AGENT_";
+
+/// The origin of a settings value. Variants are declared from lowest to highest
+/// precedence so that later sources win when layers are merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Cli,
+    /// Settings managed remotely with the `set_remote_settings` command, see
+    /// [crate::mqtt::command].
+    Remote,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Default => "default",
+            Source::File => "file",
+            Source::Env => "env",
+            Source::Cli => "cli",
+            Source::Remote => "remote",
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A partial settings object contributed by a single source.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub source: Source,
+    pub values: Map<String, Value>,
+}
+
+impl Layer {
+    pub fn new(source: Source, values: Map<String, Value>) -> Self {
+        Self { source, values }
+    }
+
+    pub fn from_value(source: Source, value: Value) -> Result<Self, ConfigErr> {
+        match value {
+            Value::Object(values) => Ok(Self::new(source, values)),
+            _ => Err(ConfigErr::InvalidLayerErr(InvalidLayerErr {
+                layer: source,
+                trace: trace!(),
+            })),
+        }
+    }
+
+    /// Builds a layer from `field.path=value` style overrides. Values are parsed
+    /// according to the type of the field's default value: lists take a JSON array
+    /// or a comma-separated list of strings and optional fields take JSON. Renamed
+    /// fields may be given by their old names.
+    pub fn from_overrides(
+        source: Source,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigErr> {
+        let fields = default_fields()?;
+        let mut values = Map::new();
+        for (field, raw) in overrides {
+            let default = field_default(&fields, field).ok_or_else(|| {
+                ConfigErr::UnknownFieldErr(UnknownFieldErr {
+                    field: field.clone(),
+                    trace: trace!(),
+                })
+            })?;
+            let value = parse_value(field, raw, default)?;
+            insert_path(&mut values, field, value);
+        }
+        Ok(Self::new(source, values))
+    }
+
+    /// Builds a layer from the `MIRU_AGENT_*` variables in `vars`. Variables which
+    /// don't name a settings field or fail to parse are skipped with a warning
+    /// since the environment is shared with unrelated processes.
+    pub fn from_env<I>(vars: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let fields = match default_fields() {
+            Ok(fields) => fields,
+            Err(e) => {
+                warn!("unable to determine settings fields for env overrides: {e}");
+                return Self::new(Source::Env, Map::new());
+            }
+        };
+
+        let mut values = Map::new();
+        for (key, raw) in vars {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let name = name.to_lowercase();
+            let renamed = compat::SETTINGS_FIELDS
+                .iter()
+                .map(|rename| rename.old)
+                .filter_map(|old| Some((old, field_default(&fields, old)?)));
+            let Some((field, default)) = fields
+                .iter()
+                .map(|(field, default)| (field.as_str(), default))
+                .chain(renamed)
+                .find(|(field, _)| field.replace('.', "_") == name)
+            else {
+                warn!("ignoring environment variable '{key}': not a settings field");
+                continue;
+            };
+            match parse_value(field, &raw, default) {
+                Ok(value) => insert_path(&mut values, field, value),
+                Err(e) => warn!("ignoring environment variable '{key}': {e}"),
+            }
+        }
+        Self::new(Source::Env, values)
+    }
+}
+
+/// The effective settings along with the source of each field's value.
+#[derive(Debug)]
+pub struct Resolved {
+    pub settings: Settings,
+    pub values: BTreeMap<String, Value>,
+    pub sources: BTreeMap<String, Source>,
+    /// The renamed fields the layers gave by their old names.
+    pub deprecations: Vec<Deprecation>,
+}
+
+impl Resolved {
+    pub fn source_of(&self, field: &str) -> Option<Source> {
+        self.sources.get(field).copied()
+    }
+
+    /// Renders one `field = value (source)` line per settings field. The report is
+    /// logged, so set fields the default redaction rules match, such as the proxy
+    /// password and the cell token, are redacted.
+    pub fn report(&self) -> String {
+        let redactor = Redactor::default();
+        self.values
+            .iter()
+            .map(|(field, value)| {
+                let source = self.source_of(field).unwrap_or(Source::Default);
+                let mut value = value.clone();
+                if !value.is_null() {
+                    redactor.redact_field(field, &mut value);
+                }
+                format!("{field} = {value} ({source})")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Merges the layers over the default settings in order of precedence. When a
+/// layer supplies a value that the settings deserializer rejects and replaces
+/// with the default, the field is attributed to the default source. Values given
+/// under the old names of renamed fields are moved to the new names and recorded
+/// as deprecations.
+pub fn resolve(layers: &[Layer]) -> Result<Resolved, ConfigErr> {
+    let mut merged = to_map(&Settings::default())?;
+    let defaults = default_fields()?;
+    let mut sources = BTreeMap::new();
+    let mut deprecations = Vec::new();
+
+    let mut layers = layers.iter().collect::<Vec<_>>();
+    layers.sort_by_key(|layer| layer.source);
+    for layer in layers {
+        let mut values = layer.values.clone();
+        deprecations.extend(compat::migrate_settings(&mut values, layer.source));
+        merge(&mut merged, &values, "", layer.source, &mut sources);
+    }
+    for deprecation in &deprecations {
+        compat::record(deprecation.clone());
+    }
+
+    let mut requested = BTreeMap::new();
+    flatten(&merged, "", &mut requested);
+
+    let settings: Settings = serde_json::from_value(Value::Object(merged)).map_err(|e| {
+        ConfigErr::SerdeErr(SerdeErr {
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+
+    let mut values = BTreeMap::new();
+    flatten(&to_map(&settings)?, "", &mut values);
+
+    let sources = values
+        .iter()
+        .map(|(field, value)| {
+            let rejected =
+                requested.get(field) != Some(value) && defaults.get(field) == Some(value);
+            let source = match sources.get(field) {
+                Some(source) if !rejected => *source,
+                _ => Source::Default,
+            };
+            (field.clone(), source)
+        })
+        .collect();
+
+    Ok(Resolved {
+        settings,
+        values,
+        sources,
+        deprecations,
+    })
+}
+
+/// Resolves the settings from the settings file, the environment, the provided
+/// CLI overrides, and the remotely managed settings file.
+pub async fn load(
+    settings_file: &File,
+    remote_settings_file: &File,
+    cli_overrides: &[(String, String)],
+) -> Result<Resolved, ConfigErr> {
+    let file = Layer::from_value(Source::File, settings_file.read_json::<Value>().await?)?;
+    let env = Layer::from_env(std::env::vars());
+    let cli = Layer::from_overrides(Source::Cli, cli_overrides)?;
+    let remote = load_remote(remote_settings_file).await?;
+    resolve(&[file, env, cli, remote])
+}
+
+/// Reads the remotely managed settings, which are empty until a remote sets them.
+/// Fields a remote may not set are dropped, see [crate::config::remote].
+pub async fn load_remote(remote_settings_file: &File) -> Result<Layer, ConfigErr> {
+    if !remote_settings_file.exists() {
+        return Ok(Layer::new(Source::Remote, Map::new()));
+    }
+    let layer = Layer::from_value(
+        Source::Remote,
+        remote_settings_file.read_json::<Value>().await?,
+    )?;
+    Ok(Layer::new(Source::Remote, remote::filter(layer.values)))
+}
+
+fn to_map(settings: &Settings) -> Result<Map<String, Value>, ConfigErr> {
+    match serde_json::to_value(settings) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(ConfigErr::InvalidLayerErr(InvalidLayerErr {
+            layer: Source::Default,
+            trace: trace!(),
+        })),
+        Err(e) => Err(ConfigErr::SerdeErr(SerdeErr {
+            source: e,
+            trace: trace!(),
+        })),
+    }
+}
+
+fn default_fields() -> Result<BTreeMap<String, Value>, ConfigErr> {
+    let mut fields = BTreeMap::new();
+    flatten(&to_map(&Settings::default())?, "", &mut fields);
+    Ok(fields)
+}
+
+/// The default value of a field, looking renamed fields up by their new names.
+fn field_default<'a>(fields: &'a BTreeMap<String, Value>, field: &str) -> Option<&'a Value> {
+    fields.get(field).or_else(|| {
+        let rename = compat::find(compat::SETTINGS_FIELDS, field)?;
+        fields.get(rename.new)
+    })
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+fn flatten(map: &Map<String, Value>, prefix: &str, out: &mut BTreeMap<String, Value>) {
+    for (key, value) in map {
+        let path = join(prefix, key);
+        match value {
+            // empty maps (e.g. rules keyed by name) are fields in their own right
+            Value::Object(child) if !child.is_empty() => flatten(child, &path, out),
+            _ => {
+                out.insert(path, value.clone());
+            }
+        }
+    }
+}
+
+fn merge(
+    base: &mut Map<String, Value>,
+    layer: &Map<String, Value>,
+    prefix: &str,
+    source: Source,
+    sources: &mut BTreeMap<String, Source>,
+) {
+    for (key, value) in layer {
+        let path = join(prefix, key);
+        match (base.get_mut(key), value) {
+            (Some(Value::Object(base_child)), Value::Object(layer_child)) => {
+                merge(base_child, layer_child, &path, source, sources);
+            }
+            _ => {
+                let mut leaves = BTreeMap::new();
+                match value {
+                    Value::Object(child) => flatten(child, &path, &mut leaves),
+                    _ => {
+                        leaves.insert(path, value.clone());
+                    }
+                }
+                for field in leaves.into_keys() {
+                    sources.insert(field, source);
+                }
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+pub(crate) fn insert_path(map: &mut Map<String, Value>, field: &str, value: Value) {
+    match field.split_once('.') {
+        Some((head, rest)) => {
+            let child = map
+                .entry(head.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !child.is_object() {
+                *child = Value::Object(Map::new());
+            }
+            if let Value::Object(child) = child {
+                insert_path(child, rest, value);
+            }
+        }
+        None => {
+            map.insert(field.to_string(), value);
+        }
+    }
+}
+
+fn parse_value(field: &str, raw: &str, default: &Value) -> Result<Value, ConfigErr> {
+    let invalid = |expected: &'static str| {
+        ConfigErr::InvalidValueErr(InvalidValueErr {
+            field: field.to_string(),
+            value: raw.to_string(),
+            expected,
+            trace: trace!(),
+        })
+    };
+    match default {
+        Value::Bool(_) => match raw.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(Value::Bool(true)),
+            "false" | "0" | "no" | "off" => Ok(Value::Bool(false)),
+            _ => Err(invalid("a boolean")),
+        },
+        Value::Number(_) => raw
+            .trim()
+            .parse::<serde_json::Number>()
+            .map(Value::Number)
+            .map_err(|_| invalid("a number")),
+        // lists may be given as JSON or, for lists of strings, comma-separated
+        Value::Array(_) if !raw.trim_start().starts_with('[') => Ok(Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )),
+        Value::Array(_) => match serde_json::from_str(raw) {
+            Ok(value @ Value::Array(_)) => Ok(value),
+            _ => Err(invalid("a JSON array or comma-separated list")),
+        },
+        // optional and structured fields have no scalar type to parse by. Optional
+        // scalars such as sizes may be given as plain strings
+        Value::Null | Value::Object(_) => match raw.trim() {
+            "" => Ok(Value::Null),
+            raw if raw.starts_with(['{', '[']) => {
+                serde_json::from_str(raw).map_err(|_| invalid("JSON"))
+            }
+            raw => Ok(serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))),
+        },
+        Value::String(_) => Ok(Value::String(raw.to_string())),
+    }
+}
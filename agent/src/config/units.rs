@@ -0,0 +1,300 @@
+// Human-friendly durations ("30s", "5m", "1h30m") and sizes ("512KiB", "10MB") for
+// settings. Plain numbers are still accepted, as seconds and bytes respectively, so
+// settings files written before units were supported keep working. Settings are
+// serialized in the human-friendly form.
+
+// standard crates
+use std::fmt;
+use std::time::Duration;
+
+// internal crates
+use crate::config::errors::ParseUnitErr;
+use crate::trace;
+
+// external crates
+use serde::{de, Deserializer, Serializer};
+
+pub const DURATION_FORMATS: &str =
+    "a number of seconds or a duration like \"30s\", \"5m\", \"2h\", \"1d\" or \"1h30m\" (units: ms, s, m, h, d)";
+
+pub const SIZE_FORMATS: &str =
+    "a number of bytes or a size like \"512B\", \"64KiB\", \"10MiB\", \"1GiB\" or \"10MB\" (units: B, KB, MB, GB, TB, KiB, MiB, GiB, TiB)";
+
+const DURATION_UNITS: [(&str, f64); 5] = [
+    ("ms", 0.001),
+    ("s", 1.0),
+    ("m", 60.0),
+    ("h", 60.0 * 60.0),
+    ("d", 24.0 * 60.0 * 60.0),
+];
+
+const SIZE_UNITS: [(&str, u64); 9] = [
+    ("b", 1),
+    ("kb", 1000),
+    ("mb", 1000 * 1000),
+    ("gb", 1000 * 1000 * 1000),
+    ("tb", 1000 * 1000 * 1000 * 1000),
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+];
+
+fn invalid(raw: &str, kind: &'static str, expected: &'static str) -> ParseUnitErr {
+    ParseUnitErr {
+        value: raw.to_string(),
+        kind,
+        expected,
+        trace: trace!(),
+    }
+}
+
+/// Splits a leading, possibly fractional, number from the rest of the string.
+fn split_number(s: &str) -> Option<(f64, &str)> {
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let number = s[..end].parse::<f64>().ok()?;
+    Some((number, s[end..].trim_start()))
+}
+
+/// Parses a plain number of seconds or a sequence of numbers with units, e.g. "1h30m".
+pub fn parse_duration(raw: &str) -> Result<Duration, ParseUnitErr> {
+    let err = || invalid(raw, "duration", DURATION_FORMATS);
+    let s = raw.trim().to_lowercase();
+    if s.is_empty() {
+        return Err(err());
+    }
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut rest = s.as_str();
+    let mut secs = 0.0;
+    while !rest.is_empty() {
+        let (number, after) = split_number(rest).ok_or_else(err)?;
+        let end = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+        let factor = DURATION_UNITS
+            .iter()
+            .find(|(unit, _)| *unit == &after[..end])
+            .map(|(_, factor)| *factor)
+            .ok_or_else(err)?;
+        secs += number * factor;
+        rest = after[end..].trim_start();
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| err())
+}
+
+/// Formats a duration with the largest units which represent it exactly, e.g. 5400
+/// seconds as "1h30m".
+pub fn format_duration(duration: Duration) -> String {
+    let mut secs = duration.as_secs();
+    let millis = duration.subsec_millis();
+    if secs == 0 && millis == 0 {
+        return "0s".to_string();
+    }
+
+    let mut out = String::new();
+    for (unit, factor) in [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)] {
+        if secs >= factor {
+            out.push_str(&format!("{}{unit}", secs / factor));
+            secs %= factor;
+        }
+    }
+    if millis > 0 {
+        out.push_str(&format!("{millis}ms"));
+    }
+    out
+}
+
+/// Parses a plain number of bytes or a number with a decimal or binary unit.
+pub fn parse_size(raw: &str) -> Result<u64, ParseUnitErr> {
+    let err = || invalid(raw, "size", SIZE_FORMATS);
+    let s = raw.trim().to_lowercase();
+    if let Ok(bytes) = s.parse::<u64>() {
+        return Ok(bytes);
+    }
+
+    let (number, unit) = split_number(&s).ok_or_else(err)?;
+    let factor = SIZE_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, factor)| *factor)
+        .ok_or_else(err)?;
+    let bytes = (number * factor as f64).round();
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(err());
+    }
+    Ok(bytes as u64)
+}
+
+/// Formats a size with the largest binary unit which represents it exactly.
+pub fn format_size(bytes: u64) -> String {
+    for (unit, factor) in [
+        ("TiB", 1u64 << 40),
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+    ] {
+        if bytes >= factor && bytes.is_multiple_of(factor) {
+            return format!("{}{unit}", bytes / factor);
+        }
+    }
+    format!("{bytes}B")
+}
+
+// ================================= SERDE ========================================= //
+struct SecsVisitor;
+
+impl de::Visitor<'_> for SecsVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(DURATION_FORMATS)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        Ok(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+        i64::try_from(v)
+            .map_err(|_| E::custom(invalid(&v.to_string(), "duration", DURATION_FORMATS)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+        let duration = parse_duration(v).map_err(E::custom)?;
+        if duration.subsec_nanos() != 0 {
+            return Err(E::custom(format!(
+                "invalid duration '{v}': must be a whole number of seconds"
+            )));
+        }
+        i64::try_from(duration.as_secs())
+            .map_err(|_| E::custom(invalid(v, "duration", DURATION_FORMATS)))
+    }
+}
+
+struct BytesVisitor;
+
+impl de::Visitor<'_> for BytesVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(SIZE_FORMATS)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<u64, E> {
+        u64::try_from(v).map_err(|_| E::custom(invalid(&v.to_string(), "size", SIZE_FORMATS)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u64, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<u64, E> {
+        parse_size(v).map_err(E::custom)
+    }
+}
+
+/// Durations stored as a number of seconds.
+pub mod secs {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(secs: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_duration(Duration::from_secs(*secs)))
+    }
+
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        let secs = deserializer.deserialize_any(SecsVisitor)?;
+        u64::try_from(secs).map(Some).map_err(|_| {
+            de::Error::custom(invalid(&secs.to_string(), "duration", DURATION_FORMATS))
+        })
+    }
+}
+
+/// Durations stored as a number of seconds which may be negative, e.g. to be
+/// clamped later.
+pub mod signed_secs {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(secs: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        match u64::try_from(*secs) {
+            Ok(secs) => serializer.serialize_str(&format_duration(Duration::from_secs(secs))),
+            Err(_) => serializer.serialize_i64(*secs),
+        }
+    }
+
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<i64>, D::Error> {
+        deserializer.deserialize_any(SecsVisitor).map(Some)
+    }
+}
+
+/// Sizes stored as a number of bytes.
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_size(*bytes))
+    }
+
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        deserializer.deserialize_any(BytesVisitor).map(Some)
+    }
+}
+
+/// Optional sizes stored as a number of bytes, where null means no limit.
+pub mod opt_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_str(&format_size(*bytes as u64)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    struct OptBytesVisitor;
+
+    impl<'de> de::Visitor<'de> for OptBytesVisitor {
+        type Value = Option<usize>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "null or {SIZE_FORMATS}")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            let bytes = deserializer.deserialize_any(BytesVisitor)?;
+            usize::try_from(bytes)
+                .map(Some)
+                .map_err(|_| de::Error::custom(invalid(&bytes.to_string(), "size", SIZE_FORMATS)))
+        }
+    }
+
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<usize>, D::Error> {
+        deserializer.deserialize_option(OptBytesVisitor)
+    }
+}
@@ -1,7 +1,10 @@
 // standard crates
 use std::cmp::min;
 
-#[derive(Debug, Clone, Copy)]
+// external crates
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Backoff {
     pub base_secs: i64,
     pub growth_factor: i64,
@@ -0,0 +1,208 @@
+// A digest identifies content by its hash. Each digest carries the algorithm which
+// produced it, formatted as `<algorithm>:<hex>`, so the algorithm used for new
+// digests can change (e.g. to BLAKE3 on CPUs without SHA extensions, or whatever the
+// backend moves to) without invalidating the digests already recorded. Bare hex is
+// parsed as SHA-256, which is what digests were before they named their algorithm.
+
+// standard crates
+use std::fmt;
+use std::str::FromStr;
+
+// internal crates
+use crate::crypt::errors::{CryptErr, InvalidDigestErr};
+use crate::trace;
+
+// external crates
+use openssl::sha::{sha256, Sha256};
+use serde::{Deserialize, Serialize};
+
+// ================================== ALGORITHM ==================================== //
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 2] = [Algorithm::Sha256, Algorithm::Blake3];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// The length of the algorithm's hashes in bytes.
+    pub fn hash_len(&self) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Blake3 => 32,
+        }
+    }
+
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Algorithm::Sha256 => sha256(data).to_vec(),
+            Algorithm::Blake3 => blake3::hash(data).as_bytes().to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = CryptErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Algorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                CryptErr::InvalidDigestErr(InvalidDigestErr {
+                    digest: s.to_string(),
+                    msg: format!(
+                        "unknown hash algorithm, expected one of {:?}",
+                        Algorithm::ALL
+                    ),
+                    trace: trace!(),
+                })
+            })
+    }
+}
+
+// =================================== DIGEST ====================================== //
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest {
+    algorithm: Algorithm,
+    /// Lowercase hex of the hash.
+    hex: String,
+}
+
+impl Digest {
+    pub fn compute(algorithm: Algorithm, data: &[u8]) -> Self {
+        let hex = algorithm
+            .hash(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Self { algorithm, hex }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn hex(&self) -> &str {
+        &self.hex
+    }
+
+    /// Whether the data hashes to this digest with the digest's algorithm.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        Self::compute(self.algorithm, data) == *self
+    }
+}
+
+// =================================== HASHER ====================================== //
+/// Computes a digest of data given a piece at a time, e.g. as it is downloaded.
+pub struct Hasher {
+    inner: HasherInner,
+}
+
+enum HasherInner {
+    Sha256(Box<Sha256>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        let inner = match algorithm {
+            Algorithm::Sha256 => HasherInner::Sha256(Box::new(Sha256::new())),
+            Algorithm::Blake3 => HasherInner::Blake3(Box::default()),
+        };
+        Self { inner }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        match self.inner {
+            HasherInner::Sha256(_) => Algorithm::Sha256,
+            HasherInner::Blake3(_) => Algorithm::Blake3,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.inner {
+            HasherInner::Sha256(hasher) => hasher.update(data),
+            HasherInner::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> Digest {
+        let algorithm = self.algorithm();
+        let hash = match self.inner {
+            HasherInner::Sha256(hasher) => hasher.finish().to_vec(),
+            HasherInner::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        };
+        Digest {
+            algorithm,
+            hex: hash.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.hex)
+    }
+}
+
+impl FromStr for Digest {
+    type Err = CryptErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, hex) = match s.split_once(':') {
+            Some((algorithm, hex)) => (algorithm.parse()?, hex),
+            None => (Algorithm::Sha256, s),
+        };
+        let expected_len = algorithm.hash_len() * 2;
+        if hex.len() != expected_len || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(CryptErr::InvalidDigestErr(InvalidDigestErr {
+                digest: s.to_string(),
+                msg: format!("a {algorithm} digest must be {expected_len} hex characters"),
+                trace: trace!(),
+            }));
+        }
+        Ok(Self {
+            algorithm,
+            hex: hex.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl Serialize for Digest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Digest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
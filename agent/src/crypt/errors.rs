@@ -20,6 +20,16 @@ pub struct InvalidJWTPayloadFormatErr {
 
 impl crate::errors::Error for InvalidJWTPayloadFormatErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid digest '{digest}': {msg}")]
+pub struct InvalidDigestErr {
+    pub digest: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidDigestErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Base64 decode error: {source}")]
 pub struct Base64DecodeErr {
@@ -74,6 +84,15 @@ pub struct GenerateRSAKeyPairErr {
 
 impl crate::errors::Error for GenerateRSAKeyPairErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("Generate key error: {source}")]
+pub struct GenerateKeyErr {
+    pub source: openssl::error::ErrorStack,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for GenerateKeyErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Read key error: {source}")]
 pub struct ReadKeyErr {
@@ -110,6 +129,16 @@ pub struct VerifyDataErr {
 
 impl crate::errors::Error for VerifyDataErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("External signer '{program}' error: {msg}")]
+pub struct ExternalSignerErr {
+    pub program: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ExternalSignerErr {}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CryptErr {
     #[error(transparent)]
@@ -119,6 +148,8 @@ pub enum CryptErr {
     #[error(transparent)]
     FileSysErr(filesys::FileSysErr),
     #[error(transparent)]
+    InvalidDigestErr(InvalidDigestErr),
+    #[error(transparent)]
     Base64DecodeErr(Base64DecodeErr),
     #[error(transparent)]
     ConvertBytesToStringErr(ConvertBytesToStringErr),
@@ -131,6 +162,8 @@ pub enum CryptErr {
     #[error(transparent)]
     GenerateRSAKeyPairErr(GenerateRSAKeyPairErr),
     #[error(transparent)]
+    GenerateKeyErr(GenerateKeyErr),
+    #[error(transparent)]
     ReadKeyErr(ReadKeyErr),
     #[error(transparent)]
     RSAToPKeyErr(RSAToPKeyErr),
@@ -138,6 +171,8 @@ pub enum CryptErr {
     SignDataErr(SignDataErr),
     #[error(transparent)]
     VerifyDataErr(VerifyDataErr),
+    #[error(transparent)]
+    ExternalSignerErr(ExternalSignerErr),
 }
 
 impl From<filesys::FileSysErr> for CryptErr {
@@ -150,14 +185,17 @@ crate::impl_error!(CryptErr {
     InvalidJWTErr,
     InvalidJWTPayloadErr,
     FileSysErr,
+    InvalidDigestErr,
     Base64DecodeErr,
     ConvertBytesToStringErr,
     ConvertPrivateKeyToPEMErr,
     ConvertPublicKeyToPEMErr,
     ConvertPublicKeyToDERErr,
     GenerateRSAKeyPairErr,
+    GenerateKeyErr,
     ReadKeyErr,
     RSAToPKeyErr,
     SignDataErr,
     VerifyDataErr,
+    ExternalSignerErr,
 });
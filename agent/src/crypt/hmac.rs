@@ -0,0 +1,42 @@
+// internal crates
+use crate::crypt::errors::{CryptErr, GenerateKeyErr, SignDataErr};
+use crate::trace;
+
+// external crates
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+
+/// Generate a random symmetric key of the given length in bytes.
+pub fn generate_key(len: usize) -> Result<Vec<u8>, CryptErr> {
+    let mut key = vec![0u8; len];
+    rand_bytes(&mut key).map_err(|e| {
+        CryptErr::GenerateKeyErr(GenerateKeyErr {
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    Ok(key)
+}
+
+/// Create an HMAC-SHA256 (RFC 2104) of the data.
+pub fn sign_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptErr> {
+    let sign_err = |e| {
+        CryptErr::SignDataErr(SignDataErr {
+            source: e,
+            trace: trace!(),
+        })
+    };
+    let key = PKey::hmac(key).map_err(sign_err)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).map_err(sign_err)?;
+    signer.update(data).map_err(sign_err)?;
+    signer.sign_to_vec().map_err(sign_err)
+}
+
+/// Verify an HMAC-SHA256 of the data. The signature is compared in constant time.
+pub fn verify_sha256(key: &[u8], data: &[u8], signature: &[u8]) -> Result<bool, CryptErr> {
+    let expected = sign_sha256(key, data)?;
+    Ok(expected.len() == signature.len() && memcmp::eq(&expected, signature))
+}
@@ -0,0 +1,220 @@
+// Where the device's private key is kept. The key signs the JWTs the backend exchanges
+// for tokens (see [crate::authn::issue]). It's usually the PEM file written when the
+// device is activated, but it can instead live in a TPM 2.0 or a PKCS#11 token (an
+// HSM, smart card or secure element) so it's never readable on disk. Hardware keys are
+// used through the standard command line tools, tpm2-tools' `tpm2_sign` and OpenSC's
+// `pkcs11-tool`, rather than linking their libraries into the agent. The public key
+// stays in the auth directory either way.
+
+// standard crates
+use std::process::Stdio;
+use std::time::Duration;
+
+// internal crates
+use crate::crypt::{errors::*, rsa};
+use crate::filesys::{self, PathExt, WriteOptions};
+use crate::trace;
+
+// external crates
+use openssl::sha::sha512;
+use secrecy::ExposeSecret;
+use tokio::process::Command;
+use zeroize::Zeroizing;
+
+/// The tpm2-tools executable which signs with a TPM key.
+pub const TPM2_SIGN: &str = "tpm2_sign";
+
+/// The OpenSC executable which signs with a PKCS#11 key.
+pub const PKCS11_TOOL: &str = "pkcs11-tool";
+
+/// The environment variable the PKCS#11 PIN is handed to `pkcs11-tool` in, so it
+/// doesn't show up in the process list.
+pub const PKCS11_PIN_ENV: &str = "MIRU_PKCS11_PIN";
+
+/// How long a signing tool may run before it's killed. A hung TPM or token would
+/// otherwise block token refreshes forever.
+pub const SIGN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivateKey {
+    /// A PEM file on disk.
+    File(filesys::File),
+    Tpm(TpmKey),
+    Pkcs11(Pkcs11Key),
+}
+
+/// A signing key persisted in a TPM 2.0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmKey {
+    /// The key's persistent handle, e.g. "0x81010001".
+    pub handle: String,
+    /// How the TPM is reached, e.g. "device:/dev/tpmrm0". The tools' default if unset.
+    pub tcti: Option<String>,
+    pub program: String,
+    pub timeout: Duration,
+}
+
+/// A signing key in a PKCS#11 token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pkcs11Key {
+    /// The token's PKCS#11 module, e.g. "/usr/lib/softhsm/libsofthsm2.so".
+    pub module: String,
+    /// The token's slot. The first slot with a token if unset.
+    pub slot: Option<u64>,
+    /// The label of the key in the token.
+    pub label: String,
+    /// A file holding the user PIN. The token isn't logged into if unset.
+    pub pin_file: Option<filesys::File>,
+    pub program: String,
+    pub timeout: Duration,
+}
+
+impl From<filesys::File> for PrivateKey {
+    fn from(file: filesys::File) -> Self {
+        Self::File(file)
+    }
+}
+
+impl PrivateKey {
+    /// The key's file if it's kept on disk.
+    pub fn file(&self) -> Option<&filesys::File> {
+        match self {
+            Self::File(file) => Some(file),
+            Self::Tpm(_) | Self::Pkcs11(_) => None,
+        }
+    }
+
+    /// Errors if the key is kept on disk and its file doesn't exist. Hardware keys are
+    /// only checked when they're used.
+    pub fn assert_exists(&self) -> Result<(), filesys::FileSysErr> {
+        if let Some(file) = self.file() {
+            file.assert_exists()?;
+        }
+        Ok(())
+    }
+
+    /// Create an RSASSA-PKCS1-v1_5 (RFC 7518 §3.3) signature using SHA-512.
+    pub async fn sign_rs512(&self, data: &[u8]) -> Result<Vec<u8>, CryptErr> {
+        match self {
+            Self::File(file) => rsa::sign_rs512(file, data).await,
+            Self::Tpm(key) => key.sign_rs512(data).await,
+            Self::Pkcs11(key) => key.sign_rs512(data).await,
+        }
+    }
+}
+
+impl TpmKey {
+    pub fn new(handle: String, tcti: Option<String>) -> Self {
+        Self {
+            handle,
+            tcti,
+            program: TPM2_SIGN.to_string(),
+            timeout: SIGN_TIMEOUT,
+        }
+    }
+
+    async fn sign_rs512(&self, data: &[u8]) -> Result<Vec<u8>, CryptErr> {
+        // the TPM only hashes small messages itself, so it's handed the digest
+        let dir = filesys::Dir::create_temp_dir("miru-tpm-sign").await?;
+        let result = async {
+            let digest_file = dir.file("digest.bin");
+            digest_file
+                .write_bytes(&sha512(data), WriteOptions::default())
+                .await?;
+            let signature_file = dir.file("signature.bin");
+
+            let mut cmd = Command::new(&self.program);
+            cmd.args(["-c", &self.handle])
+                .args(["-g", "sha512", "-s", "rsassa", "-d", "-f", "plain", "-o"])
+                .arg(signature_file.path())
+                .arg(digest_file.path());
+            if let Some(tcti) = &self.tcti {
+                cmd.env("TPM2TOOLS_TCTI", tcti);
+            }
+            run(cmd, &self.program, self.timeout).await?;
+            Ok(signature_file.read_bytes().await?)
+        }
+        .await;
+        let _ = dir.delete().await;
+        result
+    }
+}
+
+impl Pkcs11Key {
+    pub fn new(
+        module: String,
+        slot: Option<u64>,
+        label: String,
+        pin_file: Option<filesys::File>,
+    ) -> Self {
+        Self {
+            module,
+            slot,
+            label,
+            pin_file,
+            program: PKCS11_TOOL.to_string(),
+            timeout: SIGN_TIMEOUT,
+        }
+    }
+
+    async fn sign_rs512(&self, data: &[u8]) -> Result<Vec<u8>, CryptErr> {
+        let dir = filesys::Dir::create_temp_dir("miru-pkcs11-sign").await?;
+        let result = async {
+            let input_file = dir.file("input.bin");
+            input_file
+                .write_bytes(data, WriteOptions::default())
+                .await?;
+            let signature_file = dir.file("signature.bin");
+
+            let mut cmd = Command::new(&self.program);
+            cmd.args(["--module", &self.module]);
+            if let Some(slot) = self.slot {
+                cmd.args(["--slot", &slot.to_string()]);
+            }
+            if let Some(pin_file) = &self.pin_file {
+                let pin = pin_file.read_secret_bytes().await?;
+                let pin = Zeroizing::new(String::from_utf8_lossy(pin.expose_secret()).into_owned());
+                cmd.args(["--login", "--pin", &format!("env:{PKCS11_PIN_ENV}")])
+                    .env(PKCS11_PIN_ENV, pin.trim_end());
+            }
+            cmd.args(["--sign", "--mechanism", "SHA512-RSA-PKCS"])
+                .args(["--label", &self.label])
+                .arg("--input-file")
+                .arg(input_file.path())
+                .arg("--output-file")
+                .arg(signature_file.path());
+            run(cmd, &self.program, self.timeout).await?;
+            Ok(signature_file.read_bytes().await?)
+        }
+        .await;
+        let _ = dir.delete().await;
+        result
+    }
+}
+
+/// Runs a signing tool, killing it if it runs past the timeout.
+async fn run(mut cmd: Command, program: &str, timeout: Duration) -> Result<(), CryptErr> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let msg = match cmd.spawn() {
+        // the child is killed when the timed out wait drops it
+        Ok(child) => match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(Ok(output)) if output.status.success() => return Ok(()),
+            Ok(Ok(output)) => format!(
+                "exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("timed out after {timeout:?}"),
+        },
+        Err(e) => e.to_string(),
+    };
+    Err(CryptErr::ExternalSignerErr(ExternalSignerErr {
+        program: program.to_string(),
+        msg,
+        trace: trace!(),
+    }))
+}
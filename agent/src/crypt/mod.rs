@@ -1,6 +1,10 @@
 pub mod base64;
+pub mod digest;
 pub mod errors;
+pub mod hmac;
 pub mod jwt;
+pub mod keystore;
 pub mod rsa;
+pub mod salt;
 
 pub use self::errors::CryptErr;
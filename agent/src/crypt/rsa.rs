@@ -1,4 +1,5 @@
 // standard crates
+#[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
 // internal crates
@@ -13,6 +14,7 @@ use openssl::rsa::Rsa;
 use openssl::sha::sha256;
 use openssl::sign::{Signer, Verifier};
 use secrecy::ExposeSecret;
+use zeroize::Zeroizing;
 
 /// Maps an `openssl::error::ErrorStack` to a `CryptErr` variant. The variant name and
 /// inner struct name must match (e.g. `SignDataErr` maps to `CryptErr::SignDataErr(SignDataErr { .. })`).
@@ -47,7 +49,10 @@ pub async fn gen_key_pair(
     let rsa = ssl_err!(GenerateRSAKeyPairErr, Rsa::generate(num_bits))?;
 
     // Extract and write the private key
-    let private_key_pem = ssl_err!(ConvertPrivateKeyToPEMErr, rsa.private_key_to_pem())?;
+    let private_key_pem = Zeroizing::new(ssl_err!(
+        ConvertPrivateKeyToPEMErr,
+        rsa.private_key_to_pem()
+    )?);
     private_key_file
         .write_bytes(
             &private_key_pem,
@@ -59,8 +64,11 @@ pub async fn gen_key_pair(
         .await?;
     // 600 gives the owner read/write permissions. Permissions to the group and others
     // are not granted.
-    let permissions = std::fs::Permissions::from_mode(0o600);
-    private_key_file.set_permissions(permissions).await?;
+    #[cfg(unix)]
+    {
+        let permissions = std::fs::Permissions::from_mode(0o600);
+        private_key_file.set_permissions(permissions).await?;
+    }
 
     // Extract and write the public key
     let public_key_pem = ssl_err!(ConvertPublicKeyToPEMErr, rsa.public_key_to_pem())?;
@@ -75,8 +83,11 @@ pub async fn gen_key_pair(
         .await?;
     // 640 gives the owner read/write permissions, the group read permissions, and
     // nothing for other
-    let permissions = std::fs::Permissions::from_mode(0o640);
-    public_key_file.set_permissions(permissions).await?;
+    #[cfg(unix)]
+    {
+        let permissions = std::fs::Permissions::from_mode(0o640);
+        public_key_file.set_permissions(permissions).await?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,93 @@
+// A random salt generated once per device for anonymizing identifiers, such as the
+// device id and host name, in exported diagnostics. The same identifier always hashes
+// to the same value on a device so exports can still be correlated, but the hashes
+// can't be matched to the identifier without the salt, which never leaves the device.
+
+// standard crates
+use std::fmt;
+
+// internal crates
+use crate::crypt::{base64, errors::CryptErr, hmac};
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use tracing::warn;
+use zeroize::Zeroizing;
+
+/// The length of a salt in bytes.
+pub const SALT_LEN: usize = 32;
+
+/// The bytes of the HMAC kept in an anonymized identifier.
+const HASH_LEN: usize = 12;
+
+/// Prefixes anonymized identifiers so they can't be mistaken for real ones.
+pub const PREFIX: &str = "anon_";
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Salt {
+    bytes: Zeroizing<Vec<u8>>,
+}
+
+impl fmt::Debug for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Salt")
+            .field("bytes", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl Salt {
+    pub fn generate() -> Result<Self, CryptErr> {
+        Ok(Self {
+            bytes: Zeroizing::new(hmac::generate_key(SALT_LEN)?),
+        })
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes: Zeroizing::new(bytes),
+        }
+    }
+
+    /// Reads the device's salt, generating and persisting one if there is none yet.
+    /// A salt which can't be decoded is replaced, which changes every anonymized
+    /// identifier from then on.
+    pub async fn load_or_create(file: &filesys::File) -> Result<Self, CryptErr> {
+        if file.exists() {
+            let encoded = Zeroizing::new(file.read_string().await?);
+            match base64::decode_bytes_standard(encoded.trim()) {
+                Ok(bytes) if bytes.len() == SALT_LEN => return Ok(Self::from_bytes(bytes)),
+                Ok(_) => warn!("replacing the device salt in {file}: it has the wrong length"),
+                Err(e) => warn!("replacing the device salt in {file}: {e}"),
+            }
+        }
+        let salt = Self::generate()?;
+        salt.save(file).await?;
+        Ok(salt)
+    }
+
+    /// Writes the salt, readable only by the agent's user.
+    pub async fn save(&self, file: &filesys::File) -> Result<(), CryptErr> {
+        let encoded = Zeroizing::new(base64::encode_bytes_standard(&self.bytes));
+        file.write_string(&encoded, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The anonymized form of the identifier: a truncated HMAC-SHA256 of it keyed by
+    /// the salt, e.g. `anon_3f9a...`.
+    pub fn anonymize(&self, identifier: &str) -> Result<String, CryptErr> {
+        let hash = hmac::sign_sha256(&self.bytes, identifier.as_bytes())?;
+        let hex: String = hash[..HASH_LEN]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(format!("{PREFIX}{hex}"))
+    }
+}
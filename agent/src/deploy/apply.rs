@@ -1,5 +1,11 @@
+// standard crates
+use std::time::{Duration, Instant};
+
 // internal crates
-use crate::deploy::{errors::*, filesys as dpl_filesys, fsm};
+use crate::deploy::{
+    created_dirs, errors::*, filesys as dpl_filesys, fsm, hooks, order, pause, reboot, schedule,
+    schema, signature, space, trash, versions,
+};
 use crate::filesys;
 use crate::models;
 use crate::storage;
@@ -7,10 +13,36 @@ use crate::trace;
 
 // external crates
 use chrono::Utc;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
+#[derive(Default)]
 pub struct DeployOpts {
     pub retry_policy: fsm::RetryPolicy,
+    pub reboot: reboot::Options,
+    pub hooks: hooks::Options,
+    /// Skips applying deployments while the agent is in safe mode.
+    pub safe_mode: bool,
+    /// Removed files are moved into the trash rather than unlinked, if set.
+    pub trash: Option<trash::Trash>,
+    /// Config instances under the deployment directory's `current` link are deployed
+    /// as versioned releases, if set.
+    pub versions: Option<versions::Versions>,
+    /// The directories created for config instances written in place are recorded,
+    /// so offboarding can remove them, if set.
+    pub created_dirs: Option<created_dirs::CreatedDirs>,
+    /// Validates config instance content against its config schema before it's
+    /// written, failing deployments whose content doesn't match.
+    pub validate_schemas: bool,
+    /// Content isn't downloaded or deployed while a disk is nearly full, if set.
+    pub space: Option<space::Guard>,
+    /// Verifies the signature of the deployment's release before it's deployed, if
+    /// set.
+    pub signatures: Option<signature::Verifier>,
+    /// Deployments are only applied while a maintenance window is open. Content is
+    /// still downloaded outside them.
+    pub windows: schedule::Windows,
+    /// Deployments wait instead of transitioning while the switch is paused, if set.
+    pub pause: Option<pause::Switch>,
 }
 
 pub struct Args<'a> {
@@ -21,6 +53,7 @@ pub struct Args<'a> {
 pub struct Storage<'a> {
     pub deployments: &'a storage::Deployments,
     pub cfg_insts: storage::CfgInstRef<'a>,
+    pub releases: &'a storage::Releases,
 }
 
 pub struct Outcome {
@@ -33,6 +66,16 @@ pub struct Outcome {
 }
 
 pub async fn apply(args: &Args<'_>) -> Result<Vec<Outcome>, DeployErr> {
+    if args.opts.safe_mode {
+        warn!("agent is in safe mode, skipping applying deployments");
+        return Ok(Vec::new());
+    }
+    if let Some(switch) = &args.opts.pause {
+        if switch.is_paused().await {
+            return hold(args.storage.deployments).await;
+        }
+    }
+
     let mut categorized = read_deployments(args.storage.deployments).await?;
 
     categorized.remove = mark_removing(args.storage.deployments, categorized.remove).await?;
@@ -69,6 +112,29 @@ pub async fn apply(args: &Args<'_>) -> Result<Vec<Outcome>, DeployErr> {
     }
 }
 
+/// Leaves every deployment as it is while deployments are paused, reporting the ones
+/// which would otherwise have transitioned as waiting.
+async fn hold(storage: &storage::Deployments) -> Result<Vec<Outcome>, DeployErr> {
+    let held = storage
+        .find_where(|d| fsm::next_action_paused(d) != fsm::NextAction::None)
+        .await?;
+    if !held.is_empty() {
+        info!(
+            "Deployments are paused, holding {} deployments until they're resumed",
+            held.len()
+        );
+    }
+    Ok(held
+        .into_iter()
+        .map(|deployment| Outcome {
+            deployment,
+            wait: None,
+            error: None,
+            transitioned: false,
+        })
+        .collect())
+}
+
 async fn read_deployments(storage: &storage::Deployments) -> Result<Categorized, DeployErr> {
     let target_deployed = find_target_deployed(storage).await?;
     let tgt_dpl_id = target_deployed.as_ref().map(|d| d.id.clone());
@@ -189,7 +255,7 @@ async fn apply_all(
     dont_remove: &[filesys::File],
 ) -> Result<Vec<Outcome>, DeployErr> {
     let mut outcomes = Vec::new();
-    for deployment in deployments {
+    for deployment in order::for_removal(deployments) {
         let outcome = apply_one(args, deployment, dont_remove).await;
         outcomes.push(outcome);
     }
@@ -248,34 +314,322 @@ async fn deploy(
 ) -> Outcome {
     debug_assert_eq!(fsm::next_action(&deployment), fsm::NextAction::Deploy);
 
-    match dpl_filesys::deploy(&storage.cfg_insts, &deployment).await {
-        Ok(()) => {
-            let deployment = fsm::deploy(deployment);
+    match check_dependencies(storage.deployments, &deployment).await {
+        Ok(()) => {}
+        Err(DeployErr::UnmetDependencies(e)) => return wait_for_dependencies(opts, deployment, e),
+        Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+    }
+    if opts.validate_schemas {
+        if let Err(e) = validate_content(storage, &deployment).await {
+            return deploy_failed(storage, opts, deployment, e).await;
+        }
+    }
+    if let Some(verifier) = &opts.signatures {
+        if let Err(e) = verify_signature(storage, verifier, &deployment).await {
+            return deploy_failed(storage, opts, deployment, e).await;
+        }
+    }
+
+    if opts.reboot.is_enabled() {
+        match requires_reboot(storage, &opts.reboot, &deployment).await {
+            Ok(true) => return deploy_with_reboot(storage, opts, deployment).await,
+            Ok(false) => {}
+            Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+        }
+    }
+
+    let pre_hook = match run_hook(storage, opts, hooks::Stage::PreDeploy, &deployment).await {
+        Ok(duration) => duration,
+        Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+    };
+    let started_at = Instant::now();
+    if let Err(e) = dpl_filesys::deploy(
+        &storage.cfg_insts,
+        &deployment,
+        opts.versions.as_ref(),
+        opts.created_dirs.as_ref(),
+    )
+    .await
+    {
+        return deploy_failed(storage, opts, deployment, e).await;
+    }
+    let materialization = started_at.elapsed();
+    // the files are left in place if the post-deploy hook fails; they're rewritten
+    // when the deployment is retried
+    let post_hook = match run_hook(storage, opts, hooks::Stage::PostDeploy, &deployment).await {
+        Ok(duration) => duration,
+        Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+    };
+
+    let mut deployment = fsm::deploy(deployment);
+    deployment.metrics.materialization_duration_ms = Some(materialization.as_millis() as u64);
+    deployment.metrics.hook_duration_ms = hook_duration_ms(&[pre_hook, post_hook]);
+    let error = store_dpl(storage.deployments, &deployment).await.err();
+    Outcome {
+        deployment,
+        wait: None,
+        error,
+        transitioned: true,
+    }
+}
+
+async fn deploy_failed(
+    storage: &Storage<'_>,
+    opts: &DeployOpts,
+    deployment: models::Deployment,
+    e: DeployErr,
+) -> Outcome {
+    let deployment = match e {
+        // retrying cannot resolve a cycle or change the content so they're failed
+        // immediately
+        DeployErr::DependencyCycle(_)
+        | DeployErr::SchemaViolation(_)
+        | DeployErr::InvalidSignature(_) => fsm::fail(deployment),
+        _ => fsm::error(deployment, &opts.retry_policy, &e, true),
+    };
+    if let Err(write_e) = store_dpl(storage.deployments, &deployment).await {
+        error!(
+            "failed to update deployment {} after error: {write_e}",
+            deployment.id
+        );
+    }
+    let wait = remaining_cooldown(&deployment);
+    Outcome {
+        deployment,
+        wait,
+        error: Some(e),
+        transitioned: true,
+    }
+}
+
+/// A deployment whose dependencies haven't been deployed yet isn't failing, so it's
+/// left as it is, without counting an attempt or starting a cooldown, and checked
+/// again after the retry policy's base cooldown. Dependents are handled before their
+/// dependencies, so a dependency deployed in the same pass is only seen on the next.
+fn wait_for_dependencies(
+    opts: &DeployOpts,
+    deployment: models::Deployment,
+    e: UnmetDependenciesErr,
+) -> Outcome {
+    info!("'{}' is waiting for its dependencies: {e}", deployment.id);
+    let wait = chrono::TimeDelta::seconds(opts.retry_policy.backoff.base_secs);
+    Outcome {
+        deployment,
+        wait: Some(wait),
+        error: None,
+        transitioned: false,
+    }
+}
+
+async fn check_dependencies(
+    storage: &storage::Deployments,
+    deployment: &models::Deployment,
+) -> Result<(), DeployErr> {
+    if deployment.dependencies.deployments.is_empty() {
+        return Ok(());
+    }
+    let deployments = storage.values().await?;
+    order::check_dependencies(deployment, &deployments)
+}
+
+/// Validates the content of the deployment's config instances against their config
+/// schemas. Config instances whose schema isn't cached are deployed unvalidated, as
+/// is content in another format or which isn't JSON unless it's written to a `.json`
+/// file.
+async fn validate_content(
+    storage: &Storage<'_>,
+    deployment: &models::Deployment,
+) -> Result<(), DeployErr> {
+    let cfg_insts = read_cfg_insts(storage.cfg_insts.meta, &deployment.config_instance_ids).await?;
+    for cfg_inst in cfg_insts {
+        if cfg_inst.config_schema_id.is_empty()
+            || cfg_inst.content_format != models::config_instance::ContentFormat::Json
+        {
+            continue;
+        }
+        let Some(config_schema) = storage
+            .cfg_insts
+            .schemas
+            .read_optional(cfg_inst.config_schema_id.clone())
+            .await?
+        else {
+            debug!(
+                "config schema '{}' isn't cached, deploying config instance '{}' unvalidated",
+                cfg_inst.config_schema_id, cfg_inst.id
+            );
+            continue;
+        };
+        let content = storage.cfg_insts.content.read(cfg_inst.id.clone()).await?;
+        let violations: Vec<String> = match serde_json::from_str(&content) {
+            Ok(document) => schema::validate(&config_schema, &document)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            Err(e) if is_json_file(&cfg_inst.filepath) => vec![format!("/: invalid JSON: {e}")],
+            Err(_) => {
+                debug!(
+                    "config instance '{}' isn't JSON, deploying it unvalidated",
+                    cfg_inst.id
+                );
+                continue;
+            }
+        };
+        if !violations.is_empty() {
+            return Err(SchemaViolationErr {
+                cfg_inst_id: cfg_inst.id,
+                filepath: cfg_inst.filepath,
+                config_schema_id: cfg_inst.config_schema_id,
+                violations: violations
+                    .into_iter()
+                    .take(MAX_REPORTED_VIOLATIONS)
+                    .collect(),
+                trace: trace!(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the signature of the deployment's release over the content about to be
+/// deployed. A release which isn't cached is treated as unsigned.
+async fn verify_signature(
+    storage: &Storage<'_>,
+    verifier: &signature::Verifier,
+    deployment: &models::Deployment,
+) -> Result<(), DeployErr> {
+    let release = storage
+        .releases
+        .read_optional(deployment.release_id.clone())
+        .await?
+        .unwrap_or_else(|| models::Release {
+            id: deployment.release_id.clone(),
+            ..Default::default()
+        });
+    let cfg_insts = read_cfg_insts(storage.cfg_insts.meta, &deployment.config_instance_ids).await?;
+    let mut files = Vec::with_capacity(cfg_insts.len());
+    for cfg_inst in cfg_insts {
+        let content = storage.cfg_insts.content.read(cfg_inst.id.clone()).await?;
+        files.push((cfg_inst.filepath, content));
+    }
+    let files: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(filepath, content)| (filepath.as_str(), content.as_str()))
+        .collect();
+    verifier.verify(&release, &files)?;
+    Ok(())
+}
+
+fn is_json_file(filepath: &str) -> bool {
+    std::path::Path::new(filepath)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+async fn requires_reboot(
+    storage: &Storage<'_>,
+    options: &reboot::Options,
+    deployment: &models::Deployment,
+) -> Result<bool, DeployErr> {
+    let cfg_insts = read_cfg_insts(storage.cfg_insts.meta, &deployment.config_instance_ids).await?;
+    Ok(cfg_insts
+        .iter()
+        .any(|cfg_inst| options.requires_reboot(&cfg_inst.filepath)))
+}
+
+/// Deploys a deployment whose config instances only take effect after the host
+/// reboots. The config instances are written and a pending finalize record is
+/// persisted before a reboot is requested. The deployment is marked deployed on the
+/// first apply after the host reboots.
+async fn deploy_with_reboot(
+    storage: &Storage<'_>,
+    opts: &DeployOpts,
+    mut deployment: models::Deployment,
+) -> Outcome {
+    let boot_id = match reboot::boot_id(&opts.reboot).await {
+        Ok(boot_id) => boot_id,
+        Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+    };
+
+    match &deployment.pending_finalize {
+        Some(pending) if reboot::has_rebooted(pending, &boot_id) => {
+            info!("host rebooted, finalizing deployment '{}'", deployment.id);
+            let post_hook =
+                match run_hook(storage, opts, hooks::Stage::PostDeploy, &deployment).await {
+                    Ok(duration) => duration,
+                    Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+                };
+            let mut deployment = fsm::deploy(deployment);
+            if let Some(ms) = hook_duration_ms(&[post_hook]) {
+                let pre_ms = deployment.metrics.hook_duration_ms.unwrap_or_default();
+                deployment.metrics.hook_duration_ms = Some(pre_ms + ms);
+            }
             let error = store_dpl(storage.deployments, &deployment).await.err();
-            Outcome {
+            return Outcome {
                 deployment,
                 wait: None,
                 error,
                 transitioned: true,
-            }
+            };
         }
-        Err(e) => {
-            let deployment = fsm::error(deployment, &opts.retry_policy, &e, true);
-            if let Err(write_e) = store_dpl(storage.deployments, &deployment).await {
-                error!(
-                    "failed to update deployment {} after error: {write_e}",
-                    deployment.id
-                );
+        Some(_) => {}
+        None => {
+            let pre_hook = match run_hook(storage, opts, hooks::Stage::PreDeploy, &deployment).await
+            {
+                Ok(duration) => duration,
+                Err(e) => return deploy_failed(storage, opts, deployment, e).await,
+            };
+            let started_at = Instant::now();
+            if let Err(e) = dpl_filesys::deploy(
+                &storage.cfg_insts,
+                &deployment,
+                opts.versions.as_ref(),
+                opts.created_dirs.as_ref(),
+            )
+            .await
+            {
+                return deploy_failed(storage, opts, deployment, e).await;
             }
-            let wait = remaining_cooldown(&deployment);
-            Outcome {
-                deployment,
-                wait,
-                error: Some(e),
-                transitioned: true,
+            deployment.metrics.materialization_duration_ms =
+                Some(started_at.elapsed().as_millis() as u64);
+            deployment.metrics.hook_duration_ms = hook_duration_ms(&[pre_hook]);
+            deployment.pending_finalize = Some(models::PendingFinalize {
+                boot_id,
+                staged_at: Utc::now(),
+                reboot_requested_at: None,
+            });
+            info!(
+                "staged deployment '{}' pending a host reboot",
+                deployment.id
+            );
+
+            // persist the record before rebooting so the deployment is finalized
+            // on the next boot
+            if let Err(e) = store_dpl(storage.deployments, &deployment).await {
+                return Outcome {
+                    deployment,
+                    wait: None,
+                    error: Some(e),
+                    transitioned: false,
+                };
             }
         }
     }
+
+    let (wait, error) = match reboot::request(&opts.reboot, &mut deployment, Utc::now()).await {
+        Ok(wait) => (wait, None),
+        Err(e) => (opts.reboot.recheck_interval, Some(e)),
+    };
+    let error = match store_dpl(storage.deployments, &deployment).await {
+        Ok(()) => error,
+        Err(e) => error.or(Some(e)),
+    };
+    Outcome {
+        deployment,
+        wait: Some(wait),
+        error,
+        transitioned: false,
+    }
 }
 
 fn remaining_cooldown(deployment: &models::Deployment) -> Option<chrono::TimeDelta> {
@@ -326,7 +680,7 @@ async fn remove(
 ) -> Outcome {
     debug_assert_eq!(fsm::next_action(&deployment), fsm::NextAction::Remove);
 
-    match dpl_filesys::remove(&storage.cfg_insts, &deployment, ignored).await {
+    match remove_files(storage, opts, &deployment, ignored).await {
         Ok(()) => {
             let deployment = fsm::archive(deployment);
             let error = store_dpl(storage.deployments, &deployment).await.err();
@@ -356,6 +710,18 @@ async fn remove(
     }
 }
 
+async fn remove_files(
+    storage: &Storage<'_>,
+    opts: &DeployOpts,
+    deployment: &models::Deployment,
+    ignored: &[filesys::File],
+) -> Result<(), DeployErr> {
+    run_hook(storage, opts, hooks::Stage::PreRemove, deployment).await?;
+    dpl_filesys::remove(&storage.cfg_insts, deployment, ignored, opts.trash.as_ref()).await?;
+    run_hook(storage, opts, hooks::Stage::PostRemove, deployment).await?;
+    Ok(())
+}
+
 // ================================= ARCHIVE ======================================= //
 
 async fn archive(deployments: &storage::Deployments, deployment: models::Deployment) -> Outcome {
@@ -373,6 +739,36 @@ async fn archive(deployments: &storage::Deployments, deployment: models::Deploym
 
 // ================================= HELPERS ======================================= //
 
+/// Runs the stage's hook for the deployment, returning how long it ran or `None` if
+/// the stage has no hook.
+async fn run_hook(
+    storage: &Storage<'_>,
+    opts: &DeployOpts,
+    stage: hooks::Stage,
+    deployment: &models::Deployment,
+) -> Result<Option<Duration>, DeployErr> {
+    if !opts.hooks.is_enabled(stage) {
+        return Ok(None);
+    }
+    let filepaths: Vec<String> =
+        read_cfg_insts(storage.cfg_insts.meta, &deployment.config_instance_ids)
+            .await?
+            .into_iter()
+            .map(|cfg_inst| cfg_inst.filepath)
+            .collect();
+    let output = hooks::run(&opts.hooks, stage, deployment, &filepaths).await?;
+    Ok(output.map(|output| output.duration))
+}
+
+fn hook_duration_ms(durations: &[Option<Duration>]) -> Option<u64> {
+    durations
+        .iter()
+        .flatten()
+        .copied()
+        .reduce(|total, duration| total + duration)
+        .map(|total| total.as_millis() as u64)
+}
+
 async fn store_dpl(
     storage: &storage::Deployments,
     deployment: &models::Deployment,
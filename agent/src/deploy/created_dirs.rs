@@ -0,0 +1,84 @@
+// The directories the agent creates to write config files in place are recorded in a
+// file under the data directory. Offboarding (see [crate::provisioning::deprovision])
+// only removes recorded directories once the deployed files are gone, so a directory
+// which held config files before the agent ran, such as `/etc/foo`, is never removed
+// even if deprovisioning leaves it empty. Recording is best-effort: a directory which
+// couldn't be recorded is left in place on offboarding.
+
+// standard crates
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+// internal crates
+use crate::filesys::{self, errors::FileSysErr, PathExt, WriteOptions};
+
+// external crates
+use tracing::warn;
+
+#[derive(Clone, Debug)]
+pub struct CreatedDirs {
+    file: filesys::File,
+}
+
+impl CreatedDirs {
+    pub fn new(file: filesys::File) -> Self {
+        Self { file }
+    }
+
+    pub fn file(&self) -> &filesys::File {
+        &self.file
+    }
+
+    /// The recorded directories, deepest first so that each one is removed before its
+    /// parent.
+    pub async fn read(&self) -> Vec<PathBuf> {
+        if !self.file.exists() {
+            return Vec::new();
+        }
+        let dirs = self
+            .file
+            .read_json::<BTreeSet<PathBuf>>()
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "unable to read the directories recorded in {}: {e}",
+                    self.file
+                );
+                BTreeSet::new()
+            });
+        let mut dirs: Vec<PathBuf> = dirs.into_iter().collect();
+        dirs.sort_by(|a, b| {
+            b.components()
+                .count()
+                .cmp(&a.components().count())
+                .then_with(|| a.cmp(b))
+        });
+        dirs
+    }
+
+    /// Adds the directories to those already recorded.
+    pub async fn record(&self, dirs: &[PathBuf]) -> Result<(), FileSysErr> {
+        if dirs.is_empty() {
+            return Ok(());
+        }
+        let mut recorded: BTreeSet<PathBuf> = self.read().await.into_iter().collect();
+        let len = recorded.len();
+        recorded.extend(dirs.iter().cloned());
+        if recorded.len() == len {
+            return Ok(());
+        }
+        self.file
+            .write_json(&recorded, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+    }
+}
+
+/// The directories writing the file creates: its ancestors which don't exist yet,
+/// deepest first.
+pub fn missing_ancestors(path: &Path) -> Vec<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect()
+}
@@ -0,0 +1,182 @@
+// Detects deployed config files which no longer hold the content they were deployed
+// with, e.g. because an operator edited one by hand or another process overwrote it.
+// Each file's hash is compared to the hash of its config instance's cached content,
+// rendered as it's written (see [format]). A deployment with a drifted file is marked
+// `Drifted`, which the FSM redeploys while it's still targeting deployed, so the
+// next apply rewrites its files.
+//
+// Files under the deployment directory's `current` link are skipped while a release
+// other than the newest is live since an operator rolled back to it on purpose.
+
+// standard crates
+use std::path::Path;
+
+// internal crates
+use crate::crypt::digest::{Algorithm, Digest};
+use crate::deploy::{apply::Storage, errors::DeployErr, format, fsm, versions};
+use crate::filesys::{self, PathExt};
+use crate::models;
+use crate::storage;
+
+// external crates
+use tracing::{debug, info, warn};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Drift {
+    pub deployment_id: String,
+    pub cfg_inst_id: String,
+    pub filepath: String,
+    /// The lowercase hex SHA-256 of the content the file was deployed with.
+    pub expected: String,
+    /// The lowercase hex SHA-256 of the file's content, if the file still exists.
+    pub actual: Option<String>,
+}
+
+/// The drifted files of the deployments which are deployed and targeting deployed.
+/// Files which can't be checked, e.g. because their content is no longer cached, are
+/// logged and skipped rather than failing the rest.
+pub async fn detect(
+    storage: &Storage<'_>,
+    versions: Option<&versions::Versions>,
+) -> Result<Vec<Drift>, DeployErr> {
+    let deployments = storage
+        .deployments
+        .find_where(|d| {
+            d.activity_status == models::DplActivity::Deployed
+                && d.target_status == models::DplTarget::Deployed
+        })
+        .await?;
+    let rolled_back = match versions {
+        Some(versions) => versions.is_rolled_back().await?,
+        None => false,
+    };
+
+    let mut drifts = Vec::new();
+    for deployment in deployments {
+        for cfg_inst_id in &deployment.config_instance_ids {
+            let cfg_inst = match storage.cfg_insts.meta.read(cfg_inst_id.clone()).await {
+                Ok(cfg_inst) => cfg_inst,
+                Err(e) => {
+                    warn!(
+                        "unable to read config instance '{cfg_inst_id}' to check it for drift: {e}"
+                    );
+                    continue;
+                }
+            };
+            let versioned = versions
+                .and_then(|v| v.relative_path(Path::new(&cfg_inst.filepath)))
+                .is_some();
+            if versioned && rolled_back {
+                debug!(
+                    "skipping drift check of '{}' since its release was rolled back",
+                    cfg_inst.filepath
+                );
+                continue;
+            }
+            if let Some(drift) = check(storage, &deployment, &cfg_inst).await {
+                drifts.push(drift);
+            }
+        }
+    }
+    Ok(drifts)
+}
+
+async fn check(
+    storage: &Storage<'_>,
+    deployment: &models::Deployment,
+    cfg_inst: &models::ConfigInstance,
+) -> Option<Drift> {
+    let content = match storage.cfg_insts.content.read(cfg_inst.id.clone()).await {
+        Ok(content) => content,
+        Err(e) => {
+            warn!(
+                "unable to read the content of config instance '{}' to check it for drift: {e}",
+                cfg_inst.id
+            );
+            return None;
+        }
+    };
+    let expected = match format::render(cfg_inst, &content) {
+        Ok(expected) => Digest::compute(Algorithm::Sha256, &expected),
+        Err(e) => {
+            warn!(
+                "unable to render config instance '{}' to check it for drift: {e}",
+                cfg_inst.id
+            );
+            return None;
+        }
+    };
+
+    let file = filesys::File::new(&cfg_inst.filepath);
+    let actual = match file.exists() {
+        true => match file.read_bytes().await {
+            Ok(bytes) => Some(Digest::compute(Algorithm::Sha256, &bytes)),
+            Err(e) => {
+                warn!(
+                    "unable to read '{}' to check it for drift: {e}",
+                    cfg_inst.filepath
+                );
+                return None;
+            }
+        },
+        false => None,
+    };
+    if actual.as_ref() == Some(&expected) {
+        return None;
+    }
+    Some(Drift {
+        deployment_id: deployment.id.clone(),
+        cfg_inst_id: cfg_inst.id.clone(),
+        filepath: cfg_inst.filepath.clone(),
+        expected: expected.hex().to_string(),
+        actual: actual.map(|digest| digest.hex().to_string()),
+    })
+}
+
+/// Marks the deployments with drifted files as `Drifted`, returning the ids of those
+/// marked. Deployments which have left the deployed state since their files were
+/// checked are left as they are.
+pub async fn mark(
+    deployments: &storage::Deployments,
+    drifts: &[Drift],
+) -> Result<Vec<String>, DeployErr> {
+    let mut marked: Vec<String> = Vec::new();
+    for drift in drifts {
+        if marked.contains(&drift.deployment_id) {
+            continue;
+        }
+        let deployment = match deployments
+            .read_optional(drift.deployment_id.clone())
+            .await?
+        {
+            Some(deployment) => deployment,
+            None => continue,
+        };
+        if deployment.activity_status != models::DplActivity::Deployed
+            || deployment.target_status != models::DplTarget::Deployed
+        {
+            continue;
+        }
+        match &drift.actual {
+            Some(_) => info!(
+                "'{}' of deployment '{}' has drifted from its deployed content",
+                drift.filepath, drift.deployment_id
+            ),
+            None => info!(
+                "'{}' of deployment '{}' has been removed",
+                drift.filepath, drift.deployment_id
+            ),
+        }
+        let deployment = fsm::drift(deployment);
+        deployments
+            .write(
+                deployment.id.clone(),
+                deployment,
+                storage::deployments::is_dirty,
+                filesys::Overwrite::Allow,
+            )
+            .await?;
+        marked.push(drift.deployment_id.clone());
+    }
+    Ok(marked)
+}
@@ -1,5 +1,6 @@
 // internal crates
 use crate::cache;
+use crate::deploy::hooks;
 use crate::errors::Trace;
 use crate::filesys;
 use crate::models;
@@ -45,6 +46,31 @@ pub struct ConflictingDeploymentsErr {
 
 impl crate::errors::Error for ConflictingDeploymentsErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("dependency cycle between {kind}: [{}]", ids.join(" -> "))]
+pub struct DependencyCycleErr {
+    /// Either "deployments" or "config types".
+    pub kind: &'static str,
+    pub ids: Vec<String>,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for DependencyCycleErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::DependencyCycle
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("deployment '{deployment_id}' depends on deployments which have not been deployed: [{}]", ids.join(", "))]
+pub struct UnmetDependenciesErr {
+    pub deployment_id: String,
+    pub ids: Vec<String>,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for UnmetDependenciesErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("internal server error: {msg}")]
 pub struct GenericErr {
@@ -91,6 +117,169 @@ pub struct WriteAccessDeniedErr {
 
 impl crate::errors::Error for WriteAccessDeniedErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("reboot command '{command}' failed: {msg}")]
+pub struct RebootCommandErr {
+    pub command: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for RebootCommandErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "{stage} hook '{command}' for deployment '{deployment_id}' failed: {msg}{}",
+    stderr_suffix(stderr)
+)]
+pub struct HookErr {
+    pub deployment_id: String,
+    pub stage: hooks::Stage,
+    pub command: String,
+    pub msg: String,
+    /// The end of the hook's stderr.
+    pub stderr: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for HookErr {}
+
+fn stderr_suffix(stderr: &str) -> String {
+    match stderr.is_empty() {
+        true => String::new(),
+        false => format!(" (stderr: {stderr})"),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("config instance '{cfg_inst_id}' for '{filepath}' doesn't match config schema '{config_schema_id}': {}", violations.join("; "))]
+pub struct SchemaViolationErr {
+    pub cfg_inst_id: String,
+    pub filepath: String,
+    pub config_schema_id: String,
+    /// The first of the violations, see [MAX_REPORTED_VIOLATIONS].
+    pub violations: Vec<String>,
+    pub trace: Box<Trace>,
+}
+
+/// The most schema violations reported in a [SchemaViolationErr].
+pub const MAX_REPORTED_VIOLATIONS: usize = 5;
+
+impl crate::errors::Error for SchemaViolationErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::SchemaViolation
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("release '{release_id}' failed signature verification: {msg}")]
+pub struct InvalidSignatureErr {
+    pub release_id: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidSignatureErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::InvalidSignature
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unable to write config instance '{cfg_inst_id}' to '{filepath}' as {format}: {msg}")]
+pub struct ContentConversionErr {
+    pub cfg_inst_id: String,
+    pub filepath: String,
+    pub format: models::config_instance::ContentFormat,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ContentConversionErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("the trash has no entry '{id}'")]
+pub struct TrashEntryNotFoundErr {
+    pub id: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for TrashEntryNotFoundErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("can't restore trash entry '{id}' since a file already exists at '{filepath}'")]
+pub struct RestoreConflictErr {
+    pub id: String,
+    pub filepath: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for RestoreConflictErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("deployment directory '{dir}' has no release {number}")]
+pub struct ReleaseNotFoundErr {
+    pub dir: String,
+    pub number: u64,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ReleaseNotFoundErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::ResourceNotFound
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::NOT_FOUND
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("deployment directory '{dir}' has no earlier release to roll back to")]
+pub struct NoRollbackTargetErr {
+    pub dir: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for NoRollbackTargetErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::ResourceConflict
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::CONFLICT
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("only {available_bytes} bytes are free on '{mount_point}' (at least {min_free_bytes} are required)")]
+pub struct InsufficientDiskSpaceErr {
+    pub mount_point: String,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+    pub min_free_bytes: u64,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InsufficientDiskSpaceErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::InsufficientDiskSpace
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::INSUFFICIENT_STORAGE
+    }
+
+    fn params(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "mount_point": self.mount_point,
+            "available_bytes": self.available_bytes,
+            "total_bytes": self.total_bytes,
+            "min_free_bytes": self.min_free_bytes,
+        }))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DeployErr {
     #[error(transparent)]
@@ -98,20 +287,44 @@ pub enum DeployErr {
     #[error(transparent)]
     ConflictingDeployments(ConflictingDeploymentsErr),
     #[error(transparent)]
+    ContentConversion(ContentConversionErr),
+    #[error(transparent)]
+    DependencyCycle(DependencyCycleErr),
+    #[error(transparent)]
     DuplicateFilepath(DuplicateFilepathErr),
     #[error(transparent)]
     EmptyConfigInstances(EmptyConfigInstancesErr),
     #[error(transparent)]
+    Hook(HookErr),
+    #[error(transparent)]
+    InsufficientDiskSpace(InsufficientDiskSpaceErr),
+    #[error(transparent)]
     InvalidDeploymentTarget(InvalidDeploymentTargetErr),
     #[error(transparent)]
+    InvalidSignature(InvalidSignatureErr),
+    #[error(transparent)]
     CacheErr(cache::CacheErr),
     #[error(transparent)]
     FileSysErr(filesys::FileSysErr),
     #[error(transparent)]
+    NoRollbackTarget(NoRollbackTargetErr),
+    #[error(transparent)]
     PathNotAllowed(PathNotAllowedErr),
     #[error(transparent)]
+    RebootCommand(RebootCommandErr),
+    #[error(transparent)]
+    ReleaseNotFound(ReleaseNotFoundErr),
+    #[error(transparent)]
+    RestoreConflict(RestoreConflictErr),
+    #[error(transparent)]
+    SchemaViolation(SchemaViolationErr),
+    #[error(transparent)]
     StorageErr(StorageErr),
     #[error(transparent)]
+    TrashEntryNotFound(TrashEntryNotFoundErr),
+    #[error(transparent)]
+    UnmetDependencies(UnmetDependenciesErr),
+    #[error(transparent)]
     WriteAccessDenied(WriteAccessDeniedErr),
     #[error(transparent)]
     GenericErr(GenericErr),
@@ -147,12 +360,30 @@ impl From<InvalidDeploymentTargetErr> for DeployErr {
     }
 }
 
+impl From<InsufficientDiskSpaceErr> for DeployErr {
+    fn from(e: InsufficientDiskSpaceErr) -> Self {
+        Self::InsufficientDiskSpace(e)
+    }
+}
+
 impl From<ConflictingDeploymentsErr> for DeployErr {
     fn from(e: ConflictingDeploymentsErr) -> Self {
         Self::ConflictingDeployments(e)
     }
 }
 
+impl From<DependencyCycleErr> for DeployErr {
+    fn from(e: DependencyCycleErr) -> Self {
+        Self::DependencyCycle(e)
+    }
+}
+
+impl From<UnmetDependenciesErr> for DeployErr {
+    fn from(e: UnmetDependenciesErr) -> Self {
+        Self::UnmetDependencies(e)
+    }
+}
+
 impl From<PathNotAllowedErr> for DeployErr {
     fn from(e: PathNotAllowedErr) -> Self {
         Self::PathNotAllowed(e)
@@ -177,6 +408,48 @@ impl From<BackupAccessDeniedErr> for DeployErr {
     }
 }
 
+impl From<HookErr> for DeployErr {
+    fn from(e: HookErr) -> Self {
+        Self::Hook(e)
+    }
+}
+
+impl From<SchemaViolationErr> for DeployErr {
+    fn from(e: SchemaViolationErr) -> Self {
+        Self::SchemaViolation(e)
+    }
+}
+
+impl From<InvalidSignatureErr> for DeployErr {
+    fn from(e: InvalidSignatureErr) -> Self {
+        Self::InvalidSignature(e)
+    }
+}
+
+impl From<RebootCommandErr> for DeployErr {
+    fn from(e: RebootCommandErr) -> Self {
+        Self::RebootCommand(e)
+    }
+}
+
+impl From<ReleaseNotFoundErr> for DeployErr {
+    fn from(e: ReleaseNotFoundErr) -> Self {
+        Self::ReleaseNotFound(e)
+    }
+}
+
+impl From<NoRollbackTargetErr> for DeployErr {
+    fn from(e: NoRollbackTargetErr) -> Self {
+        Self::NoRollbackTarget(e)
+    }
+}
+
+impl From<ContentConversionErr> for DeployErr {
+    fn from(e: ContentConversionErr) -> Self {
+        Self::ContentConversion(e)
+    }
+}
+
 impl From<WriteAccessDeniedErr> for DeployErr {
     fn from(e: WriteAccessDeniedErr) -> Self {
         Self::WriteAccessDenied(e)
@@ -186,13 +459,25 @@ impl From<WriteAccessDeniedErr> for DeployErr {
 crate::impl_error!(DeployErr {
     BackupAccessDenied,
     ConflictingDeployments,
+    ContentConversion,
+    DependencyCycle,
     DuplicateFilepath,
     EmptyConfigInstances,
+    Hook,
+    InsufficientDiskSpace,
     InvalidDeploymentTarget,
+    InvalidSignature,
     CacheErr,
     FileSysErr,
+    NoRollbackTarget,
     PathNotAllowed,
+    RebootCommand,
+    ReleaseNotFound,
+    RestoreConflict,
+    SchemaViolation,
     StorageErr,
+    TrashEntryNotFound,
+    UnmetDependencies,
     WriteAccessDenied,
     GenericErr,
 });
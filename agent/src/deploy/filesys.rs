@@ -1,32 +1,74 @@
 // standard crates
 use std::collections::HashMap;
-use std::path::Component;
+use std::path::{Component, Path, PathBuf};
 
 // internal crates
-use crate::deploy::errors::*;
+use crate::deploy::{
+    created_dirs::{self, CreatedDirs},
+    errors::*,
+    format, order, trash, versions,
+};
 use crate::filesys::{self, errors::FileSysErr, PathExt, WriteOptions};
 use crate::models;
 use crate::storage;
 use crate::trace;
 
 // external crates
-use tracing::{error, info, warn};
+use chrono::Utc;
+use tracing::{debug, error, info, warn};
 
 pub const BACKUP_FILE_PREFIX: &str = "miru.backup";
 
 /// Reads the deployment's config instances and writes them to their filesystem
-/// destinations using a snapshot+atomic-rename loop with rollback on partial failure.
+/// destinations, all or nothing. Every config instance's content is read before any
+/// file is touched, and rendered into the format of its file (see [format]). Config
+/// instances under the deployment directory's `current` link are staged into a new
+/// release, and the rest written with a snapshot+atomic-rename loop that rolls back on
+/// partial failure. Config instances are written, and the release made live, in the
+/// order of their config types' dependencies. The directories created for files
+/// written in place are recorded in `created_dirs`, if set.
 pub async fn deploy(
     storage: &storage::CfgInstRef<'_>,
     deployment: &models::Deployment,
+    versions: Option<&versions::Versions>,
+    created_dirs: Option<&CreatedDirs>,
 ) -> Result<(), DeployErr> {
     validate_deploy_target(deployment)?;
     validate_has_cfg_insts(deployment)?;
 
     let cfg_insts = read_cfg_insts(storage.meta, &deployment.config_instance_ids).await?;
     validate_cfg_insts(&cfg_insts)?;
+    if let Some(versions) = versions {
+        validate_versioned(&cfg_insts, versions)?;
+    }
+    let cfg_insts = order::by_config_type(cfg_insts, &deployment.dependencies.config_types)?;
+
+    let mut rendered = Vec::with_capacity(cfg_insts.len());
+    for cfg_inst in cfg_insts {
+        let content = storage.content.read(cfg_inst.id.clone()).await?;
+        let content = format::render(&cfg_inst, &content)?;
+        let release_path = versions.and_then(|v| v.relative_path(Path::new(&cfg_inst.filepath)));
+        rendered.push(Rendered {
+            cfg_inst,
+            release_path,
+            content,
+        });
+    }
 
-    write_cfg_insts(&cfg_insts, storage.content).await
+    match versions {
+        Some(versions) if rendered.iter().any(|r| r.release_path.is_some()) => {
+            deploy_release(versions, &deployment.id, &rendered, created_dirs).await
+        }
+        _ => write_cfg_insts(&rendered, created_dirs).await,
+    }
+}
+
+/// A config instance's content rendered for its file, with the file's path relative
+/// to a release if it's deployed through the deployment directory's `current` link.
+struct Rendered {
+    cfg_inst: models::ConfigInstance,
+    release_path: Option<PathBuf>,
+    content: Vec<u8>,
 }
 
 fn validate_has_cfg_insts(deployment: &models::Deployment) -> Result<(), DeployErr> {
@@ -83,6 +125,26 @@ fn validate_cfg_insts(cfg_insts: &[models::ConfigInstance]) -> Result<(), Deploy
     Ok(())
 }
 
+fn validate_versioned(
+    cfg_insts: &[models::ConfigInstance],
+    versions: &versions::Versions,
+) -> Result<(), DeployErr> {
+    for cfg_inst in cfg_insts {
+        let file = filesys::File::new(&cfg_inst.filepath);
+        if versions.is_reserved(file.path()) {
+            return Err(DeployErr::PathNotAllowed(PathNotAllowedErr {
+                filepath: file.path().display().to_string(),
+                reason: format!(
+                    "filepath is in the deployment directory but not under its '{}' link",
+                    versions::CURRENT_LINK
+                ),
+                trace: trace!(),
+            }));
+        }
+    }
+    Ok(())
+}
+
 fn validate_filepath(file: &filesys::File) -> Result<(), DeployErr> {
     if !file.is_absolute() {
         return Err(DeployErr::PathNotAllowed(PathNotAllowedErr {
@@ -108,14 +170,109 @@ fn validate_filepath(file: &filesys::File) -> Result<(), DeployErr> {
 }
 
 async fn write_cfg_insts(
-    cfg_insts: &[models::ConfigInstance],
-    content_stor: &storage::CfgInstContent,
+    rendered: &[Rendered],
+    created_dirs: Option<&CreatedDirs>,
 ) -> Result<(), DeployErr> {
-    let mut snapshots: Vec<Snapshot> = Vec::with_capacity(cfg_insts.len());
-    if let Err(e) = write_cfg_insts_impl(&mut snapshots, cfg_insts, content_stor).await {
+    let mut snapshots: Vec<Snapshot> = Vec::with_capacity(rendered.len());
+    if let Err(e) = write_cfg_insts_impl(&mut snapshots, rendered).await {
         rollback(&snapshots).await;
         return Err(e);
     }
+    remove_backups(&snapshots).await;
+    record_created_dirs(created_dirs, &snapshots).await;
+    Ok(())
+}
+
+/// Stages the versioned config instances into a new release, then writes the rest in
+/// place and switches the deployment directory to the new release in a single pass in
+/// config type dependency order. The previous release is made live again, and the
+/// files written in place are rolled back, if any step fails.
+async fn deploy_release(
+    versions: &versions::Versions,
+    deployment_id: &str,
+    rendered: &[Rendered],
+    created_dirs: Option<&CreatedDirs>,
+) -> Result<(), DeployErr> {
+    let previous = versions.current().await?;
+    let staged = versions.stage().await?;
+    let number = match stage_release(&staged, rendered).await {
+        Ok(()) => match versions.promote(&staged, deployment_id).await {
+            Ok(number) => number,
+            Err(e) => {
+                versions.discard(&staged).await;
+                return Err(e.into());
+            }
+        },
+        Err(e) => {
+            versions.discard(&staged).await;
+            return Err(e);
+        }
+    };
+
+    let mut snapshots: Vec<Snapshot> = Vec::with_capacity(rendered.len());
+    let mut activated = false;
+    if let Err(e) =
+        write_and_activate(versions, number, rendered, &mut snapshots, &mut activated).await
+    {
+        rollback(&snapshots).await;
+        // a release left live can't be removed from under the link
+        if !activated || versions.restore(previous).await {
+            versions.discard_release(number).await;
+        }
+        return Err(e);
+    }
+    remove_backups(&snapshots).await;
+    record_created_dirs(created_dirs, &snapshots).await;
+    versions.prune().await;
+    Ok(())
+}
+
+/// Writes the in-place config instances and activates the staged release in config
+/// type dependency order. A release goes live as a whole, so it's activated just
+/// before the first in-place config instance ordered after one of its own: the
+/// in-place files its config types depend on are written before it goes live and
+/// those depending on its config types after.
+async fn write_and_activate(
+    versions: &versions::Versions,
+    number: u64,
+    rendered: &[Rendered],
+    snapshots: &mut Vec<Snapshot>,
+    activated: &mut bool,
+) -> Result<(), DeployErr> {
+    let mut staged = false;
+    for r in rendered {
+        if r.release_path.is_some() {
+            staged = true;
+            continue;
+        }
+        if staged && !*activated {
+            versions.activate(number).await?;
+            *activated = true;
+        }
+        write_cfg_inst(snapshots, &r.cfg_inst, &r.content).await?;
+    }
+    if !*activated {
+        versions.activate(number).await?;
+        *activated = true;
+    }
+    Ok(())
+}
+
+async fn stage_release(staged: &filesys::Dir, rendered: &[Rendered]) -> Result<(), DeployErr> {
+    for r in rendered {
+        let Some(rel_path) = &r.release_path else {
+            continue;
+        };
+        let dest = filesys::File::new(staged.path().join(rel_path));
+        debug!(
+            "staging config instance {} at {}",
+            r.cfg_inst.id,
+            dest.path().display()
+        );
+        dest.write_bytes(&r.content, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .map_err(|e| map_write_err(&r.cfg_inst, e))?;
+    }
     Ok(())
 }
 
@@ -166,29 +323,47 @@ fn map_snapshot_err(
 
 async fn write_cfg_insts_impl(
     snapshots: &mut Vec<Snapshot>,
-    cfg_insts: &[models::ConfigInstance],
-    content_stor: &storage::CfgInstContent,
+    rendered: &[Rendered],
 ) -> Result<(), DeployErr> {
-    for cfg_inst in cfg_insts {
-        let dest = filesys::File::new(&cfg_inst.filepath);
-        let content = content_stor.read(cfg_inst.id.clone()).await?;
-        info!(
-            "writing config instance {} to {}",
+    for r in rendered {
+        write_cfg_inst(snapshots, &r.cfg_inst, &r.content).await?;
+    }
+    Ok(())
+}
+
+async fn write_cfg_inst(
+    snapshots: &mut Vec<Snapshot>,
+    cfg_inst: &models::ConfigInstance,
+    content: &[u8],
+) -> Result<(), DeployErr> {
+    let dest = filesys::File::new(&cfg_inst.filepath);
+    // leave files which already hold the content untouched
+    if dest
+        .read_bytes()
+        .await
+        .is_ok_and(|existing| existing == content)
+    {
+        debug!(
+            "config instance {} is unchanged at {}",
             cfg_inst.id,
             dest.path().display()
         );
-        let backup = backup_location(&dest)?;
-        let snapshot = snapshot(&dest, &backup)
-            .await
-            .map_err(|e| map_snapshot_err(cfg_inst, &dest, &backup, e))?;
-        snapshots.push(snapshot);
-
-        dest.write_string(&content, WriteOptions::OVERWRITE_ATOMIC)
-            .await
-            .map_err(|e| map_write_err(cfg_inst, e))?;
+        return Ok(());
     }
+    info!(
+        "writing config instance {} to {}",
+        cfg_inst.id,
+        dest.path().display()
+    );
+    let backup = backup_location(&dest)?;
+    let snapshot = snapshot(&dest, &backup)
+        .await
+        .map_err(|e| map_snapshot_err(cfg_inst, &dest, &backup, e))?;
+    snapshots.push(snapshot);
 
-    remove_backups(snapshots).await;
+    dest.write_bytes(content, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .map_err(|e| map_write_err(cfg_inst, e))?;
     Ok(())
 }
 
@@ -197,6 +372,8 @@ async fn write_cfg_insts_impl(
 enum Snapshot {
     DidNotExist {
         dst: filesys::File,
+        /// The directories writing the file creates.
+        created: Vec<PathBuf>,
     },
     Existed {
         dst: filesys::File,
@@ -205,6 +382,8 @@ enum Snapshot {
 }
 
 async fn snapshot(dst: &filesys::File, backup: &filesys::File) -> Result<Snapshot, FileSysErr> {
+    // before copying, which creates the backup's (and so the file's) directory
+    let created = created_dirs::missing_ancestors(dst.path());
     // The backup only needs to survive within the same process run for
     // application-level rollback, not across power loss — skip fsync.
     match dst
@@ -216,7 +395,10 @@ async fn snapshot(dst: &filesys::File, backup: &filesys::File) -> Result<Snapsho
             backup: backup.clone(),
         }),
         Err(e) => match e {
-            FileSysErr::PathDoesNotExistErr(_) => Ok(Snapshot::DidNotExist { dst: dst.clone() }),
+            FileSysErr::PathDoesNotExistErr(_) => Ok(Snapshot::DidNotExist {
+                dst: dst.clone(),
+                created,
+            }),
             e => Err(e),
         },
     }
@@ -240,7 +422,7 @@ async fn rollback(snapshots: &[Snapshot]) {
 async fn rollback_snapshot(snapshot: &Snapshot) -> Result<(), FileSysErr> {
     match snapshot {
         Snapshot::Existed { dst, backup } => backup.move_to(dst, filesys::Overwrite::Allow).await,
-        Snapshot::DidNotExist { dst } => dst.delete().await,
+        Snapshot::DidNotExist { dst, .. } => dst.delete().await,
     }
 }
 
@@ -261,11 +443,36 @@ async fn remove_backups(snapshots: &[Snapshot]) {
     }
 }
 
+/// Best-effort housekeeping that records the directories the written files were
+/// created in, so offboarding can remove them. Failures are logged at `warn!` level.
+/// Never returns an error.
+async fn record_created_dirs(created_dirs: Option<&CreatedDirs>, snapshots: &[Snapshot]) {
+    let Some(created_dirs) = created_dirs else {
+        return;
+    };
+    let dirs: Vec<PathBuf> = snapshots
+        .iter()
+        .filter_map(|snapshot| match snapshot {
+            Snapshot::DidNotExist { created, .. } => Some(created.iter().cloned()),
+            Snapshot::Existed { .. } => None,
+        })
+        .flatten()
+        .collect();
+    if let Err(e) = created_dirs.record(&dirs).await {
+        warn!(
+            "failed to record the directories created for config instances in '{}': {}",
+            created_dirs.file().path().display(),
+            e,
+        );
+    }
+}
+
 // ================================= REMOVE ======================================== //
 pub async fn remove(
     storage: &storage::CfgInstRef<'_>,
     deployment: &models::Deployment,
     keeps: &[filesys::File],
+    trash: Option<&trash::Trash>,
 ) -> Result<(), DeployErr> {
     if deployment.config_instance_ids.is_empty() {
         return Ok(());
@@ -273,19 +480,32 @@ pub async fn remove(
     let cfg_insts = read_cfg_insts(storage.meta, &deployment.config_instance_ids).await?;
     validate_cfg_insts(&cfg_insts)?;
 
-    remove_cfg_insts(&cfg_insts, keeps).await
+    let result = remove_cfg_insts(&deployment.id, &cfg_insts, keeps, trash).await;
+    if let Some(trash) = trash {
+        trash.expire(Utc::now()).await;
+    }
+    result
 }
 
 async fn remove_cfg_insts(
+    deployment_id: &str,
     cfg_insts: &[models::ConfigInstance],
     keeps: &[filesys::File],
+    trash: Option<&trash::Trash>,
 ) -> Result<(), DeployErr> {
     for cfg_inst in cfg_insts {
         let dest = filesys::File::new(&cfg_inst.filepath);
         if keeps.contains(&dest) {
             continue;
         }
-        dest.delete().await?;
+        match trash {
+            Some(trash) => {
+                trash
+                    .put(&dest, deployment_id, &cfg_inst.id, Utc::now())
+                    .await?;
+            }
+            None => dest.delete().await?,
+        }
     }
     Ok(())
 }
@@ -566,6 +786,7 @@ mod tests {
         let snapshots = vec![
             Snapshot::DidNotExist {
                 dst: filesys::File::new(dne_parent.join("dst.json")),
+                created: Vec::new(),
             },
             Snapshot::Existed {
                 dst: filesys::File::new(existed_parent.join("dst.json")),
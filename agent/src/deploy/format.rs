@@ -0,0 +1,320 @@
+// Renders a config instance's cached content into the bytes written to its file. JSON
+// content written to a `.yaml`/`.yml`, `.toml` or `.ini` file is converted to the
+// file's format, so a config type can be authored as JSON and read by applications in
+// whichever format they expect. Everything else is written verbatim: content already in
+// another format, content written to a file of the same or an unknown format, and
+// binary content, which is decoded from the base64 it's cached as.
+//
+// The backend doesn't report a content format yet, so its content is taken to be JSON.
+// JSON content which doesn't parse is written verbatim rather than failing since it's
+// likely already in the file's format. Converted documents list their keys sorted,
+// as serde_json holds them. TOML is written by the `toml` crate. YAML is written here
+// so that every string is double quoted: serde_yaml leaves strings such as `yes` plain,
+// which YAML 1.1 parsers read back as booleans. INI has no escaping, so content which
+// would change the meaning of the file is rejected rather than written.
+
+// internal crates
+use crate::crypt::base64;
+use crate::deploy::errors::{ContentConversionErr, DeployErr};
+use crate::models::{self, config_instance::ContentFormat};
+use crate::trace;
+
+// external crates
+use serde_json::{Map, Value};
+use tracing::debug;
+
+/// The bytes to write to the config instance's file.
+pub fn render(cfg_inst: &models::ConfigInstance, content: &str) -> Result<Vec<u8>, DeployErr> {
+    let conversion_err = |format: ContentFormat, msg: String| ContentConversionErr {
+        cfg_inst_id: cfg_inst.id.clone(),
+        filepath: cfg_inst.filepath.clone(),
+        format,
+        msg,
+        trace: trace!(),
+    };
+
+    let source = cfg_inst.content_format;
+    if source == ContentFormat::Binary {
+        return base64::decode_bytes_standard(content.trim())
+            .map_err(|e| conversion_err(source, format!("invalid base64: {e}")).into());
+    }
+    let target = match ContentFormat::from_filepath(&cfg_inst.filepath) {
+        Some(target) if source == ContentFormat::Json && target != source => target,
+        _ => return Ok(content.as_bytes().to_vec()),
+    };
+    let document: Value = match serde_json::from_str(content) {
+        Ok(document) => document,
+        Err(e) => {
+            debug!(
+                "config instance '{}' isn't JSON ({e}), writing it verbatim",
+                cfg_inst.id
+            );
+            return Ok(content.as_bytes().to_vec());
+        }
+    };
+
+    let converted = match target {
+        ContentFormat::Yaml => Ok(to_yaml(&document)),
+        ContentFormat::Toml => to_toml(&document),
+        ContentFormat::Ini => to_ini(&document),
+        ContentFormat::Json | ContentFormat::Binary => Ok(content.to_string()),
+    };
+    converted
+        .map(String::into_bytes)
+        .map_err(|msg| conversion_err(target, msg).into())
+}
+
+// =================================== YAML ======================================== //
+/// Serializes a JSON document as block-style YAML. Strings are always double quoted,
+/// whose escapes are a superset of JSON's, so no string is read back as another type.
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => yaml_block(&mut out, value, 0),
+        Value::Array(items) if !items.is_empty() => yaml_block(&mut out, value, 0),
+        _ => {
+            out.push_str(&yaml_scalar(value));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn yaml_block(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                out.push_str(&format!("{pad}{}:", yaml_key(key)));
+                yaml_child(out, value, indent);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                out.push_str(&format!("{pad}-"));
+                yaml_child(out, item, indent);
+            }
+        }
+        _ => out.push_str(&format!("{pad}{}\n", yaml_scalar(value))),
+    }
+}
+
+fn yaml_child(out: &mut String, value: &Value, indent: usize) {
+    if is_nonempty_container(value) {
+        out.push('\n');
+        yaml_block(out, value, indent + 2);
+    } else {
+        out.push_str(&format!(" {}\n", yaml_scalar(value)));
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => yaml_number(n),
+        Value::String(s) => quote(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+    }
+}
+
+/// Floats in a form YAML 1.1 reads as a float too, which requires a `.` in the
+/// mantissa and a sign on the exponent (`1.0e+100` rather than `1e100`).
+fn yaml_number(n: &serde_json::Number) -> String {
+    let n = n.to_string();
+    let Some((mantissa, exponent)) = n.split_once(['e', 'E']) else {
+        return n;
+    };
+    let mantissa = match mantissa.contains('.') {
+        true => mantissa.to_string(),
+        false => format!("{mantissa}.0"),
+    };
+    let exponent = match exponent.starts_with(['+', '-']) {
+        true => exponent.to_string(),
+        false => format!("+{exponent}"),
+    };
+    format!("{mantissa}e{exponent}")
+}
+
+fn yaml_key(key: &str) -> String {
+    const RESERVED: &[&str] = &[
+        "true", "false", "null", "yes", "no", "on", "off", "y", "n", "~",
+    ];
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !RESERVED.contains(&key.to_ascii_lowercase().as_str());
+    match plain {
+        true => key.to_string(),
+        false => quote(key),
+    }
+}
+
+// =================================== TOML ======================================== //
+/// Serializes a JSON document as TOML. The document must be an object, and TOML has
+/// no null.
+pub fn to_toml(value: &Value) -> Result<String, String> {
+    let Value::Object(map) = value else {
+        return Err("a TOML document must be a table (a JSON object)".to_string());
+    };
+    let table = toml_table(&[], map)?;
+    toml::to_string(&table).map_err(|e| e.to_string())
+}
+
+fn toml_table(path: &[String], map: &Map<String, Value>) -> Result<toml::Table, String> {
+    map.iter()
+        .map(|(key, value)| {
+            let mut child = path.to_vec();
+            child.push(key.clone());
+            Ok((key.clone(), toml_value(&child, value)?))
+        })
+        .collect()
+}
+
+fn toml_value(path: &[String], value: &Value) -> Result<toml::Value, String> {
+    let err = |msg: String| format!("{}: {msg}", path.join("."));
+    match value {
+        Value::Null => Err(err("TOML has no null".to_string())),
+        Value::Bool(b) => Ok(toml::Value::Boolean(*b)),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Ok(toml::Value::Integer(i)),
+            (None, _) if n.is_u64() => Err(err(format!("{n} is too large for a TOML integer"))),
+            (None, Some(f)) => Ok(toml::Value::Float(f)),
+            (None, None) => Err(err(format!("{n} isn't a TOML number"))),
+        },
+        Value::String(s) => Ok(toml::Value::String(s.clone())),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let mut child = path.to_vec();
+                child.push(i.to_string());
+                toml_value(&child, item)
+            })
+            .collect::<Result<_, _>>()
+            .map(toml::Value::Array),
+        Value::Object(map) => toml_table(path, map).map(toml::Value::Table),
+    }
+}
+
+// ==================================== INI ======================================== //
+/// Serializes a JSON document as INI. Top-level values are written before the first
+/// section, objects become `[sections]` (nested ones `[parent.child]`) and values are
+/// written unquoted. Arrays have no INI form, and keys, section names and values which
+/// INI parsers would read differently (e.g. a key containing `=`, a value starting
+/// with `;` or spanning lines) are rejected.
+pub fn to_ini(value: &Value) -> Result<String, String> {
+    let Value::Object(map) = value else {
+        return Err("an INI document must be a JSON object".to_string());
+    };
+    let mut out = String::new();
+    ini_section(&mut out, &[], map)?;
+    Ok(out)
+}
+
+fn ini_section(out: &mut String, path: &[String], map: &Map<String, Value>) -> Result<(), String> {
+    let has_values = map.values().any(|value| !value.is_object());
+    if !path.is_empty() && has_values {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("[{}]\n", path.join(".")));
+    }
+    for (key, value) in map {
+        if value.is_object() {
+            continue;
+        }
+        let err = |msg: &str| format!("{}: {msg}", dotted(path, key));
+        if let Some(msg) = ini_key_problem(key) {
+            return Err(err(msg));
+        }
+        let value = match value {
+            Value::Object(_) => continue,
+            Value::Null => String::new(),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => {
+                if let Some(msg) = ini_value_problem(s) {
+                    return Err(err(msg));
+                }
+                s.clone()
+            }
+            Value::Array(_) => return Err(err("INI has no arrays")),
+        };
+        match value.is_empty() {
+            true => out.push_str(&format!("{key} =\n")),
+            false => out.push_str(&format!("{key} = {value}\n")),
+        }
+    }
+    for (key, value) in map {
+        if let Value::Object(section) = value {
+            if let Some(msg) = ini_section_problem(key) {
+                return Err(format!("{}: {msg}", dotted(path, key)));
+            }
+            let mut child = path.to_vec();
+            child.push(key.clone());
+            ini_section(out, &child, section)?;
+        }
+    }
+    Ok(())
+}
+
+fn ini_key_problem(key: &str) -> Option<&'static str> {
+    if key.is_empty() || key.trim() != key {
+        Some("INI keys can't be empty or start or end with whitespace")
+    } else if key.contains(['=', ':', '[', ']', '\n', '\r']) {
+        Some("INI keys can't contain '=', ':', '[', ']' or line breaks")
+    } else if key.starts_with([';', '#']) {
+        Some("INI keys can't start with a comment character")
+    } else {
+        None
+    }
+}
+
+fn ini_section_problem(name: &str) -> Option<&'static str> {
+    if name.is_empty() || name.trim() != name {
+        Some("INI section names can't be empty or start or end with whitespace")
+    } else if name.contains(['[', ']', '.', '\n', '\r']) {
+        Some("INI section names can't contain '[', ']', '.' or line breaks")
+    } else {
+        None
+    }
+}
+
+fn ini_value_problem(value: &str) -> Option<&'static str> {
+    if value.contains(['\n', '\r']) {
+        Some("INI values can't span lines")
+    } else if value.trim() != value {
+        Some("INI values can't start or end with whitespace")
+    } else if value.starts_with([';', '#']) || value.contains(" ;") || value.contains(" #") {
+        Some("INI values can't contain comments")
+    } else {
+        None
+    }
+}
+
+// ================================= UTILITIES ===================================== //
+/// A double-quoted string with JSON escapes, which are a subset of YAML's.
+fn quote(s: &str) -> String {
+    Value::String(s.to_string()).to_string()
+}
+
+fn is_nonempty_container(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn dotted(path: &[String], key: &str) -> String {
+    match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{key}", path.join(".")),
+    }
+}
@@ -1,6 +1,7 @@
 // internal crates
 use crate::cooldown;
 use crate::errors::Error;
+use crate::metrics;
 use crate::models::{self, deployment::Updates, Patch};
 
 // external crates
@@ -31,6 +32,14 @@ pub fn next_action(deployment: &models::Deployment) -> NextAction {
         );
     }
 
+    // a deployment awaiting a reboot has already written its config instances so
+    // they must be removed if it is no longer targeting deployed
+    if deployment.pending_finalize.is_some()
+        && deployment.target_status != models::DplTarget::Deployed
+    {
+        return NextAction::Remove;
+    }
+
     // determine the next action
     match deployment.target_status {
         models::DplTarget::Staged => match deployment.activity_status {
@@ -42,7 +51,8 @@ pub fn next_action(deployment: &models::Deployment) -> NextAction {
             models::DplActivity::Archived => NextAction::None,
         },
         models::DplTarget::Deployed => match deployment.activity_status {
-            models::DplActivity::Drifted => NextAction::None,
+            // a deployed file no longer holds its content so the files are rewritten
+            models::DplActivity::Drifted => NextAction::Deploy,
             models::DplActivity::Staged => NextAction::None,
             models::DplActivity::Queued => NextAction::Deploy,
             models::DplActivity::Deployed => NextAction::None,
@@ -60,6 +70,19 @@ pub fn next_action(deployment: &models::Deployment) -> NextAction {
     }
 }
 
+/// The next action while deployments are paused: a deployment which would be
+/// deployed, removed or archived waits until deployments are resumed instead,
+/// whatever its target status.
+pub fn next_action_paused(deployment: &models::Deployment) -> NextAction {
+    match next_action(deployment) {
+        NextAction::None => NextAction::None,
+        NextAction::Wait(wait) => NextAction::Wait(wait),
+        NextAction::Deploy | NextAction::Remove | NextAction::Archive => {
+            NextAction::Wait(TimeDelta::zero())
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct RetryPolicy {
     pub max_attempts: u32,
@@ -86,6 +109,15 @@ pub fn deploy(mut deployment: models::Deployment) -> models::Deployment {
     let new_activity = models::DplActivity::Deployed;
     let patch = get_success_updates(&deployment, new_activity);
     deployment.patch(patch);
+    deployment.pending_finalize = None;
+    deployment
+}
+
+/// Marks a deployed deployment whose files no longer hold their deployed content.
+pub fn drift(mut deployment: models::Deployment) -> models::Deployment {
+    let new_activity = models::DplActivity::Drifted;
+    let patch = get_success_updates(&deployment, new_activity);
+    deployment.patch(patch);
     deployment
 }
 
@@ -93,6 +125,7 @@ pub fn removing(mut deployment: models::Deployment) -> models::Deployment {
     let new_activity = models::DplActivity::Removing;
     let patch = get_success_updates(&deployment, new_activity);
     deployment.patch(patch);
+    deployment.pending_finalize = None;
     deployment
 }
 
@@ -100,6 +133,7 @@ pub fn archive(mut deployment: models::Deployment) -> models::Deployment {
     let new_activity = models::DplActivity::Archived;
     let patch = get_success_updates(&deployment, new_activity);
     deployment.patch(patch);
+    deployment.pending_finalize = None;
     deployment
 }
 
@@ -107,6 +141,9 @@ fn get_success_updates(
     deployment: &models::Deployment,
     new_activity: models::DplActivity,
 ) -> Updates {
+    metrics::global()
+        .deployment_transitions
+        .inc(&[deployment.activity_status.as_str(), new_activity.as_str()]);
     Updates {
         activity_status: Some(new_activity),
         error_status: if has_recovered(deployment, new_activity) {
@@ -193,6 +230,21 @@ pub fn error(
     deployment
 }
 
+/// Fails a deployment outright, without retrying, for errors which retrying cannot
+/// resolve (e.g. a dependency cycle).
+pub fn fail(mut deployment: models::Deployment) -> models::Deployment {
+    metrics::global()
+        .deployment_errors
+        .inc(&[models::DplErrStatus::Failed.as_str()]);
+    let patch = Updates {
+        error_status: Some(models::DplErrStatus::Failed),
+        attempts: Some(deployment.attempts.saturating_add(1)),
+        ..Updates::empty()
+    };
+    deployment.patch(patch);
+    deployment
+}
+
 fn should_bump_attempts(e: &impl Error) -> bool {
     !e.is_network_conn_err()
 }
@@ -215,6 +267,10 @@ fn get_error_updates(
         new_error_status = Some(models::DplErrStatus::Failed);
     }
 
+    if let Some(status) = new_error_status {
+        metrics::global().deployment_errors.inc(&[status.as_str()]);
+    }
+
     let cooldown = cooldown::calc(&retry_policy.backoff, attempts);
 
     Updates {
@@ -321,7 +377,7 @@ mod tests {
         //  target\activity | Drifted | Staged | Queued  | Deployed | Removing | Archived
         //  ----------------+---------+--------+---------+----------+----------+---------
         //  Staged          | None    | None   | Archive | Remove   | Remove | None
-        //  Deployed        | None    | None   | Deploy  | None     | Deploy | Deploy
+        //  Deployed        | Deploy  | None   | Deploy  | None     | Deploy | Deploy
         //  Archived        | Archive | Archive| Archive | Remove   | Remove | None
 
         fn validate_for_activity(activity: DplActivity, actionable: Expected) {
@@ -365,7 +421,7 @@ mod tests {
                 DplActivity::Drifted,
                 Expected {
                     staged: NextAction::None,
-                    deployed: NextAction::None,
+                    deployed: NextAction::Deploy,
                     archived: NextAction::Archive,
                 },
             );
@@ -432,6 +488,64 @@ mod tests {
         }
     }
 
+    mod next_action_paused_fn {
+        use super::*;
+
+        #[test]
+        fn transitions_wait() {
+            for (target, activity) in [
+                (DplTarget::Deployed, DplActivity::Queued),
+                (DplTarget::Deployed, DplActivity::Drifted),
+                (DplTarget::Staged, DplActivity::Deployed),
+                (DplTarget::Archived, DplActivity::Staged),
+            ] {
+                let deployment = Deployment {
+                    target_status: target,
+                    activity_status: activity,
+                    ..Default::default()
+                };
+                assert_ne!(next_action(&deployment), NextAction::None);
+                assert_eq!(
+                    next_action_paused(&deployment),
+                    NextAction::Wait(TimeDelta::zero()),
+                    "{target:?} {activity:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn settled_and_failed_deployments_stay_settled() {
+            let settled = Deployment {
+                target_status: DplTarget::Deployed,
+                activity_status: DplActivity::Deployed,
+                ..Default::default()
+            };
+            assert_eq!(next_action_paused(&settled), NextAction::None);
+
+            let failed = Deployment {
+                target_status: DplTarget::Deployed,
+                activity_status: DplActivity::Queued,
+                error_status: DplErrStatus::Failed,
+                ..Default::default()
+            };
+            assert_eq!(next_action_paused(&failed), NextAction::None);
+        }
+
+        #[test]
+        fn cooldowns_are_kept() {
+            let mut deployment = Deployment {
+                target_status: DplTarget::Deployed,
+                activity_status: DplActivity::Queued,
+                ..Default::default()
+            };
+            deployment.set_cooldown(TimeDelta::minutes(60));
+            match next_action_paused(&deployment) {
+                NextAction::Wait(wait) => assert!(wait > TimeDelta::minutes(59)),
+                other => panic!("expected a wait, got {other:?}"),
+            }
+        }
+    }
+
     // =============================== TRANSITIONS ================================= //
 
     fn all_status_combos() -> Vec<Deployment> {
@@ -823,6 +937,28 @@ mod tests {
                 validate_removing_transition(deployment);
             }
         }
+
+        // --- drift transition ---
+
+        #[test]
+        fn drift_preserves_everything_but_activity() {
+            let deployment = Deployment {
+                target_status: DplTarget::Deployed,
+                activity_status: DplActivity::Deployed,
+                deployed_at: Some(Utc::now() - TimeDelta::hours(1)),
+                ..Default::default()
+            };
+            let actual = drift(deployment.clone());
+            let expected = Deployment {
+                activity_status: DplActivity::Drifted,
+                ..deployment
+            };
+            assert!(
+                expected == actual,
+                "expected:\n{expected:?}\n actual:\n{actual:?}\n",
+            );
+            assert_eq!(next_action(&actual), NextAction::Deploy);
+        }
     }
 
     mod error_transitions {
@@ -938,6 +1074,25 @@ mod tests {
         }
     }
 
+    mod fail_transition {
+        use super::*;
+
+        #[test]
+        fn fails_without_cooldown() {
+            for mut deployment in all_status_combos() {
+                deployment.attempts = 3;
+                let actual = fail(deployment.clone());
+                let expected = Deployment {
+                    error_status: DplErrStatus::Failed,
+                    attempts: 4,
+                    ..deployment
+                };
+                assert_eq!(expected, actual);
+                assert_eq!(next_action(&actual), NextAction::None);
+            }
+        }
+    }
+
     // ============================== HAS_RECOVERED ================================ //
 
     mod has_recovered_fn {
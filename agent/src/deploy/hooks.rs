@@ -0,0 +1,243 @@
+// User-configured commands run around a deployment's files changing, e.g. to validate
+// the new configs before they're written or to restart the application which reads
+// them afterwards. A hook which fails, exits non-zero or runs past its timeout fails
+// the deployment's deploy (or removal) like any other error, so it's retried after a
+// cooldown.
+
+// standard crates
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+// internal crates
+use crate::deploy::errors::*;
+use crate::models;
+use crate::trace;
+
+// external crates
+use tracing::{debug, info};
+
+/// The most output kept from each of a hook's stdout and stderr. Longer output is
+/// truncated from the front since a failure's cause is usually at the end.
+pub const MAX_OUTPUT_BYTES: usize = 4096;
+
+// How long to wait for a hook's output once it has exited. Processes the hook left
+// running in the background may hold its pipes open indefinitely.
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PreDeploy,
+    PostDeploy,
+    PreRemove,
+    PostRemove,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PreDeploy => "pre_deploy",
+            Self::PostDeploy => "post_deploy",
+            Self::PreRemove => "pre_remove",
+            Self::PostRemove => "post_remove",
+        }
+    }
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// ================================== OPTIONS ====================================== //
+/// The command run at each stage, as a program followed by its arguments. A stage
+/// with an empty command has no hook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub pre_deploy: Vec<String>,
+    pub post_deploy: Vec<String>,
+    pub pre_remove: Vec<String>,
+    pub post_remove: Vec<String>,
+    /// How long a hook may run before it's killed and counted as failed.
+    pub timeout: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            pre_deploy: Vec::new(),
+            post_deploy: Vec::new(),
+            pre_remove: Vec::new(),
+            post_remove: Vec::new(),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Options {
+    pub fn command(&self, stage: Stage) -> &[String] {
+        match stage {
+            Stage::PreDeploy => &self.pre_deploy,
+            Stage::PostDeploy => &self.post_deploy,
+            Stage::PreRemove => &self.pre_remove,
+            Stage::PostRemove => &self.post_remove,
+        }
+    }
+
+    pub fn is_enabled(&self, stage: Stage) -> bool {
+        !self.command(stage).is_empty()
+    }
+}
+
+// ==================================== RUN ======================================== //
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub stage: Stage,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+/// Runs the stage's hook for a deployment, if one is configured. The hook is given
+/// the stage, the deployment and its release in the `MIRU_HOOK`, `MIRU_DEPLOYMENT_ID`
+/// and `MIRU_RELEASE_ID` environment variables and the deployment's config files,
+/// one per line, in `MIRU_CONFIG_FILES`.
+pub async fn run(
+    options: &Options,
+    stage: Stage,
+    deployment: &models::Deployment,
+    filepaths: &[String],
+) -> Result<Option<Output>, DeployErr> {
+    let command = options.command(stage).to_vec();
+    if command.is_empty() {
+        return Ok(None);
+    }
+    let display = command.join(" ");
+    info!("running {stage} hook for deployment '{}'", deployment.id);
+
+    let env = vec![
+        ("MIRU_HOOK".to_string(), stage.as_str().to_string()),
+        ("MIRU_DEPLOYMENT_ID".to_string(), deployment.id.clone()),
+        ("MIRU_RELEASE_ID".to_string(), deployment.release_id.clone()),
+        ("MIRU_CONFIG_FILES".to_string(), filepaths.join("\n")),
+    ];
+    let timeout = options.timeout;
+    let started_at = Instant::now();
+    let result = tokio::task::spawn_blocking(move || run_blocking(&command, env, timeout))
+        .await
+        .unwrap_or_else(|e| Err(Failure::new(e.to_string())));
+    let duration = started_at.elapsed();
+
+    match result {
+        Ok((stdout, stderr)) => {
+            debug!("{stage} hook stdout: {stdout}");
+            debug!("{stage} hook stderr: {stderr}");
+            Ok(Some(Output {
+                stage,
+                stdout,
+                stderr,
+                duration,
+            }))
+        }
+        Err(failure) => Err(HookErr {
+            deployment_id: deployment.id.clone(),
+            stage,
+            command: display,
+            msg: failure.msg,
+            stderr: failure.stderr,
+            trace: trace!(),
+        }
+        .into()),
+    }
+}
+
+pub(crate) struct Failure {
+    pub msg: String,
+    pub stderr: String,
+}
+
+impl Failure {
+    pub(crate) fn new(msg: String) -> Self {
+        Self {
+            msg,
+            stderr: String::new(),
+        }
+    }
+}
+
+/// Runs a command to completion, killing it once the timeout passes, and returns the
+/// ends of its stdout and stderr. Blocks, so it's run with `spawn_blocking`.
+pub(crate) fn run_blocking(
+    command: &[String],
+    env: Vec<(String, String)>,
+    timeout: Duration,
+) -> Result<(String, String), Failure> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Failure::new("no command configured".to_string()))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Failure::new(e.to_string()))?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => break Err(e.to_string()),
+        }
+    };
+    let stdout = stdout.recv_timeout(OUTPUT_GRACE).unwrap_or_default();
+    let stderr = stderr.recv_timeout(OUTPUT_GRACE).unwrap_or_default();
+
+    match status {
+        Ok(status) if status.success() => Ok((stdout, stderr)),
+        Ok(status) => Err(Failure {
+            msg: format!("exited with {status}"),
+            stderr,
+        }),
+        Err(msg) => Err(Failure { msg, stderr }),
+    }
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    if let Some(mut pipe) = pipe {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let mut buf = [0; 4096];
+            while let Ok(n) = pipe.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                output.extend_from_slice(&buf[..n]);
+                if output.len() > 2 * MAX_OUTPUT_BYTES {
+                    output.drain(..output.len() - MAX_OUTPUT_BYTES);
+                }
+            }
+            let _ = tx.send(tail(&output));
+        });
+    }
+    rx
+}
+
+fn tail(output: &[u8]) -> String {
+    let start = output.len().saturating_sub(MAX_OUTPUT_BYTES);
+    String::from_utf8_lossy(&output[start..]).trim().to_string()
+}
@@ -1,7 +1,20 @@
 pub mod apply;
+pub mod created_dirs;
+pub mod drift;
 pub mod errors;
 pub mod filesys;
+pub mod format;
 pub mod fsm;
+pub mod hooks;
+pub mod order;
+pub mod pause;
+pub mod reboot;
+pub mod schedule;
+pub mod schema;
+pub mod signature;
+pub mod space;
+pub mod trash;
+pub mod versions;
 
 pub use self::apply::apply;
 pub use self::errors::DeployErr;
@@ -0,0 +1,164 @@
+// standard crates
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// internal crates
+use crate::deploy::errors::*;
+use crate::models;
+use crate::trace;
+
+// ================================= TOPOLOGICAL =================================== //
+/// Orders the given roots, and every node reachable from them, so that each node
+/// comes after the nodes it depends on. Nodes without dependencies between them keep
+/// the order of the roots. Returns the nodes of a cycle (the first node repeated at
+/// the end) if one is reachable.
+pub fn topological<'a>(
+    roots: impl IntoIterator<Item = &'a str>,
+    deps: impl Fn(&str) -> Vec<&'a str>,
+) -> Result<Vec<String>, Vec<String>> {
+    let mut done = HashSet::new();
+    let mut path = Vec::new();
+    let mut ordered = Vec::new();
+    for root in roots {
+        visit(root, &deps, &mut done, &mut path, &mut ordered)?;
+    }
+    Ok(ordered)
+}
+
+fn visit<'a>(
+    node: &'a str,
+    deps: &impl Fn(&str) -> Vec<&'a str>,
+    done: &mut HashSet<&'a str>,
+    path: &mut Vec<&'a str>,
+    ordered: &mut Vec<String>,
+) -> Result<(), Vec<String>> {
+    if done.contains(node) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|n| *n == node) {
+        let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+        cycle.push(node.to_string());
+        return Err(cycle);
+    }
+    path.push(node);
+    for dep in deps(node) {
+        visit(dep, deps, done, path, ordered)?;
+    }
+    path.pop();
+    done.insert(node);
+    ordered.push(node.to_string());
+    Ok(())
+}
+
+// ================================= DEPLOYMENTS =================================== //
+/// Checks that every deployment the given deployment depends on has been deployed.
+/// The deployments it (transitively) depends on must not form a cycle.
+pub fn check_dependencies(
+    deployment: &models::Deployment,
+    deployments: &[models::Deployment],
+) -> Result<(), DeployErr> {
+    if deployment.dependencies.deployments.is_empty() {
+        return Ok(());
+    }
+    let by_id: HashMap<&str, &models::Deployment> =
+        deployments.iter().map(|d| (d.id.as_str(), d)).collect();
+    let deps = |id: &str| -> Vec<&str> {
+        let dependencies = if id == deployment.id {
+            &deployment.dependencies
+        } else {
+            match by_id.get(id) {
+                Some(dpl) => &dpl.dependencies,
+                None => return Vec::new(),
+            }
+        };
+        dependencies
+            .deployments
+            .iter()
+            .map(String::as_str)
+            .collect()
+    };
+    topological([deployment.id.as_str()], deps).map_err(|ids| DependencyCycleErr {
+        kind: "deployments",
+        ids,
+        trace: trace!(),
+    })?;
+
+    let unmet: Vec<String> = deployment
+        .dependencies
+        .deployments
+        .iter()
+        .filter(|id| {
+            by_id
+                .get(id.as_str())
+                .is_none_or(|dpl| dpl.deployed_at.is_none())
+        })
+        .cloned()
+        .collect();
+    if !unmet.is_empty() {
+        return Err(UnmetDependenciesErr {
+            deployment_id: deployment.id.clone(),
+            ids: unmet,
+            trace: trace!(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Orders deployments so that a deployment is handled before the deployments it
+/// depends on, i.e. dependents are removed before their dependencies. The original
+/// order is kept if the dependencies form a cycle.
+pub fn for_removal(deployments: Vec<models::Deployment>) -> Vec<models::Deployment> {
+    let ids: HashSet<&str> = deployments.iter().map(|d| d.id.as_str()).collect();
+    let by_id: HashMap<&str, &models::Deployment> =
+        deployments.iter().map(|d| (d.id.as_str(), d)).collect();
+    let deps = |id: &str| -> Vec<&str> {
+        by_id
+            .get(id)
+            .map(|dpl| {
+                dpl.dependencies
+                    .deployments
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|dep| ids.contains(dep))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let Ok(mut ordered) = topological(deployments.iter().map(|d| d.id.as_str()), deps) else {
+        return deployments;
+    };
+    ordered.reverse();
+    let rank: HashMap<String, usize> = ordered.into_iter().zip(0..).collect();
+    let mut deployments = deployments;
+    deployments.sort_by_key(|d| rank.get(&d.id).copied().unwrap_or(usize::MAX));
+    deployments
+}
+
+// ================================= CONFIG TYPES ================================== //
+/// Orders config instances so that instances of a config type are written after the
+/// instances of the config types it depends on.
+pub fn by_config_type(
+    cfg_insts: Vec<models::ConfigInstance>,
+    deps: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<models::ConfigInstance>, DeployErr> {
+    if deps.is_empty() {
+        return Ok(cfg_insts);
+    }
+    let ordered = topological(
+        cfg_insts.iter().map(|ci| ci.config_type_id.as_str()),
+        |config_type_id| {
+            deps.get(config_type_id)
+                .map(|ids| ids.iter().map(String::as_str).collect())
+                .unwrap_or_default()
+        },
+    )
+    .map_err(|ids| DependencyCycleErr {
+        kind: "config types",
+        ids,
+        trace: trace!(),
+    })?;
+    let rank: HashMap<String, usize> = ordered.into_iter().zip(0..).collect();
+    let mut cfg_insts = cfg_insts;
+    cfg_insts.sort_by_key(|ci| rank.get(&ci.config_type_id).copied().unwrap_or(usize::MAX));
+    Ok(cfg_insts)
+}
@@ -0,0 +1,116 @@
+// A switch operators flip during an incident to stop the agent from changing the
+// device's config files without stopping the agent. While deployments are paused the
+// sync still pulls the backend's deployments and their content but every deployment
+// which would be deployed, removed or archived waits instead, until deployments are
+// resumed. The switch is a file under the data directory so that it survives
+// restarts and so that the CLI can flip it without going through the running agent.
+
+// internal crates
+use crate::deploy::errors::DeployErr;
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Where deployments were paused or resumed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Socket,
+    Mqtt,
+    Cli,
+}
+
+impl Origin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Socket => "socket",
+            Self::Mqtt => "mqtt",
+            Self::Cli => "cli",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub paused: bool,
+    /// When deployments were last paused or resumed.
+    #[serde(default)]
+    pub changed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub changed_by: Option<Origin>,
+    /// Why deployments were paused, as given by the operator.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Switch {
+    file: filesys::File,
+}
+
+impl Switch {
+    pub fn new(file: filesys::File) -> Self {
+        Self { file }
+    }
+
+    pub fn file(&self) -> &filesys::File {
+        &self.file
+    }
+
+    /// Deployments are resumed unless the switch says otherwise. A switch which can't
+    /// be read keeps deployments paused, since whoever wrote it most likely meant to
+    /// pause them.
+    pub async fn status(&self) -> Status {
+        if !self.file.exists() {
+            return Status::default();
+        }
+        self.file.read_json().await.unwrap_or_else(|e| {
+            warn!("unable to read whether deployments are paused, pausing them: {e}");
+            Status {
+                paused: true,
+                ..Status::default()
+            }
+        })
+    }
+
+    pub async fn is_paused(&self) -> bool {
+        self.status().await.paused
+    }
+
+    pub async fn pause(&self, reason: Option<String>, by: Origin) -> Result<Status, DeployErr> {
+        let status = Status {
+            paused: true,
+            changed_at: Some(Utc::now()),
+            changed_by: Some(by),
+            reason: reason.filter(|reason| !reason.trim().is_empty()),
+        };
+        self.write(&status).await?;
+        match &status.reason {
+            Some(reason) => info!("Deployments paused from the {}: {reason}", by.as_str()),
+            None => info!("Deployments paused from the {}", by.as_str()),
+        }
+        Ok(status)
+    }
+
+    pub async fn resume(&self, by: Origin) -> Result<Status, DeployErr> {
+        let status = Status {
+            paused: false,
+            changed_at: Some(Utc::now()),
+            changed_by: Some(by),
+            reason: None,
+        };
+        self.write(&status).await?;
+        info!("Deployments resumed from the {}", by.as_str());
+        Ok(status)
+    }
+
+    async fn write(&self, status: &Status) -> Result<(), DeployErr> {
+        self.file
+            .write_json(status, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+// standard crates
+use std::path::Path;
+use std::time::Duration;
+
+// internal crates
+use crate::deploy::errors::*;
+use crate::deploy::{hooks, schedule};
+use crate::filesys;
+use crate::models;
+use crate::platform;
+use crate::trace;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use tracing::{info, warn};
+
+pub const DEFAULT_BOOT_ID_FILE: &str = "/proc/sys/kernel/random/boot_id";
+
+// ================================== OPTIONS ====================================== //
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Directories (or files) whose config instances only take effect after the host
+    /// reboots. Deployments writing to any of them are staged and finalized on the
+    /// next boot. Reboot coordination is disabled when empty.
+    pub paths: Vec<String>,
+    /// Whether the agent may reboot the host itself. Otherwise the reboot is only
+    /// requested and left to an operator.
+    pub authorized: bool,
+    /// The maintenance windows the agent may reboot the host in. Any time if there
+    /// are none.
+    pub windows: schedule::Windows,
+    pub command: Vec<String>,
+    /// How long the reboot command may run before it's killed, so a hung command
+    /// can't stall applying deployments.
+    pub command_timeout: Duration,
+    pub boot_id_file: filesys::File,
+    /// How often to check whether the host has rebooted and to repeat the request.
+    pub recheck_interval: TimeDelta,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            authorized: false,
+            windows: schedule::Windows::default(),
+            command: platform::default_reboot_command(),
+            command_timeout: Duration::from_secs(60),
+            boot_id_file: filesys::File::new(DEFAULT_BOOT_ID_FILE),
+            recheck_interval: TimeDelta::minutes(5),
+        }
+    }
+}
+
+impl Options {
+    pub fn is_enabled(&self) -> bool {
+        !self.paths.is_empty()
+    }
+
+    pub fn requires_reboot(&self, filepath: &str) -> bool {
+        self.paths
+            .iter()
+            .any(|path| Path::new(filepath).starts_with(path))
+    }
+}
+
+// ================================= COORDINATION ================================== //
+/// Reads the id the kernel assigns to the current boot.
+pub async fn boot_id(options: &Options) -> Result<String, DeployErr> {
+    let boot_id = options.boot_id_file.read_string().await?;
+    Ok(boot_id.trim().to_string())
+}
+
+/// Whether the host has rebooted since the deployment was staged.
+pub fn has_rebooted(pending: &models::PendingFinalize, boot_id: &str) -> bool {
+    pending.boot_id != boot_id
+}
+
+/// Requests a reboot for a staged deployment. The reboot command is only run if the
+/// agent is authorized and the maintenance window is open; otherwise the request is
+/// logged for an operator. Requests are repeated at most once per recheck interval.
+/// Returns how long to wait before checking whether the host has rebooted.
+pub async fn request(
+    options: &Options,
+    deployment: &mut models::Deployment,
+    now: DateTime<Utc>,
+) -> Result<TimeDelta, DeployErr> {
+    let wait = options.recheck_interval;
+    if !options.windows.is_open(now) {
+        return Ok(options
+            .windows
+            .next_open(now)
+            .map_or(wait, |opens_at| (opens_at - now).max(wait)));
+    }
+    let Some(pending) = deployment.pending_finalize.as_mut() else {
+        return Ok(wait);
+    };
+    if pending
+        .reboot_requested_at
+        .is_some_and(|requested_at| now - requested_at < options.recheck_interval)
+    {
+        return Ok(wait);
+    }
+    pending.reboot_requested_at = Some(now);
+
+    if !options.authorized {
+        warn!(
+            "deployment '{}' requires a host reboot to take effect; waiting for the host to be rebooted",
+            deployment.id
+        );
+        return Ok(wait);
+    }
+    info!("rebooting host to finalize deployment '{}'", deployment.id);
+    run_command(&options.command, options.command_timeout).await?;
+    Ok(wait)
+}
+
+async fn run_command(command: &[String], timeout: Duration) -> Result<(), DeployErr> {
+    let owned = command.to_vec();
+    let result =
+        tokio::task::spawn_blocking(move || hooks::run_blocking(&owned, Vec::new(), timeout))
+            .await
+            .unwrap_or_else(|e| Err(hooks::Failure::new(e.to_string())));
+    result.map(|_| ()).map_err(|failure| {
+        RebootCommandErr {
+            command: command.join(" "),
+            msg: failure.msg,
+            trace: trace!(),
+        }
+        .into()
+    })
+}
@@ -0,0 +1,226 @@
+// standard crates
+use std::fmt;
+
+// external crates
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, TimeDelta, Timelike, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// How far ahead to look for the next matching minute. Far enough for any date a
+/// schedule can name (the 29th of February comes around at least every eight years).
+const HORIZON_DAYS: u64 = 8 * 366;
+
+// ================================= SCHEDULE ====================================== //
+/// A cron-like expression of the minutes (UTC) in which deployments may be applied:
+/// `minute hour day-of-month month day-of-week`. Each field is `*`, a value, a range
+/// (`a-b`) or a comma separated list of them, optionally stepped (`*/15`, `9-17/2`).
+/// Months and weekdays may be named (`JAN`, `SUN`) and Sunday is both 0 and 7. As in
+/// cron, when both the day of the month and the day of the week are restricted a day
+/// matching either is in the schedule.
+///
+/// `* 2-4 * * SAT,SUN` is every minute from 02:00 to 04:59 UTC on weekends.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+impl Schedule {
+    pub fn new(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "schedule `{expr}` has {} fields (expected 5: minute hour day-of-month month day-of-week)",
+                fields.len()
+            ));
+        };
+        let field_err = |name: &str, e: String| format!("schedule `{expr}` {name}: {e}");
+        let mut weekdays =
+            parse_field(weekday, 0, 7, &WEEKDAYS).map_err(|e| field_err("day-of-week", e))?;
+        // 7 is another name for sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expr: fields.join(" "),
+            minutes: parse_field(minute, 0, 59, &[]).map_err(|e| field_err("minute", e))?,
+            hours: parse_field(hour, 0, 23, &[]).map_err(|e| field_err("hour", e))?,
+            days: parse_field(day, 1, 31, &[]).map_err(|e| field_err("day-of-month", e))?,
+            months: parse_field(month, 1, 12, &MONTHS).map_err(|e| field_err("month", e))?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        self.matches_day(now.date_naive())
+            && bit(self.hours, now.hour())
+            && bit(self.minutes, now.minute())
+    }
+
+    /// The start of the first minute in the schedule at or after `now`; `now` itself
+    /// if it's in the schedule. None if no minute is, such as the 30th of February.
+    pub fn next(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.contains(now) {
+            return Some(now);
+        }
+        let horizon = now.checked_add_days(Days::new(HORIZON_DAYS))?;
+        let mut at = now
+            .with_second(0)
+            .and_then(|at| at.with_nanosecond(0))?
+            .checked_add_signed(TimeDelta::minutes(1))?;
+        while at < horizon {
+            let date = at.date_naive();
+            if !bit(self.months, date.month()) {
+                // skip to the first of the next month
+                let first = date.with_day(1)?.checked_add_months(Months::new(1))?;
+                at = first.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !self.matches_day(date) {
+                at = date.succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !bit(self.hours, at.hour()) {
+                at = at.with_minute(0)? + TimeDelta::hours(1);
+            } else if !bit(self.minutes, at.minute()) {
+                at += TimeDelta::minutes(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if !bit(self.months, date.month()) {
+            return false;
+        }
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parses a field into a bit set of the values in it.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step `{step}`"))?;
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, names)?,
+                parse_value(end, min, max, names)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, names)?;
+            // a stepped value runs to the end of the field's range
+            (value, if step.is_some() { max } else { value })
+        };
+        if start > end {
+            return Err(format!("range `{range}` is backwards"));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    if let Some(i) = names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        // months are numbered from 1 and weekdays from 0
+        return Ok(i as u32 + min);
+    }
+    match value.parse::<u32>() {
+        Ok(n) if (min..=max).contains(&n) => Ok(n),
+        Ok(n) => Err(format!("{n} is outside {min}-{max}")),
+        Err(_) => Err(format!("invalid value `{value}`")),
+    }
+}
+
+impl fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Schedule({:?})", self.expr)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expr)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::new(&raw).map_err(serde::de::Error::custom)
+    }
+}
+
+// ============================= MAINTENANCE WINDOWS ================================ //
+/// The maintenance windows deployments are applied in, and those the host may be
+/// rebooted in (see [crate::deploy::reboot]). Outside them the sync still downloads
+/// content, so changes are staged and applied once a window opens. With no windows,
+/// any time is in a window.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Windows {
+    pub schedules: Vec<Schedule>,
+}
+
+impl Windows {
+    pub fn new(schedules: Vec<Schedule>) -> Self {
+        Self { schedules }
+    }
+
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.schedules.is_empty() || self.schedules.iter().any(|s| s.contains(now))
+    }
+
+    /// When a window next opens; `now` if one is open. None if none ever will.
+    pub fn next_open(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.is_open(now) {
+            return Some(now);
+        }
+        self.schedules.iter().filter_map(|s| s.next(now)).min()
+    }
+}
@@ -0,0 +1,49 @@
+// Validates config instance content against its config schema before it's deployed so
+// that invalid config is never written to disk. Validation is delegated to the
+// `jsonschema` crate, which implements every JSON Schema draft, including `format`
+// assertions. A schema which can't be compiled (it's malformed, or references another
+// document, which isn't fetched) fails validation rather than letting content through
+// unchecked.
+
+// external crates
+use serde_json::Value;
+
+/// Where the content violates the schema and how.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// JSON Pointer to the offending value, empty for the document itself.
+    pub path: String,
+    pub msg: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.is_empty() {
+            true => write!(f, "/: {}", self.msg),
+            false => write!(f, "{}: {}", self.path, self.msg),
+        }
+    }
+}
+
+/// Validates a document against a schema, returning every violation found.
+pub fn validate(schema: &Value, instance: &Value) -> Vec<Violation> {
+    let validator = match jsonschema::options()
+        .should_validate_formats(true)
+        .build(schema)
+    {
+        Ok(validator) => validator,
+        Err(e) => {
+            return vec![Violation {
+                path: String::new(),
+                msg: format!("invalid config schema: {e}"),
+            }]
+        }
+    };
+    validator
+        .iter_errors(instance)
+        .map(|e| Violation {
+            path: e.instance_path().as_str().to_string(),
+            msg: e.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,184 @@
+// Releases may be signed so that a compromised backend, or anyone in between, can't
+// get a device to deploy configs the release's publisher didn't sign off on. The
+// signature covers a manifest of the configs a deployment of the release writes:
+//
+//     miru-release-manifest-v1
+//     release <release id>
+//     version <release version>
+//     <sha256 hex of the content>  <filepath>
+//     ...
+//
+// with one line per config instance, sorted by filepath, each ending in a newline.
+// Filepaths, and the release's id and version, holding a control character are
+// rejected rather than signed or verified: a newline in one could otherwise forge the
+// lines after it.
+
+// internal crates
+use crate::crypt::base64;
+use crate::crypt::digest::{Algorithm, Digest};
+use crate::deploy::errors::InvalidSignatureErr;
+use crate::models::release::{Release, SignatureAlgorithm};
+use crate::trace;
+
+// external crates
+use openssl::ec::EcKey;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Public};
+use openssl::sign;
+use tracing::error;
+
+pub const MANIFEST_HEADER: &str = "miru-release-manifest-v1";
+
+/// A public key releases may be signed with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrustedKey {
+    pub key_id: String,
+    /// The PEM encoded Ed25519 or P-256 public key.
+    pub public_key: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub trusted_keys: Vec<TrustedKey>,
+    /// Fails deployments of unsigned releases rather than deploying them unverified.
+    pub required: bool,
+}
+
+/// Verifies release signatures against the trusted keys.
+pub struct Verifier {
+    keys: Vec<(String, PKey<Public>)>,
+    required: bool,
+}
+
+impl Verifier {
+    /// Keys which can't be parsed are left out, so releases signed with them fail
+    /// verification.
+    pub fn new(options: &Options) -> Self {
+        let keys = options
+            .trusted_keys
+            .iter()
+            .filter_map(
+                |key| match PKey::public_key_from_pem(key.public_key.as_bytes()) {
+                    Ok(public_key) => Some((key.key_id.clone(), public_key)),
+                    Err(e) => {
+                        error!("ignoring trusted release key '{}': {e}", key.key_id);
+                        None
+                    }
+                },
+            )
+            .collect();
+        Self {
+            keys,
+            required: options.required,
+        }
+    }
+
+    /// Verifies the release's signature over the manifest of the files. Unsigned
+    /// releases pass unless signatures are required.
+    pub fn verify(
+        &self,
+        release: &Release,
+        files: &[(&str, &str)],
+    ) -> Result<(), InvalidSignatureErr> {
+        let invalid = |msg: String| InvalidSignatureErr {
+            release_id: release.id.clone(),
+            msg,
+            trace: trace!(),
+        };
+        let Some(signature) = &release.signature else {
+            return match self.required {
+                true => Err(invalid("the release isn't signed".to_string())),
+                false => Ok(()),
+            };
+        };
+        let manifest = manifest(release, files)?;
+        let Some((_, public_key)) = self
+            .keys
+            .iter()
+            .find(|(key_id, _)| *key_id == signature.key_id)
+        else {
+            return Err(invalid(format!(
+                "the release is signed with untrusted key '{}'",
+                signature.key_id
+            )));
+        };
+        if !key_matches(public_key, signature.algorithm) {
+            return Err(invalid(format!(
+                "key '{}' isn't an {} key",
+                signature.key_id, signature.algorithm
+            )));
+        }
+        let value = base64::decode_bytes_standard(&signature.value)
+            .map_err(|e| invalid(format!("the signature isn't valid base64: {e}")))?;
+
+        let verified = match signature.algorithm {
+            SignatureAlgorithm::Ed25519 => sign::Verifier::new_without_digest(public_key)
+                .and_then(|mut verifier| verifier.verify_oneshot(&value, manifest.as_bytes())),
+            SignatureAlgorithm::EcdsaP256Sha256 => {
+                sign::Verifier::new(MessageDigest::sha256(), public_key).and_then(|mut verifier| {
+                    verifier.update(manifest.as_bytes())?;
+                    verifier.verify(&value)
+                })
+            }
+        };
+        match verified {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(invalid(format!(
+                "the signature doesn't match the configs (signed with key '{}')",
+                signature.key_id
+            ))),
+            // a malformed signature fails to parse rather than to verify
+            Err(e) => Err(invalid(format!("unable to verify the signature: {e}"))),
+        }
+    }
+}
+
+fn key_matches(public_key: &PKey<Public>, algorithm: SignatureAlgorithm) -> bool {
+    match algorithm {
+        SignatureAlgorithm::Ed25519 => public_key.id() == Id::ED25519,
+        SignatureAlgorithm::EcdsaP256Sha256 => public_key
+            .ec_key()
+            .ok()
+            .and_then(|key: EcKey<Public>| key.group().curve_name())
+            .is_some_and(|curve| curve == Nid::X9_62_PRIME256V1),
+    }
+}
+
+/// The manifest a release's signature covers, from the filepath and content of each
+/// config instance the deployment writes. Fails if a filepath, or the release's id or
+/// version, holds a control character.
+pub fn manifest(release: &Release, files: &[(&str, &str)]) -> Result<String, InvalidSignatureErr> {
+    let fields = [
+        ("release id", release.id.as_str()),
+        ("version", &release.version),
+    ];
+    let filepaths = files.iter().map(|(filepath, _)| ("filepath", *filepath));
+    for (field, value) in fields.into_iter().chain(filepaths) {
+        if value.chars().any(char::is_control) {
+            return Err(InvalidSignatureErr {
+                release_id: release.id.clone(),
+                msg: format!("the {field} {value:?} contains a control character"),
+                trace: trace!(),
+            });
+        }
+    }
+
+    let mut files: Vec<(&str, String)> = files
+        .iter()
+        .map(|(filepath, content)| {
+            let digest = Digest::compute(Algorithm::Sha256, content.as_bytes());
+            (*filepath, digest.hex().to_string())
+        })
+        .collect();
+    files.sort();
+
+    let mut manifest = format!(
+        "{MANIFEST_HEADER}\nrelease {}\nversion {}\n",
+        release.id, release.version
+    );
+    for (filepath, hex) in files {
+        manifest.push_str(&format!("{hex}  {filepath}\n"));
+    }
+    Ok(manifest)
+}
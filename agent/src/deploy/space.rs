@@ -0,0 +1,79 @@
+// standard crates
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// internal crates
+use crate::deploy::errors::InsufficientDiskSpaceErr;
+#[cfg(feature = "telemetry")]
+use crate::telemetry::{self, DiskSpace};
+#[cfg(feature = "telemetry")]
+use crate::trace;
+
+/// Refuses to download or stage content while the disks holding the storage layout
+/// (and the deployment directory) are nearly full. A disk which fills mid-deploy
+/// leaves truncated content in the caches.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    /// The least free space each disk must have.
+    pub min_free_bytes: u64,
+    /// The paths whose disks are checked.
+    pub paths: Vec<PathBuf>,
+    /// Whether the last check found a disk low, shared between clones so that a disk
+    /// running low is reported once rather than on every check while it stays low.
+    low: Arc<AtomicBool>,
+}
+
+impl PartialEq for Guard {
+    fn eq(&self, other: &Self) -> bool {
+        self.min_free_bytes == other.min_free_bytes && self.paths == other.paths
+    }
+}
+
+impl Guard {
+    pub fn new(min_free_bytes: u64, paths: Vec<PathBuf>) -> Self {
+        Self {
+            min_free_bytes,
+            paths,
+            low: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Records whether the latest check found a disk low, returning true only when the
+    /// disk has just run low.
+    pub fn ran_low(&self, low: bool) -> bool {
+        let was_low = self.low.swap(low, Ordering::Relaxed);
+        low && !was_low
+    }
+
+    /// Measures the free space on the guarded disks. Disks which can't be measured
+    /// don't hold the sync up.
+    #[cfg(feature = "telemetry")]
+    pub fn check(&self) -> Result<(), InsufficientDiskSpaceErr> {
+        assess(self.min_free_bytes, &telemetry::disks_space(&self.paths))
+    }
+
+    /// Disks can't be measured without the telemetry feature so nothing is refused.
+    #[cfg(not(feature = "telemetry"))]
+    pub fn check(&self) -> Result<(), InsufficientDiskSpaceErr> {
+        Ok(())
+    }
+}
+
+/// Fails for the first disk with less free space than the threshold.
+#[cfg(feature = "telemetry")]
+pub fn assess(min_free_bytes: u64, disks: &[DiskSpace]) -> Result<(), InsufficientDiskSpaceErr> {
+    match disks
+        .iter()
+        .find(|disk| disk.available_bytes < min_free_bytes)
+    {
+        Some(disk) => Err(InsufficientDiskSpaceErr {
+            mount_point: disk.mount_point.clone(),
+            available_bytes: disk.available_bytes,
+            total_bytes: disk.total_bytes,
+            min_free_bytes,
+            trace: trace!(),
+        }),
+        None => Ok(()),
+    }
+}
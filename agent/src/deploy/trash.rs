@@ -0,0 +1,247 @@
+// Files a deployment's removal takes off the device are moved into a trash under the
+// data directory instead of being unlinked, so a removal the backend triggered by
+// mistake can be undone on the device with `trash restore`. Each entry is a
+// directory holding the file's content and a record of where it came from. Entries
+// are purged once they outlive the retention and, oldest first, whenever the trash
+// grows past its size budget.
+
+// standard crates
+use std::io;
+
+// internal crates
+use crate::deploy::errors::{DeployErr, RestoreConflictErr, TrashEntryNotFoundErr};
+use crate::filesys::{self, errors::FileSysErr, CopyOptions, Overwrite, PathExt, WriteOptions};
+use crate::trace;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+const ENTRY_FILE: &str = "entry.json";
+const CONTENT_FILE: &str = "content";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub retention: TimeDelta,
+    /// The most bytes the trashed files may take up. No limit bounds the trash by
+    /// the retention alone.
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            retention: TimeDelta::days(7),
+            max_bytes: Some(64 * 1024 * 1024),
+        }
+    }
+}
+
+/// A trashed file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    /// Where the file was removed from and is restored to.
+    pub filepath: String,
+    pub deployment_id: String,
+    pub config_instance_id: String,
+    pub trashed_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+/// What a purge removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub purged: u64,
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Trash {
+    dir: filesys::Dir,
+    options: Options,
+}
+
+impl Trash {
+    pub fn new(dir: filesys::Dir, options: Options) -> Self {
+        Self { dir, options }
+    }
+
+    pub fn dir(&self) -> &filesys::Dir {
+        &self.dir
+    }
+
+    /// Moves the file into the trash. Nothing is trashed if the file doesn't exist.
+    pub async fn put(
+        &self,
+        file: &filesys::File,
+        deployment_id: &str,
+        config_instance_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Entry>, DeployErr> {
+        if !file.exists() {
+            return Ok(None);
+        }
+        let size_bytes = file.size().await?;
+        let entry_dir = self.new_entry_dir(config_instance_id, now);
+        entry_dir.create().await?;
+        move_file(file, &entry_dir.file(CONTENT_FILE)).await?;
+
+        // a crash before the record is written leaves an entry which isn't listed and
+        // is only removed by purging the whole trash
+        let entry = Entry {
+            id: entry_dir.name()?.to_string(),
+            filepath: file.path().display().to_string(),
+            deployment_id: deployment_id.to_string(),
+            config_instance_id: config_instance_id.to_string(),
+            trashed_at: now,
+            size_bytes,
+        };
+        entry_dir
+            .file(ENTRY_FILE)
+            .write_json(&entry, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        debug!("trashed {} as {}", entry.filepath, entry.id);
+        Ok(Some(entry))
+    }
+
+    fn new_entry_dir(&self, config_instance_id: &str, now: DateTime<Utc>) -> filesys::Dir {
+        let base = format!(
+            "{}-{}",
+            now.format("%Y%m%dT%H%M%S%3fZ"),
+            filesys::file::sanitize_filename(config_instance_id)
+        );
+        let mut dir = self.dir.subdir(&base);
+        let mut n = 1;
+        while dir.exists() {
+            dir = self.dir.subdir(format!("{base}-{n}"));
+            n += 1;
+        }
+        dir
+    }
+
+    /// The trashed files, oldest first.
+    pub async fn list(&self) -> Result<Vec<Entry>, DeployErr> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for dir in self.dir.subdirs().await? {
+            match dir.file(ENTRY_FILE).read_json::<Entry>().await {
+                Ok(entry) => entries.push(entry),
+                Err(e) => debug!(
+                    "skipping incomplete trash entry {}: {e}",
+                    dir.path().display()
+                ),
+            }
+        }
+        entries.sort_by(|a, b| a.trashed_at.cmp(&b.trashed_at).then(a.id.cmp(&b.id)));
+        Ok(entries)
+    }
+
+    /// The bytes the trashed files take up.
+    pub async fn size(&self) -> Result<u64, DeployErr> {
+        Ok(self.list().await?.iter().map(|e| e.size_bytes).sum())
+    }
+
+    async fn find(&self, id: &str) -> Result<Entry, DeployErr> {
+        // looked up among the listed entries so the id can't name a path outside the
+        // trash
+        self.list()
+            .await?
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| {
+                DeployErr::TrashEntryNotFound(TrashEntryNotFoundErr {
+                    id: id.to_string(),
+                    trace: trace!(),
+                })
+            })
+    }
+
+    /// Moves the trashed file back to where it was removed from. A file which has
+    /// since been written there is only replaced if `overwrite` is set.
+    pub async fn restore(&self, id: &str, overwrite: bool) -> Result<Entry, DeployErr> {
+        let entry = self.find(id).await?;
+        let dest = filesys::File::new(&entry.filepath);
+        if !overwrite && dest.exists() {
+            return Err(DeployErr::RestoreConflict(RestoreConflictErr {
+                id: entry.id,
+                filepath: entry.filepath,
+                trace: trace!(),
+            }));
+        }
+        let entry_dir = self.dir.subdir(&entry.id);
+        move_file(&entry_dir.file(CONTENT_FILE), &dest).await?;
+        entry_dir.delete().await?;
+        info!("restored {} from trash entry {}", entry.filepath, entry.id);
+        Ok(entry)
+    }
+
+    /// Permanently removes one trashed file.
+    pub async fn purge(&self, id: &str) -> Result<Entry, DeployErr> {
+        let entry = self.find(id).await?;
+        self.dir.subdir(&entry.id).delete().await?;
+        Ok(entry)
+    }
+
+    /// Permanently removes every trashed file, incomplete entries included.
+    pub async fn purge_all(&self) -> Result<Report, DeployErr> {
+        let entries = self.list().await?;
+        let report = Report {
+            purged: entries.len() as u64,
+            reclaimed_bytes: entries.iter().map(|e| e.size_bytes).sum(),
+        };
+        self.dir.delete().await?;
+        Ok(report)
+    }
+
+    /// Purges the entries older than the retention and then, oldest first, as many
+    /// as it takes to bring the trash within its size budget. Failures are logged
+    /// rather than returned since the trash is housekeeping for the caller.
+    pub async fn expire(&self, now: DateTime<Utc>) -> Report {
+        let mut report = Report::default();
+        let entries = match self.list().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("unable to list the trash: {e}");
+                return report;
+            }
+        };
+        let mut size: u64 = entries.iter().map(|e| e.size_bytes).sum();
+        for entry in entries {
+            let expired = now - entry.trashed_at > self.options.retention;
+            let over_budget = self.options.max_bytes.is_some_and(|max| size > max);
+            if !expired && !over_budget {
+                // entries are oldest first so the rest are kept too
+                break;
+            }
+            if let Err(e) = self.dir.subdir(&entry.id).delete().await {
+                warn!("unable to purge trash entry {}: {e}", entry.id);
+                continue;
+            }
+            size -= entry.size_bytes;
+            report.purged += 1;
+            report.reclaimed_bytes += entry.size_bytes;
+        }
+        if report.purged > 0 {
+            info!(
+                "Purged {} trashed files ({} bytes reclaimed)",
+                report.purged, report.reclaimed_bytes
+            );
+        }
+        report
+    }
+}
+
+/// Moves the file, copying it when the trash is on another filesystem.
+async fn move_file(src: &filesys::File, dest: &filesys::File) -> Result<(), FileSysErr> {
+    match src.move_to(dest, Overwrite::Allow).await {
+        Err(FileSysErr::MoveFileErr(e)) if e.source.kind() == io::ErrorKind::CrossesDevices => {
+            src.copy_to(dest, CopyOptions::OVERWRITE_SYNC).await?;
+            src.delete().await
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,448 @@
+// A deployment directory holds versioned releases of the config files deployed
+// under its `current` link. A deployment's files are written whole into a staging
+// directory, which is renamed to the next `releases/<n>` and only then made live by
+// atomically repointing `current` at it. Applications reading through the link see
+// every file of either the previous release or the new one, never a mix, and a
+// deployment which fails partway leaves the previous release live.
+//
+// The newest releases are kept (see [Versions::with_keep]) so an operator, or a
+// health check which finds the new configs broken, can roll `current` back to the
+// release which was live before it. Each release's deployment and when it was last
+// made live are recorded beside it in `releases/<n>.json`. Rolling back only switches
+// the link; config files deployed in place and the deployment's status on the backend
+// are left as they are.
+
+// standard crates
+use std::path::{Path, PathBuf};
+
+// internal crates
+use crate::deploy::errors::{
+    DeployErr, NoRollbackTargetErr, PathNotAllowedErr, ReleaseNotFoundErr,
+};
+use crate::filesys::{
+    self,
+    errors::{CreateSymlinkErr, FileMetadataErr, FileSysErr, MoveFileErr},
+    Overwrite, PathExt, WriteOptions,
+};
+use crate::trace;
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+pub const CURRENT_LINK: &str = "current";
+const RELEASES_DIR: &str = "releases";
+const STAGING_DIR: &str = "staging";
+
+/// How many releases are kept by default, the live one included, so the previous
+/// release is still on disk to roll back to.
+pub const DEFAULT_KEEP: usize = 2;
+
+/// What's recorded about a release beside its directory.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct Metadata {
+    #[serde(default)]
+    deployment_id: Option<String>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    activated_at: Option<DateTime<Utc>>,
+    /// Set once the release has been rolled back from, so it isn't rolled back to.
+    #[serde(default)]
+    rolled_back: bool,
+}
+
+/// A release on disk, as reported to operators.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Release {
+    pub number: u64,
+    /// Whether the `current` link points at the release.
+    pub live: bool,
+    pub deployment_id: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the release was last made live, if ever.
+    pub activated_at: Option<DateTime<Utc>>,
+    pub rolled_back: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Versions {
+    dir: filesys::Dir,
+    keep: usize,
+}
+
+impl Versions {
+    pub fn new(dir: filesys::Dir) -> Self {
+        Self {
+            dir,
+            keep: DEFAULT_KEEP,
+        }
+    }
+
+    /// Keeps the newest `keep` releases, the live one included. At least the live
+    /// release is always kept.
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        self
+    }
+
+    pub fn dir(&self) -> &filesys::Dir {
+        &self.dir
+    }
+
+    pub fn keep(&self) -> usize {
+        self.keep
+    }
+
+    pub fn current_link(&self) -> filesys::File {
+        self.dir.file(CURRENT_LINK)
+    }
+
+    /// The link and directories the releases are kept in under the deployment
+    /// directory.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        vec![
+            self.current_link().path().to_path_buf(),
+            self.dir.subdir(RELEASES_DIR).path().to_path_buf(),
+            self.dir.subdir(STAGING_DIR).path().to_path_buf(),
+        ]
+    }
+
+    pub fn release_dir(&self, number: u64) -> filesys::Dir {
+        self.dir.subdir(RELEASES_DIR).subdir(number.to_string())
+    }
+
+    fn metadata_file(&self, number: u64) -> filesys::File {
+        self.dir
+            .subdir(RELEASES_DIR)
+            .file(&format!("{number}.json"))
+    }
+
+    async fn read_metadata(&self, number: u64) -> Metadata {
+        let file = self.metadata_file(number);
+        if !file.exists() {
+            return Metadata::default();
+        }
+        file.read_json().await.unwrap_or_else(|e| {
+            warn!(
+                "unable to read the metadata of release {number} of {}: {e}",
+                self.dir
+            );
+            Metadata::default()
+        })
+    }
+
+    async fn write_metadata(&self, number: u64, metadata: &Metadata) -> Result<(), FileSysErr> {
+        self.metadata_file(number)
+            .write_json(metadata, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+    }
+
+    /// Where a config file is written relative to a release if it's deployed through
+    /// the `current` link.
+    pub fn relative_path(&self, filepath: &Path) -> Option<PathBuf> {
+        filepath
+            .strip_prefix(self.current_link().path())
+            .ok()
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+    }
+
+    /// Whether a filepath is in the deployment directory without being deployed
+    /// through the `current` link, e.g. the link itself or a file of an older release.
+    pub fn is_reserved(&self, filepath: &Path) -> bool {
+        filepath.starts_with(self.dir.path()) && self.relative_path(filepath).is_none()
+    }
+
+    /// The numbers of the releases on disk, oldest first.
+    pub async fn releases(&self) -> Result<Vec<u64>, FileSysErr> {
+        let releases_dir = self.dir.subdir(RELEASES_DIR);
+        if !releases_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut numbers: Vec<u64> = releases_dir
+            .subdirs()
+            .await?
+            .iter()
+            .filter_map(|dir| dir.name().ok()?.parse().ok())
+            .collect();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    /// The release the `current` link points at, if it exists.
+    pub async fn current(&self) -> Result<Option<u64>, DeployErr> {
+        let link = self.current_link();
+        match tokio::fs::symlink_metadata(link.path()).await {
+            Ok(metadata) if metadata.file_type().is_symlink() => {}
+            Ok(_) => {
+                return Err(PathNotAllowedErr {
+                    filepath: link.path().display().to_string(),
+                    reason: "the deployment directory's current link is not a symlink".to_string(),
+                    trace: trace!(),
+                }
+                .into());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(metadata_err(&link, e).into()),
+        }
+        let target = tokio::fs::read_link(link.path())
+            .await
+            .map_err(|e| metadata_err(&link, e))?;
+        Ok(target
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.parse().ok()))
+    }
+
+    /// Whether a release other than the newest is live, i.e. the deployment directory
+    /// was rolled back.
+    pub async fn is_rolled_back(&self) -> Result<bool, DeployErr> {
+        let Some(current) = self.current().await? else {
+            return Ok(false);
+        };
+        Ok(self
+            .releases()
+            .await?
+            .last()
+            .is_some_and(|newest| *newest != current))
+    }
+
+    /// Creates an empty directory to write a release's files into. Nothing in it is
+    /// visible through the `current` link until it's promoted and activated.
+    pub async fn stage(&self) -> Result<filesys::Dir, FileSysErr> {
+        let staged = self
+            .dir
+            .subdir(STAGING_DIR)
+            .subdir(uuid::Uuid::new_v4().to_string());
+        staged.create().await?;
+        Ok(staged)
+    }
+
+    /// The releases on disk, oldest first.
+    pub async fn list(&self) -> Result<Vec<Release>, DeployErr> {
+        let current = self.current().await?;
+        let mut releases = Vec::new();
+        for number in self.releases().await? {
+            let metadata = self.read_metadata(number).await;
+            releases.push(Release {
+                number,
+                live: Some(number) == current,
+                deployment_id: metadata.deployment_id,
+                created_at: metadata.created_at,
+                activated_at: metadata.activated_at,
+                rolled_back: metadata.rolled_back,
+            });
+        }
+        Ok(releases)
+    }
+
+    /// Renames a staged directory to the next release for the deployment, returning
+    /// its number.
+    pub async fn promote(
+        &self,
+        staged: &filesys::Dir,
+        deployment_id: &str,
+    ) -> Result<u64, FileSysErr> {
+        let number = self.releases().await?.last().map_or(1, |last| last + 1);
+        let metadata = Metadata {
+            deployment_id: Some(deployment_id.to_string()),
+            created_at: Some(Utc::now()),
+            ..Metadata::default()
+        };
+        self.write_metadata(number, &metadata).await?;
+        if let Err(e) = staged
+            .move_to(&self.release_dir(number), Overwrite::Deny)
+            .await
+        {
+            let _ = self.metadata_file(number).delete().await;
+            return Err(e);
+        }
+        debug!("staged release {number} in {}", self.dir);
+        Ok(number)
+    }
+
+    /// Atomically points the `current` link at the release by renaming a new link
+    /// over it.
+    pub async fn activate(&self, number: u64) -> Result<(), DeployErr> {
+        // fails if a directory or file is in the link's place, which renaming would
+        // replace or fail on
+        let previous = self.current().await?;
+        let release = self.release_dir(number);
+        release.assert_exists()?;
+
+        let link = self.current_link();
+        let tmp_link = self
+            .dir
+            .file(&format!(".{CURRENT_LINK}.{}", uuid::Uuid::new_v4()));
+        // relative so the deployment directory can be moved or mounted elsewhere
+        let target = Path::new(RELEASES_DIR).join(number.to_string());
+        tokio::fs::symlink(&target, tmp_link.path())
+            .await
+            .map_err(|e| {
+                FileSysErr::CreateSymlinkErr(CreateSymlinkErr {
+                    source: Box::new(e),
+                    file: filesys::File::new(release.path()),
+                    link: tmp_link.clone(),
+                    trace: trace!(),
+                })
+            })?;
+        if let Err(e) = tokio::fs::rename(tmp_link.path(), link.path()).await {
+            let _ = tmp_link.delete().await;
+            return Err(FileSysErr::MoveFileErr(MoveFileErr {
+                source: Box::new(e),
+                src_file: tmp_link,
+                dest_file: link,
+                trace: trace!(),
+            })
+            .into());
+        }
+        match previous {
+            Some(previous) => info!("switched {} from release {previous} to {number}", self.dir),
+            None => info!("switched {} to release {number}", self.dir),
+        }
+
+        // the release is live regardless, so a failure to record it is only logged
+        let mut metadata = self.read_metadata(number).await;
+        metadata.activated_at = Some(Utc::now());
+        metadata.rolled_back = false;
+        if let Err(e) = self.write_metadata(number, &metadata).await {
+            warn!(
+                "unable to record the activation of release {number} of {}: {e}",
+                self.dir
+            );
+        }
+        Ok(())
+    }
+
+    /// Points the `current` link back at a release, returning its number. Without a
+    /// release number, it's the release which was live most recently before the
+    /// current one and hasn't been rolled back from itself.
+    pub async fn rollback(&self, to: Option<u64>) -> Result<u64, DeployErr> {
+        let current = self.current().await?;
+        let releases = self.list().await?;
+        let target = match to {
+            Some(number) => releases
+                .iter()
+                .find(|release| release.number == number)
+                .map(|release| release.number)
+                .ok_or_else(|| ReleaseNotFoundErr {
+                    dir: self.dir.to_string(),
+                    number,
+                    trace: trace!(),
+                })?,
+            None => releases
+                .iter()
+                .filter(|release| !release.live && !release.rolled_back)
+                .filter_map(|release| release.activated_at.map(|at| (at, release.number)))
+                .max()
+                .map(|(_, number)| number)
+                .ok_or_else(|| NoRollbackTargetErr {
+                    dir: self.dir.to_string(),
+                    trace: trace!(),
+                })?,
+        };
+        if Some(target) == current {
+            return Ok(target);
+        }
+
+        self.activate(target).await?;
+        if let Some(current) = current {
+            let mut metadata = self.read_metadata(current).await;
+            metadata.rolled_back = true;
+            if let Err(e) = self.write_metadata(current, &metadata).await {
+                warn!(
+                    "unable to record the rollback of release {current} of {}: {e}",
+                    self.dir
+                );
+            }
+        }
+        info!("rolled {} back to release {target}", self.dir);
+        Ok(target)
+    }
+
+    /// Points the `current` link back at the release which was live before a failed
+    /// deploy, or removes the link if none was. Returns whether it did; failures are
+    /// logged since the deployment has already failed.
+    pub async fn restore(&self, previous: Option<u64>) -> bool {
+        let result = match previous {
+            Some(number) => self.activate(number).await,
+            None => self.current_link().delete().await.map_err(DeployErr::from),
+        };
+        if let Err(e) = result {
+            warn!(
+                "unable to restore the previous release of {}: {e}",
+                self.dir
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Removes a staged directory which never became a release. Failures are logged
+    /// since the deployment has already failed.
+    pub async fn discard(&self, dir: &filesys::Dir) {
+        if let Err(e) = dir.delete().await {
+            warn!("unable to remove unused release {dir}: {e}");
+        }
+    }
+
+    /// Removes a release which never went live, along with its metadata.
+    pub async fn discard_release(&self, number: u64) {
+        self.discard(&self.release_dir(number)).await;
+        if let Err(e) = self.metadata_file(number).delete().await {
+            warn!(
+                "unable to remove the metadata of release {number} of {}: {e}",
+                self.dir
+            );
+        }
+    }
+
+    /// Removes the releases older than the newest ones kept, sparing the live
+    /// release, and anything left in the staging directory by an interrupted deploy.
+    /// Failures are logged rather than returned since the new release is already live.
+    pub async fn prune(&self) {
+        let current = match self.current().await {
+            Ok(current) => current,
+            Err(e) => {
+                warn!("unable to read the current release of {}: {e}", self.dir);
+                return;
+            }
+        };
+        let releases = match self.releases().await {
+            Ok(releases) => releases,
+            Err(e) => {
+                warn!("unable to list the releases of {}: {e}", self.dir);
+                return;
+            }
+        };
+        let stale = releases
+            .iter()
+            .rev()
+            .skip(self.keep)
+            .filter(|number| Some(**number) != current);
+        for number in stale {
+            let release = self.release_dir(*number);
+            match release.delete().await {
+                Ok(()) => debug!("removed release {number} of {}", self.dir),
+                Err(e) => {
+                    warn!("unable to remove release {release}: {e}");
+                    continue;
+                }
+            }
+            let _ = self.metadata_file(*number).delete().await;
+        }
+        if let Err(e) = self.dir.subdir(STAGING_DIR).delete().await {
+            warn!("unable to clear the staging directory of {}: {e}", self.dir);
+        }
+    }
+}
+
+fn metadata_err(link: &filesys::File, e: std::io::Error) -> FileSysErr {
+    FileSysErr::FileMetadataErr(FileMetadataErr {
+        file: link.clone(),
+        source: Box::new(e),
+        trace: trace!(),
+    })
+}
@@ -0,0 +1,31 @@
+// internal crates
+use crate::crypt::salt::Salt;
+use crate::diagnostics::errors::DiagnosticsErr;
+use crate::platform;
+use crate::storage::{self, Layout};
+
+// external crates
+use tracing::warn;
+
+/// The device's identifiers paired with their anonymized form, for
+/// [crate::diagnostics::Redactor::with_identifiers]: the device id, if it can be
+/// resolved, and the host name. The device's salt is created if it doesn't exist yet.
+pub async fn identifiers(layout: &Layout) -> Result<Vec<(String, String)>, DiagnosticsErr> {
+    let salt = Salt::load_or_create(&layout.auth().device_salt()).await?;
+
+    let mut identifiers = Vec::new();
+    match storage::resolve_device_id(layout).await {
+        Ok(device_id) => identifiers.push(device_id),
+        Err(e) => warn!("unable to resolve the device id to anonymize it: {e}"),
+    }
+    identifiers.push(platform::host_name());
+
+    identifiers
+        .into_iter()
+        .filter(|identifier| !identifier.is_empty())
+        .map(|identifier| {
+            let anonymized = salt.anonymize(&identifier)?;
+            Ok((identifier, anonymized))
+        })
+        .collect()
+}
@@ -0,0 +1,88 @@
+// internal crates
+use crate::diagnostics::errors::DiagnosticsErr;
+use crate::diagnostics::redact::Redactor;
+use crate::filesys::{self, PathExt, WriteOptions};
+use crate::storage::Layout;
+
+// external crates
+use serde_json::Value;
+use tracing::warn;
+
+const OMITTED: &str = "[OMITTED: unable to parse file for redaction]";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Bundle {
+    pub entries: Vec<Entry>,
+}
+
+impl Bundle {
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Writes each entry to a file of the same name under `dir`.
+    pub async fn write(&self, dir: &filesys::Dir) -> Result<(), DiagnosticsErr> {
+        for entry in &self.entries {
+            dir.file(&entry.name)
+                .write_string(&entry.content, WriteOptions::OVERWRITE_ATOMIC)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the settings file, the device file, and the log files in `log_dir`
+/// into a bundle, passing every entry through `redactor`. The auth directory and
+/// config instance contents are never read. JSON files which can't be parsed are
+/// omitted rather than included unredacted.
+pub async fn assemble(
+    layout: &Layout,
+    log_dir: &filesys::Dir,
+    redactor: &Redactor,
+) -> Result<Bundle, DiagnosticsErr> {
+    let mut bundle = Bundle::default();
+
+    for (name, file) in [
+        ("settings.json", layout.settings()),
+        ("device.json", layout.device()),
+    ] {
+        if !file.exists() {
+            warn!("support bundle: skipping missing file {file}");
+            continue;
+        }
+        let content = match file.read_json::<Value>().await {
+            Ok(mut value) => {
+                redactor.redact_json(&mut value);
+                serde_json::to_string_pretty(&value).unwrap_or_else(|_| OMITTED.to_string())
+            }
+            Err(e) => {
+                warn!("support bundle: omitting {file}: {e}");
+                OMITTED.to_string()
+            }
+        };
+        bundle.entries.push(Entry {
+            name: name.to_string(),
+            content,
+        });
+    }
+
+    if log_dir.exists() {
+        let mut log_files = log_dir.files().await?;
+        log_files.sort_by(|a, b| a.path().cmp(b.path()));
+        for file in log_files {
+            let bytes = file.read_bytes().await?;
+            bundle.entries.push(Entry {
+                name: format!("logs/{}", file.name()?),
+                content: redactor.redact_text(&String::from_utf8_lossy(&bytes)),
+            });
+        }
+    }
+
+    Ok(bundle)
+}
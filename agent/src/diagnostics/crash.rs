@@ -0,0 +1,228 @@
+// A panic anywhere in the agent writes a crash report before the thread unwinds, so
+// field crashes are visible without someone pulling the journal off the device. The
+// report waits under `crash/pending/` until the crash reports worker uploads it and
+// the most recent one is kept in `crash/last_crash.json` for `status`. Reports are only
+// queued when they're uploaded, and only the most recent ones are kept queued so that a
+// crash loop on a device which can't reach the backend doesn't fill its disk.
+
+// standard crates
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::io::{self, Write};
+use std::panic::PanicHookInfo;
+use std::time::SystemTime;
+
+// internal crates
+use crate::diagnostics::errors::DiagnosticsErr;
+use crate::diagnostics::redact::Redactor;
+use crate::filesys::{self, PathExt};
+use crate::logs::RecentLines;
+use crate::storage::layout::CrashLayout;
+use crate::version;
+
+// external crates
+use atomicwrites::{AllowOverwrite, AtomicFile};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// The most reports waiting to be uploaded. Older reports are dropped beyond it.
+pub const MAX_PENDING: usize = 10;
+
+/// The most bytes of reports waiting to be uploaded, though the newest report is kept
+/// whatever its size.
+pub const MAX_PENDING_BYTES: u64 = 4 * 1024 * 1024;
+
+/// The build of the agent which crashed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Build {
+    pub version: String,
+    pub commit: String,
+    pub build_date: String,
+    pub rust_version: String,
+    pub os: String,
+    pub arch: String,
+}
+
+impl Build {
+    pub fn current() -> Self {
+        Self {
+            version: version::VERSION.to_string(),
+            commit: version::COMMIT.to_string(),
+            build_date: version::BUILD_DATE.to_string(),
+            rust_version: version::RUST_VERSION.to_string(),
+            os: version::OS.to_string(),
+            arch: version::ARCH.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub id: String,
+    pub crashed_at: DateTime<Utc>,
+    pub message: String,
+    /// Where the panic was raised, as `file:line:column`.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+    pub build: Build,
+    /// The last lines the agent logged before the panic, oldest first.
+    pub log_lines: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub layout: CrashLayout,
+    pub recent_lines: RecentLines,
+    /// Applied to the message and log lines before the report is written, since it's
+    /// uploaded to the backend.
+    pub redactor: Option<Redactor>,
+    /// Queues reports for upload. Only the last crash is written otherwise.
+    pub upload: bool,
+}
+
+impl Report {
+    /// A report of a panic with `message` raised at `location`, captured from the
+    /// current thread.
+    pub fn capture(message: &str, location: Option<String>, options: &Options) -> Self {
+        let redact = |text: &str| match &options.redactor {
+            Some(redactor) => redactor.redact_text(text),
+            None => text.to_string(),
+        };
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            crashed_at: Utc::now(),
+            message: redact(message),
+            location,
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: Backtrace::force_capture().to_string(),
+            build: Build::current(),
+            log_lines: options
+                .recent_lines
+                .lines()
+                .iter()
+                .map(|line| redact(line))
+                .collect(),
+        }
+    }
+
+    pub fn from_panic(info: &PanicHookInfo<'_>, options: &Options) -> Self {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+        Self::capture(&panic_message(info.payload()), location, options)
+    }
+}
+
+/// The message a panic was raised with, if it was raised with one.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "panicked without a message".to_string()
+    }
+}
+
+/// Writes a crash report for every panic from here on, then runs the hook installed
+/// before it (which prints the panic to stderr by default).
+pub fn install(options: Options) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = Report::from_panic(info, &options);
+        if let Err(e) = write(&options.layout, &report, options.upload) {
+            eprintln!("Failed to write the crash report: {e}");
+        }
+        previous(info);
+    }));
+}
+
+/// Writes the report as the last crash and, if it's to be uploaded, queues it for
+/// upload. The write is blocking since panic hooks can't await.
+pub fn write(layout: &CrashLayout, report: &Report, upload: bool) -> Result<(), io::Error> {
+    let json = serde_json::to_vec_pretty(report)?;
+    let mut files = vec![layout.last_crash()];
+    if upload {
+        files.push(pending_file(layout, &report.id));
+    }
+    for file in files {
+        if let Some(parent) = file.path().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        AtomicFile::new(file.path(), AllowOverwrite).write(|f| f.write_all(&json))?;
+    }
+    if upload {
+        prune_pending(layout, &pending_file(layout, &report.id))?;
+    }
+    Ok(())
+}
+
+/// Deletes the oldest reports waiting to be uploaded beyond [MAX_PENDING] reports or
+/// [MAX_PENDING_BYTES], keeping the one just written.
+fn prune_pending(layout: &CrashLayout, written: &filesys::File) -> Result<(), io::Error> {
+    let mut reports = Vec::new();
+    for entry in std::fs::read_dir(layout.pending().path())? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let path = entry.path();
+        reports.push((&path == written.path(), modified, metadata.len(), path));
+    }
+    // the one just written, then newest first
+    reports.sort_by_key(|report| std::cmp::Reverse((report.0, report.1)));
+
+    let mut kept_bytes = 0;
+    for (i, (_, _, len, path)) in reports.into_iter().enumerate() {
+        kept_bytes += len;
+        if i == 0 || (i < MAX_PENDING && kept_bytes <= MAX_PENDING_BYTES) {
+            continue;
+        }
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn pending_file(layout: &CrashLayout, id: &str) -> filesys::File {
+    layout.pending().file(&format!("{id}.json"))
+}
+
+/// The reports waiting to be uploaded, oldest first. Reports which can't be read are
+/// deleted since they'd never upload.
+pub async fn pending(layout: &CrashLayout) -> Result<Vec<Report>, DiagnosticsErr> {
+    let dir = layout.pending();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut reports = Vec::new();
+    for file in dir.files().await? {
+        match file.read_json::<Report>().await {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                warn!("deleting unreadable crash report {file}: {e}");
+                file.delete().await?;
+            }
+        }
+    }
+    reports.sort_by_key(|report| report.crashed_at);
+    Ok(reports)
+}
+
+/// Removes a report once it's uploaded. The last crash is kept.
+pub async fn remove_pending(layout: &CrashLayout, id: &str) -> Result<(), DiagnosticsErr> {
+    pending_file(layout, id).delete().await?;
+    Ok(())
+}
+
+/// The most recent crash, if the agent has crashed since it was installed.
+pub async fn last_crash(layout: &CrashLayout) -> Option<Report> {
+    let file = layout.last_crash();
+    if !file.exists() {
+        return None;
+    }
+    file.read_json::<Report>().await.ok()
+}
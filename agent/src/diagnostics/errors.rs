@@ -0,0 +1,42 @@
+// internal crates
+use crate::crypt::CryptErr;
+use crate::errors::Trace;
+use crate::filesys;
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid redaction pattern '{pattern}': {source}")]
+pub struct InvalidPatternErr {
+    pub pattern: String,
+    pub source: regex::Error,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidPatternErr {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiagnosticsErr {
+    #[error(transparent)]
+    InvalidPatternErr(InvalidPatternErr),
+    #[error(transparent)]
+    FileSysErr(filesys::FileSysErr),
+    #[error(transparent)]
+    CryptErr(CryptErr),
+}
+
+impl From<filesys::FileSysErr> for DiagnosticsErr {
+    fn from(e: filesys::FileSysErr) -> Self {
+        Self::FileSysErr(e)
+    }
+}
+
+impl From<CryptErr> for DiagnosticsErr {
+    fn from(e: CryptErr) -> Self {
+        Self::CryptErr(e)
+    }
+}
+
+crate::impl_error!(DiagnosticsErr {
+    InvalidPatternErr,
+    FileSysErr,
+    CryptErr,
+});
@@ -0,0 +1,9 @@
+pub mod anonymize;
+pub mod bundle;
+pub mod crash;
+pub mod errors;
+pub mod redact;
+
+pub use self::bundle::{assemble, Bundle, Entry};
+pub use self::errors::DiagnosticsErr;
+pub use self::redact::{Redactor, Rules, REDACTED};
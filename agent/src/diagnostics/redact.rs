@@ -0,0 +1,183 @@
+// internal crates
+use crate::diagnostics::errors::{DiagnosticsErr, InvalidPatternErr};
+use crate::trace;
+
+// external crates
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Redaction rules applied to everything included in a support bundle.
+///
+/// `fields` are dotted JSON paths anchored at the document root, where a `*`
+/// segment matches any key; the value at a matching path is replaced entirely.
+/// `patterns` are regular expressions whose matches are replaced in log lines
+/// and in every JSON string value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            fields: [
+                "token",
+                "session_id",
+                "*.token",
+                "*.private_key",
+                "*.secret",
+                "*.proxy_username",
+                "*.proxy_password",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            patterns: [
+                // JSON web tokens
+                r"eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*",
+                // PEM encoded keys and certificates
+                r"(?s)-----BEGIN [A-Z0-9 ]+-----.*?-----END [A-Z0-9 ]+-----",
+                // authorization headers
+                r"(?i)bearer\s+[A-Za-z0-9._~+/=-]+",
+                // key/value pairs which look like credentials
+                r#"(?i)"?(?:token|secret|password|private_key|api_key)"?\s*[:=]\s*"?[^\s",}]+"?"#,
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+impl Rules {
+    /// Adds the given rules to this set of rules.
+    pub fn extend(mut self, other: Rules) -> Self {
+        self.fields.extend(other.fields);
+        self.patterns.extend(other.patterns);
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Redactor {
+    fields: Vec<Vec<String>>,
+    patterns: Vec<Regex>,
+    /// Identifiers replaced by their anonymized form, longest first.
+    identifiers: Vec<(String, String)>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&Rules::default()).expect("default redaction rules must be valid")
+    }
+}
+
+impl Redactor {
+    pub fn new(rules: &Rules) -> Result<Self, DiagnosticsErr> {
+        let fields = rules
+            .fields
+            .iter()
+            .map(|field| field.split('.').map(String::from).collect())
+            .collect();
+        let patterns = rules
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    DiagnosticsErr::InvalidPatternErr(InvalidPatternErr {
+                        pattern: pattern.clone(),
+                        source: e,
+                        trace: trace!(),
+                    })
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            fields,
+            patterns,
+            identifiers: Vec::new(),
+        })
+    }
+
+    /// Replaces every occurrence of each identifier with its anonymized form, e.g. the
+    /// device id with its salted hash. Empty identifiers are ignored.
+    pub fn with_identifiers(
+        mut self,
+        identifiers: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        self.identifiers.extend(
+            identifiers
+                .into_iter()
+                .filter(|(identifier, _)| !identifier.is_empty()),
+        );
+        // so identifiers containing others are replaced whole
+        self.identifiers
+            .sort_by_key(|(identifier, _)| std::cmp::Reverse(identifier.len()));
+        self
+    }
+
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut redacted = text.to_string();
+        for (identifier, anonymized) in &self.identifiers {
+            redacted = redacted.replace(identifier, anonymized);
+        }
+        for pattern in &self.patterns {
+            redacted = pattern.replace_all(&redacted, REDACTED).into_owned();
+        }
+        redacted
+    }
+
+    pub fn redact_json(&self, value: &mut Value) {
+        let mut path = Vec::new();
+        self.redact_value(value, &mut path);
+    }
+
+    /// Redacts a value found at a dotted path, e.g. a settings field such as
+    /// `network.proxy_password`.
+    pub fn redact_field(&self, field: &str, value: &mut Value) {
+        let mut path = field.split('.').map(String::from).collect();
+        self.redact_value(value, &mut path);
+    }
+
+    fn redact_value(&self, value: &mut Value, path: &mut Vec<String>) {
+        if !path.is_empty() && self.matches_field(path) {
+            *value = Value::String(REDACTED.to_string());
+            return;
+        }
+        match value {
+            Value::String(s) => {
+                let redacted = self.redact_text(s);
+                *s = redacted;
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.redact_value(item, path);
+                }
+            }
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    path.push(key.clone());
+                    self.redact_value(child, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn matches_field(&self, path: &[String]) -> bool {
+        self.fields.iter().any(|field| {
+            field.len() == path.len()
+                && field
+                    .iter()
+                    .zip(path)
+                    .all(|(segment, key)| segment == "*" || segment == key)
+        })
+    }
+}
@@ -0,0 +1,97 @@
+// Decides whether errors the agent doesn't know about are network connection errors.
+// Network connection errors are expected on devices with flaky connectivity, so they
+// don't count toward the syncer's error streak (and its cooldown) the way logic errors
+// do. Embedders with their own transports wrap their errors in
+// `http::errors::TransportErr` and register a classifier for their error types here.
+
+// standard crates
+use std::error::Error as StdError;
+use std::io;
+use std::sync::{Arc, RwLock};
+
+/// Returns `Some(true)` for network connection errors, `Some(false)` for errors which
+/// are definitely not, and `None` to leave the decision to the next classifier.
+pub type Classifier = Arc<dyn Fn(&(dyn StdError + 'static)) -> Option<bool> + Send + Sync>;
+
+static CLASSIFIERS: RwLock<Vec<Classifier>> = RwLock::new(Vec::new());
+
+/// Registers a classifier. Classifiers are consulted in registration order, before the
+/// built-in rules, for the error and then each of its sources.
+pub fn register<F>(classifier: F)
+where
+    F: Fn(&(dyn StdError + 'static)) -> Option<bool> + Send + Sync + 'static,
+{
+    let mut classifiers = CLASSIFIERS.write().unwrap_or_else(|e| e.into_inner());
+    classifiers.push(Arc::new(classifier));
+}
+
+/// Registers a classifier for a single error type, matched anywhere in an error's
+/// source chain.
+pub fn register_type<E, F>(classify: F)
+where
+    E: StdError + 'static,
+    F: Fn(&E) -> bool + Send + Sync + 'static,
+{
+    register(move |err| err.downcast_ref::<E>().map(&classify));
+}
+
+/// Classifies an error by walking its source chain. The first error in the chain
+/// which a registered classifier or a built-in rule recognizes decides; errors nothing
+/// recognizes are not network connection errors.
+pub fn is_network_conn_err(err: &(dyn StdError + 'static)) -> bool {
+    // clone the classifiers so they may themselves classify (or register) errors
+    let classifiers = CLASSIFIERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(is_network_conn_err) = classifiers
+            .iter()
+            .find_map(|classify| classify(err))
+            .or_else(|| builtin(err))
+        {
+            return is_network_conn_err;
+        }
+        current = source(err);
+    }
+    false
+}
+
+fn source<'a>(err: &'a (dyn StdError + 'static)) -> Option<&'a (dyn StdError + 'static)> {
+    // io errors report the source of the error they wrap rather than the error itself
+    match err.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+        Some(inner) => Some(inner),
+        None => err.source(),
+    }
+}
+
+fn builtin(err: &(dyn StdError + 'static)) -> Option<bool> {
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        // io errors often only wrap the interesting error
+        if err.get_ref().is_some() && err.kind() == io::ErrorKind::Other {
+            return None;
+        }
+        return Some(matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::AddrNotAvailable
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::NetworkUnreachable
+                | io::ErrorKind::NetworkDown
+        ));
+    }
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        if err.is_connect() || err.is_timeout() {
+            return Some(true);
+        }
+        return None;
+    }
+    None
+}
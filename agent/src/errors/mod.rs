@@ -1,5 +1,7 @@
+pub mod classify;
+
 // external crates
-use axum::http::StatusCode;
+use reqwest::StatusCode;
 #[allow(unused_imports)]
 use tracing::{error, info, trace, warn};
 
@@ -11,6 +13,15 @@ pub enum Code {
     ResourceNotFound,
     CursorExpired,
     MalformedCursor,
+    InvalidQuery,
+    DependencyCycle,
+    SchemaViolation,
+    InvalidLogLevel,
+    LogLevelLocked,
+    ResourceConflict,
+    Overloaded,
+    InsufficientDiskSpace,
+    InvalidSignature,
     BackendError(String),
 }
 
@@ -21,6 +32,15 @@ impl Code {
             Self::ResourceNotFound => "resource_not_found",
             Self::CursorExpired => "cursor_expired",
             Self::MalformedCursor => "malformed_cursor",
+            Self::InvalidQuery => "invalid_query",
+            Self::DependencyCycle => "dependency_cycle",
+            Self::SchemaViolation => "schema_violation",
+            Self::InvalidLogLevel => "invalid_log_level",
+            Self::LogLevelLocked => "log_level_locked",
+            Self::ResourceConflict => "resource_conflict",
+            Self::Overloaded => "overloaded",
+            Self::InsufficientDiskSpace => "insufficient_disk_space",
+            Self::InvalidSignature => "invalid_signature",
             Self::BackendError(code) => code,
         }
     }
@@ -16,6 +16,9 @@ pub struct SpawnOptions {
     pub buffer_size: usize,
     pub max_retained: usize,
     pub broadcast_capacity: usize,
+    /// Whether events are written to the log file so they can be replayed after a
+    /// restart.
+    pub persist: bool,
 }
 
 impl Default for SpawnOptions {
@@ -24,6 +27,7 @@ impl Default for SpawnOptions {
             buffer_size: 64,
             max_retained: DEFAULT_MAX_RETAINED,
             broadcast_capacity: 256,
+            persist: true,
         }
     }
 }
@@ -92,7 +96,10 @@ impl EventHub {
         log_file: filesys::File,
         opts: SpawnOptions,
     ) -> Result<(Self, JoinHandle<()>), EventsErr> {
-        let store = EventStore::init(log_file, opts.max_retained).await?;
+        let mut store = EventStore::init(log_file, opts.max_retained).await?;
+        if !opts.persist {
+            store = store.without_persistence();
+        }
         let (broadcast_tx, _) = broadcast::channel(opts.broadcast_capacity);
         let (sender, receiver) = mpsc::channel(opts.buffer_size);
 
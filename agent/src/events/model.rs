@@ -14,10 +14,86 @@ pub type EventTypeFilter = HashSet<String>;
 
 pub const DEPLOYMENT_DEPLOYED: &str = "deployment.deployed";
 pub const DEPLOYMENT_REMOVED: &str = "deployment.removed";
+pub const DEPLOYMENT_FAILED: &str = "deployment.failed";
+pub const CONFIG_CHANGED: &str = "config.changed";
+pub const SYNC_FAILED: &str = "sync.failed";
+pub const AUTH_REVOKED: &str = "auth.revoked";
+pub const DISK_LOW: &str = "disk.low";
+pub const AGENT_SAFE_MODE: &str = "agent.safe_mode";
 
 pub type DeploymentDeployedEvent = device_server::DeploymentDeployedEvent;
 pub type DeploymentRemovedEvent = device_server::DeploymentRemovedEvent;
 
+/// Emitted when a deployment exhausts its retries and will no longer be applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentFailedEvent {
+    pub deployment_id: String,
+    pub release_id: String,
+    pub attempts: u32,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChangeKind {
+    /// A config file was written where no deployed config instance had one.
+    Deployed,
+    /// A deployed config file was rewritten, by another config instance or the same one
+    /// being redeployed.
+    Updated,
+    /// A deployed config file was removed.
+    Removed,
+}
+
+/// A deployed config file the agent wrote or removed. A removed file's config
+/// instance and deployment are the ones which had deployed it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    pub kind: ConfigChangeKind,
+    pub filepath: String,
+    pub config_instance_id: String,
+    pub config_type_name: String,
+    pub deployment_id: String,
+}
+
+/// Emitted when applying deployments changes deployed config files, so applications
+/// can reload their configs without watching the files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChangedEvent {
+    pub changes: Vec<ConfigChange>,
+}
+
+/// Emitted when a sync with the backend fails. Only the first of consecutive network
+/// failures is emitted since devices are expected to go offline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncFailedEvent {
+    /// What failed: `network`, `auth`, `server`, `filesystem` or `other`.
+    pub failure_class: String,
+    /// The consecutive failures of this class, including this one.
+    pub streak: u32,
+    pub error: String,
+}
+
+/// Emitted when the backend rejects the device's credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthRevokedEvent {
+    pub error: String,
+}
+
+/// Emitted when the free space of a filesystem the agent writes to runs low.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskLowEvent {
+    pub path: String,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Emitted when the agent starts in safe mode after crashing repeatedly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafeModeEvent {
+    pub recent_crashes: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     pub id: i64,
@@ -84,4 +160,55 @@ impl EventArgs {
             },
         )
     }
+
+    pub fn failed(deployment: &models::Deployment, error: &str) -> Result<Self, EventsErr> {
+        Self::new(
+            DEPLOYMENT_FAILED,
+            DeploymentFailedEvent {
+                deployment_id: deployment.id.clone(),
+                release_id: deployment.release_id.clone(),
+                attempts: deployment.attempts,
+                error: error.to_string(),
+            },
+        )
+    }
+
+    pub fn config_changed(changes: Vec<ConfigChange>) -> Result<Self, EventsErr> {
+        Self::new(CONFIG_CHANGED, ConfigChangedEvent { changes })
+    }
+
+    pub fn sync_failed(failure_class: &str, streak: u32, error: &str) -> Result<Self, EventsErr> {
+        Self::new(
+            SYNC_FAILED,
+            SyncFailedEvent {
+                failure_class: failure_class.to_string(),
+                streak,
+                error: error.to_string(),
+            },
+        )
+    }
+
+    pub fn auth_revoked(error: &str) -> Result<Self, EventsErr> {
+        Self::new(
+            AUTH_REVOKED,
+            AuthRevokedEvent {
+                error: error.to_string(),
+            },
+        )
+    }
+
+    pub fn disk_low(path: &str, available_bytes: u64, total_bytes: u64) -> Result<Self, EventsErr> {
+        Self::new(
+            DISK_LOW,
+            DiskLowEvent {
+                path: path.to_string(),
+                available_bytes,
+                total_bytes,
+            },
+        )
+    }
+
+    pub fn safe_mode(recent_crashes: u32) -> Result<Self, EventsErr> {
+        Self::new(AGENT_SAFE_MODE, SafeModeEvent { recent_crashes })
+    }
 }
@@ -16,6 +16,7 @@ pub struct EventStore {
     events: Vec<Event>,
     next_event_id: i64,
     max_retained: usize,
+    persist: bool,
 }
 
 impl EventStore {
@@ -36,9 +37,17 @@ impl EventStore {
             events,
             next_event_id,
             max_retained,
+            persist: true,
         })
     }
 
+    /// Keeps new events in memory only. Events already in the log are still loaded but
+    /// events published from now on don't survive a restart. Used in low-wear mode.
+    pub fn without_persistence(mut self) -> Self {
+        self.persist = false;
+        self
+    }
+
     pub async fn append(&mut self, event_args: EventArgs) -> Result<Event, EventsErr> {
         let event = Event::new(self.next_event_id, event_args);
         self.next_event_id = self
@@ -46,10 +55,12 @@ impl EventStore {
             .checked_add(1)
             .expect("event ID overflow: exhausted i64 space");
 
-        let json = serde_json::to_string(&event)?;
-        self.log_file
-            .append_bytes(format!("{json}\n").as_bytes(), AppendOptions::SYNC)
-            .await?;
+        if self.persist {
+            let json = serde_json::to_string(&event)?;
+            self.log_file
+                .append_bytes(format!("{json}\n").as_bytes(), AppendOptions::SYNC)
+                .await?;
+        }
 
         self.events.push(event.clone());
 
@@ -112,8 +123,10 @@ impl EventStore {
         }
         let drain_count = self.events.len() - keep_count;
 
-        let content = self.get_compacted_content(drain_count)?;
-        self.write_compacted_content(&content).await?;
+        if self.persist {
+            let content = self.get_compacted_content(drain_count)?;
+            self.write_compacted_content(&content).await?;
+        }
 
         // only trim in-memory after disk write succeeded
         self.events.drain(..drain_count);
@@ -85,6 +85,11 @@ where
         self.cache.clone()
     }
 
+    /// The cached content, for callers which can't await.
+    pub fn cached(&self) -> Arc<ContentT> {
+        self.cache.clone()
+    }
+
     pub async fn write(&mut self, data: ContentT) -> Result<(), FileSysErr> {
         self.file
             .write_json(&data, WriteOptions::OVERWRITE_ATOMIC)
@@ -6,7 +6,7 @@ use std::time::SystemTime;
 
 // internal crates
 use crate::filesys::{
-    dir::Dir, errors::*, path::PathExt, Atomic, CopyOptions, Overwrite, WriteOptions,
+    dir::Dir, errors::*, path::PathExt, wear, Atomic, CopyOptions, Overwrite, WriteOptions,
 };
 use crate::trace;
 
@@ -166,6 +166,7 @@ impl File {
                 })
             })?;
         }
+        wear::record_write(self.path(), buf.len());
         Ok(())
     }
 
@@ -222,6 +223,7 @@ impl File {
                 })
             })?;
         }
+        wear::record_write(self.path(), buf.len());
         Ok(())
     }
 
@@ -257,6 +259,34 @@ impl File {
         }
     }
 
+    /// Overwrites the file's content with zeros, flushing it to disk, before deleting
+    /// it so that secrets don't linger in the freed blocks. Best effort on flash
+    /// storage whose wear leveling may remap the overwritten blocks.
+    pub async fn shred(&self) -> Result<(), FileSysErr> {
+        if !self.exists() {
+            return Ok(());
+        }
+        let size = self.size().await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(self.path())
+            .await
+            .map_err(|e| File::map_io_err_for_open(e, self))?;
+        let map_write_err = |e: std::io::Error| {
+            FileSysErr::WriteFileErr(WriteFileErr {
+                source: Box::new(e),
+                file: self.clone(),
+                trace: trace!(),
+            })
+        };
+        let zeros = vec![0u8; size as usize];
+        file.write_all(&zeros).await.map_err(map_write_err)?;
+        file.sync_all().await.map_err(map_write_err)?;
+        drop(file);
+        wear::record_write(self.path(), zeros.len());
+        self.delete().await
+    }
+
     /// Copy this file to a new file.
     pub async fn copy_to(&self, dst: &File, opts: CopyOptions) -> Result<(), FileSysErr> {
         if self.path() == dst.path() {
@@ -280,7 +310,7 @@ impl File {
         // ensure the parent directory of the new file exists and create it if not
         dst.parent()?.create_if_absent().await?;
 
-        tokio::fs::copy(self.path(), dst.path())
+        let copied = tokio::fs::copy(self.path(), dst.path())
             .await
             .map_err(|e| {
                 if e.kind() == std::io::ErrorKind::NotFound {
@@ -310,6 +340,7 @@ impl File {
                 })
             })?;
         }
+        wear::record_write(dst.path(), copied as usize);
 
         Ok(())
     }
@@ -3,6 +3,8 @@ pub mod dir;
 pub mod errors;
 pub mod file;
 pub mod path;
+pub mod sweep;
+pub mod wear;
 
 // internal crates
 pub use self::dir::Dir;
@@ -0,0 +1,172 @@
+// A crash mid-write or mid-move leaves its scratch space behind: `Dir::move_to` parks
+// the directory it replaces in a `.rename_trash_*` sibling and atomic writes stage the
+// new contents in an `.atomicwrite*` sibling. Nothing references these once the
+// process dies so they are swept on startup.
+
+// standard crates
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// internal crates
+use crate::filesys::{dir::Dir, path::PathExt};
+
+// external crates
+use serde::Serialize;
+use tracing::{debug, warn};
+
+/// Name prefixes of the scratch directories left behind by interrupted operations.
+pub const ARTIFACT_PREFIXES: &[&str] = &[".rename_trash_", ".atomicwrite"];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Artifacts modified more recently than this are left alone since an operation
+    /// in another process may still be using them.
+    pub older_than: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            older_than: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// What a sweep removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub removed: u64,
+    pub reclaimed_bytes: u64,
+    /// Stale artifacts which couldn't be removed.
+    pub failed: u64,
+}
+
+impl Report {
+    pub fn merge(&mut self, other: Report) {
+        self.removed += other.removed;
+        self.reclaimed_bytes += other.reclaimed_bytes;
+        self.failed += other.failed;
+    }
+}
+
+pub fn is_artifact(name: &str) -> bool {
+    ARTIFACT_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Removes the stale artifacts anywhere under `dir`. Symlinks aren't followed.
+pub async fn sweep(dir: &Dir, opts: Options) -> Report {
+    let dir = dir.path().clone();
+    let now = SystemTime::now();
+    run_blocking(move || {
+        let mut report = Report::default();
+        walk(&dir, opts, now, &mut report);
+        report
+    })
+    .await
+}
+
+/// Removes every entry directly inside `dir` which is older than the threshold,
+/// whatever its name. For directories which only ever hold scratch space.
+pub async fn sweep_all(dir: &Dir, opts: Options) -> Report {
+    let dir = dir.path().clone();
+    let now = SystemTime::now();
+    run_blocking(move || {
+        let mut report = Report::default();
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    debug!("unable to sweep {}: {e}", dir.display());
+                }
+                return report;
+            }
+        };
+        for entry in entries.flatten() {
+            remove_if_stale(&entry.path(), opts, now, &mut report);
+        }
+        report
+    })
+    .await
+}
+
+async fn run_blocking(f: impl FnOnce() -> Report + Send + 'static) -> Report {
+    tokio::task::spawn_blocking(f).await.unwrap_or_else(|e| {
+        warn!("sweep task failed: {e}");
+        Report::default()
+    })
+}
+
+fn walk(dir: &Path, opts: Options, now: SystemTime, report: &mut Report) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                debug!("unable to sweep {}: {e}", dir.display());
+            }
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if entry.file_name().to_str().is_some_and(is_artifact) {
+            remove_if_stale(&path, opts, now, report);
+        } else if file_type.is_dir() {
+            walk(&path, opts, now, report);
+        }
+    }
+}
+
+fn remove_if_stale(path: &Path, opts: Options, now: SystemTime, report: &mut Report) {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return;
+    };
+    let age = meta
+        .modified()
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+        .unwrap_or_default();
+    if age < opts.older_than {
+        return;
+    }
+
+    let bytes = size(path, &meta);
+    let result = if meta.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Ok(()) => {
+            debug!("removed stale {} ({bytes} bytes)", path.display());
+            report.removed += 1;
+            report.reclaimed_bytes += bytes;
+        }
+        Err(e) => {
+            warn!("unable to remove stale {}: {e}", path.display());
+            report.failed += 1;
+        }
+    }
+}
+
+fn size(path: &Path, meta: &fs::Metadata) -> u64 {
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = fs::symlink_metadata(entry.path()).ok()?;
+            Some(size(&entry.path(), &meta))
+        })
+        .sum()
+}
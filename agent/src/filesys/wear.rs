@@ -0,0 +1,99 @@
+// Flash storage (eMMC, SD cards) wears out with the number of bytes written to it so
+// the agent counts every byte it writes. The counters only cover the current process;
+// totals since install are persisted by storage::wear.
+
+// standard crates
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+// external crates
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Log files.
+    Logs,
+    /// The agent's own state under its data directory: caches, the journal, events,
+    /// settings and keys.
+    Caches,
+    /// Config instances deployed to the host.
+    Deployments,
+}
+
+static LOGS: AtomicU64 = AtomicU64::new(0);
+static CACHES: AtomicU64 = AtomicU64::new(0);
+static DEPLOYMENTS: AtomicU64 = AtomicU64::new(0);
+
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Bytes written by the current process, per category.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Written {
+    pub logs: u64,
+    pub caches: u64,
+    pub deployments: u64,
+}
+
+/// Sets the agent's data directory. Files written under it are counted as caches and
+/// files written anywhere else as deployments. Until it is set every file write is
+/// counted as caches.
+pub fn set_data_dir(dir: &Path) {
+    let mut data_dir = DATA_DIR.write().unwrap_or_else(|e| e.into_inner());
+    *data_dir = Some(dir.to_path_buf());
+}
+
+pub fn classify(path: &Path) -> Category {
+    let data_dir = DATA_DIR.read().unwrap_or_else(|e| e.into_inner());
+    match data_dir.as_deref() {
+        Some(data_dir) if !path.starts_with(data_dir) => Category::Deployments,
+        _ => Category::Caches,
+    }
+}
+
+pub fn record(category: Category, bytes: usize) {
+    let counter = match category {
+        Category::Logs => &LOGS,
+        Category::Caches => &CACHES,
+        Category::Deployments => &DEPLOYMENTS,
+    };
+    counter.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Records a write to the file at the given path.
+pub fn record_write(path: &Path, bytes: usize) {
+    record(classify(path), bytes);
+}
+
+pub fn written() -> Written {
+    Written {
+        logs: LOGS.load(Ordering::Relaxed),
+        caches: CACHES.load(Ordering::Relaxed),
+        deployments: DEPLOYMENTS.load(Ordering::Relaxed),
+    }
+}
+
+/// Wraps a writer so that the bytes written through it are counted as logs.
+pub struct CountingWriter<W> {
+    inner: W,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<W: io::Write> io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        record(Category::Logs, n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
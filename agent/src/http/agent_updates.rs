@@ -0,0 +1,88 @@
+// The agent's self-updates. The backend names the release the device should run; the
+// binary is then downloaded from the path it gives and checked against the digest and
+// signature before it's installed (see [crate::updater]).
+
+// internal crates
+use crate::crypt::digest::Digest;
+use crate::filesys::File;
+use crate::http::{
+    download, errors::HTTPErr, priority::Priority, request, Client, ClientI, QueryParams,
+};
+
+// external crates
+use serde::{Deserialize, Serialize};
+
+// ================================ PARAM STRUCTS ================================== //
+
+pub struct LatestParams<'a> {
+    /// The version the agent is running.
+    pub version: &'a str,
+    pub os: &'a str,
+    pub arch: &'a str,
+    pub token: &'a str,
+}
+
+pub struct DownloadParams<'a> {
+    pub release: &'a AgentRelease,
+    pub dest: &'a File,
+    pub token: &'a str,
+}
+
+// The generated client doesn't include agent releases yet so they're defined here.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentRelease {
+    pub version: String,
+    /// Where the binary is downloaded from, relative to the backend's base URL.
+    pub path: String,
+    pub digest: Digest,
+    /// The release key's RSASSA-PKCS1-v1_5 SHA-256 signature of the binary, base64
+    /// encoded.
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatestRelease {
+    /// The release to update to. Unset if the agent is up to date.
+    pub release: Option<AgentRelease>,
+}
+
+// ================================== REQUESTS ===================================== //
+
+/// The release the agent should update to, if any.
+pub async fn latest(
+    client: &impl ClientI,
+    params: LatestParams<'_>,
+) -> Result<Option<AgentRelease>, HTTPErr> {
+    let url = format!("{}/agent/releases/latest", client.base_url());
+    let query = QueryParams::new()
+        .add("version", params.version)
+        .add("os", params.os)
+        .add("arch", params.arch);
+    let request = request::Params::get(&url)
+        .with_query(query)
+        .with_token(params.token);
+    let latest: LatestRelease = super::client::fetch(client, request).await?;
+    Ok(latest.release)
+}
+
+/// Downloads a release's binary to `dest`, verifying it has the release's digest.
+pub async fn download(
+    client: &Client,
+    params: DownloadParams<'_>,
+) -> Result<download::Downloaded, HTTPErr> {
+    let url = format!(
+        "{}/{}",
+        client.base_url(),
+        params.release.path.trim_start_matches('/')
+    );
+    let request = request::Params::get(&url)
+        .with_token(params.token)
+        .with_priority(Priority::Low);
+    let options = download::Options {
+        expected: Some(params.release.digest.clone()),
+        ..Default::default()
+    };
+    client
+        .download(request, params.dest, &options, |_| {})
+        .await
+}
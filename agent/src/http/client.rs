@@ -1,14 +1,26 @@
 // standard crates
 use std::sync::Arc;
+use std::time::Instant;
 
 // internal crates
+use crate::cell;
+use crate::clock;
+use crate::filesys::File;
 use crate::http::{
+    conditional::{Conditional, Validators},
+    download,
     errors::{reqwest_err_to_http_client_err, BuildReqwestErr, HTTPErr, TimeoutErr},
+    priority,
+    record::Recorder,
     request, response,
+    retry::{self, Budget, BudgetOptions, RetryPolicy},
 };
+use crate::metrics;
+use crate::network::egress;
 use crate::trace;
 
 // external crates
+use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 use tokio::time::timeout;
 
@@ -17,6 +29,10 @@ pub struct Client {
     client: reqwest::Client,
     base_url: String,
     headers: request::Headers,
+    scheduler: priority::Scheduler,
+    recorder: Option<Arc<Recorder>>,
+    retry_policy: RetryPolicy,
+    retry_budget: Budget,
 }
 
 // Per the reqwest docs, we do not need to wrap the client in Rc or Arc to reuse it
@@ -35,6 +51,28 @@ pub trait ClientI: Send + Sync {
         &self,
         params: request::Params<'_>,
     ) -> impl std::future::Future<Output = Result<(String, request::Meta), HTTPErr>> + Send;
+
+    /// Executes the request conditionally on the resource having changed since the
+    /// validators were returned. Clients which don't support conditional requests
+    /// always return the resource, without validators.
+    fn execute_conditional(
+        &self,
+        params: request::Params<'_>,
+        _validators: &Validators,
+    ) -> impl std::future::Future<Output = Result<(Conditional<String>, request::Meta), HTTPErr>> + Send
+    {
+        async move {
+            let (value, meta) = self.execute(params).await?;
+            let validators = Validators::default();
+            Ok((Conditional::Modified { value, validators }, meta))
+        }
+    }
+
+    /// Whether the client retries failed requests itself, in which case callers
+    /// shouldn't retry them again.
+    fn retries_requests(&self) -> bool {
+        false
+    }
 }
 
 impl ClientI for Client {
@@ -46,11 +84,29 @@ impl ClientI for Client {
         &self,
         params: request::Params<'_>,
     ) -> Result<(String, request::Meta), HTTPErr> {
-        let req = self.build_request(params)?;
-        let meta = req.meta.clone();
-        let resp = self.send(req).await?;
-        let text = response::handle(resp).await?;
-        Ok((text, meta))
+        let (result, meta) = self.execute_with_retries(params, false).await?;
+        match result {
+            Conditional::Modified { value, .. } => Ok((value, meta)),
+            Conditional::NotModified => unreachable!("an unconditional request wasn't modified"),
+        }
+    }
+
+    async fn execute_conditional(
+        &self,
+        params: request::Params<'_>,
+        validators: &Validators,
+    ) -> Result<(Conditional<String>, request::Meta), HTTPErr> {
+        // recorded exchanges are replayed unconditionally, so they must hold the
+        // resource itself
+        let params = match self.recorder {
+            Some(_) => params,
+            None => validators.apply(params),
+        };
+        self.execute_with_retries(params, true).await
+    }
+
+    fn retries_requests(&self) -> bool {
+        true
     }
 }
 
@@ -65,6 +121,18 @@ impl<T: ClientI> ClientI for Arc<T> {
     ) -> Result<(String, request::Meta), HTTPErr> {
         self.as_ref().execute(params).await
     }
+
+    async fn execute_conditional(
+        &self,
+        params: request::Params<'_>,
+        validators: &Validators,
+    ) -> Result<(Conditional<String>, request::Meta), HTTPErr> {
+        self.as_ref().execute_conditional(params, validators).await
+    }
+
+    fn retries_requests(&self) -> bool {
+        self.as_ref().retries_requests()
+    }
 }
 
 impl Client {
@@ -79,25 +147,161 @@ impl Client {
             client,
             base_url: base_url.to_string(),
             headers: request::Headers::default(),
+            scheduler: priority::Scheduler::default(),
+            recorder: None,
+            retry_policy: RetryPolicy::default(),
+            retry_budget: Budget::default(),
+        })
+    }
+
+    /// Connects through the egress proxy and trusts the CA bundle, if configured, and
+    /// sends the fleet-defined headers with every request.
+    pub fn with_egress(mut self, egress: &egress::Options) -> Result<Self, HTTPErr> {
+        self.headers.fleet = egress.headers.clone();
+        // fail here rather than on every request
+        self.headers.to_map()?;
+        self.client = egress
+            .configure(reqwest::Client::builder())?
+            .build()
+            .map_err(|e| {
+                HTTPErr::BuildReqwestErr(BuildReqwestErr {
+                    source: e,
+                    trace: trace!(),
+                })
+            })?;
+        Ok(self)
+    }
+
+    /// Sends the requests to a cell controller's proxy instead of the backend,
+    /// authenticated as one of its peers.
+    pub fn with_upstream(mut self, upstream: cell::Upstream) -> Self {
+        self.base_url = upstream.url.clone();
+        self.headers.cell_peer = Some(upstream);
+        self
+    }
+
+    /// Replaces the scheduler which admits outbound requests by priority class.
+    pub fn with_scheduling(mut self, options: priority::Options) -> Self {
+        self.scheduler = priority::Scheduler::new(options);
+        self
+    }
+
+    /// Records every exchange with the backend for later replay.
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Replaces the default retry policy of requests and the budget their retries
+    /// draw from.
+    pub fn with_retries(mut self, policy: RetryPolicy, budget: BudgetOptions) -> Self {
+        self.retry_policy = policy;
+        self.retry_budget = Budget::new(budget);
+        self
+    }
+
+    pub fn retry_budget(&self) -> &Budget {
+        &self.retry_budget
+    }
+
+    pub fn scheduler(&self) -> &priority::Scheduler {
+        &self.scheduler
+    }
+
+    async fn execute_with_retries(
+        &self,
+        params: request::Params<'_>,
+        conditional: bool,
+    ) -> Result<(Conditional<String>, request::Meta), HTTPErr> {
+        // long polls are expected to time out and are reissued by their worker
+        let policy = match (params.retry, params.long_poll) {
+            (Some(policy), _) => policy,
+            (None, true) => RetryPolicy::NONE,
+            (None, false) => self.retry_policy,
+        };
+        let method = params.method.clone();
+        retry::run(&policy, &method, &self.retry_budget, || {
+            self.execute_once(params.clone(), conditional)
         })
+        .await
+    }
+
+    /// Executes the request once. A 304 is only expected, and so only treated as the
+    /// resource not being modified, for conditional requests.
+    async fn execute_once(
+        &self,
+        params: request::Params<'_>,
+        conditional: bool,
+    ) -> Result<(Conditional<String>, request::Meta), HTTPErr> {
+        let _permit = match params.long_poll {
+            true => None,
+            false => Some(self.scheduler.acquire(params.priority).await),
+        };
+        let request_body = match self.recorder {
+            Some(_) => params.body.clone(),
+            None => None,
+        };
+        let req = self.build_request(params)?;
+        let meta = req.meta.clone();
+        let mut validators = Validators::default();
+        let result = match self.send(req).await {
+            Ok(resp) if conditional && resp.reqwest.status() == StatusCode::NOT_MODIFIED => {
+                return Ok((Conditional::NotModified, meta));
+            }
+            Ok(resp) => {
+                validators = Validators::from_headers(resp.reqwest.headers());
+                response::handle(resp).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Some(recorder) = &self.recorder {
+            recorder
+                .record(&self.base_url, &meta, request_body.as_deref(), &result)
+                .await;
+        }
+        let value = result?;
+        Ok((Conditional::Modified { value, validators }, meta))
     }
 
     pub fn build_request(&self, params: request::Params) -> Result<request::Request, HTTPErr> {
         request::build(&self.client, &self.headers, params)
     }
 
+    /// Streams the response body to `dest`, reporting progress as it arrives and
+    /// resuming after connection loss. See [download].
+    pub async fn download<F>(
+        &self,
+        params: request::Params<'_>,
+        dest: &File,
+        options: &download::Options,
+        on_progress: F,
+    ) -> Result<download::Downloaded, HTTPErr>
+    where
+        F: FnMut(download::Progress),
+    {
+        let _permit = self.scheduler.acquire(params.priority).await;
+        download::download(self, params, dest, options, on_progress).await
+    }
+
     pub async fn send(&self, req: request::Request) -> Result<response::Response, HTTPErr> {
-        match timeout(req.meta.timeout, self.client.execute(req.reqwest)).await {
+        let started = Instant::now();
+        let sent_at = chrono::Utc::now();
+        let result = timeout(req.meta.timeout, self.client.execute(req.reqwest)).await;
+        metrics::global().record_http_request(req.meta.method.as_str(), started.elapsed());
+        match result {
             Err(e) => Err(HTTPErr::TimeoutErr(TimeoutErr {
                 msg: e.to_string(),
                 request: req.meta,
                 trace: trace!(),
             })),
             Ok(Err(e)) => Err(reqwest_err_to_http_client_err(e, req.meta, trace!())),
-            Ok(Ok(response)) => Ok(response::Response {
-                reqwest: response,
-                meta: req.meta,
-            }),
+            Ok(Ok(response)) => {
+                clock::global().observe_headers(response.headers(), sent_at, chrono::Utc::now());
+                Ok(response::Response {
+                    reqwest: response,
+                    meta: req.meta,
+                })
+            }
         }
     }
 }
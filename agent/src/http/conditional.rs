@@ -0,0 +1,66 @@
+// Conditional requests, so resources which rarely change (e.g. the deployments list
+// the agent polls) aren't downloaded again when they haven't. The validators the
+// backend returned with a resource are sent back in `If-None-Match` and
+// `If-Modified-Since`, and a 304 response means the resource is as it was.
+
+// internal crates
+use crate::http::request;
+
+// external crates
+use reqwest::header::{HeaderMap, ETAG, LAST_MODIFIED};
+
+/// The validators of a version of a resource.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Makes the request conditional on the resource having changed.
+    pub fn apply<'a>(&self, mut params: request::Params<'a>) -> request::Params<'a> {
+        if let Some(etag) = &self.etag {
+            params = params.with_header("If-None-Match", etag.clone());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            params = params.with_header("If-Modified-Since", last_modified.clone());
+        }
+        params
+    }
+}
+
+/// The response to a conditional request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conditional<T> {
+    Modified { value: T, validators: Validators },
+    NotModified,
+}
+
+impl<T> Conditional<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Conditional<U> {
+        match self {
+            Conditional::Modified { value, validators } => Conditional::Modified {
+                value: f(value),
+                validators,
+            },
+            Conditional::NotModified => Conditional::NotModified,
+        }
+    }
+}
@@ -1,11 +1,48 @@
 // internal crates
-use crate::http::{errors::HTTPErr, request, ClientI};
+use crate::http::{client, errors::HTTPErr, query::QueryParams, request, ClientI};
+
+// external crates
+use serde::{Deserialize, Serialize};
 
 pub struct GetContentParams<'a> {
     pub id: &'a str,
     pub token: &'a str,
 }
 
+pub struct GetPatchParams<'a> {
+    pub id: &'a str,
+    pub base_id: &'a str,
+    /// Digest of the cached base content, so the backend refuses to patch a base
+    /// which differs from its own
+    pub base_digest: &'a str,
+    pub token: &'a str,
+}
+
+/// How a content patch is expressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchFormat {
+    /// RFC 6902 JSON Patch, an array of operations
+    JsonPatch,
+    /// RFC 7386 JSON Merge Patch
+    MergePatch,
+}
+
+// The generated client doesn't include content patches yet so the response is
+// modeled here.
+/// The difference between the content of a config instance and the content of an
+/// earlier config instance (the base) which the agent already has cached.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContentPatch {
+    pub base_id: String,
+    pub format: PatchFormat,
+    pub patch: serde_json::Value,
+    /// Digest of the patched document in its canonical form (see
+    /// [crate::sync::patch::canonical]), either hex SHA-256 or `<algorithm>:<hex>`
+    /// (e.g. `blake3:...`)
+    pub digest: String,
+}
+
 pub async fn get_content(
     client: &impl ClientI,
     params: GetContentParams<'_>,
@@ -19,3 +56,22 @@ pub async fn get_content(
     let (text, _meta) = client.execute(request).await?;
     Ok(text)
 }
+
+pub async fn get_patch(
+    client: &impl ClientI,
+    params: GetPatchParams<'_>,
+) -> Result<ContentPatch, HTTPErr> {
+    let url = format!(
+        "{}/config_instances/{}/content/patch",
+        client.base_url(),
+        params.id
+    );
+    let request = request::Params::get(&url)
+        .with_query(
+            QueryParams::new()
+                .add("base", params.base_id)
+                .add("base_digest", params.base_digest),
+        )
+        .with_token(params.token);
+    client::fetch(client, request).await
+}
@@ -0,0 +1,24 @@
+// The JSON Schemas config instances are validated against before they're deployed
+// (see [crate::deploy::schema]). The generated client doesn't include config schemas
+// yet so the response is taken to be the schema document itself.
+
+// internal crates
+use crate::http::{client, errors::HTTPErr, request, ClientI};
+
+// ================================ PARAM STRUCTS ================================== //
+
+pub struct GetParams<'a> {
+    pub id: &'a str,
+    pub token: &'a str,
+}
+
+// ================================== REQUESTS ===================================== //
+
+pub async fn get(
+    client: &impl ClientI,
+    params: GetParams<'_>,
+) -> Result<serde_json::Value, HTTPErr> {
+    let url = format!("{}/config_schemas/{}", client.base_url(), params.id);
+    let request = request::Params::get(&url).with_token(params.token);
+    client::fetch(client, request).await
+}
@@ -0,0 +1,24 @@
+// internal crates
+use crate::diagnostics::crash::Report;
+use crate::http::{errors::HTTPErr, priority::Priority, request, ClientI};
+
+// ================================ PARAM STRUCTS ================================== //
+
+pub struct ReportParams<'a> {
+    pub device_id: &'a str,
+    pub report: &'a Report,
+    pub token: &'a str,
+}
+
+// ================================ FREE FUNCTIONS ================================= //
+/// Uploads a crash report. The generated client doesn't include crash reports yet so
+/// the report is posted as the agent writes it.
+pub async fn report(client: &impl ClientI, params: ReportParams<'_>) -> Result<(), HTTPErr> {
+    let url = format!("{}/devices/{}/crashes", client.base_url(), params.device_id);
+    let request = request::Params::post(&url, request::marshal_json(params.report)?)
+        .with_token(params.token)
+        .with_priority(Priority::Low);
+    // the backend's acknowledgement has nothing worth reading
+    client.execute(request).await?;
+    Ok(())
+}
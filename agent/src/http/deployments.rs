@@ -1,13 +1,25 @@
 // internal crates
 use crate::http::{
+    conditional::{Conditional, Validators},
     errors::HTTPErr,
+    priority::Priority,
     query::{Page, QueryParams, MAX_PAGE_LIMIT},
-    request, ClientI,
+    request, response,
+    retry::RetryPolicy,
+    ClientI,
 };
+use crate::models::{DplDependencies, DplMetrics, Signature};
 use backend_api::models::{
     Deployment, DeploymentActivityStatus, DeploymentList, UpdateDeploymentRequest,
 };
 
+// external crates
+use serde::{Deserialize, Serialize};
+
+/// Carries the head of the config access log's hash chain (`<seq>:<hash>`) so the
+/// backend keeps a record of it outside the device.
+pub const AUDIT_ANCHOR_HEADER: &str = "Miru-Audit-Anchor";
+
 // ================================ PARAM STRUCTS ================================== //
 
 pub struct ListParams<'a> {
@@ -21,20 +33,59 @@ pub struct ListAllParams<'a> {
     pub activity_status: &'a [DeploymentActivityStatus],
     pub expansions: &'a [&'a str],
     pub token: &'a str,
+    /// Sent with the first page in the `Miru-Audit-Anchor` header.
+    pub audit_anchor: Option<&'a str>,
 }
 
 pub struct UpdateParams<'a> {
     pub id: &'a str,
     pub updates: &'a UpdateDeploymentRequest,
+    pub metrics: Option<&'a DplMetrics>,
     pub token: &'a str,
 }
 
+// The generated request doesn't include rollout metrics yet so they're appended
+// to the body alongside the status fields.
+#[derive(Serialize)]
+struct UpdateBody<'a> {
+    #[serde(flatten)]
+    updates: &'a UpdateDeploymentRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<&'a DplMetrics>,
+}
+
+// The generated deployment doesn't include dependencies, nor its release's signature,
+// yet so they're read alongside it when listing deployments.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ListedDeployment {
+    #[serde(flatten)]
+    pub deployment: Deployment,
+    #[serde(default)]
+    pub dependencies: DplDependencies,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_signature: Option<Signature>,
+}
+
+#[derive(Deserialize)]
+struct ListedDeploymentPage {
+    has_more: bool,
+    data: Vec<ListedDeployment>,
+}
+
 // ================================ FREE FUNCTIONS ================================= //
 
 pub async fn list(
     client: &impl ClientI,
     params: ListParams<'_>,
 ) -> Result<DeploymentList, HTTPErr> {
+    let url = format!("{}/deployments", client.base_url());
+    let request = request::Params::get(&url)
+        .with_query(list_query(&params))
+        .with_token(params.token);
+    super::client::fetch(client, request).await
+}
+
+fn list_query(params: &ListParams<'_>) -> QueryParams {
     let mut qp = QueryParams::new().paginate(params.pagination);
     if !params.activity_status.is_empty() {
         let values: Vec<String> = params
@@ -44,43 +95,82 @@ pub async fn list(
             .collect();
         qp = qp.add("activity_status", &values.join("|"));
     }
-    qp = qp.expand(params.expansions);
+    qp.expand(params.expansions)
+}
 
+/// Lists every page of deployments, along with the dependencies each declares.
+pub async fn list_all(
+    client: &impl ClientI,
+    params: ListAllParams<'_>,
+) -> Result<Vec<ListedDeployment>, HTTPErr> {
     let url = format!("{}/deployments", client.base_url());
-    let request = request::Params::get(&url)
-        .with_query(qp)
-        .with_token(params.token);
-    super::client::fetch(client, request).await
+    let first: ListedDeploymentPage =
+        super::client::fetch(client, page_request(&url, &params, 0)).await?;
+    rest_of_pages(client, &url, &params, first).await
 }
 
-pub async fn list_all(
+/// Lists every page of deployments unless the list hasn't changed since the validators
+/// were returned. The validators only cover the first page, so they're only returned
+/// for lists which fit in one page; longer lists are always fetched whole.
+pub async fn list_all_if_modified(
     client: &impl ClientI,
     params: ListAllParams<'_>,
-) -> Result<Vec<Deployment>, HTTPErr> {
-    let mut all_deployments = Vec::new();
-    let mut pagination = Page {
-        limit: MAX_PAGE_LIMIT,
-        offset: 0,
+    validators: &Validators,
+) -> Result<Conditional<Vec<ListedDeployment>>, HTTPErr> {
+    let url = format!("{}/deployments", client.base_url());
+    let request = page_request(&url, &params, 0);
+    let (result, meta) = client.execute_conditional(request, validators).await?;
+    let Conditional::Modified { value, validators } = result else {
+        return Ok(Conditional::NotModified);
+    };
+    let first: ListedDeploymentPage = response::parse_json(value, meta)?;
+    let validators = match first.has_more {
+        true => Validators::default(),
+        false => validators,
     };
+    let value = rest_of_pages(client, &url, &params, first).await?;
+    Ok(Conditional::Modified { value, validators })
+}
 
-    loop {
-        let page = list(
-            client,
-            ListParams {
-                activity_status: params.activity_status,
-                expansions: params.expansions,
-                pagination: &pagination,
-                token: params.token,
-            },
-        )
-        .await?;
-        all_deployments.extend(page.data);
-        if !page.has_more {
-            break;
-        }
-        pagination.offset += pagination.limit;
+fn page_request<'a>(
+    url: &'a str,
+    params: &ListAllParams<'a>,
+    offset: usize,
+) -> request::Params<'a> {
+    let qp = list_query(&ListParams {
+        activity_status: params.activity_status,
+        expansions: params.expansions,
+        pagination: &Page {
+            limit: MAX_PAGE_LIMIT,
+            offset,
+        },
+        token: params.token,
+    });
+    let request = request::Params::get(url)
+        .with_query(qp)
+        .with_token(params.token);
+    match params.audit_anchor {
+        Some(anchor) if offset == 0 => request.with_header(AUDIT_ANCHOR_HEADER, anchor.to_string()),
+        _ => request,
     }
+}
 
+async fn rest_of_pages(
+    client: &impl ClientI,
+    url: &str,
+    params: &ListAllParams<'_>,
+    first: ListedDeploymentPage,
+) -> Result<Vec<ListedDeployment>, HTTPErr> {
+    let mut all_deployments = first.data;
+    let mut has_more = first.has_more;
+    let mut offset = 0;
+    while has_more {
+        offset += MAX_PAGE_LIMIT;
+        let page: ListedDeploymentPage =
+            super::client::fetch(client, page_request(url, params, offset)).await?;
+        all_deployments.extend(page.data);
+        has_more = page.has_more;
+    }
     Ok(all_deployments)
 }
 
@@ -101,7 +191,14 @@ pub async fn update(
     params: UpdateParams<'_>,
 ) -> Result<Deployment, HTTPErr> {
     let url = format!("{}/deployments/{}", client.base_url(), params.id,);
-    let request = request::Params::patch(&url, request::marshal_json(params.updates)?)
-        .with_token(params.token);
+    let body = UpdateBody {
+        updates: params.updates,
+        metrics: params.metrics,
+    };
+    // setting a deployment's status is idempotent, so it's safe to retry
+    let request = request::Params::patch(&url, request::marshal_json(&body)?)
+        .with_token(params.token)
+        .with_priority(Priority::High)
+        .with_retry_policy(RetryPolicy::default().retry_non_idempotent());
     super::client::fetch(client, request).await
 }
@@ -1,10 +1,22 @@
+// standard crates
+use std::time::Duration;
+
 // internal crates
-use crate::http::{errors::HTTPErr, request, ClientI};
+use crate::http::{errors::HTTPErr, priority::Priority, request, ClientI, QueryParams};
+#[cfg(feature = "telemetry")]
+use crate::telemetry::Network;
 use backend_api::models::{
-    Device, ProvisionDeviceRequest, ReprovisionDeviceRequest, TokenResponse,
+    Device, ProvisionDeviceRequest, ReprovisionDeviceRequest, SyncDevice, TokenResponse,
     UpdateDeviceFromAgentRequest,
 };
 
+// external crates
+use serde::{Deserialize, Serialize};
+
+/// How much longer than the requested wait the agent gives the backend to answer a
+/// long poll before giving up on it.
+pub const WAIT_FOR_SYNC_GRACE: Duration = Duration::from_secs(30);
+
 // ================================ PARAM STRUCTS ================================== //
 
 pub struct ProvisionParams<'a> {
@@ -27,6 +39,44 @@ pub struct UpdateParams<'a> {
     pub token: &'a str,
 }
 
+#[cfg(feature = "telemetry")]
+pub struct UpdateNetworkParams<'a> {
+    pub id: &'a str,
+    pub network: &'a Network,
+    pub token: &'a str,
+}
+
+pub struct WaitForSyncParams<'a> {
+    pub id: &'a str,
+    pub wait: Duration,
+    pub token: &'a str,
+}
+
+pub struct RegisterKeyParams<'a> {
+    pub public_key_pem: &'a str,
+    pub token: &'a str,
+}
+
+// The generated client doesn't include key registration yet so its request and
+// response are defined here.
+#[derive(Serialize)]
+struct RegisterKeyRequest<'a> {
+    public_key_pem: &'a str,
+}
+
+// The generated client doesn't include the device's network yet either.
+#[cfg(feature = "telemetry")]
+#[derive(Serialize)]
+struct UpdateNetworkRequest<'a> {
+    network: &'a Network,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredKey {
+    /// The fingerprint the backend computed for the registered public key.
+    pub fingerprint: String,
+}
+
 // ================================ FREE FUNCTIONS ================================= //
 pub async fn provision(
     client: &impl ClientI,
@@ -34,7 +84,8 @@ pub async fn provision(
 ) -> Result<Device, HTTPErr> {
     let url = format!("{}/devices/provision", client.base_url());
     let request = request::Params::post(&url, request::marshal_json(params.payload)?)
-        .with_token(params.token);
+        .with_token(params.token)
+        .with_priority(Priority::Critical);
     super::client::fetch(client, request).await
 }
 
@@ -44,7 +95,8 @@ pub async fn reprovision(
 ) -> Result<Device, HTTPErr> {
     let url = format!("{}/devices/reprovision", client.base_url());
     let request = request::Params::post(&url, request::marshal_json(params.payload)?)
-        .with_token(params.token);
+        .with_token(params.token)
+        .with_priority(Priority::Critical);
     super::client::fetch(client, request).await
 }
 
@@ -53,19 +105,82 @@ pub async fn issue_token(
     params: IssueTokenParams<'_>,
 ) -> Result<TokenResponse, HTTPErr> {
     let url = format!("{}/devices/token", client.base_url());
-    let request = request::Params::post(&url, String::new()).with_token(params.token);
+    let request = request::Params::post(&url, String::new())
+        .with_token(params.token)
+        .with_priority(Priority::Critical);
+    super::client::fetch(client, request).await
+}
+
+/// Registers a new public key for the device alongside its current one. The backend
+/// retires the old key once a token is issued with the new one.
+pub async fn register_key(
+    client: &impl ClientI,
+    params: RegisterKeyParams<'_>,
+) -> Result<RegisteredKey, HTTPErr> {
+    let url = format!("{}/devices/keys", client.base_url());
+    let body = RegisterKeyRequest {
+        public_key_pem: params.public_key_pem,
+    };
+    let request = request::Params::post(&url, request::marshal_json(&body)?)
+        .with_token(params.token)
+        .with_priority(Priority::Critical);
     super::client::fetch(client, request).await
 }
 
 pub async fn update(client: &impl ClientI, params: UpdateParams<'_>) -> Result<Device, HTTPErr> {
     let url = format!("{}/devices/{}", client.base_url(), params.id);
     let request = request::Params::patch(&url, request::marshal_json(params.payload)?)
-        .with_token(params.token);
+        .with_token(params.token)
+        .with_priority(Priority::High);
     super::client::fetch(client, request).await
 }
 
+/// Reports the device's network interfaces and connectivity.
+#[cfg(feature = "telemetry")]
+pub async fn update_network(
+    client: &impl ClientI,
+    params: UpdateNetworkParams<'_>,
+) -> Result<(), HTTPErr> {
+    let url = format!("{}/devices/{}", client.base_url(), params.id);
+    let body = UpdateNetworkRequest {
+        network: params.network,
+    };
+    let request = request::Params::patch(&url, request::marshal_json(&body)?)
+        .with_token(params.token)
+        .with_priority(Priority::Low);
+    // the device in the response has nothing the agent doesn't already know
+    client.execute(request).await?;
+    Ok(())
+}
+
 pub async fn get(client: &impl ClientI, token: &str) -> Result<Device, HTTPErr> {
     let url = format!("{}/device", client.base_url());
     let request = request::Params::get(&url).with_token(token);
     super::client::fetch(client, request).await
 }
+
+/// Deregisters the device the token was issued to. The backend revokes its keys and
+/// tokens, so the device must be activated again to rejoin.
+pub async fn deregister(client: &impl ClientI, token: &str) -> Result<Device, HTTPErr> {
+    let url = format!("{}/device", client.base_url());
+    let request = request::Params::delete(&url)
+        .with_token(token)
+        .with_priority(Priority::Critical);
+    super::client::fetch(client, request).await
+}
+
+/// Long polls the backend for a sync request. The backend holds the request until the
+/// device needs to sync or the wait elapses, reporting whether the device is synced.
+pub async fn wait_for_sync(
+    client: &impl ClientI,
+    params: WaitForSyncParams<'_>,
+) -> Result<SyncDevice, HTTPErr> {
+    let url = format!("{}/devices/{}/sync", client.base_url(), params.id);
+    let wait_secs = params.wait.as_secs().to_string();
+    let request = request::Params::get(&url)
+        .with_query(QueryParams::new().add("wait_secs", &wait_secs))
+        .with_timeout(params.wait + WAIT_FOR_SYNC_GRACE)
+        .with_token(params.token)
+        .as_long_poll();
+    super::client::fetch(client, request).await
+}
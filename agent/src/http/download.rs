@@ -0,0 +1,315 @@
+// Streams large payloads (config instance content, artifacts) to a file instead of
+// buffering them in memory. The body is appended to a `<name>.part` file next to the
+// destination as it arrives, so a download interrupted by a network error resumes
+// with an HTTP Range request from the bytes already received rather than starting
+// over; a partial file left by a previous run is resumed the same way. The server's
+// ETag is sent back in `If-Range` so a payload which changed between attempts is sent
+// whole instead of being spliced onto the old one. The finished file is verified
+// against the expected digest before it replaces the destination.
+
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::crypt::digest::{Algorithm, Digest, Hasher};
+use crate::errors::Error;
+use crate::filesys::{self, AppendOptions, File, Overwrite, PathExt};
+use crate::http::{
+    client::Client,
+    errors::{
+        reqwest_err_to_http_client_err, DownloadDigestMismatchErr, DownloadFileErr, HTTPErr,
+        ReqwestErr, ReqwestErrKind,
+    },
+    request, response,
+};
+use crate::trace;
+
+// external crates
+use reqwest::header::{CONTENT_RANGE, ETAG};
+use reqwest::StatusCode;
+use tracing::warn;
+
+/// How much of the body is buffered in memory before it's appended to the file.
+const WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The digest the downloaded file must have. The file is hashed with its
+    /// algorithm, or SHA-256 if no digest is expected.
+    pub expected: Option<Digest>,
+    /// How many times a download interrupted by a network error is resumed before
+    /// giving up.
+    pub max_resumes: u32,
+    /// How long to wait before resuming.
+    pub resume_delay: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            expected: None,
+            max_resumes: 5,
+            resume_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Reported each time part of the body is received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes received so far, including any resumed from a partial file.
+    pub received: u64,
+    /// The size of the payload, if the server sent it.
+    pub total: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Downloaded {
+    pub bytes: u64,
+    /// How many times the download was resumed after a network error.
+    pub resumes: u32,
+    pub digest: Digest,
+}
+
+/// The file a download to `dest` is received into until it completes.
+pub fn partial_file(dest: &File) -> Result<File, filesys::FileSysErr> {
+    Ok(dest.parent()?.file(&format!("{}.part", dest.name()?)))
+}
+
+pub(crate) async fn download<F>(
+    client: &Client,
+    params: request::Params<'_>,
+    dest: &File,
+    options: &Options,
+    mut on_progress: F,
+) -> Result<Downloaded, HTTPErr>
+where
+    F: FnMut(Progress),
+{
+    let meta = params.meta()?;
+    let store_err = |source| {
+        HTTPErr::DownloadFileErr(DownloadFileErr {
+            request: meta.clone(),
+            source: Box::new(source),
+            trace: trace!(),
+        })
+    };
+    let algorithm = options
+        .expected
+        .as_ref()
+        .map_or(Algorithm::default(), Digest::algorithm);
+    let partial = partial_file(dest).map_err(store_err)?;
+    let mut received = Received::resume(partial, algorithm)
+        .await
+        .map_err(store_err)?;
+
+    let mut resumes = 0;
+    let mut etag = None;
+    loop {
+        match attempt(client, &params, &mut received, &mut etag, &mut on_progress).await {
+            Ok(Outcome::Complete) => break,
+            Ok(Outcome::Restart) => {
+                warn!("download of {meta} can't be resumed, restarting it");
+            }
+            Err(e) if is_resumable(&e) && resumes < options.max_resumes => {
+                resumes += 1;
+                warn!(
+                    "download of {meta} interrupted after {} bytes, resuming ({resumes}/{}): {e}",
+                    received.bytes, options.max_resumes
+                );
+                tokio::time::sleep(options.resume_delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let Received {
+        file: partial,
+        bytes,
+        hasher,
+    } = received;
+    let digest = hasher.finalize();
+    if let Some(expected) = &options.expected {
+        if digest != *expected {
+            // the partial file can't be resumed into the expected payload
+            if let Err(e) = partial.delete().await {
+                warn!("failed to delete the mismatched download {partial}: {e}");
+            }
+            return Err(HTTPErr::DownloadDigestMismatchErr(
+                DownloadDigestMismatchErr {
+                    request: meta,
+                    expected: Box::new(expected.clone()),
+                    actual: Box::new(digest),
+                    trace: trace!(),
+                },
+            ));
+        }
+    }
+    partial
+        .move_to(dest, Overwrite::Allow)
+        .await
+        .map_err(store_err)?;
+    Ok(Downloaded {
+        bytes,
+        resumes,
+        digest,
+    })
+}
+
+enum Outcome {
+    Complete,
+    /// The partial file was discarded and the payload must be requested whole.
+    Restart,
+}
+
+/// Requests the rest of the payload and appends it to the partial file. Progress made
+/// before an error is kept so the next attempt can resume from it.
+async fn attempt<F>(
+    client: &Client,
+    params: &request::Params<'_>,
+    received: &mut Received,
+    etag: &mut Option<String>,
+    on_progress: &mut F,
+) -> Result<Outcome, HTTPErr>
+where
+    F: FnMut(Progress),
+{
+    let mut params = params.clone();
+    if received.bytes > 0 {
+        params = params.with_header("Range", format!("bytes={}-", received.bytes));
+        if let Some(etag) = etag.as_ref() {
+            params = params.with_header("If-Range", etag.clone());
+        }
+    }
+    let req = client.build_request(params)?;
+    let meta = req.meta.clone();
+    let store_err = |source| {
+        HTTPErr::DownloadFileErr(DownloadFileErr {
+            request: meta.clone(),
+            source: Box::new(source),
+            trace: trace!(),
+        })
+    };
+    let mut resp = client.send(req).await?;
+
+    let total = match resp.reqwest.status() {
+        StatusCode::PARTIAL_CONTENT => match content_range(&resp.reqwest) {
+            Some((start, total)) if start == received.bytes => total,
+            // not the range requested
+            _ if received.bytes > 0 => {
+                received.restart().await.map_err(store_err)?;
+                return Ok(Outcome::Restart);
+            }
+            _ => return Err(response::failed(resp).await),
+        },
+        // the partial file is at least as long as the payload, so it isn't a prefix
+        // of the current payload
+        StatusCode::RANGE_NOT_SATISFIABLE if received.bytes > 0 => {
+            received.restart().await.map_err(store_err)?;
+            return Ok(Outcome::Restart);
+        }
+        status if status.is_success() => {
+            // the server ignored the range or the payload changed, so this is the
+            // whole payload
+            if received.bytes > 0 {
+                received.restart().await.map_err(store_err)?;
+            }
+            resp.reqwest.content_length()
+        }
+        _ => return Err(response::failed(resp).await),
+    };
+    *etag = resp
+        .reqwest
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let mut buf = Vec::with_capacity(WRITE_BUFFER_SIZE);
+    loop {
+        let chunk = match resp.reqwest.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                received.append(&buf).await.map_err(store_err)?;
+                return Err(reqwest_err_to_http_client_err(e, resp.meta, trace!()));
+            }
+        };
+        received.hasher.update(&chunk);
+        received.bytes += chunk.len() as u64;
+        buf.extend_from_slice(&chunk);
+        if buf.len() >= WRITE_BUFFER_SIZE {
+            received.append(&buf).await.map_err(store_err)?;
+            buf.clear();
+        }
+        on_progress(Progress {
+            received: received.bytes,
+            total,
+        });
+    }
+    received.append(&buf).await.map_err(store_err)?;
+    Ok(Outcome::Complete)
+}
+
+/// Whether the download can be resumed after the error. A connection dropped while the
+/// body is streaming surfaces from reqwest as a body decoding error rather than a
+/// connection error.
+fn is_resumable(e: &HTTPErr) -> bool {
+    match e {
+        HTTPErr::ReqwestErr(ReqwestErr {
+            kind: ReqwestErrKind::DecodeBody,
+            ..
+        }) => true,
+        e => e.is_network_conn_err(),
+    }
+}
+
+/// The start and, if known, total length of a `Content-Range: bytes <start>-<end>/<total>`
+/// header.
+fn content_range(resp: &reqwest::Response) -> Option<(u64, Option<u64>)> {
+    let value = resp.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+// ================================== RECEIVED ===================================== //
+/// What has been received of the payload: the partial file, its length and the hash
+/// of its content so far.
+struct Received {
+    file: File,
+    bytes: u64,
+    hasher: Hasher,
+}
+
+impl Received {
+    /// Picks up from the partial file a previous run left behind, if any.
+    async fn resume(file: File, algorithm: Algorithm) -> Result<Self, filesys::FileSysErr> {
+        let mut hasher = Hasher::new(algorithm);
+        let mut bytes = 0;
+        if file.exists() {
+            let content = file.read_bytes().await?;
+            hasher.update(&content);
+            bytes = content.len() as u64;
+        }
+        Ok(Self {
+            file,
+            bytes,
+            hasher,
+        })
+    }
+
+    async fn restart(&mut self) -> Result<(), filesys::FileSysErr> {
+        self.file.delete().await?;
+        self.bytes = 0;
+        self.hasher = Hasher::new(self.hasher.algorithm());
+        Ok(())
+    }
+
+    async fn append(&self, buf: &[u8]) -> Result<(), filesys::FileSysErr> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        self.file.append_bytes(buf, AppendOptions::default()).await
+    }
+}
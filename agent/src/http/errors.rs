@@ -1,5 +1,7 @@
 // internal crates
-use crate::errors::{Code, HTTPCode, Trace};
+use crate::crypt::digest::Digest;
+use crate::errors::{classify, Code, HTTPCode, Trace};
+use crate::filesys::FileSysErr;
 use crate::http::request;
 use backend_api::models::ErrorResponse;
 
@@ -160,6 +162,77 @@ pub struct BuildReqwestErr {
 
 impl crate::errors::Error for BuildReqwestErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("invalid egress settings: {msg}")]
+pub struct InvalidEgressErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidEgressErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid client certificate: {msg}")]
+pub struct InvalidClientIdentityErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidClientIdentityErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("replaying request {request} failed: {msg}")]
+pub struct ReplayErr {
+    pub request: request::Meta,
+    pub msg: String,
+    pub is_network_conn_err: bool,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ReplayErr {
+    fn is_network_conn_err(&self) -> bool {
+        self.is_network_conn_err
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("failed to store the download of {request}: {source}")]
+pub struct DownloadFileErr {
+    pub request: request::Meta,
+    pub source: Box<FileSysErr>,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for DownloadFileErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("download of {request} has digest {actual} but {expected} was expected")]
+pub struct DownloadDigestMismatchErr {
+    pub request: request::Meta,
+    pub expected: Box<Digest>,
+    pub actual: Box<Digest>,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for DownloadDigestMismatchErr {}
+
+/// An error from a transport other than the built-in reqwest client, e.g. one an
+/// embedder plugs in through `ClientI`. Whether it is a network connection error is
+/// decided by the classifiers registered with `errors::classify`.
+#[derive(Debug, thiserror::Error)]
+#[error("request {request} failed with transport error: {source}")]
+pub struct TransportErr {
+    pub request: request::Meta,
+    pub source: Box<dyn std::error::Error + Send + Sync>,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for TransportErr {
+    fn is_network_conn_err(&self) -> bool {
+        classify::is_network_conn_err(self.source.as_ref())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Mock error (is network connection error: {is_network_conn_err})")]
 pub struct MockErr {
@@ -191,6 +264,18 @@ pub enum HTTPErr {
     #[error(transparent)]
     BuildReqwestErr(BuildReqwestErr),
     #[error(transparent)]
+    InvalidEgressErr(InvalidEgressErr),
+    #[error(transparent)]
+    InvalidClientIdentityErr(InvalidClientIdentityErr),
+    #[error(transparent)]
+    ReplayErr(ReplayErr),
+    #[error(transparent)]
+    TransportErr(TransportErr),
+    #[error(transparent)]
+    DownloadFileErr(DownloadFileErr),
+    #[error(transparent)]
+    DownloadDigestMismatchErr(DownloadDigestMismatchErr),
+    #[error(transparent)]
     MockErr(MockErr),
 }
 
@@ -203,6 +288,12 @@ crate::impl_error!(HTTPErr {
     UnmarshalJSONErr,
     ReqwestErr,
     BuildReqwestErr,
+    InvalidEgressErr,
+    InvalidClientIdentityErr,
+    ReplayErr,
+    TransportErr,
+    DownloadFileErr,
+    DownloadDigestMismatchErr,
     MockErr,
 });
 
@@ -1,16 +1,26 @@
+pub mod agent_updates;
 pub mod client;
+pub mod conditional;
 pub mod config_instances;
+pub mod config_schemas;
+pub mod crashes;
 pub mod deployments;
 pub mod devices;
+pub mod download;
 pub mod errors;
 pub mod git_commits;
+pub mod priority;
 pub mod query;
+pub mod record;
 pub mod releases;
 pub mod request;
 pub mod response;
 pub mod retry;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
 
 pub use self::errors::HTTPErr;
+pub use self::priority::Priority;
 pub use self::query::QueryParams;
 pub use client::{Client, ClientI};
-pub use retry::with_retry;
+pub use retry::{with_retry, RetryPolicy};
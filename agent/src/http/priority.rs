@@ -0,0 +1,192 @@
+// standard crates
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// external crates
+use tokio::sync::oneshot;
+
+/// The scheduling class of an outbound request. When the client is saturated,
+/// waiting requests are admitted highest class first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Telemetry and diagnostics uploads.
+    Low,
+    /// Fetching deployments, releases, and config instances.
+    #[default]
+    Normal,
+    /// Reporting device and deployment status.
+    High,
+    /// Authentication and provisioning.
+    Critical,
+}
+
+impl Priority {
+    const COUNT: usize = 4;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// The maximum number of requests in flight at once.
+    pub max_concurrent: usize,
+    /// How long a request may wait before it is admitted ahead of higher classes.
+    pub starvation_timeout: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            starvation_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Waiter {
+    enqueued_at: Instant,
+    grant: oneshot::Sender<()>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: usize,
+    waiting: [VecDeque<Waiter>; Priority::COUNT],
+}
+
+impl State {
+    fn is_waiting(&self) -> bool {
+        self.waiting.iter().any(|queue| !queue.is_empty())
+    }
+
+    /// Removes the next waiter to admit: the longest waiting request if it has
+    /// exceeded the starvation timeout, otherwise the oldest request of the highest
+    /// waiting class.
+    fn next_waiter(&mut self, starvation_timeout: Duration) -> Option<Waiter> {
+        let starved = self
+            .waiting
+            .iter()
+            .enumerate()
+            .filter_map(|(i, queue)| queue.front().map(|w| (i, w.enqueued_at)))
+            .filter(|(_, enqueued_at)| enqueued_at.elapsed() >= starvation_timeout)
+            .min_by_key(|(_, enqueued_at)| *enqueued_at)
+            .map(|(i, _)| i);
+        let index = starved.or_else(|| self.waiting.iter().rposition(|q| !q.is_empty()))?;
+        self.waiting[index].pop_front()
+    }
+}
+
+/// Admits requests into a fixed number of concurrency slots by priority class.
+#[derive(Debug)]
+pub struct Scheduler {
+    options: Options,
+    state: Mutex<State>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
+}
+
+impl Scheduler {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options: Options {
+                max_concurrent: options.max_concurrent.max(1),
+                ..options
+            },
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn options(&self) -> Options {
+        self.options
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    pub fn num_waiting(&self, priority: Priority) -> usize {
+        self.lock().waiting[priority.index()].len()
+    }
+
+    /// Waits for a concurrency slot. The slot is released when the returned permit
+    /// is dropped.
+    pub async fn acquire(&self, priority: Priority) -> Permit<'_> {
+        let grant = {
+            let mut state = self.lock();
+            if state.in_flight < self.options.max_concurrent && !state.is_waiting() {
+                state.in_flight += 1;
+                return Permit { scheduler: self };
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting[priority.index()].push_back(Waiter {
+                enqueued_at: Instant::now(),
+                grant: tx,
+            });
+            rx
+        };
+
+        let mut pending = Pending {
+            scheduler: self,
+            grant: Some(grant),
+        };
+        if let Some(grant) = pending.grant.as_mut() {
+            // the sender is only dropped after a successful send
+            let _ = grant.await;
+        }
+        pending.grant = None;
+        Permit { scheduler: self }
+    }
+
+    fn release(&self) {
+        let mut state = self.lock();
+        // hand the slot directly to the next waiter, skipping cancelled requests
+        while let Some(waiter) = state.next_waiter(self.options.starvation_timeout) {
+            if waiter.grant.send(()).is_ok() {
+                return;
+            }
+        }
+        state.in_flight = state.in_flight.saturating_sub(1);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A concurrency slot held for the duration of a request.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+// Releases a slot that was granted to a request which was cancelled before it
+// could claim it.
+struct Pending<'a> {
+    scheduler: &'a Scheduler,
+    grant: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(mut grant) = self.grant.take() {
+            grant.close();
+            if grant.try_recv().is_ok() {
+                self.scheduler.release();
+            }
+        }
+    }
+}
@@ -0,0 +1,362 @@
+// Record and replay of backend exchanges. A recorder attached to the HTTP client
+// captures every response the backend sends, sanitized, to a capture file which a
+// replayer later serves to the syncer offline so that field-reported reconciliation
+// bugs can be reproduced deterministically. The capture file is JSON lines: a header
+// with the agent version and when recording started, then one exchange per line,
+// appended as it happens so that the capture survives the agent being killed.
+
+// standard crates
+use std::sync::Mutex;
+
+// internal crates
+use crate::diagnostics::Redactor;
+use crate::filesys::{
+    errors::{FileSysErr, ParseJSONErr},
+    AppendOptions, File, WriteOptions,
+};
+use crate::http::{
+    errors::{HTTPErr, ReplayErr, RequestFailed},
+    request, ClientI,
+};
+use crate::trace;
+use crate::version;
+use backend_api::models::ErrorResponse;
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+/// The most a capture file may grow to. Exchanges which would grow it past this are
+/// dropped so that a recording left running can't fill the disk.
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Everything the backend sent the agent while recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+    pub agent_version: String,
+    pub recorded_at: DateTime<Utc>,
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            agent_version: version::VERSION.to_string(),
+            recorded_at: Utc::now(),
+            exchanges: Vec::new(),
+        }
+    }
+}
+
+impl Capture {
+    /// Reads a capture file. Exchanges which don't parse, such as a last line cut
+    /// short by the agent being killed mid-write, are skipped.
+    pub async fn read(file: &File) -> Result<Self, FileSysErr> {
+        let content = file.read_string().await?;
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        let header =
+            serde_json::from_str::<Header>(lines.next().unwrap_or_default()).map_err(|e| {
+                FileSysErr::ParseJSONErr(ParseJSONErr {
+                    source: Box::new(e),
+                    file: file.clone(),
+                    trace: trace!(),
+                })
+            })?;
+        let mut exchanges = Vec::new();
+        for (line_num, line) in lines.enumerate() {
+            match serde_json::from_str::<Exchange>(line) {
+                Ok(exchange) => exchanges.push(exchange),
+                // the header is the first line
+                Err(e) => warn!("skipping malformed exchange at line {}: {e}", line_num + 2),
+            }
+        }
+        Ok(Self {
+            agent_version: header.agent_version,
+            recorded_at: header.recorded_at,
+            exchanges,
+        })
+    }
+}
+
+/// The first line of a capture file.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    agent_version: String,
+    recorded_at: DateTime<Utc>,
+}
+
+/// A single request and the backend's response to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    /// The request URL without the backend's base URL so that captures don't leak
+    /// which backend a device talks to and can be replayed against any base URL.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<Value>,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Outcome {
+    Success {
+        body: Value,
+    },
+    Failed {
+        status: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<Value>,
+    },
+    /// The backend couldn't be reached or the response couldn't be read.
+    Unreachable {
+        error: String,
+        is_network_conn_err: bool,
+    },
+}
+
+// ================================== RECORDER ===================================== //
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+    redactor: Redactor,
+    max_bytes: u64,
+    state: tokio::sync::Mutex<RecordState>,
+}
+
+#[derive(Debug, Default)]
+struct RecordState {
+    /// Whether the header has been written, replacing any earlier capture.
+    started: bool,
+    bytes: u64,
+    full: bool,
+}
+
+impl Recorder {
+    pub fn new(file: File, redactor: Redactor) -> Self {
+        Self {
+            file,
+            redactor,
+            max_bytes: DEFAULT_MAX_BYTES,
+            state: tokio::sync::Mutex::new(RecordState::default()),
+        }
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Sanitizes an exchange and appends it to the capture file, unless that would
+    /// grow the file past its limit. Failing to record never fails the request
+    /// itself.
+    pub async fn record(
+        &self,
+        base_url: &str,
+        meta: &request::Meta,
+        request_body: Option<&str>,
+        result: &Result<String, HTTPErr>,
+    ) {
+        let outcome = match result {
+            Ok(text) => Outcome::Success {
+                body: self.sanitize(text),
+            },
+            Err(HTTPErr::RequestFailed(e)) => Outcome::Failed {
+                status: e.status.as_u16(),
+                error: e.error.as_ref().and_then(|error| {
+                    let mut value = serde_json::to_value(error).ok()?;
+                    self.redactor.redact_json(&mut value);
+                    Some(value)
+                }),
+            },
+            Err(e) => Outcome::Unreachable {
+                error: self.redactor.redact_text(&e.to_string()),
+                is_network_conn_err: crate::errors::Error::is_network_conn_err(e),
+            },
+        };
+        let exchange = Exchange {
+            method: meta.method.to_string(),
+            path: relative_path(base_url, &meta.url),
+            request_body: request_body.map(|body| self.sanitize(body)),
+            outcome,
+        };
+        let line = match serde_json::to_string(&exchange) {
+            Ok(json) => format!("{json}\n"),
+            Err(e) => {
+                warn!("unable to serialize a backend exchange: {e}");
+                return;
+            }
+        };
+
+        let mut state = self.state.lock().await;
+        if !state.started {
+            match self.start().await {
+                Ok(bytes) => state.bytes = bytes,
+                Err(e) => {
+                    warn!("unable to write the backend capture: {e}");
+                    return;
+                }
+            }
+            state.started = true;
+        }
+        if state.full {
+            return;
+        }
+        if state.bytes + line.len() as u64 > self.max_bytes {
+            state.full = true;
+            warn!(
+                "the backend capture reached its limit of {} bytes; no more exchanges are recorded",
+                self.max_bytes
+            );
+            return;
+        }
+        match self
+            .file
+            .append_bytes(line.as_bytes(), AppendOptions::default())
+            .await
+        {
+            Ok(()) => state.bytes += line.len() as u64,
+            Err(e) => warn!("unable to write the backend capture: {e}"),
+        }
+    }
+
+    /// Replaces any earlier capture with a new one holding only the header and
+    /// returns its size.
+    async fn start(&self) -> Result<u64, FileSysErr> {
+        let capture = Capture::default();
+        let header = Header {
+            agent_version: capture.agent_version,
+            recorded_at: capture.recorded_at,
+        };
+        let line = format!("{}\n", serde_json::to_string(&header).unwrap_or_default());
+        self.file
+            .write_string(&line, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        Ok(line.len() as u64)
+    }
+
+    fn sanitize(&self, text: &str) -> Value {
+        match serde_json::from_str::<Value>(text) {
+            Ok(mut value) => {
+                self.redactor.redact_json(&mut value);
+                value
+            }
+            Err(_) => Value::String(self.redactor.redact_text(text)),
+        }
+    }
+}
+
+fn relative_path(base_url: &str, url: &str) -> String {
+    url.strip_prefix(base_url).unwrap_or(url).to_string()
+}
+
+// ================================== REPLAYER ===================================== //
+/// Serves a capture in place of the backend. Each request is answered by the first
+/// exchange with the same method and path which hasn't been served yet.
+#[derive(Debug)]
+pub struct Replayer {
+    base_url: String,
+    state: Mutex<ReplayState>,
+}
+
+#[derive(Debug)]
+struct ReplayState {
+    exchanges: Vec<(Exchange, bool)>,
+    misses: Vec<String>,
+}
+
+/// How a capture was used by a replay.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub served: usize,
+    /// Exchanges which no request asked for.
+    pub unused: usize,
+    /// Requests which the capture has no response for.
+    pub misses: Vec<String>,
+}
+
+impl Replayer {
+    pub fn new(base_url: &str, capture: Capture) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            state: Mutex::new(ReplayState {
+                exchanges: capture.exchanges.into_iter().map(|e| (e, false)).collect(),
+                misses: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn usage(&self) -> Usage {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let served = state.exchanges.iter().filter(|(_, used)| *used).count();
+        Usage {
+            served,
+            unused: state.exchanges.len() - served,
+            misses: state.misses.clone(),
+        }
+    }
+
+    fn next(&self, method: &str, path: &str) -> Option<Outcome> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let found = state
+            .exchanges
+            .iter_mut()
+            .find(|(e, used)| !*used && e.method == method && e.path == path);
+        match found {
+            Some((exchange, used)) => {
+                *used = true;
+                Some(exchange.outcome.clone())
+            }
+            None => {
+                state.misses.push(format!("{method} {path}"));
+                None
+            }
+        }
+    }
+}
+
+impl ClientI for Replayer {
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn execute(
+        &self,
+        params: request::Params<'_>,
+    ) -> Result<(String, request::Meta), HTTPErr> {
+        let meta = params.meta()?;
+        let path = relative_path(&self.base_url, &meta.url);
+        let outcome = self.next(meta.method.as_str(), &path);
+        match outcome {
+            Some(Outcome::Success { body }) => Ok((body.to_string(), meta)),
+            Some(Outcome::Failed { status, error }) => Err(HTTPErr::RequestFailed(RequestFailed {
+                status: reqwest::StatusCode::from_u16(status)
+                    .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR),
+                error: error.and_then(|e| serde_json::from_value::<ErrorResponse>(e).ok()),
+                request: meta,
+                trace: trace!(),
+            })),
+            Some(Outcome::Unreachable {
+                error,
+                is_network_conn_err,
+            }) => Err(HTTPErr::ReplayErr(ReplayErr {
+                request: meta,
+                msg: error,
+                is_network_conn_err,
+                trace: trace!(),
+            })),
+            None => Err(HTTPErr::ReplayErr(ReplayErr {
+                request: meta,
+                msg: "the capture has no response for this request".to_string(),
+                is_network_conn_err: false,
+                trace: trace!(),
+            })),
+        }
+    }
+}
@@ -2,22 +2,31 @@
 use std::fmt;
 
 // internal crates
+use crate::cell;
 use crate::http::{
-    errors::{BuildReqwestErr, HTTPErr, InvalidHeaderValueErr, InvalidURLErr, MarshalJSONErr},
+    errors::{
+        BuildReqwestErr, HTTPErr, InvalidEgressErr, InvalidHeaderValueErr, InvalidURLErr,
+        MarshalJSONErr,
+    },
+    priority::Priority,
     query::QueryParams,
+    retry::RetryPolicy,
 };
-use crate::telemetry::SystemInfo;
+use crate::platform;
+#[cfg(feature = "telemetry")]
+use crate::telemetry::Capabilities;
 use crate::trace;
 use crate::version;
 
 // external crates
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
 use serde::Serialize;
 use tokio::time::Duration;
+use zeroize::Zeroizing;
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Params<'a> {
     pub method: reqwest::Method,
     pub url: &'a str,
@@ -25,6 +34,31 @@ pub struct Params<'a> {
     pub body: Option<String>,
     pub timeout: Duration,
     pub token: Option<&'a str>,
+    pub priority: Priority,
+    /// Headers sent in addition to the agent's standard headers.
+    pub headers: Vec<(&'static str, String)>,
+    /// Held open by the backend until it has something to send. Long polls bypass the
+    /// scheduler so they don't hold one of its slots while they wait.
+    pub long_poll: bool,
+    /// Overrides the client's retry policy for this request.
+    pub retry: Option<RetryPolicy>,
+}
+
+impl fmt::Debug for Params<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Params")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("query", &self.query)
+            .field("body", &self.body)
+            .field("timeout", &self.timeout)
+            .field("token", &self.token.map(|_| "[REDACTED]"))
+            .field("priority", &self.priority)
+            .field("headers", &self.headers)
+            .field("long_poll", &self.long_poll)
+            .field("retry", &self.retry)
+            .finish()
+    }
 }
 
 impl<'a> Params<'a> {
@@ -65,6 +99,10 @@ impl<'a> Params<'a> {
             body: None,
             timeout: DEFAULT_TIMEOUT,
             token: None,
+            priority: Priority::Normal,
+            headers: Vec::new(),
+            long_poll: false,
+            retry: None,
         }
     }
 
@@ -76,6 +114,10 @@ impl<'a> Params<'a> {
             body: Some(body),
             timeout: DEFAULT_TIMEOUT,
             token: None,
+            priority: Priority::Normal,
+            headers: Vec::new(),
+            long_poll: false,
+            retry: None,
         }
     }
 
@@ -87,6 +129,25 @@ impl<'a> Params<'a> {
             body: Some(body),
             timeout: DEFAULT_TIMEOUT,
             token: None,
+            priority: Priority::Normal,
+            headers: Vec::new(),
+            long_poll: false,
+            retry: None,
+        }
+    }
+
+    pub fn delete(url: &'a str) -> Self {
+        Self {
+            method: reqwest::Method::DELETE,
+            url,
+            query: Vec::new(),
+            body: None,
+            timeout: DEFAULT_TIMEOUT,
+            token: None,
+            priority: Priority::Normal,
+            headers: Vec::new(),
+            long_poll: false,
+            retry: None,
         }
     }
 
@@ -100,10 +161,30 @@ impl<'a> Params<'a> {
         self
     }
 
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     pub fn with_query(mut self, qp: QueryParams) -> Self {
         self.query = qp.into_pairs();
         self
     }
+
+    pub fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    pub fn as_long_poll(mut self) -> Self {
+        self.long_poll = true;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
 }
 
 pub struct Request {
@@ -135,12 +216,24 @@ pub struct Headers {
     // build information
     pub agent_version: String,
     pub api_version: String,
+    /// e.g. "miru-agent/v0.7.0 (linux; aarch64; commit 1a2b3c4)"
+    pub user_agent: String,
 
     // host information
     pub host_name: String,
     pub arch: String,
     pub language: String,
     pub os: String,
+    /// The device's hardware capabilities as JSON. Detected when the client is built.
+    pub capabilities: String,
+
+    // cell information
+    /// Authenticates a cell peer to the controller its requests go through.
+    pub cell_peer: Option<cell::Upstream>,
+
+    // fleet information
+    /// Fleet-defined headers from the network settings.
+    pub fleet: Vec<(String, String)>,
 }
 
 impl Default for Headers {
@@ -149,12 +242,20 @@ impl Default for Headers {
             // build information
             agent_version: version::VERSION.to_string(),
             api_version: backend_api::models::ApiVersion::API_VERSION.to_string(),
+            user_agent: user_agent(),
 
             // host information
-            host_name: SystemInfo::host_name(),
-            arch: SystemInfo::arch(),
+            host_name: platform::host_name(),
+            arch: platform::arch(),
             language: "rust".to_string(),
-            os: SystemInfo::os(),
+            os: platform::os(),
+            capabilities: capabilities(),
+
+            // cell information
+            cell_peer: None,
+
+            // fleet information
+            fleet: Vec::new(),
         }
     }
 }
@@ -162,16 +263,49 @@ impl Default for Headers {
 impl Headers {
     pub fn to_map(&self) -> Result<HeaderMap, HTTPErr> {
         let mut headers = HeaderMap::new();
+        // inserted first so the agent's own headers always win
+        for (name, value) in &self.fleet {
+            insert_fleet_header(&mut headers, name, value)?;
+        }
+        insert_header(&mut headers, "User-Agent", &self.user_agent)?;
         insert_header(&mut headers, "Miru-Version", &self.api_version)?;
         insert_header(&mut headers, "Miru-Agent-Version", &self.agent_version)?;
         insert_header(&mut headers, "Miru-Agent-Host-Name", &self.host_name)?;
         insert_header(&mut headers, "Miru-Agent-Arch", &self.arch)?;
         insert_header(&mut headers, "Miru-Agent-Language", &self.language)?;
         insert_header(&mut headers, "Miru-Agent-OS", &self.os)?;
+        insert_header(&mut headers, "Miru-Agent-Capabilities", &self.capabilities)?;
+        if let Some(upstream) = &self.cell_peer {
+            insert_header(&mut headers, cell::PEER_HEADER, &upstream.peer_id)?;
+            insert_header(&mut headers, cell::TOKEN_HEADER, &upstream.token)?;
+        }
         Ok(headers)
     }
 }
 
+/// Identifies the agent build and the host it runs on, so backend logs and WAF rules
+/// can segment traffic by agent build.
+pub fn user_agent() -> String {
+    format!(
+        "miru-agent/{} ({}; {}; commit {})",
+        version::VERSION,
+        platform::os(),
+        platform::arch(),
+        version::COMMIT,
+    )
+}
+
+#[cfg(feature = "telemetry")]
+fn capabilities() -> String {
+    Capabilities::detect().to_json()
+}
+
+/// Capabilities aren't detected without the telemetry feature.
+#[cfg(not(feature = "telemetry"))]
+fn capabilities() -> String {
+    "{}".to_string()
+}
+
 fn insert_header(headers: &mut HeaderMap, key: &'static str, value: &str) -> Result<(), HTTPErr> {
     match HeaderValue::from_str(value) {
         Ok(value) => {
@@ -186,6 +320,24 @@ fn insert_header(headers: &mut HeaderMap, key: &'static str, value: &str) -> Res
     }
 }
 
+fn insert_fleet_header(headers: &mut HeaderMap, name: &str, value: &str) -> Result<(), HTTPErr> {
+    let key = HeaderName::from_bytes(name.as_bytes()).map_err(|e| {
+        HTTPErr::InvalidEgressErr(InvalidEgressErr {
+            msg: format!("invalid header name `{name}`: {e}"),
+            trace: trace!(),
+        })
+    })?;
+    let value = HeaderValue::from_str(value).map_err(|source| {
+        HTTPErr::InvalidHeaderValueErr(InvalidHeaderValueErr {
+            msg: format!("failed to set header {name}: {source}"),
+            source,
+            trace: trace!(),
+        })
+    })?;
+    headers.insert(key, value);
+    Ok(())
+}
+
 pub fn marshal_json<T>(payload: &T) -> Result<String, HTTPErr>
 where
     T: Serialize,
@@ -212,6 +364,9 @@ pub fn build(
     }
     // headers
     let mut header_map = headers.to_map()?;
+    for (name, value) in &params.headers {
+        insert_header(&mut header_map, name, value)?;
+    }
     if let Some(token) = params.token {
         add_token_to_headers(&mut header_map, token)?;
     }
@@ -234,15 +389,16 @@ pub fn build(
 }
 
 fn add_token_to_headers(headers: &mut HeaderMap, token: &str) -> Result<(), HTTPErr> {
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {token}")).map_err(|e| {
-            HTTPErr::InvalidHeaderValueErr(InvalidHeaderValueErr {
-                msg: e.to_string(),
-                source: e,
-                trace: trace!(),
-            })
-        })?,
-    );
+    let bearer = Zeroizing::new(format!("Bearer {token}"));
+    let mut value = HeaderValue::from_str(&bearer).map_err(|e| {
+        HTTPErr::InvalidHeaderValueErr(InvalidHeaderValueErr {
+            msg: e.to_string(),
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    // keeps the token out of the request's Debug output
+    value.set_sensitive(true);
+    headers.insert(AUTHORIZATION, value);
     Ok(())
 }
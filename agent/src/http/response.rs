@@ -16,28 +16,30 @@ pub struct Response {
 }
 
 pub async fn handle(resp: Response) -> Result<String, HTTPErr> {
-    let status = resp.reqwest.status();
-    let meta = resp.meta;
-
-    if !status.is_success() {
-        let error_response = match resp.reqwest.text().await {
-            Ok(text) => parse_json::<ErrorResponse>(text, meta.clone()).ok(),
-            Err(_) => None,
-        };
-        Err(HTTPErr::RequestFailed(RequestFailed {
-            request: meta,
-            status,
-            error: error_response,
-            trace: trace!(),
-        }))
-    } else {
-        match resp.reqwest.text().await {
-            Ok(text) => Ok(text),
-            Err(e) => Err(reqwest_err_to_http_client_err(e, meta, trace!())),
-        }
+    if !resp.reqwest.status().is_success() {
+        return Err(failed(resp).await);
+    }
+    match resp.reqwest.text().await {
+        Ok(text) => Ok(text),
+        Err(e) => Err(reqwest_err_to_http_client_err(e, resp.meta, trace!())),
     }
 }
 
+/// The error for an unsuccessful response, with the backend's error body if it sent one.
+pub async fn failed(resp: Response) -> HTTPErr {
+    let status = resp.reqwest.status();
+    let error_response = match resp.reqwest.text().await {
+        Ok(text) => parse_json::<ErrorResponse>(text, resp.meta.clone()).ok(),
+        Err(_) => None,
+    };
+    HTTPErr::RequestFailed(RequestFailed {
+        request: resp.meta,
+        status,
+        error: error_response,
+        trace: trace!(),
+    })
+}
+
 pub fn parse_json<T>(text: String, meta: request::Meta) -> Result<T, HTTPErr>
 where
     T: DeserializeOwned,
@@ -1,46 +1,238 @@
+// Retries of requests which failed with a network connection error. Non-network
+// errors (4xx, 5xx, decode, application) fail immediately. The client retries its
+// requests itself according to each request's `RetryPolicy`, drawing from a retry
+// budget shared by all of its requests so that an outage doesn't multiply the load on
+// the backend. `with_retry` gives transports which don't retry (mocks, replays,
+// embedders' clients) the same policy.
+
+// standard crates
+use std::sync::Mutex;
+use std::time::Duration;
+
+// internal crates
+use crate::cooldown;
+use crate::http::ClientI;
+
 // external crates
 use tracing::debug;
 
-const MAX_RETRIES: u32 = 2; // up to 3 total attempts
+// ================================== POLICY ======================================= //
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first.
+    pub max_attempts: u32,
+    /// The delay before each retry, growing with the number of retries so far.
+    pub backoff: cooldown::Backoff,
+    /// Whether requests whose method isn't idempotent (POST, PATCH) are retried. A
+    /// request which failed with a network error may still have been applied.
+    pub retry_non_idempotent: bool,
+}
+
+impl RetryPolicy {
+    /// A single attempt.
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        backoff: cooldown::Backoff {
+            base_secs: 0,
+            growth_factor: 1,
+            max_secs: 0,
+        },
+        retry_non_idempotent: false,
+    };
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Retries non-idempotent requests too, for requests the backend applies
+    /// idempotently (e.g. setting a deployment's status).
+    pub fn retry_non_idempotent(mut self) -> Self {
+        self.retry_non_idempotent = true;
+        self
+    }
 
-/// Computes the retry delay with jitter, using subsecond nanos to avoid
-/// adding a rand dependency. Not cryptographic, just enough to spread retries.
+    /// Whether a request with the method may be retried at all.
+    pub fn allows(&self, method: &reqwest::Method) -> bool {
+        self.max_attempts > 1 && (self.retry_non_idempotent || method.is_idempotent())
+    }
+
+    /// The delay before the given retry (the first retry is 1).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let secs = cooldown::calc(&self.backoff, retry.saturating_sub(1)).max(0) as u64;
+        Duration::from_secs(secs) + jitter()
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Up to 3 attempts, 1s then 2s apart.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: 10,
+            },
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+/// Up to half a second of jitter, using subsecond nanos to avoid adding a rand
+/// dependency. Not cryptographic, just enough to spread retries.
 #[cfg(not(feature = "test"))]
-fn retry_delay_ms() -> u64 {
-    const BASE_MS: u64 = 500;
-    const JITTER_MS: u64 = 500; // delay = base + [0, jitter)
+fn jitter() -> Duration {
+    const JITTER_MS: u64 = 500;
     let nanos = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .subsec_nanos() as u64;
-    BASE_MS + (nanos % JITTER_MS)
+    Duration::from_millis(nanos % JITTER_MS)
+}
+
+#[cfg(feature = "test")]
+fn jitter() -> Duration {
+    Duration::ZERO
+}
+
+#[cfg(not(feature = "test"))]
+async fn sleep(delay: Duration) {
+    tokio::time::sleep(delay).await;
 }
 
 #[cfg(feature = "test")]
-fn retry_delay_ms() -> u64 {
-    0
+async fn sleep(_: Duration) {}
+
+// ================================== BUDGET ======================================= //
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BudgetOptions {
+    /// The budget's capacity. Retries are allowed while more than half remains.
+    pub max_tokens: u32,
+    /// The tokens each successful request returns to the budget.
+    pub token_ratio: f64,
+}
+
+impl Default for BudgetOptions {
+    fn default() -> Self {
+        Self {
+            max_tokens: 100,
+            token_ratio: 0.1,
+        }
+    }
+}
+
+/// Throttles retries when most requests are failing, as in gRPC's retry throttling:
+/// each failed attempt takes a token, each success returns `token_ratio` tokens, and
+/// requests are only retried while the budget is more than half full. With the
+/// defaults, 50 failures more than one in ten requests stop retries until requests
+/// succeed again.
+#[derive(Debug)]
+pub struct Budget {
+    options: BudgetOptions,
+    tokens: Mutex<f64>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new(BudgetOptions::default())
+    }
 }
 
-/// Retries an async operation up to 3 times on network connection errors.
-/// Non-network errors (4xx, 5xx, decode, application) fail immediately.
-pub async fn with_retry<F, Fut, T, E>(f: F) -> Result<T, E>
+impl Budget {
+    pub fn new(options: BudgetOptions) -> Self {
+        Self {
+            tokens: Mutex::new(options.max_tokens as f64),
+            options,
+        }
+    }
+
+    pub fn tokens(&self) -> f64 {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn record_success(&self) {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        *tokens = (*tokens + self.options.token_ratio).min(self.options.max_tokens as f64);
+    }
+
+    /// Records a failed attempt and returns whether it may be retried.
+    pub fn record_failure(&self) -> bool {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        *tokens = (*tokens - 1.0).max(0.0);
+        *tokens > self.options.max_tokens as f64 / 2.0
+    }
+}
+
+// ================================ WITH RETRY ===================================== //
+/// Retries an async operation on network connection errors with the default policy,
+/// unless the client already retries its requests. Use it around calls to clients
+/// which may not retry, so the operation is retried once either way.
+pub async fn with_retry<C, F, Fut, T, E>(client: &C, f: F) -> Result<T, E>
 where
+    C: ClientI + ?Sized,
     F: Fn() -> Fut,
     Fut: std::future::Future<Output = Result<T, E>>,
     E: crate::errors::Error + std::fmt::Display,
 {
-    let mut attempt = 0;
+    if client.retries_requests() {
+        return f().await;
+    }
+    let policy = RetryPolicy::default();
+    let mut attempt = 1;
     loop {
         match f().await {
             Ok(val) => return Ok(val),
             Err(e) => {
-                if !e.is_network_conn_err() || attempt >= MAX_RETRIES {
+                if !e.is_network_conn_err() || attempt >= policy.max_attempts {
+                    return Err(e);
+                }
+                let delay = policy.delay(attempt);
+                debug!("network error on attempt {attempt}, retrying in {delay:?}: {e}");
+                sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Runs the attempts of a request: retried per the policy on network errors while the
+/// budget allows.
+pub(crate) async fn run<F, Fut, T, E>(
+    policy: &RetryPolicy,
+    method: &reqwest::Method,
+    budget: &Budget,
+    f: F,
+) -> Result<T, E>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: crate::errors::Error + std::fmt::Display,
+{
+    let retryable = policy.allows(method);
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(val) => {
+                budget.record_success();
+                return Ok(val);
+            }
+            Err(e) => {
+                if !e.is_network_conn_err() {
+                    return Err(e);
+                }
+                let within_budget = budget.record_failure();
+                if !retryable || attempt >= policy.max_attempts {
+                    return Err(e);
+                }
+                if !within_budget {
+                    debug!("retry budget exhausted, not retrying: {e}");
                     return Err(e);
                 }
+                let delay = policy.delay(attempt);
+                debug!("network error on attempt {attempt}, retrying in {delay:?}: {e}");
+                sleep(delay).await;
                 attempt += 1;
-                let delay = retry_delay_ms();
-                debug!("network error on attempt {attempt}, retrying in {delay}ms: {e}");
-                tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
             }
         }
     }
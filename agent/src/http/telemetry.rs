@@ -0,0 +1,39 @@
+// internal crates
+use crate::http::{errors::HTTPErr, priority::Priority, request, ClientI};
+use crate::telemetry::Sample;
+
+// external crates
+use serde::Serialize;
+
+// ================================ PARAM STRUCTS ================================== //
+
+pub struct ReportParams<'a> {
+    pub device_id: &'a str,
+    pub samples: &'a [Sample],
+    pub token: &'a str,
+}
+
+// The generated client doesn't include telemetry yet so its request is defined here.
+#[derive(Serialize)]
+pub struct ReportRequest<'a> {
+    pub samples: &'a [Sample],
+}
+
+// ================================ FREE FUNCTIONS ================================= //
+/// Reports the device's resource usage samples, oldest first.
+pub async fn report(client: &impl ClientI, params: ReportParams<'_>) -> Result<(), HTTPErr> {
+    let url = format!(
+        "{}/devices/{}/telemetry",
+        client.base_url(),
+        params.device_id
+    );
+    let body = ReportRequest {
+        samples: params.samples,
+    };
+    let request = request::Params::post(&url, request::marshal_json(&body)?)
+        .with_token(params.token)
+        .with_priority(Priority::Low);
+    // the backend's acknowledgement has nothing worth reading
+    client.execute(request).await?;
+    Ok(())
+}
@@ -0,0 +1,16 @@
+// internal crates
+use crate::filesys;
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalErr {
+    #[error(transparent)]
+    FileSysErr(filesys::FileSysErr),
+}
+
+impl From<filesys::FileSysErr> for JournalErr {
+    fn from(e: filesys::FileSysErr) -> Self {
+        Self::FileSysErr(e)
+    }
+}
+
+crate::impl_error!(JournalErr { FileSysErr });
@@ -0,0 +1,7 @@
+pub mod errors;
+pub mod queue;
+pub mod request;
+
+pub use self::errors::JournalErr;
+pub use self::queue::{Drained, Entry, Journal, Options, Retention};
+pub use self::request::{Kind, Request};
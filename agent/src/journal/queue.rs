@@ -0,0 +1,354 @@
+// standard crates
+use std::collections::{HashMap, HashSet};
+
+// internal crates
+use crate::diagnostics::crash;
+use crate::errors::Error;
+use crate::filesys::{self, PathExt, WriteOptions};
+use crate::http;
+use crate::journal::{
+    errors::JournalErr,
+    request::{Kind, Request},
+};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+
+// ================================== OPTIONS ====================================== //
+/// How long requests of a kind are kept while the backend is unreachable. The oldest
+/// requests are dropped first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retention {
+    pub max_entries: usize,
+    pub max_age: TimeDelta,
+    /// How many times a request is sent before it's dropped if the backend keeps
+    /// failing it. Network errors, rejected tokens and server errors (5xx) aren't
+    /// counted since resending the request later may well succeed.
+    pub max_attempts: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_entries: 1_000,
+            max_age: TimeDelta::days(7),
+            max_attempts: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Retention per kind of request. Kinds without an entry use the default
+    /// retention.
+    pub retention: HashMap<Kind, Retention>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        let mut retention = HashMap::new();
+        // crash reports are large and only the latest few are worth keeping
+        retention.insert(
+            Kind::CrashReport,
+            Retention {
+                max_entries: crash::MAX_PENDING,
+                ..Retention::default()
+            },
+        );
+        // an hour of samples at the default interval
+        #[cfg(feature = "telemetry")]
+        retention.insert(
+            Kind::Telemetry,
+            Retention {
+                max_entries: 60,
+                max_age: TimeDelta::days(1),
+                ..Retention::default()
+            },
+        );
+        Self { retention }
+    }
+}
+
+impl Options {
+    pub fn retention(&self, kind: Kind) -> Retention {
+        self.retention.get(&kind).copied().unwrap_or_default()
+    }
+}
+
+// ================================== JOURNAL ====================================== //
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    pub enqueued_at: DateTime<Utc>,
+    /// The failed sends counted against the retention's `max_attempts`.
+    pub attempts: u32,
+    pub request: Request,
+}
+
+/// The outcome of draining the journal.
+#[derive(Debug, Default)]
+pub struct Drained {
+    pub sent: usize,
+    /// Requests dropped because the backend rejected them or kept failing them.
+    pub dropped: usize,
+    /// Whether the backend rejected the token (401, 403). The drain stops there and
+    /// the requests stay queued for a drain with a refreshed token.
+    pub unauthorized: bool,
+    pub errors: Vec<http::HTTPErr>,
+}
+
+#[derive(Debug)]
+struct Entries {
+    entries: Vec<Entry>,
+    next_id: u64,
+}
+
+/// A persisted queue of device-originated backend calls. Requests survive agent
+/// restarts and are sent in the order they were queued, per key, once the backend
+/// is reachable.
+#[derive(Debug)]
+pub struct Journal {
+    file: filesys::File,
+    options: Options,
+    // held for the entire drain so requests with the same key are never sent
+    // concurrently or out of order
+    draining: Mutex<()>,
+    // never held while a request is sent, so requests can be queued during a drain
+    inner: Mutex<Entries>,
+}
+
+impl Journal {
+    /// Loads the journal from its file. An unreadable journal is discarded so the
+    /// agent always comes up, as are the entries of kinds this build doesn't send.
+    pub async fn init(file: filesys::File, options: Options) -> Result<Self, JournalErr> {
+        let entries = if file.exists() {
+            match file.read_json::<Vec<serde_json::Value>>().await {
+                Ok(entries) => entries
+                    .into_iter()
+                    .filter_map(|entry| match serde_json::from_value::<Entry>(entry) {
+                        Ok(entry) => Some(entry),
+                        Err(e) => {
+                            warn!("discarding unreadable queued request: {e}");
+                            None
+                        }
+                    })
+                    .collect(),
+                Err(e) => {
+                    error!("discarding unreadable request journal: {e}");
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        Ok(Self {
+            file,
+            options,
+            draining: Mutex::new(()),
+            inner: Mutex::new(Entries { entries, next_id }),
+        })
+    }
+
+    pub async fn entries(&self) -> Vec<Entry> {
+        self.inner.lock().await.entries.clone()
+    }
+
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.entries.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    pub async fn enqueue(&self, request: Request) -> Result<(), JournalErr> {
+        let mut inner = self.inner.lock().await;
+        let now = Utc::now();
+        let entry = Entry {
+            id: inner.next_id,
+            enqueued_at: now,
+            attempts: 0,
+            request,
+        };
+        inner.next_id += 1;
+
+        let existing = entry
+            .request
+            .supersedes()
+            .then(|| {
+                let key = entry.request.key();
+                inner.entries.iter().position(|e| e.request.key() == key)
+            })
+            .flatten();
+        match existing {
+            // the same status is already queued, keep its place and attempts
+            Some(i) if inner.entries[i].request == entry.request => {
+                debug!("request '{}' is already queued", entry.request.key());
+                return Ok(());
+            }
+            Some(i) => {
+                debug!("superseding queued request '{}'", entry.request.key());
+                inner.entries[i] = entry;
+            }
+            None => inner.entries.push(entry),
+        }
+        self.apply_retention(&mut inner.entries, now);
+        self.persist(&inner.entries).await
+    }
+
+    /// Sends the queued requests. A request which fails stays queued and holds back
+    /// the requests queued after it with the same key until a later drain, unless the
+    /// backend rejected it as invalid (400, 404, 409, 422) or it has failed the
+    /// retention's `max_attempts` times, in which case it's dropped. The drain stops
+    /// at the first network error or rejected token since the remaining requests
+    /// would fail the same way. Requests queued while draining are sent by the next
+    /// drain.
+    pub async fn drain(
+        &self,
+        client: &impl http::ClientI,
+        token: &str,
+    ) -> Result<Drained, JournalErr> {
+        let _draining = self.draining.lock().await;
+        let queued = {
+            let mut inner = self.inner.lock().await;
+            let before = inner.entries.len();
+            self.apply_retention(&mut inner.entries, Utc::now());
+            if inner.entries.len() != before {
+                self.persist(&inner.entries).await?;
+            }
+            inner.entries.clone()
+        };
+
+        let mut drained = Drained::default();
+        let mut blocked = HashSet::new();
+        let mut sent = HashSet::new();
+        let mut failed = HashSet::new();
+        let mut dropped = HashSet::new();
+        for entry in queued {
+            let key = entry.request.key();
+            if blocked.contains(&key) {
+                continue;
+            }
+            match entry.request.send(client, token).await {
+                Ok(()) => {
+                    drained.sent += 1;
+                    sent.insert(entry.id);
+                }
+                Err(e) => {
+                    debug!("failed to send queued request '{key}': {e}");
+                    let offline = e.is_network_conn_err();
+                    let unauthorized = is_unauthorized(&e);
+                    let attempts = entry.attempts.saturating_add(1);
+                    let max_attempts = self.options.retention(entry.request.kind()).max_attempts;
+                    if is_rejected(&e) {
+                        warn!("dropping queued request '{key}' the backend rejected: {e}");
+                        drained.dropped += 1;
+                        dropped.insert(entry.id);
+                    } else if offline || unauthorized || is_server_err(&e) {
+                        blocked.insert(key);
+                    } else if attempts >= max_attempts {
+                        warn!("dropping queued request '{key}' after {attempts} attempts: {e}");
+                        drained.dropped += 1;
+                        dropped.insert(entry.id);
+                    } else {
+                        blocked.insert(key);
+                        failed.insert(entry.id);
+                    }
+                    drained.errors.push(e);
+                    if unauthorized {
+                        drained.unauthorized = true;
+                        break;
+                    }
+                    if offline {
+                        break;
+                    }
+                }
+            }
+        }
+        if sent.is_empty() && failed.is_empty() && dropped.is_empty() {
+            return Ok(drained);
+        }
+
+        // entries superseded while they were sent have new ids and stay queued
+        let mut inner = self.inner.lock().await;
+        inner
+            .entries
+            .retain(|e| !sent.contains(&e.id) && !dropped.contains(&e.id));
+        for entry in inner.entries.iter_mut() {
+            if failed.contains(&entry.id) {
+                entry.attempts = entry.attempts.saturating_add(1);
+            }
+        }
+        self.persist(&inner.entries).await?;
+        Ok(drained)
+    }
+
+    fn apply_retention(&self, entries: &mut Vec<Entry>, now: DateTime<Utc>) {
+        let before = entries.len();
+        entries.retain(|e| now - e.enqueued_at <= self.options.retention(e.request.kind()).max_age);
+
+        // entries are in queue order so the oldest of each kind are dropped first
+        let mut counts: HashMap<Kind, usize> = HashMap::new();
+        for entry in entries.iter() {
+            *counts.entry(entry.request.kind()).or_default() += 1;
+        }
+        entries.retain(|e| {
+            let kind = e.request.kind();
+            match counts.get_mut(&kind) {
+                Some(count) if *count > self.options.retention(kind).max_entries => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        let dropped = before - entries.len();
+        if dropped > 0 {
+            warn!("dropped {dropped} queued requests past their retention");
+        }
+    }
+
+    async fn persist(&self, entries: &[Entry]) -> Result<(), JournalErr> {
+        self.file
+            .write_json(&entries, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Whether a request failed in a way resending it won't fix: the backend rejected the
+/// request itself as invalid, or it couldn't be built.
+fn is_rejected(err: &http::HTTPErr) -> bool {
+    match err {
+        http::HTTPErr::RequestFailed(e) => matches!(
+            e.status,
+            StatusCode::BAD_REQUEST
+                | StatusCode::NOT_FOUND
+                | StatusCode::CONFLICT
+                | StatusCode::UNPROCESSABLE_ENTITY
+        ),
+        http::HTTPErr::InvalidHeaderValueErr(_)
+        | http::HTTPErr::InvalidURLErr(_)
+        | http::HTTPErr::MarshalJSONErr(_) => true,
+        _ => false,
+    }
+}
+
+fn is_unauthorized(err: &http::HTTPErr) -> bool {
+    matches!(
+        err,
+        http::HTTPErr::RequestFailed(e)
+            if matches!(e.status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+    )
+}
+
+/// Whether the backend failed a request with a server error (5xx).
+pub fn is_server_err(err: &http::HTTPErr) -> bool {
+    matches!(err, http::HTTPErr::RequestFailed(e) if e.status.is_server_error())
+}
@@ -0,0 +1,124 @@
+// internal crates
+use crate::diagnostics::crash;
+use crate::http;
+use crate::models::{self, DeploymentID};
+#[cfg(feature = "telemetry")]
+use crate::telemetry::Sample;
+
+// external crates
+use backend_api::models::UpdateDeploymentRequest;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    DeploymentStatus,
+    CrashReport,
+    #[cfg(feature = "telemetry")]
+    Telemetry,
+}
+
+/// A device-originated backend call which must not be lost while the backend is
+/// unreachable.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Request {
+    DeploymentStatus {
+        deployment_id: DeploymentID,
+        updates: UpdateDeploymentRequest,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metrics: Option<models::DplMetrics>,
+    },
+    /// A report of a past crash, see [crash].
+    CrashReport {
+        device_id: String,
+        report: crash::Report,
+    },
+    /// A batch of the host's resource usage samples, oldest first.
+    #[cfg(feature = "telemetry")]
+    Telemetry {
+        device_id: String,
+        samples: Vec<Sample>,
+    },
+}
+
+impl Request {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::DeploymentStatus { .. } => Kind::DeploymentStatus,
+            Self::CrashReport { .. } => Kind::CrashReport,
+            #[cfg(feature = "telemetry")]
+            Self::Telemetry { .. } => Kind::Telemetry,
+        }
+    }
+
+    /// Requests with the same key are sent in the order they were queued.
+    pub fn key(&self) -> String {
+        match self {
+            Self::DeploymentStatus { deployment_id, .. } => {
+                format!("deployment:{deployment_id}")
+            }
+            // crash reports are independent of each other
+            Self::CrashReport { report, .. } => format!("crash:{}", report.id),
+            #[cfg(feature = "telemetry")]
+            Self::Telemetry { device_id, .. } => format!("telemetry:{device_id}"),
+        }
+    }
+
+    /// Whether the request replaces a queued request with the same key instead of
+    /// being sent after it. Status updates carry the entire status so only the latest
+    /// one needs to reach the backend.
+    pub fn supersedes(&self) -> bool {
+        match self {
+            Self::DeploymentStatus { .. } => true,
+            Self::CrashReport { .. } => false,
+            #[cfg(feature = "telemetry")]
+            Self::Telemetry { .. } => false,
+        }
+    }
+
+    pub async fn send(
+        &self,
+        client: &impl http::ClientI,
+        token: &str,
+    ) -> Result<(), http::HTTPErr> {
+        match self {
+            Self::DeploymentStatus {
+                deployment_id,
+                updates,
+                metrics,
+            } => {
+                http::with_retry(client, || {
+                    let params = http::deployments::UpdateParams {
+                        id: deployment_id,
+                        updates,
+                        metrics: metrics.as_ref(),
+                        token,
+                    };
+                    http::deployments::update(client, params)
+                })
+                .await?;
+            }
+            // sent once per drain rather than retried in place since the journal worker
+            // retries them with its backoff and neither is urgent
+            Self::CrashReport { device_id, report } => {
+                let params = http::crashes::ReportParams {
+                    device_id,
+                    report,
+                    token,
+                };
+                http::crashes::report(client, params).await?;
+            }
+            #[cfg(feature = "telemetry")]
+            Self::Telemetry { device_id, samples } => {
+                let params = http::telemetry::ReportParams {
+                    device_id,
+                    samples,
+                    token,
+                };
+                http::telemetry::report(client, params).await?;
+            }
+        }
+        Ok(())
+    }
+}
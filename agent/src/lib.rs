@@ -1,24 +1,39 @@
 pub mod activity;
 pub mod app;
+pub mod audit;
 pub mod authn;
 pub mod cache;
+pub mod cell;
 pub mod cli;
+pub mod clock;
+pub mod config;
 pub mod cooldown;
 pub mod crypt;
 pub mod deploy;
+pub mod diagnostics;
 pub mod errors;
 pub mod events;
 pub mod filesys;
 pub mod http;
+pub mod journal;
 pub mod logs;
+pub mod metrics;
 pub mod models;
+#[cfg(feature = "mqtt")]
 pub mod mqtt;
 pub mod network;
+pub mod notifications;
+pub mod platform;
 pub mod provisioning;
 pub mod server;
 pub mod services;
 pub mod storage;
+pub mod supervisor;
 pub mod sync;
+#[cfg(feature = "telemetry")]
 pub mod telemetry;
+#[cfg(feature = "test")]
+pub mod testkit;
+pub mod updater;
 pub mod version;
 pub mod workers;
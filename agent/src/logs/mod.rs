@@ -1,6 +1,14 @@
 // standard crates
+use std::collections::VecDeque;
 use std::fmt::Display;
+use std::io;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+// internal crates
+use crate::filesys::wear::CountingWriter;
+use crate::platform;
 
 // external crates
 use serde::{Deserialize, Serialize};
@@ -8,7 +16,12 @@ use thiserror::Error;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, trace, warn};
 use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::{fmt, prelude::*, registry::Registry, reload, EnvFilter};
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    prelude::*,
+    registry::Registry,
+    reload, EnvFilter,
+};
 
 #[derive(Clone, Debug, Default, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -33,6 +46,21 @@ impl LogLevel {
     }
 }
 
+impl FromStr for LogLevel {
+    type Err = LogsErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "trace" => Ok(LogLevel::Trace),
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(LogsErr::InvalidLevel(s.to_string())),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for LogLevel {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -48,13 +76,9 @@ impl<'de> Deserialize<'de> for LogLevel {
                 return Ok(default);
             }
         };
-        match s.to_lowercase().as_str() {
-            "trace" => Ok(LogLevel::Trace),
-            "debug" => Ok(LogLevel::Debug),
-            "info" => Ok(LogLevel::Info),
-            "warn" | "warning" => Ok(LogLevel::Warn),
-            "error" => Ok(LogLevel::Error),
-            _ => {
+        match s.parse() {
+            Ok(level) => Ok(level),
+            Err(_) => {
                 error!(
                     "Invalid log level: {}. Setting to default: '{}'",
                     s, default
@@ -88,7 +112,7 @@ impl Default for Options {
         Self {
             stdout: true,
             log_level: LogLevel::Info,
-            log_dir: PathBuf::from("/var/log/miru"),
+            log_dir: platform::default_log_dir(),
         }
     }
 }
@@ -99,19 +123,37 @@ pub enum LogsErr {
     SetGlobalDefault(#[from] tracing::subscriber::SetGlobalDefaultError),
     #[error("failed to reload tracing filter: {0}")]
     ReloadFailed(String),
+    #[error("invalid log level '{0}': expected one of trace, debug, info, warn or error")]
+    InvalidLevel(String),
+    #[error("the log level is set by the RUST_LOG environment variable")]
+    EnvFilterLocked,
 }
 
-impl crate::errors::Error for LogsErr {}
+impl crate::errors::Error for LogsErr {
+    fn code(&self) -> crate::errors::Code {
+        match self {
+            Self::InvalidLevel(_) => crate::errors::Code::InvalidLogLevel,
+            Self::EnvFilterLocked => crate::errors::Code::LogLevelLocked,
+            _ => crate::errors::Code::InternalServerError,
+        }
+    }
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        match self {
+            Self::InvalidLevel(_) => crate::errors::HTTPCode::BAD_REQUEST,
+            Self::EnvFilterLocked => crate::errors::HTTPCode::CONFLICT,
+            _ => crate::errors::HTTPCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
-type ReloadHandle = reload::Handle<EnvFilter, Registry>;
+pub type ReloadHandle = reload::Handle<EnvFilter, Registry>;
 
 pub type BoxedLogLayer = Box<dyn tracing_subscriber::Layer<Registry> + Send + Sync + 'static>;
 
 pub struct LoggingGuard {
     _worker: WorkerGuard,
-    reload_handle: ReloadHandle,
-    // True if RUST_LOG provided the initial filter; reload_level becomes a no-op.
-    env_filter_locked: bool,
+    level: LevelControl,
+    recent: RecentLines,
 }
 
 impl LoggingGuard {
@@ -120,25 +162,170 @@ impl LoggingGuard {
     /// If `RUST_LOG` was set at process startup, this is a no-op; the env filter wins.
     /// Adjusts filter/level only — does not change the log destination.
     pub fn reload_level(&self, level: LogLevel) -> Result<(), LogsErr> {
-        if self.env_filter_locked {
+        if self.level.env_filter_locked() {
             return Ok(());
         }
-        let new_filter = EnvFilter::new(level.to_string());
-        self.reload_handle
-            .reload(new_filter)
-            .map_err(|e| LogsErr::ReloadFailed(e.to_string()))?;
-        Ok(())
+        self.level.set(level)
+    }
+
+    pub fn env_filter_locked(&self) -> bool {
+        self.level.env_filter_locked()
+    }
+
+    /// A handle for changing the log level at runtime which outlives borrows of the
+    /// guard, e.g. for the socket server.
+    pub fn level_control(&self) -> LevelControl {
+        self.level.clone()
+    }
+
+    /// The most recent log lines, whether the logs go to stdout or to files.
+    pub fn recent_lines(&self) -> RecentLines {
+        self.recent.clone()
+    }
+}
+
+/// Changes the log level of a running agent. Changes aren't persisted so the agent
+/// restarts with the level in its settings.
+#[derive(Clone, Debug, Default)]
+pub struct LevelControl {
+    // None if logging wasn't initialized, in which case only the level is tracked
+    reload_handle: Option<ReloadHandle>,
+    // True if RUST_LOG provided the initial filter, which can't be changed then.
+    env_filter_locked: bool,
+    level: Arc<Mutex<LogLevel>>,
+}
+
+impl LevelControl {
+    pub fn new(reload_handle: ReloadHandle, level: LogLevel, env_filter_locked: bool) -> Self {
+        Self {
+            reload_handle: Some(reload_handle),
+            env_filter_locked,
+            level: Arc::new(Mutex::new(level)),
+        }
+    }
+
+    pub fn level(&self) -> LogLevel {
+        self.level.lock().unwrap_or_else(|e| e.into_inner()).clone()
     }
 
     pub fn env_filter_locked(&self) -> bool {
         self.env_filter_locked
     }
+
+    pub fn set(&self, level: LogLevel) -> Result<(), LogsErr> {
+        if self.env_filter_locked {
+            return Err(LogsErr::EnvFilterLocked);
+        }
+        if let Some(handle) = &self.reload_handle {
+            handle
+                .reload(EnvFilter::new(level.to_string()))
+                .map_err(|e| LogsErr::ReloadFailed(e.to_string()))?;
+        }
+        *self.level.lock().unwrap_or_else(|e| e.into_inner()) = level;
+        Ok(())
+    }
+}
+
+/// How many log lines [RecentLines] keeps unless resized.
+pub const RECENT_LINES: usize = 100;
+
+/// The last log lines the agent wrote, kept in memory so a crash report can include
+/// them wherever the logs went (the journal usually).
+#[derive(Clone, Debug)]
+pub struct RecentLines {
+    inner: Arc<Mutex<RecentLinesInner>>,
+}
+
+#[derive(Debug)]
+struct RecentLinesInner {
+    capacity: usize,
+    lines: VecDeque<String>,
+}
+
+impl RecentLines {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RecentLinesInner {
+                capacity,
+                lines: VecDeque::with_capacity(capacity),
+            })),
+        }
+    }
+
+    /// Changes how many lines are kept, dropping the oldest ones beyond it.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.capacity = capacity;
+        let excess = inner.lines.len().saturating_sub(capacity);
+        inner.lines.drain(..excess);
+    }
+
+    pub fn push(&self, line: String) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner.lines.len() == inner.capacity {
+            inner.lines.pop_front();
+        }
+        inner.lines.push_back(line);
+    }
+
+    /// The kept lines, oldest first. Empty if they're being written to, since this
+    /// is read from the panic hook, which may run while the panicking thread holds
+    /// the lock.
+    pub fn lines(&self) -> Vec<String> {
+        match self.inner.try_lock() {
+            Ok(inner) => inner.lines.iter().cloned().collect(),
+            Err(std::sync::TryLockError::Poisoned(e)) => {
+                e.into_inner().lines.iter().cloned().collect()
+            }
+            Err(std::sync::TryLockError::WouldBlock) => Vec::new(),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RecentLines {
+    type Writer = RecentLinesWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RecentLinesWriter {
+            lines: self.clone(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Buffers a formatted event and keeps its lines once it's written.
+pub struct RecentLinesWriter {
+    lines: RecentLines,
+    buf: Vec<u8>,
+}
+
+impl io::Write for RecentLinesWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RecentLinesWriter {
+    fn drop(&mut self) {
+        for line in String::from_utf8_lossy(&self.buf).lines() {
+            self.lines.push(line.to_string());
+        }
+    }
 }
 
 pub fn build_layers(options: Options) -> (BoxedLogLayer, WorkerGuard, ReloadHandle, bool) {
     // initialize the file appender for logging
     let file_appender = tracing_appender::rolling::hourly(options.log_dir, "miru.log");
-    let (non_blocking, worker_guard) = tracing_appender::non_blocking(file_appender);
+    let (non_blocking, worker_guard) =
+        tracing_appender::non_blocking(CountingWriter::new(file_appender));
 
     // respect RUST_LOG environment variable if set, otherwise use provided log level
     let (env_filter, env_filter_locked) = match EnvFilter::try_from_default_env() {
@@ -170,12 +357,19 @@ pub fn build_layers(options: Options) -> (BoxedLogLayer, WorkerGuard, ReloadHand
 }
 
 pub fn init(options: Options) -> Result<LoggingGuard, LogsErr> {
+    let level = options.log_level.clone();
     let (layers, worker_guard, reload_handle, env_filter_locked) = build_layers(options);
-    let subscriber = Registry::default().with(layers);
+    // the level filter in the layers applies to the recent lines as well
+    let recent = RecentLines::new(RECENT_LINES);
+    let recent_layer = fmt::layer()
+        .with_writer(recent.clone())
+        .with_ansi(false)
+        .with_thread_names(true);
+    let subscriber = Registry::default().with(layers).with(recent_layer);
     tracing::subscriber::set_global_default(subscriber)?;
     Ok(LoggingGuard {
         _worker: worker_guard,
-        reload_handle,
-        env_filter_locked,
+        level: LevelControl::new(reload_handle, level, env_filter_locked),
+        recent,
     })
 }
@@ -1,53 +1,628 @@
 // standard crates
 use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 // internal crates
 use backend_api::models as backend_client;
 use miru_agent::app::run::run;
 use miru_agent::app::{
-    options::{AppOptions, LifecycleOptions},
-    upgrade,
+    options::{AppOptions, LifecycleOptions, StorageOptions},
+    replay, safe_mode, sync_once, upgrade,
 };
+use miru_agent::cell;
 use miru_agent::cli;
-use miru_agent::filesys::{dir::Dir, path::PathExt};
+use miru_agent::config;
+use miru_agent::deploy::{pause, trash};
+use miru_agent::diagnostics;
+#[cfg(feature = "installer")]
+use miru_agent::filesys::WriteOptions;
+use miru_agent::filesys::{dir::Dir, path::PathExt, File};
 use miru_agent::http;
 use miru_agent::logs;
-use miru_agent::mqtt::options::{ConnectAddress, Protocol};
-use miru_agent::network::BackendUrl;
-use miru_agent::provisioning::{self, display, errors::*, provision, reprovision};
+use miru_agent::metrics::workers::CountingAlloc;
+#[cfg(feature = "mqtt")]
+use miru_agent::mqtt::{
+    command,
+    options::{ConnectAddress, Protocol, Tls},
+};
+use miru_agent::network::{egress, identity::ClientIdentity};
+use miru_agent::notifications::signing::{Keyring, SIGNATURE_HEADER, SIGNATURE_SCHEME};
+use miru_agent::platform;
+use miru_agent::provisioning::{self, display, errors::*, provision, reprovision, seed};
+#[cfg(feature = "server")]
+use miru_agent::server;
 use miru_agent::storage;
+use miru_agent::sync;
+use miru_agent::updater::{launch, Slot, Slots};
 use miru_agent::version;
+#[cfg(feature = "mqtt")]
 use miru_agent::workers::mqtt;
 
 // external crates
-use tokio::signal::unix::signal;
-use tracing::{error, info};
+use chrono::Utc;
+use tracing::{error, info, warn};
+
+// attributes allocations to the worker making them for the worker metrics
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
 
 #[tokio::main]
 async fn main() {
-    let cli_args = cli::Args::parse(&env::args().collect::<Vec<String>>());
+    let command = match cli::parse(&env::args().collect::<Vec<String>>()) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {e}\n\nRun '{} --help' for usage.", cli::BINARY);
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        cli::Command::Help(help) => print!("{help}"),
+        cli::Command::Version => println!("{}", version::format()),
+        cli::Command::Status(args) => {
+            let layout = layout(&args.data_dir);
+            let settings = get_bootstrap_settings(&layout, &[]).await;
+            let private_key = settings
+                .key_storage
+                .private_key(layout.auth().private_key());
+            println!("{}", cli::status::Status::read(&layout, &private_key).await)
+        }
+        cli::Command::SyncHistory(args) => print_sync_events(args).await,
+        cli::Command::SupportBundle(args) => {
+            create_support_bundle(&layout(&args.data_dir), &args.dir).await
+        }
+        cli::Command::Fsck(args) => run_fsck(args).await,
+        cli::Command::Replay(args) => run_replay(args).await,
+        cli::Command::WebhookKeys(args) => manage_webhook_keys(args).await,
+        #[cfg(feature = "installer")]
+        cli::Command::Install(args) => install_service_definition(args).await,
+        #[cfg(feature = "installer")]
+        cli::Command::Uninstall(args) => uninstall(args).await,
+        cli::Command::ConfigSources(args) => {
+            display_config_sources(&layout(&args.data_dir), &args.settings_overrides).await
+        }
+        cli::Command::Activate(args) => {
+            let result = run_provision(args).await;
+            handle_provision_result(result);
+        }
+        cli::Command::Reprovision(args) => {
+            let result = run_reprovision(args).await;
+            handle_reprovision_result(result);
+        }
+        cli::Command::SyncOnce(args) => run_sync_once(args).await,
+        cli::Command::Trash(args) => manage_trash(args).await,
+        cli::Command::Deployments(args) => manage_deployments(args).await,
+        cli::Command::Run(args) => {
+            run_agent(
+                layout(&args.data_dir),
+                &args.settings_overrides,
+                args.record,
+            )
+            .await
+        }
+    }
+}
+
+fn layout(data_dir: &Option<PathBuf>) -> storage::Layout {
+    match data_dir {
+        Some(dir) => storage::Layout::with_data_dir(Dir::new(dir)),
+        None => storage::Layout::default(),
+    }
+}
+
+#[cfg(feature = "installer")]
+async fn install_service_definition(args: cli::InstallArgs) {
+    // the service runs the agent with the same data directory
+    let service_args: Vec<String> = args
+        .data_dir
+        .iter()
+        .map(|dir| format!("--data-dir={}", dir.display()))
+        .collect();
+    // a sandboxed service may always write to the agent's own directories
+    let layout = layout(&args.data_dir);
+    let socket_dir = layout
+        .socket_file()
+        .path()
+        .parent()
+        .map(|dir| dir.to_path_buf());
+    let mut writable_paths: Vec<PathBuf> = Vec::new();
+    for path in [layout.root().path().to_path_buf(), layout.log_dir()]
+        .into_iter()
+        .chain(socket_dir)
+        .chain(args.writable_paths)
+    {
+        if !writable_paths.contains(&path) {
+            writable_paths.push(path);
+        }
+    }
+    let mut options = platform::ServiceOptions {
+        hardening: args.hardening,
+        writable_paths,
+        watchdog_sec: args.watchdog_sec,
+        ..Default::default()
+    };
+    if let Some(restart) = args.restart {
+        options.restart = restart;
+    }
 
-    if cli_args.display_version {
-        println!("{}", version::format());
+    let definition = match env::current_exe() {
+        Ok(binary) => platform::service_definition(&binary, &service_args, &options),
+        Err(e) => {
+            println!("Unable to determine the agent's executable path: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(tmpfiles) = args.tmpfiles {
+        let entry = platform::tmpfiles_entry(&layout.log_dir());
+        match File::new(&tmpfiles)
+            .write_string(&entry, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+        {
+            Ok(()) => eprintln!("Wrote the tmpfiles.d entry to {}", tmpfiles.display()),
+            Err(e) => {
+                println!("Unable to write the tmpfiles.d entry: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    let Some(output) = args.output else {
+        print!("{definition}");
         return;
+    };
+    let file = File::new(&output);
+    match file
+        .write_string(&definition, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+    {
+        Ok(()) => println!("Wrote the service definition to {}", output.display()),
+        Err(e) => {
+            println!("Unable to write the service definition: {e}");
+            std::process::exit(1);
+        }
     }
+}
 
-    if let Some(provision_args) = cli_args.provision_args {
-        let result = run_provision(provision_args).await;
-        handle_provision_result(result);
+#[cfg(feature = "installer")]
+async fn uninstall(args: cli::UninstallArgs) {
+    let layout = layout(&args.data_dir);
+    let settings = get_bootstrap_settings(&layout, &[]).await;
+    let opts = provisioning::deprovision::Options {
+        deregister: !args.keep_registration,
+        force: args.force,
+        keep_logs: args.keep_logs,
+        versions: settings.deployment_dir.versions(),
+    };
+    let service = args
+        .service
+        .unwrap_or_else(|| platform::SERVICE_NAME.to_string());
+
+    if !args.yes {
+        let plan = provisioning::deprovision::plan(&layout, &opts).await;
+        if opts.deregister {
+            println!("Uninstalling deregisters the device from the backend");
+            println!("then stops the '{service}' service");
+        } else {
+            println!("Uninstalling stops the '{service}' service");
+        }
+        println!("and removes:");
+        for path in plan
+            .deployed_files
+            .iter()
+            .chain(&plan.created_dirs)
+            .chain(&plan.releases)
+            .chain(&plan.state)
+            .chain(&plan.logs)
+        {
+            println!("  {}", path.display());
+        }
+        println!("Re-run with --yes to uninstall");
         return;
     }
 
-    if let Some(reprovision_args) = cli_args.reprovision_args {
-        let result = run_reprovision(reprovision_args).await;
-        handle_reprovision_result(result);
+    let private_key = settings
+        .key_storage
+        .private_key(layout.auth().private_key());
+    let http_client = match http::Client::new(settings.backend.base_url.as_str())
+        .and_then(|client| client.with_egress(&egress_options(&layout, &settings.network)))
+    {
+        Ok(client) => client,
+        Err(e) => {
+            println!("Unable to construct the http client: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // deregister while the service still runs, so a device which can't be
+    // deregistered is left running as it was
+    if opts.deregister {
+        match provisioning::deprovision::deregister(&http_client, &layout, &private_key).await {
+            Ok(true) => println!("Deregistered the device"),
+            Ok(false) => {}
+            Err(e) if opts.force => {
+                println!("Unable to deregister the device ({e}), removing it anyway")
+            }
+            Err(e) => {
+                println!("Unable to deregister the device, nothing was stopped or removed: {e}");
+                println!("Re-run with --force to remove it anyway");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let command = platform::stop_service_command(&service);
+    let status = tokio::task::spawn_blocking(move || {
+        std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+    })
+    .await;
+    match status {
+        Ok(Ok(status)) if status.success() => println!("Stopped the '{service}' service"),
+        Ok(Ok(status)) => println!("Unable to stop the '{service}' service ({status}), continuing"),
+        Ok(Err(e)) => println!("Unable to stop the '{service}' service ({e}), continuing"),
+        Err(e) => println!("Unable to stop the '{service}' service ({e}), continuing"),
+    }
+
+    // the device was deregistered above
+    let opts = provisioning::deprovision::Options {
+        deregister: false,
+        ..opts
+    };
+    let report =
+        match provisioning::deprovision::deprovision(&http_client, &layout, &private_key, &opts)
+            .await
+        {
+            Ok(report) => report,
+            Err(e) => {
+                println!("Unable to remove the device's state: {e}");
+                std::process::exit(1);
+            }
+        };
+    for path in &report.removed {
+        println!("Removed {}", path.display());
+    }
+    for (path, e) in &report.failed {
+        println!("Unable to remove {}: {e}", path.display());
+    }
+    if !report.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+async fn print_sync_events(args: cli::SyncHistoryArgs) {
+    let file = layout(&args.data_dir).sync_events();
+    if !file.exists() {
+        println!("No sync events recorded (is sync_history.persist enabled?)");
+        return;
+    }
+    let records = sync::event_log::read(&file).await;
+    if args.json {
+        match serde_json::to_string_pretty(&records) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Unable to serialize the sync events: {e}"),
+        }
+        return;
+    }
+    for record in records {
+        println!("{record}");
+    }
+}
+
+async fn run_fsck(args: cli::FsckArgs) {
+    let layout = layout(&args.data_dir);
+    let report = storage::fsck::run(&layout, storage::fsck::Options { fix: args.fix }).await;
+    if args.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Unable to serialize the report: {e}"),
+        }
+    } else {
+        println!("{}", report.render(args.level));
+    }
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+}
+
+async fn run_replay(args: cli::ReplayArgs) {
+    let capture = match http::record::Capture::read(&File::new(&args.capture)).await {
+        Ok(capture) => capture,
+        Err(e) => {
+            println!("Unable to read the capture: {e}");
+            std::process::exit(1);
+        }
+    };
+    let dir = match &args.data_dir {
+        Some(dir) => Dir::new(dir),
+        None => match Dir::create_temp_dir("replay").await {
+            Ok(dir) => dir,
+            Err(e) => {
+                println!("Unable to create a directory to replay into: {e}");
+                std::process::exit(1);
+            }
+        },
+    };
+    let layout = storage::Layout::with_data_dir(dir.clone());
+    let opts = replay::Options { apply: args.apply };
+    let report = match replay::run(&layout, capture, opts).await {
+        Ok(report) => report,
+        Err(e) => {
+            println!("Unable to replay the capture: {e}");
+            std::process::exit(1);
+        }
+    };
+    if args.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Unable to serialize the report: {e}"),
+        }
+    } else {
+        print!("{}", report.render());
+        println!("Replayed into {}", dir.path().display());
+    }
+    if !report.errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+async fn manage_webhook_keys(args: cli::WebhookKeysArgs) {
+    let file = layout(&args.data_dir).auth().webhook_keys();
+    let mut keyring = match Keyring::load(&file).await {
+        Ok(keyring) => keyring,
+        Err(e) => {
+            println!("Unable to read the webhook signing keys: {e}");
+            std::process::exit(1);
+        }
+    };
+    let now = Utc::now();
+    if args.rotate {
+        let grace = chrono::TimeDelta::seconds(args.grace_secs);
+        let key = match keyring.rotate(&args.url, grace, now) {
+            Ok(key) => key,
+            Err(e) => {
+                println!("Unable to generate a webhook signing key: {e}");
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = keyring.save(&file).await {
+            println!("Unable to save the webhook signing keys: {e}");
+            std::process::exit(1);
+        }
+        println!("Added key {} for {}", key.id, args.url);
+        println!("Secret (shown only once): {}", key.secret);
+        println!("Signature scheme: {SIGNATURE_HEADER}: {SIGNATURE_SCHEME}");
+    } else if args.revoke {
+        if !keyring.revoke(&args.url) {
+            println!("{} has no signing keys", args.url);
+            return;
+        }
+        if let Err(e) = keyring.save(&file).await {
+            println!("Unable to save the webhook signing keys: {e}");
+            std::process::exit(1);
+        }
+        println!("Revoked the signing keys for {}", args.url);
+    }
+
+    let keys = keyring.active(&args.url, now);
+    if keys.is_empty() {
+        println!("{} is sent unsigned", args.url);
+    }
+    for key in keys {
+        match key.retires_at {
+            Some(retires_at) => println!(
+                "{}  created {}  retires {}",
+                key.id,
+                key.created_at.to_rfc3339(),
+                retires_at.to_rfc3339()
+            ),
+            None => println!("{}  created {}", key.id, key.created_at.to_rfc3339()),
+        }
+    }
+}
+
+async fn manage_deployments(args: cli::DeploymentsArgs) {
+    let switch = pause::Switch::new(layout(&args.data_dir).deployments_paused());
+    let resuming = args.action == cli::DeploymentsAction::Resume;
+    // the running agent reads the switch before applying deployments
+    let status = match args.action {
+        cli::DeploymentsAction::Status => switch.status().await,
+        cli::DeploymentsAction::Pause { reason } => {
+            match switch.pause(reason, pause::Origin::Cli).await {
+                Ok(status) => status,
+                Err(e) => {
+                    println!("Unable to pause deployments: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        cli::DeploymentsAction::Resume => match switch.resume(pause::Origin::Cli).await {
+            Ok(status) => status,
+            Err(e) => {
+                println!("Unable to resume deployments: {e}");
+                std::process::exit(1);
+            }
+        },
+    };
+    if args.json {
+        match serde_json::to_string_pretty(&status) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Unable to serialize whether deployments are paused: {e}"),
+        }
+        return;
+    }
+    if !status.paused {
+        println!("Deployments are not paused");
+        if resuming {
+            println!("Held deployments are applied at the agent's next sync");
+        }
+        return;
+    }
+    let mut line = "Deployments are paused".to_string();
+    if let Some(changed_at) = status.changed_at {
+        line.push_str(&format!(" since {}", changed_at.to_rfc3339()));
+    }
+    if let Some(by) = status.changed_by {
+        line.push_str(&format!(" (from the {})", by.as_str()));
+    }
+    println!("{line}");
+    if let Some(reason) = status.reason {
+        println!("Reason: {reason}");
+    }
+}
+
+async fn manage_trash(args: cli::TrashArgs) {
+    // the retention and size budget only matter to the agent purging the trash
+    let trash = trash::Trash::new(
+        layout(&args.data_dir).trash_dir(),
+        trash::Options::default(),
+    );
+    match args.action {
+        cli::TrashAction::List => {}
+        cli::TrashAction::Restore { entry: id, force } => match trash.restore(&id, force).await {
+            Ok(entry) => {
+                println!("Restored {} from {}", entry.filepath, entry.id);
+                return;
+            }
+            Err(e) => {
+                println!("Unable to restore {id}: {e}");
+                std::process::exit(1);
+            }
+        },
+        cli::TrashAction::Purge { entry: Some(id) } => match trash.purge(&id).await {
+            Ok(entry) => {
+                println!("Purged {} ({} bytes)", entry.id, entry.size_bytes);
+                return;
+            }
+            Err(e) => {
+                println!("Unable to purge {id}: {e}");
+                std::process::exit(1);
+            }
+        },
+        cli::TrashAction::Purge { entry: None } => match trash.purge_all().await {
+            Ok(report) => {
+                println!(
+                    "Purged {} entries ({} bytes)",
+                    report.purged, report.reclaimed_bytes
+                );
+                return;
+            }
+            Err(e) => {
+                println!("Unable to empty the trash: {e}");
+                std::process::exit(1);
+            }
+        },
+    }
+
+    let entries = match trash.list().await {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("Unable to read the trash: {e}");
+            std::process::exit(1);
+        }
+    };
+    if args.json {
+        match serde_json::to_string_pretty(&entries) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Unable to serialize the trash entries: {e}"),
+        }
         return;
     }
+    if entries.is_empty() {
+        println!("The trash is empty");
+    }
+    for entry in entries {
+        println!(
+            "{}  trashed {}  {} bytes  {}  (deployment {})",
+            entry.id,
+            entry.trashed_at.to_rfc3339(),
+            entry.size_bytes,
+            entry.filepath,
+            entry.deployment_id
+        );
+    }
+}
+
+async fn display_config_sources(layout: &storage::Layout, settings_overrides: &[(String, String)]) {
+    let settings_file = layout.settings();
+    let remote_settings_file = layout.remote_settings();
+    match config::load(&settings_file, &remote_settings_file, settings_overrides).await {
+        Ok(resolved) => {
+            println!("{}", resolved.report());
+            for deprecation in &resolved.deprecations {
+                println!("warning: {deprecation}");
+            }
+        }
+        Err(e) => {
+            println!("Unable to resolve settings: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The built-in redaction rules merged with any operator-provided rules.
+async fn redaction_rules(
+    layout: &storage::Layout,
+) -> Result<diagnostics::Rules, miru_agent::filesys::FileSysErr> {
+    let rules = diagnostics::Rules::default();
+    let rules_file = layout.redaction_rules();
+    if !rules_file.exists() {
+        return Ok(rules);
+    }
+    let extra = rules_file.read_json::<diagnostics::Rules>().await?;
+    Ok(rules.extend(extra))
+}
 
-    run_agent().await;
+/// Redacts crash reports like support bundles since they're uploaded too. Reports are
+/// written unredacted if the rules can't be loaded rather than not written at all.
+async fn crash_redactor(layout: &storage::Layout) -> Option<diagnostics::Redactor> {
+    match redaction_rules(layout)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|rules| diagnostics::Redactor::new(&rules).map_err(|e| e.to_string()))
+    {
+        Ok(redactor) => Some(redactor),
+        Err(e) => {
+            error!("Unable to load the redaction rules for crash reports: {e}");
+            None
+        }
+    }
 }
 
-async fn run_provision(args: cli::ProvisionArgs) -> Result<provision::Outcome, ProvisionErr> {
+async fn create_support_bundle(layout: &storage::Layout, dir: &str) {
+    let rules = match redaction_rules(layout).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            println!("Unable to read redaction rules: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let privacy_mode = get_bootstrap_settings(layout, &[]).await.privacy_mode;
+
+    let result = async {
+        let mut redactor = diagnostics::Redactor::new(&rules)?;
+        if privacy_mode {
+            redactor =
+                redactor.with_identifiers(diagnostics::anonymize::identifiers(layout).await?);
+        }
+        let log_dir = Dir::new(layout.log_dir());
+        let bundle = diagnostics::assemble(layout, &log_dir, &redactor).await?;
+        bundle.write(&Dir::new(dir)).await?;
+        Ok::<_, diagnostics::DiagnosticsErr>(bundle.entries.len())
+    }
+    .await;
+
+    match result {
+        Ok(count) => println!("Wrote {count} support bundle files to {dir}"),
+        Err(e) => {
+            println!("Unable to create support bundle: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run_provision(mut args: cli::ProvisionArgs) -> Result<provision::Outcome, ProvisionErr> {
     // initialize logging
     let tmp_dir = Dir::create_temp_dir("miru-agent-provision-logs").await?;
     let options = logs::Options {
@@ -58,10 +633,11 @@ async fn run_provision(args: cli::ProvisionArgs) -> Result<provision::Outcome, P
     };
     let _guard = logs::init(options)?;
 
+    let token = provision::resolve_token(&mut args)?;
     let settings = provision::determine_settings(&args);
-    let http_client = http::Client::new(settings.backend.base_url.as_str())?;
-    let layout = storage::Layout::default();
-    let token = provisioning::read_token_from_env()?;
+    let layout = layout(&args.data_dir);
+    let http_client = http::Client::new(settings.backend.base_url.as_str())?
+        .with_egress(&egress_options(&layout, &settings.network))?;
 
     let result =
         provision::provision(&http_client, &layout, &settings, &token, args.device_name).await;
@@ -112,8 +688,9 @@ async fn run_reprovision(
     let _guard = logs::init(options)?;
 
     let settings = reprovision::determine_settings(&args);
-    let http_client = http::Client::new(settings.backend.base_url.as_str())?;
-    let layout = storage::Layout::default();
+    let layout = layout(&args.data_dir);
+    let http_client = http::Client::new(settings.backend.base_url.as_str())?
+        .with_egress(&egress_options(&layout, &settings.network))?;
     let token = provisioning::read_token_from_env()?;
 
     let result = reprovision::reprovision(&http_client, &layout, &settings, &token).await;
@@ -143,12 +720,60 @@ fn handle_reprovision_result(result: Result<backend_client::Device, ProvisionErr
     }
 }
 
-async fn run_agent() {
-    let layout = storage::Layout::default();
+async fn import_seed(layout: &storage::Layout) {
+    let seed = match seed::read(&layout.seed_dir()).await {
+        Ok(Some(seed)) => seed,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Unable to read the provisioning seed: {e}");
+            return;
+        }
+    };
+    let (base_url, egress) = seed
+        .settings
+        .as_ref()
+        .map(|settings| {
+            (
+                settings.backend.base_url.clone(),
+                egress_options(layout, &settings.network),
+            )
+        })
+        .unwrap_or_else(|| {
+            (
+                Default::default(),
+                egress_options(layout, &Default::default()),
+            )
+        });
+    let http_client =
+        match http::Client::new(base_url.as_str()).and_then(|client| client.with_egress(&egress)) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("seed: failed to construct http client: {e}");
+                return;
+            }
+        };
+    match seed::import(&http_client, layout, seed).await {
+        Ok(outcome) if outcome.already_provisioned => {}
+        Ok(outcome) => info!(
+            "Provisioned this device from the seed as {}",
+            outcome.device_name
+        ),
+        Err(e) => error!("Unable to provision from the seed, retrying on the next start: {e}"),
+    }
+}
 
+async fn run_agent(
+    layout: storage::Layout,
+    settings_overrides: &[(String, String)],
+    record: Option<PathBuf>,
+) {
     // initialize logging early so reconciliation and pre-settings activity are
     // observable. The level is reloaded once settings are read below.
-    let log_guard = match logs::init(logs::Options::default()) {
+    let log_options = logs::Options {
+        log_dir: layout.log_dir(),
+        ..Default::default()
+    };
+    let log_guard = match logs::init(log_options) {
         Ok(g) => g,
         Err(e) => {
             // tracing is not yet installed if init failed, so use eprintln!
@@ -157,49 +782,259 @@ async fn run_agent() {
         }
     };
 
-    // check the agent has been activated
-    if let Err(e) = storage::assert_activated(&layout).await {
-        error!("Device is not yet activated: {}", e);
-        return;
+    // run the version a self-update installed, which prepares the device itself
+    let slot = match launch::launch(&Slots::new(layout.updates_dir()), &layout).await {
+        Ok(slot) => slot,
+        Err(e) => {
+            error!("Failed to launch the updated agent: {e}");
+            None
+        }
+    };
+    let trigger = upgrade_trigger(&layout, slot).await;
+    let settings = match prepare(&layout, settings_overrides, trigger, &log_guard).await {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+
+    // write a crash report for any panic from here on
+    let recent_lines = log_guard.recent_lines();
+    recent_lines.set_capacity(settings.crash_reports.log_lines);
+    diagnostics::crash::install(diagnostics::crash::Options {
+        layout: layout.crash(),
+        recent_lines,
+        redactor: crash_redactor(&layout).await,
+        upload: settings.crash_reports.upload,
+    });
+
+    // count the starts which weren't followed by a clean shutdown to detect crash
+    // loops
+    let crash_record = layout.crash_record();
+    let safe_mode =
+        match safe_mode::record_start(&crash_record, &settings.safe_mode.options(), Utc::now())
+            .await
+        {
+            Ok(status) => status,
+            Err(e) => {
+                error!("Failed to record agent start in the crash record: {e}");
+                safe_mode::Status::default()
+            }
+        };
+
+    // capture the exchanges with the backend, sanitized, for offline replay
+    let http_recorder = match record {
+        Some(path) => match redaction_rules(&layout)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|rules| diagnostics::Redactor::new(&rules).map_err(|e| e.to_string()))
+        {
+            Ok(redactor) => {
+                info!("Recording backend exchanges to {}", path.display());
+                Some(Arc::new(http::record::Recorder::new(
+                    File::new(path),
+                    redactor,
+                )))
+            }
+            Err(e) => {
+                error!("Unable to load the redaction rules for recording: {e}");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    // run the server
+    let mut options = AppOptions {
+        safe_mode,
+        http_recorder,
+        ..app_options(&layout, settings, settings_overrides, &log_guard)
+    };
+    options.updater.slot = slot;
+    info!("Running the server with options: {:?}", options);
+    let result = run(options, platform::shutdown_signal()).await;
+    match result {
+        Ok(()) => {
+            if let Err(e) = safe_mode::record_clean_shutdown(&crash_record).await {
+                error!("Failed to clear the crash record: {e}");
+            }
+            if launch::is_restart_requested() {
+                launch::revert_migrations(
+                    &Slots::new(layout.updates_dir()),
+                    &layout,
+                    storage::migrations::MIGRATIONS,
+                    version::VERSION,
+                )
+                .await;
+                error!("Failed to restart the agent: {}", launch::relaunch());
+            }
+        }
+        Err(e) => error!("Failed to run the server: {e}"),
     }
+}
 
-    // reconcile the agent package version to ensure the file system storage state
-    // is compatible with the running version
-    let url = get_bootstrap_base_url().await;
-    let bootstrap_http_client = match http::Client::new(url.as_str()) {
-        Ok(c) => c,
+async fn run_sync_once(args: cli::RunArgs) {
+    let layout = layout(&args.data_dir);
+    // stdout is kept for the report
+    let log_options = logs::Options {
+        stdout: false,
+        log_dir: layout.log_dir(),
+        ..Default::default()
+    };
+    let log_guard = match logs::init(log_options) {
+        Ok(g) => g,
         Err(e) => {
-            error!("upgrade: failed to construct http client: {e}");
-            return;
+            eprintln!("Failed to initialize logging: {e}");
+            std::process::exit(1);
         }
     };
-    if let Err(e) = upgrade::reconcile(
-        &layout,
+    let trigger = upgrade_trigger(&layout, None).await;
+    let report = match prepare(&layout, &args.settings_overrides, trigger, &log_guard).await {
+        Ok(settings) => {
+            sync_once::run(&app_options(
+                &layout,
+                settings,
+                &args.settings_overrides,
+                &log_guard,
+            ))
+            .await
+        }
+        Err(e) => {
+            error!("{e}");
+            sync_once::Report::failed(e)
+        }
+    };
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{json}"),
+        Err(e) => eprintln!("Unable to serialize the report: {e}"),
+    }
+    std::process::exit(report.outcome.exit_code());
+}
+
+/// Readies the device for the agent to run: imports the provisioning seed, checks the
+/// device is activated, reconciles the data directory with this version and resolves
+/// the settings.
+async fn prepare(
+    layout: &storage::Layout,
+    settings_overrides: &[(String, String)],
+    trigger: upgrade::Trigger,
+    log_guard: &logs::LoggingGuard,
+) -> Result<storage::Settings, String> {
+    // deprecated flags and commands were recorded before logging was initialized
+    config::compat::warn_pending();
+
+    // provision from the seed the manufacturing line left on the boot media
+    import_seed(layout).await;
+
+    // check the agent has been activated
+    let bootstrap_settings = get_bootstrap_settings(layout, settings_overrides).await;
+    let private_key = bootstrap_settings
+        .key_storage
+        .private_key(layout.auth().private_key());
+    if let Err(e) = storage::assert_activated(layout, &private_key).await {
+        return Err(format!("Device is not yet activated: {e}"));
+    }
+
+    // reconcile the agent package version to ensure the file system storage state
+    // is compatible with the running version
+    let bootstrap_http_client = http::Client::new(bootstrap_settings.backend.base_url.as_str())
+        .and_then(|client| client.with_egress(&egress_options(layout, &bootstrap_settings.network)))
+        .map_err(|e| format!("upgrade: failed to construct http client: {e}"))?;
+    let upgrade = upgrade::reconcile(
+        layout,
+        &private_key,
         &bootstrap_http_client,
         version::VERSION,
+        trigger,
+        storage::migrations::MIGRATIONS,
         tokio::time::sleep,
     )
     .await
-    {
-        error!("upgrade: failed to reconcile agent package version: {e}");
-        return;
-    }
+    .map_err(|e| format!("upgrade: failed to reconcile agent package version: {e}"))?;
 
-    // retrieve the settings files
+    // resolve the settings from the settings file, environment, cli overrides, and remote
     let settings_file = layout.settings();
-    let settings = match settings_file.read_json::<storage::Settings>().await {
-        Ok(settings) => settings,
-        Err(e) => {
-            error!("Unable to read settings file: {}", e);
-            return;
-        }
-    };
+    let remote_settings_file = layout.remote_settings();
+    let settings =
+        match config::load(&settings_file, &remote_settings_file, settings_overrides).await {
+            Ok(resolved) => {
+                info!("Resolved settings:\n{}", resolved.report());
+                resolved.settings
+            }
+            Err(e) => return Err(format!("Unable to resolve settings: {e}")),
+        };
+
+    // an upgrade must never turn off the verification of release signatures
+    if upgrade.upgraded && upgrade::drops_release_signing(&bootstrap_settings, &settings) {
+        return Err(
+            "Release signatures were verified before the upgrade but no longer are, refusing \
+             to start"
+                .to_string(),
+        );
+    }
 
     // apply the configured log level to the running subscriber
     if let Err(e) = log_guard.reload_level(settings.log_level.clone()) {
         tracing::warn!("Failed to apply settings.log_level to running logger: {e}");
     }
+    Ok(settings)
+}
+
+/// Whether a version change was the self-updater's doing, which keeps the agent state.
+async fn upgrade_trigger(layout: &storage::Layout, slot: Option<Slot>) -> upgrade::Trigger {
+    let slots = Slots::new(layout.updates_dir());
+    match launch::is_updater_driven(&slots, layout, slot).await {
+        true => upgrade::Trigger::Updater,
+        false => upgrade::Trigger::Package,
+    }
+}
 
+fn app_options(
+    layout: &storage::Layout,
+    settings: storage::Settings,
+    settings_overrides: &[(String, String)],
+    log_guard: &logs::LoggingGuard,
+) -> AppOptions {
+    let egress = egress_options(layout, &settings.network);
+    let updater = settings.self_update.worker_options();
+    if settings.self_update.enabled && updater.is_none() {
+        warn!("Self-updates are enabled without a release key to verify them, disabling them");
+    }
+    let dpl_signatures = settings.release_signing.verifier_options();
+    if settings.release_signing.required && settings.release_signing.trusted_keys.is_empty() {
+        warn!("Release signatures are required without a trusted key, no release will deploy");
+    }
+    let supervisor = settings.supervisor.worker_options();
+    if settings.supervisor.enabled && supervisor.is_none() {
+        warn!("The supervisor is enabled without an application command, disabling it");
+    }
+    if egress.proxy.is_some() && settings.enable_mqtt_worker {
+        warn!("MQTT connects to the broker directly rather than through the proxy");
+    }
+    if let Some(identity) = &egress.identity {
+        info!("Presenting client certificate {}", identity.subject());
+    }
+    // the overrides are only needed to reload the settings over MQTT
+    #[cfg(not(feature = "mqtt"))]
+    let _ = settings_overrides;
+    #[cfg(feature = "mqtt")]
+    let commands = command::Options {
+        log_level: log_guard.level_control(),
+        settings: Some(command::SettingsSource {
+            file: layout.settings(),
+            remote_file: layout.remote_settings(),
+            overrides: settings_overrides.to_vec(),
+            applied: serde_json::to_value(&settings).unwrap_or_default(),
+        }),
+        pause: Some(pause::Switch::new(layout.deployments_paused())),
+    };
+    #[cfg(feature = "mqtt")]
+    let tls = Tls {
+        ca_bundle: egress.ca_bundle.clone(),
+        identity: egress.identity.clone(),
+    };
+    #[cfg(feature = "mqtt")]
     let broker_address = ConnectAddress::new_or(
         settings.mqtt_broker.host,
         Protocol::SSL,
@@ -207,51 +1042,109 @@ async fn run_agent() {
         ConnectAddress::default(),
     );
 
-    // run the server
-    let options = AppOptions {
+    AppOptions {
         lifecycle: LifecycleOptions {
             is_persistent: settings.is_persistent,
             ..Default::default()
         },
+        startup: settings.startup.options(),
+        storage: StorageOptions {
+            layout: layout.clone(),
+            private_key: Some(
+                settings
+                    .key_storage
+                    .private_key(layout.auth().private_key()),
+            ),
+            content_admission: settings.content_cache.policy(),
+            content_max_bytes: settings.content_cache.max_bytes,
+            content_digest_algorithm: settings.content_cache.digest_algorithm,
+            capacities: storage::Capacities {
+                telemetry_batches: settings.telemetry.retention,
+                ..Default::default()
+            },
+        },
+        #[cfg(feature = "server")]
+        server: server::Options {
+            socket_file: layout.socket_file(),
+            shedding: settings.load_shedding.options(),
+        },
         backend_base_url: settings.backend.base_url,
+        http_scheduling: settings.http.scheduling(),
+        dpl_reboot: settings.reboot.options(),
+        dpl_hooks: settings.hooks.options(),
+        dpl_validate_schemas: settings.validate_config_schemas,
+        trash: settings
+            .trash
+            .options()
+            .map(|options| trash::Trash::new(layout.trash_dir(), options)),
+        dpl_versions: settings.deployment_dir.versions(),
+        dpl_space: settings.disk_guard.guard(layout, &settings.deployment_dir),
+        dpl_signatures,
+        dpl_windows: settings.poller.windows(),
+        dpl_pause: Some(pause::Switch::new(layout.deployments_paused())),
+        notifications: settings.notifications.options(),
         enable_socket_server: settings.enable_socket_server,
+        log_level: log_guard.level_control(),
+        metrics_addr: settings.metrics.listen_addr.clone(),
+        cell: cell::Options::new(&settings.cell),
+        egress,
         enable_mqtt_worker: settings.enable_mqtt_worker,
         enable_poller: settings.enable_poller,
+        poller: settings.poller.worker_options(),
+        enable_long_poll: settings.enable_long_poll,
+        long_poll: settings.long_poll.options(),
+        sync_history: settings.sync_history.options(layout.sync_history()),
+        sync_events: settings.sync_history.event_options(layout.sync_events()),
+        content_warming: settings.content_warming.options(layout.content_warming()),
+        sync_backoff: settings.sync_backoff.policies(),
+        sync_network: settings.telemetry.network_report(),
+        low_wear_mode: settings.wear.low_wear_mode,
+        wear_worker: settings.wear.worker_options(),
+        token_refresh_worker: settings.token_refresh.worker_options(),
+        enable_telemetry: settings.telemetry.enabled,
+        #[cfg(feature = "telemetry")]
+        telemetry: settings.telemetry.worker_options(layout),
+        enable_updater: settings.self_update.enabled && updater.is_some(),
+        updater: updater.unwrap_or_default(),
+        enable_supervisor: settings.supervisor.enabled && supervisor.is_some(),
+        supervisor: supervisor.unwrap_or_default(),
+        enable_crash_reports: settings.crash_reports.upload,
+        #[cfg(feature = "mqtt")]
         mqtt_worker: mqtt::Options {
             broker_address,
-            ..Default::default()
+            tls,
+            commands,
+            ..settings.mqtt_connection.worker_options()
         },
         ..Default::default()
-    };
-    info!("Running the server with options: {:?}", options);
-    let result = run(options, await_shutdown_signal()).await;
-    if let Err(e) = result {
-        error!("Failed to run the server: {e}");
     }
 }
 
-async fn get_bootstrap_base_url() -> BackendUrl {
-    let settings_file = storage::Layout::default().settings();
-    if let Ok(settings) = settings_file.read_json::<storage::Settings>().await {
-        return settings.backend.base_url;
+/// The egress settings along with the device's client certificate, if it has one.
+fn egress_options(layout: &storage::Layout, network: &storage::Network) -> egress::Options {
+    let auth = layout.auth();
+    let identity = ClientIdentity::read_if_present(&auth.client_cert(), &auth.client_key())
+        .unwrap_or_else(|e| {
+            error!("Not presenting a client certificate: {e}");
+            None
+        });
+    egress::Options {
+        identity,
+        ..network.egress()
     }
-
-    storage::Backend::default().base_url
 }
 
-async fn await_shutdown_signal() {
-    let mut sigterm = signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
-    let mut sigint = signal(tokio::signal::unix::SignalKind::interrupt()).unwrap();
-
-    tokio::select! {
-        _ = sigterm.recv() => {
-            info!("SIGTERM received, shutting down...");
-        }
-        _ = sigint.recv() => {
-            info!("SIGINT received, shutting down...");
-        }
-        _ = tokio::signal::ctrl_c() => {
-            info!("received ctrl-c, shutting down...");
-        }
+async fn get_bootstrap_settings(
+    layout: &storage::Layout,
+    settings_overrides: &[(String, String)],
+) -> storage::Settings {
+    let settings_file = layout.settings();
+    let remote_settings_file = layout.remote_settings();
+    if let Ok(resolved) =
+        config::load(&settings_file, &remote_settings_file, settings_overrides).await
+    {
+        return resolved.settings;
     }
+
+    storage::Settings::default()
 }
@@ -0,0 +1,218 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+type Labels = Vec<String>;
+
+fn labels_of(values: &[&str]) -> Labels {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+pub(super) fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Formats label pairs as `{a="x",b="y"}`, or nothing if there are none.
+pub(super) fn format_labels(
+    names: &[&str],
+    values: &[String],
+    extra: Option<(&str, &str)>,
+) -> String {
+    let mut pairs = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{name}=\"{}\"", escape(value)))
+        .collect::<Vec<_>>();
+    if let Some((name, value)) = extra {
+        pairs.push(format!("{name}=\"{}\"", escape(value)));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn format_float(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// ================================= COUNTER ======================================= //
+#[derive(Debug)]
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    pub fn encode(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "counter");
+        let _ = writeln!(out, "{} {}", self.name, self.get());
+    }
+}
+
+// =============================== COUNTER VEC ===================================== //
+/// A counter per combination of label values.
+#[derive(Debug)]
+pub struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    values: Mutex<BTreeMap<Labels, u64>>,
+}
+
+impl CounterVec {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Increments the counter for the label values, given in the order of the label
+    /// names.
+    pub fn inc(&self, label_values: &[&str]) {
+        debug_assert_eq!(label_values.len(), self.label_names.len());
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        *values.entry(labels_of(label_values)).or_default() += 1;
+    }
+
+    pub fn get(&self, label_values: &[&str]) -> u64 {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        values.get(&labels_of(label_values)).copied().unwrap_or(0)
+    }
+
+    pub fn encode(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "counter");
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        for (labels, value) in values.iter() {
+            let labels = format_labels(self.label_names, labels, None);
+            let _ = writeln!(out, "{}{labels} {value}", self.name);
+        }
+    }
+}
+
+// ============================== HISTOGRAM VEC ==================================== //
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Observations {
+    /// Observations per bucket, not cumulative, in the order of the buckets.
+    pub buckets: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+/// A histogram per combination of label values.
+#[derive(Debug)]
+pub struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    /// Upper bounds of the buckets, in increasing order. Observations above the last
+    /// bound are only counted in the implicit `+Inf` bucket.
+    bounds: &'static [f64],
+    values: Mutex<BTreeMap<Labels, Observations>>,
+}
+
+impl HistogramVec {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+        bounds: &'static [f64],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+            bounds,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn observe(&self, label_values: &[&str], value: f64) {
+        debug_assert_eq!(label_values.len(), self.label_names.len());
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let observations = values
+            .entry(labels_of(label_values))
+            .or_insert_with(|| Observations {
+                buckets: vec![0; self.bounds.len()],
+                sum: 0.0,
+                count: 0,
+            });
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            observations.buckets[bucket] += 1;
+        }
+        observations.sum += value;
+        observations.count += 1;
+    }
+
+    pub fn get(&self, label_values: &[&str]) -> Observations {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        values
+            .get(&labels_of(label_values))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn encode(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "histogram");
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        for (labels, observations) in values.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in self.bounds.iter().zip(&observations.buckets) {
+                cumulative += count;
+                let le = format_float(*bound);
+                let labels = format_labels(self.label_names, labels, Some(("le", &le)));
+                let _ = writeln!(out, "{}_bucket{labels} {cumulative}", self.name);
+            }
+            let inf = format_labels(self.label_names, labels, Some(("le", "+Inf")));
+            let _ = writeln!(out, "{}_bucket{inf} {}", self.name, observations.count);
+
+            let labels = format_labels(self.label_names, labels, None);
+            let _ = writeln!(
+                out,
+                "{}_sum{labels} {}",
+                self.name,
+                format_float(observations.sum)
+            );
+            let _ = writeln!(out, "{}_count{labels} {}", self.name, observations.count);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Counters and histograms of what the agent does, exposed in the Prometheus text
+// format by the socket server's `/metrics` route and, optionally, a TCP listener. The
+// metrics live in a process-wide registry so that subsystems record them where they
+// happen without threading a handle through every call.
+
+pub mod family;
+pub mod workers;
+
+pub use self::family::{Counter, CounterVec, HistogramVec, Observations};
+
+// standard crates
+use std::time::Duration;
+
+/// Content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const HTTP_DURATION_BOUNDS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+#[derive(Debug)]
+pub struct Metrics {
+    pub syncs: Counter,
+    pub sync_failures: CounterVec,
+    pub deployment_transitions: CounterVec,
+    pub deployment_errors: CounterVec,
+    pub mqtt_connections: Counter,
+    pub mqtt_probe_failures: Counter,
+    pub cache_hits: CounterVec,
+    pub cache_misses: CounterVec,
+    pub http_request_duration: HistogramVec,
+    pub api_requests_shed: CounterVec,
+    pub cache_audits: Counter,
+    pub cache_audit_outcomes: CounterVec,
+    pub cache_audit_divergences: CounterVec,
+    pub worker_usage: workers::Usage,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            syncs: Counter::new(
+                "miru_syncs_total",
+                "Syncs with the backend, successful or not.",
+            ),
+            sync_failures: CounterVec::new(
+                "miru_sync_failures_total",
+                "Failed syncs with the backend by kind of failure (network or other).",
+                &["kind"],
+            ),
+            deployment_transitions: CounterVec::new(
+                "miru_deployment_transitions_total",
+                "Deployment activity status transitions.",
+                &["from", "to"],
+            ),
+            deployment_errors: CounterVec::new(
+                "miru_deployment_errors_total",
+                "Failed deployment transitions by the resulting error status.",
+                &["status"],
+            ),
+            mqtt_connections: Counter::new(
+                "miru_mqtt_connections_total",
+                "Connections established with the MQTT broker, including reconnects.",
+            ),
+            mqtt_probe_failures: Counter::new(
+                "miru_mqtt_probe_failures_total",
+                "MQTT loopback probes which didn't come back within their timeout.",
+            ),
+            cache_hits: CounterVec::new(
+                "miru_cache_hits_total",
+                "Cache lookups which found an entry.",
+                &["cache"],
+            ),
+            cache_misses: CounterVec::new(
+                "miru_cache_misses_total",
+                "Cache lookups which found no entry.",
+                &["cache"],
+            ),
+            http_request_duration: HistogramVec::new(
+                "miru_http_request_duration_seconds",
+                "Latency of requests to the backend, including failed requests.",
+                &["method"],
+                HTTP_DURATION_BOUNDS,
+            ),
+            api_requests_shed: CounterVec::new(
+                "miru_api_requests_shed_total",
+                "Socket API requests rejected because the agent was overloaded, by reason.",
+                &["reason"],
+            ),
+            cache_audits: Counter::new(
+                "miru_cache_audits_total",
+                "Audits of the cached deployments against the backend.",
+            ),
+            cache_audit_outcomes: CounterVec::new(
+                "miru_cache_audit_outcomes_total",
+                "Deployments audited, by outcome (consistent, diverged, pending or failed).",
+                &["outcome"],
+            ),
+            cache_audit_divergences: CounterVec::new(
+                "miru_cache_audit_divergences_total",
+                "Cached deployment fields found to differ from the backend, by field.",
+                &["field"],
+            ),
+            worker_usage: workers::Usage::new(),
+        }
+    }
+
+    pub fn record_sync_success(&self) {
+        self.syncs.inc();
+    }
+
+    pub fn record_sync_failure(&self, is_network_conn_err: bool) {
+        self.syncs.inc();
+        let kind = if is_network_conn_err {
+            "network"
+        } else {
+            "other"
+        };
+        self.sync_failures.inc(&[kind]);
+    }
+
+    pub fn record_http_request(&self, method: &str, duration: Duration) {
+        self.http_request_duration
+            .observe(&[method], duration.as_secs_f64());
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.syncs.encode(&mut out);
+        self.sync_failures.encode(&mut out);
+        self.deployment_transitions.encode(&mut out);
+        self.deployment_errors.encode(&mut out);
+        self.mqtt_connections.encode(&mut out);
+        self.mqtt_probe_failures.encode(&mut out);
+        self.cache_hits.encode(&mut out);
+        self.cache_misses.encode(&mut out);
+        self.http_request_duration.encode(&mut out);
+        self.api_requests_shed.encode(&mut out);
+        self.cache_audits.encode(&mut out);
+        self.cache_audit_outcomes.encode(&mut out);
+        self.cache_audit_divergences.encode(&mut out);
+        self.worker_usage.encode(&mut out);
+        out
+    }
+}
+
+/// A short label for a type, e.g. "Deployment" for `miru_agent::models::Deployment`.
+pub fn type_label<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+static METRICS: Metrics = Metrics::new();
+
+/// The agent's metrics.
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
@@ -0,0 +1,259 @@
+// Approximate CPU time and allocations of each of the agent's workers, so a regression
+// in one subsystem (e.g. a busy-looping reconnect) shows up against that subsystem on
+// fleet dashboards. A worker's futures are wrapped in `Instrumented`, which marks the
+// thread as running the worker while it's polled: the time spent in `poll` is
+// attributed to the worker as CPU time (the workers don't block, so it's close to the
+// CPU time they use) and `CountingAlloc`, when installed as the global allocator,
+// attributes allocations made meanwhile to it.
+
+// standard crates
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+// internal crates
+use crate::metrics::family::{format_labels, write_header};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Worker {
+    Syncer,
+    Mqtt,
+    Poller,
+    LongPoll,
+    Socket,
+    TokenRefresh,
+    CacheAudit,
+    Drift,
+    Journal,
+    Wear,
+    Notifications,
+    CellProxy,
+    Updater,
+    Telemetry,
+    Supervisor,
+    Watchdog,
+    CrashReports,
+}
+
+const WORKERS: usize = 17;
+
+impl Worker {
+    pub const ALL: [Worker; WORKERS] = [
+        Worker::Syncer,
+        Worker::Mqtt,
+        Worker::Poller,
+        Worker::LongPoll,
+        Worker::Socket,
+        Worker::TokenRefresh,
+        Worker::CacheAudit,
+        Worker::Drift,
+        Worker::Journal,
+        Worker::Wear,
+        Worker::Notifications,
+        Worker::CellProxy,
+        Worker::Updater,
+        Worker::Telemetry,
+        Worker::Supervisor,
+        Worker::Watchdog,
+        Worker::CrashReports,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Worker::Syncer => "syncer",
+            Worker::Mqtt => "mqtt",
+            Worker::Poller => "poller",
+            Worker::LongPoll => "long_poll",
+            Worker::Socket => "socket",
+            Worker::TokenRefresh => "token_refresh",
+            Worker::CacheAudit => "cache_audit",
+            Worker::Drift => "drift",
+            Worker::Journal => "journal",
+            Worker::Wear => "wear",
+            Worker::Notifications => "notifications",
+            Worker::CellProxy => "cell_proxy",
+            Worker::Updater => "updater",
+            Worker::Telemetry => "telemetry",
+            Worker::Supervisor => "supervisor",
+            Worker::Watchdog => "watchdog",
+            Worker::CrashReports => "crash_reports",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+const NO_WORKER: usize = usize::MAX;
+
+thread_local! {
+    /// The worker being polled on this thread, if any.
+    static CURRENT: Cell<usize> = const { Cell::new(NO_WORKER) };
+    /// Time spent polling instrumented futures nested in the one being polled, which
+    /// is attributed to their workers rather than to its own.
+    static NESTED_NANOS: Cell<u64> = const { Cell::new(0) };
+}
+
+// =================================== USAGE ======================================= //
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub cpu_time: Duration,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+}
+
+/// The resources used by each worker since the agent started.
+#[derive(Debug)]
+pub struct Usage {
+    cpu_nanos: [AtomicU64; WORKERS],
+    allocations: [AtomicU64; WORKERS],
+    allocated_bytes: [AtomicU64; WORKERS],
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Usage {
+    pub const fn new() -> Self {
+        Self {
+            cpu_nanos: [const { AtomicU64::new(0) }; WORKERS],
+            allocations: [const { AtomicU64::new(0) }; WORKERS],
+            allocated_bytes: [const { AtomicU64::new(0) }; WORKERS],
+        }
+    }
+
+    pub fn get(&self, worker: Worker) -> Snapshot {
+        let i = worker.index();
+        Snapshot {
+            cpu_time: Duration::from_nanos(self.cpu_nanos[i].load(Ordering::Relaxed)),
+            allocations: self.allocations[i].load(Ordering::Relaxed),
+            allocated_bytes: self.allocated_bytes[i].load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_cpu(&self, worker: usize, nanos: u64) {
+        self.cpu_nanos[worker].fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn record_alloc(&self, worker: usize, bytes: usize) {
+        self.allocations[worker].fetch_add(1, Ordering::Relaxed);
+        self.allocated_bytes[worker].fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn encode(&self, out: &mut String) {
+        let usage = Worker::ALL.map(|worker| (worker, self.get(worker)));
+        let label = |worker: Worker| format_labels(&["worker"], &[worker.as_str().into()], None);
+
+        let name = "miru_worker_cpu_seconds_total";
+        write_header(
+            out,
+            name,
+            "Approximate CPU time spent by each worker, measured as time spent polling it.",
+            "counter",
+        );
+        for (worker, snapshot) in &usage {
+            let secs = snapshot.cpu_time.as_secs_f64();
+            let _ = writeln!(out, "{name}{} {secs}", label(*worker));
+        }
+
+        let name = "miru_worker_allocations_total";
+        write_header(
+            out,
+            name,
+            "Heap allocations made by each worker.",
+            "counter",
+        );
+        for (worker, snapshot) in &usage {
+            let _ = writeln!(out, "{name}{} {}", label(*worker), snapshot.allocations);
+        }
+
+        let name = "miru_worker_allocated_bytes_total";
+        write_header(out, name, "Bytes allocated by each worker.", "counter");
+        for (worker, snapshot) in &usage {
+            let _ = writeln!(out, "{name}{} {}", label(*worker), snapshot.allocated_bytes);
+        }
+    }
+}
+
+fn usage() -> &'static Usage {
+    &crate::metrics::global().worker_usage
+}
+
+// ================================ INSTRUMENTED =================================== //
+/// A future whose polls are attributed to a worker.
+pub struct Instrumented<F> {
+    worker: Worker,
+    inner: Pin<Box<F>>,
+}
+
+/// Attributes the CPU time and allocations of the future to the worker.
+pub fn instrument<F: Future>(worker: Worker, future: F) -> Instrumented<F> {
+    Instrumented {
+        worker,
+        inner: Box::pin(future),
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let outer_worker = CURRENT.with(|current| current.replace(self.worker.index()));
+        let outer_nested = NESTED_NANOS.with(|nested| nested.replace(0));
+        let started = Instant::now();
+
+        let poll = self.inner.as_mut().poll(cx);
+
+        let elapsed = started.elapsed().as_nanos() as u64;
+        let nested = NESTED_NANOS.with(|nested| nested.replace(outer_nested + elapsed));
+        CURRENT.with(|current| current.set(outer_worker));
+        usage().record_cpu(self.worker.index(), elapsed.saturating_sub(nested));
+        poll
+    }
+}
+
+// ================================= ALLOCATOR ===================================== //
+/// The system allocator, counting allocations against the worker being polled. Install
+/// it with `#[global_allocator]` for the allocation metrics to be recorded.
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    fn record(size: usize) {
+        // the thread local has no destructor, so it's only inaccessible while the
+        // thread is being torn down
+        let worker = CURRENT.try_with(Cell::get).unwrap_or(NO_WORKER);
+        if worker != NO_WORKER {
+            usage().record_alloc(worker, size);
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::record(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
@@ -12,6 +12,53 @@ use uuid::Uuid;
 // =============================== CONFIG INSTANCE ================================= //
 pub type CfgInstID = String;
 
+/// The format of a config instance's content as downloaded and cached. Binary content
+/// is cached base64 encoded and decoded when it's written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+    Ini,
+    Binary,
+}
+
+impl ContentFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+            Self::Ini => "ini",
+            Self::Binary => "binary",
+        }
+    }
+
+    /// The text format a file is expected to hold by its extension, if it has one
+    /// the agent knows.
+    pub fn from_filepath(filepath: &str) -> Option<Self> {
+        let ext = std::path::Path::new(filepath)
+            .extension()?
+            .to_str()?
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "ini" => Some(Self::Ini),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ContentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct ConfigInstance {
     pub id: String,
@@ -20,6 +67,7 @@ pub struct ConfigInstance {
     pub created_at: DateTime<Utc>,
     pub config_schema_id: String,
     pub config_type_id: String,
+    pub content_format: ContentFormat,
 }
 
 impl Default for ConfigInstance {
@@ -31,6 +79,7 @@ impl Default for ConfigInstance {
             created_at: DateTime::<Utc>::UNIX_EPOCH,
             config_schema_id: format!("unknown-{}", Uuid::new_v4()),
             config_type_id: format!("unknown-{}", Uuid::new_v4()),
+            content_format: ContentFormat::default(),
         }
     }
 }
@@ -50,6 +99,8 @@ impl From<backend_client::ConfigInstance> for ConfigInstance {
                 }),
             config_schema_id: cfg_inst.config_schema_id,
             config_type_id: cfg_inst.config_type_id,
+            // the backend doesn't report a content format yet
+            content_format: ContentFormat::default(),
         }
     }
 }
@@ -67,6 +118,7 @@ impl<'de> Deserialize<'de> for ConfigInstance {
             created_at: Option<DateTime<Utc>>,
             config_schema_id: String,
             config_type_id: String,
+            content_format: Option<ContentFormat>,
         }
 
         let result = match DeserializeConfigInstance::deserialize(deserializer) {
@@ -90,6 +142,8 @@ impl<'de> Deserialize<'de> for ConfigInstance {
             created_at,
             config_schema_id: result.config_schema_id,
             config_type_id: result.config_type_id,
+            // cached before content formats were recorded, when all content was JSON
+            content_format: result.content_format.unwrap_or_default(),
         })
     }
 }
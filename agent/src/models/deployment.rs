@@ -1,3 +1,6 @@
+// standard crates
+use std::collections::BTreeMap;
+
 // internal crates
 use crate::deserialize_error;
 use crate::models::{config_instance::CfgInstID, status::impl_status_enum, Patch};
@@ -183,6 +186,64 @@ impl_status_enum!(
     ]
 );
 
+// ============================== DEPLOYMENT METRICS ================================= //
+/// Size and timing measurements of a deployment's rollout on this device.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DplMetrics {
+    /// Bytes of config instance content downloaded for the deployment.
+    #[serde(default)]
+    pub bytes_downloaded: u64,
+    /// Time spent downloading the deployment's config instance content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staging_duration_ms: Option<u64>,
+    /// Time spent writing the deployment's config instances to the filesystem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialization_duration_ms: Option<u64>,
+    /// Time spent running the deployment's hooks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hook_duration_ms: Option<u64>,
+}
+
+impl DplMetrics {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// ============================== PENDING FINALIZE ================================== //
+/// Records a deployment whose config instances have been written but which only
+/// takes effect once the host reboots. The deployment is finalized on the first
+/// apply after the boot id changes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingFinalize {
+    /// The boot id of the host when the deployment was staged.
+    pub boot_id: String,
+    pub staged_at: DateTime<Utc>,
+    /// The last time the agent requested (or performed) a reboot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reboot_requested_at: Option<DateTime<Utc>>,
+}
+
+// ============================ DEPLOYMENT DEPENDENCIES ============================== //
+/// Ordering constraints a deployment declares on other deployments and between the
+/// config types of its own config instances.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DplDependencies {
+    /// Deployments which must have been deployed before this one is.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deployments: Vec<DeploymentID>,
+    /// Config type ids mapped to the config type ids whose instances must be written
+    /// before instances of the keyed config type.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config_types: BTreeMap<String, Vec<String>>,
+}
+
+impl DplDependencies {
+    pub fn is_empty(&self) -> bool {
+        self.deployments.is_empty() && self.config_types.is_empty()
+    }
+}
+
 // ================================ DEPLOYMENT ====================================== //
 pub type DeploymentID = String;
 
@@ -208,6 +269,11 @@ pub struct Deployment {
     // `activity_status`, not by which timestamp is present.
     pub deployed_at: Option<DateTime<Utc>>,
     pub archived_at: Option<DateTime<Utc>>,
+    // Agent-side rollout measurements pushed to backend.
+    pub metrics: DplMetrics,
+    // Agent-side record of a deployment awaiting a host reboot (not pushed).
+    pub pending_finalize: Option<PendingFinalize>,
+    pub dependencies: DplDependencies,
 }
 
 impl Default for Deployment {
@@ -227,6 +293,9 @@ impl Default for Deployment {
             deployed_at: None,
             archived_at: None,
             config_instance_ids: Vec::new(),
+            metrics: DplMetrics::default(),
+            pending_finalize: None,
+            dependencies: DplDependencies::default(),
         }
     }
 }
@@ -257,6 +326,9 @@ impl Deployment {
             deployed_at: None,
             archived_at: None,
             config_instance_ids,
+            metrics: DplMetrics::default(),
+            pending_finalize: None,
+            dependencies: DplDependencies::default(),
         }
     }
 
@@ -308,6 +380,9 @@ impl<'de> Deserialize<'de> for Deployment {
             deployed_at: Option<DateTime<Utc>>,
             archived_at: Option<DateTime<Utc>>,
             config_instance_ids: Vec<CfgInstID>,
+            metrics: Option<DplMetrics>,
+            pending_finalize: Option<PendingFinalize>,
+            dependencies: Option<DplDependencies>,
         }
 
         let result = DeserializeDeployment::deserialize(deserializer)?;
@@ -336,6 +411,10 @@ impl<'de> Deserialize<'de> for Deployment {
             deployed_at: result.deployed_at,
             archived_at: result.archived_at,
             config_instance_ids: result.config_instance_ids,
+            // caches written before metrics were recorded have none
+            metrics: result.metrics.unwrap_or_default(),
+            pending_finalize: result.pending_finalize,
+            dependencies: result.dependencies.unwrap_or_default(),
         })
     }
 }
@@ -12,9 +12,12 @@ pub use self::config_instance::ConfigInstance;
 pub use self::deployment::Deployment;
 pub use self::deployment::DeploymentID;
 pub use self::deployment::DplActivity;
+pub use self::deployment::DplDependencies;
 pub use self::deployment::DplErrStatus;
+pub use self::deployment::DplMetrics;
 pub use self::deployment::DplStatus;
 pub use self::deployment::DplTarget;
+pub use self::deployment::PendingFinalize;
 pub use self::device::Device;
 pub use self::device::DeviceStatus;
 pub use self::errors::ModelsErr;
@@ -22,6 +25,7 @@ pub use self::git_commit::GitCommit;
 pub use self::git_commit::GitCommitID;
 pub use self::release::Release;
 pub use self::release::ReleaseID;
+pub use self::release::Signature;
 
 pub trait Patch<PatchT> {
     fn patch(&mut self, patch: PatchT);
@@ -18,6 +18,9 @@ pub struct Release {
     pub git_commit_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// The release's signature over the manifest of the configs it deploys, if it's
+    /// signed.
+    pub signature: Option<Signature>,
 }
 
 impl Default for Release {
@@ -28,6 +31,7 @@ impl Default for Release {
             git_commit_id: None,
             created_at: DateTime::<Utc>::UNIX_EPOCH,
             updated_at: DateTime::<Utc>::UNIX_EPOCH,
+            signature: None,
         }
     }
 }
@@ -46,6 +50,9 @@ impl From<backend_client::Release> for Release {
                 .updated_at
                 .parse::<DateTime<Utc>>()
                 .unwrap_or(DateTime::<Utc>::UNIX_EPOCH),
+            // the generated release doesn't include its signature yet so it's read
+            // alongside the deployment it's expanded in
+            signature: None,
         }
     }
 }
@@ -62,6 +69,8 @@ impl<'de> Deserialize<'de> for Release {
             git_commit_id: Option<String>,
             created_at: Option<DateTime<Utc>>,
             updated_at: Option<DateTime<Utc>>,
+            #[serde(default)]
+            signature: Option<Signature>,
         }
 
         let result = DeserializeRelease::deserialize(deserializer)?;
@@ -77,6 +86,34 @@ impl<'de> Deserialize<'de> for Release {
             updated_at: result
                 .updated_at
                 .unwrap_or_else(|| deserialize_error!("release", "updated_at", default.updated_at)),
+            signature: result.signature,
         })
     }
 }
+
+// ================================ SIGNATURE ======================================= //
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureAlgorithm {
+    Ed25519,
+    /// ECDSA over P-256 with SHA-256, DER encoded.
+    EcdsaP256Sha256,
+}
+
+impl std::fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ed25519 => write!(f, "ed25519"),
+            Self::EcdsaP256Sha256 => write!(f, "ecdsa_p256_sha256"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    pub algorithm: SignatureAlgorithm,
+    /// Identifies the trusted key the release was signed with.
+    pub key_id: String,
+    /// The base64 encoded signature.
+    pub value: String,
+}
@@ -3,15 +3,21 @@ use std::future::Future;
 use std::time::Duration;
 
 // internal crates
+use crate::filesys;
 use crate::mqtt::{
     errors::*,
-    options::{Options, Protocol, Timeouts},
+    options::{Options, Protocol, QoSLevels, Timeouts, Tls},
 };
+use crate::network::egress;
 use crate::trace;
 
 // external crates
 use chrono::{DateTime, Utc};
-use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, QoS, TlsConfiguration, Transport};
+use rumqttc::tokio_native_tls::native_tls;
+use rumqttc::{
+    AsyncClient, Event, EventLoop, LastWill, MqttOptions, QoS, TlsConfiguration, Transport,
+};
+use tracing::warn;
 
 pub struct Publish<'a> {
     pub topic: &'a str,
@@ -32,12 +38,18 @@ pub trait ClientI: Send + Sync {
     fn unsubscribe(&self, topic: &str) -> impl Future<Output = Result<(), MQTTError>> + Send;
 
     fn disconnect(&self) -> impl Future<Output = Result<(), MQTTError>> + Send;
+
+    /// The QoS of each class of topic.
+    fn qos(&self) -> QoSLevels {
+        QoSLevels::default()
+    }
 }
 
 pub struct Client {
     pub created_at: DateTime<Utc>,
     pub(crate) client: AsyncClient,
     pub(crate) timeouts: Timeouts,
+    pub(crate) qos: QoSLevels,
 }
 
 impl Client {
@@ -49,7 +61,17 @@ impl Client {
         );
 
         mqtt_options.set_keep_alive(options.keep_alive);
+        mqtt_options.set_clean_session(options.clean_session);
+        mqtt_options.set_inflight(options.max_inflight.max(1));
         mqtt_options.set_credentials(&options.credentials.username, &options.credentials.password);
+        if let Some(will) = &options.last_will {
+            mqtt_options.set_last_will(LastWill::new(
+                &will.topic,
+                will.payload.clone(),
+                options.qos.state,
+                true,
+            ));
+        }
 
         match options.connect_address.protocol() {
             Protocol::TCP => {
@@ -62,7 +84,12 @@ impl Client {
                 // `native_tls::TlsConnector::new()` inside rumqttc, which loads
                 // the system trust store — matching the prior rustls-native-certs
                 // behaviour.
-                mqtt_options.set_transport(Transport::tls_with_config(TlsConfiguration::Native));
+                let tls_config = if options.tls == Tls::default() {
+                    TlsConfiguration::Native
+                } else {
+                    native_connector(&options.tls)
+                };
+                mqtt_options.set_transport(Transport::tls_with_config(tls_config));
             }
         }
 
@@ -73,12 +100,58 @@ impl Client {
                 created_at: Utc::now(),
                 client,
                 timeouts: options.timeouts,
+                qos: options.qos,
             },
             eventloop,
         )
     }
 }
 
+fn native_connector(tls: &Tls) -> TlsConfiguration {
+    match tls_connector(tls) {
+        Ok(connector) => TlsConfiguration::NativeConnector(connector),
+        Err(e) => {
+            warn!("Falling back to the default TLS settings for the MQTT broker: {e}");
+            TlsConfiguration::Native
+        }
+    }
+}
+
+/// A connector trusting the CA bundle in addition to the system's CAs and
+/// presenting the client certificate. Whatever can't be loaded is left out with a
+/// warning; the broker then refuses the connection if it required it.
+pub fn tls_connector(tls: &Tls) -> Result<native_tls::TlsConnector, native_tls::Error> {
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(ca_bundle) = &tls.ca_bundle {
+        match root_certificates(ca_bundle) {
+            Ok(certs) => {
+                for cert in certs {
+                    builder.add_root_certificate(cert);
+                }
+            }
+            Err(e) => warn!("Trusting only the system's CAs for the MQTT broker: {e}"),
+        }
+    }
+    if let Some(identity) = &tls.identity {
+        match native_tls::Identity::from_pkcs8(identity.cert_pem(), identity.key_pem()) {
+            Ok(identity) => {
+                builder.identity(identity);
+            }
+            Err(e) => warn!("Connecting to the MQTT broker without a client certificate: {e}"),
+        }
+    }
+    builder.build()
+}
+
+fn root_certificates(ca_bundle: &filesys::File) -> Result<Vec<native_tls::Certificate>, String> {
+    let mut certs = Vec::new();
+    for cert in egress::read_ca_bundle(ca_bundle).map_err(|e| e.to_string())? {
+        let der = cert.to_der().map_err(|e| e.to_string())?;
+        certs.push(native_tls::Certificate::from_der(&der).map_err(|e| e.to_string())?);
+    }
+    Ok(certs)
+}
+
 impl ClientI for Client {
     async fn publish(&self, msg: Publish<'_>) -> Result<(), MQTTError> {
         with_timeout(
@@ -140,6 +213,10 @@ impl ClientI for Client {
         )
         .await
     }
+
+    fn qos(&self) -> QoSLevels {
+        self.qos
+    }
 }
 
 pub async fn poll(eventloop: &mut EventLoop) -> Result<Event, MQTTError> {
@@ -0,0 +1,194 @@
+// Remote operations sent to a device on its command topic, e.g. to sync right away or
+// raise the log level while debugging. Each command is answered on the command
+// response topic with the request's message id so the sender can match them up. The
+// commands are executed by the MQTT worker, see `workers::mqtt::handle_command_event`.
+
+// internal crates
+use crate::config::{self, ConfigErr};
+use crate::deploy::pause;
+use crate::filesys;
+use crate::logs::{LevelControl, LogLevel};
+use crate::models::DeviceStatus;
+use crate::storage::Settings;
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What the worker needs to execute commands beyond the syncer and the device.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub log_level: LevelControl,
+    /// Where the settings are resolved from. Settings can't be reloaded if `None`.
+    pub settings: Option<SettingsSource>,
+    /// Deployments can't be paused or resumed if `None`.
+    pub pause: Option<pause::Switch>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SettingsSource {
+    pub file: filesys::File,
+    /// The remotely managed settings, which `set_remote_settings` replaces.
+    pub remote_file: filesys::File,
+    pub overrides: Vec<(String, String)>,
+    /// The settings the agent started with, as serialized.
+    pub applied: Value,
+}
+
+impl SettingsSource {
+    pub async fn load(&self) -> Result<Settings, ConfigErr> {
+        Ok(config::load(&self.file, &self.remote_file, &self.overrides)
+            .await?
+            .settings)
+    }
+
+    /// Replaces the remotely managed settings, which are only written if they only
+    /// set fields a remote may set (see [config::remote]) and the settings they
+    /// resolve to are valid. Returns the resolved settings.
+    pub async fn set_remote(&self, remote: Value) -> Result<Settings, ConfigErr> {
+        let remote_layer = config::Layer::from_value(config::Source::Remote, remote.clone())?;
+        config::remote::check(&remote_layer.values)?;
+        let layers = [
+            config::Layer::from_value(config::Source::File, self.file.read_json().await?)?,
+            config::Layer::from_env(std::env::vars()),
+            config::Layer::from_overrides(config::Source::Cli, &self.overrides)?,
+            remote_layer,
+        ];
+        let resolved = config::resolve(&layers)?;
+        self.remote_file
+            .write_json(&remote, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+        Ok(resolved.settings)
+    }
+
+    /// Whether the settings differ from those the agent started with in more than
+    /// the log level, which is the only setting applied without a restart.
+    pub fn restart_required(&self, settings: &Settings) -> bool {
+        let mut reloaded = serde_json::to_value(settings).unwrap_or_default();
+        let mut applied = self.applied.clone();
+        for value in [&mut reloaded, &mut applied] {
+            if let Some(map) = value.as_object_mut() {
+                map.remove("log_level");
+            }
+        }
+        reloaded != applied
+    }
+}
+
+/// A command as published, whose command is parsed separately so unknown commands
+/// can still be answered.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub message_id: String,
+    pub command: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Command {
+    /// Syncs with the backend, even during a sync cooldown.
+    SyncNow,
+    /// Re-reads the settings and applies the log level.
+    ReloadSettings,
+    /// Replaces the remotely managed settings, which take precedence over every other
+    /// source, and applies the log level like `reload_settings`. Only the fields in
+    /// [config::remote::FIELDS] may be set. An empty object clears them.
+    SetRemoteSettings {
+        settings: Value,
+    },
+    /// Sets the log level until the agent restarts. The level is parsed when the
+    /// command is executed since log levels deserialize leniently.
+    SetLogLevel {
+        level: String,
+    },
+    ReportHealth,
+    /// Holds every deployment as it is, while syncs keep pulling them, until
+    /// deployments are resumed.
+    PauseDeployments {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Resumes paused deployments and syncs to apply what was held.
+    ResumeDeployments,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SyncNow => "sync_now",
+            Self::ReloadSettings => "reload_settings",
+            Self::SetRemoteSettings { .. } => "set_remote_settings",
+            Self::SetLogLevel { .. } => "set_log_level",
+            Self::ReportHealth => "report_health",
+            Self::PauseDeployments { .. } => "pause_deployments",
+            Self::ResumeDeployments => "resume_deployments",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Succeeded,
+    Failed,
+}
+
+/// The answer to a command, published on the command response topic.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub message_id: String,
+    /// The command's name, if it had one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: String,
+}
+
+impl Response {
+    pub fn succeeded(message_id: String, command: &Command, result: Option<Value>) -> Self {
+        Self {
+            message_id,
+            command: Some(command.name().to_string()),
+            status: Status::Succeeded,
+            result,
+            error: None,
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn failed(message_id: String, command: Option<String>, error: String) -> Self {
+        Self {
+            message_id,
+            command,
+            status: Status::Failed,
+            result: None,
+            error: Some(error),
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// The result of `reload_settings`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Reload {
+    pub log_level: LogLevel,
+    /// Whether other settings changed, which take effect once the agent restarts.
+    pub restart_required: bool,
+}
+
+/// The result of `report_health`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Health {
+    pub agent_version: String,
+    pub device_status: DeviceStatus,
+    pub log_level: LogLevel,
+    pub last_synced_at: DateTime<Utc>,
+    pub last_attempted_sync_at: DateTime<Utc>,
+    pub sync_err_streak: u32,
+    pub sync_in_cooldown: bool,
+}
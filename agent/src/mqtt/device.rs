@@ -1,22 +1,81 @@
 // internal crates
 use crate::mqtt::{
     client::{ClientI, Publish},
+    command,
     errors::*,
-    topics::{device_ping, device_pong, device_sync},
+    options::LastWill,
+    topics::{
+        device_command, device_command_response, device_ping, device_pong, device_probe,
+        device_state, device_sync,
+    },
 };
 use crate::trace;
+use crate::version;
 
 // external crates
 use chrono::Utc;
-use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
 
 pub type SyncDevice = backend_api::models::SyncDevice;
 pub type Ping = backend_api::models::Ping;
 pub type Pong = backend_api::models::Pong;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Online,
+    Offline,
+}
+
+/// Why the agent went offline, which tells a stopped agent apart from a crashed or
+/// unreachable one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflineReason {
+    /// The agent disconnected cleanly on shutdown.
+    Stopped,
+    /// The broker lost the connection and published the agent's last will.
+    ConnectionLost,
+}
+
+/// The message retained on the device's state topic.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionState {
+    pub status: ConnectionStatus,
+    pub agent_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<OfflineReason>,
+    /// Unset in the last will, which is composed when connecting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+impl ConnectionState {
+    pub fn online() -> Self {
+        Self {
+            status: ConnectionStatus::Online,
+            agent_version: version::VERSION.to_string(),
+            reason: None,
+            timestamp: Some(Utc::now().to_rfc3339()),
+        }
+    }
+
+    pub fn offline(reason: OfflineReason) -> Self {
+        Self {
+            status: ConnectionStatus::Offline,
+            agent_version: version::VERSION.to_string(),
+            reason: Some(reason),
+            timestamp: match reason {
+                OfflineReason::Stopped => Some(Utc::now().to_rfc3339()),
+                OfflineReason::ConnectionLost => None,
+            },
+        }
+    }
+}
+
 pub async fn subscribe_sync(client: &impl ClientI, device_id: &str) -> Result<(), MQTTError> {
     let topic = device_sync(device_id);
-    client.subscribe(&topic, QoS::AtLeastOnce).await
+    client.subscribe(&topic, client.qos().sync).await
 }
 
 pub async fn publish_sync(client: &impl ClientI, device_id: &str) -> Result<(), MQTTError> {
@@ -31,7 +90,7 @@ pub async fn publish_sync(client: &impl ClientI, device_id: &str) -> Result<(),
     client
         .publish(Publish {
             topic: &topic,
-            qos: QoS::AtLeastOnce,
+            qos: client.qos().sync,
             retained: true,
             payload: &payload_bytes,
         })
@@ -40,7 +99,43 @@ pub async fn publish_sync(client: &impl ClientI, device_id: &str) -> Result<(),
 
 pub async fn subscribe_ping(client: &impl ClientI, device_id: &str) -> Result<(), MQTTError> {
     let topic = device_ping(device_id);
-    client.subscribe(&topic, QoS::AtLeastOnce).await
+    client.subscribe(&topic, client.qos().ping).await
+}
+
+pub async fn subscribe_command(client: &impl ClientI, device_id: &str) -> Result<(), MQTTError> {
+    let topic = device_command(device_id);
+    client.subscribe(&topic, client.qos().commands).await
+}
+
+pub async fn subscribe_probe(client: &impl ClientI, device_id: &str) -> Result<(), MQTTError> {
+    let topic = device_probe(device_id);
+    client.subscribe(&topic, client.qos().ping).await
+}
+
+pub async fn publish_probe(
+    client: &impl ClientI,
+    device_id: &str,
+    message_id: String,
+) -> Result<(), MQTTError> {
+    let topic = device_probe(device_id);
+    let payload = Ping {
+        message_id,
+        timestamp: Utc::now().to_rfc3339(),
+    };
+    let payload_bytes = serde_json::to_vec(&payload).map_err(|e| {
+        MQTTError::SerdeErr(SerdeErr {
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    client
+        .publish(Publish {
+            topic: &topic,
+            qos: client.qos().ping,
+            retained: false,
+            payload: &payload_bytes,
+        })
+        .await
 }
 
 pub async fn publish_pong(
@@ -62,9 +157,64 @@ pub async fn publish_pong(
     client
         .publish(Publish {
             topic: &topic,
-            qos: QoS::AtLeastOnce,
+            qos: client.qos().ping,
             retained: false,
             payload: &payload_bytes,
         })
         .await
 }
+
+pub async fn publish_command_response(
+    client: &impl ClientI,
+    device_id: &str,
+    response: &command::Response,
+) -> Result<(), MQTTError> {
+    let topic = device_command_response(device_id);
+    let payload_bytes = serde_json::to_vec(response).map_err(|e| {
+        MQTTError::SerdeErr(SerdeErr {
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    client
+        .publish(Publish {
+            topic: &topic,
+            qos: client.qos().commands,
+            retained: false,
+            payload: &payload_bytes,
+        })
+        .await
+}
+
+/// The last will marking the device offline on its state topic if the connection is
+/// lost.
+pub fn last_will(device_id: &str) -> LastWill {
+    let payload = ConnectionState::offline(OfflineReason::ConnectionLost);
+    LastWill {
+        topic: device_state(device_id),
+        // serializing plain data to JSON can't fail
+        payload: serde_json::to_vec(&payload).unwrap_or_default(),
+    }
+}
+
+pub async fn publish_state(
+    client: &impl ClientI,
+    device_id: &str,
+    state: &ConnectionState,
+) -> Result<(), MQTTError> {
+    let topic = device_state(device_id);
+    let payload_bytes = serde_json::to_vec(state).map_err(|e| {
+        MQTTError::SerdeErr(SerdeErr {
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    client
+        .publish(Publish {
+            topic: &topic,
+            qos: client.qos().state,
+            retained: true,
+            payload: &payload_bytes,
+        })
+        .await
+}
@@ -115,6 +115,15 @@ pub struct InvalidConnectAddressErr {
 
 impl crate::errors::Error for InvalidConnectAddressErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to start the MQTT websocket bridge: {source}")]
+pub struct WebSocketBridgeErr {
+    pub source: std::io::Error,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for WebSocketBridgeErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Mock MQTT error (is authentication error: {is_authentication_error}, is network connection error: {is_network_conn_err})")]
 pub struct MockErr {
@@ -149,6 +158,8 @@ pub enum MQTTError {
     #[error(transparent)]
     SerdeErr(SerdeErr),
     #[error(transparent)]
+    WebSocketBridgeErr(WebSocketBridgeErr),
+    #[error(transparent)]
     MockErr(MockErr),
 }
 
@@ -162,6 +173,7 @@ crate::impl_error!(MQTTError {
     UnsubscribeErr,
     DisconnectErr,
     SerdeErr,
+    WebSocketBridgeErr,
     MockErr,
 });
 
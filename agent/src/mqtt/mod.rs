@@ -1,8 +1,11 @@
 pub mod client;
+pub mod command;
 pub mod device;
 pub mod errors;
 pub mod options;
+pub mod probe;
 pub mod topics;
+pub mod websocket;
 
 pub use self::client::{Client, ClientI};
 pub use self::errors::MQTTError;
@@ -3,11 +3,14 @@ use std::fmt;
 use std::time::Duration;
 
 // internal crates
+use crate::filesys;
 use crate::mqtt::errors::InvalidConnectAddressErr;
-use crate::network::{is_loopback_host, MqttHost};
+use crate::network::{identity::ClientIdentity, is_loopback_host, MqttHost};
 
 // external crates
+use rumqttc::QoS;
 use tracing::warn;
+use zeroize::Zeroize;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Protocol {
@@ -101,12 +104,28 @@ impl fmt::Display for ConnectAddress {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq)]
 pub struct Credentials {
     pub username: String,
+    /// The device's token when connecting to the backend's broker.
     pub password: String,
 }
 
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        self.password.zeroize();
+    }
+}
+
 impl Default for Credentials {
     fn default() -> Self {
         Self {
@@ -135,6 +154,47 @@ impl Default for Timeouts {
     }
 }
 
+/// What the client trusts and presents when connecting over SSL.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Tls {
+    /// PEM file of CA certificates trusted in addition to the system's.
+    pub ca_bundle: Option<filesys::File>,
+    /// The client certificate presented to brokers requiring mutual TLS.
+    pub identity: Option<ClientIdentity>,
+}
+
+/// The QoS of each class of topic the agent subscribes and publishes to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct QoSLevels {
+    /// Sync requests from the backend and the device's sync acknowledgements.
+    pub sync: QoS,
+    /// Pings, pongs and subscription probes.
+    pub ping: QoS,
+    /// Remote commands and their responses.
+    pub commands: QoS,
+    /// The device's connection state, including the last will.
+    pub state: QoS,
+}
+
+impl Default for QoSLevels {
+    fn default() -> Self {
+        Self {
+            sync: QoS::AtLeastOnce,
+            ping: QoS::AtLeastOnce,
+            commands: QoS::AtLeastOnce,
+            state: QoS::AtLeastOnce,
+        }
+    }
+}
+
+/// Published by the broker on the client's behalf when the connection is lost without
+/// a clean disconnect. Retained and sent with the state QoS.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LastWill {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Options {
     pub connect_address: ConnectAddress,
@@ -143,6 +203,16 @@ pub struct Options {
     pub keep_alive: Duration,
     pub timeouts: Timeouts,
     pub capacity: usize,
+    pub tls: Tls,
+    pub last_will: Option<LastWill>,
+    pub qos: QoSLevels,
+    /// Whether the broker discards the session when the client disconnects. Persistent
+    /// sessions keep the subscriptions and queue messages published while the device
+    /// is offline. MQTT 3.1.1 has no session expiry, so a persistent session is kept
+    /// until the client connects with a clean session.
+    pub clean_session: bool,
+    /// The most outgoing QoS 1 and 2 messages awaiting acknowledgement.
+    pub max_inflight: u16,
 }
 
 impl Options {
@@ -154,6 +224,11 @@ impl Options {
             keep_alive: Duration::from_secs(20),
             timeouts: Timeouts::default(),
             capacity: 64,
+            tls: Tls::default(),
+            last_will: None,
+            qos: QoSLevels::default(),
+            clean_session: true,
+            max_inflight: 100,
         }
     }
 
@@ -177,7 +252,38 @@ impl Options {
         self
     }
 
+    pub fn with_tls(mut self, tls: Tls) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn with_last_will(mut self, last_will: LastWill) -> Self {
+        self.last_will = Some(last_will);
+        self
+    }
+
+    pub fn with_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    pub fn with_qos(mut self, qos: QoSLevels) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    pub fn with_clean_session(mut self, clean_session: bool) -> Self {
+        self.clean_session = clean_session;
+        self
+    }
+
+    pub fn with_max_inflight(mut self, max_inflight: u16) -> Self {
+        self.max_inflight = max_inflight;
+        self
+    }
+
     pub fn set_password(&mut self, password: String) {
+        self.credentials.password.zeroize();
         self.credentials.password = password;
     }
 }
@@ -0,0 +1,119 @@
+// Subscriptions can silently die while the connection to the broker stays up, e.g.
+// when a broker failover doesn't carry the session over, leaving the device deaf to
+// sync requests. The probe periodically publishes a message to a device-specific
+// topic the agent itself subscribes to and expects it back within a timeout.
+
+// standard crates
+use std::time::Duration;
+
+// external crates
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// Time between a probe being answered and the next probe.
+    pub interval: Duration,
+    /// Time a probe has to come back before it counts as failed.
+    pub timeout: Duration,
+    /// Consecutive failed probes after which the client reconnects rather than only
+    /// resubscribing.
+    pub reconnect_after: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5 * 60),
+            timeout: Duration::from_secs(30),
+            reconnect_after: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Publish a probe with this message id.
+    Send(String),
+    /// The pending probe never came back.
+    Failed { streak: u32 },
+}
+
+#[derive(Debug)]
+pub struct Probe {
+    options: Options,
+    deadline: Instant,
+    pending: Option<String>,
+    sent: u64,
+    failure_streak: u32,
+}
+
+impl Probe {
+    pub fn new(options: Options, now: Instant) -> Self {
+        Self {
+            options,
+            deadline: now + options.interval,
+            pending: None,
+            sent: 0,
+            failure_streak: 0,
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// When [Probe::fire] should next be called.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn pending(&self) -> Option<&str> {
+        self.pending.as_deref()
+    }
+
+    pub fn failure_streak(&self) -> u32 {
+        self.failure_streak
+    }
+
+    /// Sends a new probe or, if the last one is still pending, fails it. A failed
+    /// probe is followed by a new one right away to check whether resubscribing
+    /// helped.
+    pub fn fire(&mut self, now: Instant) -> Action {
+        match self.pending.take() {
+            None => {
+                self.sent += 1;
+                let message_id = format!("probe-{}", self.sent);
+                self.pending = Some(message_id.clone());
+                self.deadline = now + self.options.timeout;
+                Action::Send(message_id)
+            }
+            Some(_) => {
+                self.failure_streak += 1;
+                self.deadline = now;
+                Action::Failed {
+                    streak: self.failure_streak,
+                }
+            }
+        }
+    }
+
+    /// Records a probe coming back. Returns whether it was the pending probe; late
+    /// replies to failed probes are ignored.
+    pub fn received(&mut self, message_id: &str, now: Instant) -> bool {
+        if self.pending.as_deref() != Some(message_id) {
+            return false;
+        }
+        self.pending = None;
+        self.failure_streak = 0;
+        self.deadline = now + self.options.interval;
+        true
+    }
+
+    /// Starts over after (re)connecting since the new session's subscriptions are
+    /// fresh.
+    pub fn reset(&mut self, now: Instant) {
+        self.pending = None;
+        self.failure_streak = 0;
+        self.deadline = now + self.options.interval;
+    }
+}
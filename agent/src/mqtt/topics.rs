@@ -11,11 +11,33 @@ pub fn device_ping(device_id: &str) -> String {
 pub fn device_pong(device_id: &str) -> String {
     format!("{VERSION}/resp/devices/{device_id}/pong")
 }
+pub fn device_command(device_id: &str) -> String {
+    format!("{VERSION}/cmd/devices/{device_id}/command")
+}
+pub fn device_command_response(device_id: &str) -> String {
+    format!("{VERSION}/resp/devices/{device_id}/command")
+}
+/// The agent publishes to and subscribes to this topic itself to check that its
+/// subscriptions are alive.
+pub fn device_probe(device_id: &str) -> String {
+    format!("{VERSION}/probe/devices/{device_id}")
+}
+/// Retained. Whether the agent is online, set to offline by the broker through the
+/// agent's last will if the connection is lost.
+pub fn device_state(device_id: &str) -> String {
+    format!("{VERSION}/state/devices/{device_id}")
+}
+/// The host's resource usage samples, published by the telemetry worker.
+pub fn device_telemetry(device_id: &str) -> String {
+    format!("{VERSION}/telemetry/devices/{device_id}")
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SubscriptionTopics {
     Sync,
     Ping,
+    Command,
+    Probe,
     Unknown,
 }
 
@@ -24,6 +46,10 @@ pub fn parse_subscription(device_id: &str, topic: &str) -> SubscriptionTopics {
         SubscriptionTopics::Sync
     } else if topic == device_ping(device_id) {
         SubscriptionTopics::Ping
+    } else if topic == device_command(device_id) {
+        SubscriptionTopics::Command
+    } else if topic == device_probe(device_id) {
+        SubscriptionTopics::Probe
     } else {
         SubscriptionTopics::Unknown
     }
@@ -0,0 +1,428 @@
+// MQTT over WebSockets, for sites whose firewalls block the MQTT port but let HTTPS
+// through. rumqttc only tunnels over WebSockets with rustls, which the agent doesn't
+// build (see `mqtt::client`), so the client connects to a bridge listening on the
+// loopback interface instead. The bridge tunnels each connection to the broker's
+// WebSocket endpoint over TLS, so the MQTT session itself is unchanged.
+
+// standard crates
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::crypt::{base64, hmac};
+use crate::mqtt::errors::{MQTTError, WebSocketBridgeErr};
+use crate::trace;
+
+// external crates
+use openssl::sha::sha1;
+use rumqttc::tokio_native_tls::{native_tls, TlsConnector};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Appended to the handshake key to compute the accept key (RFC 6455).
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The subprotocol brokers expect MQTT over WebSockets to announce.
+const SUBPROTOCOL: &str = "mqtt";
+
+/// The longest handshake response accepted.
+const MAX_RESPONSE_LEN: usize = 8 * 1024;
+
+/// The largest frame accepted from the broker. MQTT packets are far smaller.
+pub const MAX_FRAME_LEN: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// The port of the broker's WebSocket endpoint.
+    pub port: u16,
+    /// The path of the broker's WebSocket endpoint.
+    pub path: String,
+    /// How long connecting to the broker and upgrading the connection may take.
+    pub handshake_timeout: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            port: 443,
+            path: "/mqtt".to_string(),
+            handshake_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The broker's WebSocket endpoint.
+#[derive(Clone)]
+pub struct Target {
+    pub host: String,
+    pub options: Options,
+    /// Connects over TLS if set, which it is for every broker but local test brokers.
+    pub tls: Option<native_tls::TlsConnector>,
+}
+
+// ================================= BRIDGE ======================================== //
+/// Accepts MQTT connections on a loopback port and tunnels each to the target. Stops
+/// accepting when dropped.
+pub struct Bridge {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl Bridge {
+    pub async fn spawn(target: Target) -> Result<Self, MQTTError> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .map_err(bridge_err)?;
+        let addr = listener.local_addr().map_err(bridge_err)?;
+        let target = Arc::new(target);
+        let task = tokio::spawn(async move {
+            loop {
+                let local = match listener.accept().await {
+                    Ok((local, _)) => local,
+                    Err(e) => {
+                        warn!("mqtt websocket bridge stopped accepting connections: {e}");
+                        return;
+                    }
+                };
+                let target = target.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = tunnel(local, &target).await {
+                        debug!("mqtt websocket tunnel to {} closed: {e}", target.host);
+                    }
+                });
+            }
+        });
+        Ok(Self { addr, task })
+    }
+
+    /// The loopback port the MQTT client connects to.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn bridge_err(e: io::Error) -> MQTTError {
+    MQTTError::WebSocketBridgeErr(WebSocketBridgeErr {
+        source: e,
+        trace: trace!(),
+    })
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+async fn tunnel(local: TcpStream, target: &Target) -> io::Result<()> {
+    let timeout = target.options.handshake_timeout;
+    let timed_out = || io::Error::new(io::ErrorKind::TimedOut, "websocket handshake timed out");
+    let connect = async {
+        let tcp = TcpStream::connect((target.host.as_str(), target.options.port)).await?;
+        match &target.tls {
+            Some(connector) => TlsConnector::from(connector.clone())
+                .connect(&target.host, tcp)
+                .await
+                .map(Upstream::Tls)
+                .map_err(io::Error::other),
+            None => Ok(Upstream::Plain(tcp)),
+        }
+    };
+    match tokio::time::timeout(timeout, connect)
+        .await
+        .map_err(|_| timed_out())??
+    {
+        Upstream::Tls(tls) => {
+            let (upstream, buffered) = tokio::time::timeout(timeout, upgrade(tls, target))
+                .await
+                .map_err(|_| timed_out())??;
+            pump(local, upstream, buffered).await
+        }
+        Upstream::Plain(tcp) => {
+            let (upstream, buffered) = tokio::time::timeout(timeout, upgrade(tcp, target))
+                .await
+                .map_err(|_| timed_out())??;
+            pump(local, upstream, buffered).await
+        }
+    }
+}
+
+enum Upstream {
+    Tls(rumqttc::tokio_native_tls::TlsStream<TcpStream>),
+    Plain(TcpStream),
+}
+
+/// Upgrades the connection to a WebSocket, returning it along with whatever the
+/// broker sent after its handshake response.
+async fn upgrade<S>(mut stream: S, target: &Target) -> io::Result<(S, Vec<u8>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let key = base64::encode_bytes_standard(&hmac::generate_key(16).map_err(io::Error::other)?);
+    let request = handshake_request(&target.host, &target.options.path, &key);
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        if let Some(end) = find_header_end(&response) {
+            break end;
+        }
+        if response.len() > MAX_RESPONSE_LEN {
+            return Err(invalid_data("websocket handshake response is too long"));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "broker closed the connection during the websocket handshake",
+            ));
+        }
+        response.extend_from_slice(&chunk[..n]);
+    };
+    check_handshake_response(&String::from_utf8_lossy(&response[..header_end]), &key)?;
+    Ok((stream, response[header_end..].to_vec()))
+}
+
+fn find_header_end(response: &[u8]) -> Option<usize> {
+    response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|i| i + 4)
+}
+
+/// The HTTP request upgrading a connection to an MQTT WebSocket.
+pub fn handshake_request(host: &str, path: &str, key: &str) -> String {
+    format!(
+        "GET {path} HTTP/1.1\r\n\
+         Host: {host}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {key}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Protocol: {SUBPROTOCOL}\r\n\
+         \r\n"
+    )
+}
+
+/// The accept key the server must answer the handshake key with.
+pub fn accept_key(key: &str) -> String {
+    base64::encode_bytes_standard(&sha1(format!("{key}{GUID}").as_bytes()))
+}
+
+/// Checks that the response's headers accept the upgrade requested with the key.
+pub fn check_handshake_response(response: &str, key: &str) -> io::Result<()> {
+    let mut lines = response.split("\r\n");
+    let status = lines.next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(invalid_data(format!(
+            "broker refused the websocket upgrade: {status}"
+        )));
+    }
+    let accept = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("sec-websocket-accept")
+            .then(|| value.trim().to_string())
+    });
+    if accept.as_deref() != Some(accept_key(key).as_str()) {
+        return Err(invalid_data(
+            "broker answered with the wrong websocket accept key",
+        ));
+    }
+    Ok(())
+}
+
+// ================================= FRAMES ======================================== //
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn bits(&self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xA => Some(Self::Pong),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+/// Encodes a final frame. Clients must mask every frame they send, servers must not.
+pub fn encode_frame(opcode: Opcode, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode.bits());
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+/// Decodes the first frame in the buffer, removing it. Returns `None` if the buffer
+/// doesn't hold a whole frame yet.
+pub fn decode_frame(buf: &mut Vec<u8>) -> io::Result<Option<Frame>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let opcode = Opcode::from_bits(buf[0] & 0x0F)
+        .ok_or_else(|| invalid_data(format!("unknown websocket opcode {}", buf[0] & 0x0F)))?;
+    let masked = buf[1] & 0x80 != 0;
+    let (len, mut offset) = match buf[1] & 0x7F {
+        126 => {
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4)
+        }
+        127 => {
+            if buf.len() < 10 {
+                return Ok(None);
+            }
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(bytes), 10)
+        }
+        len => (len as u64, 2),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(format!(
+            "websocket frame of {len} bytes is too large"
+        )));
+    }
+    let mask = if masked {
+        if buf.len() < offset + 4 {
+            return Ok(None);
+        }
+        let mask = [
+            buf[offset],
+            buf[offset + 1],
+            buf[offset + 2],
+            buf[offset + 3],
+        ];
+        offset += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    let end = offset + len as usize;
+    if buf.len() < end {
+        return Ok(None);
+    }
+    let mut payload: Vec<u8> = buf.drain(..end).skip(offset).collect();
+    if let Some(mask) = mask {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    Ok(Some(Frame { opcode, payload }))
+}
+
+fn client_frame(opcode: Opcode, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let bytes = hmac::generate_key(4).map_err(io::Error::other)?;
+    let mask = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Ok(encode_frame(opcode, payload, Some(mask)))
+}
+
+/// Forwards the client's bytes to the broker as binary frames and the payloads of the
+/// broker's data frames back to the client until either side closes.
+async fn pump<U>(local: TcpStream, upstream: U, buffered: Vec<u8>) -> io::Result<()>
+where
+    U: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut local_read, mut local_write) = local.into_split();
+    let (mut upstream_read, mut upstream_write) = tokio::io::split(upstream);
+    let mut frames = buffered;
+    let mut local_buf = vec![0u8; 16 * 1024];
+    let mut upstream_buf = vec![0u8; 16 * 1024];
+    loop {
+        while let Some(frame) = decode_frame(&mut frames)? {
+            match frame.opcode {
+                Opcode::Binary | Opcode::Continuation | Opcode::Text => {
+                    local_write.write_all(&frame.payload).await?;
+                }
+                Opcode::Ping => {
+                    upstream_write
+                        .write_all(&client_frame(Opcode::Pong, &frame.payload)?)
+                        .await?;
+                }
+                Opcode::Pong => {}
+                Opcode::Close => {
+                    let _ = upstream_write
+                        .write_all(&client_frame(Opcode::Close, &frame.payload)?)
+                        .await;
+                    return Ok(());
+                }
+            }
+        }
+        tokio::select! {
+            n = local_read.read(&mut local_buf) => {
+                let n = n?;
+                if n == 0 {
+                    let _ = upstream_write.write_all(&client_frame(Opcode::Close, &[])?).await;
+                    return Ok(());
+                }
+                upstream_write
+                    .write_all(&client_frame(Opcode::Binary, &local_buf[..n])?)
+                    .await?;
+            }
+            n = upstream_read.read(&mut upstream_buf) => {
+                let n = n?;
+                if n == 0 {
+                    return Ok(());
+                }
+                frames.extend_from_slice(&upstream_buf[..n]);
+            }
+        }
+    }
+}
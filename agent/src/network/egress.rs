@@ -0,0 +1,178 @@
+// Egress for sites which route outbound traffic through a proxy and issue TLS
+// certificates from a private CA. The HTTP client connects to the backend through the
+// proxy and trusts the CA bundle in addition to the system's roots. The MQTT client
+// trusts the bundle as well but connects to the broker directly, since rumqttc is
+// built without proxy support; sites which can only reach the backend through the
+// proxy long poll instead. Fleet-defined headers are added to every backend request
+// for gateways and WAF rules which segment traffic by them.
+
+// standard crates
+use std::fmt;
+
+// internal crates
+use crate::filesys::{self, PathExt};
+use crate::http::errors::{BuildReqwestErr, HTTPErr, InvalidEgressErr};
+use crate::network::identity::ClientIdentity;
+use crate::trace;
+
+// external crates
+use openssl::x509::X509;
+
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+/// Headers the agent sets itself, which fleet-defined headers can't replace.
+const RESERVED_HEADERS: [&str; 3] = ["authorization", "user-agent", "miru-"];
+
+/// An egress proxy, e.g. "http://proxy.corp:3128" or "socks5://proxy.corp:1080".
+#[derive(Clone, PartialEq, Eq)]
+pub struct Proxy {
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    /// Hosts reached directly rather than through the proxy, as in `NO_PROXY`.
+    no_proxy: Vec<String>,
+}
+
+impl Proxy {
+    pub fn new(url: &str) -> Result<Self, String> {
+        let parsed = url::Url::parse(url).map_err(|e| format!("invalid proxy URL: {e}"))?;
+        if !PROXY_SCHEMES.contains(&parsed.scheme()) {
+            return Err(format!(
+                "proxy scheme `{}` not supported, expected one of {}",
+                parsed.scheme(),
+                PROXY_SCHEMES.join(", ")
+            ));
+        }
+        if parsed.host_str().is_none() {
+            return Err("proxy URL must contain a host".into());
+        }
+        if !parsed.username().is_empty() || parsed.password().is_some() {
+            return Err("proxy credentials go in proxy_username and proxy_password".into());
+        }
+        Ok(Self {
+            url: url.to_string(),
+            username: None,
+            password: None,
+            no_proxy: Vec::new(),
+        })
+    }
+
+    pub fn with_credentials(mut self, username: String, password: Option<String>) -> Self {
+        self.username = Some(username);
+        self.password = password;
+        self
+    }
+
+    pub fn with_no_proxy(mut self, hosts: Vec<String>) -> Self {
+        self.no_proxy = hosts;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn to_reqwest(&self) -> Result<reqwest::Proxy, HTTPErr> {
+        let mut proxy = reqwest::Proxy::all(&self.url).map_err(|e| {
+            HTTPErr::BuildReqwestErr(BuildReqwestErr {
+                source: e,
+                trace: trace!(),
+            })
+        })?;
+        if let Some(username) = &self.username {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(",")));
+        }
+        Ok(proxy)
+    }
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "[REDACTED]"))
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub proxy: Option<Proxy>,
+    /// PEM file of CA certificates trusted in addition to the system's.
+    pub ca_bundle: Option<filesys::File>,
+    /// The client certificate presented to servers requiring mutual TLS.
+    pub identity: Option<ClientIdentity>,
+    /// Fleet-defined headers sent with every backend request.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Options {
+    /// Configures an HTTP client to connect through the proxy, trust the CA bundle
+    /// and present the client certificate.
+    pub fn configure(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, HTTPErr> {
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.to_reqwest()?);
+        }
+        if let Some(ca_bundle) = &self.ca_bundle {
+            let mut certs = Vec::new();
+            for cert in read_ca_bundle(ca_bundle)? {
+                let der = cert.to_der().map_err(|e| invalid_ca_bundle(ca_bundle, e))?;
+                let cert = reqwest::Certificate::from_der(&der).map_err(|e| {
+                    HTTPErr::BuildReqwestErr(BuildReqwestErr {
+                        source: e,
+                        trace: trace!(),
+                    })
+                })?;
+                certs.push(cert);
+            }
+            builder = builder.tls_certs_merge(certs);
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.to_reqwest()?);
+        }
+        Ok(builder)
+    }
+}
+
+/// Checks a fleet-defined header is a valid header which doesn't replace one of the
+/// agent's own.
+pub fn validate_header(name: &str, value: &str) -> Result<(), String> {
+    let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| format!("invalid header name `{name}`: {e}"))?;
+    // header names are lowercased when parsed
+    let lowercase = header_name.as_str();
+    if RESERVED_HEADERS.iter().any(|reserved| {
+        lowercase == *reserved || (reserved.ends_with('-') && lowercase.starts_with(reserved))
+    }) {
+        return Err(format!("header `{name}` is set by the agent"));
+    }
+    reqwest::header::HeaderValue::from_str(value)
+        .map_err(|e| format!("invalid value for header `{name}`: {e}"))?;
+    Ok(())
+}
+
+/// Reads the certificates of a PEM bundle. A bundle without any certificates is
+/// invalid, since it's almost certainly not the file that was meant.
+pub fn read_ca_bundle(file: &filesys::File) -> Result<Vec<X509>, HTTPErr> {
+    let pem = std::fs::read(file.path()).map_err(|e| invalid_ca_bundle(file, e))?;
+    let certs = X509::stack_from_pem(&pem).map_err(|e| invalid_ca_bundle(file, e))?;
+    if certs.is_empty() {
+        return Err(invalid_ca_bundle(file, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn invalid_ca_bundle(file: &filesys::File, e: impl fmt::Display) -> HTTPErr {
+    HTTPErr::InvalidEgressErr(InvalidEgressErr {
+        msg: format!("CA bundle {}: {e}", file.path().display()),
+        trace: trace!(),
+    })
+}
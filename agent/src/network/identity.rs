@@ -0,0 +1,137 @@
+// A client certificate for sites which mandate mutual TLS. The device's certificate
+// (optionally followed by its chain) and private key are kept in the auth directory
+// next to the key the agent signs its token requests with, and are presented to the
+// backend by the HTTP client and to the broker by the MQTT client. They are provided
+// by the site's PKI rather than generated by the agent; bearer tokens are still sent
+// as before.
+
+// standard crates
+use std::fmt;
+
+// internal crates
+use crate::filesys::{self, PathExt};
+use crate::http::errors::{BuildReqwestErr, HTTPErr, InvalidClientIdentityErr};
+use crate::trace;
+
+// external crates
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use zeroize::Zeroize;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    /// The certificate followed by its chain, if any, in PEM.
+    cert_pem: Vec<u8>,
+    /// The private key in PKCS#8 PEM, the one form both TLS stacks accept.
+    key_pem: Vec<u8>,
+    subject: String,
+}
+
+impl ClientIdentity {
+    /// Reads the identity from a PEM certificate (chain) file and a PEM private key
+    /// file in PKCS#1, PKCS#8 or SEC1 form. The key must belong to the first
+    /// certificate.
+    pub fn read(cert: &filesys::File, key: &filesys::File) -> Result<Self, HTTPErr> {
+        let cert_pem = std::fs::read(cert.path()).map_err(|e| invalid(cert, e))?;
+        let chain = X509::stack_from_pem(&cert_pem).map_err(|e| invalid(cert, e))?;
+        let Some(leaf) = chain.first() else {
+            return Err(invalid(cert, "no certificates found"));
+        };
+
+        let mut raw_key = std::fs::read(key.path()).map_err(|e| invalid(key, e))?;
+        let parsed = PKey::private_key_from_pem(&raw_key);
+        raw_key.zeroize();
+        let pkey = parsed.map_err(|e| invalid(key, e))?;
+        let public = leaf.public_key().map_err(|e| invalid(cert, e))?;
+        if !public.public_eq(&pkey) {
+            return Err(invalid(
+                key,
+                format!("the key doesn't belong to {}", cert.path().display()),
+            ));
+        }
+        let key_pem = pkey
+            .private_key_to_pem_pkcs8()
+            .map_err(|e| invalid(key, e))?;
+
+        let subject = leaf
+            .subject_name()
+            .entries()
+            .filter_map(|entry| {
+                let name = entry.object().nid().short_name().ok()?;
+                let value = entry.data().to_string().ok()?;
+                Some(format!("{name}={value}"))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Ok(Self {
+            cert_pem,
+            key_pem,
+            subject,
+        })
+    }
+
+    /// Reads the identity if both files exist. A certificate without its key, or
+    /// the other way around, is invalid rather than ignored.
+    pub fn read_if_present(
+        cert: &filesys::File,
+        key: &filesys::File,
+    ) -> Result<Option<Self>, HTTPErr> {
+        match (cert.exists(), key.exists()) {
+            (false, false) => Ok(None),
+            (true, false) => Err(invalid(key, "the private key is missing")),
+            (false, true) => Err(invalid(cert, "the certificate is missing")),
+            (true, true) => Self::read(cert, key).map(Some),
+        }
+    }
+
+    /// The subject of the certificate, e.g. "CN=robot-1, O=Acme".
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn cert_pem(&self) -> &[u8] {
+        &self.cert_pem
+    }
+
+    pub fn key_pem(&self) -> &[u8] {
+        &self.key_pem
+    }
+
+    pub(crate) fn to_reqwest(&self) -> Result<reqwest::Identity, HTTPErr> {
+        let mut pem = Vec::with_capacity(self.cert_pem.len() + self.key_pem.len() + 1);
+        pem.extend_from_slice(&self.cert_pem);
+        pem.push(b'\n');
+        pem.extend_from_slice(&self.key_pem);
+        let identity = reqwest::Identity::from_pem(&pem).map_err(|e| {
+            HTTPErr::BuildReqwestErr(BuildReqwestErr {
+                source: e,
+                trace: trace!(),
+            })
+        });
+        pem.zeroize();
+        identity
+    }
+}
+
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity")
+            .field("subject", &self.subject)
+            .field("key_pem", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl Drop for ClientIdentity {
+    fn drop(&mut self) {
+        self.key_pem.zeroize();
+    }
+}
+
+fn invalid(file: &filesys::File, e: impl fmt::Display) -> HTTPErr {
+    HTTPErr::InvalidClientIdentityErr(InvalidClientIdentityErr {
+        msg: format!("{}: {e}", file.path().display()),
+        trace: trace!(),
+    })
+}
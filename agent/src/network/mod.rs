@@ -1,3 +1,6 @@
+pub mod egress;
+pub mod identity;
+
 // standard crates
 use std::fmt;
 
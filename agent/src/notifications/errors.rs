@@ -0,0 +1,86 @@
+// internal crates
+use crate::errors::Trace;
+
+#[derive(Debug, thiserror::Error)]
+#[error("notification serialization error: {0}")]
+pub struct SerializationErr(pub serde_json::Error);
+impl crate::errors::Error for SerializationErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("notification command '{command}' failed: {msg}")]
+pub struct CommandErr {
+    pub command: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for CommandErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unable to queue notification for mqtt topic '{topic}': {msg}")]
+pub struct MqttOutboxErr {
+    pub topic: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for MqttOutboxErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unable to deliver webhook to '{url}': {msg}")]
+pub struct WebhookErr {
+    pub url: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for WebhookErr {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NotificationsErr {
+    #[error(transparent)]
+    SerializationErr(SerializationErr),
+    #[error(transparent)]
+    CommandErr(CommandErr),
+    #[error(transparent)]
+    MqttOutboxErr(MqttOutboxErr),
+    #[error(transparent)]
+    FileSysErr(#[from] crate::filesys::FileSysErr),
+    #[error(transparent)]
+    WebhookErr(WebhookErr),
+    #[error(transparent)]
+    CryptErr(#[from] crate::crypt::CryptErr),
+}
+
+impl From<serde_json::Error> for NotificationsErr {
+    fn from(e: serde_json::Error) -> Self {
+        Self::SerializationErr(SerializationErr(e))
+    }
+}
+
+impl From<CommandErr> for NotificationsErr {
+    fn from(e: CommandErr) -> Self {
+        Self::CommandErr(e)
+    }
+}
+
+impl From<MqttOutboxErr> for NotificationsErr {
+    fn from(e: MqttOutboxErr) -> Self {
+        Self::MqttOutboxErr(e)
+    }
+}
+
+impl From<WebhookErr> for NotificationsErr {
+    fn from(e: WebhookErr) -> Self {
+        Self::WebhookErr(e)
+    }
+}
+
+crate::impl_error!(NotificationsErr {
+    SerializationErr,
+    CommandErr,
+    MqttOutboxErr,
+    FileSysErr,
+    WebhookErr,
+    CryptErr,
+});
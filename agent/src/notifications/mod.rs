@@ -0,0 +1,10 @@
+pub mod errors;
+pub mod model;
+pub mod signing;
+pub mod sinks;
+pub mod webhook;
+
+pub use self::errors::NotificationsErr;
+pub use self::model::{Notification, Severity};
+pub use self::signing::Keyring;
+pub use self::sinks::{MqttMessage, MqttOutbox, Retry, Sink, Target};
@@ -0,0 +1,114 @@
+// internal crates
+use crate::events::{self, model::Event};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How urgently an operator should act on a notification. Sinks only receive
+/// notifications at or above their configured minimum severity.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub severity: Severity,
+    pub message: String,
+    pub occurred_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+impl Notification {
+    /// Builds the notification for an event, if the event is one operators are
+    /// notified of.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let (severity, message) = match event.event_type.as_str() {
+            events::model::DEPLOYMENT_FAILED => (
+                Severity::Critical,
+                format!(
+                    "deployment {} failed: {}",
+                    str_field(event, "deployment_id"),
+                    str_field(event, "error")
+                ),
+            ),
+            events::model::AUTH_REVOKED => (
+                Severity::Critical,
+                format!(
+                    "device authentication was rejected: {}",
+                    str_field(event, "error")
+                ),
+            ),
+            events::model::AGENT_SAFE_MODE => (
+                Severity::Critical,
+                format!(
+                    "agent started in safe mode after {} crashes; deployments are paused",
+                    event.data["recent_crashes"]
+                ),
+            ),
+            events::model::DISK_LOW => (
+                Severity::Warning,
+                format!(
+                    "disk space is low on {}: {} of {} bytes available",
+                    str_field(event, "path"),
+                    event.data["available_bytes"],
+                    event.data["total_bytes"]
+                ),
+            ),
+            events::model::SYNC_FAILED => (
+                Severity::Warning,
+                format!(
+                    "syncing with the backend failed ({} failure): {}",
+                    str_field(event, "failure_class"),
+                    str_field(event, "error")
+                ),
+            ),
+            events::model::CONFIG_CHANGED => (
+                Severity::Info,
+                format!(
+                    "{} deployed config files changed",
+                    event.data["changes"].as_array().map_or(0, Vec::len)
+                ),
+            ),
+            events::model::DEPLOYMENT_DEPLOYED => (
+                Severity::Info,
+                format!("deployment {} deployed", str_field(event, "deployment_id")),
+            ),
+            events::model::DEPLOYMENT_REMOVED => (
+                Severity::Info,
+                format!("deployment {} removed", str_field(event, "deployment_id")),
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            event_type: event.event_type.clone(),
+            severity,
+            message,
+            occurred_at: event.occurred_at,
+            data: event.data.clone(),
+        })
+    }
+}
+
+fn str_field<'a>(event: &'a Event, field: &str) -> &'a str {
+    event.data[field].as_str().unwrap_or("unknown")
+}
@@ -0,0 +1,250 @@
+// Signs webhook notifications so receivers can tell the agent's callbacks from forged
+// ones. Each webhook destination has its own keys, kept with the device's other
+// credentials. Rotating a destination's key keeps the previous keys signing for a
+// grace period so receivers can switch over without rejecting callbacks in between.
+
+// standard crates
+use std::collections::BTreeMap;
+
+// internal crates
+use crate::crypt::{base64, hmac};
+use crate::filesys::{
+    self,
+    errors::{FileSysErr, WriteFileErr},
+    Overwrite, PathExt,
+};
+use crate::notifications::errors::*;
+use crate::trace;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use zeroize::{Zeroize, Zeroizing};
+
+/// The header carrying a webhook's signatures.
+pub const SIGNATURE_HEADER: &str = "Miru-Signature";
+
+/// How the signature header is built. `<t>` is the unix time in seconds at which the
+/// webhook was signed and each `v1` entry is one key's HMAC-SHA256, hex encoded, of
+/// `<t>.<body>` keyed with the UTF-8 bytes of the key's secret. A destination's keys
+/// are all included while a rotation is in progress. Receivers should accept the
+/// webhook if any signature verifies with a secret they hold and should reject
+/// timestamps too far from their own clock to prevent replays.
+pub const SIGNATURE_SCHEME: &str = "t=<t>,v1=<key id>:<hex hmac-sha256 of \"<t>.<body>\">[,v1=...]";
+
+const SECRET_LEN: usize = 32;
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Key {
+    pub id: String,
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    /// When the key stops signing. Set when a newer key replaces it.
+    #[serde(default)]
+    pub retires_at: Option<DateTime<Utc>>,
+}
+
+impl Key {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.retires_at.is_none_or(|retires_at| now < retires_at)
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Key")
+            .field("id", &self.id)
+            .field("secret", &"<redacted>")
+            .field("created_at", &self.created_at)
+            .field("retires_at", &self.retires_at)
+            .finish()
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// The signing keys of every webhook destination, by URL. Destinations without keys
+/// receive unsigned webhooks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keyring {
+    #[serde(default)]
+    pub destinations: BTreeMap<String, Vec<Key>>,
+}
+
+impl Keyring {
+    /// Reads the keyring, which is empty if the file doesn't exist.
+    pub async fn load(file: &filesys::File) -> Result<Self, NotificationsErr> {
+        if !file.exists() {
+            return Ok(Self::default());
+        }
+        Ok(file.read_json::<Self>().await?)
+    }
+
+    /// Writes the keyring, readable only by the agent's user. The secrets go to a
+    /// temporary file which is created with those permissions before anything is
+    /// written to it and then renamed into place, so other users can't read them even
+    /// briefly.
+    pub async fn save(&self, file: &filesys::File) -> Result<(), NotificationsErr> {
+        let json = Zeroizing::new(serde_json::to_vec_pretty(self)?);
+        let mut tmp = file.path().clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = filesys::File::new(tmp);
+
+        file.parent()?.create_if_absent().await?;
+        // the permissions only apply when the file is created
+        if tmp.exists() {
+            tmp.delete().await?;
+        }
+        if let Err(e) = write_private(&tmp, &json).await {
+            let _ = tmp.delete().await;
+            return Err(e.into());
+        }
+        tmp.move_to(file, Overwrite::Allow).await?;
+        Ok(())
+    }
+
+    /// The destination's keys which are still signing, oldest first.
+    pub fn active(&self, destination: &str, now: DateTime<Utc>) -> Vec<&Key> {
+        self.destinations
+            .get(destination)
+            .map(|keys| keys.iter().filter(|key| key.is_active(now)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds a new key for the destination. The destination's other keys keep signing
+    /// for the grace period (or less, if they were already retiring sooner) and keys
+    /// which have already retired are dropped.
+    pub fn rotate(
+        &mut self,
+        destination: &str,
+        grace: TimeDelta,
+        now: DateTime<Utc>,
+    ) -> Result<Key, NotificationsErr> {
+        let secret = Zeroizing::new(hmac::generate_key(SECRET_LEN)?);
+        let secret = base64::encode_bytes_url_safe_no_pad(&secret);
+        let key = Key {
+            id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            secret,
+            created_at: now,
+            retires_at: None,
+        };
+
+        let keys = self
+            .destinations
+            .entry(destination.to_string())
+            .or_default();
+        keys.retain(|key| key.is_active(now));
+        let retires_at = now + grace.max(TimeDelta::zero());
+        for key in keys.iter_mut() {
+            key.retires_at = Some(key.retires_at.map_or(retires_at, |t| t.min(retires_at)));
+        }
+        keys.push(key.clone());
+        Ok(key)
+    }
+
+    /// Removes every key of the destination, returning whether it had any.
+    pub fn revoke(&mut self, destination: &str) -> bool {
+        self.destinations.remove(destination).is_some()
+    }
+
+    /// The signature header value for a body sent to the destination, or `None` if
+    /// the destination has no active keys.
+    pub fn sign(
+        &self,
+        destination: &str,
+        body: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<String>, NotificationsErr> {
+        let keys = self.active(destination, now);
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        let t = now.timestamp();
+        let payload = format!("{t}.{body}");
+        let mut header = format!("t={t}");
+        for key in keys {
+            let signature = hmac::sign_sha256(key.secret.as_bytes(), payload.as_bytes())?;
+            header.push_str(&format!(",v1={}:{}", key.id, hex(&signature)));
+        }
+        Ok(Some(header))
+    }
+}
+
+/// Verifies a signature header built per [SIGNATURE_SCHEME] against a secret, for
+/// receivers written in Rust. Signatures older or newer than the tolerance are
+/// rejected.
+pub fn verify(
+    header: &str,
+    body: &str,
+    secret: &str,
+    tolerance: TimeDelta,
+    now: DateTime<Utc>,
+) -> Result<bool, NotificationsErr> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.split_once('=') {
+            Some(("t", t)) => timestamp = t.parse::<i64>().ok(),
+            Some(("v1", signature)) => {
+                if let Some((_, hex)) = signature.split_once(':') {
+                    signatures.push(hex);
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(t) = timestamp.and_then(|t| DateTime::<Utc>::from_timestamp(t, 0)) else {
+        return Ok(false);
+    };
+    if (now - t).abs() > tolerance {
+        return Ok(false);
+    }
+    let payload = format!("{}.{body}", t.timestamp());
+    for signature in signatures {
+        let Some(signature) = unhex(signature) else {
+            continue;
+        };
+        if hmac::verify_sha256(secret.as_bytes(), payload.as_bytes(), &signature)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Writes a new file which only its owner can read or write.
+async fn write_private(file: &filesys::File, buf: &[u8]) -> Result<(), FileSysErr> {
+    let write_err = |e: std::io::Error| {
+        FileSysErr::WriteFileErr(WriteFileErr {
+            source: Box::new(e),
+            file: file.clone(),
+            trace: trace!(),
+        })
+    };
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut handle = options.open(file.path()).await.map_err(write_err)?;
+    handle.write_all(buf).await.map_err(write_err)?;
+    handle.sync_all().await.map_err(write_err)?;
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
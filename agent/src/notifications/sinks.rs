@@ -0,0 +1,170 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::cooldown;
+use crate::deploy::hooks;
+use crate::filesys::{self, AppendOptions};
+use crate::notifications::{errors::*, model::*, signing, webhook};
+use crate::trace;
+
+// external crates
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// A message queued for the MQTT worker, which owns the broker connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+pub type MqttOutbox = mpsc::Sender<MqttMessage>;
+
+/// How long a command sink's command may run before it's killed, if not configured.
+pub const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 10;
+
+fn default_command_timeout_secs() -> u64 {
+    DEFAULT_COMMAND_TIMEOUT_SECS
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Target {
+    /// Publishes the notification as JSON to a topic on the device's MQTT broker.
+    Mqtt { topic: String },
+    /// POSTs the notification as JSON to a URL. The body is signed if the URL has
+    /// signing keys (see [signing::SIGNATURE_SCHEME]).
+    Webhook { url: String },
+    /// Appends the notification as a JSON line to a local file.
+    File { path: String },
+    /// Runs a command, e.g. to toggle a GPIO pin or LED. The notification is passed
+    /// through the `MIRU_NOTIFICATION_*` environment variables. The command is killed
+    /// if it runs past the timeout so it can't hold up later notifications.
+    Command {
+        command: Vec<String>,
+        #[serde(default = "default_command_timeout_secs")]
+        timeout_secs: u64,
+    },
+}
+
+/// How a sink retries notifications it fails to deliver, e.g. to a webhook receiver
+/// which is briefly down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retry {
+    /// Attempts in total, including the first.
+    pub max_attempts: u32,
+    /// The delay before each retry, growing with the number of retries so far.
+    pub backoff: cooldown::Backoff,
+}
+
+impl Retry {
+    /// The delay before the given retry (the first retry is 1).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let secs = cooldown::calc(&self.backoff, retry.saturating_sub(1)).max(0);
+        Duration::from_secs(secs as u64)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sink {
+    #[serde(flatten)]
+    pub target: Target,
+    #[serde(default)]
+    pub min_severity: Severity,
+    /// The event types delivered (e.g. `deployment.deployed`, `sync.failed`), at any
+    /// severity. Every event at or above the minimum severity if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+    /// Delivery is attempted once if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<Retry>,
+}
+
+impl Sink {
+    pub fn accepts(&self, notification: &Notification) -> bool {
+        if self.events.is_empty() {
+            notification.severity >= self.min_severity
+        } else {
+            self.events.contains(&notification.event_type)
+        }
+    }
+
+    /// The attempts delivery is made in before giving up.
+    pub fn max_attempts(&self) -> u32 {
+        self.retry.map_or(1, |retry| retry.max_attempts.max(1))
+    }
+
+    pub async fn deliver(
+        &self,
+        notification: &Notification,
+        webhook_client: &webhook::Client,
+        mqtt_outbox: &MqttOutbox,
+        keyring: &signing::Keyring,
+    ) -> Result<(), NotificationsErr> {
+        match &self.target {
+            Target::Mqtt { topic } => {
+                let payload = serde_json::to_vec(notification)?;
+                mqtt_outbox
+                    .try_send(MqttMessage {
+                        topic: topic.clone(),
+                        payload,
+                    })
+                    .map_err(|e| MqttOutboxErr {
+                        topic: topic.clone(),
+                        msg: e.to_string(),
+                        trace: trace!(),
+                    })?;
+            }
+            Target::Webhook { url } => {
+                let body = serde_json::to_string(notification)?;
+                let signature = keyring.sign(url, &body, Utc::now())?;
+                webhook_client.post(url, body, signature).await?;
+            }
+            Target::File { path } => {
+                let mut line = serde_json::to_vec(notification)?;
+                line.push(b'\n');
+                filesys::File::new(path)
+                    .append_bytes(&line, AppendOptions::SYNC)
+                    .await?;
+            }
+            Target::Command {
+                command,
+                timeout_secs,
+            } => run_command(command, *timeout_secs, notification).await?,
+        }
+        Ok(())
+    }
+}
+
+async fn run_command(
+    command: &[String],
+    timeout_secs: u64,
+    notification: &Notification,
+) -> Result<(), CommandErr> {
+    let owned = command.to_vec();
+    let env = vec![
+        (
+            "MIRU_NOTIFICATION_TYPE".to_string(),
+            notification.event_type.clone(),
+        ),
+        (
+            "MIRU_NOTIFICATION_SEVERITY".to_string(),
+            notification.severity.as_str().to_string(),
+        ),
+        (
+            "MIRU_NOTIFICATION_MESSAGE".to_string(),
+            notification.message.clone(),
+        ),
+    ];
+    let timeout = Duration::from_secs(timeout_secs);
+    let result = tokio::task::spawn_blocking(move || hooks::run_blocking(&owned, env, timeout))
+        .await
+        .unwrap_or_else(|e| Err(hooks::Failure::new(e.to_string())));
+    result.map(|_| ()).map_err(|failure| CommandErr {
+        command: command.join(" "),
+        msg: failure.msg,
+        trace: trace!(),
+    })
+}
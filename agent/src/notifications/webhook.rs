@@ -0,0 +1,85 @@
+// Webhooks are delivered to third parties, so they're sent with a client of their
+// own rather than the backend's: the backend client adds the fleet headers, the cell
+// credentials, the device's host name and capabilities to every request and presents
+// the device's client certificate, none of which a webhook receiver should see. A
+// webhook carries its body, content type and signature and nothing else. It does go
+// through the egress proxy and trust the CA bundle, since a site's receivers are
+// reached the same way as the backend.
+
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::http::errors::{BuildReqwestErr, HTTPErr};
+use crate::network::egress;
+use crate::notifications::{errors::*, signing};
+use crate::trace;
+
+// external crates
+use reqwest::header::CONTENT_TYPE;
+
+/// How long a webhook receiver has to respond.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    client: reqwest::Client,
+}
+
+impl Client {
+    pub fn new() -> Result<Self, HTTPErr> {
+        Self::with_egress(&egress::Options::default())
+    }
+
+    /// Connects through the egress proxy and trusts the CA bundle, if configured.
+    /// Neither the client certificate nor the fleet-defined headers are used.
+    pub fn with_egress(egress: &egress::Options) -> Result<Self, HTTPErr> {
+        let egress = egress::Options {
+            proxy: egress.proxy.clone(),
+            ca_bundle: egress.ca_bundle.clone(),
+            identity: None,
+            headers: Vec::new(),
+        };
+        let client = egress
+            .configure(reqwest::Client::builder().timeout(TIMEOUT))?
+            .build()
+            .map_err(|e| {
+                HTTPErr::BuildReqwestErr(BuildReqwestErr {
+                    source: e,
+                    trace: trace!(),
+                })
+            })?;
+        Ok(Self { client })
+    }
+
+    /// POSTs a JSON body to a URL, with its signature if it's signed.
+    pub async fn post(
+        &self,
+        url: &str,
+        body: String,
+        signature: Option<String>,
+    ) -> Result<(), WebhookErr> {
+        let mut request = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(signature) = signature {
+            request = request.header(signing::SIGNATURE_HEADER, signature);
+        }
+        let response = request.send().await.map_err(|e| WebhookErr {
+            url: url.to_string(),
+            msg: e.to_string(),
+            trace: trace!(),
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(WebhookErr {
+                url: url.to_string(),
+                msg: format!("responded with status code {status}"),
+                trace: trace!(),
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+// Production gateways run Linux. The macOS and Windows variants below exist so that
+// developers can run the agent natively on their workstations while building
+// integrations against it; they are not supported for production deployments.
+
+pub mod systemd;
+
+// standard crates
+#[cfg(feature = "installer")]
+use std::path::Path;
+use std::path::PathBuf;
+
+// internal crates
+use crate::filesys;
+
+// external crates
+use tracing::info;
+
+// ================================== PATHS ======================================== //
+/// The directories, relative to the filesystem root, in which the agent keeps its
+/// state.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub const DATA_DIR: &[&str] = &["var", "lib", "miru"];
+#[cfg(target_os = "macos")]
+pub const DATA_DIR: &[&str] = &["Library", "Application Support", "miru"];
+#[cfg(windows)]
+pub const DATA_DIR: &[&str] = &["ProgramData", "miru"];
+
+/// The directory, relative to the filesystem root, in which the manufacturing line
+/// leaves the seed a device provisions itself from on first boot.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub const SEED_DIR: &[&str] = &["boot", "miru-seed"];
+#[cfg(target_os = "macos")]
+pub const SEED_DIR: &[&str] = &["Library", "Application Support", "miru-seed"];
+#[cfg(windows)]
+pub const SEED_DIR: &[&str] = &["ProgramData", "miru-seed"];
+
+/// The root under which the agent's default paths are resolved. On macOS this is the
+/// user's home directory so that the agent doesn't need elevated permissions.
+pub fn filesystem_root() -> filesys::Dir {
+    filesys::Dir::new(root_path())
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn root_path() -> PathBuf {
+    PathBuf::from("/")
+}
+
+#[cfg(target_os = "macos")]
+fn root_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+#[cfg(windows)]
+fn root_path() -> PathBuf {
+    let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    PathBuf::from(format!("{drive}\\"))
+}
+
+/// Resolves the agent's data directory under the given filesystem root.
+pub fn data_dir(filesystem_root: &filesys::Dir) -> filesys::Dir {
+    DATA_DIR
+        .iter()
+        .fold(filesystem_root.clone(), |dir, name| dir.subdir(*name))
+}
+
+/// Resolves the provisioning seed directory under the given filesystem root.
+pub fn seed_dir(filesystem_root: &filesys::Dir) -> filesys::Dir {
+    SEED_DIR
+        .iter()
+        .fold(filesystem_root.clone(), |dir, name| dir.subdir(*name))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn default_log_dir() -> PathBuf {
+    PathBuf::from("/var/log/miru")
+}
+
+#[cfg(target_os = "macos")]
+pub fn default_log_dir() -> PathBuf {
+    root_path().join("Library").join("Logs").join("miru")
+}
+
+#[cfg(windows)]
+pub fn default_log_dir() -> PathBuf {
+    data_dir(&filesystem_root()).path().join("logs")
+}
+
+/// The unix socket the device API is served on. Windows has no unix socket support in
+/// tokio so the device API is served on [LOOPBACK_SERVER_ADDR] instead and the socket
+/// file is unused.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn default_socket_file() -> filesys::File {
+    filesys::File::new("/run/miru/miru.sock")
+}
+
+#[cfg(any(target_os = "macos", windows))]
+pub fn default_socket_file() -> filesys::File {
+    data_dir(&filesystem_root()).file("miru.sock")
+}
+
+/// The loopback address the device API is served on where unix sockets aren't
+/// available.
+pub const LOOPBACK_SERVER_ADDR: &str = "127.0.0.1:7453";
+
+/// The command used to reboot the host.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn default_reboot_command() -> Vec<String> {
+    vec!["systemctl".to_string(), "reboot".to_string()]
+}
+
+#[cfg(target_os = "macos")]
+pub fn default_reboot_command() -> Vec<String> {
+    vec!["shutdown".to_string(), "-r".to_string(), "now".to_string()]
+}
+
+#[cfg(windows)]
+pub fn default_reboot_command() -> Vec<String> {
+    ["shutdown", "/r", "/t", "0"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+// ================================== SIGNALS ====================================== //
+/// Resolves once the platform asks the agent to stop: SIGTERM or SIGINT on unix and a
+/// console ctrl-c, ctrl-break, close or shutdown event on Windows.
+#[cfg(unix)]
+pub async fn shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    let mut sigint = signal(SignalKind::interrupt()).unwrap();
+
+    tokio::select! {
+        _ = sigterm.recv() => {
+            info!("SIGTERM received, shutting down...");
+        }
+        _ = sigint.recv() => {
+            info!("SIGINT received, shutting down...");
+        }
+        _ = tokio::signal::ctrl_c() => {
+            info!("received ctrl-c, shutting down...");
+        }
+    }
+}
+
+#[cfg(windows)]
+pub async fn shutdown_signal() {
+    use tokio::signal::windows;
+
+    let mut ctrl_break = windows::ctrl_break().unwrap();
+    let mut ctrl_close = windows::ctrl_close().unwrap();
+    let mut ctrl_shutdown = windows::ctrl_shutdown().unwrap();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            info!("received ctrl-c, shutting down...");
+        }
+        _ = ctrl_break.recv() => {
+            info!("received ctrl-break, shutting down...");
+        }
+        _ = ctrl_close.recv() => {
+            info!("console closed, shutting down...");
+        }
+        _ = ctrl_shutdown.recv() => {
+            info!("system shutdown received, shutting down...");
+        }
+    }
+}
+
+// =================================== HOST ======================================== //
+/// The host's name. Without the telemetry feature it is read from the kernel or the
+/// environment rather than through sysinfo.
+#[cfg(feature = "telemetry")]
+pub fn host_name() -> String {
+    crate::telemetry::SystemInfo::host_name()
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub fn host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "telemetry")]
+pub fn arch() -> String {
+    crate::telemetry::SystemInfo::arch()
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub fn arch() -> String {
+    std::env::consts::ARCH.to_string()
+}
+
+#[cfg(feature = "telemetry")]
+pub fn os() -> String {
+    crate::telemetry::SystemInfo::os()
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub fn os() -> String {
+    std::env::consts::OS.to_string()
+}
+
+// ================================== SERVICE ====================================== //
+/// The restart policies a systemd service accepts.
+#[cfg(feature = "installer")]
+pub const RESTART_POLICIES: &[&str] = &[
+    "no",
+    "always",
+    "on-success",
+    "on-failure",
+    "on-abnormal",
+    "on-abort",
+    "on-watchdog",
+];
+
+/// How the agent's service is run. Only the systemd unit honours these; the launchd agent
+/// and the Task Scheduler task keep their fixed restart behaviour.
+#[cfg(feature = "installer")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceOptions {
+    /// When the service is restarted, one of [RESTART_POLICIES].
+    pub restart: String,
+    pub restart_sec: u32,
+    /// Restrict the service to what the agent needs: a read-only system outside of
+    /// `writable_paths`, no privilege escalation and no access to kernel settings.
+    pub hardening: bool,
+    /// The paths the hardened service may write to besides the agent's own
+    /// directories, e.g. where configs are deployed.
+    pub writable_paths: Vec<PathBuf>,
+    /// Restart the service if the agent stops sending keep-alives for this long.
+    pub watchdog_sec: Option<u32>,
+}
+
+#[cfg(feature = "installer")]
+impl Default for ServiceOptions {
+    fn default() -> Self {
+        Self {
+            restart: "on-failure".to_string(),
+            restart_sec: 5,
+            hardening: false,
+            writable_paths: Vec::new(),
+            watchdog_sec: None,
+        }
+    }
+}
+
+/// The name the agent's service is installed under: the systemd unit, launchd label
+/// or scheduled task.
+#[cfg(all(feature = "installer", not(any(target_os = "macos", windows))))]
+pub const SERVICE_NAME: &str = "miru";
+
+#[cfg(all(feature = "installer", target_os = "macos"))]
+pub const SERVICE_NAME: &str = "com.mirurobotics.agent";
+
+#[cfg(all(feature = "installer", windows))]
+pub const SERVICE_NAME: &str = "Miru Agent";
+
+/// The command which stops the agent's service and keeps it from starting again.
+#[cfg(all(feature = "installer", not(any(target_os = "macos", windows))))]
+pub fn stop_service_command(service: &str) -> Vec<String> {
+    ["systemctl", "disable", "--now", service]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+#[cfg(all(feature = "installer", target_os = "macos"))]
+pub fn stop_service_command(service: &str) -> Vec<String> {
+    vec![
+        "launchctl".to_string(),
+        "remove".to_string(),
+        service.to_string(),
+    ]
+}
+
+#[cfg(all(feature = "installer", windows))]
+pub fn stop_service_command(service: &str) -> Vec<String> {
+    ["schtasks", "/Delete", "/TN", service, "/F"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+/// The tmpfiles.d entry which creates the agent's log directory at boot.
+#[cfg(feature = "installer")]
+pub fn tmpfiles_entry(log_dir: &Path) -> String {
+    format!("d {} 0750 root root -\n", log_dir.display())
+}
+
+/// Renders a definition for running the agent at the given path, with the given
+/// arguments, as a background service on the current platform: a systemd unit on
+/// Linux, a launchd agent on macOS and a Task Scheduler task on Windows.
+#[cfg(all(feature = "installer", not(any(target_os = "macos", windows))))]
+pub fn service_definition(binary: &Path, args: &[String], options: &ServiceOptions) -> String {
+    let exec_start = std::iter::once(binary.display().to_string())
+        .chain(args.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    // with Type=notify systemd waits for the agent to report it's ready before starting
+    // the watchdog, since keep-alives are only sent from then on. Startup may wait out
+    // a backend outage during an upgrade, so it isn't timed out.
+    let mut service = "Type=notify\nTimeoutStartSec=infinity\n".to_string();
+    service.push_str(&format!(
+        "ExecStart={exec_start}\nRestart={}\nRestartSec={}\n",
+        options.restart, options.restart_sec
+    ));
+    if let Some(secs) = options.watchdog_sec {
+        service.push_str(&format!("WatchdogSec={secs}\nNotifyAccess=main\n"));
+    }
+    if options.hardening {
+        service.push_str(
+            "NoNewPrivileges=yes
+ProtectSystem=strict
+ProtectHome=read-only
+PrivateTmp=yes
+ProtectKernelTunables=yes
+ProtectKernelModules=yes
+ProtectKernelLogs=yes
+ProtectControlGroups=yes
+RestrictSUIDSGID=yes
+RestrictRealtime=yes
+RestrictNamespaces=yes
+LockPersonality=yes
+RuntimeDirectory=miru
+",
+        );
+        // a leading '-' keeps a missing path from failing the service
+        for path in &options.writable_paths {
+            service.push_str(&format!("ReadWritePaths=-{}\n", path.display()));
+        }
+    }
+    format!(
+        "[Unit]
+Description=Miru Agent
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+{service}
+[Install]
+WantedBy=multi-user.target
+"
+    )
+}
+
+#[cfg(all(feature = "installer", target_os = "macos"))]
+pub fn service_definition(binary: &Path, args: &[String], _options: &ServiceOptions) -> String {
+    let program_arguments = std::iter::once(binary.display().to_string())
+        .chain(args.iter().cloned())
+        .map(|arg| format!("        <string>{arg}</string>"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>com.mirurobotics.agent</string>
+    <key>ProgramArguments</key>
+    <array>
+{program_arguments}
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+</dict>
+</plist>
+"#
+    )
+}
+
+#[cfg(all(feature = "installer", windows))]
+pub fn service_definition(binary: &Path, args: &[String], _options: &ServiceOptions) -> String {
+    let arguments = if args.is_empty() {
+        String::new()
+    } else {
+        format!("\n      <Arguments>{}</Arguments>", args.join(" "))
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Miru Agent</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger>
+      <Enabled>true</Enabled>
+    </LogonTrigger>
+  </Triggers>
+  <Settings>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <RestartOnFailure>
+      <Interval>PT1M</Interval>
+      <Count>999</Count>
+    </RestartOnFailure>
+  </Settings>
+  <Actions>
+    <Exec>
+      <Command>{}</Command>{arguments}
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        binary.display()
+    )
+}
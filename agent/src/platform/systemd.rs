@@ -0,0 +1,111 @@
+// systemd's service notification protocol (sd_notify(3)): the service manager passes
+// the path of a datagram socket in NOTIFY_SOCKET and the service reports its state by
+// sending newline separated assignments to it. The agent reports when it's ready,
+// when it stops and, if the unit sets WatchdogSec=, keep-alives which let systemd
+// restart an agent which stopped making progress.
+
+// standard crates
+use std::time::Duration;
+
+// external crates
+use tracing::debug;
+
+pub const READY: &str = "READY=1";
+pub const STOPPING: &str = "STOPPING=1";
+pub const WATCHDOG: &str = "WATCHDOG=1";
+
+/// Sends notifications to the service manager. Does nothing unless the agent was
+/// started by one which listens for them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Notifier {
+    socket: Option<String>,
+}
+
+impl Notifier {
+    pub fn new(socket: Option<String>) -> Self {
+        Self {
+            socket: socket.filter(|socket| !socket.is_empty()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("NOTIFY_SOCKET").ok())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// Sends the state, e.g. [READY]. Failures are logged and otherwise ignored since
+    /// the agent runs the same without a service manager.
+    pub fn notify(&self, state: &str) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        if let Err(e) = send(socket, state) {
+            debug!("failed to notify the service manager at '{socket}' of '{state}': {e}");
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &str, state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    // never block the runtime if the service manager falls behind
+    datagram.set_nonblocking(true)?;
+    // a leading '@' names a socket in the abstract namespace
+    match socket.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "abstract sockets are only supported on Linux",
+            ));
+        }
+        None => {
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "service notifications are only supported on Unix",
+    ))
+}
+
+/// How long the service manager waits for a keep-alive before restarting the agent,
+/// from WATCHDOG_USEC. `None` if the watchdog isn't enabled or, per WATCHDOG_PID, is
+/// meant for another process.
+pub fn watchdog_timeout(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.trim().parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    match usec?.trim().parse::<u64>().ok()? {
+        0 => None,
+        usec => Some(Duration::from_micros(usec)),
+    }
+}
+
+/// The watchdog timeout of the agent's process, see [watchdog_timeout].
+pub fn watchdog_timeout_from_env() -> Option<Duration> {
+    watchdog_timeout(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
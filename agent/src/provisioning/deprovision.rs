@@ -0,0 +1,295 @@
+// Offboarding, the reverse of activation: the device is deregistered from the backend,
+// then the config files its deployments wrote and the agent's state (caches, token,
+// device file, keys and settings) are removed, so the host is left as it was before the
+// agent ran. Only the directories the agent recorded creating for the config files
+// (see [crate::deploy::created_dirs]) are removed with them, and only once empty, and
+// the deployment directory keeps nothing but what was in it besides the releases. The
+// logs can be kept for later investigation. Stopping the service is left to the caller
+// since it's specific to how the agent was installed. The caller deregisters the device
+// with [deregister] before stopping it, so a device which can't be deregistered is left
+// running.
+
+// standard crates
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+// internal crates
+use crate::authn;
+use crate::cache::entry::CacheEntry;
+use crate::crypt::keystore::PrivateKey;
+use crate::deploy::{created_dirs::CreatedDirs, versions::Versions};
+use crate::filesys::{self, errors::FileSysErr, PathExt};
+use crate::http;
+use crate::models::{self, DplActivity};
+use crate::provisioning::errors::*;
+use crate::storage;
+
+// external crates
+use serde::{de::DeserializeOwned, Serialize};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Deregisters the device from the backend before its credentials are removed.
+    pub deregister: bool,
+    /// Removes the local state even if the device couldn't be deregistered.
+    pub force: bool,
+    pub keep_logs: bool,
+    /// The releases kept under the deployment directory, if one is configured.
+    pub versions: Option<Versions>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            deregister: true,
+            force: false,
+            keep_logs: false,
+            versions: None,
+        }
+    }
+}
+
+/// What deprovisioning removes.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    /// The config files the device's deployments wrote in place.
+    pub deployed_files: Vec<PathBuf>,
+    /// The directories created for the deployed files, deepest first. Each is only
+    /// removed if it's empty once the files are.
+    pub created_dirs: Vec<PathBuf>,
+    /// The deployment directory's `current` link and the releases behind it.
+    pub releases: Vec<PathBuf>,
+    /// The agent's files and directories under the data directory, except the logs if
+    /// they're kept.
+    pub state: Vec<PathBuf>,
+    /// The log directory, unless the logs are kept or it's part of the state.
+    pub logs: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub deregistered: bool,
+    pub removed: Vec<PathBuf>,
+    /// The paths which couldn't be removed and why.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+type Entries<V> = HashMap<String, CacheEntry<String, V>>;
+
+/// The files and directories deprovisioning the device removes.
+pub async fn plan(layout: &storage::Layout, opts: &Options) -> Plan {
+    let root = layout.root();
+    let log_dir = layout.log_dir();
+    let logs_in_root = log_dir.starts_with(root.path());
+
+    let mut state = Vec::new();
+    for path in agent_paths(layout) {
+        if !path.exists() || (opts.keep_logs && log_dir.starts_with(&path)) {
+            continue;
+        }
+        state.push(path);
+    }
+    state.sort();
+
+    let releases = opts.versions.as_ref().map(Versions::paths);
+    let releases: Vec<PathBuf> = releases
+        .into_iter()
+        .flatten()
+        .filter(|path| path.is_symlink() || path.exists())
+        .collect();
+    // files deployed through the `current` link go with the releases
+    let deployed_files = deployed_files(layout)
+        .await
+        .into_iter()
+        .filter(|path| {
+            !opts
+                .versions
+                .as_ref()
+                .is_some_and(|versions| path.starts_with(versions.dir().path()))
+        })
+        .collect();
+
+    Plan {
+        deployed_files,
+        created_dirs: CreatedDirs::new(layout.created_dirs()).read().await,
+        releases,
+        state,
+        logs: (!opts.keep_logs && !logs_in_root && log_dir.exists()).then_some(log_dir),
+    }
+}
+
+/// The files and directories the agent creates under its data directory. Anything
+/// else in the directory isn't the agent's and is left alone, since the directory may
+/// well be shared (e.g. `--data-dir=/srv`).
+fn agent_paths(layout: &storage::Layout) -> Vec<PathBuf> {
+    let root = layout.root();
+    let mut paths: Vec<PathBuf> = [
+        layout.auth().root,
+        layout.resources(),
+        layout.crash().root,
+        layout.updates_dir(),
+        layout.trash_dir(),
+        layout.events_dir(),
+        layout.sync_dir(),
+        layout.temp_dir(),
+    ]
+    .iter()
+    .map(|dir| dir.path().to_path_buf())
+    .collect();
+    paths.extend(
+        [
+            layout.settings(),
+            layout.device(),
+            layout.agent_version(),
+            layout.migrations(),
+            layout.journal(),
+            layout.wear(),
+            layout.config_access(),
+            layout.content_warming(),
+            layout.crash_record(),
+            layout.provenance(),
+            layout.redaction_rules(),
+            layout.deployments_paused(),
+            layout.created_dirs(),
+        ]
+        .iter()
+        .map(|file| file.path().to_path_buf()),
+    );
+    // the logs and socket only live in the data directory if it was given explicitly
+    for path in [layout.log_dir(), layout.socket_file().path().to_path_buf()] {
+        if path.starts_with(root.path()) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// The files written by deployments which are, or may partly still be, deployed.
+async fn deployed_files(layout: &storage::Layout) -> Vec<PathBuf> {
+    let deployments = read_cache::<models::Deployment>(&layout.deployments()).await;
+    let cfg_insts = read_cache::<models::ConfigInstance>(&layout.config_instance_meta()).await;
+
+    let mut files = BTreeSet::new();
+    for entry in deployments.values() {
+        let dpl = &entry.value;
+        if !matches!(
+            dpl.activity_status,
+            DplActivity::Deployed | DplActivity::Removing
+        ) {
+            continue;
+        }
+        for id in &dpl.config_instance_ids {
+            if let Some(cfg_inst) = cfg_insts.get(id) {
+                files.insert(PathBuf::from(&cfg_inst.value.filepath));
+            }
+        }
+    }
+    files
+        .into_iter()
+        .filter(|path| path.is_absolute())
+        .collect()
+}
+
+async fn read_cache<V>(file: &filesys::File) -> Entries<V>
+where
+    V: Clone + Serialize + DeserializeOwned,
+{
+    if !file.exists() {
+        return HashMap::new();
+    }
+    file.read_json::<Entries<V>>().await.unwrap_or_else(|e| {
+        warn!("unable to read {file}, its deployed files are left in place: {e}");
+        HashMap::new()
+    })
+}
+
+/// Deregisters the device and removes what [plan] lists. The state is left untouched
+/// if deregistering fails, so it can be retried, unless `opts.force` is set.
+pub async fn deprovision<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    layout: &storage::Layout,
+    private_key: &PrivateKey,
+    opts: &Options,
+) -> Result<Report, ProvisionErr> {
+    let plan = plan(layout, opts).await;
+    let mut report = Report::default();
+
+    if opts.deregister {
+        match deregister(http_client, layout, private_key).await {
+            Ok(deregistered) => report.deregistered = deregistered,
+            Err(e) if opts.force => {
+                warn!("unable to deregister the device, removing it anyway: {e}")
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    for path in &plan.deployed_files {
+        let file = filesys::File::new(path);
+        if !file.exists() {
+            continue;
+        }
+        match file.delete().await {
+            Ok(()) => report.removed.push(path.clone()),
+            Err(e) => report.failed.push((path.clone(), e.to_string())),
+        }
+    }
+    // directories still holding files which aren't the agent's are left in place
+    for path in &plan.created_dirs {
+        let dir = filesys::Dir::new(path);
+        if dir.is_empty().await.unwrap_or(false) && dir.delete().await.is_ok() {
+            report.removed.push(path.clone());
+        }
+    }
+
+    for path in plan
+        .releases
+        .iter()
+        .chain(&plan.state)
+        .chain(plan.logs.iter())
+    {
+        match remove(path).await {
+            Ok(()) => report.removed.push(path.clone()),
+            Err(e) => report.failed.push((path.clone(), e.to_string())),
+        }
+    }
+    let root = layout.root();
+    if root.is_empty().await.unwrap_or(false) {
+        if let Err(e) = root.delete().await {
+            report
+                .failed
+                .push((root.path().to_path_buf(), e.to_string()));
+        }
+    }
+    Ok(report)
+}
+
+/// Removes a file, directory or link, without following the link.
+async fn remove(path: &Path) -> Result<(), FileSysErr> {
+    match path.is_dir() && !path.is_symlink() {
+        true => filesys::Dir::new(path).delete().await,
+        false => filesys::File::new(path).delete().await,
+    }
+}
+
+/// Deregisters the device from the backend. Returns false if it was never activated.
+/// Needs only the device's key and the backend, so the agent needn't be running.
+pub async fn deregister<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    layout: &storage::Layout,
+    private_key: &PrivateKey,
+) -> Result<bool, ProvisionErr> {
+    if storage::assert_activated(layout, private_key)
+        .await
+        .is_err()
+    {
+        info!("the device isn't activated, skipping its deregistration");
+        return Ok(false);
+    }
+    let token = authn::issue_token(http_client, private_key, &layout.auth().public_key()).await?;
+    let device = http::devices::deregister(http_client, &token.token).await?;
+    info!("deregistered device {}", device.id);
+    Ok(true)
+}
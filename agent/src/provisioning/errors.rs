@@ -25,6 +25,34 @@ pub struct InvalidSettingsErr {
 
 impl crate::errors::Error for InvalidSettingsErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("invalid provisioning seed {file}: {msg}")]
+pub struct InvalidSeedErr {
+    pub file: filesys::File,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidSeedErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid activation URL: {msg}")]
+pub struct InvalidActivationUrlErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidActivationUrlErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("the device was activated but couldn't be verified: {msg}")]
+pub struct VerificationErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for VerificationErr {}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ProvisionErr {
     #[error(transparent)]
@@ -32,6 +60,12 @@ pub enum ProvisionErr {
     #[error(transparent)]
     InvalidSettingsErr(InvalidSettingsErr),
     #[error(transparent)]
+    InvalidSeedErr(InvalidSeedErr),
+    #[error(transparent)]
+    InvalidActivationUrlErr(InvalidActivationUrlErr),
+    #[error(transparent)]
+    VerificationErr(VerificationErr),
+    #[error(transparent)]
     AuthnErr(authn::AuthnErr),
     #[error(transparent)]
     CryptErr(crypt::CryptErr),
@@ -87,9 +121,30 @@ impl From<InvalidSettingsErr> for ProvisionErr {
     }
 }
 
+impl From<InvalidSeedErr> for ProvisionErr {
+    fn from(e: InvalidSeedErr) -> Self {
+        Self::InvalidSeedErr(e)
+    }
+}
+
+impl From<InvalidActivationUrlErr> for ProvisionErr {
+    fn from(e: InvalidActivationUrlErr) -> Self {
+        Self::InvalidActivationUrlErr(e)
+    }
+}
+
+impl From<VerificationErr> for ProvisionErr {
+    fn from(e: VerificationErr) -> Self {
+        Self::VerificationErr(e)
+    }
+}
+
 crate::impl_error!(ProvisionErr {
     MissingEnvVarErr,
     InvalidSettingsErr,
+    InvalidSeedErr,
+    InvalidActivationUrlErr,
+    VerificationErr,
     AuthnErr,
     CryptErr,
     FileSysErr,
@@ -164,6 +219,17 @@ mod tests {
             assert!(matches!(install_err, ProvisionErr::LogsErr(_)));
         }
 
+        #[test]
+        fn from_invalid_seed_err() {
+            let err = InvalidSeedErr {
+                file: filesys::File::new("/boot/miru-seed/seed.json"),
+                msg: "test".to_string(),
+                trace: crate::trace!(),
+            };
+            let install_err = ProvisionErr::from(err);
+            assert!(matches!(install_err, ProvisionErr::InvalidSeedErr(_)));
+        }
+
         #[test]
         fn from_invalid_settings_err() {
             let err = InvalidSettingsErr {
@@ -1,9 +1,11 @@
 pub mod display;
 pub mod errors;
 
+pub mod deprovision;
 pub mod provision;
 pub mod reprovision;
+pub mod seed;
 mod shared;
 
 pub use self::errors::ProvisionErr;
-pub use self::shared::read_token_from_env;
+pub use self::shared::{parse_activation, read_token_from_env, Activation};
@@ -1,12 +1,13 @@
 // internal crates
+use crate::authn;
 use crate::cli;
-use crate::crypt::rsa;
-use crate::filesys::{self, Overwrite};
+use crate::crypt::{jwt, rsa};
+use crate::filesys::{self, Overwrite, WriteOptions};
 use crate::http;
 use crate::models;
+use crate::platform;
 use crate::provisioning::{errors::*, shared};
 use crate::storage::{self, settings};
-use crate::telemetry;
 use crate::version;
 use backend_api::models as backend_client;
 
@@ -28,7 +29,13 @@ pub async fn provision<HTTPClientT: http::ClientI>(
     device_name: Option<String>,
 ) -> Result<Outcome, ProvisionErr> {
     // if a machine has already been provisioned, then just return the device's name
-    if storage::assert_activated(layout).await.is_ok() {
+    let private_key = settings
+        .key_storage
+        .private_key(layout.auth().private_key());
+    if storage::assert_activated(layout, &private_key)
+        .await
+        .is_ok()
+    {
         let device_name = match layout.device().read_json::<models::Device>().await {
             Ok(device) => device.name,
             Err(e) => {
@@ -62,6 +69,7 @@ pub async fn provision<HTTPClientT: http::ClientI>(
             version::VERSION,
         )
         .await?;
+        verify(http_client, layout, &device.id).await?;
         Ok(Outcome {
             already_provisioned: false,
             device_name: device.name,
@@ -83,7 +91,7 @@ async fn provision_with_backend<HTTPClientT: http::ClientI>(
     let payload = backend_client::ProvisionDeviceRequest {
         public_key_pem,
         agent_version: version::VERSION.to_string(),
-        name: device_name.unwrap_or_else(telemetry::SystemInfo::host_name),
+        name: device_name.unwrap_or_else(platform::host_name),
     };
     let params = http::devices::ProvisionParams {
         payload: &payload,
@@ -92,6 +100,48 @@ async fn provision_with_backend<HTTPClientT: http::ClientI>(
     Ok(http::devices::provision(http_client, params).await?)
 }
 
+/// Confirms the device is usable by exchanging its new key for a token, which must be
+/// for the device the backend just registered. The token is kept so the agent doesn't
+/// need to fetch one when it first starts.
+async fn verify<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    layout: &storage::Layout,
+    device_id: &str,
+) -> Result<(), ProvisionErr> {
+    let auth = layout.auth();
+    let private_key = auth.private_key().into();
+    storage::assert_activated(layout, &private_key).await?;
+
+    let token = authn::issue_token(http_client, &private_key, &auth.public_key()).await?;
+    let token_device_id = jwt::extract_device_id(&token.token)?;
+    if token_device_id != device_id {
+        return Err(ProvisionErr::VerificationErr(VerificationErr {
+            msg: format!(
+                "the backend issued a token for device '{token_device_id}' instead of '{device_id}'"
+            ),
+            trace: crate::trace!(),
+        }));
+    }
+    auth.token()
+        .write_json(&token, WriteOptions::OVERWRITE_ATOMIC)
+        .await?;
+    Ok(())
+}
+
+/// The provisioning token to activate with. It's given with `--token`, either as is or
+/// inside an activation URL, or else read from the environment. An activation URL's
+/// backend is used unless `--backend-host` is also given.
+pub fn resolve_token(args: &mut cli::ProvisionArgs) -> Result<String, ProvisionErr> {
+    let Some(value) = args.token.take() else {
+        return shared::read_token_from_env();
+    };
+    let activation = shared::parse_activation(&value)?;
+    if args.backend_host.is_none() {
+        args.backend_host = activation.backend_host;
+    }
+    Ok(activation.token)
+}
+
 pub fn determine_settings(args: &cli::ProvisionArgs) -> settings::Settings {
     shared::determine_settings(
         args.backend_host.as_deref(),
@@ -103,6 +153,39 @@ pub fn determine_settings(args: &cli::ProvisionArgs) -> settings::Settings {
 mod tests {
     use super::*;
 
+    mod resolve_token {
+        use super::*;
+
+        #[test]
+        fn activation_url_sets_the_backend() {
+            let mut args = cli::ProvisionArgs {
+                token: Some("https://app.example.com/activate?token=abc&backend_host=https://api.example.com".to_string()),
+                ..Default::default()
+            };
+
+            assert_eq!(resolve_token(&mut args).unwrap(), "abc");
+            assert_eq!(
+                args.backend_host.as_deref(),
+                Some("https://api.example.com")
+            );
+        }
+
+        #[test]
+        fn backend_host_flag_wins() {
+            let mut args = cli::ProvisionArgs {
+                backend_host: Some("https://flag.example.com".to_string()),
+                token: Some("https://app.example.com/activate?token=abc&backend_host=https://api.example.com".to_string()),
+                ..Default::default()
+            };
+
+            assert_eq!(resolve_token(&mut args).unwrap(), "abc");
+            assert_eq!(
+                args.backend_host.as_deref(),
+                Some("https://flag.example.com")
+            );
+        }
+    }
+
     mod determine_settings {
         use super::*;
 
@@ -0,0 +1,131 @@
+// Zero-touch provisioning for the manufacturing line: a seed file written onto the
+// boot media carries the provisioning token and initial settings. On first boot the
+// agent provisions itself from the seed, shreds it so the token doesn't outlive its
+// purpose and records where the device's identity came from.
+
+// standard crates
+use std::path::PathBuf;
+
+// internal crates
+use crate::filesys::{self, PathExt, WriteOptions};
+use crate::http;
+use crate::provisioning::{errors::*, provision};
+use crate::storage::{self, settings};
+use crate::trace;
+use crate::version;
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+pub const SEED_FILE: &str = "seed.json";
+
+#[derive(Deserialize)]
+pub struct Seed {
+    /// The provisioning token, as given to `activate` in `MIRU_PROVISIONING_TOKEN`.
+    pub token: String,
+    /// Defaults to the host name.
+    #[serde(default)]
+    pub device_name: Option<String>,
+    /// Written to the settings file on activation. Defaults to the default settings.
+    #[serde(default)]
+    pub settings: Option<settings::Settings>,
+    /// Free-form description of where the seed came from, e.g. the manufacturing
+    /// line or batch, kept in the provenance record.
+    #[serde(default)]
+    pub origin: Option<String>,
+}
+
+impl std::fmt::Debug for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Seed")
+            .field("token", &"<redacted>")
+            .field("device_name", &self.device_name)
+            .field("settings", &self.settings)
+            .field("origin", &self.origin)
+            .finish()
+    }
+}
+
+/// Where a device's identity came from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub seed_dir: PathBuf,
+    pub origin: Option<String>,
+    pub device_name: String,
+    pub provisioned_at: DateTime<Utc>,
+    pub agent_version: String,
+}
+
+/// Reads the seed from the seed directory, if there is one.
+pub async fn read(seed_dir: &filesys::Dir) -> Result<Option<Seed>, ProvisionErr> {
+    let file = seed_dir.file(SEED_FILE);
+    if !file.exists() {
+        return Ok(None);
+    }
+    let seed = file.read_json::<Seed>().await?;
+    if seed.token.trim().is_empty() {
+        return Err(ProvisionErr::InvalidSeedErr(InvalidSeedErr {
+            file,
+            msg: "the token is empty".to_string(),
+            trace: trace!(),
+        }));
+    }
+    Ok(Some(seed))
+}
+
+/// Provisions the device from the seed, then shreds the seed. A seed found on a
+/// device which is already activated is shredded without provisioning. If
+/// provisioning fails the seed is kept so that the next boot retries.
+pub async fn import<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    layout: &storage::Layout,
+    seed: Seed,
+) -> Result<provision::Outcome, ProvisionErr> {
+    let seed_dir = layout.seed_dir();
+    let settings = seed.settings.unwrap_or_default();
+    let outcome = provision::provision(
+        http_client,
+        layout,
+        &settings,
+        seed.token.trim(),
+        seed.device_name,
+    )
+    .await?;
+
+    if outcome.already_provisioned {
+        warn!(
+            "discarding the provisioning seed in {} since the device is already activated",
+            seed_dir.path().display()
+        );
+    } else {
+        let provenance = Provenance {
+            seed_dir: seed_dir.path().clone(),
+            origin: seed.origin,
+            device_name: outcome.device_name.clone(),
+            provisioned_at: Utc::now(),
+            agent_version: version::VERSION.to_string(),
+        };
+        layout
+            .provenance()
+            .write_json(&provenance, WriteOptions::OVERWRITE_ATOMIC)
+            .await?;
+    }
+
+    discard(&seed_dir).await?;
+    Ok(outcome)
+}
+
+/// Shreds every file in the seed directory and removes it.
+pub async fn discard(seed_dir: &filesys::Dir) -> Result<(), ProvisionErr> {
+    if !seed_dir.exists() {
+        return Ok(());
+    }
+    for file in seed_dir.files().await? {
+        file.shred().await?;
+    }
+    seed_dir.delete().await?;
+    Ok(())
+}
@@ -13,6 +13,55 @@ use tracing::{debug, error, info, warn};
 
 const TOKEN_ENV_VAR: &str = "MIRU_PROVISIONING_TOKEN";
 
+/// The query parameters of an activation URL.
+const TOKEN_PARAM: &str = "token";
+const BACKEND_HOST_PARAM: &str = "backend_host";
+
+/// A provisioning token given on the command line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Activation {
+    pub token: String,
+    pub backend_host: Option<String>,
+}
+
+/// Parses a provisioning token which is either the token itself or an activation URL
+/// carrying it, e.g. `https://app.mirurobotics.com/activate?token=...`. The URL may
+/// also name the backend to activate against in a `backend_host` parameter.
+pub fn parse_activation(value: &str) -> Result<Activation, ProvisionErr> {
+    let value = value.trim();
+    let url = match url::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        // anything that isn't a web URL is the token itself
+        _ => {
+            return Ok(Activation {
+                token: value.to_string(),
+                backend_host: None,
+            })
+        }
+    };
+
+    let mut activation = Activation::default();
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            TOKEN_PARAM => activation.token = value.into_owned(),
+            BACKEND_HOST_PARAM if !value.is_empty() => {
+                activation.backend_host = Some(value.into_owned())
+            }
+            _ => {}
+        }
+    }
+    if activation.token.is_empty() {
+        // the URL isn't echoed since it may hold other secrets
+        return Err(ProvisionErr::InvalidActivationUrlErr(
+            InvalidActivationUrlErr {
+                msg: format!("the URL has no '{TOKEN_PARAM}' parameter"),
+                trace: crate::trace!(),
+            },
+        ));
+    }
+    Ok(activation)
+}
+
 pub fn read_token_from_env() -> Result<String, ProvisionErr> {
     if let Ok(token) = env::var(TOKEN_ENV_VAR) {
         if !token.is_empty() {
@@ -60,6 +109,47 @@ mod tests {
             .expect("env lock should not be poisoned")
     }
 
+    mod parse_activation {
+        use super::*;
+
+        #[test]
+        fn bare_token() {
+            let activation = parse_activation(" eyJhbGciOi.payload.sig\n").unwrap();
+            assert_eq!(activation.token, "eyJhbGciOi.payload.sig");
+            assert_eq!(activation.backend_host, None);
+        }
+
+        #[test]
+        fn activation_url() {
+            let activation = parse_activation(
+                "https://app.mirurobotics.com/activate?token=abc%2B123&backend_host=https%3A%2F%2Fapi.example.com",
+            )
+            .unwrap();
+            assert_eq!(activation.token, "abc+123");
+            assert_eq!(
+                activation.backend_host.as_deref(),
+                Some("https://api.example.com")
+            );
+        }
+
+        #[test]
+        fn activation_url_without_backend() {
+            let activation =
+                parse_activation("https://app.mirurobotics.com/activate?token=abc&foo=bar")
+                    .unwrap();
+            assert_eq!(activation.token, "abc");
+            assert_eq!(activation.backend_host, None);
+        }
+
+        #[test]
+        fn activation_url_without_token() {
+            let err =
+                parse_activation("https://app.mirurobotics.com/activate?secret=abc").unwrap_err();
+            assert!(matches!(err, ProvisionErr::InvalidActivationUrlErr(_)));
+            assert!(!err.to_string().contains("abc"));
+        }
+    }
+
     mod read_token_from_env {
         use super::*;
 
@@ -1,11 +1,14 @@
 // internal crates
+use crate::app::startup;
 use crate::authn;
 use crate::cache;
 use crate::crypt;
+use crate::deploy;
 use crate::errors::Trace;
 use crate::events;
 use crate::filesys;
 use crate::http;
+use crate::logs;
 use crate::services;
 use crate::storage::StorageErr;
 use crate::sync;
@@ -25,6 +28,60 @@ impl std::fmt::Display for MissingDeviceIDErr {
 
 impl crate::errors::Error for MissingDeviceIDErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("the agent is overloaded ({reason}), retry in {retry_after_secs}s")]
+pub struct OverloadedErr {
+    pub reason: String,
+    pub retry_after_secs: u64,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for OverloadedErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::Overloaded
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::SERVICE_UNAVAILABLE
+    }
+
+    fn params(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "retry_after_secs": self.retry_after_secs }))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("no deployment directory is configured")]
+pub struct DeploymentDirDisabledErr {
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for DeploymentDirDisabledErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::ResourceNotFound
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::NOT_FOUND
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("deployments can't be paused on this agent")]
+pub struct PauseDisabledErr {
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for PauseDisabledErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::ResourceNotFound
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::NOT_FOUND
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("shutdown manager was provided the same argument ({arg_name}) twice")]
 pub struct ShutdownMngrDuplicateArgErr {
@@ -34,6 +91,22 @@ pub struct ShutdownMngrDuplicateArgErr {
 
 impl crate::errors::Error for ShutdownMngrDuplicateArgErr {}
 
+#[derive(Debug, thiserror::Error)]
+pub struct StartupFailed {
+    /// The components which didn't become ready, in the order they were started.
+    pub failures: Vec<startup::Outcome>,
+    pub trace: Box<Trace>,
+}
+
+impl std::fmt::Display for StartupFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let failures: Vec<String> = self.failures.iter().map(|o| o.to_string()).collect();
+        write!(f, "failed to start the agent: {}", failures.join("; "))
+    }
+}
+
+impl crate::errors::Error for StartupFailed {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("failed to bind unix socket '{socket_file}': {source}")]
 pub struct BindUnixSocketErr {
@@ -44,6 +117,26 @@ pub struct BindUnixSocketErr {
 
 impl crate::errors::Error for BindUnixSocketErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("failed to bind the metrics listener to {addr}: {source}")]
+pub struct BindMetricsListenerErr {
+    pub addr: String,
+    pub source: std::io::Error,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for BindMetricsListenerErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("failed to bind the cell proxy to {addr}: {source}")]
+pub struct BindCellProxyErr {
+    pub addr: String,
+    pub source: std::io::Error,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for BindCellProxyErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("failed to run axum server: {source}")]
 pub struct RunAxumServerErr {
@@ -72,6 +165,15 @@ pub struct SendShutdownSignalErr {
 
 impl crate::errors::Error for SendShutdownSignalErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("the agent panicked: {msg}")]
+pub struct PanickedErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for PanickedErr {}
+
 #[derive(Debug, thiserror::Error)]
 #[error("timestamp conversion error: {msg}")]
 pub struct TimestampConversionErr {
@@ -90,6 +192,16 @@ pub enum ServerErr {
     TimestampConversionErr(TimestampConversionErr),
     #[error(transparent)]
     ShutdownMngrDuplicateArgErr(ShutdownMngrDuplicateArgErr),
+    #[error(transparent)]
+    StartupFailed(StartupFailed),
+    #[error(transparent)]
+    OverloadedErr(OverloadedErr),
+    #[error(transparent)]
+    DeploymentDirDisabledErr(DeploymentDirDisabledErr),
+    #[error(transparent)]
+    PauseDisabledErr(PauseDisabledErr),
+    #[error(transparent)]
+    PanickedErr(PanickedErr),
 
     // internal crate errors
     #[error(transparent)]
@@ -101,10 +213,14 @@ pub enum ServerErr {
     #[error(transparent)]
     CryptErr(crypt::CryptErr),
     #[error(transparent)]
+    DeployErr(Box<deploy::DeployErr>),
+    #[error(transparent)]
     FileSysErr(filesys::FileSysErr),
     #[error(transparent)]
     HTTPErr(http::HTTPErr),
     #[error(transparent)]
+    LogsErr(logs::LogsErr),
+    #[error(transparent)]
     ServiceErr(services::ServiceErr),
     #[error(transparent)]
     StorageErr(StorageErr),
@@ -115,6 +231,10 @@ pub enum ServerErr {
     #[error(transparent)]
     BindUnixSocketErr(BindUnixSocketErr),
     #[error(transparent)]
+    BindMetricsListenerErr(BindMetricsListenerErr),
+    #[error(transparent)]
+    BindCellProxyErr(BindCellProxyErr),
+    #[error(transparent)]
     RunAxumServerErr(RunAxumServerErr),
     #[error(transparent)]
     SendShutdownSignalErr(SendShutdownSignalErr),
@@ -146,6 +266,12 @@ impl From<crypt::CryptErr> for ServerErr {
     }
 }
 
+impl From<deploy::DeployErr> for ServerErr {
+    fn from(e: deploy::DeployErr) -> Self {
+        Self::DeployErr(Box::new(e))
+    }
+}
+
 impl From<filesys::FileSysErr> for ServerErr {
     fn from(e: filesys::FileSysErr) -> Self {
         Self::FileSysErr(e)
@@ -158,6 +284,12 @@ impl From<http::HTTPErr> for ServerErr {
     }
 }
 
+impl From<logs::LogsErr> for ServerErr {
+    fn from(e: logs::LogsErr) -> Self {
+        Self::LogsErr(e)
+    }
+}
+
 impl From<services::ServiceErr> for ServerErr {
     fn from(e: services::ServiceErr) -> Self {
         Self::ServiceErr(e)
@@ -180,16 +312,25 @@ crate::impl_error!(ServerErr {
     MissingDeviceIDErr,
     TimestampConversionErr,
     ShutdownMngrDuplicateArgErr,
+    StartupFailed,
+    OverloadedErr,
+    DeploymentDirDisabledErr,
+    PauseDisabledErr,
+    PanickedErr,
     EventsErr,
     AuthnErr,
     CacheErr,
     CryptErr,
+    DeployErr,
     FileSysErr,
     HTTPErr,
+    LogsErr,
     ServiceErr,
     StorageErr,
     SyncErr,
     BindUnixSocketErr,
+    BindMetricsListenerErr,
+    BindCellProxyErr,
     RunAxumServerErr,
     SendShutdownSignalErr,
     JoinHandleErr,
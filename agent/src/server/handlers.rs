@@ -3,34 +3,40 @@ use std::future::Future;
 use std::sync::Arc;
 
 // internal crates
+use crate::audit;
+use crate::deploy::{pause, versions};
 use crate::errors::Error;
-use crate::server::{errors::*, state::State};
+use crate::logs::{LogLevel, LogsErr};
+use crate::metrics;
+use crate::models;
+use crate::server::{errors::*, health, openapi, peer::Peer, response, state::State};
 use crate::services::{
-    deployment as dpl_svc, device as dvc_svc, git_commit as git_cmt_svc, release as rls_svc,
-    HttpBackend,
+    config_instance as cfg_inst_svc, deployment as dpl_svc, device as dvc_svc,
+    git_commit as git_cmt_svc, release as rls_svc, HttpBackend,
 };
+use crate::sync::{history, SyncerExt};
+use crate::trace;
 use crate::version;
 use device_api::models as device_server;
 
 // external crates
 use axum::{
-    extract::{Path, State as AxumState},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Query, State as AxumState},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tracing::error;
+use tracing::{error, info, warn};
 
 // ================================= AGENT INFO ==================================== //
-pub async fn health() -> impl IntoResponse {
-    (
-        StatusCode::OK,
-        Json(device_server::HealthResponse {
-            status: "ok".to_string(),
-        }),
-    )
+pub async fn health(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(health::report(&state).await))
+}
+
+pub async fn openapi() -> impl IntoResponse {
+    (StatusCode::OK, Json(openapi::document()))
 }
 
 pub async fn version() -> impl IntoResponse {
@@ -49,6 +55,33 @@ pub async fn version() -> impl IntoResponse {
     )
 }
 
+fn log_level_response(state: &State) -> device_server::LogLevelResponse {
+    device_server::LogLevelResponse {
+        level: (&state.log_level.level()).into(),
+        env_filter_locked: state.log_level.env_filter_locked(),
+    }
+}
+
+pub async fn get_log_level(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(log_level_response(&state)))
+}
+
+pub async fn put_log_level(
+    AxumState(state): AxumState<Arc<State>>,
+    Json(body): Json<device_server::LogLevelBody>,
+) -> impl IntoResponse {
+    handle(
+        async move {
+            let level = body.level.parse::<LogLevel>()?;
+            state.log_level.set(level.clone())?;
+            info!("log level changed to '{level}'");
+            Ok::<_, LogsErr>(log_level_response(&state))
+        },
+        "Error setting log level",
+    )
+    .await
+}
+
 // ================================= DEVICE ======================================== //
 pub async fn get_device(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
     handle(
@@ -69,7 +102,84 @@ pub async fn sync_device(AxumState(state): AxumState<Arc<State>>) -> impl IntoRe
     .await
 }
 
+/// The summary covers every recorded sync, the records only those matching the query.
+pub async fn get_sync_history(
+    AxumState(state): AxumState<Arc<State>>,
+    Query(query): Query<history::Query>,
+) -> impl IntoResponse {
+    handle(
+        async move {
+            let records = state.syncer.get_sync_history().await?;
+            let summary = history::summarize(&records);
+            let records = query.apply(&records);
+            Ok::<_, ServerErr>(json!({ "summary": summary, "records": records }))
+        },
+        "Error getting the sync history",
+    )
+    .await
+}
+
+pub async fn get_sync_events(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    handle(
+        async move { state.syncer.get_sync_events().await },
+        "Error getting the sync events",
+    )
+    .await
+}
+
+pub async fn get_sync_plan(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    handle(
+        async move { state.syncer.plan().await },
+        "Error planning the next sync",
+    )
+    .await
+}
+
+pub async fn rotate_device_key(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    handle(
+        async move {
+            let fingerprint = state.token_mngr.rotate_key().await?;
+            Ok::<_, ServerErr>(device_server::KeyRotation { fingerprint })
+        },
+        "Error rotating the device key",
+    )
+    .await
+}
+
 // ================================ DEPLOYMENTS ==================================== //
+/// Newest first, a page at a time; `next_cursor` continues from the last deployment.
+pub async fn list_deployments(
+    AxumState(state): AxumState<Arc<State>>,
+    Query(query): Query<dpl_svc::ListQuery>,
+) -> impl IntoResponse {
+    handle(
+        async move {
+            let page = dpl_svc::list(
+                &state.storage.deployments,
+                &state.storage.cfg_insts.meta,
+                &query,
+            )
+            .await?;
+            let filepaths =
+                dpl_svc::filepaths(&state.storage.cfg_insts.meta, &page.deployments).await?;
+            let data = page
+                .deployments
+                .iter()
+                .zip(filepaths)
+                .map(|(dpl, filepaths)| response::deployment_state(dpl, filepaths))
+                .collect();
+            Ok::<_, ServerErr>(device_server::DeploymentList {
+                object: device_server::deployment_list::Object::List,
+                data,
+                has_more: page.has_more(),
+                next_cursor: page.next_cursor,
+            })
+        },
+        "Error listing deployments",
+    )
+    .await
+}
+
 pub async fn get_deployment(
     AxumState(state): AxumState<Arc<State>>,
     Path(deployment_id): Path<String>,
@@ -78,7 +188,7 @@ pub async fn get_deployment(
         async {
             let backend = HttpBackend::new(state.http_client.as_ref(), state.token_mngr.as_ref());
             let dpl = dpl_svc::get(&state.storage.deployments, &backend, deployment_id).await?;
-            Ok::<_, ServerErr>(device_server::Deployment::from(&dpl))
+            deployment_state(&state, dpl).await
         },
         "Error getting deployment",
     )
@@ -89,13 +199,193 @@ pub async fn get_current_deployment(AxumState(state): AxumState<Arc<State>>) ->
     handle(
         async {
             let dpl = dpl_svc::get_current(&state.storage.deployments).await?;
-            Ok::<_, ServerErr>(device_server::Deployment::from(&dpl))
+            deployment_state(&state, dpl).await
         },
         "Error getting current deployment",
     )
     .await
 }
 
+async fn deployment_state(
+    state: &State,
+    dpl: models::Deployment,
+) -> Result<device_server::DeploymentState, ServerErr> {
+    let filepaths = dpl_svc::filepaths(&state.storage.cfg_insts.meta, std::slice::from_ref(&dpl))
+        .await?
+        .pop()
+        .unwrap_or_default();
+    Ok(response::deployment_state(&dpl, filepaths))
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    /// Whether to include a line diff of each changed file.
+    #[serde(default)]
+    pub text: bool,
+}
+
+pub async fn get_deployment_diff(
+    AxumState(state): AxumState<Arc<State>>,
+    Path(deployment_id): Path<String>,
+    Query(params): Query<DiffQuery>,
+    Peer(client): Peer,
+) -> impl IntoResponse {
+    handle(
+        async {
+            let backend = HttpBackend::new(state.http_client.as_ref(), state.token_mngr.as_ref());
+            let diff = dpl_svc::diff(
+                &state.storage.deployments,
+                state.storage.cfg_insts.as_ref(),
+                &backend,
+                deployment_id,
+                params.text,
+            )
+            .await?;
+            // text diffs expose the content of the config instances
+            if params.text {
+                for file in &diff.files {
+                    let ids = [&file.old_config_instance_id, &file.new_config_instance_id];
+                    for id in ids.into_iter().flatten() {
+                        state
+                            .storage
+                            .config_access
+                            .record(&client, id, &file.filepath)
+                            .await;
+                    }
+                }
+            }
+            Ok::<_, ServerErr>(diff)
+        },
+        "Error getting deployment diff",
+    )
+    .await
+}
+
+fn pause_switch(state: &State) -> Result<&pause::Switch, ServerErr> {
+    state
+        .pause
+        .as_ref()
+        .ok_or_else(|| ServerErr::PauseDisabledErr(PauseDisabledErr { trace: trace!() }))
+}
+
+pub async fn get_deployments_pause(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    handle(
+        async move {
+            let status = pause_switch(&state)?.status().await;
+            Ok::<_, ServerErr>(device_server::DeploymentsPause::from(&status))
+        },
+        "Error getting whether deployments are paused",
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PauseQuery {
+    /// Why deployments are paused, shown to whoever checks on them.
+    pub reason: Option<String>,
+}
+
+pub async fn pause_deployments(
+    AxumState(state): AxumState<Arc<State>>,
+    Query(query): Query<PauseQuery>,
+) -> impl IntoResponse {
+    handle(
+        async move {
+            let status = pause_switch(&state)?
+                .pause(query.reason, pause::Origin::Socket)
+                .await?;
+            Ok::<_, ServerErr>(device_server::DeploymentsPause::from(&status))
+        },
+        "Error pausing deployments",
+    )
+    .await
+}
+
+/// Resumes deployments and syncs so that what was held while they were paused is
+/// applied right away.
+pub async fn resume_deployments(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    handle(
+        async move {
+            let status = pause_switch(&state)?.resume(pause::Origin::Socket).await?;
+            if let Err(e) = state.syncer.sync().await {
+                warn!("Failed to sync after resuming deployments: {e}");
+            }
+            Ok::<_, ServerErr>(device_server::DeploymentsPause::from(&status))
+        },
+        "Error resuming deployments",
+    )
+    .await
+}
+
+// ============================== CONFIG INSTANCES ================================= //
+pub async fn get_config_instance(
+    AxumState(state): AxumState<Arc<State>>,
+    Path(config_instance_id): Path<String>,
+    Peer(client): Peer,
+) -> impl IntoResponse {
+    handle(
+        async {
+            let cfg_inst =
+                cfg_inst_svc::get(state.storage.cfg_insts.as_ref(), config_instance_id).await?;
+            state
+                .storage
+                .config_access
+                .record(&client, &cfg_inst.id, &cfg_inst.filepath)
+                .await;
+            Ok::<_, ServerErr>(cfg_inst)
+        },
+        "Error getting config instance",
+    )
+    .await
+}
+
+/// Serves the latest deployed config instance of a config type. Responds 304 without
+/// a body when the request's `If-None-Match` holds the instance's entity tag.
+pub async fn get_config_type_content(
+    AxumState(state): AxumState<Arc<State>>,
+    Path(config_type_name): Path<String>,
+    Peer(client): Peer,
+    headers: HeaderMap,
+) -> Response {
+    let cfg_inst = match cfg_inst_svc::get_latest(
+        &state.storage.deployments,
+        state.storage.cfg_insts.as_ref(),
+        config_type_name,
+    )
+    .await
+    {
+        Ok(cfg_inst) => cfg_inst,
+        Err(e) => {
+            let e = ServerErr::from(e);
+            error!("Error getting config type content: {e:?}");
+            return (e.http_status(), Json(json!(to_error_response(e)))).into_response();
+        }
+    };
+    state
+        .storage
+        .config_access
+        .record(&client, &cfg_inst.id, &cfg_inst.filepath)
+        .await;
+
+    let etag = cfg_inst.etag();
+    if if_none_match(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (StatusCode::OK, [(header::ETAG, etag)], Json(cfg_inst)).into_response()
+}
+
+/// Whether the `If-None-Match` header matches the entity tag, comparing weakly as
+/// RFC 9110 requires for `If-None-Match`.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || strip_weak(tag) == strip_weak(etag))
+}
+
 // ================================= RELEASES ====================================== //
 pub async fn get_release(
     AxumState(state): AxumState<Arc<State>>,
@@ -145,6 +435,99 @@ pub async fn get_git_commit(
     .await
 }
 
+// ============================== DEPLOYMENT DIR =================================== //
+fn versions(state: &State) -> Result<&versions::Versions, ServerErr> {
+    state.versions.as_ref().ok_or_else(|| {
+        ServerErr::DeploymentDirDisabledErr(DeploymentDirDisabledErr { trace: trace!() })
+    })
+}
+
+/// The releases in the deployment directory, oldest first.
+pub async fn list_releases(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    handle(
+        async move {
+            let releases = versions(&state)?.list().await?;
+            Ok::<_, ServerErr>(device_server::ReleaseList {
+                releases: releases.iter().map(Into::into).collect(),
+            })
+        },
+        "Error listing the deployment directory's releases",
+    )
+    .await
+}
+
+/// The release to roll back to, the one live before the current one if unset.
+#[derive(Debug, Deserialize)]
+pub struct RollbackQuery {
+    pub release: Option<u64>,
+}
+
+pub async fn rollback(
+    AxumState(state): AxumState<Arc<State>>,
+    Query(query): Query<RollbackQuery>,
+) -> impl IntoResponse {
+    handle(
+        async move {
+            let number = versions(&state)?.rollback(query.release).await?;
+            Ok::<_, ServerErr>(device_server::Rollback {
+                release: number.min(i64::MAX as u64) as i64,
+            })
+        },
+        "Error rolling back the deployment directory",
+    )
+    .await
+}
+
+// ================================== METRICS ====================================== //
+pub async fn metrics() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+        metrics::global().render(),
+    )
+}
+
+// ================================== STORAGE ====================================== //
+pub async fn get_storage_wear(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(json!(state.storage.wear.report(state.low_wear_mode))),
+    )
+}
+
+pub async fn get_storage_sweep(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(json!(state.storage.sweep)))
+}
+
+// =================================== AUDIT ======================================= //
+pub async fn get_config_access(
+    AxumState(state): AxumState<Arc<State>>,
+    Query(query): Query<audit::Query>,
+) -> impl IntoResponse {
+    let accesses = state.storage.config_access.query(&query).await;
+    (StatusCode::OK, Json(json!({ "accesses": accesses })))
+}
+
+/// The anchor to verify the config access log against, as last sent to the backend.
+/// The log is only verified against an anchor if both are given.
+#[derive(Deserialize)]
+pub struct VerifyQuery {
+    pub seq: Option<u64>,
+    pub hash: Option<String>,
+}
+
+pub async fn verify_config_access(
+    AxumState(state): AxumState<Arc<State>>,
+    Query(query): Query<VerifyQuery>,
+) -> impl IntoResponse {
+    let anchor = query
+        .seq
+        .zip(query.hash)
+        .map(|(seq, hash)| audit::Anchor { seq, hash });
+    let verification = state.storage.config_access.verify(anchor.as_ref()).await;
+    (StatusCode::OK, Json(json!(verification)))
+}
+
 // ================================ UTILITIES ====================================== //
 async fn handle<F, T, E>(service: F, err_msg: &str) -> (StatusCode, Json<Value>)
 where
@@ -162,7 +545,7 @@ where
     }
 }
 
-fn to_error_response(e: impl Error) -> device_server::ErrorResponse {
+pub(crate) fn to_error_response(e: impl Error) -> device_server::ErrorResponse {
     let params = e
         .params()
         .and_then(|v| serde_json::from_value(v).ok())
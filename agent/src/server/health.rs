@@ -0,0 +1,332 @@
+// Per-subsystem health reported by the health endpoint. The agent is alive as long as
+// it answers at all; a degraded subsystem means it is running but not doing its job,
+// e.g. it can't reach the broker, its token has expired, its disk is nearly full or
+// its clock is far off the backend's.
+
+// standard crates
+use std::sync::Arc;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::clock;
+use crate::config::{compat, Deprecation};
+use crate::filesys;
+use crate::models::DeviceStatus;
+use crate::server::state::State;
+use crate::sync::{history, SyncerExt};
+use crate::workers::poller;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The disk holding the data directory is degraded below this share of free space.
+pub const MIN_FREE_DISK_PERCENT: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Degraded,
+    /// The subsystem isn't running, e.g. the mqtt worker in safe mode.
+    Disabled,
+    /// The subsystem's state couldn't be read.
+    Unknown,
+}
+
+/// What the health endpoint needs to know beyond the server's state: which workers
+/// were started and where the agent keeps its data.
+#[derive(Clone, Debug)]
+pub struct Probes {
+    pub mqtt_enabled: bool,
+    pub poller_enabled: bool,
+    pub poll_interval_secs: i64,
+    pub poller: Arc<poller::Metrics>,
+    pub data_dir: Option<filesys::Dir>,
+}
+
+impl Default for Probes {
+    fn default() -> Self {
+        Self {
+            mqtt_enabled: false,
+            poller_enabled: false,
+            poll_interval_secs: poller::Options::default().poll_interval_secs,
+            poller: Arc::new(poller::Metrics::default()),
+            data_dir: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    /// "ok" or "safe_mode"; the agent is alive either way.
+    pub status: String,
+    /// Set if no subsystem is degraded.
+    pub healthy: bool,
+    pub subsystems: Subsystems,
+    /// The deprecated settings fields, flags and commands the agent was given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deprecations: Vec<Deprecation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Subsystems {
+    pub mqtt: Mqtt,
+    pub syncer: Syncer,
+    pub poller: Poller,
+    pub token: Token,
+    pub disk: Disk,
+    pub clock: Clock,
+}
+
+impl Subsystems {
+    fn statuses(&self) -> [Status; 6] {
+        [
+            self.mqtt.status,
+            self.syncer.status,
+            self.poller.status,
+            self.token.status,
+            self.disk.status,
+            self.clock.status,
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mqtt {
+    pub status: Status,
+    pub connected: Option<bool>,
+    pub last_connected_at: Option<DateTime<Utc>>,
+    pub last_disconnected_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Syncer {
+    pub status: Status,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_attempted_sync_at: Option<DateTime<Utc>>,
+    /// Consecutive failed syncs, not counting network connection errors.
+    pub err_streak: Option<u32>,
+    pub cooldown_ends_at: Option<DateTime<Utc>>,
+    /// Durations and outcomes of the recent syncs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<history::Summary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Poller {
+    pub status: Status,
+    pub runs: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Token {
+    pub status: Status,
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Disk {
+    pub status: Status,
+    pub mount_point: Option<String>,
+    pub available_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The device's clock compared to the backend's, see [crate::clock]. Timestamps are
+/// corrected by the skew either way; a large one is degraded since the device's own
+/// time, e.g. in its logs, is off by it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    pub status: Status,
+    /// The backend's time minus the device's.
+    pub skew_secs: i64,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+pub async fn report(state: &State) -> Report {
+    let subsystems = Subsystems {
+        mqtt: mqtt(state).await,
+        syncer: syncer(state).await,
+        poller: poller(&state.health),
+        token: token(state).await,
+        disk: disk(&state.health).await,
+        clock: clock(clock::global().status()),
+    };
+    // the agent is still serving requests in safe mode so it isn't reported as an
+    // error, but it is distinguished so operators can tell deployments are paused
+    let status = if state.safe_mode { "safe_mode" } else { "ok" };
+    Report {
+        status: status.to_string(),
+        healthy: !subsystems.statuses().contains(&Status::Degraded),
+        subsystems,
+        deprecations: compat::recorded(),
+    }
+}
+
+fn ok_or_degraded(degraded: bool) -> Status {
+    if degraded {
+        Status::Degraded
+    } else {
+        Status::Ok
+    }
+}
+
+async fn mqtt(state: &State) -> Mqtt {
+    let mut mqtt = Mqtt {
+        status: Status::Disabled,
+        connected: None,
+        last_connected_at: None,
+        last_disconnected_at: None,
+        error: None,
+    };
+    if !state.health.mqtt_enabled {
+        return mqtt;
+    }
+    match state.storage.device.read().await {
+        Ok(device) => {
+            let connected = device.status == DeviceStatus::Online;
+            mqtt.status = ok_or_degraded(!connected);
+            mqtt.connected = Some(connected);
+            mqtt.last_connected_at = Some(device.last_connected_at);
+            mqtt.last_disconnected_at = Some(device.last_disconnected_at);
+        }
+        Err(e) => {
+            mqtt.status = Status::Unknown;
+            mqtt.error = Some(e.to_string());
+        }
+    }
+    mqtt
+}
+
+async fn syncer(state: &State) -> Syncer {
+    let history = state
+        .syncer
+        .get_sync_history()
+        .await
+        .ok()
+        .map(|records| history::summarize(&records));
+    match state.syncer.get_sync_state().await {
+        Ok(sync_state) => Syncer {
+            status: ok_or_degraded(sync_state.err_streak > 0),
+            last_synced_at: Some(sync_state.last_synced_at),
+            last_attempted_sync_at: Some(sync_state.last_attempted_sync_at),
+            err_streak: Some(sync_state.err_streak),
+            cooldown_ends_at: Some(sync_state.cooldown_ends_at),
+            history,
+            error: None,
+        },
+        Err(e) => Syncer {
+            status: Status::Unknown,
+            last_synced_at: None,
+            last_attempted_sync_at: None,
+            err_streak: None,
+            cooldown_ends_at: None,
+            history,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+fn poller(probes: &Probes) -> Poller {
+    let snapshot = probes.poller.snapshot();
+    let status = if !probes.poller_enabled {
+        Status::Disabled
+    } else {
+        // the poller wakes up at least once per interval, so give it two before
+        // reporting it as stuck
+        let stale_after = TimeDelta::seconds(probes.poll_interval_secs.saturating_mul(2));
+        ok_or_degraded(match snapshot.last_run_at {
+            Some(last_run_at) => Utc::now() - last_run_at > stale_after,
+            None => true,
+        })
+    };
+    Poller {
+        status,
+        runs: snapshot.runs,
+        last_run_at: snapshot.last_run_at,
+    }
+}
+
+async fn token(state: &State) -> Token {
+    match state.token_mngr.get_token().await {
+        Ok(token) => Token {
+            status: ok_or_degraded(token.is_expired()),
+            expires_at: Some(token.expires_at),
+            error: None,
+        },
+        Err(e) => Token {
+            status: Status::Unknown,
+            expires_at: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+pub fn clock(status: clock::Status) -> Clock {
+    Clock {
+        status: match status.checked_at {
+            Some(_) => ok_or_degraded(status.is_large()),
+            None => Status::Unknown,
+        },
+        skew_secs: status.skew.num_seconds(),
+        checked_at: status.checked_at,
+    }
+}
+
+async fn disk(probes: &Probes) -> Disk {
+    let mut disk = Disk {
+        status: Status::Unknown,
+        mount_point: None,
+        available_bytes: None,
+        total_bytes: None,
+        error: None,
+    };
+    let Some(data_dir) = &probes.data_dir else {
+        disk.error = Some("the data directory is unknown".to_string());
+        return disk;
+    };
+
+    #[cfg(feature = "telemetry")]
+    {
+        use crate::filesys::PathExt;
+
+        let path = data_dir.path().clone();
+        let space = tokio::task::spawn_blocking(move || crate::telemetry::disk_space(&path))
+            .await
+            .ok()
+            .flatten();
+        match space {
+            Some(space) => {
+                disk.status = ok_or_degraded(
+                    space.available_bytes.saturating_mul(100)
+                        < space.total_bytes.saturating_mul(MIN_FREE_DISK_PERCENT),
+                );
+                disk.mount_point = Some(space.mount_point);
+                disk.available_bytes = Some(space.available_bytes);
+                disk.total_bytes = Some(space.total_bytes);
+            }
+            None => {
+                disk.error = Some(format!(
+                    "no disk found for the data directory {}",
+                    data_dir.path().display()
+                ));
+            }
+        }
+    }
+    #[cfg(not(feature = "telemetry"))]
+    {
+        let _ = data_dir;
+        disk.error = Some("the agent was built without disk telemetry".to_string());
+    }
+    disk
+}
@@ -1,10 +1,27 @@
+// The errors are the agent's top-level error type so they are built without the
+// server feature as well.
 pub mod errors;
+#[cfg(feature = "server")]
 pub mod handlers;
+#[cfg(feature = "server")]
+pub mod health;
+#[cfg(feature = "server")]
+pub mod openapi;
+#[cfg(feature = "server")]
+pub mod peer;
+#[cfg(feature = "server")]
 pub mod response;
+#[cfg(feature = "server")]
 pub mod serve;
+#[cfg(feature = "server")]
+pub mod shed;
+#[cfg(feature = "server")]
 pub mod sse;
+#[cfg(feature = "server")]
 pub mod state;
 
 pub use self::errors::ServerErr;
+#[cfg(feature = "server")]
 pub use self::serve::Options;
+#[cfg(feature = "server")]
 pub use self::state::State;
@@ -0,0 +1,132 @@
+// The OpenAPI document describing the socket API, served so integrators can generate
+// clients for their language. It's the device API spec (api/specs/device) the
+// device-api models are generated from, so every route in `serve::routes` is
+// described there and [operations] lists them for the tests to check against the
+// router.
+
+// standard crates
+use std::sync::LazyLock;
+
+// internal crates
+use crate::version;
+
+// external crates
+use serde_json::{json, Value};
+
+const SPEC: &str = include_str!("../../../api/specs/device/v02.yaml");
+
+static DOCUMENT: LazyLock<Value> = LazyLock::new(|| {
+    // the spec is checked in with the agent and parsed by the tests, so a spec
+    // which doesn't parse can't ship
+    let mut document: Value = serde_yaml::from_str(SPEC).expect("the device API spec parses");
+    document["info"]["x-agent-version"] = json!(version::VERSION);
+    document
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "get",
+            Self::Post => "post",
+            Self::Put => "put",
+        }
+    }
+
+    fn parse(method: &str) -> Option<Self> {
+        match method {
+            "get" => Some(Self::Get),
+            "post" => Some(Self::Post),
+            "put" => Some(Self::Put),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    /// Where the parameter is given: path, query or header.
+    pub location: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    pub method: Method,
+    /// The path as written in the spec, below its server's base path.
+    pub path: String,
+    /// The path as routed, with the server's base path prefixed.
+    pub route: String,
+    pub operation_id: String,
+    pub params: Vec<Param>,
+}
+
+/// The OpenAPI document for the socket API.
+pub fn document() -> Value {
+    DOCUMENT.clone()
+}
+
+/// Every operation the document describes.
+pub fn operations() -> Vec<Operation> {
+    let document = &*DOCUMENT;
+    let default_base = base_path(&document["servers"]);
+    let mut operations = Vec::new();
+    let Some(paths) = document["paths"].as_object() else {
+        return operations;
+    };
+    for (path, item) in paths {
+        let base = item
+            .get("servers")
+            .map(base_path)
+            .unwrap_or_else(|| default_base.clone());
+        let Some(item) = item.as_object() else {
+            continue;
+        };
+        for (method, operation) in item {
+            let Some(method) = Method::parse(method) else {
+                continue;
+            };
+            let params = operation["parameters"]
+                .as_array()
+                .map(|params| params.iter().map(|p| param(document, p)).collect())
+                .unwrap_or_default();
+            operations.push(Operation {
+                method,
+                path: path.clone(),
+                route: format!("{base}{path}"),
+                operation_id: operation["operationId"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                params,
+            });
+        }
+    }
+    operations
+}
+
+/// The path of the first server's url, which the agent serves on localhost.
+fn base_path(servers: &Value) -> String {
+    let url = servers[0]["url"].as_str().unwrap_or_default();
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    path.find('/').map_or("", |i| &path[i..]).to_string()
+}
+
+fn param(document: &Value, param: &Value) -> Param {
+    let param = match param["$ref"].as_str() {
+        Some(reference) => document
+            .pointer(reference.trim_start_matches('#'))
+            .unwrap_or(&Value::Null),
+        None => param,
+    };
+    Param {
+        name: param["name"].as_str().unwrap_or_default().to_string(),
+        location: param["in"].as_str().unwrap_or_default().to_string(),
+    }
+}
@@ -0,0 +1,63 @@
+// standard crates
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+// internal crates
+use crate::audit;
+
+// external crates
+use axum::{
+    extract::{connect_info::Connected, ConnectInfo, FromRequestParts},
+    http::request::Parts,
+    serve::IncomingStream,
+};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+/// The local process on the other end of a connection to the server. Requests routed
+/// without connection info (e.g. in tests) come from an unknown peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Peer(pub audit::Client);
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, UnixListener>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, UnixListener>) -> Self {
+        match stream.io().peer_cred() {
+            Ok(cred) => Peer(audit::Client {
+                uid: Some(cred.uid()),
+                gid: Some(cred.gid()),
+                pid: cred.pid(),
+                addr: None,
+            }),
+            Err(_) => Peer::default(),
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Peer::from(*stream.remote_addr())
+    }
+}
+
+impl From<SocketAddr> for Peer {
+    fn from(addr: SocketAddr) -> Self {
+        Peer(audit::Client {
+            addr: Some(addr.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Peer {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ConnectInfo<Peer>>()
+            .map(|ConnectInfo(peer)| peer.clone())
+            .unwrap_or_default())
+    }
+}
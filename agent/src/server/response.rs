@@ -1,8 +1,13 @@
 // internal crates
+use crate::deploy::{pause, versions};
 use crate::events;
+use crate::logs::LogLevel;
 use crate::models;
 use device_api::models as device_server;
 
+// external crates
+use chrono::{DateTime, Utc};
+
 impl From<&models::Device> for device_server::Device {
     fn from(device: &models::Device) -> Self {
         device_server::Device {
@@ -35,6 +40,34 @@ impl From<&models::Deployment> for device_server::Deployment {
     }
 }
 
+/// A deployment with the state the deploy FSM keeps for it and the files its config
+/// instances are written to.
+pub fn deployment_state(
+    dpl: &models::Deployment,
+    filepaths: Vec<String>,
+) -> device_server::DeploymentState {
+    let rfc3339 = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339());
+    let status = dpl.status();
+    device_server::DeploymentState {
+        object: device_server::deployment_state::Object::Deployment,
+        id: dpl.id.clone(),
+        description: dpl.description.clone(),
+        status: (&status).into(),
+        activity_status: (&dpl.activity_status).into(),
+        error_status: (&dpl.error_status).into(),
+        target_status: (&dpl.target_status).into(),
+        device_id: dpl.device_id.clone(),
+        release_id: dpl.release_id.clone(),
+        created_at: dpl.created_at.to_rfc3339(),
+        attempts: dpl.attempts.min(i32::MAX as u32) as i32,
+        cooldown_ends_at: rfc3339(dpl.is_in_cooldown().then_some(dpl.cooldown_ends_at)),
+        deployed_at: rfc3339(dpl.deployed_at),
+        archived_at: rfc3339(dpl.archived_at),
+        config_instance_ids: dpl.config_instance_ids.clone(),
+        filepaths,
+    }
+}
+
 impl From<&models::Release> for device_server::Release {
     fn from(release: &models::Release) -> Self {
         device_server::Release {
@@ -71,3 +104,43 @@ impl From<&events::model::Event> for device_server::Event {
         }
     }
 }
+
+impl From<&LogLevel> for device_server::LogLevel {
+    fn from(level: &LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => device_server::LogLevel::LOG_LEVEL_TRACE,
+            LogLevel::Debug => device_server::LogLevel::LOG_LEVEL_DEBUG,
+            LogLevel::Info => device_server::LogLevel::LOG_LEVEL_INFO,
+            LogLevel::Warn => device_server::LogLevel::LOG_LEVEL_WARN,
+            LogLevel::Error => device_server::LogLevel::LOG_LEVEL_ERROR,
+        }
+    }
+}
+
+impl From<&pause::Status> for device_server::DeploymentsPause {
+    fn from(status: &pause::Status) -> Self {
+        device_server::DeploymentsPause {
+            paused: status.paused,
+            changed_at: status.changed_at.map(|t| t.to_rfc3339()),
+            changed_by: status.changed_by.map(|origin| match origin {
+                pause::Origin::Socket => device_server::deployments_pause::ChangedBy::Socket,
+                pause::Origin::Mqtt => device_server::deployments_pause::ChangedBy::Mqtt,
+                pause::Origin::Cli => device_server::deployments_pause::ChangedBy::Cli,
+            }),
+            reason: status.reason.clone(),
+        }
+    }
+}
+
+impl From<&versions::Release> for device_server::DeploymentDirRelease {
+    fn from(release: &versions::Release) -> Self {
+        device_server::DeploymentDirRelease {
+            number: release.number.min(i64::MAX as u64) as i64,
+            live: release.live,
+            deployment_id: release.deployment_id.clone(),
+            created_at: release.created_at.map(|t| t.to_rfc3339()),
+            activated_at: release.activated_at.map(|t| t.to_rfc3339()),
+            rolled_back: release.rolled_back,
+        }
+    }
+}
@@ -1,16 +1,23 @@
 // standard crates
 use std::future::Future;
 use std::sync::Arc;
+#[cfg(unix)]
 use std::{
     env,
     os::unix::io::{FromRawFd, RawFd},
 };
 
 // internal crates
-use crate::filesys::{self, PathExt};
+use crate::filesys;
+#[cfg(unix)]
+use crate::filesys::PathExt;
+use crate::metrics::workers::{instrument, Worker};
+use crate::platform;
 use crate::server::{
-    errors::{BindUnixSocketErr, RunAxumServerErr, ServerErr},
+    errors::{BindMetricsListenerErr, BindUnixSocketErr, RunAxumServerErr, ServerErr},
     handlers,
+    peer::Peer,
+    shed,
     state::State,
 };
 use crate::trace;
@@ -20,6 +27,7 @@ use axum::{
     routing::{get, post},
     Router,
 };
+#[cfg(unix)]
 use tokio::net::UnixListener;
 use tokio::task::JoinHandle;
 use tower::ServiceBuilder;
@@ -32,12 +40,15 @@ use tracing::Level;
 #[derive(Debug)]
 pub struct Options {
     pub socket_file: filesys::File,
+    /// Sheds requests while the agent is overloaded, unless disabled.
+    pub shedding: Option<shed::Options>,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
-            socket_file: filesys::File::new("/run/miru/miru.sock"),
+            socket_file: platform::default_socket_file(),
+            shedding: Some(shed::Options::default()),
         }
     }
 }
@@ -55,6 +66,14 @@ pub fn routes(state: Arc<State>) -> Router {
             format!("/{api_version}/version").as_str(),
             get(handlers::version),
         )
+        .route(
+            format!("/{api_version}/log_level").as_str(),
+            get(handlers::get_log_level).put(handlers::put_log_level),
+        )
+        .route(
+            format!("/{api_version}/openapi.json").as_str(),
+            get(handlers::openapi),
+        )
         // ============================= DEVICE ==================================== //
         .route(
             format!("/{api_version}/device").as_str(),
@@ -64,16 +83,57 @@ pub fn routes(state: Arc<State>) -> Router {
             format!("/{api_version}/device/sync").as_str(),
             post(handlers::sync_device),
         )
+        .route(
+            format!("/{api_version}/device/sync/history").as_str(),
+            get(handlers::get_sync_history),
+        )
+        .route(
+            format!("/{api_version}/device/sync/events").as_str(),
+            get(handlers::get_sync_events),
+        )
+        .route(
+            format!("/{api_version}/device/sync/plan").as_str(),
+            get(handlers::get_sync_plan),
+        )
+        .route(
+            format!("/{api_version}/device/keys/rotate").as_str(),
+            post(handlers::rotate_device_key),
+        )
         // ============================= DEPLOYMENTS =============================== //
-        // /current before /{id} so "current" isn't captured as a deployment_id
+        .route(
+            format!("/{api_version}/deployments").as_str(),
+            get(handlers::list_deployments),
+        )
+        // /current and /pause before /{id} so they aren't captured as a deployment_id
         .route(
             format!("/{api_version}/deployments/current").as_str(),
             get(handlers::get_current_deployment),
         )
+        .route(
+            format!("/{api_version}/deployments/pause").as_str(),
+            get(handlers::get_deployments_pause).post(handlers::pause_deployments),
+        )
+        .route(
+            format!("/{api_version}/deployments/resume").as_str(),
+            post(handlers::resume_deployments),
+        )
         .route(
             format!("/{api_version}/deployments/{{deployment_id}}").as_str(),
             get(handlers::get_deployment),
         )
+        .route(
+            format!("/{api_version}/deployments/{{deployment_id}}/diff").as_str(),
+            get(handlers::get_deployment_diff),
+        )
+        // =========================== CONFIG INSTANCES ============================ //
+        .route(
+            format!("/{api_version}/config_instances/{{config_instance_id}}").as_str(),
+            get(handlers::get_config_instance),
+        )
+        .route(
+            format!("/{api_version}/config_instances/{{config_type_name}}/content").as_str(),
+            get(handlers::get_config_type_content),
+        )
         // ============================= RELEASES ================================== //
         // /current before /{id} so "current" isn't captured as a release_id
         .route(
@@ -89,6 +149,36 @@ pub fn routes(state: Arc<State>) -> Router {
             format!("/{api_version}/git_commits/{{git_commit_id}}").as_str(),
             get(handlers::get_git_commit),
         )
+        // =========================== DEPLOYMENT DIR ============================== //
+        .route(
+            format!("/{api_version}/deployment_dir/releases").as_str(),
+            get(handlers::list_releases),
+        )
+        .route(
+            format!("/{api_version}/deployment_dir/rollback").as_str(),
+            post(handlers::rollback),
+        )
+        // ============================== STORAGE ================================== //
+        .route(
+            format!("/{api_version}/storage/wear").as_str(),
+            get(handlers::get_storage_wear),
+        )
+        .route(
+            format!("/{api_version}/storage/sweep").as_str(),
+            get(handlers::get_storage_sweep),
+        )
+        // =============================== AUDIT =================================== //
+        .route(
+            format!("/{api_version}/audit/config_access").as_str(),
+            get(handlers::get_config_access),
+        )
+        .route(
+            format!("/{api_version}/audit/config_access/verify").as_str(),
+            get(handlers::verify_config_access),
+        )
+        // ============================== METRICS ================================== //
+        // unversioned, where Prometheus scrapers look by default
+        .route("/metrics", get(handlers::metrics))
         // ============================== EVENTS =================================== //
         .route(
             format!("/{api_version}/events").as_str(),
@@ -103,6 +193,9 @@ pub(crate) async fn serve(
     shutdown_signal: impl Future<Output = ()> + Send + 'static,
 ) -> Result<JoinHandle<Result<(), ServerErr>>, ServerErr> {
     let state_for_middleware = state.clone();
+    let shedder = options
+        .shedding
+        .map(|opts| Arc::new(shed::Shedder::new(opts)));
     let app = routes(state)
         // ============================= LAYERS ===================================== //
         .layer(
@@ -111,10 +204,12 @@ pub(crate) async fn serve(
                 .layer(axum::middleware::from_fn(
                     move |req: axum::extract::Request, next: axum::middleware::Next| {
                         let state = state_for_middleware.clone();
-                        async move {
+                        // each connection is served on its own task, so requests are
+                        // attributed to the socket server individually
+                        instrument(Worker::Socket, async move {
                             state.activity_tracker.touch();
                             next.run(req).await
-                        }
+                        })
                     },
                 ))
                 // logging middleware
@@ -127,16 +222,59 @@ pub(crate) async fn serve(
                                 .level(Level::INFO)
                                 .latency_unit(LatencyUnit::Micros),
                         ),
-                ),
+                )
+                // load shedding middleware, inside the logging so shed requests are
+                // still logged
+                .option_layer(shedder.map(|shedder| {
+                    axum::middleware::from_fn(
+                        move |req: axum::extract::Request, next: axum::middleware::Next| {
+                            shed::middleware(shedder.clone(), req, next)
+                        },
+                    )
+                })),
         );
 
     // obtain the unix socket file listener
+    #[cfg(unix)]
     let listener = acquire_unix_socket_listener(&options.socket_file, async move {
         create_unix_socket_listener(&options.socket_file).await
     })
     .await?;
+    // there's no unix socket support on windows so serve on loopback instead
+    #[cfg(not(unix))]
+    let listener = create_loopback_listener(&options.socket_file).await?;
 
     // serve with graceful shutdown
+    let server_handle = tokio::task::spawn(instrument(Worker::Socket, async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<Peer>())
+            .with_graceful_shutdown(shutdown_signal)
+            .await
+            .map_err(|e| {
+                ServerErr::RunAxumServerErr(RunAxumServerErr {
+                    source: e,
+                    trace: trace!(),
+                })
+            })
+    }));
+
+    Ok(server_handle)
+}
+
+/// Serves only the metrics route on a TCP listener, for scrapers which can't reach the
+/// unix socket.
+pub async fn serve_metrics(
+    addr: &str,
+    shutdown_signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<Result<(), ServerErr>>, ServerErr> {
+    let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
+        ServerErr::BindMetricsListenerErr(BindMetricsListenerErr {
+            addr: addr.to_string(),
+            source: e,
+            trace: trace!(),
+        })
+    })?;
+    let app = Router::new().route("/metrics", get(handlers::metrics));
+
     let server_handle = tokio::task::spawn(async move {
         axum::serve(listener, app)
             .with_graceful_shutdown(shutdown_signal)
@@ -152,6 +290,7 @@ pub(crate) async fn serve(
     Ok(server_handle)
 }
 
+#[cfg(unix)]
 async fn acquire_unix_socket_listener(
     socket_file: &filesys::File,
     fallback: impl Future<Output = Result<UnixListener, ServerErr>>,
@@ -195,6 +334,7 @@ async fn acquire_unix_socket_listener(
     Ok(listener)
 }
 
+#[cfg(unix)]
 async fn create_unix_socket_listener(
     socket_file: &filesys::File,
 ) -> Result<UnixListener, ServerErr> {
@@ -208,3 +348,18 @@ async fn create_unix_socket_listener(
         })
     })
 }
+
+#[cfg(not(unix))]
+async fn create_loopback_listener(
+    socket_file: &filesys::File,
+) -> Result<tokio::net::TcpListener, ServerErr> {
+    tokio::net::TcpListener::bind(platform::LOOPBACK_SERVER_ADDR)
+        .await
+        .map_err(|e| {
+            ServerErr::BindUnixSocketErr(BindUnixSocketErr {
+                socket_file: socket_file.clone(),
+                source: e,
+                trace: trace!(),
+            })
+        })
+}
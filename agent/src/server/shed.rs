@@ -0,0 +1,236 @@
+// Sheds load on the socket API when the agent is saturated. Requests are rejected with
+// 503 and a Retry-After header while too many are in flight or while recent requests
+// took too long to complete, e.g. because the disk or the storage actors are backed
+// up, so chatty local clients can't starve the sync and deploy loops. The health and
+// metrics routes are never shed so probes keep answering under load.
+
+// standard crates
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// internal crates
+use crate::errors::Error;
+use crate::metrics;
+use crate::server::{
+    errors::{OverloadedErr, ServerErr},
+    handlers,
+};
+use crate::trace;
+
+// external crates
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::debug;
+
+/// The weight of each completed request in the average latency.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// The most requests handled at once.
+    pub max_in_flight: usize,
+    /// Requests are shed while the average latency of recent requests exceeds this.
+    pub max_latency: Duration,
+    /// How long rejected clients are told to wait. Requests are admitted again once
+    /// no request has completed for this long, to measure whether the agent
+    /// recovered.
+    pub retry_after: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 32,
+            max_latency: Duration::from_secs(2),
+            retry_after: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    InFlight,
+    Latency,
+}
+
+impl Reason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InFlight => "in_flight",
+            Self::Latency => "latency",
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InFlight => f.write_str("too many requests are in flight"),
+            Self::Latency => f.write_str("requests are slow to complete"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Latency {
+    average: Duration,
+    completed_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Load {
+    in_flight: usize,
+    latency: Option<Latency>,
+}
+
+#[derive(Debug)]
+pub struct Shedder {
+    options: Options,
+    load: Mutex<Load>,
+}
+
+impl Shedder {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            load: Mutex::new(Load::default()),
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    /// The average latency of recent requests, if any has completed.
+    pub fn latency(&self) -> Option<Duration> {
+        self.lock().latency.as_ref().map(|latency| latency.average)
+    }
+
+    /// Admits a request unless the API is overloaded. The request counts as in flight
+    /// until its permit is completed or dropped.
+    pub fn admit(&self, now: Instant) -> Result<Permit<'_>, Reason> {
+        let mut load = self.lock();
+        if load.in_flight >= self.options.max_in_flight {
+            return Err(Reason::InFlight);
+        }
+        if let Some(latency) = &load.latency {
+            if latency.average > self.options.max_latency
+                && now.saturating_duration_since(latency.completed_at) < self.options.retry_after
+            {
+                return Err(Reason::Latency);
+            }
+        }
+        load.in_flight += 1;
+        Ok(Permit {
+            shedder: self,
+            started_at: now,
+            done: false,
+        })
+    }
+
+    fn release(&self, sample: Option<(Duration, Instant)>) {
+        let mut load = self.lock();
+        load.in_flight = load.in_flight.saturating_sub(1);
+        let Some((took, now)) = sample else {
+            return;
+        };
+        let average = match &load.latency {
+            Some(latency) => latency
+                .average
+                .mul_f64(1.0 - LATENCY_WEIGHT)
+                .saturating_add(took.mul_f64(LATENCY_WEIGHT)),
+            None => took,
+        };
+        load.latency = Some(Latency {
+            average,
+            completed_at: now,
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Load> {
+        // the load is plain counters so a panic can't leave it inconsistent
+        self.load.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An admitted request.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    shedder: &'a Shedder,
+    started_at: Instant,
+    done: bool,
+}
+
+impl Permit<'_> {
+    /// Completes the request, counting how long it took towards the average latency.
+    pub fn complete(mut self, now: Instant) {
+        self.done = true;
+        let took = now.saturating_duration_since(self.started_at);
+        self.shedder.release(Some((took, now)));
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        // requests which were cancelled or don't reflect the agent's load
+        if !self.done {
+            self.shedder.release(None);
+        }
+    }
+}
+
+/// Routes which are never shed.
+fn is_exempt(path: &str) -> bool {
+    path == "/metrics" || path.ends_with("/health")
+}
+
+/// Routes which wait on the backend, whose latency says nothing about the agent's
+/// load.
+fn waits_on_backend(path: &str) -> bool {
+    path.ends_with("/device/sync")
+}
+
+pub async fn middleware(shedder: Arc<Shedder>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    if is_exempt(&path) {
+        return next.run(req).await;
+    }
+    let permit = match shedder.admit(Instant::now()) {
+        Ok(permit) => permit,
+        Err(reason) => {
+            debug!("shedding {path}: {reason}");
+            metrics::global().api_requests_shed.inc(&[reason.as_str()]);
+            return overloaded(reason, shedder.options().retry_after);
+        }
+    };
+    let response = next.run(req).await;
+    if !waits_on_backend(&path) {
+        permit.complete(Instant::now());
+    }
+    response
+}
+
+fn overloaded(reason: Reason, retry_after: Duration) -> Response {
+    // Retry-After takes whole seconds
+    let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let e = ServerErr::OverloadedErr(OverloadedErr {
+        reason: reason.to_string(),
+        retry_after_secs,
+        trace: trace!(),
+    });
+    let mut response = (e.http_status(), Json(handlers::to_error_response(e))).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    response
+}
@@ -4,8 +4,11 @@ use std::sync::Arc;
 // internal crates
 use crate::activity;
 use crate::authn;
+use crate::deploy::{pause, versions};
 use crate::events;
 use crate::http;
+use crate::logs;
+use crate::server::health;
 use crate::storage::Storage;
 use crate::sync;
 
@@ -21,6 +24,20 @@ pub struct State {
     pub activity_tracker: Arc<activity::Tracker>,
     pub event_hub: events::EventHub,
     pub shutdown_tx: broadcast::Sender<()>,
+    /// Whether the agent started in safe mode, reported by the health endpoint.
+    pub safe_mode: bool,
+    /// Whether the agent limits its writes to flash storage, reported by the storage
+    /// wear endpoint.
+    pub low_wear_mode: bool,
+    /// Changes the agent's log level at runtime.
+    pub log_level: logs::LevelControl,
+    /// Which workers were started, and where, for the health endpoint.
+    pub health: health::Probes,
+    /// The deployment directory whose releases can be listed and rolled back, if one
+    /// is configured.
+    pub versions: Option<versions::Versions>,
+    /// Pauses and resumes deployments, if deployments can be paused.
+    pub pause: Option<pause::Switch>,
 }
 
 impl State {
@@ -41,6 +58,42 @@ impl State {
             activity_tracker,
             event_hub,
             shutdown_tx,
+            safe_mode: false,
+            low_wear_mode: false,
+            log_level: logs::LevelControl::default(),
+            health: health::Probes::default(),
+            versions: None,
+            pause: None,
         }
     }
+
+    pub fn with_safe_mode(mut self, safe_mode: bool) -> Self {
+        self.safe_mode = safe_mode;
+        self
+    }
+
+    pub fn with_low_wear_mode(mut self, low_wear_mode: bool) -> Self {
+        self.low_wear_mode = low_wear_mode;
+        self
+    }
+
+    pub fn with_log_level(mut self, log_level: logs::LevelControl) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    pub fn with_health(mut self, health: health::Probes) -> Self {
+        self.health = health;
+        self
+    }
+
+    pub fn with_versions(mut self, versions: Option<versions::Versions>) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    pub fn with_pause(mut self, pause: Option<pause::Switch>) -> Self {
+        self.pause = pause;
+        self
+    }
 }
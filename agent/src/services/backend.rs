@@ -45,7 +45,7 @@ impl<'a, C: ClientI, T: TokenManagerExt> HttpBackend<'a, C, T> {
 impl<'a, C: ClientI, T: TokenManagerExt> BackendFetcher for HttpBackend<'a, C, T> {
     async fn fetch_deployment(&self, id: &str) -> Result<backend_client::Deployment, ServiceErr> {
         let token = self.token().await?;
-        http::with_retry(|| async {
+        http::with_retry(self.client, || async {
             http::deployments::get(self.client, id, &["config_instances"], &token.token).await
         })
         .await
@@ -54,14 +54,16 @@ impl<'a, C: ClientI, T: TokenManagerExt> BackendFetcher for HttpBackend<'a, C, T
 
     async fn fetch_release(&self, id: &str) -> Result<backend_client::Release, ServiceErr> {
         let token = self.token().await?;
-        http::with_retry(|| async { http::releases::get(self.client, id, &[], &token.token).await })
-            .await
-            .map_err(ServiceErr::from)
+        http::with_retry(self.client, || async {
+            http::releases::get(self.client, id, &[], &token.token).await
+        })
+        .await
+        .map_err(ServiceErr::from)
     }
 
     async fn fetch_git_commit(&self, id: &str) -> Result<backend_client::GitCommit, ServiceErr> {
         let token = self.token().await?;
-        http::with_retry(|| async {
+        http::with_retry(self.client, || async {
             http::git_commits::get(self.client, id, &[], &token.token).await
         })
         .await
@@ -0,0 +1,45 @@
+// internal crates
+use crate::crypt::digest::{Algorithm, Digest};
+use crate::models::config_instance::ContentFormat;
+use crate::services::errors::ServiceErr;
+use crate::storage;
+
+// external crates
+use serde::Serialize;
+
+/// A config instance deployed to the device along with its content.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ConfigInstanceContent {
+    pub id: String,
+    pub config_type_name: String,
+    pub filepath: String,
+    /// Binary content is base64 encoded.
+    pub content_format: ContentFormat,
+    pub content: String,
+}
+
+impl ConfigInstanceContent {
+    /// A strong entity tag of the config instance as served, quoted, which changes
+    /// whenever the instance or its content does.
+    pub fn etag(&self) -> String {
+        let served = serde_json::to_vec(self).unwrap_or_default();
+        format!("\"{}\"", Digest::compute(Algorithm::Sha256, &served).hex())
+    }
+}
+
+/// Reads a config instance and its content from storage. Config instances are only
+/// served once the agent has downloaded them.
+pub async fn get(
+    cfg_insts: storage::CfgInstRef<'_>,
+    id: String,
+) -> Result<ConfigInstanceContent, ServiceErr> {
+    let cfg_inst = cfg_insts.meta.read(id.clone()).await?;
+    let content = cfg_insts.content.read(id).await?;
+    Ok(ConfigInstanceContent {
+        id: cfg_inst.id,
+        config_type_name: cfg_inst.config_type_name,
+        filepath: cfg_inst.filepath,
+        content_format: cfg_inst.content_format,
+        content,
+    })
+}
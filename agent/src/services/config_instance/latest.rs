@@ -0,0 +1,60 @@
+// internal crates
+use crate::models::{self, DplActivity};
+use crate::services::config_instance::ConfigInstanceContent;
+use crate::services::errors::{ConfigTypeNotDeployedErr, ServiceErr};
+use crate::storage;
+use crate::trace;
+
+/// Reads the latest deployed config instance of a config type and its content, so
+/// applications can read their configs through the agent without knowing where they
+/// are deployed. Of the config instances of the live deployments with the type, the
+/// most recently created is served.
+pub async fn get_latest(
+    deployments: &storage::Deployments,
+    cfg_insts: storage::CfgInstRef<'_>,
+    config_type_name: String,
+) -> Result<ConfigInstanceContent, ServiceErr> {
+    let live = deployments
+        .find_where(|d| {
+            matches!(
+                d.activity_status,
+                DplActivity::Deployed | DplActivity::Drifted
+            )
+        })
+        .await?;
+    let ids = live
+        .iter()
+        .flat_map(|dpl| dpl.config_instance_ids.iter().cloned())
+        .collect();
+    let latest = cfg_insts
+        .meta
+        .read_many(ids)
+        .await?
+        .into_values()
+        .filter(|cfg_inst| cfg_inst.config_type_name == config_type_name)
+        .max_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+    let cfg_inst: models::ConfigInstance = match latest {
+        Some(cfg_inst) => cfg_inst,
+        None => {
+            return Err(ServiceErr::ConfigTypeNotDeployedErr(
+                ConfigTypeNotDeployedErr {
+                    config_type_name,
+                    trace: trace!(),
+                },
+            ))
+        }
+    };
+
+    let content = cfg_insts.content.read(cfg_inst.id.clone()).await?;
+    Ok(ConfigInstanceContent {
+        id: cfg_inst.id,
+        config_type_name: cfg_inst.config_type_name,
+        filepath: cfg_inst.filepath,
+        content_format: cfg_inst.content_format,
+        content,
+    })
+}
@@ -0,0 +1,4 @@
+mod get;
+mod latest;
+pub use get::*;
+pub use latest::*;
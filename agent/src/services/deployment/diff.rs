@@ -0,0 +1,202 @@
+// standard crates
+use std::collections::BTreeMap;
+
+// internal crates
+use crate::models::{self, config_instance::ContentFormat};
+use crate::services::{backend::BackendFetcher, deployment::get, errors::ServiceErr};
+use crate::storage;
+use crate::sync::patch::digest;
+
+// external crates
+use serde::Serialize;
+
+/// Text diffs are skipped for files with more lines than this since the line diff is
+/// quadratic in the number of lines.
+pub const MAX_TEXT_DIFF_LINES: usize = 2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    pub filepath: String,
+    pub change: FileChange,
+    /// The currently deployed config instance written to the file, if any.
+    pub old_config_instance_id: Option<String>,
+    /// The deployment's config instance written to the file, if any.
+    pub new_config_instance_id: Option<String>,
+    /// SHA-256 of the currently deployed content, if the file is currently deployed.
+    pub old_hash: Option<String>,
+    /// SHA-256 of the deployment's content, if the deployment writes the file.
+    pub new_hash: Option<String>,
+    /// A line diff of the content, only included when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_diff: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeploymentDiff {
+    pub deployment_id: String,
+    /// The deployment the diff is relative to, none if nothing is deployed.
+    pub current_deployment_id: Option<String>,
+    /// The files which would change, sorted by filepath. Unchanged files are omitted.
+    pub files: Vec<FileDiff>,
+}
+
+/// Computes which files deploying the given deployment would add, remove or modify
+/// relative to the currently deployed deployment.
+pub async fn diff<B: BackendFetcher>(
+    deployments: &storage::Deployments,
+    cfg_insts: storage::CfgInstRef<'_>,
+    backend: &B,
+    id: String,
+    text: bool,
+) -> Result<DeploymentDiff, ServiceErr> {
+    let target = get(deployments, backend, id).await?;
+    let current = deployments
+        .find_one_optional("deployed", |d| {
+            d.activity_status == models::DplActivity::Deployed
+        })
+        .await?;
+
+    let new_files = read_files(&cfg_insts, &target.config_instance_ids).await?;
+    let old_files = match &current {
+        Some(current) => read_files(&cfg_insts, &current.config_instance_ids).await?,
+        None => BTreeMap::new(),
+    };
+
+    let mut files = Vec::new();
+    for (filepath, new) in &new_files {
+        let old = old_files.get(filepath);
+        let change = match old {
+            None => FileChange::Added,
+            Some(old) if old.content != new.content => FileChange::Modified,
+            Some(_) => continue,
+        };
+        files.push(file_diff(filepath, change, old, Some(new), text));
+    }
+    for (filepath, old) in &old_files {
+        if !new_files.contains_key(filepath) {
+            files.push(file_diff(
+                filepath,
+                FileChange::Removed,
+                Some(old),
+                None,
+                text,
+            ));
+        }
+    }
+    files.sort_by(|a, b| a.filepath.cmp(&b.filepath));
+
+    Ok(DeploymentDiff {
+        deployment_id: target.id,
+        current_deployment_id: current.map(|d| d.id),
+        files,
+    })
+}
+
+/// A config instance's content, keyed by the file it is written to.
+struct File {
+    config_instance_id: String,
+    content: String,
+    /// Binary content, cached base64 encoded, has no text diff.
+    binary: bool,
+}
+
+async fn read_files(
+    cfg_insts: &storage::CfgInstRef<'_>,
+    ids: &[String],
+) -> Result<BTreeMap<String, File>, ServiceErr> {
+    let mut files = BTreeMap::new();
+    for id in ids {
+        let cfg_inst = cfg_insts.meta.read(id.clone()).await?;
+        let content = cfg_insts.content.read(id.clone()).await?;
+        files.insert(
+            cfg_inst.filepath,
+            File {
+                config_instance_id: id.clone(),
+                content,
+                binary: cfg_inst.content_format == ContentFormat::Binary,
+            },
+        );
+    }
+    Ok(files)
+}
+
+fn file_diff(
+    filepath: &str,
+    change: FileChange,
+    old: Option<&File>,
+    new: Option<&File>,
+    text: bool,
+) -> FileDiff {
+    let binary = old.is_some_and(|f| f.binary) || new.is_some_and(|f| f.binary);
+    let text_diff = if text && !binary {
+        line_diff(
+            old.map(|f| f.content.as_str()).unwrap_or_default(),
+            new.map(|f| f.content.as_str()).unwrap_or_default(),
+        )
+    } else {
+        None
+    };
+    FileDiff {
+        filepath: filepath.to_string(),
+        change,
+        old_config_instance_id: old.map(|f| f.config_instance_id.clone()),
+        new_config_instance_id: new.map(|f| f.config_instance_id.clone()),
+        old_hash: old.map(|f| digest(&f.content)),
+        new_hash: new.map(|f| digest(&f.content)),
+        text_diff,
+    }
+}
+
+/// Renders a line diff of the two texts where removed lines are prefixed with '-',
+/// added lines with '+' and unchanged lines with a space. Returns none if either text
+/// has more than [MAX_TEXT_DIFF_LINES] lines.
+pub fn line_diff(old: &str, new: &str) -> Option<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    if old.len() > MAX_TEXT_DIFF_LINES || new.len() > MAX_TEXT_DIFF_LINES {
+        return None;
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push_line(&mut out, ' ', old[i]);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push_line(&mut out, '-', old[i]);
+            i += 1;
+        } else {
+            push_line(&mut out, '+', new[j]);
+            j += 1;
+        }
+    }
+    Some(out)
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    out.push('\n');
+}
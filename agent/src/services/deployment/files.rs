@@ -0,0 +1,26 @@
+// internal crates
+use crate::models;
+use crate::services::errors::ServiceErr;
+use crate::storage;
+
+/// The files each deployment's config instances are written to, in the order of its
+/// config instances. Config instances the agent hasn't downloaded yet are left out.
+pub async fn filepaths(
+    cfg_insts: &storage::CfgInsts,
+    deployments: &[models::Deployment],
+) -> Result<Vec<Vec<String>>, ServiceErr> {
+    let ids = deployments
+        .iter()
+        .flat_map(|dpl| dpl.config_instance_ids.iter().cloned())
+        .collect();
+    let cached = cfg_insts.read_many(ids).await?;
+    Ok(deployments
+        .iter()
+        .map(|dpl| {
+            dpl.config_instance_ids
+                .iter()
+                .filter_map(|id| cached.get(id).map(|ci| ci.filepath.clone()))
+                .collect()
+        })
+        .collect())
+}
@@ -0,0 +1,194 @@
+// standard crates
+use std::collections::HashSet;
+
+// internal crates
+use crate::crypt::base64;
+use crate::models::{self, DplActivity, DplErrStatus, DplTarget};
+use crate::services::errors::{InvalidQueryErr, MalformedCursorErr, ServiceErr};
+use crate::storage;
+use crate::trace;
+
+// external crates
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 500;
+
+/// Filters and pages the cached deployments. Statuses are given by their wire names
+/// (e.g. `deployed`) and every given filter must match.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ListQuery {
+    /// The most deployments to return, at most [MAX_LIMIT].
+    pub limit: Option<usize>,
+    /// Continues a previous list from its `next_cursor`.
+    pub cursor: Option<String>,
+    pub activity_status: Option<String>,
+    pub target_status: Option<String>,
+    pub error_status: Option<String>,
+    /// Only deployments with a config instance of this config type.
+    pub config_type: Option<String>,
+}
+
+/// A page of deployments, newest first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    pub deployments: Vec<models::Deployment>,
+    /// Where the next page starts, if there are more matching deployments.
+    pub next_cursor: Option<String>,
+}
+
+impl Page {
+    pub fn has_more(&self) -> bool {
+        self.next_cursor.is_some()
+    }
+}
+
+/// Lists the cached deployments matching the query, newest first. Pages are keyed on
+/// the last deployment returned rather than an offset, so deployments cached between
+/// requests don't shift later pages.
+pub async fn list(
+    deployments: &storage::Deployments,
+    cfg_insts: &storage::CfgInsts,
+    query: &ListQuery,
+) -> Result<Page, ServiceErr> {
+    let limit = match query.limit {
+        None => DEFAULT_LIMIT,
+        Some(limit) if (1..=MAX_LIMIT).contains(&limit) => limit,
+        Some(limit) => {
+            return Err(invalid_query(format!(
+                "limit must be between 1 and {MAX_LIMIT}, got {limit}"
+            )))
+        }
+    };
+    let after = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let activity = parse_status(
+        "activity_status",
+        query.activity_status.as_deref(),
+        DplActivity::variants(),
+        DplActivity::as_str,
+    )?;
+    let target = parse_status(
+        "target_status",
+        query.target_status.as_deref(),
+        DplTarget::variants(),
+        DplTarget::as_str,
+    )?;
+    let error = parse_status(
+        "error_status",
+        query.error_status.as_deref(),
+        DplErrStatus::variants(),
+        DplErrStatus::as_str,
+    )?;
+    let cfg_inst_ids = match &query.config_type {
+        Some(config_type) => {
+            let config_type = config_type.clone();
+            let ids: HashSet<String> = cfg_insts
+                .find_where(move |cfg_inst| cfg_inst.config_type_name == config_type)
+                .await?
+                .into_iter()
+                .map(|cfg_inst| cfg_inst.id)
+                .collect();
+            Some(ids)
+        }
+        None => None,
+    };
+
+    let mut matches = deployments
+        .find_where(move |dpl| {
+            activity.is_none_or(|status| dpl.activity_status == status)
+                && target.is_none_or(|status| dpl.target_status == status)
+                && error.is_none_or(|status| dpl.error_status == status)
+                && cfg_inst_ids
+                    .as_ref()
+                    .is_none_or(|ids| dpl.config_instance_ids.iter().any(|id| ids.contains(id)))
+                && after
+                    .as_ref()
+                    .is_none_or(|cursor| cursor.precedes(&Cursor::of(dpl)))
+        })
+        .await?;
+    matches.sort_by_key(|dpl| std::cmp::Reverse(Cursor::of(dpl)));
+
+    let has_more = matches.len() > limit;
+    matches.truncate(limit);
+    let next_cursor = match matches.last() {
+        Some(last) if has_more => Some(Cursor::of(last).encode()),
+        _ => None,
+    };
+    Ok(Page {
+        deployments: matches,
+        next_cursor,
+    })
+}
+
+fn parse_status<T: Copy>(
+    field: &str,
+    value: Option<&str>,
+    variants: Vec<T>,
+    as_str: fn(&T) -> &'static str,
+) -> Result<Option<T>, ServiceErr> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    match variants.iter().find(|variant| as_str(variant) == value) {
+        Some(variant) => Ok(Some(*variant)),
+        None => {
+            let expected: Vec<&str> = variants.iter().map(as_str).collect();
+            Err(invalid_query(format!(
+                "{field} '{value}' is not one of {expected:?}"
+            )))
+        }
+    }
+}
+
+fn invalid_query(msg: String) -> ServiceErr {
+    ServiceErr::InvalidQueryErr(InvalidQueryErr {
+        msg,
+        trace: trace!(),
+    })
+}
+
+// =================================== CURSOR ====================================== //
+/// The position of a deployment in the list, ordered by creation time and then id.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Cursor {
+    created_at: DateTime<Utc>,
+    id: String,
+}
+
+impl Cursor {
+    fn of(dpl: &models::Deployment) -> Self {
+        Self {
+            created_at: dpl.created_at,
+            id: dpl.id.clone(),
+        }
+    }
+
+    /// Whether the deployment at `other` comes after this one, newest first.
+    fn precedes(&self, other: &Cursor) -> bool {
+        other < self
+    }
+
+    fn encode(&self) -> String {
+        let created_at = self.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true);
+        base64::encode_string_url_safe_no_pad(&format!("{created_at}|{}", self.id))
+    }
+
+    fn decode(cursor: &str) -> Result<Self, ServiceErr> {
+        let malformed = || {
+            ServiceErr::MalformedCursorErr(MalformedCursorErr {
+                cursor: cursor.to_string(),
+                trace: trace!(),
+            })
+        };
+        let decoded = base64::decode_string_url_safe_no_pad(cursor).map_err(|_| malformed())?;
+        let (created_at, id) = decoded.split_once('|').ok_or_else(malformed)?;
+        let created_at = DateTime::parse_from_rfc3339(created_at)
+            .map_err(|_| malformed())?
+            .with_timezone(&Utc);
+        Ok(Self {
+            created_at,
+            id: id.to_string(),
+        })
+    }
+}
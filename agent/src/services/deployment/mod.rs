@@ -1,4 +1,10 @@
 mod current;
+mod diff;
+mod files;
 mod get;
+mod list;
 pub use current::*;
+pub use diff::*;
+pub use files::*;
 pub use get::*;
+pub use list::*;
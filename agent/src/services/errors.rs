@@ -1,5 +1,6 @@
 // internal crates
 use crate::cache;
+use crate::errors::Trace;
 use crate::events;
 use crate::filesys;
 use crate::http;
@@ -7,6 +8,54 @@ use crate::models;
 use crate::storage::StorageErr;
 use crate::sync;
 
+#[derive(Debug, thiserror::Error)]
+#[error("invalid query: {msg}")]
+pub struct InvalidQueryErr {
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidQueryErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::InvalidQuery
+    }
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::BAD_REQUEST
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("malformed cursor '{cursor}'")]
+pub struct MalformedCursorErr {
+    pub cursor: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for MalformedCursorErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::MalformedCursor
+    }
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::BAD_REQUEST
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("no config instance of config type '{config_type_name}' is deployed")]
+pub struct ConfigTypeNotDeployedErr {
+    pub config_type_name: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ConfigTypeNotDeployedErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::ResourceNotFound
+    }
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::NOT_FOUND
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ServiceErr {
     #[error(transparent)]
@@ -23,6 +72,12 @@ pub enum ServiceErr {
     EventsErr(events::errors::EventsErr),
     #[error(transparent)]
     SyncErr(sync::SyncErr),
+    #[error(transparent)]
+    InvalidQueryErr(InvalidQueryErr),
+    #[error(transparent)]
+    MalformedCursorErr(MalformedCursorErr),
+    #[error(transparent)]
+    ConfigTypeNotDeployedErr(ConfigTypeNotDeployedErr),
 }
 
 impl From<cache::CacheErr> for ServiceErr {
@@ -75,4 +130,7 @@ crate::impl_error!(ServiceErr {
     StorageErr,
     HTTPErr,
     SyncErr,
+    InvalidQueryErr,
+    MalformedCursorErr,
+    ConfigTypeNotDeployedErr,
 });
@@ -13,3 +13,7 @@ pub type CfgInstEntry = cache::CacheEntry<models::CfgInstID, models::ConfigInsta
 // for the content.
 pub type CfgInsts = cache::FileCache<models::CfgInstID, models::ConfigInstance>;
 pub type CfgInstContent = cache::DirCache<models::CfgInstID, String>;
+
+/// The JSON Schemas config instance content is validated against before it's deployed,
+/// by config schema id. Schemas are immutable so they're only downloaded once.
+pub type ConfigSchemas = cache::DirCache<String, serde_json::Value>;
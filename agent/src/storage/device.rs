@@ -1,6 +1,6 @@
 // internal crates
 use crate::authn::token_mngr::TokenFile;
-use crate::crypt::jwt;
+use crate::crypt::{jwt, keystore::PrivateKey};
 use crate::filesys::{cached_file::ConcurrentCachedFile, PathExt};
 use crate::models::{self, device};
 use crate::storage::{
@@ -11,9 +11,11 @@ use crate::trace;
 
 pub type Device = ConcurrentCachedFile<models::Device, device::Updates>;
 
-pub async fn assert_activated(layout: &Layout) -> Result<(), StorageErr> {
+/// The device is activated once its keys are in place. A private key kept in hardware
+/// is only checked when it's used.
+pub async fn assert_activated(layout: &Layout, private_key: &PrivateKey) -> Result<(), StorageErr> {
     let auth_dir = layout.auth();
-    if !auth_dir.private_key().exists() {
+    if private_key.file().is_some_and(|file| !file.exists()) {
         return Err(StorageErr::DeviceNotActivatedErr(DeviceNotActivatedErr {
             msg: "device is not activated".to_string(),
             trace: trace!(),
@@ -3,6 +3,8 @@ use crate::cache;
 use crate::crypt;
 use crate::errors::Trace;
 use crate::filesys;
+use crate::journal;
+use crate::storage::locks;
 
 #[derive(Debug, thiserror::Error)]
 #[error("device is not activated: {msg}")]
@@ -32,6 +34,16 @@ pub struct PruneCacheErrs {
 
 impl crate::errors::Error for PruneCacheErrs {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("storage migration '{id}' failed: {msg}")]
+pub struct MigrationErr {
+    pub id: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for MigrationErr {}
+
 #[derive(Debug, thiserror::Error)]
 pub struct ResolveDeviceIDErr {
     pub device_file_err: Box<filesys::FileSysErr>,
@@ -51,6 +63,28 @@ impl std::fmt::Display for ResolveDeviceIDErr {
 
 impl crate::errors::Error for ResolveDeviceIDErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("{resource} is being written by {}", holder.map_or("another writer".to_string(), |w| w.to_string()))]
+pub struct ResourceConflictErr {
+    pub resource: locks::Resource,
+    pub holder: Option<locks::Writer>,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ResourceConflictErr {
+    fn code(&self) -> crate::errors::Code {
+        crate::errors::Code::ResourceConflict
+    }
+
+    fn http_status(&self) -> crate::errors::HTTPCode {
+        crate::errors::HTTPCode::CONFLICT
+    }
+
+    fn params(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "resource": self.resource, "holder": self.holder }))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum StorageErr {
     #[error(transparent)]
@@ -66,7 +100,13 @@ pub enum StorageErr {
     #[error(transparent)]
     JoinHandleErr(JoinHandleErr),
     #[error(transparent)]
+    MigrationErr(MigrationErr),
+    #[error(transparent)]
     ResolveDeviceIDErr(Box<ResolveDeviceIDErr>),
+    #[error(transparent)]
+    JournalErr(journal::JournalErr),
+    #[error(transparent)]
+    ResourceConflictErr(ResourceConflictErr),
 }
 
 impl From<cache::CacheErr> for StorageErr {
@@ -87,6 +127,12 @@ impl From<filesys::FileSysErr> for StorageErr {
     }
 }
 
+impl From<journal::JournalErr> for StorageErr {
+    fn from(e: journal::JournalErr) -> Self {
+        Self::JournalErr(e)
+    }
+}
+
 crate::impl_error!(StorageErr {
     DeviceNotActivatedErr,
     PruneCacheErrs,
@@ -94,5 +140,8 @@ crate::impl_error!(StorageErr {
     CryptErr,
     FileSysErr,
     JoinHandleErr,
+    MigrationErr,
     ResolveDeviceIDErr,
+    JournalErr,
+    ResourceConflictErr,
 });
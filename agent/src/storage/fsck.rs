@@ -0,0 +1,559 @@
+// The caches reference one another by id: deployments reference config instances and
+// releases, releases reference git commits, and each config instance's content lives in
+// its own file next to the shared metadata file. A crash or a hand-edited file can
+// break these references in ways the agent only notices when it next deploys. fsck
+// reads the files directly, so the agent should be stopped before repairing them.
+
+// standard crates
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// internal crates
+use crate::authn::Token;
+use crate::cache::CacheEntry;
+use crate::crypt::jwt;
+use crate::filesys::{self, PathExt, WriteOptions};
+use crate::models;
+use crate::storage::layout::Layout;
+
+// external crates
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Expected to resolve itself, e.g. on the next sync or cache eviction.
+    Info,
+    /// Wasted space or a reference the next sync restores.
+    Warning,
+    /// Breaks deploying or syncing until repaired.
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "info" => Some(Severity::Info),
+            "warning" | "warn" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    UnreadableFile,
+    DeviceMismatch,
+    MissingDeviceFile,
+    KeyMismatch,
+    MissingConfigInstance,
+    MissingRelease,
+    MissingGitCommit,
+    MissingContent,
+    CorruptContent,
+    OrphanedContent,
+    UnreferencedConfigInstance,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub check: Check,
+    /// The file or cache entry the finding is about.
+    pub subject: String,
+    pub message: String,
+    /// How `--fix` repairs the finding, if it can.
+    pub repair: Option<&'static str>,
+    pub repaired: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    pub fix: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// The findings at or above the given severity.
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity >= severity)
+    }
+
+    /// Whether any error is left unrepaired.
+    pub fn has_errors(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|f| f.severity == Severity::Error && !f.repaired)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    /// Renders one line per finding at or above the given severity followed by a
+    /// summary of every finding.
+    pub fn render(&self, severity: Severity) -> String {
+        let mut lines = self
+            .at_least(severity)
+            .map(|f| {
+                let repair = match (f.repair, f.repaired) {
+                    (Some(repair), true) => format!(" [repaired: {repair}]"),
+                    (Some(repair), false) => format!(" [--fix will {repair}]"),
+                    (None, _) => String::new(),
+                };
+                format!(
+                    "{:<7} {}: {}{repair}",
+                    f.severity.as_str(),
+                    f.subject,
+                    f.message
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!(
+            "{} errors, {} warnings, {} info, {} repaired",
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Info),
+            self.findings.iter().filter(|f| f.repaired).count()
+        ));
+        lines.join("\n")
+    }
+}
+
+type Entries<V> = HashMap<String, CacheEntry<String, V>>;
+
+/// Checks the references between the caches under the layout, repairing what it can
+/// when `opts.fix` is set.
+pub async fn run(layout: &Layout, opts: Options) -> Report {
+    let mut fsck = Fsck {
+        fix: opts.fix,
+        report: Report::default(),
+    };
+
+    fsck.check_device(layout).await;
+
+    let deployments_file = layout.deployments();
+    let mut deployments = fsck
+        .read_cache::<models::Deployment>(&deployments_file)
+        .await;
+    let releases = fsck.read_cache::<models::Release>(&layout.releases()).await;
+    let git_commits = fsck
+        .read_cache::<models::GitCommit>(&layout.git_commits())
+        .await;
+    let meta_file = layout.config_instance_meta();
+    let meta = fsck.read_cache::<models::ConfigInstance>(&meta_file).await;
+    let content = fsck
+        .check_content(&layout.config_instance_content(), meta.as_ref())
+        .await;
+
+    if let Some(deployments) = deployments.as_mut() {
+        let changed = fsck.check_deployments(deployments, meta.as_ref(), releases.as_ref());
+        if changed {
+            fsck.write_cache(
+                &deployments_file,
+                deployments,
+                &[Check::KeyMismatch, Check::MissingConfigInstance],
+            )
+            .await;
+        }
+    }
+    if let (Some(releases), Some(git_commits)) = (&releases, &git_commits) {
+        fsck.check_releases(releases, git_commits);
+    }
+    if let (Some(meta), Some(content)) = (&meta, &content) {
+        fsck.check_cfg_insts(meta, content, deployments.as_ref());
+    }
+
+    fsck.report
+        .findings
+        .sort_by_key(|f| std::cmp::Reverse(f.severity));
+    fsck.report
+}
+
+struct Fsck {
+    fix: bool,
+    report: Report,
+}
+
+impl Fsck {
+    fn push(
+        &mut self,
+        severity: Severity,
+        check: Check,
+        subject: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.report.findings.push(Finding {
+            severity,
+            check,
+            subject: subject.into(),
+            message: message.into(),
+            repair: None,
+            repaired: false,
+        });
+    }
+
+    /// Records a finding which `--fix` repairs as `repair` describes, returning
+    /// whether the caller should apply the repair.
+    fn push_repairable(
+        &mut self,
+        severity: Severity,
+        check: Check,
+        subject: impl Into<String>,
+        message: impl Into<String>,
+        repair: &'static str,
+    ) -> bool {
+        self.push(severity, check, subject, message);
+        if let Some(finding) = self.report.findings.last_mut() {
+            finding.repair = Some(repair);
+            finding.repaired = self.fix;
+        }
+        self.fix
+    }
+
+    /// Marks the last finding as unrepaired after its repair failed.
+    fn repair_failed(&mut self, e: impl fmt::Display) {
+        if let Some(finding) = self.report.findings.last_mut() {
+            warn!("unable to repair {}: {e}", finding.subject);
+            finding.repaired = false;
+        }
+    }
+
+    async fn read_cache<V>(&mut self, file: &filesys::File) -> Option<Entries<V>>
+    where
+        V: Clone + Serialize + DeserializeOwned,
+    {
+        if !file.exists() {
+            return Some(HashMap::new());
+        }
+        match file.read_json::<Entries<V>>().await {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                self.push(
+                    Severity::Error,
+                    Check::UnreadableFile,
+                    file.to_string(),
+                    format!("unable to read the cache: {e}"),
+                );
+                None
+            }
+        }
+    }
+
+    /// Writes back a cache, marking the findings of the given checks as unrepaired if
+    /// the write fails.
+    async fn write_cache<V>(&mut self, file: &filesys::File, entries: &Entries<V>, checks: &[Check])
+    where
+        V: Clone + Serialize,
+    {
+        if let Err(e) = file
+            .write_json(entries, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+        {
+            warn!("unable to repair {file}: {e}");
+            for finding in &mut self.report.findings {
+                if checks.contains(&finding.check) {
+                    finding.repaired = false;
+                }
+            }
+        }
+    }
+
+    async fn check_device(&mut self, layout: &Layout) {
+        let token_device_id = match layout.auth().token().read_json::<Token>().await {
+            Ok(token) => jwt::extract_device_id(&token.token).ok(),
+            Err(_) => None,
+        };
+
+        let device_file = layout.device();
+        if !device_file.exists() {
+            if layout.auth().private_key().exists() {
+                self.push(
+                    Severity::Info,
+                    Check::MissingDeviceFile,
+                    device_file.to_string(),
+                    "the device file is missing and is recreated when the agent starts",
+                );
+            }
+            return;
+        }
+
+        match device_file.read_json::<models::Device>().await {
+            Ok(device) => match token_device_id {
+                Some(token_device_id) if token_device_id != device.id => self.push(
+                    Severity::Error,
+                    Check::DeviceMismatch,
+                    device_file.to_string(),
+                    format!(
+                        "the device file is for device '{}' but the token is for device \
+                         '{token_device_id}'; reprovision the device",
+                        device.id
+                    ),
+                ),
+                _ => {}
+            },
+            // the agent recreates the device file from the token on startup
+            Err(e) if token_device_id.is_some() => {
+                let repair = self.push_repairable(
+                    Severity::Error,
+                    Check::UnreadableFile,
+                    device_file.to_string(),
+                    format!("unable to read the device file: {e}"),
+                    "delete it so the agent recreates it from the token",
+                );
+                if repair {
+                    if let Err(e) = device_file.delete().await {
+                        self.repair_failed(e);
+                    }
+                }
+            }
+            Err(e) => self.push(
+                Severity::Error,
+                Check::UnreadableFile,
+                device_file.to_string(),
+                format!("unable to read the device file: {e}"),
+            ),
+        }
+    }
+
+    /// Reads the content files, returning the ids of the config instances with
+    /// content.
+    async fn check_content(
+        &mut self,
+        dir: &filesys::Dir,
+        meta: Option<&Entries<models::ConfigInstance>>,
+    ) -> Option<HashSet<String>> {
+        if !dir.exists() {
+            return Some(HashSet::new());
+        }
+        let files = match dir.files().await {
+            Ok(files) => files,
+            Err(e) => {
+                self.push(
+                    Severity::Error,
+                    Check::UnreadableFile,
+                    dir.to_string(),
+                    format!("unable to list the content directory: {e}"),
+                );
+                return None;
+            }
+        };
+
+        let mut ids = HashSet::new();
+        for file in files {
+            let entry = match file.read_json::<CacheEntry<String, String>>().await {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let repair = self.push_repairable(
+                        Severity::Error,
+                        Check::CorruptContent,
+                        file.to_string(),
+                        format!("unable to read the config instance content: {e}"),
+                        "delete it so the next sync downloads it again",
+                    );
+                    if repair {
+                        if let Err(e) = file.delete().await {
+                            self.repair_failed(e);
+                        }
+                    }
+                    continue;
+                }
+            };
+            if meta.is_some_and(|meta| !meta.contains_key(&entry.key)) {
+                let repair = self.push_repairable(
+                    Severity::Warning,
+                    Check::OrphanedContent,
+                    file.to_string(),
+                    format!(
+                        "content for config instance '{}' which has no metadata",
+                        entry.key
+                    ),
+                    "delete it",
+                );
+                if repair {
+                    if let Err(e) = file.delete().await {
+                        self.repair_failed(e);
+                    }
+                }
+                continue;
+            }
+            ids.insert(entry.key);
+        }
+        Some(ids)
+    }
+
+    /// Checks the deployments' references, returning whether any were changed.
+    fn check_deployments(
+        &mut self,
+        deployments: &mut Entries<models::Deployment>,
+        meta: Option<&Entries<models::ConfigInstance>>,
+        releases: Option<&Entries<models::Release>>,
+    ) -> bool {
+        let mut changed = false;
+
+        // entries stored under a key other than the deployment's id are never found
+        let mismatched = deployments
+            .iter()
+            .filter(|(key, entry)| **key != entry.key || entry.key != entry.value.id)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in mismatched {
+            let Some(mut entry) = deployments.remove(&key) else {
+                continue;
+            };
+            let repair = self.push_repairable(
+                Severity::Warning,
+                Check::KeyMismatch,
+                format!("deployment {}", entry.value.id),
+                format!("stored under the key '{key}'"),
+                "store it under its id",
+            );
+            if repair {
+                entry.key = entry.value.id.clone();
+                deployments.insert(entry.key.clone(), entry);
+                changed = true;
+            } else {
+                deployments.insert(key, entry);
+            }
+        }
+
+        let mut ids = deployments.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let Some(entry) = deployments.get(&id) else {
+                continue;
+            };
+            let deployment = &entry.value;
+
+            if let Some(releases) = releases {
+                if !deployment.release_id.is_empty()
+                    && !releases.contains_key(&deployment.release_id)
+                {
+                    self.push(
+                        Severity::Warning,
+                        Check::MissingRelease,
+                        format!("deployment {id}"),
+                        format!(
+                            "references release '{}' which isn't cached; the next sync \
+                             restores it",
+                            deployment.release_id
+                        ),
+                    );
+                }
+            }
+
+            let Some(meta) = meta else {
+                continue;
+            };
+            let missing = deployment
+                .config_instance_ids
+                .iter()
+                .filter(|cfg_inst_id| !meta.contains_key(*cfg_inst_id))
+                .cloned()
+                .collect::<Vec<_>>();
+            if missing.is_empty() {
+                continue;
+            }
+            let repair = self.push_repairable(
+                Severity::Error,
+                Check::MissingConfigInstance,
+                format!("deployment {id}"),
+                format!(
+                    "references config instances which aren't cached: {}",
+                    missing.join(", ")
+                ),
+                "remove the deployment so the next sync fetches it again",
+            );
+            if repair {
+                deployments.remove(&id);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn check_releases(
+        &mut self,
+        releases: &Entries<models::Release>,
+        git_commits: &Entries<models::GitCommit>,
+    ) {
+        let mut releases = releases.values().collect::<Vec<_>>();
+        releases.sort_by(|a, b| a.key.cmp(&b.key));
+        for entry in releases {
+            let Some(git_commit_id) = &entry.value.git_commit_id else {
+                continue;
+            };
+            if !git_commits.contains_key(git_commit_id) {
+                self.push(
+                    Severity::Warning,
+                    Check::MissingGitCommit,
+                    format!("release {}", entry.key),
+                    format!(
+                        "references git commit '{git_commit_id}' which isn't cached; the \
+                         next sync restores it"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_cfg_insts(
+        &mut self,
+        meta: &Entries<models::ConfigInstance>,
+        content: &HashSet<String>,
+        deployments: Option<&Entries<models::Deployment>>,
+    ) {
+        let referenced = deployments.map(|deployments| {
+            deployments
+                .values()
+                .flat_map(|entry| entry.value.config_instance_ids.iter())
+                .collect::<HashSet<_>>()
+        });
+
+        let mut ids = meta.keys().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let is_referenced = referenced.as_ref().is_none_or(|r| r.contains(id));
+            if !is_referenced {
+                self.push(
+                    Severity::Info,
+                    Check::UnreferencedConfigInstance,
+                    format!("config instance {id}"),
+                    "no cached deployment references it; it is evicted once the cache \
+                     is full",
+                );
+            } else if !content.contains(id) {
+                self.push(
+                    Severity::Warning,
+                    Check::MissingContent,
+                    format!("config instance {id}"),
+                    "its content isn't cached; the next sync downloads it again",
+                );
+            }
+        }
+    }
+}
@@ -1,21 +1,52 @@
+// standard crates
+use std::path::PathBuf;
+
 // internal crates
-use crate::filesys;
+use crate::filesys::{self, PathExt};
+use crate::platform;
 
 #[derive(Clone, Debug)]
 pub struct Layout {
     pub filesystem_root: filesys::Dir,
+    /// Replaces the platform's data directory so that several agents can run on one
+    /// host. The agent's logs and socket are kept under it as well.
+    pub data_dir: Option<filesys::Dir>,
 }
 
 impl Layout {
     pub fn new(filesystem_root: filesys::Dir) -> Self {
-        Self { filesystem_root }
+        Self {
+            filesystem_root,
+            data_dir: None,
+        }
+    }
+
+    pub fn with_data_dir(data_dir: filesys::Dir) -> Self {
+        Self {
+            filesystem_root: platform::filesystem_root(),
+            data_dir: Some(data_dir),
+        }
     }
 
     pub fn root(&self) -> filesys::Dir {
-        self.filesystem_root
-            .subdir("var")
-            .subdir("lib")
-            .subdir("miru")
+        match &self.data_dir {
+            Some(data_dir) => data_dir.clone(),
+            None => platform::data_dir(&self.filesystem_root),
+        }
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        match &self.data_dir {
+            Some(data_dir) => data_dir.subdir("logs").path().clone(),
+            None => platform::default_log_dir(),
+        }
+    }
+
+    pub fn socket_file(&self) -> filesys::File {
+        match &self.data_dir {
+            Some(data_dir) => data_dir.file("miru.sock"),
+            None => platform::default_socket_file(),
+        }
     }
 
     pub fn temp_dir(&self) -> filesys::Dir {
@@ -30,6 +61,12 @@ impl Layout {
         self.root().file("settings.json")
     }
 
+    /// Settings set remotely, which take precedence over all others, see
+    /// [crate::config].
+    pub fn remote_settings(&self) -> filesys::File {
+        self.root().file("remote_settings.json")
+    }
+
     pub fn resources(&self) -> filesys::Dir {
         self.root().subdir("resources")
     }
@@ -42,6 +79,67 @@ impl Layout {
         self.root().file("agent_version")
     }
 
+    pub fn migrations(&self) -> filesys::File {
+        self.root().file("migrations.json")
+    }
+
+    pub fn journal(&self) -> filesys::File {
+        self.root().file("journal.json")
+    }
+
+    pub fn wear(&self) -> filesys::File {
+        self.root().file("wear.json")
+    }
+
+    pub fn config_access(&self) -> filesys::File {
+        self.root().file("config_access.json")
+    }
+
+    /// The syncer's persisted history, events and state.
+    pub fn sync_dir(&self) -> filesys::Dir {
+        self.root().subdir("sync")
+    }
+
+    pub fn sync_history(&self) -> filesys::File {
+        self.sync_dir().file("history.json")
+    }
+
+    pub fn sync_state(&self) -> filesys::File {
+        self.sync_dir().file("state.json")
+    }
+
+    pub fn sync_events(&self) -> filesys::File {
+        self.sync_dir().file("events.json")
+    }
+
+    pub fn content_warming(&self) -> filesys::File {
+        self.root().file("content_warming.json")
+    }
+
+    pub fn crash_record(&self) -> filesys::File {
+        self.root().file("crash_record.json")
+    }
+
+    /// The reports written when the agent panics, see [crate::diagnostics::crash].
+    pub fn crash(&self) -> CrashLayout {
+        CrashLayout::new(self.root().subdir("crash"))
+    }
+
+    /// Where the device was provisioned from, if it provisioned itself from a seed.
+    pub fn provenance(&self) -> filesys::File {
+        self.root().file("provenance.json")
+    }
+
+    /// Checked for a provisioning seed on first boot. Always under the filesystem
+    /// root since the seed is written onto the boot media, not the data directory.
+    pub fn seed_dir(&self) -> filesys::Dir {
+        platform::seed_dir(&self.filesystem_root)
+    }
+
+    pub fn redaction_rules(&self) -> filesys::File {
+        self.root().file("redaction.json")
+    }
+
     fn config_instances(&self) -> filesys::Dir {
         self.resources().subdir("config_instances")
     }
@@ -54,6 +152,10 @@ impl Layout {
         self.config_instances().subdir("contents")
     }
 
+    pub fn config_schemas(&self) -> filesys::Dir {
+        self.config_instances().subdir("schemas")
+    }
+
     pub fn deployments(&self) -> filesys::File {
         self.resources().file("deployments.json")
     }
@@ -66,6 +168,27 @@ impl Layout {
         self.resources().file("git_commits.json")
     }
 
+    /// The agent binaries installed by self-updates, see [crate::updater].
+    pub fn updates_dir(&self) -> filesys::Dir {
+        self.root().subdir("updates")
+    }
+
+    /// The directories created for deployed config files, see
+    /// [crate::deploy::created_dirs].
+    pub fn created_dirs(&self) -> filesys::File {
+        self.root().file("created_dirs.json")
+    }
+
+    /// Whether deployments are paused, see [crate::deploy::pause].
+    pub fn deployments_paused(&self) -> filesys::File {
+        self.root().file("deployments_paused.json")
+    }
+
+    /// Files removed with deployments, kept for a while so they can be restored.
+    pub fn trash_dir(&self) -> filesys::Dir {
+        self.root().subdir("trash")
+    }
+
     pub fn events_dir(&self) -> filesys::Dir {
         self.root().subdir("events")
     }
@@ -77,7 +200,7 @@ impl Layout {
 
 impl Default for Layout {
     fn default() -> Self {
-        Self::new(filesys::Dir::new("/"))
+        Self::new(platform::filesystem_root())
     }
 }
 
@@ -98,7 +221,48 @@ impl AuthLayout {
         self.root.file("public_key.pem")
     }
 
+    /// The device's client certificate (and chain) for sites which require mutual
+    /// TLS.
+    pub fn client_cert(&self) -> filesys::File {
+        self.root.file("client_cert.pem")
+    }
+
+    pub fn client_key(&self) -> filesys::File {
+        self.root.file("client_key.pem")
+    }
+
     pub fn token(&self) -> filesys::File {
         self.root.file("token.json")
     }
+
+    pub fn webhook_keys(&self) -> filesys::File {
+        self.root.file("webhook_keys.json")
+    }
+
+    /// The salt for anonymizing the device's identifiers in exports, see
+    /// [crate::crypt::salt].
+    pub fn device_salt(&self) -> filesys::File {
+        self.root.file("device_salt")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CrashLayout {
+    pub root: filesys::Dir,
+}
+
+impl CrashLayout {
+    pub fn new(root: filesys::Dir) -> Self {
+        Self { root }
+    }
+
+    /// The reports not yet uploaded to the backend.
+    pub fn pending(&self) -> filesys::Dir {
+        self.root.subdir("pending")
+    }
+
+    /// The most recent report, kept after it's uploaded.
+    pub fn last_crash(&self) -> filesys::File {
+        self.root.file("last_crash.json")
+    }
 }
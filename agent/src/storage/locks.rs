@@ -0,0 +1,208 @@
+// Coordinates the writers of the same stored resources. The caches serialize single
+// reads and writes but not a read followed by a write, so a local API write (e.g. a
+// pin on a deployment) and the syncer reconciling the same deployment could each
+// overwrite the other's changes. Both writers lock the resources they read-modify-
+// write here first: the syncer waits for its locks, local API writes fail fast with a
+// conflict error so the client can retry rather than stall behind a sync.
+
+// standard crates
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// internal crates
+use crate::storage::errors::{ResourceConflictErr, StorageErr};
+use crate::trace;
+
+// external crates
+use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+
+/// A stored resource. Resources are always locked in this type's order, so writers
+/// locking overlapping sets of resources can't deadlock.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum Resource {
+    Device,
+    Deployment(String),
+    ConfigInstance(String),
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Device => write!(f, "device"),
+            Self::Deployment(id) => write!(f, "deployment '{id}'"),
+            Self::ConfigInstance(id) => write!(f, "config instance '{id}'"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Writer {
+    Syncer,
+    LocalApi,
+}
+
+impl fmt::Display for Writer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syncer => write!(f, "the syncer"),
+            Self::LocalApi => write!(f, "a local API request"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    holder: Mutex<Option<Writer>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Locks {
+    slots: Mutex<HashMap<Resource, Arc<Slot>>>,
+}
+
+/// Holds the locks on a set of resources until dropped.
+#[must_use = "the resources are unlocked when the guard is dropped"]
+pub struct Guard<'a> {
+    locks: &'a Locks,
+    /// Every resource the guard was asked to lock, held or not, so that the slots of
+    /// an abandoned acquisition are forgotten too.
+    requested: Vec<Resource>,
+    held: Vec<(Resource, Arc<Slot>, OwnedMutexGuard<()>)>,
+}
+
+impl fmt::Debug for Guard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guard")
+            .field("resources", &self.resources())
+            .finish()
+    }
+}
+
+impl Guard<'_> {
+    pub fn resources(&self) -> Vec<&Resource> {
+        self.held.iter().map(|(resource, _, _)| resource).collect()
+    }
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        for (_, slot, guard) in std::mem::take(&mut self.held) {
+            *slot.holder.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            drop(guard);
+        }
+        self.locks.release(&self.requested);
+    }
+}
+
+impl Locks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the resources, waiting for other writers to release them.
+    pub async fn lock(
+        &self,
+        writer: Writer,
+        resources: impl IntoIterator<Item = Resource>,
+    ) -> Guard<'_> {
+        let slots = self.slots_for(resources);
+        let mut guard = Guard {
+            locks: self,
+            requested: slots.iter().map(|(resource, _)| resource.clone()).collect(),
+            held: Vec::new(),
+        };
+        for (resource, slot) in slots {
+            let lock = slot.lock.clone().lock_owned().await;
+            *slot.holder.lock().unwrap_or_else(|e| e.into_inner()) = Some(writer);
+            guard.held.push((resource, slot, lock));
+        }
+        guard
+    }
+
+    /// Locks the resources or, if another writer holds any of them, fails with a
+    /// conflict error without locking any.
+    pub fn try_lock(
+        &self,
+        writer: Writer,
+        resources: impl IntoIterator<Item = Resource>,
+    ) -> Result<Guard<'_>, StorageErr> {
+        let slots = self.slots_for(resources);
+        let mut guard = Guard {
+            locks: self,
+            requested: slots.iter().map(|(resource, _)| resource.clone()).collect(),
+            held: Vec::new(),
+        };
+        for (resource, slot) in slots {
+            match slot.lock.clone().try_lock_owned() {
+                Ok(lock) => {
+                    *slot.holder.lock().unwrap_or_else(|e| e.into_inner()) = Some(writer);
+                    guard.held.push((resource, slot, lock));
+                }
+                Err(_) => {
+                    let holder = *slot.holder.lock().unwrap_or_else(|e| e.into_inner());
+                    // dropping the guard unlocks the resources locked so far
+                    return Err(StorageErr::ResourceConflictErr(ResourceConflictErr {
+                        resource,
+                        holder,
+                        trace: trace!(),
+                    }));
+                }
+            }
+        }
+        Ok(guard)
+    }
+
+    /// The writer holding the resource, if any.
+    pub fn holder(&self, resource: &Resource) -> Option<Writer> {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots
+            .get(resource)
+            .and_then(|slot| *slot.holder.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Number of resources currently locked or waited on.
+    pub fn len(&self) -> usize {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The slots of the resources, deduplicated and in locking order.
+    fn slots_for(
+        &self,
+        resources: impl IntoIterator<Item = Resource>,
+    ) -> Vec<(Resource, Arc<Slot>)> {
+        let resources = resources.into_iter().collect::<BTreeSet<_>>();
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        resources
+            .into_iter()
+            .map(|resource| {
+                let slot = slots.entry(resource.clone()).or_default().clone();
+                (resource, slot)
+            })
+            .collect()
+    }
+
+    /// Forgets the slots no writer holds or waits on so the map doesn't grow with
+    /// every resource ever locked.
+    fn release(&self, resources: &[Resource]) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        for resource in resources {
+            // the map holds one reference; a writer holding or waiting on the slot holds
+            // another
+            if slots
+                .get(resource)
+                .is_some_and(|slot| Arc::strong_count(slot) == 1)
+            {
+                slots.remove(resource);
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// internal crates
+use crate::filesys::{self, Overwrite, PathExt, WriteOptions};
+use crate::storage::errors::{MigrationErr, StorageErr};
+use crate::storage::layout::Layout;
+use crate::trace;
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{error, info};
+
+pub type FileFn = fn(&Layout) -> filesys::File;
+pub type DirFn = fn(&Layout) -> filesys::Dir;
+pub type TransformFn = fn(Value) -> Value;
+
+/// A single reversible operation on the storage layout. Every step is a no-op
+/// when its source is absent so that re-running a partially applied migration is
+/// safe.
+#[derive(Clone, Copy)]
+pub enum Step {
+    /// Renames a top-level field of a JSON object file.
+    RenameField {
+        file: FileFn,
+        from: &'static str,
+        to: &'static str,
+    },
+    /// Moves a file to a new location in the layout.
+    MoveFile { from: FileFn, to: FileFn },
+    /// Moves a directory to a new location in the layout.
+    MoveDir { from: DirFn, to: DirFn },
+    /// Re-encodes a JSON file. `down` must undo `up`.
+    Rewrite {
+        file: FileFn,
+        up: TransformFn,
+        down: TransformFn,
+    },
+}
+
+impl Step {
+    pub fn reversed(&self) -> Step {
+        match *self {
+            Step::RenameField { file, from, to } => Step::RenameField {
+                file,
+                from: to,
+                to: from,
+            },
+            Step::MoveFile { from, to } => Step::MoveFile { from: to, to: from },
+            Step::MoveDir { from, to } => Step::MoveDir { from: to, to: from },
+            Step::Rewrite { file, up, down } => Step::Rewrite {
+                file,
+                up: down,
+                down: up,
+            },
+        }
+    }
+
+    pub async fn apply(&self, layout: &Layout) -> Result<(), StorageErr> {
+        match self {
+            Step::RenameField { file, from, to } => {
+                let file = file(layout);
+                if !file.exists() {
+                    return Ok(());
+                }
+                let mut value = file.read_json::<Value>().await?;
+                let Some(obj) = value.as_object_mut() else {
+                    return Ok(());
+                };
+                let Some(field) = obj.remove(*from) else {
+                    return Ok(());
+                };
+                obj.insert(to.to_string(), field);
+                file.write_json(&value, WriteOptions::OVERWRITE_ATOMIC)
+                    .await?;
+            }
+            Step::MoveFile { from, to } => {
+                let src = from(layout);
+                if src.exists() {
+                    src.move_to(&to(layout), Overwrite::Deny).await?;
+                }
+            }
+            Step::MoveDir { from, to } => {
+                let src = from(layout);
+                if src.exists() {
+                    src.move_to(&to(layout), Overwrite::Deny).await?;
+                }
+            }
+            Step::Rewrite { file, up, .. } => {
+                let file = file(layout);
+                if !file.exists() {
+                    return Ok(());
+                }
+                let value = up(file.read_json::<Value>().await?);
+                file.write_json(&value, WriteOptions::OVERWRITE_ATOMIC)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An ordered set of steps identified by a unique, never reused id. Ids are
+/// applied in the order migrations appear in the registry.
+#[derive(Clone, Copy)]
+pub struct Migration {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// The migrations shipped with this agent version, oldest first. Append new
+/// migrations to the end; never edit or remove a migration once released.
+pub const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Applied {
+    pub id: String,
+    pub agent_version: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+pub async fn read_applied(file: &filesys::File) -> Result<Vec<Applied>, StorageErr> {
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(file.read_json::<Vec<Applied>>().await?)
+}
+
+async fn write_applied(file: &filesys::File, applied: &[Applied]) -> Result<(), StorageErr> {
+    file.write_json(&applied, WriteOptions::OVERWRITE_ATOMIC)
+        .await?;
+    Ok(())
+}
+
+/// Applies every migration which hasn't been recorded as applied yet and returns
+/// the ids of the newly applied migrations. The record is persisted after each
+/// migration so a crash never causes a completed migration to run twice. A
+/// migration that fails has its completed steps reverted before returning.
+pub async fn apply(
+    layout: &Layout,
+    migrations: &[Migration],
+    agent_version: &str,
+) -> Result<Vec<&'static str>, StorageErr> {
+    let record_file = layout.migrations();
+    let mut applied = read_applied(&record_file).await?;
+    let mut newly_applied = Vec::new();
+
+    for migration in migrations {
+        if applied.iter().any(|a| a.id == migration.id) {
+            continue;
+        }
+        info!(
+            "applying storage migration '{}': {}",
+            migration.id, migration.description
+        );
+        run_steps(layout, migration.id, migration.steps).await?;
+        applied.push(Applied {
+            id: migration.id.to_string(),
+            agent_version: agent_version.to_string(),
+            applied_at: Utc::now(),
+        });
+        write_applied(&record_file, &applied).await?;
+        newly_applied.push(migration.id);
+    }
+
+    Ok(newly_applied)
+}
+
+/// Reverts, newest first, every applied migration recorded after `target` (or
+/// all of them if `target` is `None`) and returns the ids of the reverted
+/// migrations. Recorded migrations unknown to `migrations` can't be reverted and
+/// stop the revert with an error.
+pub async fn revert(
+    layout: &Layout,
+    migrations: &[Migration],
+    target: Option<&str>,
+) -> Result<Vec<String>, StorageErr> {
+    let record_file = layout.migrations();
+    let mut applied = read_applied(&record_file).await?;
+    let keep = match target {
+        Some(id) => match applied.iter().position(|a| a.id == id) {
+            Some(i) => i + 1,
+            None => {
+                return Err(StorageErr::MigrationErr(MigrationErr {
+                    id: id.to_string(),
+                    msg: "migration has not been applied".to_string(),
+                    trace: trace!(),
+                }))
+            }
+        },
+        None => 0,
+    };
+
+    let mut reverted = Vec::new();
+    while applied.len() > keep {
+        let Some(record) = applied.last() else {
+            break;
+        };
+        let Some(migration) = migrations.iter().find(|m| m.id == record.id) else {
+            return Err(StorageErr::MigrationErr(MigrationErr {
+                id: record.id.clone(),
+                msg: "migration is unknown to this agent version".to_string(),
+                trace: trace!(),
+            }));
+        };
+        info!("reverting storage migration '{}'", migration.id);
+        let steps = migration
+            .steps
+            .iter()
+            .rev()
+            .map(Step::reversed)
+            .collect::<Vec<_>>();
+        run_steps(layout, migration.id, &steps).await?;
+        applied.pop();
+        write_applied(&record_file, &applied).await?;
+        reverted.push(migration.id.to_string());
+    }
+
+    Ok(reverted)
+}
+
+/// Reverts the migrations `agent_version` applied, which are the last ones recorded,
+/// so that the version it replaced finds the layout as it left it. Returns the ids of
+/// the reverted migrations.
+pub async fn revert_version(
+    layout: &Layout,
+    migrations: &[Migration],
+    agent_version: &str,
+) -> Result<Vec<String>, StorageErr> {
+    let applied = read_applied(&layout.migrations()).await?;
+    if applied
+        .last()
+        .is_none_or(|a| a.agent_version != agent_version)
+    {
+        return Ok(Vec::new());
+    }
+    let target = applied
+        .iter()
+        .rev()
+        .find(|a| a.agent_version != agent_version)
+        .map(|a| a.id.clone());
+    revert(layout, migrations, target.as_deref()).await
+}
+
+async fn run_steps(layout: &Layout, id: &str, steps: &[Step]) -> Result<(), StorageErr> {
+    for (i, step) in steps.iter().enumerate() {
+        let Err(e) = step.apply(layout).await else {
+            continue;
+        };
+        for done in steps[..i].iter().rev() {
+            if let Err(rollback_err) = done.reversed().apply(layout).await {
+                error!("failed to roll back step of storage migration '{id}': {rollback_err}");
+            }
+        }
+        return Err(StorageErr::MigrationErr(MigrationErr {
+            id: id.to_string(),
+            msg: e.to_string(),
+            trace: trace!(),
+        }));
+    }
+    Ok(())
+}
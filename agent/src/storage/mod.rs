@@ -7,37 +7,54 @@ pub mod config_instances;
 pub mod deployments;
 pub mod device;
 pub mod errors;
+pub mod fsck;
 pub mod git_commits;
 pub mod layout;
+pub mod locks;
+pub mod migrations;
 pub mod releases;
 pub mod settings;
 pub mod setup;
+pub mod wear;
 
-pub use self::config_instances::{CfgInstContent, CfgInsts};
+pub use self::config_instances::{CfgInstContent, CfgInsts, ConfigSchemas};
 pub use self::deployments::{Deployments, DplEntry};
 pub use self::device::{assert_activated, resolve_device_id, Device};
 pub use self::errors::{DeviceNotActivatedErr, StorageErr};
 pub use self::git_commits::GitCommits;
 pub use self::layout::Layout;
+pub use self::locks::Locks;
 pub use self::releases::Releases;
-pub use self::settings::{Backend, MQTTBroker, Settings};
+pub use self::settings::{
+    Backend, Cell, ContentCache, ContentWarming, CrashReports, DeploymentDir, DiskGuard, Hooks,
+    KeyBackend, KeyStorage, LoadShedding, LongPoll, MQTTBroker, MQTTConnection, MQTTQoS,
+    MQTTTransport, Metrics, Network, Notifications, Poller, Reboot, ReleaseSigning, SafeMode,
+    SelfUpdate, Settings, Startup, Supervisor, SyncBackoff, SyncHistory, Telemetry,
+    TelemetryTransport, TokenRefresh, Trash, TrustedReleaseKey, Wear, HTTP,
+};
 pub use crate::network::{BackendUrl, MqttHost};
 
 use self::device::Device as DeviceStorage;
 use self::errors::StorageErr as StorErr;
 use self::layout::Layout as StorLayout;
-use crate::filesys::Overwrite;
+use crate::audit;
+use crate::filesys::{self, Overwrite, PathExt};
+use crate::journal::{self, Journal};
 use crate::models;
 
-use tracing::info;
+use tracing::{error, info};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Capacities {
     pub cfg_insts: usize,
     pub cfg_inst_content: usize,
+    pub config_schemas: usize,
     pub deployments: usize,
     pub releases: usize,
     pub git_commits: usize,
+    /// The telemetry batches the request journal keeps while the backend is
+    /// unreachable.
+    pub telemetry_batches: usize,
 }
 
 impl Default for Capacities {
@@ -45,9 +62,11 @@ impl Default for Capacities {
         Self {
             cfg_insts: 1000,
             cfg_inst_content: 1000,
+            config_schemas: 100,
             deployments: 100,
             releases: 1000,
             git_commits: 100,
+            telemetry_batches: 60,
         }
     }
 }
@@ -56,11 +75,13 @@ impl Default for Capacities {
 pub struct CfgInstStor {
     pub meta: Arc<CfgInsts>,
     pub content: Arc<CfgInstContent>,
+    pub schemas: Arc<ConfigSchemas>,
 }
 
 pub struct CfgInstRef<'a> {
     pub meta: &'a CfgInsts,
     pub content: &'a CfgInstContent,
+    pub schemas: &'a ConfigSchemas,
 }
 
 impl CfgInstStor {
@@ -68,6 +89,7 @@ impl CfgInstStor {
         CfgInstRef {
             meta: &self.meta,
             content: &self.content,
+            schemas: &self.schemas,
         }
     }
 }
@@ -79,6 +101,14 @@ pub struct Storage {
     pub deployments: Arc<Deployments>,
     pub releases: Arc<Releases>,
     pub git_commits: Arc<GitCommits>,
+    pub journal: Arc<Journal>,
+    pub wear: Arc<wear::Meter>,
+    pub config_access: Arc<audit::AccessLog>,
+    /// Serializes the syncer's and local API's read-modify-writes of the same
+    /// resources.
+    pub locks: Arc<Locks>,
+    /// What the startup sweep of stale trash and temp artifacts removed.
+    pub sweep: filesys::sweep::Report,
 }
 
 impl Storage {
@@ -87,6 +117,9 @@ impl Storage {
         capacities: Capacities,
         device_id: String,
     ) -> Result<(Storage, impl Future<Output = ()>), StorErr> {
+        // artifacts left behind by operations a crash interrupted
+        let sweep = sweep(layout).await;
+
         // device storage
         let (device_storage, device_storage_handle) = DeviceStorage::spawn_with_default(
             64,
@@ -123,6 +156,11 @@ impl Storage {
         .await?;
         let cfg_inst_content = Arc::new(cfg_inst_content_stor);
 
+        // config schemas
+        let (config_schema_stor, config_schema_stor_handle) =
+            ConfigSchemas::spawn(64, layout.config_schemas(), capacities.config_schemas).await?;
+        let config_schemas = Arc::new(config_schema_stor);
+
         // deployments
         let (deployment_stor, deployment_stor_handle) =
             Deployments::spawn(64, layout.deployments(), capacities.deployments).await?;
@@ -139,11 +177,37 @@ impl Storage {
             GitCommits::spawn(64, layout.git_commits(), capacities.git_commits).await?;
         let git_commits = Arc::new(git_commit_stor);
 
+        // device-originated requests queued while the backend is unreachable
+        let journal_options = journal::Options::default();
+        #[cfg(feature = "telemetry")]
+        let journal_options = {
+            let mut options = journal_options;
+            let retention = journal::Retention {
+                max_entries: capacities.telemetry_batches,
+                ..options.retention(journal::Kind::Telemetry)
+            };
+            options
+                .retention
+                .insert(journal::Kind::Telemetry, retention);
+            options
+        };
+        let journal = Arc::new(Journal::init(layout.journal(), journal_options).await?);
+
+        // bytes written by the agent since install
+        filesys::wear::set_data_dir(layout.root().path());
+        let wear = Arc::new(wear::Meter::init(layout.wear()).await);
+
+        // which local clients read which config instances
+        let config_access = Arc::new(
+            audit::AccessLog::init(layout.config_access(), audit::Options::default()).await,
+        );
+
         let shutdown_handle = async move {
             let handles = vec![
                 device_storage_handle,
                 cfg_inst_stor_handle,
                 cfg_inst_content_stor_handle,
+                config_schema_stor_handle,
                 deployment_stor_handle,
                 release_stor_handle,
                 git_commit_stor_handle,
@@ -158,10 +222,16 @@ impl Storage {
                 cfg_insts: CfgInstStor {
                     meta: cfg_inst_metadata,
                     content: cfg_inst_content,
+                    schemas: config_schemas,
                 },
                 deployments,
                 releases,
                 git_commits,
+                journal,
+                wear,
+                config_access,
+                locks: Arc::new(Locks::new()),
+                sweep,
             },
             shutdown_handle,
         ))
@@ -185,9 +255,16 @@ impl Storage {
         self.device.shutdown().await?;
         self.cfg_insts.meta.shutdown().await?;
         self.cfg_insts.content.shutdown().await?;
+        self.cfg_insts.schemas.shutdown().await?;
         self.deployments.shutdown().await?;
         self.releases.shutdown().await?;
         self.git_commits.shutdown().await?;
+        if let Err(e) = self.wear.persist().await {
+            error!("failed to persist storage wear totals: {e}");
+        }
+        if let Err(e) = self.config_access.persist().await {
+            error!("failed to persist config access log: {e}");
+        }
 
         Ok(())
     }
@@ -196,6 +273,19 @@ impl Storage {
 /// Resets retry state (attempts, cooldown) for all persisted deployments so
 /// they are retried immediately after an agent restart. The most common
 /// reason for a restart is "I fixed the problem, retry now."
+async fn sweep(layout: &StorLayout) -> filesys::sweep::Report {
+    let opts = filesys::sweep::Options::default();
+    let mut report = filesys::sweep::sweep(&layout.root(), opts).await;
+    report.merge(filesys::sweep::sweep_all(&layout.temp_dir(), opts).await);
+    if report.removed > 0 || report.failed > 0 {
+        info!(
+            "Swept {} stale artifacts ({} bytes reclaimed, {} failed)",
+            report.removed, report.reclaimed_bytes, report.failed
+        );
+    }
+    report
+}
+
 async fn reset_deployment_retry_state(deployments: &Deployments) -> Result<(), StorErr> {
     let entries = deployments
         .find_entries_where(|e| !e.value.has_clean_retry_state())
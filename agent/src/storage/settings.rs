@@ -1,21 +1,76 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 // internal crates
+use crate::app::{safe_mode, startup};
+use crate::cache::admission;
+use crate::cell;
+use crate::config::units;
+use crate::cooldown;
+use crate::crypt::{digest, keystore};
+use crate::deploy::{hooks, reboot, schedule, signature, space, trash, versions};
 use crate::deserialize_warn;
-use crate::logs::LogLevel;
-use crate::network::{BackendUrl, MqttHost};
+use crate::filesys;
+use crate::http::priority;
+use crate::logs::{self, LogLevel};
+use crate::network::{egress, BackendUrl, MqttHost};
+use crate::notifications::Sink;
+use crate::supervisor::{health, process};
+use crate::sync::{backoff, event_log, history, network, warming};
+use crate::workers::{long_poll, notifications, poller, supervisor, token_refresh, updater, wear};
 
 // external crates
+use chrono::TimeDelta;
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::{error, warn};
 
 #[derive(Debug, Serialize, PartialEq, Eq)]
 pub struct Settings {
     pub log_level: LogLevel,
     pub backend: Backend,
     pub mqtt_broker: MQTTBroker,
+    pub mqtt_connection: MQTTConnection,
     pub is_persistent: bool,
     pub enable_socket_server: bool,
     pub enable_mqtt_worker: bool,
     pub enable_poller: bool,
+    pub poller: Poller,
+    /// Long polls the backend for sync requests. Off by default since MQTT delivers
+    /// them with less overhead where it's reachable.
+    pub enable_long_poll: bool,
+    pub long_poll: LongPoll,
+    pub http: HTTP,
+    pub reboot: Reboot,
+    pub hooks: Hooks,
+    /// Validates config instance content against its config schema before it's
+    /// deployed, see [crate::deploy::schema].
+    pub validate_config_schemas: bool,
+    pub deployment_dir: DeploymentDir,
+    pub notifications: Notifications,
+    pub safe_mode: SafeMode,
+    pub wear: Wear,
+    pub content_cache: ContentCache,
+    pub content_warming: ContentWarming,
+    pub metrics: Metrics,
+    pub sync_history: SyncHistory,
+    pub sync_backoff: SyncBackoff,
+    pub startup: Startup,
+    pub cell: Cell,
+    pub network: Network,
+    pub trash: Trash,
+    pub load_shedding: LoadShedding,
+    /// Replaces the device id and host name in exported diagnostics with stable
+    /// anonymized hashes, see [crate::crypt::salt].
+    pub privacy_mode: bool,
+    pub token_refresh: TokenRefresh,
+    pub key_storage: KeyStorage,
+    pub self_update: SelfUpdate,
+    pub telemetry: Telemetry,
+    pub disk_guard: DiskGuard,
+    pub supervisor: Supervisor,
+    pub release_signing: ReleaseSigning,
+    pub crash_reports: CrashReports,
 }
 
 impl Default for Settings {
@@ -24,10 +79,41 @@ impl Default for Settings {
             log_level: LogLevel::Info,
             backend: Backend::default(),
             mqtt_broker: MQTTBroker::default(),
+            mqtt_connection: MQTTConnection::default(),
             is_persistent: true,
             enable_socket_server: true,
             enable_mqtt_worker: true,
             enable_poller: true,
+            poller: Poller::default(),
+            enable_long_poll: false,
+            long_poll: LongPoll::default(),
+            http: HTTP::default(),
+            reboot: Reboot::default(),
+            hooks: Hooks::default(),
+            validate_config_schemas: true,
+            deployment_dir: DeploymentDir::default(),
+            notifications: Notifications::default(),
+            safe_mode: SafeMode::default(),
+            wear: Wear::default(),
+            content_cache: ContentCache::default(),
+            content_warming: ContentWarming::default(),
+            metrics: Metrics::default(),
+            sync_history: SyncHistory::default(),
+            sync_backoff: SyncBackoff::default(),
+            startup: Startup::default(),
+            cell: Cell::default(),
+            network: Network::default(),
+            trash: Trash::default(),
+            load_shedding: LoadShedding::default(),
+            privacy_mode: false,
+            token_refresh: TokenRefresh::default(),
+            key_storage: KeyStorage::default(),
+            self_update: SelfUpdate::default(),
+            telemetry: Telemetry::default(),
+            disk_guard: DiskGuard::default(),
+            supervisor: Supervisor::default(),
+            release_signing: ReleaseSigning::default(),
+            crash_reports: CrashReports::default(),
         }
     }
 }
@@ -42,10 +128,41 @@ impl<'de> Deserialize<'de> for Settings {
             log_level: Option<LogLevel>,
             backend: Option<Backend>,
             mqtt_broker: Option<MQTTBroker>,
+            mqtt_connection: Option<MQTTConnection>,
             is_persistent: Option<bool>,
             enable_socket_server: Option<bool>,
             enable_mqtt_worker: Option<bool>,
             enable_poller: Option<bool>,
+            poller: Option<Poller>,
+            enable_long_poll: Option<bool>,
+            long_poll: Option<LongPoll>,
+            http: Option<HTTP>,
+            reboot: Option<Reboot>,
+            hooks: Option<Hooks>,
+            validate_config_schemas: Option<bool>,
+            deployment_dir: Option<DeploymentDir>,
+            notifications: Option<Notifications>,
+            safe_mode: Option<SafeMode>,
+            wear: Option<Wear>,
+            content_cache: Option<ContentCache>,
+            content_warming: Option<ContentWarming>,
+            metrics: Option<Metrics>,
+            sync_history: Option<SyncHistory>,
+            sync_backoff: Option<SyncBackoff>,
+            startup: Option<Startup>,
+            cell: Option<Cell>,
+            network: Option<Network>,
+            trash: Option<Trash>,
+            load_shedding: Option<LoadShedding>,
+            privacy_mode: Option<bool>,
+            token_refresh: Option<TokenRefresh>,
+            key_storage: Option<KeyStorage>,
+            self_update: Option<SelfUpdate>,
+            telemetry: Option<Telemetry>,
+            disk_guard: Option<DiskGuard>,
+            supervisor: Option<Supervisor>,
+            release_signing: Option<ReleaseSigning>,
+            crash_reports: Option<CrashReports>,
         }
 
         let default = Settings::default();
@@ -68,6 +185,9 @@ impl<'de> Deserialize<'de> for Settings {
             mqtt_broker: result.mqtt_broker.unwrap_or_else(|| {
                 deserialize_warn!("settings", "mqtt_broker", default.mqtt_broker)
             }),
+            mqtt_connection: result.mqtt_connection.unwrap_or_else(|| {
+                deserialize_warn!("settings", "mqtt_connection", default.mqtt_connection)
+            }),
             is_persistent: result.is_persistent.unwrap_or_else(|| {
                 deserialize_warn!("settings", "is_persistent", default.is_persistent)
             }),
@@ -84,6 +204,100 @@ impl<'de> Deserialize<'de> for Settings {
             enable_poller: result.enable_poller.unwrap_or_else(|| {
                 deserialize_warn!("settings", "enable_poller", default.enable_poller)
             }),
+            poller: result
+                .poller
+                .unwrap_or_else(|| deserialize_warn!("settings", "poller", default.poller)),
+            enable_long_poll: result.enable_long_poll.unwrap_or_else(|| {
+                deserialize_warn!("settings", "enable_long_poll", default.enable_long_poll)
+            }),
+            long_poll: result
+                .long_poll
+                .unwrap_or_else(|| deserialize_warn!("settings", "long_poll", default.long_poll)),
+            http: result
+                .http
+                .unwrap_or_else(|| deserialize_warn!("settings", "http", default.http)),
+            reboot: result
+                .reboot
+                .unwrap_or_else(|| deserialize_warn!("settings", "reboot", default.reboot)),
+            hooks: result
+                .hooks
+                .unwrap_or_else(|| deserialize_warn!("settings", "hooks", default.hooks)),
+            validate_config_schemas: result.validate_config_schemas.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "settings",
+                    "validate_config_schemas",
+                    default.validate_config_schemas
+                )
+            }),
+            deployment_dir: result.deployment_dir.unwrap_or_else(|| {
+                deserialize_warn!("settings", "deployment_dir", default.deployment_dir)
+            }),
+            notifications: result.notifications.unwrap_or_else(|| {
+                deserialize_warn!("settings", "notifications", default.notifications)
+            }),
+            safe_mode: result
+                .safe_mode
+                .unwrap_or_else(|| deserialize_warn!("settings", "safe_mode", default.safe_mode)),
+            wear: result
+                .wear
+                .unwrap_or_else(|| deserialize_warn!("settings", "wear", default.wear)),
+            content_cache: result.content_cache.unwrap_or_else(|| {
+                deserialize_warn!("settings", "content_cache", default.content_cache)
+            }),
+            content_warming: result.content_warming.unwrap_or_else(|| {
+                deserialize_warn!("settings", "content_warming", default.content_warming)
+            }),
+            metrics: result
+                .metrics
+                .unwrap_or_else(|| deserialize_warn!("settings", "metrics", default.metrics)),
+            sync_history: result.sync_history.unwrap_or_else(|| {
+                deserialize_warn!("settings", "sync_history", default.sync_history)
+            }),
+            sync_backoff: result.sync_backoff.unwrap_or_else(|| {
+                deserialize_warn!("settings", "sync_backoff", default.sync_backoff)
+            }),
+            startup: result
+                .startup
+                .unwrap_or_else(|| deserialize_warn!("settings", "startup", default.startup)),
+            cell: result
+                .cell
+                .unwrap_or_else(|| deserialize_warn!("settings", "cell", default.cell)),
+            network: result
+                .network
+                .unwrap_or_else(|| deserialize_warn!("settings", "network", default.network)),
+            trash: result
+                .trash
+                .unwrap_or_else(|| deserialize_warn!("settings", "trash", default.trash)),
+            load_shedding: result.load_shedding.unwrap_or_else(|| {
+                deserialize_warn!("settings", "load_shedding", default.load_shedding)
+            }),
+            privacy_mode: result.privacy_mode.unwrap_or_else(|| {
+                deserialize_warn!("settings", "privacy_mode", default.privacy_mode)
+            }),
+            token_refresh: result.token_refresh.unwrap_or_else(|| {
+                deserialize_warn!("settings", "token_refresh", default.token_refresh)
+            }),
+            key_storage: result.key_storage.unwrap_or_else(|| {
+                deserialize_warn!("settings", "key_storage", default.key_storage)
+            }),
+            self_update: result.self_update.unwrap_or_else(|| {
+                deserialize_warn!("settings", "self_update", default.self_update)
+            }),
+            telemetry: result
+                .telemetry
+                .unwrap_or_else(|| deserialize_warn!("settings", "telemetry", default.telemetry)),
+            disk_guard: result
+                .disk_guard
+                .unwrap_or_else(|| deserialize_warn!("settings", "disk_guard", default.disk_guard)),
+            supervisor: result
+                .supervisor
+                .unwrap_or_else(|| deserialize_warn!("settings", "supervisor", default.supervisor)),
+            release_signing: result.release_signing.unwrap_or_else(|| {
+                deserialize_warn!("settings", "release_signing", default.release_signing)
+            }),
+            crash_reports: result.crash_reports.unwrap_or_else(|| {
+                deserialize_warn!("settings", "crash_reports", default.crash_reports)
+            }),
         })
     }
 }
@@ -154,3 +368,2321 @@ impl<'de> Deserialize<'de> for MQTTBroker {
         Ok(MQTTBroker { host })
     }
 }
+
+/// How the MQTT worker connects to the broker, for tuning it to constrained networks.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct MQTTConnection {
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub keep_alive_secs: u64,
+    /// Whether the broker discards the session on disconnect. A persistent session
+    /// queues the messages published while the device is offline. MQTT 3.1.1 has no
+    /// session expiry, so the broker keeps it until the agent connects with a clean
+    /// session again.
+    pub clean_session: bool,
+    /// The most outgoing messages awaiting acknowledgement.
+    pub max_inflight: u16,
+    pub qos: MQTTQoS,
+    /// The backoff between reconnects after errors.
+    pub reconnect_backoff: cooldown::Backoff,
+    pub transport: MQTTTransport,
+    /// The port of the broker's WebSocket endpoint.
+    pub websocket_port: u16,
+    /// The path of the broker's WebSocket endpoint.
+    pub websocket_path: String,
+    /// The failed connection attempts in a row after which the `auto` transport
+    /// switches to WebSockets.
+    pub websocket_after_failures: u32,
+}
+
+impl Default for MQTTConnection {
+    fn default() -> Self {
+        Self {
+            keep_alive_secs: 20,
+            clean_session: true,
+            max_inflight: 100,
+            qos: MQTTQoS::default(),
+            reconnect_backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: 5 * 60,
+            },
+            transport: MQTTTransport::default(),
+            websocket_port: 443,
+            websocket_path: "/mqtt".to_string(),
+            websocket_after_failures: 3,
+        }
+    }
+}
+
+impl MQTTConnection {
+    #[cfg(feature = "mqtt")]
+    pub fn worker_options(&self) -> crate::workers::mqtt::Options {
+        crate::workers::mqtt::Options {
+            backoff: self.reconnect_backoff,
+            // rumqttc rejects keep alives under a second
+            keep_alive: Duration::from_secs(self.keep_alive_secs.max(1)),
+            qos: self.qos.levels(),
+            clean_session: self.clean_session,
+            max_inflight: self.max_inflight.max(1),
+            websocket: self.websocket_fallback(),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "mqtt")]
+    fn websocket_fallback(&self) -> Option<crate::workers::mqtt::WebSocketFallback> {
+        let after_failures = match self.transport {
+            MQTTTransport::Tcp => return None,
+            MQTTTransport::Auto => self.websocket_after_failures.max(1),
+            MQTTTransport::WebSocket => 0,
+        };
+        Some(crate::workers::mqtt::WebSocketFallback {
+            options: crate::mqtt::websocket::Options {
+                port: self.websocket_port,
+                path: self.websocket_path.clone(),
+                ..Default::default()
+            },
+            after_failures,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for MQTTConnection {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeMQTTConnection {
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            keep_alive_secs: Option<u64>,
+            clean_session: Option<bool>,
+            max_inflight: Option<u16>,
+            qos: Option<MQTTQoS>,
+            reconnect_backoff: Option<cooldown::Backoff>,
+            transport: Option<MQTTTransport>,
+            websocket_port: Option<u16>,
+            websocket_path: Option<String>,
+            websocket_after_failures: Option<u32>,
+        }
+
+        let default = MQTTConnection::default();
+
+        let result = match DeserializeMQTTConnection::deserialize(deserializer) {
+            Ok(mqtt_connection) => mqtt_connection,
+            Err(e) => {
+                error!("error deserializing mqtt connection settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(MQTTConnection {
+            keep_alive_secs: result.keep_alive_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "mqtt_connection",
+                    "keep_alive_secs",
+                    default.keep_alive_secs
+                )
+            }),
+            clean_session: result.clean_session.unwrap_or_else(|| {
+                deserialize_warn!("mqtt_connection", "clean_session", default.clean_session)
+            }),
+            max_inflight: result.max_inflight.unwrap_or_else(|| {
+                deserialize_warn!("mqtt_connection", "max_inflight", default.max_inflight)
+            }),
+            qos: result
+                .qos
+                .unwrap_or_else(|| deserialize_warn!("mqtt_connection", "qos", default.qos)),
+            reconnect_backoff: result.reconnect_backoff.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "mqtt_connection",
+                    "reconnect_backoff",
+                    default.reconnect_backoff
+                )
+            }),
+            transport: result.transport.unwrap_or_else(|| {
+                deserialize_warn!("mqtt_connection", "transport", default.transport)
+            }),
+            websocket_port: result.websocket_port.unwrap_or_else(|| {
+                deserialize_warn!("mqtt_connection", "websocket_port", default.websocket_port)
+            }),
+            websocket_path: result.websocket_path.unwrap_or_else(|| {
+                deserialize_warn!("mqtt_connection", "websocket_path", default.websocket_path)
+            }),
+            websocket_after_failures: result.websocket_after_failures.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "mqtt_connection",
+                    "websocket_after_failures",
+                    default.websocket_after_failures
+                )
+            }),
+        })
+    }
+}
+
+/// How the MQTT worker reaches the broker. `auto` connects over MQTT and switches to
+/// WebSockets on port 443 if the MQTT port seems blocked, which it stays on until the
+/// agent restarts.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MQTTTransport {
+    #[default]
+    Auto,
+    Tcp,
+    #[serde(rename = "websocket")]
+    WebSocket,
+}
+
+impl<'de> Deserialize<'de> for MQTTTransport {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let default = MQTTTransport::default();
+        let transport = String::deserialize(deserializer)?;
+        match transport.to_lowercase().as_str() {
+            "auto" => Ok(MQTTTransport::Auto),
+            "tcp" => Ok(MQTTTransport::Tcp),
+            "websocket" | "websockets" => Ok(MQTTTransport::WebSocket),
+            _ => {
+                warn!("invalid mqtt transport `{transport}`, using default: {default:?}");
+                Ok(default)
+            }
+        }
+    }
+}
+
+/// The QoS (0, 1 or 2) of each class of MQTT topic. See [crate::mqtt::options::QoSLevels].
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct MQTTQoS {
+    pub sync: u8,
+    pub ping: u8,
+    pub commands: u8,
+    pub state: u8,
+}
+
+impl Default for MQTTQoS {
+    fn default() -> Self {
+        Self {
+            sync: 1,
+            ping: 1,
+            commands: 1,
+            state: 1,
+        }
+    }
+}
+
+impl MQTTQoS {
+    #[cfg(feature = "mqtt")]
+    pub fn levels(&self) -> crate::mqtt::options::QoSLevels {
+        fn qos(level: u8) -> rumqttc::QoS {
+            match level {
+                0 => rumqttc::QoS::AtMostOnce,
+                2 => rumqttc::QoS::ExactlyOnce,
+                _ => rumqttc::QoS::AtLeastOnce,
+            }
+        }
+        crate::mqtt::options::QoSLevels {
+            sync: qos(self.sync),
+            ping: qos(self.ping),
+            commands: qos(self.commands),
+            state: qos(self.state),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MQTTQoS {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeMQTTQoS {
+            sync: Option<u8>,
+            ping: Option<u8>,
+            commands: Option<u8>,
+            state: Option<u8>,
+        }
+
+        let default = MQTTQoS::default();
+
+        let result = match DeserializeMQTTQoS::deserialize(deserializer) {
+            Ok(qos) => qos,
+            Err(e) => {
+                error!("error deserializing mqtt qos settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        let level = |field: &str, value: Option<u8>, default: u8| match value {
+            Some(value) if value <= 2 => value,
+            Some(value) => {
+                warn!("'{field}' in 'mqtt_connection.qos' must be 0, 1 or 2, not {value}, setting to default: '{default}'");
+                default
+            }
+            None => deserialize_warn!("mqtt_connection.qos", field, default),
+        };
+        Ok(MQTTQoS {
+            sync: level("sync", result.sync, default.sync),
+            ping: level("ping", result.ping, default.ping),
+            commands: level("commands", result.commands, default.commands),
+            state: level("state", result.state, default.state),
+        })
+    }
+}
+
+/// When the poller syncs with the backend and when the deployments it pulls may be
+/// applied.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Poller {
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub interval_secs: u64,
+    /// Up to this long is added to each interval at random.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub jitter_secs: u64,
+    /// Cron-like schedules of the minutes deployments may be applied in, see
+    /// [crate::deploy::schedule]. Deployments are applied at any time if empty.
+    pub maintenance_windows: Vec<schedule::Schedule>,
+}
+
+impl Default for Poller {
+    fn default() -> Self {
+        let options = poller::Options::default();
+        Self {
+            interval_secs: options.poll_interval_secs as u64,
+            jitter_secs: options.jitter_secs as u64,
+            maintenance_windows: Vec::new(),
+        }
+    }
+}
+
+impl Poller {
+    pub fn worker_options(&self) -> poller::Options {
+        poller::Options {
+            poll_interval_secs: self.interval_secs.clamp(1, i64::MAX as u64) as i64,
+            jitter_secs: self.jitter_secs.min(i64::MAX as u64) as i64,
+        }
+    }
+
+    pub fn windows(&self) -> schedule::Windows {
+        schedule::Windows::new(self.maintenance_windows.clone())
+    }
+}
+
+impl<'de> Deserialize<'de> for Poller {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializePoller {
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            interval_secs: Option<u64>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            jitter_secs: Option<u64>,
+            maintenance_windows: Option<Vec<schedule::Schedule>>,
+        }
+
+        let default = Poller::default();
+
+        let result = match DeserializePoller::deserialize(deserializer) {
+            Ok(poller) => poller,
+            Err(e) => {
+                error!("error deserializing poller settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Poller {
+            interval_secs: result.interval_secs.unwrap_or_else(|| {
+                deserialize_warn!("poller", "interval_secs", default.interval_secs)
+            }),
+            jitter_secs: result
+                .jitter_secs
+                .unwrap_or_else(|| deserialize_warn!("poller", "jitter_secs", default.jitter_secs)),
+            // no maintenance windows applies deployments at any time
+            maintenance_windows: result.maintenance_windows.unwrap_or_default(),
+        })
+    }
+}
+
+/// Long polling the backend for sync requests, for networks where MQTT is blocked and
+/// frequent polling is too costly.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct LongPoll {
+    /// How long the backend may hold each poll open.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub wait_secs: u64,
+    /// The shortest time between the starts of consecutive polls.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub min_interval_secs: u64,
+}
+
+impl Default for LongPoll {
+    fn default() -> Self {
+        let options = long_poll::Options::default();
+        Self {
+            wait_secs: options.wait.as_secs(),
+            min_interval_secs: options.min_interval.as_secs(),
+        }
+    }
+}
+
+impl LongPoll {
+    pub fn options(&self) -> long_poll::Options {
+        long_poll::Options {
+            wait: Duration::from_secs(self.wait_secs),
+            min_interval: Duration::from_secs(self.min_interval_secs),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LongPoll {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeLongPoll {
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            wait_secs: Option<u64>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            min_interval_secs: Option<u64>,
+        }
+
+        let default = LongPoll::default();
+
+        let result = match DeserializeLongPoll::deserialize(deserializer) {
+            Ok(long_poll) => long_poll,
+            Err(e) => {
+                error!("error deserializing long poll settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(LongPoll {
+            wait_secs: result
+                .wait_secs
+                .unwrap_or_else(|| deserialize_warn!("long_poll", "wait_secs", default.wait_secs)),
+            min_interval_secs: result.min_interval_secs.unwrap_or_else(|| {
+                deserialize_warn!("long_poll", "min_interval_secs", default.min_interval_secs)
+            }),
+        })
+    }
+}
+
+/// Scheduling of outbound HTTP requests by priority class.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct HTTP {
+    pub max_concurrent_requests: usize,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub starvation_timeout_secs: u64,
+}
+
+impl Default for HTTP {
+    fn default() -> Self {
+        let options = priority::Options::default();
+        Self {
+            max_concurrent_requests: options.max_concurrent,
+            starvation_timeout_secs: options.starvation_timeout.as_secs(),
+        }
+    }
+}
+
+impl HTTP {
+    pub fn scheduling(&self) -> priority::Options {
+        priority::Options {
+            max_concurrent: self.max_concurrent_requests,
+            starvation_timeout: Duration::from_secs(self.starvation_timeout_secs),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HTTP {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeHTTP {
+            max_concurrent_requests: Option<usize>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            starvation_timeout_secs: Option<u64>,
+        }
+
+        let default = HTTP::default();
+
+        let result = match DeserializeHTTP::deserialize(deserializer) {
+            Ok(http) => http,
+            Err(e) => {
+                error!("error deserializing http settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(HTTP {
+            max_concurrent_requests: result.max_concurrent_requests.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "http",
+                    "max_concurrent_requests",
+                    default.max_concurrent_requests
+                )
+            }),
+            starvation_timeout_secs: result.starvation_timeout_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "http",
+                    "starvation_timeout_secs",
+                    default.starvation_timeout_secs
+                )
+            }),
+        })
+    }
+}
+
+/// Coordination of host reboots for deployments which only take effect after one.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Reboot {
+    pub paths: Vec<String>,
+    pub authorized: bool,
+    /// Cron-like schedules of the minutes the host may be rebooted in, as for the
+    /// poller's. The host may be rebooted at any time if empty.
+    pub maintenance_windows: Vec<schedule::Schedule>,
+    pub command: Vec<String>,
+}
+
+impl Default for Reboot {
+    fn default() -> Self {
+        let options = reboot::Options::default();
+        Self {
+            paths: options.paths,
+            authorized: options.authorized,
+            maintenance_windows: options.windows.schedules,
+            command: options.command,
+        }
+    }
+}
+
+impl Reboot {
+    pub fn options(&self) -> reboot::Options {
+        reboot::Options {
+            paths: self.paths.clone(),
+            authorized: self.authorized,
+            windows: schedule::Windows::new(self.maintenance_windows.clone()),
+            command: self.command.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Reboot {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeReboot {
+            paths: Option<Vec<String>>,
+            authorized: Option<bool>,
+            maintenance_windows: Option<Vec<schedule::Schedule>>,
+            command: Option<Vec<String>>,
+        }
+
+        let default = Reboot::default();
+
+        let result = match DeserializeReboot::deserialize(deserializer) {
+            Ok(reboot) => reboot,
+            Err(e) => {
+                error!("error deserializing reboot settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Reboot {
+            paths: result
+                .paths
+                .unwrap_or_else(|| deserialize_warn!("reboot", "paths", default.paths)),
+            authorized: result
+                .authorized
+                .unwrap_or_else(|| deserialize_warn!("reboot", "authorized", default.authorized)),
+            // no maintenance windows allows rebooting at any time
+            maintenance_windows: result.maintenance_windows.unwrap_or_default(),
+            command: result
+                .command
+                .unwrap_or_else(|| deserialize_warn!("reboot", "command", default.command)),
+        })
+    }
+}
+
+/// Commands run before and after a deployment's files are written or removed, see
+/// [crate::deploy::hooks]. No hooks run by default.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Hooks {
+    pub pre_deploy: Vec<String>,
+    pub post_deploy: Vec<String>,
+    pub pre_remove: Vec<String>,
+    pub post_remove: Vec<String>,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub timeout_secs: u64,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        let options = hooks::Options::default();
+        Self {
+            pre_deploy: options.pre_deploy,
+            post_deploy: options.post_deploy,
+            pre_remove: options.pre_remove,
+            post_remove: options.post_remove,
+            timeout_secs: options.timeout.as_secs(),
+        }
+    }
+}
+
+impl Hooks {
+    pub fn options(&self) -> hooks::Options {
+        hooks::Options {
+            pre_deploy: self.pre_deploy.clone(),
+            post_deploy: self.post_deploy.clone(),
+            pre_remove: self.pre_remove.clone(),
+            post_remove: self.post_remove.clone(),
+            timeout: Duration::from_secs(self.timeout_secs.max(1)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Hooks {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeHooks {
+            pre_deploy: Option<Vec<String>>,
+            post_deploy: Option<Vec<String>>,
+            pre_remove: Option<Vec<String>>,
+            post_remove: Option<Vec<String>>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            timeout_secs: Option<u64>,
+        }
+
+        let default = Hooks::default();
+
+        let result = match DeserializeHooks::deserialize(deserializer) {
+            Ok(hooks) => hooks,
+            Err(e) => {
+                error!("error deserializing hooks settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Hooks {
+            pre_deploy: result
+                .pre_deploy
+                .unwrap_or_else(|| deserialize_warn!("hooks", "pre_deploy", default.pre_deploy)),
+            post_deploy: result
+                .post_deploy
+                .unwrap_or_else(|| deserialize_warn!("hooks", "post_deploy", default.post_deploy)),
+            pre_remove: result
+                .pre_remove
+                .unwrap_or_else(|| deserialize_warn!("hooks", "pre_remove", default.pre_remove)),
+            post_remove: result
+                .post_remove
+                .unwrap_or_else(|| deserialize_warn!("hooks", "post_remove", default.post_remove)),
+            timeout_secs: result.timeout_secs.unwrap_or_else(|| {
+                deserialize_warn!("hooks", "timeout_secs", default.timeout_secs)
+            }),
+        })
+    }
+}
+
+/// The directory config instances under its `current` link are deployed into as
+/// versioned releases, see [crate::deploy::versions]. Unset by default, which writes
+/// every config instance in place.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct DeploymentDir {
+    pub path: Option<String>,
+    /// How many releases are kept, the live one included, to roll back to.
+    pub keep: usize,
+}
+
+impl Default for DeploymentDir {
+    fn default() -> Self {
+        Self {
+            path: None,
+            keep: versions::DEFAULT_KEEP,
+        }
+    }
+}
+
+impl DeploymentDir {
+    pub fn versions(&self) -> Option<versions::Versions> {
+        self.path
+            .as_ref()
+            .map(|path| versions::Versions::new(filesys::Dir::new(path)).with_keep(self.keep))
+    }
+}
+
+impl<'de> Deserialize<'de> for DeploymentDir {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeDeploymentDir {
+            path: Option<String>,
+            keep: Option<usize>,
+        }
+
+        let result = match DeserializeDeploymentDir::deserialize(deserializer) {
+            Ok(deployment_dir) => deployment_dir,
+            Err(e) => {
+                error!("error deserializing deployment_dir settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        let default = DeploymentDir::default();
+        Ok(DeploymentDir {
+            path: result.path,
+            keep: result
+                .keep
+                .unwrap_or_else(|| deserialize_warn!("deployment_dir", "keep", default.keep)),
+        })
+    }
+}
+
+/// Where operator-facing notifications are delivered. No notifications are sent when
+/// no sinks are configured.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct Notifications {
+    pub sinks: Vec<Sink>,
+}
+
+impl Notifications {
+    pub fn options(&self) -> notifications::Options {
+        notifications::Options {
+            sinks: self.sinks.clone(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Notifications {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeNotifications {
+            sinks: Option<Vec<Sink>>,
+        }
+
+        let default = Notifications::default();
+
+        let result = match DeserializeNotifications::deserialize(deserializer) {
+            Ok(notifications) => notifications,
+            Err(e) => {
+                error!("error deserializing notifications settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Notifications {
+            sinks: result
+                .sinks
+                .unwrap_or_else(|| deserialize_warn!("notifications", "sinks", default.sinks)),
+        })
+    }
+}
+
+/// When the agent starts in safe mode after repeatedly crashing.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct SafeMode {
+    pub max_crashes: u32,
+    #[serde(serialize_with = "units::signed_secs::serialize")]
+    pub window_secs: i64,
+}
+
+impl Default for SafeMode {
+    fn default() -> Self {
+        let options = safe_mode::Options::default();
+        Self {
+            max_crashes: options.max_crashes,
+            window_secs: options.window.num_seconds(),
+        }
+    }
+}
+
+impl SafeMode {
+    pub fn options(&self) -> safe_mode::Options {
+        safe_mode::Options {
+            max_crashes: self.max_crashes,
+            window: TimeDelta::seconds(self.window_secs.max(0)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SafeMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeSafeMode {
+            max_crashes: Option<u32>,
+            #[serde(default, deserialize_with = "units::signed_secs::deserialize_option")]
+            window_secs: Option<i64>,
+        }
+
+        let default = SafeMode::default();
+
+        let result = match DeserializeSafeMode::deserialize(deserializer) {
+            Ok(safe_mode) => safe_mode,
+            Err(e) => {
+                error!("error deserializing safe mode settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(SafeMode {
+            max_crashes: result.max_crashes.unwrap_or_else(|| {
+                deserialize_warn!("safe_mode", "max_crashes", default.max_crashes)
+            }),
+            window_secs: result.window_secs.unwrap_or_else(|| {
+                deserialize_warn!("safe_mode", "window_secs", default.window_secs)
+            }),
+        })
+    }
+}
+
+/// When the device's token is refreshed ahead of its expiry.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TokenRefresh {
+    /// How long before the token expires it is refreshed.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub margin_secs: u64,
+    /// How often the token's expiry is checked while waiting to refresh it, which
+    /// keeps the refresh on time after suspends and clock jumps. Zero only checks
+    /// when the refresh is due.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub watchdog_interval_secs: u64,
+}
+
+impl Default for TokenRefresh {
+    fn default() -> Self {
+        let options = token_refresh::TokenRefreshWorkerOptions::default();
+        Self {
+            margin_secs: options.refresh_advance_secs as u64,
+            watchdog_interval_secs: options
+                .watchdog_interval
+                .map_or(0, |interval| interval.as_secs()),
+        }
+    }
+}
+
+impl TokenRefresh {
+    pub fn worker_options(&self) -> token_refresh::TokenRefreshWorkerOptions {
+        token_refresh::TokenRefreshWorkerOptions {
+            refresh_advance_secs: self.margin_secs.min(i64::MAX as u64) as i64,
+            watchdog_interval: (self.watchdog_interval_secs > 0)
+                .then(|| Duration::from_secs(self.watchdog_interval_secs)),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenRefresh {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeTokenRefresh {
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            margin_secs: Option<u64>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            watchdog_interval_secs: Option<u64>,
+        }
+
+        let default = TokenRefresh::default();
+
+        let result = match DeserializeTokenRefresh::deserialize(deserializer) {
+            Ok(token_refresh) => token_refresh,
+            Err(e) => {
+                error!("error deserializing token refresh settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(TokenRefresh {
+            margin_secs: result.margin_secs.unwrap_or_else(|| {
+                deserialize_warn!("token_refresh", "margin_secs", default.margin_secs)
+            }),
+            watchdog_interval_secs: result.watchdog_interval_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "token_refresh",
+                    "watchdog_interval_secs",
+                    default.watchdog_interval_secs
+                )
+            }),
+        })
+    }
+}
+
+/// Where the device's private key is kept, see [crate::crypt::keystore]. Keys kept in
+/// hardware must be loaded into it, and removed from the auth directory, out of band.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct KeyStorage {
+    pub backend: KeyBackend,
+    /// The persistent handle of the TPM key.
+    pub tpm_handle: String,
+    /// How the TPM is reached, e.g. "device:/dev/tpmrm0". tpm2-tools' default if unset.
+    pub tpm_tcti: Option<String>,
+    /// The PKCS#11 module of the token holding the key.
+    pub pkcs11_module: String,
+    /// The token's slot. The first slot with a token if unset.
+    pub pkcs11_slot: Option<u64>,
+    pub pkcs11_key_label: String,
+    /// A file holding the token's user PIN. The token isn't logged into if unset.
+    pub pkcs11_pin_file: Option<String>,
+}
+
+impl Default for KeyStorage {
+    fn default() -> Self {
+        Self {
+            backend: KeyBackend::default(),
+            tpm_handle: "0x81010001".to_string(),
+            tpm_tcti: None,
+            pkcs11_module: String::new(),
+            pkcs11_slot: None,
+            pkcs11_key_label: "miru-device".to_string(),
+            pkcs11_pin_file: None,
+        }
+    }
+}
+
+impl KeyStorage {
+    /// The device's private key, which is `file` if it's kept on disk.
+    pub fn private_key(&self, file: filesys::File) -> keystore::PrivateKey {
+        match self.backend {
+            KeyBackend::File => keystore::PrivateKey::File(file),
+            KeyBackend::Tpm => keystore::PrivateKey::Tpm(keystore::TpmKey::new(
+                self.tpm_handle.clone(),
+                self.tpm_tcti.clone(),
+            )),
+            KeyBackend::Pkcs11 => keystore::PrivateKey::Pkcs11(keystore::Pkcs11Key::new(
+                self.pkcs11_module.clone(),
+                self.pkcs11_slot,
+                self.pkcs11_key_label.clone(),
+                self.pkcs11_pin_file.as_ref().map(filesys::File::new),
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyStorage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeKeyStorage {
+            backend: Option<KeyBackend>,
+            tpm_handle: Option<String>,
+            tpm_tcti: Option<String>,
+            pkcs11_module: Option<String>,
+            pkcs11_slot: Option<u64>,
+            pkcs11_key_label: Option<String>,
+            pkcs11_pin_file: Option<String>,
+        }
+
+        let default = KeyStorage::default();
+
+        let result = match DeserializeKeyStorage::deserialize(deserializer) {
+            Ok(key_storage) => key_storage,
+            Err(e) => {
+                error!("error deserializing key storage settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        // the hardware fields only matter for their backend and the optional ones
+        // have no default, so only the backend is warned about
+        Ok(KeyStorage {
+            backend: result
+                .backend
+                .unwrap_or_else(|| deserialize_warn!("key_storage", "backend", default.backend)),
+            tpm_handle: result.tpm_handle.unwrap_or(default.tpm_handle),
+            tpm_tcti: result.tpm_tcti,
+            pkcs11_module: result.pkcs11_module.unwrap_or(default.pkcs11_module),
+            pkcs11_slot: result.pkcs11_slot,
+            pkcs11_key_label: result.pkcs11_key_label.unwrap_or(default.pkcs11_key_label),
+            pkcs11_pin_file: result.pkcs11_pin_file,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyBackend {
+    /// The PEM file in the auth directory.
+    #[default]
+    File,
+    Tpm,
+    Pkcs11,
+}
+
+impl<'de> Deserialize<'de> for KeyBackend {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let default = KeyBackend::default();
+        let backend = String::deserialize(deserializer)?;
+        match backend.to_lowercase().as_str() {
+            "file" => Ok(KeyBackend::File),
+            "tpm" | "tpm2" => Ok(KeyBackend::Tpm),
+            "pkcs11" => Ok(KeyBackend::Pkcs11),
+            _ => {
+                warn!("invalid key storage backend `{backend}`, using default: {default:?}");
+                Ok(default)
+            }
+        }
+    }
+}
+
+/// Limits the agent's writes to flash storage (eMMC, SD cards) to extend its life.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct Wear {
+    /// Stops persisting events and flushes the storage wear totals less often.
+    pub low_wear_mode: bool,
+}
+
+impl Wear {
+    pub fn worker_options(&self) -> wear::Options {
+        if self.low_wear_mode {
+            wear::Options::low_wear()
+        } else {
+            wear::Options::default()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Wear {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeWear {
+            low_wear_mode: Option<bool>,
+        }
+
+        let default = Wear::default();
+
+        let result = match DeserializeWear::deserialize(deserializer) {
+            Ok(wear) => wear,
+            Err(e) => {
+                error!("error deserializing wear settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Wear {
+            low_wear_mode: result.low_wear_mode.unwrap_or_else(|| {
+                deserialize_warn!("wear", "low_wear_mode", default.low_wear_mode)
+            }),
+        })
+    }
+}
+
+/// Exposes the agent's metrics to Prometheus. They are always served on the socket
+/// server's `/metrics` route; scrapers which can't reach the socket can use a TCP
+/// listener instead.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct Metrics {
+    /// Address of the TCP listener serving `/metrics`, e.g. "127.0.0.1:9464". No
+    /// address serves the metrics on the socket only.
+    pub listen_addr: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for Metrics {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeMetrics {
+            listen_addr: Option<String>,
+        }
+
+        let result = match DeserializeMetrics::deserialize(deserializer) {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                error!("error deserializing metrics settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        // a missing address just means there is no listener, so there's nothing to warn
+        // about
+        Ok(Metrics {
+            listen_addr: result.listen_addr,
+        })
+    }
+}
+
+/// How many syncs the status API summarizes and whether they survive restarts.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct SyncHistory {
+    pub capacity: usize,
+    /// Writes the history to disk after every sync. Ignored in low wear mode.
+    pub persist: bool,
+}
+
+impl Default for SyncHistory {
+    fn default() -> Self {
+        Self {
+            capacity: history::Options::default().capacity,
+            persist: false,
+        }
+    }
+}
+
+impl SyncHistory {
+    /// The history's options, persisting it to `file` if enabled.
+    pub fn options(&self, file: filesys::File) -> history::Options {
+        history::Options {
+            capacity: self.capacity,
+            file: self.persist.then_some(file),
+        }
+    }
+
+    /// The event log's options. Events are persisted alongside the history.
+    pub fn event_options(&self, file: filesys::File) -> event_log::Options {
+        event_log::Options {
+            file: self.persist.then_some(file),
+            ..event_log::Options::default()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SyncHistory {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeSyncHistory {
+            capacity: Option<usize>,
+            persist: Option<bool>,
+        }
+
+        let default = SyncHistory::default();
+
+        let result = match DeserializeSyncHistory::deserialize(deserializer) {
+            Ok(sync_history) => sync_history,
+            Err(e) => {
+                error!("error deserializing sync history settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(SyncHistory {
+            capacity: result
+                .capacity
+                .unwrap_or_else(|| deserialize_warn!("sync_history", "capacity", default.capacity)),
+            persist: result
+                .persist
+                .unwrap_or_else(|| deserialize_warn!("sync_history", "persist", default.persist)),
+        })
+    }
+}
+
+/// Spreads the downloads of content which isn't needed yet over a period when the
+/// content cache is cold, e.g. after a fresh install.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ContentWarming {
+    pub enabled: bool,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub period_secs: u64,
+    /// The number of missing config instances at which the cache counts as cold.
+    pub cold_threshold: usize,
+}
+
+impl Default for ContentWarming {
+    fn default() -> Self {
+        let options = warming::Options::default();
+        Self {
+            enabled: options.enabled,
+            period_secs: options.period.as_secs(),
+            cold_threshold: options.cold_threshold,
+        }
+    }
+}
+
+impl ContentWarming {
+    /// The warming options, persisting the progress to `file`.
+    pub fn options(&self, file: filesys::File) -> warming::Options {
+        warming::Options {
+            enabled: self.enabled,
+            period: Duration::from_secs(self.period_secs),
+            cold_threshold: self.cold_threshold,
+            file: Some(file),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentWarming {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeContentWarming {
+            enabled: Option<bool>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            period_secs: Option<u64>,
+            cold_threshold: Option<usize>,
+        }
+
+        let default = ContentWarming::default();
+
+        let result = match DeserializeContentWarming::deserialize(deserializer) {
+            Ok(content_warming) => content_warming,
+            Err(e) => {
+                error!("error deserializing content warming settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(ContentWarming {
+            enabled: result.enabled.unwrap_or_else(|| {
+                deserialize_warn!("content_warming", "enabled", default.enabled)
+            }),
+            period_secs: result.period_secs.unwrap_or_else(|| {
+                deserialize_warn!("content_warming", "period_secs", default.period_secs)
+            }),
+            cold_threshold: result.cold_threshold.unwrap_or_else(|| {
+                deserialize_warn!("content_warming", "cold_threshold", default.cold_threshold)
+            }),
+        })
+    }
+}
+
+/// How long the agent's components may take to start before startup fails.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Startup {
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub timeout_secs: u64,
+    /// Overrides `timeout_secs` for individual components, e.g. `token_refresh`.
+    pub component_timeouts_secs: BTreeMap<startup::Component, u64>,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self {
+            timeout_secs: startup::Options::default().timeout.as_secs(),
+            component_timeouts_secs: BTreeMap::new(),
+        }
+    }
+}
+
+impl Startup {
+    pub fn options(&self) -> startup::Options {
+        startup::Options {
+            timeout: Duration::from_secs(self.timeout_secs.max(1)),
+            timeouts: self
+                .component_timeouts_secs
+                .iter()
+                .map(|(component, secs)| (*component, Duration::from_secs(*secs)))
+                .collect(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Startup {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeStartup {
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            timeout_secs: Option<u64>,
+            component_timeouts_secs: Option<BTreeMap<startup::Component, u64>>,
+        }
+
+        let default = Startup::default();
+
+        let result = match DeserializeStartup::deserialize(deserializer) {
+            Ok(startup) => startup,
+            Err(e) => {
+                error!("error deserializing startup settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Startup {
+            timeout_secs: result.timeout_secs.unwrap_or_else(|| {
+                deserialize_warn!("startup", "timeout_secs", default.timeout_secs)
+            }),
+            component_timeouts_secs: result.component_timeouts_secs.unwrap_or_default(),
+        })
+    }
+}
+
+/// How long the syncer waits before retrying, by why the sync failed. Failure classes
+/// without a policy use the syncer's default backoff.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct SyncBackoff {
+    pub network: Option<cooldown::Backoff>,
+    /// Rejected device credentials (401, 403) or failing to get a token.
+    pub auth: Option<cooldown::Backoff>,
+    /// Backend errors (5xx).
+    pub server: Option<cooldown::Backoff>,
+    pub filesystem: Option<cooldown::Backoff>,
+}
+
+impl SyncBackoff {
+    pub fn policies(&self) -> backoff::Policies {
+        backoff::Policies {
+            network: self.network,
+            auth: self.auth,
+            server: self.server,
+            filesystem: self.filesystem,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SyncBackoff {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeSyncBackoff {
+            network: Option<cooldown::Backoff>,
+            auth: Option<cooldown::Backoff>,
+            server: Option<cooldown::Backoff>,
+            filesystem: Option<cooldown::Backoff>,
+        }
+
+        let result = match DeserializeSyncBackoff::deserialize(deserializer) {
+            Ok(sync_backoff) => sync_backoff,
+            Err(e) => {
+                error!("error deserializing sync backoff settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(SyncBackoff {
+            network: result.network,
+            auth: result.auth,
+            server: result.server,
+            filesystem: result.filesystem,
+        })
+    }
+}
+
+/// Keeps rare, huge config instances from evicting the frequently used ones out of
+/// the config instance content cache.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ContentCache {
+    /// The most bytes the cached content may take up on disk. Probationary content,
+    /// then the least recently read, is evicted first. No limit bounds the cache by
+    /// its entry count alone.
+    #[serde(serialize_with = "units::opt_bytes::serialize")]
+    pub max_bytes: Option<usize>,
+    /// Content larger than this is kept on probation, first in line for eviction,
+    /// until it has been read `min_accesses` times. No threshold admits content of
+    /// any size.
+    #[serde(serialize_with = "units::opt_bytes::serialize")]
+    pub max_entry_bytes: Option<usize>,
+    pub min_accesses: u32,
+    /// Admission rules by config type name which take precedence over the size
+    /// threshold.
+    pub config_types: BTreeMap<String, admission::Rule>,
+    /// The algorithm content digests are computed with. BLAKE3 is cheaper on CPUs
+    /// without SHA extensions; content cached with the other algorithm is still
+    /// verified with it.
+    pub digest_algorithm: digest::Algorithm,
+}
+
+impl Default for ContentCache {
+    fn default() -> Self {
+        let policy = admission::Policy::default();
+        Self {
+            max_bytes: None,
+            max_entry_bytes: policy.max_entry_bytes,
+            min_accesses: policy.min_accesses,
+            config_types: policy.rules,
+            digest_algorithm: digest::Algorithm::default(),
+        }
+    }
+}
+
+impl ContentCache {
+    pub fn policy(&self) -> admission::Policy {
+        admission::Policy {
+            max_entry_bytes: self.max_entry_bytes,
+            min_accesses: self.min_accesses,
+            rules: self.config_types.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentCache {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeContentCache {
+            #[serde(default, deserialize_with = "units::opt_bytes::deserialize_option")]
+            max_bytes: Option<usize>,
+            #[serde(default, deserialize_with = "units::opt_bytes::deserialize_option")]
+            max_entry_bytes: Option<usize>,
+            min_accesses: Option<u32>,
+            config_types: Option<BTreeMap<String, admission::Rule>>,
+            digest_algorithm: Option<digest::Algorithm>,
+        }
+
+        let default = ContentCache::default();
+
+        let result = match DeserializeContentCache::deserialize(deserializer) {
+            Ok(content_cache) => content_cache,
+            Err(e) => {
+                error!("error deserializing content cache settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(ContentCache {
+            max_bytes: result.max_bytes,
+            max_entry_bytes: result.max_entry_bytes,
+            min_accesses: result.min_accesses.unwrap_or_else(|| {
+                deserialize_warn!("content_cache", "min_accesses", default.min_accesses)
+            }),
+            config_types: result.config_types.unwrap_or_else(|| {
+                deserialize_warn!("content_cache", "config_types", default.config_types)
+            }),
+            digest_algorithm: result.digest_algorithm.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "content_cache",
+                    "digest_algorithm",
+                    default.digest_algorithm
+                )
+            }),
+        })
+    }
+}
+
+/// The agent's place in a multi-robot cell. A controller proxies the backend for the
+/// peers listed here; a peer reaches the backend through its controller's proxy. See
+/// [cell].
+#[derive(Default, Serialize, PartialEq, Eq)]
+pub struct Cell {
+    pub role: cell::Role,
+    /// Controller: address the proxy listens on, e.g. "10.0.0.1:8443".
+    pub listen_addr: Option<String>,
+    /// Controller: the peers allowed to use the proxy.
+    pub peers: Vec<cell::PeerCredential>,
+    /// Peer: base URL of the controller's proxy, e.g. "http://10.0.0.1:8443".
+    pub controller_url: Option<String>,
+    /// Peer: the id the controller knows this peer by.
+    pub peer_id: Option<String>,
+    /// Peer: the token authenticating this peer to the controller.
+    pub token: Option<String>,
+}
+
+impl std::fmt::Debug for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cell")
+            .field("role", &self.role)
+            .field("listen_addr", &self.listen_addr)
+            .field("peers", &self.peers)
+            .field("controller_url", &self.controller_url)
+            .field("peer_id", &self.peer_id)
+            .field("token", &self.token.as_ref().map(|_| "[REDACTED]"))
+            .finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for Cell {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeCell {
+            role: Option<cell::Role>,
+            listen_addr: Option<String>,
+            #[serde(default)]
+            peers: Vec<cell::PeerCredential>,
+            controller_url: Option<String>,
+            peer_id: Option<String>,
+            token: Option<String>,
+        }
+
+        let default = Cell::default();
+
+        let result = match DeserializeCell::deserialize(deserializer) {
+            Ok(cell) => cell,
+            Err(e) => {
+                error!("error deserializing cell settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        // only the role's own settings are required, which `cell::Options` checks
+        Ok(Cell {
+            role: result
+                .role
+                .unwrap_or_else(|| deserialize_warn!("cell", "role", default.role)),
+            listen_addr: result.listen_addr,
+            peers: result.peers,
+            controller_url: result.controller_url,
+            peer_id: result.peer_id,
+            token: result.token,
+        })
+    }
+}
+
+/// Egress for sites which route outbound traffic through a proxy or issue
+/// certificates from a private CA. See [egress].
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct Network {
+    /// e.g. "http://proxy.corp:3128" or "socks5://proxy.corp:1080"
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    /// Hosts reached directly rather than through the proxy, as in `NO_PROXY`.
+    pub no_proxy: Vec<String>,
+    /// PEM file of CA certificates trusted in addition to the system's.
+    pub ca_bundle_path: Option<String>,
+    /// Headers sent with every backend request, e.g. a fleet or site identifier.
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Network {
+    /// The egress options. An invalid proxy is ignored, with a warning, so the agent
+    /// still starts on networks which don't need it.
+    pub fn egress(&self) -> egress::Options {
+        let proxy = self.proxy_url.as_deref().and_then(|url| {
+            let proxy = match egress::Proxy::new(url) {
+                Ok(proxy) => proxy,
+                Err(msg) => {
+                    warn!("ignoring the network proxy settings: {msg}");
+                    return None;
+                }
+            };
+            let proxy = match &self.proxy_username {
+                Some(username) => {
+                    proxy.with_credentials(username.clone(), self.proxy_password.clone())
+                }
+                None => proxy,
+            };
+            Some(proxy.with_no_proxy(self.no_proxy.clone()))
+        });
+        // likewise an invalid header, which would otherwise fail every request
+        let headers = self
+            .headers
+            .iter()
+            .filter(|(name, value)| match egress::validate_header(name, value) {
+                Ok(()) => true,
+                Err(msg) => {
+                    warn!("ignoring a network header setting: {msg}");
+                    false
+                }
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        egress::Options {
+            proxy,
+            ca_bundle: self.ca_bundle_path.as_deref().map(filesys::File::new),
+            // read from the auth directory rather than configured
+            identity: None,
+            headers,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Network {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeNetwork {
+            proxy_url: Option<String>,
+            proxy_username: Option<String>,
+            proxy_password: Option<String>,
+            #[serde(default)]
+            no_proxy: Vec<String>,
+            ca_bundle_path: Option<String>,
+            #[serde(default)]
+            headers: BTreeMap<String, String>,
+        }
+
+        let result = match DeserializeNetwork::deserialize(deserializer) {
+            Ok(network) => network,
+            Err(e) => {
+                error!("error deserializing network settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        // everything is optional since most sites reach the backend directly
+        Ok(Network {
+            proxy_url: result.proxy_url,
+            proxy_username: result.proxy_username,
+            proxy_password: result.proxy_password,
+            no_proxy: result.no_proxy,
+            ca_bundle_path: result.ca_bundle_path,
+            headers: result.headers,
+        })
+    }
+}
+
+/// Files removed with a deployment are moved into the trash, from where they can be
+/// restored until the retention or the size budget purges them. See [trash].
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Trash {
+    /// Removed files are unlinked immediately when disabled.
+    pub enabled: bool,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub retention_secs: u64,
+    /// The most bytes the trashed files may take up. Null bounds the trash by the
+    /// retention alone.
+    #[serde(serialize_with = "units::opt_bytes::serialize")]
+    pub max_bytes: Option<usize>,
+}
+
+impl Default for Trash {
+    fn default() -> Self {
+        let options = trash::Options::default();
+        Self {
+            enabled: true,
+            retention_secs: options.retention.num_seconds() as u64,
+            max_bytes: options.max_bytes.map(|max| max as usize),
+        }
+    }
+}
+
+impl Trash {
+    /// The trash's options, or `None` if removed files are unlinked.
+    pub fn options(&self) -> Option<trash::Options> {
+        self.enabled.then(|| trash::Options {
+            retention: i64::try_from(self.retention_secs)
+                .ok()
+                .and_then(TimeDelta::try_seconds)
+                .unwrap_or(TimeDelta::MAX),
+            max_bytes: self.max_bytes.map(|max| max as u64),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Trash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        fn default_max_bytes() -> Option<usize> {
+            Trash::default().max_bytes
+        }
+
+        #[derive(Deserialize)]
+        struct DeserializeTrash {
+            enabled: Option<bool>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            retention_secs: Option<u64>,
+            #[serde(
+                default = "default_max_bytes",
+                deserialize_with = "units::opt_bytes::deserialize_option"
+            )]
+            max_bytes: Option<usize>,
+        }
+
+        let default = Trash::default();
+
+        let result = match DeserializeTrash::deserialize(deserializer) {
+            Ok(trash) => trash,
+            Err(e) => {
+                error!("error deserializing trash settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Trash {
+            enabled: result
+                .enabled
+                .unwrap_or_else(|| deserialize_warn!("trash", "enabled", default.enabled)),
+            retention_secs: result.retention_secs.unwrap_or_else(|| {
+                deserialize_warn!("trash", "retention_secs", default.retention_secs)
+            }),
+            max_bytes: result.max_bytes,
+        })
+    }
+}
+
+/// Rejects socket API requests with 503 while the agent is overloaded. See
+/// [crate::server::shed].
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct LoadShedding {
+    pub enabled: bool,
+    pub max_in_flight: usize,
+    pub max_latency_ms: u64,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub retry_after_secs: u64,
+}
+
+impl Default for LoadShedding {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_in_flight: 32,
+            max_latency_ms: 2000,
+            retry_after_secs: 1,
+        }
+    }
+}
+
+impl LoadShedding {
+    /// The shedding options, or `None` if requests are never shed.
+    #[cfg(feature = "server")]
+    pub fn options(&self) -> Option<crate::server::shed::Options> {
+        self.enabled.then(|| crate::server::shed::Options {
+            max_in_flight: self.max_in_flight.max(1),
+            max_latency: Duration::from_millis(self.max_latency_ms),
+            retry_after: Duration::from_secs(self.retry_after_secs.max(1)),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for LoadShedding {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeLoadShedding {
+            enabled: Option<bool>,
+            max_in_flight: Option<usize>,
+            max_latency_ms: Option<u64>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            retry_after_secs: Option<u64>,
+        }
+
+        let default = LoadShedding::default();
+
+        let result = match DeserializeLoadShedding::deserialize(deserializer) {
+            Ok(load_shedding) => load_shedding,
+            Err(e) => {
+                error!("error deserializing load shedding settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(LoadShedding {
+            enabled: result
+                .enabled
+                .unwrap_or_else(|| deserialize_warn!("load_shedding", "enabled", default.enabled)),
+            max_in_flight: result.max_in_flight.unwrap_or_else(|| {
+                deserialize_warn!("load_shedding", "max_in_flight", default.max_in_flight)
+            }),
+            max_latency_ms: result.max_latency_ms.unwrap_or_else(|| {
+                deserialize_warn!("load_shedding", "max_latency_ms", default.max_latency_ms)
+            }),
+            retry_after_secs: result.retry_after_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "load_shedding",
+                    "retry_after_secs",
+                    default.retry_after_secs
+                )
+            }),
+        })
+    }
+}
+
+/// Installs new agent releases from the backend, see [crate::updater]. Off by default;
+/// releases are only installed if they're signed with the release key.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct SelfUpdate {
+    pub enabled: bool,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub check_interval_secs: u64,
+    /// How long a newly installed version has to sync with the backend before it's
+    /// rolled back.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub health_check_secs: u64,
+    /// The PEM public key releases are signed with. Updates are disabled if unset.
+    pub release_key_file: Option<String>,
+}
+
+impl Default for SelfUpdate {
+    fn default() -> Self {
+        let options = updater::Options::default();
+        Self {
+            enabled: false,
+            check_interval_secs: options.check_interval_secs as u64,
+            health_check_secs: options.health_check_secs as u64,
+            release_key_file: None,
+        }
+    }
+}
+
+impl SelfUpdate {
+    /// The updater's options, or `None` if there's no release key to verify releases
+    /// with.
+    pub fn worker_options(&self) -> Option<updater::Options> {
+        let release_key = self.release_key_file.as_ref()?;
+        Some(updater::Options {
+            check_interval_secs: self.check_interval_secs.min(i64::MAX as u64) as i64,
+            health_check_secs: self.health_check_secs.min(i64::MAX as u64) as i64,
+            release_key: filesys::File::new(release_key),
+            ..Default::default()
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for SelfUpdate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeSelfUpdate {
+            enabled: Option<bool>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            check_interval_secs: Option<u64>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            health_check_secs: Option<u64>,
+            release_key_file: Option<String>,
+        }
+
+        let default = SelfUpdate::default();
+
+        let result = match DeserializeSelfUpdate::deserialize(deserializer) {
+            Ok(self_update) => self_update,
+            Err(e) => {
+                error!("error deserializing self update settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(SelfUpdate {
+            enabled: result
+                .enabled
+                .unwrap_or_else(|| deserialize_warn!("self_update", "enabled", default.enabled)),
+            check_interval_secs: result.check_interval_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "self_update",
+                    "check_interval_secs",
+                    default.check_interval_secs
+                )
+            }),
+            health_check_secs: result.health_check_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "self_update",
+                    "health_check_secs",
+                    default.health_check_secs
+                )
+            }),
+            release_key_file: result.release_key_file,
+        })
+    }
+}
+
+/// Reports the host's CPU, memory, disk and temperature to the backend, see
+/// [crate::telemetry]. Off by default.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Telemetry {
+    pub enabled: bool,
+    /// Reports the device's network interfaces and connectivity with each sync, see
+    /// [crate::sync::network]. Independent of `enabled`.
+    pub report_network: bool,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub interval_secs: u64,
+    /// How many unpublished samples are kept while the backend is unreachable. The
+    /// oldest samples are dropped first. Over HTTP the samples are queued in the
+    /// request journal, a batch per interval.
+    pub retention: usize,
+    pub transport: TelemetryTransport,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            report_network: true,
+            interval_secs: 60,
+            retention: 60,
+            transport: TelemetryTransport::default(),
+        }
+    }
+}
+
+impl Telemetry {
+    pub fn network_report(&self) -> network::Options {
+        network::Options {
+            enabled: self.report_network,
+            ..Default::default()
+        }
+    }
+
+    /// The worker's options, sampling the disks holding the agent's storage.
+    #[cfg(feature = "telemetry")]
+    pub fn worker_options(
+        &self,
+        layout: &crate::storage::Layout,
+    ) -> crate::workers::telemetry::Options {
+        use crate::filesys::PathExt;
+        use crate::workers::telemetry::{Options, Transport};
+
+        Options {
+            interval_secs: self.interval_secs.clamp(1, i64::MAX as u64) as i64,
+            retention: self.retention.max(1),
+            transport: match self.transport {
+                TelemetryTransport::Http => Transport::Http,
+                TelemetryTransport::Mqtt => Transport::Mqtt,
+            },
+            disk_paths: vec![
+                layout.root().path().clone(),
+                layout.log_dir(),
+                layout.updates_dir().path().clone(),
+            ],
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Telemetry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeTelemetry {
+            enabled: Option<bool>,
+            report_network: Option<bool>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            interval_secs: Option<u64>,
+            retention: Option<usize>,
+            transport: Option<TelemetryTransport>,
+        }
+
+        let default = Telemetry::default();
+
+        let result = match DeserializeTelemetry::deserialize(deserializer) {
+            Ok(telemetry) => telemetry,
+            Err(e) => {
+                error!("error deserializing telemetry settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Telemetry {
+            enabled: result
+                .enabled
+                .unwrap_or_else(|| deserialize_warn!("telemetry", "enabled", default.enabled)),
+            report_network: result.report_network.unwrap_or_else(|| {
+                deserialize_warn!("telemetry", "report_network", default.report_network)
+            }),
+            interval_secs: result.interval_secs.unwrap_or_else(|| {
+                deserialize_warn!("telemetry", "interval_secs", default.interval_secs)
+            }),
+            retention: result
+                .retention
+                .unwrap_or_else(|| deserialize_warn!("telemetry", "retention", default.retention)),
+            transport: result
+                .transport
+                .unwrap_or_else(|| deserialize_warn!("telemetry", "transport", default.transport)),
+        })
+    }
+}
+
+/// How telemetry reaches the backend. `mqtt` samples are published by the MQTT
+/// worker, so they're held while it's disabled or disconnected.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryTransport {
+    #[default]
+    Http,
+    Mqtt,
+}
+
+impl<'de> Deserialize<'de> for TelemetryTransport {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let default = TelemetryTransport::default();
+        let transport = String::deserialize(deserializer)?;
+        match transport.to_lowercase().as_str() {
+            "http" => Ok(TelemetryTransport::Http),
+            "mqtt" => Ok(TelemetryTransport::Mqtt),
+            _ => {
+                warn!("invalid telemetry transport `{transport}`, using default: {default:?}");
+                Ok(default)
+            }
+        }
+    }
+}
+
+/// Refuses to download or deploy content while the disks holding the agent's storage
+/// or the deployment directory are nearly full, see [crate::deploy::space].
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct DiskGuard {
+    /// The least free space each disk must keep. Zero disables the guard.
+    #[serde(serialize_with = "units::bytes::serialize")]
+    pub min_free_bytes: u64,
+}
+
+impl Default for DiskGuard {
+    fn default() -> Self {
+        Self {
+            min_free_bytes: 64 * 1024 * 1024, // 64 MiB
+        }
+    }
+}
+
+impl DiskGuard {
+    /// The guard over the disks holding the agent's storage and the deployment
+    /// directory, if enabled.
+    pub fn guard(
+        &self,
+        layout: &crate::storage::Layout,
+        deployment_dir: &DeploymentDir,
+    ) -> Option<space::Guard> {
+        use crate::filesys::PathExt;
+
+        if self.min_free_bytes == 0 {
+            return None;
+        }
+        let mut paths = vec![layout.root().path().clone()];
+        if let Some(path) = &deployment_dir.path {
+            paths.push(path.into());
+        }
+        Some(space::Guard::new(self.min_free_bytes, paths))
+    }
+}
+
+impl<'de> Deserialize<'de> for DiskGuard {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeDiskGuard {
+            #[serde(default, deserialize_with = "units::bytes::deserialize_option")]
+            min_free_bytes: Option<u64>,
+        }
+
+        let default = DiskGuard::default();
+
+        let result = match DeserializeDiskGuard::deserialize(deserializer) {
+            Ok(disk_guard) => disk_guard,
+            Err(e) => {
+                error!("error deserializing disk guard settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(DiskGuard {
+            min_free_bytes: result.min_free_bytes.unwrap_or_else(|| {
+                deserialize_warn!("disk_guard", "min_free_bytes", default.min_free_bytes)
+            }),
+        })
+    }
+}
+
+/// Runs and supervises the customer application the agent is paired with, see
+/// [crate::supervisor]. Off by default; enabling it without a command has no effect.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Supervisor {
+    pub enabled: bool,
+    /// The application, as a program followed by its arguments.
+    pub command: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub working_dir: Option<String>,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub stop_timeout_secs: u64,
+    pub restart_backoff: cooldown::Backoff,
+    /// How long the application must run before its crashes no longer count as
+    /// consecutive, resetting the restart backoff.
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub reset_after_secs: u64,
+    /// A command which exits zero while the application is healthy. No health check
+    /// runs if empty.
+    pub health_check: Vec<String>,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub health_check_interval_secs: u64,
+    #[serde(serialize_with = "units::secs::serialize")]
+    pub health_check_timeout_secs: u64,
+    /// How many consecutive health checks must fail before the application is
+    /// restarted.
+    pub health_check_failures: u32,
+    /// Restarts the application once deployments changed its config files, after the
+    /// post-deploy hooks ran.
+    pub restart_on_config_change: bool,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        let options = supervisor::Options::default();
+        let health_check = health::Options::default();
+        Self {
+            enabled: false,
+            command: Vec::new(),
+            env: BTreeMap::new(),
+            working_dir: None,
+            stop_timeout_secs: options.app.stop_timeout.as_secs(),
+            restart_backoff: options.restart_backoff,
+            reset_after_secs: options.reset_after.as_secs(),
+            health_check: Vec::new(),
+            health_check_interval_secs: health_check.interval.as_secs(),
+            health_check_timeout_secs: health_check.timeout.as_secs(),
+            health_check_failures: health_check.failure_threshold,
+            restart_on_config_change: options.restart_on_config_change,
+        }
+    }
+}
+
+impl Supervisor {
+    /// The worker's options, or `None` if there's no application to supervise.
+    pub fn worker_options(&self) -> Option<supervisor::Options> {
+        if self.command.is_empty() {
+            return None;
+        }
+        let health_check = (!self.health_check.is_empty()).then(|| health::Options {
+            command: self.health_check.clone(),
+            interval: Duration::from_secs(self.health_check_interval_secs.max(1)),
+            timeout: Duration::from_secs(self.health_check_timeout_secs.max(1)),
+            failure_threshold: self.health_check_failures.max(1),
+        });
+        Some(supervisor::Options {
+            app: process::Options {
+                command: self.command.clone(),
+                env: self.env.clone(),
+                working_dir: self.working_dir.as_ref().map(Into::into),
+                stop_timeout: Duration::from_secs(self.stop_timeout_secs),
+            },
+            restart_backoff: self.restart_backoff,
+            reset_after: Duration::from_secs(self.reset_after_secs),
+            health_check,
+            restart_on_config_change: self.restart_on_config_change,
+            ..Default::default()
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Supervisor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeSupervisor {
+            enabled: Option<bool>,
+            command: Option<Vec<String>>,
+            env: Option<BTreeMap<String, String>>,
+            working_dir: Option<String>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            stop_timeout_secs: Option<u64>,
+            restart_backoff: Option<cooldown::Backoff>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            reset_after_secs: Option<u64>,
+            health_check: Option<Vec<String>>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            health_check_interval_secs: Option<u64>,
+            #[serde(default, deserialize_with = "units::secs::deserialize_option")]
+            health_check_timeout_secs: Option<u64>,
+            health_check_failures: Option<u32>,
+            restart_on_config_change: Option<bool>,
+        }
+
+        let default = Supervisor::default();
+
+        let result = match DeserializeSupervisor::deserialize(deserializer) {
+            Ok(supervisor) => supervisor,
+            Err(e) => {
+                error!("error deserializing supervisor settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(Supervisor {
+            enabled: result
+                .enabled
+                .unwrap_or_else(|| deserialize_warn!("supervisor", "enabled", default.enabled)),
+            command: result
+                .command
+                .unwrap_or_else(|| deserialize_warn!("supervisor", "command", default.command)),
+            env: result
+                .env
+                .unwrap_or_else(|| deserialize_warn!("supervisor", "env", default.env)),
+            working_dir: result.working_dir,
+            stop_timeout_secs: result.stop_timeout_secs.unwrap_or_else(|| {
+                deserialize_warn!("supervisor", "stop_timeout_secs", default.stop_timeout_secs)
+            }),
+            restart_backoff: result.restart_backoff.unwrap_or_else(|| {
+                deserialize_warn!("supervisor", "restart_backoff", default.restart_backoff)
+            }),
+            reset_after_secs: result.reset_after_secs.unwrap_or_else(|| {
+                deserialize_warn!("supervisor", "reset_after_secs", default.reset_after_secs)
+            }),
+            health_check: result.health_check.unwrap_or_else(|| {
+                deserialize_warn!("supervisor", "health_check", default.health_check)
+            }),
+            health_check_interval_secs: result.health_check_interval_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "supervisor",
+                    "health_check_interval_secs",
+                    default.health_check_interval_secs
+                )
+            }),
+            health_check_timeout_secs: result.health_check_timeout_secs.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "supervisor",
+                    "health_check_timeout_secs",
+                    default.health_check_timeout_secs
+                )
+            }),
+            health_check_failures: result.health_check_failures.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "supervisor",
+                    "health_check_failures",
+                    default.health_check_failures
+                )
+            }),
+            restart_on_config_change: result.restart_on_config_change.unwrap_or_else(|| {
+                deserialize_warn!(
+                    "supervisor",
+                    "restart_on_config_change",
+                    default.restart_on_config_change
+                )
+            }),
+        })
+    }
+}
+
+/// The keys release signatures are verified with, see [crate::deploy::signature].
+/// Releases are deployed unverified unless a key is trusted or signatures are
+/// required.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ReleaseSigning {
+    /// Fails deployments of unsigned releases rather than deploying them unverified.
+    pub required: bool,
+    pub trusted_keys: Vec<TrustedReleaseKey>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrustedReleaseKey {
+    pub key_id: String,
+    /// The PEM encoded Ed25519 or P-256 public key.
+    pub public_key: String,
+}
+
+impl ReleaseSigning {
+    /// The verifier's options, or `None` if release signatures aren't verified.
+    pub fn verifier_options(&self) -> Option<signature::Options> {
+        if !self.required && self.trusted_keys.is_empty() {
+            return None;
+        }
+        Some(signature::Options {
+            trusted_keys: self
+                .trusted_keys
+                .iter()
+                .map(|key| signature::TrustedKey {
+                    key_id: key.key_id.clone(),
+                    public_key: key.public_key.clone(),
+                })
+                .collect(),
+            required: self.required,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ReleaseSigning {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeReleaseSigning {
+            required: Option<bool>,
+            trusted_keys: Option<Vec<TrustedReleaseKey>>,
+        }
+
+        let default = ReleaseSigning::default();
+
+        let result = match DeserializeReleaseSigning::deserialize(deserializer) {
+            Ok(release_signing) => release_signing,
+            Err(e) => {
+                error!("error deserializing release signing settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(ReleaseSigning {
+            required: result.required.unwrap_or_else(|| {
+                deserialize_warn!("release_signing", "required", default.required)
+            }),
+            trusted_keys: result.trusted_keys.unwrap_or_else(|| {
+                deserialize_warn!("release_signing", "trusted_keys", default.trusted_keys)
+            }),
+        })
+    }
+}
+
+/// The reports written when the agent panics, see [crate::diagnostics::crash]. The last
+/// crash is always written; reports are only queued for upload if uploading is on.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct CrashReports {
+    /// Uploads the reports to the backend after the agent next syncs.
+    pub upload: bool,
+    /// How many of the last log lines a report includes.
+    pub log_lines: usize,
+}
+
+impl Default for CrashReports {
+    fn default() -> Self {
+        Self {
+            upload: true,
+            log_lines: logs::RECENT_LINES,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CrashReports {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct DeserializeCrashReports {
+            upload: Option<bool>,
+            log_lines: Option<usize>,
+        }
+
+        let default = CrashReports::default();
+
+        let result = match DeserializeCrashReports::deserialize(deserializer) {
+            Ok(crash_reports) => crash_reports,
+            Err(e) => {
+                error!("error deserializing crash reports settings: {}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(CrashReports {
+            upload: result
+                .upload
+                .unwrap_or_else(|| deserialize_warn!("crash_reports", "upload", default.upload)),
+            log_lines: result.log_lines.unwrap_or_else(|| {
+                deserialize_warn!("crash_reports", "log_lines", default.log_lines)
+            }),
+        })
+    }
+}
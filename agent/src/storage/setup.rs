@@ -13,6 +13,21 @@ pub async fn reset(
     device: &models::Device,
     settings: &Settings,
     agent_version: &str,
+) -> Result<(), StorageErr> {
+    // overwrite the settings file
+    let settings_file = layout.settings();
+    settings_file
+        .write_json(&settings, WriteOptions::OVERWRITE_ATOMIC)
+        .await?;
+
+    reset_state(layout, device, agent_version).await
+}
+
+/// Like [reset] but keeps the settings file as it is, which an upgrade carries over.
+pub async fn reset_state(
+    layout: &Layout,
+    device: &models::Device,
+    agent_version: &str,
 ) -> Result<(), StorageErr> {
     // ensure auth dir exists (token.json lives there)
     let auth_dir = layout.auth();
@@ -24,12 +39,6 @@ pub async fn reset(
         .write_json(&device, WriteOptions::OVERWRITE_ATOMIC)
         .await?;
 
-    // overwrite the settings file
-    let settings_file = layout.settings();
-    settings_file
-        .write_json(&settings, WriteOptions::OVERWRITE_ATOMIC)
-        .await?;
-
     // blank token.json
     let token = authn::Token::default();
     auth_dir
@@ -0,0 +1,96 @@
+// internal crates
+use crate::filesys::{self, wear, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// Bytes written by the agent since it was installed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// When the agent started tracking its writes.
+    pub since: DateTime<Utc>,
+    pub logs_bytes: u64,
+    pub caches_bytes: u64,
+    pub deployments_bytes: u64,
+}
+
+impl Usage {
+    pub fn new(since: DateTime<Utc>) -> Self {
+        Self {
+            since,
+            logs_bytes: 0,
+            caches_bytes: 0,
+            deployments_bytes: 0,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.logs_bytes + self.caches_bytes + self.deployments_bytes
+    }
+
+    fn plus(&self, written: wear::Written) -> Self {
+        Self {
+            since: self.since,
+            logs_bytes: self.logs_bytes + written.logs,
+            caches_bytes: self.caches_bytes + written.caches,
+            deployments_bytes: self.deployments_bytes + written.deployments,
+        }
+    }
+}
+
+/// What the local API reports about the agent's storage wear.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub total_bytes: u64,
+    pub low_wear_mode: bool,
+}
+
+/// Tracks the bytes written by the agent across restarts. The totals of previous runs
+/// are read from the wear file and the writes of the current process are added on top
+/// of them.
+#[derive(Debug)]
+pub struct Meter {
+    file: filesys::File,
+    previous: Usage,
+}
+
+impl Meter {
+    pub async fn init(file: filesys::File) -> Self {
+        let previous = if file.exists() {
+            match file.read_json::<Usage>().await {
+                Ok(usage) => usage,
+                Err(e) => {
+                    error!("resetting unreadable storage wear totals: {e}");
+                    Usage::new(Utc::now())
+                }
+            }
+        } else {
+            Usage::new(Utc::now())
+        };
+        Self { file, previous }
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.previous.plus(wear::written())
+    }
+
+    pub fn report(&self, low_wear_mode: bool) -> Report {
+        let usage = self.usage();
+        Report {
+            total_bytes: usage.total_bytes(),
+            usage,
+            low_wear_mode,
+        }
+    }
+
+    /// Writes the current totals to the wear file.
+    pub async fn persist(&self) -> Result<(), filesys::FileSysErr> {
+        self.file
+            .write_json(&self.usage(), WriteOptions::OVERWRITE_ATOMIC)
+            .await
+    }
+}
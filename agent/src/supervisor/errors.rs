@@ -0,0 +1,49 @@
+// internal crates
+use crate::errors::Trace;
+
+#[derive(Debug, thiserror::Error)]
+#[error("unable to start '{command}': {msg}")]
+pub struct StartErr {
+    pub command: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for StartErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("health check '{command}' failed: {msg}")]
+pub struct HealthCheckErr {
+    pub command: String,
+    pub msg: String,
+    /// The end of the health check's stderr.
+    pub stderr: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for HealthCheckErr {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SupervisorErr {
+    #[error(transparent)]
+    StartErr(StartErr),
+    #[error(transparent)]
+    HealthCheckErr(HealthCheckErr),
+}
+
+impl From<StartErr> for SupervisorErr {
+    fn from(e: StartErr) -> Self {
+        Self::StartErr(e)
+    }
+}
+
+impl From<HealthCheckErr> for SupervisorErr {
+    fn from(e: HealthCheckErr) -> Self {
+        Self::HealthCheckErr(e)
+    }
+}
+
+crate::impl_error!(SupervisorErr {
+    StartErr,
+    HealthCheckErr,
+});
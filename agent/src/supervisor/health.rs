@@ -0,0 +1,48 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::deploy::hooks;
+use crate::supervisor::errors::*;
+use crate::trace;
+
+/// A command checking whether the application is healthy, e.g. by querying its
+/// health endpoint. The application is healthy if the command exits zero within the
+/// timeout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub command: Vec<String>,
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// How many consecutive checks must fail before the application is restarted.
+    pub failure_threshold: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            failure_threshold: 3,
+        }
+    }
+}
+
+/// Runs the health check against the application running as `pid`, which the
+/// command is given in `MIRU_APP_PID`.
+pub async fn check(options: &Options, pid: u32) -> Result<(), HealthCheckErr> {
+    let command = options.command.clone();
+    let display = command.join(" ");
+    let env = vec![("MIRU_APP_PID".to_string(), pid.to_string())];
+    let timeout = options.timeout;
+    let result = tokio::task::spawn_blocking(move || hooks::run_blocking(&command, env, timeout))
+        .await
+        .unwrap_or_else(|e| Err(hooks::Failure::new(e.to_string())));
+    result.map(|_| ()).map_err(|failure| HealthCheckErr {
+        command: display,
+        msg: failure.msg,
+        stderr: failure.stderr,
+        trace: trace!(),
+    })
+}
@@ -0,0 +1,14 @@
+// Supervision of the customer application the agent is paired with. The application
+// runs as a child of the agent in a process group of its own, so stopping it stops
+// whatever it started as well. The supervisor worker ([crate::workers::supervisor])
+// restarts it when it exits, after a cooldown which grows with each consecutive crash,
+// when its health check keeps failing, and, if enabled, once applying deployments
+// changed config files, which is after the post-deploy hooks ran. The application is
+// stopped with the agent.
+
+pub mod errors;
+pub mod health;
+pub mod process;
+
+pub use self::errors::SupervisorErr;
+pub use self::process::Process;
@@ -0,0 +1,165 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+// internal crates
+use crate::supervisor::errors::*;
+use crate::trace;
+
+// external crates
+use tracing::{info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The application, as a program followed by its arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub command: Vec<String>,
+    /// Added to the environment the agent runs with.
+    pub env: BTreeMap<String, String>,
+    /// The agent's working directory if unset.
+    pub working_dir: Option<PathBuf>,
+    /// How long the application has to exit once asked to before it's killed.
+    pub stop_timeout: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            env: BTreeMap::new(),
+            working_dir: None,
+            stop_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A running instance of the application. Its stdout and stderr are the agent's, so
+/// its output lands in the same journal.
+#[derive(Debug)]
+pub struct Process {
+    child: Child,
+    started_at: Instant,
+}
+
+impl Process {
+    pub fn start(options: &Options) -> Result<Self, StartErr> {
+        let start_err = |msg: String| StartErr {
+            command: options.command.join(" "),
+            msg,
+            trace: trace!(),
+        };
+        let (program, args) = options
+            .command
+            .split_first()
+            .ok_or_else(|| start_err("no command configured".to_string()))?;
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .envs(&options.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        if let Some(dir) = &options.working_dir {
+            cmd.current_dir(dir);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+        let child = cmd.spawn().map_err(|e| start_err(e.to_string()))?;
+        info!(
+            "started '{}' as process {}",
+            options.command.join(" "),
+            child.id()
+        );
+        Ok(Self {
+            child,
+            started_at: Instant::now(),
+        })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// The exit status if the application has exited.
+    pub fn try_wait(&mut self) -> Option<ExitStatus> {
+        match self.child.try_wait() {
+            Ok(status) => status,
+            Err(e) => {
+                warn!("failed to poll process {}: {e}", self.pid());
+                None
+            }
+        }
+    }
+
+    /// Asks the application's process group to terminate and kills it once the
+    /// timeout passes. Returns the application's exit status, if it could be reaped.
+    pub async fn stop(self, timeout: Duration) -> Option<ExitStatus> {
+        let pid = self.pid();
+        tokio::task::spawn_blocking(move || stop_blocking(self.child, timeout))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("failed to stop process {pid}: {e}");
+                None
+            })
+    }
+}
+
+fn stop_blocking(mut child: Child, timeout: Duration) -> Option<ExitStatus> {
+    let pid = child.id();
+    if let Ok(Some(status)) = child.try_wait() {
+        signal_group(pid, "KILL");
+        return Some(status);
+    }
+    signal_group(pid, "TERM");
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() >= deadline => {
+                warn!(
+                    "process {pid} didn't exit within {}s, killing it",
+                    timeout.as_secs()
+                );
+                let _ = child.kill();
+                break child.wait().ok();
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                warn!("failed to poll process {pid}: {e}");
+                let _ = child.kill();
+                break child.wait().ok();
+            }
+        }
+    };
+    // whatever the application left running goes with it
+    signal_group(pid, "KILL");
+    status
+}
+
+/// Signals the process group led by `pid`. There's no binding for kill(2) so the
+/// signal is sent with `kill`, which every unix has.
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: &str) {
+    let _ = Command::new("kill")
+        .arg(format!("-{signal}"))
+        .arg("--")
+        .arg(format!("-{pid}"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Elsewhere only the application itself is killed, once the timeout passes.
+#[cfg(not(unix))]
+fn signal_group(_: u32, _: &str) {}
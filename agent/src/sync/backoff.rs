@@ -0,0 +1,151 @@
+// Lets the syncer back off differently depending on why a sync failed. An expired or
+// revoked device token won't fix itself within seconds so retrying it quickly only
+// floods the backend, while a network blip usually clears up by the next attempt.
+
+// internal crates
+use crate::cooldown;
+use crate::deploy;
+use crate::errors::Error;
+use crate::http;
+use crate::sync::errors::SyncErr;
+
+// external crates
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The backend couldn't be reached.
+    Network,
+    /// The backend rejected the device's credentials (401, 403) or no token could be
+    /// issued.
+    Auth,
+    /// The backend failed to handle the request (5xx).
+    Server,
+    /// Reading or writing the agent's local files failed.
+    Filesystem,
+    Other,
+}
+
+impl FailureClass {
+    pub fn of(e: &SyncErr) -> Self {
+        if e.is_network_conn_err() {
+            return Self::Network;
+        }
+        match e {
+            SyncErr::AuthnErr(_) => Self::Auth,
+            SyncErr::HTTPClientErr(e) => Self::of_http(e),
+            SyncErr::CacheErr(_)
+            | SyncErr::FileSysErr(_)
+            | SyncErr::StorageErr(_)
+            | SyncErr::JournalErr(_) => Self::Filesystem,
+            SyncErr::DeployErr(e) => match e.as_ref() {
+                deploy::DeployErr::CacheErr(_)
+                | deploy::DeployErr::FileSysErr(_)
+                | deploy::DeployErr::InsufficientDiskSpace(_)
+                | deploy::DeployErr::StorageErr(_) => Self::Filesystem,
+                _ => Self::Other,
+            },
+            // the failure most in need of backing off decides for the whole sync;
+            // network errors only if every error was one
+            SyncErr::SyncErrors(e) => e
+                .errors
+                .iter()
+                .map(Self::of)
+                .filter(|class| *class != Self::Network)
+                .max_by_key(|class| class.precedence())
+                .unwrap_or(Self::Network),
+            _ => Self::Other,
+        }
+    }
+
+    fn of_http(e: &http::HTTPErr) -> Self {
+        match e {
+            http::HTTPErr::RequestFailed(e)
+                if matches!(e.status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) =>
+            {
+                Self::Auth
+            }
+            http::HTTPErr::RequestFailed(e) if e.status.is_server_error() => Self::Server,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Auth => "auth",
+            Self::Server => "server",
+            Self::Filesystem => "filesystem",
+            Self::Other => "other",
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Network => 0,
+            Self::Other => 1,
+            Self::Server => 2,
+            Self::Filesystem => 3,
+            Self::Auth => 4,
+        }
+    }
+}
+
+/// Backoff policies by failure class. A policy waits its base period after the first
+/// failure of its class and grows from there. A class without a policy uses the
+/// syncer's default backoff, with network errors always retried after its base
+/// period.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Policies {
+    pub network: Option<cooldown::Backoff>,
+    pub auth: Option<cooldown::Backoff>,
+    pub server: Option<cooldown::Backoff>,
+    pub filesystem: Option<cooldown::Backoff>,
+}
+
+impl Policies {
+    pub fn get(&self, class: FailureClass) -> Option<&cooldown::Backoff> {
+        match class {
+            FailureClass::Network => self.network.as_ref(),
+            FailureClass::Auth => self.auth.as_ref(),
+            FailureClass::Server => self.server.as_ref(),
+            FailureClass::Filesystem => self.filesystem.as_ref(),
+            FailureClass::Other => None,
+        }
+    }
+
+    /// The longest cooldown any of the policies allows.
+    pub fn max_secs(&self) -> Option<i64> {
+        [&self.network, &self.auth, &self.server, &self.filesystem]
+            .into_iter()
+            .flatten()
+            .map(|policy| policy.max_secs)
+            .max()
+    }
+}
+
+/// Consecutive failures of the same class. A failure of another class or a
+/// successful sync starts the count over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Streak {
+    pub class: Option<FailureClass>,
+    pub count: u32,
+}
+
+impl Streak {
+    pub fn record(&mut self, class: FailureClass) -> u32 {
+        if self.class == Some(class) {
+            self.count = self.count.saturating_add(1);
+        } else {
+            self.class = Some(class);
+            self.count = 1;
+        }
+        self.count
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
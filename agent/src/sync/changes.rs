@@ -0,0 +1,102 @@
+// The deployed config files a sync's apply changed, published as a `config.changed`
+// event so applications can reload their configs instead of polling the files. The
+// live files are read before and after applying deployments and compared by path: a
+// path which only exists after was deployed, one which only existed before was
+// removed, and one whose config instance changed, or whose deployment was deployed
+// again, was updated.
+
+// standard crates
+use std::collections::{BTreeMap, HashSet};
+
+// internal crates
+use crate::events::model::{ConfigChange, ConfigChangeKind};
+use crate::models::{self, DplActivity};
+use crate::storage;
+use crate::sync::errors::SyncErr;
+
+/// A config file of a live deployment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiveFile {
+    pub config_instance_id: String,
+    pub config_type_name: String,
+    pub deployment_id: String,
+}
+
+/// The config files of the deployments whose files are on disk, by path. Files of
+/// deployments marked drifted are still live until they're redeployed.
+pub async fn live_files(
+    deployments: &storage::Deployments,
+    cfg_insts: &storage::CfgInsts,
+) -> Result<BTreeMap<String, LiveFile>, SyncErr> {
+    let live = deployments
+        .find_where(|d| {
+            matches!(
+                d.activity_status,
+                DplActivity::Deployed | DplActivity::Drifted
+            )
+        })
+        .await?;
+    let ids = live
+        .iter()
+        .flat_map(|dpl| dpl.config_instance_ids.iter().cloned())
+        .collect();
+    let cached = cfg_insts.read_many(ids).await?;
+
+    let mut files = BTreeMap::new();
+    for dpl in &live {
+        for cfg_inst in dpl
+            .config_instance_ids
+            .iter()
+            .filter_map(|id| cached.get(id))
+        {
+            files.insert(cfg_inst.filepath.clone(), live_file(dpl, cfg_inst));
+        }
+    }
+    Ok(files)
+}
+
+fn live_file(dpl: &models::Deployment, cfg_inst: &models::ConfigInstance) -> LiveFile {
+    LiveFile {
+        config_instance_id: cfg_inst.id.clone(),
+        config_type_name: cfg_inst.config_type_name.clone(),
+        deployment_id: dpl.id.clone(),
+    }
+}
+
+/// The changes between the live files before and after applying deployments, in
+/// path order. `deployed` holds the ids of the deployments the apply deployed, whose
+/// files were rewritten even where their config instances didn't change.
+pub fn diff(
+    before: &BTreeMap<String, LiveFile>,
+    after: &BTreeMap<String, LiveFile>,
+    deployed: &HashSet<String>,
+) -> Vec<ConfigChange> {
+    let change = |kind, filepath: &str, file: &LiveFile| ConfigChange {
+        kind,
+        filepath: filepath.to_string(),
+        config_instance_id: file.config_instance_id.clone(),
+        config_type_name: file.config_type_name.clone(),
+        deployment_id: file.deployment_id.clone(),
+    };
+
+    let mut changes = Vec::new();
+    for (filepath, file) in after {
+        match before.get(filepath) {
+            None => changes.push(change(ConfigChangeKind::Deployed, filepath, file)),
+            Some(prev)
+                if prev.config_instance_id != file.config_instance_id
+                    || deployed.contains(&file.deployment_id) =>
+            {
+                changes.push(change(ConfigChangeKind::Updated, filepath, file))
+            }
+            Some(_) => {}
+        }
+    }
+    for (filepath, file) in before {
+        if !after.contains_key(filepath) {
+            changes.push(change(ConfigChangeKind::Removed, filepath, file));
+        }
+    }
+    changes.sort_by(|a, b| a.filepath.cmp(&b.filepath));
+    changes
+}
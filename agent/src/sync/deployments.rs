@@ -1,11 +1,31 @@
+// standard crates
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use std::time::Instant;
+
 // internal crates
-use crate::deploy::apply;
+use crate::cache::CacheErr;
+use crate::crypt::digest::{Algorithm, Digest};
+use crate::deploy::{apply, DeployErr};
+use crate::errors::{Error, HTTPCode};
 use crate::events;
 use crate::filesys::Overwrite;
-use crate::http;
-use crate::models::{self, deployment::DplActivity};
-use crate::storage;
+use crate::http::{
+    self,
+    conditional::{Conditional, Validators},
+};
+use crate::journal;
+use crate::models::{
+    self,
+    deployment::{DplActivity, DplErrStatus, DplTarget},
+};
+use crate::storage::{
+    self,
+    locks::{Resource, Writer},
+};
 use crate::sync::errors::*;
+use crate::sync::history::{self, PhaseTimer};
+use crate::sync::{changes, patch, warming};
 use crate::trace;
 use backend_api::models::{
     self as backend_client, DeploymentActivityStatus as BackendActivityStatus,
@@ -13,7 +33,8 @@ use backend_api::models::{
 };
 
 // external crates
-use tracing::{debug, error};
+use chrono::Utc;
+use tracing::{debug, error, info, warn};
 
 // =================================== SYNC ======================================== //
 pub struct SyncArgs<'a, HTTPClientT> {
@@ -22,6 +43,13 @@ pub struct SyncArgs<'a, HTTPClientT> {
     pub opts: &'a apply::DeployOpts,
     pub token: &'a str,
     pub event_hub: &'a events::EventHub,
+    pub warming: &'a warming::Warming,
+    /// The validators of the last deployments list stored, to skip storing it again
+    /// while it's unchanged.
+    pub list_validators: &'a Mutex<Validators>,
+    /// The head of the config access log's hash chain, anchored with the backend
+    /// when the deployments are listed.
+    pub audit_anchor: Option<&'a str>,
 }
 
 pub struct Storage<'a> {
@@ -29,6 +57,8 @@ pub struct Storage<'a> {
     pub cfg_insts: storage::CfgInstRef<'a>,
     pub releases: &'a storage::Releases,
     pub git_commits: &'a storage::GitCommits,
+    pub journal: &'a journal::Journal,
+    pub locks: &'a storage::Locks,
 }
 
 impl<'a> Storage<'a> {
@@ -38,34 +68,113 @@ impl<'a> Storage<'a> {
             cfg_insts: storage::CfgInstRef {
                 meta: self.cfg_insts.meta,
                 content: self.cfg_insts.content,
+                schemas: self.cfg_insts.schemas,
             },
+            releases: self.releases,
         }
     }
 }
 
+/// Syncs deployments with the backend, adding the time spent in each phase to
+/// `phases`.
 pub async fn sync<HTTPClientT: http::ClientI>(
     args: &SyncArgs<'_, HTTPClientT>,
+    phases: &mut history::Phases,
 ) -> Result<Option<chrono::TimeDelta>, SyncErr> {
     let mut errors = Vec::new();
 
     debug!("pulling deployments from server");
-    if let Err(e) = pull_deployments(args.http_client, args.storage, args.token).await {
+    let timer = PhaseTimer::start(&mut phases.fetch_ms);
+    if let Err(e) = pull_deployments(
+        args.http_client,
+        args.storage,
+        args.token,
+        args.list_validators,
+        args.audit_anchor,
+    )
+    .await
+    {
         error!("Failed to pull deployments: {e}");
         errors.push(e);
     }
+    drop(timer);
 
-    debug!("pulling content for config instances");
-    if let Err(e) = pull_content_for_cfg_insts(args.http_client, args.storage, args.token).await {
-        error!("Failed to pull content for config instances: {e}");
-        errors.push(e);
+    // content is neither downloaded nor deployed onto a nearly full disk, which would
+    // leave truncated content in the caches; statuses are still pushed
+    let has_space = match check_space(args.opts, args.event_hub).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Skipping content downloads and deployments: {e}");
+            errors.push(e);
+            false
+        }
+    };
+
+    if has_space {
+        debug!("pulling content for config instances");
+        let timer = PhaseTimer::start(&mut phases.download_ms);
+        if let Err(e) =
+            pull_content_for_cfg_insts(args.http_client, args.storage, args.token, args.warming)
+                .await
+        {
+            error!("Failed to pull content for config instances: {e}");
+            errors.push(e);
+        }
+        if args.opts.validate_schemas {
+            if let Err(e) = pull_config_schemas(args.http_client, args.storage, args.token).await {
+                error!("Failed to pull config schemas: {e}");
+                errors.push(e);
+            }
+        }
+        drop(timer);
     }
 
-    let wait = apply_deployments(args.storage, args.opts, args.event_hub, &mut errors).await;
+    // outside the maintenance windows the content downloaded above stays staged and
+    // the sync is repeated when the next window opens
+    let now = Utc::now();
+    let (in_window, window_wait) = match args.opts.windows.next_open(now) {
+        Some(opens_at) if opens_at <= now => (true, chrono::TimeDelta::zero()),
+        Some(opens_at) => {
+            info!("Outside the maintenance windows, staging deployments until {opens_at}");
+            (false, opens_at - now)
+        }
+        None => {
+            warn!("No maintenance window will open, staging deployments indefinitely");
+            (false, chrono::TimeDelta::zero())
+        }
+    };
+
+    let timer = PhaseTimer::start(&mut phases.materialize_ms);
+    let mut wait = if has_space && in_window {
+        apply_deployments(args.storage, args.opts, args.event_hub, &mut errors).await
+    } else {
+        window_wait
+    };
+    if let Err(e) = pin_deployed_content(args.storage).await {
+        error!("Failed to pin the content of deployed config instances: {e}");
+    }
+    drop(timer);
+
+    // sync again when the next deferred download is due
+    if let Some(warming_wait) = args.warming.next_due(Utc::now()).await {
+        if wait.is_zero() || warming_wait < wait {
+            wait = warming_wait;
+        }
+    }
 
     debug!("pushing deployment status updates to server");
-    if let Err(e) = push_deployments(args.http_client, args.storage.deployments, args.token).await {
+    let timer = PhaseTimer::start(&mut phases.reconcile_ms);
+    if let Err(e) = push_deployments(
+        args.http_client,
+        args.storage.deployments,
+        args.storage.journal,
+        args.token,
+    )
+    .await
+    {
         errors.push(e);
     }
+    drop(timer);
 
     if errors.is_empty() {
         Ok(if wait.is_zero() { None } else { Some(wait) })
@@ -77,56 +186,146 @@ pub async fn sync<HTTPClientT: http::ClientI>(
     }
 }
 
+async fn check_space(
+    opts: &apply::DeployOpts,
+    event_hub: &events::EventHub,
+) -> Result<(), SyncErr> {
+    let Some(guard) = &opts.space else {
+        return Ok(());
+    };
+    let result = guard.check();
+    if guard.ran_low(result.is_err()) {
+        if let Err(e) = &result {
+            match events::EventArgs::disk_low(&e.mount_point, e.available_bytes, e.total_bytes) {
+                Ok(event) => event_hub.try_publish(event).await,
+                Err(e) => error!("failed to build disk low event: {e}"),
+            }
+        }
+    }
+    result.map_err(|e| DeployErr::from(e).into())
+}
+
 // =================================== PULL ======================================== //
 async fn pull_deployments<'a, HTTPClientT: http::ClientI>(
     http_client: &HTTPClientT,
     storage: &Storage<'a>,
     token: &str,
+    list_validators: &Mutex<Validators>,
+    audit_anchor: Option<&str>,
 ) -> Result<(), SyncErr> {
-    let active_deployments = fetch_active_deployments(http_client, token).await?;
+    // the validators are only put back once the list is stored, so a list which
+    // failed to store is fetched whole next time
+    let validators = std::mem::take(&mut *lock(list_validators));
+    let fetched =
+        fetch_active_deployments_if_modified(http_client, token, &validators, audit_anchor).await?;
+    let (active_deployments, validators) = match fetched {
+        Conditional::Modified { value, validators } => (value, validators),
+        Conditional::NotModified => {
+            debug!("active deployments unchanged since the last sync");
+            *lock(list_validators) = validators;
+            return Ok(());
+        }
+    };
     debug!("found {} active deployments", active_deployments.len());
 
-    for backend_dpl in active_deployments {
-        let cfg_insts = backend_dpl.config_instances.clone().ok_or_else(|| {
+    let mut cfg_inst_ids = Vec::with_capacity(active_deployments.len());
+    let mut cfg_insts = Vec::new();
+    for listed in &active_deployments {
+        let backend_dpl = &listed.deployment;
+        let backend_cfg_insts = backend_dpl.config_instances.as_ref().ok_or_else(|| {
             SyncErr::CfgInstsNotExpanded(CfgInstsNotExpandedErr {
                 deployment_id: backend_dpl.id.clone(),
             })
         })?;
-        let cfg_inst_ids = cfg_insts.iter().map(|inst| inst.id.clone()).collect();
+        cfg_inst_ids.push(
+            backend_cfg_insts
+                .iter()
+                .map(|inst| inst.id.clone())
+                .collect(),
+        );
+        cfg_insts.extend(
+            backend_cfg_insts
+                .iter()
+                .cloned()
+                .map(models::ConfigInstance::from),
+        );
+    }
 
-        store_expanded_release(storage, &backend_dpl).await?;
-        store_deployment(storage.deployments, backend_dpl, cfg_inst_ids).await?;
+    // the config instances are stored before the deployments referencing them
+    store_cfg_insts(storage, cfg_insts).await?;
 
-        for backend_cfg_inst in &cfg_insts {
-            let cfg_inst: models::ConfigInstance = backend_cfg_inst.clone().into();
-            let cfg_inst_id = cfg_inst.id.clone();
-            storage
-                .cfg_insts
-                .meta
-                .write_if_absent(cfg_inst_id, cfg_inst, |_, _| false)
-                .await?;
-        }
+    for (listed, cfg_inst_ids) in active_deployments.into_iter().zip(cfg_inst_ids) {
+        let backend_dpl = listed.deployment;
+        store_expanded_release(storage, &backend_dpl, listed.release_signature).await?;
+        let _guard = storage
+            .locks
+            .lock(
+                Writer::Syncer,
+                [Resource::Deployment(backend_dpl.id.clone())],
+            )
+            .await;
+        store_deployment(
+            storage.deployments,
+            backend_dpl,
+            cfg_inst_ids,
+            listed.dependencies,
+        )
+        .await?;
     }
 
+    *lock(list_validators) = validators;
+    Ok(())
+}
+
+fn lock(validators: &Mutex<Validators>) -> std::sync::MutexGuard<'_, Validators> {
+    validators.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Stores the config instances which aren't already cached. Config instances are
+/// immutable so cached ones are left as they are. The cache is read and written in
+/// one batch each rather than once per config instance.
+async fn store_cfg_insts(
+    storage: &Storage<'_>,
+    cfg_insts: Vec<models::ConfigInstance>,
+) -> Result<(), SyncErr> {
+    let ids = cfg_insts.iter().map(|inst| inst.id.clone()).collect();
+    let cached = storage.cfg_insts.meta.read_many(ids).await?;
+    let mut seen = HashSet::new();
+    let absent: Vec<_> = cfg_insts
+        .into_iter()
+        .filter(|inst| !cached.contains_key(&inst.id) && seen.insert(inst.id.clone()))
+        .map(|inst| (inst.id.clone(), inst))
+        .collect();
+    if absent.is_empty() {
+        return Ok(());
+    }
+    debug!("storing {} new config instances", absent.len());
+    storage
+        .cfg_insts
+        .meta
+        .write_many(absent, |_, _| false, Overwrite::Allow)
+        .await?;
     Ok(())
 }
 
-async fn fetch_active_deployments<HTTPClientT: http::ClientI>(
+const ACTIVE_STATUSES: &[BackendActivityStatus] = &[
+    BackendActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_QUEUED,
+    BackendActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+];
+const LIST_EXPANSIONS: &[&str] = &["config_instances", "release.git_commit"];
+
+pub(super) async fn fetch_active_deployments<HTTPClientT: http::ClientI>(
     http_client: &HTTPClientT,
     token: &str,
-) -> Result<Vec<backend_api::models::Deployment>, SyncErr> {
-    let activity_status_filter = &[
-        BackendActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_QUEUED,
-        BackendActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
-    ];
-    let expansions: &[&str] = &["config_instances", "release.git_commit"];
-    http::with_retry(|| {
+) -> Result<Vec<http::deployments::ListedDeployment>, SyncErr> {
+    http::with_retry(http_client, || {
         http::deployments::list_all(
             http_client,
             http::deployments::ListAllParams {
-                activity_status: activity_status_filter,
-                expansions,
+                activity_status: ACTIVE_STATUSES,
+                expansions: LIST_EXPANSIONS,
                 token,
+                audit_anchor: None,
             },
         )
     })
@@ -134,27 +333,137 @@ async fn fetch_active_deployments<HTTPClientT: http::ClientI>(
     .map_err(SyncErr::from)
 }
 
+async fn fetch_active_deployments_if_modified<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    token: &str,
+    validators: &Validators,
+    audit_anchor: Option<&str>,
+) -> Result<Conditional<Vec<http::deployments::ListedDeployment>>, SyncErr> {
+    http::with_retry(http_client, || {
+        http::deployments::list_all_if_modified(
+            http_client,
+            http::deployments::ListAllParams {
+                activity_status: ACTIVE_STATUSES,
+                expansions: LIST_EXPANSIONS,
+                token,
+                audit_anchor,
+            },
+            validators,
+        )
+    })
+    .await
+    .map_err(SyncErr::from)
+}
+
 async fn pull_content_for_cfg_insts<'a, HTTPClientT: http::ClientI>(
     http_client: &HTTPClientT,
     storage: &Storage<'a>,
     token: &str,
+    warming: &warming::Warming,
 ) -> Result<(), SyncErr> {
-    let deployments = storage.deployments.entries().await?;
-    let mut seen = std::collections::HashSet::new();
+    // deployments targeted Deployed can't be applied without their content, so it is
+    // pulled first and never deferred while the cache warms
+    let mut deployments = storage.deployments.entries().await?;
+    deployments.sort_by_key(|entry| entry.value.target_status != DplTarget::Deployed);
+    let urgent: HashSet<&String> = deployments
+        .iter()
+        .filter(|entry| entry.value.target_status == DplTarget::Deployed)
+        .flat_map(|entry| entry.value.config_instance_ids.iter())
+        .collect();
+
     let mut errors = Vec::new();
+    let mut checked = HashSet::new();
+    let mut missing = HashSet::new();
+    for cfg_inst_id in deployments
+        .iter()
+        .flat_map(|entry| entry.value.config_instance_ids.iter())
+    {
+        if !checked.insert(cfg_inst_id) {
+            continue;
+        }
+        match is_cached(&storage.cfg_insts, cfg_inst_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                missing.insert(cfg_inst_id.clone());
+            }
+            Err(e) => {
+                error!("Failed to read content for config instance: {e}");
+                errors.push(e);
+            }
+        }
+    }
+    let deferrable = missing.iter().filter(|id| !urgent.contains(id)).count();
+    let mut budget = warming.budget(deferrable, Utc::now()).await;
+    let mut warmed = 0;
+    let mut deferred = 0;
 
-    for deployment in deployments {
-        for cfg_inst_id in deployment.value.config_instance_ids.clone() {
-            if !seen.insert(cfg_inst_id.clone()) {
+    let mut seen = HashSet::new();
+    for deployment in &deployments {
+        let started_at = Instant::now();
+        let mut bytes_downloaded = 0;
+        let mut downloaded_any = false;
+        for cfg_inst_id in &deployment.value.config_instance_ids {
+            if !missing.contains(cfg_inst_id) || !seen.insert(cfg_inst_id.clone()) {
                 continue;
             }
-            if let Err(e) =
-                pull_cfg_inst_content(http_client, &storage.cfg_insts, cfg_inst_id, token).await
+            let is_urgent = urgent.contains(cfg_inst_id);
+            if !is_urgent {
+                match budget.as_mut() {
+                    Some(0) => {
+                        deferred += 1;
+                        continue;
+                    }
+                    Some(remaining) => *remaining -= 1,
+                    None => {}
+                }
+            }
+            let _guard = storage
+                .locks
+                .lock(
+                    Writer::Syncer,
+                    [Resource::ConfigInstance(cfg_inst_id.clone())],
+                )
+                .await;
+            match download_cfg_inst_content(
+                http_client,
+                &storage.cfg_insts,
+                cfg_inst_id.clone(),
+                token,
+            )
+            .await
             {
-                error!("Failed to pull content for config instance: {e}");
-                errors.push(e);
+                Ok(bytes) => {
+                    bytes_downloaded += bytes;
+                    downloaded_any = true;
+                    if !is_urgent {
+                        warmed += 1;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to pull content for config instance: {e}");
+                    errors.push(e);
+                }
             }
         }
+        if downloaded_any {
+            let staging_ms = started_at.elapsed().as_millis() as u64;
+            if let Err(e) = record_staging(
+                storage.deployments,
+                deployment.key.clone(),
+                bytes_downloaded,
+                staging_ms,
+            )
+            .await
+            {
+                error!("Failed to record deployment staging metrics: {e}");
+            }
+        }
+    }
+    if budget.is_some() {
+        warming.record(warmed).await;
+        if deferred > 0 {
+            debug!("deferred the download of {deferred} config instances while warming the cache");
+        }
     }
 
     if errors.is_empty() {
@@ -167,22 +476,49 @@ async fn pull_content_for_cfg_insts<'a, HTTPClientT: http::ClientI>(
     }
 }
 
-async fn pull_cfg_inst_content<HTTPClientT: http::ClientI>(
+/// Whether the content of a config instance is cached intact. Reading it verifies its
+/// digest, so corrupted content counts as missing and is downloaded again.
+async fn is_cached(storage: &storage::CfgInstRef<'_>, cfg_inst_id: &str) -> Result<bool, SyncErr> {
+    match storage.content.read_optional(cfg_inst_id.to_string()).await {
+        Ok(cached) => Ok(cached.is_some()),
+        Err(CacheErr::CorruptedCacheElement(e)) => {
+            warn!("{e}");
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Downloads and caches the content of a config instance, returning the number of
+/// bytes downloaded.
+async fn download_cfg_inst_content<HTTPClientT: http::ClientI>(
     http_client: &HTTPClientT,
     storage: &storage::CfgInstRef<'_>,
     cfg_inst_id: String,
     token: &str,
-) -> Result<(), SyncErr> {
-    if storage
-        .content
+) -> Result<u64, SyncErr> {
+    // the content cache's admission policy has rules by config type
+    let class = storage
+        .meta
         .read_optional(cfg_inst_id.clone())
         .await?
-        .is_some()
-    {
-        return Ok(());
+        .map(|cfg_inst| cfg_inst.config_type_name);
+
+    match pull_cfg_inst_patch(http_client, storage, &cfg_inst_id, token).await {
+        Ok(Some((content, bytes))) => {
+            storage
+                .content
+                .write_with_class(cfg_inst_id, content, class, |_, _| false, Overwrite::Allow)
+                .await?;
+            return Ok(bytes);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            debug!("falling back to a full download of config instance '{cfg_inst_id}': {e}");
+        }
     }
 
-    let content = http::with_retry(|| {
+    let content = http::with_retry(http_client, || {
         http::config_instances::get_content(
             http_client,
             http::config_instances::GetContentParams {
@@ -192,10 +528,180 @@ async fn pull_cfg_inst_content<HTTPClientT: http::ClientI>(
         )
     })
     .await?;
+    let bytes = content.len() as u64;
 
     storage
         .content
-        .write(cfg_inst_id, content, |_, _| false, Overwrite::Allow)
+        .write_with_class(cfg_inst_id, content, class, |_, _| false, Overwrite::Allow)
+        .await?;
+    Ok(bytes)
+}
+
+/// Downloads the config schemas of the config instances of deployments targeting
+/// deployed which aren't cached yet, so their content can be validated before it's
+/// deployed. Schemas the backend doesn't have are skipped.
+async fn pull_config_schemas<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    storage: &Storage<'_>,
+    token: &str,
+) -> Result<(), SyncErr> {
+    let deployments = storage
+        .deployments
+        .find_where(|dpl| dpl.target_status == DplTarget::Deployed)
+        .await?;
+    let mut schema_ids = HashSet::new();
+    for deployment in deployments {
+        for cfg_inst_id in deployment.config_instance_ids {
+            if let Some(cfg_inst) = storage.cfg_insts.meta.read_optional(cfg_inst_id).await? {
+                if !cfg_inst.config_schema_id.is_empty() {
+                    schema_ids.insert(cfg_inst.config_schema_id);
+                }
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    for schema_id in schema_ids {
+        if storage
+            .cfg_insts
+            .schemas
+            .read_optional(schema_id.clone())
+            .await?
+            .is_some()
+        {
+            continue;
+        }
+        let result = http::with_retry(http_client, || {
+            http::config_schemas::get(
+                http_client,
+                http::config_schemas::GetParams {
+                    id: &schema_id,
+                    token,
+                },
+            )
+        })
+        .await;
+        match result {
+            Ok(config_schema) => {
+                storage
+                    .cfg_insts
+                    .schemas
+                    .write(schema_id, config_schema, |_, _| false, Overwrite::Allow)
+                    .await?;
+            }
+            Err(e) if e.http_status() == HTTPCode::NOT_FOUND => {
+                debug!("config schema '{schema_id}' doesn't exist, skipping its validation");
+            }
+            Err(e) => {
+                error!("Failed to pull config schema '{schema_id}': {e}");
+                errors.push(e.into());
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(SyncErr::SyncErrors(SyncErrors {
+            errors,
+            trace: trace!(),
+        }))
+    }
+}
+
+/// Builds the content of a config instance by patching the cached content of an
+/// earlier config instance written to the same file, returning the patched content
+/// and the size of the patch. Returns `None`, without asking the backend for a patch,
+/// if no such config instance is cached with content a patch can be applied to.
+async fn pull_cfg_inst_patch<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    storage: &storage::CfgInstRef<'_>,
+    cfg_inst_id: &str,
+    token: &str,
+) -> Result<Option<(String, u64)>, SyncErr> {
+    let Some((base_id, base)) = find_patch_base(storage, cfg_inst_id).await? else {
+        return Ok(None);
+    };
+    let content_patch = http::config_instances::get_patch(
+        http_client,
+        http::config_instances::GetPatchParams {
+            id: cfg_inst_id,
+            base_id: &base_id,
+            base_digest: &Digest::compute(Algorithm::Sha256, base.as_bytes()).to_string(),
+            token,
+        },
+    )
+    .await?;
+    let bytes = content_patch.patch.to_string().len() as u64;
+    let content = patch::apply(&base, &content_patch)?;
+    debug!("patched config instance '{cfg_inst_id}' from '{base_id}' ({bytes} bytes)");
+    Ok(Some((content, bytes)))
+}
+
+/// Finds the most recent cached config instance (other than the given one) of the
+/// same config type written to the same file, returning its id and content. Content
+/// which fails its cache digest, or isn't canonical JSON (see [patch::canonical]),
+/// can't reproduce a full download so isn't a base.
+async fn find_patch_base(
+    storage: &storage::CfgInstRef<'_>,
+    cfg_inst_id: &str,
+) -> Result<Option<(models::CfgInstID, String)>, SyncErr> {
+    let Some(cfg_inst) = storage.meta.read_optional(cfg_inst_id.to_string()).await? else {
+        return Ok(None);
+    };
+    let mut candidates = storage
+        .meta
+        .find_where(move |other| {
+            other.id != cfg_inst.id
+                && other.filepath == cfg_inst.filepath
+                && other.config_type_id == cfg_inst.config_type_id
+        })
+        .await?;
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+    for candidate in candidates {
+        let content = match storage.content.read_optional(candidate.id.clone()).await {
+            Ok(Some(content)) => content,
+            Ok(None) => continue,
+            Err(CacheErr::CorruptedCacheElement(e)) => {
+                warn!("{e}");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !patch::is_canonical(&content) {
+            debug!(
+                "cached content of '{}' isn't canonical JSON, not patching from it",
+                candidate.id
+            );
+            continue;
+        }
+        return Ok(Some((candidate.id, content)));
+    }
+    Ok(None)
+}
+
+/// Adds the size and duration of a content download to a deployment's metrics.
+/// Staging may span several syncs (e.g. after a failed download) so both are
+/// accumulated rather than replaced.
+async fn record_staging(
+    storage: &storage::Deployments,
+    deployment_id: models::DeploymentID,
+    bytes_downloaded: u64,
+    staging_ms: u64,
+) -> Result<(), SyncErr> {
+    let Some(mut deployment) = storage.read_optional(deployment_id.clone()).await? else {
+        return Ok(());
+    };
+    deployment.metrics.bytes_downloaded += bytes_downloaded;
+    deployment.metrics.staging_duration_ms =
+        Some(deployment.metrics.staging_duration_ms.unwrap_or(0) + staging_ms);
+    storage
+        .write(
+            deployment_id,
+            deployment,
+            |old, _| old.is_some_and(|entry| entry.is_dirty),
+            Overwrite::Allow,
+        )
         .await
         .map_err(SyncErr::from)
 }
@@ -210,8 +716,12 @@ async fn store_deployment(
     storage: &storage::Deployments,
     backend_dpl: backend_client::Deployment,
     cfg_inst_ids: Vec<String>,
+    dependencies: models::DplDependencies,
 ) -> Result<(), SyncErr> {
-    let storage_dpl = models::Deployment::from_backend(backend_dpl, cfg_inst_ids);
+    let storage_dpl = models::Deployment {
+        dependencies,
+        ..models::Deployment::from_backend(backend_dpl, cfg_inst_ids)
+    };
     let deployment_id = storage_dpl.id.clone();
 
     let existing = storage.read_optional(deployment_id.clone()).await?;
@@ -234,21 +744,38 @@ async fn store_deployment(
 ///
 /// Uses `write_if_absent` because releases and git_commits are immutable on the
 /// backend — once created, their fields never change. Skipping writes for
-/// already-cached entries avoids unnecessary I/O on every sync cycle.
+/// already-cached entries avoids unnecessary I/O on every sync cycle. The one
+/// exception is a signature missing from a release cached before it was read.
 async fn store_expanded_release(
     storage: &Storage<'_>,
     backend_dpl: &backend_client::Deployment,
+    signature: Option<models::Signature>,
 ) -> Result<(), SyncErr> {
     let Some(backend_release) = backend_dpl.release.as_deref() else {
         return Ok(());
     };
 
-    let release: models::Release = backend_release.clone().into();
+    let mut release: models::Release = backend_release.clone().into();
+    release.signature = signature;
     let release_id = release.id.clone();
-    storage
-        .releases
-        .write_if_absent(release_id, release, |_, _| false)
-        .await?;
+    let cached = match release.signature {
+        Some(_) => storage.releases.read_optional(release_id.clone()).await?,
+        None => None,
+    };
+    match cached {
+        Some(cached) if cached.signature != release.signature => {
+            storage
+                .releases
+                .write(release_id, release, |_, _| false, Overwrite::Allow)
+                .await?;
+        }
+        _ => {
+            storage
+                .releases
+                .write_if_absent(release_id, release, |_, _| false)
+                .await?;
+        }
+    }
 
     let Some(Some(backend_gc)) = &backend_release.git_commit else {
         return Ok(());
@@ -265,16 +792,20 @@ async fn store_expanded_release(
 }
 
 // Cached deployment entries are intentionally authoritative for all fields except
-// `target_status`, which is always taken from the backend payload.
+// `target_status` and `dependencies`, which are always taken from the backend payload.
 //
 // This preserves locally derived state (activity/error transitions, attempts,
 // cooldown metadata, and dirty-retry context) while still reacting to backend
 // target changes.
-fn resolve_dpl(new: models::Deployment, cached: Option<models::Deployment>) -> models::Deployment {
+pub(super) fn resolve_dpl(
+    new: models::Deployment,
+    cached: Option<models::Deployment>,
+) -> models::Deployment {
     match cached {
         Some(cached) => models::Deployment {
             target_status: new.target_status,
             updated_at: new.updated_at,
+            dependencies: new.dependencies,
             ..cached
         },
         None => new,
@@ -289,6 +820,27 @@ async fn apply_deployments<'a>(
     errors: &mut Vec<SyncErr>,
 ) -> chrono::TimeDelta {
     debug!("applying deployments");
+    // the deployments are read up front and written back as they transition, so no
+    // other writer may touch them until every deployment has been applied
+    let ids = match storage.deployments.entries().await {
+        Ok(entries) => entries.into_iter().map(|entry| entry.key),
+        Err(e) => {
+            error!("Failed to read deployments to apply: {e}");
+            errors.push(SyncErr::from(e));
+            return chrono::TimeDelta::zero();
+        }
+    };
+    let _guard = storage
+        .locks
+        .lock(Writer::Syncer, ids.map(Resource::Deployment))
+        .await;
+    let before = match changes::live_files(storage.deployments, storage.cfg_insts.meta).await {
+        Ok(files) => Some(files),
+        Err(e) => {
+            error!("Failed to read the live config files before applying deployments: {e}");
+            None
+        }
+    };
     let apply_stor = storage.apply_storage();
     let apply_args = apply::Args {
         storage: &apply_stor,
@@ -303,17 +855,28 @@ async fn apply_deployments<'a>(
         }
     };
     let mut wait: Option<chrono::TimeDelta> = None;
+    let mut deployed = HashSet::new();
     for outcome in outcomes {
         if let Some(e) = outcome.error {
             error!("error applying deployment {}: {}", outcome.deployment.id, e);
+            // deployments which will no longer be retried are surfaced to operators
+            if outcome.transitioned && outcome.deployment.error_status == DplErrStatus::Failed {
+                match events::EventArgs::failed(&outcome.deployment, &e.to_string()) {
+                    Ok(event) => event_hub.try_publish(event).await,
+                    Err(e) => error!("failed to build failed event: {e}"),
+                }
+            }
         } else if outcome.transitioned {
             debug!("successfully applied deployment {}", outcome.deployment.id);
             // emit deployment events on success
             match outcome.deployment.activity_status {
-                DplActivity::Deployed => match events::EventArgs::deployed(&outcome.deployment) {
-                    Ok(event) => event_hub.try_publish(event).await,
-                    Err(e) => error!("failed to build deployed event: {e}"),
-                },
+                DplActivity::Deployed => {
+                    deployed.insert(outcome.deployment.id.clone());
+                    match events::EventArgs::deployed(&outcome.deployment) {
+                        Ok(event) => event_hub.try_publish(event).await,
+                        Err(e) => error!("failed to build deployed event: {e}"),
+                    }
+                }
                 DplActivity::Archived => match events::EventArgs::removed(&outcome.deployment) {
                     Ok(event) => event_hub.try_publish(event).await,
                     Err(e) => error!("failed to build removed event: {e}"),
@@ -331,13 +894,86 @@ async fn apply_deployments<'a>(
             });
         }
     }
+    if let Some(before) = before {
+        publish_config_changes(storage, event_hub, &before, &deployed).await;
+    }
     wait.unwrap_or(chrono::TimeDelta::zero())
 }
 
+/// Publishes the config files the apply deployed, updated or removed so applications
+/// can reload them.
+async fn publish_config_changes(
+    storage: &Storage<'_>,
+    event_hub: &events::EventHub,
+    before: &BTreeMap<String, changes::LiveFile>,
+    deployed: &HashSet<String>,
+) {
+    let after = match changes::live_files(storage.deployments, storage.cfg_insts.meta).await {
+        Ok(files) => files,
+        Err(e) => {
+            error!("Failed to read the live config files after applying deployments: {e}");
+            return;
+        }
+    };
+    let changes = changes::diff(before, &after, deployed);
+    if changes.is_empty() {
+        return;
+    }
+    match events::EventArgs::config_changed(changes) {
+        Ok(event) => event_hub.try_publish(event).await,
+        Err(e) => error!("failed to build config changed event: {e}"),
+    }
+}
+
+/// Pins the content of deployed config instances so that pruning the content cache
+/// can't evict content a live deployment references, and unpins the content of
+/// config instances which are no longer deployed.
+async fn pin_deployed_content(storage: &Storage<'_>) -> Result<(), SyncErr> {
+    let deployments = storage.deployments.entries().await?;
+    let deployed: HashSet<&String> = deployments
+        .iter()
+        .filter(|entry| entry.value.activity_status == DplActivity::Deployed)
+        .flat_map(|entry| entry.value.config_instance_ids.iter())
+        .collect();
+    let undeployed: HashSet<&String> = deployments
+        .iter()
+        .flat_map(|entry| entry.value.config_instance_ids.iter())
+        .filter(|id| !deployed.contains(id))
+        .collect();
+
+    let pins = deployed
+        .into_iter()
+        .map(|id| (id, true))
+        .chain(undeployed.into_iter().map(|id| (id, false)));
+    for (cfg_inst_id, pin) in pins {
+        let _guard = storage
+            .locks
+            .lock(
+                Writer::Syncer,
+                [Resource::ConfigInstance(cfg_inst_id.clone())],
+            )
+            .await;
+        let content = storage.cfg_insts.content;
+        let result = if pin {
+            content.pin(cfg_inst_id.clone()).await
+        } else {
+            content.unpin(cfg_inst_id.clone()).await
+        };
+        if let Err(e) = result {
+            warn!("Failed to update the pin of config instance '{cfg_inst_id}': {e}");
+        }
+    }
+    Ok(())
+}
+
 // =================================== PUSH ======================================== //
+/// Queues the status of every dirty deployment in the request journal and then
+/// drains the journal. Status updates which can't be sent stay queued until the next
+/// sync or journal drain.
 async fn push_deployments<HTTPClientT: http::ClientI>(
     http_client: &HTTPClientT,
     storage: &storage::Deployments,
+    journal: &journal::Journal,
     token: &str,
 ) -> Result<(), SyncErr> {
     let dirty_entries = storage.get_dirty_entries().await?;
@@ -347,12 +983,15 @@ async fn push_deployments<HTTPClientT: http::ClientI>(
 
     for dirty_entry in dirty_entries {
         let deployment = dirty_entry.value;
-        if let Err(e) = push_deployment(http_client, storage, deployment, token).await {
+        if let Err(e) = queue_deployment(storage, journal, deployment).await {
             errors.push(e);
             continue;
         }
     }
 
+    let drained = journal.drain(http_client, token).await?;
+    errors.extend(drained.errors.into_iter().map(SyncErr::from));
+
     if errors.is_empty() {
         Ok(())
     } else {
@@ -363,11 +1002,10 @@ async fn push_deployments<HTTPClientT: http::ClientI>(
     }
 }
 
-async fn push_deployment<HTTPClientT: http::ClientI>(
-    http_client: &HTTPClientT,
+async fn queue_deployment(
     storage: &storage::Deployments,
+    journal: &journal::Journal,
     deployment: models::Deployment,
-    token: &str,
 ) -> Result<(), SyncErr> {
     let activity = Some((&deployment.activity_status).into());
     let error_status = Some((&deployment.error_status).into());
@@ -379,21 +1017,19 @@ async fn push_deployment<HTTPClientT: http::ClientI>(
     };
 
     debug!(
-        "pushing deployment '{}' updates to server: {:?}",
+        "queueing deployment '{}' updates for the server: {:?}",
         deployment.id, payload
     );
 
-    http::with_retry(|| {
-        let params = http::deployments::UpdateParams {
-            id: &deployment.id,
-            updates: &payload,
-            token,
-        };
-        http::deployments::update(http_client, params)
-    })
-    .await?;
+    journal
+        .enqueue(journal::Request::DeploymentStatus {
+            deployment_id: deployment.id.clone(),
+            updates: payload,
+            metrics: (!deployment.metrics.is_empty()).then(|| deployment.metrics.clone()),
+        })
+        .await?;
 
-    // mark as clean in storage
+    // the journal now owns the update so mark as clean in storage
     storage
         .write(
             deployment.id.clone(),
@@ -5,6 +5,7 @@ use crate::deploy;
 use crate::errors::Trace;
 use crate::filesys;
 use crate::http;
+use crate::journal;
 use crate::storage::StorageErr;
 
 // external crates
@@ -70,6 +71,26 @@ pub struct CfgInstsNotExpandedErr {
 
 impl crate::errors::Error for CfgInstsNotExpandedErr {}
 
+#[derive(Debug, thiserror::Error)]
+#[error("failed to apply patch from config instance '{base_id}': {msg}")]
+pub struct ContentPatchErr {
+    pub base_id: String,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ContentPatchErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("patched content digest '{actual}' does not match the expected digest '{expected}'")]
+pub struct ContentDigestMismatchErr {
+    pub expected: String,
+    pub actual: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for ContentDigestMismatchErr {}
+
 #[derive(Debug, thiserror::Error)]
 pub enum SyncErr {
     #[error(transparent)]
@@ -96,6 +117,12 @@ pub enum SyncErr {
     MockErr(MockErr),
     #[error(transparent)]
     CfgInstsNotExpanded(CfgInstsNotExpandedErr),
+    #[error(transparent)]
+    ContentPatchErr(ContentPatchErr),
+    #[error(transparent)]
+    ContentDigestMismatchErr(ContentDigestMismatchErr),
+    #[error(transparent)]
+    JournalErr(journal::JournalErr),
 }
 
 impl From<authn::AuthnErr> for SyncErr {
@@ -140,6 +167,24 @@ impl From<CfgInstsNotExpandedErr> for SyncErr {
     }
 }
 
+impl From<ContentPatchErr> for SyncErr {
+    fn from(e: ContentPatchErr) -> Self {
+        Self::ContentPatchErr(e)
+    }
+}
+
+impl From<ContentDigestMismatchErr> for SyncErr {
+    fn from(e: ContentDigestMismatchErr) -> Self {
+        Self::ContentDigestMismatchErr(e)
+    }
+}
+
+impl From<journal::JournalErr> for SyncErr {
+    fn from(e: journal::JournalErr) -> Self {
+        Self::JournalErr(e)
+    }
+}
+
 crate::impl_error!(SyncErr {
     AuthnErr,
     CacheErr,
@@ -153,4 +198,7 @@ crate::impl_error!(SyncErr {
     ReceiveActorMessageErr,
     MockErr,
     CfgInstsNotExpanded,
+    ContentPatchErr,
+    ContentDigestMismatchErr,
+    JournalErr,
 });
@@ -0,0 +1,146 @@
+// Keeps the syncer's recent events with the time each was sent. Subscribers only see
+// the latest event, so a failure the syncer recovered from before anyone looked would
+// otherwise leave no trace. Like the sync history the log is optionally persisted so
+// the `sync-history` command can show it whether or not the agent is running.
+
+// standard crates
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+// internal crates
+use crate::filesys::{self, PathExt, WriteOptions};
+use crate::sync::syncer::{CooldownEnd, SyncEvent};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Number of events kept, oldest first out.
+    pub capacity: usize,
+    /// Persists the log to this file after every event.
+    pub file: Option<filesys::File>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            capacity: 100,
+            file: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub at: DateTime<Utc>,
+    pub event: SyncEvent,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event = match &self.event {
+            SyncEvent::SyncSuccess => "sync succeeded",
+            SyncEvent::SyncFailed(failure) if failure.is_network_conn_err => {
+                "sync failed (network connection error)"
+            }
+            SyncEvent::SyncFailed(_) => "sync failed",
+            SyncEvent::CooldownEnd(CooldownEnd::SyncSuccess) => "cooldown ended after a success",
+            SyncEvent::CooldownEnd(CooldownEnd::SyncFailure) => "cooldown ended after a failure",
+            SyncEvent::CooldownEnd(CooldownEnd::DeploymentWait) => {
+                "cooldown ended for a deployment wait"
+            }
+        };
+        write!(f, "{}  {event}", self.at.to_rfc3339())
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    records: VecDeque<Record>,
+    file: Option<filesys::File>,
+}
+
+/// A ring buffer of the syncer's events. Clones share the same buffer so events sent
+/// from the syncer's background tasks land in it too.
+#[derive(Clone, Debug)]
+pub struct Log {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
+}
+
+impl Log {
+    pub fn new(options: Options) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                capacity: options.capacity.max(1),
+                records: VecDeque::new(),
+                file: options.file,
+            })),
+        }
+    }
+
+    /// Creates a log with the events persisted to the options' file, if any. An
+    /// unreadable file starts an empty log rather than failing the agent.
+    pub async fn load(options: Options) -> Self {
+        let records = match &options.file {
+            Some(file) if file.exists() => read(file).await,
+            _ => Vec::new(),
+        };
+        let log = Self::new(options);
+        {
+            let mut inner = log.inner.lock().await;
+            let overflow = records.len().saturating_sub(inner.capacity);
+            inner.records = records.into_iter().skip(overflow).collect();
+        }
+        log
+    }
+
+    /// The recorded events, oldest first.
+    pub async fn records(&self) -> Vec<Record> {
+        self.inner.lock().await.records.iter().cloned().collect()
+    }
+
+    pub async fn push(&self, event: SyncEvent) {
+        let mut inner = self.inner.lock().await;
+        if inner.records.len() == inner.capacity {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(Record {
+            at: Utc::now(),
+            event,
+        });
+
+        // write while holding the lock so concurrent pushes persist in order
+        let Some(file) = &inner.file else {
+            return;
+        };
+        if let Err(e) = file
+            .write_json(&inner.records, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+        {
+            error!("failed to persist the sync events: {e}");
+        }
+    }
+}
+
+/// Reads the events persisted to a file, oldest first. An unreadable file has none.
+pub async fn read(file: &filesys::File) -> Vec<Record> {
+    match file.read_json::<Vec<Record>>().await {
+        Ok(records) => records,
+        Err(e) => {
+            warn!("discarding the unreadable sync events: {e}");
+            Vec::new()
+        }
+    }
+}
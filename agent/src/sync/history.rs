@@ -0,0 +1,272 @@
+// Keeps the durations, outcomes and errors of the last syncs so support can tell at a
+// glance whether a slow device is network bound (fetch, download) or disk bound
+// (materialize), and can diagnose intermittent failures after the fact. The history
+// lives in the syncer and is optionally persisted so it survives restarts.
+
+// standard crates
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// internal crates
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Number of syncs kept, oldest first out.
+    pub capacity: usize,
+    /// Persists the history to this file after every sync.
+    pub file: Option<filesys::File>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            capacity: 50,
+            file: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    /// The sync failed because the backend couldn't be reached.
+    NetworkError,
+    Error,
+}
+
+/// Time spent in each phase of a sync, in milliseconds. Phases a failed sync never
+/// reached are zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Phases {
+    /// Listing the active deployments from the backend.
+    pub fetch_ms: u64,
+    /// Downloading the content of new config instances.
+    pub download_ms: u64,
+    /// Writing deployed config instances to their file paths.
+    pub materialize_ms: u64,
+    /// Reporting deployment statuses back to the backend.
+    pub reconcile_ms: u64,
+}
+
+/// Times a phase of a sync, adding its duration to the phase when dropped so that
+/// phases which return early are still timed.
+pub struct PhaseTimer<'a> {
+    phase: &'a mut u64,
+    started_at: Instant,
+}
+
+impl<'a> PhaseTimer<'a> {
+    pub fn start(phase: &'a mut u64) -> Self {
+        Self {
+            phase,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl Drop for PhaseTimer<'_> {
+    fn drop(&mut self) {
+        *self.phase += millis(self.started_at.elapsed());
+    }
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// The longest error message kept in a record, in characters.
+pub const MAX_ERROR_LEN: usize = 512;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub outcome: Outcome,
+    pub phases: Phases,
+    /// Why the sync failed, cut to [MAX_ERROR_LEN] characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A failed sync's error message as kept in its record.
+pub fn error_summary(e: &impl std::fmt::Display) -> String {
+    let msg = e.to_string();
+    match msg.char_indices().nth(MAX_ERROR_LEN) {
+        Some((end, _)) => format!("{}...", &msg[..end]),
+        None => msg,
+    }
+}
+
+/// Filters for querying the history. Unset filters match every sync.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Query {
+    pub outcome: Option<Outcome>,
+    /// Only syncs started at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only the most recent matching syncs.
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    /// The matching syncs of those given oldest first, still oldest first.
+    pub fn apply(&self, records: &[Record]) -> Vec<Record> {
+        let matches: Vec<&Record> = records
+            .iter()
+            .filter(|r| self.outcome.is_none_or(|outcome| r.outcome == outcome))
+            .filter(|r| self.since.is_none_or(|since| r.started_at >= since))
+            .collect();
+        let skip = match self.limit {
+            Some(limit) => matches.len().saturating_sub(limit),
+            None => 0,
+        };
+        matches.into_iter().skip(skip).cloned().collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles of the values, or `None` if there are none.
+    pub fn of(mut values: Vec<u64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+        let rank = |p: usize| values[(values.len() * p).div_ceil(100).max(1) - 1];
+        Some(Self {
+            p50_ms: rank(50),
+            p95_ms: rank(95),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhasePercentiles {
+    pub fetch: Percentiles,
+    pub download: Percentiles,
+    pub materialize: Percentiles,
+    pub reconcile: Percentiles,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub syncs: usize,
+    pub successes: usize,
+    /// Consecutive successful syncs, counting back from the most recent.
+    pub success_streak: usize,
+    pub last_outcome: Option<Outcome>,
+    pub duration: Option<Percentiles>,
+    pub phases: Option<PhasePercentiles>,
+}
+
+/// Summarizes syncs given oldest first.
+pub fn summarize(records: &[Record]) -> Summary {
+    let of = |f: fn(&Record) -> u64| Percentiles::of(records.iter().map(f).collect());
+    let phases = match (
+        of(|r| r.phases.fetch_ms),
+        of(|r| r.phases.download_ms),
+        of(|r| r.phases.materialize_ms),
+        of(|r| r.phases.reconcile_ms),
+    ) {
+        (Some(fetch), Some(download), Some(materialize), Some(reconcile)) => {
+            Some(PhasePercentiles {
+                fetch,
+                download,
+                materialize,
+                reconcile,
+            })
+        }
+        _ => None,
+    };
+    Summary {
+        syncs: records.len(),
+        successes: records
+            .iter()
+            .filter(|r| r.outcome == Outcome::Success)
+            .count(),
+        success_streak: records
+            .iter()
+            .rev()
+            .take_while(|r| r.outcome == Outcome::Success)
+            .count(),
+        last_outcome: records.last().map(|r| r.outcome),
+        duration: of(|r| r.duration_ms),
+        phases,
+    }
+}
+
+#[derive(Debug)]
+pub struct History {
+    capacity: usize,
+    records: VecDeque<Record>,
+    file: Option<filesys::File>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
+}
+
+impl History {
+    pub fn new(options: Options) -> Self {
+        Self {
+            capacity: options.capacity.max(1),
+            records: VecDeque::new(),
+            file: options.file,
+        }
+    }
+
+    /// Creates a history with the syncs persisted to the options' file, if any. An
+    /// unreadable file starts an empty history rather than failing the agent.
+    pub async fn load(options: Options) -> Self {
+        let mut history = Self::new(options);
+        let Some(file) = &history.file else {
+            return history;
+        };
+        if !file.exists() {
+            return history;
+        }
+        match file.read_json::<Vec<Record>>().await {
+            Ok(records) => {
+                let overflow = records.len().saturating_sub(history.capacity);
+                history.records = records.into_iter().skip(overflow).collect();
+            }
+            Err(e) => warn!("discarding the unreadable sync history: {e}"),
+        }
+        history
+    }
+
+    /// The recorded syncs, oldest first.
+    pub fn records(&self) -> Vec<Record> {
+        self.records.iter().cloned().collect()
+    }
+
+    pub async fn push(&mut self, record: Record) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+
+        let Some(file) = &self.file else {
+            return;
+        };
+        if let Err(e) = file
+            .write_json(&self.records, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+        {
+            error!("failed to persist the sync history: {e}");
+        }
+    }
+}
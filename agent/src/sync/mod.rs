@@ -1,6 +1,16 @@
+pub mod backoff;
+pub mod changes;
 pub mod deployments;
 pub mod errors;
+pub mod event_log;
+pub mod history;
+pub mod network;
+pub mod patch;
+pub mod plan;
+pub mod state;
 pub mod syncer;
+pub mod trigger;
+pub mod warming;
 
 pub use self::errors::SyncErr;
 pub use self::syncer::{Syncer, SyncerExt};
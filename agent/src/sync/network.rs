@@ -0,0 +1,107 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::http;
+use crate::storage;
+#[cfg(feature = "telemetry")]
+use crate::telemetry::{network, Network};
+
+// external crates
+use chrono::TimeDelta;
+#[cfg(feature = "telemetry")]
+use chrono::{DateTime, Utc};
+use tracing::debug;
+#[cfg(feature = "telemetry")]
+use tracing::warn;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub enabled: bool,
+    /// How long an unchanged network goes unreported, so the traffic counters and
+    /// probe latency don't go stale.
+    pub max_age: TimeDelta,
+    /// How long the backend probe waits for a connection.
+    pub probe_timeout: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_age: TimeDelta::hours(1),
+            probe_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Reports the device's network interfaces and connectivity with each sync, but only
+/// when they changed or the last report is older than the max age.
+#[derive(Debug, Default)]
+pub struct Reporter {
+    options: Options,
+    #[cfg(feature = "telemetry")]
+    last: Option<(DateTime<Utc>, Network)>,
+}
+
+impl Reporter {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            #[cfg(feature = "telemetry")]
+            last: None,
+        }
+    }
+
+    /// Failures are only logged since the report doesn't affect the sync.
+    #[cfg(feature = "telemetry")]
+    pub async fn report<HTTPClientT: http::ClientI>(
+        &mut self,
+        http_client: &HTTPClientT,
+        device_stor: &storage::Device,
+        token: &str,
+    ) {
+        if !self.options.enabled {
+            return;
+        }
+        let network =
+            network::detect(Some(http_client.base_url()), self.options.probe_timeout).await;
+        let now = Utc::now();
+        if let Some((reported_at, last)) = &self.last {
+            if !network.changed_from(last) && now - *reported_at < self.options.max_age {
+                debug!("network unchanged since {reported_at}, not reporting it");
+                return;
+            }
+        }
+
+        let device = match device_stor.read().await {
+            Ok(device) => device,
+            Err(e) => {
+                warn!("Failed to read the device to report its network: {e}");
+                return;
+            }
+        };
+        let params = http::devices::UpdateNetworkParams {
+            id: &device.id,
+            network: &network,
+            token,
+        };
+        match http::devices::update_network(http_client, params).await {
+            Ok(()) => self.last = Some((now, network)),
+            Err(e) => warn!("Failed to report the device's network: {e}"),
+        }
+    }
+
+    /// The network can't be detected without the telemetry feature.
+    #[cfg(not(feature = "telemetry"))]
+    pub async fn report<HTTPClientT: http::ClientI>(
+        &mut self,
+        _: &HTTPClientT,
+        _: &storage::Device,
+        _: &str,
+    ) {
+        if self.options.enabled {
+            debug!("not reporting the network since the agent was built without telemetry");
+        }
+    }
+}
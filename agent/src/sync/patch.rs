@@ -0,0 +1,244 @@
+// A patch rebuilds a config instance's content from the cached content of an earlier
+// one, so what it produces has to be byte-for-byte what a full download would have
+// returned. Both sides agree on a canonical form of JSON documents for this: the
+// backend serves JSON content in canonical form and computes patch digests over it,
+// and the agent only patches a base which is itself canonical and re-serializes the
+// patched document canonically before verifying it.
+
+// internal crates
+use crate::crypt::digest::{Algorithm, Digest};
+use crate::http::config_instances::{ContentPatch, PatchFormat};
+use crate::sync::errors::*;
+use crate::trace;
+
+// external crates
+use serde_json::{Map, Value};
+
+/// Applies a content patch to the cached content of its base config instance and
+/// verifies the result against the patch's digest. The patched document is returned
+/// in canonical form, which is also what the digest is computed over.
+pub fn apply(base: &str, patch: &ContentPatch) -> Result<String, SyncErr> {
+    let patch_err = |msg: String| ContentPatchErr {
+        base_id: patch.base_id.clone(),
+        msg,
+        trace: trace!(),
+    };
+
+    let mut doc: Value = serde_json::from_str(base)
+        .map_err(|e| patch_err(format!("base content is not valid JSON: {e}")))?;
+    match patch.format {
+        PatchFormat::MergePatch => merge_patch(&mut doc, &patch.patch),
+        PatchFormat::JsonPatch => json_patch(&mut doc, &patch.patch).map_err(patch_err)?,
+    }
+    let content = canonical(&doc);
+
+    // the digest is computed with whichever algorithm the backend names
+    let expected: Digest = patch
+        .digest
+        .parse()
+        .map_err(|e| patch_err(format!("invalid digest: {e}")))?;
+    let actual = Digest::compute(expected.algorithm(), content.as_bytes());
+    if actual != expected {
+        return Err(ContentDigestMismatchErr {
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+            trace: trace!(),
+        }
+        .into());
+    }
+    Ok(content)
+}
+
+/// Serializes a JSON document in canonical form: compact, with the members of every
+/// object sorted by key.
+pub fn canonical(doc: &Value) -> String {
+    sort_keys(doc).to_string()
+}
+
+/// Whether content is a JSON document in canonical form, i.e. a base a patch
+/// reproduces a full download from.
+pub fn is_canonical(content: &str) -> bool {
+    serde_json::from_str::<Value>(content).is_ok_and(|doc| canonical(&doc) == content)
+}
+
+// rebuilt rather than relying on serde_json's map ordering, which follows insertion
+// order if any crate in the build enables its `preserve_order` feature
+fn sort_keys(doc: &Value) -> Value {
+    match doc {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), sort_keys(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.iter().map(sort_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Lowercase hex SHA-256 of the content.
+pub fn digest(content: &str) -> String {
+    Digest::compute(Algorithm::Sha256, content.as_bytes())
+        .hex()
+        .to_string()
+}
+
+// ================================= MERGE PATCH =================================== //
+/// Applies an RFC 7386 JSON Merge Patch.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+// ================================== JSON PATCH =================================== //
+/// Applies an RFC 6902 JSON Patch. Operations are applied in order and the first
+/// failing operation aborts the patch.
+fn json_patch(doc: &mut Value, ops: &Value) -> Result<(), String> {
+    let Value::Array(ops) = ops else {
+        return Err("a JSON patch must be an array of operations".to_string());
+    };
+    for (i, op) in ops.iter().enumerate() {
+        apply_op(doc, op).map_err(|e| format!("operation {i}: {e}"))?;
+    }
+    Ok(())
+}
+
+fn apply_op(doc: &mut Value, op: &Value) -> Result<(), String> {
+    let field = |name: &str| {
+        op.get(name)
+            .ok_or_else(|| format!("missing '{name}' member"))
+    };
+    let str_field = |name: &str| {
+        field(name)?
+            .as_str()
+            .ok_or_else(|| format!("'{name}' member must be a string"))
+    };
+
+    let path = parse_pointer(str_field("path")?)?;
+    match str_field("op")? {
+        "add" => add(doc, &path, field("value")?.clone()),
+        "remove" => remove(doc, &path).map(|_| ()),
+        "replace" => {
+            *lookup(doc, &path)? = field("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = parse_pointer(str_field("from")?)?;
+            if path.len() > from.len() && path.starts_with(&from) {
+                return Err("cannot move a value into one of its children".to_string());
+            }
+            let value = remove(doc, &from)?;
+            add(doc, &path, value)
+        }
+        "copy" => {
+            let from = parse_pointer(str_field("from")?)?;
+            let value = lookup(doc, &from)?.clone();
+            add(doc, &path, value)
+        }
+        "test" => {
+            if *lookup(doc, &path)? == *field("value")? {
+                Ok(())
+            } else {
+                Err(format!("test failed at '{}'", str_field("path")?))
+            }
+        }
+        other => Err(format!("unknown operation '{other}'")),
+    }
+}
+
+/// Splits an RFC 6901 JSON pointer into its unescaped reference tokens.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(format!("invalid JSON pointer '{pointer}'"));
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn array_index(token: &str, len: usize) -> Result<usize, String> {
+    match token.parse::<usize>() {
+        Ok(i) if i < len && (token == "0" || !token.starts_with('0')) => Ok(i),
+        _ => Err(format!("invalid array index '{token}'")),
+    }
+}
+
+fn lookup<'a>(doc: &'a mut Value, path: &[String]) -> Result<&'a mut Value, String> {
+    let mut cur = doc;
+    for token in path {
+        cur = match cur {
+            Value::Object(map) => map
+                .get_mut(token)
+                .ok_or_else(|| format!("path member '{token}' does not exist"))?,
+            Value::Array(arr) => {
+                let i = array_index(token, arr.len())?;
+                &mut arr[i]
+            }
+            _ => return Err(format!("cannot index into a scalar with '{token}'")),
+        };
+    }
+    Ok(cur)
+}
+
+fn add(doc: &mut Value, path: &[String], value: Value) -> Result<(), String> {
+    let Some((last, parent)) = path.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match lookup(doc, parent)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+            Ok(())
+        }
+        Value::Array(arr) if last == "-" => {
+            arr.push(value);
+            Ok(())
+        }
+        Value::Array(arr) => {
+            // inserting at the end of an array is allowed
+            let i = array_index(last, arr.len() + 1)?;
+            arr.insert(i, value);
+            Ok(())
+        }
+        _ => Err(format!("cannot add '{last}' to a scalar")),
+    }
+}
+
+fn remove(doc: &mut Value, path: &[String]) -> Result<Value, String> {
+    let Some((last, parent)) = path.split_last() else {
+        return Err("cannot remove the whole document".to_string());
+    };
+    match lookup(doc, parent)? {
+        Value::Object(map) => map
+            .remove(last)
+            .ok_or_else(|| format!("path member '{last}' does not exist")),
+        Value::Array(arr) => {
+            let i = array_index(last, arr.len())?;
+            Ok(arr.remove(i))
+        }
+        _ => Err(format!("cannot remove '{last}' from a scalar")),
+    }
+}
@@ -0,0 +1,161 @@
+// Reports what the next sync would do without doing it. The backend's deployments are
+// merged with the cached ones the same way a sync would merge them but nothing is
+// written, so an operator can check what a sync is about to deploy or remove before
+// letting it happen.
+
+// standard crates
+use std::collections::HashSet;
+
+// internal crates
+use crate::deploy::{apply, fsm};
+use crate::http;
+use crate::models::{self, DplActivity, DplErrStatus, DplTarget};
+use crate::storage;
+use crate::sync::deployments;
+use crate::sync::errors::*;
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    None,
+    Deploy,
+    Remove,
+    Archive,
+    Wait,
+}
+
+impl From<fsm::NextAction> for Action {
+    fn from(action: fsm::NextAction) -> Self {
+        match action {
+            fsm::NextAction::None => Self::None,
+            fsm::NextAction::Deploy => Self::Deploy,
+            fsm::NextAction::Remove => Self::Remove,
+            fsm::NextAction::Archive => Self::Archive,
+            fsm::NextAction::Wait(_) => Self::Wait,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Step {
+    pub deployment_id: models::DeploymentID,
+    pub target_status: DplTarget,
+    pub activity_status: DplActivity,
+    pub error_status: DplErrStatus,
+    pub action: Action,
+    /// Seconds until the deployment's cooldown ends if the action is `wait`; zero if
+    /// it's waiting for deployments to be resumed.
+    pub wait_secs: Option<i64>,
+    /// Whether the deployment isn't cached yet.
+    pub new: bool,
+    /// Whether the backend listed the deployment as active. Cached deployments it no
+    /// longer lists are applied from their cached state.
+    pub listed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    pub planned_at: DateTime<Utc>,
+    /// In safe mode deployments are pulled but none are applied.
+    pub safe_mode: bool,
+    /// While deployments are paused every deployment which would transition waits.
+    pub paused: bool,
+    /// The deployments targeting deployed at the same time. Applying fails without
+    /// taking any action if there is more than one.
+    pub conflicts: Vec<models::DeploymentID>,
+    /// A step for every known deployment, ordered by deployment id.
+    pub steps: Vec<Step>,
+}
+
+/// Plans the next sync from the backend's active deployments and the cached ones.
+pub async fn plan<HTTPClientT: http::ClientI>(
+    http_client: &HTTPClientT,
+    storage: &storage::Deployments,
+    opts: &apply::DeployOpts,
+    token: &str,
+) -> Result<Plan, SyncErr> {
+    let active_deployments = deployments::fetch_active_deployments(http_client, token).await?;
+    let paused = match &opts.pause {
+        Some(switch) => switch.is_paused().await,
+        None => false,
+    };
+
+    let mut steps = Vec::new();
+    let mut listed_ids = HashSet::new();
+    for listed in active_deployments {
+        let backend_dpl = listed.deployment;
+        let cfg_inst_ids = backend_dpl
+            .config_instances
+            .as_ref()
+            .ok_or_else(|| {
+                SyncErr::CfgInstsNotExpanded(CfgInstsNotExpandedErr {
+                    deployment_id: backend_dpl.id.clone(),
+                })
+            })?
+            .iter()
+            .map(|inst| inst.id.clone())
+            .collect();
+        let new_dpl = models::Deployment {
+            dependencies: listed.dependencies,
+            ..models::Deployment::from_backend(backend_dpl, cfg_inst_ids)
+        };
+        let cached = storage.read_optional(new_dpl.id.clone()).await?;
+        let is_new = cached.is_none();
+        let deployment = deployments::resolve_dpl(new_dpl, cached);
+        listed_ids.insert(deployment.id.clone());
+        steps.push((step(&deployment, is_new, true, paused), deployment));
+    }
+
+    for cached in storage.values().await? {
+        if listed_ids.contains(&cached.id) {
+            continue;
+        }
+        steps.push((step(&cached, false, false, paused), cached));
+    }
+    steps.sort_by(|a, b| a.0.deployment_id.cmp(&b.0.deployment_id));
+
+    let conflicts = steps
+        .iter()
+        .filter(|(_, dpl)| {
+            dpl.target_status == DplTarget::Deployed && dpl.error_status != DplErrStatus::Failed
+        })
+        .map(|(step, _)| step.deployment_id.clone())
+        .collect::<Vec<_>>();
+
+    Ok(Plan {
+        planned_at: Utc::now(),
+        safe_mode: opts.safe_mode,
+        paused,
+        conflicts: if conflicts.len() > 1 {
+            conflicts
+        } else {
+            Vec::new()
+        },
+        steps: steps.into_iter().map(|(step, _)| step).collect(),
+    })
+}
+
+fn step(deployment: &models::Deployment, new: bool, listed: bool, paused: bool) -> Step {
+    let next = if paused {
+        fsm::next_action_paused(deployment)
+    } else {
+        fsm::next_action(deployment)
+    };
+    Step {
+        deployment_id: deployment.id.clone(),
+        target_status: deployment.target_status,
+        activity_status: deployment.activity_status,
+        error_status: deployment.error_status,
+        action: next.into(),
+        wait_secs: match next {
+            fsm::NextAction::Wait(wait) => Some(wait.num_seconds().max(0)),
+            _ => None,
+        },
+        new,
+        listed,
+    }
+}
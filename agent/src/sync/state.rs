@@ -0,0 +1,67 @@
+// The syncer's state (error streak, last sync and cooldown) is persisted so that an
+// agent which crashes and restarts keeps backing off instead of syncing afresh each
+// time it starts, which a crash-looping agent would otherwise do every few seconds.
+
+// internal crates
+use crate::filesys::{self, cached_file::SingleThreadCachedFile, errors::FileSysErr};
+use crate::models::Patch;
+use crate::sync::syncer::State;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use tracing::{info, warn};
+
+pub type StateFile = SingleThreadCachedFile<State, Updates>;
+
+pub struct Updates {
+    pub last_attempted_sync_at: Option<DateTime<Utc>>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub cooldown_ends_at: Option<DateTime<Utc>>,
+    pub err_streak: Option<u32>,
+}
+
+impl Patch<Updates> for State {
+    fn patch(&mut self, patch: Updates) {
+        if let Some(last_attempted_sync_at) = patch.last_attempted_sync_at {
+            self.last_attempted_sync_at = last_attempted_sync_at;
+        }
+        if let Some(last_synced_at) = patch.last_synced_at {
+            self.last_synced_at = last_synced_at;
+        }
+        if let Some(cooldown_ends_at) = patch.cooldown_ends_at {
+            self.cooldown_ends_at = cooldown_ends_at;
+        }
+        if let Some(err_streak) = patch.err_streak {
+            self.err_streak = err_streak;
+        }
+    }
+}
+
+/// Opens the persisted state, starting from the default state if the file is missing
+/// or unreadable.
+pub async fn open(file: filesys::File) -> Result<StateFile, FileSysErr> {
+    StateFile::new_with_default(file, State::default()).await
+}
+
+/// The state to resume from. A cooldown which has already ended is dropped and one
+/// ending further away than `max_cooldown` (the longest backoff, e.g. if the clock
+/// jumped back since it was persisted) is shortened to it.
+pub fn restore(state: &State, now: DateTime<Utc>, max_cooldown: TimeDelta) -> State {
+    let mut state = state.clone();
+    if state.cooldown_ends_at <= now {
+        state.cooldown_ends_at = DateTime::<Utc>::UNIX_EPOCH;
+    } else if state.cooldown_ends_at > now + max_cooldown {
+        warn!(
+            "shortening the persisted sync cooldown ending at {} to {max_cooldown}",
+            state.cooldown_ends_at
+        );
+        state.cooldown_ends_at = now + max_cooldown;
+    }
+    if state.cooldown_ends_at > now {
+        info!(
+            "resuming the sync cooldown until {} (err streak: {})",
+            state.cooldown_ends_at, state.err_streak
+        );
+    }
+    state
+}
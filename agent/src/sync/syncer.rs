@@ -1,20 +1,27 @@
 // standard crates
-use std::sync::Arc;
+use std::ops::BitOr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 // internal crates
 use crate::authn::{self, TokenManagerExt};
+use crate::clock;
 use crate::cooldown;
 use crate::deploy::apply;
 use crate::errors::*;
 use crate::events;
 use crate::http;
+use crate::metrics;
 use crate::storage;
-use crate::sync::{deployments, errors::*};
+use crate::sync::{
+    backoff, deployments, errors::*, event_log, history, network, plan, state, warming,
+};
 use crate::trace;
 
 // external crates
 use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, oneshot, watch};
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info};
@@ -29,25 +36,92 @@ macro_rules! dispatch {
 }
 
 // =============================== SYNCER EVENTS ================================== //
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SyncFailure {
     pub is_network_conn_err: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CooldownEnd {
     SyncSuccess,
     SyncFailure,
     DeploymentWait,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SyncEvent {
     SyncSuccess,
     SyncFailed(SyncFailure),
     CooldownEnd(CooldownEnd),
 }
 
+/// A set of [SyncEvent] kinds a subscriber is interested in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventMask(u8);
+
+impl EventMask {
+    pub const NONE: Self = Self(0);
+    pub const SYNC_SUCCESS: Self = Self(1);
+    pub const SYNC_FAILED: Self = Self(1 << 1);
+    pub const COOLDOWN_END: Self = Self(1 << 2);
+    pub const ALL: Self = Self(Self::SYNC_SUCCESS.0 | Self::SYNC_FAILED.0 | Self::COOLDOWN_END.0);
+
+    pub fn matches(&self, event: &SyncEvent) -> bool {
+        let kind = match event {
+            SyncEvent::SyncSuccess => Self::SYNC_SUCCESS,
+            SyncEvent::SyncFailed(_) => Self::SYNC_FAILED,
+            SyncEvent::CooldownEnd(_) => Self::COOLDOWN_END,
+        };
+        self.0 & kind.0 != 0
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A subscription to the syncer's events which only yields the events in its mask.
+/// Like the underlying watch channel, the latest event is delivered first if one was
+/// sent before subscribing and events sent in quick succession may be coalesced so
+/// only the latest is observed.
+pub struct Subscription {
+    rx: watch::Receiver<SyncEvent>,
+    mask: EventMask,
+}
+
+impl Subscription {
+    pub fn new(rx: watch::Receiver<SyncEvent>, mask: EventMask) -> Self {
+        Self { rx, mask }
+    }
+
+    /// Waits for the next event matching the mask. Returns `None` once the syncer has
+    /// shut down.
+    pub async fn recv(&mut self) -> Option<SyncEvent> {
+        loop {
+            self.rx.changed().await.ok()?;
+            let event = self.rx.borrow_and_update().clone();
+            if self.mask.matches(&event) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// The most recent event sent on the channel, if any has been sent. The receiver must
+/// never have been marked as seen.
+fn last_event(rx: &watch::Receiver<SyncEvent>) -> Option<SyncEvent> {
+    match rx.has_changed() {
+        Ok(true) => Some(rx.borrow().clone()),
+        _ => None,
+    }
+}
+
 // ======================== SINGLE-THREADED IMPLEMENTATION ========================= //
 pub struct SyncerArgs<HTTPClientT, TokenManagerT: TokenManagerExt> {
     pub storage: Arc<storage::Storage>,
@@ -58,12 +132,16 @@ pub struct SyncerArgs<HTTPClientT, TokenManagerT: TokenManagerExt> {
     pub event_hub: events::EventHub,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct State {
     pub last_attempted_sync_at: DateTime<Utc>,
     pub last_synced_at: DateTime<Utc>,
     pub cooldown_ends_at: DateTime<Utc>,
     pub err_streak: u32,
+    /// The syncs completed since the agent started. Not persisted, so a sync restored
+    /// from the state file never counts.
+    #[serde(skip)]
+    pub syncs: u32,
 }
 
 impl Default for State {
@@ -73,13 +151,14 @@ impl Default for State {
             last_synced_at: DateTime::<Utc>::UNIX_EPOCH,
             cooldown_ends_at: DateTime::<Utc>::UNIX_EPOCH,
             err_streak: 0,
+            syncs: 0,
         }
     }
 }
 
 impl State {
     pub fn is_in_cooldown(&self) -> bool {
-        Utc::now() < self.cooldown_ends_at
+        clock::now() < self.cooldown_ends_at
     }
 }
 
@@ -96,7 +175,15 @@ pub struct SingleThreadSyncer<HTTPClientT> {
 
     // syncer state
     backoff: cooldown::Backoff,
+    backoff_policies: backoff::Policies,
+    failure_streak: backoff::Streak,
     state: State,
+    state_file: Option<state::StateFile>,
+    history: history::History,
+    events: event_log::Log,
+    warming: warming::Warming,
+    list_validators: Mutex<http::conditional::Validators>,
+    network: network::Reporter,
 }
 
 impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
@@ -108,8 +195,16 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
             token_mngr: args.token_mngr,
             deploy_opts: args.deploy_opts,
             backoff: args.backoff,
+            backoff_policies: backoff::Policies::default(),
+            failure_streak: backoff::Streak::default(),
             event_hub: args.event_hub,
             state: State::default(),
+            state_file: None,
+            history: history::History::default(),
+            events: event_log::Log::default(),
+            warming: warming::Warming::default(),
+            list_validators: Mutex::default(),
+            network: network::Reporter::default(),
             subscriber_tx,
             subscriber_rx,
         }
@@ -119,6 +214,11 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
         Ok(self.subscriber_rx.clone())
     }
 
+    fn last_event(&self) -> Result<Option<SyncEvent>, SyncErr> {
+        // the syncer's own receiver is never marked as seen
+        Ok(last_event(&self.subscriber_rx))
+    }
+
     fn schedule_cooldown_end_notification(&self, wait: TimeDelta, source: CooldownEnd) {
         if wait <= TimeDelta::zero() {
             return;
@@ -127,8 +227,10 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
         // cleared when sending the cooldown end event.
         let cooldown_secs = (wait.num_seconds().max(0) + 1) as u64;
         let tx = self.subscriber_tx.clone();
+        let events = self.events.clone();
         tokio::spawn(async move {
             tokio::time::sleep(Duration::from_secs(cooldown_secs)).await;
+            events.push(SyncEvent::CooldownEnd(source.clone())).await;
             if let Err(e) = tx.send(SyncEvent::CooldownEnd(source)) {
                 error!("failed to send cooldown ended event: {:?}", e);
             }
@@ -144,6 +246,73 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
         self.state = state;
     }
 
+    /// Resumes from the state persisted to the file and persists the state to it from
+    /// now on.
+    fn set_state_file(&mut self, file: state::StateFile) {
+        let max_secs = self
+            .backoff_policies
+            .max_secs()
+            .map_or(self.backoff.max_secs, |secs| {
+                secs.max(self.backoff.max_secs)
+            });
+        let now = clock::now();
+        self.state = state::restore(&file.cached(), now, TimeDelta::seconds(max_secs));
+        self.state_file = Some(file);
+        let source = match self.state.err_streak {
+            0 => CooldownEnd::SyncSuccess,
+            _ => CooldownEnd::SyncFailure,
+        };
+        self.schedule_cooldown_end_notification(self.state.cooldown_ends_at - now, source);
+    }
+
+    async fn persist_state(&mut self) {
+        let Some(file) = self.state_file.as_mut() else {
+            return;
+        };
+        if let Err(e) = file.write(self.state.clone()).await {
+            error!("failed to persist the sync state: {e}");
+        }
+    }
+
+    fn get_sync_history(&self) -> Result<Vec<history::Record>, SyncErr> {
+        Ok(self.history.records())
+    }
+
+    fn set_sync_history(&mut self, history: history::History) {
+        self.history = history;
+    }
+
+    async fn get_sync_events(&self) -> Result<Vec<event_log::Record>, SyncErr> {
+        Ok(self.events.records().await)
+    }
+
+    fn set_event_log(&mut self, events: event_log::Log) {
+        self.events = events;
+    }
+
+    fn set_content_warming(&mut self, warming: warming::Warming) {
+        self.warming = warming;
+    }
+
+    fn set_backoff_policies(&mut self, policies: backoff::Policies) {
+        self.backoff_policies = policies;
+    }
+
+    fn set_network_report(&mut self, options: network::Options) {
+        self.network = network::Reporter::new(options);
+    }
+
+    async fn plan(&self) -> Result<plan::Plan, SyncErr> {
+        let token = self.token_mngr.get_token().await?;
+        plan::plan(
+            self.http_client.as_ref(),
+            self.storage.deployments.as_ref(),
+            &self.deploy_opts,
+            &token.token,
+        )
+        .await
+    }
+
     async fn sync_if_not_in_cooldown(&mut self) -> Result<(), SyncErr> {
         if self.state.is_in_cooldown() {
             info!("skipping device sync since the cooldown ends at {:?} (err streak: {}, last successful sync at: {:?})",
@@ -165,15 +334,32 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
             }));
         }
 
-        self.state.last_attempted_sync_at = Utc::now();
-        let result = self.sync_impl().await;
+        self.state.last_attempted_sync_at = clock::now();
+        self.persist_state().await;
+        let started_at = std::time::Instant::now();
+        let mut phases = history::Phases::default();
+        let result = self.sync_impl(&mut phases).await;
+        self.history
+            .push(history::Record {
+                started_at: self.state.last_attempted_sync_at,
+                duration_ms: history::millis(started_at.elapsed()),
+                outcome: match &result {
+                    Ok(_) => history::Outcome::Success,
+                    Err(e) if e.is_network_conn_err() => history::Outcome::NetworkError,
+                    Err(_) => history::Outcome::Error,
+                },
+                phases,
+                error: result.as_ref().err().map(history::error_summary),
+            })
+            .await;
 
         // determine the syncer's own cooldown period
         let (event, sync_wait) = match &result {
-            Ok(_) => (CooldownEnd::SyncSuccess, self.handle_sync_success()),
-            Err(e) => (CooldownEnd::SyncFailure, self.handle_sync_failure(e)),
+            Ok(_) => (CooldownEnd::SyncSuccess, self.handle_sync_success().await),
+            Err(e) => (CooldownEnd::SyncFailure, self.handle_sync_failure(e).await),
         };
-        self.state.cooldown_ends_at = Utc::now() + sync_wait;
+        self.state.cooldown_ends_at = clock::now() + sync_wait;
+        self.persist_state().await;
         self.schedule_cooldown_end_notification(sync_wait, event);
         debug!(
             "backend syncer cooling down for {sync_wait} (until {:?})",
@@ -188,7 +374,9 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
         result.map(|_| ())
     }
 
-    fn handle_sync_success(&mut self) -> TimeDelta {
+    async fn handle_sync_success(&mut self) -> TimeDelta {
+        metrics::global().record_sync_success();
+        self.events.push(SyncEvent::SyncSuccess).await;
         if let Err(e) = self.subscriber_tx.send(SyncEvent::SyncSuccess) {
             error!("failed to send sync success event: {:?}", e);
         }
@@ -200,15 +388,20 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
         } else {
             info!("successfully synced with backend");
         }
-        self.state.last_synced_at = Utc::now();
+        self.state.last_synced_at = clock::now();
+        self.state.syncs = self.state.syncs.saturating_add(1);
         self.state.err_streak = 0;
+        self.failure_streak.reset();
         TimeDelta::seconds(self.backoff.base_secs)
     }
 
-    fn handle_sync_failure(&mut self, e: &SyncErr) -> TimeDelta {
-        if let Err(e) = self.subscriber_tx.send(SyncEvent::SyncFailed(SyncFailure {
+    async fn handle_sync_failure(&mut self, e: &SyncErr) -> TimeDelta {
+        metrics::global().record_sync_failure(e.is_network_conn_err());
+        let event = SyncEvent::SyncFailed(SyncFailure {
             is_network_conn_err: e.is_network_conn_err(),
-        })) {
+        });
+        self.events.push(event.clone()).await;
+        if let Err(e) = self.subscriber_tx.send(event) {
             error!("failed to send sync failed event: {:?}", e);
         }
         // network connection errors are expected to happen and do not count
@@ -216,21 +409,44 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
         // network connection errors even if the previous errors were not
         // network connection errors so we use an error streak of 0 when
         // calculating the cooldown period
-        if e.is_network_conn_err() {
+        let class = backoff::FailureClass::of(e);
+        let class_streak = self.failure_streak.record(class);
+        // offline devices fail every sync, so only the first of consecutive network
+        // failures is published
+        if class != backoff::FailureClass::Network || class_streak == 1 {
+            match events::EventArgs::sync_failed(class.as_str(), class_streak, &e.to_string()) {
+                Ok(event) => self.event_hub.try_publish(event).await,
+                Err(e) => error!("failed to build sync failed event: {e}"),
+            }
+        }
+        if class == backoff::FailureClass::Network {
             debug!(
                 "unable to sync with backend due to a network connection error: {:?}",
                 e
             );
-            TimeDelta::seconds(self.backoff.base_secs)
         } else {
-            error!("unable to sync with backend: {:?}", e);
+            error!("unable to sync with backend ({class:?} failure): {:?}", e);
             self.state.err_streak += 1;
-            TimeDelta::seconds(cooldown::calc(&self.backoff, self.state.err_streak))
         }
+        let secs = match (self.backoff_policies.get(class), class) {
+            (Some(policy), _) => cooldown::calc(policy, class_streak - 1),
+            (None, backoff::FailureClass::Network) => self.backoff.base_secs,
+            (None, _) => cooldown::calc(&self.backoff, self.state.err_streak),
+        };
+        TimeDelta::seconds(secs)
     }
 
-    async fn sync_impl(&mut self) -> Result<Option<chrono::TimeDelta>, SyncErr> {
+    async fn sync_impl(
+        &mut self,
+        phases: &mut history::Phases,
+    ) -> Result<Option<chrono::TimeDelta>, SyncErr> {
         let token = self.token_mngr.get_token().await?;
+        let audit_anchor = self
+            .storage
+            .config_access
+            .anchor()
+            .await
+            .map(|anchor| anchor.to_string());
 
         let storage_ref = self.storage.as_ref();
         let sync_storage = deployments::Storage {
@@ -238,15 +454,34 @@ impl<HTTPClientT: http::ClientI> SingleThreadSyncer<HTTPClientT> {
             cfg_insts: storage_ref.cfg_insts.as_ref(),
             releases: storage_ref.releases.as_ref(),
             git_commits: storage_ref.git_commits.as_ref(),
+            journal: storage_ref.journal.as_ref(),
+            locks: storage_ref.locks.as_ref(),
         };
-        deployments::sync(&deployments::SyncArgs {
-            http_client: self.http_client.as_ref(),
-            storage: &sync_storage,
-            opts: &self.deploy_opts,
-            token: &token.token,
-            event_hub: &self.event_hub,
-        })
-        .await
+        let result = deployments::sync(
+            &deployments::SyncArgs {
+                http_client: self.http_client.as_ref(),
+                storage: &sync_storage,
+                opts: &self.deploy_opts,
+                token: &token.token,
+                event_hub: &self.event_hub,
+                warming: &self.warming,
+                list_validators: &self.list_validators,
+                audit_anchor: audit_anchor.as_deref(),
+            },
+            phases,
+        )
+        .await;
+
+        // reported whether or not the sync succeeded since the network is most
+        // interesting when it's flaky
+        self.network
+            .report(
+                self.http_client.as_ref(),
+                &self.storage.device,
+                &token.token,
+            )
+            .await;
+        result
     }
 }
 
@@ -261,6 +496,24 @@ pub trait SyncerExt {
     async fn sync(&self) -> Result<(), SyncErr>;
     async fn sync_if_not_in_cooldown(&self) -> Result<(), SyncErr>;
     async fn subscribe(&self) -> Result<watch::Receiver<SyncEvent>, SyncErr>;
+    /// The most recent event the syncer has sent, so late subscribers can initialize
+    /// their state without waiting for the next event. `None` if no event has been
+    /// sent yet.
+    async fn last_event(&self) -> Result<Option<SyncEvent>, SyncErr>;
+    /// The most recent syncs, oldest first.
+    async fn get_sync_history(&self) -> Result<Vec<history::Record>, SyncErr>;
+    /// The most recent events with the time each was sent, oldest first. Unlike a
+    /// subscription, no event is coalesced away.
+    async fn get_sync_events(&self) -> Result<Vec<event_log::Record>, SyncErr>;
+    /// What the next sync would do, without syncing or touching the caches.
+    async fn plan(&self) -> Result<plan::Plan, SyncErr>;
+    /// Whether a sync requested through this handle hasn't completed yet. Answered
+    /// without the actor, which doesn't serve other commands while it syncs.
+    fn is_syncing(&self) -> bool;
+
+    async fn subscribe_filtered(&self, mask: EventMask) -> Result<Subscription, SyncErr> {
+        Ok(Subscription::new(self.subscribe().await?, mask))
+    }
 }
 
 pub enum Command {
@@ -284,6 +537,42 @@ pub enum Command {
     Subscribe {
         respond_to: oneshot::Sender<Result<watch::Receiver<SyncEvent>, SyncErr>>,
     },
+    GetLastEvent {
+        respond_to: oneshot::Sender<Result<Option<SyncEvent>, SyncErr>>,
+    },
+    GetSyncHistory {
+        respond_to: oneshot::Sender<Result<Vec<history::Record>, SyncErr>>,
+    },
+    GetSyncEvents {
+        respond_to: oneshot::Sender<Result<Vec<event_log::Record>, SyncErr>>,
+    },
+    Plan {
+        respond_to: oneshot::Sender<Result<plan::Plan, SyncErr>>,
+    },
+    SetStateFile {
+        file: state::StateFile,
+        respond_to: oneshot::Sender<Result<(), SyncErr>>,
+    },
+    SetSyncHistory {
+        history: history::History,
+        respond_to: oneshot::Sender<Result<(), SyncErr>>,
+    },
+    SetEventLog {
+        events: event_log::Log,
+        respond_to: oneshot::Sender<Result<(), SyncErr>>,
+    },
+    SetContentWarming {
+        warming: warming::Warming,
+        respond_to: oneshot::Sender<Result<(), SyncErr>>,
+    },
+    SetBackoffPolicies {
+        policies: backoff::Policies,
+        respond_to: oneshot::Sender<Result<(), SyncErr>>,
+    },
+    SetNetworkReport {
+        options: network::Options,
+        respond_to: oneshot::Sender<Result<(), SyncErr>>,
+    },
 }
 
 pub struct Worker<HTTPClientT: Send> {
@@ -342,6 +631,85 @@ impl<HTTPClientT: http::ClientI> Worker<HTTPClientT> {
                         "Actor failed to send subscribe response"
                     );
                 }
+                Command::GetLastEvent { respond_to } => {
+                    dispatch!(
+                        self.syncer.last_event(),
+                        respond_to,
+                        "Actor failed to send last event response"
+                    );
+                }
+                Command::GetSyncHistory { respond_to } => {
+                    dispatch!(
+                        self.syncer.get_sync_history(),
+                        respond_to,
+                        "Actor failed to send sync history response"
+                    );
+                }
+                Command::GetSyncEvents { respond_to } => {
+                    dispatch!(
+                        self.syncer.get_sync_events().await,
+                        respond_to,
+                        "Actor failed to send sync events response"
+                    );
+                }
+                Command::Plan { respond_to } => {
+                    dispatch!(
+                        self.syncer.plan().await,
+                        respond_to,
+                        "Actor failed to send plan response"
+                    );
+                }
+                Command::SetStateFile { file, respond_to } => {
+                    self.syncer.set_state_file(file);
+                    if let Err(e) = respond_to.send(Ok(())) {
+                        error!("Actor failed to send set state file response: {:?}", e);
+                    }
+                }
+                Command::SetSyncHistory {
+                    history,
+                    respond_to,
+                } => {
+                    self.syncer.set_sync_history(history);
+                    if let Err(e) = respond_to.send(Ok(())) {
+                        error!("Actor failed to send set sync history response: {:?}", e);
+                    }
+                }
+                Command::SetEventLog { events, respond_to } => {
+                    self.syncer.set_event_log(events);
+                    if let Err(e) = respond_to.send(Ok(())) {
+                        error!("Actor failed to send set event log response: {:?}", e);
+                    }
+                }
+                Command::SetContentWarming {
+                    warming,
+                    respond_to,
+                } => {
+                    self.syncer.set_content_warming(warming);
+                    if let Err(e) = respond_to.send(Ok(())) {
+                        error!("Actor failed to send set content warming response: {:?}", e);
+                    }
+                }
+                Command::SetBackoffPolicies {
+                    policies,
+                    respond_to,
+                } => {
+                    self.syncer.set_backoff_policies(policies);
+                    if let Err(e) = respond_to.send(Ok(())) {
+                        error!(
+                            "Actor failed to send set backoff policies response: {:?}",
+                            e
+                        );
+                    }
+                }
+                Command::SetNetworkReport {
+                    options,
+                    respond_to,
+                } => {
+                    self.syncer.set_network_report(options);
+                    if let Err(e) = respond_to.send(Ok(())) {
+                        error!("Actor failed to send set network report response: {:?}", e);
+                    }
+                }
             }
         }
     }
@@ -350,6 +718,24 @@ impl<HTTPClientT: http::ClientI> Worker<HTTPClientT> {
 #[derive(Debug)]
 pub struct Syncer {
     sender: mpsc::Sender<Command>,
+    syncs_in_flight: Arc<AtomicUsize>,
+}
+
+/// Counts a sync as in flight until dropped, so a cancelled request isn't counted
+/// forever.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl Syncer {
@@ -362,12 +748,18 @@ impl Syncer {
             syncer: SingleThreadSyncer::new(args),
             receiver,
         };
-        let worker_handle = tokio::spawn(worker.run());
-        Ok((Self { sender }, worker_handle))
+        let worker_handle = tokio::spawn(metrics::workers::instrument(
+            metrics::workers::Worker::Syncer,
+            worker.run(),
+        ));
+        Ok((Self::new(sender), worker_handle))
     }
 
     pub fn new(sender: mpsc::Sender<Command>) -> Self {
-        Self { sender }
+        Self {
+            sender,
+            syncs_in_flight: Arc::new(AtomicUsize::new(0)),
+        }
     }
 
     async fn send_command<R>(
@@ -397,6 +789,63 @@ impl Syncer {
         })
         .await?
     }
+
+    /// Resumes from the state persisted to the file at startup and keeps persisting
+    /// the state to it. Set after the backoff policies, which bound the cooldown
+    /// resumed.
+    pub async fn set_state_file(&self, file: state::StateFile) -> Result<(), SyncErr> {
+        self.send_command(|tx| Command::SetStateFile {
+            file,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Replaces the sync history, e.g. with one loaded from disk at startup.
+    pub async fn set_sync_history(&self, history: history::History) -> Result<(), SyncErr> {
+        self.send_command(|tx| Command::SetSyncHistory {
+            history,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Replaces the event log, e.g. with one loaded from disk at startup.
+    pub async fn set_event_log(&self, events: event_log::Log) -> Result<(), SyncErr> {
+        self.send_command(|tx| Command::SetEventLog {
+            events,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Replaces the schedule which spreads content downloads while the cache warms.
+    pub async fn set_content_warming(&self, warming: warming::Warming) -> Result<(), SyncErr> {
+        self.send_command(|tx| Command::SetContentWarming {
+            warming,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Replaces the backoff policies applied to failed syncs from the next failure
+    /// on.
+    pub async fn set_backoff_policies(&self, policies: backoff::Policies) -> Result<(), SyncErr> {
+        self.send_command(|tx| Command::SetBackoffPolicies {
+            policies,
+            respond_to: tx,
+        })
+        .await?
+    }
+
+    /// Replaces the options of the network report sent with each sync.
+    pub async fn set_network_report(&self, options: network::Options) -> Result<(), SyncErr> {
+        self.send_command(|tx| Command::SetNetworkReport {
+            options,
+            respond_to: tx,
+        })
+        .await?
+    }
 }
 
 impl SyncerExt for Syncer {
@@ -428,17 +877,43 @@ impl SyncerExt for Syncer {
     }
 
     async fn sync_if_not_in_cooldown(&self) -> Result<(), SyncErr> {
+        let _in_flight = InFlight::new(&self.syncs_in_flight);
         self.send_command(|tx| Command::SyncIfNotInCooldown { respond_to: tx })
             .await?
     }
 
     async fn sync(&self) -> Result<(), SyncErr> {
+        let _in_flight = InFlight::new(&self.syncs_in_flight);
         self.send_command(|tx| Command::Sync { respond_to: tx })
             .await?
     }
 
+    fn is_syncing(&self) -> bool {
+        self.syncs_in_flight.load(Ordering::SeqCst) > 0
+    }
+
     async fn subscribe(&self) -> Result<watch::Receiver<SyncEvent>, SyncErr> {
         self.send_command(|tx| Command::Subscribe { respond_to: tx })
             .await?
     }
+
+    async fn last_event(&self) -> Result<Option<SyncEvent>, SyncErr> {
+        self.send_command(|tx| Command::GetLastEvent { respond_to: tx })
+            .await?
+    }
+
+    async fn get_sync_history(&self) -> Result<Vec<history::Record>, SyncErr> {
+        self.send_command(|tx| Command::GetSyncHistory { respond_to: tx })
+            .await?
+    }
+
+    async fn get_sync_events(&self) -> Result<Vec<event_log::Record>, SyncErr> {
+        self.send_command(|tx| Command::GetSyncEvents { respond_to: tx })
+            .await?
+    }
+
+    async fn plan(&self) -> Result<plan::Plan, SyncErr> {
+        self.send_command(|tx| Command::Plan { respond_to: tx })
+            .await?
+    }
 }
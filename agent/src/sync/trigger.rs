@@ -0,0 +1,17 @@
+// internal crates
+use crate::sync::SyncerExt;
+use backend_api::models::SyncDevice;
+
+// external crates
+use tracing::error;
+
+/// Handles a sync request from the backend, whether it was published over MQTT or
+/// answered a long poll. The device syncs unless the backend reports it's synced.
+pub async fn handle_request<SyncerT: SyncerExt>(request: &SyncDevice, syncer: &SyncerT) {
+    if request.is_synced {
+        return;
+    }
+    if let Err(e) = syncer.sync_if_not_in_cooldown().await {
+        error!("error syncing device: {e:?}");
+    }
+}
@@ -0,0 +1,207 @@
+// After a fresh install, or once corrupt content has been quarantined, the content of
+// every deployment is missing and the first sync would download all of it at once,
+// which can saturate a slow link. While the content cache warms, content which isn't
+// needed yet (that of deployments not targeted Deployed) is instead spread evenly
+// over a period, a few entries per sync. Content of deployments targeted Deployed is
+// always downloaded first and never deferred. The progress is persisted so that a
+// restart resumes the schedule rather than starting over.
+
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub enabled: bool,
+    /// How long the deferred downloads are spread over.
+    pub period: Duration,
+    /// The number of missing entries at which the cache counts as cold.
+    pub cold_threshold: usize,
+    /// Persists the progress to this file so warming resumes after a restart.
+    pub file: Option<filesys::File>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            period: Duration::from_secs(60 * 60),
+            cold_threshold: 10,
+            file: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    pub started_at: DateTime<Utc>,
+    /// The entries to download, which grows if deployments are added while warming.
+    pub total: usize,
+    pub downloaded: usize,
+}
+
+impl Progress {
+    /// The number of downloads due by `now`. Download `i` is due `i / total` of the
+    /// way through the period, so the first is due as soon as warming starts.
+    pub fn due(&self, period: Duration, now: DateTime<Utc>) -> usize {
+        let elapsed = (now - self.started_at).to_std().unwrap_or_default();
+        if elapsed >= period {
+            return self.total;
+        }
+        let due = self.total as u128 * elapsed.as_millis() / period.as_millis().max(1) + 1;
+        (due as usize).min(self.total)
+    }
+
+    /// When the next download is due, if any remain.
+    pub fn next_due_at(&self, period: Duration) -> Option<DateTime<Utc>> {
+        if self.downloaded >= self.total {
+            return None;
+        }
+        let offset = period.as_millis() * self.downloaded as u128 / self.total as u128;
+        Some(self.started_at + TimeDelta::milliseconds(offset as i64))
+    }
+}
+
+/// Rations the downloads of deferrable content while the content cache is cold.
+#[derive(Debug)]
+pub struct Warming {
+    options: Options,
+    progress: Mutex<Option<Progress>>,
+}
+
+impl Default for Warming {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
+}
+
+impl Warming {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            progress: Mutex::new(None),
+        }
+    }
+
+    /// Creates a warming schedule resuming the progress persisted to the options'
+    /// file, if any. An unreadable file is discarded.
+    pub async fn load(options: Options) -> Self {
+        let progress = match &options.file {
+            Some(file) if file.exists() => match file.read_json::<Progress>().await {
+                Ok(progress) => Some(progress),
+                Err(e) => {
+                    warn!("discarding the unreadable content warming progress: {e}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        Self {
+            options,
+            progress: Mutex::new(progress),
+        }
+    }
+
+    pub async fn progress(&self) -> Option<Progress> {
+        self.progress.lock().await.clone()
+    }
+
+    /// How many of the `missing` deferrable entries may be downloaded now, starting
+    /// to warm if the cache is cold and finishing once nothing is missing. `None`
+    /// when not warming, so every entry may be downloaded.
+    pub async fn budget(&self, missing: usize, now: DateTime<Utc>) -> Option<usize> {
+        if !self.options.enabled {
+            return None;
+        }
+        let mut guard = self.progress.lock().await;
+        let (progress, changed) = match guard.as_mut() {
+            None if missing == 0 || missing < self.options.cold_threshold => return None,
+            None => {
+                info!(
+                    "content cache is cold ({missing} entries missing), spreading their downloads over {:?}",
+                    self.options.period
+                );
+                let progress = guard.insert(Progress {
+                    started_at: now,
+                    total: missing,
+                    downloaded: 0,
+                });
+                (progress, true)
+            }
+            Some(_) if missing == 0 => {
+                info!("finished warming the content cache");
+                *guard = None;
+                self.delete().await;
+                return None;
+            }
+            Some(progress) => {
+                let total = progress.total.max(progress.downloaded + missing);
+                let changed = total != progress.total;
+                progress.total = total;
+                (progress, changed)
+            }
+        };
+        let budget = progress
+            .due(self.options.period, now)
+            .saturating_sub(progress.downloaded);
+        if changed {
+            let progress = progress.clone();
+            self.persist(&progress).await;
+        }
+        Some(budget)
+    }
+
+    /// Records the number of deferrable entries downloaded while warming.
+    pub async fn record(&self, downloaded: usize) {
+        let mut guard = self.progress.lock().await;
+        let Some(progress) = guard.as_mut() else {
+            return;
+        };
+        if downloaded == 0 {
+            return;
+        }
+        progress.downloaded += downloaded;
+        info!(
+            "warming the content cache: {} of {} entries downloaded",
+            progress.downloaded, progress.total
+        );
+        let progress = progress.clone();
+        self.persist(&progress).await;
+    }
+
+    /// How long until the next deferred download is due, if still warming.
+    pub async fn next_due(&self, now: DateTime<Utc>) -> Option<TimeDelta> {
+        let guard = self.progress.lock().await;
+        let next_due_at = guard.as_ref()?.next_due_at(self.options.period)?;
+        Some(next_due_at - now).filter(|wait| *wait > TimeDelta::zero())
+    }
+
+    async fn persist(&self, progress: &Progress) {
+        let Some(file) = &self.options.file else {
+            return;
+        };
+        if let Err(e) = file
+            .write_json(progress, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+        {
+            error!("failed to persist the content warming progress: {e}");
+        }
+    }
+
+    async fn delete(&self) {
+        let Some(file) = &self.options.file else {
+            return;
+        };
+        if let Err(e) = file.delete().await {
+            error!("failed to delete the content warming progress: {e}");
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// standard crates
+use std::collections::VecDeque;
+
+// internal crates
+use crate::telemetry::Sample;
+
+/// The samples waiting to be published, oldest first. Once full the oldest sample is
+/// dropped for each new one so that a device which can't reach the backend keeps its
+/// most recent samples.
+#[derive(Debug)]
+pub struct Buffer {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl Buffer {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds a sample, returning the oldest one if it was dropped to make room.
+    pub fn push(&mut self, sample: Sample) -> Option<Sample> {
+        let dropped = if self.samples.len() >= self.capacity {
+            self.samples.pop_front()
+        } else {
+            None
+        };
+        self.samples.push_back(sample);
+        dropped
+    }
+
+    pub fn samples(&mut self) -> &[Sample] {
+        self.samples.make_contiguous()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
@@ -0,0 +1,168 @@
+// standard crates
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// external crates
+use serde::Serialize;
+use tracing::debug;
+
+/// The hardware the device exposes, reported to the backend so deployments can target
+/// devices by capability. Each entry is the name of the device node or interface.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub gpus: Vec<String>,
+    pub can_interfaces: Vec<String>,
+    pub serial_ports: Vec<String>,
+    pub cameras: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        Self::detect_in(Path::new("/"))
+    }
+
+    /// Detects the capabilities of the filesystem mounted at `root`. A capability the
+    /// device lacks, or one which can't be probed on this platform, is reported as
+    /// empty.
+    pub fn detect_in(root: &Path) -> Self {
+        Self {
+            gpus: run(&Gpu, root),
+            can_interfaces: run(&Can, root),
+            serial_ports: run(&Serial, root),
+            cameras: run(&Camera, root),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // serializing string lists can't fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Detects the devices providing a single capability.
+pub trait Provider {
+    fn name(&self) -> &'static str;
+    fn detect(&self, root: &Path) -> io::Result<Vec<String>>;
+}
+
+fn run(provider: &impl Provider, root: &Path) -> Vec<String> {
+    match provider.detect(root) {
+        Ok(mut found) => {
+            found.sort();
+            found
+        }
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                debug!("unable to detect {} capability: {e}", provider.name());
+            }
+            Vec::new()
+        }
+    }
+}
+
+pub struct Gpu;
+
+impl Provider for Gpu {
+    fn name(&self) -> &'static str {
+        "gpu"
+    }
+
+    fn detect(&self, root: &Path) -> io::Result<Vec<String>> {
+        // NVIDIA discrete GPUs, Jetson integrated GPUs, and Mali or Adreno GPUs
+        let mut found = list_or_empty(&root.join("dev"), |name| {
+            numbered(name, "nvidia")
+                || name == "nvhost-gpu"
+                || numbered(name, "mali")
+                || name == "kgsl-3d0"
+        })?;
+        // any GPU with a DRM render node
+        let render_nodes = list_or_empty(&root.join("dev/dri"), |name| numbered(name, "renderD"))?;
+        found.extend(render_nodes.into_iter().map(|name| format!("dri/{name}")));
+        Ok(found)
+    }
+}
+
+pub struct Can;
+
+/// The ARPHRD_CAN hardware type of CAN network interfaces.
+const ARPHRD_CAN: &str = "280";
+
+impl Provider for Can {
+    fn name(&self) -> &'static str {
+        "can"
+    }
+
+    fn detect(&self, root: &Path) -> io::Result<Vec<String>> {
+        let net = root.join("sys/class/net");
+        let interfaces = list(&net, |_| true)?;
+        Ok(interfaces
+            .into_iter()
+            .filter(|interface| {
+                fs::read_to_string(net.join(interface).join("type"))
+                    .is_ok_and(|kind| kind.trim() == ARPHRD_CAN)
+            })
+            .collect())
+    }
+}
+
+pub struct Serial;
+
+impl Provider for Serial {
+    fn name(&self) -> &'static str {
+        "serial"
+    }
+
+    fn detect(&self, root: &Path) -> io::Result<Vec<String>> {
+        // the legacy ttyS ports are skipped since the kernel creates them whether or
+        // not a UART is attached
+        list(&root.join("dev"), |name| {
+            ["ttyUSB", "ttyACM", "ttyAMA", "ttyTHS", "ttymxc"]
+                .iter()
+                .any(|prefix| numbered(name, prefix))
+        })
+    }
+}
+
+pub struct Camera;
+
+impl Provider for Camera {
+    fn name(&self) -> &'static str {
+        "camera"
+    }
+
+    fn detect(&self, root: &Path) -> io::Result<Vec<String>> {
+        list(&root.join("sys/class/video4linux"), |name| {
+            numbered(name, "video")
+        })
+    }
+}
+
+/// Lists the entries of a directory which match the filter. Names which can't be sent
+/// in a header are skipped.
+fn list(dir: &Path, filter: impl Fn(&str) -> bool) -> io::Result<Vec<String>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.chars().all(|c| c.is_ascii_graphic()) && filter(name) {
+            found.push(name.to_string());
+        }
+    }
+    Ok(found)
+}
+
+fn list_or_empty(dir: &Path, filter: impl Fn(&str) -> bool) -> io::Result<Vec<String>> {
+    match list(dir, filter) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+/// Whether the name is the prefix followed by a device number, e.g. `ttyUSB0`.
+fn numbered(name: &str, prefix: &str) -> bool {
+    name.strip_prefix(prefix)
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
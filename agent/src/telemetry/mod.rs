@@ -1,5 +1,19 @@
+pub mod buffer;
+pub mod capabilities;
+pub mod network;
+pub mod sample;
+
+pub use self::buffer::Buffer;
+pub use self::capabilities::Capabilities;
+pub use self::network::Network;
+pub use self::sample::{Sample, Sampler};
+
+// standard crates
+use std::path::{Path, PathBuf};
+
 // external crates
-use sysinfo::System;
+use serde::{Deserialize, Serialize};
+use sysinfo::{DiskRefreshKind, Disks, System};
 
 #[derive(Debug)]
 pub struct SystemInfo {
@@ -10,6 +24,7 @@ pub struct SystemInfo {
     pub n_cpus: usize,
     pub tot_mem: u64,
     pub tot_swap: u64,
+    pub interfaces: Vec<network::Interface>,
 }
 
 impl SystemInfo {
@@ -25,6 +40,7 @@ impl SystemInfo {
             n_cpus: sys.cpus().len(),
             tot_mem: sys.total_memory(),
             tot_swap: sys.total_swap(),
+            interfaces: network::interfaces(),
             system: sys,
         }
     }
@@ -61,3 +77,46 @@ impl SystemInfo {
         self.system.used_swap()
     }
 }
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskSpace {
+    pub mount_point: String,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Returns the space on the disk holding the path, i.e. the disk mounted at the
+/// longest prefix of the path.
+pub fn disk_space(path: &Path) -> Option<DiskSpace> {
+    holding(&storage_disks(), path)
+}
+
+/// Returns the space on the disks holding the paths, once per disk, in the order the
+/// paths are given.
+pub fn disks_space(paths: &[PathBuf]) -> Vec<DiskSpace> {
+    let disks = storage_disks();
+    let mut spaces: Vec<DiskSpace> = Vec::new();
+    for space in paths.iter().filter_map(|path| holding(&disks, path)) {
+        if !spaces.iter().any(|s| s.mount_point == space.mount_point) {
+            spaces.push(space);
+        }
+    }
+    spaces
+}
+
+fn storage_disks() -> Disks {
+    Disks::new_with_refreshed_list_specifics(DiskRefreshKind::nothing().with_storage())
+}
+
+fn holding(disks: &Disks, path: &Path) -> Option<DiskSpace> {
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| DiskSpace {
+            mount_point: disk.mount_point().to_string_lossy().into_owned(),
+            available_bytes: disk.available_space(),
+            total_bytes: disk.total_space(),
+        })
+}
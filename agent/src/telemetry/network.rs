@@ -0,0 +1,246 @@
+// standard crates
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// external crates
+use serde::{Deserialize, Serialize};
+use sysinfo::Networks;
+use tokio::net::TcpStream;
+
+/// Where Linux exposes each interface's link state and hardware.
+pub const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// The kernel's IPv4 routing table.
+pub const ROUTE_TABLE: &str = "/proc/net/route";
+
+/// The host's network interfaces and whether the backend can be reached.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Network {
+    /// Sorted by name.
+    pub interfaces: Vec<Interface>,
+    pub connectivity: Connectivity,
+}
+
+impl Network {
+    /// Whether the interfaces' addresses, links or kinds, the default route or the
+    /// backend's reachability differ. Traffic counters and probe latencies are
+    /// ignored since they change all the time.
+    pub fn changed_from(&self, other: &Network) -> bool {
+        let interfaces = |network: &Network| -> Vec<Interface> {
+            network
+                .interfaces
+                .iter()
+                .map(|iface| Interface {
+                    rx_bytes: 0,
+                    tx_bytes: 0,
+                    ..iface.clone()
+                })
+                .collect()
+        };
+        let reachable = |network: &Network| {
+            network
+                .connectivity
+                .backend
+                .as_ref()
+                .map(|probe| (probe.host.clone(), probe.reachable))
+        };
+        interfaces(self) != interfaces(other)
+            || self.connectivity.default_interface != other.connectivity.default_interface
+            || reachable(self) != reachable(other)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interface {
+    pub name: String,
+    pub kind: Kind,
+    pub link: Link,
+    /// Unset for interfaces without a hardware address, e.g. loopback and tunnels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+    /// In CIDR notation, e.g. `192.168.1.20/24`.
+    pub ip_addresses: Vec<String>,
+    /// Bytes received since the host booted.
+    pub rx_bytes: u64,
+    /// Bytes transmitted since the host booted.
+    pub tx_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Ethernet,
+    Wifi,
+    Cellular,
+    Loopback,
+    /// Bridges, tunnels, VPNs and other interfaces without hardware of their own.
+    Virtual,
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Link {
+    Up,
+    Down,
+    /// The driver doesn't report the link, as for loopback.
+    Unknown,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connectivity {
+    /// The interface carrying the default IPv4 route, i.e. the one backend traffic
+    /// leaves through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_interface: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<Probe>,
+}
+
+/// The outcome of opening a TCP connection to a host.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Probe {
+    /// `host:port`.
+    pub host: String,
+    pub reachable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Lists the host's network interfaces.
+pub fn interfaces() -> Vec<Interface> {
+    let networks = Networks::new_with_refreshed_list();
+    let sys_class_net = Path::new(SYS_CLASS_NET);
+    let mut interfaces: Vec<Interface> = networks
+        .list()
+        .iter()
+        .map(|(name, data)| {
+            let mut ip_addresses: Vec<String> = data
+                .ip_networks()
+                .iter()
+                .map(|network| network.to_string())
+                .collect();
+            ip_addresses.sort();
+            let mac = data.mac_address();
+            Interface {
+                name: name.clone(),
+                kind: kind(sys_class_net, name),
+                link: link(sys_class_net, name),
+                mac_address: (!mac.is_unspecified()).then(|| mac.to_string()),
+                ip_addresses,
+                rx_bytes: data.total_received(),
+                tx_bytes: data.total_transmitted(),
+            }
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Reads an interface's kind from sysfs. Hosts without sysfs report loopback by name
+/// and everything else as unknown.
+pub fn kind(sys_class_net: &Path, name: &str) -> Kind {
+    let dir = sys_class_net.join(name);
+    // ARPHRD_LOOPBACK
+    if name == "lo" || read_trimmed(&dir.join("type")).as_deref() == Some("772") {
+        return Kind::Loopback;
+    }
+    if !dir.exists() {
+        return Kind::Unknown;
+    }
+    if dir.join("wireless").exists() || dir.join("phy80211").exists() {
+        return Kind::Wifi;
+    }
+    if name.starts_with("wwan") || read_trimmed(&dir.join("uevent")).is_some_and(is_wwan) {
+        return Kind::Cellular;
+    }
+    // virtual interfaces have no device behind them
+    if dir.join("device").exists() {
+        Kind::Ethernet
+    } else {
+        Kind::Virtual
+    }
+}
+
+fn is_wwan(uevent: String) -> bool {
+    uevent.lines().any(|line| line == "DEVTYPE=wwan")
+}
+
+/// Reads an interface's link state from sysfs.
+pub fn link(sys_class_net: &Path, name: &str) -> Link {
+    match read_trimmed(&sys_class_net.join(name).join("operstate")).as_deref() {
+        Some("up") => Link::Up,
+        Some("down" | "lowerlayerdown" | "notpresent" | "dormant") => Link::Down,
+        _ => Link::Unknown,
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+}
+
+/// The interface of the default route with the lowest metric in a routing table in
+/// the format of `/proc/net/route`.
+pub fn default_interface(route_table: &str) -> Option<String> {
+    route_table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+            if fields.len() < 8 || fields[1] != "00000000" || fields[7] != "00000000" {
+                return None;
+            }
+            let metric = fields[6].parse::<u64>().unwrap_or(u64::MAX);
+            Some((metric, fields[0]))
+        })
+        .min_by_key(|(metric, _)| *metric)
+        .map(|(_, iface)| iface.to_string())
+}
+
+/// Opens (and drops) a TCP connection to `host:port`, which includes resolving the
+/// host. The connection is made directly, so behind a proxy it only tells whether
+/// the device's own network is up.
+pub async fn probe(host: &str, port: u16, timeout: Duration) -> Probe {
+    let target = format!("{host}:{port}");
+    let started_at = Instant::now();
+    let result = tokio::time::timeout(timeout, TcpStream::connect(target.as_str())).await;
+    let (reachable, error) = match result {
+        Ok(Ok(_)) => (true, None),
+        Ok(Err(e)) => (false, Some(e.to_string())),
+        Err(_) => (
+            false,
+            Some(format!("timed out after {}ms", timeout.as_millis())),
+        ),
+    };
+    Probe {
+        host: target,
+        reachable,
+        latency_ms: reachable.then(|| started_at.elapsed().as_millis() as u64),
+        error,
+    }
+}
+
+/// Lists the host's interfaces and probes the backend at `backend_url`, if given.
+pub async fn detect(backend_url: Option<&str>, probe_timeout: Duration) -> Network {
+    let backend = match backend_url.and_then(|url| reqwest::Url::parse(url).ok()) {
+        Some(url) => match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => Some(probe(host, port, probe_timeout).await),
+            _ => None,
+        },
+        None => None,
+    };
+    Network {
+        interfaces: interfaces(),
+        connectivity: Connectivity {
+            default_interface: std::fs::read_to_string(ROUTE_TABLE)
+                .ok()
+                .and_then(|table| default_interface(&table)),
+            backend,
+        },
+    }
+}
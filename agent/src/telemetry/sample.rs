@@ -0,0 +1,92 @@
+// standard crates
+use std::path::PathBuf;
+
+// internal crates
+use crate::telemetry::{disks_space, DiskSpace};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Components, System};
+
+/// The host's resource usage at a point in time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub sampled_at: DateTime<Utc>,
+    /// The usage across all cores since the previous sample, in percent.
+    pub cpu_usage_percent: f32,
+    pub load_average: LoadAverage,
+    pub memory: Memory,
+    pub uptime_secs: u64,
+    /// The disks holding the agent's storage, once per disk.
+    pub disks: Vec<DiskSpace>,
+    /// The hottest sensor's temperature. Unset on hosts which don't expose sensors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_celsius: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Memory {
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+    pub total_swap_bytes: u64,
+    pub used_swap_bytes: u64,
+}
+
+/// Samples the host's resource usage. The sampler is kept between samples since CPU
+/// usage is measured between refreshes.
+pub struct Sampler {
+    system: System,
+    disk_paths: Vec<PathBuf>,
+}
+
+impl Sampler {
+    /// Samples the disks holding `disk_paths` along with the host's usage.
+    pub fn new(disk_paths: Vec<PathBuf>) -> Self {
+        let mut system = System::new();
+        // the baseline the first sample's CPU usage is measured against
+        system.refresh_cpu_usage();
+        Self { system, disk_paths }
+    }
+
+    pub fn sample(&mut self) -> Sample {
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        let load = System::load_average();
+        Sample {
+            sampled_at: Utc::now(),
+            cpu_usage_percent: self.system.global_cpu_usage(),
+            load_average: LoadAverage {
+                one: load.one,
+                five: load.five,
+                fifteen: load.fifteen,
+            },
+            memory: Memory {
+                total_bytes: self.system.total_memory(),
+                used_bytes: self.system.used_memory(),
+                available_bytes: self.system.available_memory(),
+                total_swap_bytes: self.system.total_swap(),
+                used_swap_bytes: self.system.used_swap(),
+            },
+            uptime_secs: System::uptime(),
+            disks: disks_space(&self.disk_paths),
+            temperature_celsius: temperature(),
+        }
+    }
+}
+
+fn temperature() -> Option<f32> {
+    Components::new_with_refreshed_list()
+        .iter()
+        .filter_map(|component| component.temperature())
+        .filter(|celsius| celsius.is_finite())
+        .max_by(|a, b| a.total_cmp(b))
+}
@@ -0,0 +1,244 @@
+// standard crates
+use std::sync::Arc;
+
+// internal crates
+use crate::activity;
+use crate::audit;
+use crate::authn::{token_mngr::TokenFile, Token, TokenManager};
+use crate::cooldown;
+use crate::deploy::apply;
+use crate::events::hub::{EventHub, SpawnOptions};
+use crate::filesys::{self, WriteOptions};
+use crate::http;
+use crate::journal::{self, Journal};
+use crate::models;
+use crate::server::{self, serve, ServerErr};
+use crate::storage::{
+    self, CfgInstContent, CfgInstStor, CfgInsts, ConfigSchemas, Deployments, GitCommits, Releases,
+    Storage,
+};
+use crate::sync::syncer::{SingleThreadSyncer, SyncerArgs, Worker};
+use crate::sync::Syncer;
+
+// external crates
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+// Helpers for spinning up agent components in temporary directories. Every
+// component only touches files under the directory it is given, so tests which
+// use separate directories can safely run in parallel. The helpers panic on
+// failure since they're only meant to be used from tests.
+
+pub async fn temp_dir(prefix: &str) -> filesys::Dir {
+    filesys::Dir::create_temp_dir(prefix)
+        .await
+        .expect("failed to create temp dir")
+}
+
+/// Spawns every store of [`Storage`] under `dir`.
+pub async fn spawn_storage(dir: &filesys::Dir, capacities: storage::Capacities) -> Storage {
+    let (cfg_insts, _) = CfgInsts::spawn(16, dir.file("cfg_inst_cache.json"), capacities.cfg_insts)
+        .await
+        .expect("failed to spawn config instance cache");
+    let (cfg_inst_content, _) = CfgInstContent::spawn(
+        16,
+        dir.subdir("cfg_inst_content_cache"),
+        capacities.cfg_inst_content,
+    )
+    .await
+    .expect("failed to spawn config instance content cache");
+    let (config_schemas, _) = ConfigSchemas::spawn(
+        16,
+        dir.subdir("config_schema_cache"),
+        capacities.config_schemas,
+    )
+    .await
+    .expect("failed to spawn config schema cache");
+    let (deployments, _) = Deployments::spawn(
+        16,
+        dir.file("deployment_cache.json"),
+        capacities.deployments,
+    )
+    .await
+    .expect("failed to spawn deployment cache");
+    let (device, _) =
+        storage::Device::spawn_with_default(64, dir.file("device.json"), models::Device::default())
+            .await
+            .expect("failed to spawn device file");
+    let (releases, _) = Releases::spawn(16, dir.file("releases_cache.json"), capacities.releases)
+        .await
+        .expect("failed to spawn release cache");
+    let (git_commits, _) = GitCommits::spawn(
+        16,
+        dir.file("git_commits_cache.json"),
+        capacities.git_commits,
+    )
+    .await
+    .expect("failed to spawn git commit cache");
+
+    Storage {
+        device: Arc::new(device),
+        cfg_insts: CfgInstStor {
+            meta: Arc::new(cfg_insts),
+            content: Arc::new(cfg_inst_content),
+            schemas: Arc::new(config_schemas),
+        },
+        deployments: Arc::new(deployments),
+        releases: Arc::new(releases),
+        git_commits: Arc::new(git_commits),
+        journal: Arc::new(
+            Journal::init(dir.file("journal.json"), journal::Options::default())
+                .await
+                .expect("failed to load request journal"),
+        ),
+        wear: Arc::new(storage::wear::Meter::init(dir.file("wear.json")).await),
+        config_access: Arc::new(
+            audit::AccessLog::init(dir.file("config_access.json"), audit::Options::default()).await,
+        ),
+        locks: Arc::new(storage::Locks::new()),
+        sweep: filesys::sweep::Report::default(),
+    }
+}
+
+/// Spawns a token manager whose token and (placeholder) key files live in `dir`.
+pub async fn spawn_token_manager<HTTPClientT: http::ClientI + 'static>(
+    dir: &filesys::Dir,
+    http_client: Arc<HTTPClientT>,
+) -> (TokenManager, JoinHandle<()>) {
+    let token_file = TokenFile::new_with_default(dir.file("token.json"), Token::default())
+        .await
+        .expect("failed to create token file");
+    let private_key_file = dir.file("private_key.pem");
+    private_key_file
+        .write_string("private_key", WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .expect("failed to write private key file");
+    let public_key_file = dir.file("public_key.pem");
+    public_key_file
+        .write_string("public_key", WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .expect("failed to write public key file");
+
+    TokenManager::spawn(
+        32,
+        http_client,
+        token_file,
+        private_key_file.into(),
+        public_key_file,
+    )
+    .expect("failed to spawn token manager")
+}
+
+pub async fn spawn_event_hub(dir: &filesys::Dir) -> (EventHub, JoinHandle<()>) {
+    EventHub::spawn(dir.file("events.jsonl"), SpawnOptions::default())
+        .await
+        .expect("failed to spawn event hub")
+}
+
+/// Spawns a syncer backed by any HTTP client (including mocks).
+pub fn spawn_syncer<HTTPClientT: http::ClientI + 'static>(
+    args: SyncerArgs<HTTPClientT, TokenManager>,
+) -> (Syncer, JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel(32);
+    let worker = Worker::new(SingleThreadSyncer::new(args), receiver);
+    let handle = tokio::spawn(worker.run());
+    (Syncer::new(sender), handle)
+}
+
+/// A fully wired set of agent components rooted in a temporary directory.
+pub struct Harness<HTTPClientT> {
+    pub dir: filesys::Dir,
+    pub http_client: Arc<HTTPClientT>,
+    pub storage: Arc<Storage>,
+    pub token_mngr: Arc<TokenManager>,
+    pub event_hub: EventHub,
+    pub syncer: Arc<Syncer>,
+}
+
+impl<HTTPClientT: http::ClientI + 'static> Harness<HTTPClientT> {
+    pub async fn new(prefix: &str, http_client: Arc<HTTPClientT>) -> Self {
+        let dir = temp_dir(prefix).await;
+        let storage = Arc::new(spawn_storage(&dir, storage::Capacities::default()).await);
+        let (token_mngr, _) = spawn_token_manager(&dir, http_client.clone()).await;
+        let token_mngr = Arc::new(token_mngr);
+        let (event_hub, _) = spawn_event_hub(&dir).await;
+        let (syncer, _) = spawn_syncer(SyncerArgs {
+            storage: storage.clone(),
+            http_client: http_client.clone(),
+            token_mngr: token_mngr.clone(),
+            deploy_opts: apply::DeployOpts::default(),
+            backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: 60,
+            },
+            event_hub: event_hub.clone(),
+        });
+
+        Self {
+            dir,
+            http_client,
+            storage,
+            token_mngr,
+            event_hub,
+            syncer: Arc::new(syncer),
+        }
+    }
+
+    /// Deletes the harness's temporary directory.
+    pub async fn cleanup(self) {
+        if let Err(e) = self.dir.delete().await {
+            tracing::warn!("failed to delete harness dir: {e}");
+        }
+    }
+}
+
+impl Harness<http::Client> {
+    pub fn server_state(&self, shutdown_tx: broadcast::Sender<()>) -> Arc<server::State> {
+        Arc::new(server::State::new(
+            self.storage.clone(),
+            self.http_client.clone(),
+            self.syncer.clone(),
+            self.token_mngr.clone(),
+            Arc::new(activity::Tracker::new()),
+            self.event_hub.clone(),
+            shutdown_tx,
+        ))
+    }
+
+    /// Serves the socket API on a socket file inside the harness's directory.
+    pub async fn serve(&self) -> Server {
+        let socket_file = self.dir.file("miru.sock");
+        let (shutdown_tx, mut shutdown_rx) = broadcast::channel(1);
+        let handle = serve::serve(
+            &server::Options {
+                socket_file: socket_file.clone(),
+                ..Default::default()
+            },
+            self.server_state(shutdown_tx.clone()),
+            async move {
+                let _ = shutdown_rx.recv().await;
+            },
+        )
+        .await
+        .expect("failed to serve socket server");
+        Server {
+            socket_file,
+            shutdown_tx,
+            handle,
+        }
+    }
+}
+
+pub struct Server {
+    pub socket_file: filesys::File,
+    pub shutdown_tx: broadcast::Sender<()>,
+    pub handle: JoinHandle<Result<(), ServerErr>>,
+}
+
+impl Server {
+    pub async fn shutdown(self) -> Result<(), ServerErr> {
+        let _ = self.shutdown_tx.send(());
+        self.handle.await.expect("socket server task panicked")
+    }
+}
@@ -0,0 +1,96 @@
+// internal crates
+use crate::authn;
+use crate::crypt;
+use crate::errors::Trace;
+use crate::filesys;
+use crate::http;
+use crate::sync;
+
+#[derive(Debug, thiserror::Error)]
+#[error("the signature of agent {version} doesn't match the release key")]
+pub struct InvalidSignatureErr {
+    pub version: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for InvalidSignatureErr {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unable to launch {binary}: {msg}")]
+pub struct LaunchErr {
+    pub binary: filesys::File,
+    pub msg: String,
+    pub trace: Box<Trace>,
+}
+
+impl crate::errors::Error for LaunchErr {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateErr {
+    #[error(transparent)]
+    InvalidSignatureErr(InvalidSignatureErr),
+    #[error(transparent)]
+    LaunchErr(LaunchErr),
+    #[error(transparent)]
+    AuthnErr(authn::AuthnErr),
+    #[error(transparent)]
+    CryptErr(crypt::CryptErr),
+    #[error(transparent)]
+    FileSysErr(filesys::FileSysErr),
+    #[error(transparent)]
+    HTTPErr(http::HTTPErr),
+    #[error(transparent)]
+    SyncErr(sync::SyncErr),
+}
+
+impl From<InvalidSignatureErr> for UpdateErr {
+    fn from(e: InvalidSignatureErr) -> Self {
+        Self::InvalidSignatureErr(e)
+    }
+}
+
+impl From<LaunchErr> for UpdateErr {
+    fn from(e: LaunchErr) -> Self {
+        Self::LaunchErr(e)
+    }
+}
+
+impl From<authn::AuthnErr> for UpdateErr {
+    fn from(e: authn::AuthnErr) -> Self {
+        Self::AuthnErr(e)
+    }
+}
+
+impl From<crypt::CryptErr> for UpdateErr {
+    fn from(e: crypt::CryptErr) -> Self {
+        Self::CryptErr(e)
+    }
+}
+
+impl From<filesys::FileSysErr> for UpdateErr {
+    fn from(e: filesys::FileSysErr) -> Self {
+        Self::FileSysErr(e)
+    }
+}
+
+impl From<http::HTTPErr> for UpdateErr {
+    fn from(e: http::HTTPErr) -> Self {
+        Self::HTTPErr(e)
+    }
+}
+
+impl From<sync::SyncErr> for UpdateErr {
+    fn from(e: sync::SyncErr) -> Self {
+        Self::SyncErr(e)
+    }
+}
+
+crate::impl_error!(UpdateErr {
+    InvalidSignatureErr,
+    LaunchErr,
+    AuthnErr,
+    CryptErr,
+    FileSysErr,
+    HTTPErr,
+    SyncErr,
+});
@@ -0,0 +1,93 @@
+// internal crates
+use crate::crypt::{base64, rsa};
+use crate::filesys;
+use crate::http::{self, agent_updates};
+use crate::trace;
+use crate::updater::errors::*;
+use crate::updater::slots::{Slot, Slots};
+
+// external crates
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+pub struct InstallParams<'a> {
+    pub release: &'a agent_updates::AgentRelease,
+    /// The public key the release must be signed with.
+    pub release_key: &'a filesys::File,
+    pub token: &'a str,
+    pub now: DateTime<Utc>,
+}
+
+/// Downloads the release into the slot which isn't running, checks its signature and
+/// switches to it on trial. The agent runs it once it's relaunched. Returns the slot
+/// it was installed to.
+pub async fn install(
+    client: &http::Client,
+    slots: &Slots,
+    params: InstallParams<'_>,
+) -> Result<Slot, UpdateErr> {
+    let mut state = slots.load().await;
+    let slot = state.inactive();
+    let binary = slots.binary(slot);
+    binary.parent()?.create_if_absent().await?;
+
+    info!(
+        "downloading agent {} into slot {}",
+        params.release.version,
+        slot.as_str()
+    );
+    agent_updates::download(
+        client,
+        agent_updates::DownloadParams {
+            release: params.release,
+            dest: &binary,
+            token: params.token,
+        },
+    )
+    .await?;
+    if let Err(e) = verify(&binary, params.release, params.release_key).await {
+        if let Err(e) = binary.delete().await {
+            warn!("failed to delete the unverified agent {binary}: {e}");
+        }
+        return Err(e);
+    }
+    make_executable(&binary).await?;
+
+    state.begin_trial(slot, &params.release.version, params.now);
+    slots.save(&state).await?;
+    info!(
+        "installed agent {} into slot {}",
+        params.release.version,
+        slot.as_str()
+    );
+    Ok(slot)
+}
+
+async fn verify(
+    binary: &filesys::File,
+    release: &agent_updates::AgentRelease,
+    release_key: &filesys::File,
+) -> Result<(), UpdateErr> {
+    let signature = base64::decode_bytes_standard(&release.signature)?;
+    let content = binary.read_bytes().await?;
+    if !rsa::verify(release_key, &content, &signature).await? {
+        return Err(UpdateErr::InvalidSignatureErr(InvalidSignatureErr {
+            version: release.version.clone(),
+            trace: trace!(),
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn make_executable(binary: &filesys::File) -> Result<(), filesys::FileSysErr> {
+    use std::os::unix::fs::PermissionsExt;
+    binary
+        .set_permissions(std::fs::Permissions::from_mode(0o755))
+        .await
+}
+
+#[cfg(not(unix))]
+async fn make_executable(_binary: &filesys::File) -> Result<(), filesys::FileSysErr> {
+    Ok(())
+}
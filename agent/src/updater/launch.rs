@@ -0,0 +1,191 @@
+// Once an update is active the installed binary is only a launcher: it execs the active
+// slot's binary, passing along its arguments and naming the slot and itself in the
+// environment. The slot's binary execs the launcher again to restart into another
+// version, after installing one or rolling one back. Exec keeps the process id, so the
+// service manager sees a single long running process throughout. The versions share the
+// device's state, so switching between them doesn't reset it. A version rolled back
+// first reverts the storage migrations it applied, since the version it replaced
+// doesn't know them.
+
+// standard crates
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+// internal crates
+use crate::filesys;
+use crate::storage::migrations::{self, Migration};
+use crate::storage::{agent_version, Layout};
+use crate::trace;
+use crate::updater::errors::*;
+use crate::updater::slots::{Slot, Slots};
+use crate::version;
+
+// external crates
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+/// Names the slot a binary was launched from. Unset for the installed binary.
+pub const SLOT_ENV: &str = "MIRU_AGENT_SLOT";
+
+/// The installed binary, as given to the binaries it launches.
+pub const LAUNCHER_ENV: &str = "MIRU_AGENT_LAUNCHER";
+
+/// How many times a version on trial may start before it's rolled back, so a version
+/// which crashes before its health check is rolled back as well.
+pub const MAX_TRIAL_STARTS: u32 = 3;
+
+/// Runs the active slot's binary in place of this process if this is the installed
+/// binary, or records the start of a version on trial if this is a slot's binary.
+/// Returns the slot the process runs from, `None` for the installed binary, and only
+/// returns at all if this process should keep running.
+pub async fn launch(slots: &Slots, layout: &Layout) -> Result<Option<Slot>, UpdateErr> {
+    let mut state = slots.load().await;
+
+    if let Some(slot) = env::var(SLOT_ENV).ok().and_then(|v| Slot::parse(&v)) {
+        let Some(trial) = state.trial.as_mut().filter(|trial| trial.slot == slot) else {
+            return Ok(Some(slot));
+        };
+        trial.starts += 1;
+        if trial.starts <= MAX_TRIAL_STARTS {
+            slots.save(&state).await?;
+            return Ok(Some(slot));
+        }
+        warn!(
+            "agent {} didn't stay up after {MAX_TRIAL_STARTS} starts, rolling it back",
+            trial.version
+        );
+        state.rollback();
+        slots.save(&state).await?;
+        revert_migrations(slots, layout, migrations::MIGRATIONS, version::VERSION).await;
+        return Err(relaunch());
+    }
+
+    loop {
+        let Some(slot) = state.active else {
+            return Ok(None);
+        };
+        let binary = slots.binary(slot);
+        let launcher = env::current_exe().map_err(|e| launch_err(&binary, e.to_string()))?;
+        info!("launching the agent installed in slot {}", slot.as_str());
+        // only returns if the binary couldn't be run
+        let e = exec(&binary, Some((slot, launcher)));
+        error!("{e}");
+        // a version on trial which can't be run fails its trial at once
+        match state.trial.as_ref().filter(|trial| trial.slot == slot) {
+            Some(trial) => {
+                warn!("rolling back agent {}", trial.version);
+                state.rollback();
+                slots.save(&state).await?;
+            }
+            None => {
+                warn!("running the installed agent instead");
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Whether the self-updater chose the version this process runs rather than the
+/// agent's package: it runs from a slot, or from the installed binary after the
+/// version which last ran was rolled back.
+pub async fn is_updater_driven(slots: &Slots, layout: &Layout, slot: Option<Slot>) -> bool {
+    if slot.is_some() {
+        return true;
+    }
+    let Ok(Some(prev_version)) = agent_version::read(&layout.agent_version()).await else {
+        return false;
+    };
+    slots.load().await.rejected.contains(&prev_version)
+}
+
+/// Reverts the storage migrations `version` applied if it was rolled back, so the
+/// version relaunched finds the data directory as it left it. Only called while nothing
+/// else writes to the data directory: before the agent runs or once it has shut down.
+pub async fn revert_migrations(
+    slots: &Slots,
+    layout: &Layout,
+    migrations: &[Migration],
+    version: &str,
+) {
+    if !slots.load().await.rejected.iter().any(|v| v == version) {
+        return;
+    }
+    if let Err(e) = migrations::revert_version(layout, migrations, version).await {
+        error!("failed to revert the storage migrations of agent {version}: {e}");
+    }
+}
+
+/// Restarts the agent through the launcher, which runs whichever slot is now active.
+/// Only returns if the launcher couldn't be run.
+pub fn relaunch() -> UpdateErr {
+    let launcher = match env::var_os(LAUNCHER_ENV) {
+        Some(path) => PathBuf::from(path),
+        None => match env::current_exe() {
+            Ok(path) => path,
+            Err(e) => return launch_err(&filesys::File::new("miru-agent"), e.to_string()),
+        },
+    };
+    exec(&filesys::File::new(launcher), None)
+}
+
+#[cfg(unix)]
+fn exec(binary: &filesys::File, slot: Option<(Slot, PathBuf)>) -> UpdateErr {
+    use crate::filesys::PathExt;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    let mut cmd = Command::new(binary.path());
+    cmd.args(env::args_os().skip(1));
+    match slot {
+        Some((slot, launcher)) => {
+            cmd.env(SLOT_ENV, slot.as_str()).env(LAUNCHER_ENV, launcher);
+        }
+        None => {
+            cmd.env_remove(SLOT_ENV).env_remove(LAUNCHER_ENV);
+        }
+    }
+    let e = cmd.exec();
+    launch_err(binary, e.to_string())
+}
+
+#[cfg(not(unix))]
+fn exec(binary: &filesys::File, _slot: Option<(Slot, PathBuf)>) -> UpdateErr {
+    launch_err(
+        binary,
+        "self-updates are only supported on unix".to_string(),
+    )
+}
+
+fn launch_err(binary: &filesys::File, msg: String) -> UpdateErr {
+    UpdateErr::LaunchErr(LaunchErr {
+        binary: binary.clone(),
+        msg,
+        trace: trace!(),
+    })
+}
+
+// ================================== RESTART ===================================== //
+static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+fn restart_notify() -> &'static Notify {
+    static NOTIFY: OnceLock<Notify> = OnceLock::new();
+    NOTIFY.get_or_init(Notify::new)
+}
+
+/// Asks the agent to shut down so it can be relaunched into another version.
+pub fn request_restart() {
+    RESTART_REQUESTED.store(true, Ordering::SeqCst);
+    restart_notify().notify_one();
+}
+
+/// Whether a restart was requested. Checked once the agent has shut down.
+pub fn is_restart_requested() -> bool {
+    RESTART_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Resolves once a restart is requested.
+pub async fn restart_requested() {
+    restart_notify().notified().await
+}
@@ -0,0 +1,16 @@
+// Self-updates. New agent binaries are installed into one of two slots, A and B, under
+// the data directory's `updates` directory, leaving the binary the agent was installed
+// with untouched. State written atomically next to the slots names the active one, so
+// an interrupted update leaves the previous version running. A new version runs on
+// trial until it syncs with the backend within a window of starting; a version which
+// doesn't, or which keeps crashing, is rolled back and never installed again. The
+// updater worker ([crate::workers::updater]) fetches and verifies releases and runs the
+// health check; [launch] switches between the versions.
+
+pub mod errors;
+pub mod install;
+pub mod launch;
+pub mod slots;
+
+pub use self::errors::UpdateErr;
+pub use self::slots::{Slot, Slots, State, Trial};
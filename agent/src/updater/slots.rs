@@ -0,0 +1,138 @@
+// internal crates
+use crate::cli;
+use crate::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// One of the two places an update is installed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Slot {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Slot::A => "a",
+            Slot::B => "b",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "a" => Some(Slot::A),
+            "b" => Some(Slot::B),
+            _ => None,
+        }
+    }
+
+    pub fn other(&self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+}
+
+/// A newly installed version which hasn't yet passed its health check.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trial {
+    pub version: String,
+    pub slot: Slot,
+    /// The slot to return to if the trial fails. The installed binary if unset.
+    pub previous: Option<Slot>,
+    pub installed_at: DateTime<Utc>,
+    /// How many times the version has been started during its trial.
+    pub starts: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    /// The slot the agent runs from. The installed binary if unset.
+    pub active: Option<Slot>,
+    pub trial: Option<Trial>,
+    /// Versions which failed their trial. They aren't installed again.
+    #[serde(default)]
+    pub rejected: Vec<String>,
+}
+
+impl State {
+    /// The slot an update is installed to, which is never the one running.
+    pub fn inactive(&self) -> Slot {
+        self.active.map_or(Slot::A, |slot| slot.other())
+    }
+
+    /// Switches to the version installed in `slot`, on trial.
+    pub fn begin_trial(&mut self, slot: Slot, version: &str, now: DateTime<Utc>) {
+        self.trial = Some(Trial {
+            version: version.to_string(),
+            slot,
+            previous: self.active,
+            installed_at: now,
+            starts: 0,
+        });
+        self.active = Some(slot);
+    }
+
+    /// Keeps the version on trial. Returns it, if there was one.
+    pub fn commit(&mut self) -> Option<Trial> {
+        self.trial.take()
+    }
+
+    /// Returns to the slot which was active before the trial and rejects the version
+    /// on trial. Returns it, if there was one.
+    pub fn rollback(&mut self) -> Option<Trial> {
+        let trial = self.trial.take()?;
+        self.active = trial.previous;
+        if !self.rejected.contains(&trial.version) {
+            self.rejected.push(trial.version.clone());
+        }
+        Some(trial)
+    }
+}
+
+/// The `updates` directory: a binary per slot and the state naming the active one.
+#[derive(Clone, Debug)]
+pub struct Slots {
+    pub dir: filesys::Dir,
+}
+
+impl Slots {
+    pub fn new(dir: filesys::Dir) -> Self {
+        Self { dir }
+    }
+
+    pub fn binary(&self, slot: Slot) -> filesys::File {
+        self.dir.subdir(slot.as_str()).file(cli::BINARY)
+    }
+
+    fn state_file(&self) -> filesys::File {
+        self.dir.file("state.json")
+    }
+
+    /// The state, or the default (run the installed binary) if there's none or it's
+    /// unreadable.
+    pub async fn load(&self) -> State {
+        let file = self.state_file();
+        if !file.exists() {
+            return State::default();
+        }
+        file.read_json::<State>().await.unwrap_or_else(|e| {
+            warn!("update state is unreadable, running the installed agent: {e}");
+            State::default()
+        })
+    }
+
+    /// Saves the state. The write is atomic, so switching slots is too.
+    pub async fn save(&self, state: &State) -> Result<(), filesys::FileSysErr> {
+        self.dir.create_if_absent().await?;
+        self.state_file()
+            .write_json(state, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+    }
+}
@@ -0,0 +1,291 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::http;
+use crate::metrics;
+use crate::models::{self, DeploymentID};
+use crate::storage;
+use crate::sync::SyncerExt;
+
+// external crates
+use chrono::{DateTime, Utc};
+use tracing::{debug, error, info, warn};
+
+/// Each audit fetches its sample from the backend, so a misconfigured interval
+/// can't audit more often than this.
+pub const MIN_INTERVAL_SECS: i64 = 60;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub interval_secs: i64,
+    pub sample_size: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60 * 60, // 1 hour
+            sample_size: 5,
+        }
+    }
+}
+
+// ================================== AUDIT ======================================= //
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The cached deployment matches the backend.
+    Consistent,
+    /// The cached deployment differs from the backend in the listed fields even
+    /// though the backend change predates the last successful sync.
+    Diverged(Vec<&'static str>),
+    /// The backend changed after the last successful sync so a difference is
+    /// expected until the next sync.
+    Pending,
+    /// The deployment couldn't be fetched from the backend.
+    Failed,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Consistent => "consistent",
+            Self::Diverged(_) => "diverged",
+            Self::Pending => "pending",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub deployment_id: DeploymentID,
+    pub outcome: Outcome,
+}
+
+pub struct Deps<'a, HTTPClientT, TokenManagerT, SyncerT> {
+    pub http_client: &'a HTTPClientT,
+    pub token_mngr: &'a TokenManagerT,
+    pub syncer: &'a SyncerT,
+    pub deployments: &'a storage::Deployments,
+}
+
+/// Compares the fields of a deployment which are owned by the backend. Fields the
+/// agent owns (activity and error status, retry state) are pushed rather than
+/// pulled so they're excluded.
+pub fn diverged_fields(
+    cached: &models::Deployment,
+    backend: &models::Deployment,
+) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if cached.description != backend.description {
+        fields.push("description");
+    }
+    if cached.target_status != backend.target_status {
+        fields.push("target_status");
+    }
+    if cached.device_id != backend.device_id {
+        fields.push("device_id");
+    }
+    if cached.release_id != backend.release_id {
+        fields.push("release_id");
+    }
+    if cached.config_instance_ids != backend.config_instance_ids {
+        fields.push("config_instance_ids");
+    }
+    fields
+}
+
+/// Selects up to `sample_size` clean deployments starting at `cursor` in id order,
+/// wrapping around, so successive audits rotate through the entire cache. Dirty
+/// deployments have unpushed local changes and are skipped.
+pub async fn select_sample(
+    deployments: &storage::Deployments,
+    sample_size: usize,
+    cursor: usize,
+) -> Vec<DeploymentID> {
+    let mut ids = match deployments.entries().await {
+        Ok(entries) => entries
+            .into_iter()
+            .filter(|entry| !entry.is_dirty)
+            .map(|entry| entry.key)
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            error!("cache audit: failed to read cached deployments: {e}");
+            return Vec::new();
+        }
+    };
+    if ids.is_empty() {
+        return ids;
+    }
+    ids.sort();
+    let start = cursor % ids.len();
+    ids.rotate_left(start);
+    ids.truncate(sample_size);
+    ids
+}
+
+/// Refetches each sampled deployment from the backend, compares it against the
+/// cached copy, and records the outcomes in the agent's metrics.
+pub async fn audit<HTTPClientT, TokenManagerT, SyncerT>(
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+    sample: &[DeploymentID],
+) -> Vec<Report>
+where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    let reports = match deps.token_mngr.get_token().await {
+        Ok(token) => {
+            let last_synced_at = deps
+                .syncer
+                .get_sync_state()
+                .await
+                .map(|state| state.last_synced_at)
+                .unwrap_or_default();
+            let mut reports = Vec::with_capacity(sample.len());
+            for id in sample {
+                let outcome = audit_one(deps, id, &token.token, last_synced_at).await;
+                reports.push(Report {
+                    deployment_id: id.clone(),
+                    outcome,
+                });
+            }
+            reports
+        }
+        Err(e) => {
+            error!("cache audit: failed to get token: {e}");
+            sample
+                .iter()
+                .map(|id| Report {
+                    deployment_id: id.clone(),
+                    outcome: Outcome::Failed,
+                })
+                .collect()
+        }
+    };
+
+    record(&reports);
+    reports
+}
+
+fn record(reports: &[Report]) {
+    let metrics = metrics::global();
+    metrics.cache_audits.inc();
+    for report in reports {
+        metrics.cache_audit_outcomes.inc(&[report.outcome.as_str()]);
+        if let Outcome::Diverged(fields) = &report.outcome {
+            for field in fields {
+                metrics.cache_audit_divergences.inc(&[field]);
+            }
+        }
+    }
+}
+
+async fn audit_one<HTTPClientT, TokenManagerT, SyncerT>(
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+    id: &str,
+    token: &str,
+    last_synced_at: DateTime<Utc>,
+) -> Outcome
+where
+    HTTPClientT: http::ClientI,
+{
+    let backend_dpl =
+        match http::deployments::get(deps.http_client, id, &["config_instances"], token).await {
+            Ok(dpl) => dpl,
+            Err(e) => {
+                warn!("cache audit: failed to fetch deployment {id}: {e}");
+                return Outcome::Failed;
+            }
+        };
+    let cached = match deps.deployments.read_optional(id.to_string()).await {
+        Ok(Some(cached)) => cached,
+        // evicted or removed since the sample was taken
+        Ok(None) => return Outcome::Consistent,
+        Err(e) => {
+            error!("cache audit: failed to read cached deployment {id}: {e}");
+            return Outcome::Failed;
+        }
+    };
+
+    let cfg_inst_ids = match &backend_dpl.config_instances {
+        Some(insts) => insts.iter().map(|inst| inst.id.clone()).collect(),
+        None => cached.config_instance_ids.clone(),
+    };
+    let backend = models::Deployment::from_backend(backend_dpl, cfg_inst_ids);
+
+    let fields = diverged_fields(&cached, &backend);
+    if fields.is_empty() {
+        Outcome::Consistent
+    } else if backend.updated_at > last_synced_at {
+        Outcome::Pending
+    } else {
+        warn!("cache audit: cached deployment {id} diverged from the backend in fields {fields:?}");
+        Outcome::Diverged(fields)
+    }
+}
+
+// ================================= WORKER ======================================= //
+pub async fn run<F, Fut, HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Cache audit worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, deps, sleep_fn) => {}
+    }
+}
+
+async fn run_impl<F, Fut, HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+    sleep_fn: F, // for testing purposes
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    info!("Running cache audit worker");
+    let mut cursor = 0;
+
+    loop {
+        // audits are low priority so wait a full interval before the first one
+        let interval_secs = options.interval_secs.max(MIN_INTERVAL_SECS);
+        sleep_fn(Duration::from_secs(interval_secs as u64)).await;
+
+        let sample = select_sample(deps.deployments, options.sample_size, cursor).await;
+        cursor = cursor.wrapping_add(sample.len());
+        if sample.is_empty() {
+            debug!("cache audit: no cached deployments to audit");
+            continue;
+        }
+
+        let reports = audit(deps, &sample).await;
+        let diverged = reports
+            .iter()
+            .filter(|r| matches!(r.outcome, Outcome::Diverged(_)))
+            .count();
+        debug!(
+            "cache audit: audited {} deployments ({diverged} diverged)",
+            reports.len()
+        );
+    }
+}
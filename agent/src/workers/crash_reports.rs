@@ -0,0 +1,137 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::diagnostics::crash;
+use crate::http;
+use crate::journal::{Journal, Request};
+use crate::storage::{self, layout::CrashLayout};
+use crate::sync::syncer::{EventMask, Subscription, SyncEvent, SyncerExt};
+
+// external crates
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub layout: CrashLayout,
+}
+
+pub struct Deps<'a, HTTPClientT, TokenManagerT, SyncerT> {
+    pub http_client: &'a HTTPClientT,
+    pub token_mngr: &'a TokenManagerT,
+    pub syncer: &'a SyncerT,
+    pub device_stor: &'a storage::Device,
+    pub journal: &'a Journal,
+}
+
+/// Moves the pending crash reports into the request journal, oldest first, then drains
+/// it. A report leaves the pending directory once it's queued and is retried with the
+/// journal's backoff from then on. Returns how many reports were queued.
+pub async fn upload<HTTPClientT, TokenManagerT, SyncerT>(
+    layout: &CrashLayout,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+) -> usize
+where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    let reports = match crash::pending(layout).await {
+        Ok(reports) => reports,
+        Err(e) => {
+            error!("crash reports: failed to read the pending reports: {e}");
+            return 0;
+        }
+    };
+    if reports.is_empty() {
+        return 0;
+    }
+    let device = match deps.device_stor.read().await {
+        Ok(device) => device,
+        Err(e) => {
+            error!("crash reports: failed to read the device: {e}");
+            return 0;
+        }
+    };
+
+    let mut queued = 0;
+    for report in reports {
+        let id = report.id.clone();
+        let request = Request::CrashReport {
+            device_id: device.id.clone(),
+            report,
+        };
+        if let Err(e) = deps.journal.enqueue(request).await {
+            error!("crash reports: failed to queue crash report {id}: {e}");
+            break;
+        }
+        if let Err(e) = crash::remove_pending(layout, &id).await {
+            error!("crash reports: failed to remove queued crash report {id}: {e}");
+        }
+        queued += 1;
+    }
+
+    let token = match deps.token_mngr.get_token().await {
+        Ok(token) => token,
+        Err(e) => {
+            error!("crash reports: failed to get token: {e}");
+            return queued;
+        }
+    };
+    match deps.journal.drain(deps.http_client, &token.token).await {
+        Ok(drained) if drained.errors.is_empty() => {
+            info!("crash reports: queued {queued} crash reports for upload")
+        }
+        Ok(drained) => warn!(
+            "crash reports: queued {queued} crash reports, {} queued requests failed to send",
+            drained.errors.len()
+        ),
+        Err(e) => error!("crash reports: failed to drain the journal: {e}"),
+    }
+    queued
+}
+
+// ================================= WORKER ======================================= //
+pub async fn run<HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Crash reports worker shutdown complete");
+        }
+        _ = run_impl(options, deps) => {}
+    }
+}
+
+async fn run_impl<HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT, SyncerT>,
+) where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    info!("Running crash reports worker");
+
+    // a successful sync shows the backend is reachable. Reports written by panics in
+    // this run are uploaded after the next one.
+    let mut syncer_events = deps
+        .syncer
+        .subscribe_filtered(EventMask::SYNC_SUCCESS)
+        .await
+        .unwrap_or_else(|e| {
+            error!("error subscribing to syncer events: {e:?}");
+            Subscription::new(watch::channel(SyncEvent::SyncSuccess).1, EventMask::NONE)
+        });
+    while syncer_events.recv().await.is_some() {
+        upload(&options.layout, deps).await;
+    }
+}
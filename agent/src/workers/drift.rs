@@ -0,0 +1,101 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::deploy::{apply, drift, versions};
+use crate::sync::SyncerExt;
+
+// external crates
+use tracing::{debug, error, info};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often the deployed files are checked for drift.
+    pub interval_secs: i64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval_secs: 5 * 60, // 5 minutes
+        }
+    }
+}
+
+pub struct Deps<'a, SyncerT> {
+    pub syncer: &'a SyncerT,
+    pub storage: &'a apply::Storage<'a>,
+    pub versions: Option<&'a versions::Versions>,
+}
+
+/// Checks the deployed files for drift once, marks the deployments with drifted files
+/// and, if any were marked, syncs so they're redeployed. Returns the ids of the
+/// deployments marked.
+pub async fn check<SyncerT: SyncerExt>(deps: &Deps<'_, SyncerT>) -> Vec<String> {
+    let drifts = match drift::detect(deps.storage, deps.versions).await {
+        Ok(drifts) => drifts,
+        Err(e) => {
+            error!("drift detection: failed to check deployed files: {e}");
+            return Vec::new();
+        }
+    };
+    if drifts.is_empty() {
+        debug!("drift detection: no deployed files have drifted");
+        return Vec::new();
+    }
+
+    let marked = match drift::mark(deps.storage.deployments, &drifts).await {
+        Ok(marked) => marked,
+        Err(e) => {
+            error!("drift detection: failed to mark drifted deployments: {e}");
+            return Vec::new();
+        }
+    };
+    if !marked.is_empty() {
+        info!("drift detection: redeploying drifted deployments {marked:?}");
+        if let Err(e) = deps.syncer.sync_if_not_in_cooldown().await {
+            error!("drift detection: failed to sync after marking drifted deployments: {e}");
+        }
+    }
+    marked
+}
+
+// ================================= WORKER ======================================= //
+pub async fn run<F, Fut, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, SyncerT>,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    SyncerT: SyncerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Drift detection worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, deps, sleep_fn) => {}
+    }
+}
+
+async fn run_impl<F, Fut, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, SyncerT>,
+    sleep_fn: F, // for testing purposes
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    SyncerT: SyncerExt,
+{
+    info!("Running drift detection worker");
+    loop {
+        // the files were just deployed if the agent is starting so wait a full
+        // interval before the first check
+        sleep_fn(Duration::from_secs(options.interval_secs.max(0) as u64)).await;
+        check(deps).await;
+    }
+}
@@ -0,0 +1,145 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::cooldown;
+use crate::errors::Error;
+use crate::http;
+use crate::journal::{queue, Journal};
+
+// external crates
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often to check the journal for queued requests.
+    pub interval_secs: i64,
+    /// How long to wait before the next drain while the backend is unreachable.
+    pub backoff: cooldown::Backoff,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            backoff: cooldown::Backoff {
+                base_secs: 30,
+                growth_factor: 2,
+                max_secs: 60 * 60, // 1 hour
+            },
+        }
+    }
+}
+
+pub struct Deps<'a, HTTPClientT, TokenManagerT> {
+    pub http_client: &'a HTTPClientT,
+    pub token_mngr: &'a TokenManagerT,
+    pub journal: &'a Journal,
+}
+
+/// Drains the journal once, refreshing the token and draining again if the backend
+/// rejected it. Returns whether the worker should back off: the backend was
+/// unreachable or failing with server errors.
+pub async fn drain<HTTPClientT, TokenManagerT>(deps: &Deps<'_, HTTPClientT, TokenManagerT>) -> bool
+where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    if deps.journal.is_empty().await {
+        return false;
+    }
+    let mut refreshed = false;
+    loop {
+        let token = match deps.token_mngr.get_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                error!("journal: failed to get token: {e}");
+                return e.is_network_conn_err();
+            }
+        };
+        let drained = match deps.journal.drain(deps.http_client, &token.token).await {
+            Ok(drained) => drained,
+            Err(e) => {
+                error!("journal: failed to drain queued requests: {e}");
+                return false;
+            }
+        };
+        if !drained.errors.is_empty() {
+            warn!(
+                "journal: {} queued requests failed to send ({} sent)",
+                drained.errors.len(),
+                drained.sent
+            );
+        } else if drained.sent > 0 {
+            debug!("journal: sent {} queued requests", drained.sent);
+        }
+
+        if drained.unauthorized && !refreshed {
+            warn!("journal: the backend rejected the token, refreshing it");
+            if let Err(e) = deps.token_mngr.refresh_token().await {
+                error!("journal: failed to refresh token: {e}");
+                return e.is_network_conn_err();
+            }
+            refreshed = true;
+            continue;
+        }
+        return drained
+            .errors
+            .iter()
+            .any(|e| e.is_network_conn_err() || queue::is_server_err(e));
+    }
+}
+
+// ================================= WORKER ======================================= //
+pub async fn run<F, Fut, HTTPClientT, TokenManagerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT>,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Journal worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, deps, sleep_fn) => {}
+    }
+}
+
+async fn run_impl<F, Fut, HTTPClientT, TokenManagerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT>,
+    sleep_fn: F, // for testing purposes
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    info!("Running journal worker");
+    let mut err_streak = 0;
+
+    loop {
+        let wait = if err_streak == 0 {
+            options.interval_secs
+        } else {
+            cooldown::calc(&options.backoff, err_streak)
+        };
+        sleep_fn(Duration::from_secs(wait.max(0) as u64)).await;
+
+        if drain(deps).await {
+            err_streak += 1;
+            debug!("journal: backend unreachable (streak: {err_streak})");
+        } else {
+            err_streak = 0;
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// standard crates
+use std::cmp::max;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::clock;
+use crate::cooldown;
+use crate::errors::*;
+use crate::http::{self, devices::WaitForSyncParams, ClientI};
+use crate::models;
+use crate::storage;
+use crate::sync::{trigger, SyncerExt};
+
+// external crates
+use tokio::time::Instant;
+use tracing::{debug, error, info};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How long the backend may hold each poll open before answering.
+    pub wait: Duration,
+    /// The shortest time between the starts of consecutive polls, so a backend which
+    /// answers at once (e.g. while the device isn't synced) isn't polled in a tight loop.
+    pub min_interval: Duration,
+    pub backoff: cooldown::Backoff,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        let five_mins = 5 * 60;
+        Self {
+            wait: Duration::from_secs(five_mins),
+            min_interval: Duration::from_secs(10),
+            backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: five_mins as i64,
+            },
+        }
+    }
+}
+
+pub async fn run<F, Fut, HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    http_client: &HTTPClientT,
+    token_mngr: &TokenManagerT,
+    syncer: &SyncerT,
+    device_stor: &storage::Device,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Long poll worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(
+            options,
+            http_client,
+            token_mngr,
+            syncer,
+            device_stor,
+            sleep_fn,
+        ) => {}
+    }
+}
+
+async fn run_impl<F, Fut, HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    http_client: &HTTPClientT,
+    token_mngr: &TokenManagerT,
+    syncer: &SyncerT,
+    device_stor: &storage::Device,
+    sleep_fn: F, // for testing purposes
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    info!("Running long poll worker");
+
+    let device = device_stor
+        .read()
+        .await
+        .unwrap_or_else(|_| Arc::new(models::Device::default()));
+
+    let mut err_streak: u32 = 0;
+    loop {
+        let started_at = Instant::now();
+        let wait = match poll(options, http_client, token_mngr, syncer, &device.id).await {
+            Ok(until_cooldown_ends) => {
+                err_streak = 0;
+                let min_wait = options.min_interval.saturating_sub(started_at.elapsed());
+                max(min_wait, until_cooldown_ends)
+            }
+            Err(()) => {
+                let cooldown_secs = cooldown::calc(&options.backoff, err_streak);
+                err_streak = err_streak.saturating_add(1);
+                Duration::from_secs(cooldown_secs as u64)
+            }
+        };
+        if !wait.is_zero() {
+            debug!("Waiting {:?} before the next long poll", wait);
+            sleep_fn(wait).await;
+        }
+    }
+}
+
+/// Polls the backend once, syncing if it reports the device isn't synced. Returns how
+/// long until the syncer's cooldown ends, before which there's no point polling again.
+async fn poll<HTTPClientT, TokenManagerT, SyncerT>(
+    options: &Options,
+    http_client: &HTTPClientT,
+    token_mngr: &TokenManagerT,
+    syncer: &SyncerT,
+    device_id: &str,
+) -> Result<Duration, ()>
+where
+    HTTPClientT: ClientI,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    let token = match token_mngr.get_token().await {
+        Ok(token) => token,
+        Err(e) => {
+            error!("error getting token for long poll: {e:?}");
+            return Err(());
+        }
+    };
+    let params = WaitForSyncParams {
+        id: device_id,
+        wait: options.wait,
+        token: &token.token,
+    };
+    let sync_req = match http::devices::wait_for_sync(http_client, params).await {
+        Ok(sync_req) => sync_req,
+        Err(e) => {
+            handle_error(&e, token_mngr).await;
+            return Err(());
+        }
+    };
+    if sync_req.is_synced {
+        return Ok(Duration::ZERO);
+    }
+    trigger::handle_request(&sync_req, syncer).await;
+
+    // the backend answers at once while the device is unsynced
+    let secs_until_cooldown_ends = syncer
+        .get_cooldown_ends_at()
+        .await
+        .unwrap_or_default()
+        .signed_duration_since(clock::now())
+        .num_seconds();
+    Ok(Duration::from_secs(max(secs_until_cooldown_ends, 0) as u64))
+}
+
+async fn handle_error<TokenManagerT: TokenManagerExt>(
+    e: &http::HTTPErr,
+    token_mngr: &TokenManagerT,
+) {
+    // auth error -> refresh the token for the next poll
+    if e.http_status() == HTTPCode::UNAUTHORIZED {
+        error!("authentication error while long polling backend for sync requests: {e:?}");
+        if let Err(e) = token_mngr.refresh_token().await {
+            error!("error refreshing token for long poll worker: {e:?}");
+        }
+    }
+    // network connection error -> ignore
+    else if e.is_network_conn_err() {
+        debug!("network connection error while long polling backend for sync requests: {e:?}");
+    }
+    // other errors -> log
+    else {
+        error!("error long polling backend for sync requests: {e:?}");
+    }
+}
@@ -1,3 +1,16 @@
+pub mod cache_audit;
+pub mod crash_reports;
+pub mod drift;
+pub mod journal;
+pub mod long_poll;
+#[cfg(feature = "mqtt")]
 pub mod mqtt;
+pub mod notifications;
 pub mod poller;
+pub mod supervisor;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
 pub mod token_refresh;
+pub mod updater;
+pub mod watchdog;
+pub mod wear;
@@ -5,35 +5,72 @@ use std::sync::Arc;
 use std::time::Duration;
 
 // internal crates
-use crate::authn::{self, TokenManagerExt};
+use crate::authn::{Token, TokenManagerExt};
 use crate::cooldown;
+use crate::deploy::pause;
 use crate::errors::*;
+use crate::logs::LogLevel;
+use crate::metrics;
 use crate::models::{self, device};
 use crate::mqtt::{
     self,
-    client::{poll, ClientI},
-    device::{Ping, SyncDevice},
+    client::{self as mqtt_client, poll, ClientI},
+    command::{self, Command},
+    device::{ConnectionState, OfflineReason, Ping, SyncDevice},
     errors::*,
-    options::{ConnectAddress, Credentials, Options as MqttOptions},
-    topics,
+    options::{ConnectAddress, Credentials, Options as MqttOptions, Protocol, QoSLevels, Tls},
+    probe::{self, Probe},
+    topics, websocket,
 };
+use crate::network::MqttHost;
+use crate::notifications::MqttMessage;
 use crate::storage;
-use crate::sync::{syncer::SyncEvent, SyncerExt};
+use crate::sync::{
+    syncer::{EventMask, Subscription, SyncEvent},
+    trigger, SyncerExt,
+};
+use crate::version;
 
 // external crates
-use rumqttc::{ConnectReturnCode, Event, EventLoop, Incoming, Publish};
-use tokio::sync::watch;
+use rumqttc::{ConnectReturnCode, Event, EventLoop, Incoming, Outgoing, Publish, QoS};
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub backoff: cooldown::Backoff,
     pub broker_address: ConnectAddress,
+    /// Periodically checks that the subscriptions are alive. Disabled if `None`.
+    pub probe: Option<probe::Options>,
+    /// The CA bundle the broker's certificate may chain to, in addition to the
+    /// system's CAs, and the client certificate presented to it.
+    pub tls: Tls,
+    /// What remote commands need to be executed.
+    pub commands: command::Options,
+    pub keep_alive: Duration,
+    pub qos: QoSLevels,
+    /// Whether the broker discards the session on disconnect, see
+    /// [MqttOptions::clean_session].
+    pub clean_session: bool,
+    pub max_inflight: u16,
+    /// Tunnels the connection over WebSockets when the broker can't be reached
+    /// directly. Disabled if `None`.
+    pub websocket: Option<WebSocketFallback>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketFallback {
+    pub options: websocket::Options,
+    /// The failed connection attempts in a row after which the worker switches to
+    /// WebSockets. Zero connects over WebSockets from the start.
+    pub after_failures: u32,
 }
 
 impl Default for Options {
     fn default() -> Self {
         let five_mins = 5 * 60;
+        let mqtt_defaults = MqttOptions::default();
         Self {
             backoff: cooldown::Backoff {
                 base_secs: 1,
@@ -41,6 +78,14 @@ impl Default for Options {
                 max_secs: five_mins,
             },
             broker_address: ConnectAddress::default(),
+            probe: Some(probe::Options::default()),
+            tls: Tls::default(),
+            commands: command::Options::default(),
+            keep_alive: mqtt_defaults.keep_alive,
+            qos: mqtt_defaults.qos,
+            clean_session: mqtt_defaults.clean_session,
+            max_inflight: mqtt_defaults.max_inflight,
+            websocket: None,
         }
     }
 }
@@ -50,25 +95,25 @@ pub async fn run<F, Fut, TokenManagerT: TokenManagerExt, SyncerT: SyncerExt>(
     token_mngr: &TokenManagerT,
     syncer: &SyncerT,
     device_stor: &storage::Device,
+    outbox: mpsc::Receiver<MqttMessage>,
     sleep_fn: F,
-    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+    shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
 ) where
     F: Fn(Duration) -> Fut,
     Fut: Future<Output = ()> + Send,
 {
-    tokio::select! {
-        _ = shutdown_signal.as_mut() => {
-            info!("MQTT worker shutdown complete");
-        }
-        // doesn't return but we do need to run it in the background
-        _ = run_impl(
-            options,
-            token_mngr,
-            syncer,
-            device_stor,
-            sleep_fn,
-        ) => {}
-    }
+    // only returns once shut down
+    run_impl(
+        options,
+        token_mngr,
+        syncer,
+        device_stor,
+        outbox,
+        sleep_fn,
+        shutdown_signal,
+    )
+    .await;
+    info!("MQTT worker shutdown complete");
 }
 
 pub async fn run_impl<F, Fut, TokenManagerT: TokenManagerExt, SyncerT: SyncerExt>(
@@ -76,56 +121,134 @@ pub async fn run_impl<F, Fut, TokenManagerT: TokenManagerExt, SyncerT: SyncerExt
     token_mngr: &TokenManagerT,
     syncer: &SyncerT,
     device_stor: &storage::Device,
+    mut outbox: mpsc::Receiver<MqttMessage>,
     sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
 ) where
     F: Fn(Duration) -> Fut,
     Fut: Future<Output = ()> + Send,
 {
     info!("Running mqtt worker");
 
-    // subscribe to syncer events
-    let mut syncer_subscriber = syncer.subscribe().await.unwrap_or_else(|e| {
-        error!("error subscribing to syncer events: {e:?}");
-        // Create a dummy receiver that never sends anything
-        watch::channel(SyncEvent::SyncSuccess).1
-    });
+    // only successful syncs are published to the backend
+    let mut syncer_events = syncer
+        .subscribe_filtered(EventMask::SYNC_SUCCESS)
+        .await
+        .unwrap_or_else(|e| {
+            error!("error subscribing to syncer events: {e:?}");
+            // Create a dummy subscription that never yields anything
+            Subscription::new(watch::channel(SyncEvent::SyncSuccess).1, EventMask::NONE)
+        });
+    let mut syncer_events_open = true;
 
     let device = device_stor
         .read()
         .await
         .unwrap_or_else(|_| Arc::new(models::Device::default()));
 
+    // the client connects to the websocket bridge instead of the broker once the
+    // worker switches to websockets
+    let mut client_options = options.clone();
+    let mut bridge = None;
+    let mut connect_failures = 0;
+    if let Some(fallback) = options.websocket.as_ref().filter(|f| f.after_failures == 0) {
+        bridge = start_websocket_bridge(options, fallback, &mut client_options).await;
+    }
+
     // create the mqtt client
-    let (mqtt_client, eventloop) = init_client(
-        &device.id,
-        &device.session_id,
-        token_mngr,
-        options.broker_address.clone(),
-    )
-    .await;
+    let (mqtt_client, eventloop) = tokio::select! {
+        _ = shutdown_signal.as_mut() => return,
+        client = init_client(
+            &device.id,
+            &device.session_id,
+            token_mngr,
+            &client_options,
+        ) => client,
+    };
 
     let mut state = State {
         client: mqtt_client,
         eventloop,
         err_streak: 0,
     };
+    let mut outbox_open = true;
+    let mut probe = options.probe.map(|opts| Probe::new(opts, Instant::now()));
+    let mut tokens = token_mngr.subscribe();
+    let mut tokens_open = true;
 
     loop {
         tokio::select! {
+            // mark the device as stopped rather than letting the last will mark it
+            // as lost
+            _ = shutdown_signal.as_mut() => {
+                disconnect_cleanly(&mut state, &device.id).await;
+                return;
+            }
+
             // listen for syncer events from the syncer worker (this device)
-            _ = syncer_subscriber.changed() => {
-                let syncer_event = syncer_subscriber.borrow().clone();
-                handle_syncer_event(
-                    &syncer_event,
-                    &device.id,
-                    &state.client,
-                ).await;
+            syncer_event = syncer_events.recv(), if syncer_events_open => {
+                match syncer_event {
+                    Some(syncer_event) => {
+                        handle_syncer_event(
+                            &syncer_event,
+                            &device.id,
+                            &state.client,
+                        ).await;
+                    }
+                    None => syncer_events_open = false,
+                }
+            }
+
+            // publish messages queued by other workers (e.g. notifications)
+            msg = outbox.recv(), if outbox_open => {
+                match msg {
+                    Some(msg) => publish_outbox_msg(&msg, &state.client).await,
+                    None => outbox_open = false,
+                }
+            }
+
+            // reconnect with the refreshed token rather than the one the client
+            // was created with, which the broker rejects once it expires
+            changed = tokens.changed(), if tokens_open => {
+                match changed {
+                    Ok(()) => {
+                        let token = tokens.borrow_and_update().clone();
+                        set_credentials(&mut state.eventloop, &device.session_id, &token);
+                    }
+                    Err(_) => tokens_open = false,
+                }
+            }
+
+            // check that the subscriptions are still alive
+            _ = tokio::time::sleep_until(
+                probe.as_ref().map_or_else(Instant::now, Probe::deadline)
+            ), if probe.is_some() => {
+                if let Some(probe) = probe.as_mut() {
+                    if handle_probe_deadline(probe, &state.client, &device.id).await {
+                        let (mqtt_client, eventloop) = init_client(
+                            &device.id,
+                            &device.session_id,
+                            token_mngr,
+                            &client_options,
+                        )
+                        .await;
+                        state.client = mqtt_client;
+                        state.eventloop = eventloop;
+                        probe.reset(Instant::now());
+                    }
+                }
             }
 
             // listen for sync commands from the backend (via mqtt broker)
             mqtt_result = poll(&mut state.eventloop) => {
                 match mqtt_result {
                     Ok(mqtt_event) => {
+                        if is_connected(&mqtt_event) {
+                            connect_failures = 0;
+                        }
+                        if let Some(probe) = probe.as_mut() {
+                            handle_probe_event(&mqtt_event, probe, &device.id);
+                        }
                         state.err_streak = handle_event(
                             &mqtt_event,
                             &state.client,
@@ -133,16 +256,50 @@ pub async fn run_impl<F, Fut, TokenManagerT: TokenManagerExt, SyncerT: SyncerExt
                             &device.id,
                             device_stor,
                         ).await;
+                        handle_command_event(
+                            &mqtt_event,
+                            &options.commands,
+                            &state.client,
+                            syncer,
+                            &device.id,
+                            device_stor,
+                        ).await;
                     }
                     Err(e) => {
+                        // the connection is down so the probe couldn't come back
+                        if let Some(probe) = probe.as_mut() {
+                            probe.reset(Instant::now());
+                        }
+                        if e.is_network_conn_err() {
+                            connect_failures += 1;
+                        }
                         state = handle_error(
                             state,
                             e,
                             &device,
                             token_mngr,
-                            &options.broker_address,
+                            &client_options,
                             device_stor,
                         ).await;
+                        if let Some(fallback) = options.websocket.as_ref().filter(|f| {
+                            bridge.is_none() && connect_failures >= f.after_failures
+                        }) {
+                            warn!(
+                                "failed to connect to the mqtt broker {connect_failures} times in a row, switching to websockets"
+                            );
+                            bridge = start_websocket_bridge(options, fallback, &mut client_options).await;
+                            if bridge.is_some() {
+                                let (mqtt_client, eventloop) = init_client(
+                                    &device.id,
+                                    &device.session_id,
+                                    token_mngr,
+                                    &client_options,
+                                )
+                                .await;
+                                state.client = mqtt_client;
+                                state.eventloop = eventloop;
+                            }
+                        }
                     }
                 }
             }
@@ -151,20 +308,97 @@ pub async fn run_impl<F, Fut, TokenManagerT: TokenManagerExt, SyncerT: SyncerExt
         // sleep for the cooldown period to prevent throttling from mqtt errors
         let cooldown_secs = cooldown::calc(&options.backoff, state.err_streak);
         let cooldown_duration = Duration::from_secs(cooldown_secs as u64);
-        sleep_fn(cooldown_duration).await;
+        tokio::select! {
+            _ = shutdown_signal.as_mut() => {
+                disconnect_cleanly(&mut state, &device.id).await;
+                return;
+            }
+            _ = sleep_fn(cooldown_duration) => {}
+        }
+    }
+}
+
+/// Replaces the credentials the client reconnects with, leaving the current
+/// connection as it is.
+pub fn set_credentials(eventloop: &mut EventLoop, device_session_id: &str, token: &Token) {
+    eventloop
+        .mqtt_options
+        .set_credentials(device_session_id, token.token.as_str());
+    debug!(
+        "updated the mqtt credentials to the token expiring at {}",
+        token.expires_at
+    );
+}
+
+/// Starts tunnelling the broker connection over websockets and points the client
+/// options at the tunnel. Returns `None` if the bridge couldn't be started, in which
+/// case the client keeps connecting to the broker directly.
+async fn start_websocket_bridge(
+    options: &Options,
+    fallback: &WebSocketFallback,
+    client_options: &mut Options,
+) -> Option<websocket::Bridge> {
+    let broker = &options.broker_address;
+    let tls = match broker.protocol() {
+        Protocol::SSL => match mqtt_client::tls_connector(&options.tls) {
+            Ok(connector) => Some(connector),
+            Err(e) => {
+                error!("error configuring TLS for the mqtt websocket bridge: {e}");
+                return None;
+            }
+        },
+        Protocol::TCP => None,
+    };
+    let target = websocket::Target {
+        host: broker.broker().as_str().to_string(),
+        options: fallback.options.clone(),
+        tls,
+    };
+    let bridge = match websocket::Bridge::spawn(target).await {
+        Ok(bridge) => bridge,
+        Err(e) => {
+            error!("error starting the mqtt websocket bridge: {e:?}");
+            return None;
+        }
+    };
+    let address = MqttHost::new("127.0.0.1")
+        .map_err(|e| e.to_string())
+        .and_then(|host| {
+            ConnectAddress::new(host, Protocol::TCP, bridge.port()).map_err(|e| e.to_string())
+        });
+    match address {
+        Ok(address) => {
+            info!(
+                "connecting to the mqtt broker over websockets on port {}",
+                fallback.options.port
+            );
+            client_options.broker_address = address;
+            Some(bridge)
+        }
+        Err(e) => {
+            error!("error addressing the mqtt websocket bridge: {e}");
+            None
+        }
     }
 }
 
+fn is_connected(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Incoming(Incoming::ConnAck(connack)) if connack.code == ConnectReturnCode::Success
+    )
+}
+
 async fn init_client<TokenManagerT: TokenManagerExt>(
     device_id: &str,
     device_session_id: &str,
     token_mngr: &TokenManagerT,
-    broker_address: ConnectAddress,
+    options: &Options,
 ) -> (mqtt::Client, EventLoop) {
     // update the mqtt password
     let token = match token_mngr.get_token().await {
         Ok(token) => token.token.clone(),
-        Err(_) => authn::Token::default().token,
+        Err(_) => String::new(),
     };
 
     // initialize the mqtt client
@@ -172,20 +406,91 @@ async fn init_client<TokenManagerT: TokenManagerExt>(
         username: device_session_id.to_string(),
         password: token,
     };
-    let options = MqttOptions::new(credentials)
-        .with_connect_address(broker_address)
-        .with_client_id(device_id.to_string());
-    let (mqtt_client, eventloop) = mqtt::Client::new(&options).await;
+    let mqtt_options = MqttOptions::new(credentials)
+        .with_connect_address(options.broker_address.clone())
+        .with_client_id(device_id.to_string())
+        .with_tls(options.tls.clone())
+        .with_last_will(mqtt::device::last_will(device_id))
+        .with_keep_alive(options.keep_alive)
+        .with_qos(options.qos)
+        .with_clean_session(options.clean_session)
+        .with_max_inflight(options.max_inflight);
+    let (mqtt_client, eventloop) = mqtt::Client::new(&mqtt_options).await;
+    subscribe(&mqtt_client, device_id).await;
 
+    (mqtt_client, eventloop)
+}
+
+pub async fn subscribe<ClientT: ClientI>(mqtt_client: &ClientT, device_id: &str) {
     // subscribe to device synchronization updates
-    if let Err(e) = mqtt::device::subscribe_sync(&mqtt_client, device_id).await {
+    if let Err(e) = mqtt::device::subscribe_sync(mqtt_client, device_id).await {
         error!("error subscribing to device synchronization updates: {e:?}");
     };
-    if let Err(e) = mqtt::device::subscribe_ping(&mqtt_client, device_id).await {
+    if let Err(e) = mqtt::device::subscribe_ping(mqtt_client, device_id).await {
         error!("error subscribing to device ping updates: {e:?}");
     };
+    if let Err(e) = mqtt::device::subscribe_command(mqtt_client, device_id).await {
+        error!("error subscribing to remote commands: {e:?}");
+    };
+    if let Err(e) = mqtt::device::subscribe_probe(mqtt_client, device_id).await {
+        error!("error subscribing to the subscription probe: {e:?}");
+    };
+}
 
-    (mqtt_client, eventloop)
+/// Sends the next probe or handles the pending probe not coming back by
+/// resubscribing. Returns whether the client should reconnect because resubscribing
+/// hasn't helped either.
+pub async fn handle_probe_deadline<ClientT: ClientI>(
+    probe: &mut Probe,
+    mqtt_client: &ClientT,
+    device_id: &str,
+) -> bool {
+    match probe.fire(Instant::now()) {
+        probe::Action::Send(message_id) => {
+            if let Err(e) = mqtt::device::publish_probe(mqtt_client, device_id, message_id).await {
+                error!("error publishing subscription probe: {e:?}");
+            }
+            false
+        }
+        probe::Action::Failed { streak } => {
+            metrics::global().mqtt_probe_failures.inc();
+            if streak >= probe.options().reconnect_after {
+                warn!("subscription probe failed {streak} times in a row, reconnecting to the mqtt broker");
+                return true;
+            }
+            warn!("subscription probe didn't come back, resubscribing");
+            subscribe(mqtt_client, device_id).await;
+            false
+        }
+    }
+}
+
+/// Marks the probe as answered when it comes back and starts it over on new
+/// connections.
+pub fn handle_probe_event(event: &Event, probe: &mut Probe, device_id: &str) {
+    match event {
+        Event::Incoming(Incoming::ConnAck(connack))
+            if connack.code == ConnectReturnCode::Success =>
+        {
+            probe.reset(Instant::now());
+        }
+        Event::Incoming(Incoming::Publish(publish)) => {
+            if topics::parse_subscription(device_id, &publish.topic)
+                != topics::SubscriptionTopics::Probe
+            {
+                return;
+            }
+            match serde_json::from_slice::<Ping>(&publish.payload) {
+                Ok(ping) => {
+                    if !probe.received(&ping.message_id, Instant::now()) {
+                        debug!("ignoring stale subscription probe {}", ping.message_id);
+                    }
+                }
+                Err(e) => error!("error deserializing subscription probe: {e:?}"),
+            }
+        }
+        _ => {}
+    }
 }
 
 pub async fn handle_syncer_event<ClientT: ClientI>(
@@ -209,6 +514,20 @@ pub async fn handle_syncer_event<ClientT: ClientI>(
     }
 }
 
+pub async fn publish_outbox_msg<ClientT: ClientI>(msg: &MqttMessage, mqtt_client: &ClientT) {
+    let result = mqtt_client
+        .publish(mqtt_client::Publish {
+            topic: &msg.topic,
+            qos: QoS::AtLeastOnce,
+            retained: false,
+            payload: &msg.payload,
+        })
+        .await;
+    if let Err(e) = result {
+        error!("error publishing message to topic {}: {e:?}", msg.topic);
+    }
+}
+
 type ErrStreak = u32;
 
 pub async fn handle_event<ClientT: ClientI, SyncerT: SyncerExt>(
@@ -227,7 +546,13 @@ pub async fn handle_event<ClientT: ClientI, SyncerT: SyncerExt>(
                 return err_streak;
             }
             info!("Established connection to mqtt broker");
+            metrics::global().mqtt_connections.inc();
             let _ = device_stor.patch(device::Updates::connected()).await;
+            // replaces the last will retained if the previous connection was lost
+            let online = ConnectionState::online();
+            if let Err(e) = mqtt::device::publish_state(mqtt_client, device_id, &online).await {
+                error!("error publishing the device's online state: {e:?}");
+            }
         }
         // update the device connection status on successful disconnections
         Event::Incoming(Incoming::Disconnect) => {
@@ -245,6 +570,10 @@ pub async fn handle_event<ClientT: ClientI, SyncerT: SyncerExt>(
                 topics::SubscriptionTopics::Ping => {
                     handle_ping_event(publish, mqtt_client, device_id).await;
                 }
+                // see handle_command_event
+                topics::SubscriptionTopics::Command => {}
+                // probes are the worker's own messages, see handle_probe_event
+                topics::SubscriptionTopics::Probe => {}
                 topics::SubscriptionTopics::Unknown => {
                     debug_assert!(false, "unknown topic: {}", publish.topic);
                     warn!("unknown topic: {}", publish.topic);
@@ -259,19 +588,14 @@ pub async fn handle_event<ClientT: ClientI, SyncerT: SyncerExt>(
 }
 
 async fn handle_sync_event<SyncerT: SyncerExt>(publish: &Publish, syncer: &SyncerT) {
-    let is_synced = match serde_json::from_slice::<SyncDevice>(&publish.payload) {
-        Ok(sync_req) => sync_req.is_synced,
+    let sync_req = match serde_json::from_slice::<SyncDevice>(&publish.payload) {
+        Ok(sync_req) => sync_req,
         Err(e) => {
             error!("error deserializing sync request: {e:?}");
-            false
+            SyncDevice { is_synced: false }
         }
     };
-    if is_synced {
-        return;
-    }
-    if let Err(e) = syncer.sync_if_not_in_cooldown().await {
-        error!("error syncing device: {e:?}");
-    }
+    trigger::handle_request(&sync_req, syncer).await;
 }
 
 async fn handle_ping_event<ClientT: ClientI>(publish: &Publish, client: &ClientT, device_id: &str) {
@@ -295,18 +619,193 @@ async fn handle_ping_event<ClientT: ClientI>(publish: &Publish, client: &ClientT
     }
 }
 
+/// Executes remote commands published on the device's command topic and publishes
+/// their outcome on the command response topic. Commands which can't be parsed are
+/// answered as failed as long as they carry a message id.
+pub async fn handle_command_event<ClientT: ClientI, SyncerT: SyncerExt>(
+    event: &Event,
+    options: &command::Options,
+    mqtt_client: &ClientT,
+    syncer: &SyncerT,
+    device_id: &str,
+    device_stor: &storage::Device,
+) {
+    let Event::Incoming(Incoming::Publish(publish)) = event else {
+        return;
+    };
+    if topics::parse_subscription(device_id, &publish.topic) != topics::SubscriptionTopics::Command
+    {
+        return;
+    }
+    let request = match serde_json::from_slice::<command::Request>(&publish.payload) {
+        Ok(request) => request,
+        Err(e) => {
+            error!("error deserializing remote command: {e:?}");
+            return;
+        }
+    };
+    let response = match serde_json::from_value::<Command>(request.command.clone()) {
+        Ok(cmd) => {
+            info!(
+                "executing remote command {} with message id {}",
+                cmd.name(),
+                request.message_id
+            );
+            match execute_command(&cmd, options, syncer, device_stor).await {
+                Ok(result) => command::Response::succeeded(request.message_id, &cmd, result),
+                Err(e) => {
+                    error!("remote command {} failed: {e}", cmd.name());
+                    command::Response::failed(request.message_id, Some(cmd.name().to_string()), e)
+                }
+            }
+        }
+        Err(e) => {
+            let name = request.command.get("name").and_then(|name| name.as_str());
+            warn!("rejecting remote command {name:?}: {e}");
+            command::Response::failed(
+                request.message_id,
+                name.map(str::to_string),
+                format!("invalid command: {e}"),
+            )
+        }
+    };
+    if let Err(e) = mqtt::device::publish_command_response(mqtt_client, device_id, &response).await
+    {
+        error!("error publishing remote command response: {e:?}");
+    }
+}
+
+async fn execute_command<SyncerT: SyncerExt>(
+    cmd: &Command,
+    options: &command::Options,
+    syncer: &SyncerT,
+    device_stor: &storage::Device,
+) -> Result<Option<serde_json::Value>, String> {
+    let result = match cmd {
+        Command::SyncNow => {
+            syncer.sync().await.map_err(|e| e.to_string())?;
+            return Ok(None);
+        }
+        Command::SetLogLevel { level } => {
+            let level = level.parse::<LogLevel>().map_err(|e| e.to_string())?;
+            options.log_level.set(level).map_err(|e| e.to_string())?;
+            return Ok(None);
+        }
+        Command::ReloadSettings => {
+            let Some(source) = &options.settings else {
+                return Err("settings can't be reloaded by this agent".to_string());
+            };
+            let settings = source.load().await.map_err(|e| e.to_string())?;
+            options
+                .log_level
+                .set(settings.log_level.clone())
+                .map_err(|e| e.to_string())?;
+            serde_json::to_value(command::Reload {
+                log_level: settings.log_level.clone(),
+                restart_required: source.restart_required(&settings),
+            })
+        }
+        Command::SetRemoteSettings { settings } => {
+            let Some(source) = &options.settings else {
+                return Err("settings can't be set remotely on this agent".to_string());
+            };
+            let settings = source
+                .set_remote(settings.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            options
+                .log_level
+                .set(settings.log_level.clone())
+                .map_err(|e| e.to_string())?;
+            serde_json::to_value(command::Reload {
+                log_level: settings.log_level.clone(),
+                restart_required: source.restart_required(&settings),
+            })
+        }
+        Command::ReportHealth => {
+            let device = device_stor.read().await.map_err(|e| e.to_string())?;
+            let sync_state = syncer.get_sync_state().await.map_err(|e| e.to_string())?;
+            serde_json::to_value(command::Health {
+                agent_version: version::VERSION.to_string(),
+                device_status: device.status.clone(),
+                log_level: options.log_level.level(),
+                last_synced_at: sync_state.last_synced_at,
+                last_attempted_sync_at: sync_state.last_attempted_sync_at,
+                sync_err_streak: sync_state.err_streak,
+                sync_in_cooldown: sync_state.is_in_cooldown(),
+            })
+        }
+        Command::PauseDeployments { reason } => {
+            let Some(switch) = &options.pause else {
+                return Err("deployments can't be paused on this agent".to_string());
+            };
+            let status = switch
+                .pause(reason.clone(), pause::Origin::Mqtt)
+                .await
+                .map_err(|e| e.to_string())?;
+            serde_json::to_value(status)
+        }
+        Command::ResumeDeployments => {
+            let Some(switch) = &options.pause else {
+                return Err("deployments can't be paused on this agent".to_string());
+            };
+            let status = switch
+                .resume(pause::Origin::Mqtt)
+                .await
+                .map_err(|e| e.to_string())?;
+            if let Err(e) = syncer.sync().await {
+                warn!("Failed to sync after resuming deployments: {e}");
+            }
+            serde_json::to_value(status)
+        }
+    };
+    result.map(Some).map_err(|e| e.to_string())
+}
+
 pub struct State {
     pub client: mqtt::Client,
     pub eventloop: EventLoop,
     pub err_streak: ErrStreak,
 }
 
+/// Marks the device as stopped on its state topic and disconnects, which keeps the
+/// broker from publishing the last will. The event loop is driven until the
+/// disconnect has gone out, for at most the client's disconnect timeout.
+pub async fn disconnect_cleanly(state: &mut State, device_id: &str) {
+    let stopped = ConnectionState::offline(OfflineReason::Stopped);
+    if let Err(e) = mqtt::device::publish_state(&state.client, device_id, &stopped).await {
+        error!("error publishing the device's offline state: {e:?}");
+    }
+    if let Err(e) = state.client.disconnect().await {
+        error!("error disconnecting from the mqtt broker: {e:?}");
+        return;
+    }
+    let flush = async {
+        loop {
+            match poll(&mut state.eventloop).await {
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    debug!("mqtt connection closed while disconnecting: {e:?}");
+                    break;
+                }
+            }
+        }
+    };
+    if tokio::time::timeout(state.client.timeouts.disconnect, flush)
+        .await
+        .is_err()
+    {
+        warn!("timed out disconnecting from the mqtt broker");
+    }
+}
+
 pub async fn handle_error<TokenManagerT: TokenManagerExt>(
     mut state: State,
     e: MQTTError,
     device: &models::Device,
     token_mngr: &TokenManagerT,
-    broker_address: &ConnectAddress,
+    options: &Options,
     device_stor: &storage::Device,
 ) -> State {
     state.err_streak = if e.is_network_conn_err() {
@@ -335,13 +834,8 @@ pub async fn handle_error<TokenManagerT: TokenManagerExt>(
         if let Err(e) = token_mngr.refresh_token().await {
             error!("error refreshing token for backend sync worker: {e:?}");
         }
-        let (mqtt_client, eventloop) = init_client(
-            &device.id,
-            &device.session_id,
-            token_mngr,
-            broker_address.clone(),
-        )
-        .await;
+        let (mqtt_client, eventloop) =
+            init_client(&device.id, &device.session_id, token_mngr, options).await;
         state.client = mqtt_client;
         state.eventloop = eventloop;
         state
@@ -0,0 +1,156 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::events::model::Event;
+use crate::filesys;
+use crate::notifications::{webhook, Keyring, MqttOutbox, Notification, Sink, Target};
+
+// external crates
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub sinks: Vec<Sink>,
+}
+
+pub struct Deps<'a> {
+    pub webhook_client: &'a webhook::Client,
+    pub mqtt_outbox: &'a MqttOutbox,
+    /// The webhook signing keyring, read for every notification so that rotated
+    /// keys take effect without restarting the agent.
+    pub webhook_keys: &'a filesys::File,
+}
+
+pub async fn run<F, Fut>(
+    options: &Options,
+    deps: &Deps<'_>,
+    sleep_fn: F,
+    events: broadcast::Receiver<Event>,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Notifications worker shutdown complete");
+        }
+        _ = run_impl(options, deps, sleep_fn, events) => {}
+    }
+}
+
+async fn run_impl<F, Fut>(
+    options: &Options,
+    deps: &Deps<'_>,
+    sleep_fn: F, // for testing purposes
+    mut events: broadcast::Receiver<Event>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    info!(
+        "Running notifications worker with {} sinks",
+        options.sinks.len()
+    );
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("notifications worker lagged behind and skipped {skipped} events");
+                continue;
+            }
+            Err(RecvError::Closed) => {
+                info!("event hub closed, stopping notifications worker");
+                return;
+            }
+        };
+        let Some(notification) = Notification::from_event(&event) else {
+            continue;
+        };
+        notify(&options.sinks, deps, &notification, &sleep_fn).await;
+    }
+}
+
+/// Delivers a notification to every sink accepting it. A sink failing to deliver
+/// doesn't prevent delivery to the remaining sinks. Webhooks aren't sent if the
+/// signing keyring can't be read rather than being sent unsigned.
+pub async fn notify<F, Fut>(
+    sinks: &[Sink],
+    deps: &Deps<'_>,
+    notification: &Notification,
+    sleep_fn: &F,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    let keyring = match Keyring::load(deps.webhook_keys).await {
+        Ok(keyring) => Some(keyring),
+        Err(e) => {
+            error!("failed to read the webhook signing keys: {e}");
+            None
+        }
+    };
+    let no_keys = Keyring::default();
+    for sink in sinks.iter().filter(|sink| sink.accepts(notification)) {
+        let keyring = match (&sink.target, &keyring) {
+            (_, Some(keyring)) => keyring,
+            (Target::Webhook { .. }, None) => {
+                error!(
+                    "not delivering {} notification to {:?} since it can't be signed",
+                    notification.event_type, sink.target
+                );
+                continue;
+            }
+            (_, None) => &no_keys,
+        };
+        deliver(sink, deps, notification, keyring, sleep_fn).await;
+    }
+}
+
+/// Delivers a notification to a sink, retrying failed attempts with the sink's
+/// backoff. Retries hold up the notifications behind this one, so sinks should keep
+/// their backoff short.
+async fn deliver<F, Fut>(
+    sink: &Sink,
+    deps: &Deps<'_>,
+    notification: &Notification,
+    keyring: &Keyring,
+    sleep_fn: &F,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    let max_attempts = sink.max_attempts();
+    for attempt in 1..=max_attempts {
+        if attempt > 1 {
+            if let Some(retry) = &sink.retry {
+                sleep_fn(retry.delay(attempt - 1)).await;
+            }
+        }
+        match sink
+            .deliver(notification, deps.webhook_client, deps.mqtt_outbox, keyring)
+            .await
+        {
+            Ok(()) => {
+                debug!(
+                    "delivered {} notification to {:?}",
+                    notification.event_type, sink.target
+                );
+                return;
+            }
+            Err(e) if attempt < max_attempts => warn!(
+                "failed to deliver {} notification to {:?} (attempt {attempt} of {max_attempts}), retrying: {e}",
+                notification.event_type, sink.target
+            ),
+            Err(e) => error!(
+                "failed to deliver {} notification to {:?}: {e}",
+                notification.event_type, sink.target
+            ),
+        }
+    }
+}
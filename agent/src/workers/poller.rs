@@ -2,9 +2,11 @@
 use std::cmp::max;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Mutex;
 use std::time::Duration;
 
 // internal crates
+use crate::clock;
 use crate::models::device;
 use crate::storage;
 use crate::sync::{
@@ -13,13 +15,17 @@ use crate::sync::{
 };
 
 // external crates
-use chrono::{TimeDelta, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
 use tokio::sync::watch;
 use tracing::{debug, error, info};
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub poll_interval_secs: i64,
+    /// Up to this many seconds are added to each poll interval at random, so a fleet
+    /// restarted together doesn't poll the backend in lockstep.
+    pub jitter_secs: i64,
 }
 
 impl Default for Options {
@@ -27,14 +33,55 @@ impl Default for Options {
         let twelve_hours = 12 * 60 * 60;
         Self {
             poll_interval_secs: twelve_hours,
+            jitter_secs: 0,
         }
     }
 }
 
+/// Random seconds in `0..=max_secs`, using subsecond nanos to avoid adding a rand
+/// dependency. Not cryptographic, just enough to spread polls.
+pub fn jitter(max_secs: i64) -> i64 {
+    if max_secs <= 0 {
+        return 0;
+    }
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as i64;
+    nanos % (max_secs + 1)
+}
+
+// ================================= METRICS ====================================== //
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Snapshot {
+    /// Number of times the poller has woken up since the agent started.
+    pub runs: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Snapshot>,
+}
+
+impl Metrics {
+    pub fn snapshot(&self) -> Snapshot {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn record(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.runs += 1;
+        inner.last_run_at = Some(Utc::now());
+    }
+}
+
+// ================================== WORKER ======================================= //
 pub async fn run<F, Fut, SyncerT: SyncerExt>(
     options: &Options,
     syncer: &SyncerT,
     device_stor: &storage::Device,
+    metrics: &Metrics,
     sleep_fn: F,
     mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
 ) where
@@ -50,6 +97,7 @@ pub async fn run<F, Fut, SyncerT: SyncerExt>(
             options,
             syncer,
             device_stor,
+            metrics,
             sleep_fn,
         ) => {}
     }
@@ -59,6 +107,7 @@ async fn run_impl<F, Fut, SyncerT: SyncerExt>(
     options: &Options,
     syncer: &SyncerT,
     device_stor: &storage::Device,
+    metrics: &Metrics,
     sleep_fn: F, // for testing purposes
 ) where
     F: Fn(Duration) -> Fut,
@@ -76,27 +125,32 @@ async fn run_impl<F, Fut, SyncerT: SyncerExt>(
     // begin by syncing
     let _ = syncer.sync_if_not_in_cooldown().await;
 
+    // drawn once per scheduled sync so events don't reshuffle the next one
+    let mut jitter_secs = jitter(options.jitter_secs);
+
     loop {
+        metrics.record();
+
         // poll from the last sync attempt, not the current time
         let last_attempted_sync_at = syncer
             .get_last_attempted_sync_at()
             .await
             .unwrap_or_default()
             .timestamp();
-        let secs_since_last_sync = Utc::now().timestamp() - last_attempted_sync_at;
-        let secs_until_next_sync = options.poll_interval_secs - secs_since_last_sync;
+        let secs_since_last_sync = clock::now().timestamp() - last_attempted_sync_at;
+        let secs_until_next_sync = options.poll_interval_secs + jitter_secs - secs_since_last_sync;
 
         // wait until the cooldown ends or the poll interval elapses (max of the two)
         let secs_until_cooldown_ends = syncer
             .get_cooldown_ends_at()
             .await
             .unwrap_or_default()
-            .signed_duration_since(Utc::now())
+            .signed_duration_since(clock::now())
             .num_seconds();
         let wait_secs = max(secs_until_next_sync, secs_until_cooldown_ends);
 
         // log the next scheduled sync time
-        let next_sync_at = Utc::now() + TimeDelta::seconds(wait_secs);
+        let next_sync_at = clock::now() + TimeDelta::seconds(wait_secs);
         debug!(
             "Waiting until {:?} ({:?} seconds) for next *scheduled* device sync",
             next_sync_at, wait_secs
@@ -106,6 +160,7 @@ async fn run_impl<F, Fut, SyncerT: SyncerExt>(
             // next scheduled sync
             _ = sleep_fn(Duration::from_secs(wait_secs as u64)) => {
                 let _ = syncer.sync_if_not_in_cooldown().await;
+                jitter_secs = jitter(options.jitter_secs);
             }
 
             // listen for syncer events from the syncer worker (this device)
@@ -120,7 +175,7 @@ async fn run_impl<F, Fut, SyncerT: SyncerExt>(
                     }
                     SyncEvent::SyncSuccess => {
                         let patch = device::Updates {
-                            last_synced_at: Some(Utc::now()),
+                            last_synced_at: Some(clock::now()),
                             ..device::Updates::empty()
                         };
                         let _ = device_stor.patch(patch).await;
@@ -0,0 +1,264 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+// internal crates
+use crate::cooldown;
+use crate::events::model::{Event, CONFIG_CHANGED};
+use crate::supervisor::{health, process, Process};
+
+// external crates
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub app: process::Options,
+    /// How long to wait before restarting the application after it exits, growing with
+    /// each consecutive crash.
+    pub restart_backoff: cooldown::Backoff,
+    /// How long the application must run before an exit no longer counts as
+    /// consecutive to the previous one.
+    pub reset_after: Duration,
+    pub health_check: Option<health::Options>,
+    /// Restarts the application once applying deployments changed config files.
+    pub restart_on_config_change: bool,
+    /// How often the application is checked for having exited.
+    pub poll_interval: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            app: process::Options::default(),
+            restart_backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: 5 * 60,
+            },
+            reset_after: Duration::from_secs(60),
+            health_check: None,
+            restart_on_config_change: true,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub pid: Option<u32>,
+    /// How many times the application was started, including failed attempts.
+    pub starts: u32,
+    /// The application's consecutive crashes and failed starts.
+    pub crashes: u32,
+    pub last_exit: Option<String>,
+}
+
+/// The application's lifecycle: starting it, restarting it after a cooldown once it
+/// exits and restarting it once its health check keeps failing.
+#[derive(Debug)]
+pub struct Supervisor {
+    options: Options,
+    process: Option<Process>,
+    /// When the application is next started. Unset while it runs or once stopped.
+    next_start: Option<Instant>,
+    next_health_check: Option<Instant>,
+    health_failures: u32,
+    starts: u32,
+    crashes: u32,
+    last_exit: Option<String>,
+}
+
+impl Supervisor {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            process: None,
+            next_start: None,
+            next_health_check: None,
+            health_failures: 0,
+            starts: 0,
+            crashes: 0,
+            last_exit: None,
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            pid: self.process.as_ref().map(Process::pid),
+            starts: self.starts,
+            crashes: self.crashes,
+            last_exit: self.last_exit.clone(),
+        }
+    }
+
+    /// Starts the application unless it's running. A failed start is retried after
+    /// the cooldown like a crash.
+    pub fn start(&mut self) {
+        if self.process.is_some() {
+            return;
+        }
+        self.starts += 1;
+        self.health_failures = 0;
+        match Process::start(&self.options.app) {
+            Ok(process) => {
+                self.next_start = None;
+                self.next_health_check = self
+                    .options
+                    .health_check
+                    .as_ref()
+                    .map(|health_check| process.started_at() + health_check.interval);
+                self.process = Some(process);
+            }
+            Err(e) => {
+                error!("failed to start the application: {e}");
+                self.crashed(Instant::now());
+            }
+        }
+    }
+
+    /// Stops the application and doesn't restart it until it's started again.
+    pub async fn stop(&mut self) {
+        self.next_start = None;
+        self.next_health_check = None;
+        let Some(process) = self.process.take() else {
+            return;
+        };
+        let pid = process.pid();
+        info!("stopping the application (process {pid})");
+        if let Some(status) = process.stop(self.options.app.stop_timeout).await {
+            info!("the application (process {pid}) stopped with {status}");
+            self.last_exit = Some(status.to_string());
+        }
+    }
+
+    pub async fn restart(&mut self, reason: &str) {
+        info!("restarting the application: {reason}");
+        self.stop().await;
+        self.start();
+    }
+
+    /// Restarts the application if it exited and its cooldown passed, and runs its
+    /// health check if one is due.
+    pub async fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(process) = self.process.as_mut() {
+            if let Some(status) = process.try_wait() {
+                let ran_for = now - process.started_at();
+                warn!(
+                    "the application (process {}) exited with {status} after {}s",
+                    process.pid(),
+                    ran_for.as_secs()
+                );
+                self.process = None;
+                self.last_exit = Some(status.to_string());
+                if ran_for >= self.options.reset_after {
+                    self.crashes = 0;
+                }
+                self.crashed(now);
+            }
+        }
+        if self.process.is_none() {
+            if self.next_start.is_some_and(|at| at <= now) {
+                self.start();
+            }
+            return;
+        }
+
+        let Some(health_check) = self.options.health_check.clone() else {
+            return;
+        };
+        if self.next_health_check.is_none_or(|at| at > now) {
+            return;
+        }
+        self.next_health_check = Some(now + health_check.interval);
+        let Some(pid) = self.process.as_ref().map(Process::pid) else {
+            return;
+        };
+        match health::check(&health_check, pid).await {
+            Ok(()) => self.health_failures = 0,
+            Err(e) => {
+                self.health_failures += 1;
+                warn!(
+                    "the application failed its health check ({} of {}): {e}",
+                    self.health_failures, health_check.failure_threshold
+                );
+                if self.health_failures >= health_check.failure_threshold.max(1) {
+                    self.stop().await;
+                    self.crashed(Instant::now());
+                }
+            }
+        }
+    }
+
+    fn crashed(&mut self, now: Instant) {
+        let cooldown = cooldown::calc(&self.options.restart_backoff, self.crashes);
+        self.crashes = self.crashes.saturating_add(1);
+        let cooldown = Duration::from_secs(cooldown.max(0) as u64);
+        info!("restarting the application in {}s", cooldown.as_secs());
+        self.next_start = Some(now + cooldown);
+    }
+}
+
+// ================================= WORKER ======================================= //
+/// Supervises the application until shutdown, then stops it.
+pub async fn run<F, Fut>(
+    options: &Options,
+    sleep_fn: F,
+    events: broadcast::Receiver<Event>,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    let mut supervisor = Supervisor::new(options.clone());
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {}
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, &mut supervisor, sleep_fn, events) => {}
+    }
+    supervisor.stop().await;
+    info!("Supervisor worker shutdown complete");
+}
+
+async fn run_impl<F, Fut>(
+    options: &Options,
+    supervisor: &mut Supervisor,
+    sleep_fn: F, // for testing purposes
+    events: broadcast::Receiver<Event>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    info!("Running supervisor worker");
+    let mut events = options.restart_on_config_change.then_some(events);
+    supervisor.start();
+
+    loop {
+        let event = async {
+            match events.as_mut() {
+                Some(events) => events.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = sleep_fn(options.poll_interval) => supervisor.tick().await,
+            event = event => match event {
+                Ok(event) if event.event_type == CONFIG_CHANGED => {
+                    if supervisor.status().pid.is_some() {
+                        supervisor.restart("deployed configs changed").await;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("supervisor worker lagged behind and skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => {
+                    info!("event hub closed, no longer restarting the application on config changes");
+                    events = None;
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// standard crates
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::http;
+#[cfg(feature = "mqtt")]
+use crate::http::telemetry::ReportRequest;
+use crate::journal::{Journal, Request};
+#[cfg(feature = "mqtt")]
+use crate::mqtt::topics;
+#[cfg(feature = "mqtt")]
+use crate::notifications::MqttMessage;
+use crate::notifications::MqttOutbox;
+use crate::storage;
+use crate::telemetry::{Buffer, Sample, Sampler};
+
+// external crates
+use tracing::{debug, error, info, warn};
+
+/// How samples reach the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    #[default]
+    Http,
+    /// Published by the MQTT worker, so samples are only published while it runs.
+    Mqtt,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often the host is sampled and the samples published.
+    pub interval_secs: i64,
+    /// How many unpublished samples are kept while they can't be published. Samples
+    /// published over HTTP are queued in the request journal, which keeps them while
+    /// the backend is unreachable.
+    pub retention: usize,
+    pub transport: Transport,
+    /// The paths whose disks are sampled.
+    pub disk_paths: Vec<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            retention: 60, // an hour at the default interval
+            transport: Transport::default(),
+            disk_paths: Vec::new(),
+        }
+    }
+}
+
+pub struct Deps<'a, HTTPClientT, TokenManagerT> {
+    pub http_client: &'a HTTPClientT,
+    pub token_mngr: &'a TokenManagerT,
+    pub mqtt_outbox: &'a MqttOutbox,
+    pub device_stor: &'a storage::Device,
+    pub journal: &'a Journal,
+}
+
+/// Publishes the samples over the transport. Returns whether they were published;
+/// samples queued in the request journal or handed to the MQTT worker count as
+/// published.
+pub async fn publish<HTTPClientT, TokenManagerT>(
+    transport: Transport,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT>,
+    samples: &[Sample],
+) -> bool
+where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    let device = match deps.device_stor.read().await {
+        Ok(device) => device,
+        Err(e) => {
+            error!("telemetry: failed to read the device: {e}");
+            return false;
+        }
+    };
+    match transport {
+        Transport::Http => {
+            let request = Request::Telemetry {
+                device_id: device.id.clone(),
+                samples: samples.to_vec(),
+            };
+            if let Err(e) = deps.journal.enqueue(request).await {
+                error!("telemetry: failed to queue {} samples: {e}", samples.len());
+                return false;
+            }
+            drain(deps).await;
+            true
+        }
+        Transport::Mqtt => queue(deps.mqtt_outbox, &device.id, samples),
+    }
+}
+
+/// Sends the queued requests, the samples just queued among them. Samples which
+/// can't be sent stay queued for the journal worker.
+async fn drain<HTTPClientT, TokenManagerT>(deps: &Deps<'_, HTTPClientT, TokenManagerT>)
+where
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    let token = match deps.token_mngr.get_token().await {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("telemetry: failed to get token, samples stay queued: {e}");
+            return;
+        }
+    };
+    match deps.journal.drain(deps.http_client, &token.token).await {
+        Ok(drained) if !drained.errors.is_empty() => warn!(
+            "telemetry: {} queued requests failed to send, samples stay queued",
+            drained.errors.len()
+        ),
+        Ok(_) => {}
+        Err(e) => error!("telemetry: failed to drain the journal: {e}"),
+    }
+}
+
+/// Hands the samples to the MQTT worker.
+#[cfg(feature = "mqtt")]
+fn queue(mqtt_outbox: &MqttOutbox, device_id: &str, samples: &[Sample]) -> bool {
+    let payload = match serde_json::to_vec(&ReportRequest { samples }) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("telemetry: failed to serialize samples: {e}");
+            return false;
+        }
+    };
+    let msg = MqttMessage {
+        topic: topics::device_telemetry(device_id),
+        payload,
+    };
+    match mqtt_outbox.try_send(msg) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("telemetry: failed to queue {} samples: {e}", samples.len());
+            false
+        }
+    }
+}
+
+/// Samples can't be published over MQTT without the mqtt feature.
+#[cfg(not(feature = "mqtt"))]
+fn queue(_: &MqttOutbox, _: &str, _: &[Sample]) -> bool {
+    error!("telemetry: the agent was built without mqtt");
+    false
+}
+
+// ================================= WORKER ======================================= //
+pub async fn run<F, Fut, HTTPClientT, TokenManagerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT>,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Telemetry worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, deps, sleep_fn) => {}
+    }
+}
+
+async fn run_impl<F, Fut, HTTPClientT, TokenManagerT>(
+    options: &Options,
+    deps: &Deps<'_, HTTPClientT, TokenManagerT>,
+    sleep_fn: F, // for testing purposes
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    HTTPClientT: http::ClientI,
+    TokenManagerT: TokenManagerExt,
+{
+    info!("Running telemetry worker");
+    let mut sampler = Sampler::new(options.disk_paths.clone());
+    let mut buffer = Buffer::new(options.retention);
+
+    loop {
+        // a full interval passes before the first sample so its CPU usage is measured
+        // over the interval like the rest
+        sleep_fn(Duration::from_secs(options.interval_secs.max(1) as u64)).await;
+
+        if let Some(dropped) = buffer.push(sampler.sample()) {
+            debug!(
+                "telemetry: dropped the unpublished sample from {}",
+                dropped.sampled_at
+            );
+        }
+        if publish(options.transport, deps, buffer.samples()).await {
+            buffer.clear();
+        } else {
+            debug!("telemetry: holding {} unpublished samples", buffer.len());
+        }
+    }
+}
@@ -5,17 +5,23 @@ use std::time::Duration;
 
 // internal crates
 use crate::authn::TokenManagerExt;
+use crate::clock;
 use crate::cooldown;
 use crate::errors::*;
+use crate::events;
 
 // external crates
-use chrono::Utc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone)]
 pub struct TokenRefreshWorkerOptions {
+    /// How long before the token expires it is refreshed.
     pub refresh_advance_secs: i64,
     pub backoff: cooldown::Backoff,
+    /// The longest the worker sleeps before checking the token's expiry again, so the
+    /// token is still refreshed on time after the device was suspended or its clock
+    /// jumped. Sleeps until the refresh is due if `None`.
+    pub watchdog_interval: Option<Duration>,
 }
 
 impl Default for TokenRefreshWorkerOptions {
@@ -27,6 +33,7 @@ impl Default for TokenRefreshWorkerOptions {
                 growth_factor: 2,
                 max_secs: 60 * 60, // 1 hour
             },
+            watchdog_interval: Some(Duration::from_secs(60)),
         }
     }
 }
@@ -34,6 +41,7 @@ impl Default for TokenRefreshWorkerOptions {
 pub async fn run_token_refresh_worker<F, Fut, TokenManagerT: TokenManagerExt>(
     options: &TokenRefreshWorkerOptions,
     token_mngr: &TokenManagerT,
+    event_hub: &events::EventHub,
     sleep_fn: F, // for testing purposes
     mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
 ) where
@@ -42,8 +50,30 @@ pub async fn run_token_refresh_worker<F, Fut, TokenManagerT: TokenManagerExt>(
 {
     info!("Running token refresh worker");
     let mut err_streak = 0;
+    let mut revoked = false;
+    let mut refresh = true;
 
     loop {
+        // the watchdog only checks the token's expiry until the refresh is due
+        if !refresh {
+            warn_if_expired(token_mngr).await;
+            let next_wait = calc_refresh_wait(
+                token_mngr,
+                options.refresh_advance_secs,
+                err_streak,
+                options.backoff,
+            )
+            .await;
+            refresh = tokio::select! {
+                _ = shutdown_signal.as_mut() => {
+                    info!("token refresh worker shutdown complete");
+                    return;
+                }
+                due = watchdog_sleep(options, next_wait, &sleep_fn) => due,
+            };
+            continue;
+        }
+
         // refresh
         let next_wait = match token_mngr.refresh_token().await {
             Ok(_) => {
@@ -55,6 +85,7 @@ pub async fn run_token_refresh_worker<F, Fut, TokenManagerT: TokenManagerExt>(
                     info!("token refreshed successfully");
                 }
                 err_streak = 0;
+                revoked = false;
                 calc_refresh_wait(
                     token_mngr,
                     options.refresh_advance_secs,
@@ -79,6 +110,14 @@ pub async fn run_token_refresh_worker<F, Fut, TokenManagerT: TokenManagerExt>(
                 } else {
                     error!("error refreshing token (error streak: {err_streak}): {e:?}");
                     err_streak += 1;
+                    // only notify once per revocation rather than on every retry
+                    if !revoked && is_auth_revoked(&e) {
+                        revoked = true;
+                        match events::EventArgs::auth_revoked(&e.to_string()) {
+                            Ok(event) => event_hub.try_publish(event).await,
+                            Err(e) => error!("failed to build auth revoked event: {e}"),
+                        }
+                    }
                     calc_refresh_wait(
                         token_mngr,
                         options.refresh_advance_secs,
@@ -90,20 +129,61 @@ pub async fn run_token_refresh_worker<F, Fut, TokenManagerT: TokenManagerExt>(
             }
         };
 
-        let refresh_time = Utc::now() + next_wait;
+        let refresh_time = clock::now() + next_wait;
         debug!("waiting until {:?} to refresh token", refresh_time);
 
         // wait to refresh or shutdown if the signal is received
-        tokio::select! {
+        refresh = tokio::select! {
             _ = shutdown_signal.as_mut() => {
                 info!("token refresh worker shutdown complete");
                 return;
             }
-            _ = sleep_fn(next_wait) => {},
+            due = watchdog_sleep(options, next_wait, &sleep_fn) => due,
+        };
+    }
+}
+
+/// Sleeps until the refresh is due or the watchdog should check the token's expiry
+/// again, whichever comes first. Returns whether the refresh is due.
+async fn watchdog_sleep<F, Fut>(
+    options: &TokenRefreshWorkerOptions,
+    next_wait: Duration,
+    sleep_fn: &F,
+) -> bool
+where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    match options.watchdog_interval {
+        Some(interval) if interval < next_wait => {
+            sleep_fn(interval).await;
+            false
+        }
+        _ => {
+            sleep_fn(next_wait).await;
+            true
         }
     }
 }
 
+async fn warn_if_expired<TokenManagerT: TokenManagerExt>(token_mngr: &TokenManagerT) {
+    if let Ok(token) = token_mngr.get_token().await {
+        if token.is_expired() {
+            warn!(
+                "token expired at {} before it was refreshed",
+                token.expires_at
+            );
+        }
+    }
+}
+
+fn is_auth_revoked(e: &impl Error) -> bool {
+    matches!(
+        e.http_status(),
+        HTTPCode::UNAUTHORIZED | HTTPCode::FORBIDDEN
+    )
+}
+
 pub async fn calc_refresh_wait<TokenManagerT: TokenManagerExt>(
     token_mngr: &TokenManagerT,
     refresh_advance_secs: i64,
@@ -116,7 +196,7 @@ pub async fn calc_refresh_wait<TokenManagerT: TokenManagerExt>(
     match token_mngr.get_token().await {
         Ok(token) => {
             let expiration = token.expires_at;
-            let secs_until_exp = (expiration - Utc::now()).num_seconds();
+            let secs_until_exp = (expiration - clock::now()).num_seconds();
 
             // if the token will expire within our refresh advance period, only wait
             // for the cooldown period before refreshing the token
@@ -0,0 +1,261 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::clock::Clock;
+use crate::filesys;
+use crate::http::{self, agent_updates};
+use crate::sync::SyncerExt;
+use crate::updater::install::{self, InstallParams};
+use crate::updater::{Slot, Slots, UpdateErr};
+use crate::version;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often the backend is asked for a newer release.
+    pub check_interval_secs: i64,
+    /// How long a version on trial has to sync with the backend before it's rolled
+    /// back.
+    pub health_check_secs: i64,
+    /// How often the health check looks for a sync.
+    pub health_poll_secs: i64,
+    /// The public key releases must be signed with.
+    pub release_key: filesys::File,
+    /// The version the agent is running.
+    pub version: String,
+    /// The slot the agent was launched from. The installed binary if unset.
+    pub slot: Option<Slot>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: 6 * 60 * 60, // 6 hours
+            health_check_secs: 10 * 60,       // 10 minutes
+            health_poll_secs: 10,
+            release_key: filesys::File::new("release_key.pem"),
+            version: version::VERSION.to_string(),
+            slot: None,
+        }
+    }
+}
+
+pub struct Deps<'a, TokenManagerT, SyncerT> {
+    pub http_client: &'a http::Client,
+    pub token_mngr: &'a TokenManagerT,
+    pub syncer: &'a SyncerT,
+    pub slots: &'a Slots,
+    /// The skew-corrected clock the syncer's sync times are read from, so the trial's
+    /// times compare with them.
+    pub clock: &'a Clock,
+}
+
+// ================================= HEALTH ======================================= //
+/// Whether the agent synced with the backend after `since`, waiting up to the health
+/// check window for it to. Only a sync by this process counts: the sync state restored
+/// from disk may record a sync by the previous version.
+pub async fn health_check<F, Fut, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, TokenManagerT, SyncerT>,
+    since: DateTime<Utc>,
+    sleep_fn: &F,
+) -> bool
+where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    SyncerT: SyncerExt,
+{
+    let deadline = deps.clock.now() + TimeDelta::seconds(options.health_check_secs);
+    loop {
+        match deps.syncer.get_sync_state().await {
+            Ok(state) if state.syncs > 0 && state.last_synced_at > since => return true,
+            Ok(_) => {}
+            Err(e) => warn!("health check: unable to read the sync state: {e}"),
+        }
+        if deps.clock.now() >= deadline {
+            return false;
+        }
+        sleep_fn(Duration::from_secs(options.health_poll_secs.max(1) as u64)).await;
+    }
+}
+
+/// Keeps the version on trial if it's healthy and rolls it back if not. Returns whether
+/// the agent must restart to run another version.
+async fn settle_trial<F, Fut, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, TokenManagerT, SyncerT>,
+    sleep_fn: &F,
+) -> Result<bool, UpdateErr>
+where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    SyncerT: SyncerExt,
+{
+    let trial = deps.slots.load().await.trial;
+    let Some(trial) = trial.filter(|trial| Some(trial.slot) == options.slot) else {
+        return Ok(false);
+    };
+
+    // the previous version is relaunched as soon as it installs the trial so any
+    // later sync is the new version's
+    info!("agent {} is on trial, checking its health", trial.version);
+    let healthy = health_check(options, deps, trial.installed_at, sleep_fn).await;
+    // reloaded since the launcher counts starts in it
+    let mut state = deps.slots.load().await;
+    if healthy {
+        if let Some(trial) = state.commit() {
+            info!("agent {} passed its health check", trial.version);
+        }
+        deps.slots.save(&state).await?;
+        return Ok(false);
+    }
+    // the storage migrations this version applied are reverted once the agent has
+    // shut down, see [crate::updater::launch::revert_migrations]
+    if let Some(trial) = state.rollback() {
+        error!(
+            "agent {} didn't sync within {}s of starting, rolling it back",
+            trial.version, options.health_check_secs
+        );
+    }
+    deps.slots.save(&state).await?;
+    Ok(true)
+}
+
+// ================================= UPDATE ======================================= //
+/// Installs the backend's latest release if it's newer than the running version and
+/// hasn't been rolled back before. Returns whether one was installed.
+pub async fn check<TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, TokenManagerT, SyncerT>,
+) -> Result<bool, UpdateErr>
+where
+    TokenManagerT: TokenManagerExt,
+{
+    let token = deps.token_mngr.get_token().await?;
+    let release = agent_updates::latest(
+        deps.http_client,
+        agent_updates::LatestParams {
+            version: &options.version,
+            os: version::OS,
+            arch: version::ARCH,
+            token: &token.token,
+        },
+    )
+    .await?;
+    let Some(release) = release.filter(|release| is_newer(&release.version, &options.version))
+    else {
+        return Ok(false);
+    };
+    let state = deps.slots.load().await;
+    if state.rejected.contains(&release.version) {
+        warn!(
+            "agent {} was rolled back before, not installing it again",
+            release.version
+        );
+        return Ok(false);
+    }
+    if state.trial.is_some() {
+        // the version on trial is settled first
+        return Ok(false);
+    }
+
+    install::install(
+        deps.http_client,
+        deps.slots,
+        InstallParams {
+            release: &release,
+            release_key: &options.release_key,
+            token: &token.token,
+            now: deps.clock.now(),
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Whether `candidate` is a strictly newer semantic version than `current`, so that a
+/// stale or compromised backend can't downgrade the agent to an older signed release.
+/// Either may have a leading `v`. A version which doesn't parse is never newer.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse =
+        |version: &str| semver::Version::parse(version.strip_prefix('v').unwrap_or(version));
+    match (parse(candidate), parse(current)) {
+        (Ok(candidate), Ok(current)) => candidate > current,
+        (Err(e), _) => {
+            warn!("ignoring agent release {candidate}, its version is invalid: {e}");
+            false
+        }
+        (_, Err(e)) => {
+            warn!("unable to compare agent versions, {current} is invalid: {e}");
+            false
+        }
+    }
+}
+
+// ================================= WORKER ======================================= //
+/// Settles a version on trial, then periodically installs newer releases. Calls
+/// `restart_fn` when the agent must be relaunched to run another version, after which
+/// the worker stops.
+pub async fn run<F, Fut, R, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, TokenManagerT, SyncerT>,
+    sleep_fn: F,
+    restart_fn: R,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    R: Fn(),
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Updater worker shutdown complete");
+        }
+        restart = run_impl(options, deps, sleep_fn) => {
+            if restart {
+                info!("Restarting to run another agent version");
+                restart_fn();
+            }
+        }
+    }
+}
+
+async fn run_impl<F, Fut, TokenManagerT, SyncerT>(
+    options: &Options,
+    deps: &Deps<'_, TokenManagerT, SyncerT>,
+    sleep_fn: F, // for testing purposes
+) -> bool
+where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+    TokenManagerT: TokenManagerExt,
+    SyncerT: SyncerExt,
+{
+    info!("Running updater worker");
+    match settle_trial(options, deps, &sleep_fn).await {
+        Ok(true) => return true,
+        Ok(false) => {}
+        Err(e) => error!("failed to settle the agent version on trial: {e}"),
+    }
+
+    loop {
+        match check(options, deps).await {
+            Ok(true) => return true,
+            Ok(false) => {}
+            Err(e) => error!("failed to update the agent: {e}"),
+        }
+        sleep_fn(Duration::from_secs(
+            options.check_interval_secs.max(0) as u64
+        ))
+        .await;
+    }
+}
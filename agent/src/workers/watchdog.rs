@@ -0,0 +1,116 @@
+// When the agent's unit sets WatchdogSec=, systemd restarts the agent unless it
+// receives a keep-alive at least that often. A keep-alive is only sent while the
+// syncer and token manager still answer: their actors serve every sync and backend
+// request, so an agent whose actors are wedged (e.g. deadlocked) stops sending them
+// and gets restarted, while one which is merely offline keeps running. The syncer's
+// actor serves nothing else while it syncs, so it isn't checked during a sync: a slow
+// sync (e.g. a large download or a long deploy hook) mustn't get the agent restarted.
+
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::authn::TokenManagerExt;
+use crate::platform::systemd::{self, Notifier};
+use crate::sync::SyncerExt;
+
+// external crates
+use tracing::{error, info};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often a keep-alive is sent, half the watchdog timeout.
+    pub interval: Duration,
+    /// How long each worker has to answer the liveness check.
+    pub check_timeout: Duration,
+}
+
+impl Options {
+    /// The options for a watchdog restarting the agent after `timeout` without a
+    /// keep-alive, which allows for one missed keep-alive.
+    pub fn from_timeout(timeout: Duration) -> Self {
+        Self {
+            interval: timeout / 2,
+            check_timeout: (timeout / 4).min(Duration::from_secs(10)),
+        }
+    }
+}
+
+pub struct Deps<'a, SyncerT: SyncerExt, TokenManagerT: TokenManagerExt> {
+    pub syncer: &'a SyncerT,
+    pub token_mngr: &'a TokenManagerT,
+    pub notifier: &'a Notifier,
+}
+
+/// Checks that the workers answer within the timeout, returning the first one which
+/// didn't. The syncer isn't checked while it syncs.
+pub async fn check_liveness<SyncerT: SyncerExt, TokenManagerT: TokenManagerExt>(
+    deps: &Deps<'_, SyncerT, TokenManagerT>,
+    timeout: Duration,
+) -> Result<(), String> {
+    if !deps.syncer.is_syncing() {
+        match tokio::time::timeout(timeout, deps.syncer.get_sync_state()).await {
+            Ok(Ok(_)) => {}
+            // the sync may have started while the check was queued behind it
+            _ if deps.syncer.is_syncing() => {}
+            Ok(Err(e)) => return Err(format!("the syncer isn't running: {e}")),
+            Err(_) => return Err(format!("the syncer didn't answer within {timeout:?}")),
+        }
+    }
+    match tokio::time::timeout(timeout, deps.token_mngr.get_token()).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => return Err(format!("the token manager isn't running: {e}")),
+        Err(_) => {
+            return Err(format!(
+                "the token manager didn't answer within {timeout:?}"
+            ))
+        }
+    }
+    Ok(())
+}
+
+// ================================= WORKER ======================================= //
+pub async fn run<SyncerT, TokenManagerT, F, Fut>(
+    options: &Options,
+    deps: &Deps<'_, SyncerT, TokenManagerT>,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    SyncerT: SyncerExt,
+    TokenManagerT: TokenManagerExt,
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Watchdog worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, deps, sleep_fn) => {}
+    }
+}
+
+async fn run_impl<SyncerT, TokenManagerT, F, Fut>(
+    options: &Options,
+    deps: &Deps<'_, SyncerT, TokenManagerT>,
+    sleep_fn: F, // for testing purposes
+) where
+    SyncerT: SyncerExt,
+    TokenManagerT: TokenManagerExt,
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    info!(
+        "Running watchdog worker (keep-alive every {:?})",
+        options.interval
+    );
+    loop {
+        match check_liveness(deps, options.check_timeout).await {
+            Ok(()) => deps.notifier.notify(systemd::WATCHDOG),
+            Err(msg) => error!("withholding the watchdog keep-alive since {msg}"),
+        }
+        sleep_fn(options.interval).await;
+    }
+}
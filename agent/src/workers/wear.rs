@@ -0,0 +1,75 @@
+// standard crates
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// internal crates
+use crate::storage::wear::Meter;
+
+// external crates
+use tracing::{error, info};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often the storage wear totals are written to disk.
+    pub interval_secs: i64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval_secs: 5 * 60, // 5 minutes
+        }
+    }
+}
+
+impl Options {
+    /// Flushes the totals far less often to limit the agent's writes in low-wear mode.
+    pub fn low_wear() -> Self {
+        Self {
+            interval_secs: 60 * 60, // 1 hour
+        }
+    }
+}
+
+pub struct Deps<'a> {
+    pub meter: &'a Meter,
+}
+
+// ================================= WORKER ======================================= //
+/// Periodically persists the storage wear totals. The final totals are persisted when
+/// storage shuts down.
+pub async fn run<F, Fut>(
+    options: &Options,
+    deps: &Deps<'_>,
+    sleep_fn: F,
+    mut shutdown_signal: Pin<Box<impl Future<Output = ()> + Send + 'static>>,
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::select! {
+        _ = shutdown_signal.as_mut() => {
+            info!("Storage wear worker shutdown complete");
+        }
+        // doesn't return but we do need to run it in the background
+        _ = run_impl(options, deps, sleep_fn) => {}
+    }
+}
+
+async fn run_impl<F, Fut>(
+    options: &Options,
+    deps: &Deps<'_>,
+    sleep_fn: F, // for testing purposes
+) where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send,
+{
+    info!("Running storage wear worker");
+    loop {
+        sleep_fn(Duration::from_secs(options.interval_secs.max(0) as u64)).await;
+        if let Err(e) = deps.meter.persist().await {
+            error!("failed to persist storage wear totals: {e}");
+        }
+    }
+}
@@ -1,4 +1,8 @@
 pub mod options;
+pub mod replay;
 pub mod run;
+pub mod safe_mode;
+pub mod startup;
 pub mod state;
+pub mod sync_once;
 pub mod upgrade;
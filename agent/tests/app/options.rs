@@ -70,4 +70,9 @@ pub mod app_options_default {
     fn poller_enabled() {
         assert!(AppOptions::default().enable_poller);
     }
+
+    #[test]
+    fn long_poll_disabled() {
+        assert!(!AppOptions::default().enable_long_poll);
+    }
 }
@@ -0,0 +1,91 @@
+// internal crates
+use miru_agent::app::replay::{self, Options};
+use miru_agent::filesys;
+use miru_agent::http::record::{Capture, Exchange, Outcome};
+use miru_agent::storage::Layout;
+
+// external crates
+use serde_json::json;
+
+async fn replay(name: &str, capture: Capture) -> replay::Report {
+    let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+    replay::run(&Layout::new(dir), capture, Options::default())
+        .await
+        .unwrap()
+}
+
+pub mod run {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_requests_missing_from_the_capture() {
+        let report = replay("replay_missing", Capture::default()).await;
+
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.usage.served, 0);
+        assert_eq!(report.usage.misses.len(), 1);
+        assert!(report.usage.misses[0].starts_with("GET /deployments"));
+        assert!(report.deployments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn syncs_against_the_capture() {
+        // learn which request the sync makes from a replay without any responses
+        let missing = replay("replay_learn", Capture::default()).await;
+        let path = missing.usage.misses[0]
+            .strip_prefix("GET ")
+            .unwrap()
+            .to_string();
+
+        let capture = Capture {
+            exchanges: vec![Exchange {
+                method: "GET".to_string(),
+                path,
+                request_body: None,
+                outcome: Outcome::Success {
+                    body: json!({"has_more": false, "data": []}),
+                },
+            }],
+            ..Default::default()
+        };
+        let report = replay("replay_sync", capture).await;
+
+        assert!(report.errors.is_empty());
+        assert_eq!(report.usage.served, 1);
+        assert_eq!(report.usage.unused, 0);
+        assert!(report.usage.misses.is_empty());
+        assert!(report.deployments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn is_deterministic() {
+        let capture = Capture {
+            exchanges: vec![Exchange {
+                method: "GET".to_string(),
+                path: "/unrelated".to_string(),
+                request_body: None,
+                outcome: Outcome::Success { body: json!({}) },
+            }],
+            ..Default::default()
+        };
+        let first = replay("replay_first", capture.clone()).await;
+        let second = replay("replay_second", capture).await;
+
+        assert_eq!(first.errors, second.errors);
+        assert_eq!(first.usage, second.usage);
+        assert_eq!(first.usage.unused, 1);
+    }
+}
+
+pub mod report {
+    use super::*;
+
+    #[tokio::test]
+    async fn render_lists_misses() {
+        let report = replay("replay_render", Capture::default()).await;
+        let rendered = report.render();
+        assert!(rendered.starts_with("Sync failed:\n"));
+        assert!(rendered.contains("no recorded response for GET /deployments"));
+        assert!(rendered.contains("Deployments: 0"));
+    }
+}
@@ -77,6 +77,7 @@ async fn max_runtime_reached() {
         },
         server: Options {
             socket_file: filesys::File::new(PathBuf::from("/tmp").join("miru.sock")),
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -111,6 +112,7 @@ async fn is_persistent() {
         },
         server: Options {
             socket_file: filesys::File::new(PathBuf::from("/tmp").join("miru.sock")),
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -146,6 +148,7 @@ async fn idle_timeout_reached() {
         },
         server: Options {
             socket_file: filesys::File::new(PathBuf::from("/tmp").join("miru.sock")),
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -178,6 +181,7 @@ async fn shutdown_signal_received() {
         },
         server: Options {
             socket_file: filesys::File::new(PathBuf::from("/tmp").join("miru.sock")),
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -0,0 +1,153 @@
+// internal crates
+use miru_agent::app::safe_mode::{self, CrashRecord, Options, Status};
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::testkit;
+
+// external crates
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+fn at(min: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, 12, min, 0).unwrap()
+}
+
+fn options() -> Options {
+    Options {
+        max_crashes: 3,
+        window: TimeDelta::minutes(10),
+    }
+}
+
+async fn read_record(file: &filesys::File) -> CrashRecord {
+    file.read_json::<CrashRecord>().await.unwrap()
+}
+
+pub mod record_start {
+    use super::*;
+
+    #[tokio::test]
+    async fn first_start() {
+        let dir = testkit::temp_dir("safe_mode_first_start").await;
+        let file = dir.file("crash_record.json");
+
+        let status = safe_mode::record_start(&file, &options(), at(0))
+            .await
+            .unwrap();
+        assert_eq!(status, Status::default());
+        assert_eq!(read_record(&file).await.unclean_starts, vec![at(0)]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn enters_safe_mode_after_max_crashes() {
+        let dir = testkit::temp_dir("safe_mode_max_crashes").await;
+        let file = dir.file("crash_record.json");
+
+        // each start without a clean shutdown counts as a crash
+        for min in 0..3 {
+            let status = safe_mode::record_start(&file, &options(), at(min))
+                .await
+                .unwrap();
+            assert!(!status.active);
+            assert_eq!(status.recent_crashes, min);
+        }
+        let status = safe_mode::record_start(&file, &options(), at(3))
+            .await
+            .unwrap();
+        assert_eq!(
+            status,
+            Status {
+                active: true,
+                recent_crashes: 3,
+            }
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ignores_crashes_outside_window() {
+        let dir = testkit::temp_dir("safe_mode_window").await;
+        let file = dir.file("crash_record.json");
+        let record = CrashRecord {
+            unclean_starts: vec![at(0), at(1), at(2)],
+        };
+        file.write_json(&record, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        // the first two crashes fall out of the window
+        let status = safe_mode::record_start(&file, &options(), at(11))
+            .await
+            .unwrap();
+        assert_eq!(
+            status,
+            Status {
+                active: false,
+                recent_crashes: 1,
+            }
+        );
+        assert_eq!(read_record(&file).await.unclean_starts, vec![at(2), at(11)]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn disabled_when_max_crashes_is_zero() {
+        let dir = testkit::temp_dir("safe_mode_disabled").await;
+        let file = dir.file("crash_record.json");
+        let options = Options {
+            max_crashes: 0,
+            ..options()
+        };
+        for min in 0..5 {
+            let status = safe_mode::record_start(&file, &options, at(min))
+                .await
+                .unwrap();
+            assert!(!status.active);
+        }
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resets_corrupt_record() {
+        let dir = testkit::temp_dir("safe_mode_corrupt").await;
+        let file = dir.file("crash_record.json");
+        file.write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let status = safe_mode::record_start(&file, &options(), at(0))
+            .await
+            .unwrap();
+        assert_eq!(status, Status::default());
+        assert_eq!(read_record(&file).await.unclean_starts, vec![at(0)]);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod record_clean_shutdown {
+    use super::*;
+
+    #[tokio::test]
+    async fn clears_record() {
+        let dir = testkit::temp_dir("safe_mode_clean_shutdown").await;
+        let file = dir.file("crash_record.json");
+        for min in 0..3 {
+            safe_mode::record_start(&file, &options(), at(min))
+                .await
+                .unwrap();
+        }
+
+        safe_mode::record_clean_shutdown(&file).await.unwrap();
+        assert!(!file.exists());
+
+        // no previous starts count as crashes after a clean shutdown
+        let status = safe_mode::record_start(&file, &options(), at(4))
+            .await
+            .unwrap();
+        assert_eq!(status.recent_crashes, 0);
+
+        // clearing a missing record is a no-op
+        safe_mode::record_clean_shutdown(&file).await.unwrap();
+        safe_mode::record_clean_shutdown(&file).await.unwrap();
+        dir.delete().await.unwrap();
+    }
+}
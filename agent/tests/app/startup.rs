@@ -0,0 +1,375 @@
+// standard crates
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+// internal crates
+use miru_agent::app::options::AppOptions;
+use miru_agent::app::safe_mode;
+use miru_agent::app::startup::{self, Component, Options, Outcome, Startup, Status};
+use miru_agent::server::errors::{ServerErr, ShutdownMngrDuplicateArgErr};
+
+fn all() -> BTreeSet<Component> {
+    Component::ALL.into_iter().collect()
+}
+
+fn init_err() -> ServerErr {
+    ServerErr::ShutdownMngrDuplicateArgErr(ShutdownMngrDuplicateArgErr {
+        arg_name: "poller_handle".to_string(),
+        trace: miru_agent::trace!(),
+    })
+}
+
+pub mod stages {
+    use super::*;
+
+    #[test]
+    fn all_components() {
+        use Component::*;
+        assert_eq!(
+            startup::stages(&all()),
+            vec![
+                vec![AppState, MetricsListener, CellProxy],
+                vec![TokenRefresh, SocketServer, Wear, Drift, Supervisor],
+                vec![
+                    Journal,
+                    Poller,
+                    LongPoll,
+                    Mqtt,
+                    CacheAudit,
+                    Updater,
+                    CrashReports
+                ],
+                vec![Notifications, Telemetry],
+            ]
+        );
+    }
+
+    #[test]
+    fn dependencies_precede_dependents() {
+        // drop each component in turn to cover the graph with missing dependencies
+        let mut subsets = vec![all()];
+        for component in Component::ALL {
+            let mut subset = all();
+            subset.remove(&component);
+            subsets.push(subset);
+        }
+
+        for enabled in subsets {
+            let stages = startup::stages(&enabled);
+            let staged: Vec<Component> = stages.iter().flatten().copied().collect();
+            assert_eq!(staged.iter().copied().collect::<BTreeSet<_>>(), enabled);
+            for (i, stage) in stages.iter().enumerate() {
+                let earlier: BTreeSet<Component> = stages[..i].iter().flatten().copied().collect();
+                for component in stage {
+                    for dep in component.dependencies() {
+                        assert!(
+                            !enabled.contains(dep) || earlier.contains(dep),
+                            "{component} is staged before {dep}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn disabled_dependencies_are_ignored() {
+        use Component::*;
+        let enabled = BTreeSet::from([AppState, TokenRefresh, Notifications]);
+        assert_eq!(
+            startup::stages(&enabled),
+            vec![vec![AppState], vec![TokenRefresh, Notifications]]
+        );
+    }
+}
+
+pub mod enabled {
+    use super::*;
+
+    #[test]
+    fn default_options() {
+        let enabled = startup::enabled(&AppOptions::default());
+        for component in [
+            Component::AppState,
+            Component::TokenRefresh,
+            Component::SocketServer,
+            Component::Journal,
+            Component::Wear,
+            Component::Poller,
+            Component::Mqtt,
+            Component::CacheAudit,
+            Component::Drift,
+            Component::CrashReports,
+        ] {
+            assert!(enabled.contains(&component), "{component} isn't enabled");
+        }
+        for component in [
+            Component::MetricsListener,
+            Component::CellProxy,
+            Component::LongPoll,
+            Component::Notifications,
+            Component::Telemetry,
+            Component::Updater,
+            Component::Supervisor,
+        ] {
+            assert!(!enabled.contains(&component), "{component} is enabled");
+        }
+    }
+
+    #[test]
+    fn safe_mode_disables_nonessential_workers() {
+        let options = AppOptions {
+            safe_mode: safe_mode::Status {
+                active: true,
+                ..Default::default()
+            },
+            enable_long_poll: true,
+            enable_telemetry: true,
+            ..Default::default()
+        };
+        let enabled = startup::enabled(&options);
+        assert!(!enabled.contains(&Component::Poller));
+        assert!(!enabled.contains(&Component::LongPoll));
+        assert!(!enabled.contains(&Component::CacheAudit));
+        assert!(!enabled.contains(&Component::Drift));
+        assert!(!enabled.contains(&Component::Telemetry));
+        assert!(enabled.contains(&Component::Journal));
+        assert!(enabled.contains(&Component::TokenRefresh));
+    }
+
+    #[test]
+    fn safe_mode_keeps_the_updater() {
+        let options = AppOptions {
+            safe_mode: safe_mode::Status {
+                active: true,
+                ..Default::default()
+            },
+            enable_updater: true,
+            ..Default::default()
+        };
+        assert!(startup::enabled(&options).contains(&Component::Updater));
+    }
+
+    #[test]
+    fn safe_mode_keeps_the_supervisor() {
+        let options = AppOptions {
+            safe_mode: safe_mode::Status {
+                active: true,
+                ..Default::default()
+            },
+            enable_supervisor: true,
+            ..Default::default()
+        };
+        assert!(startup::enabled(&options).contains(&Component::Supervisor));
+    }
+
+    #[test]
+    fn safe_mode_keeps_crash_reports() {
+        let options = AppOptions {
+            safe_mode: safe_mode::Status {
+                active: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(startup::enabled(&options).contains(&Component::CrashReports));
+
+        let options = AppOptions {
+            enable_crash_reports: false,
+            ..Default::default()
+        };
+        assert!(!startup::enabled(&options).contains(&Component::CrashReports));
+    }
+}
+
+pub mod cell {
+    use super::*;
+    use miru_agent::cell::{Options, Peers, Upstream};
+
+    #[test]
+    fn controller_serves_the_proxy() {
+        let options = AppOptions {
+            cell: Options::Controller {
+                listen_addr: "10.0.0.1:8443".to_string(),
+                peers: Peers::default(),
+            },
+            ..Default::default()
+        };
+        let enabled = startup::enabled(&options);
+        assert!(enabled.contains(&Component::CellProxy));
+        assert!(enabled.contains(&Component::Mqtt));
+    }
+
+    #[test]
+    fn peer_skips_mqtt() {
+        let options = AppOptions {
+            cell: Options::Peer(Upstream {
+                url: "http://10.0.0.1:8443".to_string(),
+                peer_id: "robot-1".to_string(),
+                token: "token-1".to_string(),
+            }),
+            ..Default::default()
+        };
+        let enabled = startup::enabled(&options);
+        assert!(!enabled.contains(&Component::Mqtt));
+        assert!(!enabled.contains(&Component::CellProxy));
+        assert!(enabled.contains(&Component::Poller));
+    }
+}
+
+pub mod start {
+    use super::*;
+
+    fn startup(enabled: &[Component]) -> Startup {
+        let options = Options {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        Startup::new(options, enabled.iter().copied().collect())
+    }
+
+    #[tokio::test]
+    async fn ready() {
+        let mut startup = startup(&[Component::AppState]);
+        let output = startup.start(Component::AppState, async { Ok(7) }).await;
+        assert_eq!(output, Some(7));
+        assert!(startup.is_ready(Component::AppState));
+        assert_eq!(startup.outcomes()[0].status, Status::Ready);
+        startup.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed() {
+        let mut startup = startup(&[Component::AppState]);
+        let output = startup
+            .start(Component::AppState, async { Err::<(), _>(init_err()) })
+            .await;
+        assert_eq!(output, None);
+        assert!(!startup.is_ready(Component::AppState));
+        assert!(matches!(startup.outcomes()[0].status, Status::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn timed_out() {
+        let mut startup = startup(&[Component::AppState]);
+        let output = startup
+            .start(
+                Component::AppState,
+                std::future::pending::<Result<(), ServerErr>>(),
+            )
+            .await;
+        assert_eq!(output, None);
+        assert_eq!(
+            startup.outcomes()[0].status,
+            Status::TimedOut(Duration::from_millis(50))
+        );
+    }
+
+    #[tokio::test]
+    async fn per_component_timeouts() {
+        let options = Options {
+            timeout: Duration::from_secs(60),
+            timeouts: [(Component::TokenRefresh, Duration::from_millis(10))].into(),
+        };
+        let mut startup = Startup::new(options, BTreeSet::from([Component::TokenRefresh]));
+        startup
+            .start(
+                Component::TokenRefresh,
+                std::future::pending::<Result<(), ServerErr>>(),
+            )
+            .await;
+        assert_eq!(
+            startup.outcomes()[0].status,
+            Status::TimedOut(Duration::from_millis(10))
+        );
+    }
+
+    #[tokio::test]
+    async fn dependents_of_failures_are_skipped() {
+        use Component::*;
+        let mut startup = startup(&[AppState, TokenRefresh, Poller, Wear]);
+        startup.start(AppState, async { Ok(()) }).await;
+        startup
+            .start(TokenRefresh, async { Err::<(), _>(init_err()) })
+            .await;
+
+        let mut started = false;
+        let output = startup
+            .start(Poller, async {
+                started = true;
+                Ok(())
+            })
+            .await;
+        assert_eq!(output, None);
+        assert!(!started);
+        assert_eq!(startup.outcomes()[2].status, Status::Skipped(TokenRefresh));
+
+        // components which don't depend on the failure still start
+        assert_eq!(startup.start(Wear, async { Ok(()) }).await, Some(()));
+    }
+
+    #[tokio::test]
+    async fn disabled_dependencies_dont_block() {
+        let mut startup = startup(&[Component::Notifications]);
+        let output = startup
+            .start(Component::Notifications, async { Ok(()) })
+            .await;
+        assert_eq!(output, Some(()));
+    }
+}
+
+pub mod finish {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_every_failure() {
+        use Component::*;
+        let options = Options {
+            timeout: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let mut startup = Startup::new(
+            options,
+            BTreeSet::from([AppState, TokenRefresh, Poller, Wear]),
+        );
+        startup.start(AppState, async { Ok(()) }).await;
+        startup
+            .start(
+                TokenRefresh,
+                std::future::pending::<Result<(), ServerErr>>(),
+            )
+            .await;
+        startup.start(Poller, async { Ok(()) }).await;
+        startup
+            .start(Wear, async { Err::<(), _>(init_err()) })
+            .await;
+
+        let ServerErr::StartupFailed(err) = startup.finish().unwrap_err() else {
+            panic!("expected a startup failure");
+        };
+        let failures: Vec<(Component, &Status)> = err
+            .failures
+            .iter()
+            .map(
+                |Outcome {
+                     component, status, ..
+                 }| (*component, status),
+            )
+            .collect();
+        assert_eq!(failures.len(), 3);
+        assert_eq!(
+            failures[0],
+            (TokenRefresh, &Status::TimedOut(Duration::from_millis(10)))
+        );
+        assert_eq!(failures[1], (Poller, &Status::Skipped(TokenRefresh)));
+        assert_eq!(failures[2].0, Wear);
+
+        let msg = err.to_string();
+        assert!(msg.contains("token_refresh timed out after 10ms"), "{msg}");
+        assert!(
+            msg.contains("poller skipped since token_refresh isn't ready"),
+            "{msg}"
+        );
+        assert!(msg.contains("wear failed: "), "{msg}");
+    }
+}
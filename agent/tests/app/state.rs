@@ -5,7 +5,8 @@ use std::sync::Arc;
 // internal crates
 use miru_agent::app::state::AppState;
 use miru_agent::authn::Token;
-use miru_agent::deploy::fsm;
+use miru_agent::deploy::apply;
+use miru_agent::events::hub::SpawnOptions;
 use miru_agent::filesys::{self, FileSysErr, WriteOptions};
 use miru_agent::http;
 use miru_agent::logs;
@@ -25,9 +26,11 @@ pub mod init {
         let layout = Layout::new(dir);
         let result = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await;
         match result {
@@ -56,9 +59,11 @@ pub mod init {
 
         let result = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await;
         match result {
@@ -93,9 +98,11 @@ pub mod init {
 
         let result = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await;
         assert!(matches!(
@@ -137,9 +144,11 @@ pub mod init {
 
         let (state, _) = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await
         .unwrap();
@@ -190,9 +199,11 @@ pub mod init {
 
         let (state, _) = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await
         .unwrap();
@@ -241,9 +252,11 @@ pub mod init {
 
         let _ = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await
         .unwrap();
@@ -287,9 +300,11 @@ pub mod shutdown {
 
         let (state, state_handle) = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await
         .unwrap();
@@ -333,9 +348,11 @@ pub mod shutdown {
         let before_shutdown = Utc::now();
         let (state, state_handle) = AppState::init(
             &layout,
+            layout.auth().private_key().into(),
             Capacities::default(),
             Arc::new(http::Client::new("doesntmatter").unwrap()),
-            fsm::RetryPolicy::default(),
+            apply::DeployOpts::default(),
+            SpawnOptions::default(),
         )
         .await
         .unwrap();
@@ -0,0 +1,95 @@
+// standard crates
+use std::path::PathBuf;
+
+// internal crates
+use miru_agent::app::options::{AppOptions, StorageOptions};
+use miru_agent::app::sync_once::{run, Outcome, Report};
+use miru_agent::filesys::{self, WriteOptions};
+use miru_agent::models::Device;
+use miru_agent::network::BackendUrl;
+use miru_agent::server::Options;
+use miru_agent::storage::Layout;
+
+// external crates
+use serial_test::serial;
+
+async fn prepare_valid_server_storage(dir: filesys::Dir) {
+    let layout = Layout::new(dir);
+    layout
+        .auth()
+        .private_key()
+        .write_string("test", WriteOptions::default())
+        .await
+        .unwrap();
+    layout
+        .auth()
+        .public_key()
+        .write_string("test", WriteOptions::default())
+        .await
+        .unwrap();
+    layout
+        .device()
+        .write_json(&Device::default(), WriteOptions::default())
+        .await
+        .unwrap();
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(Outcome::Success.exit_code(), 0);
+    assert_eq!(Outcome::Cooldown.exit_code(), 75);
+    assert_eq!(Outcome::Failure.exit_code(), 1);
+}
+
+#[test]
+fn failed_report() {
+    let report = Report::failed("not activated".to_string());
+    assert_eq!(report.outcome, Outcome::Failure);
+    assert_eq!(report.errors, vec!["not activated".to_string()]);
+    assert_eq!(report.next_sync_at, None);
+    assert!(report.deployments.is_empty());
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["outcome"], "failure");
+    assert_eq!(json["errors"][0], "not activated");
+}
+
+#[tokio::test]
+async fn uninitialized_storage_fails() {
+    let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+    let options = AppOptions {
+        storage: StorageOptions {
+            layout: Layout::new(dir),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let report = run(&options).await;
+    assert_eq!(report.outcome, Outcome::Failure);
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.deployments.is_empty());
+}
+
+#[serial]
+#[tokio::test]
+async fn unreachable_backend_fails() {
+    let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+    prepare_valid_server_storage(dir.clone()).await;
+    let options = AppOptions {
+        storage: StorageOptions {
+            layout: Layout::new(dir),
+            ..Default::default()
+        },
+        server: Options {
+            socket_file: filesys::File::new(PathBuf::from("/tmp").join("miru.sock")),
+            ..Default::default()
+        },
+        backend_base_url: BackendUrl::new("http://127.0.0.1:1").unwrap(),
+        ..Default::default()
+    };
+    let report = run(&options).await;
+    assert_eq!(report.outcome, Outcome::Failure);
+    assert!(!report.errors.is_empty());
+    assert!(report.next_sync_at.is_some());
+    assert!(report.deployments.is_empty());
+}
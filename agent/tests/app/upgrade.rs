@@ -5,16 +5,24 @@ use std::time::Duration as StdDuration;
 // internal crates
 use crate::mocks::http_client::{Call, MockClient};
 use backend_api::models as backend_client;
-use miru_agent::app::upgrade::{needs_upgrade, reconcile, reconcile_impl};
+use miru_agent::app::upgrade::{
+    drops_release_signing, needs_upgrade, reconcile, reconcile_impl, Trigger,
+};
 use miru_agent::app::UpgradeErr;
 use miru_agent::crypt::rsa;
 use miru_agent::filesys::{self, Overwrite, PathExt};
 use miru_agent::http::errors::{HTTPErr, MockErr as HTTPMockErr};
+use miru_agent::logs::LogLevel;
 use miru_agent::models::Device;
-use miru_agent::storage::{self, Layout};
+use miru_agent::storage::migrations::{self, Migration, Step};
+use miru_agent::storage::{self, KeyBackend, Layout, ReleaseSigning, Settings, TrustedReleaseKey};
+use miru_agent::updater::launch::{self, SLOT_ENV};
+use miru_agent::updater::{Slot, Slots, State};
 
 // external crates
 use chrono::{Duration, Utc};
+use serde_json::json;
+use serial_test::serial;
 
 // ============================ TEST HARNESS ============================ //
 
@@ -94,9 +102,17 @@ mod reconcile {
             .unwrap();
 
         let mock = make_mock_client(backend_device("dvc_1", "alpha"));
-        let outcome = reconcile(&layout, mock.as_ref(), "v1.0.0", no_sleep)
-            .await
-            .unwrap();
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v1.0.0",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
 
         assert!(!outcome.upgraded);
         assert_eq!(outcome.attempts, 0);
@@ -113,9 +129,17 @@ mod reconcile {
         let (priv_before, pub_before) = read_keys(&layout).await;
 
         let mock = make_mock_client(backend_device("dvc_2", "beta"));
-        let outcome = reconcile(&layout, mock.as_ref(), "v0.9.0", no_sleep)
-            .await
-            .unwrap();
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.9.0",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
 
         assert!(outcome.upgraded);
         assert_eq!(outcome.attempts, 0);
@@ -150,9 +174,17 @@ mod reconcile {
             .unwrap();
 
         let mock = make_mock_client(backend_device("dvc_3", "gamma"));
-        let outcome = reconcile(&layout, mock.as_ref(), "v0.0.2", no_sleep)
-            .await
-            .unwrap();
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.0.2",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
 
         assert!(outcome.upgraded);
         assert_eq!(outcome.attempts, 0);
@@ -164,6 +196,201 @@ mod reconcile {
         assert_eq!(mock.num_update_device_calls(), 1);
     }
 
+    #[tokio::test]
+    async fn keeps_the_settings() {
+        let (layout, _dir) = prepare_layout("upgrade_keeps_settings").await;
+        storage::agent_version::write(&layout.agent_version(), "v0.0.1")
+            .await
+            .unwrap();
+        layout
+            .settings()
+            .write_json(
+                &json!({
+                    "key_storage": {"backend": "tpm", "tpm_handle": "0x81000002"},
+                    "self_update": {
+                        "enabled": true,
+                        "release_key_file": "/etc/miru/release_key.pub",
+                    },
+                    "release_signing": {
+                        "required": true,
+                        "trusted_keys": [{"key_id": "release-2026", "public_key": "pem"}],
+                    },
+                    "hooks": {"pre_deploy": ["/usr/bin/true"]},
+                    "deployment_dir": {"path": "/srv/app", "keep": 5},
+                    "cell": {"role": "peer", "peer_id": "peer-1"},
+                }),
+                filesys::WriteOptions::OVERWRITE_ATOMIC,
+            )
+            .await
+            .unwrap();
+        let before = layout.settings().read_json::<Settings>().await.unwrap();
+        assert_eq!(before.key_storage.backend, KeyBackend::Tpm);
+
+        let mock = make_mock_client(backend_device("dvc_ks", "kept"));
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.0.2",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
+
+        assert!(outcome.upgraded);
+        let after = layout.settings().read_json::<Settings>().await.unwrap();
+        assert_eq!(after, before);
+    }
+
+    #[tokio::test]
+    async fn resets_unreadable_settings_to_the_defaults() {
+        let (layout, _dir) = prepare_layout("upgrade_unreadable_settings").await;
+        layout
+            .settings()
+            .write_string("{not json", filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let mock = make_mock_client(backend_device("dvc_us", "unreadable"));
+        reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.0.2",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
+
+        let after = layout.settings().read_json::<Settings>().await.unwrap();
+        assert_eq!(after, Settings::default());
+    }
+
+    #[tokio::test]
+    #[serial(slot_env)]
+    async fn keeps_the_state_when_a_trial_slot_starts() {
+        let (layout, _dir) = prepare_layout("upgrade_trial_slot").await;
+        storage::agent_version::write(&layout.agent_version(), "v0.1.0")
+            .await
+            .unwrap();
+        let settings = json!({"key_storage": {"backend": "tpm", "tpm_handle": "0x81000002"}});
+        let device = json!({"id": "dvc_trial", "name": "trial"});
+        let token = json!({"token": "jwt", "expires_at": "2099-01-01T00:00:00Z"});
+        let write = filesys::WriteOptions::OVERWRITE_ATOMIC;
+        layout
+            .settings()
+            .write_json(&settings, write)
+            .await
+            .unwrap();
+        layout.device().write_json(&device, write).await.unwrap();
+        layout
+            .auth()
+            .token()
+            .write_json(&token, write)
+            .await
+            .unwrap();
+        let resource = layout.resources().file("deployments.json");
+        resource.write_string("{}", write).await.unwrap();
+        let event = layout.events_dir().file("events.jsonl");
+        event.write_string("{}\n", write).await.unwrap();
+
+        // the self-updater installed v0.2.0 in slot A and relaunched into it
+        let slots = Slots::new(layout.updates_dir());
+        let mut state = State::default();
+        state.begin_trial(Slot::A, "v0.2.0", Utc::now());
+        slots.save(&state).await.unwrap();
+        // SAFETY: `#[serial(slot_env)]` excludes the other tests which read the slot
+        // the agent was launched from
+        unsafe { std::env::set_var(SLOT_ENV, "a") };
+        let slot = launch::launch(&slots, &layout).await;
+        unsafe { std::env::remove_var(SLOT_ENV) };
+        let slot = slot.unwrap();
+        assert_eq!(slot, Some(Slot::A));
+        assert!(launch::is_updater_driven(&slots, &layout, slot).await);
+
+        let mock = make_mock_client(backend_device("dvc_other", "other"));
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.2.0",
+            Trigger::Updater,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
+
+        assert!(outcome.upgraded);
+        let marker = storage::agent_version::read(&layout.agent_version())
+            .await
+            .unwrap();
+        assert_eq!(marker, Some("v0.2.0".to_string()));
+        let read = |file: filesys::File| async move {
+            file.read_json::<serde_json::Value>().await.unwrap()
+        };
+        assert_eq!(read(layout.settings()).await, settings);
+        assert_eq!(read(layout.device()).await, device);
+        assert_eq!(read(layout.auth().token()).await, token);
+        assert!(resource.exists());
+        assert!(event.exists());
+        assert_eq!(mock.call_count(Call::IssueDeviceToken), 0);
+        assert_eq!(mock.num_get_device_calls(), 0);
+        assert_eq!(mock.num_update_device_calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_migrations_of_the_upgrade() {
+        const RENAME_LOG_LEVEL: Migration = Migration {
+            id: "0001_rename_log_level",
+            description: "rename the settings' log level",
+            steps: &[Step::RenameField {
+                file: Layout::settings,
+                from: "level",
+                to: "log_level",
+            }],
+        };
+
+        let (layout, _dir) = prepare_layout("upgrade_keeps_migrations").await;
+        storage::agent_version::write(&layout.agent_version(), "v0.0.1")
+            .await
+            .unwrap();
+        layout
+            .settings()
+            .write_json(
+                &json!({"level": "debug"}),
+                filesys::WriteOptions::OVERWRITE_ATOMIC,
+            )
+            .await
+            .unwrap();
+
+        let mock = make_mock_client(backend_device("dvc_mg", "migrated"));
+        reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.0.2",
+            Trigger::Package,
+            &[RENAME_LOG_LEVEL],
+            no_sleep,
+        )
+        .await
+        .unwrap();
+
+        let settings = layout.settings().read_json::<Settings>().await.unwrap();
+        assert_eq!(settings.log_level, LogLevel::Debug);
+        let applied = migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].id, "0001_rename_log_level");
+        assert_eq!(applied[0].agent_version, "v0.0.2");
+    }
+
     #[tokio::test]
     async fn retries_until_get_device_succeeds() {
         let (layout, _dir) = prepare_layout("upgrade_retry").await;
@@ -187,9 +414,17 @@ mod reconcile {
             }
         });
 
-        let outcome = reconcile(&layout, mock.as_ref(), "v1.2.3", no_sleep)
-            .await
-            .unwrap();
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v1.2.3",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
 
         assert!(outcome.upgraded);
         assert_eq!(outcome.attempts, 2);
@@ -226,9 +461,17 @@ mod reconcile {
             }
         });
 
-        let outcome = reconcile(&layout, mock.as_ref(), "v9.9.9", no_sleep)
-            .await
-            .unwrap();
+        let outcome = reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v9.9.9",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
 
         assert!(outcome.upgraded);
         assert_eq!(outcome.attempts, 4);
@@ -236,6 +479,94 @@ mod reconcile {
     }
 }
 
+mod drops_release_signing {
+    use super::*;
+
+    fn signing(required: bool, trusted: bool) -> Settings {
+        let trusted_keys = match trusted {
+            true => vec![TrustedReleaseKey {
+                key_id: "release-2026".to_string(),
+                public_key: "pem".to_string(),
+            }],
+            false => Vec::new(),
+        };
+        Settings {
+            release_signing: ReleaseSigning {
+                required,
+                trusted_keys,
+            },
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn true_when_verification_is_turned_off() {
+        assert!(drops_release_signing(
+            &signing(true, true),
+            &Settings::default()
+        ));
+        assert!(drops_release_signing(
+            &signing(false, true),
+            &Settings::default()
+        ));
+        assert!(drops_release_signing(
+            &signing(true, false),
+            &Settings::default()
+        ));
+    }
+
+    #[test]
+    fn false_when_verification_is_kept_or_was_off() {
+        assert!(!drops_release_signing(
+            &signing(true, true),
+            &signing(true, true)
+        ));
+        assert!(!drops_release_signing(
+            &signing(true, true),
+            &signing(false, true)
+        ));
+        assert!(!drops_release_signing(
+            &Settings::default(),
+            &Settings::default()
+        ));
+        assert!(!drops_release_signing(
+            &Settings::default(),
+            &signing(true, true)
+        ));
+    }
+
+    #[tokio::test]
+    async fn upgrade_keeps_release_signing() {
+        let (layout, _dir) = prepare_layout("upgrade_keeps_release_signing").await;
+        storage::agent_version::write(&layout.agent_version(), "v0.0.1")
+            .await
+            .unwrap();
+        let before = signing(true, true);
+        layout
+            .settings()
+            .write_json(&before, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let mock = make_mock_client(backend_device("dvc_rs", "signed"));
+        reconcile(
+            &layout,
+            &layout.auth().private_key().into(),
+            mock.as_ref(),
+            "v0.0.2",
+            Trigger::Package,
+            migrations::MIGRATIONS,
+            no_sleep,
+        )
+        .await
+        .unwrap();
+
+        let after = layout.settings().read_json::<Settings>().await.unwrap();
+        assert!(!drops_release_signing(&before, &after));
+        assert_eq!(after.release_signing, before.release_signing);
+    }
+}
+
 mod needs_upgrade {
     use super::*;
 
@@ -285,9 +616,14 @@ mod reconcile_impl {
         let mock = make_mock_client(backend_device("dvc_ri1", "happy"));
 
         let version = "v3.4.5";
-        reconcile_impl(mock.as_ref(), &layout, version)
-            .await
-            .unwrap();
+        reconcile_impl(
+            mock.as_ref(),
+            &layout,
+            &layout.auth().private_key().into(),
+            version,
+        )
+        .await
+        .unwrap();
 
         let marker = storage::agent_version::read(&layout.agent_version())
             .await
@@ -306,9 +642,14 @@ mod reconcile_impl {
             .unwrap();
 
         let mock = make_mock_client(backend_device("dvc_ri2", "no_pk"));
-        let err = reconcile_impl(mock.as_ref(), &layout, "v1.0.0")
-            .await
-            .expect_err("expected AuthnErr from missing private key");
+        let err = reconcile_impl(
+            mock.as_ref(),
+            &layout,
+            &layout.auth().private_key().into(),
+            "v1.0.0",
+        )
+        .await
+        .expect_err("expected AuthnErr from missing private key");
         match err {
             UpgradeErr::AuthnErr(_) => {}
             other => panic!("expected UpgradeErr::AuthnErr, got {other:?}"),
@@ -325,9 +666,14 @@ mod reconcile_impl {
             }))
         });
 
-        let err = reconcile_impl(mock.as_ref(), &layout, "v1.0.0")
-            .await
-            .expect_err("expected HTTPErr from get_device failure");
+        let err = reconcile_impl(
+            mock.as_ref(),
+            &layout,
+            &layout.auth().private_key().into(),
+            "v1.0.0",
+        )
+        .await
+        .expect_err("expected HTTPErr from get_device failure");
         match err {
             UpgradeErr::HTTPErr(_) => {}
             other => panic!("expected UpgradeErr::HTTPErr, got {other:?}"),
@@ -345,9 +691,14 @@ mod reconcile_impl {
             .unwrap();
 
         let mock = make_mock_client(backend_device("dvc_ri4", "reset_fail"));
-        let err = reconcile_impl(mock.as_ref(), &layout, "v1.0.0")
-            .await
-            .expect_err("expected StorageErr from reset failure");
+        let err = reconcile_impl(
+            mock.as_ref(),
+            &layout,
+            &layout.auth().private_key().into(),
+            "v1.0.0",
+        )
+        .await
+        .expect_err("expected StorageErr from reset failure");
         match err {
             UpgradeErr::StorageErr(_) => {}
             other => panic!("expected UpgradeErr::StorageErr, got {other:?}"),
@@ -365,9 +716,14 @@ mod reconcile_impl {
         });
 
         let version = "v7.8.9";
-        let err = reconcile_impl(mock.as_ref(), &layout, version)
-            .await
-            .expect_err("expected HTTPErr from update_device failure");
+        let err = reconcile_impl(
+            mock.as_ref(),
+            &layout,
+            &layout.auth().private_key().into(),
+            version,
+        )
+        .await
+        .expect_err("expected HTTPErr from update_device failure");
         match err {
             UpgradeErr::HTTPErr(_) => {}
             other => panic!("expected UpgradeErr::HTTPErr, got {other:?}"),
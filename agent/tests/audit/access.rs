@@ -0,0 +1,436 @@
+// internal crates
+use miru_agent::audit::{Access, AccessLog, Anchor, AnchorStatus, Client, Options, Query};
+use miru_agent::filesys::{self, PathExt};
+
+// external crates
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 15, 12, 0, 0).unwrap()
+}
+
+fn client(uid: u32) -> Client {
+    Client {
+        uid: Some(uid),
+        gid: Some(uid),
+        pid: Some(100 + uid as i32),
+        addr: None,
+    }
+}
+
+async fn setup(name: &str, options: Options) -> (filesys::Dir, filesys::File, AccessLog) {
+    let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+    let file = dir.file("config_access.json");
+    let log = AccessLog::init(file.clone(), options).await;
+    (dir, file, log)
+}
+
+pub mod record {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_each_client_and_config_instance() {
+        let (dir, _, log) = setup("audit_record", Options::default()).await;
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(&client(2), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(&client(1), "ci-2", "/srv/b.json", t0()).await;
+
+        let accesses = log.query(&Query::default()).await;
+        assert_eq!(accesses.len(), 3);
+        assert!(accesses.iter().all(|a| a.reads == 1));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn repeated_reads_within_the_window_are_folded() {
+        let (dir, _, log) = setup("audit_fold", Options::default()).await;
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        let later = t0() + TimeDelta::seconds(30);
+        log.record_at(&client(1), "ci-1", "/srv/a.json", later)
+            .await;
+        // the window is measured from the last read
+        let latest = later + TimeDelta::seconds(59);
+        log.record_at(&client(1), "ci-1", "/srv/a.json", latest)
+            .await;
+
+        let accesses = log.query(&Query::default()).await;
+        assert_eq!(
+            vec![Access {
+                client: client(1),
+                config_instance_id: "ci-1".to_string(),
+                filepath: "/srv/a.json".to_string(),
+                first_read_at: t0(),
+                last_read_at: latest,
+                reads: 3,
+                seal: None,
+            }],
+            accesses
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_after_the_window_are_new_accesses() {
+        let (dir, _, log) = setup("audit_window", Options::default()).await;
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(
+            &client(1),
+            "ci-1",
+            "/srv/a.json",
+            t0() + TimeDelta::minutes(2),
+        )
+        .await;
+
+        assert_eq!(log.query(&Query::default()).await.len(), 2);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn oldest_accesses_are_dropped() {
+        let options = Options {
+            max_entries: 2,
+            ..Default::default()
+        };
+        let (dir, _, log) = setup("audit_max_entries", options).await;
+        for i in 0..3 {
+            log.record_at(&client(1), &format!("ci-{i}"), "/srv/a.json", t0())
+                .await;
+        }
+
+        let ids: Vec<String> = log
+            .query(&Query::default())
+            .await
+            .into_iter()
+            .map(|a| a.config_instance_id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&"ci-0".to_string()));
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod persist {
+    use super::*;
+
+    #[tokio::test]
+    async fn first_access_is_persisted_immediately_and_later_ones_are_rate_limited() {
+        let (dir, file, log) = setup("audit_rate_limit", Options::default()).await;
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        assert_eq!(file.read_json::<Vec<Access>>().await.unwrap().len(), 1);
+
+        // within the persist interval
+        let later = t0() + TimeDelta::minutes(2);
+        log.record_at(&client(1), "ci-2", "/srv/b.json", later)
+            .await;
+        assert_eq!(file.read_json::<Vec<Access>>().await.unwrap().len(), 1);
+
+        // after the persist interval
+        let latest = t0() + TimeDelta::minutes(6);
+        log.record_at(&client(1), "ci-3", "/srv/c.json", latest)
+            .await;
+        assert_eq!(file.read_json::<Vec<Access>>().await.unwrap().len(), 3);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn persist_writes_pending_accesses() {
+        let (dir, file, log) = setup("audit_persist", Options::default()).await;
+        // nothing to write
+        log.persist().await.unwrap();
+        assert!(!file.exists());
+
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(&client(1), "ci-2", "/srv/b.json", t0()).await;
+        log.persist().await.unwrap();
+
+        let reloaded = AccessLog::init(file.clone(), Options::default()).await;
+        assert_eq!(
+            log.query(&Query::default()).await,
+            reloaded.query(&Query::default()).await
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreadable_log_is_discarded() {
+        let dir = filesys::Dir::create_temp_dir("audit_unreadable")
+            .await
+            .unwrap();
+        let file = dir.file("config_access.json");
+        file.write_string("not json", filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let log = AccessLog::init(file, Options::default()).await;
+        assert!(log.query(&Query::default()).await.is_empty());
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod query {
+    use super::*;
+
+    async fn populated(name: &str) -> (filesys::Dir, AccessLog) {
+        let (dir, _, log) = setup(name, Options::default()).await;
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(
+            &client(2),
+            "ci-1",
+            "/srv/a.json",
+            t0() + TimeDelta::minutes(1),
+        )
+        .await;
+        log.record_at(
+            &client(1),
+            "ci-2",
+            "/srv/b.json",
+            t0() + TimeDelta::minutes(2),
+        )
+        .await;
+        (dir, log)
+    }
+
+    #[tokio::test]
+    async fn most_recent_first() {
+        let (dir, log) = populated("audit_query_order").await;
+        let ids: Vec<(String, Option<u32>)> = log
+            .query(&Query::default())
+            .await
+            .into_iter()
+            .map(|a| (a.config_instance_id, a.client.uid))
+            .collect();
+        assert_eq!(
+            vec![
+                ("ci-2".to_string(), Some(1)),
+                ("ci-1".to_string(), Some(2)),
+                ("ci-1".to_string(), Some(1)),
+            ],
+            ids
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn filters() {
+        let (dir, log) = populated("audit_query_filters").await;
+
+        let by_id = Query {
+            config_instance_id: Some("ci-1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&by_id).await.len(), 2);
+
+        let by_uid = Query {
+            uid: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&by_uid).await.len(), 1);
+
+        let since = Query {
+            since: Some(t0() + TimeDelta::minutes(1)),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&since).await.len(), 2);
+
+        let limit = Query {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let accesses = log.query(&limit).await;
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(accesses[0].config_instance_id, "ci-2");
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod chain {
+    use super::*;
+
+    fn seqs(accesses: &[Access]) -> Vec<Option<u64>> {
+        accesses
+            .iter()
+            .map(|a| a.seal.as_ref().map(|seal| seal.seq))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn accesses_are_sealed_once_the_window_passes() {
+        let (dir, _, log) = setup("audit_seal_window", Options::default()).await;
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(&client(2), "ci-1", "/srv/a.json", t0()).await;
+        let later = t0() + TimeDelta::minutes(2);
+        log.record_at(&client(1), "ci-1", "/srv/a.json", later)
+            .await;
+
+        // most recent first
+        let accesses = log.query(&Query::default()).await;
+        assert_eq!(accesses.len(), 3);
+        assert_eq!(accesses[0].seal, None);
+        let mut sealed = seqs(&accesses[1..]);
+        sealed.sort();
+        assert_eq!(vec![Some(0), Some(1)], sealed);
+
+        // reads are no longer folded into a sealed access
+        assert_eq!(accesses[0].reads, 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn polled_accesses_are_sealed_after_max_open() {
+        let (dir, _, log) = setup("audit_seal_max_open", Options::default()).await;
+        // a client polling every 30 seconds keeps folding into the same access until
+        // it has been open for five minutes
+        for i in 0..=10 {
+            log.record_at(
+                &client(1),
+                "ci-1",
+                "/srv/a.json",
+                t0() + TimeDelta::seconds(30 * i),
+            )
+            .await;
+        }
+
+        let accesses = log.query(&Query::default()).await;
+        assert_eq!(vec![None, Some(0)], seqs(&accesses));
+        assert_eq!(accesses[1].reads, 10);
+        assert_eq!(accesses[0].reads, 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn anchor_seals_and_persists_the_head() {
+        let (dir, file, log) = setup("audit_anchor", Options::default()).await;
+        assert_eq!(log.anchor().await, None);
+
+        log.record_at(&client(1), "ci-1", "/srv/a.json", t0()).await;
+        log.record_at(&client(1), "ci-2", "/srv/b.json", t0()).await;
+        log.record_at(&client(1), "ci-3", "/srv/c.json", t0()).await;
+        let anchor = log.anchor().await.unwrap();
+        assert_eq!(anchor.seq, 2);
+        assert_eq!(anchor.hash.len(), 64);
+        assert_eq!(anchor.to_string(), format!("2:{}", anchor.hash));
+
+        let persisted = file.read_json::<Vec<Access>>().await.unwrap();
+        assert!(persisted.iter().all(|a| a.seal.is_some()));
+        let reloaded = AccessLog::init(file.clone(), Options::default()).await;
+        assert_eq!(reloaded.anchor().await, Some(anchor.clone()));
+
+        // the chain continues from the reloaded head
+        reloaded
+            .record_at(&client(2), "ci-1", "/srv/a.json", t0())
+            .await;
+        assert_eq!(reloaded.anchor().await.unwrap().seq, 3);
+        assert!(reloaded.verify(Some(&anchor)).await.is_intact());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_intact_chain() {
+        let (dir, _, log) = setup("audit_verify_intact", Options::default()).await;
+        let verification = log.verify(None).await;
+        assert_eq!(verification.sealed, 0);
+        assert!(verification.is_intact());
+
+        for i in 0..3 {
+            log.record_at(&client(1), &format!("ci-{i}"), "/srv/a.json", t0())
+                .await;
+        }
+        let anchor = log.anchor().await.unwrap();
+        let verification = log.verify(Some(&anchor)).await;
+        assert_eq!(verification.sealed, 3);
+        assert_eq!(verification.head, Some(anchor));
+        assert_eq!(verification.broken_at, None);
+        assert_eq!(verification.anchor, Some(AnchorStatus::Matches));
+        assert!(verification.is_intact());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_detects_edited_accesses() {
+        let (dir, file, log) = setup("audit_verify_edited", Options::default()).await;
+        for i in 0..3 {
+            log.record_at(&client(1), &format!("ci-{i}"), "/srv/a.json", t0())
+                .await;
+        }
+        let anchor = log.anchor().await.unwrap();
+
+        // rewrite which client read the second config instance
+        let mut accesses = file.read_json::<Vec<Access>>().await.unwrap();
+        accesses[1].client = client(2);
+        file.write_json(&accesses, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let tampered = AccessLog::init(file.clone(), Options::default()).await;
+        let verification = tampered.verify(Some(&anchor)).await;
+        assert_eq!(verification.broken_at, Some(1));
+        assert!(!verification.is_intact());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_detects_removed_accesses() {
+        let (dir, file, log) = setup("audit_verify_removed", Options::default()).await;
+        for i in 0..3 {
+            log.record_at(&client(1), &format!("ci-{i}"), "/srv/a.json", t0())
+                .await;
+        }
+        let anchor = log.anchor().await.unwrap();
+
+        let original = file.read_json::<Vec<Access>>().await.unwrap();
+
+        // removing the last access leaves an intact but shorter chain which no
+        // longer matches the anchor
+        let mut accesses = original.clone();
+        accesses.pop();
+        file.write_json(&accesses, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let tampered = AccessLog::init(file.clone(), Options::default()).await;
+        let verification = tampered.verify(Some(&anchor)).await;
+        assert_eq!(verification.broken_at, None);
+        assert_eq!(verification.anchor, Some(AnchorStatus::Mismatch));
+        assert!(!verification.is_intact());
+
+        // removing one in the middle breaks the chain
+        let mut accesses = original;
+        accesses.remove(1);
+        file.write_json(&accesses, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let tampered = AccessLog::init(file.clone(), Options::default()).await;
+        assert_eq!(tampered.verify(None).await.broken_at, Some(2));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn anchors_of_dropped_accesses() {
+        let options = Options {
+            max_entries: 2,
+            ..Default::default()
+        };
+        let (dir, _, log) = setup("audit_verify_dropped", options).await;
+        log.record_at(&client(1), "ci-0", "/srv/a.json", t0()).await;
+        let first = log.anchor().await.unwrap();
+        for i in 1..3 {
+            log.record_at(&client(1), &format!("ci-{i}"), "/srv/a.json", t0())
+                .await;
+        }
+        log.anchor().await.unwrap();
+
+        // the start of the chain was dropped to make room, which isn't a break
+        let verification = log.verify(Some(&first)).await;
+        assert_eq!(verification.sealed, 2);
+        assert_eq!(verification.broken_at, None);
+        assert_eq!(verification.anchor, Some(AnchorStatus::Dropped));
+        assert!(verification.is_intact());
+
+        let unknown = Anchor {
+            seq: 9,
+            hash: first.hash,
+        };
+        let verification = log.verify(Some(&unknown)).await;
+        assert_eq!(verification.anchor, Some(AnchorStatus::Mismatch));
+        dir.delete().await.unwrap();
+    }
+}
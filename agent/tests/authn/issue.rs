@@ -68,9 +68,13 @@ mod issue_token {
             ..Default::default()
         };
 
-        let token: Token = issue_token(&mock_client, &private_key_file, &public_key_file)
-            .await
-            .unwrap();
+        let token: Token = issue_token(
+            &mock_client,
+            &private_key_file.clone().into(),
+            &public_key_file,
+        )
+        .await
+        .unwrap();
 
         assert_eq!(token.token, "issued-token");
         assert!((token.expires_at - expires_at).num_seconds().abs() <= 1);
@@ -98,7 +102,12 @@ mod issue_token {
             ..Default::default()
         };
 
-        let result = issue_token(&mock_client, &private_key_file, &public_key_file).await;
+        let result = issue_token(
+            &mock_client,
+            &private_key_file.clone().into(),
+            &public_key_file,
+        )
+        .await;
 
         assert!(matches!(result, Err(AuthnErr::TimestampConversionErr(_))));
         assert_eq!(mock_client.call_count(Call::IssueDeviceToken), 1);
@@ -116,7 +125,12 @@ mod issue_token {
             ..Default::default()
         };
 
-        let result = issue_token(&mock_client, &private_key_file, &public_key_file).await;
+        let result = issue_token(
+            &mock_client,
+            &private_key_file.clone().into(),
+            &public_key_file,
+        )
+        .await;
 
         assert!(matches!(result, Err(AuthnErr::HTTPErr(_))));
         assert_eq!(mock_client.call_count(Call::IssueDeviceToken), 1);
@@ -135,7 +149,12 @@ mod issue_token {
         public_key_file.delete().await.unwrap();
         let mock_client = MockClient::default();
 
-        let result = issue_token(&mock_client, &private_key_file, &public_key_file).await;
+        let result = issue_token(
+            &mock_client,
+            &private_key_file.clone().into(),
+            &public_key_file,
+        )
+        .await;
 
         assert!(result.is_err());
         assert_eq!(mock_client.call_count(Call::IssueDeviceToken), 0);
@@ -148,7 +167,9 @@ mod mint_jwt {
     #[tokio::test]
     async fn has_three_parts() {
         let (_dir, private_key_file, public_key_file) = generate_keys().await;
-        let jwt = mint_jwt(&private_key_file, &public_key_file).await.unwrap();
+        let jwt = mint_jwt(&private_key_file.clone().into(), &public_key_file)
+            .await
+            .unwrap();
 
         assert_eq!(3, jwt.split('.').count());
     }
@@ -156,7 +177,9 @@ mod mint_jwt {
     #[tokio::test]
     async fn header_decodes_to_rs512_with_kid() {
         let (_dir, private_key_file, public_key_file) = generate_keys().await;
-        let jwt = mint_jwt(&private_key_file, &public_key_file).await.unwrap();
+        let jwt = mint_jwt(&private_key_file.clone().into(), &public_key_file)
+            .await
+            .unwrap();
         let parts: Vec<&str> = jwt.split('.').collect();
         let header_bytes = base64::decode_bytes_url_safe_no_pad(parts[0]).unwrap();
         let header: Value = serde_json::from_slice(&header_bytes).unwrap();
@@ -177,7 +200,9 @@ mod mint_jwt {
     async fn payload_decodes_with_jti_iat_exp() {
         let (_dir, private_key_file, public_key_file) = generate_keys().await;
         let before = Utc::now().timestamp();
-        let jwt = mint_jwt(&private_key_file, &public_key_file).await.unwrap();
+        let jwt = mint_jwt(&private_key_file.clone().into(), &public_key_file)
+            .await
+            .unwrap();
         let after = Utc::now().timestamp();
         let parts: Vec<&str> = jwt.split('.').collect();
         let payload_bytes = base64::decode_bytes_url_safe_no_pad(parts[1]).unwrap();
@@ -200,7 +225,9 @@ mod mint_jwt {
     #[tokio::test]
     async fn signature_verifies_with_public_key() {
         let (_dir, private_key_file, public_key_file) = generate_keys().await;
-        let jwt = mint_jwt(&private_key_file, &public_key_file).await.unwrap();
+        let jwt = mint_jwt(&private_key_file.clone().into(), &public_key_file)
+            .await
+            .unwrap();
         let parts: Vec<&str> = jwt.split('.').collect();
         let signing_input = format!("{}.{}", parts[0], parts[1]);
         let signature = base64::decode_bytes_url_safe_no_pad(parts[2]).unwrap();
@@ -223,8 +250,12 @@ mod mint_jwt {
     #[tokio::test]
     async fn generates_unique_jti_across_calls() {
         let (_dir, private_key_file, public_key_file) = generate_keys().await;
-        let jwt_a = mint_jwt(&private_key_file, &public_key_file).await.unwrap();
-        let jwt_b = mint_jwt(&private_key_file, &public_key_file).await.unwrap();
+        let jwt_a = mint_jwt(&private_key_file.clone().into(), &public_key_file)
+            .await
+            .unwrap();
+        let jwt_b = mint_jwt(&private_key_file.clone().into(), &public_key_file)
+            .await
+            .unwrap();
 
         let parts_a: Vec<&str> = jwt_a.split('.').collect();
         let parts_b: Vec<&str> = jwt_b.split('.').collect();
@@ -250,7 +281,7 @@ mod mint_jwt {
             .unwrap();
         public_key_file.delete().await.unwrap();
 
-        let result = mint_jwt(&private_key_file, &public_key_file).await;
+        let result = mint_jwt(&private_key_file.clone().into(), &public_key_file).await;
 
         assert!(result.is_err());
     }
@@ -267,7 +298,7 @@ mod mint_jwt {
             .unwrap();
         private_key_file.delete().await.unwrap();
 
-        let result = mint_jwt(&private_key_file, &public_key_file).await;
+        let result = mint_jwt(&private_key_file.clone().into(), &public_key_file).await;
 
         assert!(result.is_err());
     }
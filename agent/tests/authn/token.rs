@@ -6,6 +6,7 @@ use miru_agent::models::Patch;
 // external crates
 use chrono::{DateTime, Duration, Utc};
 use serde_json::json;
+use zeroize::Zeroize;
 
 #[test]
 fn deserialize_token() {
@@ -40,6 +41,16 @@ fn debug_redacts_token() {
     assert!(!debug_output.contains("secret-value"));
 }
 
+#[test]
+fn zeroize_clears_token() {
+    let mut token = Token {
+        token: "secret-value".to_string(),
+        expires_at: Utc::now(),
+    };
+    token.zeroize();
+    assert!(token.token.is_empty());
+}
+
 #[test]
 fn token_update_empty() {
     let initial = Token {
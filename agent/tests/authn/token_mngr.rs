@@ -5,13 +5,16 @@ use std::sync::Arc;
 use crate::mocks::http_client::MockClient;
 use backend_api::models::TokenResponse;
 use miru_agent::authn::{token_mngr::TokenFile, AuthnErr, Token, TokenManager, TokenManagerExt};
+use miru_agent::crypt::keystore::{PrivateKey, TpmKey};
 use miru_agent::crypt::rsa;
-use miru_agent::filesys::{self, Overwrite, WriteOptions};
-use miru_agent::http::errors::MockErr;
-use miru_agent::http::{self, HTTPErr};
+use miru_agent::filesys::{self, Overwrite, PathExt, WriteOptions};
+use miru_agent::http::errors::{MockErr, RequestFailed};
+use miru_agent::http::{self, devices::RegisteredKey, request::Params, HTTPErr};
+use miru_agent::trace;
 
 // external crates
 use chrono::{Duration, Utc};
+use reqwest::StatusCode;
 use tokio::task::JoinHandle;
 
 /// Setup a TokenManager with a dummy private key (for tests that don't reach RSA signing).
@@ -34,7 +37,7 @@ async fn setup(mock_client: MockClient) -> (filesys::Dir, TokenManager, JoinHand
         32,
         Arc::new(mock_client),
         token_file,
-        private_key_file,
+        private_key_file.into(),
         public_key_file,
     )
     .unwrap();
@@ -56,7 +59,7 @@ async fn setup_with_rsa(mock_client: MockClient) -> (filesys::Dir, TokenManager,
         32,
         Arc::new(mock_client),
         token_file,
-        private_key_file,
+        private_key_file.into(),
         public_key_file,
     )
     .unwrap();
@@ -89,7 +92,7 @@ pub mod spawn {
             32,
             Arc::new(http_client),
             token_file,
-            private_key_file,
+            private_key_file.into(),
             public_key_file,
         )
         .unwrap_err();
@@ -113,7 +116,7 @@ pub mod spawn {
             32,
             Arc::new(http_client),
             token_file,
-            dir.file("private_key.pem"),
+            dir.file("private_key.pem").into(),
             public_key_file,
         )
         .unwrap_err();
@@ -137,7 +140,7 @@ pub mod spawn {
             32,
             Arc::new(http_client),
             token_file,
-            private_key_file,
+            private_key_file.into(),
             dir.file("public_key.pem"),
         )
         .unwrap_err();
@@ -341,6 +344,298 @@ pub mod refresh_token {
     }
 }
 
+pub mod subscribe {
+    use super::*;
+
+    #[tokio::test]
+    async fn receives_refreshed_tokens() {
+        let call_count = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let call_count_clone = call_count.clone();
+        let mock_client = MockClient {
+            issue_device_token_fn: Box::new(move || {
+                let n = call_count_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(TokenResponse {
+                    token: format!("token-{n}"),
+                    expires_at: (Utc::now() + Duration::days(1)).to_rfc3339(),
+                })
+            }),
+            ..Default::default()
+        };
+        let (_dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+
+        let mut tokens = token_mngr.subscribe();
+        assert_eq!(tokens.borrow_and_update().as_ref(), &Token::default());
+
+        token_mngr.refresh_token().await.unwrap();
+        tokens.changed().await.unwrap();
+        assert_eq!(tokens.borrow_and_update().token, "token-0");
+
+        // subscribers joining later start from the current token
+        let mut late = token_mngr.subscribe();
+        assert_eq!(late.borrow_and_update().token, "token-0");
+        assert!(!late.has_changed().unwrap());
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_refreshes_are_not_published() {
+        let mock_client = MockClient {
+            issue_device_token_fn: Box::new(|| {
+                Err(HTTPErr::MockErr(MockErr {
+                    is_network_conn_err: false,
+                }))
+            }),
+            ..Default::default()
+        };
+        let (_dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+
+        let tokens = token_mngr.subscribe();
+        token_mngr.refresh_token().await.unwrap_err();
+        assert!(!tokens.has_changed().unwrap());
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn closes_on_shutdown() {
+        let (_dir, token_mngr, worker_handle) = setup(MockClient::default()).await;
+        let mut tokens = token_mngr.subscribe();
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+        assert!(tokens.changed().await.is_err());
+    }
+}
+
+pub mod rotate_key {
+    use super::*;
+
+    /// Issues tokens except on the calls listed in `failures`, which fail with the
+    /// paired error.
+    fn issue_token_fn(
+        failures: Vec<(u32, fn() -> HTTPErr)>,
+    ) -> Box<dyn Fn() -> Result<TokenResponse, HTTPErr> + Send + Sync> {
+        let call_count = std::sync::atomic::AtomicU32::new(0);
+        Box::new(move || {
+            let n = call_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if let Some((_, err)) = failures.iter().find(|(i, _)| *i == n) {
+                return Err(err());
+            }
+            Ok(TokenResponse {
+                token: format!("token-{n}"),
+                expires_at: (Utc::now() + Duration::days(1)).to_rfc3339(),
+            })
+        })
+    }
+
+    fn unauthorized() -> HTTPErr {
+        HTTPErr::RequestFailed(RequestFailed {
+            request: Params::get("http://mock/devices/token").meta().unwrap(),
+            status: StatusCode::UNAUTHORIZED,
+            error: None,
+            trace: trace!(),
+        })
+    }
+
+    fn network_err() -> HTTPErr {
+        HTTPErr::MockErr(MockErr {
+            is_network_conn_err: true,
+        })
+    }
+
+    async fn fingerprint(dir: &filesys::Dir) -> String {
+        let key = rsa::read_public_key(&dir.file("public_key.pem"))
+            .await
+            .unwrap();
+        rsa::fingerprint(&key).unwrap()
+    }
+
+    async fn private_key(dir: &filesys::Dir) -> String {
+        dir.file("private_key.pem").read_string().await.unwrap()
+    }
+
+    fn assert_no_pending_files(dir: &filesys::Dir) {
+        for name in [
+            "private_key.pem.new",
+            "public_key.pem.new",
+            "private_key.pem.old",
+            "public_key.pem.old",
+        ] {
+            assert!(!dir.file(name).exists(), "{name} should not exist");
+        }
+    }
+
+    #[tokio::test]
+    async fn success() {
+        let mock_client = MockClient {
+            issue_device_token_fn: issue_token_fn(vec![]),
+            ..Default::default()
+        };
+        let requests = mock_client.requests.clone();
+        let (dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+        let old_fingerprint = fingerprint(&dir).await;
+        let old_private_key = private_key(&dir).await;
+
+        let new_fingerprint = token_mngr.rotate_key().await.unwrap();
+        assert_ne!(new_fingerprint, old_fingerprint);
+        assert_eq!(fingerprint(&dir).await, new_fingerprint);
+        assert_ne!(private_key(&dir).await, old_private_key);
+        assert_no_pending_files(&dir);
+
+        // the expired token is refreshed before registering, then the new key is
+        // confirmed with another token
+        let requests = requests.lock().unwrap().clone();
+        let register = requests.iter().find(|r| r.path == "/devices/keys").unwrap();
+        assert_eq!(register.token.as_deref(), Some("token-0"));
+        let token = token_mngr.get_token().await.unwrap();
+        assert_eq!(token.token, "token-1");
+
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn registration_failure_keeps_the_current_key() {
+        let mock_client = MockClient {
+            issue_device_token_fn: issue_token_fn(vec![]),
+            ..Default::default()
+        };
+        mock_client.set_register_key(|_| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+        let (dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+        let old_fingerprint = fingerprint(&dir).await;
+
+        let err = token_mngr.rotate_key().await.unwrap_err();
+        assert!(matches!(err, AuthnErr::HTTPErr(_)));
+        assert_eq!(fingerprint(&dir).await, old_fingerprint);
+        assert_no_pending_files(&dir);
+
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn fingerprint_mismatch_keeps_the_current_key() {
+        let mock_client = MockClient {
+            issue_device_token_fn: issue_token_fn(vec![]),
+            ..Default::default()
+        };
+        mock_client.set_register_key(|_| {
+            Ok(RegisteredKey {
+                fingerprint: "someone-elses-key".to_string(),
+            })
+        });
+        let (dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+        let old_fingerprint = fingerprint(&dir).await;
+
+        let err = token_mngr.rotate_key().await.unwrap_err();
+        assert!(matches!(err, AuthnErr::KeyRotationErr(_)));
+        assert_eq!(fingerprint(&dir).await, old_fingerprint);
+        assert_no_pending_files(&dir);
+
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejected_key_restores_the_previous_key() {
+        // the token issued with the new key is refused
+        let mock_client = MockClient {
+            issue_device_token_fn: issue_token_fn(vec![(1, unauthorized)]),
+            ..Default::default()
+        };
+        let (dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+        let old_fingerprint = fingerprint(&dir).await;
+        let old_private_key = private_key(&dir).await;
+
+        let err = token_mngr.rotate_key().await.unwrap_err();
+        assert!(matches!(err, AuthnErr::KeyRotationErr(_)));
+        assert_eq!(fingerprint(&dir).await, old_fingerprint);
+        assert_eq!(private_key(&dir).await, old_private_key);
+        assert_no_pending_files(&dir);
+
+        // the previous key issued the token
+        let token = token_mngr.get_token().await.unwrap();
+        assert_eq!(token.token, "token-2");
+
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_rotation_is_confirmed_by_the_next_refresh() {
+        // the backend can't be reached to confirm the new key
+        let mock_client = MockClient {
+            issue_device_token_fn: issue_token_fn(vec![(1, network_err)]),
+            ..Default::default()
+        };
+        let (dir, token_mngr, worker_handle) = setup_with_rsa(mock_client).await;
+        let old_fingerprint = fingerprint(&dir).await;
+
+        let new_fingerprint = token_mngr.rotate_key().await.unwrap();
+        assert_eq!(fingerprint(&dir).await, new_fingerprint);
+        assert!(dir.file("private_key.pem.old").exists());
+        assert!(dir.file("public_key.pem.old").exists());
+        let old_key = rsa::read_public_key(&dir.file("public_key.pem.old"))
+            .await
+            .unwrap();
+        assert_eq!(rsa::fingerprint(&old_key).unwrap(), old_fingerprint);
+
+        // another rotation waits for this one to be confirmed
+        let err = token_mngr.rotate_key().await.unwrap_err();
+        assert!(matches!(err, AuthnErr::KeyRotationErr(_)));
+
+        token_mngr.refresh_token().await.unwrap();
+        assert_eq!(fingerprint(&dir).await, new_fingerprint);
+        assert_no_pending_files(&dir);
+
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn hardware_keys_are_not_rotated() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let token_file = TokenFile::new_with_default(dir.file("token.json"), Token::default())
+            .await
+            .unwrap();
+        let public_key_file = dir.file("public_key.pem");
+        public_key_file
+            .write_string("public_key", WriteOptions::default())
+            .await
+            .unwrap();
+        let private_key = PrivateKey::Tpm(TpmKey::new("0x81010001".to_string(), None));
+        let (token_mngr, worker_handle) = TokenManager::spawn(
+            32,
+            Arc::new(MockClient::default()),
+            token_file,
+            private_key,
+            public_key_file,
+        )
+        .unwrap();
+
+        let err = token_mngr.rotate_key().await.unwrap_err();
+        assert!(matches!(err, AuthnErr::KeyRotationErr(_)));
+        assert!(!dir.file("public_key.pem.new").exists());
+
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn after_shutdown() {
+        let (_dir, token_mngr, worker_handle) = setup(MockClient::default()).await;
+        token_mngr.shutdown().await.unwrap();
+        worker_handle.await.unwrap();
+
+        let result = token_mngr.rotate_key().await;
+        assert!(matches!(result, Err(AuthnErr::SendActorMessageErr(_))));
+    }
+}
+
 pub mod arc_delegation {
     use super::*;
 
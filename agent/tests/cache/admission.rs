@@ -0,0 +1,203 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// internal crates
+use miru_agent::cache::admission::{Decision, Frequencies, Policy, Rule};
+use miru_agent::cache::{DirCache, Probation};
+use miru_agent::filesys::{self, Overwrite};
+
+fn size_policy(max_entry_bytes: usize, min_accesses: u32) -> Policy {
+    Policy {
+        max_entry_bytes: Some(max_entry_bytes),
+        min_accesses,
+        ..Default::default()
+    }
+}
+
+pub mod policy {
+    use super::*;
+
+    #[test]
+    fn default_admits_everything() {
+        let policy = Policy::default();
+        assert_eq!(policy.decide(None, usize::MAX, 0), Decision::Admit);
+        assert_eq!(policy.decide(Some("mobility"), 1, 0), Decision::Admit);
+    }
+
+    #[test]
+    fn oversized_entries_need_accesses() {
+        let policy = size_policy(100, 3);
+        assert_eq!(policy.decide(None, 100, 0), Decision::Admit);
+        assert_eq!(policy.decide(None, 101, 0), Decision::Probation);
+        assert_eq!(policy.decide(None, 101, 2), Decision::Probation);
+        assert_eq!(policy.decide(None, 101, 3), Decision::Admit);
+    }
+
+    #[test]
+    fn rules_take_precedence() {
+        let policy = Policy {
+            rules: BTreeMap::from([
+                ("maps".to_string(), Rule::Always),
+                ("firmware".to_string(), Rule::Never),
+            ]),
+            ..size_policy(100, 3)
+        };
+        assert_eq!(policy.decide(Some("maps"), 1000, 0), Decision::Admit);
+        assert_eq!(policy.decide(Some("firmware"), 1, 100), Decision::Probation);
+        assert_eq!(
+            policy.decide(Some("mobility"), 1000, 0),
+            Decision::Probation
+        );
+    }
+}
+
+pub mod frequencies {
+    use super::*;
+
+    #[test]
+    fn counts_accesses() {
+        let mut frequencies = Frequencies::new(100);
+        assert_eq!(frequencies.count("a"), 0);
+        assert_eq!(frequencies.record("a"), 1);
+        assert_eq!(frequencies.record("a"), 2);
+        assert_eq!(frequencies.record("b"), 1);
+        assert_eq!(frequencies.len(), 2);
+
+        frequencies.forget("a");
+        assert_eq!(frequencies.count("a"), 0);
+        assert_eq!(frequencies.len(), 1);
+    }
+
+    #[test]
+    fn ages_counts_over_the_window() {
+        let mut frequencies = Frequencies::new(4);
+        for _ in 0..4 {
+            frequencies.record("a");
+        }
+        assert_eq!(frequencies.count("a"), 4);
+
+        // the access past the window halves every count first
+        assert_eq!(frequencies.record("b"), 1);
+        assert_eq!(frequencies.count("a"), 2);
+    }
+
+    #[test]
+    fn forgets_rarely_accessed_keys() {
+        let mut frequencies = Frequencies::new(2);
+        frequencies.record("a");
+        frequencies.record("b");
+        frequencies.record("c");
+        assert_eq!(frequencies.count("a"), 0);
+        assert_eq!(frequencies.count("b"), 0);
+        assert_eq!(frequencies.len(), 1);
+        assert!(!frequencies.is_empty());
+    }
+}
+
+pub mod dir_cache {
+    use super::*;
+
+    type TestCache = DirCache<String, String>;
+
+    async fn spawn_cache(capacity: usize, policy: Policy) -> TestCache {
+        let dir = filesys::Dir::create_temp_dir("testing")
+            .await
+            .unwrap()
+            .subdir(PathBuf::from("cache"));
+        let (cache, _) = TestCache::spawn(32, dir, capacity).await.unwrap();
+        cache.set_admission_policy(policy).await.unwrap();
+        cache
+    }
+
+    async fn write(cache: &TestCache, key: &str, value: &str, class: Option<&str>) {
+        cache
+            .write_with_class(
+                key.to_string(),
+                value.to_string(),
+                class.map(str::to_string),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn admits_everything_by_default() {
+        let cache = spawn_cache(10, Policy::default()).await;
+        write(&cache, "a", &"x".repeat(10_000), Some("maps")).await;
+        let entry = cache.read_entry("a".to_string()).await.unwrap();
+        assert!(!entry.is_probationary());
+    }
+
+    #[tokio::test]
+    async fn puts_oversized_entries_on_probation() {
+        let cache = spawn_cache(10, size_policy(100, 3)).await;
+        write(&cache, "small", "x", None).await;
+        write(&cache, "huge", &"x".repeat(1000), Some("maps")).await;
+
+        let entries = cache.entry_map().await.unwrap();
+        assert!(!entries["small"].is_probationary());
+        assert_eq!(
+            entries["huge"].probation,
+            Some(Probation {
+                class: Some("maps".to_string())
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn promotes_frequently_read_entries() {
+        let cache = spawn_cache(10, size_policy(100, 3)).await;
+        write(&cache, "huge", &"x".repeat(1000), None).await;
+
+        for _ in 0..2 {
+            let entry = cache.read_entry("huge".to_string()).await.unwrap();
+            assert!(entry.is_probationary());
+        }
+        let entry = cache.read_entry("huge".to_string()).await.unwrap();
+        assert!(!entry.is_probationary());
+
+        // promotion is persisted
+        let entries = cache.entry_map().await.unwrap();
+        assert!(!entries["huge"].is_probationary());
+    }
+
+    #[tokio::test]
+    async fn never_promotes_entries_ruled_out_by_class() {
+        let policy = Policy {
+            rules: BTreeMap::from([("firmware".to_string(), Rule::Never)]),
+            ..Policy::default()
+        };
+        let cache = spawn_cache(10, policy).await;
+        write(&cache, "blob", "x", Some("firmware")).await;
+        for _ in 0..5 {
+            let entry = cache.read_entry("blob".to_string()).await.unwrap();
+            assert!(entry.is_probationary());
+        }
+
+        // rewriting without a class keeps the class the entry was written with
+        write(&cache, "blob", "y", None).await;
+        let entry = cache.read_entry("blob".to_string()).await.unwrap();
+        assert!(entry.is_probationary());
+    }
+
+    #[tokio::test]
+    async fn prunes_probationary_entries_first() {
+        let cache = spawn_cache(3, size_policy(100, 3)).await;
+        write(&cache, "huge", &"x".repeat(1000), None).await;
+        for key in ["a", "b", "c"] {
+            write(&cache, key, key, None).await;
+        }
+        // the huge entry is the most recently accessed but still goes first
+        cache.read_entry("huge".to_string()).await.unwrap();
+        write(&cache, "d", "d", None).await;
+
+        let entries = cache.entry_map().await.unwrap();
+        assert!(!entries.contains_key("huge"));
+        for key in ["a", "b", "c", "d"] {
+            assert!(entries.contains_key(key), "missing {key}");
+        }
+    }
+}
@@ -187,6 +187,41 @@ macro_rules! concurrent_cache_tests {
             }
         }
 
+        pub mod read_many {
+            use super::*;
+
+            #[tokio::test]
+            async fn skips_missing_entries() {
+                $crate::cache::concurrent::read_many::skips_missing_entries_impl($spawn_cache)
+                    .await;
+            }
+        }
+
+        pub mod write_many {
+            use super::*;
+
+            #[tokio::test]
+            async fn writes_every_entry() {
+                $crate::cache::concurrent::write_many::writes_every_entry_impl($spawn_cache).await;
+            }
+
+            #[tokio::test]
+            async fn stops_at_the_first_error() {
+                $crate::cache::concurrent::write_many::stops_at_the_first_error_impl($spawn_cache)
+                    .await;
+            }
+        }
+
+        pub mod delete_many {
+            use super::*;
+
+            #[tokio::test]
+            async fn deletes_every_entry() {
+                $crate::cache::concurrent::delete_many::deletes_every_entry_impl($spawn_cache)
+                    .await;
+            }
+        }
+
         pub mod prune {
             use super::*;
 
@@ -213,6 +248,36 @@ macro_rules! concurrent_cache_tests {
             }
         }
 
+        pub mod pin {
+            use super::*;
+
+            #[tokio::test]
+            async fn pinned_entries_survive_pruning() {
+                $crate::cache::concurrent::pin::pinned_entries_survive_pruning_impl(
+                    $spawn_cache_with_capacity,
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn unpinned_entries_are_pruned() {
+                $crate::cache::concurrent::pin::unpinned_entries_are_pruned_impl(
+                    $spawn_cache_with_capacity,
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn writes_keep_the_pin() {
+                $crate::cache::concurrent::pin::writes_keep_the_pin_impl($spawn_cache).await;
+            }
+
+            #[tokio::test]
+            async fn missing_entries() {
+                $crate::cache::concurrent::pin::missing_entries_impl($spawn_cache).await;
+            }
+        }
+
         pub mod find_entries_where {
             use super::*;
 
@@ -591,7 +656,11 @@ pub mod read_entry_optional {
             created_at: read_entry.created_at,
             last_accessed: read_entry.last_accessed,
             is_dirty: false,
+            probation: None,
+            digest: read_entry.digest.clone(),
+            pinned: false,
         };
+        assert!(read_entry.is_intact());
         assert_eq!(read_entry, expected_entry);
     }
 }
@@ -659,7 +728,11 @@ pub mod read_entry {
             created_at: read_entry.created_at,
             last_accessed: read_entry.last_accessed,
             is_dirty: false,
+            probation: None,
+            digest: read_entry.digest.clone(),
+            pinned: false,
         };
+        assert!(read_entry.is_intact());
         assert_eq!(read_entry, expected_entry);
     }
 }
@@ -1048,6 +1121,170 @@ pub mod delete {
     }
 }
 
+pub mod read_many {
+    use super::*;
+
+    pub async fn skips_missing_entries_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn() -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache().await;
+        for i in 0..3 {
+            cache
+                .write(
+                    format!("key{i}"),
+                    format!("value{i}"),
+                    |_, _| false,
+                    Overwrite::Deny,
+                )
+                .await
+                .unwrap();
+        }
+
+        let keys = vec![
+            "key0".to_string(),
+            "missing".to_string(),
+            "key2".to_string(),
+        ];
+        let values = cache.read_many(keys).await.unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["key0"], "value0");
+        assert_eq!(values["key2"], "value2");
+
+        let values = cache.read_many(Vec::new()).await.unwrap();
+        assert!(values.is_empty());
+    }
+}
+
+pub mod write_many {
+    use super::*;
+
+    pub async fn writes_every_entry_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn() -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache().await;
+        cache
+            .write(
+                "key0".to_string(),
+                "old value".to_string(),
+                |_, _| false,
+                Overwrite::Deny,
+            )
+            .await
+            .unwrap();
+
+        let entries = (0..3)
+            .map(|i| (format!("key{i}"), format!("value{i}")))
+            .collect();
+        cache
+            .write_many(entries, |old, _| old.is_none(), Overwrite::Allow)
+            .await
+            .unwrap();
+
+        assert_eq!(cache.size().await.unwrap(), 3);
+        for i in 0..3 {
+            let entry = cache.read_entry(format!("key{i}")).await.unwrap();
+            assert_eq!(entry.value, format!("value{i}"));
+            // is_dirty judges each entry against its own existing entry
+            assert_eq!(entry.is_dirty, i != 0);
+        }
+    }
+
+    pub async fn stops_at_the_first_error_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn() -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache().await;
+        cache
+            .write(
+                "key1".to_string(),
+                "existing".to_string(),
+                |_, _| false,
+                Overwrite::Deny,
+            )
+            .await
+            .unwrap();
+
+        let entries = (0..3)
+            .map(|i| (format!("key{i}"), format!("value{i}")))
+            .collect();
+        cache
+            .write_many(entries, |_, _| false, Overwrite::Deny)
+            .await
+            .unwrap_err();
+
+        assert_eq!(cache.read("key0".to_string()).await.unwrap(), "value0");
+        assert_eq!(cache.read("key1".to_string()).await.unwrap(), "existing");
+        assert!(cache
+            .read_optional("key2".to_string())
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
+
+pub mod delete_many {
+    use super::*;
+
+    pub async fn deletes_every_entry_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn() -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache().await;
+        for i in 0..3 {
+            cache
+                .write(
+                    format!("key{i}"),
+                    format!("value{i}"),
+                    |_, _| false,
+                    Overwrite::Deny,
+                )
+                .await
+                .unwrap();
+        }
+
+        // missing keys are ignored like with delete
+        let keys = vec![
+            "key0".to_string(),
+            "missing".to_string(),
+            "key2".to_string(),
+        ];
+        cache.delete_many(keys).await.unwrap();
+
+        assert_eq!(cache.size().await.unwrap(), 1);
+        assert_eq!(cache.read("key1".to_string()).await.unwrap(), "value1");
+    }
+}
+
 pub mod prune {
     use super::*;
 
@@ -1141,6 +1378,130 @@ pub mod prune {
     }
 }
 
+pub mod pin {
+    use super::*;
+
+    async fn write_entries<SingleThreadCacheT>(
+        cache: &ConcurrentCache<SingleThreadCacheT, String, String>,
+        keys: std::ops::Range<usize>,
+    ) where
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        for i in keys {
+            cache
+                .write(
+                    format!("key{i}"),
+                    format!("value{i}"),
+                    |_, _| false,
+                    Overwrite::Deny,
+                )
+                .await
+                .unwrap();
+        }
+    }
+
+    pub async fn pinned_entries_survive_pruning_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn(usize) -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache(3).await;
+        write_entries(&cache, 0..3).await;
+
+        // the oldest entry would be the first pruned
+        cache.pin("key0".to_string()).await.unwrap();
+        write_entries(&cache, 3..5).await;
+        cache.prune().await.unwrap();
+
+        assert_eq!(cache.read("key0".to_string()).await.unwrap(), "value0");
+        for i in [1, 2] {
+            cache.read(format!("key{i}")).await.unwrap_err();
+        }
+        for i in [3, 4] {
+            cache.read(format!("key{i}")).await.unwrap();
+        }
+        assert!(cache.read_entry("key0".to_string()).await.unwrap().pinned);
+    }
+
+    pub async fn unpinned_entries_are_pruned_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn(usize) -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache(3).await;
+        write_entries(&cache, 0..3).await;
+        cache.pin("key0".to_string()).await.unwrap();
+        cache.unpin("key0".to_string()).await.unwrap();
+
+        write_entries(&cache, 3..5).await;
+        cache.prune().await.unwrap();
+
+        cache.read("key0".to_string()).await.unwrap_err();
+        assert_eq!(cache.size().await.unwrap(), 3);
+    }
+
+    pub async fn writes_keep_the_pin_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn() -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache().await;
+        write_entries(&cache, 0..1).await;
+        cache.pin("key0".to_string()).await.unwrap();
+
+        cache
+            .write(
+                "key0".to_string(),
+                "new value".to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+
+        let entry = cache.read_entry("key0".to_string()).await.unwrap();
+        assert_eq!(entry.value, "new value");
+        assert!(entry.pinned);
+    }
+
+    pub async fn missing_entries_impl<F, Fut, SingleThreadCacheT>(spawn_cache: F)
+    where
+        F: Fn() -> Fut + Clone,
+        Fut: Future<
+            Output = (
+                ConcurrentCache<SingleThreadCacheT, String, String>,
+                JoinHandle<()>,
+            ),
+        >,
+        SingleThreadCacheT: SingleThreadCache<String, String>,
+    {
+        let (cache, _) = spawn_cache().await;
+
+        let err = cache.pin("missing".to_string()).await.unwrap_err();
+        assert!(matches!(err, CacheErr::CacheElementNotFound(_)));
+        cache.unpin("missing".to_string()).await.unwrap();
+        assert_eq!(cache.size().await.unwrap(), 0);
+    }
+}
+
 pub mod find_entries_where {
     use super::*;
 
@@ -4,7 +4,8 @@ use std::path::PathBuf;
 // internal crates
 use crate::concurrent_cache_tests;
 use crate::single_thread_cache_tests;
-use miru_agent::cache::{DirCache, SingleThreadDirCache};
+use miru_agent::cache::{CacheEntry, CacheErr, DirCache, SingleThreadDirCache};
+use miru_agent::crypt::digest;
 use miru_agent::filesys::{self, Overwrite, PathExt, WriteOptions};
 
 // external crates
@@ -83,6 +84,292 @@ pub mod concurrent {
             assert_eq!(value, format!("value{i}"));
         }
     }
+
+    /// Flips the value of an entry on disk without updating its digest.
+    async fn corrupt(dir: &filesys::Dir, key: &str) {
+        let file = dir.file(&format!("{key}.json"));
+        let mut entry = file
+            .read_json::<CacheEntry<String, String>>()
+            .await
+            .unwrap();
+        entry.value.push_str("-rotted");
+        file.write_json(&entry, WriteOptions::OVERWRITE_NONATOMIC)
+            .await
+            .unwrap();
+    }
+
+    async fn spawn_in_temp_dir(name: &str) -> (TestCache, filesys::Dir) {
+        let dir = filesys::Dir::create_temp_dir(name)
+            .await
+            .unwrap()
+            .subdir(PathBuf::from("cache"));
+        let (cache, _) = TestCache::spawn(32, dir.clone(), 10).await.unwrap();
+        (cache, dir)
+    }
+
+    #[tokio::test]
+    async fn writes_record_a_digest() {
+        let (cache, _dir) = spawn_in_temp_dir("dir_cache_digest").await;
+        cache
+            .write("key".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+
+        let entry = cache.read_entry("key".into()).await.unwrap();
+        assert_eq!(
+            entry.digest,
+            Some(miru_agent::cache::entry::digest(
+                digest::Algorithm::Sha256,
+                &"value"
+            ))
+        );
+        assert!(entry.is_intact());
+    }
+
+    #[tokio::test]
+    async fn digest_algorithm_applies_to_new_writes() {
+        let (cache, dir) = spawn_in_temp_dir("dir_cache_digest_algorithm").await;
+        cache
+            .write("old".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+
+        cache
+            .set_digest_algorithm(digest::Algorithm::Blake3)
+            .await
+            .unwrap();
+        cache
+            .write("new".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+
+        // entries written before the switch are still verified with their algorithm
+        let old = cache.read_entry("old".into()).await.unwrap();
+        assert_eq!(old.digest.unwrap().algorithm(), digest::Algorithm::Sha256);
+        let new = cache.read_entry("new".into()).await.unwrap();
+        assert_eq!(new.digest.unwrap().algorithm(), digest::Algorithm::Blake3);
+        assert!(cache.verify_all().await.unwrap().is_empty());
+
+        corrupt(&dir, "new").await;
+        let err = cache.read("new".into()).await.unwrap_err();
+        assert!(matches!(err, CacheErr::CorruptedCacheElement(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn corrupted_entries_fail_to_read() {
+        let (cache, dir) = spawn_in_temp_dir("dir_cache_corrupted_read").await;
+        cache
+            .write("key".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        corrupt(&dir, "key").await;
+
+        let err = cache.read("key".into()).await.unwrap_err();
+        assert!(matches!(err, CacheErr::CorruptedCacheElement(_)), "{err:?}");
+        let err = cache.read_optional("key".into()).await.unwrap_err();
+        assert!(matches!(err, CacheErr::CorruptedCacheElement(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn writing_repairs_corrupted_entries() {
+        let (cache, dir) = spawn_in_temp_dir("dir_cache_corrupted_write").await;
+        cache
+            .write("key".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        corrupt(&dir, "key").await;
+
+        cache
+            .write("key".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        assert_eq!(cache.read("key".into()).await.unwrap(), "value");
+
+        corrupt(&dir, "key").await;
+        cache
+            .write_if_absent("key".into(), "value".into(), |_, _| false)
+            .await
+            .unwrap();
+        assert_eq!(cache.read("key".into()).await.unwrap(), "value");
+    }
+
+    #[tokio::test]
+    async fn entries_without_digests_are_trusted() {
+        let (cache, dir) = spawn_in_temp_dir("dir_cache_legacy").await;
+        cache
+            .write("key".into(), "value".into(), |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        let file = dir.file("key.json");
+        let mut entry = file
+            .read_json::<CacheEntry<String, String>>()
+            .await
+            .unwrap();
+        entry.digest = None;
+        file.write_json(&entry, WriteOptions::OVERWRITE_NONATOMIC)
+            .await
+            .unwrap();
+
+        assert_eq!(cache.read("key".into()).await.unwrap(), "value");
+        assert!(cache.verify_all().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn verify_all_deletes_corrupted_entries() {
+        let (cache, dir) = spawn_in_temp_dir("dir_cache_verify_all").await;
+        for i in 0..3 {
+            cache
+                .write(
+                    format!("key{i}"),
+                    format!("value{i}"),
+                    |_, _| false,
+                    Overwrite::Allow,
+                )
+                .await
+                .unwrap();
+        }
+        corrupt(&dir, "key1").await;
+        let unparseable = dir.file("key2.json");
+        unparseable
+            .write_string(
+                "{\"key\": \"key2\", \"val",
+                WriteOptions::OVERWRITE_NONATOMIC,
+            )
+            .await
+            .unwrap();
+
+        let corrupted = cache.verify_all().await.unwrap();
+        assert_eq!(corrupted, vec!["key1".to_string()]);
+        assert!(!unparseable.exists());
+        assert_eq!(cache.read_optional("key1".into()).await.unwrap(), None);
+        assert_eq!(cache.read("key0".into()).await.unwrap(), "value0");
+
+        // a second pass finds nothing
+        assert!(cache.verify_all().await.unwrap().is_empty());
+    }
+
+    pub mod max_bytes {
+        use super::*;
+
+        fn value(i: usize) -> String {
+            format!("{i}").repeat(1000)
+        }
+
+        /// The size of an entry with one of the test values.
+        async fn entry_bytes() -> usize {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_entry_bytes").await;
+            cache
+                .write("key".into(), value(0), |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+            cache.read_entry("key".into()).await.unwrap().bytes()
+        }
+
+        async fn write(cache: &TestCache, i: usize) {
+            cache
+                .write(format!("key{i}"), value(i), |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn size_bytes_sums_the_entry_files() {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_size_bytes").await;
+            assert_eq!(cache.size_bytes().await.unwrap(), 0);
+            for i in 0..3 {
+                write(&cache, i).await;
+            }
+
+            let entries = cache.entries().await.unwrap();
+            let expected: usize = entries.iter().map(CacheEntry::bytes).sum();
+            assert_eq!(cache.size_bytes().await.unwrap(), expected);
+        }
+
+        #[tokio::test]
+        async fn writes_evict_the_least_recently_read() {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_max_bytes").await;
+            let max_bytes = entry_bytes().await * 7 / 2;
+            cache.set_max_bytes(Some(max_bytes)).await.unwrap();
+            for i in 0..3 {
+                write(&cache, i).await;
+            }
+            cache.read("key0".into()).await.unwrap();
+
+            write(&cache, 3).await;
+
+            assert_eq!(cache.read_optional("key1".into()).await.unwrap(), None);
+            for i in [0, 2, 3] {
+                assert_eq!(cache.read(format!("key{i}")).await.unwrap(), value(i));
+            }
+            assert!(cache.size_bytes().await.unwrap() <= max_bytes);
+        }
+
+        #[tokio::test]
+        async fn an_entry_over_the_limit_is_kept_until_the_next_write() {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_max_bytes_large").await;
+            cache.set_max_bytes(Some(100)).await.unwrap();
+
+            write(&cache, 0).await;
+            assert_eq!(cache.read("key0".into()).await.unwrap(), value(0));
+
+            write(&cache, 1).await;
+            assert_eq!(cache.read_optional("key0".into()).await.unwrap(), None);
+            assert_eq!(cache.read("key1".into()).await.unwrap(), value(1));
+        }
+
+        #[tokio::test]
+        async fn lowering_the_limit_prunes() {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_max_bytes_lowered").await;
+            for i in 0..4 {
+                write(&cache, i).await;
+            }
+            assert_eq!(cache.size().await.unwrap(), 4);
+
+            cache
+                .set_max_bytes(Some(entry_bytes().await * 5 / 2))
+                .await
+                .unwrap();
+
+            let mut keys: Vec<_> = cache
+                .entries()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|e| e.key)
+                .collect();
+            keys.sort();
+            assert_eq!(keys, vec!["key2".to_string(), "key3".to_string()]);
+        }
+
+        #[tokio::test]
+        async fn pinned_entries_are_spared() {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_max_bytes_pinned").await;
+            cache
+                .set_max_bytes(Some(entry_bytes().await * 5 / 2))
+                .await
+                .unwrap();
+            write(&cache, 0).await;
+            cache.pin("key0".into()).await.unwrap();
+            for i in 1..4 {
+                write(&cache, i).await;
+            }
+
+            assert_eq!(cache.read("key0".into()).await.unwrap(), value(0));
+            assert_eq!(cache.read_optional("key1".into()).await.unwrap(), None);
+            assert_eq!(cache.read("key3".into()).await.unwrap(), value(3));
+        }
+
+        #[tokio::test]
+        async fn no_limit_keeps_every_entry() {
+            let (cache, _dir) = spawn_in_temp_dir("dir_cache_no_max_bytes").await;
+            cache.set_max_bytes(Some(100)).await.unwrap();
+            cache.set_max_bytes(None).await.unwrap();
+            for i in 0..5 {
+                write(&cache, i).await;
+            }
+            assert_eq!(cache.size().await.unwrap(), 5);
+        }
+    }
 }
 
 pub mod single_thread {
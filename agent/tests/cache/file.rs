@@ -1,8 +1,9 @@
 // internal crates
 use crate::concurrent_cache_tests;
 use crate::single_thread_cache_tests;
+use miru_agent::cache::file::{quarantine_file, Quarantined};
 use miru_agent::cache::{FileCache, SingleThreadFileCache};
-use miru_agent::filesys::{self, PathExt};
+use miru_agent::filesys::{self, Overwrite, PathExt, WriteOptions};
 
 // external crates
 use tokio::task::JoinHandle;
@@ -82,3 +83,144 @@ pub mod single_thread {
 
     single_thread_cache_tests!(new_cache, new_cache_with_capacity);
 }
+
+pub mod integrity {
+    use super::*;
+    use serde_json::{json, Value};
+
+    type TestCache = FileCache<String, String>;
+
+    /// Writes a cache file with the given entries through a cache, which is shut down.
+    async fn seed(name: &str, keys: &[&str]) -> filesys::File {
+        let file = filesys::Dir::create_temp_dir(name)
+            .await
+            .unwrap()
+            .file("cache.json");
+        let (cache, handle) = TestCache::spawn(32, file.clone(), 1000).await.unwrap();
+        for key in keys {
+            cache
+                .write(
+                    key.to_string(),
+                    format!("{key}-value"),
+                    |_, _| false,
+                    Overwrite::Allow,
+                )
+                .await
+                .unwrap();
+        }
+        cache.shutdown().await.unwrap();
+        handle.await.unwrap();
+        file
+    }
+
+    async fn quarantined(file: &filesys::File) -> Vec<Quarantined> {
+        let sidecar = quarantine_file(file);
+        if !sidecar.exists() {
+            return Vec::new();
+        }
+        sidecar
+            .read_string()
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn valid_file_is_untouched() {
+        let file = seed("file_cache_valid", &["a", "b"]).await;
+        let before = file.read_bytes().await.unwrap();
+        let modified = file.last_modified().await.unwrap();
+
+        let (cache, _) = TestCache::spawn(32, file.clone(), 1000).await.unwrap();
+        assert_eq!(cache.size().await.unwrap(), 2);
+        assert_eq!(file.read_bytes().await.unwrap(), before);
+        assert_eq!(file.last_modified().await.unwrap(), modified);
+        assert!(!quarantine_file(&file).exists());
+    }
+
+    #[tokio::test]
+    async fn corrupt_entries_are_quarantined() {
+        let file = seed("file_cache_corrupt_entry", &["a", "b", "c"]).await;
+        let mut entries = file
+            .read_json::<serde_json::Map<String, Value>>()
+            .await
+            .unwrap();
+        entries.insert("b".to_string(), json!({"key": "b", "value": 42}));
+        let moved = entries["c"].clone();
+        entries.insert("d".to_string(), moved);
+        file.write_json(&entries, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let (cache, _) = TestCache::spawn(32, file.clone(), 1000).await.unwrap();
+        assert_eq!(cache.read("a".to_string()).await.unwrap(), "a-value");
+        assert_eq!(cache.read("c".to_string()).await.unwrap(), "c-value");
+        assert_eq!(cache.size().await.unwrap(), 2);
+
+        let mut quarantined = quarantined(&file).await;
+        quarantined.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(quarantined.len(), 2);
+        assert_eq!(quarantined[0].key.as_deref(), Some("b"));
+        assert_eq!(quarantined[0].raw, json!({"key": "b", "value": 42}));
+        assert_eq!(quarantined[1].key.as_deref(), Some("d"));
+        assert!(
+            quarantined[1].error.contains("wrong key"),
+            "{:?}",
+            quarantined[1]
+        );
+
+        // the file is compacted to the valid entries
+        let entries = file
+            .read_json::<serde_json::Map<String, Value>>()
+            .await
+            .unwrap();
+        let mut keys: Vec<&String> = entries.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn truncated_file_starts_empty() {
+        let file = seed("file_cache_truncated", &["a", "b"]).await;
+        let bytes = file.read_bytes().await.unwrap();
+        let truncated = &bytes[..bytes.len() / 2];
+        file.write_bytes(truncated, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let (cache, _) = TestCache::spawn(32, file.clone(), 1000).await.unwrap();
+        assert_eq!(cache.size().await.unwrap(), 0);
+        cache
+            .write(
+                "a".to_string(),
+                "new".to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        assert_eq!(cache.read("a".to_string()).await.unwrap(), "new");
+
+        let quarantined = quarantined(&file).await;
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].key, None);
+        assert_eq!(
+            quarantined[0].raw,
+            Value::String(String::from_utf8(truncated.to_vec()).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn quarantines_accumulate() {
+        let file = seed("file_cache_quarantines_accumulate", &["a"]).await;
+        for _ in 0..2 {
+            file.write_string("{\"a\": tru", WriteOptions::OVERWRITE_ATOMIC)
+                .await
+                .unwrap();
+            TestCache::spawn(32, file.clone(), 1000).await.unwrap();
+        }
+        assert_eq!(quarantined(&file).await.len(), 2);
+    }
+}
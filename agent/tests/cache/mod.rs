@@ -1,3 +1,4 @@
+pub mod admission;
 pub mod concurrent;
 pub mod dir;
 pub mod errors;
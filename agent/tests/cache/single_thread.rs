@@ -496,7 +496,11 @@ pub mod read_entry_optional {
             created_at: read_entry.created_at,
             last_accessed: read_entry.last_accessed,
             is_dirty: false,
+            probation: None,
+            digest: read_entry.digest.clone(),
+            pinned: false,
         };
+        assert!(read_entry.is_intact());
         assert_eq!(read_entry, expected_entry);
     }
 }
@@ -554,7 +558,11 @@ pub mod read_entry {
             created_at: read_entry.created_at,
             last_accessed: read_entry.last_accessed,
             is_dirty: false,
+            probation: None,
+            digest: read_entry.digest.clone(),
+            pinned: false,
         };
+        assert!(read_entry.is_intact());
         assert_eq!(read_entry, expected_entry);
     }
 }
@@ -0,0 +1,178 @@
+pub mod proxy;
+
+// internal crates
+use miru_agent::cell::{Options, PeerCredential, Peers, Role, Upstream};
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::http::request::Headers;
+use miru_agent::storage::Cell;
+
+fn credential(id: &str, token: &str) -> PeerCredential {
+    PeerCredential {
+        id: id.to_string(),
+        token_digest: Digest::compute(Algorithm::Sha256, token.as_bytes()),
+    }
+}
+
+pub mod peers {
+    use super::*;
+
+    #[test]
+    fn authenticates_known_peers() {
+        let peers = Peers::new(vec![
+            credential("robot-1", "token-1"),
+            credential("robot-2", "token-2"),
+        ]);
+        assert!(peers.authenticate("robot-1", "token-1"));
+        assert!(peers.authenticate("robot-2", "token-2"));
+    }
+
+    #[test]
+    fn rejects_wrong_tokens() {
+        let peers = Peers::new(vec![
+            credential("robot-1", "token-1"),
+            credential("robot-2", "token-2"),
+        ]);
+        // another peer's token
+        assert!(!peers.authenticate("robot-1", "token-2"));
+        assert!(!peers.authenticate("robot-1", ""));
+        assert!(!peers.authenticate("robot-3", "token-1"));
+    }
+
+    #[test]
+    fn digests_with_the_credentials_algorithm() {
+        let peers = Peers::new(vec![PeerCredential {
+            id: "robot-1".to_string(),
+            token_digest: Digest::compute(Algorithm::Blake3, b"token-1"),
+        }]);
+        assert!(peers.authenticate("robot-1", "token-1"));
+        assert!(!peers.authenticate("robot-1", "token-2"));
+    }
+
+    #[test]
+    fn no_peers() {
+        let peers = Peers::default();
+        assert!(peers.is_empty());
+        assert!(!peers.authenticate("robot-1", "token-1"));
+    }
+}
+
+pub mod options {
+    use super::*;
+
+    fn peer_settings() -> Cell {
+        Cell {
+            role: Role::Peer,
+            controller_url: Some("http://10.0.0.1:8443/".to_string()),
+            peer_id: Some("robot-1".to_string()),
+            token: Some("token-1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn standalone_by_default() {
+        let options = Options::new(&Cell::default());
+        assert_eq!(options, Options::Standalone);
+        assert_eq!(options.role(), Role::Standalone);
+        assert_eq!(options.upstream(), None);
+    }
+
+    #[test]
+    fn controller() {
+        let settings = Cell {
+            role: Role::Controller,
+            listen_addr: Some("10.0.0.1:8443".to_string()),
+            peers: vec![credential("robot-1", "token-1")],
+            ..Default::default()
+        };
+        let options = Options::new(&settings);
+        assert_eq!(
+            options,
+            Options::Controller {
+                listen_addr: "10.0.0.1:8443".to_string(),
+                peers: Peers::new(vec![credential("robot-1", "token-1")]),
+            }
+        );
+        assert_eq!(options.role(), Role::Controller);
+        assert_eq!(options.upstream(), None);
+    }
+
+    #[test]
+    fn controller_without_listen_addr_is_standalone() {
+        let settings = Cell {
+            role: Role::Controller,
+            peers: vec![credential("robot-1", "token-1")],
+            ..Default::default()
+        };
+        assert_eq!(Options::new(&settings), Options::Standalone);
+    }
+
+    #[test]
+    fn peer() {
+        let options = Options::new(&peer_settings());
+        let expected = Upstream {
+            url: "http://10.0.0.1:8443".to_string(),
+            peer_id: "robot-1".to_string(),
+            token: "token-1".to_string(),
+        };
+        assert_eq!(options.role(), Role::Peer);
+        assert_eq!(options.upstream(), Some(&expected));
+    }
+
+    #[test]
+    fn incomplete_peer_is_standalone() {
+        let missing_token = Cell {
+            token: None,
+            ..peer_settings()
+        };
+        assert_eq!(Options::new(&missing_token), Options::Standalone);
+
+        let missing_id = Cell {
+            peer_id: None,
+            ..peer_settings()
+        };
+        assert_eq!(Options::new(&missing_id), Options::Standalone);
+
+        for url in [None, Some("ftp://10.0.0.1"), Some("not a url")] {
+            let settings = Cell {
+                controller_url: url.map(str::to_string),
+                ..peer_settings()
+            };
+            assert_eq!(Options::new(&settings), Options::Standalone, "{url:?}");
+        }
+    }
+
+    #[test]
+    fn upstream_debug_redacts_the_token() {
+        let Options::Peer(upstream) = Options::new(&peer_settings()) else {
+            panic!("expected a peer");
+        };
+        let debug = format!("{upstream:?}");
+        assert!(debug.contains("robot-1"));
+        assert!(!debug.contains("token-1"));
+    }
+}
+
+pub mod headers {
+    use super::*;
+
+    #[test]
+    fn cell_peer_headers() {
+        let headers = Headers::default().to_map().unwrap();
+        assert!(headers.get("Miru-Cell-Peer").is_none());
+        assert!(headers.get("Miru-Cell-Token").is_none());
+
+        let headers = Headers {
+            cell_peer: Some(Upstream {
+                url: "http://10.0.0.1:8443".to_string(),
+                peer_id: "robot-1".to_string(),
+                token: "token-1".to_string(),
+            }),
+            ..Default::default()
+        }
+        .to_map()
+        .unwrap();
+        assert_eq!(headers["Miru-Cell-Peer"], "robot-1");
+        assert_eq!(headers["Miru-Cell-Token"], "token-1");
+    }
+}
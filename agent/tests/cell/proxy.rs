@@ -0,0 +1,280 @@
+// standard crates
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::cell::{proxy, PeerCredential, Peers, Upstream};
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::filesys;
+use miru_agent::http::{self, request, ClientI};
+use miru_agent::network::egress;
+
+// external crates
+use axum::extract::{Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::response::IntoResponse;
+use axum::Router;
+use serde_json::{json, Value};
+
+const PEER: &str = "robot-1";
+const TOKEN: &str = "token-1";
+
+#[derive(Default)]
+struct Backend {
+    requests: AtomicUsize,
+}
+
+/// Echoes what the backend received so the tests can check what was forwarded.
+async fn echo(State(backend): State<Arc<Backend>>, req: Request) -> impl IntoResponse {
+    backend.requests.fetch_add(1, Ordering::SeqCst);
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .map(|v| v.to_str().unwrap().to_string())
+    };
+    let body = json!({
+        "method": req.method().as_str(),
+        "path": req.uri().path(),
+        "query": req.uri().query(),
+        "authorization": header("authorization"),
+        "miru_version": header("miru-version"),
+        "cell_peer": header("miru-cell-peer"),
+        "cell_token": header("miru-cell-token"),
+    });
+    ([(header::ETAG, "\"v1\"")], body.to_string())
+}
+
+struct Cell {
+    backend: Arc<Backend>,
+    proxy: mock::Server,
+    _backend_server: mock::Server,
+}
+
+/// Serves the proxy for a single peer in front of an echoing backend whose API is
+/// under `/agent/v1`.
+async fn cell() -> Cell {
+    let backend = Arc::new(Backend::default());
+    let backend_server =
+        mock::run_server(Router::new().fallback(echo).with_state(backend.clone())).await;
+    let peers = Peers::new(vec![PeerCredential {
+        id: PEER.to_string(),
+        token_digest: Digest::compute(Algorithm::Sha256, TOKEN.as_bytes()),
+    }]);
+    let backend_url = format!("{}/agent/v1/", backend_server.base_url);
+    let state =
+        Arc::new(proxy::State::new(&backend_url, peers, &egress::Options::default()).unwrap());
+    let proxy = mock::run_server(proxy::routes(state)).await;
+    Cell {
+        backend,
+        proxy,
+        _backend_server: backend_server,
+    }
+}
+
+fn peer_client(cell: &Cell, token: &str) -> http::Client {
+    http::Client::new("https://api.mirurobotics.com/agent/v1")
+        .unwrap()
+        .with_upstream(Upstream {
+            url: cell.proxy.base_url.clone(),
+            peer_id: PEER.to_string(),
+            token: token.to_string(),
+        })
+}
+
+pub mod allowed {
+    use super::*;
+
+    #[test]
+    fn sync_requests() {
+        for (method, path) in [
+            (Method::POST, "/devices/token"),
+            (Method::GET, "/device"),
+            (Method::PATCH, "/devices/dvc_1"),
+            (Method::GET, "/devices/dvc_1/sync"),
+            (Method::GET, "/deployments"),
+            (Method::GET, "/deployments/dpl_1"),
+            (Method::PATCH, "/deployments/dpl_1"),
+            (Method::GET, "/config_instances/cfg_1/content"),
+            (Method::GET, "/config_instances/cfg_1/content/patch"),
+            (Method::GET, "/releases/rls_1"),
+            (Method::GET, "/git_commits/gc_1"),
+        ] {
+            assert!(proxy::allowed(&method, path), "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn other_requests() {
+        for (method, path) in [
+            (Method::POST, "/devices/provision"),
+            (Method::POST, "/devices/reprovision"),
+            (Method::DELETE, "/deployments/dpl_1"),
+            (Method::GET, "/devices"),
+            (Method::GET, "/deployments/dpl_1/extra"),
+            (Method::GET, "/"),
+            (Method::GET, "/deployments/"),
+            (Method::GET, "/deployments//"),
+            (Method::GET, "/releases/.."),
+            (Method::GET, "/config_instances/../content"),
+            (Method::GET, "/deployments/%2e%2e"),
+            (Method::GET, "/devices/%2E%2E/sync"),
+            (
+                Method::GET,
+                "/config_instances/cfg_inst_1%2F..%2F..%2Fdevices/content",
+            ),
+        ] {
+            assert!(!proxy::allowed(&method, path), "{method} {path}");
+        }
+    }
+}
+
+pub mod forward {
+    use super::*;
+
+    #[tokio::test]
+    async fn authenticated_request() {
+        let cell = cell().await;
+        let client = peer_client(&cell, TOKEN);
+        assert_eq!(client.base_url(), cell.proxy.base_url);
+
+        let url = format!("{}/deployments", client.base_url());
+        let params = request::Params::get(&url)
+            .with_query(http::query::QueryParams::new().add("limit", "1"))
+            .with_token("device-token");
+        let (body, _) = client.execute(params).await.unwrap();
+        let echo: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(echo["method"], "GET");
+        assert_eq!(echo["path"], "/agent/v1/deployments");
+        assert_eq!(echo["query"], "limit=1");
+        // the backend authenticates the peer's own device token
+        assert_eq!(echo["authorization"], "Bearer device-token");
+        assert!(echo["miru_version"].is_string());
+        // the proxy credentials stay on the cell's subnet
+        assert!(echo["cell_peer"].is_null());
+        assert!(echo["cell_token"].is_null());
+        assert_eq!(cell.backend.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn response_headers_are_forwarded() {
+        let cell = cell().await;
+        let resp = reqwest::Client::new()
+            .get(format!("{}/device", cell.proxy.base_url))
+            .header("Miru-Cell-Peer", PEER)
+            .header("Miru-Cell-Token", TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::ETAG], "\"v1\"");
+    }
+
+    #[tokio::test]
+    async fn unauthenticated_peers_are_rejected() {
+        let cell = cell().await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/device", cell.proxy.base_url);
+
+        let resp = client.get(&url).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = client
+            .get(&url)
+            .header("Miru-Cell-Peer", PEER)
+            .header("Miru-Cell-Token", "token-2")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unauthenticated");
+
+        // the agent's client reports the rejection like a backend error
+        let peer = peer_client(&cell, "token-2");
+        let url = format!("{}/device", peer.base_url());
+        assert!(peer.execute(request::Params::get(&url)).await.is_err());
+        assert_eq!(cell.backend.requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn disallowed_requests_are_rejected() {
+        let cell = cell().await;
+        let resp = reqwest::Client::new()
+            .post(format!("{}/devices/provision", cell.proxy.base_url))
+            .header("Miru-Cell-Peer", PEER)
+            .header("Miru-Cell-Token", TOKEN)
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "forbidden");
+        assert_eq!(cell.backend.requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn unreachable_backend() {
+        let peers = Peers::new(vec![PeerCredential {
+            id: PEER.to_string(),
+            token_digest: Digest::compute(Algorithm::Sha256, TOKEN.as_bytes()),
+        }]);
+        let state = Arc::new(
+            proxy::State::new("http://127.0.0.1:1", peers, &egress::Options::default()).unwrap(),
+        );
+        let proxy = mock::run_server(proxy::routes(state)).await;
+        let resp = reqwest::Client::new()
+            .get(format!("{}/device", proxy.base_url))
+            .header("Miru-Cell-Peer", PEER)
+            .header("Miru-Cell-Token", TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn forwards_through_the_egress_proxy() {
+        // the egress proxy answers for the unreachable backend
+        let egress_proxy = Arc::new(Backend::default());
+        let egress_server = mock::run_server(
+            Router::new()
+                .fallback(echo)
+                .with_state(egress_proxy.clone()),
+        )
+        .await;
+        let peers = Peers::new(vec![PeerCredential {
+            id: PEER.to_string(),
+            token_digest: Digest::compute(Algorithm::Sha256, TOKEN.as_bytes()),
+        }]);
+        let options = egress::Options {
+            proxy: Some(egress::Proxy::new(&egress_server.base_url).unwrap()),
+            ..Default::default()
+        };
+        let state = Arc::new(proxy::State::new("http://127.0.0.1:1", peers, &options).unwrap());
+        let proxy = mock::run_server(proxy::routes(state)).await;
+        let resp = reqwest::Client::new()
+            .get(format!("{}/device", proxy.base_url))
+            .header("Miru-Cell-Peer", PEER)
+            .header("Miru-Cell-Token", TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(egress_proxy.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_ca_bundle() {
+        let dir = filesys::Dir::create_temp_dir("cell-proxy-ca-bundle")
+            .await
+            .unwrap();
+        let options = egress::Options {
+            ca_bundle: Some(dir.file("missing.pem")),
+            ..Default::default()
+        };
+        let result = proxy::State::new("http://127.0.0.1:1", Peers::new(Vec::new()), &options);
+        assert!(result.is_err());
+    }
+}
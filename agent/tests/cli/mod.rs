@@ -1,246 +1,929 @@
 // internal crates
-use miru_agent::cli::{Args, ProvisionArgs, ReprovisionArgs};
+use miru_agent::cli::{
+    self, status::Status, CliErr, Command, DeploymentsAction, DeploymentsArgs, FsckArgs,
+    InstallArgs, ProvisionArgs, ReplayArgs, ReprovisionArgs, RunArgs, StatusArgs,
+    SupportBundleArgs, SyncHistoryArgs, TrashAction, TrashArgs, UninstallArgs, WebhookKeysArgs,
+    DEFAULT_WEBHOOK_KEY_GRACE_SECS,
+};
+use miru_agent::config::compat;
+use miru_agent::storage::fsck::Severity;
 
 fn to_inputs(values: &[&str]) -> Vec<String> {
     values.iter().map(|value| value.to_string()).collect()
 }
 
-mod args_parse {
-    use super::*;
+fn parse(values: &[&str]) -> Result<Command, CliErr> {
+    let mut inputs = vec!["miru-agent"];
+    inputs.extend_from_slice(values);
+    cli::parse(&to_inputs(&inputs))
+}
 
-    #[test]
-    fn parses_version_and_provision_with_provision_args() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--version",
-            "provision",
-            "--backend-host=https://backend.example.com",
-            "--mqtt-broker-host=mqtt.example.com",
-            "--device-name=robot-1",
-        ]);
+fn overrides(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(field, value)| (field.to_string(), value.to_string()))
+        .collect()
+}
 
-        let args = Args::parse(&inputs);
+mod parse_commands {
+    use super::*;
 
-        assert!(args.display_version);
-        assert!(args.provision_args.is_some());
+    #[test]
+    fn empty_input_runs_the_agent() {
+        assert_eq!(Ok(Command::Run(RunArgs::default())), cli::parse(&[]));
+        assert_eq!(Ok(Command::Run(RunArgs::default())), parse(&[]));
+    }
 
-        let provision_args = args
-            .provision_args
-            .expect("provision args should be present");
+    #[test]
+    fn run_with_settings_overrides() {
+        let command = parse(&[
+            "run",
+            "--set=log_level=debug",
+            "--set",
+            "backend.base_url=x=y",
+        ]);
         assert_eq!(
-            Some("https://backend.example.com"),
-            provision_args.backend_host.as_deref()
+            Ok(Command::Run(RunArgs {
+                settings_overrides: overrides(&[
+                    ("log_level", "debug"),
+                    ("backend.base_url", "x=y")
+                ]),
+                data_dir: None,
+                record: None,
+            })),
+            command
         );
+    }
+
+    #[test]
+    fn settings_overrides_without_a_command_run_the_agent() {
+        let command = parse(&["--set=log_level=debug"]);
         assert_eq!(
-            Some("mqtt.example.com"),
-            provision_args.mqtt_broker_host.as_deref()
+            Ok(Command::Run(RunArgs {
+                settings_overrides: overrides(&[("log_level", "debug")]),
+                data_dir: None,
+                record: None,
+            })),
+            command
         );
-        assert_eq!(Some("robot-1"), provision_args.device_name.as_deref());
     }
 
     #[test]
-    fn ignores_provision_options_without_provision_flag() {
-        let inputs = to_inputs(&[
-            "miru-agent",
+    fn activate() {
+        let command = parse(&[
+            "activate",
             "--backend-host=https://backend.example.com",
-            "--mqtt-broker-host=mqtt.example.com",
+            "--mqtt-broker-host",
+            "mqtt.example.com",
+            "--device-name=robot-1",
         ]);
+        assert_eq!(
+            Ok(Command::Activate(ProvisionArgs {
+                backend_host: Some("https://backend.example.com".to_string()),
+                mqtt_broker_host: Some("mqtt.example.com".to_string()),
+                device_name: Some("robot-1".to_string()),
+                token: None,
+                data_dir: None,
+            })),
+            command
+        );
+    }
 
-        let args = Args::parse(&inputs);
+    #[test]
+    fn activate_with_token() {
+        for inputs in [
+            &["activate", "--token=abc"][..],
+            &["activate", "--activation-url", "abc"][..],
+            &["--activate", "--token", "abc"][..],
+        ] {
+            assert_eq!(
+                Ok(Command::Activate(ProvisionArgs {
+                    token: Some("abc".to_string()),
+                    ..Default::default()
+                })),
+                parse(inputs)
+            );
+        }
+    }
 
-        assert!(!args.display_version);
-        assert!(args.provision_args.is_none());
+    #[test]
+    fn provision_is_an_alias_of_activate() {
+        for inputs in [&["provision"][..], &["--provision"][..]] {
+            assert_eq!(
+                Ok(Command::Activate(ProvisionArgs::default())),
+                parse(inputs)
+            );
+        }
+        assert!(compat::recorded()
+            .iter()
+            .any(|d| d.kind == compat::Kind::Command && d.name == "provision"));
     }
 
     #[test]
-    fn empty_input_returns_defaults() {
-        let inputs: Vec<String> = vec![];
-        let args = Args::parse(&inputs);
+    fn current_names_are_not_deprecations() {
+        parse(&["reprovision", "--data-dir=/tmp/agent-a"]).unwrap();
+        assert!(!compat::recorded()
+            .iter()
+            .any(|d| d.name == "reprovision" || d.name == "data-dir"));
+    }
 
-        assert!(!args.display_version);
-        assert!(args.provision_args.is_none());
-        assert!(args.reprovision_args.is_none());
+    #[test]
+    fn empty_values_are_unset() {
+        let command = parse(&["activate", "--backend-host=", "--device-name="]);
+        assert_eq!(Ok(Command::Activate(ProvisionArgs::default())), command);
     }
 
     #[test]
-    fn parses_reprovision_subcommand_with_reprovision_args() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "reprovision",
-            "--backend-host=https://backend.example.com",
-            "--mqtt-broker-host=mqtt.example.com",
-        ]);
+    fn last_value_wins() {
+        let command = parse(&["reprovision", "--backend-host=a", "--backend-host=b"]);
+        assert_eq!(
+            Ok(Command::Reprovision(ReprovisionArgs {
+                backend_host: Some("b".to_string()),
+                mqtt_broker_host: None,
+                data_dir: None,
+            })),
+            command
+        );
+    }
 
-        let args = Args::parse(&inputs);
+    #[test]
+    fn install() {
+        assert_eq!(
+            Ok(Command::Install(InstallArgs::default())),
+            parse(&["install"])
+        );
+        assert_eq!(
+            Ok(Command::Install(InstallArgs {
+                output: Some("/etc/systemd/system/miru.service".into()),
+                ..Default::default()
+            })),
+            parse(&["install", "--output=/etc/systemd/system/miru.service"])
+        );
+        assert_eq!(
+            Ok(Command::Install(InstallArgs::default())),
+            parse(&["--service-definition"])
+        );
+    }
 
-        assert!(args.reprovision_args.is_some());
-        assert!(args.provision_args.is_none());
+    #[test]
+    fn install_systemd_options() {
+        assert_eq!(
+            Ok(Command::Install(InstallArgs {
+                hardening: true,
+                writable_paths: vec!["/etc/robot".into(), "/opt/robot".into()],
+                restart: Some("always".to_string()),
+                watchdog_sec: Some(30),
+                tmpfiles: Some("/etc/tmpfiles.d/miru.conf".into()),
+                ..Default::default()
+            })),
+            parse(&[
+                "install",
+                "--hardening",
+                "--writable-path=/etc/robot",
+                "--writable-path",
+                "/opt/robot",
+                "--restart=always",
+                "--watchdog=30",
+                "--tmpfiles=/etc/tmpfiles.d/miru.conf",
+            ])
+        );
+    }
+
+    #[test]
+    fn install_rejects_invalid_systemd_options() {
+        assert!(matches!(
+            parse(&["install", "--restart=sometimes"]),
+            Err(CliErr::InvalidValue {
+                flag: "restart",
+                ..
+            })
+        ));
+        for watchdog in ["0", "-5", "soon"] {
+            assert!(matches!(
+                parse(&["install", &format!("--watchdog={watchdog}")]),
+                Err(CliErr::InvalidValue {
+                    flag: "watchdog",
+                    ..
+                })
+            ));
+        }
+    }
 
-        let reprovision_args = args
-            .reprovision_args
-            .expect("reprovision args should be present");
+    #[test]
+    fn uninstall() {
+        for name in ["uninstall", "reset", "factory-reset"] {
+            assert_eq!(
+                Ok(Command::Uninstall(UninstallArgs::default())),
+                parse(&[name])
+            );
+        }
         assert_eq!(
-            Some("https://backend.example.com"),
-            reprovision_args.backend_host.as_deref()
+            Ok(Command::Uninstall(UninstallArgs {
+                yes: true,
+                keep_logs: true,
+                keep_registration: true,
+                force: true,
+                service: Some("miru-staging".to_string()),
+                data_dir: Some("/tmp/agent-a".into()),
+            })),
+            parse(&[
+                "uninstall",
+                "--yes",
+                "--keep-logs",
+                "--keep-registration",
+                "--force",
+                "--service=miru-staging",
+                "--data-dir=/tmp/agent-a",
+            ])
         );
+    }
+
+    #[test]
+    fn status_and_version() {
         assert_eq!(
-            Some("mqtt.example.com"),
-            reprovision_args.mqtt_broker_host.as_deref()
+            Ok(Command::Status(StatusArgs::default())),
+            parse(&["status"])
         );
+        assert_eq!(Ok(Command::Version), parse(&["version"]));
+        assert_eq!(Ok(Command::Version), parse(&["--version"]));
     }
 
     #[test]
-    fn ignores_reprovision_options_without_reprovision_flag() {
-        let inputs = to_inputs(&["miru-agent", "--backend-host=https://backend.example.com"]);
+    fn sync_history() {
+        assert_eq!(
+            Ok(Command::SyncHistory(SyncHistoryArgs::default())),
+            parse(&["sync-history"])
+        );
+        let Ok(Command::SyncHistory(args)) =
+            parse(&["sync-history", "--json", "--data-dir=/tmp/agent-a"])
+        else {
+            panic!("expected the sync-history command");
+        };
+        assert!(args.json);
+        assert_eq!(args.data_dir, Some("/tmp/agent-a".into()));
+    }
 
-        let args = Args::parse(&inputs);
+    #[test]
+    fn sync_once() {
+        assert_eq!(
+            Ok(Command::SyncOnce(RunArgs::default())),
+            parse(&["sync-once"])
+        );
+        assert_eq!(
+            Ok(Command::SyncOnce(RunArgs {
+                settings_overrides: overrides(&[("log_level", "debug")]),
+                data_dir: Some("/tmp/agent-a".into()),
+                record: None,
+            })),
+            parse(&[
+                "sync-once",
+                "--set=log_level=debug",
+                "--data-dir=/tmp/agent-a"
+            ])
+        );
+    }
 
-        assert!(args.reprovision_args.is_none());
+    #[test]
+    fn trash() {
+        assert_eq!(Ok(Command::Trash(TrashArgs::default())), parse(&["trash"]));
+        assert_eq!(
+            Ok(Command::Trash(TrashArgs {
+                json: true,
+                ..Default::default()
+            })),
+            parse(&["trash", "list", "--json"])
+        );
+        assert_eq!(
+            Ok(Command::Trash(TrashArgs {
+                action: TrashAction::Restore {
+                    entry: "20261018T101500000Z-cfg_inst_1".to_string(),
+                    force: true,
+                },
+                data_dir: Some("/tmp/agent-a".into()),
+                ..Default::default()
+            })),
+            parse(&[
+                "trash",
+                "restore",
+                "--entry=20261018T101500000Z-cfg_inst_1",
+                "--force",
+                "--data-dir=/tmp/agent-a"
+            ])
+        );
+        assert_eq!(
+            Ok(Command::Trash(TrashArgs {
+                action: TrashAction::Purge { entry: None },
+                ..Default::default()
+            })),
+            parse(&["trash", "purge"])
+        );
+        assert_eq!(
+            Ok(Command::Trash(TrashArgs {
+                action: TrashAction::Purge {
+                    entry: Some("abc".to_string())
+                },
+                ..Default::default()
+            })),
+            parse(&["trash", "purge", "--entry=abc"])
+        );
     }
 
     #[test]
-    fn recognizes_provision_and_reprovision_independently() {
-        let provision_only = to_inputs(&["miru-agent", "provision"]);
-        let provision_args = Args::parse(&provision_only);
-        assert!(provision_args.provision_args.is_some());
-        assert!(provision_args.reprovision_args.is_none());
+    fn deployments() {
+        assert_eq!(
+            Ok(Command::Deployments(DeploymentsArgs::default())),
+            parse(&["deployments"])
+        );
+        assert_eq!(
+            Ok(Command::Deployments(DeploymentsArgs {
+                json: true,
+                ..Default::default()
+            })),
+            parse(&["deployments", "status", "--json"])
+        );
+        assert_eq!(
+            Ok(Command::Deployments(DeploymentsArgs {
+                action: DeploymentsAction::Pause {
+                    reason: Some("INC-42".to_string()),
+                },
+                data_dir: Some("/tmp/agent-a".into()),
+                ..Default::default()
+            })),
+            parse(&[
+                "deployments",
+                "pause",
+                "--reason=INC-42",
+                "--data-dir=/tmp/agent-a"
+            ])
+        );
+        assert_eq!(
+            Ok(Command::Deployments(DeploymentsArgs {
+                action: DeploymentsAction::Pause { reason: None },
+                ..Default::default()
+            })),
+            parse(&["deployments", "pause"])
+        );
+        assert_eq!(
+            Ok(Command::Deployments(DeploymentsArgs {
+                action: DeploymentsAction::Resume,
+                ..Default::default()
+            })),
+            parse(&["deployments", "resume"])
+        );
+    }
 
-        let reprovision_only = to_inputs(&["miru-agent", "reprovision"]);
-        let reprovision_args = Args::parse(&reprovision_only);
-        assert!(reprovision_args.provision_args.is_none());
-        assert!(reprovision_args.reprovision_args.is_some());
+    #[test]
+    fn config_sources() {
+        let command = parse(&["--config-sources", "--set=log_level=info"]);
+        assert_eq!(
+            Ok(Command::ConfigSources(RunArgs {
+                settings_overrides: overrides(&[("log_level", "info")]),
+                data_dir: None,
+                record: None,
+            })),
+            command
+        );
     }
-}
 
-mod provision_args_parse {
-    use super::*;
+    #[test]
+    fn support_bundle() {
+        let expected = Ok(Command::SupportBundle(SupportBundleArgs {
+            dir: "/tmp/bundle".to_string(),
+            data_dir: None,
+        }));
+        assert_eq!(expected, parse(&["support-bundle", "/tmp/bundle"]));
+        assert_eq!(expected, parse(&["--support-bundle=/tmp/bundle"]));
+        assert_eq!(expected, parse(&["--support-bundle", "/tmp/bundle"]));
+    }
 
     #[test]
-    fn parses_known_key_value_options() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--backend-host=https://backend.example.com",
-            "--mqtt-broker-host=mqtt.example.com",
-            "--device-name=robot-1",
-        ]);
+    fn fsck() {
+        assert_eq!(Ok(Command::Fsck(FsckArgs::default())), parse(&["fsck"]));
+        assert_eq!(
+            Ok(Command::Fsck(FsckArgs {
+                fix: true,
+                level: Severity::Info,
+                json: true,
+                data_dir: None,
+            })),
+            parse(&["fsck", "--fix", "--level=info", "--json"])
+        );
+    }
 
-        let args = ProvisionArgs::parse(&inputs);
+    #[test]
+    fn run_with_record() {
+        assert_eq!(
+            Ok(Command::Run(RunArgs {
+                record: Some("/tmp/capture.json".into()),
+                ..Default::default()
+            })),
+            parse(&["run", "--record=/tmp/capture.json"])
+        );
+    }
 
+    #[test]
+    fn replay() {
+        assert_eq!(
+            Ok(Command::Replay(ReplayArgs {
+                capture: "/tmp/capture.json".into(),
+                ..Default::default()
+            })),
+            parse(&["replay", "/tmp/capture.json"])
+        );
         assert_eq!(
-            Some("https://backend.example.com"),
-            args.backend_host.as_deref()
+            Ok(Command::Replay(ReplayArgs {
+                capture: "/tmp/capture.json".into(),
+                apply: true,
+                json: true,
+                data_dir: Some("/tmp/replay".into()),
+            })),
+            parse(&[
+                "replay",
+                "/tmp/capture.json",
+                "--apply",
+                "--json",
+                "--data-dir=/tmp/replay"
+            ])
         );
-        assert_eq!(Some("mqtt.example.com"), args.mqtt_broker_host.as_deref());
-        assert_eq!(Some("robot-1"), args.device_name.as_deref());
     }
 
     #[test]
-    fn ignores_unknown_or_non_key_value_tokens() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--unknown=value",
-            "--backend-host=https://backend.example.com",
-            "--device-name",
-            "provision",
-        ]);
+    fn replay_requires_a_capture() {
+        assert_eq!(
+            Err(CliErr::MissingArg {
+                command: "replay",
+                name: "FILE",
+            }),
+            parse(&["replay"])
+        );
+    }
 
-        let args = ProvisionArgs::parse(&inputs);
+    #[test]
+    fn webhook_keys() {
+        let url = "https://example.com/alerts";
+        assert_eq!(
+            Ok(Command::WebhookKeys(WebhookKeysArgs {
+                url: url.to_string(),
+                rotate: false,
+                grace_secs: DEFAULT_WEBHOOK_KEY_GRACE_SECS,
+                revoke: false,
+                data_dir: None,
+            })),
+            parse(&["webhook-keys", url])
+        );
+        assert_eq!(
+            Ok(Command::WebhookKeys(WebhookKeysArgs {
+                url: url.to_string(),
+                rotate: true,
+                grace_secs: 600,
+                revoke: false,
+                data_dir: Some("/tmp/miru".into()),
+            })),
+            parse(&[
+                "webhook-keys",
+                url,
+                "--rotate",
+                "--grace=600",
+                "--data-dir=/tmp/miru"
+            ])
+        );
+    }
 
+    #[test]
+    fn webhook_keys_requires_a_url() {
         assert_eq!(
-            Some("https://backend.example.com"),
-            args.backend_host.as_deref()
+            Err(CliErr::MissingArg {
+                command: "webhook-keys",
+                name: "URL",
+            }),
+            parse(&["webhook-keys", "--rotate"])
         );
-        assert!(args.mqtt_broker_host.is_none());
-        assert!(args.device_name.is_none());
     }
 
     #[test]
-    fn last_duplicate_value_wins() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--backend-host=https://first.example.com",
-            "--backend-host=https://second.example.com",
-        ]);
+    fn webhook_keys_rejects_rotate_and_revoke() {
+        assert_eq!(
+            Err(CliErr::ConflictingFlags {
+                flag: "rotate",
+                other: "revoke",
+            }),
+            parse(&[
+                "webhook-keys",
+                "https://example.com",
+                "--rotate",
+                "--revoke"
+            ])
+        );
+    }
 
-        let args = ProvisionArgs::parse(&inputs);
+    #[test]
+    fn webhook_keys_rejects_invalid_grace() {
+        for grace in ["-1", "soon"] {
+            assert_eq!(
+                Err(CliErr::InvalidValue {
+                    flag: "grace",
+                    value: grace.to_string(),
+                    msg: "expected a number of seconds".to_string(),
+                }),
+                parse(&[
+                    "webhook-keys",
+                    "https://example.com",
+                    &format!("--grace={grace}")
+                ])
+            );
+        }
+    }
+}
 
+mod data_dir {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn is_accepted_by_every_command_which_reads_state() {
+        let dir = Some(PathBuf::from("/tmp/agent-a"));
         assert_eq!(
-            Some("https://second.example.com"),
-            args.backend_host.as_deref()
+            Ok(Command::Run(RunArgs {
+                data_dir: dir.clone(),
+                ..Default::default()
+            })),
+            parse(&["--data-dir=/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::Activate(ProvisionArgs {
+                data_dir: dir.clone(),
+                ..Default::default()
+            })),
+            parse(&["activate", "--data-dir", "/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::Reprovision(ReprovisionArgs {
+                data_dir: dir.clone(),
+                ..Default::default()
+            })),
+            parse(&["reprovision", "--data-dir=/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::Install(InstallArgs {
+                data_dir: dir.clone(),
+                ..Default::default()
+            })),
+            parse(&["install", "--data-dir=/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::Status(StatusArgs {
+                data_dir: dir.clone(),
+            })),
+            parse(&["status", "--data-dir=/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::ConfigSources(RunArgs {
+                data_dir: dir.clone(),
+                ..Default::default()
+            })),
+            parse(&["config-sources", "--data-dir=/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::SupportBundle(SupportBundleArgs {
+                dir: "/tmp/bundle".to_string(),
+                data_dir: dir,
+            })),
+            parse(&["support-bundle", "/tmp/bundle", "--data-dir=/tmp/agent-a"])
+        );
+        assert_eq!(
+            Ok(Command::Fsck(FsckArgs {
+                data_dir: Some(PathBuf::from("/tmp/agent-a")),
+                ..Default::default()
+            })),
+            parse(&["fsck", "--data-dir=/tmp/agent-a"])
         );
     }
 
     #[test]
-    fn empty_values_are_treated_as_none() {
-        let inputs = to_inputs(&["miru-agent", "--device-name="]);
-        let args = ProvisionArgs::parse(&inputs);
+    fn config_is_an_alias() {
+        assert_eq!(
+            Ok(Command::Run(RunArgs {
+                data_dir: Some(PathBuf::from("/tmp/agent-a")),
+                ..Default::default()
+            })),
+            parse(&["run", "--config=/tmp/agent-a"])
+        );
+        assert!(compat::recorded()
+            .iter()
+            .any(|d| d.kind == compat::Kind::Flag
+                && d.name == "config"
+                && d.replacement == "data-dir"));
+    }
 
-        assert!(args.device_name.is_none());
+    #[test]
+    fn relative_dirs_are_made_absolute() {
+        let Ok(Command::Status(args)) = parse(&["status", "--data-dir=agent-a"]) else {
+            panic!("expected the status command");
+        };
+        let expected = std::env::current_dir().unwrap().join("agent-a");
+        assert_eq!(Some(expected), args.data_dir);
+    }
+
+    #[test]
+    fn is_listed_in_help_with_its_alias() {
+        let Ok(Command::Help(help)) = parse(&["run", "--help"]) else {
+            panic!("expected help");
+        };
+        assert!(help.contains("--data-dir=<DIR>"), "{help}");
+        assert!(help.contains("(alias: --config)"), "{help}");
     }
 }
 
-mod reprovision_args_parse {
+mod parse_errors {
     use super::*;
 
     #[test]
-    fn parses_known_key_value_options() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--backend-host=https://backend.example.com",
-            "--mqtt-broker-host=mqtt.example.com",
-            "--device-name=robot-1",
-        ]);
-
-        let args = ReprovisionArgs::parse(&inputs);
+    fn unknown_command_is_rejected_with_a_suggestion() {
+        assert_eq!(
+            Err(CliErr::UnknownCommand {
+                command: "statsu".to_string(),
+                suggestion: Some("status"),
+            }),
+            parse(&["statsu"])
+        );
+        assert_eq!(
+            Err(CliErr::UnknownCommand {
+                command: "frobnicate".to_string(),
+                suggestion: None,
+            }),
+            parse(&["frobnicate"])
+        );
+    }
 
+    #[test]
+    fn unknown_flag_is_rejected_with_a_suggestion() {
+        let err = parse(&["activate", "--backend-hots=https://backend.example.com"]);
         assert_eq!(
-            Some("https://backend.example.com"),
-            args.backend_host.as_deref()
+            Err(CliErr::UnknownFlag {
+                command: "activate",
+                flag: "backend-hots".to_string(),
+                suggestion: Some("backend-host"),
+            }),
+            err
         );
-        assert_eq!(Some("mqtt.example.com"), args.mqtt_broker_host.as_deref());
-        // ReprovisionArgs has no device_name field by design — the value is silently ignored.
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("did you mean 'backend-host'"));
     }
 
     #[test]
-    fn ignores_unknown_or_non_key_value_tokens() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--unknown=value",
-            "--backend-host=https://backend.example.com",
-            "--mqtt-broker-host",
-            "reprovision",
-        ]);
+    fn unknown_flag_without_a_command_is_rejected() {
+        assert_eq!(
+            Err(CliErr::UnknownFlag {
+                command: "run",
+                flag: "sett".to_string(),
+                suggestion: Some("set"),
+            }),
+            parse(&["--sett=log_level=debug"])
+        );
+    }
 
-        let args = ReprovisionArgs::parse(&inputs);
+    #[test]
+    fn flags_of_other_commands_are_rejected() {
+        assert!(matches!(
+            parse(&["run", "--device-name=robot-1"]),
+            Err(CliErr::UnknownFlag { command: "run", .. })
+        ));
+    }
 
+    #[test]
+    fn missing_value() {
+        assert_eq!(
+            Err(CliErr::MissingValue {
+                flag: "backend-host"
+            }),
+            parse(&["activate", "--backend-host"])
+        );
         assert_eq!(
-            Some("https://backend.example.com"),
-            args.backend_host.as_deref()
+            Err(CliErr::MissingValue {
+                flag: "backend-host"
+            }),
+            parse(&["activate", "--backend-host", "--device-name=robot-1"])
+        );
+        assert_eq!(
+            Err(CliErr::MissingValue { flag: "entry" }),
+            parse(&["trash", "restore"])
         );
-        assert!(args.mqtt_broker_host.is_none());
     }
 
     #[test]
-    fn last_duplicate_value_wins() {
-        let inputs = to_inputs(&[
-            "miru-agent",
-            "--backend-host=https://first.example.com",
-            "--backend-host=https://second.example.com",
-        ]);
+    fn invalid_settings_override() {
+        assert!(matches!(
+            parse(&["run", "--set=log_level"]),
+            Err(CliErr::InvalidValue { flag: "set", .. })
+        ));
+        assert!(matches!(
+            parse(&["run", "--set==debug"]),
+            Err(CliErr::InvalidValue { flag: "set", .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_fsck_level() {
+        assert!(matches!(
+            parse(&["fsck", "--level=fatal"]),
+            Err(CliErr::InvalidValue { flag: "level", .. })
+        ));
+    }
 
-        let args = ReprovisionArgs::parse(&inputs);
+    #[test]
+    fn unexpected_argument() {
+        assert_eq!(
+            Err(CliErr::UnexpectedArg {
+                command: "status",
+                arg: "now".to_string(),
+            }),
+            parse(&["status", "now"])
+        );
+        assert_eq!(
+            Err(CliErr::UnexpectedArg {
+                command: "trash",
+                arg: "empty".to_string(),
+            }),
+            parse(&["trash", "empty"])
+        );
+        assert_eq!(
+            Err(CliErr::UnexpectedArg {
+                command: "deployments",
+                arg: "stop".to_string(),
+            }),
+            parse(&["deployments", "stop"])
+        );
+        assert!(matches!(
+            parse(&["support-bundle", "a", "b"]),
+            Err(CliErr::UnexpectedArg { .. })
+        ));
+    }
 
+    #[test]
+    fn missing_argument() {
         assert_eq!(
-            Some("https://second.example.com"),
-            args.backend_host.as_deref()
+            Err(CliErr::MissingArg {
+                command: "support-bundle",
+                name: "DIR",
+            }),
+            parse(&["support-bundle"])
         );
     }
+}
+
+mod help {
+    use super::*;
+
+    fn help_text(command: Result<Command, CliErr>) -> String {
+        match command {
+            Ok(Command::Help(help)) => help,
+            other => panic!("expected help, got {other:?}"),
+        }
+    }
 
     #[test]
-    fn empty_values_are_treated_as_none() {
-        let inputs = to_inputs(&["miru-agent", "--backend-host="]);
-        let args = ReprovisionArgs::parse(&inputs);
+    fn top_level_help_lists_the_commands() {
+        for inputs in [&["--help"][..], &["-h"][..], &["help"][..]] {
+            let help = help_text(parse(inputs));
+            for command in ["run", "activate", "install", "status", "version"] {
+                assert!(help.contains(command), "{command} missing from:\n{help}");
+            }
+        }
+    }
+
+    #[test]
+    fn command_help_lists_the_flags() {
+        for inputs in [&["activate", "--help"][..], &["help", "activate"][..]] {
+            let help = help_text(parse(inputs));
+            assert!(help.contains("--backend-host=<URL>"), "{help}");
+            assert!(help.contains("--device-name=<NAME>"), "{help}");
+            assert!(help.contains("Aliases: provision"), "{help}");
+        }
+    }
+
+    #[test]
+    fn help_is_printed_despite_other_flags() {
+        let help = help_text(parse(&["run", "--set=log_level=debug", "--help"]));
+        assert!(help.contains("--set=<FIELD=VALUE>"), "{help}");
+    }
+
+    #[test]
+    fn help_for_an_unknown_command() {
+        assert!(matches!(
+            parse(&["help", "instal"]),
+            Err(CliErr::UnknownCommand {
+                suggestion: Some("install"),
+                ..
+            })
+        ));
+    }
+}
+
+mod status {
+    use super::*;
+    use miru_agent::deploy::pause;
+    use miru_agent::diagnostics::crash;
+    use miru_agent::filesys::{Dir, WriteOptions};
+    use miru_agent::logs::RecentLines;
+    use miru_agent::models::Device;
+    use miru_agent::storage::{wear::Usage, Layout};
+
+    #[tokio::test]
+    async fn unactivated_device() {
+        let dir = Dir::create_temp_dir("cli-status").await.unwrap();
+        let layout = Layout::new(dir.clone());
+
+        let status = Status::read(&layout, &layout.auth().private_key().into()).await;
+        assert!(!status.activated);
+        assert!(status.device.is_none());
+        assert!(status.wear.is_none());
+
+        let output = status.to_string();
+        assert!(output.contains("Activated:      no"), "{output}");
+        assert!(output.contains("unknown"), "{output}");
+        assert!(output.contains("Last crash:     none"), "{output}");
+        assert!(output.contains("Deployments:    running"), "{output}");
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_whether_deployments_are_paused() {
+        let dir = Dir::create_temp_dir("cli-status").await.unwrap();
+        let layout = Layout::new(dir.clone());
+        pause::Switch::new(layout.deployments_paused())
+            .pause(Some("INC-42".to_string()), pause::Origin::Cli)
+            .await
+            .unwrap();
+
+        let status = Status::read(&layout, &layout.auth().private_key().into()).await;
+        assert!(status.deployments.paused);
+
+        let output = status.to_string();
+        assert!(
+            output.contains("Deployments:    paused (INC-42)"),
+            "{output}"
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_the_device_and_wear_files() {
+        let dir = Dir::create_temp_dir("cli-status").await.unwrap();
+        let layout = Layout::new(dir.clone());
+        let device = Device {
+            id: "dvc_123".to_string(),
+            name: "robot-1".to_string(),
+            ..Default::default()
+        };
+        layout
+            .device()
+            .write_json(&device, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let usage = Usage {
+            logs_bytes: 10,
+            caches_bytes: 20,
+            deployments_bytes: 30,
+            ..Usage::new(chrono::Utc::now())
+        };
+        layout
+            .wear()
+            .write_json(&usage, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let status = Status::read(&layout, &layout.auth().private_key().into()).await;
+        assert_eq!(Some(device), status.device);
+        assert_eq!(Some(usage), status.wear);
+
+        let output = status.to_string();
+        assert!(output.contains("Device ID:      dvc_123"), "{output}");
+        assert!(output.contains("Device name:    robot-1"), "{output}");
+        assert!(output.contains("Bytes written:  60"), "{output}");
+        dir.delete().await.unwrap();
+    }
 
-        assert!(args.backend_host.is_none());
+    #[tokio::test]
+    async fn reads_the_last_crash() {
+        let dir = Dir::create_temp_dir("cli-status").await.unwrap();
+        let layout = Layout::new(dir.clone());
+        let options = crash::Options {
+            layout: layout.crash(),
+            recent_lines: RecentLines::new(1),
+            redactor: None,
+            upload: true,
+        };
+        let report = crash::Report::capture("boom", None, &options);
+        crash::write(&layout.crash(), &report, true).unwrap();
+
+        let status = Status::read(&layout, &layout.auth().private_key().into()).await;
+        assert_eq!(Some(report.clone()), status.last_crash);
+
+        let output = status.to_string();
+        let expected = format!("Last crash:     {} (boom)", report.crashed_at.to_rfc3339());
+        assert!(output.contains(&expected), "{output}");
+        dir.delete().await.unwrap();
     }
 }
@@ -0,0 +1,154 @@
+// internal crates
+use miru_agent::clock::{self, Clock, LARGE_SKEW, TOLERANCE};
+use miru_agent::server::health::{self, Status};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, DATE};
+
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+}
+
+pub mod observe {
+    use super::*;
+
+    #[test]
+    fn starts_unchecked() {
+        let clock = Clock::new();
+        let status = clock.status();
+        assert_eq!(status.skew, TimeDelta::zero());
+        assert_eq!(status.checked_at, None);
+    }
+
+    #[test]
+    fn behind_the_backend() {
+        let clock = Clock::new();
+        // the device booted thinking it's years ago
+        clock.observe(at(0), at(-3 * 365 * 86_400), at(-3 * 365 * 86_400));
+        assert_eq!(clock.skew(), TimeDelta::days(3 * 365));
+        assert!(clock.status().is_large());
+
+        let corrected = clock.now() - Utc::now();
+        assert!((corrected - TimeDelta::days(3 * 365)).abs() < TimeDelta::seconds(1));
+    }
+
+    #[test]
+    fn ahead_of_the_backend() {
+        let clock = Clock::new();
+        clock.observe(at(0), at(300), at(300));
+        assert_eq!(clock.skew(), TimeDelta::seconds(-300));
+        assert_eq!(clock.status().checked_at, Some(at(0)));
+    }
+
+    #[test]
+    fn the_backend_answers_halfway_through_the_request() {
+        let clock = Clock::new();
+        // sent 10s before and received 10s after the backend's time
+        clock.observe(at(100), at(90), at(110));
+        assert_eq!(clock.skew(), TimeDelta::zero());
+
+        clock.observe(at(100), at(0), at(20));
+        assert_eq!(clock.skew(), TimeDelta::seconds(90));
+    }
+
+    #[test]
+    fn ignores_skews_within_the_tolerance() {
+        let clock = Clock::new();
+        clock.observe(at(1), at(0), at(0));
+        assert_eq!(clock.skew(), TimeDelta::zero());
+        assert_eq!(clock.status().checked_at, Some(at(1)));
+
+        clock.observe(at(120), at(0), at(0));
+        assert_eq!(clock.skew(), TimeDelta::seconds(120));
+        // a second's jitter doesn't move it
+        clock.observe(at(121), at(0), at(0));
+        assert_eq!(clock.skew(), TimeDelta::seconds(120));
+    }
+
+    #[test]
+    fn corrected_clocks_return_to_no_skew() {
+        let clock = Clock::new();
+        clock.observe(at(3600), at(0), at(0));
+        assert!(clock.status().is_large());
+
+        // NTP caught up
+        clock.observe(at(0), at(0), at(0));
+        assert_eq!(clock.skew(), TimeDelta::zero());
+        assert!(!clock.status().is_large());
+    }
+
+    #[test]
+    fn skews_up_to_a_minute_are_not_large() {
+        let clock = Clock::new();
+        clock.observe(at(0) + LARGE_SKEW - TOLERANCE, at(0), at(0));
+        assert!(!clock.status().is_large());
+        clock.observe(at(0) + LARGE_SKEW, at(0), at(0));
+        assert!(clock.status().is_large());
+    }
+}
+
+pub mod headers {
+    use super::*;
+
+    #[test]
+    fn parses_http_dates() {
+        let date = clock::parse_http_date("Tue, 14 Nov 2023 22:13:20 GMT").unwrap();
+        // the middle of the second
+        assert_eq!(date, at(0) + TimeDelta::milliseconds(500));
+
+        assert_eq!(clock::parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn observes_the_date_header() {
+        let clock = Clock::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            DATE,
+            HeaderValue::from_static("Tue, 14 Nov 2023 23:13:20 GMT"),
+        );
+        clock.observe_headers(&headers, at(0), at(0));
+        assert_eq!(clock.skew(), TimeDelta::milliseconds(3_600_500));
+    }
+
+    #[test]
+    fn ignores_missing_and_invalid_dates() {
+        let clock = Clock::new();
+        clock.observe_headers(&HeaderMap::new(), at(0), at(0));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(DATE, HeaderValue::from_static("not a date"));
+        clock.observe_headers(&headers, at(0), at(0));
+
+        assert_eq!(clock.status().checked_at, None);
+        assert_eq!(clock.skew(), TimeDelta::zero());
+    }
+}
+
+pub mod health_report {
+    use super::*;
+
+    #[test]
+    fn unknown_until_checked() {
+        let report = health::clock(Clock::new().status());
+        assert_eq!(report.status, Status::Unknown);
+        assert_eq!(report.skew_secs, 0);
+        assert_eq!(report.checked_at, None);
+    }
+
+    #[test]
+    fn large_skews_are_degraded() {
+        let clock = Clock::new();
+        clock.observe(at(30), at(0), at(0));
+        let report = health::clock(clock.status());
+        assert_eq!(report.status, Status::Ok);
+        assert_eq!(report.skew_secs, 30);
+
+        clock.observe(at(-7200), at(0), at(0));
+        let report = health::clock(clock.status());
+        assert_eq!(report.status, Status::Degraded);
+        assert_eq!(report.skew_secs, -7200);
+        assert_eq!(report.checked_at, Some(at(-7200)));
+    }
+}
@@ -0,0 +1,204 @@
+// internal crates
+use miru_agent::config::compat::{self, Deprecation, Kind, Rename};
+use miru_agent::config::{self, Layer, Source};
+
+// external crates
+use serde_json::json;
+
+fn layer(source: Source, value: serde_json::Value) -> Layer {
+    Layer::from_value(source, value).unwrap()
+}
+
+pub mod renames {
+    use super::*;
+
+    #[test]
+    fn settings_fields_are_renamed_to_existing_fields() {
+        let resolved = config::resolve(&[]).unwrap();
+        for rename in compat::SETTINGS_FIELDS {
+            assert!(
+                resolved.values.contains_key(rename.new),
+                "{} is renamed to a field which doesn't exist",
+                rename.old
+            );
+            assert!(!resolved.values.contains_key(rename.old));
+        }
+    }
+
+    #[test]
+    fn find_matches_old_names_only() {
+        assert_eq!(
+            compat::find(compat::CLI_FLAGS, "config").map(|r| r.new),
+            Some("data-dir")
+        );
+        assert!(compat::find(compat::CLI_FLAGS, "data-dir").is_none());
+    }
+}
+
+pub mod migrate_settings {
+    use super::*;
+
+    #[test]
+    fn moves_old_fields_to_new_paths() {
+        let mut values = json!({
+            "enable_mqtt": false,
+            "reboot": { "allowed": true, "paths": ["/etc/a.json"] },
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        let deprecations = compat::migrate_settings(&mut values, Source::File);
+
+        assert_eq!(
+            serde_json::Value::Object(values),
+            json!({
+                "enable_mqtt_worker": false,
+                "reboot": { "authorized": true, "paths": ["/etc/a.json"] },
+            })
+        );
+        let names: Vec<_> = deprecations.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["enable_mqtt", "reboot.allowed"]);
+        assert!(deprecations
+            .iter()
+            .all(|d| d.kind == Kind::Setting && d.source == Some(Source::File)));
+    }
+
+    #[test]
+    fn new_fields_take_precedence() {
+        let mut values = json!({ "enable_mqtt": false, "enable_mqtt_worker": true })
+            .as_object()
+            .unwrap()
+            .clone();
+
+        let deprecations = compat::migrate_settings(&mut values, Source::File);
+
+        assert_eq!(
+            serde_json::Value::Object(values),
+            json!({ "enable_mqtt_worker": true })
+        );
+        assert_eq!(deprecations.len(), 1);
+    }
+
+    #[test]
+    fn current_fields_are_untouched() {
+        let value = json!({ "enable_mqtt_worker": false, "reboot": { "authorized": true } });
+        let mut values = value.as_object().unwrap().clone();
+        assert!(compat::migrate_settings(&mut values, Source::File).is_empty());
+        assert_eq!(serde_json::Value::Object(values), value);
+    }
+}
+
+pub mod resolve {
+    use super::*;
+
+    #[test]
+    fn old_names_set_the_new_fields() {
+        let layers = [
+            layer(Source::File, json!({ "enable_mqtt": false })),
+            layer(
+                Source::Cli,
+                json!({ "safe_mode": { "crash_window_secs": 120 } }),
+            ),
+        ];
+
+        let resolved = config::resolve(&layers).unwrap();
+
+        assert!(!resolved.settings.enable_mqtt_worker);
+        assert_eq!(resolved.settings.safe_mode.window_secs, 120);
+        assert_eq!(resolved.source_of("enable_mqtt_worker"), Some(Source::File));
+        assert_eq!(
+            resolved.source_of("safe_mode.window_secs"),
+            Some(Source::Cli)
+        );
+        let deprecations: Vec<_> = resolved
+            .deprecations
+            .iter()
+            .map(|d| (d.name.as_str(), d.source))
+            .collect();
+        assert_eq!(
+            deprecations,
+            [
+                ("enable_mqtt", Some(Source::File)),
+                ("safe_mode.crash_window_secs", Some(Source::Cli)),
+            ]
+        );
+        assert!(compat::recorded()
+            .iter()
+            .any(|d| d.name == "safe_mode.crash_window_secs"));
+    }
+
+    #[test]
+    fn overrides_accept_old_names() {
+        let overrides = vec![("reboot.allowed".to_string(), "yes".to_string())];
+        let layer = Layer::from_overrides(Source::Cli, &overrides).unwrap();
+
+        let resolved = config::resolve(&[layer]).unwrap();
+
+        assert!(resolved.settings.reboot.authorized);
+        assert_eq!(resolved.deprecations.len(), 1);
+        assert_eq!(resolved.deprecations[0].replacement, "reboot.authorized");
+    }
+
+    #[test]
+    fn env_vars_accept_old_names() {
+        let vars = vec![("MIRU_AGENT_ENABLE_MQTT".to_string(), "false".to_string())];
+
+        let resolved = config::resolve(&[Layer::from_env(vars)]).unwrap();
+
+        assert!(!resolved.settings.enable_mqtt_worker);
+        assert_eq!(resolved.deprecations[0].source, Some(Source::Env));
+    }
+
+    #[test]
+    fn current_names_have_no_deprecations() {
+        let layers = [layer(Source::File, json!({ "enable_mqtt_worker": false }))];
+        assert!(config::resolve(&layers).unwrap().deprecations.is_empty());
+    }
+}
+
+pub mod record {
+    use super::*;
+
+    const RENAME: Rename = Rename {
+        old: "compat-test-old",
+        new: "compat-test-new",
+        since: "v0.0.1",
+    };
+
+    #[test]
+    fn deprecations_are_recorded_once() {
+        compat::record(Deprecation::new(Kind::Flag, &RENAME));
+        compat::record(Deprecation::new(Kind::Flag, &RENAME).with_source(Source::Cli));
+        compat::warn_pending();
+
+        let recorded: Vec<_> = compat::recorded()
+            .into_iter()
+            .filter(|d| d.name == RENAME.old)
+            .collect();
+        assert_eq!(recorded, [Deprecation::new(Kind::Flag, &RENAME)]);
+    }
+
+    #[test]
+    fn display_names_the_replacement() {
+        let deprecation = Deprecation::new(Kind::Setting, &RENAME).with_source(Source::Env);
+        assert_eq!(
+            deprecation.to_string(),
+            "the setting 'compat-test-old' is deprecated since v0.0.1, use 'compat-test-new' instead (given by the env source)"
+        );
+    }
+
+    #[test]
+    fn serializes_without_an_unknown_source() {
+        let deprecation = Deprecation::new(Kind::Command, &RENAME);
+        assert_eq!(
+            serde_json::to_value(&deprecation).unwrap(),
+            json!({
+                "kind": "command",
+                "name": "compat-test-old",
+                "replacement": "compat-test-new",
+                "since": "v0.0.1",
+            })
+        );
+    }
+}
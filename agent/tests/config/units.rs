@@ -0,0 +1,111 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::config::units::{
+    format_duration, format_size, parse_duration, parse_size, DURATION_FORMATS, SIZE_FORMATS,
+};
+
+pub mod duration {
+    use super::*;
+
+    #[test]
+    fn parses_plain_seconds() {
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration(" 300 ").unwrap(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn parses_units() {
+        let cases = [
+            ("500ms", Duration::from_millis(500)),
+            ("30s", Duration::from_secs(30)),
+            ("5m", Duration::from_secs(300)),
+            ("2h", Duration::from_secs(7200)),
+            ("1d", Duration::from_secs(86_400)),
+            ("1.5h", Duration::from_secs(5400)),
+            ("1h30m", Duration::from_secs(5400)),
+            ("1h 30m 15s", Duration::from_secs(5415)),
+            ("10 S", Duration::from_secs(10)),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_duration(raw).unwrap(), expected, "{raw}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for raw in ["", "m", "5x", "5 minutes", "-5s", "1h-30m"] {
+            let err = parse_duration(raw).unwrap_err();
+            assert_eq!(err.value, raw);
+            assert!(err.to_string().contains(DURATION_FORMATS), "{err}");
+        }
+    }
+
+    #[test]
+    fn formats_with_the_largest_exact_units() {
+        let cases = [
+            (Duration::ZERO, "0s"),
+            (Duration::from_secs(45), "45s"),
+            (Duration::from_secs(300), "5m"),
+            (Duration::from_secs(5400), "1h30m"),
+            (Duration::from_secs(86_400 + 1), "1d1s"),
+            (Duration::from_millis(1500), "1s500ms"),
+        ];
+        for (duration, expected) in cases {
+            assert_eq!(format_duration(duration), expected);
+            assert_eq!(parse_duration(expected).unwrap(), duration);
+        }
+    }
+}
+
+pub mod size {
+    use super::*;
+
+    #[test]
+    fn parses_plain_bytes() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+    }
+
+    #[test]
+    fn parses_units() {
+        let cases = [
+            ("512B", 512),
+            ("64KiB", 64 * 1024),
+            ("10MiB", 10 * 1024 * 1024),
+            ("1GiB", 1 << 30),
+            ("1TiB", 1 << 40),
+            ("10MB", 10_000_000),
+            ("1kb", 1000),
+            ("1.5 KiB", 1536),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_size(raw).unwrap(), expected, "{raw}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for raw in ["", "MiB", "10 megs", "-1KiB", "10MiB5"] {
+            let err = parse_size(raw).unwrap_err();
+            assert!(err.to_string().contains(SIZE_FORMATS), "{err}");
+        }
+    }
+
+    #[test]
+    fn formats_with_the_largest_exact_binary_unit() {
+        let cases = [
+            (0, "0B"),
+            (1000, "1000B"),
+            (1024, "1KiB"),
+            (1536, "1536B"),
+            (10 * 1024 * 1024, "10MiB"),
+            (3 << 30, "3GiB"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(format_size(bytes), expected);
+            assert_eq!(parse_size(expected).unwrap(), bytes);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// internal crates
+use miru_agent::crypt::digest::{Algorithm, Digest, Hasher};
+use miru_agent::crypt::CryptErr;
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const ABC_BLAKE3: &str = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85";
+
+pub mod compute {
+    use super::*;
+
+    #[test]
+    fn sha256() {
+        let digest = Digest::compute(Algorithm::Sha256, b"abc");
+        assert_eq!(digest.algorithm(), Algorithm::Sha256);
+        assert_eq!(digest.hex(), ABC_SHA256);
+    }
+
+    #[test]
+    fn blake3() {
+        let digest = Digest::compute(Algorithm::Blake3, b"abc");
+        assert_eq!(digest.algorithm(), Algorithm::Blake3);
+        assert_eq!(digest.hex(), ABC_BLAKE3);
+    }
+
+    #[test]
+    fn matches() {
+        for algorithm in Algorithm::ALL {
+            let digest = Digest::compute(algorithm, b"abc");
+            assert!(digest.matches(b"abc"));
+            assert!(!digest.matches(b"abd"));
+        }
+    }
+}
+
+pub mod format {
+    use super::*;
+
+    #[test]
+    fn names_the_algorithm() {
+        let digest = Digest::compute(Algorithm::Blake3, b"abc");
+        assert_eq!(digest.to_string(), format!("blake3:{ABC_BLAKE3}"));
+        assert_eq!(
+            serde_json::to_value(&digest).unwrap(),
+            format!("blake3:{ABC_BLAKE3}")
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        for algorithm in Algorithm::ALL {
+            let digest = Digest::compute(algorithm, b"abc");
+            assert_eq!(digest.to_string().parse::<Digest>().unwrap(), digest);
+            let json = serde_json::to_string(&digest).unwrap();
+            assert_eq!(serde_json::from_str::<Digest>(&json).unwrap(), digest);
+        }
+    }
+}
+
+pub mod parse {
+    use super::*;
+
+    #[test]
+    fn bare_hex_is_sha256() {
+        let digest: Digest = ABC_SHA256.parse().unwrap();
+        assert_eq!(digest, Digest::compute(Algorithm::Sha256, b"abc"));
+    }
+
+    #[test]
+    fn is_case_insensitive() {
+        let digest: Digest = format!("BLAKE3:{}", ABC_BLAKE3.to_uppercase())
+            .parse()
+            .unwrap();
+        assert_eq!(digest, Digest::compute(Algorithm::Blake3, b"abc"));
+    }
+
+    #[test]
+    fn invalid_digests() {
+        let invalid = [
+            "md5:900150983cd24fb0d6963f7d28e17f72".to_string(),
+            format!("sha256:{}", &ABC_SHA256[1..]),
+            format!("blake3:{}zz", &ABC_BLAKE3[2..]),
+            String::new(),
+        ];
+        for s in invalid {
+            let err = s.parse::<Digest>().unwrap_err();
+            assert!(matches!(err, CryptErr::InvalidDigestErr(_)), "{s}: {err:?}");
+        }
+    }
+}
+
+pub mod algorithm {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("sha256".parse::<Algorithm>().unwrap(), Algorithm::Sha256);
+        assert_eq!("Blake3".parse::<Algorithm>().unwrap(), Algorithm::Blake3);
+        assert!("sha1".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn serde() {
+        assert_eq!(
+            serde_json::to_value(Algorithm::Blake3).unwrap(),
+            serde_json::json!("blake3")
+        );
+        assert_eq!(
+            serde_json::from_value::<Algorithm>(serde_json::json!("sha256")).unwrap(),
+            Algorithm::Sha256
+        );
+    }
+}
+
+pub mod hasher {
+    use super::*;
+
+    #[test]
+    fn matches_compute_for_every_algorithm() {
+        let data: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        for algorithm in Algorithm::ALL {
+            let mut hasher = Hasher::new(algorithm);
+            for chunk in data.chunks(777) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.algorithm(), algorithm);
+            assert_eq!(hasher.finalize(), Digest::compute(algorithm, &data));
+        }
+    }
+
+    #[test]
+    fn empty() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(
+                Hasher::new(algorithm).finalize(),
+                Digest::compute(algorithm, b"")
+            );
+        }
+    }
+}
@@ -0,0 +1,73 @@
+// internal crates
+use miru_agent::crypt::hmac;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub mod generate_key {
+    use super::*;
+
+    #[test]
+    fn has_the_requested_length() {
+        assert_eq!(hmac::generate_key(32).unwrap().len(), 32);
+        assert!(hmac::generate_key(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn is_random() {
+        assert_ne!(
+            hmac::generate_key(32).unwrap(),
+            hmac::generate_key(32).unwrap()
+        );
+    }
+}
+
+pub mod sign_sha256 {
+    use super::*;
+
+    #[test]
+    fn rfc_4231_test_case_2() {
+        let signature = hmac::sign_sha256(b"Jefe", b"what do ya want for nothing?").unwrap();
+        assert_eq!(
+            hex(&signature),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn depends_on_the_key() {
+        let a = hmac::sign_sha256(b"key-a", b"data").unwrap();
+        let b = hmac::sign_sha256(b"key-b", b"data").unwrap();
+        assert_ne!(a, b);
+    }
+}
+
+pub mod verify_sha256 {
+    use super::*;
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let signature = hmac::sign_sha256(b"key", b"data").unwrap();
+        assert!(hmac::verify_sha256(b"key", b"data", &signature).unwrap());
+    }
+
+    #[test]
+    fn rejects_tampered_data() {
+        let signature = hmac::sign_sha256(b"key", b"data").unwrap();
+        assert!(!hmac::verify_sha256(b"key", b"datA", &signature).unwrap());
+    }
+
+    #[test]
+    fn rejects_the_wrong_key() {
+        let signature = hmac::sign_sha256(b"key", b"data").unwrap();
+        assert!(!hmac::verify_sha256(b"other", b"data", &signature).unwrap());
+    }
+
+    #[test]
+    fn rejects_a_truncated_signature() {
+        let signature = hmac::sign_sha256(b"key", b"data").unwrap();
+        assert!(!hmac::verify_sha256(b"key", b"data", &signature[..16]).unwrap());
+        assert!(!hmac::verify_sha256(b"key", b"data", &[]).unwrap());
+    }
+}
@@ -0,0 +1,216 @@
+// standard crates
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, Instant};
+
+// internal crates
+use miru_agent::crypt::keystore::{Pkcs11Key, PrivateKey, TpmKey, PKCS11_PIN_ENV};
+use miru_agent::crypt::{rsa, CryptErr};
+use miru_agent::filesys::{self, Overwrite, PathExt, WriteOptions};
+
+// external crates
+use openssl::sha::sha512;
+
+/// Writes an executable shell script which stands in for a signing tool. The script
+/// records its arguments and environment in `record` before running `body`.
+async fn fake_tool(dir: &filesys::Dir, body: &str) -> String {
+    let script = dir.file("tool.sh");
+    let record = dir.file("record");
+    let contents = format!(
+        "#!/bin/sh\necho \"$@\" > {record}\necho \"tcti=$TPM2TOOLS_TCTI pin=${PKCS11_PIN_ENV}\" >> {record}\n{body}\n",
+        record = record.path().display(),
+    );
+    script
+        .write_string(&contents, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+    script
+        .set_permissions(std::fs::Permissions::from_mode(0o755))
+        .await
+        .unwrap();
+    script.path().display().to_string()
+}
+
+async fn record(dir: &filesys::Dir) -> String {
+    dir.file("record").read_string().await.unwrap()
+}
+
+pub mod file {
+    use super::*;
+
+    #[tokio::test]
+    async fn signs_with_the_pem_file() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        let private_key_file = dir.file("private_key.pem");
+        let public_key_file = dir.file("public_key.pem");
+        rsa::gen_key_pair(2048, &private_key_file, &public_key_file, Overwrite::Allow)
+            .await
+            .unwrap();
+
+        let key = PrivateKey::from(private_key_file.clone());
+        assert_eq!(key.file(), Some(&private_key_file));
+        key.assert_exists().unwrap();
+        let signature = key.sign_rs512(b"data").await.unwrap();
+        assert_eq!(
+            signature,
+            rsa::sign_rs512(&private_key_file, b"data").await.unwrap()
+        );
+
+        private_key_file.delete().await.unwrap();
+        assert!(key.assert_exists().is_err());
+    }
+}
+
+pub mod tpm {
+    use super::*;
+
+    #[tokio::test]
+    async fn signs_the_digest() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        // the fake TPM "signs" by echoing the digest it's given (the last argument)
+        let program = fake_tool(
+            &dir,
+            r#"while [ $# -gt 1 ]; do [ "$1" = "-o" ] && out="$2"; shift; done; cat "$1" > "$out""#,
+        )
+        .await;
+        let key = PrivateKey::Tpm(TpmKey {
+            program,
+            ..TpmKey::new(
+                "0x81010001".to_string(),
+                Some("device:/dev/tpmrm0".to_string()),
+            )
+        });
+
+        // hardware keys aren't checked up front
+        assert_eq!(key.file(), None);
+        key.assert_exists().unwrap();
+
+        let signature = key.sign_rs512(b"data").await.unwrap();
+        assert_eq!(signature, sha512(b"data").to_vec());
+
+        let record = record(&dir).await;
+        assert!(record.starts_with("-c 0x81010001 -g sha512 -s rsassa -d -f plain -o "));
+        assert!(record.contains("tcti=device:/dev/tpmrm0"));
+    }
+
+    #[tokio::test]
+    async fn failures_report_the_tool_output() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        let program = fake_tool(&dir, "echo 'handle not found' >&2; exit 1").await;
+        let key = PrivateKey::Tpm(TpmKey {
+            program,
+            ..TpmKey::new("0x81010001".to_string(), None)
+        });
+
+        let err = key.sign_rs512(b"data").await.unwrap_err();
+        assert!(matches!(err, CryptErr::ExternalSignerErr(_)));
+        assert!(err.to_string().contains("handle not found"));
+    }
+
+    #[tokio::test]
+    async fn hung_tools_time_out() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        let program = fake_tool(&dir, "exec sleep 30").await;
+        let key = PrivateKey::Tpm(TpmKey {
+            program,
+            timeout: Duration::from_millis(200),
+            ..TpmKey::new("0x81010001".to_string(), None)
+        });
+
+        let started = Instant::now();
+        let err = key.sign_rs512(b"data").await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(matches!(err, CryptErr::ExternalSignerErr(_)));
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn missing_tool() {
+        let key = PrivateKey::Tpm(TpmKey {
+            program: "/nonexistent/tpm2_sign".to_string(),
+            ..TpmKey::new("0x81010001".to_string(), None)
+        });
+
+        let err = key.sign_rs512(b"data").await.unwrap_err();
+        assert!(matches!(err, CryptErr::ExternalSignerErr(_)));
+    }
+}
+
+pub mod pkcs11 {
+    use super::*;
+
+    /// The fake token "signs" by echoing its input file.
+    const ECHO: &str = r#"while [ $# -gt 0 ]; do
+case "$1" in --input-file) in="$2";; --output-file) out="$2";; esac; shift; done
+cat "$in" > "$out""#;
+
+    #[tokio::test]
+    async fn logs_in_with_the_pin_file() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        let program = fake_tool(&dir, ECHO).await;
+        let pin_file = dir.file("pin");
+        pin_file
+            .write_string("1234\n", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let key = PrivateKey::Pkcs11(Pkcs11Key {
+            program,
+            ..Pkcs11Key::new(
+                "/usr/lib/softhsm/libsofthsm2.so".to_string(),
+                Some(2),
+                "miru-device".to_string(),
+                Some(pin_file),
+            )
+        });
+
+        let signature = key.sign_rs512(b"data").await.unwrap();
+        assert_eq!(signature, b"data");
+
+        let record = record(&dir).await;
+        assert!(record.starts_with(&format!(
+            "--module /usr/lib/softhsm/libsofthsm2.so --slot 2 --login --pin env:{PKCS11_PIN_ENV} --sign --mechanism SHA512-RSA-PKCS --label miru-device --input-file "
+        )));
+        // the PIN is only passed through the environment
+        assert!(record.contains("pin=1234\n"));
+        assert!(!record.lines().next().unwrap().contains("1234"));
+    }
+
+    #[tokio::test]
+    async fn without_a_pin_file() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        let program = fake_tool(&dir, ECHO).await;
+        let key = PrivateKey::Pkcs11(Pkcs11Key {
+            program,
+            ..Pkcs11Key::new(
+                "/usr/lib/libykcs11.so".to_string(),
+                None,
+                "device".to_string(),
+                None,
+            )
+        });
+
+        key.sign_rs512(b"data").await.unwrap();
+
+        let record = record(&dir).await;
+        assert!(record.starts_with(
+            "--module /usr/lib/libykcs11.so --sign --mechanism SHA512-RSA-PKCS --label device "
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_pin_file() {
+        let dir = filesys::Dir::create_temp_dir("keystore").await.unwrap();
+        let program = fake_tool(&dir, ECHO).await;
+        let key = PrivateKey::Pkcs11(Pkcs11Key {
+            program,
+            ..Pkcs11Key::new(
+                "/usr/lib/libykcs11.so".to_string(),
+                None,
+                "device".to_string(),
+                Some(dir.file("missing.pin")),
+            )
+        });
+
+        let err = key.sign_rs512(b"data").await.unwrap_err();
+        assert!(matches!(err, CryptErr::FileSysErr(_)));
+    }
+}
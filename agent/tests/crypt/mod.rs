@@ -1,3 +1,7 @@
 pub mod base64;
+pub mod digest;
+pub mod hmac;
 pub mod jwt;
+pub mod keystore;
 pub mod rsa;
+pub mod salt;
@@ -0,0 +1,89 @@
+// internal crates
+use miru_agent::crypt::base64;
+use miru_agent::crypt::salt::{Salt, PREFIX, SALT_LEN};
+use miru_agent::filesys::{PathExt, WriteOptions};
+use miru_agent::testkit;
+
+pub mod anonymize {
+    use super::*;
+
+    #[test]
+    fn is_stable_for_the_same_salt() {
+        let salt = Salt::from_bytes(vec![7; SALT_LEN]);
+        let first = salt.anonymize("dvc_123").unwrap();
+        assert_eq!(first, salt.anonymize("dvc_123").unwrap());
+        assert_eq!(
+            first,
+            Salt::from_bytes(vec![7; SALT_LEN])
+                .anonymize("dvc_123")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn hides_the_identifier() {
+        let salt = Salt::generate().unwrap();
+        let anonymized = salt.anonymize("dvc_123").unwrap();
+        assert!(anonymized.starts_with(PREFIX), "{anonymized}");
+        assert_eq!(anonymized.len(), PREFIX.len() + 24);
+        assert!(!anonymized.contains("dvc_123"));
+        assert_ne!(anonymized, salt.anonymize("dvc_124").unwrap());
+    }
+
+    #[test]
+    fn differs_between_salts() {
+        let a = Salt::generate().unwrap();
+        let b = Salt::generate().unwrap();
+        assert_ne!(a, b);
+        assert_ne!(
+            a.anonymize("dvc_123").unwrap(),
+            b.anonymize("dvc_123").unwrap()
+        );
+    }
+
+    #[test]
+    fn debug_redacts_the_salt() {
+        let salt = Salt::from_bytes(vec![7; SALT_LEN]);
+        assert!(format!("{salt:?}").contains("[REDACTED]"));
+    }
+}
+
+pub mod load_or_create {
+    use super::*;
+
+    #[tokio::test]
+    async fn creates_and_persists_a_salt() {
+        let dir = testkit::temp_dir("salt_create").await;
+        let file = dir.file("device_salt");
+
+        let created = Salt::load_or_create(&file).await.unwrap();
+        assert!(file.exists());
+        let loaded = Salt::load_or_create(&file).await.unwrap();
+        assert_eq!(created, loaded);
+
+        let encoded = file.read_string().await.unwrap();
+        assert_eq!(
+            base64::decode_bytes_standard(&encoded).unwrap().len(),
+            SALT_LEN
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(file.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn replaces_an_invalid_salt() {
+        let dir = testkit::temp_dir("salt_invalid").await;
+        let file = dir.file("device_salt");
+        for invalid in ["not base64!", "c2hvcnQ="] {
+            file.write_string(invalid, WriteOptions::OVERWRITE_ATOMIC)
+                .await
+                .unwrap();
+            let salt = Salt::load_or_create(&file).await.unwrap();
+            assert_eq!(Salt::load_or_create(&file).await.unwrap(), salt);
+        }
+    }
+}
@@ -1,13 +1,19 @@
 // internal crates
 use miru_agent::deploy::apply::{self, apply, Outcome};
-use miru_agent::deploy::fsm::RetryPolicy;
+use miru_agent::deploy::fsm::{self, RetryPolicy};
+use miru_agent::deploy::signature;
 use miru_agent::deploy::DeployErr;
-use miru_agent::filesys::{self, File, Overwrite, PathExt};
-use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplErrStatus, DplTarget};
+use miru_agent::deploy::{created_dirs::CreatedDirs, hooks, pause, reboot};
+use miru_agent::filesys::{self, File, Overwrite, PathExt, WriteOptions};
+use miru_agent::models::release::SignatureAlgorithm;
+use miru_agent::models::{
+    ConfigInstance, Deployment, DplActivity, DplErrStatus, DplTarget, Release,
+};
 use miru_agent::storage;
 
 // external crates
 use chrono::TimeDelta;
+use serde_json::json;
 
 // ================================= FIXTURE ===================================== //
 
@@ -15,6 +21,8 @@ struct Fixture {
     deployments: storage::Deployments,
     cfg_insts: storage::CfgInsts,
     cfg_inst_content: storage::CfgInstContent,
+    config_schemas: storage::ConfigSchemas,
+    releases: storage::Releases,
     temp_dir: filesys::Dir,
 }
 
@@ -34,11 +42,20 @@ impl Fixture {
             storage::CfgInstContent::spawn(16, resources_dir.subdir("content"), 1000)
                 .await
                 .unwrap();
+        let (config_schemas, _) =
+            storage::ConfigSchemas::spawn(16, resources_dir.subdir("schemas"), 1000)
+                .await
+                .unwrap();
+        let (releases, _) = storage::Releases::spawn(16, resources_dir.file("releases.json"), 1000)
+            .await
+            .unwrap();
 
         Self {
             deployments,
             cfg_insts,
             cfg_inst_content,
+            config_schemas,
+            releases,
             temp_dir,
         }
     }
@@ -102,15 +119,15 @@ impl Fixture {
             cfg_insts: storage::CfgInstRef {
                 meta: &self.cfg_insts,
                 content: &self.cfg_inst_content,
+                schemas: &self.config_schemas,
             },
+            releases: &self.releases,
         }
     }
 
     async fn apply(&self) -> Result<Vec<Outcome>, DeployErr> {
         let storage = self.storage();
-        let opts = apply::DeployOpts {
-            retry_policy: RetryPolicy::default(),
-        };
+        let opts = apply::DeployOpts::default();
         let args = apply::Args {
             storage: &storage,
             opts: &opts,
@@ -123,13 +140,138 @@ impl Fixture {
         retry_policy: RetryPolicy,
     ) -> Result<Vec<Outcome>, DeployErr> {
         let storage = self.storage();
-        let opts = apply::DeployOpts { retry_policy };
+        let opts = apply::DeployOpts {
+            retry_policy,
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_in_safe_mode(&self) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            safe_mode: true,
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_paused(&self) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let switch = pause::Switch::new(self.temp_dir.file("deployments_paused.json"));
+        switch.pause(None, pause::Origin::Cli).await.unwrap();
+        let opts = apply::DeployOpts {
+            pause: Some(switch),
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_recording_created_dirs(
+        &self,
+        created_dirs: &CreatedDirs,
+    ) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            created_dirs: Some(created_dirs.clone()),
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_with_reboot(&self, reboot: reboot::Options) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            reboot,
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_with_hooks(&self, hooks: hooks::Options) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            hooks,
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_validating_schemas(&self) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            validate_schemas: true,
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        apply(&args).await
+    }
+
+    async fn apply_verifying_signatures(
+        &self,
+        options: &signature::Options,
+    ) -> Result<Vec<Outcome>, DeployErr> {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            signatures: Some(signature::Verifier::new(options)),
+            ..Default::default()
+        };
         let args = apply::Args {
             storage: &storage,
             opts: &opts,
         };
         apply(&args).await
     }
+
+    async fn seed_release(&self, release: &Release) {
+        self.releases
+            .write(
+                release.id.clone(),
+                release.clone(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn seed_config_schema(&self, id: &str, schema: serde_json::Value) {
+        self.config_schemas
+            .write(id.to_string(), schema, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+    }
+
+    async fn read_deployment(&self, id: &str) -> Deployment {
+        self.deployments.read(id.to_string()).await.unwrap()
+    }
 }
 
 // ================================= HELPERS ===================================== //
@@ -1590,6 +1732,251 @@ mod no_action {
     }
 }
 
+mod safe_mode {
+    use super::*;
+
+    #[tokio::test]
+    async fn skips_all_actions() {
+        let f = Fixture::new().await;
+
+        let ci = make_cfg_inst(f.fixture_path("safe-mode.json"));
+        f.seed_cfg_inst(&ci, "content".into()).await;
+        let dpl = make_deployment(
+            "dpl-safe-mode",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+
+        let outcomes = f.apply_in_safe_mode().await.unwrap();
+        assert!(outcomes.is_empty());
+
+        // the deployment is left queued and nothing is written
+        let cached = f.read_deployment("dpl-safe-mode").await;
+        assert_eq!(cached.activity_status, DplActivity::Queued);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+}
+
+mod paused {
+    use super::*;
+
+    #[tokio::test]
+    async fn holds_every_transition() {
+        let f = Fixture::new().await;
+
+        let ci = make_cfg_inst(f.fixture_path("paused.json"));
+        f.seed_cfg_inst(&ci, "content".into()).await;
+        let deploy = make_deployment(
+            "dpl-deploy",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        let remove = make_deployment(
+            "dpl-remove",
+            DplTarget::Archived,
+            DplActivity::Deployed,
+            vec![],
+        );
+        let settled = make_deployment(
+            "dpl-settled",
+            DplTarget::Staged,
+            DplActivity::Archived,
+            vec![],
+        );
+        for dpl in [&deploy, &remove, &settled] {
+            f.seed_deployment(dpl).await;
+        }
+
+        let mut outcomes = f.apply_paused().await.unwrap();
+        outcomes.sort_by(|a, b| a.deployment.id.cmp(&b.deployment.id));
+        let outcomes: Vec<ComparableOutcome> =
+            outcomes.iter().map(ComparableOutcome::from).collect();
+        let held = |id: &str, activity| ComparableOutcome {
+            id: id.into(),
+            activity,
+            error_status: DplErrStatus::None,
+            attempts: 0,
+            has_error: false,
+            has_wait: false,
+            in_cooldown: false,
+            transitioned: false,
+        };
+        assert_eq!(
+            outcomes,
+            vec![
+                held("dpl-deploy", DplActivity::Queued),
+                held("dpl-remove", DplActivity::Deployed),
+            ]
+        );
+
+        // nothing is written and the deployments are left as they were
+        assert!(!File::new(&ci.filepath).exists());
+        assert_eq!(
+            f.read_deployment("dpl-deploy").await.activity_status,
+            DplActivity::Queued
+        );
+        assert_eq!(
+            f.read_deployment("dpl-remove").await.activity_status,
+            DplActivity::Deployed
+        );
+    }
+
+    #[tokio::test]
+    async fn resumed_deployments_are_applied() {
+        let f = Fixture::new().await;
+
+        let ci = make_cfg_inst(f.fixture_path("resumed.json"));
+        f.seed_cfg_inst(&ci, "content".into()).await;
+        let dpl = make_deployment(
+            "dpl-resumed",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+
+        let switch = pause::Switch::new(f.temp_dir.file("deployments_paused.json"));
+        switch.pause(None, pause::Origin::Cli).await.unwrap();
+        switch.resume(pause::Origin::Cli).await.unwrap();
+        let storage = f.storage();
+        let opts = apply::DeployOpts {
+            pause: Some(switch),
+            ..Default::default()
+        };
+        let outcomes = apply(&apply::Args {
+            storage: &storage,
+            opts: &opts,
+        })
+        .await
+        .unwrap();
+
+        assert!(outcomes[0].transitioned);
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Deployed
+        );
+        assert!(File::new(&ci.filepath).exists());
+    }
+}
+
+mod dependencies {
+    use super::*;
+
+    async fn seed_target(f: &Fixture, depends_on: &[&str]) -> ConfigInstance {
+        let ci = make_cfg_inst(f.fixture_path("dependent.json"));
+        f.seed_cfg_inst(&ci, "content".into()).await;
+        let mut dpl = make_deployment(
+            "dpl-dependent",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        dpl.dependencies.deployments = depends_on.iter().map(|id| id.to_string()).collect();
+        f.seed_deployment(&dpl).await;
+        ci
+    }
+
+    #[tokio::test]
+    async fn deploys_once_dependencies_deployed() {
+        let f = Fixture::new().await;
+        let mut dep = make_deployment("dpl-dep", DplTarget::Staged, DplActivity::Archived, vec![]);
+        dep.deployed_at = Some(chrono::Utc::now());
+        f.seed_deployment(&dep).await;
+        let ci = seed_target(&f, &["dpl-dep"]).await;
+
+        let outcomes = f.apply().await.unwrap();
+        assert!(outcomes[0].error.is_none());
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Deployed
+        );
+        assert!(File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn unmet_dependencies_wait_without_an_attempt() {
+        let f = Fixture::new().await;
+        let dep = make_deployment("dpl-dep", DplTarget::Staged, DplActivity::Staged, vec![]);
+        f.seed_deployment(&dep).await;
+        let ci = seed_target(&f, &["dpl-dep", "dpl-unknown"]).await;
+
+        let outcomes = f.apply().await.unwrap();
+        let outcome = &outcomes[0];
+        assert!(outcome.error.is_none());
+        assert!(!outcome.transitioned);
+        assert_eq!(outcome.deployment.error_status, DplErrStatus::None);
+        assert_eq!(outcome.deployment.attempts, 0);
+        assert_eq!(
+            outcome.wait,
+            Some(TimeDelta::seconds(RetryPolicy::default().backoff.base_secs))
+        );
+        assert!(!File::new(&ci.filepath).exists());
+
+        // waiting doesn't start a cooldown, so the next apply checks again
+        let stored = f.read_deployment(&outcome.deployment.id).await;
+        assert_eq!(stored.attempts, 0);
+        assert_eq!(fsm::next_action(&stored), fsm::NextAction::Deploy);
+    }
+
+    #[tokio::test]
+    async fn cycle_fails_without_retrying() {
+        let f = Fixture::new().await;
+        let mut dep = make_deployment("dpl-dep", DplTarget::Staged, DplActivity::Archived, vec![]);
+        dep.deployed_at = Some(chrono::Utc::now());
+        dep.dependencies.deployments = vec!["dpl-dependent".to_string()];
+        f.seed_deployment(&dep).await;
+        let ci = seed_target(&f, &["dpl-dep"]).await;
+
+        let outcomes = f.apply().await.unwrap();
+        let outcome = &outcomes[0];
+        match &outcome.error {
+            Some(DeployErr::DependencyCycle(e)) => assert_eq!(
+                e.ids,
+                vec![
+                    "dpl-dependent".to_string(),
+                    "dpl-dep".to_string(),
+                    "dpl-dependent".to_string()
+                ]
+            ),
+            other => panic!("expected dependency cycle error, got {other:?}"),
+        }
+        assert_eq!(outcome.deployment.error_status, DplErrStatus::Failed);
+        assert_eq!(outcome.deployment.attempts, 1);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn config_type_cycle_fails_without_retrying() {
+        let f = Fixture::new().await;
+        let ci = make_cfg_inst(f.fixture_path("cycle.json"));
+        f.seed_cfg_inst(&ci, "content".into()).await;
+        let mut dpl = make_deployment(
+            "dpl-cycle",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        dpl.dependencies.config_types = [
+            (ci.config_type_id.clone(), vec!["ct-other".to_string()]),
+            ("ct-other".to_string(), vec![ci.config_type_id.clone()]),
+        ]
+        .into_iter()
+        .collect();
+        f.seed_deployment(&dpl).await;
+
+        let outcomes = f.apply().await.unwrap();
+        assert!(matches!(
+            outcomes[0].error,
+            Some(DeployErr::DependencyCycle(_))
+        ));
+        assert_eq!(outcomes[0].deployment.error_status, DplErrStatus::Failed);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+}
+
 mod wait_action {
     use super::*;
 
@@ -1786,3 +2173,675 @@ mod ordering_and_composition {
         }
     }
 }
+
+mod reboot_coordination {
+    use super::*;
+
+    struct Reboot {
+        options: reboot::Options,
+        boot_id_file: File,
+    }
+
+    impl Reboot {
+        async fn new(f: &Fixture, paths: &[&str]) -> Self {
+            let boot_id_file = f.temp_dir.file("boot_id");
+            boot_id_file
+                .write_string("boot-1\n", WriteOptions::OVERWRITE_ATOMIC)
+                .await
+                .unwrap();
+            let options = reboot::Options {
+                paths: paths.iter().map(|p| f.fixture_path(p)).collect(),
+                boot_id_file: boot_id_file.clone(),
+                ..Default::default()
+            };
+            Self {
+                options,
+                boot_id_file,
+            }
+        }
+
+        async fn reboot(&self) {
+            self.boot_id_file
+                .write_string("boot-2\n", WriteOptions::OVERWRITE_ATOMIC)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn stages_until_reboot() {
+        let f = Fixture::new().await;
+        let reboot = Reboot::new(&f, &["boot"]).await;
+
+        let ci = make_cfg_inst(f.fixture_path("boot/kernel.json"));
+        f.seed_cfg_inst(&ci, r#"{"isolcpus": 2}"#.into()).await;
+        let dpl = make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+
+        let outcomes = f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(
+            ComparableOutcome::from(&outcomes[0]),
+            ComparableOutcome {
+                id: "dpl-1".into(),
+                activity: DplActivity::Queued,
+                error_status: DplErrStatus::None,
+                attempts: 0,
+                has_error: false,
+                has_wait: true,
+                in_cooldown: false,
+                transitioned: false,
+            }
+        );
+
+        // the files are written but the deployment isn't finalized
+        let file = File::new(&ci.filepath);
+        assert_eq!(file.read_string().await.unwrap(), r#"{"isolcpus": 2}"#);
+        let stored = f.read_deployment("dpl-1").await;
+        let pending = stored.pending_finalize.unwrap();
+        assert_eq!(pending.boot_id, "boot-1");
+        assert!(pending.reboot_requested_at.is_some());
+
+        // applying again before a reboot leaves the deployment pending
+        let outcomes = f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+        assert_eq!(outcomes[0].deployment.activity_status, DplActivity::Queued);
+        assert!(!outcomes[0].transitioned);
+        let stored = f.read_deployment("dpl-1").await;
+        assert_eq!(
+            stored.pending_finalize.unwrap().staged_at,
+            pending.staged_at
+        );
+    }
+
+    #[tokio::test]
+    async fn finalizes_after_reboot() {
+        let f = Fixture::new().await;
+        let reboot = Reboot::new(&f, &["boot"]).await;
+
+        let ci = make_cfg_inst(f.fixture_path("boot/kernel.json"));
+        f.seed_cfg_inst(&ci, r#"{"isolcpus": 2}"#.into()).await;
+        let dpl = make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+        f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+
+        reboot.reboot().await;
+        let outcomes = f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(
+            ComparableOutcome::from(&outcomes[0]),
+            ComparableOutcome {
+                id: "dpl-1".into(),
+                activity: DplActivity::Deployed,
+                error_status: DplErrStatus::None,
+                attempts: 0,
+                has_error: false,
+                has_wait: false,
+                in_cooldown: false,
+                transitioned: true,
+            }
+        );
+        let stored = f.read_deployment("dpl-1").await;
+        assert_eq!(stored.activity_status, DplActivity::Deployed);
+        assert_eq!(stored.pending_finalize, None);
+    }
+
+    #[tokio::test]
+    async fn unaffected_paths_deploy_immediately() {
+        let f = Fixture::new().await;
+        let reboot = Reboot::new(&f, &["boot"]).await;
+
+        let ci = make_cfg_inst(f.fixture_path("app/config.json"));
+        f.seed_cfg_inst(&ci, r#"{"speed": 4}"#.into()).await;
+        let dpl = make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+
+        let outcomes = f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Deployed
+        );
+        assert_eq!(outcomes[0].deployment.pending_finalize, None);
+    }
+
+    #[tokio::test]
+    async fn unreadable_boot_id_is_retried() {
+        let f = Fixture::new().await;
+        let options = reboot::Options {
+            paths: vec![f.fixture_path("boot")],
+            boot_id_file: f.temp_dir.file("missing_boot_id"),
+            ..Default::default()
+        };
+
+        let ci = make_cfg_inst(f.fixture_path("boot/kernel.json"));
+        f.seed_cfg_inst(&ci, r#"{"isolcpus": 2}"#.into()).await;
+        let dpl = make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+
+        let outcomes = f.apply_with_reboot(options).await.unwrap();
+        assert!(outcomes[0].error.is_some());
+        assert_eq!(outcomes[0].deployment.error_status, DplErrStatus::Retrying);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn archived_while_pending_removes_staged_files() {
+        let f = Fixture::new().await;
+        let reboot = Reboot::new(&f, &["boot"]).await;
+
+        let ci = make_cfg_inst(f.fixture_path("boot/kernel.json"));
+        f.seed_cfg_inst(&ci, r#"{"isolcpus": 2}"#.into()).await;
+        let dpl = make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        );
+        f.seed_deployment(&dpl).await;
+        f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+        assert!(File::new(&ci.filepath).exists());
+
+        let mut stored = f.read_deployment("dpl-1").await;
+        stored.target_status = DplTarget::Archived;
+        f.seed_deployment(&stored).await;
+
+        let outcomes = f.apply_with_reboot(reboot.options.clone()).await.unwrap();
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Archived
+        );
+        assert_eq!(outcomes[0].deployment.pending_finalize, None);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+}
+
+mod deploy_hooks {
+    use super::*;
+
+    fn sh(script: String) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script]
+    }
+
+    #[tokio::test]
+    async fn run_around_deploying_files() {
+        let f = Fixture::new().await;
+        let log = f.temp_dir.file("hooks.log");
+        let ci = make_cfg_inst(f.fixture_path("app/config.json"));
+        f.seed_cfg_inst(&ci, r#"{"speed": 4}"#.into()).await;
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        ))
+        .await;
+        // each hook records whether the config file exists when it runs
+        let record = |stage: &str| {
+            sh(format!(
+                "if [ -f \"$MIRU_CONFIG_FILES\" ]; then echo {stage} written; else echo {stage} absent; fi >> {}",
+                log.path().display()
+            ))
+        };
+        let hooks = hooks::Options {
+            pre_deploy: record("pre"),
+            post_deploy: record("post"),
+            ..Default::default()
+        };
+
+        let outcomes = f.apply_with_hooks(hooks).await.unwrap();
+
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Deployed
+        );
+        assert!(outcomes[0].deployment.metrics.hook_duration_ms.is_some());
+        assert_eq!(
+            log.read_string().await.unwrap(),
+            "pre absent\npost written\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn pre_deploy_failure_is_retried_without_writing_files() {
+        let f = Fixture::new().await;
+        let ci = make_cfg_inst(f.fixture_path("app/config.json"));
+        f.seed_cfg_inst(&ci, r#"{"speed": 4}"#.into()).await;
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        ))
+        .await;
+        let hooks = hooks::Options {
+            pre_deploy: vec!["false".to_string()],
+            ..Default::default()
+        };
+
+        let outcomes = f.apply_with_hooks(hooks).await.unwrap();
+
+        assert_eq!(
+            ComparableOutcome::from(&outcomes[0]),
+            ComparableOutcome {
+                id: "dpl-1".into(),
+                activity: DplActivity::Queued,
+                error_status: DplErrStatus::Retrying,
+                attempts: 1,
+                has_error: true,
+                has_wait: true,
+                in_cooldown: true,
+                transitioned: true,
+            }
+        );
+        assert!(matches!(outcomes[0].error, Some(DeployErr::Hook(_))));
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn post_deploy_failure_is_retried() {
+        let f = Fixture::new().await;
+        let ci = make_cfg_inst(f.fixture_path("app/config.json"));
+        f.seed_cfg_inst(&ci, r#"{"speed": 4}"#.into()).await;
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        ))
+        .await;
+        let hooks = hooks::Options {
+            post_deploy: vec!["false".to_string()],
+            ..Default::default()
+        };
+
+        let outcomes = f.apply_with_hooks(hooks).await.unwrap();
+
+        let stored = f.read_deployment("dpl-1").await;
+        assert_eq!(stored.activity_status, DplActivity::Queued);
+        assert_eq!(stored.error_status, DplErrStatus::Retrying);
+        assert!(matches!(outcomes[0].error, Some(DeployErr::Hook(_))));
+    }
+
+    #[tokio::test]
+    async fn pre_remove_failure_keeps_files() {
+        let f = Fixture::new().await;
+        let ci = make_cfg_inst(f.fixture_path("app/config.json"));
+        f.seed_cfg_inst(&ci, r#"{"speed": 4}"#.into()).await;
+        File::new(&ci.filepath)
+            .write_string(r#"{"speed": 4}"#, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Archived,
+            DplActivity::Deployed,
+            vec![ci.id.clone()],
+        ))
+        .await;
+        let hooks = hooks::Options {
+            pre_remove: vec!["false".to_string()],
+            ..Default::default()
+        };
+
+        let outcomes = f.apply_with_hooks(hooks).await.unwrap();
+
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Removing
+        );
+        assert_eq!(outcomes[0].deployment.error_status, DplErrStatus::Retrying);
+        assert!(matches!(outcomes[0].error, Some(DeployErr::Hook(_))));
+        assert!(File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn post_remove_runs_after_files_are_removed() {
+        let f = Fixture::new().await;
+        let log = f.temp_dir.file("hooks.log");
+        let ci = make_cfg_inst(f.fixture_path("app/config.json"));
+        f.seed_cfg_inst(&ci, r#"{"speed": 4}"#.into()).await;
+        File::new(&ci.filepath)
+            .write_string(r#"{"speed": 4}"#, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Archived,
+            DplActivity::Deployed,
+            vec![ci.id.clone()],
+        ))
+        .await;
+        let hooks = hooks::Options {
+            post_remove: sh(format!(
+                "[ -f \"$MIRU_CONFIG_FILES\" ] || echo \"$MIRU_HOOK\" > {}",
+                log.path().display()
+            )),
+            ..Default::default()
+        };
+
+        let outcomes = f.apply_with_hooks(hooks).await.unwrap();
+
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Archived
+        );
+        assert_eq!(log.read_string().await.unwrap().trim(), "post_remove");
+    }
+}
+
+mod schema_validation {
+    use super::*;
+
+    async fn seed(f: &Fixture, filepath: &str, content: &str) -> ConfigInstance {
+        let ci = ConfigInstance {
+            config_schema_id: "cfg_sch_1".to_string(),
+            ..make_cfg_inst(f.fixture_path(filepath))
+        };
+        f.seed_cfg_inst(&ci, content.into()).await;
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci.id.clone()],
+        ))
+        .await;
+        ci
+    }
+
+    fn port_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "required": ["port"],
+            "properties": {"port": {"type": "integer", "minimum": 1}},
+        })
+    }
+
+    #[tokio::test]
+    async fn valid_content_is_deployed() {
+        let f = Fixture::new().await;
+        f.seed_config_schema("cfg_sch_1", port_schema()).await;
+        let ci = seed(&f, "config.json", r#"{"port": 8080}"#).await;
+
+        let outcomes = f.apply_validating_schemas().await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Deployed
+        );
+        assert!(File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn violation_fails_without_writing_files() {
+        let f = Fixture::new().await;
+        f.seed_config_schema("cfg_sch_1", port_schema()).await;
+        let ci = seed(&f, "config.json", r#"{"port": 0, "host": "a"}"#).await;
+
+        let outcomes = f.apply_validating_schemas().await.unwrap();
+
+        match &outcomes[0].error {
+            Some(DeployErr::SchemaViolation(e)) => {
+                assert_eq!(e.cfg_inst_id, ci.id);
+                assert_eq!(e.config_schema_id, "cfg_sch_1");
+                assert_eq!(
+                    e.violations,
+                    vec!["/port: 0 is less than the minimum of 1".to_string()]
+                );
+            }
+            other => panic!("expected schema violation error, got {other:?}"),
+        }
+        assert_eq!(outcomes[0].deployment.activity_status, DplActivity::Queued);
+        assert_eq!(outcomes[0].deployment.error_status, DplErrStatus::Failed);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn invalid_json_in_json_file_is_a_violation() {
+        let f = Fixture::new().await;
+        f.seed_config_schema("cfg_sch_1", port_schema()).await;
+        let ci = seed(&f, "config.json", "port: 8080").await;
+
+        let outcomes = f.apply_validating_schemas().await.unwrap();
+
+        assert!(matches!(
+            outcomes[0].error,
+            Some(DeployErr::SchemaViolation(_))
+        ));
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn non_json_content_is_deployed_unvalidated() {
+        let f = Fixture::new().await;
+        f.seed_config_schema("cfg_sch_1", port_schema()).await;
+        let ci = seed(&f, "config.yaml", "port: 0").await;
+
+        let outcomes = f.apply_validating_schemas().await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        assert!(File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn uncached_schema_is_deployed_unvalidated() {
+        let f = Fixture::new().await;
+        let ci = seed(&f, "config.json", r#"{"port": 0}"#).await;
+
+        let outcomes = f.apply_validating_schemas().await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        assert!(File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn disabled_validation_deploys_invalid_content() {
+        let f = Fixture::new().await;
+        f.seed_config_schema("cfg_sch_1", port_schema()).await;
+        let ci = seed(&f, "config.json", r#"{"port": 0}"#).await;
+
+        let outcomes = f.apply().await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        assert!(File::new(&ci.filepath).exists());
+    }
+}
+
+mod signature_verification {
+    use super::*;
+    use crate::deploy::signature::{ed25519_key, sign, trusted};
+
+    const CONTENT: &str = r#"{"speed":4}"#;
+
+    /// Seeds a deployment of release `rls_1` writing `content`, with the release
+    /// signed over `signed_content` if set.
+    async fn seed(
+        f: &Fixture,
+        content: &str,
+        signed_content: Option<&str>,
+    ) -> (ConfigInstance, signature::Options) {
+        let key = ed25519_key();
+        let ci = make_cfg_inst(f.fixture_path("motion.json"));
+        let mut release = Release {
+            id: "rls_1".to_string(),
+            version: "1.0.0".to_string(),
+            ..Default::default()
+        };
+        if let Some(signed_content) = signed_content {
+            release.signature = Some(sign(
+                "release-2026",
+                &key,
+                SignatureAlgorithm::Ed25519,
+                &release,
+                &[(ci.filepath.as_str(), signed_content)],
+            ));
+        }
+        f.seed_release(&release).await;
+        f.seed_cfg_inst(&ci, content.into()).await;
+        f.seed_deployment(&Deployment {
+            release_id: release.id.clone(),
+            ..make_deployment(
+                "dpl-1",
+                DplTarget::Deployed,
+                DplActivity::Queued,
+                vec![ci.id.clone()],
+            )
+        })
+        .await;
+        let options = signature::Options {
+            trusted_keys: vec![trusted("release-2026", &key)],
+            required: false,
+        };
+        (ci, options)
+    }
+
+    #[tokio::test]
+    async fn signed_content_is_deployed() {
+        let f = Fixture::new().await;
+        let (ci, options) = seed(&f, CONTENT, Some(CONTENT)).await;
+
+        let outcomes = f.apply_verifying_signatures(&options).await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        assert_eq!(
+            outcomes[0].deployment.activity_status,
+            DplActivity::Deployed
+        );
+        assert!(File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn tampered_content_fails_without_writing_files() {
+        let f = Fixture::new().await;
+        let (ci, options) = seed(&f, r#"{"speed":40}"#, Some(CONTENT)).await;
+
+        let outcomes = f.apply_verifying_signatures(&options).await.unwrap();
+
+        match &outcomes[0].error {
+            Some(DeployErr::InvalidSignature(e)) => {
+                assert_eq!(e.release_id, "rls_1");
+                assert!(e.to_string().contains("doesn't match"), "{e}");
+            }
+            other => panic!("expected invalid signature error, got {other:?}"),
+        }
+        assert_eq!(outcomes[0].deployment.error_status, DplErrStatus::Failed);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn unsigned_releases_fail_if_signatures_are_required() {
+        let f = Fixture::new().await;
+        let (ci, options) = seed(&f, CONTENT, None).await;
+
+        let outcomes = f.apply_verifying_signatures(&options).await.unwrap();
+        assert!(outcomes[0].error.is_none());
+        assert!(File::new(&ci.filepath).exists());
+
+        let f = Fixture::new().await;
+        let (ci, options) = seed(&f, CONTENT, None).await;
+        let options = signature::Options {
+            required: true,
+            ..options
+        };
+
+        let outcomes = f.apply_verifying_signatures(&options).await.unwrap();
+        assert!(matches!(
+            outcomes[0].error,
+            Some(DeployErr::InvalidSignature(_))
+        ));
+        assert_eq!(outcomes[0].deployment.error_status, DplErrStatus::Failed);
+        assert!(!File::new(&ci.filepath).exists());
+    }
+
+    #[tokio::test]
+    async fn disabled_verification_deploys_tampered_content() {
+        let f = Fixture::new().await;
+        let (ci, _) = seed(&f, r#"{"speed":40}"#, Some(CONTENT)).await;
+
+        let outcomes = f.apply().await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        assert!(File::new(&ci.filepath).exists());
+    }
+}
+
+mod created_dirs {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_the_dirs_deploying_creates() {
+        let f = Fixture::new().await;
+        let created_dirs = CreatedDirs::new(f.temp_dir.file("created_dirs.json"));
+        let ci1 = make_cfg_inst(f.fixture_path("etc/app/motion/speed.json"));
+        let ci2 = make_cfg_inst(f.fixture_path("existing/limits.json"));
+        f.temp_dir
+            .subdir("existing")
+            .file("other.json")
+            .write_string("{}", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        f.seed_cfg_inst(&ci1, "{}".into()).await;
+        f.seed_cfg_inst(&ci2, "{}".into()).await;
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci1.id.clone(), ci2.id.clone()],
+        ))
+        .await;
+
+        let outcomes = f.apply_recording_created_dirs(&created_dirs).await.unwrap();
+
+        assert!(outcomes[0].error.is_none());
+        let etc = f.temp_dir.subdir("etc");
+        assert_eq!(
+            created_dirs.read().await,
+            vec![
+                etc.subdir("app").subdir("motion").path().to_path_buf(),
+                etc.subdir("app").path().to_path_buf(),
+                etc.path().to_path_buf(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_deployment_records_nothing() {
+        let f = Fixture::new().await;
+        let created_dirs = CreatedDirs::new(f.temp_dir.file("created_dirs.json"));
+        let ci1 = make_cfg_inst(f.fixture_path("etc/app/speed.json"));
+        let ci2 = make_cfg_inst(f.fixture_path("etc/app/limits.json"));
+        f.seed_cfg_inst(&ci1, "{}".into()).await;
+        f.seed_cfg_inst_meta_only(&ci2).await;
+        f.seed_deployment(&make_deployment(
+            "dpl-1",
+            DplTarget::Deployed,
+            DplActivity::Queued,
+            vec![ci1.id.clone(), ci2.id.clone()],
+        ))
+        .await;
+
+        let outcomes = f.apply_recording_created_dirs(&created_dirs).await.unwrap();
+
+        assert!(outcomes[0].error.is_some());
+        assert!(created_dirs.read().await.is_empty());
+    }
+}
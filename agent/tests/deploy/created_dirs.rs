@@ -0,0 +1,97 @@
+// internal crates
+use miru_agent::deploy::created_dirs::{missing_ancestors, CreatedDirs};
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+
+struct Fixture {
+    dir: filesys::Dir,
+    created_dirs: CreatedDirs,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let created_dirs = CreatedDirs::new(dir.file("created_dirs.json"));
+        Self { dir, created_dirs }
+    }
+}
+
+pub mod read {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_without_a_record() {
+        let f = Fixture::new("created_dirs_missing").await;
+        assert!(f.created_dirs.read().await.is_empty());
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn empty_if_the_record_is_unreadable() {
+        let f = Fixture::new("created_dirs_corrupt").await;
+        f.created_dirs
+            .file()
+            .write_string("{not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        assert!(f.created_dirs.read().await.is_empty());
+        f.dir.delete().await.unwrap();
+    }
+}
+
+pub mod record {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_earlier_dirs_deepest_first() {
+        let f = Fixture::new("created_dirs_record").await;
+        let a = f.dir.subdir("a").path().to_path_buf();
+        let a_b = f.dir.subdir("a").subdir("b").path().to_path_buf();
+        let c = f.dir.subdir("c").path().to_path_buf();
+
+        f.created_dirs
+            .record(&[a.clone(), c.clone()])
+            .await
+            .unwrap();
+        f.created_dirs
+            .record(&[a_b.clone(), a.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(f.created_dirs.read().await, vec![a_b, a, c]);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn nothing_is_written_for_no_dirs() {
+        let f = Fixture::new("created_dirs_none").await;
+        f.created_dirs.record(&[]).await.unwrap();
+        assert!(!f.created_dirs.file().exists());
+        f.dir.delete().await.unwrap();
+    }
+}
+
+pub mod missing_ancestors {
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_at_the_first_existing_dir() {
+        let f = Fixture::new("created_dirs_ancestors").await;
+        let file = f.dir.subdir("a").subdir("b").file("app.json");
+
+        assert_eq!(
+            missing_ancestors(file.path()),
+            vec![
+                f.dir.subdir("a").subdir("b").path().to_path_buf(),
+                f.dir.subdir("a").path().to_path_buf(),
+            ]
+        );
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn none_if_the_parent_exists() {
+        let f = Fixture::new("created_dirs_ancestors").await;
+        assert!(missing_ancestors(f.dir.file("app.json").path()).is_empty());
+        f.dir.delete().await.unwrap();
+    }
+}
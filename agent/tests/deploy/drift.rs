@@ -0,0 +1,376 @@
+// internal crates
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::deploy::apply::{self, apply};
+use miru_agent::deploy::drift::{self, Drift};
+use miru_agent::deploy::fsm::{self, NextAction};
+use miru_agent::deploy::versions::Versions;
+use miru_agent::filesys::{self, Overwrite, PathExt};
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplTarget};
+use miru_agent::storage;
+
+// ================================= FIXTURE ===================================== //
+
+struct Fixture {
+    deployments: storage::Deployments,
+    cfg_insts: storage::CfgInsts,
+    cfg_inst_content: storage::CfgInstContent,
+    config_schemas: storage::ConfigSchemas,
+    releases: storage::Releases,
+    temp_dir: filesys::Dir,
+}
+
+impl Fixture {
+    async fn new(name: &str) -> Self {
+        let temp_dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+        let resources_dir = temp_dir.subdir("resources");
+
+        let (deployments, _) =
+            storage::Deployments::spawn(16, resources_dir.file("deployments.json"), 1000)
+                .await
+                .unwrap();
+        let (cfg_insts, _) = storage::CfgInsts::spawn(16, resources_dir.file("ci_meta.json"), 1000)
+            .await
+            .unwrap();
+        let (cfg_inst_content, _) =
+            storage::CfgInstContent::spawn(16, resources_dir.subdir("content"), 1000)
+                .await
+                .unwrap();
+        let (config_schemas, _) =
+            storage::ConfigSchemas::spawn(16, resources_dir.subdir("schemas"), 1000)
+                .await
+                .unwrap();
+        let (releases, _) = storage::Releases::spawn(16, resources_dir.file("releases.json"), 1000)
+            .await
+            .unwrap();
+
+        Self {
+            deployments,
+            cfg_insts,
+            cfg_inst_content,
+            config_schemas,
+            releases,
+            temp_dir,
+        }
+    }
+
+    fn path(&self, rel: &str) -> String {
+        self.temp_dir.path().join(rel).display().to_string()
+    }
+
+    fn versions(&self) -> Versions {
+        Versions::new(self.temp_dir.subdir("srv"))
+    }
+
+    /// Seeds a deployment targeting deployed with one config instance per file and
+    /// applies it, so its files are written.
+    async fn deploy(
+        &self,
+        id: &str,
+        files: &[(&str, &str)],
+        versions: Option<&Versions>,
+    ) -> Deployment {
+        let mut cfg_inst_ids = Vec::new();
+        for (filepath, content) in files {
+            let cfg_inst = ConfigInstance {
+                id: format!("{id}-{}", cfg_inst_ids.len()),
+                filepath: filepath.to_string(),
+                ..Default::default()
+            };
+            self.cfg_insts
+                .write(
+                    cfg_inst.id.clone(),
+                    cfg_inst.clone(),
+                    |_, _| false,
+                    Overwrite::Allow,
+                )
+                .await
+                .unwrap();
+            self.cfg_inst_content
+                .write(
+                    cfg_inst.id.clone(),
+                    content.to_string(),
+                    |_, _| false,
+                    Overwrite::Allow,
+                )
+                .await
+                .unwrap();
+            cfg_inst_ids.push(cfg_inst.id);
+        }
+        let deployment = Deployment {
+            id: id.to_string(),
+            target_status: DplTarget::Deployed,
+            activity_status: DplActivity::Queued,
+            config_instance_ids: cfg_inst_ids,
+            ..Default::default()
+        };
+        self.write_deployment(&deployment).await;
+        self.apply(versions).await;
+        self.read_deployment(id).await
+    }
+
+    async fn write_deployment(&self, deployment: &Deployment) {
+        self.deployments
+            .write(
+                deployment.id.clone(),
+                deployment.clone(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn read_deployment(&self, id: &str) -> Deployment {
+        self.deployments.read(id.to_string()).await.unwrap()
+    }
+
+    fn storage(&self) -> apply::Storage<'_> {
+        apply::Storage {
+            deployments: &self.deployments,
+            cfg_insts: storage::CfgInstRef {
+                meta: &self.cfg_insts,
+                content: &self.cfg_inst_content,
+                schemas: &self.config_schemas,
+            },
+            releases: &self.releases,
+        }
+    }
+
+    async fn apply(&self, versions: Option<&Versions>) {
+        let storage = self.storage();
+        let opts = apply::DeployOpts {
+            versions: versions.cloned(),
+            ..Default::default()
+        };
+        let args = apply::Args {
+            storage: &storage,
+            opts: &opts,
+        };
+        for outcome in apply(&args).await.unwrap() {
+            assert!(outcome.error.is_none(), "{:?}", outcome.error);
+        }
+    }
+
+    async fn detect(&self, versions: Option<&Versions>) -> Vec<Drift> {
+        drift::detect(&self.storage(), versions).await.unwrap()
+    }
+}
+
+fn sha256(data: &str) -> String {
+    Digest::compute(Algorithm::Sha256, data.as_bytes())
+        .hex()
+        .to_string()
+}
+
+fn write(filepath: &str, content: &str) {
+    std::fs::write(filepath, content).unwrap();
+}
+
+// ================================= DETECT ====================================== //
+pub mod detect {
+    use super::*;
+
+    #[tokio::test]
+    async fn unchanged_files_have_not_drifted() {
+        let f = Fixture::new("drift_unchanged").await;
+        let filepath = f.path("app.json");
+        f.deploy("dpl-1", &[(&filepath, r#"{"speed":4}"#)], None)
+            .await;
+
+        assert!(f.detect(None).await.is_empty());
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn edited_file() {
+        let f = Fixture::new("drift_edited").await;
+        let untouched = f.path("untouched.json");
+        let edited = f.path("edited.json");
+        f.deploy(
+            "dpl-1",
+            &[(&untouched, r#"{"a":1}"#), (&edited, r#"{"b":2}"#)],
+            None,
+        )
+        .await;
+        write(&edited, r#"{"b":3}"#);
+
+        assert_eq!(
+            f.detect(None).await,
+            vec![Drift {
+                deployment_id: "dpl-1".to_string(),
+                cfg_inst_id: "dpl-1-1".to_string(),
+                filepath: edited,
+                expected: sha256(r#"{"b":2}"#),
+                actual: Some(sha256(r#"{"b":3}"#)),
+            }]
+        );
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn removed_file() {
+        let f = Fixture::new("drift_removed").await;
+        let filepath = f.path("app.json");
+        f.deploy("dpl-1", &[(&filepath, r#"{"speed":4}"#)], None)
+            .await;
+        std::fs::remove_file(&filepath).unwrap();
+
+        let drifts = f.detect(None).await;
+        assert_eq!(drifts.len(), 1);
+        assert_eq!(drifts[0].actual, None);
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn converted_content_is_compared_as_written() {
+        let f = Fixture::new("drift_converted").await;
+        let filepath = f.path("app.yaml");
+        f.deploy("dpl-1", &[(&filepath, r#"{"speed":4}"#)], None)
+            .await;
+        assert_eq!(std::fs::read_to_string(&filepath).unwrap(), "speed: 4\n");
+
+        assert!(f.detect(None).await.is_empty());
+        write(&filepath, "speed: 5\n");
+        assert_eq!(f.detect(None).await.len(), 1);
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_deployed_deployments_are_checked() {
+        let f = Fixture::new("drift_only_deployed").await;
+        let filepath = f.path("app.json");
+        let mut deployment = f
+            .deploy("dpl-1", &[(&filepath, r#"{"speed":4}"#)], None)
+            .await;
+        write(&filepath, r#"{"speed":5}"#);
+
+        // the deployment is being removed
+        deployment.target_status = DplTarget::Archived;
+        f.write_deployment(&deployment).await;
+        assert!(f.detect(None).await.is_empty());
+
+        // the deployment has already been marked
+        deployment.target_status = DplTarget::Deployed;
+        f.write_deployment(&fsm::drift(deployment)).await;
+        assert!(f.detect(None).await.is_empty());
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn versioned_files_are_skipped_while_rolled_back() {
+        let f = Fixture::new("drift_rolled_back").await;
+        let versions = f.versions();
+        let versioned = versions
+            .current_link()
+            .path()
+            .join("app.json")
+            .display()
+            .to_string();
+        let in_place = f.path("in_place.json");
+        f.deploy("dpl-1", &[(&versioned, r#"{"v":1}"#)], Some(&versions))
+            .await;
+        let mut first = f.read_deployment("dpl-1").await;
+        first.target_status = DplTarget::Archived;
+        f.write_deployment(&first).await;
+        f.deploy(
+            "dpl-2",
+            &[(&versioned, r#"{"v":2}"#), (&in_place, r#"{"p":2}"#)],
+            Some(&versions),
+        )
+        .await;
+        assert!(f.detect(Some(&versions)).await.is_empty());
+
+        // the link now holds the first release's content, which isn't drift
+        versions.rollback(None).await.unwrap();
+        assert!(f.detect(Some(&versions)).await.is_empty());
+
+        // files outside the link are still checked
+        write(&in_place, r#"{"p":3}"#);
+        let drifts = f.detect(Some(&versions)).await;
+        assert_eq!(drifts.len(), 1);
+        assert_eq!(drifts[0].filepath, in_place);
+        f.temp_dir.delete().await.unwrap();
+    }
+}
+
+// ================================== MARK ======================================= //
+pub mod mark {
+    use super::*;
+
+    #[tokio::test]
+    async fn marks_and_redeploys() {
+        let f = Fixture::new("drift_mark").await;
+        let filepath = f.path("app.json");
+        f.deploy("dpl-1", &[(&filepath, r#"{"speed":4}"#)], None)
+            .await;
+        write(&filepath, r#"{"speed":5}"#);
+
+        let drifts = f.detect(None).await;
+        let marked = drift::mark(&f.deployments, &drifts).await.unwrap();
+        assert_eq!(marked, vec!["dpl-1".to_string()]);
+        let deployment = f.read_deployment("dpl-1").await;
+        assert_eq!(deployment.activity_status, DplActivity::Drifted);
+        assert_eq!(fsm::next_action(&deployment), NextAction::Deploy);
+
+        f.apply(None).await;
+        assert_eq!(
+            std::fs::read_to_string(&filepath).unwrap(),
+            r#"{"speed":4}"#
+        );
+        let deployment = f.read_deployment("dpl-1").await;
+        assert_eq!(deployment.activity_status, DplActivity::Deployed);
+        assert!(f.detect(None).await.is_empty());
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn marks_each_deployment_once() {
+        let f = Fixture::new("drift_mark_once").await;
+        let a = f.path("a.json");
+        let b = f.path("b.json");
+        f.deploy("dpl-1", &[(&a, r#"{"a":1}"#), (&b, r#"{"b":1}"#)], None)
+            .await;
+        std::fs::remove_file(&a).unwrap();
+        std::fs::remove_file(&b).unwrap();
+
+        let drifts = f.detect(None).await;
+        assert_eq!(drifts.len(), 2);
+        let marked = drift::mark(&f.deployments, &drifts).await.unwrap();
+        assert_eq!(marked, vec!["dpl-1".to_string()]);
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn skips_deployments_which_left_the_deployed_state() {
+        let f = Fixture::new("drift_mark_skip").await;
+        let filepath = f.path("app.json");
+        let mut deployment = f
+            .deploy("dpl-1", &[(&filepath, r#"{"speed":4}"#)], None)
+            .await;
+        write(&filepath, r#"{"speed":5}"#);
+        let drifts = f.detect(None).await;
+
+        deployment.target_status = DplTarget::Archived;
+        f.write_deployment(&deployment).await;
+        let marked = drift::mark(&f.deployments, &drifts).await.unwrap();
+        assert!(marked.is_empty());
+        let deployment = f.read_deployment("dpl-1").await;
+        assert_eq!(deployment.activity_status, DplActivity::Deployed);
+        f.temp_dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn skips_evicted_deployments() {
+        let f = Fixture::new("drift_mark_evicted").await;
+        let drifts = vec![Drift {
+            deployment_id: "dpl-gone".to_string(),
+            cfg_inst_id: "cfg-inst".to_string(),
+            filepath: f.path("app.json"),
+            expected: sha256("{}"),
+            actual: None,
+        }];
+        let marked = drift::mark(&f.deployments, &drifts).await.unwrap();
+        assert!(marked.is_empty());
+        f.temp_dir.delete().await.unwrap();
+    }
+}
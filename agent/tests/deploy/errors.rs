@@ -3,10 +3,12 @@ use miru_agent::cache::errors::CacheElementNotFound;
 use miru_agent::cache::CacheErr;
 use miru_agent::deploy::errors::{
     BackupAccessDeniedErr, ConflictingDeploymentsErr, DuplicateFilepathErr,
-    EmptyConfigInstancesErr, GenericErr, InvalidDeploymentTargetErr, PathNotAllowedErr,
-    WriteAccessDeniedErr,
+    EmptyConfigInstancesErr, GenericErr, HookErr, InvalidDeploymentTargetErr, InvalidSignatureErr,
+    PathNotAllowedErr, SchemaViolationErr, WriteAccessDeniedErr,
 };
+use miru_agent::deploy::hooks::Stage;
 use miru_agent::deploy::DeployErr;
+use miru_agent::errors::Error;
 use miru_agent::filesys::errors::InvalidDirNameErr;
 use miru_agent::filesys::FileSysErr;
 use miru_agent::models::DplTarget;
@@ -160,6 +162,61 @@ mod from_conversions {
         assert!(matches!(err, DeployErr::BackupAccessDenied(_)));
     }
 
+    #[test]
+    fn hook_err_maps_to_deploy_hook() {
+        let err: DeployErr = HookErr {
+            deployment_id: "dpl_1".to_string(),
+            stage: Stage::PostDeploy,
+            command: "systemctl restart robot".to_string(),
+            msg: "exited with exit status: 1".to_string(),
+            stderr: "unit robot.service not found".to_string(),
+            trace: miru_agent::trace!(),
+        }
+        .into();
+        assert!(matches!(err, DeployErr::Hook(_)));
+        assert_eq!(
+            err.to_string(),
+            "post_deploy hook 'systemctl restart robot' for deployment 'dpl_1' failed: exited with exit status: 1 (stderr: unit robot.service not found)"
+        );
+    }
+
+    #[test]
+    fn schema_violation_err_maps_to_deploy_schema_violation() {
+        let err: DeployErr = SchemaViolationErr {
+            cfg_inst_id: "cfg_inst_1".to_string(),
+            filepath: "/srv/robot/config.json".to_string(),
+            config_schema_id: "cfg_sch_1".to_string(),
+            violations: vec![
+                "/: missing required property 'port'".to_string(),
+                "/speed: expected number, found string".to_string(),
+            ],
+            trace: miru_agent::trace!(),
+        }
+        .into();
+        assert!(matches!(err, DeployErr::SchemaViolation(_)));
+        assert_eq!(err.code().as_str(), "schema_violation");
+        assert_eq!(
+            err.to_string(),
+            "config instance 'cfg_inst_1' for '/srv/robot/config.json' doesn't match config schema 'cfg_sch_1': /: missing required property 'port'; /speed: expected number, found string"
+        );
+    }
+
+    #[test]
+    fn invalid_signature_err_maps_to_deploy_invalid_signature() {
+        let err: DeployErr = InvalidSignatureErr {
+            release_id: "rls_1".to_string(),
+            msg: "the release isn't signed".to_string(),
+            trace: miru_agent::trace!(),
+        }
+        .into();
+        assert!(matches!(err, DeployErr::InvalidSignature(_)));
+        assert_eq!(err.code().as_str(), "invalid_signature");
+        assert_eq!(
+            err.to_string(),
+            "release 'rls_1' failed signature verification: the release isn't signed"
+        );
+    }
+
     #[test]
     fn duplicate_filepath_err_maps_to_deploy_duplicate_filepath() {
         let err: DeployErr = duplicate_filepath_err().into();
@@ -1,10 +1,10 @@
 // standard crates
-use std::os::unix::fs::PermissionsExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 
 // internal crates
 use miru_agent::deploy::filesys::{deploy, remove, BACKUP_FILE_PREFIX};
-use miru_agent::deploy::DeployErr;
+use miru_agent::deploy::{trash, versions, DeployErr};
 use miru_agent::filesys::{self, Overwrite, PathExt, WriteOptions};
 use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplTarget};
 use miru_agent::storage;
@@ -15,6 +15,7 @@ use serde_json::json;
 struct Fixture {
     cfg_inst_meta: storage::CfgInsts,
     cfg_inst_content: storage::CfgInstContent,
+    config_schemas: storage::ConfigSchemas,
     pub(super) temp_dir: filesys::Dir,
 }
 
@@ -33,10 +34,15 @@ impl Fixture {
             storage::CfgInstContent::spawn(16, resources_dir.subdir("content"), 1000)
                 .await
                 .unwrap();
+        let (config_schemas, _) =
+            storage::ConfigSchemas::spawn(16, resources_dir.subdir("schemas"), 1000)
+                .await
+                .unwrap();
 
         Self {
             cfg_inst_meta,
             cfg_inst_content,
+            config_schemas,
             temp_dir,
         }
     }
@@ -97,11 +103,24 @@ impl Fixture {
         storage::CfgInstRef {
             meta: &self.cfg_inst_meta,
             content: &self.cfg_inst_content,
+            schemas: &self.config_schemas,
         }
     }
 
     async fn deploy(&self, deployment: &Deployment) -> Result<(), DeployErr> {
-        deploy(&self.storage_ref(), deployment).await
+        deploy(&self.storage_ref(), deployment, None, None).await
+    }
+
+    async fn deploy_versioned(
+        &self,
+        versions: &versions::Versions,
+        deployment: &Deployment,
+    ) -> Result<(), DeployErr> {
+        deploy(&self.storage_ref(), deployment, Some(versions), None).await
+    }
+
+    fn versions(&self) -> versions::Versions {
+        versions::Versions::new(self.temp_dir.subdir("srv"))
     }
 
     async fn remove(
@@ -109,7 +128,11 @@ impl Fixture {
         deployment: &Deployment,
         keeps: &[filesys::File],
     ) -> Result<(), DeployErr> {
-        remove(&self.storage_ref(), deployment, keeps).await
+        remove(&self.storage_ref(), deployment, keeps, None).await
+    }
+
+    fn trash(&self) -> trash::Trash {
+        trash::Trash::new(self.temp_dir.subdir("trash"), trash::Options::default())
     }
 }
 
@@ -262,6 +285,26 @@ pub mod deploy_func_success {
         assert_eq!(actual, content);
     }
 
+    #[tokio::test]
+    async fn unchanged_file_not_rewritten() {
+        let f = Fixture::new().await;
+        let filepath = f.fixture_path("test/filepath").await;
+        let cfg_inst = ConfigInstance {
+            filepath: filepath.clone(),
+            ..Default::default()
+        };
+        let content = "{\"speed\": 4}".to_string();
+        f.seed_cfg_inst(&cfg_inst, content.clone()).await;
+
+        let deployment = f.new_queued(std::slice::from_ref(&cfg_inst));
+        f.deploy(&deployment).await.unwrap();
+        let inode = std::fs::metadata(&filepath).unwrap().ino();
+
+        // atomic writes replace the file so an unchanged inode means no write
+        f.deploy(&deployment).await.unwrap();
+        assert_eq!(std::fs::metadata(&filepath).unwrap().ino(), inode);
+    }
+
     #[tokio::test]
     async fn nested_filepath() {
         let f = Fixture::new().await;
@@ -906,13 +949,479 @@ pub mod deploy_func_write_errs {
     }
 }
 
+pub mod deploy_func_versioned {
+    use super::*;
+
+    fn cfg_inst(id: &str, filepath: String) -> ConfigInstance {
+        ConfigInstance {
+            id: id.to_string(),
+            filepath,
+            ..Default::default()
+        }
+    }
+
+    fn current_path(versions: &versions::Versions, rel: &str) -> String {
+        versions
+            .current_link()
+            .path()
+            .join(rel)
+            .display()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn stages_a_release_and_switches_to_it() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let motion = cfg_inst("ci_motion", current_path(&versions, "motion.json"));
+        let vision = cfg_inst("ci_vision", current_path(&versions, "vision/camera.json"));
+        f.seed_cfg_inst(&motion, "{\"speed\": 4}".to_string()).await;
+        f.seed_cfg_inst(&vision, "{\"fps\": 30}".to_string()).await;
+
+        f.deploy_versioned(&versions, &f.new_queued(&[motion.clone(), vision.clone()]))
+            .await
+            .unwrap();
+
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(versions.releases().await.unwrap(), vec![1]);
+        let link = std::fs::read_link(versions.current_link().path()).unwrap();
+        assert_eq!(link, PathBuf::from("releases/1"));
+        assert_eq!(
+            filesys::File::new(&motion.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "{\"speed\": 4}"
+        );
+        assert_eq!(
+            filesys::File::new(&vision.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "{\"fps\": 30}"
+        );
+        assert!(!versions.dir().subdir("staging").exists());
+    }
+
+    #[tokio::test]
+    async fn new_release_replaces_every_file() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let old = cfg_inst("ci_old", current_path(&versions, "old.json"));
+        f.seed_cfg_inst(&old, "old".to_string()).await;
+        f.deploy_versioned(&versions, &f.new_queued(std::slice::from_ref(&old)))
+            .await
+            .unwrap();
+
+        let new = cfg_inst("ci_new", current_path(&versions, "new.json"));
+        f.seed_cfg_inst(&new, "new".to_string()).await;
+        f.deploy_versioned(&versions, &f.new_queued(std::slice::from_ref(&new)))
+            .await
+            .unwrap();
+
+        assert_eq!(versions.current().await.unwrap(), Some(2));
+        assert!(!filesys::File::new(&old.filepath).exists());
+        assert_eq!(
+            filesys::File::new(&new.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "new"
+        );
+        // the previous release is kept
+        assert!(versions.release_dir(1).file("old.json").exists());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_live_and_previous_releases() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        for i in 1..=4 {
+            let ci = cfg_inst(&format!("ci_{i}"), current_path(&versions, "app.json"));
+            f.seed_cfg_inst(&ci, format!("{i}")).await;
+            f.deploy_versioned(&versions, &f.new_queued(&[ci]))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(versions.releases().await.unwrap(), vec![3, 4]);
+        assert_eq!(versions.current().await.unwrap(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn writes_files_outside_the_deployment_directory_in_place() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let versioned = cfg_inst("ci_versioned", current_path(&versions, "app.json"));
+        let in_place = cfg_inst("ci_in_place", f.fixture_path("etc/app.json").await);
+        f.seed_cfg_inst(&versioned, "versioned".to_string()).await;
+        f.seed_cfg_inst(&in_place, "in place".to_string()).await;
+
+        f.deploy_versioned(
+            &versions,
+            &f.new_queued(&[versioned.clone(), in_place.clone()]),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            versions
+                .release_dir(1)
+                .file("app.json")
+                .read_string()
+                .await
+                .unwrap(),
+            "versioned"
+        );
+        assert_eq!(
+            filesys::File::new(&in_place.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "in place"
+        );
+    }
+
+    /// An in-place file written through a symlink to the `current` link lands in the
+    /// live release, so where it ends up shows whether the release was live first.
+    async fn through_current(f: &Fixture, versions: &versions::Versions) -> String {
+        let link = f.fixture_path("etc/live").await;
+        std::fs::create_dir_all(Path::new(&link).parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(versions.current_link().path(), &link).unwrap();
+        Path::new(&link).join("in_place.json").display().to_string()
+    }
+
+    #[tokio::test]
+    async fn release_goes_live_before_in_place_dependents() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let versioned = ConfigInstance {
+            config_type_id: "ct_base".to_string(),
+            ..cfg_inst("ci_versioned", current_path(&versions, "base.json"))
+        };
+        let in_place = ConfigInstance {
+            config_type_id: "ct_app".to_string(),
+            ..cfg_inst("ci_in_place", through_current(&f, &versions).await)
+        };
+        f.seed_cfg_inst(&versioned, "base".to_string()).await;
+        f.seed_cfg_inst(&in_place, "app".to_string()).await;
+        let mut deployment = f.new_queued(&[in_place.clone(), versioned.clone()]);
+        deployment.dependencies.config_types =
+            [("ct_app".to_string(), vec!["ct_base".to_string()])].into();
+
+        f.deploy_versioned(&versions, &deployment).await.unwrap();
+
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(
+            versions
+                .release_dir(1)
+                .file("in_place.json")
+                .read_string()
+                .await
+                .unwrap(),
+            "app"
+        );
+    }
+
+    #[tokio::test]
+    async fn in_place_dependencies_are_written_before_the_release_goes_live() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let versioned = ConfigInstance {
+            config_type_id: "ct_app".to_string(),
+            ..cfg_inst("ci_versioned", current_path(&versions, "app.json"))
+        };
+        let in_place = ConfigInstance {
+            config_type_id: "ct_base".to_string(),
+            ..cfg_inst("ci_in_place", through_current(&f, &versions).await)
+        };
+        f.seed_cfg_inst(&versioned, "app".to_string()).await;
+        f.seed_cfg_inst(&in_place, "base".to_string()).await;
+        let mut deployment = f.new_queued(&[versioned.clone(), in_place.clone()]);
+        deployment.dependencies.config_types =
+            [("ct_app".to_string(), vec!["ct_base".to_string()])].into();
+
+        // nothing is live yet, so the in-place file can't be written through the link
+        let result = f.deploy_versioned(&versions, &deployment).await;
+
+        assert!(result.is_err());
+        assert!(!versions.current_link().exists());
+        assert!(versions.releases().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_in_place_write_after_going_live_restores_the_previous_release() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let ci = ConfigInstance {
+            config_type_id: "ct_base".to_string(),
+            ..cfg_inst("ci_1", current_path(&versions, "base.json"))
+        };
+        f.seed_cfg_inst(&ci, "v1".to_string()).await;
+        f.deploy_versioned(&versions, &f.new_queued(std::slice::from_ref(&ci)))
+            .await
+            .unwrap();
+
+        // a directory in the dependent in-place file's way fails its write
+        let in_place = ConfigInstance {
+            config_type_id: "ct_app".to_string(),
+            ..cfg_inst("ci_in_place", f.fixture_path("etc/app.json").await)
+        };
+        std::fs::create_dir_all(&in_place.filepath).unwrap();
+        let updated = ConfigInstance {
+            id: "ci_2".to_string(),
+            ..ci.clone()
+        };
+        f.seed_cfg_inst(&updated, "v2".to_string()).await;
+        f.seed_cfg_inst(&in_place, "in place".to_string()).await;
+        let mut deployment = f.new_queued(&[in_place, updated]);
+        deployment.dependencies.config_types =
+            [("ct_app".to_string(), vec!["ct_base".to_string()])].into();
+
+        let result = f.deploy_versioned(&versions, &deployment).await;
+
+        assert!(result.is_err());
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(versions.releases().await.unwrap(), vec![1]);
+        assert_eq!(
+            filesys::File::new(&ci.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "v1"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_content_leaves_the_previous_release_live() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let ci = cfg_inst("ci_1", current_path(&versions, "app.json"));
+        f.seed_cfg_inst(&ci, "v1".to_string()).await;
+        f.deploy_versioned(&versions, &f.new_queued(std::slice::from_ref(&ci)))
+            .await
+            .unwrap();
+
+        let updated = cfg_inst("ci_2", current_path(&versions, "app.json"));
+        let unseeded = cfg_inst("ci_3", current_path(&versions, "other.json"));
+        f.seed_cfg_inst(&updated, "v2".to_string()).await;
+        f.cfg_inst_meta
+            .write(
+                unseeded.id.clone(),
+                unseeded.clone(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+
+        let result = f
+            .deploy_versioned(&versions, &f.new_queued(&[updated, unseeded]))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(versions.releases().await.unwrap(), vec![1]);
+        assert_eq!(
+            filesys::File::new(&ci.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "v1"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_in_place_write_discards_the_release() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        let ci = cfg_inst("ci_1", current_path(&versions, "app.json"));
+        f.seed_cfg_inst(&ci, "v1".to_string()).await;
+        f.deploy_versioned(&versions, &f.new_queued(std::slice::from_ref(&ci)))
+            .await
+            .unwrap();
+
+        // a directory in the in-place file's way fails its write
+        let in_place = cfg_inst("ci_in_place", f.fixture_path("etc/app.json").await);
+        std::fs::create_dir_all(&in_place.filepath).unwrap();
+        let updated = cfg_inst("ci_2", current_path(&versions, "app.json"));
+        f.seed_cfg_inst(&updated, "v2".to_string()).await;
+        f.seed_cfg_inst(&in_place, "in place".to_string()).await;
+
+        let result = f
+            .deploy_versioned(&versions, &f.new_queued(&[updated, in_place]))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(versions.releases().await.unwrap(), vec![1]);
+        assert_eq!(
+            filesys::File::new(&ci.filepath)
+                .read_string()
+                .await
+                .unwrap(),
+            "v1"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_reserved_paths() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        for filepath in [
+            versions.current_link().path().display().to_string(),
+            versions
+                .release_dir(1)
+                .file("app.json")
+                .path()
+                .display()
+                .to_string(),
+        ] {
+            let ci = cfg_inst("ci_reserved", filepath);
+            f.seed_cfg_inst(&ci, "content".to_string()).await;
+
+            let result = f.deploy_versioned(&versions, &f.new_queued(&[ci])).await;
+
+            assert!(matches!(result, Err(DeployErr::PathNotAllowed(_))));
+        }
+        assert!(!versions.current_link().exists());
+    }
+
+    #[tokio::test]
+    async fn current_directory_is_not_replaced() {
+        let f = Fixture::new().await;
+        let versions = f.versions();
+        std::fs::create_dir_all(versions.current_link().path()).unwrap();
+        let ci = cfg_inst("ci_1", current_path(&versions, "app.json"));
+        f.seed_cfg_inst(&ci, "content".to_string()).await;
+
+        let result = f.deploy_versioned(&versions, &f.new_queued(&[ci])).await;
+
+        assert!(matches!(result, Err(DeployErr::PathNotAllowed(_))));
+        assert!(versions.current_link().path().is_dir());
+        assert!(versions.releases().await.unwrap().is_empty());
+    }
+}
+
+pub mod deploy_func_formats {
+    use super::*;
+    use miru_agent::models::config_instance::ContentFormat;
+
+    #[tokio::test]
+    async fn converts_json_to_the_file_format() {
+        let f = Fixture::new().await;
+        let yaml = ConfigInstance {
+            id: "ci_yaml".to_string(),
+            filepath: f.fixture_path("motion.yaml").await,
+            ..Default::default()
+        };
+        let toml = ConfigInstance {
+            id: "ci_toml".to_string(),
+            filepath: f.fixture_path("motion.toml").await,
+            ..Default::default()
+        };
+        let content = "{\"speed\": 4, \"arm\": {\"reach\": 1.5}}".to_string();
+        f.seed_cfg_inst(&yaml, content.clone()).await;
+        f.seed_cfg_inst(&toml, content).await;
+
+        f.deploy(&f.new_queued(&[yaml.clone(), toml.clone()]))
+            .await
+            .unwrap();
+
+        let actual = filesys::File::new(&yaml.filepath)
+            .read_string()
+            .await
+            .unwrap();
+        assert_eq!(actual, "arm:\n  reach: 1.5\nspeed: 4\n");
+        let actual = filesys::File::new(&toml.filepath)
+            .read_string()
+            .await
+            .unwrap();
+        assert_eq!(actual, "speed = 4\n\n[arm]\nreach = 1.5\n");
+    }
+
+    #[tokio::test]
+    async fn writes_content_which_isnt_json_verbatim() {
+        let f = Fixture::new().await;
+        let cfg_inst = ConfigInstance {
+            filepath: f.fixture_path("motion.yaml").await,
+            ..Default::default()
+        };
+        let content = "speed: 4\n# tuned on site\n".to_string();
+        f.seed_cfg_inst(&cfg_inst, content.clone()).await;
+
+        f.deploy(&f.new_queued(std::slice::from_ref(&cfg_inst)))
+            .await
+            .unwrap();
+
+        let actual = filesys::File::new(&cfg_inst.filepath)
+            .read_string()
+            .await
+            .unwrap();
+        assert_eq!(actual, content);
+    }
+
+    #[tokio::test]
+    async fn decodes_binary_content() {
+        let f = Fixture::new().await;
+        let cfg_inst = ConfigInstance {
+            filepath: f.fixture_path("calibration.bin").await,
+            content_format: ContentFormat::Binary,
+            ..Default::default()
+        };
+        // 0x00 0xff 0x10 0x80
+        f.seed_cfg_inst(&cfg_inst, "AP8QgA==".to_string()).await;
+
+        f.deploy(&f.new_queued(std::slice::from_ref(&cfg_inst)))
+            .await
+            .unwrap();
+
+        let actual = filesys::File::new(&cfg_inst.filepath)
+            .read_bytes()
+            .await
+            .unwrap();
+        assert_eq!(actual, vec![0x00, 0xff, 0x10, 0x80]);
+
+        // unchanged binary content is left untouched
+        f.deploy(&f.new_queued(std::slice::from_ref(&cfg_inst)))
+            .await
+            .unwrap();
+        assert!(detect_backup_files(&f.temp_dir).is_empty());
+    }
+
+    #[tokio::test]
+    async fn conversion_error_writes_nothing() {
+        let f = Fixture::new().await;
+        let json = ConfigInstance {
+            id: "ci_json".to_string(),
+            filepath: f.fixture_path("motion.json").await,
+            ..Default::default()
+        };
+        let toml = ConfigInstance {
+            id: "ci_toml".to_string(),
+            filepath: f.fixture_path("motion.toml").await,
+            ..Default::default()
+        };
+        f.seed_cfg_inst(&json, "{\"speed\": 4}".to_string()).await;
+        // TOML has no null
+        f.seed_cfg_inst(&toml, "{\"speed\": null}".to_string())
+            .await;
+
+        let result = f.deploy(&f.new_queued(&[json.clone(), toml.clone()])).await;
+
+        assert!(matches!(result, Err(DeployErr::ContentConversion(_))));
+        assert!(!filesys::File::new(&json.filepath).exists());
+        assert!(!filesys::File::new(&toml.filepath).exists());
+    }
+}
+
 pub mod remove_func_success {
     use super::*;
     use miru_agent::filesys::PathExt;
 
     /// Helper: seed a cfg_inst, deploy it so the file exists on disk, then return
     /// a removal deployment referencing the same cfg_inst.
-    async fn seed_and_deploy(f: &Fixture, rel: &str, content: &str) -> ConfigInstance {
+    pub(super) async fn seed_and_deploy(f: &Fixture, rel: &str, content: &str) -> ConfigInstance {
         let filepath = f.fixture_path(rel).await;
         let cfg_inst = ConfigInstance {
             filepath,
@@ -1033,6 +1542,70 @@ pub mod remove_func_success {
     }
 }
 
+pub mod remove_func_trash {
+    use super::*;
+
+    #[tokio::test]
+    async fn moves_files_into_trash() {
+        let f = Fixture::new().await;
+        let ci = remove_func_success::seed_and_deploy(&f, "config.json", r#"{"v": 1}"#).await;
+        let dest = filesys::File::new(&ci.filepath);
+        let trash = f.trash();
+
+        let dpl = f.new_removing(std::slice::from_ref(&ci));
+        remove(&f.storage_ref(), &dpl, &[], Some(&trash))
+            .await
+            .unwrap();
+        assert!(!dest.path().exists(), "file should be removed");
+
+        let entries = trash.list().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].filepath, ci.filepath);
+        assert_eq!(entries[0].deployment_id, dpl.id);
+        assert_eq!(entries[0].config_instance_id, ci.id);
+
+        trash.restore(&entries[0].id, false).await.unwrap();
+        assert_eq!(dest.read_string().await.unwrap(), r#"{"v": 1}"#);
+    }
+
+    #[tokio::test]
+    async fn keeps_protected_files_out_of_trash() {
+        let f = Fixture::new().await;
+        let ci = remove_func_success::seed_and_deploy(&f, "keep.json", r#"{"keep": true}"#).await;
+        let keep_file = filesys::File::new(&ci.filepath);
+        let trash = f.trash();
+
+        let dpl = f.new_removing(std::slice::from_ref(&ci));
+        remove(
+            &f.storage_ref(),
+            &dpl,
+            std::slice::from_ref(&keep_file),
+            Some(&trash),
+        )
+        .await
+        .unwrap();
+        assert!(keep_file.path().exists(), "protected file should survive");
+        assert!(trash.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_not_trashed() {
+        let f = Fixture::new().await;
+        let ci = ConfigInstance {
+            filepath: f.fixture_path("nonexistent.json").await,
+            ..Default::default()
+        };
+        f.seed_cfg_inst(&ci, "{}".to_string()).await;
+        let trash = f.trash();
+
+        let dpl = f.new_removing(std::slice::from_ref(&ci));
+        remove(&f.storage_ref(), &dpl, &[], Some(&trash))
+            .await
+            .unwrap();
+        assert!(trash.list().await.unwrap().is_empty());
+    }
+}
+
 pub mod remove_func_errs {
     use super::*;
 
@@ -0,0 +1,265 @@
+// internal crates
+use miru_agent::deploy::format::{render, to_ini, to_toml, to_yaml};
+use miru_agent::deploy::DeployErr;
+use miru_agent::models::config_instance::{ConfigInstance, ContentFormat};
+
+// external crates
+use serde_json::json;
+
+fn cfg_inst(filepath: &str, content_format: ContentFormat) -> ConfigInstance {
+    ConfigInstance {
+        filepath: filepath.to_string(),
+        content_format,
+        ..Default::default()
+    }
+}
+
+pub mod render_func {
+    use super::*;
+
+    #[test]
+    fn json_file_is_verbatim() {
+        let content = "{\"speed\":   4}";
+        let cfg_inst = cfg_inst("/etc/motion.json", ContentFormat::Json);
+        assert_eq!(render(&cfg_inst, content).unwrap(), content.as_bytes());
+    }
+
+    #[test]
+    fn unknown_extension_is_verbatim() {
+        let content = "{\"speed\": 4}";
+        let cfg_inst = cfg_inst("/etc/motion.conf", ContentFormat::Json);
+        assert_eq!(render(&cfg_inst, content).unwrap(), content.as_bytes());
+    }
+
+    #[test]
+    fn converts_json_by_extension() {
+        let content = "{\"speed\": 4}";
+        let cases = [
+            ("/etc/motion.yaml", "speed: 4\n"),
+            ("/etc/motion.yml", "speed: 4\n"),
+            ("/etc/motion.toml", "speed = 4\n"),
+            ("/etc/motion.ini", "speed = 4\n"),
+        ];
+        for (filepath, expected) in cases {
+            let cfg_inst = cfg_inst(filepath, ContentFormat::Json);
+            let actual = render(&cfg_inst, content).unwrap();
+            assert_eq!(String::from_utf8(actual).unwrap(), expected, "{filepath}");
+        }
+    }
+
+    #[test]
+    fn other_formats_are_verbatim() {
+        let content = "speed = 4\n";
+        let cfg_inst = cfg_inst("/etc/motion.yaml", ContentFormat::Toml);
+        assert_eq!(render(&cfg_inst, content).unwrap(), content.as_bytes());
+    }
+
+    #[test]
+    fn binary_is_decoded() {
+        let cfg_inst = cfg_inst("/etc/firmware.bin", ContentFormat::Binary);
+        assert_eq!(render(&cfg_inst, "aGk=\n").unwrap(), b"hi");
+    }
+
+    #[test]
+    fn invalid_base64() {
+        let cfg_inst = cfg_inst("/etc/firmware.bin", ContentFormat::Binary);
+        let result = render(&cfg_inst, "not base64!");
+        assert!(matches!(result, Err(DeployErr::ContentConversion(_))));
+    }
+}
+
+pub mod yaml {
+    use super::*;
+
+    #[test]
+    fn nested_documents() {
+        let document = json!({
+            "name": "arm",
+            "limits": {"speed": 1.5, "axes": [1, 2]},
+            "tools": [{"id": "gripper", "force": 20}, []],
+            "enabled": true,
+            "offset": null,
+            "tags": {},
+        });
+        let expected = "\
+enabled: true
+limits:
+  axes:
+    - 1
+    - 2
+  speed: 1.5
+name: \"arm\"
+offset: null
+tags: {}
+tools:
+  -
+    force: 20
+    id: \"gripper\"
+  - []
+";
+        assert_eq!(to_yaml(&document), expected);
+    }
+
+    #[test]
+    fn quotes_keys_and_strings() {
+        let document = json!({"on": "yes", "with space": "line\nbreak", "1st": "x"});
+        let expected = "\
+\"1st\": \"x\"
+\"on\": \"yes\"
+\"with space\": \"line\\nbreak\"
+";
+        assert_eq!(to_yaml(&document), expected);
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(to_yaml(&json!(4)), "4\n");
+        assert_eq!(to_yaml(&json!("arm")), "\"arm\"\n");
+        assert_eq!(to_yaml(&json!([])), "[]\n");
+    }
+
+    #[test]
+    fn floats_keep_a_yaml_1_1_form() {
+        assert_eq!(to_yaml(&json!(1e100)), "1.0e+100\n");
+        assert_eq!(to_yaml(&json!(2.5e-7)), "2.5e-7\n");
+        assert_eq!(to_yaml(&json!(1.5)), "1.5\n");
+    }
+
+    #[test]
+    fn round_trips() {
+        let document = json!({
+            "big": 1e100,
+            "small": -2.5e-7,
+            "int": -3,
+            "words": ["yes", "no", "on", "off", "null", "~", "1e3", "0x1F", ""],
+            "text": "line\nbreak: \"quoted\" # not a comment",
+            "nested": {"a b": [{"c": null}], "true": false},
+        });
+        let parsed: serde_json::Value = serde_yaml::from_str(&to_yaml(&document)).unwrap();
+        assert_eq!(parsed, document);
+    }
+}
+
+pub mod toml {
+    use super::*;
+
+    #[test]
+    fn tables_and_arrays_of_tables() {
+        let document = json!({
+            "name": "arm",
+            "limits": {"speed": 1.5, "joint": {"max": 90}},
+            "tools": [{"id": "gripper"}, {"id": "welder"}],
+            "axes": [1, 2],
+            "mixed": [1, {"a": "b"}],
+            "empty": {},
+        });
+        let parsed: serde_json::Value = ::toml::from_str(&to_toml(&document).unwrap()).unwrap();
+        assert_eq!(parsed, document);
+    }
+
+    #[test]
+    fn quotes_keys_and_strings() {
+        let document = json!({
+            "with space": {"a.b": 1, "[x]": "y = \"z\"\n# not a comment"},
+            "big": 1e100,
+        });
+        let parsed: serde_json::Value = ::toml::from_str(&to_toml(&document).unwrap()).unwrap();
+        assert_eq!(parsed, document);
+    }
+
+    #[test]
+    fn unrepresentable_documents() {
+        assert!(to_toml(&json!([1, 2])).is_err());
+        let err = to_toml(&json!({"limits": {"speed": null}})).unwrap_err();
+        assert!(err.contains("limits.speed"), "{err}");
+        assert!(to_toml(&json!({"big": u64::MAX})).is_err());
+    }
+}
+
+pub mod ini {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let document = json!({
+            "name": "arm",
+            "limits": {"speed": 1.5, "joint": {"max": 90}},
+            "network": {"dhcp": true, "gateway": null},
+        });
+        let expected = "\
+name = arm
+
+[limits]
+speed = 1.5
+
+[limits.joint]
+max = 90
+
+[network]
+dhcp = true
+gateway =
+";
+        assert_eq!(to_ini(&document).unwrap(), expected);
+    }
+
+    #[test]
+    fn unrepresentable_documents() {
+        assert!(to_ini(&json!("arm")).is_err());
+        assert!(to_ini(&json!({"axes": [1, 2]})).is_err());
+        let err = to_ini(&json!({"motd": {"text": "a\nb"}})).unwrap_err();
+        assert!(err.contains("motd.text"), "{err}");
+    }
+
+    #[test]
+    fn rejects_content_which_changes_the_file() {
+        let documents = [
+            json!({"a=b": 1}),
+            json!({"[section]": 1}),
+            json!({"a\nb": 1}),
+            json!({";key": 1}),
+            json!({"#key": 1}),
+            json!({" key": 1}),
+            json!({"key": "; value"}),
+            json!({"key": "# value"}),
+            json!({"key": "value ; comment"}),
+            json!({"key": " padded"}),
+            json!({"key": "a\rb"}),
+            json!({"a]b": {"key": 1}}),
+            json!({"a.b": {"key": 1}}),
+        ];
+        for document in documents {
+            assert!(to_ini(&document).is_err(), "{document}");
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let document = json!({
+            "name": "arm=left",
+            "url": "http://robot.local/a?b=c#frag",
+            "limits": {"speed": 1.5, "enabled": true},
+        });
+        let ini = to_ini(&document).unwrap();
+
+        // parses the INI as applications commonly do: split each line at the first
+        // `=` and trim both sides
+        let mut parsed = serde_json::Map::new();
+        let mut section: Option<String> = None;
+        for line in ini.lines().filter(|line| !line.is_empty()) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.to_string());
+                parsed.insert(name.to_string(), json!({}));
+                continue;
+            }
+            let (key, value) = line.split_once('=').unwrap();
+            let value = serde_json::from_str(value.trim()).unwrap_or(json!(value.trim()));
+            match &section {
+                Some(name) => parsed[name][key.trim()] = value,
+                None => {
+                    parsed.insert(key.trim().to_string(), value);
+                }
+            }
+        }
+        assert_eq!(serde_json::Value::Object(parsed), document);
+    }
+}
@@ -0,0 +1,153 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::deploy::hooks::{self, Options, Stage};
+use miru_agent::deploy::DeployErr;
+use miru_agent::models::Deployment;
+
+fn deployment() -> Deployment {
+    Deployment {
+        id: "dpl-1".to_string(),
+        release_id: "rls-1".to_string(),
+        ..Default::default()
+    }
+}
+
+fn sh(script: &str) -> Vec<String> {
+    vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+}
+
+pub mod options {
+    use super::*;
+
+    #[test]
+    fn command_per_stage() {
+        let options = Options {
+            pre_deploy: sh("a"),
+            post_remove: sh("b"),
+            ..Default::default()
+        };
+        assert_eq!(options.command(Stage::PreDeploy), sh("a"));
+        assert_eq!(options.command(Stage::PostRemove), sh("b"));
+        assert!(options.is_enabled(Stage::PreDeploy));
+        assert!(!options.is_enabled(Stage::PostDeploy));
+        assert!(!options.is_enabled(Stage::PreRemove));
+        assert!(options.is_enabled(Stage::PostRemove));
+    }
+
+    #[test]
+    fn no_hooks_by_default() {
+        let options = Options::default();
+        for stage in [
+            Stage::PreDeploy,
+            Stage::PostDeploy,
+            Stage::PreRemove,
+            Stage::PostRemove,
+        ] {
+            assert!(!options.is_enabled(stage), "{stage}");
+        }
+    }
+}
+
+pub mod run {
+    use super::*;
+
+    #[tokio::test]
+    async fn no_hook_configured() {
+        let output = hooks::run(&Options::default(), Stage::PreDeploy, &deployment(), &[])
+            .await
+            .unwrap();
+        assert_eq!(output, None);
+    }
+
+    #[tokio::test]
+    async fn passes_the_deployment_through_env() {
+        let options = Options {
+            post_deploy: sh(
+                "echo \"$MIRU_HOOK $MIRU_DEPLOYMENT_ID $MIRU_RELEASE_ID\"; echo \"$MIRU_CONFIG_FILES\"",
+            ),
+            ..Default::default()
+        };
+        let filepaths = vec![
+            "/etc/robot/a.json".to_string(),
+            "/etc/robot/b.json".to_string(),
+        ];
+
+        let output = hooks::run(&options, Stage::PostDeploy, &deployment(), &filepaths)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(output.stage, Stage::PostDeploy);
+        assert_eq!(
+            output.stdout,
+            "post_deploy dpl-1 rls-1\n/etc/robot/a.json\n/etc/robot/b.json"
+        );
+        assert_eq!(output.stderr, "");
+    }
+
+    #[tokio::test]
+    async fn captures_stderr_on_failure() {
+        let options = Options {
+            pre_deploy: sh("echo 'invalid config' >&2; exit 3"),
+            ..Default::default()
+        };
+
+        let err = hooks::run(&options, Stage::PreDeploy, &deployment(), &[])
+            .await
+            .unwrap_err();
+
+        let DeployErr::Hook(e) = err else {
+            panic!("expected a hook error, got {err:?}");
+        };
+        assert_eq!(e.stage, Stage::PreDeploy);
+        assert_eq!(e.deployment_id, "dpl-1");
+        assert_eq!(e.stderr, "invalid config");
+        assert!(e.to_string().contains("invalid config"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn truncates_long_output() {
+        let options = Options {
+            pre_remove: sh("head -c 100000 /dev/zero | tr '\\0' 'a'; echo end"),
+            ..Default::default()
+        };
+
+        let output = hooks::run(&options, Stage::PreRemove, &deployment(), &[])
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(output.stdout.len() <= hooks::MAX_OUTPUT_BYTES);
+        assert!(output.stdout.ends_with("aend"));
+    }
+
+    #[tokio::test]
+    async fn killed_after_timeout() {
+        let options = Options {
+            post_remove: sh("sleep 10"),
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+
+        let err = hooks::run(&options, Stage::PostRemove, &deployment(), &[])
+            .await
+            .unwrap_err();
+
+        let DeployErr::Hook(e) = err else {
+            panic!("expected a hook error, got {err:?}");
+        };
+        assert!(e.msg.contains("timed out"), "{}", e.msg);
+    }
+
+    #[tokio::test]
+    async fn missing_program() {
+        let options = Options {
+            pre_deploy: vec!["/nonexistent/miru-hook".to_string()],
+            ..Default::default()
+        };
+        let result = hooks::run(&options, Stage::PreDeploy, &deployment(), &[]).await;
+        assert!(matches!(result, Err(DeployErr::Hook(_))));
+    }
+}
@@ -1,3 +1,16 @@
 pub mod apply;
+pub mod created_dirs;
+pub mod drift;
 pub mod errors;
 pub mod filesys;
+pub mod format;
+pub mod hooks;
+pub mod order;
+pub mod pause;
+pub mod reboot;
+pub mod schedule;
+pub mod schema;
+pub mod signature;
+pub mod space;
+pub mod trash;
+pub mod versions;
@@ -0,0 +1,171 @@
+// standard crates
+use std::collections::{BTreeMap, HashMap};
+
+// internal crates
+use miru_agent::deploy::order;
+use miru_agent::deploy::DeployErr;
+use miru_agent::models::{ConfigInstance, Deployment};
+
+fn deployment(id: &str, depends_on: &[&str]) -> Deployment {
+    let mut dpl = Deployment {
+        id: id.to_string(),
+        ..Default::default()
+    };
+    dpl.dependencies.deployments = depends_on.iter().map(|id| id.to_string()).collect();
+    dpl
+}
+
+fn ids(deployments: &[Deployment]) -> Vec<&str> {
+    deployments.iter().map(|d| d.id.as_str()).collect()
+}
+
+pub mod topological {
+    use super::*;
+
+    fn sort(
+        roots: &[&'static str],
+        edges: &[(&'static str, &'static str)],
+    ) -> Result<Vec<String>, Vec<String>> {
+        let mut deps: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (from, to) in edges {
+            deps.entry(from).or_default().push(to);
+        }
+        order::topological(roots.iter().copied(), |node| {
+            deps.get(node).cloned().unwrap_or_default()
+        })
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let ordered = sort(&["a", "b", "c"], &[("a", "c"), ("c", "b")]).unwrap();
+        assert_eq!(ordered, vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn independent_nodes_keep_order() {
+        let ordered = sort(&["c", "a", "b"], &[]).unwrap();
+        assert_eq!(ordered, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn includes_reachable_nodes() {
+        let ordered = sort(&["a"], &[("a", "b"), ("b", "c")]).unwrap();
+        assert_eq!(ordered, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn reports_cycle() {
+        let cycle = sort(&["a"], &[("a", "b"), ("b", "c"), ("c", "b")]).unwrap_err();
+        assert_eq!(cycle, vec!["b", "c", "b"]);
+    }
+
+    #[test]
+    fn self_dependency_is_a_cycle() {
+        let cycle = sort(&["a"], &[("a", "a")]).unwrap_err();
+        assert_eq!(cycle, vec!["a", "a"]);
+    }
+}
+
+pub mod check_dependencies {
+    use super::*;
+
+    #[test]
+    fn no_dependencies() {
+        order::check_dependencies(&deployment("a", &[]), &[]).unwrap();
+    }
+
+    #[test]
+    fn met() {
+        let mut dep = deployment("b", &[]);
+        dep.deployed_at = Some(chrono::Utc::now());
+        order::check_dependencies(&deployment("a", &["b"]), &[dep]).unwrap();
+    }
+
+    #[test]
+    fn unmet() {
+        let err = order::check_dependencies(&deployment("a", &["b", "c"]), &[deployment("b", &[])])
+            .unwrap_err();
+        match err {
+            DeployErr::UnmetDependencies(e) => assert_eq!(e.ids, vec!["b", "c"]),
+            other => panic!("expected unmet dependencies error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn transitive_cycle() {
+        let all = [deployment("b", &["c"]), deployment("c", &["a"])];
+        let err = order::check_dependencies(&deployment("a", &["b"]), &all).unwrap_err();
+        match err {
+            DeployErr::DependencyCycle(e) => assert_eq!(e.ids, vec!["a", "b", "c", "a"]),
+            other => panic!("expected dependency cycle error, got {other:?}"),
+        }
+    }
+}
+
+pub mod for_removal {
+    use super::*;
+
+    #[test]
+    fn dependents_first() {
+        let ordered = order::for_removal(vec![
+            deployment("base", &[]),
+            deployment("app", &["base"]),
+            deployment("other", &[]),
+        ]);
+        assert_eq!(ids(&ordered), vec!["other", "app", "base"]);
+    }
+
+    #[test]
+    fn cycle_keeps_order() {
+        let ordered = order::for_removal(vec![deployment("a", &["b"]), deployment("b", &["a"])]);
+        assert_eq!(ids(&ordered), vec!["a", "b"]);
+    }
+}
+
+pub mod by_config_type {
+    use super::*;
+
+    fn cfg_inst(id: &str, config_type_id: &str) -> ConfigInstance {
+        ConfigInstance {
+            id: id.to_string(),
+            config_type_id: config_type_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn deps(edges: &[(&str, &str)]) -> BTreeMap<String, Vec<String>> {
+        let mut deps: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (from, to) in edges {
+            deps.entry(from.to_string())
+                .or_default()
+                .push(to.to_string());
+        }
+        deps
+    }
+
+    #[test]
+    fn orders_by_dependencies() {
+        let cfg_insts = vec![
+            cfg_inst("ci_app", "app"),
+            cfg_inst("ci_net", "network"),
+            cfg_inst("ci_log", "logging"),
+        ];
+        let ordered = order::by_config_type(cfg_insts, &deps(&[("app", "network")])).unwrap();
+        let ids: Vec<&str> = ordered.iter().map(|ci| ci.id.as_str()).collect();
+        assert_eq!(ids, vec!["ci_net", "ci_app", "ci_log"]);
+    }
+
+    #[test]
+    fn no_dependencies_keeps_order() {
+        let cfg_insts = vec![cfg_inst("ci_b", "b"), cfg_inst("ci_a", "a")];
+        let ordered = order::by_config_type(cfg_insts.clone(), &BTreeMap::new()).unwrap();
+        assert_eq!(ordered, cfg_insts);
+    }
+
+    #[test]
+    fn cycle() {
+        let cfg_insts = vec![cfg_inst("ci_a", "a"), cfg_inst("ci_b", "b")];
+        let err = order::by_config_type(cfg_insts, &deps(&[("a", "b"), ("b", "a")])).unwrap_err();
+        assert!(matches!(err, DeployErr::DependencyCycle(_)));
+    }
+}
@@ -0,0 +1,136 @@
+// internal crates
+use miru_agent::deploy::pause::{Origin, Status, Switch};
+use miru_agent::filesys::{self, WriteOptions};
+
+struct Fixture {
+    dir: filesys::Dir,
+    switch: Switch,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let switch = Switch::new(dir.file("deployments_paused.json"));
+        Self { dir, switch }
+    }
+}
+
+pub mod status {
+    use super::*;
+
+    #[tokio::test]
+    async fn resumed_without_a_switch_file() {
+        let f = Fixture::new("pause_status_missing").await;
+        assert_eq!(f.switch.status().await, Status::default());
+        assert!(!f.switch.is_paused().await);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn paused_if_the_switch_file_is_unreadable() {
+        let f = Fixture::new("pause_status_corrupt").await;
+        f.switch
+            .file()
+            .write_string("{not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        assert!(f.switch.is_paused().await);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_switch_files_missing_optional_fields() {
+        let f = Fixture::new("pause_status_minimal").await;
+        f.switch
+            .file()
+            .write_string(r#"{"paused": true}"#, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let status = f.switch.status().await;
+        assert!(status.paused);
+        assert_eq!(status.reason, None);
+        f.dir.delete().await.unwrap();
+    }
+}
+
+pub mod pause_fn {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_the_reason_and_origin() {
+        let f = Fixture::new("pause_pause").await;
+        let before = chrono::Utc::now();
+
+        let status = f
+            .switch
+            .pause(Some("INC-42".to_string()), Origin::Socket)
+            .await
+            .unwrap();
+
+        assert!(status.paused);
+        assert_eq!(status.reason.as_deref(), Some("INC-42"));
+        assert_eq!(status.changed_by, Some(Origin::Socket));
+        assert!(status.changed_at.unwrap() >= before);
+        // a new switch over the same file sees it, as after a restart
+        let reopened = Switch::new(f.switch.file().clone());
+        assert_eq!(reopened.status().await, status);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn blank_reasons_are_dropped() {
+        let f = Fixture::new("pause_blank_reason").await;
+        let status = f
+            .switch
+            .pause(Some("  ".to_string()), Origin::Cli)
+            .await
+            .unwrap();
+        assert_eq!(status.reason, None);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pausing_twice_keeps_deployments_paused() {
+        let f = Fixture::new("pause_twice").await;
+        f.switch.pause(None, Origin::Cli).await.unwrap();
+        f.switch
+            .pause(Some("still".to_string()), Origin::Mqtt)
+            .await
+            .unwrap();
+        let status = f.switch.status().await;
+        assert!(status.paused);
+        assert_eq!(status.changed_by, Some(Origin::Mqtt));
+        f.dir.delete().await.unwrap();
+    }
+}
+
+pub mod resume {
+    use super::*;
+
+    #[tokio::test]
+    async fn clears_the_reason() {
+        let f = Fixture::new("pause_resume").await;
+        f.switch
+            .pause(Some("INC-42".to_string()), Origin::Cli)
+            .await
+            .unwrap();
+
+        let status = f.switch.resume(Origin::Mqtt).await.unwrap();
+
+        assert!(!status.paused);
+        assert_eq!(status.reason, None);
+        assert_eq!(status.changed_by, Some(Origin::Mqtt));
+        assert!(!f.switch.is_paused().await);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn serializes_the_origin_in_snake_case() {
+        let f = Fixture::new("pause_resume_json").await;
+        let status = f.switch.resume(Origin::Socket).await.unwrap();
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["paused"], false);
+        assert_eq!(json["changed_by"], "socket");
+        f.dir.delete().await.unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+// standard crates
+use std::time::{Duration, Instant};
+
+// internal crates
+use miru_agent::deploy::reboot::{self, Options};
+use miru_agent::deploy::schedule::{Schedule, Windows};
+use miru_agent::deploy::DeployErr;
+use miru_agent::filesys::{self, WriteOptions};
+use miru_agent::models::{Deployment, PendingFinalize};
+
+// external crates
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+fn at(hour: u32, min: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, hour, min, 0).unwrap()
+}
+
+fn pending_deployment() -> Deployment {
+    Deployment {
+        id: "dpl-1".to_string(),
+        pending_finalize: Some(PendingFinalize {
+            boot_id: "boot-1".to_string(),
+            staged_at: at(0, 0),
+            reboot_requested_at: None,
+        }),
+        ..Default::default()
+    }
+}
+
+pub mod options {
+    use super::*;
+
+    #[test]
+    fn disabled_by_default() {
+        let options = Options::default();
+        assert!(!options.is_enabled());
+        assert!(!options.authorized);
+        assert!(!options.requires_reboot("/boot/config.txt"));
+    }
+
+    #[test]
+    fn requires_reboot() {
+        let options = Options {
+            paths: vec!["/boot".to_string(), "/etc/modprobe.d/miru.conf".to_string()],
+            ..Default::default()
+        };
+        assert!(options.is_enabled());
+        assert!(options.requires_reboot("/boot/config.txt"));
+        assert!(options.requires_reboot("/etc/modprobe.d/miru.conf"));
+        assert!(!options.requires_reboot("/bootstrap/config.txt"));
+        assert!(!options.requires_reboot("/etc/modprobe.d/other.conf"));
+    }
+}
+
+pub mod boot_id {
+    use super::*;
+
+    #[tokio::test]
+    async fn trims_whitespace() {
+        let dir = filesys::Dir::create_temp_dir("reboot_boot_id")
+            .await
+            .unwrap();
+        let file = dir.file("boot_id");
+        file.write_string("abc-123\n", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let options = Options {
+            boot_id_file: file,
+            ..Default::default()
+        };
+        assert_eq!(reboot::boot_id(&options).await.unwrap(), "abc-123");
+        dir.delete().await.unwrap();
+    }
+
+    #[test]
+    fn has_rebooted() {
+        let pending = pending_deployment().pending_finalize.unwrap();
+        assert!(!reboot::has_rebooted(&pending, "boot-1"));
+        assert!(reboot::has_rebooted(&pending, "boot-2"));
+    }
+}
+
+pub mod request {
+    use super::*;
+
+    #[tokio::test]
+    async fn unauthorized_only_records_request() {
+        let options = Options {
+            command: vec!["false".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        let wait = reboot::request(&options, &mut dpl, at(3, 0)).await.unwrap();
+        assert_eq!(wait, options.recheck_interval);
+        assert_eq!(
+            dpl.pending_finalize.unwrap().reboot_requested_at,
+            Some(at(3, 0))
+        );
+    }
+
+    #[tokio::test]
+    async fn authorized_runs_command() {
+        let options = Options {
+            authorized: true,
+            command: vec!["true".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        reboot::request(&options, &mut dpl, at(3, 0)).await.unwrap();
+        assert_eq!(
+            dpl.pending_finalize.unwrap().reboot_requested_at,
+            Some(at(3, 0))
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_command() {
+        let options = Options {
+            authorized: true,
+            command: vec!["false".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        let result = reboot::request(&options, &mut dpl, at(3, 0)).await;
+        assert!(matches!(result, Err(DeployErr::RebootCommand(_))));
+    }
+
+    #[tokio::test]
+    async fn empty_command() {
+        let options = Options {
+            authorized: true,
+            command: Vec::new(),
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        let result = reboot::request(&options, &mut dpl, at(3, 0)).await;
+        assert!(matches!(result, Err(DeployErr::RebootCommand(_))));
+    }
+
+    #[tokio::test]
+    async fn waits_for_maintenance_window() {
+        let options = Options {
+            authorized: true,
+            windows: Windows::new(vec![Schedule::new("* 2-3 * * *").unwrap()]),
+            // would fail if run
+            command: vec!["false".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        let wait = reboot::request(&options, &mut dpl, at(1, 0)).await.unwrap();
+        assert_eq!(wait, TimeDelta::hours(1));
+        assert_eq!(dpl.pending_finalize.unwrap().reboot_requested_at, None);
+    }
+
+    #[tokio::test]
+    async fn reboots_inside_maintenance_window() {
+        let options = Options {
+            authorized: true,
+            windows: Windows::new(vec![Schedule::new("* 2-3 * * *").unwrap()]),
+            command: vec!["false".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        let result = reboot::request(&options, &mut dpl, at(3, 59)).await;
+        assert!(matches!(result, Err(DeployErr::RebootCommand(_))));
+    }
+
+    #[tokio::test]
+    async fn rechecks_when_no_window_ever_opens() {
+        let options = Options {
+            authorized: true,
+            windows: Windows::new(vec![Schedule::new("0 0 30 2 *").unwrap()]),
+            command: vec!["false".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        let wait = reboot::request(&options, &mut dpl, at(1, 0)).await.unwrap();
+        assert_eq!(wait, options.recheck_interval);
+    }
+
+    #[tokio::test]
+    async fn repeats_once_per_recheck_interval() {
+        let options = Options {
+            authorized: true,
+            command: vec!["false".to_string()],
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+        dpl.pending_finalize.as_mut().unwrap().reboot_requested_at = Some(at(3, 0));
+
+        // requested recently so the command isn't run again
+        reboot::request(&options, &mut dpl, at(3, 1)).await.unwrap();
+
+        let later = at(3, 0) + options.recheck_interval;
+        let result = reboot::request(&options, &mut dpl, later).await;
+        assert!(matches!(result, Err(DeployErr::RebootCommand(_))));
+    }
+
+    #[tokio::test]
+    async fn hung_command_times_out() {
+        let options = Options {
+            authorized: true,
+            command: vec!["sleep".to_string(), "30".to_string()],
+            command_timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let mut dpl = pending_deployment();
+
+        let started = Instant::now();
+        let result = reboot::request(&options, &mut dpl, at(3, 0)).await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        match result {
+            Err(DeployErr::RebootCommand(e)) => assert!(e.msg.contains("timed out")),
+            other => panic!("expected a reboot command error, got {other:?}"),
+        }
+    }
+}
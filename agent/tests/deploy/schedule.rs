@@ -0,0 +1,222 @@
+// internal crates
+use miru_agent::deploy::schedule::{Schedule, Windows};
+
+// external crates
+use chrono::{DateTime, TimeZone, Utc};
+
+fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+        .unwrap()
+}
+
+pub mod new {
+    use super::*;
+
+    #[test]
+    fn valid_expressions() {
+        for expr in [
+            "* * * * *",
+            "0 2 * * *",
+            "*/15 9-17 * * MON-FRI",
+            "0,30 22-23 1,15 jan,Jul 0",
+            "0 3 * * 7",
+            "5/10 0-12/3 * * *",
+        ] {
+            assert!(Schedule::new(expr).is_ok(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        let schedule = Schedule::new("  0   2 * *\t* ").unwrap();
+        assert_eq!(schedule.as_str(), "0 2 * * *");
+        assert_eq!(schedule.to_string(), "0 2 * * *");
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expr in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "* * * * FUN",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "*/x * * * *",
+            "1,,2 * * * *",
+        ] {
+            assert!(Schedule::new(expr).is_err(), "{expr:?}");
+        }
+    }
+
+    #[test]
+    fn errors_name_the_field() {
+        let err = Schedule::new("* 25 * * *").unwrap_err();
+        assert!(err.contains("hour"), "{err}");
+        assert!(err.contains("25"), "{err}");
+    }
+}
+
+pub mod contains {
+    use super::*;
+
+    #[test]
+    fn every_minute() {
+        let schedule = Schedule::new("* * * * *").unwrap();
+        assert!(schedule.contains(at(2026, 3, 4, 5, 6)));
+    }
+
+    #[test]
+    fn hours_and_weekdays() {
+        // 2026-10-17 is a saturday
+        let schedule = Schedule::new("* 2-4 * * SAT,SUN").unwrap();
+        assert!(schedule.contains(at(2026, 10, 17, 2, 0)));
+        assert!(schedule.contains(at(2026, 10, 18, 4, 59)));
+        assert!(!schedule.contains(at(2026, 10, 17, 5, 0)));
+        assert!(!schedule.contains(at(2026, 10, 19, 3, 0)));
+    }
+
+    #[test]
+    fn steps() {
+        let schedule = Schedule::new("*/20 * * * *").unwrap();
+        assert!(schedule.contains(at(2026, 1, 1, 0, 40)));
+        assert!(!schedule.contains(at(2026, 1, 1, 0, 41)));
+
+        let schedule = Schedule::new("10/20 * * * *").unwrap();
+        assert!(schedule.contains(at(2026, 1, 1, 0, 50)));
+        assert!(!schedule.contains(at(2026, 1, 1, 0, 0)));
+    }
+
+    #[test]
+    fn sunday_is_zero_and_seven() {
+        // 2026-10-18 is a sunday
+        for expr in ["* * * * 0", "* * * * 7", "* * * * 5-7"] {
+            let schedule = Schedule::new(expr).unwrap();
+            assert!(schedule.contains(at(2026, 10, 18, 12, 0)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn either_day_when_both_are_restricted() {
+        // the 1st of the month or any monday
+        let schedule = Schedule::new("* * 1 * MON").unwrap();
+        assert!(schedule.contains(at(2026, 10, 1, 0, 0)));
+        assert!(schedule.contains(at(2026, 10, 19, 0, 0)));
+        assert!(!schedule.contains(at(2026, 10, 20, 0, 0)));
+
+        // the 1st of the month, whichever day it is
+        let schedule = Schedule::new("* * 1 * *").unwrap();
+        assert!(!schedule.contains(at(2026, 10, 19, 0, 0)));
+    }
+}
+
+pub mod next {
+    use super::*;
+
+    #[test]
+    fn now_when_inside() {
+        let schedule = Schedule::new("* 2 * * *").unwrap();
+        let now = at(2026, 10, 17, 2, 30) + chrono::TimeDelta::seconds(15);
+        assert_eq!(schedule.next(now), Some(now));
+    }
+
+    #[test]
+    fn later_today() {
+        let schedule = Schedule::new("30 14 * * *").unwrap();
+        let now = at(2026, 10, 17, 9, 12) + chrono::TimeDelta::seconds(15);
+        assert_eq!(schedule.next(now), Some(at(2026, 10, 17, 14, 30)));
+    }
+
+    #[test]
+    fn tomorrow() {
+        let schedule = Schedule::new("0 2 * * *").unwrap();
+        assert_eq!(
+            schedule.next(at(2026, 10, 17, 2, 1)),
+            Some(at(2026, 10, 18, 2, 0))
+        );
+    }
+
+    #[test]
+    fn next_weekend() {
+        // from a tuesday
+        let schedule = Schedule::new("* 2-4 * * SAT,SUN").unwrap();
+        assert_eq!(
+            schedule.next(at(2026, 10, 20, 3, 0)),
+            Some(at(2026, 10, 24, 2, 0))
+        );
+    }
+
+    #[test]
+    fn next_year() {
+        let schedule = Schedule::new("0 0 1 JAN *").unwrap();
+        assert_eq!(
+            schedule.next(at(2026, 10, 17, 0, 0)),
+            Some(at(2027, 1, 1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn leap_day() {
+        let schedule = Schedule::new("0 0 29 2 *").unwrap();
+        assert_eq!(
+            schedule.next(at(2026, 10, 17, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+    }
+
+    #[test]
+    fn never() {
+        let schedule = Schedule::new("0 0 30 FEB *").unwrap();
+        assert_eq!(schedule.next(at(2026, 10, 17, 0, 0)), None);
+    }
+}
+
+pub mod windows {
+    use super::*;
+
+    #[test]
+    fn no_windows_are_always_open() {
+        let windows = Windows::default();
+        let now = at(2026, 10, 17, 12, 0);
+        assert!(windows.is_open(now));
+        assert_eq!(windows.next_open(now), Some(now));
+    }
+
+    #[test]
+    fn any_window_opens() {
+        let windows = Windows::new(vec![
+            Schedule::new("* 2-4 * * *").unwrap(),
+            Schedule::new("* 22 * * *").unwrap(),
+        ]);
+        assert!(windows.is_open(at(2026, 10, 17, 22, 15)));
+        assert!(!windows.is_open(at(2026, 10, 17, 12, 0)));
+        assert_eq!(
+            windows.next_open(at(2026, 10, 17, 12, 0)),
+            Some(at(2026, 10, 17, 22, 0))
+        );
+        assert_eq!(
+            windows.next_open(at(2026, 10, 17, 23, 0)),
+            Some(at(2026, 10, 18, 2, 0))
+        );
+    }
+
+    #[test]
+    fn never_open() {
+        let windows = Windows::new(vec![Schedule::new("0 0 31 APR *").unwrap()]);
+        assert_eq!(windows.next_open(at(2026, 10, 17, 12, 0)), None);
+    }
+
+    #[test]
+    fn serializes_as_expressions() {
+        let schedule = Schedule::new("0 2 * * SUN").unwrap();
+        let json = serde_json::to_value(&schedule).unwrap();
+        assert_eq!(json, serde_json::json!("0 2 * * SUN"));
+        assert_eq!(serde_json::from_value::<Schedule>(json).unwrap(), schedule);
+        assert!(serde_json::from_value::<Schedule>(serde_json::json!("0 2 *")).is_err());
+    }
+}
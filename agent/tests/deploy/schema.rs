@@ -0,0 +1,391 @@
+// internal crates
+use miru_agent::deploy::schema::{validate, Violation};
+
+// external crates
+use serde_json::{json, Value};
+
+fn paths(schema: Value, instance: Value) -> Vec<String> {
+    validate(&schema, &instance)
+        .into_iter()
+        .map(|v| v.path)
+        .collect()
+}
+
+fn is_valid(schema: Value, instance: Value) -> bool {
+    validate(&schema, &instance).is_empty()
+}
+
+pub mod display {
+    use super::*;
+
+    #[test]
+    fn includes_the_path() {
+        let violation = Violation {
+            path: "/server/port".to_string(),
+            msg: "\"80\" is not of type \"integer\"".to_string(),
+        };
+        assert_eq!(
+            violation.to_string(),
+            "/server/port: \"80\" is not of type \"integer\""
+        );
+    }
+
+    #[test]
+    fn document_root() {
+        let violation = Violation {
+            path: String::new(),
+            msg: "[] is not of type \"object\"".to_string(),
+        };
+        assert_eq!(violation.to_string(), "/: [] is not of type \"object\"");
+    }
+}
+
+pub mod boolean_schemas {
+    use super::*;
+
+    #[test]
+    fn true_accepts_everything() {
+        assert!(is_valid(json!(true), json!({"a": [1, 2]})));
+        assert!(is_valid(json!({}), json!(null)));
+    }
+
+    #[test]
+    fn false_rejects_everything() {
+        assert!(!is_valid(json!(false), json!({})));
+        assert!(!is_valid(
+            json!({"properties": {"a": false}}),
+            json!({"a": 1})
+        ));
+    }
+}
+
+pub mod types {
+    use super::*;
+
+    #[test]
+    fn single_type() {
+        assert!(is_valid(json!({"type": "object"}), json!({})));
+        assert!(is_valid(json!({"type": "string"}), json!("x")));
+        assert!(is_valid(json!({"type": "boolean"}), json!(false)));
+        assert!(is_valid(json!({"type": "null"}), json!(null)));
+        assert!(is_valid(json!({"type": "array"}), json!([])));
+        assert!(!is_valid(json!({"type": "object"}), json!([])));
+        assert!(!is_valid(json!({"type": "string"}), json!(1)));
+    }
+
+    #[test]
+    fn integers_are_numbers() {
+        assert!(is_valid(json!({"type": "number"}), json!(1)));
+        assert!(is_valid(json!({"type": "number"}), json!(1.5)));
+        assert!(is_valid(json!({"type": "integer"}), json!(3)));
+        assert!(is_valid(json!({"type": "integer"}), json!(3.0)));
+        assert!(!is_valid(json!({"type": "integer"}), json!(3.5)));
+    }
+
+    #[test]
+    fn any_of_several_types() {
+        let schema = json!({"type": ["string", "null"]});
+        assert!(is_valid(schema.clone(), json!("x")));
+        assert!(is_valid(schema.clone(), json!(null)));
+        let violations = validate(&schema, &json!(1));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].msg, "1 is not of types \"null\", \"string\"");
+    }
+}
+
+pub mod enums {
+    use super::*;
+
+    #[test]
+    fn enum_values() {
+        let schema = json!({"enum": ["debug", "info", 1]});
+        assert!(is_valid(schema.clone(), json!("info")));
+        assert!(is_valid(schema.clone(), json!(1.0)));
+        assert!(!is_valid(schema, json!("trace")));
+    }
+
+    #[test]
+    fn const_value() {
+        let schema = json!({"const": {"a": [1, 2]}});
+        assert!(is_valid(schema.clone(), json!({"a": [1, 2]})));
+        assert!(!is_valid(schema, json!({"a": [2, 1]})));
+    }
+}
+
+pub mod objects {
+    use super::*;
+
+    #[test]
+    fn required_properties() {
+        let schema = json!({"required": ["host", "port"]});
+        assert!(is_valid(schema.clone(), json!({"host": "a", "port": 1})));
+        let violations = validate(&schema, &json!({"host": "a"}));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "");
+        assert!(violations[0]
+            .msg
+            .contains("\"port\" is a required property"));
+    }
+
+    #[test]
+    fn nested_properties_report_their_path() {
+        let schema = json!({
+            "properties": {
+                "server": {"properties": {"port": {"type": "integer"}}},
+            },
+        });
+        assert_eq!(
+            paths(schema, json!({"server": {"port": "80"}})),
+            vec!["/server/port".to_string()]
+        );
+    }
+
+    #[test]
+    fn property_names_are_escaped() {
+        let schema = json!({"properties": {"a/b~c": {"type": "string"}}});
+        assert_eq!(
+            paths(schema, json!({"a/b~c": 1})),
+            vec!["/a~1b~0c".to_string()]
+        );
+    }
+
+    #[test]
+    fn additional_properties() {
+        let schema = json!({
+            "properties": {"a": {}},
+            "patternProperties": {"^x-": {"type": "string"}},
+            "additionalProperties": false,
+        });
+        assert!(is_valid(schema.clone(), json!({"a": 1, "x-note": "hi"})));
+        assert!(!is_valid(schema.clone(), json!({"x-note": 1})));
+        assert!(!is_valid(schema, json!({"b": 1})));
+
+        let schema = json!({"additionalProperties": {"type": "integer"}});
+        assert!(is_valid(schema.clone(), json!({"a": 1})));
+        assert!(!is_valid(schema, json!({"a": "1"})));
+    }
+
+    #[test]
+    fn property_counts() {
+        let schema = json!({"minProperties": 1, "maxProperties": 2});
+        assert!(!is_valid(schema.clone(), json!({})));
+        assert!(is_valid(schema.clone(), json!({"a": 1})));
+        assert!(!is_valid(schema, json!({"a": 1, "b": 2, "c": 3})));
+    }
+}
+
+pub mod arrays {
+    use super::*;
+
+    #[test]
+    fn items() {
+        let schema = json!({"items": {"type": "string"}});
+        assert!(is_valid(schema.clone(), json!(["a", "b"])));
+        assert_eq!(paths(schema, json!(["a", 2, "c"])), vec!["/1".to_string()]);
+    }
+
+    #[test]
+    fn prefix_items() {
+        let schema = json!({
+            "prefixItems": [{"type": "string"}, {"type": "integer"}],
+            "items": false,
+        });
+        assert!(is_valid(schema.clone(), json!(["a", 1])));
+        assert!(!is_valid(schema.clone(), json!([1, "a"])));
+        assert!(!is_valid(schema, json!(["a", 1, true])));
+    }
+
+    #[test]
+    fn item_counts_and_uniqueness() {
+        let schema = json!({"minItems": 1, "maxItems": 3, "uniqueItems": true});
+        assert!(!is_valid(schema.clone(), json!([])));
+        assert!(is_valid(schema.clone(), json!([1, 2])));
+        assert!(!is_valid(schema.clone(), json!([1, 2, 3, 4])));
+        assert!(!is_valid(schema, json!([1, 1.0])));
+    }
+
+    #[test]
+    fn contains() {
+        let schema = json!({"contains": {"const": "main"}});
+        assert!(is_valid(schema.clone(), json!(["dev", "main"])));
+        assert!(!is_valid(schema, json!(["dev"])));
+    }
+}
+
+pub mod strings {
+    use super::*;
+
+    #[test]
+    fn lengths_count_characters() {
+        let schema = json!({"minLength": 2, "maxLength": 3});
+        assert!(!is_valid(schema.clone(), json!("a")));
+        assert!(is_valid(schema.clone(), json!("äöü")));
+        assert!(!is_valid(schema, json!("abcd")));
+    }
+
+    #[test]
+    fn pattern() {
+        let schema = json!({"pattern": "^[a-z]+$"});
+        assert!(is_valid(schema.clone(), json!("abc")));
+        assert!(!is_valid(schema, json!("ABC")));
+    }
+
+    #[test]
+    fn invalid_pattern_is_a_violation() {
+        assert!(!is_valid(json!({"pattern": "("}), json!("abc")));
+    }
+
+    #[test]
+    fn format_is_asserted() {
+        assert!(is_valid(json!({"format": "ipv4"}), json!("10.0.0.1")));
+        assert!(!is_valid(json!({"format": "ipv4"}), json!("not an ip")));
+    }
+}
+
+pub mod numbers {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let schema = json!({"minimum": 1, "maximum": 65535});
+        assert!(is_valid(schema.clone(), json!(1)));
+        assert!(is_valid(schema.clone(), json!(65535)));
+        assert!(!is_valid(schema.clone(), json!(0)));
+        assert!(!is_valid(schema, json!(65536)));
+    }
+
+    #[test]
+    fn exclusive_bounds() {
+        let schema = json!({"exclusiveMinimum": 0, "exclusiveMaximum": 1});
+        assert!(is_valid(schema.clone(), json!(0.5)));
+        assert!(!is_valid(schema.clone(), json!(0)));
+        assert!(!is_valid(schema, json!(1)));
+    }
+
+    #[test]
+    fn multiple_of() {
+        let schema = json!({"multipleOf": 0.5});
+        assert!(is_valid(schema.clone(), json!(2.5)));
+        assert!(!is_valid(schema, json!(2.25)));
+    }
+}
+
+pub mod combinators {
+    use super::*;
+
+    #[test]
+    fn all_of() {
+        let schema = json!({"allOf": [{"type": "integer"}, {"minimum": 10}]});
+        assert!(is_valid(schema.clone(), json!(10)));
+        assert!(!is_valid(schema, json!(5)));
+    }
+
+    #[test]
+    fn any_of() {
+        let schema = json!({"anyOf": [{"type": "string"}, {"type": "integer"}]});
+        assert!(is_valid(schema.clone(), json!(1)));
+        assert!(!is_valid(schema, json!(true)));
+    }
+
+    #[test]
+    fn one_of() {
+        let schema = json!({"oneOf": [{"type": "integer"}, {"minimum": 2}]});
+        assert!(is_valid(schema.clone(), json!(1)));
+        assert!(is_valid(schema.clone(), json!(2.5)));
+        assert!(!is_valid(schema, json!(3)));
+    }
+
+    #[test]
+    fn not() {
+        let schema = json!({"not": {"type": "null"}});
+        assert!(is_valid(schema.clone(), json!(0)));
+        assert!(!is_valid(schema, json!(null)));
+    }
+
+    #[test]
+    fn if_then_else() {
+        let schema = json!({
+            "if": {"properties": {"tls": {"const": true}}, "required": ["tls"]},
+            "then": {"required": ["cert"]},
+            "else": {"properties": {"cert": false}},
+        });
+        assert!(is_valid(
+            schema.clone(),
+            json!({"tls": true, "cert": "a.pem"})
+        ));
+        assert!(!is_valid(schema.clone(), json!({"tls": true})));
+        assert!(is_valid(schema.clone(), json!({"tls": false})));
+        assert!(!is_valid(schema, json!({"tls": false, "cert": "a.pem"})));
+    }
+}
+
+pub mod refs {
+    use super::*;
+
+    #[test]
+    fn local_refs() {
+        let schema = json!({
+            "$defs": {"port": {"type": "integer", "minimum": 1}},
+            "properties": {"http": {"$ref": "#/$defs/port"}},
+        });
+        assert!(is_valid(schema.clone(), json!({"http": 80})));
+        assert_eq!(paths(schema, json!({"http": 0})), vec!["/http".to_string()]);
+    }
+
+    #[test]
+    fn recursive_refs() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"children": {"type": "array", "items": {"$ref": "#"}}},
+        });
+        assert!(is_valid(
+            schema.clone(),
+            json!({"children": [{"children": []}]})
+        ));
+        assert!(!is_valid(schema, json!({"children": [1]})));
+    }
+
+    #[test]
+    fn unresolvable_ref_is_a_violation() {
+        assert!(!is_valid(json!({"$ref": "#/$defs/missing"}), json!(1)));
+        assert!(!is_valid(
+            json!({"$ref": "https://example.com/schema"}),
+            json!(1)
+        ));
+    }
+
+    #[test]
+    fn self_referencing_schema_terminates() {
+        let schema = json!({"$defs": {"a": {"$ref": "#/$defs/a"}}, "$ref": "#/$defs/a"});
+        // the reference constrains nothing, the point is that validation returns
+        validate(&schema, &json!(1));
+    }
+}
+
+pub mod invalid_schemas {
+    use super::*;
+
+    #[test]
+    fn malformed_schema_rejects_everything() {
+        let violations = validate(&json!({"type": "port"}), &json!(80));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].msg.starts_with("invalid config schema"));
+        assert!(!is_valid(json!({"minimum": "one"}), json!(80)));
+    }
+}
+
+#[test]
+fn reports_every_violation() {
+    let schema = json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": {
+            "port": {"type": "integer"},
+            "tags": {"items": {"type": "string"}},
+        },
+    });
+    assert_eq!(
+        paths(schema, json!({"port": "80", "tags": ["a", 1]})),
+        vec![String::new(), "/port".to_string(), "/tags/1".to_string()]
+    );
+}
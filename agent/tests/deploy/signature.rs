@@ -0,0 +1,348 @@
+// internal crates
+use miru_agent::crypt::base64;
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::deploy::signature::{self, Options, TrustedKey, Verifier};
+use miru_agent::models::release::{Release, Signature, SignatureAlgorithm};
+
+// external crates
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+
+pub fn ed25519_key() -> PKey<Private> {
+    PKey::generate_ed25519().unwrap()
+}
+
+pub fn p256_key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+pub fn trusted(key_id: &str, key: &PKey<Private>) -> TrustedKey {
+    TrustedKey {
+        key_id: key_id.to_string(),
+        public_key: String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+    }
+}
+
+/// Signs the manifest of the files the way a release's publisher would.
+pub fn sign(
+    key_id: &str,
+    key: &PKey<Private>,
+    algorithm: SignatureAlgorithm,
+    release: &Release,
+    files: &[(&str, &str)],
+) -> Signature {
+    let manifest = signature::manifest(release, files).unwrap();
+    let value = match algorithm {
+        SignatureAlgorithm::Ed25519 => Signer::new_without_digest(key)
+            .unwrap()
+            .sign_oneshot_to_vec(manifest.as_bytes())
+            .unwrap(),
+        SignatureAlgorithm::EcdsaP256Sha256 => {
+            let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+            signer.update(manifest.as_bytes()).unwrap();
+            signer.sign_to_vec().unwrap()
+        }
+    };
+    Signature {
+        algorithm,
+        key_id: key_id.to_string(),
+        value: base64::encode_bytes_standard(&value),
+    }
+}
+
+fn rls_1() -> Release {
+    Release {
+        id: "rls_1".to_string(),
+        version: "1.2.0".to_string(),
+        ..Default::default()
+    }
+}
+
+const FILES: &[(&str, &str)] = &[
+    ("/srv/robot/motion.json", r#"{"speed":4}"#),
+    ("/srv/robot/arm.yaml", "reach: 2"),
+];
+
+fn verify(verifier: &Verifier, release: &Release, files: &[(&str, &str)]) -> Result<(), String> {
+    verifier.verify(release, files).map_err(|e| e.msg)
+}
+
+pub mod manifest {
+    use super::*;
+
+    fn sha256(content: &str) -> String {
+        Digest::compute(Algorithm::Sha256, content.as_bytes())
+            .hex()
+            .to_string()
+    }
+
+    #[test]
+    fn lists_the_files_by_filepath() {
+        let manifest = signature::manifest(&rls_1(), FILES).unwrap();
+        assert_eq!(
+            manifest,
+            format!(
+                "miru-release-manifest-v1\nrelease rls_1\nversion 1.2.0\n{}  /srv/robot/arm.yaml\n{}  /srv/robot/motion.json\n",
+                sha256("reach: 2"),
+                sha256(r#"{"speed":4}"#),
+            )
+        );
+    }
+
+    #[test]
+    fn changes_with_the_content() {
+        let tampered = [("/srv/robot/motion.json", r#"{"speed":40}"#), FILES[1]];
+        assert_ne!(
+            signature::manifest(&rls_1(), FILES).unwrap(),
+            signature::manifest(&rls_1(), &tampered).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        let forged = [(
+            "/srv/robot/motion.json\n0000  /srv/robot/arm.yaml",
+            r#"{"speed":4}"#,
+        )];
+        let err = signature::manifest(&rls_1(), &forged).unwrap_err();
+        assert!(err.msg.contains("filepath"), "{}", err.msg);
+
+        let tabbed = [("/srv/robot/motion\t.json", r#"{"speed":4}"#)];
+        assert!(signature::manifest(&rls_1(), &tabbed).is_err());
+
+        let release = Release {
+            version: "1.2.0\nrelease rls_2".to_string(),
+            ..rls_1()
+        };
+        let err = signature::manifest(&release, FILES).unwrap_err();
+        assert!(err.msg.contains("version"), "{}", err.msg);
+    }
+}
+
+pub mod verify {
+    use super::*;
+
+    #[test]
+    fn ed25519() {
+        let key = ed25519_key();
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &key)],
+            required: true,
+        });
+        let release = Release {
+            signature: Some(sign(
+                "release-2026",
+                &key,
+                SignatureAlgorithm::Ed25519,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        assert_eq!(verify(&verifier, &release, FILES), Ok(()));
+    }
+
+    #[test]
+    fn ecdsa_p256() {
+        let key = p256_key();
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &key)],
+            required: true,
+        });
+        let release = Release {
+            signature: Some(sign(
+                "release-2026",
+                &key,
+                SignatureAlgorithm::EcdsaP256Sha256,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        assert_eq!(verify(&verifier, &release, FILES), Ok(()));
+    }
+
+    #[test]
+    fn tampered_content() {
+        let key = ed25519_key();
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &key)],
+            ..Default::default()
+        });
+        let release = Release {
+            signature: Some(sign(
+                "release-2026",
+                &key,
+                SignatureAlgorithm::Ed25519,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        let tampered = [("/srv/robot/motion.json", r#"{"speed":40}"#), FILES[1]];
+        let msg = verify(&verifier, &release, &tampered).unwrap_err();
+        assert!(msg.contains("doesn't match"), "{msg}");
+
+        // nor can the signature be moved to another release
+        let other = Release {
+            id: "rls_2".to_string(),
+            ..release
+        };
+        assert!(verify(&verifier, &other, FILES).is_err());
+    }
+
+    #[test]
+    fn untrusted_key() {
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &ed25519_key())],
+            ..Default::default()
+        });
+        // signed with a key claiming to be the trusted one
+        let release = Release {
+            signature: Some(sign(
+                "release-2026",
+                &ed25519_key(),
+                SignatureAlgorithm::Ed25519,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        assert!(verify(&verifier, &release, FILES).is_err());
+
+        let release = Release {
+            signature: Some(sign(
+                "attacker",
+                &ed25519_key(),
+                SignatureAlgorithm::Ed25519,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        assert_eq!(
+            verify(&verifier, &release, FILES),
+            Err("the release is signed with untrusted key 'attacker'".to_string())
+        );
+    }
+
+    #[test]
+    fn algorithm_must_match_the_key() {
+        let key = p256_key();
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &key)],
+            ..Default::default()
+        });
+        let mut signature = sign(
+            "release-2026",
+            &key,
+            SignatureAlgorithm::EcdsaP256Sha256,
+            &rls_1(),
+            FILES,
+        );
+        signature.algorithm = SignatureAlgorithm::Ed25519;
+        let release = Release {
+            signature: Some(signature),
+            ..rls_1()
+        };
+        assert_eq!(
+            verify(&verifier, &release, FILES),
+            Err("key 'release-2026' isn't an ed25519 key".to_string())
+        );
+    }
+
+    #[test]
+    fn malformed_signature() {
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &p256_key())],
+            ..Default::default()
+        });
+        for value in ["not base64!", "c2lnbmF0dXJl"] {
+            let release = Release {
+                signature: Some(Signature {
+                    algorithm: SignatureAlgorithm::EcdsaP256Sha256,
+                    key_id: "release-2026".to_string(),
+                    value: value.to_string(),
+                }),
+                ..rls_1()
+            };
+            assert!(verify(&verifier, &release, FILES).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn control_characters_in_filepaths() {
+        let key = ed25519_key();
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![trusted("release-2026", &key)],
+            required: true,
+        });
+        let release = Release {
+            signature: Some(sign(
+                "release-2026",
+                &key,
+                SignatureAlgorithm::Ed25519,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        let forged = [
+            FILES[0],
+            FILES[1],
+            ("/srv/robot/arm.yaml\n/etc/shadow", "root::0:0"),
+        ];
+        let err = verify(&verifier, &release, &forged).unwrap_err();
+        assert!(err.contains("control character"), "{err}");
+    }
+
+    #[test]
+    fn unsigned_release() {
+        let options = Options {
+            trusted_keys: vec![trusted("release-2026", &ed25519_key())],
+            required: false,
+        };
+        assert_eq!(verify(&Verifier::new(&options), &rls_1(), FILES), Ok(()));
+
+        let required = Verifier::new(&Options {
+            required: true,
+            ..options
+        });
+        let err = required.verify(&rls_1(), FILES).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "release 'rls_1' failed signature verification: the release isn't signed"
+        );
+    }
+
+    #[test]
+    fn invalid_trusted_keys_are_ignored() {
+        let key = ed25519_key();
+        let verifier = Verifier::new(&Options {
+            trusted_keys: vec![
+                TrustedKey {
+                    key_id: "broken".to_string(),
+                    public_key: "not a key".to_string(),
+                },
+                trusted("release-2026", &key),
+            ],
+            required: true,
+        });
+        let signed = |key_id: &str| Release {
+            signature: Some(sign(
+                key_id,
+                &key,
+                SignatureAlgorithm::Ed25519,
+                &rls_1(),
+                FILES,
+            )),
+            ..rls_1()
+        };
+        assert_eq!(verify(&verifier, &signed("release-2026"), FILES), Ok(()));
+        assert!(verify(&verifier, &signed("broken"), FILES).is_err());
+    }
+}
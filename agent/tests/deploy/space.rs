@@ -0,0 +1,92 @@
+// internal crates
+use miru_agent::deploy::space::{assess, Guard};
+use miru_agent::errors::{Code, Error};
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::telemetry::{disk_space, DiskSpace};
+
+fn disk(mount_point: &str, available_bytes: u64) -> DiskSpace {
+    DiskSpace {
+        mount_point: mount_point.to_string(),
+        available_bytes,
+        total_bytes: 1 << 30,
+    }
+}
+
+pub mod assess {
+    use super::*;
+
+    #[test]
+    fn passes_when_every_disk_has_room() {
+        let disks = [disk("/", 100), disk("/data", 200)];
+        assert!(assess(100, &disks).is_ok());
+        assert!(assess(100, &[]).is_ok());
+    }
+
+    #[test]
+    fn fails_for_the_first_full_disk() {
+        let disks = [disk("/", 100), disk("/data", 50), disk("/var", 10)];
+        let err = assess(64, &disks).unwrap_err();
+        assert_eq!(err.mount_point, "/data");
+        assert_eq!(err.available_bytes, 50);
+        assert_eq!(err.total_bytes, 1 << 30);
+        assert_eq!(err.min_free_bytes, 64);
+    }
+
+    #[test]
+    fn surfaces_a_distinct_code() {
+        let err = assess(64, &[disk("/data", 50)]).unwrap_err();
+        assert!(matches!(err.code(), Code::InsufficientDiskSpace));
+        assert_eq!(err.code().as_str(), "insufficient_disk_space");
+        assert_eq!(err.http_status(), reqwest::StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(
+            err.params().unwrap(),
+            serde_json::json!({
+                "mount_point": "/data",
+                "available_bytes": 50,
+                "total_bytes": 1 << 30,
+                "min_free_bytes": 64,
+            })
+        );
+    }
+}
+
+pub mod check {
+    use super::*;
+
+    #[tokio::test]
+    async fn measures_the_guarded_disks() {
+        let dir = filesys::Dir::create_temp_dir("space_guard_check")
+            .await
+            .unwrap();
+        let Some(space) = disk_space(dir.path()) else {
+            // the sandbox may not expose its disks
+            dir.delete().await.unwrap();
+            return;
+        };
+
+        let guard = Guard::new(1, vec![dir.path().clone()]);
+        assert!(guard.check().is_ok());
+
+        let guard = Guard::new(u64::MAX, vec![dir.path().clone()]);
+        let err = guard.check().unwrap_err();
+        assert_eq!(err.mount_point, space.mount_point);
+        dir.delete().await.unwrap();
+    }
+
+    #[test]
+    fn reports_running_low_once() {
+        let guard = Guard::new(64, Vec::new());
+        assert!(!guard.ran_low(false));
+        assert!(guard.ran_low(true));
+        // clones share whether the disk is low
+        assert!(!guard.clone().ran_low(true));
+        assert!(!guard.ran_low(false));
+        assert!(guard.ran_low(true));
+    }
+
+    #[tokio::test]
+    async fn unmeasured_paths_pass() {
+        let guard = Guard::new(u64::MAX, Vec::new());
+        assert!(guard.check().is_ok());
+    }
+}
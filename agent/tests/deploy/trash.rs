@@ -0,0 +1,311 @@
+// internal crates
+use miru_agent::deploy::trash::{Options, Report, Trash};
+use miru_agent::deploy::DeployErr;
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+struct Fixture {
+    temp_dir: filesys::Dir,
+    trash: Trash,
+}
+
+impl Fixture {
+    async fn new(options: Options) -> Self {
+        let temp_dir = filesys::Dir::create_temp_dir("deploy-trash-test")
+            .await
+            .unwrap();
+        let trash = Trash::new(temp_dir.subdir("trash"), options);
+        Self { temp_dir, trash }
+    }
+
+    async fn write(&self, rel: &str, content: &str) -> filesys::File {
+        let file = self.temp_dir.subdir("deployed").file(rel);
+        file.write_string(content, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        file
+    }
+}
+
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+}
+
+pub mod put {
+    use super::*;
+
+    #[tokio::test]
+    async fn moves_file_into_trash() {
+        let f = Fixture::new(Options::default()).await;
+        let file = f.write("config.json", r#"{"v": 1}"#).await;
+
+        let entry = f
+            .trash
+            .put(&file, "dpl_1", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+        let entry = entry.unwrap();
+        assert!(!file.exists());
+        assert_eq!(entry.filepath, file.path().display().to_string());
+        assert_eq!(entry.deployment_id, "dpl_1");
+        assert_eq!(entry.config_instance_id, "cfg_inst_1");
+        assert_eq!(entry.trashed_at, at(0));
+        assert_eq!(entry.size_bytes, 8);
+        assert_eq!(f.trash.list().await.unwrap(), vec![entry]);
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_skipped() {
+        let f = Fixture::new(Options::default()).await;
+        let file = f.temp_dir.file("missing.json");
+
+        let entry = f
+            .trash
+            .put(&file, "dpl_1", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+        assert_eq!(entry, None);
+        assert!(f.trash.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn same_file_trashed_twice_keeps_both() {
+        let f = Fixture::new(Options::default()).await;
+        let file = f.write("config.json", "first").await;
+        let first = f
+            .trash
+            .put(&file, "dpl_1", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+        let file = f.write("config.json", "second").await;
+        let second = f
+            .trash
+            .put(&file, "dpl_2", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_ne!(first.id, second.id);
+        assert_eq!(f.trash.list().await.unwrap().len(), 2);
+        assert_eq!(f.trash.size().await.unwrap(), 11);
+    }
+}
+
+pub mod list {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_when_trash_absent() {
+        let f = Fixture::new(Options::default()).await;
+        assert!(f.trash.list().await.unwrap().is_empty());
+        assert_eq!(f.trash.size().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn oldest_first() {
+        let f = Fixture::new(Options::default()).await;
+        let b = f.write("b.json", "b").await;
+        let a = f.write("a.json", "a").await;
+        f.trash
+            .put(&b, "dpl_1", "cfg_inst_b", at(10))
+            .await
+            .unwrap();
+        f.trash.put(&a, "dpl_1", "cfg_inst_a", at(5)).await.unwrap();
+
+        let ids: Vec<_> = f
+            .trash
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.config_instance_id)
+            .collect();
+        assert_eq!(ids, vec!["cfg_inst_a", "cfg_inst_b"]);
+    }
+
+    #[tokio::test]
+    async fn skips_incomplete_entries() {
+        let f = Fixture::new(Options::default()).await;
+        f.trash
+            .dir()
+            .subdir("incomplete")
+            .file("content")
+            .write_string("orphan", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        assert!(f.trash.list().await.unwrap().is_empty());
+    }
+}
+
+pub mod restore {
+    use super::*;
+
+    #[tokio::test]
+    async fn moves_file_back() {
+        let f = Fixture::new(Options::default()).await;
+        let file = f.write("nested/config.json", r#"{"v": 1}"#).await;
+        let entry = f
+            .trash
+            .put(&file, "dpl_1", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+        file.parent().unwrap().delete().await.unwrap();
+
+        let restored = f.trash.restore(&entry.unwrap().id, false).await.unwrap();
+        assert_eq!(restored.filepath, file.path().display().to_string());
+        assert_eq!(file.read_string().await.unwrap(), r#"{"v": 1}"#);
+        assert!(f.trash.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn conflict_without_overwrite() {
+        let f = Fixture::new(Options::default()).await;
+        let file = f.write("config.json", "old").await;
+        let entry = f
+            .trash
+            .put(&file, "dpl_1", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+        let id = entry.unwrap().id;
+        f.write("config.json", "new").await;
+
+        let err = f.trash.restore(&id, false).await.unwrap_err();
+        assert!(matches!(err, DeployErr::RestoreConflict(_)), "{err:?}");
+        assert_eq!(file.read_string().await.unwrap(), "new");
+        assert_eq!(f.trash.list().await.unwrap().len(), 1);
+
+        f.trash.restore(&id, true).await.unwrap();
+        assert_eq!(file.read_string().await.unwrap(), "old");
+    }
+
+    #[tokio::test]
+    async fn unknown_entry() {
+        let f = Fixture::new(Options::default()).await;
+        let err = f.trash.restore("../../etc", false).await.unwrap_err();
+        assert!(matches!(err, DeployErr::TrashEntryNotFound(_)), "{err:?}");
+    }
+}
+
+pub mod purge {
+    use super::*;
+
+    #[tokio::test]
+    async fn one_entry() {
+        let f = Fixture::new(Options::default()).await;
+        let a = f.write("a.json", "a").await;
+        let b = f.write("b.json", "b").await;
+        let entry = f.trash.put(&a, "dpl_1", "cfg_inst_a", at(0)).await.unwrap();
+        f.trash.put(&b, "dpl_1", "cfg_inst_b", at(0)).await.unwrap();
+
+        let purged = f.trash.purge(&entry.unwrap().id).await.unwrap();
+        assert_eq!(purged.config_instance_id, "cfg_inst_a");
+        let remaining = f.trash.list().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].config_instance_id, "cfg_inst_b");
+    }
+
+    #[tokio::test]
+    async fn unknown_entry() {
+        let f = Fixture::new(Options::default()).await;
+        let err = f.trash.purge("missing").await.unwrap_err();
+        assert!(matches!(err, DeployErr::TrashEntryNotFound(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn all_entries() {
+        let f = Fixture::new(Options::default()).await;
+        let a = f.write("a.json", "aa").await;
+        let b = f.write("b.json", "bbb").await;
+        f.trash.put(&a, "dpl_1", "cfg_inst_a", at(0)).await.unwrap();
+        f.trash.put(&b, "dpl_1", "cfg_inst_b", at(0)).await.unwrap();
+
+        let report = f.trash.purge_all().await.unwrap();
+        assert_eq!(
+            report,
+            Report {
+                purged: 2,
+                reclaimed_bytes: 5
+            }
+        );
+        assert!(!f.trash.dir().exists());
+        assert!(f.trash.list().await.unwrap().is_empty());
+    }
+}
+
+pub mod expire {
+    use super::*;
+
+    #[tokio::test]
+    async fn purges_entries_past_retention() {
+        let f = Fixture::new(Options {
+            retention: TimeDelta::seconds(60),
+            max_bytes: None,
+        })
+        .await;
+        let old = f.write("old.json", "old").await;
+        let new = f.write("new.json", "new").await;
+        f.trash
+            .put(&old, "dpl_1", "cfg_inst_old", at(0))
+            .await
+            .unwrap();
+        f.trash
+            .put(&new, "dpl_1", "cfg_inst_new", at(30))
+            .await
+            .unwrap();
+
+        let report = f.trash.expire(at(61)).await;
+        assert_eq!(
+            report,
+            Report {
+                purged: 1,
+                reclaimed_bytes: 3
+            }
+        );
+        let remaining = f.trash.list().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].config_instance_id, "cfg_inst_new");
+    }
+
+    #[tokio::test]
+    async fn purges_oldest_while_over_budget() {
+        let f = Fixture::new(Options {
+            retention: TimeDelta::days(7),
+            max_bytes: Some(10),
+        })
+        .await;
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            let file = f.write(&format!("{name}.json"), "12345").await;
+            f.trash
+                .put(&file, "dpl_1", name, at(i as i64))
+                .await
+                .unwrap();
+        }
+
+        let report = f.trash.expire(at(3)).await;
+        assert_eq!(report.purged, 1);
+        let ids: Vec<_> = f
+            .trash
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.config_instance_id)
+            .collect();
+        assert_eq!(ids, vec!["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn nothing_to_purge() {
+        let f = Fixture::new(Options::default()).await;
+        let file = f.write("config.json", "v").await;
+        f.trash
+            .put(&file, "dpl_1", "cfg_inst_1", at(0))
+            .await
+            .unwrap();
+
+        assert_eq!(f.trash.expire(at(60)).await, Report::default());
+        assert_eq!(f.trash.list().await.unwrap().len(), 1);
+    }
+}
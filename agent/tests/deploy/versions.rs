@@ -0,0 +1,359 @@
+// standard crates
+use std::path::{Path, PathBuf};
+
+// internal crates
+use miru_agent::deploy::versions::Versions;
+use miru_agent::deploy::DeployErr;
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+
+async fn fixture(name: &str) -> (filesys::Dir, Versions) {
+    let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+    let versions = Versions::new(dir.subdir("srv"));
+    (dir, versions)
+}
+
+async fn release(versions: &Versions, content: &str) -> u64 {
+    let staged = versions.stage().await.unwrap();
+    staged
+        .file("app.json")
+        .write_string(content, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+    versions
+        .promote(&staged, &format!("dpl-{content}"))
+        .await
+        .unwrap()
+}
+
+pub mod paths {
+    use super::*;
+
+    #[test]
+    fn relative_path() {
+        let versions = Versions::new(filesys::Dir::new("/srv/robot"));
+        assert_eq!(
+            versions.relative_path(Path::new("/srv/robot/current/motion/config.json")),
+            Some(PathBuf::from("motion/config.json"))
+        );
+        assert_eq!(
+            versions.relative_path(Path::new("/srv/robot/current")),
+            None
+        );
+        assert_eq!(
+            versions.relative_path(Path::new("/srv/robot/releases/1/config.json")),
+            None
+        );
+        assert_eq!(versions.relative_path(Path::new("/etc/robot.json")), None);
+    }
+
+    #[test]
+    fn is_reserved() {
+        let versions = Versions::new(filesys::Dir::new("/srv/robot"));
+        assert!(versions.is_reserved(Path::new("/srv/robot/current")));
+        assert!(versions.is_reserved(Path::new("/srv/robot/releases/1/config.json")));
+        assert!(!versions.is_reserved(Path::new("/srv/robot/current/config.json")));
+        assert!(!versions.is_reserved(Path::new("/etc/robot.json")));
+    }
+
+    #[test]
+    fn owned_paths() {
+        let versions = Versions::new(filesys::Dir::new("/srv/robot"));
+        assert_eq!(
+            versions.paths(),
+            vec![
+                PathBuf::from("/srv/robot/current"),
+                PathBuf::from("/srv/robot/releases"),
+                PathBuf::from("/srv/robot/staging"),
+            ]
+        );
+    }
+}
+
+pub mod releases {
+    use super::*;
+
+    #[tokio::test]
+    async fn numbered_in_order() {
+        let (dir, versions) = fixture("versions_numbered").await;
+        assert!(versions.releases().await.unwrap().is_empty());
+        assert_eq!(versions.current().await.unwrap(), None);
+
+        assert_eq!(release(&versions, "1").await, 1);
+        assert_eq!(release(&versions, "2").await, 2);
+        assert_eq!(versions.releases().await.unwrap(), vec![1, 2]);
+        // nothing is live until a release is activated
+        assert_eq!(versions.current().await.unwrap(), None);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ignores_unnumbered_directories() {
+        let (dir, versions) = fixture("versions_unnumbered").await;
+        release(&versions, "1").await;
+        versions
+            .dir()
+            .subdir("releases/tmp")
+            .create()
+            .await
+            .unwrap();
+
+        assert_eq!(versions.releases().await.unwrap(), vec![1]);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod activate {
+    use super::*;
+
+    #[tokio::test]
+    async fn switches_the_current_link() {
+        let (dir, versions) = fixture("versions_activate").await;
+        release(&versions, "1").await;
+        release(&versions, "2").await;
+        let current = versions.current_link().path().join("app.json");
+
+        versions.activate(1).await.unwrap();
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(std::fs::read_to_string(&current).unwrap(), "1");
+
+        versions.activate(2).await.unwrap();
+        assert_eq!(versions.current().await.unwrap(), Some(2));
+        assert_eq!(std::fs::read_to_string(&current).unwrap(), "2");
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_release() {
+        let (dir, versions) = fixture("versions_activate_missing").await;
+        versions.dir().create().await.unwrap();
+
+        let result = versions.activate(1).await;
+
+        assert!(matches!(result, Err(DeployErr::FileSysErr(_))));
+        assert!(!versions.current_link().exists());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn current_must_be_a_symlink() {
+        let (dir, versions) = fixture("versions_activate_not_link").await;
+        release(&versions, "1").await;
+        versions
+            .current_link()
+            .write_string("not a link", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let result = versions.activate(1).await;
+
+        assert!(matches!(result, Err(DeployErr::PathNotAllowed(_))));
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod prune {
+    use super::*;
+
+    #[tokio::test]
+    async fn spares_the_live_release() {
+        let (dir, versions) = fixture("versions_prune").await;
+        for i in 1..=4 {
+            release(&versions, &i.to_string()).await;
+        }
+        versions.activate(1).await.unwrap();
+        let staged = versions.stage().await.unwrap();
+
+        versions.prune().await;
+
+        assert_eq!(versions.releases().await.unwrap(), vec![1, 3, 4]);
+        assert!(!staged.exists());
+        assert!(!versions.dir().file("releases/2.json").exists());
+        assert!(versions.dir().file("releases/3.json").exists());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_the_configured_count() {
+        let (dir, versions) = fixture("versions_prune_keep").await;
+        let versions = versions.with_keep(3);
+        for i in 1..=5 {
+            release(&versions, &i.to_string()).await;
+        }
+        versions.activate(5).await.unwrap();
+
+        versions.prune().await;
+
+        assert_eq!(versions.releases().await.unwrap(), vec![3, 4, 5]);
+        dir.delete().await.unwrap();
+    }
+
+    #[test]
+    fn keeps_at_least_the_live_release() {
+        let versions = Versions::new(filesys::Dir::new("/srv/robot")).with_keep(0);
+        assert_eq!(versions.keep(), 1);
+    }
+}
+
+pub mod list {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_each_release() {
+        let (dir, versions) = fixture("versions_list").await;
+        assert!(versions.list().await.unwrap().is_empty());
+        release(&versions, "1").await;
+        release(&versions, "2").await;
+        versions.activate(2).await.unwrap();
+
+        let releases = versions.list().await.unwrap();
+
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[0].number, 1);
+        assert_eq!(releases[0].deployment_id.as_deref(), Some("dpl-1"));
+        assert!(releases[0].created_at.is_some());
+        assert!(releases[0].activated_at.is_none());
+        assert!(!releases[0].live);
+        assert_eq!(releases[1].number, 2);
+        assert!(releases[1].activated_at.is_some());
+        assert!(releases[1].live);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tolerates_missing_metadata() {
+        let (dir, versions) = fixture("versions_list_no_metadata").await;
+        release(&versions, "1").await;
+        versions
+            .dir()
+            .file("releases/1.json")
+            .delete()
+            .await
+            .unwrap();
+
+        let releases = versions.list().await.unwrap();
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].deployment_id, None);
+        assert_eq!(releases[0].created_at, None);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod rollback {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_to_the_previously_live_release() {
+        let (dir, versions) = fixture("versions_rollback").await;
+        for i in 1..=3 {
+            release(&versions, &i.to_string()).await;
+        }
+        versions.activate(1).await.unwrap();
+        versions.activate(2).await.unwrap();
+        // release 3 was never live, so it isn't known to be good
+        let current = versions.current_link().path().join("app.json");
+
+        assert_eq!(versions.rollback(None).await.unwrap(), 1);
+
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        assert_eq!(std::fs::read_to_string(&current).unwrap(), "1");
+        let releases = versions.list().await.unwrap();
+        assert!(releases[1].rolled_back);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn skips_releases_rolled_back_from() {
+        let (dir, versions) = fixture("versions_rollback_skip").await;
+        for i in 1..=3 {
+            release(&versions, &i.to_string()).await;
+            versions.activate(i).await.unwrap();
+        }
+        assert_eq!(versions.rollback(None).await.unwrap(), 2);
+
+        // release 3 was rolled back from, so rolling back again goes further back
+        assert_eq!(versions.rollback(None).await.unwrap(), 1);
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reactivating_clears_the_rolled_back_flag() {
+        let (dir, versions) = fixture("versions_rollback_reactivate").await;
+        for i in 1..=2 {
+            release(&versions, &i.to_string()).await;
+            versions.activate(i).await.unwrap();
+        }
+        versions.rollback(None).await.unwrap();
+
+        versions.activate(2).await.unwrap();
+
+        let releases = versions.list().await.unwrap();
+        assert!(!releases[1].rolled_back);
+        assert_eq!(versions.rollback(None).await.unwrap(), 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn is_rolled_back_while_an_older_release_is_live() {
+        let (dir, versions) = fixture("versions_is_rolled_back").await;
+        assert!(!versions.is_rolled_back().await.unwrap());
+        for i in 1..=2 {
+            release(&versions, &i.to_string()).await;
+            versions.activate(i).await.unwrap();
+        }
+        assert!(!versions.is_rolled_back().await.unwrap());
+
+        versions.rollback(None).await.unwrap();
+        assert!(versions.is_rolled_back().await.unwrap());
+
+        versions.activate(2).await.unwrap();
+        assert!(!versions.is_rolled_back().await.unwrap());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn to_a_given_release() {
+        let (dir, versions) = fixture("versions_rollback_to").await;
+        for i in 1..=3 {
+            release(&versions, &i.to_string()).await;
+        }
+        versions.activate(3).await.unwrap();
+
+        assert_eq!(versions.rollback(Some(1)).await.unwrap(), 1);
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+
+        // rolling back to the live release leaves it live
+        assert_eq!(versions.rollback(Some(1)).await.unwrap(), 1);
+        let releases = versions.list().await.unwrap();
+        assert!(!releases[0].rolled_back);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_release() {
+        let (dir, versions) = fixture("versions_rollback_unknown").await;
+        release(&versions, "1").await;
+        versions.activate(1).await.unwrap();
+
+        let result = versions.rollback(Some(7)).await;
+
+        assert!(matches!(result, Err(DeployErr::ReleaseNotFound(_))));
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn nothing_to_roll_back_to() {
+        let (dir, versions) = fixture("versions_rollback_none").await;
+        release(&versions, "1").await;
+        release(&versions, "2").await;
+        versions.activate(1).await.unwrap();
+
+        let result = versions.rollback(None).await;
+
+        assert!(matches!(result, Err(DeployErr::NoRollbackTarget(_))));
+        assert_eq!(versions.current().await.unwrap(), Some(1));
+        dir.delete().await.unwrap();
+    }
+}
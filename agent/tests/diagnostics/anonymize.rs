@@ -0,0 +1,46 @@
+// internal crates
+use miru_agent::crypt::salt::{Salt, PREFIX};
+use miru_agent::diagnostics::anonymize;
+use miru_agent::filesys::WriteOptions;
+use miru_agent::models::Device;
+use miru_agent::platform;
+use miru_agent::storage::Layout;
+use miru_agent::testkit;
+
+pub mod identifiers {
+    use super::*;
+
+    #[tokio::test]
+    async fn pairs_the_device_id_and_host_name_with_salted_hashes() {
+        let dir = testkit::temp_dir("anonymize_identifiers").await;
+        let layout = Layout::new(dir.clone());
+        let device = Device {
+            id: "dvc_123".to_string(),
+            ..Default::default()
+        };
+        layout
+            .device()
+            .write_json(&device, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let identifiers = anonymize::identifiers(&layout).await.unwrap();
+        let salt = Salt::load_or_create(&layout.auth().device_salt())
+            .await
+            .unwrap();
+        assert_eq!(
+            identifiers[0],
+            ("dvc_123".to_string(), salt.anonymize("dvc_123").unwrap())
+        );
+        let host_name = platform::host_name();
+        if !host_name.is_empty() {
+            assert_eq!(identifiers[1].0, host_name);
+        }
+        for (_, anonymized) in &identifiers {
+            assert!(anonymized.starts_with(PREFIX));
+        }
+
+        // the salt is persisted so the hashes are stable
+        assert_eq!(anonymize::identifiers(&layout).await.unwrap(), identifiers);
+    }
+}
@@ -0,0 +1,235 @@
+// internal crates
+use miru_agent::diagnostics::crash::{self, Build, Options, Report};
+use miru_agent::diagnostics::{Redactor, REDACTED};
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::logs::RecentLines;
+use miru_agent::storage::{layout::CrashLayout, Layout};
+use miru_agent::version;
+
+// external crates
+use serial_test::serial;
+
+async fn setup(prefix: &str) -> (filesys::Dir, CrashLayout) {
+    let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+    let layout = Layout::new(dir.clone()).crash();
+    (dir, layout)
+}
+
+fn options(layout: &CrashLayout, lines: &[&str]) -> Options {
+    let recent_lines = RecentLines::new(10);
+    for line in lines {
+        recent_lines.push(line.to_string());
+    }
+    Options {
+        layout: layout.clone(),
+        recent_lines,
+        redactor: Some(Redactor::default()),
+        upload: true,
+    }
+}
+
+pub mod capture {
+    use super::*;
+
+    #[tokio::test]
+    async fn includes_the_build_and_recent_log_lines() {
+        let (dir, layout) = setup("crash_capture").await;
+        let options = options(&layout, &["syncing", "deployed dpl_1"]);
+
+        let report = Report::capture("boom", Some("src/lib.rs:1:1".to_string()), &options);
+
+        assert_eq!(report.message, "boom");
+        assert_eq!(report.location.as_deref(), Some("src/lib.rs:1:1"));
+        assert_eq!(report.build, Build::current());
+        assert_eq!(report.build.version, version::VERSION);
+        assert_eq!(report.log_lines, vec!["syncing", "deployed dpl_1"]);
+        assert!(!report.backtrace.is_empty());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn redacts_the_message_and_log_lines() {
+        let (dir, layout) = setup("crash_capture_redact").await;
+        let options = options(&layout, &["password=hunter2"]);
+
+        let report = Report::capture("api_key: \"k-123\"", None, &options);
+
+        assert!(!report.message.contains("k-123"), "{}", report.message);
+        assert!(report.message.contains(REDACTED));
+        assert!(!report.log_lines[0].contains("hunter2"));
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod panic_message {
+    use super::*;
+
+    #[test]
+    fn reads_str_and_string_payloads() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new("static");
+        assert_eq!(crash::panic_message(payload.as_ref()), "static");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(format!("formatted {}", 1));
+        assert_eq!(crash::panic_message(payload.as_ref()), "formatted 1");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(7);
+        assert_eq!(
+            crash::panic_message(payload.as_ref()),
+            "panicked without a message"
+        );
+    }
+}
+
+pub mod write {
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_the_pending_report_and_last_crash() {
+        let (dir, layout) = setup("crash_write").await;
+        let report = Report::capture("boom", None, &options(&layout, &[]));
+
+        crash::write(&layout, &report, true).unwrap();
+
+        assert_eq!(crash::pending(&layout).await.unwrap(), vec![report.clone()]);
+        assert_eq!(crash::last_crash(&layout).await, Some(report));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn not_queued_without_upload() {
+        let (dir, layout) = setup("crash_write_no_upload").await;
+        let report = Report::capture("boom", None, &options(&layout, &[]));
+
+        crash::write(&layout, &report, false).unwrap();
+
+        assert!(crash::pending(&layout).await.unwrap().is_empty());
+        assert_eq!(crash::last_crash(&layout).await, Some(report));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_the_most_recent_pending_reports() {
+        let (dir, layout) = setup("crash_write_retention").await;
+        let options = options(&layout, &[]);
+        let mut reports = Vec::new();
+        for i in 0..crash::MAX_PENDING + 3 {
+            let report = Report::capture(&format!("crash {i}"), None, &options);
+            crash::write(&layout, &report, true).unwrap();
+            reports.push(report);
+        }
+
+        let pending = crash::pending(&layout).await.unwrap();
+
+        assert_eq!(pending.len(), crash::MAX_PENDING);
+        let last = reports.last().unwrap();
+        assert!(pending.iter().any(|report| report.id == last.id));
+        assert_eq!(crash::last_crash(&layout).await.as_ref(), Some(last));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn caps_the_size_of_pending_reports() {
+        let (dir, layout) = setup("crash_write_size").await;
+        let options = options(&layout, &[]);
+        let big = "x".repeat(crash::MAX_PENDING_BYTES as usize / 2);
+        let mut last = None;
+        for _ in 0..3 {
+            let report = Report::capture(&big, None, &options);
+            crash::write(&layout, &report, true).unwrap();
+            last = Some(report);
+        }
+
+        let pending = crash::pending(&layout).await.unwrap();
+
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, last.unwrap().id);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_last_crash_outlives_the_upload() {
+        let (dir, layout) = setup("crash_remove").await;
+        let report = Report::capture("boom", None, &options(&layout, &[]));
+        crash::write(&layout, &report, true).unwrap();
+
+        crash::remove_pending(&layout, &report.id).await.unwrap();
+
+        assert!(crash::pending(&layout).await.unwrap().is_empty());
+        assert_eq!(crash::last_crash(&layout).await, Some(report));
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod pending {
+    use super::*;
+
+    #[tokio::test]
+    async fn none_without_a_crash() {
+        let (dir, layout) = setup("crash_pending_none").await;
+        assert!(crash::pending(&layout).await.unwrap().is_empty());
+        assert_eq!(crash::last_crash(&layout).await, None);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn oldest_first() {
+        let (dir, layout) = setup("crash_pending_order").await;
+        let options = options(&layout, &[]);
+        let newer = Report::capture("second", None, &options);
+        let older = Report {
+            crashed_at: newer.crashed_at - chrono::TimeDelta::minutes(1),
+            ..Report::capture("first", None, &options)
+        };
+        crash::write(&layout, &newer, true).unwrap();
+        crash::write(&layout, &older, true).unwrap();
+
+        let pending = crash::pending(&layout).await.unwrap();
+
+        assert_eq!(pending, vec![older, newer]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deletes_unreadable_reports() {
+        let (dir, layout) = setup("crash_pending_unreadable").await;
+        let corrupt = layout.pending().file("corrupt.json");
+        corrupt
+            .write_string("{", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        assert!(crash::pending(&layout).await.unwrap().is_empty());
+        assert!(!corrupt.exists());
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod install {
+    use super::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn writes_a_report_when_a_thread_panics() {
+        let (dir, layout) = setup("crash_install").await;
+        crash::install(options(&layout, &["before the panic"]));
+
+        let result = std::thread::Builder::new()
+            .name("crashing".to_string())
+            .spawn(|| panic!("crash install test"))
+            .unwrap()
+            .join();
+        // restore the default hook for the other tests
+        let _ = std::panic::take_hook();
+        assert!(result.is_err());
+
+        // other tests may panic while the hook is installed
+        let pending = crash::pending(&layout).await.unwrap();
+        let report = pending
+            .iter()
+            .find(|report| report.message == "crash install test")
+            .unwrap();
+        assert_eq!(report.thread.as_deref(), Some("crashing"));
+        assert!(report.location.as_ref().unwrap().contains("crash.rs"));
+        assert_eq!(report.log_lines, vec!["before the panic"]);
+        assert!(crash::last_crash(&layout).await.is_some());
+        dir.delete().await.unwrap();
+    }
+}
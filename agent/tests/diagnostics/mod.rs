@@ -0,0 +1,4 @@
+pub mod anonymize;
+pub mod bundle;
+pub mod crash;
+pub mod redact;
@@ -0,0 +1,165 @@
+// standard crates
+use std::fmt;
+use std::io;
+
+// internal crates
+use miru_agent::errors::{classify, Error};
+use miru_agent::http::errors::TransportErr;
+use miru_agent::http::request::{Meta, Params};
+use miru_agent::http::HTTPErr;
+
+// the registry is global, so every test registers classifiers for its own error types
+macro_rules! test_err {
+    ($name:ident) => {
+        #[derive(Debug)]
+        struct $name {
+            source: Option<Box<dyn std::error::Error + Send + Sync>>,
+        }
+
+        #[allow(dead_code)]
+        impl $name {
+            fn new() -> Self {
+                Self { source: None }
+            }
+
+            fn wrapping(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+                Self {
+                    source: Some(Box::new(source)),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, stringify!($name))
+            }
+        }
+
+        impl std::error::Error for $name {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                self.source.as_deref().map(|e| e as _)
+            }
+        }
+    };
+}
+
+fn meta() -> Meta {
+    Params::get("http://test/classify").meta().unwrap()
+}
+
+pub mod builtin {
+    use super::*;
+
+    test_err!(Unknown);
+
+    #[test]
+    fn unknown_errors_are_not_network_errors() {
+        assert!(!classify::is_network_conn_err(&Unknown::new()));
+    }
+
+    #[test]
+    fn io_errors_by_kind() {
+        for kind in [
+            io::ErrorKind::ConnectionRefused,
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::NetworkUnreachable,
+        ] {
+            assert!(
+                classify::is_network_conn_err(&io::Error::from(kind)),
+                "{kind:?}"
+            );
+        }
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied] {
+            assert!(
+                !classify::is_network_conn_err(&io::Error::from(kind)),
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn walks_the_source_chain() {
+        let err = Unknown::wrapping(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert!(classify::is_network_conn_err(&err));
+    }
+}
+
+pub mod registered {
+    use super::*;
+
+    test_err!(LinkDown);
+    test_err!(BadFrame);
+    test_err!(Deferred);
+    test_err!(Wrapped);
+
+    #[test]
+    fn classifies_registered_types() {
+        classify::register_type::<LinkDown, _>(|_| true);
+        classify::register_type::<BadFrame, _>(|_| false);
+        assert!(classify::is_network_conn_err(&LinkDown::new()));
+        assert!(!classify::is_network_conn_err(&BadFrame::new()));
+    }
+
+    #[test]
+    fn closest_classified_error_decides() {
+        classify::register_type::<LinkDown, _>(|_| true);
+        classify::register_type::<BadFrame, _>(|_| false);
+        // a protocol error caused by a dropped link is still a protocol error
+        let err = BadFrame::wrapping(LinkDown::new());
+        assert!(!classify::is_network_conn_err(&err));
+        let err = BadFrame::wrapping(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert!(!classify::is_network_conn_err(&err));
+    }
+
+    #[test]
+    fn undecided_classifiers_defer() {
+        classify::register(|err| err.downcast_ref::<Deferred>().and(None));
+        let err = Deferred::wrapping(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(classify::is_network_conn_err(&err));
+    }
+
+    #[test]
+    fn sees_through_io_errors() {
+        classify::register_type::<Wrapped, _>(|_| true);
+        let err = io::Error::other(Wrapped::new());
+        assert!(classify::is_network_conn_err(&err));
+    }
+}
+
+pub mod transport_err {
+    use super::*;
+
+    test_err!(Unreachable);
+    test_err!(Rejected);
+
+    #[test]
+    fn uses_registered_classifiers() {
+        classify::register_type::<Unreachable, _>(|_| true);
+
+        let err = HTTPErr::TransportErr(TransportErr {
+            request: meta(),
+            source: Box::new(Unreachable::new()),
+            trace: miru_agent::trace!(),
+        });
+        assert!(err.is_network_conn_err());
+
+        let err = HTTPErr::TransportErr(TransportErr {
+            request: meta(),
+            source: Box::new(Rejected::new()),
+            trace: miru_agent::trace!(),
+        });
+        assert!(!err.is_network_conn_err());
+    }
+
+    #[test]
+    fn display_format() {
+        let err = TransportErr {
+            request: meta(),
+            source: Box::new(Rejected::new()),
+            trace: miru_agent::trace!(),
+        };
+        let display = format!("{err}");
+        assert!(display.contains("transport error: Rejected"), "{display}");
+    }
+}
@@ -1,3 +1,5 @@
+pub mod classify;
+
 // standard crates
 use std::fmt;
 use std::time::Duration;
@@ -3,8 +3,9 @@ use device_api::models::{
     DeploymentActivityStatus, DeploymentErrorStatus, DeploymentStatus, DeploymentTargetStatus,
 };
 use miru_agent::events::model::{
-    DeploymentDeployedEvent, DeploymentRemovedEvent, Event, EventArgs, DEPLOYMENT_DEPLOYED,
-    DEPLOYMENT_REMOVED,
+    ConfigChange, ConfigChangeKind, ConfigChangedEvent, DeploymentDeployedEvent,
+    DeploymentRemovedEvent, Event, EventArgs, CONFIG_CHANGED, DEPLOYMENT_DEPLOYED,
+    DEPLOYMENT_REMOVED, SYNC_FAILED,
 };
 use miru_agent::models::{Deployment, DplActivity, DplErrStatus, DplTarget};
 
@@ -29,6 +30,16 @@ mod event_types {
     fn deployment_removed_type_string() {
         assert_eq!(DEPLOYMENT_REMOVED, "deployment.removed");
     }
+
+    #[test]
+    fn config_changed_type_string() {
+        assert_eq!(CONFIG_CHANGED, "config.changed");
+    }
+
+    #[test]
+    fn sync_failed_type_string() {
+        assert_eq!(SYNC_FAILED, "sync.failed");
+    }
 }
 
 // ========================= EVENT ========================= //
@@ -202,3 +213,62 @@ mod deployment_removed {
         );
     }
 }
+
+// ========================= CONFIG CHANGED ========================= //
+
+mod config_changed {
+    use super::*;
+
+    #[test]
+    fn serializes_all_fields() {
+        let change = ConfigChange {
+            kind: ConfigChangeKind::Updated,
+            filepath: "/srv/miru/app.json".into(),
+            config_instance_id: "cfg-inst-1".into(),
+            config_type_name: "app".into(),
+            deployment_id: "dpl-1".into(),
+        };
+
+        let event = EventArgs::config_changed(vec![change.clone()]).unwrap();
+        assert_eq!(event.event_type, CONFIG_CHANGED);
+        assert_eq!(
+            event.data,
+            serde_json::json!({
+                "changes": [{
+                    "kind": "updated",
+                    "filepath": "/srv/miru/app.json",
+                    "config_instance_id": "cfg-inst-1",
+                    "config_type_name": "app",
+                    "deployment_id": "dpl-1",
+                }]
+            })
+        );
+        let actual: ConfigChangedEvent = serde_json::from_value(event.data).unwrap();
+        assert_eq!(
+            actual,
+            ConfigChangedEvent {
+                changes: vec![change]
+            }
+        );
+    }
+}
+
+// ========================= SYNC FAILED ========================= //
+
+mod sync_failed {
+    use super::*;
+
+    #[test]
+    fn serializes_all_fields() {
+        let event = EventArgs::sync_failed("auth", 3, "token rejected").unwrap();
+        assert_eq!(event.event_type, SYNC_FAILED);
+        assert_eq!(
+            event.data,
+            serde_json::json!({
+                "failure_class": "auth",
+                "streak": 3,
+                "error": "token rejected",
+            })
+        );
+    }
+}
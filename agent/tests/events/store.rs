@@ -2,7 +2,7 @@
 use miru_agent::events::errors::EventsErr;
 use miru_agent::events::model::{Event, EventArgs, DEPLOYMENT_DEPLOYED};
 use miru_agent::events::store::{EventStore, DEFAULT_MAX_RETAINED};
-use miru_agent::filesys::{self, WriteOptions};
+use miru_agent::filesys::{self, PathExt, WriteOptions};
 
 // external crates
 use chrono::Utc;
@@ -174,6 +174,22 @@ mod append {
         assert_eq!(store.latest_id(), Some(2));
     }
 
+    #[tokio::test]
+    async fn without_persistence_keeps_events_in_memory() {
+        let dir = filesys::Dir::create_temp_dir("ev_append_memory")
+            .await
+            .unwrap();
+        let log_file = dir.file("events.jsonl");
+
+        let mut store = make_store(&dir, DEFAULT_MAX_RETAINED)
+            .await
+            .without_persistence();
+        store.append(make_event("test.a")).await.unwrap();
+        assert_eq!(store.latest_id(), Some(1));
+        assert_eq!(store.replay_after(0).unwrap().len(), 1);
+        assert!(!log_file.exists());
+    }
+
     #[tokio::test]
     async fn preserves_event_type_and_data() {
         let dir = filesys::Dir::create_temp_dir("ev_append_data")
@@ -206,6 +206,28 @@ pub mod delete {
     }
 }
 
+pub mod shred {
+    use super::*;
+
+    #[tokio::test]
+    async fn exists() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let file = dir.file("secret");
+        file.write_string("hunter2", WriteOptions::default())
+            .await
+            .unwrap();
+        file.shred().await.unwrap();
+        assert!(!file.exists());
+    }
+
+    #[tokio::test]
+    async fn doesnt_exist() {
+        let file = filesys::File::new(PathBuf::from("doesnt_exist"));
+        file.shred().await.unwrap();
+        assert!(!file.exists());
+    }
+}
+
 pub mod name {
     use super::*;
 
@@ -3,3 +3,5 @@ pub mod dir;
 pub mod errors;
 pub mod file;
 pub mod path;
+pub mod sweep;
+pub mod wear;
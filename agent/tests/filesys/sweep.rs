@@ -0,0 +1,174 @@
+// standard crates
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// internal crates
+use miru_agent::filesys::sweep::{self, Options, Report};
+use miru_agent::filesys::{self, PathExt};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Backdates the modification time of a file or directory.
+fn age(path: &Path, by: Duration) {
+    let file = std::fs::File::open(path).unwrap();
+    file.set_modified(SystemTime::now() - by).unwrap();
+}
+
+/// Creates a directory holding a single file of `bytes` bytes, aged by `by`.
+fn artifact(path: &Path, bytes: usize, by: Duration) {
+    std::fs::create_dir_all(path).unwrap();
+    std::fs::write(path.join("tmpfile.tmp"), vec![0u8; bytes]).unwrap();
+    age(path, by);
+}
+
+pub mod is_artifact {
+    use super::*;
+
+    #[test]
+    fn matches_scratch_dirs() {
+        assert!(sweep::is_artifact(".rename_trash_1234"));
+        assert!(sweep::is_artifact(".atomicwriteAbC123"));
+    }
+
+    #[test]
+    fn ignores_everything_else() {
+        assert!(!sweep::is_artifact("rename_trash_1234"));
+        assert!(!sweep::is_artifact("settings.json"));
+        assert!(!sweep::is_artifact(".atomic"));
+        assert!(!sweep::is_artifact(""));
+    }
+}
+
+pub mod sweep_artifacts {
+    use super::*;
+
+    #[tokio::test]
+    async fn removes_stale_artifacts_recursively() {
+        let dir = filesys::Dir::create_temp_dir("sweep_recursive")
+            .await
+            .unwrap();
+        let trash = dir.path().join(".rename_trash_abc");
+        let staged = dir.path().join("a/b/.atomicwriteXYZ");
+        artifact(&trash, 100, 2 * HOUR);
+        artifact(&staged, 50, 2 * HOUR);
+
+        let report = sweep::sweep(&dir, Options::default()).await;
+
+        assert_eq!(
+            report,
+            Report {
+                removed: 2,
+                reclaimed_bytes: 150,
+                failed: 0,
+            }
+        );
+        assert!(!trash.exists());
+        assert!(!staged.exists());
+        assert!(dir.path().join("a/b").exists());
+    }
+
+    #[tokio::test]
+    async fn keeps_fresh_artifacts() {
+        let dir = filesys::Dir::create_temp_dir("sweep_fresh").await.unwrap();
+        let trash = dir.path().join(".rename_trash_abc");
+        artifact(&trash, 100, Duration::from_secs(60));
+
+        let report = sweep::sweep(&dir, Options::default()).await;
+
+        assert_eq!(report, Report::default());
+        assert!(trash.exists());
+    }
+
+    #[tokio::test]
+    async fn keeps_other_entries() {
+        let dir = filesys::Dir::create_temp_dir("sweep_others").await.unwrap();
+        let kept = dir.path().join("config_instances");
+        artifact(&kept, 10, 2 * HOUR);
+        std::fs::write(dir.path().join("settings.json"), "{}").unwrap();
+        age(&dir.path().join("settings.json"), 2 * HOUR);
+
+        let report = sweep::sweep(&dir, Options::default()).await;
+
+        assert_eq!(report, Report::default());
+        assert!(kept.join("tmpfile.tmp").exists());
+        assert!(dir.path().join("settings.json").exists());
+    }
+
+    #[tokio::test]
+    async fn honors_the_threshold() {
+        let dir = filesys::Dir::create_temp_dir("sweep_threshold")
+            .await
+            .unwrap();
+        let trash = dir.path().join(".rename_trash_abc");
+        artifact(&trash, 10, Duration::from_secs(120));
+
+        let opts = Options {
+            older_than: Duration::from_secs(60),
+        };
+        let report = sweep::sweep(&dir, opts).await;
+
+        assert_eq!(report.removed, 1);
+        assert!(!trash.exists());
+    }
+
+    #[tokio::test]
+    async fn missing_dir_is_empty() {
+        let dir = filesys::Dir::create_temp_dir("sweep_missing")
+            .await
+            .unwrap();
+        let report = sweep::sweep(&dir.subdir("missing"), Options::default()).await;
+        assert_eq!(report, Report::default());
+    }
+}
+
+pub mod sweep_all {
+    use super::*;
+
+    #[tokio::test]
+    async fn removes_every_stale_entry() {
+        let dir = filesys::Dir::create_temp_dir("sweep_all").await.unwrap();
+        std::fs::write(dir.path().join("private.key"), vec![0u8; 30]).unwrap();
+        age(&dir.path().join("private.key"), 2 * HOUR);
+        artifact(&dir.path().join("nested"), 20, 2 * HOUR);
+        std::fs::write(dir.path().join("fresh.key"), "x").unwrap();
+
+        let report = sweep::sweep_all(&dir, Options::default()).await;
+
+        assert_eq!(
+            report,
+            Report {
+                removed: 2,
+                reclaimed_bytes: 50,
+                failed: 0,
+            }
+        );
+        assert!(dir.path().join("fresh.key").exists());
+        assert!(!dir.path().join("private.key").exists());
+    }
+}
+
+pub mod report {
+    use super::*;
+
+    #[test]
+    fn merge() {
+        let mut report = Report {
+            removed: 1,
+            reclaimed_bytes: 10,
+            failed: 0,
+        };
+        report.merge(Report {
+            removed: 2,
+            reclaimed_bytes: 5,
+            failed: 1,
+        });
+        assert_eq!(
+            report,
+            Report {
+                removed: 3,
+                reclaimed_bytes: 15,
+                failed: 1,
+            }
+        );
+    }
+}
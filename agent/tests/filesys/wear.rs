@@ -0,0 +1,51 @@
+// standard crates
+use std::io::Write;
+
+// internal crates
+use miru_agent::filesys::wear::{self, CountingWriter};
+use miru_agent::filesys::{self, WriteOptions};
+
+// the counters are shared by every test in the process so the tests only check that
+// they grew by at least the bytes written
+
+pub mod counters {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let before = wear::written();
+        wear::record(wear::Category::Deployments, 10);
+        wear::record(wear::Category::Caches, 20);
+        let after = wear::written();
+        assert!(after.deployments >= before.deployments + 10);
+        assert!(after.caches >= before.caches + 20);
+    }
+
+    #[tokio::test]
+    async fn file_writes_are_counted() {
+        let dir = filesys::Dir::create_temp_dir("wear_file_writes")
+            .await
+            .unwrap();
+        let before = wear::written();
+        dir.file("a.txt")
+            .write_string("0123456789", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let after = wear::written();
+        assert!(after.caches + after.deployments >= before.caches + before.deployments + 10);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod counting_writer {
+    use super::*;
+
+    #[test]
+    fn counts_logs() {
+        let before = wear::written();
+        let mut writer = CountingWriter::new(Vec::new());
+        writer.write_all(b"log line\n").unwrap();
+        writer.flush().unwrap();
+        assert!(wear::written().logs >= before.logs + 9);
+    }
+}
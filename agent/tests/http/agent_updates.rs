@@ -0,0 +1,109 @@
+// internal crates
+use crate::updater::shared::{serve, Signed};
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::http::{self, agent_updates};
+
+const CONTENT: &[u8] = b"miru-agent v0.9.0";
+
+pub mod latest {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_the_release() {
+        let signed = Signed::new("agent_updates_latest", CONTENT).await;
+        let release = signed.release("v0.9.0").await;
+        let backend = serve(Some(release.clone()), CONTENT.to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+
+        let latest = agent_updates::latest(
+            &client,
+            agent_updates::LatestParams {
+                version: "v0.8.0",
+                os: "linux",
+                arch: "aarch64",
+                token: "token",
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(latest, Some(release));
+        assert_eq!(
+            *backend.queries.lock().unwrap(),
+            vec!["version=v0.8.0&os=linux&arch=aarch64".to_string()]
+        );
+        signed.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn up_to_date() {
+        let backend = serve(None, Vec::new()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+
+        let latest = agent_updates::latest(
+            &client,
+            agent_updates::LatestParams {
+                version: "v0.9.0",
+                os: "linux",
+                arch: "x86_64",
+                token: "token",
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(latest, None);
+    }
+}
+
+pub mod download {
+    use super::*;
+
+    #[tokio::test]
+    async fn downloads_the_binary() {
+        let signed = Signed::new("agent_updates_download", CONTENT).await;
+        let release = signed.release("v0.9.0").await;
+        let backend = serve(Some(release.clone()), CONTENT.to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+        let dest = signed.dir.file("miru-agent");
+
+        let downloaded = agent_updates::download(
+            &client,
+            agent_updates::DownloadParams {
+                release: &release,
+                dest: &dest,
+                token: "token",
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(downloaded.bytes, CONTENT.len() as u64);
+        assert_eq!(dest.read_bytes().await.unwrap(), CONTENT);
+        signed.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_a_digest_mismatch() {
+        let signed = Signed::new("agent_updates_mismatch", CONTENT).await;
+        let release = agent_updates::AgentRelease {
+            digest: Digest::compute(Algorithm::Sha256, b"something else"),
+            ..signed.release("v0.9.0").await
+        };
+        let backend = serve(Some(release.clone()), CONTENT.to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+
+        let result = agent_updates::download(
+            &client,
+            agent_updates::DownloadParams {
+                release: &release,
+                dest: &signed.dir.file("miru-agent"),
+                token: "token",
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        signed.dir.delete().await.unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+// standard crates
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// internal crates
+use crate::mocks::http_client::{self as mock, MockClient};
+use miru_agent::http::conditional::{Conditional, Validators};
+use miru_agent::http::request::Params;
+use miru_agent::http::{self, ClientI, HTTPErr};
+
+// external crates
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+
+const ETAG: &str = "\"v1\"";
+const LAST_MODIFIED: &str = "Wed, 21 Oct 2026 07:28:00 GMT";
+
+/// Serves "hello" with validators, or a 304 if the request's validators match.
+async fn resource(State(requests): State<Arc<AtomicUsize>>, headers: HeaderMap) -> Response {
+    requests.fetch_add(1, Ordering::SeqCst);
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| value == ETAG);
+    if matches {
+        return StatusCode::NOT_MODIFIED.into_response();
+    }
+    (
+        [(header::ETAG, ETAG), (header::LAST_MODIFIED, LAST_MODIFIED)],
+        "hello",
+    )
+        .into_response()
+}
+
+async fn server() -> (mock::Server, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let router = Router::new()
+        .route("/resource", get(resource))
+        .route("/not-modified", get(|| async { StatusCode::NOT_MODIFIED }))
+        .with_state(requests.clone());
+    (mock::run_server(router).await, requests)
+}
+
+fn v1() -> Validators {
+    Validators {
+        etag: Some(ETAG.to_string()),
+        last_modified: Some(LAST_MODIFIED.to_string()),
+    }
+}
+
+pub mod validators {
+    use super::*;
+
+    #[test]
+    fn from_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(Validators::from_headers(&headers).is_empty());
+
+        headers.insert(header::ETAG, ETAG.parse().unwrap());
+        headers.insert(header::LAST_MODIFIED, LAST_MODIFIED.parse().unwrap());
+        assert_eq!(Validators::from_headers(&headers), v1());
+    }
+
+    #[test]
+    fn apply_sets_conditional_headers() {
+        let params = v1().apply(Params::get("https://example.com"));
+        assert_eq!(
+            params.headers,
+            vec![
+                ("If-None-Match", ETAG.to_string()),
+                ("If-Modified-Since", LAST_MODIFIED.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_without_validators_is_unconditional() {
+        let params = Validators::default().apply(Params::get("https://example.com"));
+        assert!(params.headers.is_empty());
+    }
+
+    #[test]
+    fn map_keeps_validators() {
+        let modified = Conditional::Modified {
+            value: 2,
+            validators: v1(),
+        };
+        assert_eq!(
+            modified.map(|value| value * 2),
+            Conditional::Modified {
+                value: 4,
+                validators: v1(),
+            }
+        );
+        assert_eq!(
+            Conditional::<i32>::NotModified.map(|value| value * 2),
+            Conditional::NotModified
+        );
+    }
+}
+
+pub mod execute_conditional {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_the_resource_with_its_validators() {
+        let (server, _) = server().await;
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/resource", server.base_url);
+        let (result, _) = client
+            .execute_conditional(Params::get(&url), &Validators::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Conditional::Modified {
+                value: "hello".to_string(),
+                validators: v1(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn not_modified_when_the_validators_match() {
+        let (server, requests) = server().await;
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/resource", server.base_url);
+        let (result, _) = client
+            .execute_conditional(Params::get(&url), &v1())
+            .await
+            .unwrap();
+        assert_eq!(result, Conditional::NotModified);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn modified_when_the_validators_are_stale() {
+        let (server, _) = server().await;
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/resource", server.base_url);
+        let stale = Validators {
+            etag: Some("\"v0\"".to_string()),
+            last_modified: None,
+        };
+        let (result, _) = client
+            .execute_conditional(Params::get(&url), &stale)
+            .await
+            .unwrap();
+        assert!(matches!(result, Conditional::Modified { .. }));
+    }
+
+    #[tokio::test]
+    async fn unconditional_304_is_an_error() {
+        let (server, _) = server().await;
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/not-modified", server.base_url);
+        let err = client.execute(Params::get(&url)).await.unwrap_err();
+        assert!(matches!(err, HTTPErr::RequestFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn clients_without_support_return_the_resource() {
+        let mock = MockClient::default();
+        let url = format!("{}/device", mock.base_url());
+        let (result, _) = mock
+            .execute_conditional(Params::get(&url), &v1())
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            Conditional::Modified { validators, .. } if validators.is_empty()
+        ));
+    }
+}
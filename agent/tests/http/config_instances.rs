@@ -1,6 +1,8 @@
 // internal crates
 use crate::mocks::http_client::{Call, CapturedRequest, MockClient};
-use miru_agent::http::config_instances::{self, GetContentParams};
+use miru_agent::http::config_instances::{
+    self, ContentPatch, GetContentParams, GetPatchParams, PatchFormat,
+};
 use miru_agent::http::errors::MockErr;
 use miru_agent::http::HTTPErr;
 
@@ -83,3 +85,67 @@ pub mod get_content {
         assert_eq!(result, "key: value\nother: 123");
     }
 }
+
+pub mod get_patch {
+    use super::*;
+
+    #[tokio::test]
+    async fn success() {
+        let mock = MockClient::default();
+        mock.set_get_config_instance_patch(|id| {
+            assert_eq!(id, "ci_2");
+            Ok(ContentPatch {
+                base_id: "ci_1".to_string(),
+                format: PatchFormat::MergePatch,
+                patch: serde_json::json!({"speed": 5}),
+                digest: "abc".to_string(),
+            })
+        });
+
+        let result = config_instances::get_patch(
+            &mock,
+            GetPatchParams {
+                id: "ci_2",
+                base_id: "ci_1",
+                base_digest: "sha256:abc",
+                token: "test-token",
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.base_id, "ci_1");
+        assert_eq!(result.format, PatchFormat::MergePatch);
+        assert_eq!(result.patch, serde_json::json!({"speed": 5}));
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].call, Call::GetConfigInstancePatch);
+        assert_eq!(requests[0].path, "/config_instances/ci_2/content/patch");
+        assert_eq!(
+            requests[0].query,
+            vec![
+                ("base".to_string(), "ci_1".to_string()),
+                ("base_digest".to_string(), "sha256:abc".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn error_propagates() {
+        let mock = MockClient::default();
+        mock.set_get_config_instance_patch(|_| Err(mock_err()));
+
+        let result = config_instances::get_patch(
+            &mock,
+            GetPatchParams {
+                id: "ci_2",
+                base_id: "ci_1",
+                base_digest: "sha256:abc",
+                token: "test-token",
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(HTTPErr::MockErr(_))));
+    }
+}
@@ -0,0 +1,66 @@
+// internal crates
+use crate::mocks::http_client::{Call, CapturedRequest, MockClient};
+use miru_agent::http::config_schemas::{self, GetParams};
+use miru_agent::http::errors::MockErr;
+use miru_agent::http::HTTPErr;
+
+// external crates
+use serde_json::json;
+
+pub mod get {
+    use super::*;
+
+    #[tokio::test]
+    async fn success() {
+        let mock = MockClient::default();
+        mock.set_get_config_schema(|id| {
+            assert_eq!(id, "cfg_sch_1");
+            Ok(json!({"type": "object", "required": ["port"]}))
+        });
+
+        let schema = config_schemas::get(
+            &mock,
+            GetParams {
+                id: "cfg_sch_1",
+                token: "test-token",
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(schema, json!({"type": "object", "required": ["port"]}));
+        assert_eq!(
+            mock.requests(),
+            vec![CapturedRequest {
+                call: Call::GetConfigSchema,
+                method: reqwest::Method::GET,
+                path: "/config_schemas/cfg_sch_1".into(),
+                url: "http://mock/config_schemas/cfg_sch_1".into(),
+                query: vec![],
+                body: None,
+                token: Some("test-token".into()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn error_propagates() {
+        let mock = MockClient::default();
+        mock.set_get_config_schema(|_| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+
+        let result = config_schemas::get(
+            &mock,
+            GetParams {
+                id: "cfg_sch_1",
+                token: "test-token",
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(HTTPErr::MockErr(_))));
+    }
+}
@@ -7,10 +7,14 @@ use backend_api::models::{
     Deployment as BackendDeployment, DeploymentActivityStatus, DeploymentList,
     UpdateDeploymentRequest,
 };
-use miru_agent::http::deployments::{self, ListAllParams, ListParams, UpdateParams};
+use miru_agent::http::deployments::{
+    self, ListAllParams, ListParams, ListedDeployment, UpdateParams,
+};
 use miru_agent::http::errors::MockErr;
 use miru_agent::http::query::Page;
 use miru_agent::http::HTTPErr;
+use miru_agent::models::release::SignatureAlgorithm;
+use miru_agent::models::{DplMetrics, Signature};
 
 fn mock_err() -> HTTPErr {
     HTTPErr::MockErr(MockErr {
@@ -178,6 +182,7 @@ pub mod list_all {
                 activity_status: &[],
                 expansions: &[],
                 token: "test-token",
+                audit_anchor: None,
             },
         )
         .await
@@ -187,6 +192,7 @@ pub mod list_all {
             id: "dep_1".to_string(),
             ..BackendDeployment::default()
         }];
+        let result: Vec<_> = result.into_iter().map(|l| l.deployment).collect();
         assert_eq!(result, expected);
         assert_eq!(mock.call_count(Call::ListDeployments), 1);
         assert_eq!(
@@ -239,6 +245,7 @@ pub mod list_all {
                 activity_status: &[],
                 expansions: &[],
                 token: "test-token",
+                audit_anchor: None,
             },
         )
         .await
@@ -254,6 +261,7 @@ pub mod list_all {
                 ..BackendDeployment::default()
             },
         ];
+        let result: Vec<_> = result.into_iter().map(|l| l.deployment).collect();
         assert_eq!(result, expected);
         assert_eq!(mock.call_count(Call::ListDeployments), 2);
 
@@ -294,12 +302,14 @@ pub mod list_all {
                 activity_status: &[],
                 expansions: &[],
                 token: "test-token",
+                audit_anchor: None,
             },
         )
         .await
         .unwrap();
 
         let expected: Vec<BackendDeployment> = vec![];
+        let result: Vec<_> = result.into_iter().map(|l| l.deployment).collect();
         assert_eq!(result, expected);
         assert_eq!(mock.call_count(Call::ListDeployments), 1);
     }
@@ -315,6 +325,7 @@ pub mod list_all {
                 activity_status: &[],
                 expansions: &[],
                 token: "test-token",
+                audit_anchor: None,
             },
         )
         .await;
@@ -323,6 +334,154 @@ pub mod list_all {
     }
 }
 
+pub mod list_all_if_modified {
+    use super::*;
+    use crate::mocks::http_client as mock;
+    use axum::extract::{Query, State};
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::Router;
+    use miru_agent::http;
+    use miru_agent::http::conditional::{Conditional, Validators};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    struct Backend {
+        pages: usize,
+        requests: AtomicUsize,
+    }
+
+    /// Serves `pages` pages of one deployment each, tagging each page with its offset.
+    async fn list(
+        State(backend): State<Arc<Backend>>,
+        Query(query): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response {
+        backend.requests.fetch_add(1, Ordering::SeqCst);
+        let offset: usize = query["offset"].parse().unwrap();
+        let etag = format!("\"page-{offset}\"");
+        if headers
+            .get(header::IF_NONE_MATCH)
+            .is_some_and(|value| value == etag.as_str())
+        {
+            return StatusCode::NOT_MODIFIED.into_response();
+        }
+        let page = offset / 100;
+        let body = serde_json::json!({
+            "object": "list",
+            "has_more": page + 1 < backend.pages,
+            "data": [BackendDeployment {
+                id: format!("dep_{page}"),
+                ..BackendDeployment::default()
+            }],
+        });
+        ([(header::ETAG, etag)], body.to_string()).into_response()
+    }
+
+    async fn server(pages: usize) -> (mock::Server, Arc<Backend>) {
+        let backend = Arc::new(Backend {
+            pages,
+            requests: AtomicUsize::new(0),
+        });
+        let router = Router::new()
+            .route("/deployments", get(list))
+            .with_state(backend.clone());
+        (mock::run_server(router).await, backend)
+    }
+
+    fn params() -> ListAllParams<'static> {
+        ListAllParams {
+            activity_status: &[],
+            expansions: &[],
+            token: "test-token",
+            audit_anchor: None,
+        }
+    }
+
+    fn ids(deployments: &[ListedDeployment]) -> Vec<&str> {
+        deployments
+            .iter()
+            .map(|listed| listed.deployment.id.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn single_page_returns_validators() {
+        let (server, _) = server(1).await;
+        let client = http::Client::new(&server.base_url).unwrap();
+
+        let result = deployments::list_all_if_modified(&client, params(), &Validators::default())
+            .await
+            .unwrap();
+        let Conditional::Modified { value, validators } = result else {
+            panic!("expected the list");
+        };
+        assert_eq!(ids(&value), vec!["dep_0"]);
+        assert_eq!(validators.etag.as_deref(), Some("\"page-0\""));
+    }
+
+    #[tokio::test]
+    async fn unchanged_list_is_not_modified() {
+        let (server, backend) = server(1).await;
+        let client = http::Client::new(&server.base_url).unwrap();
+
+        let Conditional::Modified { validators, .. } =
+            deployments::list_all_if_modified(&client, params(), &Validators::default())
+                .await
+                .unwrap()
+        else {
+            panic!("expected the list");
+        };
+        let result = deployments::list_all_if_modified(&client, params(), &validators)
+            .await
+            .unwrap();
+        assert_eq!(result, Conditional::NotModified);
+        assert_eq!(backend.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn multiple_pages_return_no_validators() {
+        let (server, backend) = server(3).await;
+        let client = http::Client::new(&server.base_url).unwrap();
+
+        let result = deployments::list_all_if_modified(&client, params(), &Validators::default())
+            .await
+            .unwrap();
+        let Conditional::Modified { value, validators } = result else {
+            panic!("expected the list");
+        };
+        assert_eq!(ids(&value), vec!["dep_0", "dep_1", "dep_2"]);
+        assert!(validators.is_empty());
+        assert_eq!(backend.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn client_without_support_lists_every_page() {
+        let mock = MockClient::default();
+        mock.set_list_all_deployments(move || {
+            Ok(vec![BackendDeployment {
+                id: "dep_1".to_string(),
+                ..BackendDeployment::default()
+            }])
+        });
+        let validators = Validators {
+            etag: Some("\"page-0\"".to_string()),
+            last_modified: None,
+        };
+
+        let result = deployments::list_all_if_modified(&mock, params(), &validators)
+            .await
+            .unwrap();
+        let Conditional::Modified { value, validators } = result else {
+            panic!("expected the list");
+        };
+        assert_eq!(ids(&value), vec!["dep_1"]);
+        assert!(validators.is_empty());
+        assert_eq!(mock.call_count(Call::ListDeployments), 1);
+    }
+}
+
 pub mod update {
     use super::*;
 
@@ -347,6 +506,7 @@ pub mod update {
             UpdateParams {
                 id: "dep_1",
                 updates: &updates,
+                metrics: None,
                 token: "test-token",
             },
         )
@@ -373,6 +533,47 @@ pub mod update {
         );
     }
 
+    #[tokio::test]
+    async fn appends_metrics_to_body() {
+        let mock = MockClient::default();
+
+        let updates = UpdateDeploymentRequest {
+            activity_status: Some(DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED),
+            ..UpdateDeploymentRequest::default()
+        };
+        let metrics = DplMetrics {
+            bytes_downloaded: 512,
+            staging_duration_ms: Some(20),
+            materialization_duration_ms: Some(4),
+            hook_duration_ms: None,
+        };
+        deployments::update(
+            &mock,
+            UpdateParams {
+                id: "dep_1",
+                updates: &updates,
+                metrics: Some(&metrics),
+                token: "test-token",
+            },
+        )
+        .await
+        .unwrap();
+
+        let body = mock.requests()[0].body.clone().unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "activity_status": "deployed",
+                "metrics": {
+                    "bytes_downloaded": 512,
+                    "staging_duration_ms": 20,
+                    "materialization_duration_ms": 4,
+                },
+            })
+        );
+    }
+
     #[tokio::test]
     async fn error_propagates() {
         let mock = MockClient::default();
@@ -384,6 +585,7 @@ pub mod update {
             UpdateParams {
                 id: "dep_1",
                 updates: &updates,
+                metrics: None,
                 token: "test-token",
             },
         )
@@ -392,3 +594,65 @@ pub mod update {
         assert!(matches!(result, Err(HTTPErr::MockErr(_))));
     }
 }
+
+pub mod listed_deployment {
+    use super::*;
+
+    fn backend_json() -> serde_json::Value {
+        serde_json::to_value(BackendDeployment {
+            id: "dep_2".to_string(),
+            ..BackendDeployment::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn deserializes_dependencies() {
+        let mut json = backend_json();
+        json["dependencies"] = serde_json::json!({
+            "deployments": ["dep_1"],
+            "config_types": {"ct_app": ["ct_network"]},
+        });
+
+        let listed: ListedDeployment = serde_json::from_value(json).unwrap();
+        assert_eq!(listed.deployment.id, "dep_2");
+        assert_eq!(listed.dependencies.deployments, vec!["dep_1".to_string()]);
+        assert_eq!(
+            listed.dependencies.config_types["ct_app"],
+            vec!["ct_network".to_string()]
+        );
+    }
+
+    #[test]
+    fn dependencies_default_to_empty() {
+        let listed: ListedDeployment = serde_json::from_value(backend_json()).unwrap();
+        assert_eq!(listed.deployment.id, "dep_2");
+        assert!(listed.dependencies.is_empty());
+    }
+
+    #[test]
+    fn deserializes_the_release_signature() {
+        let mut json = backend_json();
+        json["release_signature"] = serde_json::json!({
+            "algorithm": "ecdsa_p256_sha256",
+            "key_id": "release-2026",
+            "value": "c2lnbmF0dXJl",
+        });
+
+        let listed: ListedDeployment = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            listed.release_signature,
+            Some(Signature {
+                algorithm: SignatureAlgorithm::EcdsaP256Sha256,
+                key_id: "release-2026".to_string(),
+                value: "c2lnbmF0dXJl".to_string(),
+            })
+        );
+
+        // unsigned releases leave it out
+        let listed: ListedDeployment = serde_json::from_value(backend_json()).unwrap();
+        assert_eq!(listed.release_signature, None);
+        let json = serde_json::to_value(&listed).unwrap();
+        assert!(json.get("release_signature").is_none());
+    }
+}
@@ -1,11 +1,11 @@
 // internal crates
 use crate::mocks::http_client::{Call, CapturedRequest, MockClient};
 use backend_api::models::{
-    Device, ProvisionDeviceRequest, ReprovisionDeviceRequest, TokenResponse,
+    Device, ProvisionDeviceRequest, ReprovisionDeviceRequest, SyncDevice, TokenResponse,
     UpdateDeviceFromAgentRequest,
 };
 use miru_agent::http::devices::{
-    self, IssueTokenParams, ProvisionParams, ReprovisionParams, UpdateParams,
+    self, IssueTokenParams, ProvisionParams, ReprovisionParams, UpdateParams, WaitForSyncParams,
 };
 use miru_agent::http::errors::MockErr;
 use miru_agent::http::HTTPErr;
@@ -248,3 +248,91 @@ pub mod update {
         assert!(matches!(result, Err(HTTPErr::MockErr(_))));
     }
 }
+
+pub mod deregister {
+    use super::*;
+
+    #[tokio::test]
+    async fn success() {
+        let mock = MockClient::default();
+
+        let result = devices::deregister(&mock, "test-token").await.unwrap();
+
+        assert_eq!(result, Device::default());
+        assert_eq!(
+            mock.requests(),
+            vec![CapturedRequest {
+                call: Call::DeregisterDevice,
+                method: reqwest::Method::DELETE,
+                path: "/device".into(),
+                url: "http://mock/device".into(),
+                query: vec![],
+                body: None,
+                token: Some("test-token".into()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn error_propagates() {
+        let mock = MockClient::default();
+        mock.set_deregister_device(|| Err(mock_err()));
+
+        let result = devices::deregister(&mock, "test-token").await;
+
+        assert!(matches!(result, Err(HTTPErr::MockErr(_))));
+    }
+}
+
+pub mod wait_for_sync {
+    use super::*;
+
+    #[tokio::test]
+    async fn success() {
+        let mock = MockClient::default();
+        mock.set_wait_for_sync(|| Ok(SyncDevice { is_synced: false }));
+
+        let result = devices::wait_for_sync(
+            &mock,
+            WaitForSyncParams {
+                id: "dvc_1",
+                wait: std::time::Duration::from_secs(300),
+                token: "test-token",
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result, SyncDevice { is_synced: false });
+        assert_eq!(
+            mock.requests(),
+            vec![CapturedRequest {
+                call: Call::WaitForSync,
+                method: reqwest::Method::GET,
+                path: "/devices/dvc_1/sync".into(),
+                url: "http://mock/devices/dvc_1/sync".into(),
+                query: vec![("wait_secs".into(), "300".into())],
+                body: None,
+                token: Some("test-token".into()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn error_propagates() {
+        let mock = MockClient::default();
+        mock.set_wait_for_sync(|| Err(mock_err()));
+
+        let result = devices::wait_for_sync(
+            &mock,
+            WaitForSyncParams {
+                id: "dvc_1",
+                wait: std::time::Duration::from_secs(300),
+                token: "test-token",
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(HTTPErr::MockErr(_))));
+    }
+}
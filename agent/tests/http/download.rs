@@ -0,0 +1,435 @@
+// standard crates
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::http::download::{self, Options, Progress};
+use miru_agent::http::request::Params;
+use miru_agent::http::{self, HTTPErr};
+
+// external crates
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+
+const ETAG: &str = "\"v1\"";
+
+/// Serves a payload, honouring Range requests, and drops the connection partway
+/// through the body of the first few responses.
+#[derive(Default)]
+struct Payload {
+    content: Vec<u8>,
+    /// For each of the first responses, how many bytes of the body to send before
+    /// dropping the connection.
+    drop_after: Vec<usize>,
+    /// Whether Range requests are ignored and the whole payload is always sent.
+    ignore_ranges: bool,
+    /// The Range and If-Range headers of each request.
+    requests: Vec<(Option<String>, Option<String>)>,
+}
+
+type Shared = Arc<Mutex<Payload>>;
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+async fn serve(State(payload): State<Shared>, headers: HeaderMap) -> Response {
+    let mut payload = payload.lock().unwrap();
+    let header = |name| {
+        headers
+            .get(name)
+            .map(|value: &header::HeaderValue| value.to_str().unwrap().to_string())
+    };
+    let range = header(header::RANGE);
+    payload
+        .requests
+        .push((range.clone(), header(header::IF_RANGE)));
+
+    let len = payload.content.len();
+    let start = match range {
+        Some(range) if !payload.ignore_ranges => range
+            .strip_prefix("bytes=")
+            .and_then(|r| r.strip_suffix('-'))
+            .and_then(|r| r.parse::<usize>().ok())
+            .unwrap(),
+        _ => 0,
+    };
+    if start >= len && start > 0 {
+        return Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .body(Body::empty())
+            .unwrap();
+    }
+    let body = payload.content[start..].to_vec();
+    let builder = match start {
+        0 => Response::builder().status(StatusCode::OK),
+        _ => Response::builder()
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{}/{len}", len - 1),
+            ),
+    }
+    .header(header::ETAG, ETAG);
+
+    if payload.drop_after.is_empty() {
+        return builder.body(Body::from(body)).unwrap();
+    }
+    // the body is sent before the connection is dropped so the client receives it
+    let sent = payload.drop_after.remove(0).min(body.len());
+    let part = Bytes::from(body[..sent].to_vec());
+    let chunks = futures::stream::unfold(Some(part), |part| async move {
+        match part {
+            Some(part) => Some((Ok(part), None)),
+            None => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Some((Err(std::io::Error::other("connection dropped")), None))
+            }
+        }
+    });
+    builder.body(Body::from_stream(chunks)).unwrap()
+}
+
+async fn run(payload: Payload) -> (mock::Server, Shared) {
+    let shared = Arc::new(Mutex::new(payload));
+    let router = Router::new()
+        .route("/payload", get(serve))
+        .with_state(shared.clone());
+    (mock::run_server(router).await, shared)
+}
+
+fn options() -> Options {
+    Options {
+        resume_delay: Duration::ZERO,
+        ..Default::default()
+    }
+}
+
+pub mod stream {
+    use super::*;
+
+    #[tokio::test]
+    async fn streams_payload_to_file() {
+        let content = payload(1024 * 1024);
+        let (server, shared) = run(Payload {
+            content: content.clone(),
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_streams")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+
+        let mut progress = Vec::new();
+        let downloaded = client
+            .download(Params::get(&url), &dest, &options(), |p| progress.push(p))
+            .await
+            .unwrap();
+
+        assert_eq!(dest.read_bytes().await.unwrap(), content);
+        assert_eq!(downloaded.bytes, content.len() as u64);
+        assert_eq!(downloaded.resumes, 0);
+        assert_eq!(
+            downloaded.digest,
+            Digest::compute(Algorithm::Sha256, &content)
+        );
+        assert!(!download::partial_file(&dest).unwrap().exists());
+        assert_eq!(shared.lock().unwrap().requests.len(), 1);
+
+        let last = progress.last().unwrap();
+        assert_eq!(
+            *last,
+            Progress {
+                received: content.len() as u64,
+                total: Some(content.len() as u64),
+            }
+        );
+        assert!(progress.windows(2).all(|w| w[0].received < w[1].received));
+    }
+
+    #[tokio::test]
+    async fn replaces_existing_destination() {
+        let content = payload(100);
+        let (server, _) = run(Payload {
+            content: content.clone(),
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_replaces")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        dest.write_bytes(b"old", WriteOptions::default())
+            .await
+            .unwrap();
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+
+        client
+            .download(Params::get(&url), &dest, &options(), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(dest.read_bytes().await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn verifies_expected_digest() {
+        let content = payload(4096);
+        let (server, _) = run(Payload {
+            content: content.clone(),
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_digest")
+            .await
+            .unwrap();
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+
+        for algorithm in Algorithm::ALL {
+            let dest = dir.file(&format!("{algorithm}.bin"));
+            let options = Options {
+                expected: Some(Digest::compute(algorithm, &content)),
+                ..options()
+            };
+            let downloaded = client
+                .download(Params::get(&url), &dest, &options, |_| {})
+                .await
+                .unwrap();
+            assert_eq!(downloaded.digest.algorithm(), algorithm);
+            assert_eq!(dest.read_bytes().await.unwrap(), content);
+        }
+    }
+
+    #[tokio::test]
+    async fn digest_mismatch_discards_download() {
+        let (server, _) = run(Payload {
+            content: payload(4096),
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_mismatch")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+        let options = Options {
+            expected: Some(Digest::compute(Algorithm::Sha256, b"something else")),
+            ..options()
+        };
+
+        let err = client
+            .download(Params::get(&url), &dest, &options, |_| {})
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HTTPErr::DownloadDigestMismatchErr(_)));
+        assert!(!dest.exists());
+        assert!(!download::partial_file(&dest).unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn request_failure_is_returned() {
+        let (server, _) = run(Payload::default()).await;
+        let dir = filesys::Dir::create_temp_dir("download_404").await.unwrap();
+        let dest = dir.file("payload.bin");
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/missing", server.base_url);
+
+        let err = client
+            .download(Params::get(&url), &dest, &options(), |_| {})
+            .await
+            .unwrap_err();
+        match err {
+            HTTPErr::RequestFailed(e) => assert_eq!(e.status, reqwest::StatusCode::NOT_FOUND),
+            e => panic!("expected RequestFailed, got {e:?}"),
+        }
+        assert!(!dest.exists());
+    }
+}
+
+pub mod resume {
+    use super::*;
+
+    #[tokio::test]
+    async fn resumes_after_connection_loss() {
+        let content = payload(64 * 1024);
+        let (server, shared) = run(Payload {
+            content: content.clone(),
+            drop_after: vec![10_000, 20_000],
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_resumes")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+        let options = Options {
+            expected: Some(Digest::compute(Algorithm::Sha256, &content)),
+            ..options()
+        };
+
+        let downloaded = client
+            .download(Params::get(&url), &dest, &options, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(downloaded.resumes, 2);
+        assert_eq!(dest.read_bytes().await.unwrap(), content);
+
+        let requests = shared.lock().unwrap().requests.clone();
+        assert_eq!(
+            requests,
+            vec![
+                (None, None),
+                (Some("bytes=10000-".to_string()), Some(ETAG.to_string())),
+                (Some("bytes=30000-".to_string()), Some(ETAG.to_string())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_resumes() {
+        let (server, _) = run(Payload {
+            content: payload(64 * 1024),
+            drop_after: vec![1000; 10],
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_gives_up")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+        let options = Options {
+            max_resumes: 2,
+            ..options()
+        };
+
+        let err = client
+            .download(Params::get(&url), &dest, &options, |_| {})
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HTTPErr::ReqwestErr(_)));
+        assert!(!dest.exists());
+        // the bytes received are kept for the next download
+        let partial = download::partial_file(&dest).unwrap();
+        assert_eq!(partial.size().await.unwrap(), 3000);
+    }
+
+    #[tokio::test]
+    async fn resumes_partial_file_from_previous_run() {
+        let content = payload(8192);
+        let (server, shared) = run(Payload {
+            content: content.clone(),
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_prev_run")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        download::partial_file(&dest)
+            .unwrap()
+            .write_bytes(&content[..5000], WriteOptions::default())
+            .await
+            .unwrap();
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+        let options = Options {
+            expected: Some(Digest::compute(Algorithm::Blake3, &content)),
+            ..options()
+        };
+
+        let mut progress = Vec::new();
+        let downloaded = client
+            .download(Params::get(&url), &dest, &options, |p| progress.push(p))
+            .await
+            .unwrap();
+        assert_eq!(downloaded.bytes, 8192);
+        assert_eq!(downloaded.resumes, 0);
+        assert_eq!(dest.read_bytes().await.unwrap(), content);
+        assert_eq!(
+            shared.lock().unwrap().requests,
+            vec![(Some("bytes=5000-".to_string()), None)]
+        );
+        assert!(progress.iter().all(|p| p.received > 5000));
+        assert_eq!(progress.last().unwrap().total, Some(8192));
+    }
+
+    #[tokio::test]
+    async fn restarts_when_ranges_are_ignored() {
+        let content = payload(8192);
+        let (server, _) = run(Payload {
+            content: content.clone(),
+            ignore_ranges: true,
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_no_ranges")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        download::partial_file(&dest)
+            .unwrap()
+            .write_bytes(b"stale", WriteOptions::default())
+            .await
+            .unwrap();
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+        let options = Options {
+            expected: Some(Digest::compute(Algorithm::Sha256, &content)),
+            ..options()
+        };
+
+        let downloaded = client
+            .download(Params::get(&url), &dest, &options, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(downloaded.bytes, 8192);
+        assert_eq!(dest.read_bytes().await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn restarts_when_partial_file_is_too_long() {
+        let content = payload(100);
+        let (server, shared) = run(Payload {
+            content: content.clone(),
+            ..Default::default()
+        })
+        .await;
+        let dir = filesys::Dir::create_temp_dir("download_too_long")
+            .await
+            .unwrap();
+        let dest = dir.file("payload.bin");
+        download::partial_file(&dest)
+            .unwrap()
+            .write_bytes(&payload(200), WriteOptions::default())
+            .await
+            .unwrap();
+        let client = http::Client::new(&server.base_url).unwrap();
+        let url = format!("{}/payload", server.base_url);
+
+        client
+            .download(Params::get(&url), &dest, &options(), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(dest.read_bytes().await.unwrap(), content);
+        assert_eq!(
+            shared.lock().unwrap().requests,
+            vec![(Some("bytes=200-".to_string()), None), (None, None)]
+        );
+    }
+}
@@ -1,9 +1,15 @@
+pub mod agent_updates;
 pub mod client;
+pub mod conditional;
 pub mod config_instances;
+pub mod config_schemas;
 pub mod deployments;
 pub mod devices;
+pub mod download;
 pub mod errors;
+pub mod priority;
 pub mod query;
+pub mod record;
 pub mod request;
 pub mod response;
 pub mod retry;
@@ -0,0 +1,205 @@
+// standard crates
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::http::priority::{Options, Scheduler};
+use miru_agent::http::request::Params;
+use miru_agent::http::{Client, ClientI, Priority};
+
+// external crates
+use axum::routing::get;
+use axum::Router;
+use tokio::sync::mpsc;
+
+fn scheduler(max_concurrent: usize, starvation_timeout: Duration) -> Arc<Scheduler> {
+    Arc::new(Scheduler::new(Options {
+        max_concurrent,
+        starvation_timeout,
+    }))
+}
+
+// spawns a request which records its priority once admitted and then releases its
+// slot
+fn spawn_request(
+    scheduler: &Arc<Scheduler>,
+    priority: Priority,
+    admitted: &mpsc::UnboundedSender<Priority>,
+) {
+    let scheduler = scheduler.clone();
+    let admitted = admitted.clone();
+    tokio::spawn(async move {
+        let _permit = scheduler.acquire(priority).await;
+        admitted.send(priority).unwrap();
+    });
+}
+
+async fn await_waiting(scheduler: &Scheduler, priority: Priority, n: usize) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while scheduler.num_waiting(priority) < n {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+pub mod acquire {
+    use super::*;
+
+    #[tokio::test]
+    async fn admits_immediately_below_limit() {
+        let scheduler = scheduler(2, Duration::from_secs(30));
+        let first = scheduler.acquire(Priority::Low).await;
+        let second = scheduler.acquire(Priority::Normal).await;
+        assert_eq!(scheduler.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(scheduler.in_flight(), 1);
+        drop(second);
+        assert_eq!(scheduler.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn zero_max_concurrent_is_clamped() {
+        let scheduler = scheduler(0, Duration::from_secs(30));
+        assert_eq!(scheduler.options().max_concurrent, 1);
+        let _permit = scheduler.acquire(Priority::Normal).await;
+        assert_eq!(scheduler.in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn higher_classes_are_admitted_first() {
+        let scheduler = scheduler(1, Duration::from_secs(30));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let permit = scheduler.acquire(Priority::Normal).await;
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            spawn_request(&scheduler, priority, &tx);
+            await_waiting(&scheduler, priority, 1).await;
+        }
+        spawn_request(&scheduler, Priority::Critical, &tx);
+        await_waiting(&scheduler, Priority::Critical, 1).await;
+        drop(permit);
+
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            order.push(rx.recv().await.unwrap());
+        }
+        assert_eq!(
+            order,
+            vec![
+                Priority::Critical,
+                Priority::High,
+                Priority::Normal,
+                Priority::Low
+            ]
+        );
+        assert_eq!(scheduler.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn same_class_is_first_come_first_served() {
+        let scheduler = scheduler(1, Duration::from_secs(30));
+        let (order_tx, mut order_rx) = mpsc::unbounded_channel();
+
+        let permit = scheduler.acquire(Priority::Normal).await;
+        for i in 0..3 {
+            let request_scheduler = scheduler.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _permit = request_scheduler.acquire(Priority::Normal).await;
+                order_tx.send(i).unwrap();
+            });
+            await_waiting(&scheduler, Priority::Normal, i + 1).await;
+        }
+        drop(permit);
+
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            order.push(order_rx.recv().await.unwrap());
+        }
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn starved_requests_are_admitted_ahead_of_higher_classes() {
+        let scheduler = scheduler(1, Duration::ZERO);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let permit = scheduler.acquire(Priority::Normal).await;
+        spawn_request(&scheduler, Priority::Low, &tx);
+        await_waiting(&scheduler, Priority::Low, 1).await;
+        spawn_request(&scheduler, Priority::Critical, &tx);
+        await_waiting(&scheduler, Priority::Critical, 1).await;
+        drop(permit);
+
+        assert_eq!(rx.recv().await.unwrap(), Priority::Low);
+        assert_eq!(rx.recv().await.unwrap(), Priority::Critical);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_do_not_leak_slots() {
+        let scheduler = scheduler(1, Duration::from_secs(30));
+
+        let permit = scheduler.acquire(Priority::Normal).await;
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(10), scheduler.acquire(Priority::High))
+                .await;
+        assert!(cancelled.is_err());
+        drop(permit);
+
+        assert_eq!(scheduler.in_flight(), 0);
+        let _permit =
+            tokio::time::timeout(Duration::from_secs(5), scheduler.acquire(Priority::Normal))
+                .await
+                .unwrap();
+        assert_eq!(scheduler.in_flight(), 1);
+    }
+}
+
+pub mod client {
+    use super::*;
+
+    #[test]
+    fn default_scheduling() {
+        let client = Client::new("http://localhost").unwrap();
+        assert_eq!(client.scheduler().options(), Options::default());
+    }
+
+    #[test]
+    fn with_scheduling() {
+        let options = Options {
+            max_concurrent: 1,
+            starvation_timeout: Duration::from_secs(3),
+        };
+        let client = Client::new("http://localhost")
+            .unwrap()
+            .with_scheduling(options);
+        assert_eq!(client.scheduler().options(), options);
+    }
+
+    #[tokio::test]
+    async fn long_polls_bypass_the_scheduler() {
+        let server = mock::run_server(Router::new().route("/ok", get(mock::ok))).await;
+        let client = Client::new(&server.base_url)
+            .unwrap()
+            .with_scheduling(Options {
+                max_concurrent: 1,
+                starvation_timeout: Duration::from_secs(60),
+            });
+        let _permit = client.scheduler().acquire(Priority::Critical).await;
+
+        let url = format!("{}/ok", server.base_url);
+        let (text, _) = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.execute(Params::get(&url).as_long_poll()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(text, "ok");
+        assert_eq!(client.scheduler().in_flight(), 1);
+    }
+}
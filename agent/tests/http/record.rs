@@ -0,0 +1,329 @@
+// standard crates
+use std::sync::Arc;
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::diagnostics::{Redactor, REDACTED};
+use miru_agent::errors::Error;
+use miru_agent::filesys::{self, AppendOptions, FileSysErr, WriteOptions};
+use miru_agent::http::record::{Capture, Exchange, Outcome, Recorder, Replayer, Usage};
+use miru_agent::http::request::Params;
+use miru_agent::http::{self, ClientI, HTTPErr};
+use miru_agent::version;
+
+// external crates
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::json;
+
+async fn issue_token() -> Json<serde_json::Value> {
+    Json(json!({"token": "eyJhbGciOi.eyJzdWIiOi.c2lnbmF0dXJl", "expires_at": "2030-01-01"}))
+}
+
+fn router() -> Router {
+    Router::new()
+        .route("/json", get(mock::json_response))
+        .route("/token", post(issue_token))
+        .route("/unauthorized", get(mock::unauthorized))
+}
+
+async fn recording_client(name: &str) -> (http::Client, Arc<Recorder>, filesys::Dir, mock::Server) {
+    let server = mock::run_server(router()).await;
+    let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+    let recorder = Arc::new(Recorder::new(dir.file("capture.json"), Redactor::default()));
+    let client = http::Client::new(&server.base_url)
+        .unwrap()
+        .with_recorder(recorder.clone());
+    (client, recorder, dir, server)
+}
+
+async fn captured(recorder: &Recorder) -> Capture {
+    Capture::read(recorder.file()).await.unwrap()
+}
+
+pub mod recorder {
+    use super::*;
+
+    #[tokio::test]
+    async fn captures_paths_relative_to_the_backend() {
+        let (client, recorder, _dir, server) = recording_client("record_paths").await;
+        let url = format!("{}/json", server.base_url);
+        let (text, _) = client.execute(Params::get(&url)).await.unwrap();
+
+        let capture = captured(&recorder).await;
+        assert_eq!(
+            capture.exchanges,
+            vec![Exchange {
+                method: "GET".to_string(),
+                path: "/json".to_string(),
+                request_body: None,
+                outcome: Outcome::Success {
+                    body: serde_json::from_str(&text).unwrap(),
+                },
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn redacts_credentials() {
+        let (client, recorder, _dir, server) = recording_client("record_redacts").await;
+        let url = format!("{}/token", server.base_url);
+        let body = json!({"device": {"private_key": "secret"}}).to_string();
+        client.execute(Params::post(&url, body)).await.unwrap();
+
+        let capture = captured(&recorder).await;
+        let exchange = &capture.exchanges[0];
+        assert_eq!(
+            exchange.request_body,
+            Some(json!({"device": {"private_key": REDACTED}}))
+        );
+        assert_eq!(
+            exchange.outcome,
+            Outcome::Success {
+                body: json!({"token": REDACTED, "expires_at": "2030-01-01"}),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn captures_failures() {
+        let (client, recorder, _dir, server) = recording_client("record_failures").await;
+        let url = format!("{}/unauthorized", server.base_url);
+        client.execute(Params::get(&url)).await.unwrap_err();
+
+        let capture = captured(&recorder).await;
+        match &capture.exchanges[0].outcome {
+            Outcome::Failed { status, error } => {
+                assert_eq!(*status, 401);
+                assert!(error.is_some());
+            }
+            outcome => panic!("expected a failure, got {outcome:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn captures_unreachable_backends() {
+        let dir = filesys::Dir::create_temp_dir("record_unreachable")
+            .await
+            .unwrap();
+        let recorder = Arc::new(Recorder::new(dir.file("capture.json"), Redactor::default()));
+        let client = http::Client::new("http://127.0.0.1:1")
+            .unwrap()
+            .with_recorder(recorder.clone());
+        client
+            .execute(Params::get("http://127.0.0.1:1/json"))
+            .await
+            .unwrap_err();
+
+        let capture = captured(&recorder).await;
+        assert!(matches!(
+            capture.exchanges[0].outcome,
+            Outcome::Unreachable {
+                is_network_conn_err: true,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn appends_one_exchange_per_line() {
+        let (client, recorder, _dir, server) = recording_client("record_file").await;
+        let url = format!("{}/json", server.base_url);
+        client.execute(Params::get(&url)).await.unwrap();
+        client.execute(Params::get(&url)).await.unwrap();
+
+        let content = recorder.file().read_string().await.unwrap();
+        // the header and then each exchange
+        assert_eq!(content.lines().count(), 3);
+        let capture = captured(&recorder).await;
+        assert_eq!(capture.agent_version, version::VERSION);
+        assert_eq!(capture.exchanges.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn replaces_an_earlier_capture() {
+        let (client, recorder, _dir, server) = recording_client("record_replaces").await;
+        recorder
+            .file()
+            .write_string("stale\n", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let url = format!("{}/json", server.base_url);
+        client.execute(Params::get(&url)).await.unwrap();
+
+        assert_eq!(captured(&recorder).await.exchanges.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_size_limit() {
+        let server = mock::run_server(router()).await;
+        let dir = filesys::Dir::create_temp_dir("record_limit").await.unwrap();
+        let recorder = Arc::new(
+            Recorder::new(dir.file("capture.json"), Redactor::default()).with_max_bytes(1024),
+        );
+        let client = http::Client::new(&server.base_url)
+            .unwrap()
+            .with_recorder(recorder.clone());
+        let url = format!("{}/json", server.base_url);
+        for _ in 0..100 {
+            client.execute(Params::get(&url)).await.unwrap();
+        }
+
+        assert!(recorder.file().size().await.unwrap() <= 1024);
+        let recorded = captured(&recorder).await.exchanges.len();
+        assert!(recorded > 0 && recorded < 100);
+    }
+
+    #[tokio::test]
+    async fn skips_a_truncated_last_exchange() {
+        let (client, recorder, _dir, server) = recording_client("record_truncated").await;
+        let url = format!("{}/json", server.base_url);
+        client.execute(Params::get(&url)).await.unwrap();
+        recorder
+            .file()
+            .append_bytes(b"{\"method\": \"GE", AppendOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(captured(&recorder).await.exchanges.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_capture_without_a_header() {
+        let dir = filesys::Dir::create_temp_dir("record_no_header")
+            .await
+            .unwrap();
+        let file = dir.file("capture.json");
+        file.write_string("", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            Capture::read(&file).await,
+            Err(FileSysErr::ParseJSONErr(_))
+        ));
+    }
+}
+
+pub mod replayer {
+    use super::*;
+
+    const BASE_URL: &str = "http://replay.invalid";
+
+    fn exchange(method: &str, path: &str, outcome: Outcome) -> Exchange {
+        Exchange {
+            method: method.to_string(),
+            path: path.to_string(),
+            request_body: None,
+            outcome,
+        }
+    }
+
+    fn capture(exchanges: Vec<Exchange>) -> Capture {
+        Capture {
+            exchanges,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_exchanges_in_order() {
+        let replayer = Replayer::new(
+            BASE_URL,
+            capture(vec![
+                exchange("GET", "/a", Outcome::Success { body: json!(1) }),
+                exchange("GET", "/a", Outcome::Success { body: json!(2) }),
+            ]),
+        );
+        let url = format!("{BASE_URL}/a");
+        let (first, _) = replayer.execute(Params::get(&url)).await.unwrap();
+        let (second, _) = replayer.execute(Params::get(&url)).await.unwrap();
+        assert_eq!(first, "1");
+        assert_eq!(second, "2");
+        assert_eq!(
+            replayer.usage(),
+            Usage {
+                served: 2,
+                unused: 0,
+                misses: vec![],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn matches_the_method() {
+        let replayer = Replayer::new(
+            BASE_URL,
+            capture(vec![exchange(
+                "POST",
+                "/a",
+                Outcome::Success { body: json!({}) },
+            )]),
+        );
+        let url = format!("{BASE_URL}/a");
+        let err = replayer.execute(Params::get(&url)).await.unwrap_err();
+        assert!(matches!(err, HTTPErr::ReplayErr(_)));
+        assert!(!err.is_network_conn_err());
+        assert_eq!(
+            replayer.usage(),
+            Usage {
+                served: 0,
+                unused: 1,
+                misses: vec!["GET /a".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_failures() {
+        let replayer = Replayer::new(
+            BASE_URL,
+            capture(vec![exchange(
+                "GET",
+                "/a",
+                Outcome::Failed {
+                    status: 404,
+                    error: None,
+                },
+            )]),
+        );
+        let url = format!("{BASE_URL}/a");
+        match replayer.execute(Params::get(&url)).await.unwrap_err() {
+            HTTPErr::RequestFailed(e) => assert_eq!(e.status.as_u16(), 404),
+            e => panic!("expected a request failure, got {e:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_network_errors() {
+        let replayer = Replayer::new(
+            BASE_URL,
+            capture(vec![exchange(
+                "GET",
+                "/a",
+                Outcome::Unreachable {
+                    error: "connection refused".to_string(),
+                    is_network_conn_err: true,
+                },
+            )]),
+        );
+        let url = format!("{BASE_URL}/a");
+        let err = replayer.execute(Params::get(&url)).await.unwrap_err();
+        assert!(err.is_network_conn_err());
+    }
+
+    #[tokio::test]
+    async fn round_trips_a_recording() {
+        let (client, recorder, _dir, server) = recording_client("replay_round_trip").await;
+        let url = format!("{}/json", server.base_url);
+        let (recorded, _) = client.execute(Params::get(&url)).await.unwrap();
+
+        let replayer = Replayer::new(BASE_URL, captured(&recorder).await);
+        let url = format!("{BASE_URL}/json");
+        let (replayed, _) = replayer.execute(Params::get(&url)).await.unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&replayed).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&recorded).unwrap()
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::time::Duration;
 
 // internal crates
 use miru_agent::http::request::{self, Headers, Params};
-use miru_agent::http::HTTPErr;
+use miru_agent::http::{HTTPErr, Priority};
 
 pub mod params {
     use super::*;
@@ -102,6 +102,10 @@ pub mod params {
                 body: None,
                 timeout: Duration::from_secs(10),
                 token: None,
+                priority: Priority::Normal,
+                headers: Vec::new(),
+                long_poll: false,
+                retry: None,
             };
             assert_eq!(actual, expected);
         }
@@ -116,6 +120,10 @@ pub mod params {
                 body: Some("body".into()),
                 timeout: Duration::from_secs(10),
                 token: None,
+                priority: Priority::Normal,
+                headers: Vec::new(),
+                long_poll: false,
+                retry: None,
             };
             assert_eq!(actual, expected);
         }
@@ -130,6 +138,10 @@ pub mod params {
                 body: Some("data".into()),
                 timeout: Duration::from_secs(10),
                 token: None,
+                priority: Priority::Normal,
+                headers: Vec::new(),
+                long_poll: false,
+                retry: None,
             };
             assert_eq!(actual, expected);
         }
@@ -144,6 +156,14 @@ pub mod params {
             assert_eq!(params.token, Some("my-token"));
         }
 
+        #[test]
+        fn debug_redacts_token() {
+            let params = Params::get("https://example.com").with_token("my-token");
+            let debug_output = format!("{params:?}");
+            assert!(debug_output.contains("[REDACTED]"));
+            assert!(!debug_output.contains("my-token"));
+        }
+
         #[test]
         fn with_query_sets_pairs() {
             let params = Params::get("https://example.com").with_query(
@@ -159,6 +179,29 @@ pub mod params {
             let params = Params::get("https://example.com").with_timeout(Duration::from_secs(30));
             assert_eq!(params.timeout, Duration::from_secs(30));
         }
+
+        #[test]
+        fn with_priority_sets_priority() {
+            let params = Params::get("https://example.com").with_priority(Priority::Critical);
+            assert_eq!(params.priority, Priority::Critical);
+        }
+
+        #[test]
+        fn with_header_appends_header() {
+            let params = Params::get("https://example.com")
+                .with_header("Miru-Signature", "a".into())
+                .with_header("Miru-Other", "b".into());
+            assert_eq!(
+                params.headers,
+                vec![("Miru-Signature", "a".into()), ("Miru-Other", "b".into())]
+            );
+        }
+
+        #[test]
+        fn as_long_poll_sets_flag() {
+            assert!(!Params::get("https://example.com").long_poll);
+            assert!(Params::get("https://example.com").as_long_poll().long_poll);
+        }
     }
 }
 
@@ -188,7 +231,9 @@ pub mod headers {
         assert!(map.contains_key("Miru-Agent-Arch"));
         assert!(map.contains_key("Miru-Agent-Language"));
         assert!(map.contains_key("Miru-Agent-OS"));
-        assert_eq!(map.len(), 6);
+        assert!(map.contains_key("Miru-Agent-Capabilities"));
+        assert!(map.contains_key("User-Agent"));
+        assert_eq!(map.len(), 8);
     }
 
     #[test]
@@ -210,6 +255,63 @@ pub mod headers {
         let agent_version = map.get("Miru-Agent-Version").unwrap().to_str().unwrap();
         assert_eq!(agent_version, &headers.agent_version);
     }
+
+    #[test]
+    fn user_agent_identifies_build_and_host() {
+        let headers = Headers::default();
+        let map = headers.to_map().unwrap();
+        let user_agent = map.get("User-Agent").unwrap().to_str().unwrap();
+        assert_eq!(user_agent, request::user_agent());
+        assert!(user_agent.starts_with(&format!("miru-agent/{} (", headers.agent_version)));
+        assert!(user_agent.contains(&format!("; {}; ", headers.arch)));
+        assert!(user_agent.contains("; commit "));
+    }
+
+    #[test]
+    fn fleet_headers_are_added() {
+        let headers = Headers {
+            fleet: vec![
+                ("X-Fleet".to_string(), "warehouse-3".to_string()),
+                ("x-site".to_string(), "berlin".to_string()),
+            ],
+            ..Default::default()
+        };
+        let map = headers.to_map().unwrap();
+        assert_eq!(map.get("X-Fleet").unwrap(), "warehouse-3");
+        assert_eq!(map.get("X-Site").unwrap(), "berlin");
+        assert_eq!(map.len(), 10);
+    }
+
+    #[test]
+    fn fleet_headers_dont_replace_the_agents() {
+        let headers = Headers {
+            fleet: vec![("User-Agent".to_string(), "curl/8.0".to_string())],
+            ..Default::default()
+        };
+        let map = headers.to_map().unwrap();
+        assert_eq!(map.get("User-Agent").unwrap(), &request::user_agent());
+    }
+
+    #[test]
+    fn invalid_fleet_header() {
+        let headers = Headers {
+            fleet: vec![("Bad Name".to_string(), "value".to_string())],
+            ..Default::default()
+        };
+        assert!(matches!(
+            headers.to_map().unwrap_err(),
+            HTTPErr::InvalidEgressErr(_)
+        ));
+
+        let headers = Headers {
+            fleet: vec![("X-Fleet".to_string(), "line\nbreak".to_string())],
+            ..Default::default()
+        };
+        assert!(matches!(
+            headers.to_map().unwrap_err(),
+            HTTPErr::InvalidHeaderValueErr(_)
+        ));
+    }
 }
 
 pub mod marshal_json {
@@ -277,6 +379,8 @@ pub mod build {
         let req = request::build(&client, &headers, params).unwrap();
         let auth = req.reqwest.headers().get("authorization").unwrap();
         assert_eq!(auth, "Bearer tok123");
+        assert!(auth.is_sensitive());
+        assert!(!format!("{:?}", req.reqwest).contains("tok123"));
     }
 
     #[test]
@@ -303,4 +407,25 @@ pub mod build {
         assert!(h.contains_key("Miru-Agent-Language"));
         assert!(h.contains_key("Miru-Agent-OS"));
     }
+
+    #[test]
+    fn extra_headers_set_on_request() {
+        let client = make_client();
+        let headers = Headers::default();
+        let params = Params::post("https://example.com/test", "hello".into())
+            .with_header("Miru-Signature", "t=1,v1=abc:def".into());
+        let req = request::build(&client, &headers, params).unwrap();
+        let h = req.reqwest.headers();
+        assert_eq!(h.get("Miru-Signature").unwrap(), "t=1,v1=abc:def");
+        assert!(h.contains_key("Miru-Version"));
+    }
+
+    #[test]
+    fn invalid_extra_header_value_errors() {
+        let client = make_client();
+        let headers = Headers::default();
+        let params = Params::get("https://example.com/test")
+            .with_header("Miru-Signature", "line\nbreak".into());
+        assert!(request::build(&client, &headers, params).is_err());
+    }
 }
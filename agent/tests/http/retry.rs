@@ -1,10 +1,14 @@
 // standard crates
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 // internal crates
+use crate::mocks::http_client::MockClient;
 use miru_agent::errors::Error;
 use miru_agent::http::errors::MockErr;
-use miru_agent::http::{with_retry, HTTPErr};
+use miru_agent::http::request::Params;
+use miru_agent::http::retry::{Budget, BudgetOptions};
+use miru_agent::http::{self, with_retry, ClientI, HTTPErr, RetryPolicy};
 
 fn network_err() -> HTTPErr {
     HTTPErr::MockErr(MockErr {
@@ -21,7 +25,7 @@ fn app_err() -> HTTPErr {
 #[tokio::test]
 async fn success_on_first_attempt() {
     let calls = AtomicUsize::new(0);
-    let result: Result<&str, HTTPErr> = with_retry(|| {
+    let result: Result<&str, HTTPErr> = with_retry(&MockClient::default(), || {
         calls.fetch_add(1, Ordering::SeqCst);
         async { Ok("ok") }
     })
@@ -34,7 +38,7 @@ async fn success_on_first_attempt() {
 #[tokio::test]
 async fn retries_on_network_error_then_succeeds() {
     let calls = AtomicUsize::new(0);
-    let result: Result<&str, HTTPErr> = with_retry(|| {
+    let result: Result<&str, HTTPErr> = with_retry(&MockClient::default(), || {
         let n = calls.fetch_add(1, Ordering::SeqCst);
         async move {
             if n < 2 {
@@ -53,7 +57,7 @@ async fn retries_on_network_error_then_succeeds() {
 #[tokio::test]
 async fn no_retry_on_app_error() {
     let calls = AtomicUsize::new(0);
-    let result: Result<&str, HTTPErr> = with_retry(|| {
+    let result: Result<&str, HTTPErr> = with_retry(&MockClient::default(), || {
         calls.fetch_add(1, Ordering::SeqCst);
         async { Err(app_err()) }
     })
@@ -71,7 +75,7 @@ async fn no_retry_on_app_error() {
 #[tokio::test]
 async fn exhausts_retries_on_persistent_network_error() {
     let calls = AtomicUsize::new(0);
-    let result: Result<&str, HTTPErr> = with_retry(|| {
+    let result: Result<&str, HTTPErr> = with_retry(&MockClient::default(), || {
         calls.fetch_add(1, Ordering::SeqCst);
         async { Err(network_err()) }
     })
@@ -89,7 +93,7 @@ async fn exhausts_retries_on_persistent_network_error() {
 #[tokio::test]
 async fn network_error_then_app_error_stops_immediately() {
     let calls = AtomicUsize::new(0);
-    let result: Result<&str, HTTPErr> = with_retry(|| {
+    let result: Result<&str, HTTPErr> = with_retry(&MockClient::default(), || {
         let n = calls.fetch_add(1, Ordering::SeqCst);
         async move {
             if n == 0 {
@@ -113,7 +117,7 @@ async fn network_error_then_app_error_stops_immediately() {
 #[tokio::test]
 async fn recovers_on_last_attempt() {
     let calls = AtomicUsize::new(0);
-    let result: Result<&str, HTTPErr> = with_retry(|| {
+    let result: Result<&str, HTTPErr> = with_retry(&MockClient::default(), || {
         let n = calls.fetch_add(1, Ordering::SeqCst);
         async move {
             if n < 2 {
@@ -132,3 +136,204 @@ async fn recovers_on_last_attempt() {
         "should succeed on attempt 3"
     );
 }
+
+pub mod policy {
+    use super::*;
+
+    #[test]
+    fn default_retries_idempotent_methods_only() {
+        let policy = RetryPolicy::default();
+        assert!(policy.allows(&reqwest::Method::GET));
+        assert!(policy.allows(&reqwest::Method::PUT));
+        assert!(policy.allows(&reqwest::Method::DELETE));
+        assert!(!policy.allows(&reqwest::Method::POST));
+        assert!(!policy.allows(&reqwest::Method::PATCH));
+    }
+
+    #[test]
+    fn retry_non_idempotent_allows_every_method() {
+        let policy = RetryPolicy::default().retry_non_idempotent();
+        assert!(policy.allows(&reqwest::Method::POST));
+        assert!(policy.allows(&reqwest::Method::PATCH));
+    }
+
+    #[test]
+    fn none_allows_no_retries() {
+        assert!(!RetryPolicy::NONE.allows(&reqwest::Method::GET));
+        assert!(!RetryPolicy::default()
+            .with_max_attempts(1)
+            .allows(&reqwest::Method::GET));
+    }
+
+    #[test]
+    fn delay_backs_off() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(10), Duration::from_secs(10));
+    }
+}
+
+pub mod budget {
+    use super::*;
+
+    #[test]
+    fn starts_full() {
+        let budget = Budget::default();
+        assert_eq!(budget.tokens(), 100.0);
+    }
+
+    #[test]
+    fn stops_retries_below_half() {
+        let budget = Budget::new(BudgetOptions {
+            max_tokens: 10,
+            token_ratio: 1.0,
+        });
+        for _ in 0..4 {
+            assert!(budget.record_failure());
+        }
+        // 5 tokens left is no longer more than half
+        assert!(!budget.record_failure());
+        assert_eq!(budget.tokens(), 5.0);
+    }
+
+    #[test]
+    fn successes_refill_up_to_max() {
+        let budget = Budget::new(BudgetOptions {
+            max_tokens: 10,
+            token_ratio: 0.5,
+        });
+        for _ in 0..6 {
+            budget.record_failure();
+        }
+        assert_eq!(budget.tokens(), 4.0);
+        budget.record_success();
+        budget.record_success();
+        assert_eq!(budget.tokens(), 5.0);
+        for _ in 0..20 {
+            budget.record_success();
+        }
+        assert_eq!(budget.tokens(), 10.0);
+    }
+
+    #[test]
+    fn never_goes_negative() {
+        let budget = Budget::new(BudgetOptions {
+            max_tokens: 2,
+            token_ratio: 0.1,
+        });
+        for _ in 0..5 {
+            budget.record_failure();
+        }
+        assert_eq!(budget.tokens(), 0.0);
+    }
+}
+
+pub mod client {
+    use super::*;
+
+    const UNREACHABLE: &str = "http://127.0.0.1:1";
+
+    fn client() -> http::Client {
+        http::Client::new(UNREACHABLE).unwrap()
+    }
+
+    // each failed attempt takes a token from the budget, so the tokens spent count the
+    // attempts made
+    fn attempts(client: &http::Client) -> f64 {
+        100.0 - client.retry_budget().tokens()
+    }
+
+    #[test]
+    fn retries_requests() {
+        assert!(client().retries_requests());
+        assert!(!MockClient::default().retries_requests());
+    }
+
+    #[tokio::test]
+    async fn retries_get_on_network_error() {
+        let client = client();
+        let url = format!("{UNREACHABLE}/nope");
+        let err = client.execute(Params::get(&url)).await.unwrap_err();
+        assert!(err.is_network_conn_err());
+        assert_eq!(attempts(&client), 3.0);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_post() {
+        let client = client();
+        let url = format!("{UNREACHABLE}/nope");
+        let err = client
+            .execute(Params::post(&url, "{}".to_string()))
+            .await
+            .unwrap_err();
+        assert!(err.is_network_conn_err());
+        assert_eq!(attempts(&client), 1.0);
+    }
+
+    #[tokio::test]
+    async fn retries_post_when_the_request_opts_in() {
+        let client = client();
+        let url = format!("{UNREACHABLE}/nope");
+        let params = Params::post(&url, "{}".to_string())
+            .with_retry_policy(RetryPolicy::default().retry_non_idempotent());
+        client.execute(params).await.unwrap_err();
+        assert_eq!(attempts(&client), 3.0);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_long_polls() {
+        let client = client();
+        let url = format!("{UNREACHABLE}/nope");
+        client
+            .execute(Params::get(&url).as_long_poll())
+            .await
+            .unwrap_err();
+        assert_eq!(attempts(&client), 1.0);
+    }
+
+    #[tokio::test]
+    async fn uses_the_client_policy() {
+        let client = client().with_retries(
+            RetryPolicy::default().with_max_attempts(5),
+            BudgetOptions::default(),
+        );
+        let url = format!("{UNREACHABLE}/nope");
+        client.execute(Params::get(&url)).await.unwrap_err();
+        assert_eq!(attempts(&client), 5.0);
+    }
+
+    #[tokio::test]
+    async fn exhausted_budget_stops_retries() {
+        let client = client().with_retries(
+            RetryPolicy::default(),
+            BudgetOptions {
+                max_tokens: 4,
+                token_ratio: 0.1,
+            },
+        );
+        let url = format!("{UNREACHABLE}/nope");
+        // 4 -> 3 -> 2, the second failure leaves the budget at half
+        client.execute(Params::get(&url)).await.unwrap_err();
+        assert_eq!(client.retry_budget().tokens(), 2.0);
+        // no retries until requests succeed again
+        client.execute(Params::get(&url)).await.unwrap_err();
+        assert_eq!(client.retry_budget().tokens(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn with_retry_doesnt_retry_again() {
+        let client = client();
+        let url = format!("{UNREACHABLE}/nope");
+        let calls = AtomicUsize::new(0);
+        let result = with_retry(&client, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            client.execute(Params::get(&url))
+        })
+        .await;
+        assert!(result.unwrap_err().is_network_conn_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(attempts(&client), 3.0);
+    }
+}
@@ -0,0 +1,659 @@
+// standard crates
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::http_client::{Call, MockClient};
+use miru_agent::diagnostics::crash;
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::http::errors::{HTTPErr, MockErr, RequestFailed};
+use miru_agent::http::request::Params as HttpParams;
+use miru_agent::journal::{Entry, Journal, Kind, Options, Request, Retention};
+use miru_agent::logs::RecentLines;
+use miru_agent::storage::Layout;
+
+// external crates
+use backend_api::models::{
+    Deployment as BackendDeployment, DeploymentActivityStatus, UpdateDeploymentRequest,
+};
+use chrono::{TimeDelta, Utc};
+
+fn status(deployment_id: &str, activity: DeploymentActivityStatus) -> Request {
+    Request::DeploymentStatus {
+        deployment_id: deployment_id.to_string(),
+        updates: UpdateDeploymentRequest {
+            activity_status: Some(activity),
+            ..Default::default()
+        },
+        metrics: None,
+    }
+}
+
+fn request_failed(status: reqwest::StatusCode) -> HTTPErr {
+    HTTPErr::RequestFailed(RequestFailed {
+        request: HttpParams::get("http://test/deployments/dpl_1")
+            .meta()
+            .unwrap(),
+        status,
+        error: None,
+        trace: miru_agent::trace!(),
+    })
+}
+
+fn deployment_ids(entries: &[Entry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|e| match &e.request {
+            Request::DeploymentStatus { deployment_id, .. } => deployment_id.clone(),
+            request => panic!("unexpected request {request:?}"),
+        })
+        .collect()
+}
+
+async fn journal(dir: &filesys::Dir, options: Options) -> Journal {
+    Journal::init(dir.file("journal.json"), options)
+        .await
+        .unwrap()
+}
+
+pub mod enqueue {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_across_restarts() {
+        let dir = filesys::Dir::create_temp_dir("journal_persists")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        j.enqueue(status(
+            "dpl_2",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+
+        let reloaded = journal(&dir, Options::default()).await;
+        assert_eq!(reloaded.entries().await, j.entries().await);
+        assert_eq!(
+            deployment_ids(&reloaded.entries().await),
+            vec!["dpl_1", "dpl_2"]
+        );
+
+        // ids keep increasing after a restart
+        reloaded
+            .enqueue(status(
+                "dpl_3",
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ))
+            .await
+            .unwrap();
+        let ids: Vec<u64> = reloaded.entries().await.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn supersedes_queued_status_for_same_deployment() {
+        let dir = filesys::Dir::create_temp_dir("journal_supersedes")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_QUEUED,
+        ))
+        .await
+        .unwrap();
+        j.enqueue(status(
+            "dpl_2",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_QUEUED,
+        ))
+        .await
+        .unwrap();
+        let latest = status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        );
+        j.enqueue(latest.clone()).await.unwrap();
+
+        let entries = j.entries().await;
+        assert_eq!(deployment_ids(&entries), vec!["dpl_1", "dpl_2"]);
+        assert_eq!(entries[0].request, latest);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn identical_status_is_queued_once() {
+        let dir = filesys::Dir::create_temp_dir("journal_identical")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        let queued = status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_QUEUED,
+        );
+        j.enqueue(queued.clone()).await.unwrap();
+        let first = j.entries().await;
+        j.enqueue(queued).await.unwrap();
+
+        // the original entry keeps its id and enqueue time
+        assert_eq!(j.entries().await, first);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn drops_oldest_past_max_entries() {
+        let dir = filesys::Dir::create_temp_dir("journal_max_entries")
+            .await
+            .unwrap();
+        let options = Options {
+            retention: HashMap::from([(
+                Kind::DeploymentStatus,
+                Retention {
+                    max_entries: 2,
+                    ..Default::default()
+                },
+            )]),
+        };
+        let j = journal(&dir, options).await;
+        for id in ["dpl_1", "dpl_2", "dpl_3"] {
+            j.enqueue(status(
+                id,
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ))
+            .await
+            .unwrap();
+        }
+        assert_eq!(deployment_ids(&j.entries().await), vec!["dpl_2", "dpl_3"]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreadable_journal_is_discarded() {
+        let dir = filesys::Dir::create_temp_dir("journal_unreadable")
+            .await
+            .unwrap();
+        let file = dir.file("journal.json");
+        file.write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let j = Journal::init(file, Options::default()).await.unwrap();
+        assert!(j.is_empty().await);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreadable_entries_are_discarded() {
+        let dir = filesys::Dir::create_temp_dir("journal_unreadable_entry")
+            .await
+            .unwrap();
+        let file = dir.file("journal.json");
+        let queued = Entry {
+            id: 1,
+            enqueued_at: Utc::now(),
+            attempts: 0,
+            request: status(
+                "dpl_1",
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ),
+        };
+        let unknown = serde_json::json!({
+            "id": 2,
+            "enqueued_at": Utc::now(),
+            "attempts": 0,
+            "request": {"kind": "from_a_newer_agent"},
+        });
+        let entries = vec![serde_json::to_value(&queued).unwrap(), unknown];
+        file.write_json(&entries, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let j = Journal::init(file, Options::default()).await.unwrap();
+        assert_eq!(j.entries().await, vec![queued]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn crash_reports_are_not_superseded() {
+        let dir = filesys::Dir::create_temp_dir("journal_crash_reports")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        let options = crash::Options {
+            layout: Layout::new(dir.clone()).crash(),
+            recent_lines: RecentLines::new(1),
+            redactor: None,
+            upload: true,
+        };
+        for message in ["first", "second"] {
+            j.enqueue(Request::CrashReport {
+                device_id: "dvc_1".to_string(),
+                report: crash::Report::capture(message, None, &options),
+            })
+            .await
+            .unwrap();
+        }
+
+        let kinds: Vec<_> = j.entries().await.iter().map(|e| e.request.kind()).collect();
+        assert_eq!(kinds, vec![Kind::CrashReport, Kind::CrashReport]);
+        assert_eq!(
+            Options::default().retention(Kind::CrashReport).max_entries,
+            crash::MAX_PENDING
+        );
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod drain {
+    use super::*;
+
+    #[tokio::test]
+    async fn sends_in_order() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_order")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        for id in ["dpl_1", "dpl_2"] {
+            j.enqueue(status(
+                id,
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ))
+            .await
+            .unwrap();
+        }
+        let client = MockClient::default();
+
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.sent, 2);
+        assert!(drained.errors.is_empty());
+        assert!(j.is_empty().await);
+
+        assert_eq!(
+            client.paths_for(Call::UpdateDeployment),
+            vec!["/deployments/dpl_1", "/deployments/dpl_2"]
+        );
+
+        // the drained journal is persisted
+        assert!(journal(&dir, Options::default()).await.is_empty().await);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failure_keeps_request_queued() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_failure")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        for id in ["dpl_1", "dpl_2"] {
+            j.enqueue(status(
+                id,
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ))
+            .await
+            .unwrap();
+        }
+        let client = MockClient::default();
+        let calls = AtomicUsize::new(0);
+        client.set_update_deployment(move || {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(HTTPErr::MockErr(MockErr {
+                    is_network_conn_err: false,
+                }))
+            } else {
+                Ok(BackendDeployment::default())
+            }
+        });
+
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.sent, 1);
+        assert_eq!(drained.errors.len(), 1);
+
+        let entries = journal(&dir, Options::default()).await.entries().await;
+        assert_eq!(deployment_ids(&entries), vec!["dpl_1"]);
+        assert_eq!(entries[0].attempts, 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stops_at_network_error() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_offline")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        for id in ["dpl_1", "dpl_2", "dpl_3"] {
+            j.enqueue(status(
+                id,
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ))
+            .await
+            .unwrap();
+        }
+        let client = MockClient::default();
+        client.set_update_deployment(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.sent, 0);
+        assert_eq!(drained.errors.len(), 1);
+        // only the first request was tried, however often it was retried
+        assert!(client
+            .paths_for(Call::UpdateDeployment)
+            .iter()
+            .all(|path| path == "/deployments/dpl_1"));
+
+        let entries = journal(&dir, Options::default()).await.entries().await;
+        assert_eq!(deployment_ids(&entries), vec!["dpl_1", "dpl_2", "dpl_3"]);
+        // network errors don't count against the attempts
+        let attempts: Vec<_> = entries.iter().map(|e| e.attempts).collect();
+        assert_eq!(attempts, vec![0, 0, 0]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejected_request_is_dropped() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_rejected")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        for id in ["dpl_1", "dpl_2"] {
+            j.enqueue(status(
+                id,
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ))
+            .await
+            .unwrap();
+        }
+        let client = MockClient::default();
+        let calls = AtomicUsize::new(0);
+        client.set_update_deployment(move || {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(request_failed(reqwest::StatusCode::UNPROCESSABLE_ENTITY))
+            } else {
+                Ok(BackendDeployment::default())
+            }
+        });
+
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.sent, 1);
+        assert_eq!(drained.dropped, 1);
+        assert_eq!(drained.errors.len(), 1);
+        assert!(journal(&dir, Options::default()).await.is_empty().await);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rate_limited_request_stays_queued() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_rate_limited")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        let client = MockClient::default();
+        client
+            .set_update_deployment(|| Err(request_failed(reqwest::StatusCode::TOO_MANY_REQUESTS)));
+
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.dropped, 0);
+        assert_eq!(j.entries().await[0].attempts, 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unauthorized_requests_stay_queued() {
+        for status_code in [
+            reqwest::StatusCode::UNAUTHORIZED,
+            reqwest::StatusCode::FORBIDDEN,
+        ] {
+            let dir = filesys::Dir::create_temp_dir("journal_drain_unauthorized")
+                .await
+                .unwrap();
+            let j = journal(&dir, Options::default()).await;
+            for id in ["dpl_1", "dpl_2"] {
+                j.enqueue(status(
+                    id,
+                    DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+                ))
+                .await
+                .unwrap();
+            }
+            let client = MockClient::default();
+            client.set_update_deployment(move || Err(request_failed(status_code)));
+
+            let drained = j.drain(&client, "token").await.unwrap();
+            assert!(drained.unauthorized);
+            assert_eq!(drained.dropped, 0);
+            // the other requests would be refused the same token
+            assert_eq!(client.call_count(Call::UpdateDeployment), 1);
+            let entries = journal(&dir, Options::default()).await.entries().await;
+            assert_eq!(deployment_ids(&entries), vec!["dpl_1", "dpl_2"]);
+            assert!(entries.iter().all(|e| e.attempts == 0));
+            dir.delete().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn server_errors_dont_count_against_max_attempts() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_server_err")
+            .await
+            .unwrap();
+        let options = Options {
+            retention: HashMap::from([(
+                Kind::DeploymentStatus,
+                Retention {
+                    max_attempts: 1,
+                    ..Default::default()
+                },
+            )]),
+        };
+        let j = journal(&dir, options).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        let client = MockClient::default();
+        client.set_update_deployment(|| {
+            Err(request_failed(reqwest::StatusCode::SERVICE_UNAVAILABLE))
+        });
+
+        for _ in 0..3 {
+            let drained = j.drain(&client, "token").await.unwrap();
+            assert_eq!(drained.dropped, 0);
+            assert!(!drained.unauthorized);
+        }
+        assert_eq!(j.entries().await[0].attempts, 0);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn other_client_errors_count_against_max_attempts() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_gone")
+            .await
+            .unwrap();
+        let j = journal(&dir, Options::default()).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        let client = MockClient::default();
+        client.set_update_deployment(|| Err(request_failed(reqwest::StatusCode::GONE)));
+
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.dropped, 0);
+        assert_eq!(j.entries().await[0].attempts, 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn dropped_after_max_attempts() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_max_attempts")
+            .await
+            .unwrap();
+        let options = Options {
+            retention: HashMap::from([(
+                Kind::DeploymentStatus,
+                Retention {
+                    max_attempts: 3,
+                    ..Default::default()
+                },
+            )]),
+        };
+        let j = journal(&dir, options).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        let client = MockClient::default();
+        client.set_update_deployment(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+
+        for attempts in 1..3 {
+            let drained = j.drain(&client, "token").await.unwrap();
+            assert_eq!(drained.dropped, 0);
+            assert_eq!(j.entries().await[0].attempts, attempts);
+        }
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.dropped, 1);
+        assert!(j.is_empty().await);
+        assert_eq!(client.call_count(Call::UpdateDeployment), 3);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn network_errors_dont_drop_requests() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_offline_attempts")
+            .await
+            .unwrap();
+        let options = Options {
+            retention: HashMap::from([(
+                Kind::DeploymentStatus,
+                Retention {
+                    max_attempts: 1,
+                    ..Default::default()
+                },
+            )]),
+        };
+        let j = journal(&dir, options).await;
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        let client = MockClient::default();
+        client.set_update_deployment(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+
+        for _ in 0..2 {
+            let drained = j.drain(&client, "token").await.unwrap();
+            assert_eq!(drained.dropped, 0);
+        }
+        assert_eq!(j.len().await, 1);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn requests_can_be_queued_while_sending() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_enqueue")
+            .await
+            .unwrap();
+        let j = Arc::new(journal(&dir, Options::default()).await);
+        j.enqueue(status(
+            "dpl_1",
+            DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+        ))
+        .await
+        .unwrap();
+        let client = Arc::new(MockClient::default());
+        let sending = Arc::new(AtomicBool::new(false));
+        let sending_for_client = sending.clone();
+        client.set_update_deployment(move || {
+            sending_for_client.store(true, Ordering::SeqCst);
+            // a slow backend
+            std::thread::sleep(Duration::from_millis(500));
+            Ok(BackendDeployment::default())
+        });
+
+        let drain = {
+            let (j, client) = (j.clone(), client.clone());
+            tokio::spawn(async move { j.drain(client.as_ref(), "token").await })
+        };
+        while !sending.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        tokio::time::timeout(
+            Duration::from_millis(250),
+            j.enqueue(status(
+                "dpl_2",
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            )),
+        )
+        .await
+        .expect("enqueue blocked by the drain")
+        .unwrap();
+
+        let drained = drain.await.unwrap().unwrap();
+        assert_eq!(drained.sent, 1);
+        assert_eq!(deployment_ids(&j.entries().await), vec!["dpl_2"]);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_requests_are_dropped() {
+        let dir = filesys::Dir::create_temp_dir("journal_drain_expired")
+            .await
+            .unwrap();
+        let file = dir.file("journal.json");
+        let expired = Entry {
+            id: 1,
+            enqueued_at: Utc::now() - TimeDelta::days(30),
+            attempts: 12,
+            request: status(
+                "dpl_1",
+                DeploymentActivityStatus::DEPLOYMENT_ACTIVITY_STATUS_DEPLOYED,
+            ),
+        };
+        file.write_json(&vec![expired], WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let j = Journal::init(file.clone(), Options::default())
+            .await
+            .unwrap();
+        assert_eq!(j.len().await, 1);
+
+        let client = MockClient::default();
+        let drained = j.drain(&client, "token").await.unwrap();
+        assert_eq!(drained.sent, 0);
+        assert_eq!(client.call_count(Call::UpdateDeployment), 0);
+        assert!(j.is_empty().await);
+        assert!(file.exists());
+        dir.delete().await.unwrap();
+    }
+}
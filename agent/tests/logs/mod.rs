@@ -7,7 +7,7 @@ use std::sync::{Arc, Mutex};
 // internal crates
 use miru_agent::errors::{Code, Error, HTTPCode};
 use miru_agent::filesys::{Dir, PathExt};
-use miru_agent::logs::{self, LogLevel, LogsErr, Options};
+use miru_agent::logs::{self, LevelControl, LogLevel, LogsErr, Options, RecentLines};
 
 // external crates
 use serial_test::serial;
@@ -296,8 +296,7 @@ fn test_logs_err_reload_failed_display() {
 
 #[test]
 fn test_logs_err_uses_default_error_trait() {
-    // LogsErr only implements Error to opt into the project's error machinery;
-    // it relies on the trait defaults.
+    // failures of the logging machinery itself are internal errors
     let err = LogsErr::ReloadFailed("anything".to_string());
     assert_eq!(err.code().as_str(), Code::InternalServerError.as_str());
     assert_eq!(err.http_status(), HTTPCode::INTERNAL_SERVER_ERROR);
@@ -305,6 +304,117 @@ fn test_logs_err_uses_default_error_trait() {
     assert!(!err.is_network_conn_err());
 }
 
+#[test]
+fn test_logs_err_client_errors() {
+    let err = LogsErr::InvalidLevel("loud".to_string());
+    assert_eq!(err.code().as_str(), Code::InvalidLogLevel.as_str());
+    assert_eq!(err.http_status(), HTTPCode::BAD_REQUEST);
+    assert!(format!("{err}").contains("'loud'"));
+
+    let err = LogsErr::EnvFilterLocked;
+    assert_eq!(err.code().as_str(), Code::LogLevelLocked.as_str());
+    assert_eq!(err.http_status(), HTTPCode::CONFLICT);
+}
+
+// ========================= from_str ============================= //
+
+#[test]
+fn test_log_level_from_str() {
+    assert_eq!("TRACE".parse::<LogLevel>().unwrap(), LogLevel::Trace);
+    assert_eq!("warning".parse::<LogLevel>().unwrap(), LogLevel::Warn);
+    for level in LogLevel::variants() {
+        assert_eq!(level.to_string().parse::<LogLevel>().unwrap(), level);
+    }
+    assert!(matches!(
+        "loud".parse::<LogLevel>(),
+        Err(LogsErr::InvalidLevel(s)) if s == "loud"
+    ));
+}
+
+// ========================= level control ======================== //
+
+#[test]
+fn test_level_control_default_tracks_the_level() {
+    let control = LevelControl::default();
+    assert_eq!(control.level(), LogLevel::Info);
+    assert!(!control.env_filter_locked());
+
+    control.set(LogLevel::Trace).unwrap();
+    assert_eq!(control.level(), LogLevel::Trace);
+    // clones share the level
+    assert_eq!(control.clone().level(), LogLevel::Trace);
+}
+
+#[test]
+fn test_level_control_changes_filter() {
+    let buf: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+    let writer = CapturingWriter(buf.clone());
+
+    let (filter_layer, handle) = reload::Layer::new(EnvFilter::new("warn"));
+    let subscriber = Registry::default()
+        .with(filter_layer)
+        .with(fmt::layer().with_writer(writer));
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let control = LevelControl::new(handle, LogLevel::Warn, false);
+
+    tracing::debug!("before-set");
+    control.set(LogLevel::Debug).unwrap();
+    tracing::debug!("after-set");
+
+    let captured = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    assert!(!captured.contains("before-set"), "{captured}");
+    assert!(captured.contains("after-set"), "{captured}");
+    assert_eq!(control.level(), LogLevel::Debug);
+}
+
+#[test]
+fn test_level_control_rejects_changes_when_env_filter_locked() {
+    let (_filter_layer, handle) = reload::Layer::<EnvFilter, Registry>::new(EnvFilter::new("warn"));
+    let control = LevelControl::new(handle, LogLevel::Warn, true);
+
+    assert!(matches!(
+        control.set(LogLevel::Debug),
+        Err(LogsErr::EnvFilterLocked)
+    ));
+    assert_eq!(control.level(), LogLevel::Warn);
+}
+
+// ========================= recent lines ========================= //
+
+#[test]
+fn test_recent_lines_keeps_the_newest() {
+    let recent = RecentLines::new(2);
+    for line in ["one", "two", "three"] {
+        recent.push(line.to_string());
+    }
+    assert_eq!(recent.lines(), vec!["two", "three"]);
+
+    recent.set_capacity(1);
+    assert_eq!(recent.lines(), vec!["three"]);
+
+    recent.set_capacity(0);
+    recent.push("four".to_string());
+    assert!(recent.lines().is_empty());
+}
+
+#[test]
+fn test_recent_lines_captures_formatted_events() {
+    let recent = RecentLines::new(logs::RECENT_LINES);
+    let subscriber = Registry::default()
+        .with(EnvFilter::new("info"))
+        .with(fmt::layer().with_writer(recent.clone()).with_ansi(false));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    tracing::debug!("filtered out");
+    tracing::info!("first event");
+    tracing::warn!("second event");
+
+    let lines = recent.lines();
+    assert_eq!(lines.len(), 2, "{lines:?}");
+    assert!(lines[0].contains("first event"), "{lines:?}");
+    assert!(lines[1].contains("WARN"), "{lines:?}");
+}
+
 // ========================= build_layers ========================= //
 
 /// RAII guard that restores `RUST_LOG` to its prior value (or unset state)
@@ -0,0 +1,291 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::cache::FileCache;
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::metrics::{self, Counter, CounterVec, HistogramVec, Metrics, Observations};
+
+// external crates
+use serde::{Deserialize, Serialize};
+
+pub mod families {
+    use super::*;
+
+    #[test]
+    fn counter() {
+        let counter = Counter::new("test_total", "A test counter.");
+        counter.inc();
+        counter.inc();
+        assert_eq!(counter.get(), 2);
+
+        let mut out = String::new();
+        counter.encode(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_total A test counter.\n# TYPE test_total counter\ntest_total 2\n"
+        );
+    }
+
+    #[test]
+    fn counter_vec() {
+        let counter = CounterVec::new("test_total", "A test counter.", &["kind"]);
+        counter.inc(&["b"]);
+        counter.inc(&["a"]);
+        counter.inc(&["b"]);
+        assert_eq!(counter.get(&["a"]), 1);
+        assert_eq!(counter.get(&["b"]), 2);
+        assert_eq!(counter.get(&["c"]), 0);
+
+        let mut out = String::new();
+        counter.encode(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_total A test counter.\n\
+             # TYPE test_total counter\n\
+             test_total{kind=\"a\"} 1\n\
+             test_total{kind=\"b\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let counter = CounterVec::new("test_total", "A test counter.", &["path"]);
+        counter.inc(&["a\"b\\c\nd"]);
+
+        let mut out = String::new();
+        counter.encode(&mut out);
+        assert!(
+            out.contains("test_total{path=\"a\\\"b\\\\c\\nd\"} 1\n"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn histogram_vec() {
+        let histogram = HistogramVec::new(
+            "test_seconds",
+            "A test histogram.",
+            &["method"],
+            &[0.1, 1.0],
+        );
+        histogram.observe(&["GET"], 0.05);
+        histogram.observe(&["GET"], 0.5);
+        histogram.observe(&["GET"], 2.0);
+        assert_eq!(
+            histogram.get(&["GET"]),
+            Observations {
+                buckets: vec![1, 1],
+                sum: 2.55,
+                count: 3,
+            }
+        );
+        assert_eq!(histogram.get(&["POST"]), Observations::default());
+
+        let mut out = String::new();
+        histogram.encode(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_seconds A test histogram.\n\
+             # TYPE test_seconds histogram\n\
+             test_seconds_bucket{method=\"GET\",le=\"0.1\"} 1\n\
+             test_seconds_bucket{method=\"GET\",le=\"1\"} 2\n\
+             test_seconds_bucket{method=\"GET\",le=\"+Inf\"} 3\n\
+             test_seconds_sum{method=\"GET\"} 2.55\n\
+             test_seconds_count{method=\"GET\"} 3\n"
+        );
+    }
+}
+
+pub mod registry {
+    use super::*;
+
+    #[test]
+    fn records_syncs() {
+        let metrics = Metrics::new();
+        metrics.record_sync_success();
+        metrics.record_sync_failure(true);
+        metrics.record_sync_failure(false);
+        metrics.record_sync_failure(false);
+        assert_eq!(metrics.syncs.get(), 4);
+        assert_eq!(metrics.sync_failures.get(&["network"]), 1);
+        assert_eq!(metrics.sync_failures.get(&["other"]), 2);
+    }
+
+    #[test]
+    fn records_http_requests() {
+        let metrics = Metrics::new();
+        metrics.record_http_request("GET", Duration::from_millis(20));
+        let observations = metrics.http_request_duration.get(&["GET"]);
+        assert_eq!(observations.count, 1);
+        assert!((observations.sum - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn renders_every_metric() {
+        let metrics = Metrics::new();
+        metrics.record_sync_success();
+        metrics.mqtt_connections.inc();
+        metrics.deployment_transitions.inc(&["queued", "deployed"]);
+
+        let out = metrics.render();
+        for name in [
+            "miru_syncs_total",
+            "miru_sync_failures_total",
+            "miru_deployment_transitions_total",
+            "miru_deployment_errors_total",
+            "miru_mqtt_connections_total",
+            "miru_mqtt_probe_failures_total",
+            "miru_cache_hits_total",
+            "miru_cache_misses_total",
+            "miru_http_request_duration_seconds",
+            "miru_api_requests_shed_total",
+            "miru_worker_cpu_seconds_total",
+            "miru_worker_allocations_total",
+            "miru_worker_allocated_bytes_total",
+        ] {
+            assert!(out.contains(&format!("# TYPE {name} ")), "{name}: {out}");
+        }
+        assert!(out.contains("miru_syncs_total 1\n"), "{out}");
+        assert!(out.contains("miru_mqtt_connections_total 1\n"), "{out}");
+        assert!(
+            out.contains("miru_deployment_transitions_total{from=\"queued\",to=\"deployed\"} 1\n"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn type_label() {
+        assert_eq!(metrics::type_label::<String>(), "String");
+        assert_eq!(metrics::type_label::<Vec<u8>>(), "Vec");
+        assert_eq!(metrics::type_label::<u64>(), "u64");
+    }
+}
+
+pub mod workers {
+    use super::*;
+    use miru_agent::metrics::workers::{instrument, Usage, Worker};
+
+    fn spin(duration: Duration) {
+        let started = std::time::Instant::now();
+        while started.elapsed() < duration {
+            std::hint::spin_loop();
+        }
+    }
+
+    #[test]
+    fn labels_are_unique() {
+        let labels: std::collections::HashSet<_> = Worker::ALL.iter().map(Worker::as_str).collect();
+        assert_eq!(labels.len(), Worker::ALL.len());
+    }
+
+    #[tokio::test]
+    async fn attributes_poll_time() {
+        let usage = &metrics::global().worker_usage;
+        let before = usage.get(Worker::Wear);
+        instrument(Worker::Wear, async { spin(Duration::from_millis(20)) }).await;
+        let after = usage.get(Worker::Wear);
+        assert!(after.cpu_time - before.cpu_time >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn attributes_allocations() {
+        let usage = &metrics::global().worker_usage;
+        let before = usage.get(Worker::Journal);
+        let buf = instrument(Worker::Journal, async {
+            std::hint::black_box(vec![0u8; 1024 * 1024])
+        })
+        .await;
+        let after = usage.get(Worker::Journal);
+        assert_eq!(buf.len(), 1024 * 1024);
+        assert!(after.allocations > before.allocations);
+        assert!(after.allocated_bytes - before.allocated_bytes >= 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn nested_time_goes_to_the_inner_worker() {
+        let usage = &metrics::global().worker_usage;
+        let outer_before = usage.get(Worker::CacheAudit);
+        let inner_before = usage.get(Worker::TokenRefresh);
+        instrument(Worker::CacheAudit, async {
+            instrument(Worker::TokenRefresh, async {
+                spin(Duration::from_millis(30))
+            })
+            .await
+        })
+        .await;
+        let outer = usage.get(Worker::CacheAudit).cpu_time - outer_before.cpu_time;
+        let inner = usage.get(Worker::TokenRefresh).cpu_time - inner_before.cpu_time;
+        assert!(inner >= Duration::from_millis(30));
+        assert!(outer < Duration::from_millis(30), "{outer:?}");
+    }
+
+    #[test]
+    fn renders_a_series_per_worker() {
+        let mut out = String::new();
+        Usage::new().encode(&mut out);
+        for worker in Worker::ALL {
+            let worker = worker.as_str();
+            assert!(
+                out.contains(&format!(
+                    "miru_worker_cpu_seconds_total{{worker=\"{worker}\"}} 0\n"
+                )),
+                "{out}"
+            );
+            assert!(
+                out.contains(&format!(
+                    "miru_worker_allocations_total{{worker=\"{worker}\"}} 0\n"
+                )),
+                "{out}"
+            );
+            assert!(
+                out.contains(&format!(
+                    "miru_worker_allocated_bytes_total{{worker=\"{worker}\"}} 0\n"
+                )),
+                "{out}"
+            );
+        }
+    }
+}
+
+pub mod cache {
+    use super::*;
+
+    // a value type of its own so other tests' caches don't touch its counters
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct MetricsProbe(u32);
+
+    #[tokio::test]
+    async fn counts_hits_and_misses() {
+        let file = filesys::Dir::create_temp_dir("metrics_cache")
+            .await
+            .unwrap()
+            .file("cache.json");
+        let (cache, _) = FileCache::<String, MetricsProbe>::spawn(32, file, 1000)
+            .await
+            .unwrap();
+        let label = [metrics::type_label::<MetricsProbe>()];
+        let metrics = metrics::global();
+
+        // writes aren't lookups
+        cache
+            .write(
+                "a".to_string(),
+                MetricsProbe(1),
+                |_, _| true,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        assert_eq!(metrics.cache_hits.get(&label), 0);
+        assert_eq!(metrics.cache_misses.get(&label), 0);
+
+        cache.read_optional("a".to_string()).await.unwrap();
+        cache.read_optional("a".to_string()).await.unwrap();
+        cache.read_optional("b".to_string()).await.unwrap();
+        assert_eq!(metrics.cache_hits.get(&label), 2);
+        assert_eq!(metrics.cache_misses.get(&label), 1);
+
+        cache.shutdown().await.unwrap();
+    }
+}
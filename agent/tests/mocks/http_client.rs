@@ -4,9 +4,12 @@ use std::sync::{Arc, Mutex};
 // internal crates
 use backend_api::models::{
     Deployment as BackendDeployment, DeploymentList, Device, Error as ApiError, ErrorResponse,
-    GitCommit as BackendGitCommit, Release as BackendRelease, TokenResponse,
+    GitCommit as BackendGitCommit, Release as BackendRelease, SyncDevice, TokenResponse,
+};
+use miru_agent::crypt::rsa;
+use miru_agent::http::{
+    self, config_instances::ContentPatch, devices::RegisteredKey, request::Params, HTTPErr,
 };
-use miru_agent::http::{self, request::Params, HTTPErr};
 
 // external crates
 use axum::http::StatusCode;
@@ -23,12 +26,19 @@ pub enum Call {
     ProvisionDevice,
     ReprovisionDevice,
     IssueDeviceToken,
+    RegisterKey,
     UpdateDevice,
     GetDevice,
+    DeregisterDevice,
+    WaitForSync,
+    ReportTelemetry,
+    ReportCrash,
     ListDeployments,
     GetDeployment,
     UpdateDeployment,
     GetConfigInstanceContent,
+    GetConfigInstancePatch,
+    GetConfigSchema,
     GetRelease,
     GetGitCommit,
 }
@@ -53,21 +63,35 @@ type SingleDeploymentFn = Mutex<Box<dyn Fn() -> Result<BackendDeployment, HTTPEr
 type SingleReleaseFn = Mutex<Box<dyn Fn() -> Result<BackendRelease, HTTPErr> + Send + Sync>>;
 type SingleGitCommitFn = Mutex<Box<dyn Fn() -> Result<BackendGitCommit, HTTPErr> + Send + Sync>>;
 type GetCfgInstContentFn = Mutex<Box<dyn Fn(&str) -> Result<String, HTTPErr> + Send + Sync>>;
+type GetCfgInstPatchFn = Mutex<Box<dyn Fn(&str) -> Result<ContentPatch, HTTPErr> + Send + Sync>>;
+type GetConfigSchemaFn =
+    Mutex<Box<dyn Fn(&str) -> Result<serde_json::Value, HTTPErr> + Send + Sync>>;
 type UpdateDeviceFn = Mutex<Box<dyn Fn() -> Result<Device, HTTPErr> + Send + Sync>>;
 type GetDeviceFn = Mutex<Box<dyn Fn() -> Result<Device, HTTPErr> + Send + Sync>>;
+type WaitForSyncFn = Mutex<Box<dyn Fn() -> Result<SyncDevice, HTTPErr> + Send + Sync>>;
+type RegisterKeyFn = Mutex<Box<dyn Fn(&str) -> Result<RegisteredKey, HTTPErr> + Send + Sync>>;
+type ReportTelemetryFn = Mutex<Box<dyn Fn() -> Result<(), HTTPErr> + Send + Sync>>;
+type ReportCrashFn = Mutex<Box<dyn Fn() -> Result<(), HTTPErr> + Send + Sync>>;
 
 pub struct MockClient {
     pub provision_device_fn: Box<dyn Fn() -> Result<Device, HTTPErr> + Send + Sync>,
     pub reprovision_device_fn: Box<dyn Fn() -> Result<Device, HTTPErr> + Send + Sync>,
     pub issue_device_token_fn: Box<dyn Fn() -> Result<TokenResponse, HTTPErr> + Send + Sync>,
+    pub register_key_fn: RegisterKeyFn,
     pub update_device_fn: UpdateDeviceFn,
     pub get_device_fn: GetDeviceFn,
+    pub deregister_device_fn: GetDeviceFn,
+    pub wait_for_sync_fn: WaitForSyncFn,
+    pub report_telemetry_fn: ReportTelemetryFn,
+    pub report_crash_fn: ReportCrashFn,
     pub list_deployments_fn: ListDeploymentsFn,
     pub get_deployment_fn: SingleDeploymentFn,
     pub update_deployment_fn: SingleDeploymentFn,
     pub get_release_fn: SingleReleaseFn,
     pub get_git_commit_fn: SingleGitCommitFn,
     pub get_cfg_inst_content_fn: GetCfgInstContentFn,
+    pub get_cfg_inst_patch_fn: GetCfgInstPatchFn,
+    pub get_config_schema_fn: GetConfigSchemaFn,
     pub requests: Arc<Mutex<Vec<CapturedRequest>>>,
 }
 
@@ -77,20 +101,47 @@ impl Default for MockClient {
             provision_device_fn: Box::new(|| Ok(Device::default())),
             reprovision_device_fn: Box::new(|| Ok(Device::default())),
             issue_device_token_fn: Box::new(|| Ok(TokenResponse::default())),
+            // registers the posted public key under its own fingerprint
+            register_key_fn: Mutex::new(Box::new(|public_key_pem| {
+                let key =
+                    openssl::rsa::Rsa::public_key_from_pem(public_key_pem.as_bytes()).unwrap();
+                Ok(RegisteredKey {
+                    fingerprint: rsa::fingerprint(&key).unwrap(),
+                })
+            })),
             update_device_fn: Mutex::new(Box::new(|| Ok(Device::default()))),
             get_device_fn: Mutex::new(Box::new(|| Ok(Device::default()))),
+            deregister_device_fn: Mutex::new(Box::new(|| Ok(Device::default()))),
+            wait_for_sync_fn: Mutex::new(Box::new(|| Ok(SyncDevice { is_synced: true }))),
+            report_telemetry_fn: Mutex::new(Box::new(|| Ok(()))),
+            report_crash_fn: Mutex::new(Box::new(|| Ok(()))),
             list_deployments_fn: Mutex::new(Box::new(|| Ok(DeploymentList::default()))),
             get_deployment_fn: Mutex::new(Box::new(|| Ok(BackendDeployment::default()))),
             update_deployment_fn: Mutex::new(Box::new(|| Ok(BackendDeployment::default()))),
             get_release_fn: Mutex::new(Box::new(|| Ok(BackendRelease::default()))),
             get_git_commit_fn: Mutex::new(Box::new(|| Ok(BackendGitCommit::default()))),
             get_cfg_inst_content_fn: Mutex::new(Box::new(|_id| Ok("{}".to_string()))),
+            // patches are unsupported unless a test opts in
+            get_cfg_inst_patch_fn: Mutex::new(Box::new(|_id| {
+                Err(HTTPErr::MockErr(http::errors::MockErr {
+                    is_network_conn_err: false,
+                }))
+            })),
+            // the schema which accepts everything
+            get_config_schema_fn: Mutex::new(Box::new(|_id| Ok(serde_json::Value::Bool(true)))),
             requests: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
 
 impl MockClient {
+    pub fn set_register_key<F>(&self, f: F)
+    where
+        F: Fn(&str) -> Result<RegisteredKey, HTTPErr> + Send + Sync + 'static,
+    {
+        *self.register_key_fn.lock().unwrap() = Box::new(f);
+    }
+
     pub fn set_update_device<F>(&self, f: F)
     where
         F: Fn() -> Result<Device, HTTPErr> + Send + Sync + 'static,
@@ -105,6 +156,34 @@ impl MockClient {
         *self.get_device_fn.lock().unwrap() = Box::new(f);
     }
 
+    pub fn set_deregister_device<F>(&self, f: F)
+    where
+        F: Fn() -> Result<Device, HTTPErr> + Send + Sync + 'static,
+    {
+        *self.deregister_device_fn.lock().unwrap() = Box::new(f);
+    }
+
+    pub fn set_wait_for_sync<F>(&self, f: F)
+    where
+        F: Fn() -> Result<SyncDevice, HTTPErr> + Send + Sync + 'static,
+    {
+        *self.wait_for_sync_fn.lock().unwrap() = Box::new(f);
+    }
+
+    pub fn set_report_telemetry<F>(&self, f: F)
+    where
+        F: Fn() -> Result<(), HTTPErr> + Send + Sync + 'static,
+    {
+        *self.report_telemetry_fn.lock().unwrap() = Box::new(f);
+    }
+
+    pub fn set_report_crash<F>(&self, f: F)
+    where
+        F: Fn() -> Result<(), HTTPErr> + Send + Sync + 'static,
+    {
+        *self.report_crash_fn.lock().unwrap() = Box::new(f);
+    }
+
     pub fn set_list_all_deployments<F>(&self, f: F)
     where
         F: Fn() -> Result<Vec<BackendDeployment>, HTTPErr> + Send + Sync + 'static,
@@ -161,6 +240,20 @@ impl MockClient {
         *self.get_cfg_inst_content_fn.lock().unwrap() = Box::new(f);
     }
 
+    pub fn set_get_config_instance_patch<F>(&self, f: F)
+    where
+        F: Fn(&str) -> Result<ContentPatch, HTTPErr> + Send + Sync + 'static,
+    {
+        *self.get_cfg_inst_patch_fn.lock().unwrap() = Box::new(f);
+    }
+
+    pub fn set_get_config_schema<F>(&self, f: F)
+    where
+        F: Fn(&str) -> Result<serde_json::Value, HTTPErr> + Send + Sync + 'static,
+    {
+        *self.get_config_schema_fn.lock().unwrap() = Box::new(f);
+    }
+
     pub fn call_count(&self, target: Call) -> usize {
         self.requests
             .lock()
@@ -198,9 +291,33 @@ impl MockClient {
             (m, p) if *m == Method::POST && p == "/devices/provision" => Call::ProvisionDevice,
             (m, p) if *m == Method::POST && p == "/devices/reprovision" => Call::ReprovisionDevice,
             (m, p) if *m == Method::POST && p.ends_with("/devices/token") => Call::IssueDeviceToken,
+            (m, p) if *m == Method::POST && p == "/devices/keys" => Call::RegisterKey,
             (m, p) if *m == Method::PATCH && p.starts_with("/devices/") => Call::UpdateDevice,
+            (m, p) if *m == Method::GET && p.starts_with("/devices/") && p.ends_with("/sync") => {
+                Call::WaitForSync
+            }
+            (m, p)
+                if *m == Method::POST
+                    && p.starts_with("/devices/")
+                    && p.ends_with("/telemetry") =>
+            {
+                Call::ReportTelemetry
+            }
+            (m, p)
+                if *m == Method::POST && p.starts_with("/devices/") && p.ends_with("/crashes") =>
+            {
+                Call::ReportCrash
+            }
             (m, p) if *m == Method::GET && p == "/device" => Call::GetDevice,
+            (m, p) if *m == Method::DELETE && p == "/device" => Call::DeregisterDevice,
             (m, p) if *m == Method::GET && p == "/deployments" => Call::ListDeployments,
+            (m, p)
+                if *m == Method::GET
+                    && p.starts_with("/config_instances/")
+                    && p.ends_with("/content/patch") =>
+            {
+                Call::GetConfigInstancePatch
+            }
             (m, p)
                 if *m == Method::GET
                     && p.starts_with("/config_instances/")
@@ -208,6 +325,9 @@ impl MockClient {
             {
                 Call::GetConfigInstanceContent
             }
+            (m, p) if *m == Method::GET && p.starts_with("/config_schemas/") => {
+                Call::GetConfigSchema
+            }
             (m, p) if *m == Method::GET && p.starts_with("/deployments/") => Call::GetDeployment,
             (m, p) if *m == Method::PATCH && p.starts_with("/deployments/") => {
                 Call::UpdateDeployment
@@ -218,13 +338,28 @@ impl MockClient {
         }
     }
 
-    fn handle_route(&self, call: &Call, path: &str) -> Result<String, HTTPErr> {
+    fn handle_route(&self, call: &Call, path: &str, body: &str) -> Result<String, HTTPErr> {
         match call {
             Call::ProvisionDevice => json(&(self.provision_device_fn)()?),
             Call::ReprovisionDevice => json(&(self.reprovision_device_fn)()?),
             Call::IssueDeviceToken => json(&(self.issue_device_token_fn)()?),
+            Call::RegisterKey => {
+                let body: serde_json::Value = serde_json::from_str(body).unwrap();
+                let public_key_pem = body["public_key_pem"].as_str().unwrap_or("");
+                json(&(self.register_key_fn.lock().unwrap())(public_key_pem)?)
+            }
             Call::UpdateDevice => json(&(self.update_device_fn.lock().unwrap())()?),
             Call::GetDevice => json(&(self.get_device_fn.lock().unwrap())()?),
+            Call::DeregisterDevice => json(&(self.deregister_device_fn.lock().unwrap())()?),
+            Call::WaitForSync => json(&(self.wait_for_sync_fn.lock().unwrap())()?),
+            Call::ReportTelemetry => {
+                (self.report_telemetry_fn.lock().unwrap())()?;
+                Ok("{}".to_string())
+            }
+            Call::ReportCrash => {
+                (self.report_crash_fn.lock().unwrap())()?;
+                Ok("{}".to_string())
+            }
             Call::ListDeployments => {
                 let list = (self.list_deployments_fn.lock().unwrap())()?;
                 json(&list)
@@ -241,6 +376,18 @@ impl MockClient {
                     .unwrap_or("");
                 (self.get_cfg_inst_content_fn.lock().unwrap())(id)
             }
+            Call::GetConfigInstancePatch => {
+                // Extract ID from /config_instances/{id}/content/patch
+                let id = path
+                    .strip_prefix("/config_instances/")
+                    .and_then(|s| s.strip_suffix("/content/patch"))
+                    .unwrap_or("");
+                json(&(self.get_cfg_inst_patch_fn.lock().unwrap())(id)?)
+            }
+            Call::GetConfigSchema => {
+                let id = path.strip_prefix("/config_schemas/").unwrap_or("");
+                json(&(self.get_config_schema_fn.lock().unwrap())(id)?)
+            }
         }
     }
 }
@@ -274,7 +421,7 @@ impl http::ClientI for MockClient {
             body: params.body.clone(),
             token: params.token.map(|t| t.to_string()),
         });
-        let text = self.handle_route(&call, path)?;
+        let text = self.handle_route(&call, path, params.body.as_deref().unwrap_or(""))?;
         Ok((text, meta))
     }
 }
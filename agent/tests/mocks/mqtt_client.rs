@@ -11,6 +11,7 @@ use tokio::net::TcpListener;
 
 // internal crates
 use miru_agent::mqtt::client::Publish;
+use miru_agent::mqtt::options::QoSLevels;
 use miru_agent::mqtt::{ClientI, MQTTError};
 
 // external crates
@@ -41,6 +42,7 @@ pub struct MockClient {
     pub unsubscribe_fn: Box<dyn Fn() -> Result<(), Box<MQTTError>> + Send + Sync>,
     pub disconnect_fn: Box<dyn Fn() -> Result<(), Box<MQTTError>> + Send + Sync>,
     pub calls: Arc<Mutex<Vec<MockCall>>>,
+    pub qos: QoSLevels,
 }
 
 impl Default for MockClient {
@@ -51,6 +53,7 @@ impl Default for MockClient {
             unsubscribe_fn: Box::new(|| Ok(())),
             disconnect_fn: Box::new(|| Ok(())),
             calls: Arc::new(Mutex::new(Vec::new())),
+            qos: QoSLevels::default(),
         }
     }
 }
@@ -117,6 +120,10 @@ impl ClientI for MockClient {
         self.calls.lock().unwrap().push(MockCall::Disconnect);
         (self.disconnect_fn)().map_err(|err| *err)
     }
+
+    fn qos(&self) -> QoSLevels {
+        self.qos
+    }
 }
 
 // ================================ MOCK BROKER ==================================== //
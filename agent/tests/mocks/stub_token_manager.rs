@@ -6,6 +6,7 @@ use miru_agent::authn::{AuthnErr, Token, TokenManagerExt};
 
 // external crates
 use chrono::Utc;
+use tokio::sync::watch;
 
 /// One-shot test stub: each call to `get_token` consumes the canned
 /// response. `http::with_retry` does not re-fetch the token between
@@ -14,6 +15,7 @@ use chrono::Utc;
 /// than once panics with "no canned response".
 pub struct StubTokenManager {
     response: Mutex<Option<Result<Arc<Token>, AuthnErr>>>,
+    rotations: watch::Sender<Arc<Token>>,
 }
 
 impl StubTokenManager {
@@ -22,13 +24,16 @@ impl StubTokenManager {
             token: token.to_string(),
             expires_at: Utc::now() + chrono::Duration::hours(1),
         };
+        let t = Arc::new(t);
         Self {
-            response: Mutex::new(Some(Ok(Arc::new(t)))),
+            response: Mutex::new(Some(Ok(t.clone()))),
+            rotations: watch::channel(t).0,
         }
     }
     pub fn err(e: AuthnErr) -> Self {
         Self {
             response: Mutex::new(Some(Err(e))),
+            rotations: watch::channel(Arc::new(Token::default())).0,
         }
     }
 }
@@ -49,4 +54,8 @@ impl TokenManagerExt for StubTokenManager {
     async fn refresh_token(&self) -> Result<(), AuthnErr> {
         Ok(())
     }
+
+    fn subscribe(&self) -> watch::Receiver<Arc<Token>> {
+        self.rotations.subscribe()
+    }
 }
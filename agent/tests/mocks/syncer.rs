@@ -5,6 +5,9 @@ use std::sync::{Arc, Mutex};
 // internal crates
 use miru_agent::sync::{
     errors::SyncErr,
+    event_log,
+    history::Record,
+    plan::Plan,
     syncer::{State, SyncEvent, SyncerExt},
 };
 
@@ -20,6 +23,9 @@ pub struct MockSyncer {
     pub num_sync_calls: AtomicUsize,
     pub get_sync_state_fn: Arc<Mutex<GetSyncStateFn>>,
     pub sync_fn: Arc<Mutex<SyncFn>>,
+    pub sync_history: Arc<Mutex<Vec<Record>>>,
+    pub sync_events: Arc<Mutex<Vec<event_log::Record>>>,
+    pub plan: Arc<Mutex<Plan>>,
 
     // subscriptions
     pub subscribe_rx: watch::Receiver<SyncEvent>,
@@ -44,8 +50,18 @@ impl MockSyncer {
                 last_synced_at: DateTime::<Utc>::UNIX_EPOCH,
                 cooldown_ends_at: DateTime::<Utc>::UNIX_EPOCH,
                 err_streak: 0,
+                syncs: 0,
             }))),
             sync_fn: Arc::new(Mutex::new(Box::new(|| Ok(())))),
+            sync_history: Arc::new(Mutex::new(Vec::new())),
+            sync_events: Arc::new(Mutex::new(Vec::new())),
+            plan: Arc::new(Mutex::new(Plan {
+                planned_at: DateTime::<Utc>::UNIX_EPOCH,
+                safe_mode: false,
+                paused: false,
+                conflicts: Vec::new(),
+                steps: Vec::new(),
+            })),
 
             // subscriptions
             subscribe_rx: rx,
@@ -114,4 +130,27 @@ impl SyncerExt for MockSyncer {
     async fn subscribe(&self) -> Result<watch::Receiver<SyncEvent>, SyncErr> {
         Ok(self.subscribe_rx.clone())
     }
+
+    async fn last_event(&self) -> Result<Option<SyncEvent>, SyncErr> {
+        Ok(match self.subscribe_rx.has_changed() {
+            Ok(true) => Some(self.subscribe_rx.borrow().clone()),
+            _ => None,
+        })
+    }
+
+    async fn get_sync_history(&self) -> Result<Vec<Record>, SyncErr> {
+        Ok(self.sync_history.lock().unwrap().clone())
+    }
+
+    async fn get_sync_events(&self) -> Result<Vec<event_log::Record>, SyncErr> {
+        Ok(self.sync_events.lock().unwrap().clone())
+    }
+
+    async fn plan(&self) -> Result<Plan, SyncErr> {
+        Ok(self.plan.lock().unwrap().clone())
+    }
+
+    fn is_syncing(&self) -> bool {
+        false
+    }
 }
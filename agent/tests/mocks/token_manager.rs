@@ -4,6 +4,9 @@ use std::sync::{Arc, Mutex};
 // internal crates
 use miru_agent::authn::{AuthnErr, Token, TokenManagerExt};
 
+// external crates
+use tokio::sync::watch;
+
 type GetTokenFn = Box<dyn Fn() -> Result<Arc<Token>, AuthnErr> + Send + Sync>;
 type RefreshTokenFn = Box<dyn Fn() -> Result<(), AuthnErr> + Send + Sync>;
 
@@ -18,15 +21,18 @@ pub struct MockTokenManager {
     pub calls: Arc<Mutex<Vec<TokenManagerCall>>>,
     pub get_token_fn: Arc<Mutex<Option<GetTokenFn>>>,
     pub refresh_token_fn: Arc<Mutex<RefreshTokenFn>>,
+    pub rotations: Arc<watch::Sender<Arc<Token>>>,
 }
 
 impl MockTokenManager {
     pub fn new(token: Token) -> Self {
+        let (rotations, _) = watch::channel(Arc::new(token.clone()));
         Self {
             token: Arc::new(Mutex::new(token)),
             calls: Arc::new(Mutex::new(Vec::new())),
             get_token_fn: Arc::new(Mutex::new(None)),
             refresh_token_fn: Arc::new(Mutex::new(Box::new(|| Ok(())))),
+            rotations: Arc::new(rotations),
         }
     }
 
@@ -34,6 +40,12 @@ impl MockTokenManager {
         *self.token.lock().unwrap() = token;
     }
 
+    /// Sets the token and notifies the subscribers as if it had been refreshed.
+    pub fn rotate(&self, token: Token) {
+        self.set_token(token.clone());
+        self.rotations.send_replace(Arc::new(token));
+    }
+
     pub fn get_calls(&self) -> Vec<TokenManagerCall> {
         self.calls.lock().unwrap().clone()
     }
@@ -86,4 +98,8 @@ impl TokenManagerExt for MockTokenManager {
             .push(TokenManagerCall::RefreshToken);
         (*self.refresh_token_fn.lock().unwrap())()
     }
+
+    fn subscribe(&self) -> watch::Receiver<Arc<Token>> {
+        self.rotations.subscribe()
+    }
 }
@@ -1,26 +1,43 @@
 pub mod activity;
 pub mod app;
+pub mod audit;
 pub mod authn;
 pub mod cache;
+pub mod cell;
 pub mod cli;
+pub mod clock;
+pub mod config;
 pub mod cooldown;
 pub mod crypt;
 pub mod deploy;
+pub mod diagnostics;
 pub mod errors;
 pub mod events;
 pub mod filesys;
 pub mod http;
+pub mod journal;
 pub mod logs;
+pub mod metrics;
 pub mod mocks;
 pub mod models;
 pub mod mqtt;
 pub mod network;
+pub mod notifications;
+pub mod platform;
 pub mod provisioning;
 pub mod server;
 pub mod services;
 pub mod storage;
+pub mod supervisor;
 pub mod sync;
 pub mod telemetry;
 pub mod test_utils;
+pub mod testkit;
+pub mod updater;
 pub mod version;
 pub mod workers;
+
+// count allocations against workers as the agent's binary does
+#[global_allocator]
+static ALLOC: miru_agent::metrics::workers::CountingAlloc =
+    miru_agent::metrics::workers::CountingAlloc;
@@ -1,6 +1,6 @@
 // internal crates
 use backend_api::models as backend_client;
-use miru_agent::models::ConfigInstance;
+use miru_agent::models::config_instance::{ConfigInstance, ContentFormat};
 
 // external crates
 use chrono::{DateTime, Utc};
@@ -38,11 +38,18 @@ impl ModelFixture for ConfigInstance {
     }
 
     fn optional_fields() -> Vec<OptionalField> {
-        vec![OptionalField {
-            key: "created_at",
-            value: json!("2023-11-14T22:13:20Z"),
-            default_value: json!("1970-01-01T00:00:00Z"),
-        }]
+        vec![
+            OptionalField {
+                key: "created_at",
+                value: json!("2023-11-14T22:13:20Z"),
+                default_value: json!("1970-01-01T00:00:00Z"),
+            },
+            OptionalField {
+                key: "content_format",
+                value: json!("yaml"),
+                default_value: json!("json"),
+            },
+        ]
     }
 }
 
@@ -68,6 +75,7 @@ fn defaults() {
         created_at: DateTime::<Utc>::UNIX_EPOCH,
         config_schema_id,
         config_type_id,
+        content_format: ContentFormat::Json,
     };
     assert_eq!(instance, expected);
 }
@@ -96,6 +104,7 @@ fn from_backend() {
         config_schema_id: "schema_123".to_string(),
         config_type_id: "type_123".to_string(),
         created_at: now,
+        content_format: ContentFormat::Json,
     };
     assert_eq!(actual, expected);
 }
@@ -117,3 +126,23 @@ fn from_backend_invalid_date() {
     assert_eq!(instance.id, "cfg_inst_789");
     assert_eq!(instance.created_at, DateTime::<Utc>::UNIX_EPOCH);
 }
+
+#[test]
+fn content_format_from_filepath() {
+    let cases = [
+        ("/etc/robot/motion.json", Some(ContentFormat::Json)),
+        ("/etc/robot/motion.yaml", Some(ContentFormat::Yaml)),
+        ("/etc/robot/motion.YML", Some(ContentFormat::Yaml)),
+        ("/etc/robot/motion.toml", Some(ContentFormat::Toml)),
+        ("/etc/robot/motion.ini", Some(ContentFormat::Ini)),
+        ("/etc/robot/firmware.bin", None),
+        ("/etc/robot/motion", None),
+    ];
+    for (filepath, expected) in cases {
+        assert_eq!(
+            ContentFormat::from_filepath(filepath),
+            expected,
+            "{filepath}"
+        );
+    }
+}
@@ -3,7 +3,9 @@ use backend_api::models as backend_client;
 use device_api::models as agent_server;
 use miru_agent::models::deployment::Updates;
 use miru_agent::models::Patch;
-use miru_agent::models::{Deployment, DplActivity, DplErrStatus, DplStatus, DplTarget};
+use miru_agent::models::{
+    Deployment, DplActivity, DplDependencies, DplErrStatus, DplMetrics, DplStatus, DplTarget,
+};
 
 // external crates
 use chrono::{DateTime, TimeDelta, Utc};
@@ -77,12 +79,32 @@ impl ModelFixture for Deployment {
                 value: json!("2099-01-01T00:00:00Z"),
                 default_value: json!("1970-01-01T00:00:00Z"),
             },
+            OptionalField {
+                key: "metrics",
+                value: json!({ "bytes_downloaded": 2048, "staging_duration_ms": 15 }),
+                default_value: json!({ "bytes_downloaded": 0 }),
+            },
+            OptionalField {
+                key: "pending_finalize",
+                value: json!({ "boot_id": "boot_123", "staged_at": "2023-11-14T22:13:20Z" }),
+                default_value: json!(null),
+            },
         ]
     }
 }
 
 serde_tests!(Deployment);
 
+#[test]
+fn metrics_is_empty() {
+    assert!(DplMetrics::default().is_empty());
+    let metrics = DplMetrics {
+        materialization_duration_ms: Some(3),
+        ..Default::default()
+    };
+    assert!(!metrics.is_empty());
+}
+
 #[test]
 fn status_method() {
     let deployment = Deployment {
@@ -167,6 +189,9 @@ fn defaults() {
         deployed_at: None,
         archived_at: None,
         config_instance_ids: Vec::new(),
+        metrics: DplMetrics::default(),
+        pending_finalize: None,
+        dependencies: DplDependencies::default(),
     };
 
     assert_eq!(actual, expected);
@@ -880,6 +905,9 @@ fn from_backend() {
         deployed_at: None,
         archived_at: None,
         config_instance_ids: vec!["cfg_1".to_string(), "cfg_2".to_string()],
+        metrics: DplMetrics::default(),
+        pending_finalize: None,
+        dependencies: DplDependencies::default(),
     };
     assert_eq!(actual, expected);
 }
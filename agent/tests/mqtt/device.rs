@@ -1,6 +1,7 @@
 // internal crates
 use miru_agent::mqtt::device;
 use miru_agent::mqtt::errors::MockErr;
+use miru_agent::mqtt::options::QoSLevels;
 use miru_agent::mqtt::MQTTError;
 
 // test helpers
@@ -154,3 +155,283 @@ mod publish_pong {
         assert!(result.is_err());
     }
 }
+
+mod subscribe_command {
+    use super::*;
+
+    #[tokio::test]
+    async fn happy_path() {
+        let client = MockClient::default();
+        device::subscribe_command(&client, "dvc_123").await.unwrap();
+
+        let calls = client.get_calls();
+        assert_eq!(calls.len(), 1);
+        assert!(matches!(
+            &calls[0],
+            MockCall::Subscribe { topic, qos }
+                if topic == "v1/cmd/devices/dvc_123/command" && *qos == QoS::AtLeastOnce
+        ));
+    }
+
+    #[tokio::test]
+    async fn error_propagation() {
+        let client = MockClient {
+            subscribe_fn: Box::new(|| Err(Box::new(mock_error()))),
+            ..Default::default()
+        };
+        let result = device::subscribe_command(&client, "dvc_123").await;
+        assert!(result.is_err());
+    }
+}
+
+mod publish_command_response {
+    use super::*;
+    use miru_agent::mqtt::command::{Command, Response, Status};
+
+    #[tokio::test]
+    async fn happy_path() {
+        let client = MockClient::default();
+        let response = Response::succeeded("msg-123".to_string(), &Command::SyncNow, None);
+        device::publish_command_response(&client, "dvc_123", &response)
+            .await
+            .unwrap();
+
+        let calls = client.get_calls();
+        assert_eq!(calls.len(), 1);
+        match &calls[0] {
+            MockCall::Publish {
+                topic,
+                qos,
+                retained,
+                payload,
+            } => {
+                assert_eq!(topic, "v1/resp/devices/dvc_123/command");
+                assert_eq!(*qos, QoS::AtLeastOnce);
+                assert!(!*retained);
+                let published: Response = serde_json::from_slice(payload).unwrap();
+                assert_eq!(published, response);
+                assert_eq!(published.status, Status::Succeeded);
+            }
+            other => panic!("expected Publish, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn error_propagation() {
+        let client = MockClient {
+            publish_fn: Box::new(|| Err(Box::new(mock_error()))),
+            ..Default::default()
+        };
+        let response = Response::failed("msg-456".to_string(), None, "nope".to_string());
+        let result = device::publish_command_response(&client, "dvc_123", &response).await;
+        assert!(result.is_err());
+    }
+}
+
+mod subscribe_probe {
+    use super::*;
+
+    #[tokio::test]
+    async fn happy_path() {
+        let client = MockClient::default();
+        device::subscribe_probe(&client, "dvc_123").await.unwrap();
+
+        let calls = client.get_calls();
+        assert_eq!(calls.len(), 1);
+        assert!(matches!(
+            &calls[0],
+            MockCall::Subscribe { topic, qos }
+                if topic == "v1/probe/devices/dvc_123" && *qos == QoS::AtLeastOnce
+        ));
+    }
+
+    #[tokio::test]
+    async fn error_propagation() {
+        let client = MockClient {
+            subscribe_fn: Box::new(|| Err(Box::new(mock_error()))),
+            ..Default::default()
+        };
+        let result = device::subscribe_probe(&client, "dvc_123").await;
+        assert!(result.is_err());
+    }
+}
+
+mod publish_probe {
+    use super::*;
+    use miru_agent::mqtt::device::Ping;
+
+    #[tokio::test]
+    async fn happy_path() {
+        let client = MockClient::default();
+        device::publish_probe(&client, "dvc_123", "probe-1".to_string())
+            .await
+            .unwrap();
+
+        let calls = client.get_calls();
+        assert_eq!(calls.len(), 1);
+        match &calls[0] {
+            MockCall::Publish {
+                topic,
+                qos,
+                retained,
+                payload,
+            } => {
+                assert_eq!(topic, "v1/probe/devices/dvc_123");
+                assert_eq!(*qos, QoS::AtLeastOnce);
+                assert!(!*retained);
+                let probe: Ping = serde_json::from_slice(payload).unwrap();
+                assert_eq!(probe.message_id, "probe-1");
+            }
+            other => panic!("expected Publish, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn error_propagation() {
+        let client = MockClient {
+            publish_fn: Box::new(|| Err(Box::new(mock_error()))),
+            ..Default::default()
+        };
+        let result = device::publish_probe(&client, "dvc_123", "probe-1".to_string()).await;
+        assert!(result.is_err());
+    }
+}
+
+mod last_will {
+    use super::*;
+    use miru_agent::mqtt::device::{ConnectionState, ConnectionStatus, OfflineReason};
+
+    #[test]
+    fn marks_the_connection_lost() {
+        let will = device::last_will("dvc_123");
+        assert_eq!(will.topic, "v1/state/devices/dvc_123");
+        let state: ConnectionState = serde_json::from_slice(&will.payload).unwrap();
+        assert_eq!(state.status, ConnectionStatus::Offline);
+        assert_eq!(state.reason, Some(OfflineReason::ConnectionLost));
+        assert_eq!(state.agent_version, miru_agent::version::VERSION);
+        assert_eq!(state.timestamp, None);
+    }
+}
+
+mod publish_state {
+    use super::*;
+    use miru_agent::mqtt::device::{ConnectionState, OfflineReason};
+
+    #[tokio::test]
+    async fn happy_path() {
+        let client = MockClient::default();
+        let stopped = ConnectionState::offline(OfflineReason::Stopped);
+        device::publish_state(&client, "dvc_123", &stopped)
+            .await
+            .unwrap();
+
+        let calls = client.get_calls();
+        assert_eq!(calls.len(), 1);
+        match &calls[0] {
+            MockCall::Publish {
+                topic,
+                qos,
+                retained,
+                payload,
+            } => {
+                assert_eq!(topic, "v1/state/devices/dvc_123");
+                assert_eq!(*qos, QoS::AtLeastOnce);
+                assert!(*retained);
+                let json: serde_json::Value = serde_json::from_slice(payload).unwrap();
+                assert_eq!(json["status"], "offline");
+                assert_eq!(json["reason"], "stopped");
+                assert!(json["timestamp"].is_string());
+            }
+            other => panic!("expected Publish, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn online_has_no_reason() {
+        let client = MockClient::default();
+        device::publish_state(&client, "dvc_123", &ConnectionState::online())
+            .await
+            .unwrap();
+
+        let calls = client.get_calls();
+        let MockCall::Publish { payload, .. } = &calls[0] else {
+            panic!("expected Publish, got {:?}", calls[0]);
+        };
+        let json: serde_json::Value = serde_json::from_slice(payload).unwrap();
+        assert_eq!(json["status"], "online");
+        assert_eq!(json["agent_version"], miru_agent::version::VERSION);
+        assert!(json.get("reason").is_none());
+    }
+
+    #[tokio::test]
+    async fn error_propagation() {
+        let client = MockClient {
+            publish_fn: Box::new(|| Err(Box::new(mock_error()))),
+            ..Default::default()
+        };
+        let online = device::ConnectionState::online();
+        let result = device::publish_state(&client, "dvc_123", &online).await;
+        assert!(result.is_err());
+    }
+}
+
+mod qos {
+    use super::*;
+    use miru_agent::mqtt::command;
+    use miru_agent::mqtt::device::{ConnectionState, OfflineReason};
+
+    fn qos_of(call: &MockCall) -> QoS {
+        match call {
+            MockCall::Publish { qos, .. } | MockCall::Subscribe { qos, .. } => *qos,
+            call => panic!("unexpected call {call:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn uses_the_client_qos_for_each_topic_class() {
+        let client = MockClient {
+            qos: QoSLevels {
+                sync: QoS::ExactlyOnce,
+                ping: QoS::AtMostOnce,
+                commands: QoS::AtLeastOnce,
+                state: QoS::ExactlyOnce,
+            },
+            ..Default::default()
+        };
+        device::subscribe_sync(&client, "dvc_123").await.unwrap();
+        device::publish_sync(&client, "dvc_123").await.unwrap();
+        device::subscribe_ping(&client, "dvc_123").await.unwrap();
+        device::publish_pong(&client, "dvc_123", "msg_1".to_string())
+            .await
+            .unwrap();
+        device::subscribe_probe(&client, "dvc_123").await.unwrap();
+        device::publish_probe(&client, "dvc_123", "msg_2".to_string())
+            .await
+            .unwrap();
+        device::subscribe_command(&client, "dvc_123").await.unwrap();
+        let response = command::Response::failed("msg_3".to_string(), None, "e".to_string());
+        device::publish_command_response(&client, "dvc_123", &response)
+            .await
+            .unwrap();
+        let state = ConnectionState::offline(OfflineReason::Stopped);
+        device::publish_state(&client, "dvc_123", &state)
+            .await
+            .unwrap();
+
+        let actual: Vec<QoS> = client.get_calls().iter().map(qos_of).collect();
+        assert_eq!(
+            actual,
+            vec![
+                QoS::ExactlyOnce,
+                QoS::ExactlyOnce,
+                QoS::AtMostOnce,
+                QoS::AtMostOnce,
+                QoS::AtMostOnce,
+                QoS::AtMostOnce,
+                QoS::AtLeastOnce,
+                QoS::AtLeastOnce,
+                QoS::ExactlyOnce,
+            ]
+        );
+    }
+}
@@ -1,5 +1,8 @@
 pub mod client;
+pub mod command;
 pub mod device;
 pub mod errors;
 pub mod options;
+pub mod probe;
 pub mod topic;
+pub mod websocket;
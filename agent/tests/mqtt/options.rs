@@ -2,9 +2,15 @@
 use std::time::Duration;
 
 // internal crates
-use miru_agent::mqtt::options::{ConnectAddress, Credentials, Options, Protocol, Timeouts};
+use miru_agent::filesys;
+use miru_agent::mqtt::options::{
+    ConnectAddress, Credentials, LastWill, Options, Protocol, QoSLevels, Timeouts, Tls,
+};
 use miru_agent::network::MqttHost;
 
+// external crates
+use rumqttc::QoS;
+
 mod protocol_display {
     use super::*;
 
@@ -156,6 +162,18 @@ mod credentials {
         assert_eq!(creds.username, "miru-agent");
         assert_eq!(creds.password, "miru-agent-password");
     }
+
+    #[test]
+    fn debug_redacts_password() {
+        let creds = Credentials {
+            username: "device".to_string(),
+            password: "secret-value".to_string(),
+        };
+        let debug_output = format!("{creds:?}");
+        assert!(debug_output.contains("device"));
+        assert!(debug_output.contains("[REDACTED]"));
+        assert!(!debug_output.contains("secret-value"));
+    }
 }
 
 mod timeouts {
@@ -191,6 +209,11 @@ mod opts {
             keep_alive: Duration::from_secs(20),
             timeouts: Timeouts::default(),
             capacity: 64,
+            tls: Tls::default(),
+            last_will: None,
+            qos: QoSLevels::default(),
+            clean_session: true,
+            max_inflight: 100,
         };
         assert_eq!(actual, expected);
     }
@@ -205,6 +228,11 @@ mod opts {
             keep_alive: Duration::from_secs(20),
             timeouts: Timeouts::default(),
             capacity: 64,
+            tls: Tls::default(),
+            last_will: None,
+            qos: QoSLevels::default(),
+            clean_session: true,
+            max_inflight: 100,
         };
         assert!(matches!(actual.connect_address.protocol(), Protocol::SSL));
         assert_eq!(actual, expected);
@@ -255,4 +283,65 @@ mod opts {
         let opts = Options::default().with_timeouts(timeouts);
         assert_eq!(opts.timeouts.publish, Duration::from_secs(10));
     }
+
+    #[test]
+    fn with_tls() {
+        let tls = Tls {
+            ca_bundle: Some(filesys::File::new("/etc/miru/ca.pem")),
+            identity: None,
+        };
+        let opts = Options::default().with_tls(tls.clone());
+        assert_eq!(opts.tls, tls);
+    }
+
+    #[test]
+    fn with_last_will() {
+        let last_will = LastWill {
+            topic: "v1/state/devices/dvc_123".to_string(),
+            payload: b"offline".to_vec(),
+        };
+        let opts = Options::default().with_last_will(last_will.clone());
+        assert_eq!(opts.last_will, Some(last_will));
+    }
+
+    #[test]
+    fn with_keep_alive() {
+        let opts = Options::default().with_keep_alive(Duration::from_secs(60));
+        assert_eq!(opts.keep_alive, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn with_qos() {
+        let qos = QoSLevels {
+            sync: QoS::ExactlyOnce,
+            ..Default::default()
+        };
+        let opts = Options::default().with_qos(qos);
+        assert_eq!(opts.qos, qos);
+    }
+
+    #[test]
+    fn with_clean_session() {
+        let opts = Options::default().with_clean_session(false);
+        assert!(!opts.clean_session);
+    }
+
+    #[test]
+    fn with_max_inflight() {
+        let opts = Options::default().with_max_inflight(10);
+        assert_eq!(opts.max_inflight, 10);
+    }
+}
+
+mod qos_levels {
+    use super::*;
+
+    #[test]
+    fn default() {
+        let qos = QoSLevels::default();
+        assert_eq!(qos.sync, QoS::AtLeastOnce);
+        assert_eq!(qos.ping, QoS::AtLeastOnce);
+        assert_eq!(qos.commands, QoS::AtLeastOnce);
+        assert_eq!(qos.state, QoS::AtLeastOnce);
+    }
 }
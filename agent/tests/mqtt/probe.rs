@@ -0,0 +1,97 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::mqtt::probe::{Action, Options, Probe};
+
+// external crates
+use tokio::time::Instant;
+
+fn options() -> Options {
+    Options {
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(5),
+        reconnect_after: 2,
+    }
+}
+
+#[test]
+fn first_probe_waits_an_interval() {
+    let now = Instant::now();
+    let probe = Probe::new(options(), now);
+    assert_eq!(probe.deadline(), now + Duration::from_secs(60));
+    assert_eq!(probe.pending(), None);
+    assert_eq!(probe.failure_streak(), 0);
+}
+
+#[test]
+fn answered_probe() {
+    let now = Instant::now();
+    let mut probe = Probe::new(options(), now);
+
+    let Action::Send(message_id) = probe.fire(now) else {
+        panic!("expected a probe to be sent");
+    };
+    assert_eq!(probe.pending(), Some(message_id.as_str()));
+    assert_eq!(probe.deadline(), now + Duration::from_secs(5));
+
+    let later = now + Duration::from_secs(1);
+    assert!(probe.received(&message_id, later));
+    assert_eq!(probe.pending(), None);
+    assert_eq!(probe.deadline(), later + Duration::from_secs(60));
+
+    // the next probe has a new message id
+    let Action::Send(next_id) = probe.fire(later) else {
+        panic!("expected a probe to be sent");
+    };
+    assert_ne!(next_id, message_id);
+}
+
+#[test]
+fn unanswered_probes_fail() {
+    let now = Instant::now();
+    let mut probe = Probe::new(options(), now);
+
+    let Action::Send(first_id) = probe.fire(now) else {
+        panic!("expected a probe to be sent");
+    };
+    assert_eq!(probe.fire(now), Action::Failed { streak: 1 });
+    assert_eq!(probe.pending(), None);
+    // a failed probe is retried right away
+    assert_eq!(probe.deadline(), now);
+
+    let Action::Send(second_id) = probe.fire(now) else {
+        panic!("expected a probe to be sent");
+    };
+    assert_eq!(probe.fire(now), Action::Failed { streak: 2 });
+    assert_eq!(probe.failure_streak(), 2);
+
+    // late replies don't count
+    assert!(!probe.received(&first_id, now));
+    assert!(!probe.received(&second_id, now));
+    assert_eq!(probe.failure_streak(), 2);
+
+    // an answered probe ends the streak
+    let Action::Send(third_id) = probe.fire(now) else {
+        panic!("expected a probe to be sent");
+    };
+    assert!(probe.received(&third_id, now));
+    assert_eq!(probe.failure_streak(), 0);
+}
+
+#[test]
+fn reset() {
+    let now = Instant::now();
+    let mut probe = Probe::new(options(), now);
+    let Action::Send(message_id) = probe.fire(now) else {
+        panic!("expected a probe to be sent");
+    };
+    assert_eq!(probe.fire(now), Action::Failed { streak: 1 });
+
+    let later = now + Duration::from_secs(10);
+    probe.reset(later);
+    assert_eq!(probe.pending(), None);
+    assert_eq!(probe.failure_streak(), 0);
+    assert_eq!(probe.deadline(), later + Duration::from_secs(60));
+    assert!(!probe.received(&message_id, later));
+}
@@ -25,6 +25,28 @@ mod format {
             "v1/resp/devices/dev-001/pong"
         );
     }
+
+    #[test]
+    fn device_command_format() {
+        assert_eq!(
+            topics::device_command("dev-001"),
+            "v1/cmd/devices/dev-001/command"
+        );
+        assert_eq!(
+            topics::device_command_response("dev-001"),
+            "v1/resp/devices/dev-001/command"
+        );
+    }
+
+    #[test]
+    fn device_probe_format() {
+        assert_eq!(topics::device_probe("dev-001"), "v1/probe/devices/dev-001");
+    }
+
+    #[test]
+    fn device_state_format() {
+        assert_eq!(topics::device_state("dev-001"), "v1/state/devices/dev-001");
+    }
 }
 
 mod parse_subscription {
@@ -48,6 +70,31 @@ mod parse_subscription {
         );
     }
 
+    #[test]
+    fn command() {
+        let topic = topics::device_command("123");
+        assert_eq!(
+            topics::parse_subscription("123", &topic),
+            topics::SubscriptionTopics::Command
+        );
+
+        // responses are published, not subscribed to
+        let topic = topics::device_command_response("123");
+        assert_eq!(
+            topics::parse_subscription("123", &topic),
+            topics::SubscriptionTopics::Unknown
+        );
+    }
+
+    #[test]
+    fn probe() {
+        let topic = topics::device_probe("123");
+        assert_eq!(
+            topics::parse_subscription("123", &topic),
+            topics::SubscriptionTopics::Probe
+        );
+    }
+
     #[test]
     fn pong_is_unknown() {
         // pong is a response topic, not a subscription topic
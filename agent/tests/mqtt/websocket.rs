@@ -0,0 +1,282 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::mqtt::websocket::{
+    accept_key, check_handshake_response, decode_frame, encode_frame, handshake_request, Bridge,
+    Frame, Opcode, Options, Target, MAX_FRAME_LEN,
+};
+
+// external crates
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub mod handshake {
+    use super::*;
+
+    #[test]
+    fn accept_key_matches_rfc_6455() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn request_asks_for_mqtt() {
+        let request = handshake_request("mqtt.mirurobotics.com", "/mqtt", "key");
+        assert!(request.starts_with("GET /mqtt HTTP/1.1\r\n"));
+        assert!(request.contains("Host: mqtt.mirurobotics.com\r\n"));
+        assert!(request.contains("Upgrade: websocket\r\n"));
+        assert!(request.contains("Sec-WebSocket-Key: key\r\n"));
+        assert!(request.contains("Sec-WebSocket-Protocol: mqtt\r\n"));
+        assert!(request.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn response_must_switch_protocols() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let accepted = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nsec-websocket-accept: {}\r\n\r\n",
+            accept_key(key)
+        );
+        check_handshake_response(&accepted, key).unwrap();
+
+        let refused = "HTTP/1.1 403 Forbidden\r\n\r\n";
+        assert!(check_handshake_response(refused, key).is_err());
+
+        let wrong_key = "HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Accept: nope\r\n\r\n";
+        assert!(check_handshake_response(wrong_key, key).is_err());
+
+        let no_key = "HTTP/1.1 101 Switching Protocols\r\n\r\n";
+        assert!(check_handshake_response(no_key, key).is_err());
+    }
+}
+
+pub mod frames {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 1, 125, 126, 65535, 65536] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            for mask in [None, Some([1, 2, 3, 4])] {
+                let mut buf = encode_frame(Opcode::Binary, &payload, mask);
+                let frame = decode_frame(&mut buf).unwrap().unwrap();
+                assert_eq!(
+                    frame,
+                    Frame {
+                        opcode: Opcode::Binary,
+                        payload: payload.clone(),
+                    }
+                );
+                assert!(buf.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn masked_frames_are_masked() {
+        let frame = encode_frame(Opcode::Binary, b"abcd", Some([1, 1, 1, 1]));
+        assert_eq!(frame[0], 0x82);
+        assert_eq!(frame[1], 0x80 | 4);
+        assert_eq!(&frame[2..6], &[1, 1, 1, 1]);
+        assert_eq!(&frame[6..], b"`cbe");
+    }
+
+    #[test]
+    fn incomplete_frames_wait_for_more() {
+        let encoded = encode_frame(Opcode::Binary, &[7; 300], Some([9, 8, 7, 6]));
+        for len in 0..encoded.len() {
+            let mut buf = encoded[..len].to_vec();
+            assert_eq!(decode_frame(&mut buf).unwrap(), None);
+            assert_eq!(buf.len(), len);
+        }
+    }
+
+    #[test]
+    fn consecutive_frames() {
+        let mut buf = encode_frame(Opcode::Ping, b"ping", None);
+        buf.extend(encode_frame(Opcode::Binary, b"data", None));
+        buf.extend_from_slice(&[0x82]);
+
+        let ping = decode_frame(&mut buf).unwrap().unwrap();
+        assert_eq!(ping.opcode, Opcode::Ping);
+        assert_eq!(ping.payload, b"ping");
+        let data = decode_frame(&mut buf).unwrap().unwrap();
+        assert_eq!(data.opcode, Opcode::Binary);
+        assert_eq!(data.payload, b"data");
+        assert_eq!(decode_frame(&mut buf).unwrap(), None);
+        assert_eq!(buf, vec![0x82]);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut buf = vec![0x82, 127];
+        buf.extend_from_slice(&(MAX_FRAME_LEN + 1).to_be_bytes());
+        assert!(decode_frame(&mut buf).is_err());
+    }
+
+    #[test]
+    fn unknown_opcodes_are_rejected() {
+        let mut buf = vec![0x83, 0];
+        assert!(decode_frame(&mut buf).is_err());
+    }
+}
+
+pub mod bridge {
+    use super::*;
+
+    /// Reads frames off the stream until one is complete.
+    async fn read_frame(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Option<Frame> {
+        let mut chunk = [0u8; 1024];
+        loop {
+            if let Some(frame) = decode_frame(buf).unwrap() {
+                return Some(frame);
+            }
+            let n = stream.read(&mut chunk).await.unwrap();
+            if n == 0 {
+                return None;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// A WebSocket server which pings each client once and echoes its binary frames.
+    /// Returns the requests it received.
+    async fn echo_server(status: &'static str) -> (u16, tokio::sync::mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = tokio::sync::mpsc::channel(8);
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let n = stream.read(&mut chunk).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&chunk[..n]);
+                    }
+                    let request = String::from_utf8(request).unwrap();
+                    let key = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Sec-WebSocket-Key: "))
+                        .unwrap()
+                        .to_string();
+                    tx.send(request.clone()).await.unwrap();
+                    let response = format!(
+                        "HTTP/1.1 {status}\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                        accept_key(&key)
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                    stream
+                        .write_all(&encode_frame(Opcode::Ping, b"hi", None))
+                        .await
+                        .unwrap();
+
+                    let mut buf = Vec::new();
+                    while let Some(frame) = read_frame(&mut stream, &mut buf).await {
+                        match frame.opcode {
+                            Opcode::Binary => {
+                                let echo = encode_frame(Opcode::Binary, &frame.payload, None);
+                                stream.write_all(&echo).await.unwrap();
+                            }
+                            Opcode::Pong => {
+                                tx.send(format!("pong {:?}", frame.payload)).await.unwrap();
+                            }
+                            _ => return,
+                        }
+                    }
+                });
+            }
+        });
+        (port, rx)
+    }
+
+    fn target(port: u16) -> Target {
+        Target {
+            host: "127.0.0.1".to_string(),
+            options: Options {
+                port,
+                path: "/mqtt".to_string(),
+                handshake_timeout: Duration::from_secs(5),
+            },
+            tls: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn tunnels_bytes_both_ways() {
+        let (port, mut requests) = echo_server("101 Switching Protocols").await;
+        let bridge = Bridge::spawn(target(port)).await.unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.port()))
+            .await
+            .unwrap();
+        client.write_all(b"mqtt connect packet").await.unwrap();
+        let mut echoed = [0u8; 19];
+        tokio::time::timeout(Duration::from_secs(5), client.read_exact(&mut echoed))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&echoed, b"mqtt connect packet");
+
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("GET /mqtt HTTP/1.1\r\n"));
+        assert!(request.contains("Sec-WebSocket-Protocol: mqtt\r\n"));
+        let pong = requests.recv().await.unwrap();
+        assert_eq!(pong, format!("pong {:?}", b"hi".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn refused_upgrade_closes_the_connection() {
+        let (port, _requests) = echo_server("403 Forbidden").await;
+        let bridge = Bridge::spawn(target(port)).await.unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.port()))
+            .await
+            .unwrap();
+        client.write_all(b"mqtt connect packet").await.unwrap();
+        let mut buf = [0u8; 16];
+        let n = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap_or(0);
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn unreachable_broker_closes_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let bridge = Bridge::spawn(target(port)).await.unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.port()))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 16];
+        let n = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap_or(0);
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_bridge_stops_accepting() {
+        let (port, _requests) = echo_server("101 Switching Protocols").await;
+        let bridge = Bridge::spawn(target(port)).await.unwrap();
+        let bridge_port = bridge.port();
+        drop(bridge);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(TcpStream::connect(("127.0.0.1", bridge_port))
+            .await
+            .is_err());
+    }
+}
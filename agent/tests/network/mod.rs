@@ -1,3 +1,6 @@
+pub mod egress;
+pub mod identity;
+
 // internal crates
 use miru_agent::network::{BackendUrl, MqttHost};
 
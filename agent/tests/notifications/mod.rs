@@ -0,0 +1,3 @@
+pub mod model;
+pub mod signing;
+pub mod sinks;
@@ -0,0 +1,129 @@
+// internal crates
+use miru_agent::events::model::{self as events, Event};
+use miru_agent::notifications::{Notification, Severity};
+
+// external crates
+use chrono::Utc;
+use serde_json::json;
+
+fn event(event_type: &str, data: serde_json::Value) -> Event {
+    Event {
+        id: 1,
+        event_type: event_type.to_string(),
+        occurred_at: Utc::now(),
+        data,
+    }
+}
+
+pub mod severity {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        assert!(Severity::Info < Severity::Warning);
+        assert!(Severity::Warning < Severity::Critical);
+        assert_eq!(Severity::default(), Severity::Warning);
+    }
+
+    #[test]
+    fn serialize_deserialize() {
+        let serialized = serde_json::to_string(&Severity::Critical).unwrap();
+        assert_eq!(serialized, "\"critical\"");
+        let deserialized = serde_json::from_str::<Severity>(&serialized).unwrap();
+        assert_eq!(deserialized, Severity::Critical);
+        assert!(serde_json::from_str::<Severity>("\"fatal\"").is_err());
+    }
+}
+
+pub mod from_event {
+    use super::*;
+
+    #[test]
+    fn deployment_failed() {
+        let event = event(
+            events::DEPLOYMENT_FAILED,
+            json!({
+                "deployment_id": "dpl-1",
+                "release_id": "rls-1",
+                "attempts": 5,
+                "error": "disk full",
+            }),
+        );
+        let notification = Notification::from_event(&event).unwrap();
+        assert_eq!(notification.event_type, events::DEPLOYMENT_FAILED);
+        assert_eq!(notification.severity, Severity::Critical);
+        assert_eq!(notification.message, "deployment dpl-1 failed: disk full");
+        assert_eq!(notification.occurred_at, event.occurred_at);
+        assert_eq!(notification.data, event.data);
+    }
+
+    #[test]
+    fn auth_revoked() {
+        let event = event(events::AUTH_REVOKED, json!({"error": "unauthorized"}));
+        let notification = Notification::from_event(&event).unwrap();
+        assert_eq!(notification.severity, Severity::Critical);
+        assert!(notification.message.contains("unauthorized"));
+    }
+
+    #[test]
+    fn agent_safe_mode() {
+        let event = event(events::AGENT_SAFE_MODE, json!({"recent_crashes": 5}));
+        let notification = Notification::from_event(&event).unwrap();
+        assert_eq!(notification.severity, Severity::Critical);
+        assert!(notification.message.contains("after 5 crashes"));
+    }
+
+    #[test]
+    fn disk_low() {
+        let event = event(
+            events::DISK_LOW,
+            json!({"path": "/srv/miru", "available_bytes": 10, "total_bytes": 100}),
+        );
+        let notification = Notification::from_event(&event).unwrap();
+        assert_eq!(notification.severity, Severity::Warning);
+        assert_eq!(
+            notification.message,
+            "disk space is low on /srv/miru: 10 of 100 bytes available"
+        );
+    }
+
+    #[test]
+    fn deployment_lifecycle_is_info() {
+        for event_type in [events::DEPLOYMENT_DEPLOYED, events::DEPLOYMENT_REMOVED] {
+            let event = event(event_type, json!({"deployment_id": "dpl-1"}));
+            let notification = Notification::from_event(&event).unwrap();
+            assert_eq!(notification.severity, Severity::Info);
+        }
+    }
+
+    #[test]
+    fn sync_failed() {
+        let event = event(
+            events::SYNC_FAILED,
+            json!({"failure_class": "server", "streak": 2, "error": "503"}),
+        );
+        let notification = Notification::from_event(&event).unwrap();
+        assert_eq!(notification.severity, Severity::Warning);
+        assert_eq!(
+            notification.message,
+            "syncing with the backend failed (server failure): 503"
+        );
+    }
+
+    #[test]
+    fn config_changed_is_info() {
+        let event = event(
+            events::CONFIG_CHANGED,
+            json!({"changes": [{"filepath": "/a.json"}, {"filepath": "/b.json"}]}),
+        );
+        let notification = Notification::from_event(&event).unwrap();
+        assert_eq!(notification.severity, Severity::Info);
+        assert_eq!(notification.message, "2 deployed config files changed");
+    }
+
+    #[test]
+    fn unknown_event_type() {
+        let event = event("device.rebooted", json!({}));
+        assert!(Notification::from_event(&event).is_none());
+    }
+}
@@ -0,0 +1,291 @@
+// internal crates
+use miru_agent::filesys::{PathExt, WriteOptions};
+use miru_agent::notifications::signing::{self, Key, Keyring};
+use miru_agent::testkit;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+const URL: &str = "https://example.com/alerts";
+
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::<Utc>::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+}
+
+fn tolerance() -> TimeDelta {
+    TimeDelta::minutes(5)
+}
+
+pub mod rotate {
+    use super::*;
+
+    #[test]
+    fn first_key() {
+        let mut keyring = Keyring::default();
+        let key = keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+
+        assert_eq!(key.created_at, at(0));
+        assert_eq!(key.retires_at, None);
+        assert_eq!(key.id.len(), 12);
+        // 32 random bytes, base64 without padding
+        assert_eq!(key.secret.len(), 43);
+        assert_eq!(keyring.active(URL, at(0)), vec![&key]);
+    }
+
+    #[test]
+    fn previous_key_signs_for_the_grace_period() {
+        let mut keyring = Keyring::default();
+        let old = keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+        let new = keyring.rotate(URL, TimeDelta::hours(1), at(10)).unwrap();
+        assert_ne!(old.id, new.id);
+        assert_ne!(old.secret, new.secret);
+
+        let active: Vec<&str> = keyring
+            .active(URL, at(10))
+            .iter()
+            .map(|k| k.id.as_str())
+            .collect();
+        assert_eq!(active, vec![old.id.as_str(), new.id.as_str()]);
+
+        let active: Vec<&str> = keyring
+            .active(URL, at(10 + 3600))
+            .iter()
+            .map(|k| k.id.as_str())
+            .collect();
+        assert_eq!(active, vec![new.id.as_str()]);
+    }
+
+    #[test]
+    fn zero_grace_retires_previous_keys_immediately() {
+        let mut keyring = Keyring::default();
+        keyring.rotate(URL, TimeDelta::zero(), at(0)).unwrap();
+        let new = keyring.rotate(URL, TimeDelta::zero(), at(10)).unwrap();
+        assert_eq!(keyring.active(URL, at(10)), vec![&new]);
+    }
+
+    #[test]
+    fn does_not_extend_a_retiring_key() {
+        let mut keyring = Keyring::default();
+        let first = keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+        keyring.rotate(URL, TimeDelta::seconds(60), at(10)).unwrap();
+        keyring.rotate(URL, TimeDelta::hours(1), at(20)).unwrap();
+
+        let keys = &keyring.destinations[URL];
+        let first = keys.iter().find(|k| k.id == first.id).unwrap();
+        assert_eq!(first.retires_at, Some(at(70)));
+    }
+
+    #[test]
+    fn drops_retired_keys() {
+        let mut keyring = Keyring::default();
+        keyring.rotate(URL, TimeDelta::zero(), at(0)).unwrap();
+        keyring.rotate(URL, TimeDelta::zero(), at(10)).unwrap();
+        keyring.rotate(URL, TimeDelta::zero(), at(20)).unwrap();
+        // the latest key and the one it just replaced
+        assert_eq!(keyring.destinations[URL].len(), 2);
+    }
+
+    #[test]
+    fn destinations_are_independent() {
+        let mut keyring = Keyring::default();
+        let a = keyring.rotate(URL, TimeDelta::zero(), at(0)).unwrap();
+        let b = keyring
+            .rotate("https://other.example.com", TimeDelta::zero(), at(10))
+            .unwrap();
+        assert_eq!(keyring.active(URL, at(10)), vec![&a]);
+        assert_eq!(
+            keyring.active("https://other.example.com", at(10)),
+            vec![&b]
+        );
+    }
+}
+
+pub mod revoke {
+    use super::*;
+
+    #[test]
+    fn removes_every_key() {
+        let mut keyring = Keyring::default();
+        keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+        keyring.rotate(URL, TimeDelta::hours(1), at(10)).unwrap();
+
+        assert!(keyring.revoke(URL));
+        assert!(keyring.active(URL, at(10)).is_empty());
+        assert!(!keyring.revoke(URL));
+    }
+}
+
+pub mod sign {
+    use super::*;
+
+    #[test]
+    fn unsigned_without_keys() {
+        let keyring = Keyring::default();
+        assert_eq!(keyring.sign(URL, "{}", at(0)).unwrap(), None);
+    }
+
+    #[test]
+    fn known_signature() {
+        let keyring = Keyring {
+            destinations: [(
+                URL.to_string(),
+                vec![Key {
+                    id: "key1".to_string(),
+                    secret: "secret".to_string(),
+                    created_at: at(0),
+                    retires_at: None,
+                }],
+            )]
+            .into(),
+        };
+        let header = keyring.sign(URL, "{}", at(0)).unwrap().unwrap();
+        // printf '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            header,
+            "t=1700000000,v1=key1:b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[test]
+    fn verifies_with_every_active_key() {
+        let mut keyring = Keyring::default();
+        let old = keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+        let new = keyring.rotate(URL, TimeDelta::hours(1), at(10)).unwrap();
+
+        let header = keyring.sign(URL, "body", at(20)).unwrap().unwrap();
+        assert_eq!(header.matches("v1=").count(), 2);
+        for key in [&old, &new] {
+            assert!(signing::verify(&header, "body", &key.secret, tolerance(), at(20)).unwrap());
+        }
+    }
+
+    #[test]
+    fn retired_keys_stop_signing() {
+        let mut keyring = Keyring::default();
+        let old = keyring.rotate(URL, TimeDelta::seconds(60), at(0)).unwrap();
+        let new = keyring.rotate(URL, TimeDelta::seconds(60), at(10)).unwrap();
+
+        let header = keyring.sign(URL, "body", at(100)).unwrap().unwrap();
+        assert!(!signing::verify(&header, "body", &old.secret, tolerance(), at(100)).unwrap());
+        assert!(signing::verify(&header, "body", &new.secret, tolerance(), at(100)).unwrap());
+    }
+}
+
+pub mod verify {
+    use super::*;
+
+    fn signed() -> (String, Key) {
+        let mut keyring = Keyring::default();
+        let key = keyring.rotate(URL, TimeDelta::zero(), at(0)).unwrap();
+        (keyring.sign(URL, "body", at(0)).unwrap().unwrap(), key)
+    }
+
+    #[test]
+    fn rejects_tampered_body() {
+        let (header, key) = signed();
+        assert!(!signing::verify(&header, "bodY", &key.secret, tolerance(), at(0)).unwrap());
+    }
+
+    #[test]
+    fn rejects_the_wrong_secret() {
+        let (header, _) = signed();
+        assert!(!signing::verify(&header, "body", "wrong", tolerance(), at(0)).unwrap());
+    }
+
+    #[test]
+    fn rejects_stale_timestamps() {
+        let (header, key) = signed();
+        assert!(signing::verify(&header, "body", &key.secret, tolerance(), at(300)).unwrap());
+        assert!(!signing::verify(&header, "body", &key.secret, tolerance(), at(301)).unwrap());
+        assert!(!signing::verify(&header, "body", &key.secret, tolerance(), at(-301)).unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let (_, key) = signed();
+        for header in [
+            "",
+            "v1=key:00",
+            "t=abc,v1=key:00",
+            "t=1700000000",
+            "t=1700000000,v1=zz",
+            "t=1700000000,v1=key:0g",
+        ] {
+            assert!(
+                !signing::verify(header, "body", &key.secret, tolerance(), at(0)).unwrap(),
+                "{header}"
+            );
+        }
+    }
+}
+
+pub mod persistence {
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_file_is_empty() {
+        let dir = testkit::temp_dir("webhook_keys_missing").await;
+        let keyring = Keyring::load(&dir.file("webhook_keys.json")).await.unwrap();
+        assert_eq!(keyring, Keyring::default());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = testkit::temp_dir("webhook_keys_round_trip").await;
+        let file = dir.file("webhook_keys.json");
+        let mut keyring = Keyring::default();
+        keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+        keyring.rotate(URL, TimeDelta::hours(1), at(10)).unwrap();
+
+        keyring.save(&file).await.unwrap();
+        assert_eq!(Keyring::load(&file).await.unwrap(), keyring);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(file.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        dir.delete().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn replaces_readable_files_privately() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = testkit::temp_dir("webhook_keys_private").await;
+        let file = dir.file("webhook_keys.json");
+        let tmp = dir.file("webhook_keys.json.tmp");
+        for stale in [&file, &tmp] {
+            stale
+                .write_string("{}", WriteOptions::OVERWRITE_ATOMIC)
+                .await
+                .unwrap();
+            stale
+                .set_permissions(std::fs::Permissions::from_mode(0o644))
+                .await
+                .unwrap();
+        }
+        let mut keyring = Keyring::default();
+        keyring.rotate(URL, TimeDelta::hours(1), at(0)).unwrap();
+
+        keyring.save(&file).await.unwrap();
+
+        assert_eq!(Keyring::load(&file).await.unwrap(), keyring);
+        let mode = std::fs::metadata(file.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!tmp.exists());
+        dir.delete().await.unwrap();
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let mut keyring = Keyring::default();
+        let key = keyring.rotate(URL, TimeDelta::zero(), at(0)).unwrap();
+        let debug = format!("{keyring:?}");
+        assert!(!debug.contains(&key.secret));
+        assert!(debug.contains("<redacted>"));
+    }
+}
@@ -0,0 +1,484 @@
+// standard crates
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::cooldown;
+use miru_agent::filesys::PathExt;
+use miru_agent::network::egress;
+use miru_agent::notifications::{
+    signing, webhook, Keyring, MqttMessage, Notification, NotificationsErr, Retry, Severity, Sink,
+    Target,
+};
+use miru_agent::testkit;
+
+// external crates
+use axum::http::HeaderMap;
+use axum::routing::post;
+use axum::Router;
+use chrono::{TimeDelta, Utc};
+use serde_json::json;
+use tokio::sync::mpsc;
+
+fn notification(severity: Severity) -> Notification {
+    Notification {
+        event_type: "deployment.failed".to_string(),
+        severity,
+        message: "deployment dpl-1 failed: disk full".to_string(),
+        occurred_at: Utc::now(),
+        data: json!({"deployment_id": "dpl-1"}),
+    }
+}
+
+fn sink(target: Target) -> Sink {
+    Sink {
+        target,
+        min_severity: Severity::Warning,
+        events: Vec::new(),
+        retry: None,
+    }
+}
+
+async fn deliver(sink: &Sink, notification: &Notification) -> Result<(), NotificationsErr> {
+    deliver_with_keys(sink, notification, &Keyring::default()).await
+}
+
+async fn deliver_with_keys(
+    sink: &Sink,
+    notification: &Notification,
+    keyring: &Keyring,
+) -> Result<(), NotificationsErr> {
+    let webhook_client = webhook::Client::new().unwrap();
+    let (mqtt_outbox, _rx) = mpsc::channel(1);
+    sink.deliver(notification, &webhook_client, &mqtt_outbox, keyring)
+        .await
+}
+
+pub mod accepts {
+    use super::*;
+
+    #[test]
+    fn filters_by_min_severity() {
+        let sink = sink(Target::File {
+            path: "/tmp/notifications.jsonl".to_string(),
+        });
+        assert!(!sink.accepts(&notification(Severity::Info)));
+        assert!(sink.accepts(&notification(Severity::Warning)));
+        assert!(sink.accepts(&notification(Severity::Critical)));
+    }
+
+    #[test]
+    fn listed_events_are_accepted_at_any_severity() {
+        let mut sink = sink(Target::File {
+            path: "/tmp/notifications.jsonl".to_string(),
+        });
+        sink.events = vec!["deployment.failed".to_string()];
+        assert!(sink.accepts(&notification(Severity::Info)));
+
+        let mut other = notification(Severity::Critical);
+        other.event_type = "auth.revoked".to_string();
+        assert!(!sink.accepts(&other));
+    }
+}
+
+pub mod retry {
+    use super::*;
+
+    #[test]
+    fn delays_grow_with_the_backoff() {
+        let retry = Retry {
+            max_attempts: 4,
+            backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 3,
+                max_secs: 5,
+            },
+        };
+        assert_eq!(retry.delay(1), Duration::from_secs(1));
+        assert_eq!(retry.delay(2), Duration::from_secs(3));
+        assert_eq!(retry.delay(3), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn max_attempts() {
+        let mut sink = sink(Target::Webhook {
+            url: "http://localhost/alerts".to_string(),
+        });
+        assert_eq!(sink.max_attempts(), 1);
+        sink.retry = Some(Retry {
+            max_attempts: 0,
+            backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: 10,
+            },
+        });
+        assert_eq!(sink.max_attempts(), 1);
+        sink.retry = sink.retry.map(|retry| Retry {
+            max_attempts: 5,
+            ..retry
+        });
+        assert_eq!(sink.max_attempts(), 5);
+    }
+}
+
+pub mod file_sink {
+    use super::*;
+
+    #[tokio::test]
+    async fn appends_json_lines() {
+        let dir = testkit::temp_dir("notifications_file_sink").await;
+        let file = dir.subdir("alerts").file("notifications.jsonl");
+        let sink = sink(Target::File {
+            path: file.path().to_string_lossy().to_string(),
+        });
+
+        let first = notification(Severity::Warning);
+        let second = notification(Severity::Critical);
+        deliver(&sink, &first).await.unwrap();
+        deliver(&sink, &second).await.unwrap();
+
+        let contents = file.read_string().await.unwrap();
+        let lines: Vec<Notification> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, vec![first, second]);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod command_sink {
+    use super::*;
+
+    #[tokio::test]
+    async fn passes_notification_through_env() {
+        let dir = testkit::temp_dir("notifications_command_sink").await;
+        let out = dir.file("out.txt");
+        let script = format!(
+            "echo \"$MIRU_NOTIFICATION_TYPE $MIRU_NOTIFICATION_SEVERITY $MIRU_NOTIFICATION_MESSAGE\" > {}",
+            out.path().display()
+        );
+        let sink = sink(Target::Command {
+            command: vec!["sh".to_string(), "-c".to_string(), script],
+            timeout_secs: 10,
+        });
+
+        deliver(&sink, &notification(Severity::Critical))
+            .await
+            .unwrap();
+
+        let contents = out.read_string().await.unwrap();
+        assert_eq!(
+            contents.trim(),
+            "deployment.failed critical deployment dpl-1 failed: disk full"
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_command() {
+        let sink = sink(Target::Command {
+            command: vec!["false".to_string()],
+            timeout_secs: 10,
+        });
+        let result = deliver(&sink, &notification(Severity::Critical)).await;
+        assert!(matches!(result, Err(NotificationsErr::CommandErr(_))));
+    }
+
+    #[tokio::test]
+    async fn empty_command() {
+        let sink = sink(Target::Command {
+            command: Vec::new(),
+            timeout_secs: 10,
+        });
+        let result = deliver(&sink, &notification(Severity::Critical)).await;
+        assert!(matches!(result, Err(NotificationsErr::CommandErr(_))));
+    }
+
+    #[tokio::test]
+    async fn hung_command_is_killed() {
+        let sink = sink(Target::Command {
+            command: vec!["sleep".to_string(), "30".to_string()],
+            timeout_secs: 1,
+        });
+
+        let started = std::time::Instant::now();
+        let result = deliver(&sink, &notification(Severity::Critical)).await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        match result {
+            Err(NotificationsErr::CommandErr(e)) => assert!(e.msg.contains("timed out")),
+            other => panic!("expected a command error, got {other:?}"),
+        }
+    }
+}
+
+pub mod webhook_sink {
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(Option<String>, String)>>>;
+
+    /// Serves `/alerts`, recording the signature header and body of each request.
+    async fn receiver() -> (mock::Server, Received) {
+        let received = Received::default();
+        let received_for_route = received.clone();
+        let router = Router::new().route(
+            "/alerts",
+            post(move |headers: HeaderMap, body: String| async move {
+                let signature = headers
+                    .get(signing::SIGNATURE_HEADER)
+                    .map(|v| v.to_str().unwrap().to_string());
+                received_for_route.lock().unwrap().push((signature, body));
+                "ok"
+            }),
+        );
+        (mock::run_server(router).await, received)
+    }
+
+    #[tokio::test]
+    async fn posts_notification() {
+        let (server, received) = receiver().await;
+        let sink = sink(Target::Webhook {
+            url: format!("{}/alerts", server.base_url),
+        });
+
+        let notification = notification(Severity::Critical);
+        deliver(&sink, &notification).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (signature, body) = &received[0];
+        assert_eq!(*signature, None);
+        let body: Notification = serde_json::from_str(body).unwrap();
+        assert_eq!(body, notification);
+    }
+
+    #[tokio::test]
+    async fn signs_notification() {
+        let (server, received) = receiver().await;
+        let url = format!("{}/alerts", server.base_url);
+        let mut keyring = Keyring::default();
+        let key = keyring.rotate(&url, TimeDelta::zero(), Utc::now()).unwrap();
+        let sink = sink(Target::Webhook { url });
+
+        deliver_with_keys(&sink, &notification(Severity::Critical), &keyring)
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        let (signature, body) = &received[0];
+        let signature = signature.as_deref().expect("webhook should be signed");
+        assert!(signing::verify(
+            signature,
+            body,
+            &key.secret,
+            TimeDelta::minutes(5),
+            Utc::now()
+        )
+        .unwrap());
+    }
+
+    #[tokio::test]
+    async fn keys_of_other_destinations_are_not_used() {
+        let (server, received) = receiver().await;
+        let mut keyring = Keyring::default();
+        keyring
+            .rotate("https://example.com/alerts", TimeDelta::zero(), Utc::now())
+            .unwrap();
+        let sink = sink(Target::Webhook {
+            url: format!("{}/alerts", server.base_url),
+        });
+
+        deliver_with_keys(&sink, &notification(Severity::Critical), &keyring)
+            .await
+            .unwrap();
+
+        assert_eq!(received.lock().unwrap()[0].0, None);
+    }
+
+    #[tokio::test]
+    async fn sends_no_backend_headers() {
+        let headers = Arc::new(Mutex::new(HeaderMap::new()));
+        let headers_for_route = headers.clone();
+        let router = Router::new().route(
+            "/alerts",
+            post(move |received: HeaderMap| async move {
+                *headers_for_route.lock().unwrap() = received;
+                "ok"
+            }),
+        );
+        let server = mock::run_server(router).await;
+        let url = format!("{}/alerts", server.base_url);
+        let mut keyring = Keyring::default();
+        keyring.rotate(&url, TimeDelta::zero(), Utc::now()).unwrap();
+        let sink = sink(Target::Webhook { url });
+
+        deliver_with_keys(&sink, &notification(Severity::Critical), &keyring)
+            .await
+            .unwrap();
+
+        let headers = headers.lock().unwrap();
+        assert_eq!(headers["content-type"], "application/json");
+        let miru_headers: Vec<_> = headers
+            .keys()
+            .map(|name| name.as_str())
+            .filter(|name| name.starts_with("miru-"))
+            .collect();
+        assert_eq!(miru_headers, vec!["miru-signature"]);
+    }
+
+    #[tokio::test]
+    async fn goes_through_the_egress_proxy_without_fleet_headers() {
+        let seen = Arc::new(Mutex::new(Vec::<(String, HeaderMap)>::new()));
+        let seen_for_route = seen.clone();
+        let router = Router::new().fallback(move |req: axum::extract::Request| {
+            let seen = seen_for_route.clone();
+            async move {
+                let (parts, _) = req.into_parts();
+                seen.lock()
+                    .unwrap()
+                    .push((parts.uri.to_string(), parts.headers));
+                "ok"
+            }
+        });
+        let proxy = mock::run_server(router).await;
+        let egress = egress::Options {
+            proxy: Some(egress::Proxy::new(&proxy.base_url).unwrap()),
+            headers: vec![("X-Fleet".to_string(), "warehouse-3".to_string())],
+            ..Default::default()
+        };
+        let webhook_client = webhook::Client::with_egress(&egress).unwrap();
+        let (mqtt_outbox, _rx) = mpsc::channel(1);
+        let sink = sink(Target::Webhook {
+            url: "http://receiver.invalid/alerts".to_string(),
+        });
+
+        sink.deliver(
+            &notification(Severity::Critical),
+            &webhook_client,
+            &mqtt_outbox,
+            &Keyring::default(),
+        )
+        .await
+        .unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        let (uri, headers) = &seen[0];
+        assert_eq!(uri, "http://receiver.invalid/alerts");
+        assert!(headers.get("x-fleet").is_none());
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let router = Router::new().route("/alerts", post(mock::internal_server_error));
+        let server = mock::run_server(router).await;
+        let sink = sink(Target::Webhook {
+            url: format!("{}/alerts", server.base_url),
+        });
+        let result = deliver(&sink, &notification(Severity::Critical)).await;
+        assert!(matches!(result, Err(NotificationsErr::WebhookErr(_))));
+    }
+}
+
+pub mod mqtt_sink {
+    use super::*;
+
+    #[tokio::test]
+    async fn queues_message_for_mqtt_worker() {
+        let webhook_client = webhook::Client::new().unwrap();
+        let (mqtt_outbox, mut rx) = mpsc::channel(1);
+        let sink = sink(Target::Mqtt {
+            topic: "robots/alerts".to_string(),
+        });
+
+        let notification = notification(Severity::Critical);
+        sink.deliver(
+            &notification,
+            &webhook_client,
+            &mqtt_outbox,
+            &Keyring::default(),
+        )
+        .await
+        .unwrap();
+
+        let MqttMessage { topic, payload } = rx.recv().await.unwrap();
+        assert_eq!(topic, "robots/alerts");
+        let payload: Notification = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(payload, notification);
+    }
+
+    #[tokio::test]
+    async fn outbox_closed() {
+        let webhook_client = webhook::Client::new().unwrap();
+        let (mqtt_outbox, rx) = mpsc::channel(1);
+        drop(rx);
+        let sink = sink(Target::Mqtt {
+            topic: "robots/alerts".to_string(),
+        });
+        let result = sink
+            .deliver(
+                &notification(Severity::Critical),
+                &webhook_client,
+                &mqtt_outbox,
+                &Keyring::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(NotificationsErr::MqttOutboxErr(_))));
+    }
+}
+
+#[test]
+fn deserialize_sink() {
+    let sink = serde_json::from_value::<Sink>(json!({
+        "type": "file",
+        "path": "/var/log/miru/notifications.jsonl",
+        "min_severity": "info",
+    }))
+    .unwrap();
+    assert_eq!(
+        sink.target,
+        Target::File {
+            path: "/var/log/miru/notifications.jsonl".to_string()
+        }
+    );
+    assert_eq!(sink.min_severity, Severity::Info);
+}
+
+#[test]
+fn deserialize_webhook_sink_with_events_and_retry() {
+    let deserialized = serde_json::from_value::<Sink>(json!({
+        "type": "webhook",
+        "url": "http://localhost:8080/hooks/miru",
+        "events": ["deployment.deployed", "deployment.failed", "sync.failed"],
+        "retry": {
+            "max_attempts": 5,
+            "backoff": {"base_secs": 1, "growth_factor": 2, "max_secs": 30},
+        },
+    }))
+    .unwrap();
+    assert_eq!(
+        deserialized.events,
+        vec!["deployment.deployed", "deployment.failed", "sync.failed"]
+    );
+    assert_eq!(
+        deserialized.retry,
+        Some(Retry {
+            max_attempts: 5,
+            backoff: cooldown::Backoff {
+                base_secs: 1,
+                growth_factor: 2,
+                max_secs: 30,
+            },
+        })
+    );
+
+    // unset options aren't serialized
+    let serialized = serde_json::to_value(sink(Target::Webhook {
+        url: "http://localhost:8080/hooks/miru".to_string(),
+    }))
+    .unwrap();
+    assert!(serialized.get("events").is_none());
+    assert!(serialized.get("retry").is_none());
+}
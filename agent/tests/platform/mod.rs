@@ -0,0 +1,227 @@
+// standard crates
+use std::path::{Path, PathBuf};
+
+// internal crates
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::platform;
+
+pub mod paths {
+    use super::*;
+
+    #[test]
+    fn data_dir_under_filesystem_root() {
+        let dir = platform::data_dir(&filesys::Dir::new("/custom"));
+        let expected = platform::DATA_DIR
+            .iter()
+            .fold(PathBuf::from("/custom"), |path, name| path.join(name));
+        assert_eq!(dir.path(), &expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_defaults() {
+        assert_eq!(platform::filesystem_root().path(), &PathBuf::from("/"));
+        assert_eq!(platform::default_log_dir(), PathBuf::from("/var/log/miru"));
+        assert_eq!(
+            platform::default_socket_file().path(),
+            &PathBuf::from("/run/miru/miru.sock")
+        );
+        assert_eq!(
+            platform::default_reboot_command(),
+            vec!["systemctl".to_string(), "reboot".to_string()]
+        );
+    }
+}
+
+pub mod host {
+    use super::*;
+
+    #[test]
+    fn describes_the_host() {
+        assert!(!platform::arch().is_empty());
+        assert!(!platform::os().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn host_name_matches_the_kernel() {
+        let expected = std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap();
+        assert_eq!(platform::host_name(), expected.trim());
+    }
+}
+
+pub mod service_definition {
+    use super::*;
+
+    #[test]
+    fn includes_binary_path() {
+        let definition = platform::service_definition(
+            Path::new("/opt/miru/miru-agent"),
+            &[],
+            &platform::ServiceOptions::default(),
+        );
+        assert!(definition.contains("/opt/miru/miru-agent"));
+    }
+
+    #[test]
+    fn includes_args() {
+        let args = vec!["--data-dir=/srv/agent-a".to_string()];
+        let definition = platform::service_definition(
+            Path::new("/opt/miru/miru-agent"),
+            &args,
+            &platform::ServiceOptions::default(),
+        );
+        assert!(definition.contains("--data-dir=/srv/agent-a"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_systemd_unit() {
+        let definition = platform::service_definition(
+            Path::new("/usr/bin/miru-agent"),
+            &[],
+            &platform::ServiceOptions::default(),
+        );
+        assert!(definition.contains("ExecStart=/usr/bin/miru-agent\n"));
+
+        let args = vec!["--data-dir=/srv/agent-a".to_string()];
+        let definition = platform::service_definition(
+            Path::new("/usr/bin/miru-agent"),
+            &args,
+            &platform::ServiceOptions::default(),
+        );
+        assert!(definition.contains("ExecStart=/usr/bin/miru-agent --data-dir=/srv/agent-a\n"));
+        assert!(definition.contains("[Install]"));
+        assert!(definition.contains("Restart=on-failure\n"));
+        assert!(!definition.contains("WatchdogSec"));
+        assert!(!definition.contains("ProtectSystem"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_systemd_unit_options() {
+        let options = platform::ServiceOptions {
+            restart: "always".to_string(),
+            hardening: true,
+            writable_paths: vec!["/var/lib/miru".into(), "/etc/robot".into()],
+            watchdog_sec: Some(30),
+            ..Default::default()
+        };
+        let definition =
+            platform::service_definition(Path::new("/usr/bin/miru-agent"), &[], &options);
+
+        assert!(definition.contains("Type=notify\n"));
+        assert!(definition.contains("TimeoutStartSec=infinity\n"));
+        assert!(definition.contains("Restart=always\nRestartSec=5\n"));
+        assert!(definition.contains("WatchdogSec=30\nNotifyAccess=main\n"));
+        assert!(definition.contains("NoNewPrivileges=yes\n"));
+        assert!(definition.contains("ProtectSystem=strict\n"));
+        assert!(definition.contains("ReadWritePaths=-/var/lib/miru\n"));
+        assert!(definition.contains("ReadWritePaths=-/etc/robot\n"));
+        // the directives stay in the service section
+        let service = definition.split("[Install]").next().unwrap();
+        assert!(service.contains("ReadWritePaths=-/etc/robot"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn writable_paths_need_hardening() {
+        let options = platform::ServiceOptions {
+            writable_paths: vec!["/etc/robot".into()],
+            ..Default::default()
+        };
+        let definition =
+            platform::service_definition(Path::new("/usr/bin/miru-agent"), &[], &options);
+        assert!(!definition.contains("ReadWritePaths"));
+    }
+
+    #[test]
+    fn stop_service_command() {
+        let command = platform::stop_service_command(platform::SERVICE_NAME);
+        assert!(command.iter().any(|arg| arg == platform::SERVICE_NAME));
+        #[cfg(target_os = "linux")]
+        assert_eq!(command, vec!["systemctl", "disable", "--now", "miru"]);
+    }
+
+    #[test]
+    fn tmpfiles_entry() {
+        assert_eq!(
+            platform::tmpfiles_entry(Path::new("/var/log/miru")),
+            "d /var/log/miru 0750 root root -\n"
+        );
+    }
+}
+
+#[cfg(unix)]
+pub mod systemd {
+    use super::*;
+    use miru_agent::platform::systemd::{self, Notifier};
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
+
+    fn recv(socket: &UnixDatagram) -> String {
+        let mut buf = [0u8; 64];
+        let n = socket.recv(&mut buf).unwrap();
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn watchdog_timeout() {
+        assert_eq!(
+            systemd::watchdog_timeout(Some("30000000"), None, 7),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            systemd::watchdog_timeout(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(30))
+        );
+        // the watchdog is meant for another process
+        assert_eq!(
+            systemd::watchdog_timeout(Some("30000000"), Some("8"), 7),
+            None
+        );
+        assert_eq!(systemd::watchdog_timeout(None, None, 7), None);
+        assert_eq!(systemd::watchdog_timeout(Some("0"), None, 7), None);
+        assert_eq!(systemd::watchdog_timeout(Some("soon"), None, 7), None);
+    }
+
+    #[tokio::test]
+    async fn notifies_the_socket() {
+        let dir = filesys::Dir::create_temp_dir("systemd_notify")
+            .await
+            .unwrap();
+        let path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = Notifier::new(Some(path.display().to_string()));
+        assert!(notifier.is_enabled());
+        notifier.notify(systemd::READY);
+        notifier.notify(systemd::STOPPING);
+        assert_eq!(recv(&socket), "READY=1");
+        assert_eq!(recv(&socket), "STOPPING=1");
+        dir.delete().await.unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn abstract_sockets() {
+        use std::os::linux::net::SocketAddrExt;
+
+        let name = format!("miru-notify-test-{}", std::process::id());
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(&name).unwrap();
+        let socket = UnixDatagram::bind_addr(&addr).unwrap();
+
+        Notifier::new(Some(format!("@{name}"))).notify(systemd::WATCHDOG);
+        assert_eq!(recv(&socket), "WATCHDOG=1");
+    }
+
+    #[test]
+    fn without_a_service_manager() {
+        for notifier in [Notifier::new(None), Notifier::new(Some(String::new()))] {
+            assert!(!notifier.is_enabled());
+            notifier.notify(systemd::READY);
+        }
+        // an unreachable socket is ignored
+        Notifier::new(Some("/nonexistent/notify.sock".to_string())).notify(systemd::READY);
+    }
+}
@@ -0,0 +1,535 @@
+// standard crates
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// internal crates
+use super::shared::{mock_ok_provision, Env};
+use crate::mocks::http_client as mock;
+use miru_agent::cache::CacheEntry;
+use miru_agent::deploy::{created_dirs::CreatedDirs, versions::Versions};
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::http::{errors::MockErr, HTTPErr};
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity};
+use miru_agent::provisioning::{deprovision, errors::*};
+use miru_agent::storage::Layout;
+
+// external crates
+use chrono::Utc;
+use serde::Serialize;
+
+async fn write_cache<V: Clone + Serialize>(file: &filesys::File, entries: Vec<(&str, V)>) {
+    let map: HashMap<String, CacheEntry<String, V>> = entries
+        .into_iter()
+        .map(|(key, value)| {
+            let entry = CacheEntry {
+                key: key.to_string(),
+                value,
+                is_dirty: false,
+                created_at: Utc::now(),
+                last_accessed: Utc::now(),
+                probation: None,
+                digest: None,
+                pinned: false,
+            };
+            (key.to_string(), entry)
+        })
+        .collect();
+    file.write_json(&map, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+}
+
+/// A data directory with its logs, holding a deployed and an archived deployment whose
+/// config files were written under `<tmp>/deployed`, the deployed one into the `app`
+/// directory the agent created for it.
+async fn deployed_layout(tmp: &filesys::Dir) -> (Layout, PathBuf, PathBuf) {
+    let layout = Layout::with_data_dir(tmp.subdir("data"));
+    let deployed = tmp.subdir("deployed").subdir("app").file("config.json");
+    let archived = tmp.subdir("deployed").file("old.json");
+    for file in [&deployed, &archived] {
+        file.write_string("{}", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+    }
+    CreatedDirs::new(layout.created_dirs())
+        .record(&[deployed.parent().unwrap().path().to_path_buf()])
+        .await
+        .unwrap();
+    write_cache(
+        &layout.deployments(),
+        vec![
+            (
+                "dpl-1",
+                Deployment {
+                    id: "dpl-1".to_string(),
+                    activity_status: DplActivity::Deployed,
+                    config_instance_ids: vec!["cfg-1".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "dpl-2",
+                Deployment {
+                    id: "dpl-2".to_string(),
+                    activity_status: DplActivity::Archived,
+                    config_instance_ids: vec!["cfg-2".to_string()],
+                    ..Default::default()
+                },
+            ),
+        ],
+    )
+    .await;
+    write_cache(
+        &layout.config_instance_meta(),
+        vec![
+            (
+                "cfg-1",
+                ConfigInstance {
+                    id: "cfg-1".to_string(),
+                    filepath: deployed.path().to_string_lossy().to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "cfg-2",
+                ConfigInstance {
+                    id: "cfg-2".to_string(),
+                    filepath: archived.path().to_string_lossy().to_string(),
+                    ..Default::default()
+                },
+            ),
+        ],
+    )
+    .await;
+    filesys::File::new(layout.log_dir().join("miru.log"))
+        .write_string("log", WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+    (
+        layout,
+        deployed.path().to_path_buf(),
+        archived.path().to_path_buf(),
+    )
+}
+
+/// A deployment directory under `<tmp>/srv` with a live release and an older one.
+async fn deployment_dir(tmp: &filesys::Dir) -> Versions {
+    let versions = Versions::new(tmp.subdir("srv"));
+    for content in ["old", "new"] {
+        let staged = versions.stage().await.unwrap();
+        staged
+            .file("app.json")
+            .write_string(content, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let number = versions.promote(&staged, "dpl-1").await.unwrap();
+        versions.activate(number).await.unwrap();
+    }
+    versions
+}
+
+fn local_only() -> deprovision::Options {
+    deprovision::Options {
+        deregister: false,
+        ..deprovision::Options::default()
+    }
+}
+
+pub mod plan {
+    use super::*;
+
+    #[tokio::test]
+    async fn lists_deployed_files_and_state() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision-plan")
+            .await
+            .unwrap();
+        let (layout, deployed, _) = deployed_layout(&tmp).await;
+
+        let plan = deprovision::plan(&layout, &deprovision::Options::default()).await;
+
+        assert_eq!(
+            plan.created_dirs,
+            vec![deployed.parent().unwrap().to_path_buf()]
+        );
+        assert_eq!(plan.deployed_files, vec![deployed]);
+        assert!(plan.releases.is_empty());
+        let deployments = layout.deployments();
+        assert!(plan
+            .state
+            .iter()
+            .any(|path| deployments.path().starts_with(path)));
+        assert!(plan.state.contains(&layout.log_dir()));
+        assert_eq!(plan.logs, None);
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_logs() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision-plan")
+            .await
+            .unwrap();
+        let (layout, _, _) = deployed_layout(&tmp).await;
+        let opts = deprovision::Options {
+            keep_logs: true,
+            ..deprovision::Options::default()
+        };
+
+        let plan = deprovision::plan(&layout, &opts).await;
+
+        assert!(!plan.state.contains(&layout.log_dir()));
+        let deployments = layout.deployments();
+        assert!(plan
+            .state
+            .iter()
+            .any(|path| deployments.path().starts_with(path)));
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lists_the_releases() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision-plan")
+            .await
+            .unwrap();
+        let layout = Layout::with_data_dir(tmp.subdir("data"));
+        let versions = deployment_dir(&tmp).await;
+        let opts = deprovision::Options {
+            versions: Some(versions.clone()),
+            ..deprovision::Options::default()
+        };
+
+        let plan = deprovision::plan(&layout, &opts).await;
+
+        let srv = tmp.subdir("srv");
+        assert_eq!(
+            plan.releases,
+            vec![
+                srv.file("current").path().to_path_buf(),
+                srv.subdir("releases").path().to_path_buf(),
+                srv.subdir("staging").path().to_path_buf(),
+            ]
+        );
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn empty_when_nothing_is_installed() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision-plan")
+            .await
+            .unwrap();
+        let layout = Layout::with_data_dir(tmp.subdir("data"));
+
+        let plan = deprovision::plan(&layout, &deprovision::Options::default()).await;
+
+        assert_eq!(plan, deprovision::Plan::default());
+
+        tmp.delete().await.unwrap();
+    }
+}
+
+pub mod deprovision_fn {
+    use super::*;
+
+    #[tokio::test]
+    async fn removes_deployed_files_and_state() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, deployed, archived) = deployed_layout(&tmp).await;
+        let private_key = layout.auth().private_key();
+
+        let report = deprovision::deprovision(
+            &mock::MockClient::default(),
+            &layout,
+            &private_key.into(),
+            &local_only(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!report.deregistered);
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert!(!deployed.exists());
+        assert!(
+            !deployed.parent().unwrap().exists(),
+            "empty dir left behind"
+        );
+        assert!(archived.exists(), "archived deployment's file removed");
+        assert!(!layout.root().exists());
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_dirs_it_didnt_create() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, deployed, _) = deployed_layout(&tmp).await;
+        let private_key = layout.auth().private_key();
+        layout.created_dirs().delete().await.unwrap();
+
+        let report = deprovision::deprovision(
+            &mock::MockClient::default(),
+            &layout,
+            &private_key.into(),
+            &local_only(),
+        )
+        .await
+        .unwrap();
+
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert!(!deployed.exists());
+        assert!(deployed.parent().unwrap().exists(), "system dir removed");
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_created_dirs_holding_other_files() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, deployed, _) = deployed_layout(&tmp).await;
+        let private_key = layout.auth().private_key();
+        let other = filesys::File::new(deployed.parent().unwrap().join("local.json"));
+        other
+            .write_string("{}", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let report = deprovision::deprovision(
+            &mock::MockClient::default(),
+            &layout,
+            &private_key.into(),
+            &local_only(),
+        )
+        .await
+        .unwrap();
+
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert!(!deployed.exists());
+        assert!(other.exists());
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn removes_the_releases() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, _, _) = deployed_layout(&tmp).await;
+        let private_key = layout.auth().private_key();
+        let versions = deployment_dir(&tmp).await;
+        let other = tmp.subdir("srv").file("README");
+        other
+            .write_string("keep", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let opts = deprovision::Options {
+            versions: Some(versions.clone()),
+            ..local_only()
+        };
+
+        let report = deprovision::deprovision(
+            &mock::MockClient::default(),
+            &layout,
+            &private_key.into(),
+            &opts,
+        )
+        .await
+        .unwrap();
+
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        let link = versions.current_link();
+        assert!(!link.path().is_symlink());
+        assert!(!link.exists());
+        assert!(!tmp.subdir("srv").subdir("releases").exists());
+        assert!(other.exists());
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_other_files_in_the_data_dir() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, _, _) = deployed_layout(&tmp).await;
+        let private_key = layout.auth().private_key();
+        let other_file = layout.root().file("backup.tar");
+        let other_dir = layout.root().subdir("www").file("index.html");
+        for file in [&other_file, &other_dir] {
+            file.write_string("keep", WriteOptions::OVERWRITE_ATOMIC)
+                .await
+                .unwrap();
+        }
+
+        let report = deprovision::deprovision(
+            &mock::MockClient::default(),
+            &layout,
+            &private_key.into(),
+            &local_only(),
+        )
+        .await
+        .unwrap();
+
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert!(!layout.deployments().exists());
+        assert!(!layout.log_dir().exists());
+        assert!(other_file.exists());
+        assert!(other_dir.exists());
+        assert!(layout.root().exists());
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_logs() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, _, _) = deployed_layout(&tmp).await;
+        let private_key = layout.auth().private_key();
+        let opts = deprovision::Options {
+            keep_logs: true,
+            ..local_only()
+        };
+
+        let report = deprovision::deprovision(
+            &mock::MockClient::default(),
+            &layout,
+            &private_key.into(),
+            &opts,
+        )
+        .await
+        .unwrap();
+
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert!(layout.log_dir().join("miru.log").exists());
+        assert!(!layout.deployments().exists());
+        assert!(!layout.config_instance_meta().exists());
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deregisters_the_device() {
+        let env = Env::new("deprovision").await;
+        env.seed_provision("test-device").await;
+        let mock = mock_ok_provision("test-device");
+        let opts = deprovision::Options {
+            keep_logs: true,
+            ..deprovision::Options::default()
+        };
+
+        let report = deprovision::deprovision(
+            &mock,
+            &env.layout,
+            &env.layout.auth().private_key().into(),
+            &opts,
+        )
+        .await
+        .unwrap();
+
+        assert!(report.deregistered);
+        assert_eq!(mock.call_count(mock::Call::IssueDeviceToken), 1);
+        assert_eq!(mock.call_count(mock::Call::DeregisterDevice), 1);
+        assert!(!env.layout.device().exists());
+        assert!(!env.layout.auth().private_key().exists());
+        assert!(!env.layout.auth().token().exists());
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn deregister_leaves_the_state() {
+        let env = Env::new("deprovision").await;
+        env.seed_provision("test-device").await;
+        let mock = mock_ok_provision("test-device");
+
+        let deregistered =
+            deprovision::deregister(&mock, &env.layout, &env.layout.auth().private_key().into())
+                .await
+                .unwrap();
+
+        assert!(deregistered);
+        assert_eq!(mock.call_count(mock::Call::DeregisterDevice), 1);
+        assert!(env.layout.device().exists());
+        assert!(env.layout.auth().private_key().exists());
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn skips_deregistering_an_unactivated_device() {
+        let tmp = filesys::Dir::create_temp_dir("deprovision").await.unwrap();
+        let (layout, _, _) = deployed_layout(&tmp).await;
+        let mock = mock::MockClient::default();
+
+        let report = deprovision::deprovision(
+            &mock,
+            &layout,
+            &layout.auth().private_key().into(),
+            &deprovision::Options::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!report.deregistered);
+        assert_eq!(mock.call_count(mock::Call::DeregisterDevice), 0);
+        assert!(!layout.root().exists());
+
+        tmp.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deregistration_error_keeps_the_state() {
+        let env = Env::new("deprovision").await;
+        env.seed_provision("test-device").await;
+        let mock = mock_ok_provision("test-device");
+        mock.set_deregister_device(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+
+        let err = deprovision::deprovision(
+            &mock,
+            &env.layout,
+            &env.layout.auth().private_key().into(),
+            &deprovision::Options::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, ProvisionErr::HTTPErr(_)));
+        assert!(env.layout.device().exists());
+        assert!(env.layout.auth().private_key().exists());
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn force_removes_the_state_anyway() {
+        let env = Env::new("deprovision").await;
+        env.seed_provision("test-device").await;
+        let mock = mock_ok_provision("test-device");
+        mock.set_deregister_device(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+        let opts = deprovision::Options {
+            force: true,
+            keep_logs: true,
+            ..deprovision::Options::default()
+        };
+
+        let report = deprovision::deprovision(
+            &mock,
+            &env.layout,
+            &env.layout.auth().private_key().into(),
+            &opts,
+        )
+        .await
+        .unwrap();
+
+        assert!(!report.deregistered);
+        assert!(!env.layout.device().exists());
+        assert!(!env.layout.auth().private_key().exists());
+
+        env.cleanup().await;
+    }
+}
@@ -1,3 +1,5 @@
+pub mod deprovision;
 pub mod provision;
 pub mod reprovision;
+pub mod seed;
 mod shared;
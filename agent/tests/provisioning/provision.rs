@@ -1,9 +1,12 @@
 // internal crates
 use super::shared::{
-    mock_failing_provision, mock_ok_provision, validate_storage, Env, StorageSnapshot, DEVICE_ID,
+    mock_failing_provision, mock_ok_provision, new_token_response, validate_storage, Env,
+    StorageSnapshot, DEVICE_ID,
 };
 use crate::mocks::http_client as mock;
+use miru_agent::authn::{AuthnErr, Token};
 use miru_agent::filesys::{PathExt, WriteOptions};
+use miru_agent::http::{errors::MockErr, HTTPErr};
 use miru_agent::provisioning::{errors::*, provision};
 
 pub mod provision_fn {
@@ -32,6 +35,65 @@ pub mod provision_fn {
         env.cleanup().await;
     }
 
+    #[tokio::test]
+    async fn verifies_the_new_key_and_keeps_the_token() {
+        let env = Env::new("provision-test").await;
+        let mock = mock_ok_provision("test-device");
+
+        provision::provision(&mock, &env.layout, &env.settings, &env.token, None)
+            .await
+            .unwrap();
+
+        assert_eq!(mock.call_count(mock::Call::IssueDeviceToken), 1);
+        let token: Token = env.layout.auth().token().read_json().await.unwrap();
+        assert_eq!(token.token, new_token_response(DEVICE_ID).token);
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn token_for_another_device_fails_verification() {
+        let env = Env::new("provision-test").await;
+        let mock = mock::MockClient {
+            issue_device_token_fn: Box::new(|| Ok(new_token_response("another-device"))),
+            ..mock_ok_provision("test-device")
+        };
+
+        let err = provision::provision(&mock, &env.layout, &env.settings, &env.token, None)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, ProvisionErr::VerificationErr(_)));
+        assert!(err.to_string().contains("another-device"));
+        assert!(!env.layout.temp_dir().exists(), "temp dir not cleaned");
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn rejected_key_fails_verification() {
+        let env = Env::new("provision-test").await;
+        let mock = mock::MockClient {
+            issue_device_token_fn: Box::new(|| {
+                Err(HTTPErr::MockErr(MockErr {
+                    is_network_conn_err: false,
+                }))
+            }),
+            ..mock_ok_provision("test-device")
+        };
+
+        let err = provision::provision(&mock, &env.layout, &env.settings, &env.token, None)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, ProvisionErr::AuthnErr(AuthnErr::HTTPErr(_))));
+        // the device is registered, so what it was activated with is kept
+        assert!(env.layout.device().exists());
+        assert!(env.layout.auth().private_key().exists());
+
+        env.cleanup().await;
+    }
+
     #[tokio::test]
     async fn http_error_aborts_provision() {
         let env = Env::new("provision-test").await;
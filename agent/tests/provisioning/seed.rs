@@ -0,0 +1,177 @@
+// internal crates
+use super::shared::{mock_failing_provision, mock_ok_provision, validate_storage, Env};
+use crate::mocks::http_client as mock;
+use miru_agent::filesys::{PathExt, WriteOptions};
+use miru_agent::provisioning::{errors::*, seed};
+use miru_agent::storage::Settings;
+
+// external crates
+use serde_json::json;
+
+async fn write_seed(env: &Env, seed: serde_json::Value) {
+    env.layout
+        .seed_dir()
+        .file(seed::SEED_FILE)
+        .write_json(&seed, WriteOptions::default())
+        .await
+        .unwrap();
+}
+
+pub mod read {
+    use super::*;
+
+    #[tokio::test]
+    async fn no_seed() {
+        let env = Env::new("seed-test").await;
+        let seed = seed::read(&env.layout.seed_dir()).await.unwrap();
+        assert!(seed.is_none());
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn minimal_seed() {
+        let env = Env::new("seed-test").await;
+        write_seed(&env, json!({"token": env.token})).await;
+
+        let seed = seed::read(&env.layout.seed_dir()).await.unwrap().unwrap();
+        assert_eq!(seed.token, env.token);
+        assert!(seed.device_name.is_none());
+        assert!(seed.settings.is_none());
+        assert!(seed.origin.is_none());
+        // the token is never logged
+        assert!(!format!("{seed:?}").contains(&env.token));
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn empty_token() {
+        let env = Env::new("seed-test").await;
+        write_seed(&env, json!({"token": "  "})).await;
+
+        let result = seed::read(&env.layout.seed_dir()).await;
+        assert!(matches!(result, Err(ProvisionErr::InvalidSeedErr(_))));
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn malformed_seed() {
+        let env = Env::new("seed-test").await;
+        write_seed(&env, json!({"device_name": "no-token"})).await;
+
+        let result = seed::read(&env.layout.seed_dir()).await;
+        assert!(matches!(result, Err(ProvisionErr::FileSysErr(_))));
+
+        env.cleanup().await;
+    }
+}
+
+pub mod import {
+    use super::*;
+
+    #[tokio::test]
+    async fn provisions_and_shreds_the_seed() {
+        let env = Env::new("seed-test").await;
+        let settings = Settings {
+            enable_poller: false,
+            ..Settings::default()
+        };
+        write_seed(
+            &env,
+            json!({
+                "token": env.token,
+                "device_name": "line-7-unit-42",
+                "settings": settings,
+                "origin": "line 7, batch 2026-10",
+            }),
+        )
+        .await;
+        let seed = seed::read(&env.layout.seed_dir()).await.unwrap().unwrap();
+        let mock = mock_ok_provision("line-7-unit-42");
+
+        let before = chrono::Utc::now();
+        let outcome = seed::import(&mock, &env.layout, seed).await.unwrap();
+
+        assert!(!outcome.already_provisioned);
+        assert_eq!(outcome.device_name, "line-7-unit-42");
+        assert_eq!(mock.call_count(mock::Call::ProvisionDevice), 1);
+        validate_storage(&env.layout, "line-7-unit-42").await;
+
+        // the seed's settings were written
+        let written = env.layout.settings().read_json::<Settings>().await.unwrap();
+        assert!(!written.enable_poller);
+
+        // the seed is gone
+        assert!(!env.layout.seed_dir().exists());
+
+        // and its provenance recorded
+        let provenance = env
+            .layout
+            .provenance()
+            .read_json::<seed::Provenance>()
+            .await
+            .unwrap();
+        assert_eq!(provenance.seed_dir, env.layout.seed_dir().path().clone());
+        assert_eq!(provenance.origin.as_deref(), Some("line 7, batch 2026-10"));
+        assert_eq!(provenance.device_name, "line-7-unit-42");
+        assert!(provenance.provisioned_at >= before);
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn failure_keeps_the_seed() {
+        let env = Env::new("seed-test").await;
+        write_seed(&env, json!({"token": env.token})).await;
+        let seed = seed::read(&env.layout.seed_dir()).await.unwrap().unwrap();
+
+        let result = seed::import(&mock_failing_provision(), &env.layout, seed).await;
+        assert!(matches!(result, Err(ProvisionErr::HTTPErr(_))));
+
+        assert!(env.layout.seed_dir().file(seed::SEED_FILE).exists());
+        assert!(!env.layout.provenance().exists());
+        assert!(!env.layout.device().exists());
+
+        env.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn already_activated_discards_the_seed() {
+        let env = Env::new("seed-test").await;
+        env.seed_provision("initial").await;
+        write_seed(&env, json!({"token": env.token, "device_name": "ignored"})).await;
+        let seed = seed::read(&env.layout.seed_dir()).await.unwrap().unwrap();
+
+        let mock = mock_failing_provision();
+        let outcome = seed::import(&mock, &env.layout, seed).await.unwrap();
+
+        assert!(outcome.already_provisioned);
+        assert_eq!(outcome.device_name, "initial");
+        assert_eq!(mock.call_count(mock::Call::ProvisionDevice), 0);
+        assert!(!env.layout.seed_dir().exists());
+        assert!(!env.layout.provenance().exists());
+
+        env.cleanup().await;
+    }
+}
+
+#[tokio::test]
+async fn discard_removes_every_file() {
+    let env = Env::new("seed-test").await;
+    let seed_dir = env.layout.seed_dir();
+    write_seed(&env, json!({"token": env.token})).await;
+    seed_dir
+        .file("README.txt")
+        .write_string("seed for line 7", WriteOptions::default())
+        .await
+        .unwrap();
+
+    seed::discard(&seed_dir).await.unwrap();
+    assert!(!seed_dir.exists());
+
+    // discarding a missing seed is a no-op
+    seed::discard(&seed_dir).await.unwrap();
+
+    env.cleanup().await;
+}
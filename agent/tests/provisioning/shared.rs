@@ -1,6 +1,6 @@
 // internal crates
 use crate::mocks::http_client::MockClient;
-use backend_api::models::Device;
+use backend_api::models::{Device, TokenResponse};
 use miru_agent::crypt::base64;
 use miru_agent::filesys::{self, PathExt};
 use miru_agent::http::{errors::MockErr, HTTPErr};
@@ -78,11 +78,21 @@ impl Env {
     }
 }
 
-/// MockClient that succeeds with a `Device` named `name` for `/devices/provision`.
+/// A `/devices/token` response for the device `device_id`.
+pub(super) fn new_token_response(device_id: &str) -> TokenResponse {
+    TokenResponse {
+        token: new_jwt(device_id),
+        expires_at: "2099-01-01T00:00:00Z".to_string(),
+    }
+}
+
+/// MockClient that succeeds with a `Device` named `name` for `/devices/provision` and
+/// issues tokens for it.
 pub(super) fn mock_ok_provision(name: impl Into<String>) -> MockClient {
     let name = name.into();
     MockClient {
         provision_device_fn: Box::new(move || Ok(new_device(DEVICE_ID, &name))),
+        issue_device_token_fn: Box::new(|| Ok(new_token_response(DEVICE_ID))),
         ..MockClient::default()
     }
 }
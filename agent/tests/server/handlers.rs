@@ -1,5 +1,5 @@
 // internal crates
-use device_api::models::{HealthResponse, VersionResponse};
+use device_api::models::VersionResponse;
 use miru_agent::server::handlers;
 use miru_agent::version::{self, COMMIT, VERSION};
 
@@ -8,23 +8,6 @@ use axum::body;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 
-pub mod health {
-    use super::*;
-
-    #[tokio::test]
-    async fn returns_ok_with_status() {
-        let response = handlers::health().await.into_response();
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let bytes = body::to_bytes(response.into_body(), 1024).await.unwrap();
-        let actual: HealthResponse = serde_json::from_slice(&bytes).unwrap();
-        let expected = HealthResponse {
-            status: "ok".to_string(),
-        };
-        assert_eq!(actual, expected);
-    }
-}
-
 pub mod version_tests {
     use super::*;
 
@@ -60,12 +43,17 @@ pub mod routes {
 
     use device_api::models as openapi;
     use miru_agent::activity;
+    use miru_agent::config::compat;
     use miru_agent::events::hub::{EventHub, SpawnOptions};
     use miru_agent::filesys::{self, Overwrite};
+    use miru_agent::models::device::Updates as DeviceUpdates;
     use miru_agent::models::{
-        Deployment, DplActivity, DplErrStatus, DplTarget, GitCommit, Release,
+        ConfigInstance, Deployment, DplActivity, DplErrStatus, DplTarget, GitCommit, Release,
     };
+    use miru_agent::server::health::{Probes, Report, Status};
     use miru_agent::server::{serve, State};
+    use miru_agent::sync::history::{Outcome, Phases, Record, Summary};
+    use miru_agent::sync::syncer::Command;
     use miru_agent::sync::Syncer;
 
     use crate::mocks::http_client::{self as mock, MockClient};
@@ -87,6 +75,14 @@ pub mod routes {
 
     impl Fixture {
         async fn new(name: &str) -> Self {
+            Self::with_safe_mode(name, false).await
+        }
+
+        async fn with_safe_mode(name: &str, safe_mode: bool) -> Self {
+            Self::with_state(name, |state| state.with_safe_mode(safe_mode)).await
+        }
+
+        async fn with_state(name: &str, configure: impl FnOnce(State) -> State) -> Self {
             let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
             let storage = Arc::new(create_storage(&dir).await);
             let http_client = Arc::new(MockClient::default());
@@ -112,7 +108,7 @@ pub mod routes {
                 .unwrap();
 
             let (shutdown_tx, _) = broadcast::channel::<()>(1);
-            let state = Arc::new(State::new(
+            let state = Arc::new(configure(State::new(
                 storage,
                 real_http_client,
                 syncer,
@@ -120,7 +116,7 @@ pub mod routes {
                 activity_tracker,
                 event_hub,
                 shutdown_tx,
-            ));
+            )));
 
             let app = serve::routes(state.clone());
 
@@ -144,6 +140,25 @@ pub mod routes {
             (status, bytes.to_vec())
         }
 
+        async fn put_json(&self, uri: &str, body: serde_json::Value) -> (StatusCode, Vec<u8>) {
+            let response = self
+                .app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("PUT")
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = body::to_bytes(response.into_body(), 16384).await.unwrap();
+            (status, bytes.to_vec())
+        }
+
         async fn post(&self, uri: &str) -> (StatusCode, Vec<u8>) {
             let response = self
                 .app
@@ -163,6 +178,218 @@ pub mod routes {
         }
     }
 
+    mod health {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_ok_with_status() {
+            let f = Fixture::new("handler_health").await;
+
+            let (status, bytes) = f.get("/v0.2/health").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: openapi::HealthResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.status, "ok");
+            assert!(actual.subsystems.is_some());
+        }
+
+        #[tokio::test]
+        async fn reports_safe_mode() {
+            let f = Fixture::with_safe_mode("handler_health_safe_mode", true).await;
+
+            let (status, bytes) = f.get("/v0.2/health").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: openapi::HealthResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.status, "safe_mode");
+        }
+
+        #[tokio::test]
+        async fn reports_disabled_and_unknown_subsystems() {
+            let f = Fixture::new("handler_health_defaults").await;
+
+            let (status, bytes) = f.get("/v0.2/health").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let report: Report = serde_json::from_slice(&bytes).unwrap();
+            let subsystems = &report.subsystems;
+            assert_eq!(subsystems.mqtt.status, Status::Disabled);
+            assert_eq!(subsystems.mqtt.connected, None);
+            assert_eq!(subsystems.poller.status, Status::Disabled);
+            // the fixture's syncer isn't running
+            assert_eq!(subsystems.syncer.status, Status::Unknown);
+            assert!(subsystems.syncer.error.is_some());
+            assert_eq!(subsystems.disk.status, Status::Unknown);
+            // the fixture's token has expired
+            assert_eq!(subsystems.token.status, Status::Degraded);
+            assert!(!report.healthy);
+        }
+
+        #[tokio::test]
+        async fn reports_deprecations() {
+            let rename = compat::Rename {
+                old: "health-test-old",
+                new: "health-test-new",
+                since: "v0.0.1",
+            };
+            compat::record(compat::Deprecation::new(compat::Kind::Flag, &rename));
+            let f = Fixture::new("handler_health_deprecations").await;
+
+            let (status, bytes) = f.get("/v0.2/health").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let report: Report = serde_json::from_slice(&bytes).unwrap();
+            let deprecation = report
+                .deprecations
+                .iter()
+                .find(|d| d.name == "health-test-old")
+                .unwrap();
+            assert_eq!(deprecation.replacement, "health-test-new");
+        }
+
+        #[tokio::test]
+        async fn reports_degraded_workers() {
+            let dir = filesys::Dir::create_temp_dir("handler_health_data_dir")
+                .await
+                .unwrap();
+            let probes = Probes {
+                mqtt_enabled: true,
+                poller_enabled: true,
+                data_dir: Some(dir.clone()),
+                ..Default::default()
+            };
+            let f =
+                Fixture::with_state("handler_health_degraded", |state| state.with_health(probes))
+                    .await;
+
+            let (status, bytes) = f.get("/v0.2/health").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let report: Report = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(report.status, "ok");
+            assert!(!report.healthy);
+            let subsystems = &report.subsystems;
+            // the device starts out disconnected
+            assert_eq!(subsystems.mqtt.status, Status::Degraded);
+            assert_eq!(subsystems.mqtt.connected, Some(false));
+            // the poller hasn't run yet
+            assert_eq!(subsystems.poller.status, Status::Degraded);
+            assert_eq!(subsystems.poller.runs, 0);
+            // the disk holding the data directory is found
+            let disk = &subsystems.disk;
+            assert_ne!(disk.status, Status::Unknown, "{disk:?}");
+            assert!(disk.available_bytes.unwrap() <= disk.total_bytes.unwrap());
+
+            dir.delete().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn reports_connected_mqtt() {
+            let f = Fixture::with_state("handler_health_connected", |state| {
+                state.with_health(Probes {
+                    mqtt_enabled: true,
+                    ..Default::default()
+                })
+            })
+            .await;
+            f.state
+                .storage
+                .device
+                .patch(DeviceUpdates::connected())
+                .await
+                .unwrap();
+
+            let (_, bytes) = f.get("/v0.2/health").await;
+            let report: Report = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(report.subsystems.mqtt.status, Status::Ok);
+            assert_eq!(report.subsystems.mqtt.connected, Some(true));
+        }
+    }
+
+    mod metrics {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_prometheus_text() {
+            let f = Fixture::new("handler_metrics").await;
+            miru_agent::metrics::global().record_sync_success();
+
+            let response = f
+                .app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/metrics")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()["content-type"],
+                miru_agent::metrics::CONTENT_TYPE
+            );
+            let bytes = body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let text = String::from_utf8(bytes.to_vec()).unwrap();
+            assert!(text.contains("# TYPE miru_syncs_total counter\n"), "{text}");
+            assert!(
+                text.contains("miru_http_request_duration_seconds"),
+                "{text}"
+            );
+        }
+    }
+
+    mod log_level {
+        use super::*;
+
+        #[tokio::test]
+        async fn get_log_level_returns_the_current_level() {
+            let f = Fixture::new("handler_get_log_level").await;
+
+            let (status, bytes) = f.get("/v0.2/log_level").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["level"], "info");
+            assert_eq!(actual["env_filter_locked"], false);
+        }
+
+        #[tokio::test]
+        async fn put_log_level_changes_the_level() {
+            let f = Fixture::new("handler_put_log_level").await;
+
+            let (status, bytes) = f
+                .put_json("/v0.2/log_level", serde_json::json!({"level": "DEBUG"}))
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["level"], "debug");
+
+            let (_, bytes) = f.get("/v0.2/log_level").await;
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["level"], "debug");
+            assert_eq!(f.state.log_level.level(), miru_agent::logs::LogLevel::Debug);
+        }
+
+        #[tokio::test]
+        async fn put_log_level_rejects_unknown_levels() {
+            let f = Fixture::new("handler_put_log_level_invalid").await;
+
+            let (status, bytes) = f
+                .put_json("/v0.2/log_level", serde_json::json!({"level": "loud"}))
+                .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "invalid_log_level");
+
+            // the level is unchanged
+            assert_eq!(f.state.log_level.level(), miru_agent::logs::LogLevel::Info);
+        }
+    }
+
     mod device {
         use super::*;
 
@@ -189,6 +416,243 @@ pub mod routes {
             let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
             assert_eq!(actual.error.code, "internal_server_error");
         }
+
+        #[tokio::test]
+        async fn rotate_device_key_returns_500_when_the_token_cannot_be_issued() {
+            let f = Fixture::new("handler_rotate_device_key").await;
+
+            // The fixture's keys are placeholders, so the token needed to register the
+            // new key can't be signed
+            let (status, bytes) = f.post("/v0.2/device/keys/rotate").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "internal_server_error");
+        }
+    }
+
+    mod sync_history {
+        use super::*;
+
+        /// A syncer which only answers sync history requests.
+        fn syncer_with_history(records: Vec<Record>) -> Arc<Syncer> {
+            let (sender, mut receiver) = mpsc::channel(4);
+            tokio::spawn(async move {
+                while let Some(cmd) = receiver.recv().await {
+                    if let Command::GetSyncHistory { respond_to } = cmd {
+                        let _ = respond_to.send(Ok(records.clone()));
+                    }
+                }
+            });
+            Arc::new(Syncer::new(sender))
+        }
+
+        #[tokio::test]
+        async fn returns_summary_and_records() {
+            let records = vec![
+                Record {
+                    started_at: fixed_time(),
+                    duration_ms: 400,
+                    outcome: Outcome::Error,
+                    phases: Phases::default(),
+                    error: Some("backend unreachable".to_string()),
+                },
+                Record {
+                    started_at: fixed_time(),
+                    duration_ms: 100,
+                    outcome: Outcome::Success,
+                    phases: Phases {
+                        fetch_ms: 60,
+                        download_ms: 30,
+                        materialize_ms: 5,
+                        reconcile_ms: 5,
+                    },
+                    error: None,
+                },
+            ];
+            let syncer = syncer_with_history(records.clone());
+            let f = Fixture::with_state("handler_sync_history", |state| State { syncer, ..state })
+                .await;
+
+            let (status, bytes) = f.get("/v0.2/device/sync/history").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            let actual_records: Vec<Record> =
+                serde_json::from_value(actual["records"].clone()).unwrap();
+            assert_eq!(actual_records, records);
+            let summary: Summary = serde_json::from_value(actual["summary"].clone()).unwrap();
+            assert_eq!(summary.syncs, 2);
+            assert_eq!(summary.success_streak, 1);
+            assert_eq!(summary.duration.unwrap().p95_ms, 400);
+            assert_eq!(summary.phases.unwrap().fetch.p95_ms, 60);
+
+            // the health report summarizes the same history
+            let (_, bytes) = f.get("/v0.2/health").await;
+            let report: Report = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(report.subsystems.syncer.history, Some(summary));
+        }
+
+        #[tokio::test]
+        async fn filters_records_by_query() {
+            let record = |minutes: i64, outcome: Outcome| Record {
+                started_at: fixed_time() + chrono::TimeDelta::minutes(minutes),
+                duration_ms: 100,
+                outcome,
+                phases: Phases::default(),
+                error: None,
+            };
+            let records = vec![
+                record(0, Outcome::Error),
+                record(1, Outcome::Success),
+                record(2, Outcome::Error),
+                record(3, Outcome::Error),
+            ];
+            let syncer = syncer_with_history(records.clone());
+            let f = Fixture::with_state("handler_sync_history_query", |state| State {
+                syncer,
+                ..state
+            })
+            .await;
+
+            let (status, bytes) = f
+                .get("/v0.2/device/sync/history?outcome=error&limit=2")
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            let actual_records: Vec<Record> =
+                serde_json::from_value(actual["records"].clone()).unwrap();
+            assert_eq!(actual_records, records[2..].to_vec());
+            // the summary still covers every sync
+            let summary: Summary = serde_json::from_value(actual["summary"].clone()).unwrap();
+            assert_eq!(summary.syncs, 4);
+
+            let since = (fixed_time() + chrono::TimeDelta::minutes(1)).to_rfc3339();
+            let uri = format!(
+                "/v0.2/device/sync/history?since={}",
+                since.replace('+', "%2B")
+            );
+            let (_, bytes) = f.get(&uri).await;
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            let actual_records: Vec<Record> =
+                serde_json::from_value(actual["records"].clone()).unwrap();
+            assert_eq!(actual_records, records[1..].to_vec());
+
+            let (status, _) = f.get("/v0.2/device/sync/history?outcome=bogus").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn returns_500_when_syncer_channel_closed() {
+            let f = Fixture::new("handler_sync_history_closed").await;
+
+            let (status, _) = f.get("/v0.2/device/sync/history").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    mod sync_events {
+        use super::*;
+        use miru_agent::sync::event_log;
+        use miru_agent::sync::syncer::{SyncEvent, SyncFailure};
+
+        #[tokio::test]
+        async fn returns_records() {
+            let records = vec![
+                event_log::Record {
+                    at: fixed_time(),
+                    event: SyncEvent::SyncFailed(SyncFailure {
+                        is_network_conn_err: true,
+                    }),
+                },
+                event_log::Record {
+                    at: fixed_time() + chrono::TimeDelta::seconds(30),
+                    event: SyncEvent::SyncSuccess,
+                },
+            ];
+            let (sender, mut receiver) = mpsc::channel(4);
+            let served = records.clone();
+            tokio::spawn(async move {
+                while let Some(cmd) = receiver.recv().await {
+                    if let Command::GetSyncEvents { respond_to } = cmd {
+                        let _ = respond_to.send(Ok(served.clone()));
+                    }
+                }
+            });
+            let syncer = Arc::new(Syncer::new(sender));
+            let f =
+                Fixture::with_state("handler_sync_events", |state| State { syncer, ..state }).await;
+
+            let (status, bytes) = f.get("/v0.2/device/sync/events").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: Vec<event_log::Record> = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual, records);
+        }
+
+        #[tokio::test]
+        async fn returns_500_when_syncer_channel_closed() {
+            let f = Fixture::new("handler_sync_events_closed").await;
+
+            let (status, _) = f.get("/v0.2/device/sync/events").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    mod sync_plan {
+        use super::*;
+        use miru_agent::sync::plan::{Action, Plan, Step};
+
+        /// A syncer which only answers plan requests.
+        fn syncer_with_plan(plan: Plan) -> Arc<Syncer> {
+            let (sender, mut receiver) = mpsc::channel(4);
+            tokio::spawn(async move {
+                while let Some(cmd) = receiver.recv().await {
+                    if let Command::Plan { respond_to } = cmd {
+                        let _ = respond_to.send(Ok(plan.clone()));
+                    }
+                }
+            });
+            Arc::new(Syncer::new(sender))
+        }
+
+        #[tokio::test]
+        async fn returns_plan() {
+            let plan = Plan {
+                planned_at: fixed_time(),
+                safe_mode: false,
+                paused: false,
+                conflicts: Vec::new(),
+                steps: vec![Step {
+                    deployment_id: "dpl-1".into(),
+                    target_status: DplTarget::Deployed,
+                    activity_status: DplActivity::Queued,
+                    error_status: DplErrStatus::Retrying,
+                    action: Action::Wait,
+                    wait_secs: Some(30),
+                    new: false,
+                    listed: true,
+                }],
+            };
+            let syncer = syncer_with_plan(plan.clone());
+            let f =
+                Fixture::with_state("handler_sync_plan", |state| State { syncer, ..state }).await;
+
+            let (status, bytes) = f.get("/v0.2/device/sync/plan").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual, serde_json::to_value(&plan).unwrap());
+            assert_eq!(actual["steps"][0]["action"], "wait");
+            assert_eq!(actual["steps"][0]["target_status"], "deployed");
+        }
+
+        #[tokio::test]
+        async fn returns_500_when_syncer_channel_closed() {
+            let f = Fixture::new("handler_sync_plan_closed").await;
+
+            let (status, _) = f.get("/v0.2/device/sync/plan").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        }
     }
 
     mod deployments {
@@ -228,6 +692,64 @@ pub mod routes {
             );
         }
 
+        #[tokio::test]
+        async fn get_deployment_includes_fsm_state_and_filepaths() {
+            let f = Fixture::new("handler_get_dpl_state").await;
+            let cfg_inst = ConfigInstance {
+                id: "ci-1".into(),
+                filepath: "/srv/a.json".into(),
+                ..Default::default()
+            };
+            f.state
+                .storage
+                .cfg_insts
+                .meta
+                .write("ci-1".to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+            let mut dpl = Deployment {
+                id: "dpl-1".into(),
+                activity_status: DplActivity::Queued,
+                error_status: DplErrStatus::Retrying,
+                target_status: DplTarget::Deployed,
+                attempts: 2,
+                // ci-2 hasn't been downloaded yet
+                config_instance_ids: vec!["ci-1".into(), "ci-2".into()],
+                ..Default::default()
+            };
+            dpl.set_cooldown(chrono::TimeDelta::minutes(5));
+            let cooldown_ends_at = dpl.cooldown_ends_at.to_rfc3339();
+            f.state
+                .storage
+                .deployments
+                .write("dpl-1".to_string(), dpl, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+
+            let (status, bytes) = f.get("/v0.2/deployments/dpl-1").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["id"], "dpl-1");
+            assert_eq!(actual["error_status"], "retrying");
+            assert_eq!(actual["attempts"], 2);
+            assert_eq!(actual["cooldown_ends_at"], cooldown_ends_at);
+            assert!(actual["deployed_at"].is_null());
+            assert_eq!(
+                actual["config_instance_ids"],
+                serde_json::json!(["ci-1", "ci-2"])
+            );
+            assert_eq!(actual["filepaths"], serde_json::json!(["/srv/a.json"]));
+
+            let (status, bytes) = f.get("/v0.2/deployments").await;
+            assert_eq!(status, StatusCode::OK);
+            let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(page["data"][0]["attempts"], 2);
+            assert_eq!(
+                page["data"][0]["filepaths"],
+                serde_json::json!(["/srv/a.json"])
+            );
+        }
+
         #[tokio::test]
         async fn get_deployment_returns_404_when_missing() {
             let f = Fixture::new("handler_get_dpl_404").await;
@@ -295,6 +817,63 @@ pub mod routes {
             assert_eq!(status, StatusCode::NOT_FOUND);
         }
 
+        #[tokio::test]
+        async fn list_deployments_pages_newest_first() {
+            let f = Fixture::new("handler_list_dpls").await;
+            let t = fixed_time();
+            for (i, id) in ["dpl-1", "dpl-2", "dpl-3"].into_iter().enumerate() {
+                let dpl = Deployment {
+                    id: id.into(),
+                    created_at: t + chrono::Duration::seconds(i as i64),
+                    ..Default::default()
+                };
+                f.state
+                    .storage
+                    .deployments
+                    .write(id.to_string(), dpl, |_, _| false, Overwrite::Allow)
+                    .await
+                    .unwrap();
+            }
+
+            let (status, bytes) = f.get("/v0.2/deployments?limit=2").await;
+            assert_eq!(status, StatusCode::OK);
+            let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(page["object"], "list");
+            assert_eq!(page["has_more"], true);
+            let ids: Vec<&str> = page["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|d| d["id"].as_str().unwrap())
+                .collect();
+            assert_eq!(ids, vec!["dpl-3", "dpl-2"]);
+
+            let cursor = page["next_cursor"].as_str().unwrap();
+            let (status, bytes) = f
+                .get(&format!("/v0.2/deployments?limit=2&cursor={cursor}"))
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(page["data"][0]["id"], "dpl-1");
+            assert_eq!(page["has_more"], false);
+            assert!(page["next_cursor"].is_null());
+        }
+
+        #[tokio::test]
+        async fn list_deployments_rejects_invalid_filters() {
+            let f = Fixture::new("handler_list_dpls_400").await;
+
+            let (status, bytes) = f.get("/v0.2/deployments?activity_status=bogus").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "invalid_query");
+
+            let (status, bytes) = f.get("/v0.2/deployments?cursor=garbage").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "malformed_cursor");
+        }
+
         #[tokio::test]
         async fn current_route_not_captured_as_deployment_id() {
             let f = Fixture::new("handler_cur_route").await;
@@ -325,6 +904,548 @@ pub mod routes {
             let (status, _) = f.get("/v0.2/deployments/current").await;
             assert_eq!(status, StatusCode::NOT_FOUND);
         }
+
+        #[tokio::test]
+        async fn get_deployment_diff_returns_200() {
+            let f = Fixture::new("handler_get_dpl_diff").await;
+            let cfg_inst = ConfigInstance {
+                id: "ci-1".into(),
+                filepath: "/srv/a.json".into(),
+                ..Default::default()
+            };
+            let cfg_insts = &f.state.storage.cfg_insts;
+            cfg_insts
+                .meta
+                .write("ci-1".to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+            cfg_insts
+                .content
+                .write(
+                    "ci-1".to_string(),
+                    "{}".to_string(),
+                    |_, _| false,
+                    Overwrite::Allow,
+                )
+                .await
+                .unwrap();
+            let dpl = Deployment {
+                id: "dpl-1".into(),
+                activity_status: DplActivity::Queued,
+                config_instance_ids: vec!["ci-1".into()],
+                ..Default::default()
+            };
+            f.state
+                .storage
+                .deployments
+                .write("dpl-1".to_string(), dpl, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+
+            let (status, bytes) = f.get("/v0.2/deployments/dpl-1/diff?text=true").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["deployment_id"], "dpl-1");
+            assert_eq!(actual["current_deployment_id"], serde_json::Value::Null);
+            assert_eq!(actual["files"][0]["filepath"], "/srv/a.json");
+            assert_eq!(actual["files"][0]["change"], "added");
+            assert_eq!(actual["files"][0]["text_diff"], "+{}\n");
+            assert_eq!(actual["files"][0]["new_config_instance_id"], "ci-1");
+
+            // the text diff exposed the content so the read is audited
+            let accesses = f
+                .state
+                .storage
+                .config_access
+                .query(&Default::default())
+                .await;
+            assert_eq!(accesses.len(), 1);
+            assert_eq!(accesses[0].config_instance_id, "ci-1");
+        }
+
+        #[tokio::test]
+        async fn get_deployment_diff_without_text_is_not_audited() {
+            let f = Fixture::new("handler_get_dpl_diff_no_text").await;
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", "{}").await;
+            let dpl = Deployment {
+                id: "dpl-1".into(),
+                activity_status: DplActivity::Queued,
+                config_instance_ids: vec!["ci-1".into()],
+                ..Default::default()
+            };
+            f.state
+                .storage
+                .deployments
+                .write("dpl-1".to_string(), dpl, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+
+            let (status, _) = f.get("/v0.2/deployments/dpl-1/diff").await;
+            assert_eq!(status, StatusCode::OK);
+            let accesses = f
+                .state
+                .storage
+                .config_access
+                .query(&Default::default())
+                .await;
+            assert!(accesses.is_empty());
+        }
+    }
+
+    async fn write_cfg_inst(f: &Fixture, id: &str, filepath: &str, content: &str) {
+        let cfg_inst = ConfigInstance {
+            id: id.into(),
+            config_type_name: "motion".into(),
+            filepath: filepath.into(),
+            ..Default::default()
+        };
+        let cfg_insts = &f.state.storage.cfg_insts;
+        cfg_insts
+            .meta
+            .write(id.to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        cfg_insts
+            .content
+            .write(
+                id.to_string(),
+                content.to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
+
+    mod config_instances {
+        use super::*;
+
+        #[tokio::test]
+        async fn get_config_instance_returns_200_and_is_audited() {
+            let f = Fixture::new("handler_get_cfg_inst").await;
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", r#"{"speed":4}"#).await;
+
+            let (status, bytes) = f.get("/v0.2/config_instances/ci-1").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["id"], "ci-1");
+            assert_eq!(actual["config_type_name"], "motion");
+            assert_eq!(actual["filepath"], "/srv/a.json");
+            assert_eq!(actual["content"], r#"{"speed":4}"#);
+
+            // routed without a connection so the client is unknown
+            let accesses = f
+                .state
+                .storage
+                .config_access
+                .query(&Default::default())
+                .await;
+            assert_eq!(accesses.len(), 1);
+            assert_eq!(accesses[0].config_instance_id, "ci-1");
+            assert_eq!(accesses[0].filepath, "/srv/a.json");
+            assert_eq!(accesses[0].client, Default::default());
+        }
+
+        #[tokio::test]
+        async fn get_config_instance_returns_404_when_missing() {
+            let f = Fixture::new("handler_get_cfg_inst_missing").await;
+
+            let (status, _) = f.get("/v0.2/config_instances/missing").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let accesses = f
+                .state
+                .storage
+                .config_access
+                .query(&Default::default())
+                .await;
+            assert!(accesses.is_empty());
+        }
+
+        async fn deploy(f: &Fixture, cfg_inst_ids: &[&str]) {
+            let dpl = Deployment {
+                id: "dpl-1".into(),
+                activity_status: DplActivity::Deployed,
+                target_status: DplTarget::Deployed,
+                config_instance_ids: cfg_inst_ids.iter().map(|id| id.to_string()).collect(),
+                ..Default::default()
+            };
+            f.state
+                .storage
+                .deployments
+                .write("dpl-1".to_string(), dpl, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+        }
+
+        async fn get_if_none_match(
+            f: &Fixture,
+            uri: &str,
+            etag: &str,
+        ) -> (StatusCode, Option<String>, Vec<u8>) {
+            let response = f
+                .app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("if-none-match", etag)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let etag = response
+                .headers()
+                .get("etag")
+                .map(|v| v.to_str().unwrap().to_string());
+            let bytes = body::to_bytes(response.into_body(), 16384).await.unwrap();
+            (status, etag, bytes.to_vec())
+        }
+
+        #[tokio::test]
+        async fn get_config_type_content_serves_the_deployed_instance() {
+            let f = Fixture::new("handler_get_cfg_type").await;
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", r#"{"speed":4}"#).await;
+            deploy(&f, &["ci-1"]).await;
+
+            let (status, bytes) = f.get("/v0.2/config_instances/motion/content").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["id"], "ci-1");
+            assert_eq!(actual["filepath"], "/srv/a.json");
+            assert_eq!(actual["content"], r#"{"speed":4}"#);
+
+            let accesses = f
+                .state
+                .storage
+                .config_access
+                .query(&Default::default())
+                .await;
+            assert_eq!(accesses.len(), 1);
+            assert_eq!(accesses[0].config_instance_id, "ci-1");
+        }
+
+        #[tokio::test]
+        async fn get_config_type_content_honors_if_none_match() {
+            let f = Fixture::new("handler_get_cfg_type_etag").await;
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", r#"{"speed":4}"#).await;
+            deploy(&f, &["ci-1"]).await;
+            let uri = "/v0.2/config_instances/motion/content";
+
+            let (status, etag, _) = get_if_none_match(&f, uri, "\"stale\"").await;
+            assert_eq!(status, StatusCode::OK);
+            let etag = etag.unwrap();
+
+            let (status, actual, bytes) = get_if_none_match(&f, uri, &etag).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED);
+            assert_eq!(actual.as_deref(), Some(etag.as_str()));
+            assert!(bytes.is_empty());
+
+            // weak and listed tags match too
+            let listed = format!("\"other\", W/{etag}");
+            let (status, _, _) = get_if_none_match(&f, uri, &listed).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+            // the tag changes with the content
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", r#"{"speed":5}"#).await;
+            let (status, changed, _) = get_if_none_match(&f, uri, &etag).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_ne!(changed.unwrap(), etag);
+        }
+
+        #[tokio::test]
+        async fn get_config_type_content_returns_404_when_not_deployed() {
+            let f = Fixture::new("handler_get_cfg_type_missing").await;
+            // cached but not deployed
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", "{}").await;
+
+            let (status, bytes) = f.get("/v0.2/config_instances/motion/content").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "resource_not_found");
+        }
+    }
+
+    mod audit {
+        use super::*;
+
+        #[tokio::test]
+        async fn get_config_access_returns_matching_accesses() {
+            let f = Fixture::new("handler_config_access").await;
+            write_cfg_inst(&f, "ci-1", "/srv/a.json", "{}").await;
+            write_cfg_inst(&f, "ci-2", "/srv/b.json", "{}").await;
+            f.get("/v0.2/config_instances/ci-1").await;
+            f.get("/v0.2/config_instances/ci-1").await;
+            f.get("/v0.2/config_instances/ci-2").await;
+
+            let (status, bytes) = f.get("/v0.2/audit/config_access").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["accesses"].as_array().unwrap().len(), 2);
+
+            let (status, bytes) = f
+                .get("/v0.2/audit/config_access?config_instance_id=ci-1")
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            let accesses = actual["accesses"].as_array().unwrap();
+            assert_eq!(accesses.len(), 1);
+            assert_eq!(accesses[0]["config_instance_id"], "ci-1");
+            assert_eq!(accesses[0]["reads"], 2);
+        }
+
+        #[tokio::test]
+        async fn get_config_access_rejects_invalid_filters() {
+            let f = Fixture::new("handler_config_access_invalid").await;
+            let (status, _) = f.get("/v0.2/audit/config_access?uid=root").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn verify_config_access() {
+            let f = Fixture::new("handler_config_access_verify").await;
+            let log = &f.state.storage.config_access;
+            let long_ago = chrono::Utc::now() - chrono::TimeDelta::hours(1);
+            log.record_at(&Default::default(), "ci-1", "/srv/a.json", long_ago)
+                .await;
+            let anchor = log.anchor().await.unwrap();
+
+            let (status, bytes) = f.get("/v0.2/audit/config_access/verify").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["sealed"], 1);
+            assert_eq!(actual["head"]["hash"], anchor.hash);
+            assert_eq!(actual["broken_at"], serde_json::Value::Null);
+            assert_eq!(actual["anchor"], serde_json::Value::Null);
+
+            let (status, bytes) = f
+                .get(&format!(
+                    "/v0.2/audit/config_access/verify?seq=0&hash={}",
+                    "f".repeat(64)
+                ))
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["anchor"], "mismatch");
+        }
+    }
+
+    mod deployment_dir {
+        use super::*;
+        use miru_agent::deploy::versions::Versions;
+        use miru_agent::filesys::WriteOptions;
+
+        async fn versions(dir: &filesys::Dir) -> Versions {
+            let versions = Versions::new(dir.subdir("srv"));
+            for i in 1..=2 {
+                let staged = versions.stage().await.unwrap();
+                staged
+                    .file("app.json")
+                    .write_string(&i.to_string(), WriteOptions::OVERWRITE_ATOMIC)
+                    .await
+                    .unwrap();
+                let number = versions.promote(&staged, "dpl-1").await.unwrap();
+                versions.activate(number).await.unwrap();
+            }
+            versions
+        }
+
+        #[tokio::test]
+        async fn disabled_returns_404() {
+            let f = Fixture::new("handler_dpl_dir_disabled").await;
+
+            let (status, bytes) = f.get("/v0.2/deployment_dir/releases").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "resource_not_found");
+
+            let (status, _) = f.post("/v0.2/deployment_dir/rollback").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn list_releases_returns_200() {
+            let dir = filesys::Dir::create_temp_dir("handler_dpl_dir_list")
+                .await
+                .unwrap();
+            let versions = versions(&dir).await;
+            let f = Fixture::with_state("handler_dpl_dir_list", |state| {
+                state.with_versions(Some(versions))
+            })
+            .await;
+
+            let (status, bytes) = f.get("/v0.2/deployment_dir/releases").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            let releases = actual["releases"].as_array().unwrap();
+            assert_eq!(releases.len(), 2);
+            assert_eq!(releases[0]["number"], 1);
+            assert_eq!(releases[0]["live"], false);
+            assert_eq!(releases[1]["deployment_id"], "dpl-1");
+            assert_eq!(releases[1]["live"], true);
+            dir.delete().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn rollback_returns_200() {
+            let dir = filesys::Dir::create_temp_dir("handler_dpl_dir_rollback")
+                .await
+                .unwrap();
+            let versions = versions(&dir).await;
+            let f = Fixture::with_state("handler_dpl_dir_rollback", |state| {
+                state.with_versions(Some(versions.clone()))
+            })
+            .await;
+
+            let (status, bytes) = f.post("/v0.2/deployment_dir/rollback").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["release"], 1);
+            assert_eq!(versions.current().await.unwrap(), Some(1));
+
+            // release 2 was rolled back from and release 1 is live
+            let (status, bytes) = f.post("/v0.2/deployment_dir/rollback").await;
+            assert_eq!(status, StatusCode::CONFLICT);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "resource_conflict");
+
+            let (status, _) = f.post("/v0.2/deployment_dir/rollback?release=2").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(versions.current().await.unwrap(), Some(2));
+
+            let (status, _) = f.post("/v0.2/deployment_dir/rollback?release=9").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            dir.delete().await.unwrap();
+        }
+    }
+
+    mod deployments_pause {
+        use super::*;
+        use miru_agent::deploy::pause;
+
+        /// A syncer which answers sync requests and counts them.
+        fn counting_syncer() -> (Arc<Syncer>, Arc<std::sync::atomic::AtomicUsize>) {
+            let syncs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let counter = syncs.clone();
+            let (sender, mut receiver) = mpsc::channel(4);
+            tokio::spawn(async move {
+                while let Some(cmd) = receiver.recv().await {
+                    if let Command::Sync { respond_to } = cmd {
+                        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        let _ = respond_to.send(Ok(()));
+                    }
+                }
+            });
+            (Arc::new(Syncer::new(sender)), syncs)
+        }
+
+        #[tokio::test]
+        async fn disabled_returns_404() {
+            let f = Fixture::new("handler_pause_disabled").await;
+
+            // routed to the pause handler rather than looked up as a deployment
+            let (status, bytes) = f.get("/v0.2/deployments/pause").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let actual: openapi::ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual.error.code, "resource_not_found");
+            assert!(actual.error.message.contains("can't be paused"));
+
+            let (status, _) = f.post("/v0.2/deployments/pause").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let (status, _) = f.post("/v0.2/deployments/resume").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn pause_and_resume() {
+            let dir = filesys::Dir::create_temp_dir("handler_pause")
+                .await
+                .unwrap();
+            let switch = pause::Switch::new(dir.file("deployments_paused.json"));
+            let (syncer, syncs) = counting_syncer();
+            let f = Fixture::with_state("handler_pause", |state| {
+                State { syncer, ..state }.with_pause(Some(switch.clone()))
+            })
+            .await;
+
+            let (status, bytes) = f.get("/v0.2/deployments/pause").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["paused"], false);
+
+            let (status, bytes) = f.post("/v0.2/deployments/pause?reason=INC-42").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["paused"], true);
+            assert_eq!(actual["reason"], "INC-42");
+            assert_eq!(actual["changed_by"], "socket");
+            assert!(switch.is_paused().await);
+            assert_eq!(syncs.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+            let (status, bytes) = f.post("/v0.2/deployments/resume").await;
+            assert_eq!(status, StatusCode::OK);
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["paused"], false);
+            assert!(!switch.is_paused().await);
+            // the held deployments are applied right away
+            assert_eq!(syncs.load(std::sync::atomic::Ordering::SeqCst), 1);
+            dir.delete().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn resume_succeeds_when_the_sync_fails() {
+            let dir = filesys::Dir::create_temp_dir("handler_resume_sync_err")
+                .await
+                .unwrap();
+            let switch = pause::Switch::new(dir.file("deployments_paused.json"));
+            switch.pause(None, pause::Origin::Cli).await.unwrap();
+            // the fixture's syncer isn't running so syncing fails
+            let f = Fixture::with_state("handler_resume_sync_err", |state| {
+                state.with_pause(Some(switch.clone()))
+            })
+            .await;
+
+            let (status, _) = f.post("/v0.2/deployments/resume").await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(!switch.is_paused().await);
+            dir.delete().await.unwrap();
+        }
+    }
+
+    mod storage {
+        use super::*;
+
+        #[tokio::test]
+        async fn get_storage_wear_returns_200() {
+            let f = Fixture::new("handler_storage_wear").await;
+
+            let (status, bytes) = f.get("/v0.2/storage/wear").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert!(actual["total_bytes"].is_u64());
+            assert!(actual["logs_bytes"].is_u64());
+            assert!(actual["caches_bytes"].is_u64());
+            assert!(actual["deployments_bytes"].is_u64());
+            assert_eq!(actual["low_wear_mode"], false);
+        }
+
+        #[tokio::test]
+        async fn get_storage_sweep_returns_200() {
+            let f = Fixture::new("handler_storage_sweep").await;
+
+            let (status, bytes) = f.get("/v0.2/storage/sweep").await;
+            assert_eq!(status, StatusCode::OK);
+
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual["removed"], 0);
+            assert_eq!(actual["reclaimed_bytes"], 0);
+            assert_eq!(actual["failed"], 0);
+        }
     }
 
     mod releases {
@@ -340,6 +1461,7 @@ pub mod routes {
                 git_commit_id: Some("gc-1".into()),
                 created_at: t,
                 updated_at: t,
+                signature: None,
             };
             f.state
                 .storage
@@ -398,6 +1520,7 @@ pub mod routes {
                 git_commit_id: None,
                 created_at: t,
                 updated_at: t,
+                signature: None,
             };
             f.state
                 .storage
@@ -468,4 +1591,62 @@ pub mod routes {
             assert_eq!(actual.error.code, "internal_server_error");
         }
     }
+
+    mod openapi_document {
+        use super::*;
+        use miru_agent::server::openapi::{self as spec, Method};
+
+        #[tokio::test]
+        async fn serves_the_document() {
+            let f = Fixture::new("handler_openapi").await;
+
+            let response = f
+                .app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/v0.2/openapi.json")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let actual: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(actual, spec::document());
+        }
+
+        #[tokio::test]
+        async fn every_operation_is_routed() {
+            let f = Fixture::new("handler_openapi_routed").await;
+
+            for operation in spec::operations() {
+                // path parameters are filled with ids which don't exist
+                let uri = operation.route.replace('{', "missing_").replace('}', "");
+                let request = match operation.method {
+                    Method::Get => Request::builder().uri(&uri).body(Body::empty()),
+                    Method::Post => Request::builder()
+                        .method("POST")
+                        .uri(&uri)
+                        .body(Body::empty()),
+                    Method::Put => Request::builder()
+                        .method("PUT")
+                        .uri(&uri)
+                        .header("content-type", "application/json")
+                        .body(Body::from(r#"{"level":"info"}"#)),
+                };
+                let response = f.app.clone().oneshot(request.unwrap()).await.unwrap();
+                let status = response.status();
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{uri}");
+                if status == StatusCode::NOT_FOUND {
+                    // not found by a handler rather than the router
+                    let bytes = body::to_bytes(response.into_body(), 16384).await.unwrap();
+                    assert!(!bytes.is_empty(), "{uri} isn't routed");
+                }
+            }
+        }
+    }
 }
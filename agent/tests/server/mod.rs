@@ -1,4 +1,7 @@
 pub mod errors;
 pub mod handlers;
+pub mod openapi;
+pub mod peer;
 pub mod response;
+pub mod shed;
 pub mod sse;
@@ -0,0 +1,123 @@
+// standard crates
+use std::collections::HashSet;
+
+// internal crates
+use miru_agent::server::openapi;
+use miru_agent::version;
+
+// external crates
+use serde_json::Value;
+
+fn refs(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("$ref", Value::String(reference)) => found.push(reference.clone()),
+                    _ => refs(value, found),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+        _ => {}
+    }
+}
+
+pub mod document {
+    use super::*;
+
+    #[test]
+    fn describes_the_api_version() {
+        let document = openapi::document();
+        assert_eq!(document["openapi"], "3.0.3");
+        assert_eq!(document["info"]["version"], version::api_version());
+        assert_eq!(document["info"]["x-agent-version"], version::VERSION);
+    }
+
+    #[test]
+    fn every_reference_resolves() {
+        let document = openapi::document();
+        let mut found = Vec::new();
+        refs(&document, &mut found);
+        assert!(!found.is_empty());
+
+        for reference in found {
+            let pointer = reference
+                .strip_prefix('#')
+                .unwrap_or_else(|| panic!("unexpected reference {reference}"));
+            assert!(
+                document.pointer(pointer).is_some(),
+                "{reference} doesn't resolve"
+            );
+        }
+    }
+
+    #[test]
+    fn lists_every_operation() {
+        let document = openapi::document();
+        let paths = document["paths"].as_object().unwrap();
+        let operations = openapi::operations();
+        for operation in &operations {
+            let path = &paths[&operation.path];
+            assert_eq!(
+                path[operation.method.as_str()]["operationId"],
+                operation.operation_id
+            );
+        }
+        let count: usize = paths
+            .values()
+            .map(|p| {
+                p.as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|k| *k != "servers")
+                    .count()
+            })
+            .sum();
+        assert_eq!(count, operations.len());
+    }
+
+    #[test]
+    fn operation_ids_are_unique() {
+        let mut ids = HashSet::new();
+        for operation in openapi::operations() {
+            assert!(
+                ids.insert(operation.operation_id.clone()),
+                "{}",
+                operation.operation_id
+            );
+        }
+    }
+
+    #[test]
+    fn path_parameters_match_the_path() {
+        for operation in openapi::operations() {
+            let declared: Vec<_> = operation
+                .params
+                .iter()
+                .filter(|p| p.location == "path")
+                .map(|p| format!("{{{}}}", p.name))
+                .collect();
+            let in_path = operation.path.matches('{').count();
+            assert_eq!(declared.len(), in_path, "{}", operation.path);
+            for param in declared {
+                assert!(operation.path.contains(&param), "{}", operation.path);
+            }
+        }
+    }
+
+    #[test]
+    fn versioned_routes_are_prefixed() {
+        let operations = openapi::operations();
+        let health = operations
+            .iter()
+            .find(|o| o.operation_id == "health")
+            .unwrap();
+        assert_eq!(health.route, format!("/{}/health", version::api_version()));
+        let metrics = operations
+            .iter()
+            .find(|o| o.operation_id == "metrics")
+            .unwrap();
+        assert_eq!(metrics.route, "/metrics");
+    }
+}
@@ -0,0 +1,64 @@
+// standard crates
+use std::os::unix::fs::MetadataExt;
+use std::sync::Arc;
+
+// internal crates
+use miru_agent::audit::Query;
+use miru_agent::filesys::{Overwrite, PathExt};
+use miru_agent::http;
+use miru_agent::models::ConfigInstance;
+use miru_agent::testkit::Harness;
+
+// external crates
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+#[tokio::test]
+async fn records_the_peer_credentials_of_socket_clients() {
+    let http_client = Arc::new(http::Client::new("http://localhost:1").unwrap());
+    let harness = Harness::new("server_peer_creds", http_client).await;
+    let cfg_insts = &harness.storage.cfg_insts;
+    let cfg_inst = ConfigInstance {
+        id: "ci-1".into(),
+        filepath: "/srv/a.json".into(),
+        ..Default::default()
+    };
+    cfg_insts
+        .meta
+        .write("ci-1".to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+        .await
+        .unwrap();
+    cfg_insts
+        .content
+        .write(
+            "ci-1".to_string(),
+            "{}".to_string(),
+            |_, _| false,
+            Overwrite::Allow,
+        )
+        .await
+        .unwrap();
+    let server = harness.serve().await;
+
+    let api_version = device_api::models::ApiVersion::API_VERSION.to_string();
+    let mut stream = UnixStream::connect(server.socket_file.path())
+        .await
+        .unwrap();
+    let request = format!(
+        "GET /{api_version}/config_instances/ci-1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    // the socket file is owned by this process's user
+    let uid = std::fs::metadata(server.socket_file.path()).unwrap().uid();
+    let accesses = harness.storage.config_access.query(&Query::default()).await;
+    assert_eq!(accesses.len(), 1);
+    assert_eq!(accesses[0].client.uid, Some(uid));
+    assert_eq!(accesses[0].client.pid, Some(std::process::id() as i32));
+
+    server.shutdown().await.unwrap();
+    harness.cleanup().await;
+}
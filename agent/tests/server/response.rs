@@ -3,6 +3,7 @@ use device_api::models as openapi;
 use miru_agent::models::{
     Deployment, Device, DeviceStatus, DplActivity, DplErrStatus, DplTarget, GitCommit, Release,
 };
+use miru_agent::server::response;
 
 // external crates
 use chrono::{DateTime, TimeZone, Utc};
@@ -207,6 +208,49 @@ pub mod deployment_response {
     }
 }
 
+pub mod deployment_state_response {
+    use super::*;
+
+    #[test]
+    fn includes_fsm_state_and_filepaths() {
+        let t = fixed_time();
+        let dpl = Deployment {
+            id: "dpl-1".into(),
+            activity_status: DplActivity::Deployed,
+            target_status: DplTarget::Deployed,
+            attempts: 3,
+            cooldown_ends_at: t,
+            deployed_at: Some(t),
+            config_instance_ids: vec!["ci-1".into()],
+            ..Default::default()
+        };
+
+        let state = response::deployment_state(&dpl, vec!["/srv/a.json".into()]);
+        assert_eq!(state.id, "dpl-1");
+        assert_eq!(state.attempts, 3);
+        // the cooldown ended long ago
+        assert_eq!(state.cooldown_ends_at, None);
+        assert_eq!(state.deployed_at, Some(t.to_rfc3339()));
+        assert_eq!(state.archived_at, None);
+        assert_eq!(state.config_instance_ids, vec!["ci-1".to_string()]);
+        assert_eq!(state.filepaths, vec!["/srv/a.json".to_string()]);
+    }
+
+    #[test]
+    fn serializes_flat() {
+        let dpl = Deployment {
+            id: "dpl-1".into(),
+            ..Default::default()
+        };
+        let value = serde_json::to_value(response::deployment_state(&dpl, Vec::new())).unwrap();
+        assert_eq!(value["object"], "deployment");
+        assert_eq!(value["id"], "dpl-1");
+        assert_eq!(value["attempts"], 0);
+        assert!(value["cooldown_ends_at"].is_null());
+        assert_eq!(value["filepaths"], serde_json::json!([]));
+    }
+}
+
 pub mod release_response {
     use super::*;
 
@@ -219,6 +263,7 @@ pub mod release_response {
             git_commit_id: None,
             created_at: t,
             updated_at: t,
+            signature: None,
         };
 
         let expected = openapi::Release {
@@ -242,6 +287,7 @@ pub mod release_response {
             git_commit_id: Some("gc-1".into()),
             created_at: t,
             updated_at: t,
+            signature: None,
         };
 
         let expected = openapi::Release {
@@ -0,0 +1,160 @@
+// standard crates
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// internal crates
+use miru_agent::server::shed::{self, Reason, Shedder};
+
+// external crates
+use axum::body::{self, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use tower::ServiceExt;
+
+fn options() -> shed::Options {
+    shed::Options {
+        max_in_flight: 2,
+        max_latency: Duration::from_millis(100),
+        retry_after: Duration::from_secs(1),
+    }
+}
+
+pub mod admit {
+    use super::*;
+
+    #[test]
+    fn sheds_past_the_in_flight_limit() {
+        let shedder = Shedder::new(options());
+        let now = Instant::now();
+
+        let first = shedder.admit(now).unwrap();
+        let _second = shedder.admit(now).unwrap();
+        assert_eq!(shedder.in_flight(), 2);
+        assert_eq!(shedder.admit(now).unwrap_err(), Reason::InFlight);
+
+        // completing a request frees its slot
+        first.complete(now);
+        assert_eq!(shedder.in_flight(), 1);
+        assert!(shedder.admit(now).is_ok());
+    }
+
+    #[test]
+    fn dropped_permits_free_their_slot_without_a_sample() {
+        let shedder = Shedder::new(options());
+        let permit = shedder.admit(Instant::now()).unwrap();
+        drop(permit);
+        assert_eq!(shedder.in_flight(), 0);
+        assert_eq!(shedder.latency(), None);
+    }
+
+    #[test]
+    fn sheds_while_requests_are_slow() {
+        let shedder = Shedder::new(options());
+        let start = Instant::now();
+        let done = start + Duration::from_millis(500);
+        shedder.admit(start).unwrap().complete(done);
+        assert_eq!(shedder.latency(), Some(Duration::from_millis(500)));
+
+        assert_eq!(
+            shedder.admit(done + Duration::from_millis(10)).unwrap_err(),
+            Reason::Latency
+        );
+
+        // requests are admitted again once no request has completed for a while,
+        // so the agent's recovery can be measured
+        assert!(shedder.admit(done + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn fast_requests_bring_the_latency_down() {
+        let shedder = Shedder::new(options());
+        let start = Instant::now();
+        let mut now = start + Duration::from_millis(500);
+        shedder.admit(start).unwrap().complete(now);
+
+        // while slow, a request is let through each retry interval to probe
+        let mut probes = 0;
+        while shedder.latency().unwrap() > Duration::from_millis(100) {
+            now += Duration::from_secs(1);
+            let permit = shedder.admit(now).unwrap();
+            now += Duration::from_millis(1);
+            permit.complete(now);
+            assert_eq!(
+                shedder.admit(now).is_err(),
+                shedder.latency().unwrap() > Duration::from_millis(100)
+            );
+            probes += 1;
+        }
+        assert!(probes < 10, "{probes}");
+        assert!(shedder.admit(now).is_ok());
+    }
+}
+
+pub mod middleware {
+    use super::*;
+
+    fn app(shedder: Arc<Shedder>) -> Router {
+        Router::new()
+            .route("/v1/device", get(|| async { "device" }))
+            .route("/v1/health", get(|| async { "ok" }))
+            .route("/metrics", get(|| async { "metrics" }))
+            .layer(axum::middleware::from_fn(
+                move |req: axum::extract::Request, next: axum::middleware::Next| {
+                    shed::middleware(shedder.clone(), req, next)
+                },
+            ))
+    }
+
+    async fn request(app: &Router, uri: &str) -> axum::response::Response {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn admits_requests_under_the_limits() {
+        let shedder = Arc::new(Shedder::new(options()));
+        let app = app(shedder.clone());
+
+        let response = request(&app, "/v1/device").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(shedder.in_flight(), 0);
+        assert!(shedder.latency().is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_with_503_and_retry_after() {
+        let shedder = Arc::new(Shedder::new(shed::Options {
+            retry_after: Duration::from_millis(2500),
+            ..options()
+        }));
+        let app = app(shedder.clone());
+        let _first = shedder.admit(Instant::now()).unwrap();
+        let _second = shedder.admit(Instant::now()).unwrap();
+
+        let response = request(&app, "/v1/device").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+        let bytes = body::to_bytes(response.into_body(), 4096).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"]["code"], "overloaded");
+        assert_eq!(body["error"]["params"]["retry_after_secs"], 3);
+    }
+
+    #[tokio::test]
+    async fn never_sheds_health_or_metrics() {
+        let shedder = Arc::new(Shedder::new(options()));
+        let app = app(shedder.clone());
+        let _first = shedder.admit(Instant::now()).unwrap();
+        let _second = shedder.admit(Instant::now()).unwrap();
+
+        assert_eq!(request(&app, "/v1/health").await.status(), StatusCode::OK);
+        assert_eq!(request(&app, "/metrics").await.status(), StatusCode::OK);
+        assert_eq!(
+            request(&app, "/v1/device").await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
@@ -0,0 +1,139 @@
+// internal crates
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplTarget};
+use miru_agent::services::config_instance as cfg_inst_svc;
+use miru_agent::services::ServiceErr;
+use miru_agent::storage::{self, Storage};
+use miru_agent::testkit;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+async fn setup(name: &str) -> (filesys::Dir, Storage) {
+    let dir = testkit::temp_dir(name).await;
+    let storage = testkit::spawn_storage(&dir, storage::Capacities::default()).await;
+    (dir, storage)
+}
+
+async fn write_cfg_inst(storage: &Storage, id: &str, type_name: &str, created_secs: i64) {
+    let cfg_inst = ConfigInstance {
+        id: id.to_string(),
+        config_type_name: type_name.to_string(),
+        filepath: format!("/srv/{id}.json"),
+        created_at: DateTime::<Utc>::UNIX_EPOCH + TimeDelta::seconds(created_secs),
+        ..Default::default()
+    };
+    storage
+        .cfg_insts
+        .meta
+        .write(id.to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+        .await
+        .unwrap();
+    storage
+        .cfg_insts
+        .content
+        .write(
+            id.to_string(),
+            format!(r#"{{"id":"{id}"}}"#),
+            |_, _| false,
+            Overwrite::Allow,
+        )
+        .await
+        .unwrap();
+}
+
+async fn write_deployment(storage: &Storage, id: &str, activity: DplActivity, cfg_insts: &[&str]) {
+    let dpl = Deployment {
+        id: id.to_string(),
+        activity_status: activity,
+        target_status: DplTarget::Deployed,
+        config_instance_ids: cfg_insts.iter().map(|id| id.to_string()).collect(),
+        ..Default::default()
+    };
+    storage
+        .deployments
+        .write(id.to_string(), dpl, |_, _| false, Overwrite::Allow)
+        .await
+        .unwrap();
+}
+
+async fn get_latest(storage: &Storage, type_name: &str) -> Result<String, ServiceErr> {
+    cfg_inst_svc::get_latest(
+        &storage.deployments,
+        storage.cfg_insts.as_ref(),
+        type_name.to_string(),
+    )
+    .await
+    .map(|cfg_inst| cfg_inst.id)
+}
+
+pub mod get_latest {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_deployed_instance_of_the_type() {
+        let (dir, storage) = setup("svc_cfg_type_latest").await;
+        write_cfg_inst(&storage, "ci-motion", "motion", 0).await;
+        write_cfg_inst(&storage, "ci-camera", "camera", 0).await;
+        write_deployment(
+            &storage,
+            "dpl-1",
+            DplActivity::Deployed,
+            &["ci-motion", "ci-camera"],
+        )
+        .await;
+
+        let cfg_inst = cfg_inst_svc::get_latest(
+            &storage.deployments,
+            storage.cfg_insts.as_ref(),
+            "motion".to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cfg_inst.id, "ci-motion");
+        assert_eq!(cfg_inst.config_type_name, "motion");
+        assert_eq!(cfg_inst.filepath, "/srv/ci-motion.json");
+        assert_eq!(cfg_inst.content, r#"{"id":"ci-motion"}"#);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ignores_deployments_which_are_not_live() {
+        let (dir, storage) = setup("svc_cfg_type_not_live").await;
+        write_cfg_inst(&storage, "ci-queued", "motion", 2).await;
+        write_cfg_inst(&storage, "ci-archived", "motion", 1).await;
+        write_cfg_inst(&storage, "ci-drifted", "motion", 0).await;
+        write_deployment(&storage, "dpl-q", DplActivity::Queued, &["ci-queued"]).await;
+        write_deployment(&storage, "dpl-a", DplActivity::Archived, &["ci-archived"]).await;
+        write_deployment(&storage, "dpl-d", DplActivity::Drifted, &["ci-drifted"]).await;
+
+        assert_eq!(get_latest(&storage, "motion").await.unwrap(), "ci-drifted");
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prefers_the_most_recently_created() {
+        let (dir, storage) = setup("svc_cfg_type_newest").await;
+        write_cfg_inst(&storage, "ci-old", "motion", 0).await;
+        write_cfg_inst(&storage, "ci-new", "motion", 10).await;
+        write_deployment(&storage, "dpl-1", DplActivity::Deployed, &["ci-old"]).await;
+        write_deployment(&storage, "dpl-2", DplActivity::Deployed, &["ci-new"]).await;
+
+        assert_eq!(get_latest(&storage, "motion").await.unwrap(), "ci-new");
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn errors_when_no_instance_is_deployed() {
+        let (dir, storage) = setup("svc_cfg_type_missing").await;
+        write_cfg_inst(&storage, "ci-1", "motion", 0).await;
+        write_deployment(&storage, "dpl-1", DplActivity::Deployed, &["ci-1"]).await;
+
+        let err = get_latest(&storage, "camera").await.unwrap_err();
+        assert!(
+            matches!(err, ServiceErr::ConfigTypeNotDeployedErr(_)),
+            "{err:?}"
+        );
+        dir.delete().await.unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+// internal crates
+use crate::mocks::backend::PanicBackend;
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplTarget};
+use miru_agent::services::deployment::{self as dpl_svc, FileChange};
+use miru_agent::storage::{CfgInstContent, CfgInstRef, CfgInsts, ConfigSchemas, Deployments};
+use miru_agent::sync::patch::digest;
+
+struct Fixture {
+    _dir: filesys::Dir,
+    deployments: Deployments,
+    meta: CfgInsts,
+    content: CfgInstContent,
+    schemas: ConfigSchemas,
+}
+
+impl Fixture {
+    async fn new(name: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+        let (deployments, _) = Deployments::spawn(16, dir.file("deployments.json"), 1000)
+            .await
+            .unwrap();
+        let (meta, _) = CfgInsts::spawn(16, dir.file("cfg_insts.json"), 1000)
+            .await
+            .unwrap();
+        let (content, _) = CfgInstContent::spawn(16, dir.subdir("content"), 1000)
+            .await
+            .unwrap();
+        let (schemas, _) = ConfigSchemas::spawn(16, dir.subdir("schemas"), 1000)
+            .await
+            .unwrap();
+        Self {
+            _dir: dir,
+            deployments,
+            meta,
+            content,
+            schemas,
+        }
+    }
+
+    fn cfg_insts(&self) -> CfgInstRef<'_> {
+        CfgInstRef {
+            meta: &self.meta,
+            content: &self.content,
+            schemas: &self.schemas,
+        }
+    }
+
+    async fn add_cfg_inst(&self, id: &str, filepath: &str, content: &str) {
+        let cfg_inst = ConfigInstance {
+            id: id.to_string(),
+            filepath: filepath.to_string(),
+            ..Default::default()
+        };
+        self.meta
+            .write(id.to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        self.content
+            .write(
+                id.to_string(),
+                content.to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn add_deployment(&self, id: &str, activity: DplActivity, cfg_inst_ids: &[&str]) {
+        let dpl = Deployment {
+            id: id.to_string(),
+            activity_status: activity,
+            target_status: DplTarget::Deployed,
+            config_instance_ids: cfg_inst_ids.iter().map(|id| id.to_string()).collect(),
+            ..Default::default()
+        };
+        self.deployments
+            .write(id.to_string(), dpl, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+    }
+
+    async fn diff(&self, id: &str, text: bool) -> dpl_svc::DeploymentDiff {
+        dpl_svc::diff(
+            &self.deployments,
+            self.cfg_insts(),
+            &PanicBackend,
+            id.to_string(),
+            text,
+        )
+        .await
+        .unwrap()
+    }
+}
+
+pub mod diff_deployment {
+    use super::*;
+
+    #[tokio::test]
+    async fn nothing_deployed_adds_every_file() {
+        let f = Fixture::new("dpl_diff_none_deployed").await;
+        f.add_cfg_inst("ci_a", "/srv/a.json", "{}").await;
+        f.add_deployment("dpl_new", DplActivity::Queued, &["ci_a"])
+            .await;
+
+        let diff = f.diff("dpl_new", false).await;
+        assert_eq!(diff.deployment_id, "dpl_new");
+        assert_eq!(diff.current_deployment_id, None);
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(diff.files[0].filepath, "/srv/a.json");
+        assert_eq!(diff.files[0].change, FileChange::Added);
+        assert_eq!(diff.files[0].old_hash, None);
+        assert_eq!(diff.files[0].new_hash, Some(digest("{}")));
+        assert_eq!(diff.files[0].text_diff, None);
+    }
+
+    #[tokio::test]
+    async fn added_removed_and_modified() {
+        let f = Fixture::new("dpl_diff_changes").await;
+        f.add_cfg_inst("ci_keep_old", "/srv/keep.json", "same")
+            .await;
+        f.add_cfg_inst("ci_keep_new", "/srv/keep.json", "same")
+            .await;
+        f.add_cfg_inst("ci_mod_old", "/srv/mod.json", "old").await;
+        f.add_cfg_inst("ci_mod_new", "/srv/mod.json", "new").await;
+        f.add_cfg_inst("ci_gone", "/srv/gone.json", "gone").await;
+        f.add_cfg_inst("ci_added", "/srv/added.json", "added").await;
+        f.add_deployment(
+            "dpl_cur",
+            DplActivity::Deployed,
+            &["ci_keep_old", "ci_mod_old", "ci_gone"],
+        )
+        .await;
+        f.add_deployment(
+            "dpl_new",
+            DplActivity::Queued,
+            &["ci_keep_new", "ci_mod_new", "ci_added"],
+        )
+        .await;
+
+        let diff = f.diff("dpl_new", false).await;
+        assert_eq!(diff.current_deployment_id.as_deref(), Some("dpl_cur"));
+        let changes: Vec<(&str, FileChange)> = diff
+            .files
+            .iter()
+            .map(|f| (f.filepath.as_str(), f.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("/srv/added.json", FileChange::Added),
+                ("/srv/gone.json", FileChange::Removed),
+                ("/srv/mod.json", FileChange::Modified),
+            ]
+        );
+        let modified = &diff.files[2];
+        assert_eq!(modified.old_hash, Some(digest("old")));
+        assert_eq!(modified.new_hash, Some(digest("new")));
+        let removed = &diff.files[1];
+        assert_eq!(removed.old_hash, Some(digest("gone")));
+        assert_eq!(removed.new_hash, None);
+    }
+
+    #[tokio::test]
+    async fn current_deployment_has_no_changes() {
+        let f = Fixture::new("dpl_diff_current").await;
+        f.add_cfg_inst("ci_a", "/srv/a.json", "{}").await;
+        f.add_deployment("dpl_cur", DplActivity::Deployed, &["ci_a"])
+            .await;
+
+        let diff = f.diff("dpl_cur", false).await;
+        assert!(diff.files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn includes_text_diff_when_requested() {
+        let f = Fixture::new("dpl_diff_text").await;
+        f.add_cfg_inst("ci_old", "/srv/a.yaml", "a: 1\nb: 2\n")
+            .await;
+        f.add_cfg_inst("ci_new", "/srv/a.yaml", "a: 1\nb: 3\n")
+            .await;
+        f.add_deployment("dpl_cur", DplActivity::Deployed, &["ci_old"])
+            .await;
+        f.add_deployment("dpl_new", DplActivity::Queued, &["ci_new"])
+            .await;
+
+        let diff = f.diff("dpl_new", true).await;
+        assert_eq!(
+            diff.files[0].text_diff.as_deref(),
+            Some(" a: 1\n-b: 2\n+b: 3\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_content_errors() {
+        let f = Fixture::new("dpl_diff_missing").await;
+        f.add_deployment("dpl_new", DplActivity::Queued, &["ci_missing"])
+            .await;
+
+        let result = dpl_svc::diff(
+            &f.deployments,
+            f.cfg_insts(),
+            &PanicBackend,
+            "dpl_new".to_string(),
+            false,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
+
+pub mod line_diff {
+    use super::*;
+
+    #[test]
+    fn identical() {
+        assert_eq!(dpl_svc::line_diff("a\nb", "a\nb").unwrap(), " a\n b\n");
+    }
+
+    #[test]
+    fn added_and_removed_files() {
+        assert_eq!(dpl_svc::line_diff("", "a\nb").unwrap(), "+a\n+b\n");
+        assert_eq!(dpl_svc::line_diff("a\nb", "").unwrap(), "-a\n-b\n");
+    }
+
+    #[test]
+    fn keeps_common_lines() {
+        assert_eq!(
+            dpl_svc::line_diff("a\nb\nc\n", "a\nx\nc\nd\n").unwrap(),
+            " a\n-b\n+x\n c\n+d\n"
+        );
+    }
+
+    #[test]
+    fn skips_large_files() {
+        let large = "line\n".repeat(dpl_svc::MAX_TEXT_DIFF_LINES + 1);
+        assert_eq!(dpl_svc::line_diff(&large, "line"), None);
+    }
+}
@@ -5,7 +5,9 @@ use miru_agent::authn::errors::{AuthnErr, MockError as AuthnMockError};
 use miru_agent::filesys::{self, Overwrite};
 use miru_agent::http::errors::{HTTPErr, MockErr as HttpMockErr, RequestFailed};
 use miru_agent::http::request::Params as HttpParams;
-use miru_agent::models::{Deployment, DplActivity, DplErrStatus, DplTarget};
+use miru_agent::models::{
+    Deployment, DplActivity, DplDependencies, DplErrStatus, DplMetrics, DplTarget,
+};
 use miru_agent::services::deployment as dpl_svc;
 use miru_agent::services::ServiceErr;
 use miru_agent::storage::Deployments;
@@ -113,6 +115,9 @@ pub mod get_deployment_fallback {
             deployed_at: None,
             archived_at: None,
             config_instance_ids: vec!["cfg_1".to_string()],
+            metrics: DplMetrics::default(),
+            pending_finalize: None,
+            dependencies: DplDependencies::default(),
         };
         let result = dpl_svc::get(&dpl_stor, &stub, "dpl_1".to_string())
             .await
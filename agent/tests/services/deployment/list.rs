@@ -0,0 +1,355 @@
+// internal crates
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplErrStatus, DplTarget};
+use miru_agent::services::deployment::{self as dpl_svc, ListQuery};
+use miru_agent::services::ServiceErr;
+use miru_agent::storage::{CfgInsts, Deployments};
+
+// external crates
+use chrono::{DateTime, Duration, Utc};
+
+struct Fixture {
+    _dir: filesys::Dir,
+    deployments: Deployments,
+    cfg_insts: CfgInsts,
+}
+
+impl Fixture {
+    async fn new(name: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+        let (deployments, _) = Deployments::spawn(16, dir.file("deployments.json"), 1000)
+            .await
+            .unwrap();
+        let (cfg_insts, _) = CfgInsts::spawn(16, dir.file("cfg_insts.json"), 1000)
+            .await
+            .unwrap();
+        Self {
+            _dir: dir,
+            deployments,
+            cfg_insts,
+        }
+    }
+
+    async fn add_deployment(&self, dpl: Deployment) {
+        self.deployments
+            .write(dpl.id.clone(), dpl, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+    }
+
+    async fn add_cfg_inst(&self, id: &str, config_type_name: &str) {
+        let cfg_inst = ConfigInstance {
+            id: id.to_string(),
+            config_type_name: config_type_name.to_string(),
+            ..Default::default()
+        };
+        self.cfg_insts
+            .write(id.to_string(), cfg_inst, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<dpl_svc::Page, ServiceErr> {
+        dpl_svc::list(&self.deployments, &self.cfg_insts, query).await
+    }
+}
+
+/// A deployment created `age_secs` seconds after the epoch.
+fn make_deployment(id: &str, age_secs: i64) -> Deployment {
+    Deployment {
+        id: id.to_string(),
+        created_at: DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(age_secs),
+        ..Default::default()
+    }
+}
+
+fn ids(page: &dpl_svc::Page) -> Vec<&str> {
+    page.deployments.iter().map(|d| d.id.as_str()).collect()
+}
+
+pub mod pagination {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty() {
+        let fixture = Fixture::new("list_dpls_empty").await;
+        let page = fixture.list(&ListQuery::default()).await.unwrap();
+        assert!(page.deployments.is_empty());
+        assert!(!page.has_more());
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn newest_first() {
+        let fixture = Fixture::new("list_dpls_newest_first").await;
+        fixture.add_deployment(make_deployment("dpl_1", 1)).await;
+        fixture.add_deployment(make_deployment("dpl_3", 3)).await;
+        fixture.add_deployment(make_deployment("dpl_2", 2)).await;
+
+        let page = fixture.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_3", "dpl_2", "dpl_1"]);
+        assert!(!page.has_more());
+    }
+
+    #[tokio::test]
+    async fn ties_are_ordered_by_id() {
+        let fixture = Fixture::new("list_dpls_ties").await;
+        fixture.add_deployment(make_deployment("dpl_a", 1)).await;
+        fixture.add_deployment(make_deployment("dpl_b", 1)).await;
+
+        let page = fixture.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_b", "dpl_a"]);
+    }
+
+    #[tokio::test]
+    async fn cursor_walks_every_deployment_once() {
+        let fixture = Fixture::new("list_dpls_cursor_walk").await;
+        for i in 0..7 {
+            fixture
+                .add_deployment(make_deployment(&format!("dpl_{i}"), i % 3))
+                .await;
+        }
+
+        let mut seen = Vec::new();
+        let mut query = ListQuery {
+            limit: Some(3),
+            ..Default::default()
+        };
+        loop {
+            let page = fixture.list(&query).await.unwrap();
+            assert!(page.deployments.len() <= 3);
+            seen.extend(page.deployments.iter().map(|d| d.id.clone()));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(
+            seen,
+            vec!["dpl_5", "dpl_2", "dpl_4", "dpl_1", "dpl_6", "dpl_3", "dpl_0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn exact_page_has_no_more() {
+        let fixture = Fixture::new("list_dpls_exact_page").await;
+        fixture.add_deployment(make_deployment("dpl_1", 1)).await;
+        fixture.add_deployment(make_deployment("dpl_2", 2)).await;
+
+        let query = ListQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert_eq!(page.deployments.len(), 2);
+        assert!(!page.has_more());
+    }
+
+    #[tokio::test]
+    async fn later_pages_unaffected_by_new_deployments() {
+        let fixture = Fixture::new("list_dpls_new_dpls").await;
+        fixture.add_deployment(make_deployment("dpl_1", 1)).await;
+        fixture.add_deployment(make_deployment("dpl_2", 2)).await;
+        fixture.add_deployment(make_deployment("dpl_3", 3)).await;
+
+        let first = fixture
+            .list(&ListQuery {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(&first), vec!["dpl_3"]);
+
+        fixture.add_deployment(make_deployment("dpl_4", 4)).await;
+        let second = fixture
+            .list(&ListQuery {
+                limit: Some(1),
+                cursor: first.next_cursor,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(&second), vec!["dpl_2"]);
+    }
+
+    #[tokio::test]
+    async fn invalid_limit() {
+        let fixture = Fixture::new("list_dpls_invalid_limit").await;
+        for limit in [0, dpl_svc::MAX_LIMIT + 1] {
+            let query = ListQuery {
+                limit: Some(limit),
+                ..Default::default()
+            };
+            let result = fixture.list(&query).await;
+            assert!(matches!(result, Err(ServiceErr::InvalidQueryErr(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_cursor() {
+        let fixture = Fixture::new("list_dpls_malformed_cursor").await;
+        for cursor in ["not base64!", "bm8tc2VwYXJhdG9y", "bm90LWEtZGF0ZXxkcGxfMQ"] {
+            let query = ListQuery {
+                cursor: Some(cursor.to_string()),
+                ..Default::default()
+            };
+            let result = fixture.list(&query).await;
+            assert!(
+                matches!(result, Err(ServiceErr::MalformedCursorErr(_))),
+                "cursor {cursor}"
+            );
+        }
+    }
+}
+
+pub mod filters {
+    use super::*;
+
+    #[tokio::test]
+    async fn by_statuses() {
+        let fixture = Fixture::new("list_dpls_by_statuses").await;
+        fixture
+            .add_deployment(Deployment {
+                activity_status: DplActivity::Deployed,
+                target_status: DplTarget::Deployed,
+                ..make_deployment("dpl_deployed", 1)
+            })
+            .await;
+        fixture
+            .add_deployment(Deployment {
+                activity_status: DplActivity::Queued,
+                target_status: DplTarget::Deployed,
+                error_status: DplErrStatus::Retrying,
+                ..make_deployment("dpl_retrying", 2)
+            })
+            .await;
+        fixture
+            .add_deployment(Deployment {
+                activity_status: DplActivity::Archived,
+                target_status: DplTarget::Archived,
+                ..make_deployment("dpl_archived", 3)
+            })
+            .await;
+
+        let query = ListQuery {
+            activity_status: Some("deployed".to_string()),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_deployed"]);
+
+        let query = ListQuery {
+            target_status: Some("deployed".to_string()),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_retrying", "dpl_deployed"]);
+
+        let query = ListQuery {
+            target_status: Some("deployed".to_string()),
+            error_status: Some("retrying".to_string()),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_retrying"]);
+    }
+
+    #[tokio::test]
+    async fn invalid_status() {
+        let fixture = Fixture::new("list_dpls_invalid_status").await;
+        let queries = [
+            ListQuery {
+                activity_status: Some("bogus".to_string()),
+                ..Default::default()
+            },
+            ListQuery {
+                target_status: Some("Deployed".to_string()),
+                ..Default::default()
+            },
+            ListQuery {
+                error_status: Some(String::new()),
+                ..Default::default()
+            },
+        ];
+        for query in queries {
+            let result = fixture.list(&query).await;
+            assert!(matches!(result, Err(ServiceErr::InvalidQueryErr(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn by_config_type() {
+        let fixture = Fixture::new("list_dpls_by_config_type").await;
+        fixture.add_cfg_inst("ci_motion", "motion-control").await;
+        fixture.add_cfg_inst("ci_camera", "camera").await;
+        fixture
+            .add_deployment(Deployment {
+                config_instance_ids: vec!["ci_motion".to_string(), "ci_camera".to_string()],
+                ..make_deployment("dpl_both", 1)
+            })
+            .await;
+        fixture
+            .add_deployment(Deployment {
+                config_instance_ids: vec!["ci_camera".to_string()],
+                ..make_deployment("dpl_camera", 2)
+            })
+            .await;
+
+        let query = ListQuery {
+            config_type: Some("motion-control".to_string()),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_both"]);
+
+        let query = ListQuery {
+            config_type: Some("camera".to_string()),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&page), vec!["dpl_camera", "dpl_both"]);
+
+        let query = ListQuery {
+            config_type: Some("lidar".to_string()),
+            ..Default::default()
+        };
+        let page = fixture.list(&query).await.unwrap();
+        assert!(page.deployments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn filters_apply_before_the_limit() {
+        let fixture = Fixture::new("list_dpls_filter_then_limit").await;
+        for i in 0..4 {
+            let activity = if i % 2 == 0 {
+                DplActivity::Deployed
+            } else {
+                DplActivity::Queued
+            };
+            fixture
+                .add_deployment(Deployment {
+                    activity_status: activity,
+                    ..make_deployment(&format!("dpl_{i}"), i)
+                })
+                .await;
+        }
+
+        let query = ListQuery {
+            limit: Some(1),
+            activity_status: Some("queued".to_string()),
+            ..Default::default()
+        };
+        let first = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&first), vec!["dpl_3"]);
+        assert!(first.has_more());
+
+        let query = ListQuery {
+            cursor: first.next_cursor,
+            ..query
+        };
+        let second = fixture.list(&query).await.unwrap();
+        assert_eq!(ids(&second), vec!["dpl_1"]);
+        assert!(!second.has_more());
+    }
+}
@@ -1,2 +1,4 @@
 pub mod current;
+pub mod diff;
 pub mod get;
+pub mod list;
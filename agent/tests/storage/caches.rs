@@ -11,9 +11,11 @@ pub mod default_capacities {
         let expected = Capacities {
             cfg_insts: 1000,
             cfg_inst_content: 1000,
+            config_schemas: 100,
             deployments: 100,
             releases: 1000,
             git_commits: 100,
+            telemetry_batches: 60,
         };
         assert_eq!(actual, expected);
     }
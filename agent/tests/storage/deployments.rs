@@ -20,6 +20,9 @@ pub mod is_dirty_func {
             is_dirty: false,
             created_at: Utc::now(),
             last_accessed: Utc::now(),
+            probation: None,
+            digest: None,
+            pinned: false,
         };
         let old = Some(&entry);
         assert!(!is_dirty(old, &deployment));
@@ -44,6 +47,9 @@ pub mod is_dirty_func {
             is_dirty: true,
             created_at: Utc::now(),
             last_accessed: Utc::now(),
+            probation: None,
+            digest: None,
+            pinned: false,
         };
         let old = Some(&entry);
         assert!(is_dirty(old, &deployment));
@@ -66,6 +72,9 @@ pub mod is_dirty_func {
             is_dirty: false,
             created_at: Utc::now(),
             last_accessed: Utc::now(),
+            probation: None,
+            digest: None,
+            pinned: false,
         };
         let old = Some(&entry);
         assert!(is_dirty(old, &new_deployment));
@@ -88,6 +97,9 @@ pub mod is_dirty_func {
             is_dirty: false,
             created_at: Utc::now(),
             last_accessed: Utc::now(),
+            probation: None,
+            digest: None,
+            pinned: false,
         };
         let old = Some(&entry);
         assert!(is_dirty(old, &new_deployment));
@@ -109,6 +121,9 @@ pub mod is_dirty_func {
             is_dirty: false,
             created_at: Utc::now(),
             last_accessed: Utc::now(),
+            probation: None,
+            digest: None,
+            pinned: false,
         };
         let old = Some(&entry);
         assert!(is_dirty(old, &new_deployment));
@@ -130,6 +145,9 @@ pub mod is_dirty_func {
             is_dirty: false,
             created_at: Utc::now(),
             last_accessed: Utc::now(),
+            probation: None,
+            digest: None,
+            pinned: false,
         };
         let old = Some(&entry);
         assert!(is_dirty(old, &new_deployment));
@@ -1,6 +1,7 @@
 // internal crates
 use miru_agent::authn::Token;
 use miru_agent::crypt::base64;
+use miru_agent::crypt::keystore::{PrivateKey, TpmKey};
 use miru_agent::filesys::{self, WriteOptions};
 use miru_agent::models::Device;
 use miru_agent::storage::{assert_activated, resolve_device_id, Layout, StorageErr};
@@ -22,7 +23,9 @@ pub mod assert_activated {
     async fn returns_err_when_both_keys_missing() {
         let (layout, _dir) = fresh_layout().await;
 
-        let result = assert_activated(&layout).await.unwrap_err();
+        let result = assert_activated(&layout, &layout.auth().private_key().into())
+            .await
+            .unwrap_err();
         assert!(matches!(result, StorageErr::DeviceNotActivatedErr(_)));
     }
 
@@ -36,7 +39,9 @@ pub mod assert_activated {
             .await
             .unwrap();
 
-        let result = assert_activated(&layout).await.unwrap_err();
+        let result = assert_activated(&layout, &layout.auth().private_key().into())
+            .await
+            .unwrap_err();
         assert!(matches!(result, StorageErr::DeviceNotActivatedErr(_)));
     }
 
@@ -50,7 +55,9 @@ pub mod assert_activated {
             .await
             .unwrap();
 
-        let result = assert_activated(&layout).await.unwrap_err();
+        let result = assert_activated(&layout, &layout.auth().private_key().into())
+            .await
+            .unwrap_err();
         assert!(matches!(result, StorageErr::DeviceNotActivatedErr(_)));
     }
 
@@ -67,7 +74,32 @@ pub mod assert_activated {
             .await
             .unwrap();
 
-        assert_activated(&layout).await.unwrap();
+        assert_activated(&layout, &layout.auth().private_key().into())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn hardware_private_key_is_not_checked() {
+        let (layout, _dir) = fresh_layout().await;
+        layout
+            .auth()
+            .public_key()
+            .write_string("public", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let private_key = PrivateKey::Tpm(TpmKey::new("0x81010001".to_string(), None));
+
+        assert_activated(&layout, &private_key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn hardware_private_key_still_needs_the_public_key() {
+        let (layout, _dir) = fresh_layout().await;
+        let private_key = PrivateKey::Tpm(TpmKey::new("0x81010001".to_string(), None));
+
+        let result = assert_activated(&layout, &private_key).await.unwrap_err();
+        assert!(matches!(result, StorageErr::DeviceNotActivatedErr(_)));
     }
 }
 
@@ -0,0 +1,446 @@
+// standard crates
+use std::collections::HashMap;
+
+// internal crates
+use miru_agent::authn::Token;
+use miru_agent::cache::CacheEntry;
+use miru_agent::crypt::base64;
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::models::{ConfigInstance, Deployment, Device, GitCommit, Release};
+use miru_agent::storage::fsck::{self, Check, Options, Report, Severity};
+use miru_agent::storage::Layout;
+
+// external crates
+use chrono::{Duration, Utc};
+use serde::Serialize;
+
+fn entry<V: Clone + Serialize>(key: &str, value: V) -> CacheEntry<String, V> {
+    CacheEntry {
+        key: key.to_string(),
+        value,
+        is_dirty: false,
+        created_at: Utc::now(),
+        last_accessed: Utc::now(),
+        probation: None,
+        digest: None,
+        pinned: false,
+    }
+}
+
+async fn write_cache<V: Clone + Serialize>(file: &filesys::File, entries: Vec<(&str, V)>) {
+    let map: HashMap<String, CacheEntry<String, V>> = entries
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), entry(key, value)))
+        .collect();
+    file.write_json(&map, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+}
+
+async fn write_content(layout: &Layout, id: &str) {
+    layout
+        .config_instance_content()
+        .file(&format!("{id}.json"))
+        .write_json(&entry(id, "{}".to_string()), WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+}
+
+fn deployment(id: &str, release_id: &str, cfg_inst_ids: &[&str]) -> Deployment {
+    Deployment {
+        id: id.to_string(),
+        release_id: release_id.to_string(),
+        config_instance_ids: cfg_inst_ids.iter().map(|id| id.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn cfg_inst(id: &str) -> ConfigInstance {
+    ConfigInstance {
+        id: id.to_string(),
+        ..Default::default()
+    }
+}
+
+fn release(id: &str, git_commit_id: Option<&str>) -> Release {
+    Release {
+        id: id.to_string(),
+        git_commit_id: git_commit_id.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+fn new_jwt(device_id: &str) -> String {
+    let payload = serde_json::json!({
+        "iss": "miru",
+        "aud": "device",
+        "exp": 1_721_517_034u64,
+        "iat": 1_721_495_434u64,
+        "sub": device_id,
+    })
+    .to_string();
+    format!(
+        "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.{}.UIqAz_V-ZuZLIHUXwLHw-A2CrXBQrpXnJAMlVfmMXYY",
+        base64::encode_string_url_safe_no_pad(&payload)
+    )
+}
+
+async fn write_token(layout: &Layout, device_id: &str) {
+    let token = Token {
+        token: new_jwt(device_id),
+        expires_at: Utc::now() + Duration::minutes(5),
+    };
+    layout
+        .auth()
+        .token()
+        .write_json(&token, WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+}
+
+/// A layout whose caches all reference one another correctly.
+async fn consistent_layout(name: &str) -> Layout {
+    let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+    let layout = Layout::new(dir);
+    write_token(&layout, "dvc-1").await;
+    layout
+        .device()
+        .write_json(
+            &Device {
+                id: "dvc-1".to_string(),
+                ..Device::default()
+            },
+            WriteOptions::OVERWRITE_ATOMIC,
+        )
+        .await
+        .unwrap();
+    write_cache(
+        &layout.deployments(),
+        vec![("dpl-1", deployment("dpl-1", "rls-1", &["cfg-1"]))],
+    )
+    .await;
+    write_cache(
+        &layout.releases(),
+        vec![("rls-1", release("rls-1", Some("gc-1")))],
+    )
+    .await;
+    write_cache(
+        &layout.git_commits(),
+        vec![(
+            "gc-1",
+            GitCommit {
+                id: "gc-1".to_string(),
+                ..Default::default()
+            },
+        )],
+    )
+    .await;
+    write_cache(
+        &layout.config_instance_meta(),
+        vec![("cfg-1", cfg_inst("cfg-1"))],
+    )
+    .await;
+    write_content(&layout, "cfg-1").await;
+    layout
+}
+
+fn checks(report: &Report) -> Vec<Check> {
+    report.findings.iter().map(|f| f.check).collect()
+}
+
+pub mod checks {
+    use super::*;
+
+    #[tokio::test]
+    async fn consistent_caches_have_no_findings() {
+        let layout = consistent_layout("fsck_consistent").await;
+        let report = fsck::run(&layout, Options::default()).await;
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+        assert!(!report.has_errors());
+    }
+
+    #[tokio::test]
+    async fn empty_layout_has_no_findings() {
+        let dir = filesys::Dir::create_temp_dir("fsck_empty").await.unwrap();
+        let report = fsck::run(&Layout::new(dir), Options::default()).await;
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+
+    #[tokio::test]
+    async fn deployment_with_missing_config_instance() {
+        let layout = consistent_layout("fsck_missing_cfg_inst").await;
+        write_cache(
+            &layout.deployments(),
+            vec![("dpl-1", deployment("dpl-1", "rls-1", &["cfg-1", "cfg-2"]))],
+        )
+        .await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        assert_eq!(checks(&report), vec![Check::MissingConfigInstance]);
+        let finding = &report.findings[0];
+        assert_eq!(finding.severity, Severity::Error);
+        assert_eq!(finding.subject, "deployment dpl-1");
+        assert!(finding.message.contains("cfg-2"));
+        assert!(finding.repair.is_some());
+        assert!(!finding.repaired);
+        assert!(report.has_errors());
+    }
+
+    #[tokio::test]
+    async fn missing_release_and_git_commit() {
+        let layout = consistent_layout("fsck_missing_release").await;
+        write_cache(
+            &layout.deployments(),
+            vec![("dpl-1", deployment("dpl-1", "rls-2", &["cfg-1"]))],
+        )
+        .await;
+        write_cache(
+            &layout.releases(),
+            vec![("rls-1", release("rls-1", Some("gc-2")))],
+        )
+        .await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        let mut found = checks(&report);
+        found.sort_by_key(|check| format!("{check:?}"));
+        assert_eq!(found, vec![Check::MissingGitCommit, Check::MissingRelease]);
+        assert!(report
+            .findings
+            .iter()
+            .all(|f| f.severity == Severity::Warning));
+        assert!(!report.has_errors());
+    }
+
+    #[tokio::test]
+    async fn content_problems() {
+        let layout = consistent_layout("fsck_content").await;
+        write_cache(
+            &layout.config_instance_meta(),
+            vec![("cfg-1", cfg_inst("cfg-1")), ("cfg-3", cfg_inst("cfg-3"))],
+        )
+        .await;
+        write_cache(
+            &layout.deployments(),
+            vec![("dpl-1", deployment("dpl-1", "rls-1", &["cfg-1", "cfg-3"]))],
+        )
+        .await;
+        // content without metadata
+        write_content(&layout, "cfg-2").await;
+        // content which isn't a cache entry
+        layout
+            .config_instance_content()
+            .file("garbage.json")
+            .write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        let mut found = checks(&report);
+        found.sort_by_key(|check| format!("{check:?}"));
+        assert_eq!(
+            found,
+            vec![
+                Check::CorruptContent,
+                Check::MissingContent,
+                Check::OrphanedContent
+            ]
+        );
+        // errors are listed first
+        assert_eq!(report.findings[0].check, Check::CorruptContent);
+    }
+
+    #[tokio::test]
+    async fn unreferenced_config_instance() {
+        let layout = consistent_layout("fsck_unreferenced").await;
+        write_cache(
+            &layout.config_instance_meta(),
+            vec![("cfg-1", cfg_inst("cfg-1")), ("cfg-9", cfg_inst("cfg-9"))],
+        )
+        .await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        assert_eq!(checks(&report), vec![Check::UnreferencedConfigInstance]);
+        assert_eq!(report.findings[0].severity, Severity::Info);
+    }
+
+    #[tokio::test]
+    async fn deployment_stored_under_the_wrong_key() {
+        let layout = consistent_layout("fsck_key_mismatch").await;
+        write_cache(
+            &layout.deployments(),
+            vec![("dpl-other", deployment("dpl-1", "rls-1", &["cfg-1"]))],
+        )
+        .await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        assert_eq!(checks(&report), vec![Check::KeyMismatch]);
+    }
+
+    #[tokio::test]
+    async fn device_file_for_another_device() {
+        let layout = consistent_layout("fsck_device_mismatch").await;
+        write_token(&layout, "dvc-2").await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        assert_eq!(checks(&report), vec![Check::DeviceMismatch]);
+        assert!(report.findings[0].repair.is_none());
+    }
+
+    #[tokio::test]
+    async fn unreadable_caches_are_errors() {
+        let layout = consistent_layout("fsck_unreadable").await;
+        layout
+            .config_instance_meta()
+            .write_string("{", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        // checks depending on the unreadable cache are skipped
+        assert_eq!(checks(&report), vec![Check::UnreadableFile]);
+        assert!(report.has_errors());
+    }
+}
+
+pub mod fix {
+    use super::*;
+
+    #[tokio::test]
+    async fn removes_deployments_with_missing_config_instances() {
+        let layout = consistent_layout("fsck_fix_missing_cfg_inst").await;
+        write_cache(
+            &layout.deployments(),
+            vec![
+                ("dpl-1", deployment("dpl-1", "rls-1", &["cfg-1"])),
+                ("dpl-2", deployment("dpl-2", "rls-1", &["cfg-2"])),
+            ],
+        )
+        .await;
+
+        let report = fsck::run(&layout, Options { fix: true }).await;
+
+        assert_eq!(checks(&report), vec![Check::MissingConfigInstance]);
+        assert!(report.findings[0].repaired);
+        assert!(!report.has_errors());
+
+        let deployments = layout
+            .deployments()
+            .read_json::<HashMap<String, CacheEntry<String, Deployment>>>()
+            .await
+            .unwrap();
+        assert_eq!(deployments.keys().collect::<Vec<_>>(), vec!["dpl-1"]);
+
+        // a second pass finds nothing left to repair
+        let report = fsck::run(&layout, Options { fix: true }).await;
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+
+    #[tokio::test]
+    async fn deletes_corrupt_and_orphaned_content() {
+        let layout = consistent_layout("fsck_fix_content").await;
+        write_content(&layout, "cfg-2").await;
+        let garbage = layout.config_instance_content().file("garbage.json");
+        garbage
+            .write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let report = fsck::run(&layout, Options { fix: true }).await;
+
+        assert!(report.findings.iter().all(|f| f.repaired));
+        assert!(!garbage.exists());
+        assert!(!layout.config_instance_content().file("cfg-2.json").exists());
+        assert!(layout.config_instance_content().file("cfg-1.json").exists());
+    }
+
+    #[tokio::test]
+    async fn rekeys_deployments() {
+        let layout = consistent_layout("fsck_fix_key_mismatch").await;
+        write_cache(
+            &layout.deployments(),
+            vec![("dpl-other", deployment("dpl-1", "rls-1", &["cfg-1"]))],
+        )
+        .await;
+
+        fsck::run(&layout, Options { fix: true }).await;
+
+        let deployments = layout
+            .deployments()
+            .read_json::<HashMap<String, CacheEntry<String, Deployment>>>()
+            .await
+            .unwrap();
+        assert_eq!(deployments["dpl-1"].key, "dpl-1");
+        assert_eq!(deployments.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deletes_an_unreadable_device_file() {
+        let layout = consistent_layout("fsck_fix_device").await;
+        layout
+            .device()
+            .write_string("{", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let report = fsck::run(&layout, Options { fix: true }).await;
+
+        assert_eq!(checks(&report), vec![Check::UnreadableFile]);
+        assert!(report.findings[0].repaired);
+        assert!(!layout.device().exists());
+    }
+}
+
+pub mod report {
+    use super::*;
+
+    #[tokio::test]
+    async fn render_filters_by_severity() {
+        let layout = consistent_layout("fsck_render").await;
+        write_cache(
+            &layout.config_instance_meta(),
+            vec![("cfg-1", cfg_inst("cfg-1")), ("cfg-9", cfg_inst("cfg-9"))],
+        )
+        .await;
+        write_content(&layout, "cfg-2").await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        let warnings = report.render(Severity::Warning);
+        assert!(warnings.contains("cfg-2.json"));
+        assert!(warnings.contains("--fix will delete it"));
+        assert!(!warnings.contains("cfg-9"));
+        assert!(warnings.ends_with("0 errors, 1 warnings, 1 info, 0 repaired"));
+
+        let info = report.render(Severity::Info);
+        assert!(info.contains("config instance cfg-9"));
+    }
+
+    #[test]
+    fn severity_parse() {
+        assert_eq!(Severity::parse("INFO"), Some(Severity::Info));
+        assert_eq!(Severity::parse("warn"), Some(Severity::Warning));
+        assert_eq!(Severity::parse("error"), Some(Severity::Error));
+        assert_eq!(Severity::parse("fatal"), None);
+        assert!(Severity::Error > Severity::Warning);
+    }
+
+    #[tokio::test]
+    async fn serializes_findings() {
+        let layout = consistent_layout("fsck_json").await;
+        write_token(&layout, "dvc-2").await;
+
+        let report = fsck::run(&layout, Options::default()).await;
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["findings"][0]["severity"], "error");
+        assert_eq!(json["findings"][0]["check"], "device_mismatch");
+        assert_eq!(json["findings"][0]["repaired"], false);
+    }
+}
@@ -21,6 +21,9 @@ fn make_entry(dpl: Deployment) -> CacheEntry<String, Deployment> {
         is_dirty: false,
         created_at: Utc::now(),
         last_accessed: Utc::now(),
+        probation: None,
+        digest: None,
+        pinned: false,
     }
 }
 
@@ -142,3 +145,47 @@ pub mod reset_retry_state_on_init {
         assert!(dpl.has_clean_retry_state());
     }
 }
+
+// ─── stale artifact sweep on init ───────────────────────────────────────────
+
+pub mod sweep_on_init {
+    use super::*;
+    use miru_agent::filesys::PathExt;
+    use std::time::{Duration, SystemTime};
+
+    fn backdate(path: &std::path::Path) {
+        let file = std::fs::File::open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn removes_stale_trash_and_temp_files() {
+        let dir = filesys::Dir::create_temp_dir("sweep_on_init")
+            .await
+            .unwrap();
+        let layout = Layout::new(dir);
+
+        let trash = layout
+            .config_instance_content()
+            .path()
+            .join(".rename_trash_abc");
+        std::fs::create_dir_all(&trash).unwrap();
+        std::fs::write(trash.join("cfg.json"), vec![0u8; 64]).unwrap();
+        backdate(&trash);
+
+        let key = layout.temp_dir().path().join("private.key");
+        std::fs::create_dir_all(layout.temp_dir().path()).unwrap();
+        std::fs::write(&key, vec![0u8; 16]).unwrap();
+        backdate(&key);
+
+        let (storage, _) = Storage::init(&layout, Capacities::default(), "dev".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(storage.sweep.removed, 2);
+        assert_eq!(storage.sweep.reclaimed_bytes, 80);
+        assert!(!trash.exists());
+        assert!(!key.exists());
+    }
+}
@@ -28,6 +28,38 @@ pub mod storage_layout {
         assert_eq!(dir.to_string(), "/custom/var/lib/miru");
     }
 
+    #[test]
+    fn data_dir_replaces_the_root() {
+        let layout = Layout::with_data_dir(filesys::Dir::new("/srv/agent-a"));
+        assert_eq!(layout.root().to_string(), "/srv/agent-a");
+        assert_eq!(layout.settings().to_string(), "/srv/agent-a/settings.json");
+        assert_eq!(layout.device().to_string(), "/srv/agent-a/device.json");
+        assert_eq!(
+            layout.deployments().to_string(),
+            "/srv/agent-a/resources/deployments.json"
+        );
+    }
+
+    #[test]
+    fn data_dir_holds_logs_and_socket() {
+        let layout = Layout::with_data_dir(filesys::Dir::new("/srv/agent-a"));
+        assert_eq!(
+            layout.log_dir(),
+            std::path::PathBuf::from("/srv/agent-a/logs")
+        );
+        assert_eq!(layout.socket_file().to_string(), "/srv/agent-a/miru.sock");
+    }
+
+    #[test]
+    fn default_logs_and_socket_are_the_platform_defaults() {
+        let layout = Layout::default();
+        assert_eq!(layout.log_dir(), miru_agent::platform::default_log_dir());
+        assert_eq!(
+            layout.socket_file().to_string(),
+            miru_agent::platform::default_socket_file().to_string()
+        );
+    }
+
     #[test]
     fn temp_dir() {
         let layout = Layout::default();
@@ -35,6 +67,31 @@ pub mod storage_layout {
         assert_eq!(dir.to_string(), "/var/lib/miru/tmp");
     }
 
+    #[test]
+    fn trash_dir() {
+        let layout = Layout::default();
+        let dir = layout.trash_dir();
+        assert_eq!(dir.to_string(), "/var/lib/miru/trash");
+    }
+
+    #[test]
+    fn deployments_paused() {
+        let layout = Layout::default();
+        let file = layout.deployments_paused();
+        assert_eq!(file.to_string(), "/var/lib/miru/deployments_paused.json");
+    }
+
+    #[test]
+    fn crash() {
+        let crash = Layout::default().crash();
+        assert_eq!(crash.root.to_string(), "/var/lib/miru/crash");
+        assert_eq!(crash.pending().to_string(), "/var/lib/miru/crash/pending");
+        assert_eq!(
+            crash.last_crash().to_string(),
+            "/var/lib/miru/crash/last_crash.json"
+        );
+    }
+
     #[test]
     fn settings() {
         let layout = Layout::default();
@@ -76,6 +133,16 @@ pub mod storage_layout {
         );
     }
 
+    #[test]
+    fn config_schemas() {
+        let layout = Layout::default();
+        let dir = layout.config_schemas();
+        assert_eq!(
+            dir.to_string(),
+            "/var/lib/miru/resources/config_instances/schemas"
+        );
+    }
+
     #[test]
     fn deployments() {
         let layout = Layout::default();
@@ -124,6 +191,20 @@ pub mod auth_layout {
         assert_eq!(file.to_string(), "/var/lib/miru/auth/public_key.pem");
     }
 
+    #[test]
+    fn client_cert_files() {
+        let layout = Layout::default();
+        let auth = layout.auth();
+        assert_eq!(
+            auth.client_cert().to_string(),
+            "/var/lib/miru/auth/client_cert.pem"
+        );
+        assert_eq!(
+            auth.client_key().to_string(),
+            "/var/lib/miru/auth/client_key.pem"
+        );
+    }
+
     #[test]
     fn token_file() {
         let layout = Layout::default();
@@ -0,0 +1,170 @@
+// standard crates
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use miru_agent::errors::Error;
+use miru_agent::storage::locks::{Locks, Resource, Writer};
+use miru_agent::storage::StorageErr;
+
+fn dpl(id: &str) -> Resource {
+    Resource::Deployment(id.to_string())
+}
+
+fn cfg_inst(id: &str) -> Resource {
+    Resource::ConfigInstance(id.to_string())
+}
+
+pub mod lock {
+    use super::*;
+
+    #[tokio::test]
+    async fn deduplicates_and_orders_resources() {
+        let locks = Locks::new();
+        let guard = locks
+            .lock(
+                Writer::Syncer,
+                [cfg_inst("a"), dpl("b"), dpl("a"), dpl("b")],
+            )
+            .await;
+        assert_eq!(
+            guard.resources(),
+            vec![&dpl("a"), &dpl("b"), &cfg_inst("a")]
+        );
+        assert_eq!(locks.holder(&dpl("a")), Some(Writer::Syncer));
+        assert_eq!(locks.len(), 3);
+
+        drop(guard);
+        assert_eq!(locks.holder(&dpl("a")), None);
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_holder() {
+        let locks = Arc::new(Locks::new());
+        let guard = locks.lock(Writer::LocalApi, [dpl("a")]).await;
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move {
+                let _guard = locks.lock(Writer::Syncer, [dpl("a")]).await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        // the waiter keeps the resource's slot alive
+        assert_eq!(locks.len(), 1);
+
+        drop(guard);
+        waiter.await.unwrap();
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn abandoned_acquisitions_are_forgotten() {
+        let locks = Locks::new();
+        let guard = locks.lock(Writer::LocalApi, [dpl("b")]).await;
+
+        let acquisition = locks.lock(Writer::Syncer, [dpl("a"), dpl("b")]);
+        let result = tokio::time::timeout(Duration::from_millis(20), acquisition).await;
+        assert!(result.is_err());
+        assert_eq!(locks.holder(&dpl("a")), None);
+
+        drop(guard);
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn overlapping_writers_never_deadlock() {
+        let locks = Arc::new(Locks::new());
+        let in_critical_section = Arc::new(AtomicUsize::new(0));
+        let resources = [
+            dpl("a"),
+            dpl("b"),
+            cfg_inst("a"),
+            cfg_inst("b"),
+            Resource::Device,
+        ];
+
+        let mut tasks = Vec::new();
+        for i in 0..16 {
+            let locks = locks.clone();
+            let in_critical_section = in_critical_section.clone();
+            // every task locks the same resources, each in its own order
+            let mut mine = resources.to_vec();
+            mine.rotate_left(i % resources.len());
+            if i % 2 == 0 {
+                mine.reverse();
+            }
+            let writer = if i % 2 == 0 {
+                Writer::Syncer
+            } else {
+                Writer::LocalApi
+            };
+            tasks.push(tokio::spawn(async move {
+                for _ in 0..50 {
+                    let _guard = locks.lock(writer, mine.clone()).await;
+                    assert_eq!(in_critical_section.fetch_add(1, Ordering::SeqCst), 0);
+                    tokio::task::yield_now().await;
+                    in_critical_section.fetch_sub(1, Ordering::SeqCst);
+                }
+            }));
+        }
+        let all = futures::future::join_all(tasks);
+        let results = tokio::time::timeout(Duration::from_secs(30), all)
+            .await
+            .expect("writers deadlocked");
+        for result in results {
+            result.unwrap();
+        }
+        assert!(locks.is_empty());
+    }
+}
+
+pub mod try_lock {
+    use super::*;
+
+    #[tokio::test]
+    async fn locks_free_resources() {
+        let locks = Locks::new();
+        let guard = locks
+            .try_lock(Writer::LocalApi, [dpl("a"), cfg_inst("a")])
+            .unwrap();
+        assert_eq!(locks.holder(&cfg_inst("a")), Some(Writer::LocalApi));
+        drop(guard);
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn conflicts_with_the_holder() {
+        let locks = Locks::new();
+        let _guard = locks.lock(Writer::Syncer, [dpl("b")]).await;
+
+        let err = locks
+            .try_lock(Writer::LocalApi, [dpl("a"), dpl("b"), dpl("c")])
+            .unwrap_err();
+        let StorageErr::ResourceConflictErr(conflict) = &err else {
+            panic!("expected a resource conflict, got {err:?}");
+        };
+        assert_eq!(conflict.resource, dpl("b"));
+        assert_eq!(conflict.holder, Some(Writer::Syncer));
+        assert_eq!(err.code().as_str(), "resource_conflict");
+        assert_eq!(err.http_status(), axum::http::StatusCode::CONFLICT);
+        assert_eq!(
+            err.to_string(),
+            "deployment 'b' is being written by the syncer"
+        );
+        let params = err.params().unwrap();
+        assert_eq!(params["resource"]["type"], "deployment");
+        assert_eq!(params["resource"]["id"], "b");
+        assert_eq!(params["holder"], "syncer");
+
+        // nothing was left locked by the failed attempt
+        assert_eq!(locks.holder(&dpl("a")), None);
+        assert_eq!(locks.len(), 1);
+        let _other = locks
+            .try_lock(Writer::LocalApi, [dpl("a"), dpl("c")])
+            .unwrap();
+    }
+}
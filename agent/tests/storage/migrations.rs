@@ -0,0 +1,319 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::storage::migrations::{self, Migration, Step};
+use miru_agent::storage::{Layout, StorageErr};
+
+// external crates
+use serde_json::{json, Value};
+
+const AGENT_VERSION: &str = "v0.0.0";
+
+fn device_file(layout: &Layout) -> filesys::File {
+    layout.device()
+}
+
+fn legacy_file(layout: &Layout) -> filesys::File {
+    layout.root().file("legacy.json")
+}
+
+fn moved_file(layout: &Layout) -> filesys::File {
+    layout.resources().file("moved.json")
+}
+
+fn legacy_dir(layout: &Layout) -> filesys::Dir {
+    layout.root().subdir("legacy_cache")
+}
+
+fn moved_dir(layout: &Layout) -> filesys::Dir {
+    layout.resources().subdir("cache")
+}
+
+fn wrap(value: Value) -> Value {
+    json!({ "wrapped": value })
+}
+
+fn unwrap(value: Value) -> Value {
+    value.get("wrapped").cloned().unwrap_or(value)
+}
+
+const RENAME_NAME: Migration = Migration {
+    id: "0001_rename_device_name",
+    description: "rename device.json 'name' to 'device_name'",
+    steps: &[Step::RenameField {
+        file: device_file,
+        from: "name",
+        to: "device_name",
+    }],
+};
+
+const MOVE_LEGACY: Migration = Migration {
+    id: "0002_move_legacy",
+    description: "move legacy file and cache into resources",
+    steps: &[
+        Step::MoveFile {
+            from: legacy_file,
+            to: moved_file,
+        },
+        Step::MoveDir {
+            from: legacy_dir,
+            to: moved_dir,
+        },
+    ],
+};
+
+const WRAP_MOVED: Migration = Migration {
+    id: "0003_wrap_moved",
+    description: "re-encode the moved file",
+    steps: &[Step::Rewrite {
+        file: moved_file,
+        up: wrap,
+        down: unwrap,
+    }],
+};
+
+const MIGRATIONS: &[Migration] = &[RENAME_NAME, MOVE_LEGACY, WRAP_MOVED];
+
+async fn setup() -> (filesys::Dir, Layout) {
+    let dir = filesys::Dir::create_temp_dir("storage_migrations")
+        .await
+        .unwrap();
+    let layout = Layout::new(dir.clone());
+    layout
+        .device()
+        .write_json(&json!({ "name": "robot" }), WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+    legacy_file(&layout)
+        .write_json(&json!({ "a": 1 }), WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+    legacy_dir(&layout).create_if_absent().await.unwrap();
+    (dir, layout)
+}
+
+pub mod apply {
+    use super::*;
+
+    #[tokio::test]
+    async fn applies_pending_migrations_in_order() {
+        let (dir, layout) = setup().await;
+
+        let applied = migrations::apply(&layout, MIGRATIONS, AGENT_VERSION)
+            .await
+            .unwrap();
+        assert_eq!(
+            applied,
+            vec![
+                "0001_rename_device_name",
+                "0002_move_legacy",
+                "0003_wrap_moved"
+            ]
+        );
+
+        let device = layout.device().read_json::<Value>().await.unwrap();
+        assert_eq!(device, json!({ "device_name": "robot" }));
+        assert!(!legacy_file(&layout).exists());
+        assert!(!legacy_dir(&layout).exists());
+        assert!(moved_dir(&layout).exists());
+        let moved = moved_file(&layout).read_json::<Value>().await.unwrap();
+        assert_eq!(moved, json!({ "wrapped": { "a": 1 } }));
+
+        let record = migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap();
+        assert_eq!(record.len(), 3);
+        assert!(record.iter().all(|r| r.agent_version == AGENT_VERSION));
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn applies_each_migration_exactly_once() {
+        let (dir, layout) = setup().await;
+
+        migrations::apply(&layout, &MIGRATIONS[..1], AGENT_VERSION)
+            .await
+            .unwrap();
+        let applied = migrations::apply(&layout, MIGRATIONS, "v0.0.1")
+            .await
+            .unwrap();
+        assert_eq!(applied, vec!["0002_move_legacy", "0003_wrap_moved"]);
+
+        let applied = migrations::apply(&layout, MIGRATIONS, "v0.0.1")
+            .await
+            .unwrap();
+        assert!(applied.is_empty());
+
+        // the rewrite would have wrapped the file twice if applied again
+        let moved = moved_file(&layout).read_json::<Value>().await.unwrap();
+        assert_eq!(moved, json!({ "wrapped": { "a": 1 } }));
+
+        let record = migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap();
+        assert_eq!(record[0].agent_version, AGENT_VERSION);
+        assert_eq!(record[1].agent_version, "v0.0.1");
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_sources_are_skipped() {
+        let dir = filesys::Dir::create_temp_dir("storage_migrations")
+            .await
+            .unwrap();
+        let layout = Layout::new(dir.clone());
+
+        let applied = migrations::apply(&layout, MIGRATIONS, AGENT_VERSION)
+            .await
+            .unwrap();
+        assert_eq!(applied.len(), 3);
+        assert!(!moved_file(&layout).exists());
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_migration_rolls_back_completed_steps() {
+        let (dir, layout) = setup().await;
+
+        // the directory move fails because the destination already exists
+        moved_dir(&layout).create_if_absent().await.unwrap();
+
+        let err = migrations::apply(&layout, MIGRATIONS, AGENT_VERSION)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StorageErr::MigrationErr(_)));
+
+        // the file move from the failed migration was reverted
+        assert!(legacy_file(&layout).exists());
+        assert!(!moved_file(&layout).exists());
+
+        // only the first migration was recorded
+        let record = migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record[0].id, "0001_rename_device_name");
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn shipped_migrations_have_unique_ids() {
+        let mut ids = migrations::MIGRATIONS
+            .iter()
+            .map(|m| m.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), migrations::MIGRATIONS.len());
+    }
+}
+
+pub mod revert {
+    use super::*;
+
+    #[tokio::test]
+    async fn reverts_migrations_after_target() {
+        let (dir, layout) = setup().await;
+        migrations::apply(&layout, MIGRATIONS, AGENT_VERSION)
+            .await
+            .unwrap();
+
+        let reverted = migrations::revert(&layout, MIGRATIONS, Some("0001_rename_device_name"))
+            .await
+            .unwrap();
+        assert_eq!(reverted, vec!["0003_wrap_moved", "0002_move_legacy"]);
+
+        let legacy = legacy_file(&layout).read_json::<Value>().await.unwrap();
+        assert_eq!(legacy, json!({ "a": 1 }));
+        assert!(legacy_dir(&layout).exists());
+        let device = layout.device().read_json::<Value>().await.unwrap();
+        assert_eq!(device, json!({ "device_name": "robot" }));
+
+        let reverted = migrations::revert(&layout, MIGRATIONS, None).await.unwrap();
+        assert_eq!(reverted, vec!["0001_rename_device_name"]);
+        let device = layout.device().read_json::<Value>().await.unwrap();
+        assert_eq!(device, json!({ "name": "robot" }));
+        assert!(migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap()
+            .is_empty());
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_target_errors() {
+        let (dir, layout) = setup().await;
+        let err = migrations::revert(&layout, MIGRATIONS, Some("9999_unknown"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StorageErr::MigrationErr(_)));
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_recorded_migration_errors() {
+        let (dir, layout) = setup().await;
+        migrations::apply(&layout, MIGRATIONS, AGENT_VERSION)
+            .await
+            .unwrap();
+        let err = migrations::revert(&layout, &MIGRATIONS[..1], None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StorageErr::MigrationErr(_)));
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod revert_version {
+    use super::*;
+
+    #[tokio::test]
+    async fn reverts_the_versions_migrations() {
+        let (dir, layout) = setup().await;
+        migrations::apply(&layout, &MIGRATIONS[..1], AGENT_VERSION)
+            .await
+            .unwrap();
+        migrations::apply(&layout, MIGRATIONS, "v0.0.1")
+            .await
+            .unwrap();
+
+        let reverted = migrations::revert_version(&layout, MIGRATIONS, "v0.0.1")
+            .await
+            .unwrap();
+        assert_eq!(reverted, vec!["0003_wrap_moved", "0002_move_legacy"]);
+        assert!(legacy_file(&layout).exists());
+        let record = migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record[0].agent_version, AGENT_VERSION);
+
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_other_versions_migrations() {
+        let (dir, layout) = setup().await;
+        migrations::apply(&layout, MIGRATIONS, AGENT_VERSION)
+            .await
+            .unwrap();
+
+        let reverted = migrations::revert_version(&layout, MIGRATIONS, "v0.0.1")
+            .await
+            .unwrap();
+        assert!(reverted.is_empty());
+        assert_eq!(
+            migrations::read_applied(&layout.migrations())
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        dir.delete().await.unwrap();
+    }
+}
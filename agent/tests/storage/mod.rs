@@ -3,7 +3,11 @@ pub mod caches;
 pub mod deployments;
 pub mod device;
 pub mod errors;
+pub mod fsck;
 pub mod init;
 pub mod layout;
+pub mod locks;
+pub mod migrations;
 pub mod settings;
 pub mod setup;
+pub mod wear;
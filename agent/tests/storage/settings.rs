@@ -1,7 +1,38 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
 // internal crates
-use miru_agent::logs::LogLevel;
+use miru_agent::app::startup::Component;
+use miru_agent::cache::admission::Rule;
+use miru_agent::cell;
+use miru_agent::cooldown;
+use miru_agent::crypt::{
+    digest,
+    keystore::{Pkcs11Key, PrivateKey, TpmKey},
+};
+use miru_agent::deploy::hooks::{self, Stage};
+use miru_agent::deploy::schedule::Schedule;
+use miru_agent::deploy::trash;
+use miru_agent::filesys;
+use miru_agent::logs::{self, LogLevel};
+use miru_agent::mqtt::{options::QoSLevels, websocket};
 use miru_agent::network::{BackendUrl, MqttHost};
-use miru_agent::storage::{Backend, MQTTBroker, Settings};
+use miru_agent::notifications::{sinks::DEFAULT_COMMAND_TIMEOUT_SECS, Severity, Sink, Target};
+use miru_agent::server::shed;
+use miru_agent::storage::{
+    Backend, Cell, ContentCache, ContentWarming, CrashReports, DeploymentDir, DiskGuard, Hooks,
+    KeyBackend, KeyStorage, Layout, LoadShedding, LongPoll, MQTTBroker, MQTTConnection, MQTTQoS,
+    MQTTTransport, Metrics, Network, Notifications, Poller, Reboot, ReleaseSigning, SafeMode,
+    SelfUpdate, Settings, Startup, Supervisor, SyncBackoff, SyncHistory, Telemetry,
+    TelemetryTransport, TokenRefresh, Trash, TrustedReleaseKey, Wear, HTTP,
+};
+use miru_agent::sync::network;
+use miru_agent::workers::{
+    long_poll, mqtt as mqtt_worker, poller as poller_worker, telemetry as telemetry_worker,
+    token_refresh as token_refresh_worker, updater, wear as wear_worker,
+};
 
 // external crates
 use serde_json::json;
@@ -14,12 +45,191 @@ fn serialize_deserialize_settings() {
         enable_socket_server: false,
         enable_mqtt_worker: false,
         enable_poller: false,
+        poller: Poller {
+            interval_secs: 60 * 60,
+            jitter_secs: 5 * 60,
+            maintenance_windows: vec![Schedule::new("* 2-4 * * SAT,SUN").unwrap()],
+        },
+        enable_long_poll: true,
+        long_poll: LongPoll {
+            wait_secs: 120,
+            min_interval_secs: 30,
+        },
+        http: HTTP {
+            max_concurrent_requests: 2,
+            starvation_timeout_secs: 5,
+        },
+        reboot: Reboot {
+            paths: vec!["/boot".to_string()],
+            authorized: true,
+            maintenance_windows: vec![Schedule::new("* 2-3 * * *").unwrap()],
+            command: vec!["reboot".to_string()],
+        },
+        hooks: Hooks {
+            pre_deploy: vec!["/usr/bin/validate".to_string()],
+            post_deploy: vec![
+                "systemctl".to_string(),
+                "restart".to_string(),
+                "robot".to_string(),
+            ],
+            pre_remove: Vec::new(),
+            post_remove: Vec::new(),
+            timeout_secs: 30,
+        },
+        validate_config_schemas: false,
+        deployment_dir: DeploymentDir {
+            path: Some("/srv/robot".to_string()),
+            keep: 5,
+        },
+        notifications: Notifications {
+            sinks: vec![Sink {
+                target: Target::File {
+                    path: "/var/log/miru/notifications.jsonl".to_string(),
+                },
+                min_severity: Severity::Critical,
+                events: Vec::new(),
+                retry: None,
+            }],
+        },
+        safe_mode: SafeMode {
+            max_crashes: 3,
+            window_secs: 300,
+        },
+        wear: Wear {
+            low_wear_mode: true,
+        },
+        content_cache: ContentCache {
+            max_bytes: Some(64 * 1024 * 1024),
+            max_entry_bytes: Some(1024 * 1024),
+            min_accesses: 2,
+            config_types: BTreeMap::from([("firmware".to_string(), Rule::Never)]),
+            digest_algorithm: digest::Algorithm::Blake3,
+        },
+        content_warming: ContentWarming {
+            enabled: true,
+            period_secs: 2 * 60 * 60,
+            cold_threshold: 5,
+        },
+        metrics: Metrics {
+            listen_addr: Some("127.0.0.1:9090".to_string()),
+        },
+        sync_history: SyncHistory {
+            capacity: 10,
+            persist: true,
+        },
+        sync_backoff: SyncBackoff {
+            auth: Some(cooldown::Backoff {
+                base_secs: 60,
+                growth_factor: 4,
+                max_secs: 24 * 60 * 60,
+            }),
+            ..Default::default()
+        },
+        startup: Startup {
+            timeout_secs: 30,
+            component_timeouts_secs: BTreeMap::from([(Component::TokenRefresh, 90)]),
+        },
+        cell: Cell {
+            role: cell::Role::Controller,
+            listen_addr: Some("10.0.0.1:8443".to_string()),
+            peers: vec![cell::PeerCredential {
+                id: "robot-1".to_string(),
+                token_digest: digest::Digest::compute(digest::Algorithm::Sha256, b"secret"),
+            }],
+            ..Default::default()
+        },
+        network: Network {
+            proxy_url: Some("http://proxy.corp:3128".to_string()),
+            no_proxy: vec!["localhost".to_string()],
+            ca_bundle_path: Some("/etc/miru/ca.pem".to_string()),
+            ..Default::default()
+        },
+        trash: Trash {
+            enabled: true,
+            retention_secs: 3 * 86400,
+            max_bytes: None,
+        },
+        load_shedding: LoadShedding {
+            enabled: false,
+            max_in_flight: 8,
+            max_latency_ms: 500,
+            retry_after_secs: 5,
+        },
+        privacy_mode: true,
+        token_refresh: TokenRefresh {
+            margin_secs: 30 * 60,
+            watchdog_interval_secs: 0,
+        },
+        key_storage: KeyStorage {
+            backend: KeyBackend::Pkcs11,
+            pkcs11_module: "/usr/lib/softhsm/libsofthsm2.so".to_string(),
+            pkcs11_slot: Some(1),
+            pkcs11_pin_file: Some("/etc/miru/pkcs11.pin".to_string()),
+            ..Default::default()
+        },
+        self_update: SelfUpdate {
+            enabled: true,
+            check_interval_secs: 60 * 60,
+            health_check_secs: 5 * 60,
+            release_key_file: Some("/etc/miru/release_key.pub".to_string()),
+        },
+        telemetry: Telemetry {
+            enabled: true,
+            report_network: false,
+            interval_secs: 5 * 60,
+            retention: 12,
+            transport: TelemetryTransport::Mqtt,
+        },
+        disk_guard: DiskGuard {
+            min_free_bytes: 256 * 1024 * 1024,
+        },
+        supervisor: Supervisor {
+            enabled: true,
+            command: vec!["/usr/bin/robot".to_string(), "--headless".to_string()],
+            env: BTreeMap::from([("ROBOT_ENV".to_string(), "staging".to_string())]),
+            working_dir: Some("/opt/robot".to_string()),
+            stop_timeout_secs: 5,
+            restart_backoff: cooldown::Backoff {
+                base_secs: 2,
+                growth_factor: 3,
+                max_secs: 60,
+            },
+            reset_after_secs: 5 * 60,
+            health_check: vec!["/usr/bin/robot-health".to_string()],
+            health_check_interval_secs: 15,
+            health_check_timeout_secs: 3,
+            health_check_failures: 2,
+            restart_on_config_change: false,
+        },
+        release_signing: ReleaseSigning {
+            required: true,
+            trusted_keys: vec![TrustedReleaseKey {
+                key_id: "release-2026".to_string(),
+                public_key: "-----BEGIN PUBLIC KEY-----\n...\n-----END PUBLIC KEY-----\n"
+                    .to_string(),
+            }],
+        },
+        crash_reports: CrashReports {
+            upload: false,
+            log_lines: 20,
+        },
         backend: Backend {
             base_url: BackendUrl::new("https://staging.mirurobotics.com/agent/v1").unwrap(),
         },
         mqtt_broker: MQTTBroker {
             host: MqttHost::new("mqtt.staging.mirurobotics.com").unwrap(),
         },
+        mqtt_connection: MQTTConnection {
+            keep_alive_secs: 60,
+            clean_session: false,
+            max_inflight: 10,
+            qos: MQTTQoS {
+                ping: 0,
+                ..Default::default()
+            },
+            transport: MQTTTransport::WebSocket,
+            ..Default::default()
+        },
     };
     let serialized = serde_json::to_string(&settings).unwrap();
     let deserialized = serde_json::from_str::<Settings>(&serialized).unwrap();
@@ -37,19 +247,226 @@ fn deserialize_settings() {
         mqtt_broker: MQTTBroker {
             host: MqttHost::new("mqtt.staging.mirurobotics.com").unwrap(),
         },
+        mqtt_connection: MQTTConnection {
+            keep_alive_secs: 60,
+            clean_session: false,
+            max_inflight: 10,
+            qos: MQTTQoS {
+                ping: 0,
+                ..Default::default()
+            },
+            transport: MQTTTransport::WebSocket,
+            ..Default::default()
+        },
         is_persistent: false,
         enable_socket_server: false,
         enable_mqtt_worker: false,
         enable_poller: false,
+        poller: Poller {
+            interval_secs: 60 * 60,
+            jitter_secs: 5 * 60,
+            maintenance_windows: vec![Schedule::new("* 2-4 * * SAT,SUN").unwrap()],
+        },
+        enable_long_poll: true,
+        long_poll: LongPoll {
+            wait_secs: 120,
+            min_interval_secs: 30,
+        },
+        http: HTTP {
+            max_concurrent_requests: 2,
+            starvation_timeout_secs: 5,
+        },
+        reboot: Reboot {
+            paths: vec!["/boot".to_string()],
+            authorized: true,
+            maintenance_windows: vec![Schedule::new("* 2-3 * * *").unwrap()],
+            command: vec!["reboot".to_string()],
+        },
+        hooks: Hooks {
+            pre_deploy: vec!["/usr/bin/validate".to_string()],
+            post_deploy: vec![
+                "systemctl".to_string(),
+                "restart".to_string(),
+                "robot".to_string(),
+            ],
+            pre_remove: Vec::new(),
+            post_remove: Vec::new(),
+            timeout_secs: 30,
+        },
+        validate_config_schemas: false,
+        deployment_dir: DeploymentDir::default(),
+        notifications: Notifications {
+            sinks: vec![Sink {
+                target: Target::File {
+                    path: "/var/log/miru/notifications.jsonl".to_string(),
+                },
+                min_severity: Severity::Critical,
+                events: Vec::new(),
+                retry: None,
+            }],
+        },
+        safe_mode: SafeMode {
+            max_crashes: 3,
+            window_secs: 300,
+        },
+        wear: Wear {
+            low_wear_mode: true,
+        },
+        content_cache: ContentCache {
+            max_bytes: Some(64 * 1024 * 1024),
+            max_entry_bytes: Some(1024 * 1024),
+            min_accesses: 2,
+            config_types: BTreeMap::from([("firmware".to_string(), Rule::Never)]),
+            digest_algorithm: digest::Algorithm::Blake3,
+        },
+        content_warming: ContentWarming {
+            enabled: true,
+            period_secs: 2 * 60 * 60,
+            cold_threshold: 5,
+        },
+        metrics: Metrics {
+            listen_addr: Some("127.0.0.1:9090".to_string()),
+        },
+        sync_history: SyncHistory {
+            capacity: 10,
+            persist: true,
+        },
+        sync_backoff: SyncBackoff {
+            auth: Some(cooldown::Backoff {
+                base_secs: 60,
+                growth_factor: 4,
+                max_secs: 24 * 60 * 60,
+            }),
+            ..Default::default()
+        },
+        startup: Startup {
+            timeout_secs: 30,
+            component_timeouts_secs: BTreeMap::from([(Component::TokenRefresh, 90)]),
+        },
+        cell: Cell {
+            role: cell::Role::Controller,
+            listen_addr: Some("10.0.0.1:8443".to_string()),
+            peers: vec![cell::PeerCredential {
+                id: "robot-1".to_string(),
+                token_digest: digest::Digest::compute(digest::Algorithm::Sha256, b"secret"),
+            }],
+            ..Default::default()
+        },
+        network: Network {
+            proxy_url: Some("http://proxy.corp:3128".to_string()),
+            no_proxy: vec!["localhost".to_string()],
+            ca_bundle_path: Some("/etc/miru/ca.pem".to_string()),
+            ..Default::default()
+        },
+        trash: Trash {
+            enabled: true,
+            retention_secs: 3 * 86400,
+            max_bytes: None,
+        },
+        load_shedding: LoadShedding {
+            enabled: false,
+            max_in_flight: 8,
+            max_latency_ms: 500,
+            retry_after_secs: 5,
+        },
+        privacy_mode: true,
+        token_refresh: TokenRefresh {
+            margin_secs: 30 * 60,
+            watchdog_interval_secs: 0,
+        },
+        key_storage: KeyStorage {
+            backend: KeyBackend::Pkcs11,
+            pkcs11_module: "/usr/lib/softhsm/libsofthsm2.so".to_string(),
+            pkcs11_slot: Some(1),
+            pkcs11_pin_file: Some("/etc/miru/pkcs11.pin".to_string()),
+            ..Default::default()
+        },
+        self_update: SelfUpdate {
+            enabled: true,
+            check_interval_secs: 60 * 60,
+            health_check_secs: 5 * 60,
+            release_key_file: Some("/etc/miru/release_key.pub".to_string()),
+        },
+        telemetry: Telemetry {
+            enabled: true,
+            report_network: false,
+            interval_secs: 5 * 60,
+            retention: 12,
+            transport: TelemetryTransport::Mqtt,
+        },
+        disk_guard: DiskGuard {
+            min_free_bytes: 256 * 1024 * 1024,
+        },
+        supervisor: Supervisor {
+            enabled: true,
+            command: vec!["/usr/bin/robot".to_string(), "--headless".to_string()],
+            env: BTreeMap::from([("ROBOT_ENV".to_string(), "staging".to_string())]),
+            working_dir: Some("/opt/robot".to_string()),
+            stop_timeout_secs: 5,
+            restart_backoff: cooldown::Backoff {
+                base_secs: 2,
+                growth_factor: 3,
+                max_secs: 60,
+            },
+            reset_after_secs: 5 * 60,
+            health_check: vec!["/usr/bin/robot-health".to_string()],
+            health_check_interval_secs: 15,
+            health_check_timeout_secs: 3,
+            health_check_failures: 2,
+            restart_on_config_change: false,
+        },
+        release_signing: ReleaseSigning {
+            required: true,
+            trusted_keys: vec![TrustedReleaseKey {
+                key_id: "release-2026".to_string(),
+                public_key: "-----BEGIN PUBLIC KEY-----\n...\n-----END PUBLIC KEY-----\n"
+                    .to_string(),
+            }],
+        },
+        crash_reports: CrashReports {
+            upload: false,
+            log_lines: 20,
+        },
     };
     let valid_input = json!({
         "log_level": settings.log_level,
         "backend": settings.backend,
         "mqtt_broker": settings.mqtt_broker,
+        "mqtt_connection": settings.mqtt_connection,
         "is_persistent": settings.is_persistent,
         "enable_socket_server": settings.enable_socket_server,
         "enable_mqtt_worker": settings.enable_mqtt_worker,
         "enable_poller": settings.enable_poller,
+        "poller": settings.poller,
+        "enable_long_poll": settings.enable_long_poll,
+        "long_poll": settings.long_poll,
+        "http": settings.http,
+        "reboot": settings.reboot,
+        "hooks": settings.hooks,
+        "validate_config_schemas": settings.validate_config_schemas,
+        "deployment_dir": settings.deployment_dir,
+        "notifications": settings.notifications,
+        "safe_mode": settings.safe_mode,
+        "wear": settings.wear,
+        "content_cache": settings.content_cache,
+        "content_warming": settings.content_warming,
+        "metrics": settings.metrics,
+        "sync_history": settings.sync_history,
+        "sync_backoff": settings.sync_backoff,
+        "startup": settings.startup,
+        "cell": settings.cell,
+        "network": settings.network,
+        "trash": settings.trash,
+        "load_shedding": settings.load_shedding,
+        "privacy_mode": settings.privacy_mode,
+        "token_refresh": settings.token_refresh,
+        "key_storage": settings.key_storage,
+        "self_update": settings.self_update,
+        "telemetry": settings.telemetry,
+        "disk_guard": settings.disk_guard,
+        "supervisor": settings.supervisor,
+        "release_signing": settings.release_signing,
+        "crash_reports": settings.crash_reports,
     });
     let deserialized = serde_json::from_value::<Settings>(valid_input).unwrap();
     assert_eq!(deserialized, settings);
@@ -159,3 +576,1417 @@ fn deserialize_backend_accepts_allowed_host() {
         "https://api.mirurobotics.com/agent/v1"
     );
 }
+
+#[test]
+fn deserialize_long_poll() {
+    let valid_input = json!({"wait_secs": "2m", "min_interval_secs": 30});
+    let deserialized = serde_json::from_value::<LongPoll>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        LongPoll {
+            wait_secs: 120,
+            min_interval_secs: 30,
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<LongPoll>(json!({})).unwrap();
+    assert_eq!(deserialized, LongPoll::default());
+
+    // invalid JSON
+    assert!(serde_json::from_str::<LongPoll>("invalid-json").is_err());
+}
+
+#[test]
+fn long_poll_options() {
+    let options = LongPoll {
+        wait_secs: 120,
+        min_interval_secs: 30,
+    }
+    .options();
+    assert_eq!(options.wait, std::time::Duration::from_secs(120));
+    assert_eq!(options.min_interval, std::time::Duration::from_secs(30));
+    assert_eq!(options.backoff, long_poll::Options::default().backoff);
+}
+
+#[test]
+fn deserialize_startup() {
+    let valid_input = json!({
+        "timeout_secs": "2m",
+        "component_timeouts_secs": {"token_refresh": 300, "mqtt": 10},
+    });
+    let deserialized = serde_json::from_value::<Startup>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Startup {
+            timeout_secs: 120,
+            component_timeouts_secs: BTreeMap::from([
+                (Component::TokenRefresh, 300),
+                (Component::Mqtt, 10),
+            ]),
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Startup>(json!({})).unwrap();
+    assert_eq!(deserialized, Startup::default());
+
+    // unknown components are rejected
+    assert!(serde_json::from_value::<Startup>(
+        json!({"component_timeouts_secs": {"flux_capacitor": 1}})
+    )
+    .is_err());
+}
+
+#[test]
+fn startup_options() {
+    let options = Startup {
+        timeout_secs: 30,
+        component_timeouts_secs: BTreeMap::from([(Component::TokenRefresh, 90)]),
+    }
+    .options();
+    let secs = std::time::Duration::from_secs;
+    assert_eq!(options.timeout(Component::TokenRefresh), secs(90));
+    assert_eq!(options.timeout(Component::Poller), secs(30));
+}
+
+#[test]
+fn deserialize_http() {
+    // valid deserialization
+    let http = HTTP {
+        max_concurrent_requests: 8,
+        starvation_timeout_secs: 60,
+    };
+    let valid_input = json!({
+        "max_concurrent_requests": http.max_concurrent_requests,
+        "starvation_timeout_secs": http.starvation_timeout_secs,
+    });
+    let deserialized = serde_json::from_value::<HTTP>(valid_input).unwrap();
+    assert_eq!(deserialized, http);
+
+    // exclude default fields
+    let valid_input = json!({});
+    let deserialized = serde_json::from_value::<HTTP>(valid_input).unwrap();
+    assert_eq!(deserialized, HTTP::default());
+
+    // human-friendly durations
+    let valid_input = json!({"starvation_timeout_secs": "2m"});
+    let deserialized = serde_json::from_value::<HTTP>(valid_input).unwrap();
+    assert_eq!(deserialized.starvation_timeout_secs, 120);
+
+    // invalid durations list the accepted formats
+    let invalid_input = json!({"starvation_timeout_secs": "soon"});
+    let err = serde_json::from_value::<HTTP>(invalid_input).unwrap_err();
+    assert!(err.to_string().contains("\"1h30m\""), "{err}");
+    let invalid_input = json!({"starvation_timeout_secs": "1500ms"});
+    assert!(serde_json::from_value::<HTTP>(invalid_input).is_err());
+    let invalid_input = json!({"starvation_timeout_secs": -1});
+    assert!(serde_json::from_value::<HTTP>(invalid_input).is_err());
+
+    // invalid JSON
+    assert!(serde_json::from_str::<HTTP>("invalid-json").is_err());
+}
+
+#[test]
+fn serialize_units() {
+    let settings = Settings {
+        http: HTTP {
+            max_concurrent_requests: 2,
+            starvation_timeout_secs: 90,
+        },
+        safe_mode: SafeMode {
+            max_crashes: 3,
+            window_secs: 3600,
+        },
+        content_cache: ContentCache {
+            max_entry_bytes: Some(10 * 1024 * 1024),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let serialized = serde_json::to_value(&settings).unwrap();
+    assert_eq!(serialized["http"]["starvation_timeout_secs"], "1m30s");
+    assert_eq!(serialized["safe_mode"]["window_secs"], "1h");
+    assert_eq!(serialized["content_cache"]["max_entry_bytes"], "10MiB");
+
+    // no threshold
+    let serialized = serde_json::to_value(ContentCache::default()).unwrap();
+    assert!(serialized["max_entry_bytes"].is_null());
+}
+
+#[test]
+fn http_scheduling_options() {
+    let http = HTTP {
+        max_concurrent_requests: 3,
+        starvation_timeout_secs: 12,
+    };
+    let options = http.scheduling();
+    assert_eq!(options.max_concurrent, 3);
+    assert_eq!(
+        options.starvation_timeout,
+        std::time::Duration::from_secs(12)
+    );
+}
+
+#[test]
+fn deserialize_reboot() {
+    // valid deserialization
+    let reboot = Reboot {
+        paths: vec!["/boot".to_string(), "/etc/modprobe.d".to_string()],
+        authorized: true,
+        maintenance_windows: vec![Schedule::new("* 22-23,0-1 * * *").unwrap()],
+        command: vec!["shutdown".to_string(), "-r".to_string(), "now".to_string()],
+    };
+    let valid_input = json!({
+        "paths": reboot.paths,
+        "authorized": reboot.authorized,
+        "maintenance_windows": ["* 22-23,0-1 * * *"],
+        "command": reboot.command,
+    });
+    let deserialized = serde_json::from_value::<Reboot>(valid_input).unwrap();
+    assert_eq!(deserialized, reboot);
+
+    // exclude default fields
+    let valid_input = json!({});
+    let deserialized = serde_json::from_value::<Reboot>(valid_input).unwrap();
+    assert_eq!(deserialized, Reboot::default());
+
+    // invalid schedules
+    assert!(
+        serde_json::from_value::<Reboot>(json!({"maintenance_windows": ["* 25 * * *"]})).is_err()
+    );
+
+    // invalid JSON
+    assert!(serde_json::from_str::<Reboot>("invalid-json").is_err());
+}
+
+#[test]
+fn reboot_options() {
+    let reboot = Reboot {
+        paths: vec!["/boot".to_string()],
+        authorized: true,
+        maintenance_windows: vec![Schedule::new("0 2 * * *").unwrap()],
+        command: vec!["reboot".to_string()],
+    };
+    let options = reboot.options();
+    assert!(options.is_enabled());
+    assert!(options.authorized);
+    assert_eq!(options.windows.schedules, reboot.maintenance_windows);
+    assert_eq!(options.command, vec!["reboot".to_string()]);
+    assert!(!Reboot::default().options().is_enabled());
+}
+
+#[test]
+fn deserialize_hooks() {
+    // valid deserialization
+    let hooks = Hooks {
+        pre_deploy: vec!["/usr/bin/validate".to_string()],
+        post_deploy: vec![
+            "systemctl".to_string(),
+            "restart".to_string(),
+            "robot".to_string(),
+        ],
+        pre_remove: vec!["/usr/bin/drain".to_string()],
+        post_remove: vec!["/usr/bin/cleanup".to_string()],
+        timeout_secs: 120,
+    };
+    let valid_input = json!({
+        "pre_deploy": hooks.pre_deploy,
+        "post_deploy": hooks.post_deploy,
+        "pre_remove": hooks.pre_remove,
+        "post_remove": hooks.post_remove,
+        "timeout_secs": "2m",
+    });
+    let deserialized = serde_json::from_value::<Hooks>(valid_input).unwrap();
+    assert_eq!(deserialized, hooks);
+
+    // exclude default fields
+    let valid_input = json!({});
+    let deserialized = serde_json::from_value::<Hooks>(valid_input).unwrap();
+    assert_eq!(deserialized, Hooks::default());
+
+    // invalid JSON
+    assert!(serde_json::from_str::<Hooks>("invalid-json").is_err());
+}
+
+#[test]
+fn hooks_options() {
+    let hooks = Hooks {
+        post_deploy: vec!["/usr/bin/restart".to_string()],
+        timeout_secs: 0,
+        ..Hooks::default()
+    };
+    let options = hooks.options();
+    assert!(!options.is_enabled(Stage::PreDeploy));
+    assert!(options.is_enabled(Stage::PostDeploy));
+    assert_eq!(options.command(Stage::PostDeploy), ["/usr/bin/restart"]);
+    assert_eq!(options.timeout, Duration::from_secs(1));
+    assert_eq!(Hooks::default().options(), hooks::Options::default());
+}
+
+#[test]
+fn deserialize_deployment_dir() {
+    // valid deserialization
+    let valid_input = json!({"path": "/srv/robot", "keep": 5});
+    let deserialized = serde_json::from_value::<DeploymentDir>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        DeploymentDir {
+            path: Some("/srv/robot".to_string()),
+            keep: 5,
+        }
+    );
+    let versions = deserialized.versions().unwrap();
+    assert_eq!(versions.current_link().to_string(), "/srv/robot/current");
+    assert_eq!(versions.keep(), 5);
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<DeploymentDir>(json!({})).unwrap();
+    assert_eq!(deserialized, DeploymentDir::default());
+    assert!(deserialized.versions().is_none());
+
+    // invalid JSON
+    assert!(serde_json::from_str::<DeploymentDir>("invalid-json").is_err());
+}
+
+#[test]
+fn deserialize_notifications() {
+    // valid deserialization
+    let notifications = Notifications {
+        sinks: vec![
+            Sink {
+                target: Target::Mqtt {
+                    topic: "robots/alerts".to_string(),
+                },
+                min_severity: Severity::Info,
+                events: Vec::new(),
+                retry: None,
+            },
+            Sink {
+                target: Target::Command {
+                    command: vec![
+                        "gpioset".to_string(),
+                        "gpiochip0".to_string(),
+                        "17=1".to_string(),
+                    ],
+                    timeout_secs: 2,
+                },
+                min_severity: Severity::Critical,
+                events: Vec::new(),
+                retry: None,
+            },
+        ],
+    };
+    let valid_input = json!({
+        "sinks": [
+            {"type": "mqtt", "topic": "robots/alerts", "min_severity": "info"},
+            {"type": "command", "command": ["gpioset", "gpiochip0", "17=1"], "timeout_secs": 2, "min_severity": "critical"},
+        ],
+    });
+    let deserialized = serde_json::from_value::<Notifications>(valid_input).unwrap();
+    assert_eq!(deserialized, notifications);
+
+    // min severity defaults to warning
+    let valid_input = json!({
+        "sinks": [{"type": "webhook", "url": "http://localhost:8080/alerts"}],
+    });
+    let deserialized = serde_json::from_value::<Notifications>(valid_input).unwrap();
+    assert_eq!(deserialized.sinks[0].min_severity, Severity::Warning);
+
+    // command timeout has a default
+    let valid_input = json!({
+        "sinks": [{"type": "command", "command": ["true"]}],
+    });
+    let deserialized = serde_json::from_value::<Notifications>(valid_input).unwrap();
+    assert_eq!(
+        deserialized.sinks[0].target,
+        Target::Command {
+            command: vec!["true".to_string()],
+            timeout_secs: DEFAULT_COMMAND_TIMEOUT_SECS,
+        }
+    );
+
+    // exclude default fields
+    let valid_input = json!({});
+    let deserialized = serde_json::from_value::<Notifications>(valid_input).unwrap();
+    assert_eq!(deserialized, Notifications::default());
+
+    // unknown sink type
+    let invalid_input = json!({"sinks": [{"type": "pager"}]});
+    assert!(serde_json::from_value::<Notifications>(invalid_input).is_err());
+}
+
+#[test]
+fn deserialize_safe_mode() {
+    // valid deserialization
+    let safe_mode = SafeMode {
+        max_crashes: 3,
+        window_secs: 300,
+    };
+    let valid_input = json!({"max_crashes": 3, "window_secs": 300});
+    let deserialized = serde_json::from_value::<SafeMode>(valid_input).unwrap();
+    assert_eq!(deserialized, safe_mode);
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<SafeMode>(json!({})).unwrap();
+    assert_eq!(deserialized, SafeMode::default());
+
+    // invalid JSON
+    assert!(serde_json::from_str::<SafeMode>("invalid-json").is_err());
+}
+
+#[test]
+fn deserialize_safe_mode_units() {
+    let deserialized = serde_json::from_value::<SafeMode>(json!({"window_secs": "10m"})).unwrap();
+    assert_eq!(deserialized.window_secs, 600);
+
+    // negative numbers are still accepted and clamped when used
+    let deserialized = serde_json::from_value::<SafeMode>(json!({"window_secs": -1})).unwrap();
+    assert_eq!(deserialized.window_secs, -1);
+
+    assert!(serde_json::from_value::<SafeMode>(json!({"window_secs": "-1m"})).is_err());
+}
+
+#[test]
+fn safe_mode_options() {
+    let options = SafeMode {
+        max_crashes: 3,
+        window_secs: 300,
+    }
+    .options();
+    assert_eq!(options.max_crashes, 3);
+    assert_eq!(options.window, chrono::TimeDelta::minutes(5));
+
+    // negative windows are clamped
+    let options = SafeMode {
+        max_crashes: 3,
+        window_secs: -1,
+    }
+    .options();
+    assert_eq!(options.window, chrono::TimeDelta::zero());
+}
+
+#[test]
+fn deserialize_wear() {
+    let valid_input = json!({"low_wear_mode": true});
+    let deserialized = serde_json::from_value::<Wear>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Wear {
+            low_wear_mode: true
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Wear>(json!({})).unwrap();
+    assert_eq!(deserialized, Wear::default());
+    assert!(!deserialized.low_wear_mode);
+
+    // invalid JSON
+    assert!(serde_json::from_str::<Wear>("invalid-json").is_err());
+}
+
+#[test]
+fn deserialize_token_refresh() {
+    let valid_input = json!({"margin_secs": "30m", "watchdog_interval_secs": 30});
+    let deserialized = serde_json::from_value::<TokenRefresh>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        TokenRefresh {
+            margin_secs: 30 * 60,
+            watchdog_interval_secs: 30,
+        }
+    );
+    let options = deserialized.worker_options();
+    assert_eq!(options.refresh_advance_secs, 30 * 60);
+    assert_eq!(options.watchdog_interval, Some(Duration::from_secs(30)));
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<TokenRefresh>(json!({})).unwrap();
+    assert_eq!(deserialized, TokenRefresh::default());
+    let options = deserialized.worker_options();
+    let defaults = token_refresh_worker::TokenRefreshWorkerOptions::default();
+    assert_eq!(options.refresh_advance_secs, defaults.refresh_advance_secs);
+    assert_eq!(options.watchdog_interval, defaults.watchdog_interval);
+
+    // a zero interval disables the watchdog
+    let deserialized =
+        serde_json::from_value::<TokenRefresh>(json!({"watchdog_interval_secs": 0})).unwrap();
+    assert_eq!(deserialized.worker_options().watchdog_interval, None);
+
+    // invalid types
+    assert!(serde_json::from_value::<TokenRefresh>(json!({"margin_secs": "soon"})).is_err());
+}
+
+#[test]
+fn deserialize_key_storage() {
+    let valid_input = json!({
+        "backend": "tpm",
+        "tpm_handle": "0x81000002",
+        "tpm_tcti": "device:/dev/tpmrm0",
+    });
+    let deserialized = serde_json::from_value::<KeyStorage>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        KeyStorage {
+            backend: KeyBackend::Tpm,
+            tpm_handle: "0x81000002".to_string(),
+            tpm_tcti: Some("device:/dev/tpmrm0".to_string()),
+            ..Default::default()
+        }
+    );
+    let file = filesys::File::new("/srv/miru/auth/private_key.pem");
+    assert_eq!(
+        deserialized.private_key(file.clone()),
+        PrivateKey::Tpm(TpmKey::new(
+            "0x81000002".to_string(),
+            Some("device:/dev/tpmrm0".to_string())
+        ))
+    );
+
+    let deserialized = serde_json::from_value::<KeyStorage>(json!({
+        "backend": "PKCS11",
+        "pkcs11_module": "/usr/lib/libykcs11.so",
+        "pkcs11_key_label": "device",
+        "pkcs11_pin_file": "/etc/miru/pin",
+    }))
+    .unwrap();
+    assert_eq!(
+        deserialized.private_key(file.clone()),
+        PrivateKey::Pkcs11(Pkcs11Key::new(
+            "/usr/lib/libykcs11.so".to_string(),
+            None,
+            "device".to_string(),
+            Some(filesys::File::new("/etc/miru/pin")),
+        ))
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<KeyStorage>(json!({})).unwrap();
+    assert_eq!(deserialized, KeyStorage::default());
+    assert_eq!(
+        deserialized.private_key(file.clone()),
+        PrivateKey::File(file)
+    );
+
+    // an unknown backend falls back to the key file
+    let deserialized = serde_json::from_value::<KeyStorage>(json!({"backend": "yubikey"})).unwrap();
+    assert_eq!(deserialized.backend, KeyBackend::File);
+
+    // invalid types
+    assert!(serde_json::from_value::<KeyStorage>(json!({"pkcs11_slot": "first"})).is_err());
+}
+
+#[test]
+fn deserialize_self_update() {
+    let valid_input = json!({
+        "enabled": true,
+        "check_interval_secs": "1h",
+        "health_check_secs": 300,
+        "release_key_file": "/etc/miru/release_key.pub",
+    });
+    let deserialized = serde_json::from_value::<SelfUpdate>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        SelfUpdate {
+            enabled: true,
+            check_interval_secs: 60 * 60,
+            health_check_secs: 5 * 60,
+            release_key_file: Some("/etc/miru/release_key.pub".to_string()),
+        }
+    );
+    let options = deserialized.worker_options().unwrap();
+    assert_eq!(options.check_interval_secs, 60 * 60);
+    assert_eq!(options.health_check_secs, 5 * 60);
+    assert_eq!(
+        options.release_key,
+        filesys::File::new("/etc/miru/release_key.pub")
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<SelfUpdate>(json!({})).unwrap();
+    assert_eq!(deserialized, SelfUpdate::default());
+    assert!(!deserialized.enabled);
+    let defaults = updater::Options::default();
+    assert_eq!(
+        deserialized.check_interval_secs,
+        defaults.check_interval_secs as u64
+    );
+    assert_eq!(
+        deserialized.health_check_secs,
+        defaults.health_check_secs as u64
+    );
+
+    // releases can't be verified without a key
+    assert!(deserialized.worker_options().is_none());
+
+    // invalid types
+    assert!(serde_json::from_value::<SelfUpdate>(json!({"enabled": "yes"})).is_err());
+}
+
+#[test]
+fn deserialize_telemetry() {
+    let valid_input = json!({
+        "enabled": true,
+        "report_network": false,
+        "interval_secs": "5m",
+        "retention": 12,
+        "transport": "mqtt",
+    });
+    let deserialized = serde_json::from_value::<Telemetry>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Telemetry {
+            enabled: true,
+            report_network: false,
+            interval_secs: 5 * 60,
+            retention: 12,
+            transport: TelemetryTransport::Mqtt,
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Telemetry>(json!({})).unwrap();
+    assert_eq!(deserialized, Telemetry::default());
+    assert!(!deserialized.enabled);
+    assert!(deserialized.report_network);
+    assert_eq!(deserialized.transport, TelemetryTransport::Http);
+
+    // unknown transports fall back to the default
+    let deserialized =
+        serde_json::from_value::<Telemetry>(json!({"transport": "carrier pigeon"})).unwrap();
+    assert_eq!(deserialized.transport, TelemetryTransport::Http);
+
+    // invalid types
+    assert!(serde_json::from_value::<Telemetry>(json!({"retention": "many"})).is_err());
+}
+
+#[test]
+fn deserialize_poller() {
+    let valid_input = json!({
+        "interval_secs": "1h",
+        "jitter_secs": 300,
+        "maintenance_windows": ["* 2-4 * * SAT,SUN", "30 22 * * *"],
+    });
+    let deserialized = serde_json::from_value::<Poller>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Poller {
+            interval_secs: 60 * 60,
+            jitter_secs: 5 * 60,
+            maintenance_windows: vec![
+                Schedule::new("* 2-4 * * SAT,SUN").unwrap(),
+                Schedule::new("30 22 * * *").unwrap(),
+            ],
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Poller>(json!({})).unwrap();
+    assert_eq!(deserialized, Poller::default());
+    assert_eq!(deserialized.interval_secs, 12 * 60 * 60);
+    assert_eq!(deserialized.jitter_secs, 0);
+    assert!(deserialized.maintenance_windows.is_empty());
+
+    // invalid schedules
+    assert!(
+        serde_json::from_value::<Poller>(json!({"maintenance_windows": ["* 25 * * *"]})).is_err()
+    );
+
+    // invalid types
+    assert!(serde_json::from_value::<Poller>(json!({"jitter_secs": "some"})).is_err());
+}
+
+#[test]
+fn poller_options() {
+    let defaults = poller_worker::Options::default();
+    let options = Poller::default().worker_options();
+    assert_eq!(options.poll_interval_secs, defaults.poll_interval_secs);
+    assert_eq!(options.jitter_secs, defaults.jitter_secs);
+    assert!(Poller::default().windows().schedules.is_empty());
+
+    let poller = Poller {
+        interval_secs: 0,
+        jitter_secs: 30,
+        maintenance_windows: vec![Schedule::new("0 2 * * *").unwrap()],
+    };
+    let options = poller.worker_options();
+    assert_eq!(options.poll_interval_secs, 1);
+    assert_eq!(options.jitter_secs, 30);
+    assert_eq!(poller.windows().schedules, poller.maintenance_windows);
+}
+
+#[test]
+fn deserialize_crash_reports() {
+    let valid_input = json!({
+        "upload": false,
+        "log_lines": 20,
+    });
+    let deserialized = serde_json::from_value::<CrashReports>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        CrashReports {
+            upload: false,
+            log_lines: 20,
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<CrashReports>(json!({})).unwrap();
+    assert_eq!(deserialized, CrashReports::default());
+    assert!(deserialized.upload);
+    assert_eq!(deserialized.log_lines, logs::RECENT_LINES);
+
+    // invalid types
+    assert!(serde_json::from_value::<CrashReports>(json!({"log_lines": "all"})).is_err());
+}
+
+#[test]
+fn telemetry_network_report() {
+    let options = Telemetry::default().network_report();
+    assert!(options.enabled);
+    assert_eq!(options.max_age, network::Options::default().max_age);
+
+    let options = Telemetry {
+        report_network: false,
+        ..Default::default()
+    }
+    .network_report();
+    assert!(!options.enabled);
+}
+
+#[test]
+fn telemetry_worker_options() {
+    let defaults = telemetry_worker::Options::default();
+    let layout = Layout::with_data_dir(filesys::Dir::new("/srv/miru"));
+    let options = Telemetry::default().worker_options(&layout);
+    assert_eq!(options.interval_secs, defaults.interval_secs);
+    assert_eq!(options.retention, defaults.retention);
+    assert_eq!(options.transport, telemetry_worker::Transport::Http);
+    assert!(options.disk_paths.contains(&PathBuf::from("/srv/miru")));
+
+    let options = Telemetry {
+        interval_secs: 0,
+        retention: 0,
+        transport: TelemetryTransport::Mqtt,
+        ..Default::default()
+    }
+    .worker_options(&layout);
+    assert_eq!(options.interval_secs, 1);
+    assert_eq!(options.retention, 1);
+    assert_eq!(options.transport, telemetry_worker::Transport::Mqtt);
+}
+
+#[test]
+fn deserialize_disk_guard() {
+    let deserialized =
+        serde_json::from_value::<DiskGuard>(json!({"min_free_bytes": "1GiB"})).unwrap();
+    assert_eq!(deserialized.min_free_bytes, 1 << 30);
+    let deserialized =
+        serde_json::from_value::<DiskGuard>(json!({"min_free_bytes": 4096})).unwrap();
+    assert_eq!(deserialized.min_free_bytes, 4096);
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<DiskGuard>(json!({})).unwrap();
+    assert_eq!(deserialized, DiskGuard::default());
+    assert_eq!(deserialized.min_free_bytes, 64 << 20);
+
+    // invalid types
+    assert!(serde_json::from_value::<DiskGuard>(json!({"min_free_bytes": "lots"})).is_err());
+    assert!(serde_json::from_value::<DiskGuard>(json!({"min_free_bytes": -1})).is_err());
+}
+
+#[test]
+fn disk_guard() {
+    let layout = Layout::with_data_dir(filesys::Dir::new("/srv/miru"));
+    let guard = DiskGuard::default()
+        .guard(&layout, &DeploymentDir::default())
+        .unwrap();
+    assert_eq!(guard.min_free_bytes, 64 << 20);
+    assert_eq!(guard.paths, vec![PathBuf::from("/srv/miru")]);
+
+    let deployment_dir = DeploymentDir {
+        path: Some("/etc/robot".to_string()),
+        ..Default::default()
+    };
+    let guard = DiskGuard::default()
+        .guard(&layout, &deployment_dir)
+        .unwrap();
+    assert_eq!(
+        guard.paths,
+        vec![PathBuf::from("/srv/miru"), PathBuf::from("/etc/robot")]
+    );
+
+    // a zero threshold disables the guard
+    let disabled = DiskGuard { min_free_bytes: 0 };
+    assert!(disabled.guard(&layout, &deployment_dir).is_none());
+}
+
+#[test]
+fn deserialize_supervisor() {
+    let deserialized = serde_json::from_value::<Supervisor>(json!({
+        "enabled": true,
+        "command": ["/usr/bin/robot"],
+        "env": {"ROBOT_ENV": "prod"},
+        "stop_timeout_secs": "30s",
+        "health_check": ["curl", "-f", "http://localhost:8080/health"],
+        "health_check_interval_secs": "1m",
+    }))
+    .unwrap();
+    assert!(deserialized.enabled);
+    assert_eq!(deserialized.command, vec!["/usr/bin/robot".to_string()]);
+    assert_eq!(deserialized.env["ROBOT_ENV"], "prod");
+    assert_eq!(deserialized.stop_timeout_secs, 30);
+    assert_eq!(deserialized.health_check_interval_secs, 60);
+    assert_eq!(
+        deserialized.health_check_timeout_secs,
+        Supervisor::default().health_check_timeout_secs
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Supervisor>(json!({})).unwrap();
+    assert_eq!(deserialized, Supervisor::default());
+    assert!(!deserialized.enabled);
+    assert!(deserialized.restart_on_config_change);
+
+    // invalid types
+    assert!(serde_json::from_value::<Supervisor>(json!({"command": "robot"})).is_err());
+    assert!(serde_json::from_value::<Supervisor>(json!({"health_check_failures": -1})).is_err());
+}
+
+#[test]
+fn supervisor_worker_options() {
+    // nothing to supervise without a command
+    let supervisor = Supervisor {
+        enabled: true,
+        ..Default::default()
+    };
+    assert!(supervisor.worker_options().is_none());
+
+    let options = Supervisor {
+        command: vec!["/usr/bin/robot".to_string()],
+        working_dir: Some("/opt/robot".to_string()),
+        stop_timeout_secs: 5,
+        ..Default::default()
+    }
+    .worker_options()
+    .unwrap();
+    assert_eq!(options.app.command, vec!["/usr/bin/robot".to_string()]);
+    assert_eq!(options.app.working_dir, Some(PathBuf::from("/opt/robot")));
+    assert_eq!(options.app.stop_timeout, Duration::from_secs(5));
+    assert_eq!(options.health_check, None);
+    assert!(options.restart_on_config_change);
+
+    let options = Supervisor {
+        command: vec!["/usr/bin/robot".to_string()],
+        health_check: vec!["/usr/bin/robot-health".to_string()],
+        health_check_interval_secs: 0,
+        health_check_failures: 0,
+        ..Default::default()
+    }
+    .worker_options()
+    .unwrap();
+    let health_check = options.health_check.unwrap();
+    assert_eq!(
+        health_check.command,
+        vec!["/usr/bin/robot-health".to_string()]
+    );
+    assert_eq!(health_check.interval, Duration::from_secs(1));
+    assert_eq!(health_check.failure_threshold, 1);
+}
+
+#[test]
+fn deserialize_release_signing() {
+    let deserialized = serde_json::from_value::<ReleaseSigning>(json!({
+        "trusted_keys": [{"key_id": "release-2026", "public_key": "pem"}],
+    }))
+    .unwrap();
+    assert!(!deserialized.required);
+    assert_eq!(
+        deserialized.trusted_keys,
+        vec![TrustedReleaseKey {
+            key_id: "release-2026".to_string(),
+            public_key: "pem".to_string(),
+        }]
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<ReleaseSigning>(json!({})).unwrap();
+    assert_eq!(deserialized, ReleaseSigning::default());
+
+    // invalid types
+    assert!(serde_json::from_value::<ReleaseSigning>(json!({"required": "yes"})).is_err());
+    assert!(
+        serde_json::from_value::<ReleaseSigning>(json!({"trusted_keys": [{"key_id": "k"}]}))
+            .is_err()
+    );
+}
+
+#[test]
+fn release_signing_verifier_options() {
+    // releases aren't verified by default
+    assert!(ReleaseSigning::default().verifier_options().is_none());
+
+    let key = TrustedReleaseKey {
+        key_id: "release-2026".to_string(),
+        public_key: "pem".to_string(),
+    };
+    let options = ReleaseSigning {
+        required: false,
+        trusted_keys: vec![key.clone()],
+    }
+    .verifier_options()
+    .unwrap();
+    assert!(!options.required);
+    assert_eq!(options.trusted_keys.len(), 1);
+    assert_eq!(options.trusted_keys[0].key_id, key.key_id);
+    assert_eq!(options.trusted_keys[0].public_key, key.public_key);
+
+    // required without a key fails every release rather than deploying them unverified
+    let options = ReleaseSigning {
+        required: true,
+        trusted_keys: Vec::new(),
+    }
+    .verifier_options()
+    .unwrap();
+    assert!(options.required);
+    assert!(options.trusted_keys.is_empty());
+}
+
+#[test]
+fn wear_worker_options() {
+    let options = Wear::default().worker_options();
+    assert_eq!(
+        options.interval_secs,
+        wear_worker::Options::default().interval_secs
+    );
+
+    let options = Wear {
+        low_wear_mode: true,
+    }
+    .worker_options();
+    assert_eq!(
+        options.interval_secs,
+        wear_worker::Options::low_wear().interval_secs
+    );
+    assert!(options.interval_secs > wear_worker::Options::default().interval_secs);
+}
+
+#[test]
+fn deserialize_content_cache() {
+    let valid_input = json!({
+        "max_bytes": "64MiB",
+        "max_entry_bytes": 1024,
+        "min_accesses": 5,
+        "config_types": {"maps": "always", "firmware": "never"},
+        "digest_algorithm": "blake3",
+    });
+    let deserialized = serde_json::from_value::<ContentCache>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        ContentCache {
+            max_bytes: Some(64 * 1024 * 1024),
+            max_entry_bytes: Some(1024),
+            min_accesses: 5,
+            config_types: BTreeMap::from([
+                ("maps".to_string(), Rule::Always),
+                ("firmware".to_string(), Rule::Never),
+            ]),
+            digest_algorithm: digest::Algorithm::Blake3,
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<ContentCache>(json!({})).unwrap();
+    assert_eq!(deserialized, ContentCache::default());
+    assert_eq!(deserialized.max_bytes, None);
+    assert_eq!(deserialized.max_entry_bytes, None);
+
+    // human-friendly sizes and an explicit absence of a threshold
+    let deserialized =
+        serde_json::from_value::<ContentCache>(json!({"max_entry_bytes": "1.5MiB"})).unwrap();
+    assert_eq!(deserialized.max_entry_bytes, Some(1536 * 1024));
+    let deserialized =
+        serde_json::from_value::<ContentCache>(json!({"max_entry_bytes": null})).unwrap();
+    assert_eq!(deserialized.max_entry_bytes, None);
+    let invalid_input = json!({"max_entry_bytes": "huge"});
+    let err = serde_json::from_value::<ContentCache>(invalid_input).unwrap_err();
+    assert!(err.to_string().contains("\"10MiB\""), "{err}");
+
+    // unknown rules
+    let invalid_input = json!({"config_types": {"maps": "sometimes"}});
+    assert!(serde_json::from_value::<ContentCache>(invalid_input).is_err());
+
+    // unknown digest algorithms
+    let invalid_input = json!({"digest_algorithm": "md5"});
+    assert!(serde_json::from_value::<ContentCache>(invalid_input).is_err());
+}
+
+#[test]
+fn content_cache_policy() {
+    let policy = ContentCache {
+        max_bytes: None,
+        max_entry_bytes: Some(1024),
+        min_accesses: 5,
+        config_types: BTreeMap::from([("firmware".to_string(), Rule::Never)]),
+        digest_algorithm: digest::Algorithm::Sha256,
+    }
+    .policy();
+    assert_eq!(policy.max_entry_bytes, Some(1024));
+    assert_eq!(policy.min_accesses, 5);
+    assert_eq!(policy.rules.get("firmware"), Some(&Rule::Never));
+}
+
+#[test]
+fn deserialize_metrics() {
+    let valid_input = json!({"listen_addr": "127.0.0.1:9464"});
+    let deserialized = serde_json::from_value::<Metrics>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Metrics {
+            listen_addr: Some("127.0.0.1:9464".to_string()),
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Metrics>(json!({})).unwrap();
+    assert_eq!(deserialized, Metrics::default());
+    assert_eq!(deserialized.listen_addr, None);
+
+    // invalid types
+    let invalid_input = json!({"listen_addr": 9464});
+    assert!(serde_json::from_value::<Metrics>(invalid_input).is_err());
+}
+
+#[test]
+fn deserialize_sync_history() {
+    let valid_input = json!({"capacity": 10, "persist": true});
+    let deserialized = serde_json::from_value::<SyncHistory>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        SyncHistory {
+            capacity: 10,
+            persist: true,
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<SyncHistory>(json!({})).unwrap();
+    assert_eq!(deserialized, SyncHistory::default());
+    assert!(!deserialized.persist);
+
+    // invalid types
+    let invalid_input = json!({"capacity": -1});
+    assert!(serde_json::from_value::<SyncHistory>(invalid_input).is_err());
+}
+
+#[test]
+fn deserialize_content_warming() {
+    let valid_input = json!({"enabled": true, "period_secs": "2h", "cold_threshold": 5});
+    let deserialized = serde_json::from_value::<ContentWarming>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        ContentWarming {
+            enabled: true,
+            period_secs: 2 * 60 * 60,
+            cold_threshold: 5,
+        }
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<ContentWarming>(json!({})).unwrap();
+    assert_eq!(deserialized, ContentWarming::default());
+    assert!(!deserialized.enabled);
+
+    // invalid types
+    let invalid_input = json!({"cold_threshold": -1});
+    assert!(serde_json::from_value::<ContentWarming>(invalid_input).is_err());
+}
+
+#[test]
+fn content_warming_options() {
+    let file = filesys::File::new("/var/lib/miru/content_warming.json");
+    let options = ContentWarming {
+        enabled: true,
+        period_secs: 600,
+        cold_threshold: 3,
+    }
+    .options(file.clone());
+    assert!(options.enabled);
+    assert_eq!(options.period, std::time::Duration::from_secs(600));
+    assert_eq!(options.cold_threshold, 3);
+    assert_eq!(options.file, Some(file));
+}
+
+#[test]
+fn deserialize_sync_backoff() {
+    let valid_input = json!({
+        "network": {"base_secs": 1, "growth_factor": 1, "max_secs": 1},
+        "auth": {"base_secs": 60, "growth_factor": 4, "max_secs": 86400},
+    });
+    let deserialized = serde_json::from_value::<SyncBackoff>(valid_input).unwrap();
+    let network = cooldown::Backoff {
+        base_secs: 1,
+        growth_factor: 1,
+        max_secs: 1,
+    };
+    let auth = cooldown::Backoff {
+        base_secs: 60,
+        growth_factor: 4,
+        max_secs: 86400,
+    };
+    assert_eq!(
+        deserialized,
+        SyncBackoff {
+            network: Some(network),
+            auth: Some(auth),
+            server: None,
+            filesystem: None,
+        }
+    );
+    let policies = deserialized.policies();
+    assert_eq!(policies.network, Some(network));
+    assert_eq!(policies.auth, Some(auth));
+    assert_eq!(policies.server, None);
+
+    // classes without a policy use the syncer's default backoff
+    let deserialized = serde_json::from_value::<SyncBackoff>(json!({})).unwrap();
+    assert_eq!(deserialized, SyncBackoff::default());
+
+    // invalid policies
+    let invalid_input = json!({"auth": {"base_secs": 60}});
+    assert!(serde_json::from_value::<SyncBackoff>(invalid_input).is_err());
+}
+
+#[test]
+fn sync_history_options() {
+    let file = filesys::File::new("/var/lib/miru/sync_history.json");
+    let options = SyncHistory {
+        capacity: 10,
+        persist: false,
+    }
+    .options(file.clone());
+    assert_eq!(options.capacity, 10);
+    assert!(options.file.is_none());
+
+    let options = SyncHistory {
+        capacity: 10,
+        persist: true,
+    }
+    .options(file.clone());
+    assert_eq!(options.file, Some(file));
+}
+
+#[test]
+fn deserialize_cell() {
+    let digest = digest::Digest::compute(digest::Algorithm::Sha256, b"secret");
+    let valid_input = json!({
+        "role": "peer",
+        "controller_url": "http://10.0.0.1:8443",
+        "peer_id": "robot-1",
+        "token": "secret",
+        "peers": [{"id": "robot-2", "token_digest": digest.hex()}],
+    });
+    let deserialized = serde_json::from_value::<Cell>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Cell {
+            role: cell::Role::Peer,
+            listen_addr: None,
+            peers: vec![cell::PeerCredential {
+                id: "robot-2".to_string(),
+                token_digest: digest,
+            }],
+            controller_url: Some("http://10.0.0.1:8443".to_string()),
+            peer_id: Some("robot-1".to_string()),
+            token: Some("secret".to_string()),
+        }
+    );
+
+    // the token is redacted from debug output
+    let debug = format!("{deserialized:?}");
+    assert!(!debug.contains("\"secret\""));
+    assert!(debug.contains("token: Some(\"[REDACTED]\")"));
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Cell>(json!({})).unwrap();
+    assert_eq!(deserialized, Cell::default());
+    assert_eq!(deserialized.role, cell::Role::Standalone);
+
+    // invalid types
+    assert!(serde_json::from_value::<Cell>(json!({"role": "leader"})).is_err());
+    let invalid_input = json!({"peers": [{"id": "robot-2", "token_digest": "abc"}]});
+    assert!(serde_json::from_value::<Cell>(invalid_input).is_err());
+}
+
+#[test]
+fn deserialize_network() {
+    let valid_input = json!({
+        "proxy_url": "socks5://proxy.corp:1080",
+        "proxy_username": "robot",
+        "proxy_password": "hunter2",
+        "no_proxy": ["10.0.0.0/8", ".corp"],
+        "ca_bundle_path": "/etc/miru/ca.pem",
+        "headers": {"X-Fleet": "warehouse-3"},
+    });
+    let deserialized = serde_json::from_value::<Network>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Network {
+            proxy_url: Some("socks5://proxy.corp:1080".to_string()),
+            proxy_username: Some("robot".to_string()),
+            proxy_password: Some("hunter2".to_string()),
+            no_proxy: vec!["10.0.0.0/8".to_string(), ".corp".to_string()],
+            ca_bundle_path: Some("/etc/miru/ca.pem".to_string()),
+            headers: BTreeMap::from([("X-Fleet".to_string(), "warehouse-3".to_string())]),
+        }
+    );
+    let egress = deserialized.egress();
+    assert_eq!(
+        egress.proxy.as_ref().map(|p| p.url()),
+        Some("socks5://proxy.corp:1080")
+    );
+    assert_eq!(
+        egress.ca_bundle,
+        Some(filesys::File::new("/etc/miru/ca.pem"))
+    );
+    assert_eq!(
+        egress.headers,
+        vec![("X-Fleet".to_string(), "warehouse-3".to_string())]
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Network>(json!({})).unwrap();
+    assert_eq!(deserialized, Network::default());
+    assert_eq!(deserialized.egress(), Default::default());
+
+    // an invalid proxy is ignored
+    let deserialized =
+        serde_json::from_value::<Network>(json!({"proxy_url": "ftp://proxy.corp"})).unwrap();
+    assert_eq!(deserialized.egress().proxy, None);
+
+    // invalid headers and headers the agent sets itself are ignored
+    let deserialized = serde_json::from_value::<Network>(json!({"headers": {
+        "X-Fleet": "warehouse-3",
+        "Bad Name": "value",
+        "X-Bad-Value": "line\nbreak",
+        "User-Agent": "curl/8.0",
+        "miru-agent-version": "v0.0.0",
+    }}))
+    .unwrap();
+    assert_eq!(
+        deserialized.egress().headers,
+        vec![("X-Fleet".to_string(), "warehouse-3".to_string())]
+    );
+
+    // invalid types
+    assert!(serde_json::from_value::<Network>(json!({"no_proxy": "localhost"})).is_err());
+}
+
+#[test]
+fn deserialize_trash() {
+    let valid_input = json!({
+        "enabled": true,
+        "retention_secs": "2d",
+        "max_bytes": "16MiB",
+    });
+    let deserialized = serde_json::from_value::<Trash>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        Trash {
+            enabled: true,
+            retention_secs: 2 * 86400,
+            max_bytes: Some(16 * 1024 * 1024),
+        }
+    );
+    assert_eq!(
+        deserialized.options(),
+        Some(trash::Options {
+            retention: chrono::TimeDelta::days(2),
+            max_bytes: Some(16 * 1024 * 1024),
+        })
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<Trash>(json!({})).unwrap();
+    assert_eq!(deserialized, Trash::default());
+    assert_eq!(deserialized.options(), Some(trash::Options::default()));
+
+    // null means no size budget
+    let deserialized = serde_json::from_value::<Trash>(json!({"max_bytes": null})).unwrap();
+    assert_eq!(deserialized.max_bytes, None);
+
+    // disabled unlinks removed files
+    let deserialized = serde_json::from_value::<Trash>(json!({"enabled": false})).unwrap();
+    assert_eq!(deserialized.options(), None);
+
+    // invalid types
+    assert!(serde_json::from_value::<Trash>(json!({"retention_secs": "soon"})).is_err());
+}
+
+#[test]
+fn deserialize_load_shedding() {
+    let valid_input = json!({
+        "enabled": true,
+        "max_in_flight": 4,
+        "max_latency_ms": 250,
+        "retry_after_secs": "3s",
+    });
+    let deserialized = serde_json::from_value::<LoadShedding>(valid_input).unwrap();
+    assert_eq!(
+        deserialized,
+        LoadShedding {
+            enabled: true,
+            max_in_flight: 4,
+            max_latency_ms: 250,
+            retry_after_secs: 3,
+        }
+    );
+    assert_eq!(
+        deserialized.options(),
+        Some(shed::Options {
+            max_in_flight: 4,
+            max_latency: Duration::from_millis(250),
+            retry_after: Duration::from_secs(3),
+        })
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<LoadShedding>(json!({})).unwrap();
+    assert_eq!(deserialized, LoadShedding::default());
+    assert_eq!(deserialized.options(), Some(shed::Options::default()));
+
+    // a zero limit would shed every request
+    let deserialized = serde_json::from_value::<LoadShedding>(json!({"max_in_flight": 0})).unwrap();
+    assert_eq!(deserialized.options().unwrap().max_in_flight, 1);
+
+    // disabled never sheds
+    let deserialized = serde_json::from_value::<LoadShedding>(json!({"enabled": false})).unwrap();
+    assert_eq!(deserialized.options(), None);
+
+    // invalid types
+    assert!(serde_json::from_value::<LoadShedding>(json!({"max_in_flight": "many"})).is_err());
+}
+
+#[test]
+fn deserialize_mqtt_connection() {
+    let valid_input = json!({
+        "keep_alive_secs": "1m",
+        "clean_session": false,
+        "max_inflight": 10,
+        "qos": { "sync": 2, "ping": 0, "commands": 1, "state": 1 },
+        "reconnect_backoff": { "base_secs": 5, "growth_factor": 3, "max_secs": 600 },
+        "transport": "auto",
+        "websocket_port": 8443,
+        "websocket_path": "/ws",
+        "websocket_after_failures": 5,
+    });
+    let deserialized = serde_json::from_value::<MQTTConnection>(valid_input).unwrap();
+    let expected = MQTTConnection {
+        keep_alive_secs: 60,
+        clean_session: false,
+        max_inflight: 10,
+        qos: MQTTQoS {
+            sync: 2,
+            ping: 0,
+            commands: 1,
+            state: 1,
+        },
+        reconnect_backoff: cooldown::Backoff {
+            base_secs: 5,
+            growth_factor: 3,
+            max_secs: 600,
+        },
+        transport: MQTTTransport::Auto,
+        websocket_port: 8443,
+        websocket_path: "/ws".to_string(),
+        websocket_after_failures: 5,
+    };
+    assert_eq!(deserialized, expected);
+
+    let options = deserialized.worker_options();
+    assert_eq!(options.keep_alive, Duration::from_secs(60));
+    assert!(!options.clean_session);
+    assert_eq!(options.max_inflight, 10);
+    assert_eq!(options.backoff, expected.reconnect_backoff);
+    assert_eq!(
+        options.qos,
+        QoSLevels {
+            sync: rumqttc::QoS::ExactlyOnce,
+            ping: rumqttc::QoS::AtMostOnce,
+            commands: rumqttc::QoS::AtLeastOnce,
+            state: rumqttc::QoS::AtLeastOnce,
+        }
+    );
+    assert_eq!(
+        options.websocket,
+        Some(mqtt_worker::WebSocketFallback {
+            options: websocket::Options {
+                port: 8443,
+                path: "/ws".to_string(),
+                ..Default::default()
+            },
+            after_failures: 5,
+        })
+    );
+
+    // exclude default fields
+    let deserialized = serde_json::from_value::<MQTTConnection>(json!({})).unwrap();
+    assert_eq!(deserialized, MQTTConnection::default());
+    let options = deserialized.worker_options();
+    let defaults = mqtt_worker::Options::default();
+    assert_eq!(options.keep_alive, defaults.keep_alive);
+    assert_eq!(options.clean_session, defaults.clean_session);
+    assert_eq!(options.max_inflight, defaults.max_inflight);
+    assert_eq!(options.qos, defaults.qos);
+    assert_eq!(options.backoff, defaults.backoff);
+
+    // rumqttc rejects keep alives under a second and an empty in-flight window
+    let deserialized =
+        serde_json::from_value::<MQTTConnection>(json!({"keep_alive_secs": 0, "max_inflight": 0}))
+            .unwrap();
+    let options = deserialized.worker_options();
+    assert_eq!(options.keep_alive, Duration::from_secs(1));
+    assert_eq!(options.max_inflight, 1);
+
+    // invalid QoS levels fall back to the default
+    let deserialized =
+        serde_json::from_value::<MQTTConnection>(json!({"qos": {"sync": 3, "ping": 0}})).unwrap();
+    assert_eq!(
+        deserialized.qos,
+        MQTTQoS {
+            ping: 0,
+            ..Default::default()
+        }
+    );
+
+    // transports
+    let transport = |transport: &str| {
+        serde_json::from_value::<MQTTConnection>(json!({"transport": transport}))
+            .unwrap()
+            .worker_options()
+            .websocket
+            .map(|fallback| fallback.after_failures)
+    };
+    assert_eq!(transport("auto"), Some(3));
+    assert_eq!(transport("websocket"), Some(0));
+    assert_eq!(transport("tcp"), None);
+    assert_eq!(transport("carrier_pigeon"), Some(3));
+    let deserialized =
+        serde_json::from_value::<MQTTConnection>(json!({"websocket_after_failures": 0})).unwrap();
+    assert_eq!(
+        deserialized
+            .worker_options()
+            .websocket
+            .unwrap()
+            .after_failures,
+        1
+    );
+    assert_eq!(
+        serde_json::to_value(MQTTTransport::WebSocket).unwrap(),
+        json!("websocket")
+    );
+
+    // invalid types
+    assert!(serde_json::from_value::<MQTTConnection>(json!({"clean_session": "no"})).is_err());
+}
@@ -0,0 +1,104 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::storage::wear::{Meter, Usage};
+
+// external crates
+use chrono::{DateTime, Utc};
+
+fn usage() -> Usage {
+    Usage {
+        since: DateTime::<Utc>::UNIX_EPOCH,
+        logs_bytes: 1,
+        caches_bytes: 2,
+        deployments_bytes: 3,
+    }
+}
+
+pub mod meter {
+    use super::*;
+
+    #[tokio::test]
+    async fn starts_tracking_without_file() {
+        let dir = filesys::Dir::create_temp_dir("wear_meter_new")
+            .await
+            .unwrap();
+        let before = Utc::now();
+        let meter = Meter::init(dir.file("wear.json")).await;
+        assert!(meter.usage().since >= before);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn adds_to_previous_totals() {
+        let dir = filesys::Dir::create_temp_dir("wear_meter_prev")
+            .await
+            .unwrap();
+        let file = dir.file("wear.json");
+        file.write_json(&usage(), WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let meter = Meter::init(file).await;
+        let current = meter.usage();
+        assert_eq!(current.since, DateTime::<Utc>::UNIX_EPOCH);
+        assert!(current.logs_bytes >= 1);
+        assert!(current.caches_bytes >= 2);
+        assert!(current.deployments_bytes >= 3);
+        assert_eq!(
+            current.total_bytes(),
+            current.logs_bytes + current.caches_bytes + current.deployments_bytes
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resets_unreadable_file() {
+        let dir = filesys::Dir::create_temp_dir("wear_meter_bad")
+            .await
+            .unwrap();
+        let file = dir.file("wear.json");
+        file.write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let before = Utc::now();
+        let meter = Meter::init(file).await;
+        assert!(meter.usage().since >= before);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn persist() {
+        let dir = filesys::Dir::create_temp_dir("wear_meter_persist")
+            .await
+            .unwrap();
+        let file = dir.file("wear.json");
+        file.write_json(&usage(), WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let meter = Meter::init(file.clone()).await;
+        meter.persist().await.unwrap();
+        assert!(file.exists());
+
+        let persisted = file.read_json::<Usage>().await.unwrap();
+        assert_eq!(persisted.since, DateTime::<Utc>::UNIX_EPOCH);
+        assert!(persisted.caches_bytes >= 2);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn report() {
+        let dir = filesys::Dir::create_temp_dir("wear_meter_report")
+            .await
+            .unwrap();
+        let meter = Meter::init(dir.file("wear.json")).await;
+        let report = meter.report(true);
+        assert!(report.low_wear_mode);
+        assert_eq!(report.total_bytes, report.usage.total_bytes());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json["caches_bytes"].is_u64());
+        assert_eq!(json["low_wear_mode"], true);
+        dir.delete().await.unwrap();
+    }
+}
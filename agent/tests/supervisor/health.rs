@@ -0,0 +1,44 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::supervisor::health::{check, Options};
+
+fn sh(script: &str) -> Options {
+    Options {
+        command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn healthy() {
+    assert!(check(&sh("exit 0"), 42).await.is_ok());
+}
+
+#[tokio::test]
+async fn given_the_application_pid() {
+    let options = sh("test \"$MIRU_APP_PID\" = 42");
+    assert!(check(&options, 42).await.is_ok());
+    assert!(check(&options, 43).await.is_err());
+}
+
+#[tokio::test]
+async fn unhealthy() {
+    let err = check(&sh("echo 'not ready' >&2; exit 1"), 42)
+        .await
+        .unwrap_err();
+    assert_eq!(err.command, "sh -c echo 'not ready' >&2; exit 1");
+    assert_eq!(err.stderr, "not ready");
+    assert!(err.msg.contains("exit"), "{}", err.msg);
+}
+
+#[tokio::test]
+async fn times_out() {
+    let options = Options {
+        timeout: Duration::from_millis(200),
+        ..sh("sleep 30")
+    };
+    let err = check(&options, 42).await.unwrap_err();
+    assert!(err.msg.contains("timed out"), "{}", err.msg);
+}
@@ -0,0 +1,148 @@
+// standard crates
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+// internal crates
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::supervisor::process::{Options, Process};
+
+fn sh(script: &str) -> Options {
+    Options {
+        command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+        ..Default::default()
+    }
+}
+
+async fn wait_for_exit(process: &mut Process) -> std::process::ExitStatus {
+    for _ in 0..250 {
+        if let Some(status) = process.try_wait() {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("process {} didn't exit", process.pid());
+}
+
+/// Whether the process is gone or only waits to be reaped.
+fn is_gone(pid: &str) -> bool {
+    match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        Ok(stat) => stat
+            .rsplit(')')
+            .next()
+            .is_some_and(|rest| rest.trim_start().starts_with('Z')),
+        Err(_) => true,
+    }
+}
+
+pub mod start {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_the_exit_status() {
+        let mut process = Process::start(&sh("exit 3")).unwrap();
+        let status = wait_for_exit(&mut process).await;
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn env_and_working_dir() {
+        let dir = filesys::Dir::create_temp_dir("supervisor_env")
+            .await
+            .unwrap();
+        let options = Options {
+            env: BTreeMap::from([("ROBOT_ENV".to_string(), "staging".to_string())]),
+            working_dir: Some(dir.path().clone()),
+            ..sh("echo \"$ROBOT_ENV\" > out; pwd >> out")
+        };
+        let mut process = Process::start(&options).unwrap();
+        assert!(wait_for_exit(&mut process).await.success());
+
+        let out = tokio::fs::read_to_string(dir.path().join("out"))
+            .await
+            .unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "staging");
+        assert_eq!(
+            std::fs::canonicalize(lines[1]).unwrap(),
+            std::fs::canonicalize(dir.path()).unwrap()
+        );
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_command() {
+        let err = Process::start(&Options::default()).unwrap_err();
+        assert_eq!(err.msg, "no command configured");
+
+        let options = Options {
+            command: vec!["/nonexistent/robot".to_string()],
+            ..Default::default()
+        };
+        let err = Process::start(&options).unwrap_err();
+        assert_eq!(err.command, "/nonexistent/robot");
+    }
+}
+
+pub mod stop {
+    use super::*;
+
+    #[tokio::test]
+    async fn terminates_the_application() {
+        let process = Process::start(&sh("exec sleep 30")).unwrap();
+        let started_at = Instant::now();
+        let status = process.stop(Duration::from_secs(10)).await.unwrap();
+        assert!(!status.success());
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn kills_an_application_ignoring_termination() {
+        let process = Process::start(&sh("trap '' TERM; while true; do sleep 0.1; done")).unwrap();
+        // give the shell time to install the trap
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = process.stop(Duration::from_millis(300)).await.unwrap();
+        assert_eq!(status.code(), None);
+    }
+
+    #[tokio::test]
+    async fn stops_what_the_application_started() {
+        let dir = filesys::Dir::create_temp_dir("supervisor_group")
+            .await
+            .unwrap();
+        let pidfile = dir.path().join("pid");
+        let script = format!("sleep 30 & echo $! > {}; wait", pidfile.display());
+        let process = Process::start(&sh(&script)).unwrap();
+
+        let mut pid = String::new();
+        for _ in 0..100 {
+            pid = tokio::fs::read_to_string(&pidfile)
+                .await
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            if !pid.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!pid.is_empty());
+
+        process.stop(Duration::from_secs(10)).await;
+        for _ in 0..100 {
+            if is_gone(&pid) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(is_gone(&pid), "process {pid} is still running");
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn exited_applications() {
+        let mut process = Process::start(&sh("exit 0")).unwrap();
+        wait_for_exit(&mut process).await;
+        let status = process.stop(Duration::from_secs(10)).await.unwrap();
+        assert!(status.success());
+    }
+}
@@ -0,0 +1,191 @@
+// standard crates
+use std::path::PathBuf;
+
+// internal crates
+use miru_agent::authn::errors::{AuthnErr, MockError as AuthnMockErr};
+use miru_agent::cooldown;
+use miru_agent::deploy::{errors::InsufficientDiskSpaceErr, DeployErr};
+use miru_agent::filesys::errors::{FileSysErr, PathDoesNotExistErr};
+use miru_agent::http::errors::{HTTPErr, MockErr as HTTPMockErr, RequestFailed};
+use miru_agent::http::request::Params as HttpParams;
+use miru_agent::sync::backoff::{FailureClass, Policies, Streak};
+use miru_agent::sync::errors::{MockErr, SyncErrors};
+use miru_agent::sync::SyncErr;
+use miru_agent::trace;
+
+fn request_failed(status: reqwest::StatusCode) -> SyncErr {
+    SyncErr::HTTPClientErr(HTTPErr::RequestFailed(RequestFailed {
+        request: HttpParams::get("http://test/deployments").meta().unwrap(),
+        status,
+        error: None,
+        trace: trace!(),
+    }))
+}
+
+fn network_err() -> SyncErr {
+    SyncErr::HTTPClientErr(HTTPErr::MockErr(HTTPMockErr {
+        is_network_conn_err: true,
+    }))
+}
+
+fn filesys_err() -> SyncErr {
+    SyncErr::FileSysErr(FileSysErr::PathDoesNotExistErr(PathDoesNotExistErr {
+        path: PathBuf::from("/srv/miru/config.json"),
+        trace: trace!(),
+    }))
+}
+
+fn sync_errors(errors: Vec<SyncErr>) -> SyncErr {
+    SyncErr::SyncErrors(SyncErrors {
+        errors,
+        trace: trace!(),
+    })
+}
+
+pub mod failure_class {
+    use super::*;
+
+    #[test]
+    fn as_str_matches_serialization() {
+        for class in [
+            FailureClass::Network,
+            FailureClass::Auth,
+            FailureClass::Server,
+            FailureClass::Filesystem,
+            FailureClass::Other,
+        ] {
+            assert_eq!(
+                serde_json::to_value(class).unwrap(),
+                serde_json::json!(class.as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn network() {
+        assert_eq!(FailureClass::of(&network_err()), FailureClass::Network);
+        let err = SyncErr::AuthnErr(AuthnErr::MockError(AuthnMockErr {
+            is_network_conn_err: true,
+            trace: trace!(),
+        }));
+        assert_eq!(FailureClass::of(&err), FailureClass::Network);
+    }
+
+    #[test]
+    fn auth() {
+        let err = request_failed(reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(FailureClass::of(&err), FailureClass::Auth);
+        let err = request_failed(reqwest::StatusCode::FORBIDDEN);
+        assert_eq!(FailureClass::of(&err), FailureClass::Auth);
+        let err = SyncErr::AuthnErr(AuthnErr::MockError(AuthnMockErr {
+            is_network_conn_err: false,
+            trace: trace!(),
+        }));
+        assert_eq!(FailureClass::of(&err), FailureClass::Auth);
+    }
+
+    #[test]
+    fn server() {
+        let err = request_failed(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(FailureClass::of(&err), FailureClass::Server);
+        let err = request_failed(reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(FailureClass::of(&err), FailureClass::Server);
+    }
+
+    #[test]
+    fn filesystem() {
+        assert_eq!(FailureClass::of(&filesys_err()), FailureClass::Filesystem);
+        let err = SyncErr::from(DeployErr::from(InsufficientDiskSpaceErr {
+            mount_point: "/".to_string(),
+            available_bytes: 0,
+            total_bytes: 1,
+            min_free_bytes: 1,
+            trace: trace!(),
+        }));
+        assert_eq!(FailureClass::of(&err), FailureClass::Filesystem);
+    }
+
+    #[test]
+    fn other() {
+        let err = request_failed(reqwest::StatusCode::NOT_FOUND);
+        assert_eq!(FailureClass::of(&err), FailureClass::Other);
+        let err = SyncErr::MockErr(MockErr {
+            is_network_conn_err: false,
+        });
+        assert_eq!(FailureClass::of(&err), FailureClass::Other);
+    }
+
+    #[test]
+    fn sync_errors_take_the_most_severe_class() {
+        let err = sync_errors(vec![
+            network_err(),
+            request_failed(reqwest::StatusCode::BAD_GATEWAY),
+            request_failed(reqwest::StatusCode::UNAUTHORIZED),
+            filesys_err(),
+        ]);
+        assert_eq!(FailureClass::of(&err), FailureClass::Auth);
+
+        let err = sync_errors(vec![
+            request_failed(reqwest::StatusCode::BAD_GATEWAY),
+            filesys_err(),
+        ]);
+        assert_eq!(FailureClass::of(&err), FailureClass::Filesystem);
+
+        // network errors only decide if every error is one
+        let err = sync_errors(vec![
+            network_err(),
+            request_failed(reqwest::StatusCode::BAD_GATEWAY),
+        ]);
+        assert_eq!(FailureClass::of(&err), FailureClass::Server);
+        let err = sync_errors(vec![network_err(), network_err()]);
+        assert_eq!(FailureClass::of(&err), FailureClass::Network);
+    }
+}
+
+#[test]
+fn policies_by_class() {
+    let auth = cooldown::Backoff {
+        base_secs: 60,
+        growth_factor: 4,
+        max_secs: 86400,
+    };
+    let policies = Policies {
+        auth: Some(auth),
+        ..Default::default()
+    };
+    assert_eq!(policies.get(FailureClass::Auth), Some(&auth));
+    assert_eq!(policies.get(FailureClass::Network), None);
+    assert_eq!(policies.get(FailureClass::Server), None);
+    assert_eq!(policies.get(FailureClass::Filesystem), None);
+    assert_eq!(policies.get(FailureClass::Other), None);
+}
+
+#[test]
+fn policies_max_secs() {
+    assert_eq!(Policies::default().max_secs(), None);
+    let backoff = |max_secs| cooldown::Backoff {
+        base_secs: 1,
+        growth_factor: 2,
+        max_secs,
+    };
+    let policies = Policies {
+        network: Some(backoff(60)),
+        server: Some(backoff(3600)),
+        ..Default::default()
+    };
+    assert_eq!(policies.max_secs(), Some(3600));
+}
+
+#[test]
+fn streak_counts_consecutive_failures_of_a_class() {
+    let mut streak = Streak::default();
+    assert_eq!(streak.record(FailureClass::Auth), 1);
+    assert_eq!(streak.record(FailureClass::Auth), 2);
+    assert_eq!(streak.record(FailureClass::Network), 1);
+    assert_eq!(streak.record(FailureClass::Auth), 1);
+    assert_eq!(streak.class, Some(FailureClass::Auth));
+
+    streak.reset();
+    assert_eq!(streak, Streak::default());
+    assert_eq!(streak.record(FailureClass::Auth), 1);
+}
@@ -0,0 +1,148 @@
+// standard crates
+use std::collections::{BTreeMap, HashSet};
+
+// internal crates
+use miru_agent::events::model::{ConfigChange, ConfigChangeKind};
+use miru_agent::filesys::Overwrite;
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplTarget};
+use miru_agent::storage;
+use miru_agent::sync::changes::{diff, live_files, LiveFile};
+use miru_agent::testkit;
+
+fn live(cfg_inst_id: &str, deployment_id: &str) -> LiveFile {
+    LiveFile {
+        config_instance_id: cfg_inst_id.to_string(),
+        config_type_name: "app".to_string(),
+        deployment_id: deployment_id.to_string(),
+    }
+}
+
+fn files(entries: &[(&str, LiveFile)]) -> BTreeMap<String, LiveFile> {
+    entries
+        .iter()
+        .map(|(filepath, file)| (filepath.to_string(), file.clone()))
+        .collect()
+}
+
+fn change(kind: ConfigChangeKind, filepath: &str, file: &LiveFile) -> ConfigChange {
+    ConfigChange {
+        kind,
+        filepath: filepath.to_string(),
+        config_instance_id: file.config_instance_id.clone(),
+        config_type_name: file.config_type_name.clone(),
+        deployment_id: file.deployment_id.clone(),
+    }
+}
+
+pub mod diff_func {
+    use super::*;
+
+    #[test]
+    fn unchanged() {
+        let before = files(&[("/a.json", live("ci-1", "dpl-1"))]);
+        assert!(diff(&before, &before.clone(), &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn deployed_updated_and_removed() {
+        let before = files(&[
+            ("/a.json", live("ci-1", "dpl-1")),
+            ("/b.json", live("ci-2", "dpl-1")),
+        ]);
+        let after = files(&[
+            ("/b.json", live("ci-3", "dpl-2")),
+            ("/c.json", live("ci-4", "dpl-2")),
+        ]);
+
+        assert_eq!(
+            diff(&before, &after, &HashSet::new()),
+            vec![
+                change(ConfigChangeKind::Removed, "/a.json", &before["/a.json"]),
+                change(ConfigChangeKind::Updated, "/b.json", &after["/b.json"]),
+                change(ConfigChangeKind::Deployed, "/c.json", &after["/c.json"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn redeployed_files_are_updated() {
+        let before = files(&[
+            ("/a.json", live("ci-1", "dpl-1")),
+            ("/b.json", live("ci-2", "dpl-2")),
+        ]);
+        let deployed = HashSet::from(["dpl-1".to_string()]);
+
+        assert_eq!(
+            diff(&before, &before.clone(), &deployed),
+            vec![change(
+                ConfigChangeKind::Updated,
+                "/a.json",
+                &before["/a.json"]
+            )]
+        );
+    }
+}
+
+pub mod live_files_func {
+    use super::*;
+
+    #[tokio::test]
+    async fn deployed_and_drifted_deployments_are_live() {
+        let dir = testkit::temp_dir("sync_changes_live_files").await;
+        let storage = testkit::spawn_storage(&dir, storage::Capacities::default()).await;
+
+        let deployments = [
+            ("dpl-deployed", DplActivity::Deployed),
+            ("dpl-drifted", DplActivity::Drifted),
+            ("dpl-queued", DplActivity::Queued),
+            ("dpl-archived", DplActivity::Archived),
+        ];
+        for (id, activity) in deployments {
+            let cfg_inst = ConfigInstance {
+                id: format!("{id}-ci"),
+                config_type_name: "app".to_string(),
+                filepath: format!("/{id}.json"),
+                ..Default::default()
+            };
+            storage
+                .cfg_insts
+                .meta
+                .write(
+                    cfg_inst.id.clone(),
+                    cfg_inst.clone(),
+                    |_, _| false,
+                    Overwrite::Allow,
+                )
+                .await
+                .unwrap();
+            let deployment = Deployment {
+                id: id.to_string(),
+                activity_status: activity,
+                target_status: DplTarget::Deployed,
+                // config instances which aren't cached are skipped
+                config_instance_ids: vec![cfg_inst.id, "uncached".to_string()],
+                ..Default::default()
+            };
+            storage
+                .deployments
+                .write(id.to_string(), deployment, |_, _| false, Overwrite::Allow)
+                .await
+                .unwrap();
+        }
+
+        let actual = live_files(&storage.deployments, &storage.cfg_insts.meta)
+            .await
+            .unwrap();
+        assert_eq!(
+            actual,
+            files(&[
+                (
+                    "/dpl-deployed.json",
+                    live("dpl-deployed-ci", "dpl-deployed")
+                ),
+                ("/dpl-drifted.json", live("dpl-drifted-ci", "dpl-drifted")),
+            ])
+        );
+        dir.delete().await.unwrap();
+    }
+}
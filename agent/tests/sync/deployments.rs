@@ -1,15 +1,24 @@
 // standard crates
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 // internal crates
-use miru_agent::deploy::{apply, fsm};
+use miru_agent::deploy::{apply, fsm, schedule, space, DeployErr};
 use miru_agent::events::hub::{EventHub, SpawnOptions};
 use miru_agent::filesys::{self, Overwrite, PathExt};
+use miru_agent::http::conditional::Validators;
+use miru_agent::http::config_instances::{ContentPatch, PatchFormat};
 use miru_agent::http::errors::*;
+use miru_agent::journal::{self, Journal};
 use miru_agent::models::{self, DplActivity, DplErrStatus, DplTarget};
-use miru_agent::storage::{self, CfgInstContent, CfgInsts, Deployments, GitCommits, Releases};
+use miru_agent::storage::locks::{Resource, Writer};
+use miru_agent::storage::{
+    self, CfgInstContent, CfgInsts, ConfigSchemas, Deployments, GitCommits, Locks, Releases,
+};
 use miru_agent::sync::deployments::{sync, SyncArgs};
-use miru_agent::sync::SyncErr;
+use miru_agent::sync::history::Phases;
+use miru_agent::sync::{patch, warming, SyncErr};
+use miru_agent::telemetry::disk_space;
 
 // test crates
 use crate::mocks::http_client::{Call, CapturedRequest, MockClient};
@@ -29,11 +38,19 @@ struct Fixture {
     deployment_stor: Deployments,
     cfg_inst_stor: CfgInsts,
     cfg_inst_content_stor: CfgInstContent,
+    config_schema_stor: ConfigSchemas,
     release_stor: Releases,
     git_commit_stor: GitCommits,
+    journal: Journal,
     http_client: MockClient,
     retry_policy: fsm::RetryPolicy,
+    validate_schemas: bool,
+    space: Option<space::Guard>,
+    windows: schedule::Windows,
     event_hub: EventHub,
+    locks: Locks,
+    warming: warming::Warming,
+    list_validators: Mutex<Validators>,
     dir: filesys::Dir,
 }
 
@@ -50,12 +67,18 @@ impl Fixture {
             CfgInstContent::spawn(16, dir.subdir("cfg_inst_content"), 1000)
                 .await
                 .unwrap();
+        let (config_schema_stor, _) = ConfigSchemas::spawn(16, dir.subdir("config_schemas"), 1000)
+            .await
+            .unwrap();
         let (release_stor, _) = Releases::spawn(16, dir.file("releases.json"), 1000)
             .await
             .unwrap();
         let (git_commit_stor, _) = GitCommits::spawn(16, dir.file("git_commits.json"), 1000)
             .await
             .unwrap();
+        let journal = Journal::init(dir.file("journal.json"), journal::Options::default())
+            .await
+            .unwrap();
         let log_file = dir.file("events.jsonl");
         let (event_hub, _hub_handle) = EventHub::spawn(log_file, SpawnOptions::default())
             .await
@@ -64,11 +87,19 @@ impl Fixture {
             deployment_stor,
             cfg_inst_stor,
             cfg_inst_content_stor,
+            config_schema_stor,
             release_stor,
             git_commit_stor,
+            journal,
             http_client: MockClient::default(),
             retry_policy: fsm::RetryPolicy::default(),
+            validate_schemas: false,
+            space: None,
+            windows: schedule::Windows::default(),
             event_hub,
+            locks: Locks::new(),
+            warming: warming::Warming::default(),
+            list_validators: Mutex::default(),
             dir,
         }
     }
@@ -76,22 +107,35 @@ impl Fixture {
     async fn sync(&self) -> Result<Option<TimeDelta>, SyncErr> {
         let opts = apply::DeployOpts {
             retry_policy: self.retry_policy,
+            validate_schemas: self.validate_schemas,
+            space: self.space.clone(),
+            windows: self.windows.clone(),
+            ..Default::default()
         };
-        sync(&SyncArgs {
-            storage: &miru_agent::sync::deployments::Storage {
-                deployments: &self.deployment_stor,
-                cfg_insts: storage::CfgInstRef {
-                    meta: &self.cfg_inst_stor,
-                    content: &self.cfg_inst_content_stor,
+        sync(
+            &SyncArgs {
+                storage: &miru_agent::sync::deployments::Storage {
+                    deployments: &self.deployment_stor,
+                    cfg_insts: storage::CfgInstRef {
+                        meta: &self.cfg_inst_stor,
+                        content: &self.cfg_inst_content_stor,
+                        schemas: &self.config_schema_stor,
+                    },
+                    releases: &self.release_stor,
+                    git_commits: &self.git_commit_stor,
+                    journal: &self.journal,
+                    locks: &self.locks,
                 },
-                releases: &self.release_stor,
-                git_commits: &self.git_commit_stor,
+                http_client: &self.http_client,
+                opts: &opts,
+                token: "test_token",
+                event_hub: &self.event_hub,
+                warming: &self.warming,
+                list_validators: &self.list_validators,
+                audit_anchor: None,
             },
-            http_client: &self.http_client,
-            opts: &opts,
-            token: "test_token",
-            event_hub: &self.event_hub,
-        })
+            &mut Phases::default(),
+        )
         .await
     }
 
@@ -120,6 +164,35 @@ fn cfg_inst_args(f: &Fixture, ids: &[&str]) -> Vec<CfgInstArgs> {
         .collect()
 }
 
+/// Caches "cfg_inst_1" as a patch base for "cfg_inst_2" (see `patch_target_args`).
+async fn seed_patch_base(f: &Fixture, content: &str) {
+    let base: models::ConfigInstance = make_cfg_inst(CfgInstArgs {
+        id: "cfg_inst_1".to_string(),
+        filepath: f.fixture_path("patched.json"),
+    })
+    .into();
+    f.cfg_inst_stor
+        .write(base.id.clone(), base, |_, _| false, Overwrite::Allow)
+        .await
+        .unwrap();
+    f.cfg_inst_content_stor
+        .write(
+            "cfg_inst_1".to_string(),
+            content.to_string(),
+            |_, _| false,
+            Overwrite::Allow,
+        )
+        .await
+        .unwrap();
+}
+
+fn patch_target_args(f: &Fixture) -> Vec<CfgInstArgs> {
+    vec![CfgInstArgs {
+        id: "cfg_inst_2".to_string(),
+        filepath: f.fixture_path("patched.json"),
+    }]
+}
+
 // ========================= TESTS ========================= //
 
 #[tokio::test]
@@ -257,6 +330,130 @@ mod pull_success {
         assert_eq!(content, "old content");
     }
 
+    #[tokio::test]
+    async fn corrupted_content_is_downloaded_again() {
+        let f = Fixture::new("sync_content_corrupted").await;
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        f.cfg_inst_content_stor
+            .write(
+                "cfg_inst_1".to_string(),
+                "old content".to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+
+        // rot the cached content on disk without updating its digest
+        let file = f.dir.subdir("cfg_inst_content").file("cfg_inst_1.json");
+        let mut entry = file
+            .read_json::<miru_agent::cache::CacheEntry<String, String>>()
+            .await
+            .unwrap();
+        entry.value = "old cintent".to_string();
+        file.write_json(&entry, filesys::WriteOptions::OVERWRITE_NONATOMIC)
+            .await
+            .unwrap();
+
+        f.sync().await.unwrap();
+
+        assert_eq!(
+            f.http_client.call_count(Call::GetConfigInstanceContent),
+            1,
+            "should download corrupted content again"
+        );
+        let content = read_content(&f.cfg_inst_content_stor, "cfg_inst_1").await;
+        assert_ne!(content, "old cintent");
+    }
+
+    #[tokio::test]
+    async fn content_already_cached_records_no_bytes() {
+        let f = Fixture::new("sync_content_cached_metrics").await;
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+        let first = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(first.metrics.bytes_downloaded, 2);
+
+        f.sync().await.unwrap();
+        let second = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(second.metrics.bytes_downloaded, 2);
+    }
+
+    #[tokio::test]
+    async fn patches_content_from_cached_base() {
+        let f = Fixture::new("sync_content_patch").await;
+        seed_patch_base(&f, r#"{"mode":"fast","speed":4}"#).await;
+        let backend_dep = make_deployment("dpl_1", patch_target_args(&f));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        let expected = r#"{"mode":"fast","speed":5}"#;
+        f.http_client.set_get_config_instance_patch(move |id| {
+            assert_eq!(id, "cfg_inst_2");
+            Ok(ContentPatch {
+                base_id: "cfg_inst_1".to_string(),
+                format: PatchFormat::MergePatch,
+                patch: serde_json::json!({"speed": 5}),
+                digest: patch::digest(expected),
+            })
+        });
+
+        f.sync().await.unwrap();
+
+        let content = read_content(&f.cfg_inst_content_stor, "cfg_inst_2").await;
+        assert_eq!(content, expected);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstancePatch), 1);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 0);
+        let requests = f.http_client.requests();
+        let patch_request = requests
+            .iter()
+            .find(|r| r.call == Call::GetConfigInstancePatch)
+            .unwrap();
+        assert_eq!(
+            patch_request.query,
+            vec![
+                ("base".to_string(), "cfg_inst_1".to_string()),
+                (
+                    "base_digest".to_string(),
+                    format!("sha256:{}", patch::digest(r#"{"mode":"fast","speed":4}"#))
+                ),
+            ]
+        );
+        let dpl = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(dpl.metrics.bytes_downloaded, r#"{"speed":5}"#.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn non_canonical_base_skips_patch() {
+        let f = Fixture::new("sync_content_patch_non_canonical").await;
+        seed_patch_base(&f, r#"{"speed": 4, "mode": "fast"}"#).await;
+        let backend_dep = make_deployment("dpl_1", patch_target_args(&f));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstancePatch), 0);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 1);
+    }
+
+    #[tokio::test]
+    async fn no_cached_base_skips_patch() {
+        let f = Fixture::new("sync_content_patch_no_base").await;
+        let backend_dep = make_deployment("dpl_1", patch_target_args(&f));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstancePatch), 0);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 1);
+    }
+
     #[tokio::test]
     async fn merge_preserves_agent_fields() {
         let f = Fixture::new("sync_merge").await;
@@ -292,6 +489,12 @@ mod pull_success {
         f.sync().await.unwrap();
 
         let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        // staging metrics are recorded for the content downloaded during the sync
+        assert!(cached.metrics.staging_duration_ms.is_some());
+        let cached = models::Deployment {
+            metrics: seeded.metrics.clone(),
+            ..cached
+        };
         assert_eq!(seeded, cached);
     }
 
@@ -336,6 +539,39 @@ mod pull_success {
         );
     }
 
+    #[tokio::test]
+    async fn cached_config_instances_are_kept() {
+        let f = Fixture::new("cached_config_instances_are_kept").await;
+        let cached = models::ConfigInstance {
+            id: "cfg_inst_1".to_string(),
+            filepath: "/cached/path.json".to_string(),
+            ..Default::default()
+        };
+        f.cfg_inst_stor
+            .write(
+                cached.id.clone(),
+                cached.clone(),
+                |_, _| false,
+                Overwrite::Deny,
+            )
+            .await
+            .unwrap();
+        let cfg_inst_args = cfg_inst_args(&f, &["cfg_inst_1", "cfg_inst_2"]);
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args);
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        let stored = f
+            .cfg_inst_stor
+            .read("cfg_inst_1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(stored, cached);
+        assert_cfg_inst_stored(&f.cfg_inst_stor, "cfg_inst_2").await;
+    }
+
     #[tokio::test]
     async fn shared_config_instance_across_deployments() {
         let f = Fixture::new("sync_shared_cfg_inst").await;
@@ -496,6 +732,48 @@ mod pull_failure {
         }
     }
 
+    #[tokio::test]
+    async fn patch_digest_mismatch_falls_back_to_full_download() {
+        let f = Fixture::new("sync_content_patch_mismatch").await;
+        seed_patch_base(&f, r#"{"speed":4}"#).await;
+        let backend_dep = make_deployment("dpl_1", patch_target_args(&f));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        f.http_client.set_get_config_instance_patch(|_| {
+            Ok(ContentPatch {
+                base_id: "cfg_inst_1".to_string(),
+                format: PatchFormat::MergePatch,
+                patch: serde_json::json!({"speed": 5}),
+                digest: patch::digest("something else"),
+            })
+        });
+        f.http_client
+            .set_get_config_instance_content(|_| Ok(r#"{"speed": 5}"#.to_string()));
+
+        f.sync().await.unwrap();
+
+        let content = read_content(&f.cfg_inst_content_stor, "cfg_inst_2").await;
+        assert_eq!(content, r#"{"speed": 5}"#);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstancePatch), 1);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 1);
+    }
+
+    #[tokio::test]
+    async fn patch_request_failure_falls_back_to_full_download() {
+        let f = Fixture::new("sync_content_patch_unsupported").await;
+        seed_patch_base(&f, r#"{"speed":4}"#).await;
+        let backend_dep = make_deployment("dpl_1", patch_target_args(&f));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        let content = read_content(&f.cfg_inst_content_stor, "cfg_inst_2").await;
+        assert_eq!(content, "{}");
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstancePatch), 1);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 1);
+    }
+
     #[tokio::test]
     async fn content_all_fetches_fail() {
         let f = Fixture::new("sync_content_all_fail").await;
@@ -672,6 +950,284 @@ mod pull_failure {
     }
 }
 
+mod pinning {
+    use super::*;
+
+    async fn is_pinned(f: &Fixture, id: &str) -> bool {
+        f.cfg_inst_content_stor
+            .read_entry(id.to_string())
+            .await
+            .unwrap()
+            .pinned
+    }
+
+    #[tokio::test]
+    async fn deployed_content_is_pinned() {
+        let f = Fixture::new("sync_pins_deployed").await;
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        f.http_client
+            .set_get_config_instance_content(|_id| Ok("{}".to_string()));
+
+        f.sync().await.unwrap();
+
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(cached.activity_status, DplActivity::Deployed);
+        assert!(is_pinned(&f, "cfg_inst_1").await);
+    }
+
+    #[tokio::test]
+    async fn archived_content_is_unpinned() {
+        let f = Fixture::new("sync_unpins_archived").await;
+        let seeded = models::Deployment {
+            id: "dpl_1".to_string(),
+            activity_status: DplActivity::Deployed,
+            target_status: DplTarget::Deployed,
+            config_instance_ids: vec!["cfg_inst_1".to_string()],
+            ..Default::default()
+        };
+        f.deployment_stor
+            .write("dpl_1".to_string(), seeded, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        f.cfg_inst_content_stor
+            .write(
+                "cfg_inst_1".to_string(),
+                "{}".to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        f.cfg_inst_content_stor
+            .pin("cfg_inst_1".to_string())
+            .await
+            .unwrap();
+
+        let backend_dep = make_archived_dpl("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        f.sync().await.unwrap();
+
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(cached.activity_status, DplActivity::Archived);
+        assert!(!is_pinned(&f, "cfg_inst_1").await);
+    }
+}
+
+mod warming_content {
+    use super::*;
+
+    fn staged_dpl(id: &str, cfg_inst_args: Vec<CfgInstArgs>) -> BackendDeployment {
+        BackendDeployment {
+            target_status: BackendTargetStatus::DEPLOYMENT_TARGET_STATUS_STAGED,
+            ..make_deployment(id, cfg_inst_args)
+        }
+    }
+
+    async fn fixture(name: &str, cold_threshold: usize) -> Fixture {
+        let mut f = Fixture::new(name).await;
+        f.warming = warming::Warming::new(warming::Options {
+            enabled: true,
+            period: std::time::Duration::from_secs(60 * 60),
+            cold_threshold,
+            file: Some(f.dir.file("content_warming.json")),
+        });
+        let deployed = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        let staged = staged_dpl(
+            "dpl_2",
+            cfg_inst_args(&f, &["cfg_inst_2", "cfg_inst_3", "cfg_inst_4"]),
+        );
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![staged.clone(), deployed.clone()]));
+        f.http_client
+            .set_get_config_instance_content(|id| Ok(format!("{{\"id\": \"{id}\"}}")));
+        f
+    }
+
+    #[tokio::test]
+    async fn spreads_staged_content_over_the_period() {
+        let f = fixture("sync_warming_spreads", 2).await;
+
+        let wait = f.sync().await.unwrap().unwrap();
+
+        // the deployed deployment's content and the first staged entry
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 2);
+        read_content(&f.cfg_inst_content_stor, "cfg_inst_1").await;
+        let progress = f.warming.progress().await.unwrap();
+        assert_eq!((progress.total, progress.downloaded), (3, 1));
+        assert!(f.dir.file("content_warming.json").exists());
+
+        // the next entry is due a third of the way through the period
+        assert!(wait > TimeDelta::minutes(19) && wait <= TimeDelta::minutes(20));
+
+        // nothing else is due yet
+        f.sync().await.unwrap();
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 2);
+    }
+
+    #[tokio::test]
+    async fn warm_cache_downloads_everything() {
+        let f = fixture("sync_warming_warm", 10).await;
+
+        assert_eq!(f.sync().await.unwrap(), None);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 4);
+        assert!(f.warming.progress().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resumes_and_finishes() {
+        let mut f = fixture("sync_warming_resumes", 2).await;
+        let file = f.dir.file("content_warming.json");
+        let progress = warming::Progress {
+            started_at: Utc::now() - TimeDelta::hours(2),
+            total: 3,
+            downloaded: 0,
+        };
+        file.write_json(&progress, filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        f.warming = warming::Warming::load(warming::Options {
+            enabled: true,
+            period: std::time::Duration::from_secs(60 * 60),
+            cold_threshold: 2,
+            file: Some(file.clone()),
+        })
+        .await;
+
+        // the period has passed so every entry is due
+        assert_eq!(f.sync().await.unwrap(), None);
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 4);
+
+        // the next sync finds nothing missing and finishes warming
+        f.sync().await.unwrap();
+        assert!(f.warming.progress().await.is_none());
+        assert!(!file.exists());
+    }
+}
+
+mod config_schemas {
+    use super::*;
+
+    use serde_json::json;
+
+    fn schema_dpl(f: &Fixture, cfg_insts: &[(&str, &str)]) -> BackendDeployment {
+        let cfg_insts = cfg_insts
+            .iter()
+            .map(|(id, schema_id)| backend_api::models::ConfigInstance {
+                config_schema_id: schema_id.to_string(),
+                ..make_cfg_inst(CfgInstArgs {
+                    id: id.to_string(),
+                    filepath: f.fixture_path(&format!("{id}.json")),
+                })
+            })
+            .collect();
+        BackendDeployment {
+            config_instances: Some(cfg_insts),
+            ..make_deployment("dpl_1", vec![])
+        }
+    }
+
+    fn port_schema() -> serde_json::Value {
+        json!({"required": ["port"]})
+    }
+
+    #[tokio::test]
+    async fn pulls_and_caches_schemas() {
+        let mut f = Fixture::new("sync_schemas_pull").await;
+        f.validate_schemas = true;
+        let dpl = schema_dpl(
+            &f,
+            &[("cfg_inst_1", "cfg_sch_1"), ("cfg_inst_2", "cfg_sch_1")],
+        );
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![dpl.clone()]));
+        f.http_client
+            .set_get_config_instance_content(|_| Ok(r#"{"port": 80}"#.to_string()));
+        f.http_client.set_get_config_schema(|_| Ok(port_schema()));
+
+        f.sync().await.unwrap();
+
+        assert_eq!(
+            f.http_client.paths_for(Call::GetConfigSchema),
+            vec!["/config_schemas/cfg_sch_1".to_string()]
+        );
+        let cached = f
+            .config_schema_stor
+            .read("cfg_sch_1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(cached, port_schema());
+        let dpl = f.deployment_stor.read("dpl_1".to_string()).await.unwrap();
+        assert_eq!(dpl.activity_status, DplActivity::Deployed);
+
+        // cached schemas aren't pulled again
+        f.sync().await.unwrap();
+        assert_eq!(f.http_client.call_count(Call::GetConfigSchema), 1);
+    }
+
+    #[tokio::test]
+    async fn violating_content_fails_the_deployment() {
+        let mut f = Fixture::new("sync_schemas_violation").await;
+        f.validate_schemas = true;
+        let dpl = schema_dpl(&f, &[("cfg_inst_1", "cfg_sch_1")]);
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![dpl.clone()]));
+        f.http_client
+            .set_get_config_instance_content(|_| Ok(r#"{"host": "a"}"#.to_string()));
+        f.http_client.set_get_config_schema(|_| Ok(port_schema()));
+
+        f.sync().await.unwrap();
+
+        let dpl = f.deployment_stor.read("dpl_1".to_string()).await.unwrap();
+        assert_eq!(dpl.error_status, DplErrStatus::Failed);
+        assert!(!filesys::File::new(f.fixture_path("cfg_inst_1.json")).exists());
+    }
+
+    #[tokio::test]
+    async fn missing_schema_is_skipped() {
+        let mut f = Fixture::new("sync_schemas_missing").await;
+        f.validate_schemas = true;
+        let dpl = schema_dpl(&f, &[("cfg_inst_1", "cfg_sch_1")]);
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![dpl.clone()]));
+        f.http_client.set_get_config_schema(|_| {
+            Err(HTTPErr::RequestFailed(RequestFailed {
+                request: miru_agent::http::request::Params::get("http://mock/config_schemas")
+                    .meta()
+                    .unwrap(),
+                status: reqwest::StatusCode::NOT_FOUND,
+                error: None,
+                trace: miru_agent::trace!(),
+            }))
+        });
+
+        f.sync().await.unwrap();
+
+        assert!(f
+            .config_schema_stor
+            .read_optional("cfg_sch_1".to_string())
+            .await
+            .unwrap()
+            .is_none());
+        let dpl = f.deployment_stor.read("dpl_1".to_string()).await.unwrap();
+        assert_eq!(dpl.activity_status, DplActivity::Deployed);
+    }
+
+    #[tokio::test]
+    async fn not_pulled_when_validation_is_disabled() {
+        let f = Fixture::new("sync_schemas_disabled").await;
+        let dpl = schema_dpl(&f, &[("cfg_inst_1", "cfg_sch_1")]);
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![dpl.clone()]));
+
+        f.sync().await.unwrap();
+
+        assert_eq!(f.http_client.call_count(Call::GetConfigSchema), 0);
+    }
+}
+
 pub mod apply_success {
     use super::*;
 
@@ -689,6 +1245,26 @@ pub mod apply_success {
         assert_eq!(cached.error_status, DplErrStatus::None);
     }
 
+    #[tokio::test]
+    async fn deploy_records_metrics() {
+        let f = Fixture::new("apply_deploy_metrics").await;
+        let backend_dep =
+            make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1", "cfg_inst_2"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        f.http_client
+            .set_get_config_instance_content(|id| Ok(format!("{{\"id\":\"{id}\"}}")));
+
+        f.sync().await.unwrap();
+
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(cached.activity_status, DplActivity::Deployed);
+        assert_eq!(cached.metrics.bytes_downloaded, 38);
+        assert!(cached.metrics.staging_duration_ms.is_some());
+        assert!(cached.metrics.materialization_duration_ms.is_some());
+        assert_eq!(cached.metrics.hook_duration_ms, None);
+    }
+
     #[tokio::test]
     async fn archive_queued_deployment() {
         let f = Fixture::new("apply_archive_queued").await;
@@ -889,6 +1465,124 @@ pub mod apply_failure {
     }
 }
 
+mod disk_space {
+    use super::*;
+
+    #[tokio::test]
+    async fn full_disk_skips_downloads_and_deployments() {
+        let mut f = Fixture::new("sync_disk_full").await;
+        if disk_space(f.dir.path()).is_none() {
+            // the sandbox may not expose its disks
+            return;
+        }
+        f.space = Some(space::Guard::new(u64::MAX, vec![f.dir.path().clone()]));
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        let SyncErr::SyncErrors(err) = f.sync().await.unwrap_err() else {
+            panic!("expected sync errors");
+        };
+        assert!(err.errors.iter().any(|e| matches!(
+            e,
+            SyncErr::DeployErr(e) if matches!(e.as_ref(), DeployErr::InsufficientDiskSpace(_))
+        )));
+
+        // the deployments are still pulled but their content is neither downloaded
+        // nor deployed
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 0);
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_ne!(cached.activity_status, DplActivity::Deployed);
+        assert_eq!(cached.attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn full_disk_publishes_disk_low_once() {
+        let mut f = Fixture::new("sync_disk_low_event").await;
+        let Some(space) = disk_space(f.dir.path()) else {
+            // the sandbox may not expose its disks
+            return;
+        };
+        f.space = Some(space::Guard::new(u64::MAX, vec![f.dir.path().clone()]));
+
+        f.sync().await.unwrap_err();
+        f.sync().await.unwrap_err();
+
+        let events = f.event_hub.replay_after(0).await.unwrap();
+        let disk_low: Vec<_> = events
+            .iter()
+            .filter(|e| e.event_type == miru_agent::events::model::DISK_LOW)
+            .collect();
+        assert_eq!(disk_low.len(), 1, "a disk running low is reported once");
+        assert_eq!(disk_low[0].data["path"], space.mount_point);
+        assert_eq!(disk_low[0].data["total_bytes"], space.total_bytes);
+    }
+
+    #[tokio::test]
+    async fn room_on_disk_deploys() {
+        let mut f = Fixture::new("sync_disk_room").await;
+        f.space = Some(space::Guard::new(1, vec![f.dir.path().clone()]));
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(cached.activity_status, DplActivity::Deployed);
+    }
+}
+
+mod maintenance_windows {
+    use super::*;
+
+    /// A window open for the hour after the current one, every day.
+    fn next_hour() -> schedule::Schedule {
+        let hour = (Utc::now() + TimeDelta::hours(1)).format("%H");
+        schedule::Schedule::new(&format!("* {hour} * * *")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn outside_a_window_stages_deployments() {
+        let mut f = Fixture::new("sync_window_closed").await;
+        f.windows = schedule::Windows::new(vec![next_hour()]);
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        // the sync is repeated once the window opens
+        let wait = f.sync().await.unwrap().expect("should wait for the window");
+        assert!(
+            wait > TimeDelta::zero() && wait <= TimeDelta::hours(1),
+            "{wait}"
+        );
+
+        // the content is downloaded but not deployed
+        assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 1);
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_ne!(cached.activity_status, DplActivity::Deployed);
+        assert_eq!(cached.attempts, 0);
+        assert!(!std::path::Path::new(&f.fixture_path("cfg_inst_1.json")).exists());
+    }
+
+    #[tokio::test]
+    async fn inside_a_window_deploys() {
+        let mut f = Fixture::new("sync_window_open").await;
+        f.windows = schedule::Windows::new(vec![
+            next_hour(),
+            schedule::Schedule::new("* * * * *").unwrap(),
+        ]);
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        assert_eq!(f.sync().await.unwrap(), None);
+
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(cached.activity_status, DplActivity::Deployed);
+    }
+}
+
 mod apply_error_isolation {
     use super::*;
 
@@ -973,7 +1667,28 @@ mod push_success {
     }
 
     #[tokio::test]
-    async fn dirty_flag_preserved_on_failure() {
+    async fn includes_metrics_in_body() {
+        let f = Fixture::new("sync_push_metrics").await;
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        let requests = f.http_client.requests();
+        let push = requests
+            .iter()
+            .find(|r| r.call == Call::UpdateDeployment)
+            .expect("push should send UpdateDeployment");
+        let body: serde_json::Value = serde_json::from_str(push.body.as_deref().unwrap()).unwrap();
+        assert_eq!(body["metrics"]["bytes_downloaded"], 2);
+        assert!(body["metrics"]["staging_duration_ms"].is_u64());
+        assert!(body["metrics"]["materialization_duration_ms"].is_u64());
+        assert!(body["metrics"].get("hook_duration_ms").is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_push_stays_queued() {
         let f = Fixture::new("sync_push_dirty_preserved").await;
         let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
         f.http_client
@@ -984,7 +1699,7 @@ mod push_success {
             }))
         });
 
-        // first sync: push fails, dirty flag should remain
+        // first sync: push fails, the update should stay queued
         f.sync().await.unwrap_err();
         let calls_after_first_sync = f.http_client.call_count(Call::UpdateDeployment);
         assert_eq!(
@@ -992,13 +1707,17 @@ mod push_success {
             "first sync should attempt one deployment push"
         );
 
-        let dirty = f.deployment_stor.get_dirty_entries().await.unwrap();
+        let queued = f.journal.entries().await;
         assert_eq!(
-            dirty.len(),
+            queued.len(),
             1,
-            "deployment should still be dirty after push failure"
+            "update should still be queued after push failure"
         );
-        assert_eq!(dirty[0].value.id, "dpl_1");
+        assert_eq!(queued[0].attempts, 1);
+        assert!(matches!(
+            &queued[0].request,
+            journal::Request::DeploymentStatus { deployment_id, .. } if deployment_id == "dpl_1"
+        ));
 
         // second sync with fixed push: should push again
         f.http_client
@@ -1011,12 +1730,13 @@ mod push_success {
             "second sync should retry deployment push exactly once"
         );
 
-        // now no longer dirty
+        // now no longer dirty or queued
         let dirty2 = f.deployment_stor.get_dirty_entries().await.unwrap();
         assert!(
             dirty2.is_empty(),
             "deployment should be clean after successful push"
         );
+        assert!(f.journal.is_empty().await);
     }
 
     #[tokio::test]
@@ -1160,15 +1880,14 @@ mod push_failure {
             "both deployments should attempt push"
         );
 
-        // one deployment should still be dirty (the one whose push failed)
-        let dirty = f.deployment_stor.get_dirty_entries().await.unwrap();
+        // one update should still be queued (the one whose push failed)
         assert_eq!(
-            dirty.len(),
+            f.journal.len().await,
             1,
-            "exactly one deployment should remain dirty after partial push failure"
+            "exactly one update should remain queued after partial push failure"
         );
 
-        // second sync: only the previously-failed dirty entry re-pushed
+        // second sync: only the previously-failed update re-pushed
         let result2 = f.sync().await;
         assert!(result2.is_ok(), "second sync should succeed");
         assert_eq!(
@@ -1182,6 +1901,10 @@ mod push_failure {
             dirty2.is_empty(),
             "all deployments should be clean after successful retry"
         );
+        assert!(
+            f.journal.is_empty().await,
+            "no updates should remain queued after successful retry"
+        );
     }
 }
 
@@ -1394,13 +2117,14 @@ mod idempotency {
         f.http_client
             .set_list_all_deployments(move || Ok(vec![dpl.clone()]));
 
-        // first sync — deploys and should emit exactly 1 event
+        // first sync — deploys and should emit exactly 2 events: the deployment's
+        // and its config file's
         f.sync().await.unwrap();
         let events_after_first = f.event_hub.replay_after(0).await.unwrap();
         assert_eq!(
             events_after_first.len(),
-            1,
-            "first sync should emit exactly 1 event"
+            2,
+            "first sync should emit exactly 2 events"
         );
 
         // second sync — deployment is already deployed, no state change
@@ -1408,7 +2132,7 @@ mod idempotency {
         let events_after_second = f.event_hub.replay_after(0).await.unwrap();
         assert_eq!(
             events_after_second.len(),
-            1,
+            2,
             "second sync should NOT emit another event, got {} total",
             events_after_second.len()
         );
@@ -1417,7 +2141,65 @@ mod idempotency {
 
 mod event_emission {
     use super::*;
-    use miru_agent::events::model::{DEPLOYMENT_DEPLOYED, DEPLOYMENT_REMOVED};
+    use miru_agent::events::model::{
+        Event, CONFIG_CHANGED, DEPLOYMENT_DEPLOYED, DEPLOYMENT_FAILED, DEPLOYMENT_REMOVED,
+    };
+
+    /// The deployment lifecycle events published, without the config change events
+    /// published alongside them.
+    async fn deployment_events(f: &Fixture) -> Vec<Event> {
+        f.event_hub
+            .replay_after(0)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.event_type != CONFIG_CHANGED)
+            .collect()
+    }
+
+    async fn config_changed_events(f: &Fixture) -> Vec<Event> {
+        f.event_hub
+            .replay_after(0)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.event_type == CONFIG_CHANGED)
+            .collect()
+    }
+
+    async fn seed_deployed(f: &Fixture, dpl_id: &str, cfg_inst: CfgInstArgs) {
+        let cfg_inst: models::ConfigInstance = make_cfg_inst(cfg_inst).into();
+        let seeded = models::Deployment {
+            id: dpl_id.to_string(),
+            activity_status: DplActivity::Deployed,
+            error_status: DplErrStatus::None,
+            target_status: DplTarget::Deployed,
+            config_instance_ids: vec![cfg_inst.id.clone()],
+            ..Default::default()
+        };
+        f.deployment_stor
+            .write(dpl_id.to_string(), seeded, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        f.cfg_inst_content_stor
+            .write(
+                cfg_inst.id.clone(),
+                "{}".to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        f.cfg_inst_stor
+            .write(
+                cfg_inst.id.clone(),
+                cfg_inst,
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
 
     #[tokio::test]
     async fn deployed_deployment_emits_deployed_event() {
@@ -1428,7 +2210,7 @@ mod event_emission {
 
         f.sync().await.unwrap();
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert_eq!(events.len(), 1, "should emit exactly 1 event");
         assert_eq!(events[0].event_type, DEPLOYMENT_DEPLOYED);
         assert_eq!(events[0].data["deployment_id"], "dpl_1");
@@ -1468,7 +2250,7 @@ mod event_emission {
 
         f.sync().await.unwrap();
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert_eq!(events.len(), 1, "should emit exactly 1 event");
         assert_eq!(events[0].event_type, DEPLOYMENT_REMOVED);
         assert_eq!(events[0].data["deployment_id"], "dpl_1");
@@ -1511,7 +2293,7 @@ mod event_emission {
         // sync succeeds but deployment is skipped (in cooldown)
         f.sync().await.unwrap();
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert!(
             events.is_empty(),
             "non-actionable deployment should emit no events"
@@ -1530,7 +2312,7 @@ mod event_emission {
 
         let _ = f.sync().await;
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert!(
             events.is_empty(),
             "failed apply should emit no events, got {} events",
@@ -1538,6 +2320,30 @@ mod event_emission {
         );
     }
 
+    #[tokio::test]
+    async fn exhausted_retries_emit_failed_event() {
+        let mut f = Fixture::new("evt_failed").await;
+        f.retry_policy.max_attempts = 1;
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+        f.http_client.set_get_config_instance_content(|_id| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+
+        f.sync().await.unwrap_err();
+
+        let cached = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(cached.error_status, DplErrStatus::Failed);
+        let events = deployment_events(&f).await;
+        assert_eq!(events.len(), 1, "should emit exactly 1 event");
+        assert_eq!(events[0].event_type, DEPLOYMENT_FAILED);
+        assert_eq!(events[0].data["deployment_id"], "dpl_1");
+        assert_eq!(events[0].data["attempts"], 1);
+    }
+
     #[tokio::test]
     async fn deployed_event_data_includes_timestamps() {
         let f = Fixture::new("evt_timestamps").await;
@@ -1547,7 +2353,7 @@ mod event_emission {
 
         f.sync().await.unwrap();
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert_eq!(events.len(), 1);
 
         // deployed_at should be set by the FSM as a non-null string
@@ -1594,7 +2400,7 @@ mod event_emission {
 
         f.sync().await.unwrap();
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert_eq!(events.len(), 2, "should emit 2 events");
 
         let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
@@ -1642,11 +2448,171 @@ mod event_emission {
 
         f.sync().await.unwrap();
 
-        let events = f.event_hub.replay_after(0).await.unwrap();
+        let events = deployment_events(&f).await;
         assert!(
             events.is_empty(),
             "steady-state deployment should emit no events, got {} events",
             events.len()
         );
     }
+
+    #[tokio::test]
+    async fn deployed_deployment_emits_config_changed_event() {
+        let f = Fixture::new("evt_config_deployed").await;
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(&f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        let events = config_changed_events(&f).await;
+        assert_eq!(events.len(), 1);
+        let changes = events[0].data["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["kind"], "deployed");
+        assert_eq!(changes[0]["filepath"], f.fixture_path("cfg_inst_1.json"));
+        assert_eq!(changes[0]["config_instance_id"], "cfg_inst_1");
+        assert_eq!(changes[0]["deployment_id"], "dpl_1");
+
+        // nothing changes on the next sync
+        f.sync().await.unwrap();
+        assert_eq!(config_changed_events(&f).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn archived_deployment_emits_removed_config_change() {
+        let f = Fixture::new("evt_config_removed").await;
+        let cfg_insts = cfg_inst_args(&f, &["cfg_inst_1"]);
+        seed_deployed(&f, "dpl_1", cfg_insts[0].clone()).await;
+        let backend_dep = make_archived_dpl("dpl_1", cfg_insts);
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+
+        f.sync().await.unwrap();
+
+        let events = config_changed_events(&f).await;
+        assert_eq!(events.len(), 1);
+        let changes = events[0].data["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["kind"], "removed");
+        assert_eq!(changes[0]["filepath"], f.fixture_path("cfg_inst_1.json"));
+        assert_eq!(changes[0]["deployment_id"], "dpl_1");
+    }
+
+    #[tokio::test]
+    async fn replaced_file_emits_updated_config_change() {
+        let f = Fixture::new("evt_config_updated").await;
+        let old = CfgInstArgs {
+            id: "cfg_inst_1".to_string(),
+            filepath: f.fixture_path("app.json"),
+        };
+        let new = CfgInstArgs {
+            id: "cfg_inst_2".to_string(),
+            filepath: f.fixture_path("app.json"),
+        };
+        seed_deployed(&f, "dpl_1", old.clone()).await;
+        let dpl_archive = make_archived_dpl("dpl_1", vec![old]);
+        let dpl_deploy = make_deployment("dpl_2", vec![new]);
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![dpl_archive.clone(), dpl_deploy.clone()]));
+
+        f.sync().await.unwrap();
+
+        let events = config_changed_events(&f).await;
+        assert_eq!(events.len(), 1);
+        let changes = events[0].data["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["kind"], "updated");
+        assert_eq!(changes[0]["filepath"], f.fixture_path("app.json"));
+        assert_eq!(changes[0]["config_instance_id"], "cfg_inst_2");
+        assert_eq!(changes[0]["deployment_id"], "dpl_2");
+    }
+}
+
+mod concurrent_writes {
+    use super::*;
+
+    async fn seed_steady_state(f: &Fixture) {
+        let seeded = models::Deployment {
+            id: "dpl_1".to_string(),
+            activity_status: DplActivity::Deployed,
+            error_status: DplErrStatus::None,
+            target_status: DplTarget::Deployed,
+            config_instance_ids: vec!["cfg_inst_1".to_string()],
+            ..Default::default()
+        };
+        f.deployment_stor
+            .write("dpl_1".to_string(), seeded, |_, _| false, Overwrite::Allow)
+            .await
+            .unwrap();
+        f.cfg_inst_content_stor
+            .write(
+                "cfg_inst_1".to_string(),
+                "{}".to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        let backend_dep = make_deployment("dpl_1", cfg_inst_args(f, &["cfg_inst_1"]));
+        f.http_client
+            .set_list_all_deployments(move || Ok(vec![backend_dep.clone()]));
+    }
+
+    #[tokio::test]
+    async fn local_writes_are_not_lost() {
+        let f = Fixture::new("concurrent_local_writes").await;
+        seed_steady_state(&f).await;
+
+        let syncs = async {
+            for _ in 0..20 {
+                f.sync().await.unwrap();
+            }
+        };
+        let local_writes = async {
+            for i in 0..50 {
+                let _guard = f
+                    .locks
+                    .lock(
+                        Writer::LocalApi,
+                        [Resource::Deployment("dpl_1".to_string())],
+                    )
+                    .await;
+                let mut dpl = read_deployment(&f.deployment_stor, "dpl_1").await;
+                tokio::task::yield_now().await;
+                dpl.description = format!("local write {i}");
+                f.deployment_stor
+                    .write("dpl_1".to_string(), dpl, |_, _| false, Overwrite::Allow)
+                    .await
+                    .unwrap();
+            }
+        };
+        tokio::join!(syncs, local_writes);
+
+        let dpl = read_deployment(&f.deployment_stor, "dpl_1").await;
+        assert_eq!(dpl.description, "local write 49");
+        assert_eq!(dpl.activity_status, DplActivity::Deployed);
+        assert!(f.locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_waits_for_local_writes() {
+        let f = Fixture::new("concurrent_sync_waits").await;
+        seed_steady_state(&f).await;
+
+        let guard = f
+            .locks
+            .try_lock(
+                Writer::LocalApi,
+                [Resource::Deployment("dpl_1".to_string())],
+            )
+            .unwrap();
+        let sync = f.sync();
+        tokio::pin!(sync);
+        let timeout = std::time::Duration::from_millis(100);
+        assert!(tokio::time::timeout(timeout, &mut sync).await.is_err());
+
+        drop(guard);
+        sync.await.unwrap();
+    }
 }
@@ -0,0 +1,126 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::sync::event_log::{self, Log, Options};
+use miru_agent::sync::syncer::{CooldownEnd, SyncEvent, SyncFailure};
+
+fn failed() -> SyncEvent {
+    SyncEvent::SyncFailed(SyncFailure {
+        is_network_conn_err: true,
+    })
+}
+
+async fn events(log: &Log) -> Vec<SyncEvent> {
+    log.records().await.into_iter().map(|r| r.event).collect()
+}
+
+#[tokio::test]
+async fn defaults_to_the_last_100_events() {
+    let log = Log::default();
+    for _ in 0..150 {
+        log.push(SyncEvent::SyncSuccess).await;
+    }
+    assert_eq!(log.records().await.len(), 100);
+}
+
+#[tokio::test]
+async fn drops_the_oldest_events() {
+    let log = Log::new(Options {
+        capacity: 2,
+        file: None,
+    });
+    log.push(SyncEvent::SyncSuccess).await;
+    log.push(failed()).await;
+    log.push(SyncEvent::CooldownEnd(CooldownEnd::SyncFailure))
+        .await;
+
+    assert_eq!(
+        events(&log).await,
+        vec![failed(), SyncEvent::CooldownEnd(CooldownEnd::SyncFailure)]
+    );
+    let records = log.records().await;
+    assert!(records[0].at <= records[1].at);
+}
+
+#[tokio::test]
+async fn clones_share_the_events() {
+    let log = Log::default();
+    log.clone().push(failed()).await;
+    assert_eq!(events(&log).await, vec![failed()]);
+}
+
+#[tokio::test]
+async fn persists_and_loads() {
+    let dir = filesys::Dir::create_temp_dir("sync_events_persist")
+        .await
+        .unwrap();
+    let file = dir.file("sync_events.json");
+    let options = Options {
+        capacity: 3,
+        file: Some(file.clone()),
+    };
+
+    let log = Log::load(options.clone()).await;
+    assert!(log.records().await.is_empty());
+    log.push(failed()).await;
+    log.push(SyncEvent::SyncSuccess).await;
+    assert!(file.exists());
+
+    let loaded = Log::load(options).await;
+    assert_eq!(loaded.records().await, log.records().await);
+    assert_eq!(event_log::read(&file).await, log.records().await);
+}
+
+#[tokio::test]
+async fn loads_at_most_the_capacity() {
+    let dir = filesys::Dir::create_temp_dir("sync_events_capacity")
+        .await
+        .unwrap();
+    let file = dir.file("sync_events.json");
+    let log = Log::new(Options {
+        capacity: 5,
+        file: Some(file.clone()),
+    });
+    for _ in 0..4 {
+        log.push(SyncEvent::SyncSuccess).await;
+    }
+    log.push(failed()).await;
+
+    let loaded = Log::load(Options {
+        capacity: 2,
+        file: Some(file),
+    })
+    .await;
+    assert_eq!(
+        events(&loaded).await,
+        vec![SyncEvent::SyncSuccess, failed()]
+    );
+}
+
+#[tokio::test]
+async fn discards_an_unreadable_file() {
+    let dir = filesys::Dir::create_temp_dir("sync_events_unreadable")
+        .await
+        .unwrap();
+    let file = dir.file("sync_events.json");
+    file.write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+        .await
+        .unwrap();
+
+    let log = Log::load(Options {
+        capacity: 2,
+        file: Some(file),
+    })
+    .await;
+    assert!(log.records().await.is_empty());
+}
+
+#[tokio::test]
+async fn displays_the_event() {
+    let log = Log::default();
+    log.push(failed()).await;
+    let line = log.records().await[0].to_string();
+    assert!(
+        line.ends_with("sync failed (network connection error)"),
+        "{line}"
+    );
+}
@@ -0,0 +1,285 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::sync::history::{
+    self, History, Options, Outcome, Percentiles, Phases, Query, Record,
+};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+fn record(duration_ms: u64, outcome: Outcome) -> Record {
+    Record {
+        started_at: DateTime::<Utc>::UNIX_EPOCH + TimeDelta::milliseconds(duration_ms as i64),
+        duration_ms,
+        outcome,
+        phases: Phases {
+            fetch_ms: duration_ms / 2,
+            download_ms: duration_ms / 4,
+            materialize_ms: duration_ms / 8,
+            reconcile_ms: 1,
+        },
+        error: None,
+    }
+}
+
+pub mod percentiles {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(Percentiles::of(Vec::new()), None);
+    }
+
+    #[test]
+    fn single_value() {
+        assert_eq!(
+            Percentiles::of(vec![7]),
+            Some(Percentiles {
+                p50_ms: 7,
+                p95_ms: 7
+            })
+        );
+    }
+
+    #[test]
+    fn nearest_rank() {
+        let values = (1..=100).rev().collect();
+        assert_eq!(
+            Percentiles::of(values),
+            Some(Percentiles {
+                p50_ms: 50,
+                p95_ms: 95
+            })
+        );
+
+        assert_eq!(
+            Percentiles::of(vec![30, 10, 20]),
+            Some(Percentiles {
+                p50_ms: 20,
+                p95_ms: 30
+            })
+        );
+    }
+}
+
+pub mod summarize {
+    use super::*;
+
+    #[test]
+    fn no_syncs() {
+        let summary = history::summarize(&[]);
+        assert_eq!(summary.syncs, 0);
+        assert_eq!(summary.successes, 0);
+        assert_eq!(summary.success_streak, 0);
+        assert_eq!(summary.last_outcome, None);
+        assert_eq!(summary.duration, None);
+        assert_eq!(summary.phases, None);
+    }
+
+    #[test]
+    fn counts_outcomes_and_streaks() {
+        let records = [
+            record(100, Outcome::Success),
+            record(200, Outcome::Error),
+            record(300, Outcome::NetworkError),
+            record(400, Outcome::Success),
+            record(800, Outcome::Success),
+        ];
+        let summary = history::summarize(&records);
+        assert_eq!(summary.syncs, 5);
+        assert_eq!(summary.successes, 3);
+        assert_eq!(summary.success_streak, 2);
+        assert_eq!(summary.last_outcome, Some(Outcome::Success));
+        assert_eq!(
+            summary.duration,
+            Some(Percentiles {
+                p50_ms: 300,
+                p95_ms: 800
+            })
+        );
+
+        let phases = summary.phases.unwrap();
+        assert_eq!(phases.fetch.p50_ms, 150);
+        assert_eq!(phases.download.p95_ms, 200);
+        assert_eq!(phases.materialize.p50_ms, 37);
+        assert_eq!(phases.reconcile.p95_ms, 1);
+    }
+
+    #[test]
+    fn streak_is_broken_by_the_last_failure() {
+        let records = [record(100, Outcome::Success), record(100, Outcome::Error)];
+        let summary = history::summarize(&records);
+        assert_eq!(summary.success_streak, 0);
+        assert_eq!(summary.last_outcome, Some(Outcome::Error));
+    }
+}
+
+pub mod query {
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        vec![
+            record(1, Outcome::Success),
+            record(2, Outcome::Error),
+            record(3, Outcome::NetworkError),
+            record(4, Outcome::Error),
+            record(5, Outcome::Success),
+        ]
+    }
+
+    fn durations(records: Vec<Record>) -> Vec<u64> {
+        records.iter().map(|r| r.duration_ms).collect()
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(Query::default().apply(&records()), records());
+    }
+
+    #[test]
+    fn filters_by_outcome() {
+        let query = Query {
+            outcome: Some(Outcome::Error),
+            ..Default::default()
+        };
+        assert_eq!(durations(query.apply(&records())), vec![2, 4]);
+    }
+
+    #[test]
+    fn filters_by_start_time() {
+        let query = Query {
+            since: Some(DateTime::<Utc>::UNIX_EPOCH + TimeDelta::milliseconds(3)),
+            ..Default::default()
+        };
+        assert_eq!(durations(query.apply(&records())), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn limit_keeps_the_most_recent() {
+        let query = Query {
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(durations(query.apply(&records())), vec![4, 5]);
+
+        let query = Query {
+            outcome: Some(Outcome::Success),
+            limit: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(durations(query.apply(&records())), vec![1, 5]);
+
+        let query = Query {
+            limit: Some(0),
+            ..Default::default()
+        };
+        assert!(query.apply(&records()).is_empty());
+    }
+}
+
+pub mod error_summary {
+    use super::*;
+
+    #[test]
+    fn keeps_short_messages() {
+        assert_eq!(
+            history::error_summary(&"backend unreachable"),
+            "backend unreachable"
+        );
+    }
+
+    #[test]
+    fn cuts_long_messages() {
+        let msg = "é".repeat(history::MAX_ERROR_LEN + 10);
+        let summary = history::error_summary(&msg);
+        assert_eq!(summary.chars().count(), history::MAX_ERROR_LEN + 3);
+        assert!(summary.ends_with("..."));
+    }
+
+    #[test]
+    fn omitted_from_successful_records() {
+        let json = serde_json::to_value(record(1, Outcome::Success)).unwrap();
+        assert!(json.get("error").is_none());
+
+        // records persisted before errors were recorded still load
+        let mut json = serde_json::to_value(record(1, Outcome::Error)).unwrap();
+        json.as_object_mut().unwrap().remove("error");
+        let loaded: Record = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.error, None);
+    }
+}
+
+pub mod history_ring {
+    use super::*;
+
+    #[tokio::test]
+    async fn drops_the_oldest_syncs() {
+        let mut history = History::new(Options {
+            capacity: 2,
+            file: None,
+        });
+        for duration_ms in [1, 2, 3] {
+            history.push(record(duration_ms, Outcome::Success)).await;
+        }
+        let durations: Vec<u64> = history.records().iter().map(|r| r.duration_ms).collect();
+        assert_eq!(durations, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn persists_and_loads() {
+        let dir = filesys::Dir::create_temp_dir("sync_history_persist")
+            .await
+            .unwrap();
+        let file = dir.file("sync_history.json");
+        let options = Options {
+            capacity: 3,
+            file: Some(file.clone()),
+        };
+
+        let mut history = History::load(options.clone()).await;
+        assert!(history.records().is_empty());
+        history.push(record(1, Outcome::Success)).await;
+        history.push(record(2, Outcome::Error)).await;
+        assert!(file.exists());
+
+        let loaded = History::load(options).await;
+        assert_eq!(loaded.records(), history.records());
+    }
+
+    #[tokio::test]
+    async fn loads_at_most_the_capacity() {
+        let dir = filesys::Dir::create_temp_dir("sync_history_capacity")
+            .await
+            .unwrap();
+        let file = dir.file("sync_history.json");
+        let records: Vec<Record> = (1..=5).map(|i| record(i, Outcome::Success)).collect();
+        file.write_json(&records, WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let history = History::load(Options {
+            capacity: 2,
+            file: Some(file),
+        })
+        .await;
+        assert_eq!(history.records(), records[3..].to_vec());
+    }
+
+    #[tokio::test]
+    async fn discards_an_unreadable_file() {
+        let dir = filesys::Dir::create_temp_dir("sync_history_unreadable")
+            .await
+            .unwrap();
+        let file = dir.file("sync_history.json");
+        file.write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let history = History::load(Options {
+            capacity: 2,
+            file: Some(file),
+        })
+        .await;
+        assert!(history.records().is_empty());
+    }
+}
@@ -1,4 +1,13 @@
+pub mod backoff;
+pub mod changes;
 pub mod deployments;
 pub mod errors;
+pub mod event_log;
 pub mod helpers;
+pub mod history;
+pub mod network;
+pub mod patch;
+pub mod plan;
+pub mod state;
 pub mod syncer;
+pub mod warming;
@@ -0,0 +1,126 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::http_client::{Call, MockClient};
+use miru_agent::filesys;
+use miru_agent::http::errors::{HTTPErr, MockErr};
+use miru_agent::models::Device;
+use miru_agent::storage::{self, Layout};
+use miru_agent::sync::network::{Options, Reporter};
+use miru_agent::telemetry::Network;
+
+// external crates
+use chrono::TimeDelta;
+
+struct Fixture {
+    dir: filesys::Dir,
+    http_client: MockClient,
+    device_stor: storage::Device,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let device = Device {
+            id: "dvc_1".to_string(),
+            ..Device::default()
+        };
+        let (device_stor, _) =
+            storage::Device::spawn_with_default(64, Layout::new(dir.clone()).device(), device)
+                .await
+                .unwrap();
+        Self {
+            dir,
+            http_client: MockClient::default(),
+            device_stor,
+        }
+    }
+
+    async fn report(&self, reporter: &mut Reporter) {
+        reporter
+            .report(&self.http_client, &self.device_stor, "token")
+            .await;
+    }
+
+    fn reported(&self) -> Vec<Network> {
+        self.http_client
+            .requests()
+            .iter()
+            .filter(|r| r.call == Call::UpdateDevice)
+            .map(|r| {
+                let body: serde_json::Value =
+                    serde_json::from_str(r.body.as_deref().unwrap()).unwrap();
+                serde_json::from_value(body["network"].clone()).unwrap()
+            })
+            .collect()
+    }
+}
+
+fn enabled() -> Options {
+    Options {
+        enabled: true,
+        // the mock backend's host doesn't resolve
+        probe_timeout: Duration::from_millis(200),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn disabled_by_default() {
+    let f = Fixture::new("network_report_disabled").await;
+    let mut reporter = Reporter::default();
+    f.report(&mut reporter).await;
+    assert!(f.http_client.requests().is_empty());
+    f.dir.delete().await.unwrap();
+}
+
+#[tokio::test]
+async fn reports_the_network_to_the_device() {
+    let f = Fixture::new("network_report").await;
+    let mut reporter = Reporter::new(enabled());
+    f.report(&mut reporter).await;
+
+    let requests = f.http_client.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, reqwest::Method::PATCH);
+    assert_eq!(requests[0].path, "/devices/dvc_1");
+    assert_eq!(requests[0].token.as_deref(), Some("token"));
+    let reported = f.reported();
+    let backend = reported[0].connectivity.backend.as_ref().unwrap();
+    assert_eq!(backend.host, "mock:80");
+    f.dir.delete().await.unwrap();
+}
+
+#[tokio::test]
+async fn unchanged_networks_wait_for_the_max_age() {
+    let f = Fixture::new("network_report_unchanged").await;
+    let mut reporter = Reporter::new(enabled());
+    f.report(&mut reporter).await;
+    f.report(&mut reporter).await;
+    assert_eq!(f.reported().len(), 1);
+
+    let mut reporter = Reporter::new(Options {
+        max_age: TimeDelta::zero(),
+        ..enabled()
+    });
+    f.report(&mut reporter).await;
+    f.report(&mut reporter).await;
+    assert_eq!(f.reported().len(), 3);
+    f.dir.delete().await.unwrap();
+}
+
+#[tokio::test]
+async fn failed_reports_are_retried() {
+    let f = Fixture::new("network_report_failed").await;
+    f.http_client.set_update_device(|| {
+        Err(HTTPErr::MockErr(MockErr {
+            is_network_conn_err: true,
+        }))
+    });
+    let mut reporter = Reporter::new(enabled());
+    f.report(&mut reporter).await;
+    f.report(&mut reporter).await;
+    assert_eq!(f.http_client.call_count(Call::UpdateDevice), 2);
+    f.dir.delete().await.unwrap();
+}
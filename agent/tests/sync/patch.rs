@@ -0,0 +1,230 @@
+// internal crates
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::http::config_instances::{ContentPatch, PatchFormat};
+use miru_agent::sync::{patch, SyncErr};
+
+// external crates
+use serde_json::{json, Value};
+
+fn content_patch(format: PatchFormat, patch: Value, expected: &Value) -> ContentPatch {
+    ContentPatch {
+        base_id: "base".to_string(),
+        format,
+        patch,
+        digest: patch::digest(&expected.to_string()),
+    }
+}
+
+fn apply(base: Value, format: PatchFormat, patch: Value, expected: Value) {
+    let content_patch = content_patch(format, patch, &expected);
+    let content = patch::apply(&base.to_string(), &content_patch).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&content).unwrap(), expected);
+}
+
+fn apply_err(base: Value, format: PatchFormat, patch: Value) -> SyncErr {
+    let content_patch = content_patch(format, patch, &json!({}));
+    patch::apply(&base.to_string(), &content_patch).unwrap_err()
+}
+
+pub mod digest {
+    use super::*;
+
+    #[test]
+    fn sha256_hex() {
+        assert_eq!(
+            patch::digest("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
+
+pub mod canonical {
+    use super::*;
+
+    #[test]
+    fn compact_with_sorted_keys() {
+        let doc: Value =
+            serde_json::from_str(r#"{ "b": [ {"d": 1, "c": 2} ], "a": "x" }"#).unwrap();
+        assert_eq!(patch::canonical(&doc), r#"{"a":"x","b":[{"c":2,"d":1}]}"#);
+    }
+
+    #[test]
+    fn is_canonical() {
+        assert!(patch::is_canonical(r#"{"a":1,"b":[1,2]}"#));
+        assert!(!patch::is_canonical(r#"{"b":1,"a":2}"#));
+        assert!(!patch::is_canonical(r#"{"a": 1}"#));
+        assert!(!patch::is_canonical("speed: 4"));
+    }
+
+    #[test]
+    fn patched_content_is_canonical() {
+        let expected = r#"{"a":1,"b":2}"#;
+        let content_patch = ContentPatch {
+            base_id: "base".to_string(),
+            format: PatchFormat::MergePatch,
+            patch: json!({"b": 2}),
+            digest: patch::digest(expected),
+        };
+        let content = patch::apply(r#"{ "a": 1 }"#, &content_patch).unwrap();
+        assert_eq!(content, expected);
+    }
+}
+
+pub mod digest_algorithms {
+    use super::*;
+
+    fn patch_with_digest(digest: String) -> ContentPatch {
+        ContentPatch {
+            base_id: "base".to_string(),
+            format: PatchFormat::MergePatch,
+            patch: json!({"b": 2}),
+            digest,
+        }
+    }
+
+    #[test]
+    fn verifies_with_the_named_algorithm() {
+        let expected = json!({"a": 1, "b": 2}).to_string();
+        for algorithm in Algorithm::ALL {
+            let digest = Digest::compute(algorithm, expected.as_bytes()).to_string();
+            let content = patch::apply(r#"{"a":1}"#, &patch_with_digest(digest)).unwrap();
+            assert_eq!(content, expected);
+        }
+    }
+
+    #[test]
+    fn mismatched_algorithm() {
+        let expected = json!({"a": 1, "b": 2}).to_string();
+        let sha256 = Digest::compute(Algorithm::Sha256, expected.as_bytes());
+        let digest = format!("blake3:{}", sha256.hex());
+        let err = patch::apply(r#"{"a":1}"#, &patch_with_digest(digest)).unwrap_err();
+        assert!(
+            matches!(err, SyncErr::ContentDigestMismatchErr(_)),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn invalid_digest() {
+        let err = patch::apply(r#"{"a":1}"#, &patch_with_digest("nope".to_string())).unwrap_err();
+        assert!(matches!(err, SyncErr::ContentPatchErr(_)), "{err:?}");
+    }
+}
+
+pub mod merge_patch {
+    use super::*;
+
+    #[test]
+    fn adds_replaces_and_removes_members() {
+        apply(
+            json!({"a": 1, "b": {"c": 2, "d": 3}, "e": [1, 2]}),
+            PatchFormat::MergePatch,
+            json!({"a": 5, "b": {"d": null, "f": 4}, "e": [3]}),
+            json!({"a": 5, "b": {"c": 2, "f": 4}, "e": [3]}),
+        );
+    }
+
+    #[test]
+    fn non_object_patch_replaces_document() {
+        apply(
+            json!({"a": 1}),
+            PatchFormat::MergePatch,
+            json!([1, 2]),
+            json!([1, 2]),
+        );
+    }
+}
+
+pub mod json_patch {
+    use super::*;
+
+    #[test]
+    fn applies_operations_in_order() {
+        apply(
+            json!({"a": 1, "b": [1, 2], "c": {"d": "x"}}),
+            PatchFormat::JsonPatch,
+            json!([
+                {"op": "test", "path": "/a", "value": 1},
+                {"op": "replace", "path": "/a", "value": 2},
+                {"op": "add", "path": "/b/1", "value": 5},
+                {"op": "add", "path": "/b/-", "value": 9},
+                {"op": "remove", "path": "/b/0"},
+                {"op": "copy", "from": "/c/d", "path": "/e"},
+                {"op": "move", "from": "/c", "path": "/f"},
+            ]),
+            json!({"a": 2, "b": [5, 2, 9], "e": "x", "f": {"d": "x"}}),
+        );
+    }
+
+    #[test]
+    fn escaped_pointers() {
+        apply(
+            json!({"a/b": 1, "m~n": 2}),
+            PatchFormat::JsonPatch,
+            json!([
+                {"op": "replace", "path": "/a~1b", "value": 3},
+                {"op": "remove", "path": "/m~0n"},
+            ]),
+            json!({"a/b": 3}),
+        );
+    }
+
+    #[test]
+    fn failed_test_aborts() {
+        let err = apply_err(
+            json!({"a": 1}),
+            PatchFormat::JsonPatch,
+            json!([{"op": "test", "path": "/a", "value": 2}]),
+        );
+        assert!(matches!(err, SyncErr::ContentPatchErr(_)));
+    }
+
+    #[test]
+    fn missing_path_member() {
+        let err = apply_err(
+            json!({"a": 1}),
+            PatchFormat::JsonPatch,
+            json!([{"op": "replace", "path": "/b", "value": 2}]),
+        );
+        assert!(matches!(err, SyncErr::ContentPatchErr(_)));
+    }
+
+    #[test]
+    fn unknown_operation() {
+        let err = apply_err(
+            json!({"a": 1}),
+            PatchFormat::JsonPatch,
+            json!([{"op": "upsert", "path": "/a", "value": 2}]),
+        );
+        assert!(matches!(err, SyncErr::ContentPatchErr(_)));
+    }
+
+    #[test]
+    fn not_an_array() {
+        let err = apply_err(json!({"a": 1}), PatchFormat::JsonPatch, json!({"a": 2}));
+        assert!(matches!(err, SyncErr::ContentPatchErr(_)));
+    }
+}
+
+pub mod verification {
+    use super::*;
+
+    #[test]
+    fn digest_mismatch() {
+        let content_patch = ContentPatch {
+            base_id: "base".to_string(),
+            format: PatchFormat::MergePatch,
+            patch: json!({"a": 2}),
+            digest: patch::digest(r#"{"a":3}"#),
+        };
+        let err = patch::apply(r#"{"a": 1}"#, &content_patch).unwrap_err();
+        assert!(matches!(err, SyncErr::ContentDigestMismatchErr(_)));
+    }
+
+    #[test]
+    fn base_not_json() {
+        let content_patch = content_patch(PatchFormat::MergePatch, json!({"a": 2}), &json!({}));
+        let err = patch::apply("key: value", &content_patch).unwrap_err();
+        assert!(matches!(err, SyncErr::ContentPatchErr(_)));
+    }
+}
@@ -0,0 +1,313 @@
+// internal crates
+use miru_agent::deploy::{apply, pause};
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::http::errors::*;
+use miru_agent::models::{self, DplActivity, DplErrStatus, DplTarget};
+use miru_agent::storage::Deployments;
+use miru_agent::sync::plan::{plan, Action, Plan, Step};
+use miru_agent::sync::SyncErr;
+
+use crate::mocks::http_client::{Call, MockClient};
+use crate::sync::helpers::*;
+use backend_api::models::Deployment as BackendDeployment;
+
+// external crates
+use chrono::{TimeDelta, Utc};
+
+// ========================= FIXTURE ========================= //
+
+struct Fixture {
+    deployment_stor: Deployments,
+    http_client: MockClient,
+    _dir: filesys::Dir,
+}
+
+impl Fixture {
+    async fn new(name: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+        let (deployment_stor, _) = Deployments::spawn(16, dir.file("deployments.json"), 1000)
+            .await
+            .unwrap();
+        Self {
+            deployment_stor,
+            http_client: MockClient::default(),
+            _dir: dir,
+        }
+    }
+
+    fn list(&self, deployments: Vec<BackendDeployment>) {
+        self.http_client
+            .set_list_all_deployments(move || Ok(deployments.clone()));
+    }
+
+    async fn cache(&self, deployment: models::Deployment) {
+        self.deployment_stor
+            .write(
+                deployment.id.clone(),
+                deployment,
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn plan(&self) -> Result<Plan, SyncErr> {
+        self.plan_with(&apply::DeployOpts::default()).await
+    }
+
+    async fn plan_with(&self, opts: &apply::DeployOpts) -> Result<Plan, SyncErr> {
+        plan(&self.http_client, &self.deployment_stor, opts, "test_token").await
+    }
+}
+
+fn cfg_inst(id: &str) -> Vec<CfgInstArgs> {
+    vec![CfgInstArgs {
+        id: id.to_string(),
+        filepath: format!("/srv/miru/{id}.json"),
+    }]
+}
+
+fn deployed(id: &str) -> models::Deployment {
+    models::Deployment {
+        id: id.to_string(),
+        activity_status: DplActivity::Deployed,
+        error_status: DplErrStatus::None,
+        target_status: DplTarget::Deployed,
+        config_instance_ids: vec![format!("{id}_cfg_inst")],
+        ..Default::default()
+    }
+}
+
+// ========================= TESTS ========================= //
+
+#[tokio::test]
+async fn empty() {
+    let f = Fixture::new("plan_empty").await;
+    let plan = f.plan().await.unwrap();
+    assert!(plan.steps.is_empty());
+    assert!(plan.conflicts.is_empty());
+    assert!(!plan.safe_mode);
+    assert!(!plan.paused);
+}
+
+#[tokio::test]
+async fn new_deployment_is_deployed_without_being_cached() {
+    let f = Fixture::new("plan_new_deployment").await;
+    f.list(vec![make_deployment("dpl_1", cfg_inst("cfg_inst_1"))]);
+
+    let plan = f.plan().await.unwrap();
+
+    assert_eq!(
+        plan.steps,
+        vec![Step {
+            deployment_id: "dpl_1".to_string(),
+            target_status: DplTarget::Deployed,
+            activity_status: DplActivity::Queued,
+            error_status: DplErrStatus::None,
+            action: Action::Deploy,
+            wait_secs: None,
+            new: true,
+            listed: true,
+        }]
+    );
+    assert_deployment_not_stored(&f.deployment_stor, "dpl_1").await;
+    assert_eq!(f.http_client.call_count(Call::ListDeployments), 1);
+    assert_eq!(f.http_client.call_count(Call::GetConfigInstanceContent), 0);
+    assert_eq!(f.http_client.call_count(Call::UpdateDeployment), 0);
+}
+
+#[tokio::test]
+async fn archived_target_removes_cached_deployment_without_writing() {
+    let f = Fixture::new("plan_archived_target").await;
+    let cached = deployed("dpl_1");
+    f.cache(cached.clone()).await;
+    f.list(vec![make_archived_dpl("dpl_1", cfg_inst("cfg_inst_1"))]);
+
+    let plan = f.plan().await.unwrap();
+
+    assert_eq!(plan.steps.len(), 1);
+    let step = &plan.steps[0];
+    assert_eq!(step.action, Action::Remove);
+    assert_eq!(step.target_status, DplTarget::Archived);
+    assert_eq!(step.activity_status, DplActivity::Deployed);
+    assert!(!step.new);
+    assert!(step.listed);
+
+    // the cached deployment still targets deployed
+    assert_eq!(read_deployment(&f.deployment_stor, "dpl_1").await, cached);
+}
+
+#[tokio::test]
+async fn unlisted_cached_deployment_uses_its_cached_state() {
+    let f = Fixture::new("plan_unlisted_cached").await;
+    f.cache(models::Deployment {
+        target_status: DplTarget::Archived,
+        ..deployed("dpl_1")
+    })
+    .await;
+    f.list(vec![]);
+
+    let plan = f.plan().await.unwrap();
+
+    assert_eq!(plan.steps.len(), 1);
+    assert_eq!(plan.steps[0].action, Action::Remove);
+    assert!(!plan.steps[0].listed);
+    assert!(!plan.steps[0].new);
+}
+
+#[tokio::test]
+async fn deployment_in_cooldown_waits() {
+    let f = Fixture::new("plan_cooldown").await;
+    f.cache(models::Deployment {
+        activity_status: DplActivity::Queued,
+        error_status: DplErrStatus::Retrying,
+        cooldown_ends_at: Utc::now() + TimeDelta::seconds(120),
+        ..deployed("dpl_1")
+    })
+    .await;
+    f.list(vec![make_deployment("dpl_1", cfg_inst("cfg_inst_1"))]);
+
+    let plan = f.plan().await.unwrap();
+
+    let step = &plan.steps[0];
+    assert_eq!(step.action, Action::Wait);
+    let wait_secs = step.wait_secs.unwrap();
+    assert!((110..=120).contains(&wait_secs), "wait_secs: {wait_secs}");
+}
+
+#[tokio::test]
+async fn already_deployed_does_nothing() {
+    let f = Fixture::new("plan_already_deployed").await;
+    f.cache(deployed("dpl_1")).await;
+    f.list(vec![make_deployment("dpl_1", cfg_inst("cfg_inst_1"))]);
+
+    let plan = f.plan().await.unwrap();
+
+    assert_eq!(plan.steps[0].action, Action::None);
+    assert_eq!(plan.steps[0].wait_secs, None);
+}
+
+#[tokio::test]
+async fn steps_are_ordered_by_deployment_id() {
+    let f = Fixture::new("plan_ordered").await;
+    f.cache(models::Deployment {
+        target_status: DplTarget::Archived,
+        ..deployed("dpl_2")
+    })
+    .await;
+    f.list(vec![
+        make_deployment("dpl_3", cfg_inst("cfg_inst_3")),
+        make_archived_dpl("dpl_1", cfg_inst("cfg_inst_1")),
+    ]);
+
+    let plan = f.plan().await.unwrap();
+
+    let ids: Vec<_> = plan
+        .steps
+        .iter()
+        .map(|s| s.deployment_id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["dpl_1", "dpl_2", "dpl_3"]);
+}
+
+#[tokio::test]
+async fn reports_conflicting_targets() {
+    let f = Fixture::new("plan_conflicts").await;
+    f.cache(deployed("dpl_1")).await;
+    f.list(vec![make_deployment("dpl_2", cfg_inst("cfg_inst_2"))]);
+
+    let plan = f.plan().await.unwrap();
+
+    assert_eq!(
+        plan.conflicts,
+        vec!["dpl_1".to_string(), "dpl_2".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn failed_deployments_do_not_conflict() {
+    let f = Fixture::new("plan_failed_no_conflict").await;
+    f.cache(models::Deployment {
+        error_status: DplErrStatus::Failed,
+        ..deployed("dpl_1")
+    })
+    .await;
+    f.list(vec![make_deployment("dpl_2", cfg_inst("cfg_inst_2"))]);
+
+    let plan = f.plan().await.unwrap();
+
+    assert!(plan.conflicts.is_empty());
+}
+
+#[tokio::test]
+async fn reports_safe_mode() {
+    let f = Fixture::new("plan_safe_mode").await;
+    let opts = apply::DeployOpts {
+        safe_mode: true,
+        ..Default::default()
+    };
+    assert!(f.plan_with(&opts).await.unwrap().safe_mode);
+}
+
+#[tokio::test]
+async fn paused_deployments_wait() {
+    let f = Fixture::new("plan_paused").await;
+    f.list(vec![make_deployment("dpl_1", cfg_inst("cfg_inst_1"))]);
+    f.cache(models::Deployment {
+        target_status: DplTarget::Archived,
+        ..deployed("dpl_2")
+    })
+    .await;
+    let switch = pause::Switch::new(f._dir.file("deployments_paused.json"));
+    switch.pause(None, pause::Origin::Cli).await.unwrap();
+    let opts = apply::DeployOpts {
+        pause: Some(switch),
+        ..Default::default()
+    };
+
+    let plan = f.plan_with(&opts).await.unwrap();
+
+    assert!(plan.paused);
+    let actions: Vec<_> = plan
+        .steps
+        .iter()
+        .map(|step| (step.deployment_id.as_str(), step.action, step.wait_secs))
+        .collect();
+    // the new deployment would be deployed and the unlisted one removed
+    assert_eq!(
+        actions,
+        vec![
+            ("dpl_1", Action::Wait, Some(0)),
+            ("dpl_2", Action::Wait, Some(0)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn list_failure_errors() {
+    let f = Fixture::new("plan_list_failure").await;
+    f.http_client.set_list_all_deployments(|| {
+        Err(HTTPErr::MockErr(MockErr {
+            is_network_conn_err: false,
+        }))
+    });
+
+    let err = f.plan().await.unwrap_err();
+    assert!(matches!(err, SyncErr::HTTPClientErr(_)), "got: {err:?}");
+}
+
+#[tokio::test]
+async fn cfg_insts_not_expanded_errors() {
+    let f = Fixture::new("plan_cfg_insts_not_expanded").await;
+    f.list(vec![BackendDeployment {
+        config_instances: None,
+        ..make_deployment("dpl_1", cfg_inst("cfg_inst_1"))
+    }]);
+
+    let err = f.plan().await.unwrap_err();
+    assert!(
+        matches!(err, SyncErr::CfgInstsNotExpanded(_)),
+        "got: {err:?}"
+    );
+}
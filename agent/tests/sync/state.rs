@@ -0,0 +1,121 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::models::Patch;
+use miru_agent::sync::state::{self, Updates};
+use miru_agent::sync::syncer::State;
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::<Utc>::UNIX_EPOCH + TimeDelta::seconds(1_700_000_000 + secs)
+}
+
+fn failed(cooldown_ends_at: DateTime<Utc>) -> State {
+    State {
+        last_attempted_sync_at: at(-10),
+        last_synced_at: at(-3600),
+        cooldown_ends_at,
+        err_streak: 4,
+        syncs: 0,
+    }
+}
+
+pub mod restore {
+    use super::*;
+
+    #[test]
+    fn resumes_the_cooldown() {
+        let state = failed(at(300));
+        assert_eq!(state::restore(&state, at(0), TimeDelta::hours(12)), state);
+    }
+
+    #[test]
+    fn drops_ended_cooldowns() {
+        for cooldown_ends_at in [at(-300), at(0)] {
+            let restored = state::restore(&failed(cooldown_ends_at), at(0), TimeDelta::hours(12));
+            assert_eq!(
+                restored,
+                State {
+                    cooldown_ends_at: DateTime::<Utc>::UNIX_EPOCH,
+                    ..failed(cooldown_ends_at)
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn shortens_cooldowns_beyond_the_longest_backoff() {
+        let restored = state::restore(
+            &failed(at(0) + TimeDelta::days(400)),
+            at(0),
+            TimeDelta::hours(12),
+        );
+        assert_eq!(restored.cooldown_ends_at, at(0) + TimeDelta::hours(12));
+        assert_eq!(restored.err_streak, 4);
+    }
+}
+
+pub mod file {
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_file() {
+        let dir = filesys::Dir::create_temp_dir("sync_state_missing")
+            .await
+            .unwrap();
+        let file = state::open(dir.file("sync_state.json")).await.unwrap();
+        assert_eq!(*file.cached(), State::default());
+        // the default is persisted
+        assert!(file.file.exists());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreadable_file() {
+        let dir = filesys::Dir::create_temp_dir("sync_state_unreadable")
+            .await
+            .unwrap();
+        let path = dir.file("sync_state.json");
+        path.write_string("{not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let file = state::open(path).await.unwrap();
+        assert_eq!(*file.cached(), State::default());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = filesys::Dir::create_temp_dir("sync_state_round_trip")
+            .await
+            .unwrap();
+        let mut file = state::open(dir.file("sync_state.json")).await.unwrap();
+        file.write(failed(at(300))).await.unwrap();
+
+        let reopened = state::open(dir.file("sync_state.json")).await.unwrap();
+        assert_eq!(*reopened.cached(), failed(at(300)));
+        dir.delete().await.unwrap();
+    }
+}
+
+#[test]
+fn patch() {
+    let mut state = failed(at(300));
+    state.patch(Updates {
+        last_attempted_sync_at: None,
+        last_synced_at: Some(at(0)),
+        cooldown_ends_at: Some(at(1)),
+        err_streak: Some(0),
+    });
+    assert_eq!(
+        state,
+        State {
+            last_attempted_sync_at: at(-10),
+            last_synced_at: at(0),
+            cooldown_ends_at: at(1),
+            err_streak: 0,
+            syncs: 0,
+        }
+    );
+}
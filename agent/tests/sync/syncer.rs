@@ -5,23 +5,27 @@ use std::time::Duration;
 // internal crates
 use crate::mocks::http_client::{Call, MockClient};
 use crate::sync::helpers::*;
-use miru_agent::authn::token_mngr::TokenFile;
-use miru_agent::authn::{Token, TokenManager, TokenManagerExt};
+use miru_agent::authn::{TokenManager, TokenManagerExt};
 use miru_agent::cooldown;
-use miru_agent::deploy::{apply, fsm};
+use miru_agent::deploy::apply;
+use miru_agent::errors::classify;
 use miru_agent::errors::*;
 use miru_agent::events::hub::{EventHub, SpawnOptions};
-use miru_agent::filesys::{self, Overwrite, WriteOptions};
+use miru_agent::events::model::SYNC_FAILED;
+use miru_agent::filesys::{self, Overwrite};
 use miru_agent::http;
-use miru_agent::http::errors::{HTTPErr, MockErr};
-use miru_agent::models::{Device, DplActivity, DplErrStatus, DplTarget};
-use miru_agent::storage::{
-    self, CfgInstContent, CfgInstStor, CfgInsts, Deployments, GitCommits, Releases, Storage,
-};
+use miru_agent::http::errors::{HTTPErr, MockErr, RequestFailed, TransportErr};
+use miru_agent::models::{DplActivity, DplErrStatus, DplTarget};
+use miru_agent::storage::{self, Storage};
+use miru_agent::sync::backoff::Policies;
+use miru_agent::sync::event_log;
+use miru_agent::sync::history::{self, History, Outcome};
+use miru_agent::sync::state as sync_state;
 use miru_agent::sync::syncer::{
-    CooldownEnd, SingleThreadSyncer, State, SyncEvent, SyncFailure, SyncerArgs, Worker,
+    CooldownEnd, EventMask, SingleThreadSyncer, State, SyncEvent, SyncFailure, SyncerArgs, Worker,
 };
 use miru_agent::sync::{SyncErr, Syncer, SyncerExt};
+use miru_agent::testkit;
 
 // external crates
 use chrono::{DateTime, TimeDelta, Utc};
@@ -32,62 +36,20 @@ pub async fn create_token_manager(
     dir: &filesys::Dir,
     http_client: Arc<MockClient>,
 ) -> (TokenManager, JoinHandle<()>) {
-    let token_file = TokenFile::new_with_default(dir.file("token.json"), Token::default())
-        .await
-        .unwrap();
-    let private_key_file = dir.file("private_key.pem");
-    private_key_file
-        .write_string("private_key", WriteOptions::OVERWRITE_ATOMIC)
-        .await
-        .unwrap();
-    let public_key_file = dir.file("public_key.pem");
-    public_key_file
-        .write_string("public_key", WriteOptions::OVERWRITE_ATOMIC)
-        .await
-        .unwrap();
-
-    TokenManager::spawn(
-        32,
-        http_client.clone(),
-        token_file,
-        private_key_file,
-        public_key_file,
-    )
-    .unwrap()
+    testkit::spawn_token_manager(dir, http_client).await
 }
 
 pub async fn create_storage(dir: &filesys::Dir) -> Storage {
-    let (cfg_inst_stor, _) = CfgInsts::spawn(16, dir.file("cfg_inst_cache.json"), 1000)
-        .await
-        .unwrap();
-    let (cfg_inst_content_stor, _) =
-        CfgInstContent::spawn(16, dir.subdir("cfg_inst_content_cache"), 1000)
-            .await
-            .unwrap();
-    let (deployment_stor, _) = Deployments::spawn(16, dir.file("deployment_cache.json"), 1000)
-        .await
-        .unwrap();
-    let (device_stor, _) =
-        storage::Device::spawn_with_default(64, dir.file("device.json"), Device::default())
-            .await
-            .unwrap();
-    let (release_stor, _) = Releases::spawn(16, dir.file("releases_cache.json"), 1000)
-        .await
-        .unwrap();
-    let (git_commit_stor, _) = GitCommits::spawn(16, dir.file("git_commits_cache.json"), 1000)
-        .await
-        .unwrap();
-
-    Storage {
-        device: Arc::new(device_stor),
-        cfg_insts: CfgInstStor {
-            meta: Arc::new(cfg_inst_stor),
-            content: Arc::new(cfg_inst_content_stor),
-        },
-        deployments: Arc::new(deployment_stor),
-        releases: Arc::new(release_stor),
-        git_commits: Arc::new(git_commit_stor),
-    }
+    let capacities = storage::Capacities {
+        cfg_insts: 1000,
+        cfg_inst_content: 1000,
+        config_schemas: 100,
+        deployments: 1000,
+        releases: 1000,
+        git_commits: 1000,
+        telemetry_batches: 60,
+    };
+    testkit::spawn_storage(dir, capacities).await
 }
 
 pub fn spawn(
@@ -109,6 +71,7 @@ struct Fixture {
     syncer: Syncer,
     backoff: cooldown::Backoff,
     token_mngr: Arc<TokenManager>,
+    event_hub: EventHub,
 }
 
 impl Fixture {
@@ -143,11 +106,9 @@ impl Fixture {
                 storage: storage.clone(),
                 http_client: http_client.clone(),
                 token_mngr: token_mngr.clone(),
-                deploy_opts: apply::DeployOpts {
-                    retry_policy: fsm::RetryPolicy::default(),
-                },
+                deploy_opts: apply::DeployOpts::default(),
                 backoff,
-                event_hub,
+                event_hub: event_hub.clone(),
             },
         )
         .unwrap();
@@ -159,6 +120,7 @@ impl Fixture {
             syncer,
             backoff,
             token_mngr,
+            event_hub,
         }
     }
 
@@ -188,6 +150,7 @@ pub mod state {
             last_synced_at: DateTime::<Utc>::UNIX_EPOCH,
             cooldown_ends_at: DateTime::<Utc>::UNIX_EPOCH,
             err_streak: 0,
+            syncs: 0,
         };
         assert_eq!(state, expected);
     }
@@ -200,6 +163,7 @@ pub mod state {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now() + TimeDelta::seconds(10),
             err_streak: 0,
+            syncs: 0,
         };
         assert!(state.is_in_cooldown());
 
@@ -209,6 +173,7 @@ pub mod state {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now() - TimeDelta::seconds(10),
             err_streak: 0,
+            syncs: 0,
         };
         assert!(!state.is_in_cooldown());
     }
@@ -237,9 +202,7 @@ pub mod shutdown {
                 storage: storage.clone(),
                 http_client: http_client.clone(),
                 token_mngr: Arc::new(token_mngr),
-                deploy_opts: apply::DeployOpts {
-                    retry_policy: fsm::RetryPolicy::default(),
-                },
+                deploy_opts: apply::DeployOpts::default(),
                 backoff: cooldown::Backoff {
                     base_secs: 15,
                     growth_factor: 2,
@@ -413,6 +376,7 @@ pub mod sync_failure {
                 last_synced_at: DateTime::<Utc>::UNIX_EPOCH,
                 cooldown_ends_at: Utc::now() + TimeDelta::seconds(10),
                 err_streak: 0,
+                syncs: 0,
             })
             .await
             .unwrap();
@@ -492,6 +456,36 @@ pub mod sync_failure {
         }
     }
 
+    #[derive(Debug, thiserror::Error)]
+    #[error("radio link down")]
+    struct RadioLinkDown;
+
+    #[tokio::test]
+    async fn classified_transport_error() {
+        let f = Fixture::new("sync_classified_transport_error").await;
+        classify::register_type::<RadioLinkDown, _>(|_| true);
+
+        fn transport_err<T>() -> Result<T, HTTPErr> {
+            Err(HTTPErr::TransportErr(TransportErr {
+                request: http::request::Params::get("http://test").meta().unwrap(),
+                source: Box::new(RadioLinkDown),
+                trace: miru_agent::trace!(),
+            }))
+        }
+        f.http_client.set_list_all_deployments(transport_err);
+        f.http_client.set_update_deployment(transport_err);
+
+        for _ in 0..3 {
+            let error = f.syncer.sync().await.unwrap_err();
+            assert!(error.is_network_conn_err());
+            f.reset_cooldown().await;
+        }
+
+        // classified as network connection errors, so they don't build an err streak
+        let state = f.syncer.get_sync_state().await.unwrap();
+        assert_eq!(state.err_streak, 0);
+    }
+
     #[tokio::test]
     async fn non_network_error() {
         let f = Fixture::new("sync_non_network_error").await;
@@ -714,6 +708,405 @@ pub mod sync_failure {
     }
 }
 
+pub mod sync_history {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_outcomes() {
+        let f = Fixture::new("sync_history_records_outcomes").await;
+        assert!(f.syncer.get_sync_history().await.unwrap().is_empty());
+
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+        f.reset_cooldown().await;
+
+        f.http_client.set_list_all_deployments(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+        f.syncer.sync().await.unwrap_err();
+        f.reset_cooldown().await;
+
+        f.http_client.set_list_all_deployments(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+        f.syncer.sync().await.unwrap_err();
+
+        // syncs rejected by the cooldown never started
+        f.syncer.sync().await.unwrap_err();
+
+        let records = f.syncer.get_sync_history().await.unwrap();
+        let outcomes: Vec<Outcome> = records.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![Outcome::Success, Outcome::NetworkError, Outcome::Error]
+        );
+        assert_eq!(records[0].error, None);
+        for record in &records[1..] {
+            assert!(!record.error.as_ref().unwrap().is_empty());
+        }
+        let state = f.syncer.get_sync_state().await.unwrap();
+        assert_eq!(
+            records.last().unwrap().started_at,
+            state.last_attempted_sync_at
+        );
+    }
+
+    #[tokio::test]
+    async fn replaced_history() {
+        let f = Fixture::new("sync_history_replaced").await;
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+        f.reset_cooldown().await;
+
+        f.syncer
+            .set_sync_history(History::new(history::Options {
+                capacity: 1,
+                file: None,
+            }))
+            .await
+            .unwrap();
+        assert!(f.syncer.get_sync_history().await.unwrap().is_empty());
+
+        f.syncer.sync().await.unwrap();
+        f.reset_cooldown().await;
+        f.syncer.sync().await.unwrap();
+        assert_eq!(f.syncer.get_sync_history().await.unwrap().len(), 1);
+    }
+}
+
+pub mod persisted_state {
+    use super::*;
+
+    fn backoff() -> cooldown::Backoff {
+        cooldown::Backoff {
+            base_secs: 60,
+            growth_factor: 2,
+            max_secs: 3600,
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_the_cooldown_after_a_restart() {
+        let f = Fixture::new_with_backoff("persisted_state_resumes", backoff()).await;
+        let path = f._dir.file("sync_state.json");
+        f.syncer
+            .set_state_file(sync_state::open(path.clone()).await.unwrap())
+            .await
+            .unwrap();
+
+        f.http_client.set_list_all_deployments(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+        f.syncer.sync().await.unwrap_err();
+        let state = f.syncer.get_sync_state().await.unwrap();
+        assert_eq!(state.err_streak, 1);
+        assert_eq!(path.read_json::<State>().await.unwrap(), state);
+
+        let restarted = Fixture::new_with_backoff("persisted_state_restarted", backoff()).await;
+        restarted
+            .syncer
+            .set_state_file(sync_state::open(path).await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(restarted.syncer.get_sync_state().await.unwrap(), state);
+        assert!(restarted.syncer.is_in_cooldown().await.unwrap());
+        let err = restarted.syncer.sync().await.unwrap_err();
+        assert!(matches!(err, SyncErr::InCooldownErr(_)));
+    }
+
+    #[tokio::test]
+    async fn persists_successful_syncs() {
+        let f = Fixture::new("persisted_state_success").await;
+        let path = f._dir.file("sync_state.json");
+        f.syncer
+            .set_state_file(sync_state::open(path.clone()).await.unwrap())
+            .await
+            .unwrap();
+
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+        let state = f.syncer.get_sync_state().await.unwrap();
+        assert_eq!(state.syncs, 1);
+        let persisted = path.read_json::<State>().await.unwrap();
+        assert_eq!(persisted, State { syncs: 0, ..state });
+        assert_eq!(persisted.err_streak, 0);
+        assert!(persisted.last_synced_at > DateTime::<Utc>::UNIX_EPOCH);
+    }
+
+    #[tokio::test]
+    async fn syncs_before_a_restart_dont_count() {
+        let f = Fixture::new("persisted_state_syncs").await;
+        let path = f._dir.file("sync_state.json");
+        f.syncer
+            .set_state_file(sync_state::open(path.clone()).await.unwrap())
+            .await
+            .unwrap();
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+        let synced_at = f.syncer.get_sync_state().await.unwrap().last_synced_at;
+
+        let restarted = Fixture::new("persisted_state_syncs_restarted").await;
+        restarted
+            .syncer
+            .set_state_file(sync_state::open(path).await.unwrap())
+            .await
+            .unwrap();
+        let state = restarted.syncer.get_sync_state().await.unwrap();
+        assert_eq!(state.last_synced_at, synced_at);
+        assert_eq!(state.syncs, 0);
+    }
+}
+
+pub mod sync_events {
+    use super::*;
+
+    fn sync_results(records: Vec<event_log::Record>) -> Vec<SyncEvent> {
+        records
+            .into_iter()
+            .map(|r| r.event)
+            .filter(|e| !matches!(e, SyncEvent::CooldownEnd(_)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn records_sync_results() {
+        let f = Fixture::new("sync_events_records_results").await;
+        assert!(f.syncer.get_sync_events().await.unwrap().is_empty());
+
+        f.http_client.set_list_all_deployments(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+        f.syncer.sync().await.unwrap_err();
+        f.reset_cooldown().await;
+
+        // the failure stays visible after the syncer recovers
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+
+        let records = f.syncer.get_sync_events().await.unwrap();
+        assert!(records.windows(2).all(|w| w[0].at <= w[1].at));
+        assert_eq!(
+            sync_results(records),
+            vec![
+                SyncEvent::SyncFailed(SyncFailure {
+                    is_network_conn_err: true,
+                }),
+                SyncEvent::SyncSuccess,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replaced_log() {
+        let f = Fixture::new("sync_events_replaced").await;
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+        f.reset_cooldown().await;
+
+        let log = event_log::Log::new(event_log::Options {
+            capacity: 1,
+            file: None,
+        });
+        f.syncer.set_event_log(log.clone()).await.unwrap();
+        assert!(f.syncer.get_sync_events().await.unwrap().is_empty());
+
+        f.syncer.sync().await.unwrap();
+        assert_eq!(
+            sync_results(log.records().await),
+            vec![SyncEvent::SyncSuccess]
+        );
+    }
+}
+
+pub mod backoff_policies {
+    use super::*;
+
+    fn server_err<T>() -> Result<T, HTTPErr> {
+        Err(HTTPErr::RequestFailed(RequestFailed {
+            request: http::request::Params::get("http://test").meta().unwrap(),
+            status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            error: None,
+            trace: miru_agent::trace!(),
+        }))
+    }
+
+    fn network_err<T>() -> Result<T, HTTPErr> {
+        Err(HTTPErr::MockErr(MockErr {
+            is_network_conn_err: true,
+        }))
+    }
+
+    /// Syncs once, expecting a failure, and asserts the resulting cooldown.
+    async fn assert_fails_with_cooldown(f: &Fixture, cooldown_secs: i64, err_streak: u32) {
+        let before = Utc::now();
+        f.syncer.sync().await.unwrap_err();
+        let after = Utc::now();
+        let state = f.syncer.get_sync_state().await.unwrap();
+        StateAssert::new(before, after).assert_failed(
+            &state,
+            TimeDelta::seconds(cooldown_secs),
+            err_streak,
+        );
+        f.reset_cooldown().await;
+    }
+
+    #[tokio::test]
+    async fn auth_failures_back_off_by_the_auth_policy() {
+        let f = Fixture::new("backoff_policies_auth").await;
+        f.syncer
+            .set_backoff_policies(Policies {
+                auth: Some(cooldown::Backoff {
+                    base_secs: 60,
+                    growth_factor: 4,
+                    max_secs: 3600,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // fail to get a token
+        f.token_mngr.shutdown().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        assert_fails_with_cooldown(&f, 60, 1).await;
+        assert_fails_with_cooldown(&f, 240, 2).await;
+        assert_fails_with_cooldown(&f, 960, 3).await;
+        assert_fails_with_cooldown(&f, 3600, 4).await;
+    }
+
+    #[tokio::test]
+    async fn network_failures_back_off_by_the_network_policy() {
+        let f = Fixture::new("backoff_policies_network").await;
+        f.syncer
+            .set_backoff_policies(Policies {
+                network: Some(cooldown::Backoff {
+                    base_secs: 2,
+                    growth_factor: 2,
+                    max_secs: 8,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        f.http_client.set_list_all_deployments(network_err);
+        f.http_client.set_update_deployment(network_err);
+
+        // network errors still don't count toward the err streak
+        assert_fails_with_cooldown(&f, 2, 0).await;
+        assert_fails_with_cooldown(&f, 4, 0).await;
+        assert_fails_with_cooldown(&f, 8, 0).await;
+        assert_fails_with_cooldown(&f, 8, 0).await;
+
+        // a successful sync starts the backoff over
+        f.http_client.set_list_all_deployments(|| Ok(vec![]));
+        f.syncer.sync().await.unwrap();
+        f.reset_cooldown().await;
+        f.http_client.set_list_all_deployments(network_err);
+        let before = Utc::now();
+        f.syncer.sync().await.unwrap_err();
+        let after = Utc::now();
+        let state = f.syncer.get_sync_state().await.unwrap();
+        StateAssert::new(before, after)
+            .assert_between(state.cooldown_ends_at, TimeDelta::seconds(2));
+    }
+
+    #[tokio::test]
+    async fn another_failure_class_starts_the_backoff_over() {
+        let f = Fixture::new("backoff_policies_class_change").await;
+        f.syncer
+            .set_backoff_policies(Policies {
+                server: Some(cooldown::Backoff {
+                    base_secs: 5,
+                    growth_factor: 3,
+                    max_secs: 1000,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        f.http_client.set_list_all_deployments(server_err);
+        f.http_client.set_update_deployment(server_err);
+
+        assert_fails_with_cooldown(&f, 5, 1).await;
+        assert_fails_with_cooldown(&f, 15, 2).await;
+
+        // network errors without a policy retry after the default base period
+        f.http_client.set_list_all_deployments(network_err);
+        f.http_client.set_update_deployment(network_err);
+        assert_fails_with_cooldown(&f, f.backoff.base_secs, 2).await;
+
+        f.http_client.set_list_all_deployments(server_err);
+        f.http_client.set_update_deployment(server_err);
+        assert_fails_with_cooldown(&f, 5, 3).await;
+    }
+
+    #[tokio::test]
+    async fn failures_publish_sync_failed_events() {
+        let f = Fixture::new("backoff_policies_sync_failed_events").await;
+        f.http_client.set_list_all_deployments(server_err);
+        f.http_client.set_update_deployment(server_err);
+        assert_fails_with_cooldown(&f, f.backoff.base_secs * 2, 1).await;
+        assert_fails_with_cooldown(&f, f.backoff.base_secs * 4, 2).await;
+
+        // only the first of consecutive network failures is published
+        f.http_client.set_list_all_deployments(network_err);
+        f.http_client.set_update_deployment(network_err);
+        assert_fails_with_cooldown(&f, f.backoff.base_secs, 2).await;
+        assert_fails_with_cooldown(&f, f.backoff.base_secs, 2).await;
+
+        let events = f.event_hub.replay_after(0).await.unwrap();
+        let published: Vec<(String, u64)> = events
+            .iter()
+            .filter(|e| e.event_type == SYNC_FAILED)
+            .map(|e| {
+                (
+                    e.data["failure_class"].as_str().unwrap().to_string(),
+                    e.data["streak"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            published,
+            vec![
+                ("server".to_string(), 1),
+                ("server".to_string(), 2),
+                ("network".to_string(), 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn classes_without_a_policy_use_the_default_backoff() {
+        let f = Fixture::new("backoff_policies_default").await;
+        f.syncer
+            .set_backoff_policies(Policies {
+                auth: Some(cooldown::Backoff {
+                    base_secs: 60,
+                    growth_factor: 4,
+                    max_secs: 3600,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        f.http_client.set_list_all_deployments(server_err);
+        f.http_client.set_update_deployment(server_err);
+
+        for i in 1..=3 {
+            assert_fails_with_cooldown(&f, cooldown::calc(&f.backoff, i), i).await;
+        }
+    }
+}
+
 pub mod sync_if_not_in_cooldown {
     use super::*;
 
@@ -749,6 +1142,90 @@ pub mod sync_if_not_in_cooldown {
     }
 }
 
+pub mod event_mask {
+    use super::*;
+
+    #[test]
+    fn matches() {
+        let failure = SyncEvent::SyncFailed(SyncFailure {
+            is_network_conn_err: true,
+        });
+        let cooldown_end = SyncEvent::CooldownEnd(CooldownEnd::DeploymentWait);
+
+        let mask = EventMask::SYNC_SUCCESS | EventMask::SYNC_FAILED;
+        assert!(mask.matches(&SyncEvent::SyncSuccess));
+        assert!(mask.matches(&failure));
+        assert!(!mask.matches(&cooldown_end));
+
+        assert!(EventMask::ALL.matches(&cooldown_end));
+        assert!(!EventMask::NONE.matches(&SyncEvent::SyncSuccess));
+    }
+}
+
+pub mod subscribe_filtered {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_unmasked_events() {
+        let f = Fixture::new("subscribe_filtered_skips").await;
+
+        let mut subscription = f
+            .syncer
+            .subscribe_filtered(EventMask::COOLDOWN_END)
+            .await
+            .unwrap();
+        f.syncer.sync().await.unwrap();
+
+        // the sync success event is skipped in favor of the cooldown end
+        let event = tokio::time::timeout(Duration::from_secs(10), subscription.recv())
+            .await
+            .expect("timed out waiting for the cooldown end");
+        assert_eq!(
+            event,
+            Some(SyncEvent::CooldownEnd(CooldownEnd::SyncSuccess))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delivers_masked_events() {
+        let f = Fixture::new("subscribe_filtered_delivers").await;
+        f.http_client.set_list_all_deployments(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+
+        let mut subscription = f
+            .syncer
+            .subscribe_filtered(EventMask::SYNC_SUCCESS | EventMask::SYNC_FAILED)
+            .await
+            .unwrap();
+        f.syncer.sync().await.unwrap_err();
+
+        let event = tokio::time::timeout(Duration::from_secs(10), subscription.recv())
+            .await
+            .expect("timed out waiting for the sync failure");
+        assert_eq!(
+            event,
+            Some(SyncEvent::SyncFailed(SyncFailure {
+                is_network_conn_err: true,
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn last_event() {
+        let f = Fixture::new("syncer_last_event").await;
+        assert_eq!(f.syncer.last_event().await.unwrap(), None);
+
+        f.syncer.sync().await.unwrap();
+        assert_eq!(
+            f.syncer.last_event().await.unwrap(),
+            Some(SyncEvent::SyncSuccess)
+        );
+    }
+}
+
 pub mod subscribe {
     use super::*;
 
@@ -0,0 +1,162 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::sync::warming::{Options, Progress, Warming};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+const PERIOD: Duration = Duration::from_secs(100);
+
+fn progress(total: usize, downloaded: usize) -> Progress {
+    Progress {
+        started_at: DateTime::<Utc>::UNIX_EPOCH,
+        total,
+        downloaded,
+    }
+}
+
+fn options(cold_threshold: usize) -> Options {
+    Options {
+        enabled: true,
+        period: PERIOD,
+        cold_threshold,
+        file: None,
+    }
+}
+
+pub mod schedule {
+    use super::*;
+
+    #[test]
+    fn first_download_is_due_immediately() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        assert_eq!(progress(4, 0).due(PERIOD, start), 1);
+        assert_eq!(progress(4, 0).due(PERIOD, start - TimeDelta::seconds(1)), 1);
+    }
+
+    #[test]
+    fn downloads_are_spread_evenly() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let p = progress(4, 0);
+        assert_eq!(p.due(PERIOD, start + TimeDelta::seconds(24)), 1);
+        assert_eq!(p.due(PERIOD, start + TimeDelta::seconds(25)), 2);
+        assert_eq!(p.due(PERIOD, start + TimeDelta::seconds(75)), 4);
+        assert_eq!(p.due(PERIOD, start + TimeDelta::seconds(500)), 4);
+    }
+
+    #[test]
+    fn next_due_at() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        assert_eq!(progress(4, 0).next_due_at(PERIOD), Some(start));
+        assert_eq!(
+            progress(4, 3).next_due_at(PERIOD),
+            Some(start + TimeDelta::seconds(75))
+        );
+        assert_eq!(progress(4, 4).next_due_at(PERIOD), None);
+    }
+}
+
+pub mod budget {
+    use super::*;
+
+    #[tokio::test]
+    async fn disabled_never_warms() {
+        let warming = Warming::new(Options {
+            enabled: false,
+            ..options(1)
+        });
+        assert_eq!(warming.budget(100, Utc::now()).await, None);
+        assert!(warming.progress().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn warms_once_cold() {
+        let warming = Warming::new(options(3));
+        assert_eq!(warming.budget(2, Utc::now()).await, None);
+        assert!(warming.progress().await.is_none());
+
+        let now = Utc::now();
+        assert_eq!(warming.budget(4, now).await, Some(1));
+        assert_eq!(
+            warming.progress().await,
+            Some(Progress {
+                started_at: now,
+                total: 4,
+                downloaded: 0,
+            })
+        );
+        assert_eq!(warming.next_due(now).await, None);
+
+        warming.record(1).await;
+        assert_eq!(warming.budget(3, now).await, Some(0));
+        let wait = warming.next_due(now).await.unwrap();
+        assert_eq!(wait, TimeDelta::seconds(25));
+
+        // a deployment added while warming grows the total
+        assert_eq!(warming.budget(5, now).await, Some(0));
+        assert_eq!(warming.progress().await.unwrap().total, 6);
+    }
+
+    #[tokio::test]
+    async fn finishes_once_nothing_is_missing() {
+        let warming = Warming::new(options(1));
+        let now = Utc::now();
+        warming.budget(2, now).await;
+        warming.record(2).await;
+        assert_eq!(warming.next_due(now).await, None);
+
+        assert_eq!(warming.budget(0, now).await, None);
+        assert!(warming.progress().await.is_none());
+    }
+}
+
+pub mod persistence {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_and_resumes() {
+        let dir = filesys::Dir::create_temp_dir("content_warming_persist")
+            .await
+            .unwrap();
+        let file = dir.file("content_warming.json");
+        let options = Options {
+            file: Some(file.clone()),
+            ..options(1)
+        };
+
+        let warming = Warming::load(options.clone()).await;
+        assert!(warming.progress().await.is_none());
+        warming.budget(3, Utc::now()).await;
+        warming.record(1).await;
+        assert!(file.exists());
+
+        let resumed = Warming::load(options).await;
+        assert_eq!(resumed.progress().await, warming.progress().await);
+        assert_eq!(resumed.progress().await.unwrap().downloaded, 1);
+
+        // finishing deletes the progress
+        resumed.budget(0, Utc::now()).await;
+        assert!(!file.exists());
+    }
+
+    #[tokio::test]
+    async fn discards_an_unreadable_file() {
+        let dir = filesys::Dir::create_temp_dir("content_warming_unreadable")
+            .await
+            .unwrap();
+        let file = dir.file("content_warming.json");
+        file.write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let warming = Warming::load(Options {
+            file: Some(file),
+            ..options(1)
+        })
+        .await;
+        assert!(warming.progress().await.is_none());
+    }
+}
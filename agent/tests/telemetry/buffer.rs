@@ -0,0 +1,32 @@
+// internal crates
+use miru_agent::telemetry::{Buffer, Sample, Sampler};
+
+fn samples(n: usize) -> Vec<Sample> {
+    let mut sampler = Sampler::new(Vec::new());
+    (0..n).map(|_| sampler.sample()).collect()
+}
+
+#[test]
+fn drops_the_oldest_sample_once_full() {
+    let samples = samples(3);
+    let mut buffer = Buffer::new(2);
+    assert!(buffer.is_empty());
+
+    assert_eq!(buffer.push(samples[0].clone()), None);
+    assert_eq!(buffer.push(samples[1].clone()), None);
+    assert_eq!(buffer.push(samples[2].clone()), Some(samples[0].clone()));
+    assert_eq!(buffer.len(), 2);
+    assert_eq!(buffer.samples(), &samples[1..]);
+
+    buffer.clear();
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn holds_at_least_one_sample() {
+    let samples = samples(2);
+    let mut buffer = Buffer::new(0);
+    assert_eq!(buffer.push(samples[0].clone()), None);
+    assert_eq!(buffer.push(samples[1].clone()), Some(samples[0].clone()));
+    assert_eq!(buffer.samples(), &samples[1..]);
+}
@@ -0,0 +1,114 @@
+// standard crates
+use std::fs;
+use std::path::Path;
+
+// internal crates
+use miru_agent::telemetry::capabilities::{self, Capabilities, Provider};
+
+fn touch(root: &Path, path: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, "").unwrap();
+}
+
+fn net_interface(root: &Path, name: &str, kind: &str) {
+    let dir = root.join("sys/class/net").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("type"), format!("{kind}\n")).unwrap();
+}
+
+#[test]
+fn empty_root_has_no_capabilities() {
+    let root = tempfile::tempdir().unwrap();
+    assert_eq!(
+        Capabilities::default(),
+        Capabilities::detect_in(root.path())
+    );
+}
+
+#[test]
+fn detects_each_capability() {
+    let root = tempfile::tempdir().unwrap();
+    let root = root.path();
+    for path in [
+        "dev/nvidia0",
+        "dev/nvidiactl",
+        "dev/dri/renderD128",
+        "dev/dri/card0",
+        "dev/ttyUSB1",
+        "dev/ttyUSB0",
+        "dev/ttyACM0",
+        "dev/ttyS0",
+        "dev/tty1",
+        "dev/null",
+        "sys/class/video4linux/video0/name",
+        "sys/class/video4linux/v4l-subdev0/name",
+    ] {
+        touch(root, path);
+    }
+    net_interface(root, "can0", "280");
+    net_interface(root, "vcan1", "280");
+    net_interface(root, "eth0", "1");
+    net_interface(root, "lo", "772");
+
+    assert_eq!(
+        Capabilities {
+            gpus: vec!["dri/renderD128".to_string(), "nvidia0".to_string()],
+            can_interfaces: vec!["can0".to_string(), "vcan1".to_string()],
+            serial_ports: vec![
+                "ttyACM0".to_string(),
+                "ttyUSB0".to_string(),
+                "ttyUSB1".to_string()
+            ],
+            cameras: vec!["video0".to_string()],
+        },
+        Capabilities::detect_in(root)
+    );
+}
+
+#[test]
+fn detects_gpus_without_a_dri_dir() {
+    let root = tempfile::tempdir().unwrap();
+    touch(root.path(), "dev/nvhost-gpu");
+    assert_eq!(
+        vec!["nvhost-gpu".to_string()],
+        capabilities::Gpu.detect(root.path()).unwrap()
+    );
+}
+
+#[test]
+fn interfaces_without_a_type_are_skipped() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join("sys/class/net/can0")).unwrap();
+    assert!(Capabilities::detect_in(root.path())
+        .can_interfaces
+        .is_empty());
+}
+
+#[test]
+fn missing_directories_are_errors_for_providers_but_not_detection() {
+    let root = tempfile::tempdir().unwrap();
+    let err = capabilities::Camera.detect(root.path()).unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    assert!(Capabilities::detect_in(root.path()).cameras.is_empty());
+}
+
+#[test]
+fn serializes_to_json() {
+    let caps = Capabilities {
+        gpus: vec!["nvidia0".to_string()],
+        can_interfaces: vec![],
+        serial_ports: vec!["ttyUSB0".to_string()],
+        cameras: vec![],
+    };
+    assert_eq!(
+        r#"{"gpus":["nvidia0"],"can_interfaces":[],"serial_ports":["ttyUSB0"],"cameras":[]}"#,
+        caps.to_json()
+    );
+}
+
+#[test]
+fn detect_on_this_host_does_not_panic() {
+    let caps = Capabilities::detect();
+    assert!(caps.to_json().starts_with('{'));
+}
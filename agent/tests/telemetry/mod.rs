@@ -1,3 +1,8 @@
+pub mod buffer;
+pub mod capabilities;
+pub mod network;
+pub mod sample;
+
 // internal crates
 use miru_agent::telemetry::SystemInfo;
 
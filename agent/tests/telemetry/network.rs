@@ -0,0 +1,212 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::telemetry::network::{
+    self, default_interface, kind, link, Connectivity, Interface, Kind, Link, Network, Probe,
+};
+use miru_agent::telemetry::SystemInfo;
+
+// external crates
+use tokio::net::TcpListener;
+
+fn interface(name: &str, ip: &str) -> Interface {
+    Interface {
+        name: name.to_string(),
+        kind: Kind::Ethernet,
+        link: Link::Up,
+        mac_address: Some("02:42:ac:11:00:02".to_string()),
+        ip_addresses: vec![ip.to_string()],
+        rx_bytes: 100,
+        tx_bytes: 200,
+    }
+}
+
+fn network(interfaces: Vec<Interface>, reachable: bool) -> Network {
+    Network {
+        interfaces,
+        connectivity: Connectivity {
+            default_interface: Some("eth0".to_string()),
+            backend: Some(Probe {
+                host: "api.mirurobotics.com:443".to_string(),
+                reachable,
+                latency_ms: reachable.then_some(20),
+                error: None,
+            }),
+        },
+    }
+}
+
+#[test]
+fn lists_interfaces() {
+    let interfaces = network::interfaces();
+    let mut names: Vec<&str> = interfaces.iter().map(|i| i.name.as_str()).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        interfaces
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(SystemInfo::new().interfaces.len(), interfaces.len());
+}
+
+pub mod sysfs {
+    use super::*;
+
+    async fn sys_class_net(prefix: &str) -> filesys::Dir {
+        filesys::Dir::create_temp_dir(prefix).await.unwrap()
+    }
+
+    async fn write(dir: &filesys::Dir, path: &str, contents: &str) {
+        let path = dir.path().join(path);
+        tokio::fs::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(path, contents).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn kinds() {
+        let dir = sys_class_net("network_kinds").await;
+        write(&dir, "lo/type", "772\n").await;
+        write(&dir, "eth0/device/vendor", "0x8086\n").await;
+        write(&dir, "wlan0/device/vendor", "0x14e4\n").await;
+        write(&dir, "wlan0/wireless/.keep", "").await;
+        write(&dir, "wwan0/uevent", "DEVTYPE=wwan\nINTERFACE=wwan0\n").await;
+        write(&dir, "usb0/uevent", "DEVTYPE=wwan\n").await;
+        write(&dir, "docker0/bridge/.keep", "").await;
+
+        let path = dir.path();
+        assert_eq!(kind(path, "lo"), Kind::Loopback);
+        assert_eq!(kind(path, "eth0"), Kind::Ethernet);
+        assert_eq!(kind(path, "wlan0"), Kind::Wifi);
+        assert_eq!(kind(path, "wwan0"), Kind::Cellular);
+        assert_eq!(kind(path, "usb0"), Kind::Cellular);
+        assert_eq!(kind(path, "docker0"), Kind::Virtual);
+        assert_eq!(kind(path, "missing0"), Kind::Unknown);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn links() {
+        let dir = sys_class_net("network_links").await;
+        write(&dir, "eth0/operstate", "up\n").await;
+        write(&dir, "eth1/operstate", "down\n").await;
+        write(&dir, "wlan0/operstate", "dormant\n").await;
+        write(&dir, "lo/operstate", "unknown\n").await;
+
+        let path = dir.path();
+        assert_eq!(link(path, "eth0"), Link::Up);
+        assert_eq!(link(path, "eth1"), Link::Down);
+        assert_eq!(link(path, "wlan0"), Link::Down);
+        assert_eq!(link(path, "lo"), Link::Unknown);
+        assert_eq!(link(path, "missing0"), Link::Unknown);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod default_route {
+    use super::*;
+
+    const HEADER: &str =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT";
+
+    #[test]
+    fn lowest_metric_wins() {
+        let table = format!(
+            "{HEADER}\n\
+             wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n\
+             eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+             eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n"
+        );
+        assert_eq!(default_interface(&table), Some("eth0".to_string()));
+    }
+
+    #[test]
+    fn no_default_route() {
+        let table =
+            format!("{HEADER}\neth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n");
+        assert_eq!(default_interface(&table), None);
+        assert_eq!(default_interface(""), None);
+    }
+}
+
+pub mod changed_from {
+    use super::*;
+
+    #[test]
+    fn ignores_counters_and_latency() {
+        let before = network(vec![interface("eth0", "10.0.0.2/24")], true);
+        let mut after = before.clone();
+        after.interfaces[0].rx_bytes = 5000;
+        after.interfaces[0].tx_bytes = 9000;
+        after.connectivity.backend.as_mut().unwrap().latency_ms = Some(350);
+        assert!(!after.changed_from(&before));
+    }
+
+    #[test]
+    fn addresses_links_and_reachability() {
+        let before = network(vec![interface("eth0", "10.0.0.2/24")], true);
+
+        let after = network(vec![interface("eth0", "10.0.0.3/24")], true);
+        assert!(after.changed_from(&before));
+
+        let mut after = before.clone();
+        after.interfaces[0].link = Link::Down;
+        assert!(after.changed_from(&before));
+
+        let mut after = before.clone();
+        after.connectivity.default_interface = Some("wlan0".to_string());
+        assert!(after.changed_from(&before));
+
+        let after = network(vec![interface("eth0", "10.0.0.2/24")], false);
+        assert!(after.changed_from(&before));
+    }
+}
+
+pub mod probe {
+    use super::*;
+
+    #[tokio::test]
+    async fn reachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let probe = network::probe("127.0.0.1", port, Duration::from_secs(5)).await;
+        assert_eq!(probe.host, format!("127.0.0.1:{port}"));
+        assert!(probe.reachable);
+        assert!(probe.latency_ms.is_some());
+        assert_eq!(probe.error, None);
+    }
+
+    #[tokio::test]
+    async fn unreachable() {
+        // a port which was just free
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let probe = network::probe("127.0.0.1", port, Duration::from_secs(5)).await;
+        assert!(!probe.reachable);
+        assert_eq!(probe.latency_ms, None);
+        assert!(probe.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn detect_probes_the_backend() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{port}/agent/v1");
+
+        let network = network::detect(Some(&url), Duration::from_secs(5)).await;
+        let backend = network.connectivity.backend.unwrap();
+        assert_eq!(backend.host, format!("127.0.0.1:{port}"));
+        assert!(backend.reachable);
+
+        let network = network::detect(None, Duration::from_secs(5)).await;
+        assert_eq!(network.connectivity.backend, None);
+    }
+}
@@ -0,0 +1,58 @@
+// internal crates
+use miru_agent::filesys::{Dir, PathExt};
+use miru_agent::telemetry::{disk_space, disks_space, Sample, Sampler};
+
+#[test]
+fn samples_the_host() {
+    let mut sampler = Sampler::new(Vec::new());
+    let sample = sampler.sample();
+    assert!(sample.memory.total_bytes > 0);
+    assert!(sample.memory.used_bytes <= sample.memory.total_bytes);
+    assert!(sample.memory.available_bytes <= sample.memory.total_bytes);
+    assert!(sample.memory.used_swap_bytes <= sample.memory.total_swap_bytes);
+    assert!(sample.cpu_usage_percent >= 0.0);
+    assert!(sample.disks.is_empty());
+    if let Some(celsius) = sample.temperature_celsius {
+        assert!(celsius.is_finite());
+    }
+
+    let later = sampler.sample();
+    assert!(later.sampled_at >= sample.sampled_at);
+    assert!(later.uptime_secs >= sample.uptime_secs);
+}
+
+#[test]
+fn samples_each_disk_once() {
+    let dir = Dir::new(std::env::temp_dir());
+    let paths = vec![
+        dir.path().clone(),
+        dir.subdir("a").path().clone(),
+        dir.subdir("b").path().clone(),
+    ];
+    let Some(space) = disk_space(dir.path()) else {
+        // the sandbox may not expose its disks
+        return;
+    };
+
+    let spaces = disks_space(&paths);
+    assert_eq!(spaces.len(), 1);
+    assert_eq!(spaces[0].mount_point, space.mount_point);
+
+    let sample = Sampler::new(paths).sample();
+    assert_eq!(sample.disks.len(), 1);
+}
+
+#[test]
+fn serde_round_trip() {
+    let sample = Sampler::new(Vec::new()).sample();
+    let json = serde_json::to_string(&sample).unwrap();
+    assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+
+    // the temperature is left out where there are no sensors
+    let sample = Sample {
+        temperature_celsius: None,
+        ..sample
+    };
+    let json = serde_json::to_value(&sample).unwrap();
+    assert!(json.get("temperature_celsius").is_none());
+}
@@ -0,0 +1,79 @@
+// standard crates
+use std::sync::Arc;
+
+// internal crates
+use crate::mocks::http_client::MockClient;
+use miru_agent::authn::TokenManagerExt;
+use miru_agent::filesys::PathExt;
+use miru_agent::http;
+use miru_agent::storage::Capacities;
+use miru_agent::sync::SyncerExt;
+use miru_agent::testkit::{self, Harness};
+
+// external crates
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+pub mod harness {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawns_components_in_temp_dir() {
+        let harness = Harness::new("testkit_harness", Arc::new(MockClient::default())).await;
+
+        assert!(harness.dir.exists());
+        assert!(harness.dir.file("token.json").exists());
+        harness.syncer.get_sync_state().await.unwrap();
+        harness.token_mngr.get_token().await.unwrap();
+        harness.storage.deployments.size().await.unwrap();
+
+        let dir = harness.dir.clone();
+        harness.cleanup().await;
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn harnesses_are_isolated() {
+        let a = Harness::new("testkit_isolated", Arc::new(MockClient::default())).await;
+        let b = Harness::new("testkit_isolated", Arc::new(MockClient::default())).await;
+        assert_ne!(a.dir.path(), b.dir.path());
+        a.cleanup().await;
+        b.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn serves_socket_api_in_temp_dir() {
+        let http_client = Arc::new(http::Client::new("http://localhost:1").unwrap());
+        let harness = Harness::new("testkit_serve", http_client).await;
+        let server = harness.serve().await;
+        assert!(server.socket_file.path().starts_with(harness.dir.path()));
+
+        let api_version = device_api::models::ApiVersion::API_VERSION.to_string();
+        let mut stream = UnixStream::connect(server.socket_file.path())
+            .await
+            .unwrap();
+        let request = format!(
+            "GET /{api_version}/health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        server.shutdown().await.unwrap();
+        harness.cleanup().await;
+    }
+}
+
+pub mod spawn_storage {
+    use super::*;
+
+    #[tokio::test]
+    async fn uses_given_capacities() {
+        let dir = testkit::temp_dir("testkit_storage").await;
+        let storage = testkit::spawn_storage(&dir, Capacities::default()).await;
+        assert_eq!(storage.deployments.size().await.unwrap(), 0);
+        assert!(dir.file("device.json").exists());
+        dir.delete().await.unwrap();
+    }
+}
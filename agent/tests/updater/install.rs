@@ -0,0 +1,153 @@
+// internal crates
+use crate::updater::shared::{serve, Signed};
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::http;
+use miru_agent::updater::install::{install, InstallParams};
+use miru_agent::updater::{Slot, Slots, UpdateErr};
+
+// external crates
+use chrono::Utc;
+
+const CONTENT: &[u8] = b"#!/bin/sh\necho miru-agent v0.9.0\n";
+
+pub mod install_release {
+    use super::*;
+
+    #[tokio::test]
+    async fn installs_into_the_inactive_slot_on_trial() {
+        let signed = Signed::new("install_release", CONTENT).await;
+        let release = signed.release("v0.9.0").await;
+        let backend = serve(Some(release.clone()), CONTENT.to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+        let slots = Slots::new(signed.dir.subdir("updates"));
+        let now = Utc::now();
+
+        let slot = install(
+            &client,
+            &slots,
+            InstallParams {
+                release: &release,
+                release_key: &signed.public_key,
+                token: "token",
+                now,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(slot, Slot::A);
+        assert_eq!(slots.binary(slot).read_bytes().await.unwrap(), CONTENT);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(slots.binary(slot).path()).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+        }
+        let state = slots.load().await;
+        assert_eq!(state.active, Some(Slot::A));
+        let trial = state.trial.unwrap();
+        assert_eq!(trial.version, "v0.9.0");
+        assert_eq!(trial.previous, None);
+        assert_eq!(trial.installed_at, now);
+        signed.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_a_bad_signature() {
+        let signed = Signed::new("install_bad_signature", CONTENT).await;
+        let other = Signed::new("install_other_key", CONTENT).await;
+        let release = other.release("v0.9.0").await;
+        let backend = serve(Some(release.clone()), CONTENT.to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+        let slots = Slots::new(signed.dir.subdir("updates"));
+
+        let err = install(
+            &client,
+            &slots,
+            InstallParams {
+                release: &release,
+                release_key: &signed.public_key,
+                token: "token",
+                now: Utc::now(),
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, UpdateErr::InvalidSignatureErr(_)));
+        assert!(!slots.binary(Slot::A).exists());
+        assert_eq!(slots.load().await, Default::default());
+        signed.dir.delete().await.unwrap();
+        other.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_a_tampered_binary() {
+        let signed = Signed::new("install_tampered", CONTENT).await;
+        let release = signed.release("v0.9.0").await;
+        let backend = serve(Some(release.clone()), b"tampered".to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+        let slots = Slots::new(signed.dir.subdir("updates"));
+
+        let err = install(
+            &client,
+            &slots,
+            InstallParams {
+                release: &release,
+                release_key: &signed.public_key,
+                token: "token",
+                now: Utc::now(),
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, UpdateErr::HTTPErr(_)));
+        assert!(slots.load().await.active.is_none());
+        signed.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn never_overwrites_the_running_slot() {
+        let signed = Signed::new("install_running_slot", CONTENT).await;
+        let release = signed.release("v0.9.1").await;
+        let backend = serve(Some(release.clone()), CONTENT.to_vec()).await;
+        let client = http::Client::new(&backend.server.base_url).unwrap();
+        let slots = Slots::new(signed.dir.subdir("updates"));
+        let running = slots.binary(Slot::A);
+        filesys::Dir::new(running.path().parent().unwrap())
+            .create_if_absent()
+            .await
+            .unwrap();
+        running
+            .write_bytes(b"v0.9.0", filesys::WriteOptions::default())
+            .await
+            .unwrap();
+        slots
+            .save(&miru_agent::updater::State {
+                active: Some(Slot::A),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let slot = install(
+            &client,
+            &slots,
+            InstallParams {
+                release: &release,
+                release_key: &signed.public_key,
+                token: "token",
+                now: Utc::now(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(slot, Slot::B);
+        assert_eq!(running.read_bytes().await.unwrap(), b"v0.9.0");
+        let trial = slots.load().await.trial.unwrap();
+        assert_eq!(trial.previous, Some(Slot::A));
+        signed.dir.delete().await.unwrap();
+    }
+}
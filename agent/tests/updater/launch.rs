@@ -0,0 +1,149 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::storage::migrations::{self, Migration, Step};
+use miru_agent::storage::{agent_version, Layout};
+use miru_agent::updater::launch;
+use miru_agent::updater::{Slot, Slots, State};
+
+// external crates
+use chrono::Utc;
+use serde_json::json;
+use serial_test::serial;
+
+pub mod launch_agent {
+    use super::*;
+
+    #[tokio::test]
+    #[serial(slot_env)]
+    async fn runs_the_installed_binary_without_an_update() {
+        let dir = filesys::Dir::create_temp_dir("launch_installed")
+            .await
+            .unwrap();
+        let slots = Slots::new(dir.subdir("updates"));
+        let layout = Layout::new(dir.clone());
+        assert_eq!(launch::launch(&slots, &layout).await.unwrap(), None);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod is_updater_driven {
+    use super::*;
+
+    #[tokio::test]
+    async fn true_when_running_from_a_slot() {
+        let dir = filesys::Dir::create_temp_dir("launch_driven_slot")
+            .await
+            .unwrap();
+        let slots = Slots::new(dir.subdir("updates"));
+        let layout = Layout::new(dir.clone());
+        assert!(launch::is_updater_driven(&slots, &layout, Some(Slot::B)).await);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn true_after_rolling_back_to_the_installed_binary() {
+        let dir = filesys::Dir::create_temp_dir("launch_driven_rollback")
+            .await
+            .unwrap();
+        let slots = Slots::new(dir.subdir("updates"));
+        let layout = Layout::new(dir.clone());
+        agent_version::write(&layout.agent_version(), "v0.9.0")
+            .await
+            .unwrap();
+        let mut state = State::default();
+        state.begin_trial(Slot::A, "v0.9.0", Utc::now());
+        state.rollback();
+        slots.save(&state).await.unwrap();
+        assert!(launch::is_updater_driven(&slots, &layout, None).await);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn false_for_the_installed_binary() {
+        let dir = filesys::Dir::create_temp_dir("launch_driven_package")
+            .await
+            .unwrap();
+        let slots = Slots::new(dir.subdir("updates"));
+        let layout = Layout::new(dir.clone());
+        assert!(!launch::is_updater_driven(&slots, &layout, None).await);
+        agent_version::write(&layout.agent_version(), "v0.8.0")
+            .await
+            .unwrap();
+        assert!(!launch::is_updater_driven(&slots, &layout, None).await);
+        dir.delete().await.unwrap();
+    }
+}
+
+pub mod revert_migrations {
+    use super::*;
+
+    const SYNC_STATE: Migration = Migration {
+        id: "0001_sync_state",
+        description: "move the syncer's state into sync/",
+        steps: &[Step::MoveFile {
+            from: |layout| layout.root().file("sync_state.json"),
+            to: Layout::sync_state,
+        }],
+    };
+
+    /// A data directory migrated by `version`, which was installed in slot A.
+    async fn migrated(dir: &filesys::Dir, version: &str) -> (Slots, Layout) {
+        let slots = Slots::new(dir.subdir("updates"));
+        let layout = Layout::new(dir.clone());
+        layout
+            .root()
+            .file("sync_state.json")
+            .write_json(&json!({}), WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        migrations::apply(&layout, &[SYNC_STATE], version)
+            .await
+            .unwrap();
+        let mut state = State::default();
+        state.begin_trial(Slot::A, version, Utc::now());
+        slots.save(&state).await.unwrap();
+        (slots, layout)
+    }
+
+    #[tokio::test]
+    async fn reverts_a_rolled_back_versions_migrations() {
+        let dir = filesys::Dir::create_temp_dir("launch_revert")
+            .await
+            .unwrap();
+        let (slots, layout) = migrated(&dir, "v0.9.0").await;
+        assert!(layout.sync_state().exists());
+        let mut state = slots.load().await;
+        state.rollback();
+        slots.save(&state).await.unwrap();
+
+        launch::revert_migrations(&slots, &layout, &[SYNC_STATE], "v0.9.0").await;
+
+        assert!(layout.root().file("sync_state.json").exists());
+        assert!(!layout.sync_state().exists());
+        assert!(migrations::read_applied(&layout.migrations())
+            .await
+            .unwrap()
+            .is_empty());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_the_migrations_of_a_version_not_rolled_back() {
+        let dir = filesys::Dir::create_temp_dir("launch_keep_migrations")
+            .await
+            .unwrap();
+        let (slots, layout) = migrated(&dir, "v0.9.0").await;
+
+        launch::revert_migrations(&slots, &layout, &[SYNC_STATE], "v0.9.0").await;
+
+        assert!(layout.sync_state().exists());
+        assert_eq!(
+            migrations::read_applied(&layout.migrations())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        dir.delete().await.unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod install;
+pub mod launch;
+pub mod shared;
+pub mod slots;
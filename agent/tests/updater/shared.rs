@@ -0,0 +1,81 @@
+// standard crates
+use std::sync::{Arc, Mutex};
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::crypt::digest::{Algorithm, Digest};
+use miru_agent::crypt::{base64, rsa};
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::http::agent_updates::{AgentRelease, LatestRelease};
+
+// external crates
+use axum::extract::{RawQuery, State};
+use axum::routing::get;
+use axum::{Json, Router};
+
+pub const BINARY_PATH: &str = "/releases/miru-agent";
+
+/// A release key pair and a binary signed with it.
+pub struct Signed {
+    pub dir: filesys::Dir,
+    pub private_key: filesys::File,
+    pub public_key: filesys::File,
+    pub content: Vec<u8>,
+}
+
+impl Signed {
+    pub async fn new(prefix: &str, content: &[u8]) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let private_key = dir.file("release_key");
+        let public_key = dir.file("release_key.pub");
+        rsa::gen_key_pair(2048, &private_key, &public_key, Overwrite::Allow)
+            .await
+            .unwrap();
+        Self {
+            dir,
+            private_key,
+            public_key,
+            content: content.to_vec(),
+        }
+    }
+
+    /// The release of `version`, signed with the release key.
+    pub async fn release(&self, version: &str) -> AgentRelease {
+        let signature = rsa::sign_rs256(&self.private_key, &self.content)
+            .await
+            .unwrap();
+        AgentRelease {
+            version: version.to_string(),
+            path: BINARY_PATH.to_string(),
+            digest: Digest::compute(Algorithm::Sha256, &self.content),
+            signature: base64::encode_bytes_standard(&signature),
+        }
+    }
+}
+
+/// The backend's release endpoints. Records the query of each latest release request.
+pub struct Backend {
+    pub server: mock::Server,
+    pub queries: Arc<Mutex<Vec<String>>>,
+}
+
+pub async fn serve(release: Option<AgentRelease>, content: Vec<u8>) -> Backend {
+    type Shared = (Option<AgentRelease>, Arc<Mutex<Vec<String>>>);
+    let queries = Arc::new(Mutex::new(Vec::new()));
+    let router = Router::new()
+        .route(
+            "/agent/releases/latest",
+            get(
+                |State((release, queries)): State<Shared>, RawQuery(query): RawQuery| async move {
+                    queries.lock().unwrap().push(query.unwrap_or_default());
+                    Json(LatestRelease { release })
+                },
+            ),
+        )
+        .route(BINARY_PATH, get(move || async move { content }))
+        .with_state((release, queries.clone()));
+    Backend {
+        server: mock::run_server(router).await,
+        queries,
+    }
+}
@@ -0,0 +1,142 @@
+// internal crates
+use miru_agent::filesys::{self, PathExt, WriteOptions};
+use miru_agent::updater::{Slot, Slots, State, Trial};
+
+// external crates
+use chrono::{DateTime, TimeZone, Utc};
+
+fn installed_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+}
+
+pub mod slot {
+    use super::*;
+
+    #[test]
+    fn parse_round_trips() {
+        for slot in [Slot::A, Slot::B] {
+            assert_eq!(Slot::parse(slot.as_str()), Some(slot));
+        }
+        assert_eq!(Slot::parse("c"), None);
+    }
+
+    #[test]
+    fn other() {
+        assert_eq!(Slot::A.other(), Slot::B);
+        assert_eq!(Slot::B.other(), Slot::A);
+    }
+}
+
+pub mod state {
+    use super::*;
+
+    #[test]
+    fn installs_to_the_slot_not_running() {
+        let mut state = State::default();
+        assert_eq!(state.inactive(), Slot::A);
+        state.active = Some(Slot::A);
+        assert_eq!(state.inactive(), Slot::B);
+    }
+
+    #[test]
+    fn begin_trial_switches_slots() {
+        let mut state = State {
+            active: Some(Slot::A),
+            ..Default::default()
+        };
+        state.begin_trial(Slot::B, "v0.9.0", installed_at());
+        assert_eq!(state.active, Some(Slot::B));
+        assert_eq!(
+            state.trial,
+            Some(Trial {
+                version: "v0.9.0".to_string(),
+                slot: Slot::B,
+                previous: Some(Slot::A),
+                installed_at: installed_at(),
+                starts: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn commit_keeps_the_version() {
+        let mut state = State::default();
+        state.begin_trial(Slot::A, "v0.9.0", installed_at());
+        assert_eq!(state.commit().unwrap().version, "v0.9.0");
+        assert_eq!(state.active, Some(Slot::A));
+        assert!(state.trial.is_none());
+        assert!(state.rejected.is_empty());
+        assert!(state.commit().is_none());
+    }
+
+    #[test]
+    fn rollback_returns_to_the_previous_slot() {
+        let mut state = State::default();
+        state.begin_trial(Slot::A, "v0.9.0", installed_at());
+        assert_eq!(state.rollback().unwrap().version, "v0.9.0");
+        assert_eq!(state.active, None);
+        assert!(state.trial.is_none());
+        assert_eq!(state.rejected, vec!["v0.9.0".to_string()]);
+        assert!(state.rollback().is_none());
+    }
+
+    #[test]
+    fn rollback_rejects_a_version_once() {
+        let mut state = State {
+            rejected: vec!["v0.9.0".to_string()],
+            ..Default::default()
+        };
+        state.begin_trial(Slot::A, "v0.9.0", installed_at());
+        state.rollback();
+        assert_eq!(state.rejected, vec!["v0.9.0".to_string()]);
+    }
+}
+
+pub mod persistence {
+    use super::*;
+
+    #[tokio::test]
+    async fn defaults_without_state() {
+        let dir = filesys::Dir::create_temp_dir("slots_default")
+            .await
+            .unwrap();
+        let slots = Slots::new(dir.subdir("updates"));
+        assert_eq!(slots.load().await, State::default());
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn save_then_load() {
+        let dir = filesys::Dir::create_temp_dir("slots_save").await.unwrap();
+        let slots = Slots::new(dir.subdir("updates"));
+        let mut state = State::default();
+        state.begin_trial(Slot::A, "v0.9.0", installed_at());
+        slots.save(&state).await.unwrap();
+        assert_eq!(slots.load().await, state);
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreadable_state_runs_the_installed_binary() {
+        let dir = filesys::Dir::create_temp_dir("slots_unreadable")
+            .await
+            .unwrap();
+        let slots = Slots::new(dir.clone());
+        dir.file("state.json")
+            .write_string("{not json", WriteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(slots.load().await, State::default());
+        dir.delete().await.unwrap();
+    }
+
+    #[test]
+    fn binaries_are_kept_per_slot() {
+        let slots = Slots::new(filesys::Dir::new("/srv/miru/updates"));
+        assert_eq!(
+            slots.binary(Slot::A).path(),
+            std::path::Path::new("/srv/miru/updates/a/miru-agent")
+        );
+        assert_ne!(slots.binary(Slot::A), slots.binary(Slot::B));
+    }
+}
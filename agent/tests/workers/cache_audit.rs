@@ -0,0 +1,447 @@
+// standard crates
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::error::SleepController;
+use crate::mocks::http_client::{Call, MockClient};
+use crate::mocks::syncer::MockSyncer;
+use crate::mocks::token_manager::MockTokenManager;
+use crate::sync::helpers::{make_deployment, CfgInstArgs};
+use miru_agent::authn::{errors::MockError as AuthnMockError, AuthnErr, Token};
+use miru_agent::filesys::{self, Overwrite};
+use miru_agent::http::{errors::MockErr as HTTPMockErr, HTTPErr};
+use miru_agent::metrics;
+use miru_agent::models::{self, DplTarget};
+use miru_agent::storage::{self, Deployments};
+use miru_agent::sync::syncer::State;
+use miru_agent::testkit;
+use miru_agent::workers::cache_audit::{
+    self, audit, diverged_fields, select_sample, Deps, Outcome,
+};
+
+// external crates
+use backend_api::models::{
+    Deployment as BackendDeployment, DeploymentTargetStatus as BackendTargetStatus,
+};
+use chrono::{TimeDelta, Utc};
+use serial_test::serial;
+
+struct Fixture {
+    dir: filesys::Dir,
+    deployments: Arc<Deployments>,
+    http_client: Arc<MockClient>,
+    token_mngr: Arc<MockTokenManager>,
+    syncer: Arc<MockSyncer>,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        let dir = testkit::temp_dir(prefix).await;
+        let storage = testkit::spawn_storage(&dir, storage::Capacities::default()).await;
+        let syncer = MockSyncer::default();
+        syncer.set_state(State {
+            last_synced_at: Utc::now(),
+            ..State::default()
+        });
+        Self {
+            dir,
+            deployments: storage.deployments,
+            http_client: Arc::new(MockClient::default()),
+            token_mngr: Arc::new(MockTokenManager::new(Token::default())),
+            syncer: Arc::new(syncer),
+        }
+    }
+
+    fn deps(&self) -> Deps<'_, MockClient, MockTokenManager, MockSyncer> {
+        Deps {
+            http_client: self.http_client.as_ref(),
+            token_mngr: self.token_mngr.as_ref(),
+            syncer: self.syncer.as_ref(),
+            deployments: self.deployments.as_ref(),
+        }
+    }
+
+    async fn cache(&self, backend_dpl: &BackendDeployment, is_dirty: bool) {
+        let cfg_inst_ids = backend_dpl
+            .config_instances
+            .iter()
+            .flatten()
+            .map(|inst| inst.id.clone())
+            .collect();
+        let dpl = models::Deployment::from_backend(backend_dpl.clone(), cfg_inst_ids);
+        self.deployments
+            .write(dpl.id.clone(), dpl, move |_, _| is_dirty, Overwrite::Allow)
+            .await
+            .unwrap();
+    }
+
+    async fn cleanup(self) {
+        self.dir.delete().await.unwrap();
+    }
+}
+
+fn backend_dpl(id: &str, updated_at: chrono::DateTime<Utc>) -> BackendDeployment {
+    let mut dpl = make_deployment(
+        id,
+        vec![CfgInstArgs {
+            id: format!("{id}_cfg_inst"),
+            filepath: format!("/srv/{id}.json"),
+        }],
+    );
+    dpl.updated_at = updated_at.to_rfc3339();
+    dpl
+}
+
+/// The agent's cache audit counters. They're global so the tests which audit run
+/// serially and compare them before and after.
+#[derive(Debug, PartialEq, Eq)]
+struct Counts {
+    audits: u64,
+    consistent: u64,
+    diverged: u64,
+    pending: u64,
+    failed: u64,
+}
+
+fn counts() -> Counts {
+    let metrics = metrics::global();
+    let outcome = |outcome: &str| metrics.cache_audit_outcomes.get(&[outcome]);
+    Counts {
+        audits: metrics.cache_audits.get(),
+        consistent: outcome("consistent"),
+        diverged: outcome("diverged"),
+        pending: outcome("pending"),
+        failed: outcome("failed"),
+    }
+}
+pub mod diverged_fields_func {
+    use super::*;
+
+    #[test]
+    fn identical_deployments() {
+        let dpl = models::Deployment::default();
+        assert!(diverged_fields(&dpl, &dpl.clone()).is_empty());
+    }
+
+    #[test]
+    fn ignores_agent_owned_fields() {
+        let cached = models::Deployment::default();
+        let backend = models::Deployment {
+            activity_status: models::DplActivity::Deployed,
+            error_status: models::DplErrStatus::Retrying,
+            attempts: 3,
+            ..cached.clone()
+        };
+        assert!(diverged_fields(&cached, &backend).is_empty());
+    }
+
+    #[test]
+    fn reports_backend_owned_fields() {
+        let cached = models::Deployment::default();
+        let backend = models::Deployment {
+            target_status: DplTarget::Archived,
+            release_id: "rls_2".to_string(),
+            config_instance_ids: vec!["cfg_inst_2".to_string()],
+            ..cached.clone()
+        };
+        assert_eq!(
+            diverged_fields(&cached, &backend),
+            vec!["target_status", "release_id", "config_instance_ids"]
+        );
+    }
+}
+
+pub mod select_sample_func {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_cache() {
+        let f = Fixture::new("cache_audit_sample_empty").await;
+        assert!(select_sample(&f.deployments, 3, 0).await.is_empty());
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn rotates_through_cache() {
+        let f = Fixture::new("cache_audit_sample_rotate").await;
+        for id in ["dpl_c", "dpl_a", "dpl_b"] {
+            f.cache(&backend_dpl(id, Utc::now()), false).await;
+        }
+
+        assert_eq!(
+            select_sample(&f.deployments, 2, 0).await,
+            ["dpl_a", "dpl_b"]
+        );
+        assert_eq!(
+            select_sample(&f.deployments, 2, 2).await,
+            ["dpl_c", "dpl_a"]
+        );
+        assert_eq!(
+            select_sample(&f.deployments, 5, 4).await,
+            ["dpl_b", "dpl_c", "dpl_a"]
+        );
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn skips_dirty_deployments() {
+        let f = Fixture::new("cache_audit_sample_dirty").await;
+        f.cache(&backend_dpl("dpl_1", Utc::now()), true).await;
+        f.cache(&backend_dpl("dpl_2", Utc::now()), false).await;
+
+        assert_eq!(select_sample(&f.deployments, 5, 0).await, ["dpl_2"]);
+        f.cleanup().await;
+    }
+}
+
+pub mod audit_func {
+    use super::*;
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn consistent() {
+        let f = Fixture::new("cache_audit_consistent").await;
+        let dpl = backend_dpl("dpl_1", Utc::now() - TimeDelta::hours(1));
+        f.cache(&dpl, false).await;
+        f.http_client.set_get_deployment(move || Ok(dpl.clone()));
+        let before = counts();
+
+        let reports = audit(&f.deps(), &["dpl_1".to_string()]).await;
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].outcome, Outcome::Consistent);
+        assert_eq!(f.http_client.call_count(Call::GetDeployment), 1);
+
+        assert_eq!(
+            counts(),
+            Counts {
+                audits: before.audits + 1,
+                consistent: before.consistent + 1,
+                ..before
+            }
+        );
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn missed_update_is_divergence() {
+        let f = Fixture::new("cache_audit_diverged").await;
+        let updated_at = Utc::now() - TimeDelta::hours(1);
+        f.cache(&backend_dpl("dpl_1", updated_at), false).await;
+
+        // the backend archived the deployment before the last sync completed
+        let mut remote = backend_dpl("dpl_1", updated_at);
+        remote.target_status = BackendTargetStatus::DEPLOYMENT_TARGET_STATUS_ARCHIVED;
+        f.http_client.set_get_deployment(move || Ok(remote.clone()));
+        let before = counts();
+        let divergences = &metrics::global().cache_audit_divergences;
+        let target_status_before = divergences.get(&["target_status"]);
+
+        let reports = audit(&f.deps(), &["dpl_1".to_string()]).await;
+        assert_eq!(reports[0].outcome, Outcome::Diverged(vec!["target_status"]));
+
+        assert_eq!(
+            counts(),
+            Counts {
+                audits: before.audits + 1,
+                diverged: before.diverged + 1,
+                ..before
+            }
+        );
+        assert_eq!(
+            divergences.get(&["target_status"]),
+            target_status_before + 1
+        );
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn change_after_last_sync_is_pending() {
+        let f = Fixture::new("cache_audit_pending").await;
+        f.cache(
+            &backend_dpl("dpl_1", Utc::now() - TimeDelta::hours(1)),
+            false,
+        )
+        .await;
+
+        let mut remote = backend_dpl("dpl_1", Utc::now() + TimeDelta::minutes(1));
+        remote.release_id = "rls_2".to_string();
+        f.http_client.set_get_deployment(move || Ok(remote.clone()));
+        let before = counts();
+
+        let reports = audit(&f.deps(), &["dpl_1".to_string()]).await;
+        assert_eq!(reports[0].outcome, Outcome::Pending);
+
+        assert_eq!(
+            counts(),
+            Counts {
+                audits: before.audits + 1,
+                pending: before.pending + 1,
+                ..before
+            }
+        );
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn fetch_failure() {
+        let f = Fixture::new("cache_audit_fetch_failure").await;
+        f.cache(&backend_dpl("dpl_1", Utc::now()), false).await;
+        f.http_client.set_get_deployment(|| {
+            Err(HTTPErr::MockErr(HTTPMockErr {
+                is_network_conn_err: true,
+            }))
+        });
+        let before = counts();
+
+        let reports = audit(&f.deps(), &["dpl_1".to_string()]).await;
+        assert_eq!(reports[0].outcome, Outcome::Failed);
+        assert_eq!(counts().failed, before.failed + 1);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn token_failure() {
+        let f = Fixture::new("cache_audit_token_failure").await;
+        f.token_mngr.set_get_token(Box::new(|| {
+            Err(AuthnErr::MockError(AuthnMockError {
+                is_network_conn_err: false,
+                trace: miru_agent::trace!(),
+            }))
+        }));
+
+        let sample = ["dpl_1".to_string(), "dpl_2".to_string()];
+        let before = counts();
+        let reports = audit(&f.deps(), &sample).await;
+        assert!(reports.iter().all(|r| r.outcome == Outcome::Failed));
+        assert_eq!(f.http_client.call_count(Call::GetDeployment), 0);
+        assert_eq!(counts().failed, before.failed + 2);
+        f.cleanup().await;
+    }
+}
+
+pub mod run {
+    use super::*;
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn audits_sample_each_interval() {
+        let f = Fixture::new("cache_audit_run").await;
+        for id in ["dpl_1", "dpl_2", "dpl_3"] {
+            f.cache(&backend_dpl(id, Utc::now()), false).await;
+        }
+
+        let options = cache_audit::Options {
+            interval_secs: 60,
+            sample_size: 2,
+        };
+        let sleep_ctrl = Arc::new(SleepController::new());
+        let before = counts();
+
+        let sleep_fn = sleep_ctrl.sleep_fn();
+        let deployments = f.deployments.clone();
+        let http_client = f.http_client.clone();
+        let token_mngr = f.token_mngr.clone();
+        let syncer = f.syncer.clone();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            let deps = Deps {
+                http_client: http_client.as_ref(),
+                token_mngr: token_mngr.as_ref(),
+                syncer: syncer.as_ref(),
+                deployments: deployments.as_ref(),
+            };
+            cache_audit::run(
+                &options,
+                &deps,
+                sleep_fn,
+                Box::pin(async move {
+                    let _ = shutdown_rx.await;
+                }),
+            )
+            .await;
+        });
+
+        // nothing is audited before the first interval elapses
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(
+            sleep_ctrl.get_last_attempted_sleep(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(f.http_client.call_count(Call::GetDeployment), 0);
+
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(f.http_client.call_count(Call::GetDeployment), 2);
+
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(f.http_client.call_count(Call::GetDeployment), 4);
+        assert_eq!(counts().audits, before.audits + 2);
+
+        shutdown_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[serial(cache_audit)]
+    async fn waits_at_least_the_minimum_interval() {
+        let f = Fixture::new("cache_audit_min_interval").await;
+        f.cache(&backend_dpl("dpl_1", Utc::now()), false).await;
+
+        let options = cache_audit::Options {
+            interval_secs: 0,
+            sample_size: 2,
+        };
+        let sleep_ctrl = Arc::new(SleepController::new());
+
+        let sleep_fn = sleep_ctrl.sleep_fn();
+        let deployments = f.deployments.clone();
+        let http_client = f.http_client.clone();
+        let token_mngr = f.token_mngr.clone();
+        let syncer = f.syncer.clone();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            let deps = Deps {
+                http_client: http_client.as_ref(),
+                token_mngr: token_mngr.as_ref(),
+                syncer: syncer.as_ref(),
+                deployments: deployments.as_ref(),
+            };
+            cache_audit::run(
+                &options,
+                &deps,
+                sleep_fn,
+                Box::pin(async move {
+                    let _ = shutdown_rx.await;
+                }),
+            )
+            .await;
+        });
+
+        sleep_ctrl.await_sleep().await;
+        let min = Duration::from_secs(cache_audit::MIN_INTERVAL_SECS as u64);
+        assert_eq!(sleep_ctrl.get_last_attempted_sleep(), Some(min));
+
+        // every audit waits the minimum, not just the first
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(f.http_client.call_count(Call::GetDeployment), 1);
+        assert_eq!(sleep_ctrl.get_last_attempted_sleep(), Some(min));
+
+        shutdown_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        f.cleanup().await;
+    }
+}
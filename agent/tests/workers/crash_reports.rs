@@ -0,0 +1,212 @@
+// standard crates
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::{
+    http_client::{Call, MockClient},
+    syncer::MockSyncer,
+    token_manager::MockTokenManager,
+};
+use miru_agent::authn::Token;
+use miru_agent::diagnostics::crash::{self, Report};
+use miru_agent::filesys;
+use miru_agent::http::errors::{HTTPErr, MockErr};
+use miru_agent::journal::{self, Journal, Kind};
+use miru_agent::logs::RecentLines;
+use miru_agent::models::Device;
+use miru_agent::storage::{self, layout::CrashLayout, Layout};
+use miru_agent::sync::syncer::SyncEvent;
+use miru_agent::workers::crash_reports::{self, Deps, Options};
+
+struct Fixture {
+    dir: filesys::Dir,
+    layout: CrashLayout,
+    http_client: Arc<MockClient>,
+    token_mngr: Arc<MockTokenManager>,
+    syncer: Arc<MockSyncer>,
+    device_stor: Arc<storage::Device>,
+    journal: Arc<Journal>,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let layout = Layout::new(dir.clone());
+        let device = Device {
+            id: "dvc_1".to_string(),
+            ..Device::default()
+        };
+        let (device_stor, _) = storage::Device::spawn_with_default(64, layout.device(), device)
+            .await
+            .unwrap();
+        let journal = Journal::init(layout.journal(), journal::Options::default())
+            .await
+            .unwrap();
+        Self {
+            dir,
+            layout: layout.crash(),
+            http_client: Arc::new(MockClient::default()),
+            token_mngr: Arc::new(MockTokenManager::new(Token {
+                token: "token".to_string(),
+                ..Token::default()
+            })),
+            syncer: Arc::new(MockSyncer::new()),
+            device_stor: Arc::new(device_stor),
+            journal: Arc::new(journal),
+        }
+    }
+
+    fn deps(&self) -> Deps<'_, MockClient, MockTokenManager, MockSyncer> {
+        Deps {
+            http_client: self.http_client.as_ref(),
+            token_mngr: self.token_mngr.as_ref(),
+            syncer: self.syncer.as_ref(),
+            device_stor: self.device_stor.as_ref(),
+            journal: self.journal.as_ref(),
+        }
+    }
+
+    /// Writes a crash report `minutes_ago` minutes in the past.
+    fn crash(&self, message: &str, minutes_ago: i64) -> Report {
+        let options = crash::Options {
+            layout: self.layout.clone(),
+            recent_lines: RecentLines::new(10),
+            redactor: None,
+            upload: true,
+        };
+        let report = Report::capture(message, None, &options);
+        let report = Report {
+            crashed_at: report.crashed_at - chrono::TimeDelta::minutes(minutes_ago),
+            ..report
+        };
+        crash::write(&self.layout, &report, true).unwrap();
+        report
+    }
+
+    /// The messages of the uploaded crash reports.
+    fn uploaded(&self) -> Vec<String> {
+        self.http_client
+            .requests()
+            .iter()
+            .filter(|r| r.call == Call::ReportCrash)
+            .map(|r| {
+                let body: Report = serde_json::from_str(r.body.as_deref().unwrap()).unwrap();
+                body.message
+            })
+            .collect()
+    }
+}
+
+fn unreachable() -> Result<(), HTTPErr> {
+    Err(HTTPErr::MockErr(MockErr {
+        is_network_conn_err: true,
+    }))
+}
+
+pub mod upload {
+    use super::*;
+
+    #[tokio::test]
+    async fn uploads_and_removes_the_pending_reports() {
+        let f = Fixture::new("crash_reports_upload").await;
+        f.crash("second", 1);
+        let last = f.crash("first", 2);
+
+        assert_eq!(crash_reports::upload(&f.layout, &f.deps()).await, 2);
+
+        let requests = f.http_client.requests();
+        assert_eq!(requests[0].path, "/devices/dvc_1/crashes");
+        assert_eq!(requests[0].token.as_deref(), Some("token"));
+        assert_eq!(f.uploaded(), vec!["first", "second"]);
+        assert!(crash::pending(&f.layout).await.unwrap().is_empty());
+        // the last crash written is kept for status
+        assert_eq!(crash::last_crash(&f.layout).await, Some(last));
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn nothing_to_upload() {
+        let f = Fixture::new("crash_reports_upload_none").await;
+
+        assert_eq!(crash_reports::upload(&f.layout, &f.deps()).await, 0);
+
+        assert!(f.http_client.requests().is_empty());
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_the_reports_which_failed_to_upload_queued() {
+        let f = Fixture::new("crash_reports_upload_err").await;
+        f.crash("first", 2);
+        f.crash("second", 1);
+        f.http_client.set_report_crash(unreachable);
+
+        assert_eq!(crash_reports::upload(&f.layout, &f.deps()).await, 2);
+
+        // the drain stops at the first network error
+        assert_eq!(f.http_client.requests().len(), 1);
+        assert!(crash::pending(&f.layout).await.unwrap().is_empty());
+        let queued: Vec<_> = f
+            .journal
+            .entries()
+            .await
+            .iter()
+            .map(|e| e.request.kind())
+            .collect();
+        assert_eq!(queued, vec![Kind::CrashReport, Kind::CrashReport]);
+
+        // and are uploaded by a later drain
+        f.http_client.set_report_crash(|| Ok(()));
+        f.journal
+            .drain(f.http_client.as_ref(), "token")
+            .await
+            .unwrap();
+        assert_eq!(f.uploaded(), vec!["first", "first", "second"]);
+        assert!(f.journal.is_empty().await);
+        f.dir.delete().await.unwrap();
+    }
+}
+
+pub mod run {
+    use super::*;
+
+    #[tokio::test]
+    async fn uploads_after_a_successful_sync() {
+        let f = Arc::new(Fixture::new("crash_reports_run").await);
+        f.crash("boom", 1);
+
+        let f_for_spawn = f.clone();
+        let handle = tokio::spawn(async move {
+            let options = Options {
+                layout: f_for_spawn.layout.clone(),
+            };
+            crash_reports::run(
+                &options,
+                &f_for_spawn.deps(),
+                Box::pin(std::future::pending::<()>()),
+            )
+            .await;
+        });
+
+        // nothing is uploaded until the backend is known to be reachable
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(f.uploaded().is_empty());
+
+        f.syncer
+            .get_transmitter()
+            .send(SyncEvent::SyncSuccess)
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while f.uploaded().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(f.uploaded(), vec!["boom"]);
+        handle.abort();
+        f.dir.delete().await.unwrap();
+    }
+}
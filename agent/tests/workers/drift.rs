@@ -0,0 +1,180 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::syncer::MockSyncer;
+use miru_agent::deploy::apply;
+use miru_agent::filesys::{self, Overwrite, PathExt};
+use miru_agent::models::{ConfigInstance, Deployment, DplActivity, DplTarget};
+use miru_agent::storage::{self, Storage};
+use miru_agent::testkit;
+use miru_agent::workers::drift::{check, run, Deps, Options};
+
+struct Fixture {
+    dir: filesys::Dir,
+    storage: Storage,
+    syncer: MockSyncer,
+    filepath: String,
+}
+
+impl Fixture {
+    /// Caches a deployment which is deployed with one file, and writes the file.
+    async fn new(prefix: &str) -> Self {
+        let dir = testkit::temp_dir(prefix).await;
+        let storage = testkit::spawn_storage(&dir, storage::Capacities::default()).await;
+        let filepath = dir.path().join("app.json").display().to_string();
+
+        let cfg_inst = ConfigInstance {
+            id: "cfg_inst_1".to_string(),
+            filepath: filepath.clone(),
+            ..Default::default()
+        };
+        storage
+            .cfg_insts
+            .meta
+            .write(
+                cfg_inst.id.clone(),
+                cfg_inst.clone(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        storage
+            .cfg_insts
+            .content
+            .write(
+                cfg_inst.id.clone(),
+                r#"{"speed":4}"#.to_string(),
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        let deployment = Deployment {
+            id: "dpl_1".to_string(),
+            target_status: DplTarget::Deployed,
+            activity_status: DplActivity::Deployed,
+            config_instance_ids: vec![cfg_inst.id],
+            ..Default::default()
+        };
+        storage
+            .deployments
+            .write(
+                deployment.id.clone(),
+                deployment,
+                |_, _| false,
+                Overwrite::Allow,
+            )
+            .await
+            .unwrap();
+        std::fs::write(&filepath, r#"{"speed":4}"#).unwrap();
+
+        Self {
+            dir,
+            storage,
+            syncer: MockSyncer::default(),
+            filepath,
+        }
+    }
+
+    fn apply_storage(&self) -> apply::Storage<'_> {
+        apply::Storage {
+            deployments: self.storage.deployments.as_ref(),
+            cfg_insts: self.storage.cfg_insts.as_ref(),
+            releases: self.storage.releases.as_ref(),
+        }
+    }
+
+    async fn activity(&self) -> DplActivity {
+        self.storage
+            .deployments
+            .read("dpl_1".to_string())
+            .await
+            .unwrap()
+            .activity_status
+    }
+
+    async fn cleanup(self) {
+        self.dir.delete().await.unwrap();
+    }
+}
+
+pub mod check_func {
+    use super::*;
+
+    #[tokio::test]
+    async fn no_drift_does_not_sync() {
+        let f = Fixture::new("drift_worker_none").await;
+        let storage = f.apply_storage();
+        let deps = Deps {
+            syncer: &f.syncer,
+            storage: &storage,
+            versions: None,
+        };
+
+        assert!(check(&deps).await.is_empty());
+        assert_eq!(f.syncer.num_sync_calls(), 0);
+        assert_eq!(f.activity().await, DplActivity::Deployed);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn drift_marks_and_syncs() {
+        let f = Fixture::new("drift_worker_drifted").await;
+        std::fs::write(&f.filepath, r#"{"speed":5}"#).unwrap();
+        let storage = f.apply_storage();
+        let deps = Deps {
+            syncer: &f.syncer,
+            storage: &storage,
+            versions: None,
+        };
+
+        assert_eq!(check(&deps).await, vec!["dpl_1".to_string()]);
+        assert_eq!(f.syncer.num_sync_calls(), 1);
+        assert_eq!(f.activity().await, DplActivity::Drifted);
+
+        // already marked so it isn't marked again before it's redeployed
+        assert!(check(&deps).await.is_empty());
+        assert_eq!(f.syncer.num_sync_calls(), 1);
+        f.cleanup().await;
+    }
+}
+
+pub mod run_worker {
+    use super::*;
+
+    #[tokio::test]
+    async fn checks_periodically() {
+        let f = Fixture::new("drift_worker_run").await;
+        std::fs::remove_file(&f.filepath).unwrap();
+        let storage = f.apply_storage();
+        let deps = Deps {
+            syncer: &f.syncer,
+            storage: &storage,
+            versions: None,
+        };
+        let options = Options::default();
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let worker = run(
+            &options,
+            &deps,
+            |_| tokio::time::sleep(Duration::from_millis(1)),
+            Box::pin(async move {
+                let _ = rx.await;
+            }),
+        );
+        let stop = async {
+            while f.syncer.num_sync_calls() == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            let _ = tx.send(());
+        };
+        tokio::time::timeout(Duration::from_secs(5), futures::future::join(worker, stop))
+            .await
+            .unwrap();
+        assert_eq!(f.activity().await, DplActivity::Drifted);
+        f.cleanup().await;
+    }
+}
@@ -0,0 +1,157 @@
+// standard crates
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// internal crates
+use crate::mocks::http_client::{Call, MockClient};
+use crate::mocks::token_manager::MockTokenManager;
+use miru_agent::authn::Token;
+use miru_agent::filesys;
+use miru_agent::http::errors::{HTTPErr, MockErr, RequestFailed};
+use miru_agent::http::request::Params as HttpParams;
+use miru_agent::journal::{self, Journal, Request};
+use miru_agent::workers::journal::{drain, Deps};
+
+struct Fixture {
+    dir: filesys::Dir,
+    http_client: MockClient,
+    token_mngr: MockTokenManager,
+    journal: Journal,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let journal = Journal::init(dir.file("journal.json"), journal::Options::default())
+            .await
+            .unwrap();
+        Self {
+            dir,
+            http_client: MockClient::default(),
+            token_mngr: MockTokenManager::new(Token::default()),
+            journal,
+        }
+    }
+
+    fn deps(&self) -> Deps<'_, MockClient, MockTokenManager> {
+        Deps {
+            http_client: &self.http_client,
+            token_mngr: &self.token_mngr,
+            journal: &self.journal,
+        }
+    }
+
+    async fn enqueue(&self, deployment_id: &str) {
+        self.journal
+            .enqueue(Request::DeploymentStatus {
+                deployment_id: deployment_id.to_string(),
+                updates: Default::default(),
+                metrics: None,
+            })
+            .await
+            .unwrap();
+    }
+}
+
+fn request_failed(status: reqwest::StatusCode) -> HTTPErr {
+    HTTPErr::RequestFailed(RequestFailed {
+        request: HttpParams::get("http://test/deployments/dpl_1")
+            .meta()
+            .unwrap(),
+        status,
+        error: None,
+        trace: miru_agent::trace!(),
+    })
+}
+
+pub mod drain_journal {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_journal_skips_token() {
+        let f = Fixture::new("journal_worker_empty").await;
+        assert!(!drain(&f.deps()).await);
+        assert_eq!(f.token_mngr.num_get_token_calls(), 0);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sends_queued_requests() {
+        let f = Fixture::new("journal_worker_sends").await;
+        f.enqueue("dpl_1").await;
+        assert!(!drain(&f.deps()).await);
+        assert_eq!(f.http_client.call_count(Call::UpdateDeployment), 1);
+        assert!(f.journal.is_empty().await);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_backend() {
+        let f = Fixture::new("journal_worker_unreachable").await;
+        f.enqueue("dpl_1").await;
+        f.http_client.set_update_deployment(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: true,
+            }))
+        });
+        assert!(drain(&f.deps()).await);
+        assert_eq!(f.journal.len().await, 1);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn app_errors_are_not_unreachable() {
+        let f = Fixture::new("journal_worker_app_err").await;
+        f.enqueue("dpl_1").await;
+        f.http_client.set_update_deployment(|| {
+            Err(HTTPErr::MockErr(MockErr {
+                is_network_conn_err: false,
+            }))
+        });
+        assert!(!drain(&f.deps()).await);
+        assert_eq!(f.journal.len().await, 1);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refreshes_rejected_token() {
+        let f = Fixture::new("journal_worker_unauthorized").await;
+        f.enqueue("dpl_1").await;
+        let calls = AtomicUsize::new(0);
+        f.http_client.set_update_deployment(move || {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(request_failed(reqwest::StatusCode::UNAUTHORIZED))
+            } else {
+                Ok(Default::default())
+            }
+        });
+        assert!(!drain(&f.deps()).await);
+        assert_eq!(f.token_mngr.num_refresh_token_calls(), 1);
+        assert_eq!(f.http_client.call_count(Call::UpdateDeployment), 2);
+        assert!(f.journal.is_empty().await);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refreshes_token_once() {
+        let f = Fixture::new("journal_worker_still_unauthorized").await;
+        f.enqueue("dpl_1").await;
+        f.http_client
+            .set_update_deployment(|| Err(request_failed(reqwest::StatusCode::FORBIDDEN)));
+        assert!(!drain(&f.deps()).await);
+        assert_eq!(f.token_mngr.num_refresh_token_calls(), 1);
+        assert_eq!(f.http_client.call_count(Call::UpdateDeployment), 2);
+        assert_eq!(f.journal.len().await, 1);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn backs_off_on_server_errors() {
+        let f = Fixture::new("journal_worker_server_err").await;
+        f.enqueue("dpl_1").await;
+        f.http_client
+            .set_update_deployment(|| Err(request_failed(reqwest::StatusCode::BAD_GATEWAY)));
+        assert!(drain(&f.deps()).await);
+        assert_eq!(f.journal.len().await, 1);
+        f.dir.delete().await.unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+// standard crates
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::{
+    error::SleepController, http_client::Call, http_client::MockClient, syncer::MockSyncer,
+    token_manager::MockTokenManager,
+};
+use backend_api::models::SyncDevice;
+use miru_agent::authn::Token;
+use miru_agent::filesys;
+use miru_agent::http::errors::{MockErr, RequestFailed};
+use miru_agent::http::request::Params;
+use miru_agent::http::HTTPErr;
+use miru_agent::models::Device;
+use miru_agent::storage::{self, Layout};
+use miru_agent::sync::syncer::State;
+use miru_agent::workers::long_poll;
+
+// external crates
+use chrono::{TimeDelta, Utc};
+
+struct Fixture {
+    http_client: Arc<MockClient>,
+    token_mngr: Arc<MockTokenManager>,
+    syncer: Arc<MockSyncer>,
+    sleep_ctrl: Arc<SleepController>,
+    _dir: filesys::Dir,
+}
+
+impl Fixture {
+    async fn spawn(name: &str, http_client: MockClient) -> Self {
+        let dir = filesys::Dir::create_temp_dir(name).await.unwrap();
+        let layout = Layout::new(dir.clone());
+        let device = Device {
+            id: "dvc_1".to_string(),
+            ..Device::default()
+        };
+        let (device_stor, _) = storage::Device::spawn_with_default(64, layout.device(), device)
+            .await
+            .unwrap();
+
+        let fixture = Self {
+            http_client: Arc::new(http_client),
+            token_mngr: Arc::new(MockTokenManager::new(Token::default())),
+            syncer: Arc::new(MockSyncer::default()),
+            sleep_ctrl: Arc::new(SleepController::new()),
+            _dir: dir,
+        };
+
+        let http_client = fixture.http_client.clone();
+        let token_mngr = fixture.token_mngr.clone();
+        let syncer = fixture.syncer.clone();
+        let sleep_ctrl = fixture.sleep_ctrl.clone();
+        tokio::spawn(async move {
+            long_poll::run(
+                &long_poll::Options::default(),
+                http_client.as_ref(),
+                token_mngr.as_ref(),
+                syncer.as_ref(),
+                &device_stor,
+                sleep_ctrl.sleep_fn(),
+                Box::pin(std::future::pending::<()>()),
+            )
+            .await;
+        });
+        fixture
+    }
+
+    async fn next_sleep(&self) -> Duration {
+        self.sleep_ctrl.await_sleep().await;
+        let sleep = self.sleep_ctrl.get_last_attempted_sleep().unwrap();
+        self.sleep_ctrl.release().await;
+        sleep
+    }
+}
+
+fn unsynced() -> MockClient {
+    let http_client = MockClient::default();
+    http_client.set_wait_for_sync(|| Ok(SyncDevice { is_synced: false }));
+    http_client
+}
+
+fn assert_about(actual: Duration, expected: Duration) {
+    assert!(actual <= expected, "{actual:?} > {expected:?}");
+    assert!(
+        actual + Duration::from_secs(1) >= expected,
+        "{actual:?} < {expected:?}"
+    );
+}
+
+#[tokio::test]
+async fn synced_device_polls_again_after_the_min_interval() {
+    let f = Fixture::spawn("long_poll_synced", MockClient::default()).await;
+    let min_interval = long_poll::Options::default().min_interval;
+
+    assert_about(f.next_sleep().await, min_interval);
+    assert_about(f.next_sleep().await, min_interval);
+
+    assert_eq!(f.syncer.num_sync_calls(), 0);
+    let requests = f.http_client.requests();
+    assert!(requests.len() >= 2);
+    for request in requests {
+        assert_eq!(request.call, Call::WaitForSync);
+        assert_eq!(request.path, "/devices/dvc_1/sync");
+        assert_eq!(request.query, vec![("wait_secs".into(), "300".into())]);
+    }
+}
+
+#[tokio::test]
+async fn unsynced_device_syncs() {
+    let f = Fixture::spawn("long_poll_unsynced", unsynced()).await;
+
+    assert_about(
+        f.next_sleep().await,
+        long_poll::Options::default().min_interval,
+    );
+    assert_eq!(f.syncer.num_sync_calls(), 1);
+}
+
+#[tokio::test]
+async fn waits_for_the_syncer_cooldown() {
+    let f = Fixture::spawn("long_poll_cooldown", unsynced()).await;
+    f.syncer.set_state(State {
+        last_attempted_sync_at: Utc::now(),
+        last_synced_at: Utc::now(),
+        cooldown_ends_at: Utc::now() + TimeDelta::seconds(120),
+        err_streak: 1,
+        syncs: 0,
+    });
+
+    // the first poll may have raced the cooldown
+    f.next_sleep().await;
+    let sleep = f.next_sleep().await;
+    assert!(sleep >= Duration::from_secs(110), "{sleep:?}");
+    assert!(sleep <= Duration::from_secs(120), "{sleep:?}");
+}
+
+#[tokio::test]
+async fn errors_back_off() {
+    let http_client = MockClient::default();
+    http_client.set_wait_for_sync(|| {
+        Err(HTTPErr::MockErr(MockErr {
+            is_network_conn_err: true,
+        }))
+    });
+    let f = Fixture::spawn("long_poll_errors", http_client).await;
+
+    let backoff = long_poll::Options::default().backoff;
+    for i in 0..10 {
+        let expected = miru_agent::cooldown::calc(&backoff, i);
+        assert_eq!(f.next_sleep().await, Duration::from_secs(expected as u64));
+    }
+
+    // a successful poll resets the backoff
+    f.http_client
+        .set_wait_for_sync(|| Ok(SyncDevice { is_synced: true }));
+    f.next_sleep().await;
+    f.http_client.set_wait_for_sync(|| {
+        Err(HTTPErr::MockErr(MockErr {
+            is_network_conn_err: true,
+        }))
+    });
+    let sleep = f.next_sleep().await;
+    assert!(
+        sleep <= Duration::from_secs(backoff.base_secs as u64 * 2),
+        "{sleep:?}"
+    );
+}
+
+#[tokio::test]
+async fn unauthorized_refreshes_the_token() {
+    let http_client = MockClient::default();
+    http_client.set_wait_for_sync(|| {
+        Err(HTTPErr::RequestFailed(RequestFailed {
+            request: Params::get("http://mock/devices/dvc_1/sync")
+                .meta()
+                .unwrap(),
+            status: reqwest::StatusCode::UNAUTHORIZED,
+            error: None,
+            trace: miru_agent::trace!(),
+        }))
+    });
+    let f = Fixture::spawn("long_poll_unauthorized", http_client).await;
+
+    f.next_sleep().await;
+    assert_eq!(f.token_mngr.num_refresh_token_calls(), 1);
+    assert_eq!(f.syncer.num_sync_calls(), 0);
+}
+
+#[tokio::test]
+async fn shutdown() {
+    let dir = filesys::Dir::create_temp_dir("long_poll_shutdown")
+        .await
+        .unwrap();
+    let (device_stor, _) =
+        storage::Device::spawn_with_default(64, Layout::new(dir).device(), Device::default())
+            .await
+            .unwrap();
+    let http_client = MockClient::default();
+
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        long_poll::run(
+            &long_poll::Options::default(),
+            &http_client,
+            &MockTokenManager::new(Token::default()),
+            &MockSyncer::default(),
+            &device_stor,
+            tokio::time::sleep,
+            Box::pin(async {}),
+        ),
+    )
+    .await
+    .unwrap();
+}
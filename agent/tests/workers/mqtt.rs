@@ -1,22 +1,32 @@
 // internal crates
 use crate::mocks::{mqtt_client::MockClient, syncer::MockSyncer, token_manager::MockTokenManager};
 use miru_agent::authn::Token;
-use miru_agent::filesys;
+use miru_agent::filesys::{self, PathExt};
 use miru_agent::models::{Device, DeviceStatus};
 use miru_agent::mqtt::client::Client;
 use miru_agent::mqtt::device::{Ping, SyncDevice};
 use miru_agent::mqtt::errors::MockErr;
-use miru_agent::mqtt::options::Options;
-use miru_agent::mqtt::{topics, MQTTError};
+use miru_agent::mqtt::options::{ConnectAddress, Options, Protocol};
+use miru_agent::mqtt::probe::{self, Probe};
+use miru_agent::mqtt::{client::poll, topics, MQTTError};
+use miru_agent::network::MqttHost;
+use miru_agent::notifications::MqttMessage;
 use miru_agent::storage::{self, Layout};
 use miru_agent::sync::errors::MockErr as SyncMockErr;
 use miru_agent::sync::syncer::{CooldownEnd, SyncEvent, SyncFailure};
 use miru_agent::sync::SyncErr;
-use miru_agent::workers::mqtt::{self, handle_error, handle_event, handle_syncer_event};
+use miru_agent::workers::mqtt::{
+    self, disconnect_cleanly, handle_command_event, handle_error, handle_event,
+    handle_probe_deadline, handle_probe_event, handle_syncer_event, publish_outbox_msg,
+    set_credentials,
+};
 
 // external crates
 use chrono::Utc;
 use rumqttc::{ConnAck, ConnectReturnCode, Event, Incoming, Publish, QoS};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::oneshot;
 
 pub mod handle_syncer_event {
     use super::*;
@@ -51,6 +61,21 @@ pub mod handle_syncer_event {
     }
 }
 
+pub mod publish_outbox {
+    use super::*;
+
+    #[tokio::test]
+    async fn publishes_queued_message() {
+        let mqtt_client = MockClient::default();
+        let msg = MqttMessage {
+            topic: "robots/alerts".to_string(),
+            payload: b"{}".to_vec(),
+        };
+        publish_outbox_msg(&msg, &mqtt_client).await;
+        assert_eq!(mqtt_client.num_publish_calls_to("robots/alerts"), 1);
+    }
+}
+
 pub mod handle_connection_events {
     use super::*;
 
@@ -109,6 +134,12 @@ pub mod handle_connection_events {
         assert_eq!(device.status, DeviceStatus::Online);
         assert!(device.last_connected_at >= before_event);
         assert!(device.last_connected_at <= Utc::now());
+
+        // the retained state replaces the last will
+        assert_eq!(
+            mqtt_client.num_publish_calls_to(&topics::device_state("device_id")),
+            1
+        );
     }
 
     #[tokio::test]
@@ -323,6 +354,465 @@ pub mod handle_ping_events {
     }
 }
 
+pub mod subscription_probe {
+    use super::*;
+
+    fn probe_event(device_id: &str, message_id: &str) -> Event {
+        let payload = Ping {
+            message_id: message_id.to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        Event::Incoming(Incoming::Publish(Publish::new(
+            topics::device_probe(device_id),
+            QoS::AtLeastOnce,
+            serde_json::to_vec(&payload).unwrap(),
+        )))
+    }
+
+    fn new_probe() -> Probe {
+        Probe::new(
+            probe::Options {
+                interval: std::time::Duration::ZERO,
+                timeout: std::time::Duration::from_secs(30),
+                reconnect_after: 2,
+            },
+            tokio::time::Instant::now(),
+        )
+    }
+
+    #[tokio::test]
+    async fn sends_probes() {
+        let mqtt_client = MockClient::default();
+        let mut probe = new_probe();
+
+        let reconnect = handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        assert!(!reconnect);
+        assert_eq!(
+            mqtt_client.num_publish_calls_to(&topics::device_probe("dvc_1")),
+            1
+        );
+        assert!(probe.pending().is_some());
+    }
+
+    #[tokio::test]
+    async fn returned_probe_is_received() {
+        let mqtt_client = MockClient::default();
+        let mut probe = new_probe();
+        handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        let message_id = probe.pending().unwrap().to_string();
+
+        // probes of other devices are ignored
+        handle_probe_event(&probe_event("dvc_2", &message_id), &mut probe, "dvc_1");
+        assert!(probe.pending().is_some());
+
+        handle_probe_event(&probe_event("dvc_1", &message_id), &mut probe, "dvc_1");
+        assert!(probe.pending().is_none());
+        assert_eq!(probe.failure_streak(), 0);
+    }
+
+    #[tokio::test]
+    async fn lost_probes_resubscribe_then_reconnect() {
+        let mqtt_client = MockClient::default();
+        let mut probe = new_probe();
+
+        // the first lost probe resubscribes
+        handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        let reconnect = handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        assert!(!reconnect);
+        for topic in [
+            topics::device_sync("dvc_1"),
+            topics::device_ping("dvc_1"),
+            topics::device_probe("dvc_1"),
+        ] {
+            assert_eq!(mqtt_client.num_subscribe_calls_to(&topic), 1, "{topic}");
+        }
+
+        // the second in a row reconnects
+        handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        let reconnect = handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        assert!(reconnect);
+        assert_eq!(
+            mqtt_client.num_subscribe_calls_to(&topics::device_probe("dvc_1")),
+            1
+        );
+        assert_eq!(
+            mqtt_client.num_publish_calls_to(&topics::device_probe("dvc_1")),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn connack_resets_the_probe() {
+        let mqtt_client = MockClient::default();
+        let mut probe = new_probe();
+        handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        handle_probe_deadline(&mut probe, &mqtt_client, "dvc_1").await;
+        assert_eq!(probe.failure_streak(), 1);
+
+        let event = Event::Incoming(Incoming::ConnAck(ConnAck {
+            session_present: false,
+            code: ConnectReturnCode::Success,
+        }));
+        handle_probe_event(&event, &mut probe, "dvc_1");
+        assert_eq!(probe.failure_streak(), 0);
+        assert!(probe.pending().is_none());
+    }
+
+    #[tokio::test]
+    async fn probe_events_are_not_handled_as_commands() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let layout = Layout::new(dir);
+        let device = Device::default();
+        let (device_file, _) =
+            storage::Device::spawn_with_default(64, layout.device(), device.clone())
+                .await
+                .unwrap();
+
+        let mqtt_client = MockClient::default();
+        let syncer = MockSyncer::default();
+        let event = probe_event(&device.id, "probe-1");
+        let err_streak =
+            handle_event(&event, &mqtt_client, &syncer, &device.id, &device_file).await;
+        assert_eq!(err_streak, 0);
+        assert!(mqtt_client.get_calls().is_empty());
+    }
+}
+
+pub mod handle_command_events {
+    use super::*;
+    use crate::mocks::mqtt_client::MockCall;
+    use miru_agent::deploy::pause;
+    use miru_agent::logs::{LevelControl, LogLevel};
+    use miru_agent::mqtt::command::{self, Response, Status};
+
+    struct Fixture {
+        device: Device,
+        device_file: storage::Device,
+        mqtt_client: MockClient,
+        syncer: MockSyncer,
+        options: command::Options,
+        _dir: filesys::Dir,
+    }
+
+    impl Fixture {
+        async fn new() -> Self {
+            let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+            let layout = Layout::new(dir.clone());
+            let device = Device {
+                status: DeviceStatus::Online,
+                ..Device::default()
+            };
+            let (device_file, _) =
+                storage::Device::spawn_with_default(64, layout.device(), device.clone())
+                    .await
+                    .unwrap();
+            Self {
+                device,
+                device_file,
+                mqtt_client: MockClient::default(),
+                syncer: MockSyncer::default(),
+                options: command::Options {
+                    log_level: LevelControl::default(),
+                    settings: None,
+                    pause: Some(pause::Switch::new(layout.deployments_paused())),
+                },
+                _dir: dir,
+            }
+        }
+
+        async fn send(&self, topic: String, payload: serde_json::Value) {
+            let event = Event::Incoming(Incoming::Publish(Publish::new(
+                topic,
+                QoS::AtLeastOnce,
+                payload.to_string(),
+            )));
+            handle_command_event(
+                &event,
+                &self.options,
+                &self.mqtt_client,
+                &self.syncer,
+                &self.device.id,
+                &self.device_file,
+            )
+            .await;
+        }
+
+        async fn command(&self, command: serde_json::Value) -> Response {
+            let topic = topics::device_command(&self.device.id);
+            self.send(topic, json!({"message_id": "msg-1", "command": command}))
+                .await;
+            let responses = self.responses();
+            assert_eq!(responses.len(), 1, "{responses:?}");
+            let response = responses.into_iter().next().unwrap();
+            assert_eq!(response.message_id, "msg-1");
+            response
+        }
+
+        fn responses(&self) -> Vec<Response> {
+            let topic = topics::device_command_response(&self.device.id);
+            self.mqtt_client
+                .get_calls()
+                .into_iter()
+                .filter_map(|call| match call {
+                    MockCall::Publish {
+                        topic: t, payload, ..
+                    } if t == topic => Some(serde_json::from_slice(&payload).unwrap()),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_now_syncs_during_cooldown() {
+        let fixture = Fixture::new().await;
+        fixture.syncer.set_state(miru_agent::sync::syncer::State {
+            cooldown_ends_at: Utc::now() + chrono::TimeDelta::minutes(5),
+            ..Default::default()
+        });
+
+        let response = fixture.command(json!({"name": "sync_now"})).await;
+        assert_eq!(response.status, Status::Succeeded);
+        assert_eq!(response.command.as_deref(), Some("sync_now"));
+        assert_eq!(fixture.syncer.num_sync_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_now_reports_sync_errors() {
+        let fixture = Fixture::new().await;
+        fixture.syncer.set_sync(|| {
+            Err(SyncErr::MockErr(SyncMockErr {
+                is_network_conn_err: true,
+            }))
+        });
+
+        let response = fixture.command(json!({"name": "sync_now"})).await;
+        assert_eq!(response.status, Status::Failed);
+        assert!(response.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn set_log_level() {
+        let fixture = Fixture::new().await;
+        let response = fixture
+            .command(json!({"name": "set_log_level", "level": "trace"}))
+            .await;
+        assert_eq!(response.status, Status::Succeeded);
+        assert_eq!(fixture.options.log_level.level(), LogLevel::Trace);
+    }
+
+    #[tokio::test]
+    async fn set_log_level_rejects_unknown_levels() {
+        let fixture = Fixture::new().await;
+        let response = fixture
+            .command(json!({"name": "set_log_level", "level": "loud"}))
+            .await;
+        assert_eq!(response.status, Status::Failed);
+        assert_eq!(fixture.options.log_level.level(), LogLevel::Info);
+    }
+
+    #[tokio::test]
+    async fn report_health() {
+        let fixture = Fixture::new().await;
+        let response = fixture.command(json!({"name": "report_health"})).await;
+        assert_eq!(response.status, Status::Succeeded);
+        let result = response.result.unwrap();
+        assert_eq!(result["device_status"], "online");
+        assert_eq!(result["agent_version"], miru_agent::version::VERSION);
+        assert_eq!(result["sync_in_cooldown"], false);
+    }
+
+    #[tokio::test]
+    async fn reload_settings_without_a_source_fails() {
+        let fixture = Fixture::new().await;
+        let response = fixture.command(json!({"name": "reload_settings"})).await;
+        assert_eq!(response.status, Status::Failed);
+    }
+
+    #[tokio::test]
+    async fn reload_settings_applies_the_log_level() {
+        let mut fixture = Fixture::new().await;
+        let file = fixture._dir.file("settings.json");
+        file.write_json(
+            &json!({"log_level": "error", "enable_poller": false}),
+            filesys::WriteOptions::OVERWRITE_ATOMIC,
+        )
+        .await
+        .unwrap();
+        fixture.options.settings = Some(command::SettingsSource {
+            file,
+            remote_file: fixture._dir.file("remote_settings.json"),
+            overrides: Vec::new(),
+            applied: serde_json::to_value(storage::Settings::default()).unwrap(),
+        });
+
+        let response = fixture.command(json!({"name": "reload_settings"})).await;
+        assert_eq!(response.status, Status::Succeeded);
+        assert_eq!(
+            response.result.unwrap(),
+            json!({"log_level": "error", "restart_required": true})
+        );
+        assert_eq!(fixture.options.log_level.level(), LogLevel::Error);
+    }
+
+    #[tokio::test]
+    async fn set_remote_settings_applies_the_log_level() {
+        let mut fixture = Fixture::new().await;
+        let file = fixture._dir.file("settings.json");
+        file.write_json(&json!({}), filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let remote_file = fixture._dir.file("remote_settings.json");
+        fixture.options.settings = Some(command::SettingsSource {
+            file,
+            remote_file: remote_file.clone(),
+            overrides: Vec::new(),
+            applied: serde_json::to_value(storage::Settings::default()).unwrap(),
+        });
+
+        let response = fixture
+            .command(json!({"name": "set_remote_settings", "settings": {"log_level": "warn"}}))
+            .await;
+        assert_eq!(response.status, Status::Succeeded);
+        assert_eq!(
+            response.result.unwrap(),
+            json!({"log_level": "warn", "restart_required": false})
+        );
+        assert_eq!(fixture.options.log_level.level(), LogLevel::Warn);
+        assert_eq!(
+            remote_file.read_json::<serde_json::Value>().await.unwrap(),
+            json!({"log_level": "warn"})
+        );
+    }
+
+    #[tokio::test]
+    async fn set_remote_settings_refuses_hooks() {
+        let mut fixture = Fixture::new().await;
+        let file = fixture._dir.file("settings.json");
+        file.write_json(&json!({}), filesys::WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+        let remote_file = fixture._dir.file("remote_settings.json");
+        fixture.options.settings = Some(command::SettingsSource {
+            file,
+            remote_file: remote_file.clone(),
+            overrides: Vec::new(),
+            applied: serde_json::to_value(storage::Settings::default()).unwrap(),
+        });
+
+        let response = fixture
+            .command(json!({
+                "name": "set_remote_settings",
+                "settings": {"log_level": "warn", "hooks": {"pre_deploy": ["id"]}},
+            }))
+            .await;
+        assert_eq!(response.status, Status::Failed);
+        assert!(response.error.unwrap().contains("hooks"));
+        assert_ne!(fixture.options.log_level.level(), LogLevel::Warn);
+        assert!(!remote_file.exists());
+    }
+
+    #[tokio::test]
+    async fn pause_deployments() {
+        let fixture = Fixture::new().await;
+        let response = fixture
+            .command(json!({"name": "pause_deployments", "reason": "INC-42"}))
+            .await;
+        assert_eq!(response.status, Status::Succeeded);
+        let result = response.result.unwrap();
+        assert_eq!(result["paused"], true);
+        assert_eq!(result["changed_by"], "mqtt");
+        assert_eq!(result["reason"], "INC-42");
+
+        let switch = fixture.options.pause.as_ref().unwrap();
+        assert!(switch.is_paused().await);
+        assert_eq!(fixture.syncer.num_sync_calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn resume_deployments_syncs() {
+        let fixture = Fixture::new().await;
+        let switch = fixture.options.pause.as_ref().unwrap();
+        switch.pause(None, pause::Origin::Cli).await.unwrap();
+
+        let response = fixture.command(json!({"name": "resume_deployments"})).await;
+        assert_eq!(response.status, Status::Succeeded);
+        assert_eq!(response.result.unwrap()["paused"], false);
+        assert!(!switch.is_paused().await);
+        assert_eq!(fixture.syncer.num_sync_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn resume_deployments_succeeds_when_the_sync_fails() {
+        let fixture = Fixture::new().await;
+        fixture.syncer.set_sync(|| {
+            Err(SyncErr::MockErr(SyncMockErr {
+                is_network_conn_err: true,
+            }))
+        });
+
+        let response = fixture.command(json!({"name": "resume_deployments"})).await;
+        assert_eq!(response.status, Status::Succeeded);
+        assert_eq!(fixture.syncer.num_sync_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn pause_deployments_without_a_switch_fails() {
+        let mut fixture = Fixture::new().await;
+        fixture.options.pause = None;
+        let response = fixture.command(json!({"name": "pause_deployments"})).await;
+        assert_eq!(response.status, Status::Failed);
+    }
+
+    #[tokio::test]
+    async fn unknown_commands_are_answered() {
+        let fixture = Fixture::new().await;
+        let response = fixture.command(json!({"name": "reboot"})).await;
+        assert_eq!(response.status, Status::Failed);
+        assert_eq!(response.command.as_deref(), Some("reboot"));
+        assert_eq!(fixture.syncer.num_sync_calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn unparsable_requests_are_dropped() {
+        let fixture = Fixture::new().await;
+        let topic = topics::device_command(&fixture.device.id);
+        fixture.send(topic, json!("invalid")).await;
+        assert!(fixture.mqtt_client.get_calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn other_topics_are_ignored() {
+        let fixture = Fixture::new().await;
+        let topic = topics::device_ping(&fixture.device.id);
+        fixture
+            .send(
+                topic,
+                json!({"message_id": "msg-1", "command": {"name": "sync_now"}}),
+            )
+            .await;
+        assert!(fixture.mqtt_client.get_calls().is_empty());
+        assert_eq!(fixture.syncer.num_sync_calls(), 0);
+
+        // handle_event leaves commands to handle_command_event
+        let event = Event::Incoming(Incoming::Publish(Publish::new(
+            topics::device_command(&fixture.device.id),
+            QoS::AtLeastOnce,
+            json!({"message_id": "msg-1", "command": {"name": "sync_now"}}).to_string(),
+        )));
+        handle_event(
+            &event,
+            &fixture.mqtt_client,
+            &fixture.syncer,
+            &fixture.device.id,
+            &fixture.device_file,
+        )
+        .await;
+        assert!(fixture.mqtt_client.get_calls().is_empty());
+        assert_eq!(fixture.syncer.num_sync_calls(), 0);
+    }
+}
+
 pub mod handle_mqtt_error {
     use super::*;
 
@@ -367,7 +857,7 @@ pub mod handle_mqtt_error {
             error,
             &device,
             &token_mngr,
-            &options.connect_address,
+            &mqtt::Options::default(),
             &device_file,
         )
         .await;
@@ -426,7 +916,7 @@ pub mod handle_mqtt_error {
             error,
             &device,
             &token_mngr,
-            &options.connect_address,
+            &mqtt::Options::default(),
             &device_file,
         )
         .await;
@@ -488,7 +978,7 @@ pub mod handle_mqtt_error {
             error,
             &device,
             &token_mngr,
-            &options.connect_address,
+            &mqtt::Options::default(),
             &device_file,
         )
         .await;
@@ -507,3 +997,125 @@ pub mod handle_mqtt_error {
         assert!(device.last_disconnected_at <= Utc::now());
     }
 }
+
+pub mod token_rotation {
+    use super::*;
+
+    #[tokio::test]
+    async fn reconnects_use_the_rotated_token() {
+        let options = Options::default();
+        let (_client, mut eventloop) = Client::new(&options).await;
+
+        let token = Token {
+            token: "rotated".to_string(),
+            expires_at: Utc::now(),
+        };
+        set_credentials(&mut eventloop, "device_session_id", &token);
+
+        let login = eventloop.mqtt_options.credentials().unwrap();
+        assert_eq!(login.username, "device_session_id");
+        assert_eq!(login.password, "rotated");
+    }
+}
+
+pub mod last_will {
+    use super::*;
+
+    struct Received {
+        connect: Vec<u8>,
+        after_connack: Vec<u8>,
+    }
+
+    /// A broker which accepts one connection and records what the client sends until
+    /// it disconnects.
+    async fn stub_broker() -> (u16, oneshot::Receiver<Received>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            let connect = buf[..n].to_vec();
+            // CONNACK accepting the connection
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+            let mut after_connack = Vec::new();
+            // DISCONNECT ends the connection
+            while !after_connack.ends_with(&[0xE0, 0x00]) {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                after_connack.extend_from_slice(&buf[..n]);
+            }
+            let _ = tx.send(Received {
+                connect,
+                after_connack,
+            });
+        });
+        (port, rx)
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[tokio::test]
+    async fn clean_disconnect_marks_the_device_stopped() {
+        let (port, received) = stub_broker().await;
+        let address =
+            ConnectAddress::new(MqttHost::new("localhost").unwrap(), Protocol::TCP, port).unwrap();
+        let options = Options::default()
+            .with_connect_address(address)
+            .with_client_id("dvc_1".to_string())
+            .with_last_will(miru_agent::mqtt::device::last_will("dvc_1"));
+        let (client, mut eventloop) = Client::new(&options).await;
+        loop {
+            if let Event::Incoming(Incoming::ConnAck(_)) = poll(&mut eventloop).await.unwrap() {
+                break;
+            }
+        }
+        let mut state = mqtt::State {
+            client,
+            eventloop,
+            err_streak: 0,
+        };
+
+        disconnect_cleanly(&mut state, "dvc_1").await;
+
+        let received = received.await.unwrap();
+        // the will is registered when connecting
+        let topic = topics::device_state("dvc_1");
+        assert!(contains(&received.connect, topic.as_bytes()));
+        assert!(contains(&received.connect, b"connection_lost"));
+        // and the retained stopped state is published before disconnecting
+        assert!(contains(&received.after_connack, topic.as_bytes()));
+        assert!(contains(&received.after_connack, b"\"stopped\""));
+        assert!(received.after_connack.ends_with(&[0xE0, 0x00]));
+    }
+
+    #[tokio::test]
+    async fn clean_disconnect_without_a_broker_gives_up() {
+        // nothing listens on the port
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let address =
+            ConnectAddress::new(MqttHost::new("localhost").unwrap(), Protocol::TCP, port).unwrap();
+        let options = Options::default().with_connect_address(address);
+        let (client, eventloop) = Client::new(&options).await;
+        let mut state = mqtt::State {
+            client,
+            eventloop,
+            err_streak: 0,
+        };
+
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            disconnect_cleanly(&mut state, "dvc_1"),
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+// standard crates
+use std::future::{self, Ready};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::http_client as mock;
+use miru_agent::cooldown;
+use miru_agent::events::EventArgs;
+use miru_agent::filesys::{File, PathExt, WriteOptions};
+use miru_agent::models::Deployment;
+use miru_agent::notifications::{webhook, Notification, Retry, Severity, Sink, Target};
+use miru_agent::testkit;
+use miru_agent::workers::notifications::{self, Deps, Options};
+
+// external crates
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use tokio::sync::mpsc;
+
+/// A sleep which returns immediately, recording the delays requested from it.
+fn recording_sleep() -> (Arc<Mutex<Vec<Duration>>>, impl Fn(Duration) -> Ready<()>) {
+    let delays = Arc::new(Mutex::new(Vec::new()));
+    let recorded = delays.clone();
+    let sleep_fn = move |delay| {
+        recorded.lock().unwrap().push(delay);
+        future::ready(())
+    };
+    (delays, sleep_fn)
+}
+
+pub mod run {
+    use super::*;
+
+    #[tokio::test]
+    async fn delivers_events_at_or_above_min_severity() {
+        let dir = testkit::temp_dir("notifications_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
+        let file = dir.file("notifications.jsonl");
+        let options = Options {
+            sinks: vec![Sink {
+                target: Target::File {
+                    path: file.path().to_string_lossy().to_string(),
+                },
+                min_severity: Severity::Critical,
+                events: Vec::new(),
+                retry: None,
+            }],
+        };
+
+        let webhook_keys = dir.file("webhook_keys.json");
+        let (shutdown_tx, mut shutdown_rx) = tokio::sync::broadcast::channel::<()>(1);
+        let events = event_hub.subscribe();
+        let handle = tokio::spawn(async move {
+            let webhook_client = webhook::Client::new().unwrap();
+            let (mqtt_outbox, _rx) = mpsc::channel(1);
+            let deps = Deps {
+                webhook_client: &webhook_client,
+                mqtt_outbox: &mqtt_outbox,
+                webhook_keys: &webhook_keys,
+            };
+            notifications::run(
+                &options,
+                &deps,
+                tokio::time::sleep,
+                events,
+                Box::pin(async move {
+                    let _ = shutdown_rx.recv().await;
+                }),
+            )
+            .await;
+        });
+
+        let deployment = Deployment {
+            id: "dpl-1".to_string(),
+            ..Default::default()
+        };
+        // info severity so it is filtered out
+        event_hub
+            .publish(EventArgs::deployed(&deployment).unwrap())
+            .await
+            .unwrap();
+        event_hub
+            .publish(EventArgs::failed(&deployment, "disk full").unwrap())
+            .await
+            .unwrap();
+
+        // wait for the worker to write the notification
+        let mut contents = String::new();
+        for _ in 0..100 {
+            contents = file.read_string().await.unwrap_or_default();
+            if !contents.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let lines: Vec<Notification> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].event_type, "deployment.failed");
+        assert_eq!(lines[0].severity, Severity::Critical);
+
+        shutdown_tx.send(()).unwrap();
+        handle.await.unwrap();
+    }
+}
+
+pub mod notify {
+    use super::*;
+
+    #[tokio::test]
+    async fn failing_sink_does_not_block_others() {
+        let webhook_client = webhook::Client::new().unwrap();
+        let (mqtt_outbox, mut rx) = mpsc::channel(1);
+        let webhook_keys = File::new("/nonexistent/webhook_keys.json");
+        let deps = Deps {
+            webhook_client: &webhook_client,
+            mqtt_outbox: &mqtt_outbox,
+            webhook_keys: &webhook_keys,
+        };
+        let sinks = vec![
+            Sink {
+                target: Target::Command {
+                    command: vec!["false".to_string()],
+                    timeout_secs: 10,
+                },
+                min_severity: Severity::Info,
+                events: Vec::new(),
+                retry: None,
+            },
+            Sink {
+                target: Target::Mqtt {
+                    topic: "robots/alerts".to_string(),
+                },
+                min_severity: Severity::Info,
+                events: Vec::new(),
+                retry: None,
+            },
+        ];
+        let notification = Notification {
+            event_type: "disk.low".to_string(),
+            severity: Severity::Warning,
+            message: "disk space is low".to_string(),
+            occurred_at: chrono::Utc::now(),
+            data: serde_json::json!({}),
+        };
+
+        let (delays, sleep_fn) = recording_sleep();
+        notifications::notify(&sinks, &deps, &notification, &sleep_fn).await;
+
+        assert!(delays.lock().unwrap().is_empty());
+        let msg = rx.try_recv().unwrap();
+        assert_eq!(msg.topic, "robots/alerts");
+    }
+
+    #[tokio::test]
+    async fn unreadable_keyring_skips_webhooks() {
+        let dir = testkit::temp_dir("notifications_unreadable_keyring").await;
+        let webhook_keys = dir.file("webhook_keys.json");
+        webhook_keys
+            .write_string("not json", WriteOptions::OVERWRITE_ATOMIC)
+            .await
+            .unwrap();
+
+        let posts = Arc::new(AtomicUsize::new(0));
+        let posts_for_route = posts.clone();
+        let router = Router::new().route(
+            "/alerts",
+            post(move || async move {
+                posts_for_route.fetch_add(1, Ordering::SeqCst);
+                "ok"
+            }),
+        );
+        let server = mock::run_server(router).await;
+
+        let webhook_client = webhook::Client::new().unwrap();
+        let (mqtt_outbox, mut rx) = mpsc::channel(1);
+        let deps = Deps {
+            webhook_client: &webhook_client,
+            mqtt_outbox: &mqtt_outbox,
+            webhook_keys: &webhook_keys,
+        };
+        let sinks = vec![
+            Sink {
+                target: Target::Webhook {
+                    url: format!("{}/alerts", server.base_url),
+                },
+                min_severity: Severity::Info,
+                events: Vec::new(),
+                retry: None,
+            },
+            Sink {
+                target: Target::Mqtt {
+                    topic: "robots/alerts".to_string(),
+                },
+                min_severity: Severity::Info,
+                events: Vec::new(),
+                retry: None,
+            },
+        ];
+        let notification = Notification {
+            event_type: "disk.low".to_string(),
+            severity: Severity::Warning,
+            message: "disk space is low".to_string(),
+            occurred_at: chrono::Utc::now(),
+            data: serde_json::json!({}),
+        };
+
+        let (_, sleep_fn) = recording_sleep();
+        notifications::notify(&sinks, &deps, &notification, &sleep_fn).await;
+
+        assert_eq!(posts.load(Ordering::SeqCst), 0);
+        assert_eq!(rx.try_recv().unwrap().topic, "robots/alerts");
+        dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn retries_failed_deliveries() {
+        let dir = testkit::temp_dir("notifications_retry").await;
+        let webhook_keys = dir.file("webhook_keys.json");
+
+        // the receiver fails the first two deliveries
+        let posts = Arc::new(AtomicUsize::new(0));
+        let posts_for_route = posts.clone();
+        let router = Router::new().route(
+            "/hooks",
+            post(move || async move {
+                match posts_for_route.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::OK,
+                }
+            }),
+        );
+        let server = mock::run_server(router).await;
+
+        let webhook_client = webhook::Client::new().unwrap();
+        let (mqtt_outbox, _rx) = mpsc::channel(1);
+        let deps = Deps {
+            webhook_client: &webhook_client,
+            mqtt_outbox: &mqtt_outbox,
+            webhook_keys: &webhook_keys,
+        };
+        let retry = Retry {
+            max_attempts: 3,
+            backoff: cooldown::Backoff {
+                base_secs: 5,
+                growth_factor: 2,
+                max_secs: 60,
+            },
+        };
+        let sinks = vec![Sink {
+            target: Target::Webhook {
+                url: format!("{}/hooks", server.base_url),
+            },
+            min_severity: Severity::Warning,
+            events: vec!["deployment.deployed".to_string()],
+            retry: Some(retry),
+        }];
+        let notification = Notification {
+            event_type: "deployment.deployed".to_string(),
+            severity: Severity::Info,
+            message: "deployment dpl-1 deployed".to_string(),
+            occurred_at: chrono::Utc::now(),
+            data: serde_json::json!({"deployment_id": "dpl-1"}),
+        };
+
+        let (delays, sleep_fn) = recording_sleep();
+        notifications::notify(&sinks, &deps, &notification, &sleep_fn).await;
+        assert_eq!(posts.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            *delays.lock().unwrap(),
+            vec![Duration::from_secs(5), Duration::from_secs(10)]
+        );
+
+        // gives up after the last attempt
+        posts.store(0, Ordering::SeqCst);
+        let sinks = vec![Sink {
+            retry: Some(Retry {
+                max_attempts: 2,
+                ..retry
+            }),
+            ..sinks[0].clone()
+        }];
+        let (delays, sleep_fn) = recording_sleep();
+        notifications::notify(&sinks, &deps, &notification, &sleep_fn).await;
+        assert_eq!(posts.load(Ordering::SeqCst), 2);
+        assert_eq!(*delays.lock().unwrap(), vec![Duration::from_secs(5)]);
+        dir.delete().await.unwrap();
+    }
+}
@@ -14,6 +14,70 @@ use miru_agent::workers::poller;
 // external crates
 use chrono::{TimeDelta, Utc};
 
+pub mod jitter {
+    use super::*;
+
+    #[test]
+    fn within_the_max() {
+        assert_eq!(poller::jitter(0), 0);
+        assert_eq!(poller::jitter(-5), 0);
+        for _ in 0..100 {
+            let jitter = poller::jitter(60);
+            assert!((0..=60).contains(&jitter), "{jitter}");
+        }
+    }
+
+    #[tokio::test]
+    async fn added_to_the_poll_interval() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let layout = Layout::new(dir);
+
+        let (device_file, _) =
+            storage::Device::spawn_with_default(64, layout.device(), Device::default())
+                .await
+                .unwrap();
+
+        let options = poller::Options {
+            poll_interval_secs: 600,
+            jitter_secs: 60,
+        };
+        let syncer = Arc::new(MockSyncer::default());
+        let sleep_ctrl = Arc::new(SleepController::new());
+
+        let syncer_for_spawn = syncer.clone();
+        let sleep_ctrl_for_spawn = sleep_ctrl.clone();
+        let shutdown_signal = Box::pin(async move {
+            std::future::pending::<()>().await;
+        });
+        let _handle = tokio::spawn(async move {
+            poller::run(
+                &options,
+                syncer_for_spawn.as_ref(),
+                &device_file,
+                &poller::Metrics::default(),
+                sleep_ctrl_for_spawn.sleep_fn(),
+                shutdown_signal,
+            )
+            .await;
+        });
+
+        syncer.set_state(State {
+            last_attempted_sync_at: Utc::now(),
+            last_synced_at: Utc::now(),
+            cooldown_ends_at: Utc::now(),
+            err_streak: 0,
+            syncs: 0,
+        });
+
+        for _ in 0..10 {
+            sleep_ctrl.await_sleep().await;
+            let last_sleep = sleep_ctrl.get_last_attempted_sleep().unwrap().as_secs();
+            assert!((599..=660).contains(&last_sleep), "{last_sleep}");
+            sleep_ctrl.release().await;
+        }
+    }
+}
+
 pub mod run {
     use super::*;
 
@@ -42,6 +106,7 @@ pub mod run {
                 &options_for_spawn,
                 syncer_for_spawn.as_ref(),
                 &device_file,
+                &poller::Metrics::default(),
                 sleep_ctrl_for_spawn.sleep_fn(),
                 shutdown_signal,
             )
@@ -54,6 +119,7 @@ pub mod run {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now(),
             err_streak: 0,
+            syncs: 0,
         };
         syncer.set_state(state);
 
@@ -112,6 +178,7 @@ pub mod run {
 
         let options = poller::Options {
             poll_interval_secs: 30,
+            ..Default::default()
         };
         let options_for_spawn = options.clone();
         let syncer = Arc::new(MockSyncer::default());
@@ -127,6 +194,7 @@ pub mod run {
                 &options_for_spawn,
                 syncer_for_spawn.as_ref(),
                 &device_file,
+                &poller::Metrics::default(),
                 sleep_ctrl_for_spawn.sleep_fn(),
                 shutdown_signal,
             )
@@ -139,6 +207,7 @@ pub mod run {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now() + TimeDelta::seconds(secs_until_cooldown_ends),
             err_streak: 0,
+            syncs: 0,
         };
         syncer.set_state(state);
 
@@ -211,6 +280,7 @@ pub mod run {
                 &options_for_spawn,
                 syncer_for_spawn.as_ref(),
                 &device_file_for_spawn,
+                &poller::Metrics::default(),
                 sleep_ctrl_for_spawn.sleep_fn(),
                 shutdown_signal,
             )
@@ -223,6 +293,7 @@ pub mod run {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now(),
             err_streak: 0,
+            syncs: 0,
         };
         syncer.set_state(state);
 
@@ -280,6 +351,7 @@ pub mod run {
                 &options_for_spawn,
                 syncer_for_spawn.as_ref(),
                 &device_file,
+                &poller::Metrics::default(),
                 sleep_ctrl_for_spawn.sleep_fn(),
                 shutdown_signal,
             )
@@ -291,6 +363,7 @@ pub mod run {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now() - TimeDelta::seconds(10),
             err_streak: 0,
+            syncs: 0,
         };
         syncer.set_state(state);
 
@@ -341,6 +414,7 @@ pub mod run {
                 &options_for_spawn,
                 syncer_for_spawn.as_ref(),
                 &device_file,
+                &poller::Metrics::default(),
                 sleep_ctrl_for_spawn.sleep_fn(),
                 shutdown_signal,
             )
@@ -353,6 +427,7 @@ pub mod run {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now(),
             err_streak: 0,
+            syncs: 0,
         };
         syncer.set_state(state);
 
@@ -403,6 +478,7 @@ pub mod run {
                 &options_for_spawn,
                 syncer_for_spawn.as_ref(),
                 &device_file,
+                &poller::Metrics::default(),
                 sleep_ctrl_for_spawn.sleep_fn(),
                 shutdown_signal,
             )
@@ -415,6 +491,7 @@ pub mod run {
             last_synced_at: Utc::now(),
             cooldown_ends_at: Utc::now() - TimeDelta::seconds(10),
             err_streak: 0,
+            syncs: 0,
         };
         syncer.set_state(state);
 
@@ -434,4 +511,47 @@ pub mod run {
                 .unwrap();
         }
     }
+
+    #[tokio::test]
+    async fn records_runs() {
+        let dir = filesys::Dir::create_temp_dir("testing").await.unwrap();
+        let layout = Layout::new(dir);
+
+        let (device_file, _) =
+            storage::Device::spawn_with_default(64, layout.device(), Device::default())
+                .await
+                .unwrap();
+
+        let syncer = Arc::new(MockSyncer::default());
+        let sleep_ctrl = Arc::new(SleepController::new());
+        let metrics = Arc::new(poller::Metrics::default());
+        assert_eq!(metrics.snapshot(), poller::Snapshot::default());
+
+        let syncer_for_spawn = syncer.clone();
+        let sleep_ctrl_for_spawn = sleep_ctrl.clone();
+        let metrics_for_spawn = metrics.clone();
+        let shutdown_signal = Box::pin(async move {
+            std::future::pending::<()>().await;
+        });
+        let _handle = tokio::spawn(async move {
+            poller::run(
+                &poller::Options::default(),
+                syncer_for_spawn.as_ref(),
+                &device_file,
+                metrics_for_spawn.as_ref(),
+                sleep_ctrl_for_spawn.sleep_fn(),
+                shutdown_signal,
+            )
+            .await;
+        });
+
+        let before = Utc::now();
+        for i in 0..3 {
+            sleep_ctrl.await_sleep().await;
+            let snapshot = metrics.snapshot();
+            assert_eq!(snapshot.runs, i + 1);
+            assert!(snapshot.last_run_at.unwrap() >= before);
+            sleep_ctrl.release().await;
+        }
+    }
 }
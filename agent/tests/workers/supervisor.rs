@@ -0,0 +1,277 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::cooldown;
+use miru_agent::events::EventArgs;
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::supervisor::{health, process};
+use miru_agent::testkit;
+use miru_agent::workers::supervisor::{self, Options, Supervisor};
+
+// external crates
+use tokio::sync::broadcast;
+
+fn sh(script: &str) -> Vec<String> {
+    vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+}
+
+fn options(script: &str) -> Options {
+    Options {
+        app: process::Options {
+            command: sh(script),
+            ..Default::default()
+        },
+        restart_backoff: cooldown::Backoff {
+            base_secs: 0,
+            growth_factor: 2,
+            max_secs: 0,
+        },
+        poll_interval: Duration::from_millis(20),
+        ..Default::default()
+    }
+}
+
+/// Ticks until the supervisor has started the application `starts` times.
+async fn tick_until_started(supervisor: &mut Supervisor, starts: u32) {
+    for _ in 0..250 {
+        if supervisor.status().starts >= starts && supervisor.status().pid.is_some() {
+            return;
+        }
+        supervisor.tick().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the application wasn't started {starts} times");
+}
+
+pub mod supervisor_lifecycle {
+    use super::*;
+
+    #[tokio::test]
+    async fn restarts_an_exited_application() {
+        let mut supervisor = Supervisor::new(options("sleep 0.1; exit 1"));
+        supervisor.start();
+        tick_until_started(&mut supervisor, 3).await;
+
+        let status = supervisor.status();
+        assert!(status.crashes >= 2);
+        assert_eq!(status.last_exit.as_deref(), Some("exit status: 1"));
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn waits_out_the_restart_backoff() {
+        let mut supervisor = Supervisor::new(Options {
+            restart_backoff: cooldown::Backoff {
+                base_secs: 60,
+                growth_factor: 2,
+                max_secs: 60,
+            },
+            ..options("exit 1")
+        });
+        supervisor.start();
+        for _ in 0..10 {
+            supervisor.tick().await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let status = supervisor.status();
+        assert_eq!(status.pid, None);
+        assert_eq!(status.starts, 1);
+        assert_eq!(status.crashes, 1);
+    }
+
+    #[tokio::test]
+    async fn long_runs_reset_the_crashes() {
+        let mut supervisor = Supervisor::new(Options {
+            reset_after: Duration::ZERO,
+            ..options("exit 1")
+        });
+        supervisor.start();
+        tick_until_started(&mut supervisor, 3).await;
+        assert_eq!(supervisor.status().crashes, 1);
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn retries_failed_starts() {
+        let mut supervisor = Supervisor::new(Options {
+            app: process::Options {
+                command: vec!["/nonexistent/robot".to_string()],
+                ..Default::default()
+            },
+            ..options("")
+        });
+        supervisor.start();
+        for _ in 0..3 {
+            supervisor.tick().await;
+        }
+        let status = supervisor.status();
+        assert_eq!(status.pid, None);
+        assert_eq!(status.starts, 4);
+        assert_eq!(status.crashes, 4);
+    }
+
+    #[tokio::test]
+    async fn stopped_applications_stay_stopped() {
+        let mut supervisor = Supervisor::new(options("exec sleep 30"));
+        supervisor.start();
+        assert!(supervisor.status().pid.is_some());
+
+        supervisor.stop().await;
+        for _ in 0..3 {
+            supervisor.tick().await;
+        }
+        let status = supervisor.status();
+        assert_eq!(status.pid, None);
+        assert_eq!(status.starts, 1);
+        assert_eq!(status.crashes, 0);
+        assert!(status.last_exit.is_some());
+    }
+
+    #[tokio::test]
+    async fn restart() {
+        let mut supervisor = Supervisor::new(options("exec sleep 30"));
+        supervisor.start();
+        let pid = supervisor.status().pid.unwrap();
+
+        supervisor.restart("testing").await;
+        let status = supervisor.status();
+        assert_ne!(status.pid, Some(pid));
+        assert!(status.pid.is_some());
+        assert_eq!(status.starts, 2);
+        assert_eq!(status.crashes, 0);
+        supervisor.stop().await;
+    }
+}
+
+pub mod health_check {
+    use super::*;
+
+    fn checked(health_check: &str, failure_threshold: u32) -> Options {
+        Options {
+            health_check: Some(health::Options {
+                command: sh(health_check),
+                interval: Duration::ZERO,
+                failure_threshold,
+                ..Default::default()
+            }),
+            ..options("exec sleep 30")
+        }
+    }
+
+    #[tokio::test]
+    async fn healthy_applications_keep_running() {
+        let mut supervisor = Supervisor::new(checked("exit 0", 1));
+        supervisor.start();
+        let pid = supervisor.status().pid;
+        for _ in 0..3 {
+            supervisor.tick().await;
+        }
+        assert_eq!(supervisor.status().pid, pid);
+        assert_eq!(supervisor.status().starts, 1);
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn restarts_after_consecutive_failures() {
+        let mut supervisor = Supervisor::new(checked("exit 1", 2));
+        supervisor.start();
+        let pid = supervisor.status().pid;
+
+        supervisor.tick().await;
+        assert_eq!(supervisor.status().pid, pid);
+
+        supervisor.tick().await;
+        assert_eq!(supervisor.status().pid, None);
+        assert_eq!(supervisor.status().crashes, 1);
+
+        tick_until_started(&mut supervisor, 2).await;
+        assert_ne!(supervisor.status().pid, pid);
+        supervisor.stop().await;
+    }
+}
+
+pub mod run {
+    use super::*;
+
+    async fn read_lines(file: &filesys::File) -> Vec<String> {
+        tokio::fs::read_to_string(file.path())
+            .await
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    async fn wait_for_lines(file: &filesys::File, n: usize) -> Vec<String> {
+        for _ in 0..250 {
+            let lines = read_lines(file).await;
+            if lines.len() >= n {
+                return lines;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{} doesn't have {n} lines", file.path().display());
+    }
+
+    async fn config_changed(restart_on_config_change: bool) -> Vec<String> {
+        let dir = testkit::temp_dir("supervisor_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
+        let starts = dir.file("starts");
+        let options = Options {
+            restart_on_config_change,
+            ..options(&format!(
+                "echo $$ >> {}; exec sleep 30",
+                starts.path().display()
+            ))
+        };
+
+        let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
+        let events = event_hub.subscribe();
+        let handle = tokio::spawn(async move {
+            supervisor::run(
+                &options,
+                tokio::time::sleep,
+                events,
+                Box::pin(async move {
+                    let _ = shutdown_rx.recv().await;
+                }),
+            )
+            .await;
+        });
+        wait_for_lines(&starts, 1).await;
+
+        event_hub
+            .publish(EventArgs::config_changed(Vec::new()).unwrap())
+            .await
+            .unwrap();
+        if restart_on_config_change {
+            wait_for_lines(&starts, 2).await;
+        } else {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        shutdown_tx.send(()).unwrap();
+        handle.await.unwrap();
+        let lines = read_lines(&starts).await;
+        // the application stops with the worker
+        for pid in &lines {
+            assert!(
+                !std::path::Path::new(&format!("/proc/{pid}")).exists(),
+                "process {pid} is still running"
+            );
+        }
+        dir.delete().await.unwrap();
+        lines
+    }
+
+    #[tokio::test]
+    async fn restarts_on_config_changes() {
+        assert_eq!(config_changed(true).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn ignores_config_changes_if_disabled() {
+        assert_eq!(config_changed(false).await.len(), 1);
+    }
+}
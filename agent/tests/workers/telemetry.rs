@@ -0,0 +1,241 @@
+// standard crates
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::{
+    error::SleepController,
+    http_client::{Call, MockClient},
+    token_manager::MockTokenManager,
+};
+use miru_agent::authn::Token;
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::http::errors::{HTTPErr, MockErr};
+use miru_agent::journal::{self, Journal, Kind, Retention};
+use miru_agent::models::Device;
+use miru_agent::notifications::MqttMessage;
+use miru_agent::storage::{self, Layout};
+use miru_agent::telemetry::{Sample, Sampler};
+use miru_agent::workers::telemetry::{self, Deps, Options, Transport};
+
+// external crates
+use tokio::sync::mpsc;
+
+struct Fixture {
+    dir: filesys::Dir,
+    http_client: Arc<MockClient>,
+    token_mngr: Arc<MockTokenManager>,
+    mqtt_outbox: mpsc::Sender<MqttMessage>,
+    mqtt_rx: mpsc::Receiver<MqttMessage>,
+    device_stor: Arc<storage::Device>,
+    journal: Arc<Journal>,
+}
+
+impl Fixture {
+    async fn new(prefix: &str) -> Self {
+        Self::with_journal(prefix, journal::Options::default()).await
+    }
+
+    async fn with_journal(prefix: &str, options: journal::Options) -> Self {
+        let dir = filesys::Dir::create_temp_dir(prefix).await.unwrap();
+        let device = Device {
+            id: "dvc_1".to_string(),
+            ..Device::default()
+        };
+        let (device_stor, _) =
+            storage::Device::spawn_with_default(64, Layout::new(dir.clone()).device(), device)
+                .await
+                .unwrap();
+        let (mqtt_outbox, mqtt_rx) = mpsc::channel(1);
+        let journal = Journal::init(dir.file("journal.json"), options)
+            .await
+            .unwrap();
+        Self {
+            dir,
+            http_client: Arc::new(MockClient::default()),
+            token_mngr: Arc::new(MockTokenManager::new(Token {
+                token: "token".to_string(),
+                ..Token::default()
+            })),
+            mqtt_outbox,
+            mqtt_rx,
+            device_stor: Arc::new(device_stor),
+            journal: Arc::new(journal),
+        }
+    }
+
+    fn deps(&self) -> Deps<'_, MockClient, MockTokenManager> {
+        Deps {
+            http_client: self.http_client.as_ref(),
+            token_mngr: self.token_mngr.as_ref(),
+            mqtt_outbox: &self.mqtt_outbox,
+            device_stor: self.device_stor.as_ref(),
+            journal: self.journal.as_ref(),
+        }
+    }
+
+    /// The number of samples in each telemetry report.
+    fn reported(&self) -> Vec<usize> {
+        self.http_client
+            .requests()
+            .iter()
+            .filter(|r| r.call == Call::ReportTelemetry)
+            .map(|r| samples_in(r.body.as_deref().unwrap()).len())
+            .collect()
+    }
+}
+
+fn samples_in(body: &str) -> Vec<Sample> {
+    let body: serde_json::Value = serde_json::from_str(body).unwrap();
+    serde_json::from_value(body["samples"].clone()).unwrap()
+}
+
+fn unreachable() -> Result<(), HTTPErr> {
+    Err(HTTPErr::MockErr(MockErr {
+        is_network_conn_err: true,
+    }))
+}
+
+pub mod publish {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_over_http() {
+        let f = Fixture::new("telemetry_publish_http").await;
+        let samples = vec![Sampler::new(vec![f.dir.path().clone()]).sample()];
+
+        assert!(telemetry::publish(Transport::Http, &f.deps(), &samples).await);
+
+        let requests = f.http_client.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].call, Call::ReportTelemetry);
+        assert_eq!(requests[0].path, "/devices/dvc_1/telemetry");
+        assert_eq!(requests[0].token.as_deref(), Some("token"));
+        assert_eq!(samples_in(requests[0].body.as_deref().unwrap()), samples);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_reports_stay_queued() {
+        let f = Fixture::new("telemetry_publish_http_err").await;
+        f.http_client.set_report_telemetry(unreachable);
+        let samples = vec![Sampler::new(Vec::new()).sample()];
+
+        assert!(telemetry::publish(Transport::Http, &f.deps(), &samples).await);
+
+        let entries = f.journal.entries().await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].request.kind(), Kind::Telemetry);
+        // the backend was unreachable, which doesn't count against the attempts
+        assert_eq!(entries[0].attempts, 0);
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn queues_for_the_mqtt_worker() {
+        let mut f = Fixture::new("telemetry_publish_mqtt").await;
+        let samples = vec![Sampler::new(Vec::new()).sample()];
+
+        assert!(telemetry::publish(Transport::Mqtt, &f.deps(), &samples).await);
+        // the outbox is full until the mqtt worker takes the message
+        assert!(!telemetry::publish(Transport::Mqtt, &f.deps(), &samples).await);
+
+        let msg = f.mqtt_rx.try_recv().unwrap();
+        assert_eq!(msg.topic, "v1/telemetry/devices/dvc_1");
+        let payload = String::from_utf8(msg.payload).unwrap();
+        assert_eq!(samples_in(&payload), samples);
+        assert!(f.http_client.requests().is_empty());
+        f.dir.delete().await.unwrap();
+    }
+}
+
+pub mod run {
+    use super::*;
+
+    fn spawn(f: &Fixture, options: Options) -> Arc<SleepController> {
+        let sleep_ctrl = Arc::new(SleepController::new());
+        let http_client = f.http_client.clone();
+        let token_mngr = f.token_mngr.clone();
+        let mqtt_outbox = f.mqtt_outbox.clone();
+        let device_stor = f.device_stor.clone();
+        let journal = f.journal.clone();
+        let sleep_fn = sleep_ctrl.sleep_fn();
+        tokio::spawn(async move {
+            let deps = Deps {
+                http_client: http_client.as_ref(),
+                token_mngr: token_mngr.as_ref(),
+                mqtt_outbox: &mqtt_outbox,
+                device_stor: device_stor.as_ref(),
+                journal: journal.as_ref(),
+            };
+            telemetry::run(
+                &options,
+                &deps,
+                sleep_fn,
+                Box::pin(std::future::pending::<()>()),
+            )
+            .await;
+        });
+        sleep_ctrl
+    }
+
+    /// Lets the worker through one interval, returning once it's waiting for the next.
+    async fn tick(sleep_ctrl: &SleepController) {
+        sleep_ctrl.await_sleep().await;
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+    }
+
+    #[tokio::test]
+    async fn publishes_a_sample_every_interval() {
+        let f = Fixture::new("telemetry_run_interval").await;
+        let options = Options {
+            interval_secs: 30,
+            ..Default::default()
+        };
+        let sleep_ctrl = spawn(&f, options);
+
+        tick(&sleep_ctrl).await;
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+
+        assert_eq!(f.reported(), vec![1, 1]);
+        for sleep in sleep_ctrl.get_attempted_sleeps() {
+            assert_eq!(sleep, Duration::from_secs(30));
+        }
+        f.dir.delete().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn queues_samples_up_to_the_journal_retention() {
+        let mut options = journal::Options::default();
+        options.retention.insert(
+            Kind::Telemetry,
+            Retention {
+                max_entries: 2,
+                ..Retention::default()
+            },
+        );
+        let f = Fixture::with_journal("telemetry_run_retention", options).await;
+        f.http_client.set_report_telemetry(unreachable);
+        let sleep_ctrl = spawn(&f, Options::default());
+
+        // each interval's sample is queued and the oldest queued is retried until
+        // it's dropped
+        tick(&sleep_ctrl).await;
+        for _ in 0..2 {
+            sleep_ctrl.release().await;
+            sleep_ctrl.await_sleep().await;
+        }
+        assert_eq!(f.journal.len().await, 2);
+
+        // the queued samples are published once the backend is reachable again
+        f.http_client.set_report_telemetry(|| Ok(()));
+        let before = f.reported().len();
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(f.reported()[before..], [1, 1]);
+        assert!(f.journal.is_empty().await);
+        f.dir.delete().await.unwrap();
+    }
+}
@@ -7,6 +7,12 @@ use crate::mocks::{error::SleepController, token_manager::MockTokenManager};
 use miru_agent::authn::errors::MockError;
 use miru_agent::authn::{AuthnErr, Token};
 use miru_agent::cooldown;
+use miru_agent::events;
+use miru_agent::http::{
+    errors::{HTTPErr, RequestFailed},
+    request::Params,
+};
+use miru_agent::testkit;
 use miru_agent::trace;
 use miru_agent::workers::token_refresh::{
     calc_refresh_wait, run_token_refresh_worker, TokenRefreshWorkerOptions,
@@ -14,6 +20,7 @@ use miru_agent::workers::token_refresh::{
 
 // external crates
 use chrono::{TimeDelta, Utc};
+use reqwest::StatusCode;
 
 pub mod run_refresh_token_worker {
     use super::*;
@@ -29,6 +36,8 @@ pub mod run_refresh_token_worker {
 
         // create a controllable sleep function
         let sleep_ctrl = Arc::new(SleepController::new());
+        let dir = testkit::temp_dir("token_refresh_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
 
         // create the shutdown signal
         let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
@@ -52,11 +61,13 @@ pub mod run_refresh_token_worker {
         let options = TokenRefreshWorkerOptions {
             refresh_advance_secs,
             backoff: cooldown,
+            watchdog_interval: None,
         };
         let token_refresh_handle = tokio::spawn(async move {
             run_token_refresh_worker(
                 &options,
                 token_mngr_for_spawn.as_ref(),
+                &event_hub,
                 sleep_ctrl_for_spawn.sleep_fn(),
                 Box::pin(shutdown_signal),
             )
@@ -128,6 +139,8 @@ pub mod run_refresh_token_worker {
 
         // create a controllable sleep function
         let sleep_ctrl = Arc::new(SleepController::new());
+        let dir = testkit::temp_dir("token_refresh_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
 
         // create the shutdown signal
         let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
@@ -151,11 +164,13 @@ pub mod run_refresh_token_worker {
         let options = TokenRefreshWorkerOptions {
             refresh_advance_secs,
             backoff: cooldown,
+            watchdog_interval: None,
         };
         let token_refresh_handle = tokio::spawn(async move {
             run_token_refresh_worker(
                 &options,
                 token_mngr_for_spawn.as_ref(),
+                &event_hub,
                 sleep_ctrl_for_spawn.sleep_fn(),
                 Box::pin(shutdown_signal),
             )
@@ -203,6 +218,8 @@ pub mod run_refresh_token_worker {
 
         // create a controllable sleep function
         let sleep_ctrl = Arc::new(SleepController::new());
+        let dir = testkit::temp_dir("token_refresh_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
 
         // create the shutdown signal
         let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
@@ -226,11 +243,13 @@ pub mod run_refresh_token_worker {
         let options = TokenRefreshWorkerOptions {
             refresh_advance_secs,
             backoff: cooldown,
+            watchdog_interval: None,
         };
         let token_refresh_handle = tokio::spawn(async move {
             run_token_refresh_worker(
                 &options,
                 token_mngr_for_spawn.as_ref(),
+                &event_hub,
                 sleep_ctrl_for_spawn.sleep_fn(),
                 Box::pin(shutdown_signal),
             )
@@ -260,6 +279,139 @@ pub mod run_refresh_token_worker {
         token_refresh_handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn watchdog() {
+        let token_mngr = Arc::new(MockTokenManager::new(Token {
+            token: "token".to_string(),
+            expires_at: Utc::now() + TimeDelta::minutes(100),
+        }));
+        let sleep_ctrl = Arc::new(SleepController::new());
+        let dir = testkit::temp_dir("token_refresh_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
+
+        let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
+            tokio::sync::broadcast::channel(1);
+        let mut shutdown_rx = shutdown_tx.subscribe();
+        let shutdown_signal = async move {
+            let _ = shutdown_rx.recv().await;
+        };
+
+        let cooldown = cooldown::Backoff {
+            base_secs: 30,
+            growth_factor: 2,
+            max_secs: 12 * 60 * 60,
+        };
+        let token_mngr_for_spawn = token_mngr.clone();
+        let sleep_ctrl_for_spawn = sleep_ctrl.clone();
+        let options = TokenRefreshWorkerOptions {
+            refresh_advance_secs: 10 * 60,
+            backoff: cooldown,
+            watchdog_interval: Some(Duration::from_secs(60)),
+        };
+        let token_refresh_handle = tokio::spawn(async move {
+            run_token_refresh_worker(
+                &options,
+                token_mngr_for_spawn.as_ref(),
+                &event_hub,
+                sleep_ctrl_for_spawn.sleep_fn(),
+                Box::pin(shutdown_signal),
+            )
+            .await;
+        });
+
+        // the token is refreshed right away and then only checked every interval
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(sleep_ctrl.get_last_attempted_sleep().unwrap().as_secs(), 60);
+        assert_eq!(token_mngr.num_refresh_token_calls(), 1);
+        for _ in 0..3 {
+            sleep_ctrl.release().await;
+            sleep_ctrl.await_sleep().await;
+            assert_eq!(sleep_ctrl.get_last_attempted_sleep().unwrap().as_secs(), 60);
+            assert_eq!(token_mngr.num_refresh_token_calls(), 1);
+        }
+
+        // the refresh becomes due earlier than planned, e.g. after a suspend
+        token_mngr.set_token(Token {
+            token: "token".to_string(),
+            expires_at: Utc::now() + TimeDelta::minutes(5),
+        });
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(
+            sleep_ctrl.get_last_attempted_sleep().unwrap().as_secs(),
+            cooldown.base_secs as u64
+        );
+        assert_eq!(token_mngr.num_refresh_token_calls(), 1);
+        sleep_ctrl.release().await;
+        sleep_ctrl.await_sleep().await;
+        assert_eq!(token_mngr.num_refresh_token_calls(), 2);
+
+        shutdown_tx.send(()).unwrap();
+        token_refresh_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn auth_revoked_publishes_event_once() {
+        let token = Token {
+            token: "token".to_string(),
+            expires_at: Utc::now(),
+        };
+        let token_mngr = MockTokenManager::new(token);
+        token_mngr.set_refresh_token(Box::new(|| {
+            Err(AuthnErr::HTTPErr(HTTPErr::RequestFailed(RequestFailed {
+                request: Params::get("http://localhost/token").meta().unwrap(),
+                status: StatusCode::UNAUTHORIZED,
+                error: None,
+                trace: trace!(),
+            })))
+        }));
+        let token_mngr = Arc::new(token_mngr);
+
+        let sleep_ctrl = Arc::new(SleepController::new());
+        let dir = testkit::temp_dir("token_refresh_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
+        let mut events = event_hub.subscribe();
+
+        let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
+            tokio::sync::broadcast::channel(1);
+        let mut shutdown_rx = shutdown_tx.subscribe();
+        let shutdown_signal = async move {
+            let _ = shutdown_rx.recv().await;
+        };
+
+        let token_mngr_for_spawn = token_mngr.clone();
+        let sleep_ctrl_for_spawn = sleep_ctrl.clone();
+        let options = TokenRefreshWorkerOptions::default();
+        let token_refresh_handle = tokio::spawn(async move {
+            run_token_refresh_worker(
+                &options,
+                token_mngr_for_spawn.as_ref(),
+                &event_hub,
+                sleep_ctrl_for_spawn.sleep_fn(),
+                Box::pin(shutdown_signal),
+            )
+            .await;
+        });
+
+        // refresh several times while the credentials remain rejected
+        for _ in 0..3 {
+            sleep_ctrl.release().await;
+            sleep_ctrl.await_sleep().await;
+        }
+        assert_eq!(token_mngr.num_refresh_token_calls(), 3);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.event_type, events::model::AUTH_REVOKED);
+        assert!(event.data["error"].as_str().unwrap().contains("401"));
+        assert!(
+            events.try_recv().is_err(),
+            "the revocation should only be published once"
+        );
+
+        shutdown_tx.send(()).unwrap();
+        token_refresh_handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn error_recovery() {
         // create the token manager
@@ -277,6 +429,8 @@ pub mod run_refresh_token_worker {
 
         // create a controllable sleep function
         let sleep_ctrl = Arc::new(SleepController::new());
+        let dir = testkit::temp_dir("token_refresh_worker").await;
+        let (event_hub, _hub_handle) = testkit::spawn_event_hub(&dir).await;
 
         // create the shutdown signal
         let (shutdown_tx, _shutdown_rx): (tokio::sync::broadcast::Sender<()>, _) =
@@ -300,11 +454,13 @@ pub mod run_refresh_token_worker {
         let options = TokenRefreshWorkerOptions {
             refresh_advance_secs,
             backoff: cooldown,
+            watchdog_interval: None,
         };
         let token_refresh_handle = tokio::spawn(async move {
             run_token_refresh_worker(
                 &options,
                 token_mngr_for_spawn.as_ref(),
+                &event_hub,
                 sleep_ctrl_for_spawn.sleep_fn(),
                 Box::pin(shutdown_signal),
             )
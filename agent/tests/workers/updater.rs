@@ -0,0 +1,357 @@
+// standard crates
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::syncer::MockSyncer;
+use crate::mocks::token_manager::MockTokenManager;
+use crate::updater::shared::{serve, Backend, Signed};
+use miru_agent::authn::Token;
+use miru_agent::clock::Clock;
+use miru_agent::http;
+use miru_agent::sync::syncer::State as SyncState;
+use miru_agent::updater::{Slot, Slots, State};
+use miru_agent::workers::updater::{check, health_check, is_newer, run, Deps, Options};
+
+// external crates
+use chrono::{DateTime, TimeDelta, Utc};
+
+const CONTENT: &[u8] = b"miru-agent v0.9.0";
+
+struct Fixture {
+    signed: Signed,
+    backend: Backend,
+    http_client: http::Client,
+    token_mngr: MockTokenManager,
+    syncer: MockSyncer,
+    slots: Slots,
+    clock: Clock,
+}
+
+impl Fixture {
+    /// A backend offering `version`, if any.
+    async fn new(prefix: &str, version: Option<&str>) -> Self {
+        let signed = Signed::new(prefix, CONTENT).await;
+        let release = match version {
+            Some(version) => Some(signed.release(version).await),
+            None => None,
+        };
+        let backend = serve(release, CONTENT.to_vec()).await;
+        let http_client = http::Client::new(&backend.server.base_url).unwrap();
+        let slots = Slots::new(signed.dir.subdir("updates"));
+        Self {
+            signed,
+            backend,
+            http_client,
+            token_mngr: MockTokenManager::new(Token::default()),
+            syncer: MockSyncer::new(),
+            slots,
+            clock: Clock::new(),
+        }
+    }
+
+    fn options(&self) -> Options {
+        Options {
+            health_check_secs: 0,
+            release_key: self.signed.public_key.clone(),
+            version: "v0.8.0".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn deps(&self) -> Deps<'_, MockTokenManager, MockSyncer> {
+        Deps {
+            http_client: &self.http_client,
+            token_mngr: &self.token_mngr,
+            syncer: &self.syncer,
+            slots: &self.slots,
+            clock: &self.clock,
+        }
+    }
+
+    /// Skews the clock as if the device's clock was `behind` the backend's.
+    fn skew(&self, behind: TimeDelta) {
+        let now = Utc::now();
+        self.clock.observe(now + behind, now, now);
+    }
+
+    fn synced_at(&self, at: DateTime<Utc>) {
+        self.syncer.set_state(SyncState {
+            last_attempted_sync_at: at,
+            last_synced_at: at,
+            cooldown_ends_at: DateTime::<Utc>::UNIX_EPOCH,
+            err_streak: 0,
+            syncs: 1,
+        });
+    }
+
+    /// A sync state restored from disk, which this process hasn't synced since.
+    fn restored_synced_at(&self, at: DateTime<Utc>) {
+        self.syncer.set_state(SyncState {
+            last_attempted_sync_at: at,
+            last_synced_at: at,
+            cooldown_ends_at: DateTime::<Utc>::UNIX_EPOCH,
+            err_streak: 0,
+            syncs: 0,
+        });
+    }
+
+    async fn cleanup(self) {
+        self.signed.dir.delete().await.unwrap();
+    }
+}
+
+pub mod health {
+    use super::*;
+
+    #[tokio::test]
+    async fn passes_after_a_sync() {
+        let f = Fixture::new("updater_health_passes", None).await;
+        let since = Utc::now();
+        f.synced_at(since + TimeDelta::seconds(1));
+        assert!(health_check(&f.options(), &f.deps(), since, &|_| async {}).await);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn fails_without_a_sync_in_the_window() {
+        let f = Fixture::new("updater_health_fails", None).await;
+        let since = Utc::now();
+        f.synced_at(since - TimeDelta::seconds(1));
+        assert!(!health_check(&f.options(), &f.deps(), since, &|_| async {}).await);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn fails_with_only_a_restored_sync() {
+        let f = Fixture::new("updater_health_restored", None).await;
+        let since = Utc::now();
+        f.restored_synced_at(since + TimeDelta::seconds(1));
+        assert!(!health_check(&f.options(), &f.deps(), since, &|_| async {}).await);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_sync() {
+        let f = Fixture::new("updater_health_waits", None).await;
+        let since = Utc::now();
+        let options = Options {
+            health_check_secs: 60,
+            ..f.options()
+        };
+        let polls = AtomicUsize::new(0);
+        let sleep_fn = |_| {
+            if polls.fetch_add(1, Ordering::SeqCst) == 2 {
+                f.synced_at(Utc::now());
+            }
+            async {}
+        };
+        assert!(health_check(&options, &f.deps(), since, &sleep_fn).await);
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+        f.cleanup().await;
+    }
+}
+
+pub mod check_for_update {
+    use super::*;
+
+    #[tokio::test]
+    async fn up_to_date() {
+        let f = Fixture::new("updater_check_up_to_date", None).await;
+        assert!(!check(&f.options(), &f.deps()).await.unwrap());
+        assert_eq!(
+            *f.backend.queries.lock().unwrap(),
+            vec![format!(
+                "version=v0.8.0&os={}&arch={}",
+                miru_agent::version::OS,
+                miru_agent::version::ARCH
+            )]
+        );
+        assert_eq!(f.slots.load().await, State::default());
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn skips_the_running_version() {
+        let f = Fixture::new("updater_check_running", Some("v0.8.0")).await;
+        assert!(!check(&f.options(), &f.deps()).await.unwrap());
+        assert_eq!(f.slots.load().await, State::default());
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn skips_an_older_version() {
+        let f = Fixture::new("updater_check_older", Some("v0.7.9")).await;
+        assert!(!check(&f.options(), &f.deps()).await.unwrap());
+        assert_eq!(f.slots.load().await, State::default());
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn skips_an_invalid_version() {
+        let f = Fixture::new("updater_check_invalid", Some("latest")).await;
+        assert!(!check(&f.options(), &f.deps()).await.unwrap());
+        assert_eq!(f.slots.load().await, State::default());
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn skips_a_rejected_version() {
+        let f = Fixture::new("updater_check_rejected", Some("v0.9.0")).await;
+        let state = State {
+            rejected: vec!["v0.9.0".to_string()],
+            ..Default::default()
+        };
+        f.slots.save(&state).await.unwrap();
+        assert!(!check(&f.options(), &f.deps()).await.unwrap());
+        assert_eq!(f.slots.load().await, state);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn installs_a_newer_version() {
+        let f = Fixture::new("updater_check_installs", Some("v0.9.0")).await;
+        assert!(check(&f.options(), &f.deps()).await.unwrap());
+        let state = f.slots.load().await;
+        assert_eq!(state.active, Some(Slot::A));
+        assert_eq!(state.trial.unwrap().version, "v0.9.0");
+        assert_eq!(f.token_mngr.num_get_token_calls(), 1);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn a_clock_ahead_of_the_backend_doesnt_fail_the_trial() {
+        let f = Fixture::new("updater_check_skewed", Some("v0.9.0")).await;
+        // the device's clock is a day ahead of the backend's
+        f.skew(TimeDelta::days(-1));
+        let before = f.clock.now();
+        assert!(check(&f.options(), &f.deps()).await.unwrap());
+        let installed_at = f.slots.load().await.trial.unwrap().installed_at;
+        assert!(installed_at >= before && installed_at <= f.clock.now());
+
+        // the syncer's sync times are skew-corrected as well
+        f.synced_at(f.clock.now() + TimeDelta::seconds(1));
+        assert!(health_check(&f.options(), &f.deps(), installed_at, &|_| async {}).await);
+        f.cleanup().await;
+    }
+}
+
+pub mod is_newer_fn {
+    use super::*;
+
+    #[test]
+    fn compares_semantic_versions() {
+        assert!(is_newer("v0.9.0", "v0.8.0"));
+        assert!(is_newer("v0.10.0", "v0.9.0"));
+        assert!(is_newer("0.8.1", "v0.8.0"));
+        assert!(is_newer("v0.8.0", "v0.8.0-rc.1"));
+        assert!(!is_newer("v0.8.0", "v0.8.0"));
+        assert!(!is_newer("v0.7.9", "v0.8.0"));
+        assert!(!is_newer("v0.8.0-rc.1", "v0.8.0"));
+    }
+
+    #[test]
+    fn invalid_versions_are_never_newer() {
+        assert!(!is_newer("latest", "v0.8.0"));
+        assert!(!is_newer("v1.0.0", "dev"));
+    }
+}
+
+pub mod run_worker {
+    use super::*;
+
+    /// Puts the version in slot A on trial, as if the agent was relaunched into it.
+    async fn on_trial(f: &Fixture, installed_at: DateTime<Utc>) -> Options {
+        let mut state = State::default();
+        state.begin_trial(Slot::A, "v0.8.0", installed_at);
+        f.slots.save(&state).await.unwrap();
+        Options {
+            slot: Some(Slot::A),
+            ..f.options()
+        }
+    }
+
+    #[tokio::test]
+    async fn rolls_back_an_unhealthy_version() {
+        let f = Fixture::new("updater_run_rollback", None).await;
+        let options = on_trial(&f, Utc::now()).await;
+        let restarts = AtomicUsize::new(0);
+
+        run(
+            &options,
+            &f.deps(),
+            |_| async {},
+            || {
+                restarts.fetch_add(1, Ordering::SeqCst);
+            },
+            Box::pin(std::future::pending()),
+        )
+        .await;
+
+        assert_eq!(restarts.load(Ordering::SeqCst), 1);
+        let state = f.slots.load().await;
+        assert_eq!(state.active, None);
+        assert!(state.trial.is_none());
+        assert_eq!(state.rejected, vec!["v0.8.0".to_string()]);
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn commits_a_healthy_version_then_updates() {
+        let f = Fixture::new("updater_run_commit", Some("v0.9.0")).await;
+        let installed_at = Utc::now();
+        let options = on_trial(&f, installed_at).await;
+        f.synced_at(installed_at + TimeDelta::seconds(1));
+        let restarts = AtomicUsize::new(0);
+
+        run(
+            &options,
+            &f.deps(),
+            |_| async {},
+            || {
+                restarts.fetch_add(1, Ordering::SeqCst);
+            },
+            Box::pin(std::future::pending()),
+        )
+        .await;
+
+        // the committed version is the fallback for the next one
+        assert_eq!(restarts.load(Ordering::SeqCst), 1);
+        let state = f.slots.load().await;
+        assert_eq!(state.active, Some(Slot::B));
+        let trial = state.trial.unwrap();
+        assert_eq!(trial.version, "v0.9.0");
+        assert_eq!(trial.previous, Some(Slot::A));
+        f.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn ignores_a_trial_of_another_slot() {
+        let f = Fixture::new("updater_run_other_slot", None).await;
+        let mut options = on_trial(&f, Utc::now()).await;
+        options.slot = None;
+        let (shutdown_tx, mut shutdown_rx) = tokio::sync::broadcast::channel::<()>(1);
+        let restarts = AtomicUsize::new(0);
+
+        let sleep_fn = |_| {
+            let _ = shutdown_tx.send(());
+            tokio::time::sleep(Duration::from_secs(60))
+        };
+        run(
+            &options,
+            &f.deps(),
+            sleep_fn,
+            || {
+                restarts.fetch_add(1, Ordering::SeqCst);
+            },
+            Box::pin(async move {
+                let _ = shutdown_rx.recv().await;
+            }),
+        )
+        .await;
+
+        assert_eq!(restarts.load(Ordering::SeqCst), 0);
+        assert!(f.slots.load().await.trial.is_some());
+        f.cleanup().await;
+    }
+}
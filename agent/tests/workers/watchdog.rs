@@ -0,0 +1,205 @@
+// standard crates
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::time::Duration;
+
+// internal crates
+use crate::mocks::syncer::MockSyncer;
+use crate::mocks::token_manager::MockTokenManager;
+use miru_agent::authn::Token;
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::platform::systemd::Notifier;
+use miru_agent::sync::syncer::{Command, SyncerExt};
+use miru_agent::sync::Syncer;
+use miru_agent::workers::watchdog::{check_liveness, run, Deps, Options};
+
+// external crates
+use tokio::sync::mpsc;
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A syncer's actor which answers the liveness checks but, once asked to sync, syncs
+/// for longer than any test runs and serves nothing else meanwhile.
+fn slow_syncer() -> mpsc::Sender<Command> {
+    let (sender, mut receiver) = mpsc::channel(64);
+    tokio::spawn(async move {
+        while let Some(cmd) = receiver.recv().await {
+            match cmd {
+                Command::GetSyncState { respond_to } => {
+                    let _ = respond_to.send(Ok(Default::default()));
+                }
+                Command::Sync { respond_to } => {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    let _ = respond_to.send(Ok(()));
+                }
+                _ => {}
+            }
+        }
+    });
+    sender
+}
+
+async fn wait_for_sync(syncer: &Syncer) {
+    while !syncer.is_syncing() {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[test]
+fn options_from_timeout() {
+    let options = Options::from_timeout(Duration::from_secs(30));
+    assert_eq!(options.interval, Duration::from_secs(15));
+    assert_eq!(options.check_timeout, Duration::from_millis(7500));
+
+    let options = Options::from_timeout(Duration::from_secs(180));
+    assert_eq!(options.interval, Duration::from_secs(90));
+    assert_eq!(options.check_timeout, Duration::from_secs(10));
+}
+
+pub mod liveness {
+    use super::*;
+
+    #[tokio::test]
+    async fn responsive_workers() {
+        let syncer = MockSyncer::new();
+        let token_mngr = MockTokenManager::new(Token::default());
+        let notifier = Notifier::default();
+        let deps = Deps {
+            syncer: &syncer,
+            token_mngr: &token_mngr,
+            notifier: &notifier,
+        };
+        assert_eq!(check_liveness(&deps, TIMEOUT).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn wedged_syncer() {
+        // the syncer's actor never handles its commands
+        let (sender, _receiver) = mpsc::channel(4);
+        let syncer = Syncer::new(sender);
+        let token_mngr = MockTokenManager::new(Token::default());
+        let notifier = Notifier::default();
+        let deps = Deps {
+            syncer: &syncer,
+            token_mngr: &token_mngr,
+            notifier: &notifier,
+        };
+        let msg = check_liveness(&deps, TIMEOUT).await.unwrap_err();
+        assert!(msg.starts_with("the syncer didn't answer"), "{msg}");
+    }
+
+    #[tokio::test]
+    async fn stopped_syncer() {
+        let (sender, receiver) = mpsc::channel(4);
+        drop(receiver);
+        let syncer = Syncer::new(sender);
+        let token_mngr = MockTokenManager::new(Token::default());
+        let notifier = Notifier::default();
+        let deps = Deps {
+            syncer: &syncer,
+            token_mngr: &token_mngr,
+            notifier: &notifier,
+        };
+        let msg = check_liveness(&deps, TIMEOUT).await.unwrap_err();
+        assert!(msg.starts_with("the syncer isn't running"), "{msg}");
+    }
+
+    #[tokio::test]
+    async fn syncing_syncer() {
+        let syncer = Arc::new(Syncer::new(slow_syncer()));
+        let token_mngr = MockTokenManager::new(Token::default());
+        let notifier = Notifier::default();
+        let deps = Deps {
+            syncer: syncer.as_ref(),
+            token_mngr: &token_mngr,
+            notifier: &notifier,
+        };
+        let sync = tokio::spawn({
+            let syncer = syncer.clone();
+            async move { syncer.sync().await }
+        });
+        wait_for_sync(&syncer).await;
+        assert_eq!(check_liveness(&deps, TIMEOUT).await, Ok(()));
+
+        // a sync which is cancelled doesn't count as in flight
+        sync.abort();
+        let _ = sync.await;
+        assert!(!syncer.is_syncing());
+    }
+}
+
+pub mod run_worker {
+    use super::*;
+
+    async fn keep_alives(syncer: &Syncer) -> usize {
+        let dir = filesys::Dir::create_temp_dir("watchdog_worker")
+            .await
+            .unwrap();
+        let path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+
+        let token_mngr = MockTokenManager::new(Token::default());
+        let notifier = Notifier::new(Some(path.display().to_string()));
+        let deps = Deps {
+            syncer,
+            token_mngr: &token_mngr,
+            notifier: &notifier,
+        };
+        let options = Options {
+            interval: Duration::from_millis(20),
+            check_timeout: Duration::from_millis(20),
+        };
+        let worker = run(
+            &options,
+            &deps,
+            tokio::time::sleep,
+            Box::pin(tokio::time::sleep(Duration::from_millis(100))),
+        );
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 64];
+        let mut count = 0;
+        while let Ok(n) = socket.recv(&mut buf) {
+            assert_eq!(&buf[..n], b"WATCHDOG=1");
+            count += 1;
+        }
+        dir.delete().await.unwrap();
+        count
+    }
+
+    #[tokio::test]
+    async fn sends_keep_alives() {
+        let (sender, mut receiver) = mpsc::channel(4);
+        let syncer = Syncer::new(sender);
+        // answer the liveness checks like the syncer's actor would
+        tokio::spawn(async move {
+            while let Some(cmd) = receiver.recv().await {
+                if let Command::GetSyncState { respond_to } = cmd {
+                    let _ = respond_to.send(Ok(Default::default()));
+                }
+            }
+        });
+        assert!(keep_alives(&syncer).await >= 2);
+    }
+
+    #[tokio::test]
+    async fn sends_keep_alives_during_a_slow_sync() {
+        let syncer = Arc::new(Syncer::new(slow_syncer()));
+        tokio::spawn({
+            let syncer = syncer.clone();
+            async move { syncer.sync().await }
+        });
+        wait_for_sync(&syncer).await;
+        assert!(keep_alives(&syncer).await >= 2);
+    }
+
+    #[tokio::test]
+    async fn withholds_keep_alives_while_wedged() {
+        let (sender, _receiver) = mpsc::channel(64);
+        let syncer = Syncer::new(sender);
+        assert_eq!(keep_alives(&syncer).await, 0);
+    }
+}
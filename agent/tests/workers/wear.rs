@@ -0,0 +1,41 @@
+// standard crates
+use std::time::Duration;
+
+// internal crates
+use miru_agent::filesys::{self, PathExt};
+use miru_agent::storage::wear::Meter;
+use miru_agent::workers::wear::{run, Deps, Options};
+
+pub mod run_worker {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_totals_periodically() {
+        let dir = filesys::Dir::create_temp_dir("wear_worker").await.unwrap();
+        let file = dir.file("wear.json");
+        let meter = Meter::init(file.clone()).await;
+        let deps = Deps { meter: &meter };
+        let options = Options::default();
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let worker = run(
+            &options,
+            &deps,
+            |_| tokio::time::sleep(Duration::from_millis(1)),
+            Box::pin(async move {
+                let _ = rx.await;
+            }),
+        );
+        let stop = async {
+            while !file.exists() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            let _ = tx.send(());
+        };
+        tokio::time::timeout(Duration::from_secs(5), futures::future::join(worker, stop))
+            .await
+            .unwrap();
+        assert!(file.exists());
+        dir.delete().await.unwrap();
+    }
+}
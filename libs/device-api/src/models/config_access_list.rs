@@ -0,0 +1,26 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConfigAccessList {
+    /// The clients' reads of each config instance.
+    #[serde(rename = "accesses")]
+    pub accesses: Vec<serde_json::Value>,
+}
+
+impl ConfigAccessList {
+    pub fn new(accesses: Vec<serde_json::Value>) -> ConfigAccessList {
+        ConfigAccessList {
+            accesses,
+        }
+    }
+}
+
@@ -0,0 +1,46 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeploymentDirRelease {
+    /// The number of the release in the deployment directory.
+    #[serde(rename = "number")]
+    pub number: i64,
+    /// Whether the deployment directory's current link points at the release.
+    #[serde(rename = "live")]
+    pub live: bool,
+    /// ID of the deployment the release was written for.
+    #[serde(rename = "deployment_id", deserialize_with = "Option::deserialize")]
+    pub deployment_id: Option<String>,
+    /// When the release was written.
+    #[serde(rename = "created_at", deserialize_with = "Option::deserialize")]
+    pub created_at: Option<String>,
+    /// When the release was last made live.
+    #[serde(rename = "activated_at", deserialize_with = "Option::deserialize")]
+    pub activated_at: Option<String>,
+    /// Whether the release was rolled back from.
+    #[serde(rename = "rolled_back")]
+    pub rolled_back: bool,
+}
+
+impl DeploymentDirRelease {
+    pub fn new(number: i64, live: bool, deployment_id: Option<String>, created_at: Option<String>, activated_at: Option<String>, rolled_back: bool) -> DeploymentDirRelease {
+        DeploymentDirRelease {
+            number,
+            live,
+            deployment_id,
+            created_at,
+            activated_at,
+            rolled_back,
+        }
+    }
+}
+
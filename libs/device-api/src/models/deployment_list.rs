@@ -0,0 +1,50 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeploymentList {
+    /// The object type, which is always `list`.
+    #[serde(rename = "object")]
+    pub object: Object,
+    /// The deployments, newest first.
+    #[serde(rename = "data")]
+    pub data: Vec<models::DeploymentState>,
+    /// True if there are more deployments after this page.
+    #[serde(rename = "has_more")]
+    pub has_more: bool,
+    /// Continues the list after the last deployment, if there are more.
+    #[serde(rename = "next_cursor", deserialize_with = "Option::deserialize")]
+    pub next_cursor: Option<String>,
+}
+
+impl DeploymentList {
+    pub fn new(object: Object, data: Vec<models::DeploymentState>, has_more: bool, next_cursor: Option<String>) -> DeploymentList {
+        DeploymentList {
+            object,
+            data,
+            has_more,
+            next_cursor,
+        }
+    }
+}
+/// The object type, which is always `list`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Object {
+    #[serde(rename = "list")]
+    List,
+}
+
+impl Default for Object {
+    fn default() -> Object {
+        Self::List
+    }
+}
+
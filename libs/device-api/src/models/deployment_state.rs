@@ -0,0 +1,96 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+/// DeploymentState : A deployment with the state the agent keeps for it.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeploymentState {
+    /// The object type, which is always `deployment`.
+    #[serde(rename = "object")]
+    pub object: Object,
+    /// ID of the deployment.
+    #[serde(rename = "id")]
+    pub id: String,
+    /// The description of the deployment.
+    #[serde(rename = "description")]
+    pub description: String,
+    #[serde(rename = "status")]
+    pub status: models::DeploymentStatus,
+    #[serde(rename = "activity_status")]
+    pub activity_status: models::DeploymentActivityStatus,
+    #[serde(rename = "error_status")]
+    pub error_status: models::DeploymentErrorStatus,
+    #[serde(rename = "target_status")]
+    pub target_status: models::DeploymentTargetStatus,
+    /// ID of the device.
+    #[serde(rename = "device_id")]
+    pub device_id: String,
+    /// ID of the release.
+    #[serde(rename = "release_id")]
+    pub release_id: String,
+    /// Timestamp of when the device release was created.
+    #[serde(rename = "created_at")]
+    pub created_at: String,
+    /// Failed attempts since the deployment last reached its target.
+    #[serde(rename = "attempts")]
+    pub attempts: i32,
+    /// When the deployment is next retried, if it's waiting out a cooldown.
+    #[serde(rename = "cooldown_ends_at", deserialize_with = "Option::deserialize")]
+    pub cooldown_ends_at: Option<String>,
+    /// When the deployment was last deployed.
+    #[serde(rename = "deployed_at", deserialize_with = "Option::deserialize")]
+    pub deployed_at: Option<String>,
+    /// When the deployment was archived.
+    #[serde(rename = "archived_at", deserialize_with = "Option::deserialize")]
+    pub archived_at: Option<String>,
+    /// IDs of the deployment's config instances.
+    #[serde(rename = "config_instance_ids")]
+    pub config_instance_ids: Vec<String>,
+    /// The files of the config instances the agent has downloaded.
+    #[serde(rename = "filepaths")]
+    pub filepaths: Vec<String>,
+}
+
+impl DeploymentState {
+    /// A deployment with the state the agent keeps for it.
+    pub fn new(object: Object, id: String, description: String, status: models::DeploymentStatus, activity_status: models::DeploymentActivityStatus, error_status: models::DeploymentErrorStatus, target_status: models::DeploymentTargetStatus, device_id: String, release_id: String, created_at: String, attempts: i32, cooldown_ends_at: Option<String>, deployed_at: Option<String>, archived_at: Option<String>, config_instance_ids: Vec<String>, filepaths: Vec<String>) -> DeploymentState {
+        DeploymentState {
+            object,
+            id,
+            description,
+            status,
+            activity_status,
+            error_status,
+            target_status,
+            device_id,
+            release_id,
+            created_at,
+            attempts,
+            cooldown_ends_at,
+            deployed_at,
+            archived_at,
+            config_instance_ids,
+            filepaths,
+        }
+    }
+}
+/// The object type, which is always `deployment`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Object {
+    #[serde(rename = "deployment")]
+    Deployment,
+}
+
+impl Default for Object {
+    fn default() -> Object {
+        Self::Deployment
+    }
+}
+
@@ -0,0 +1,54 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeploymentsPause {
+    /// Whether deployments are paused.
+    #[serde(rename = "paused")]
+    pub paused: bool,
+    /// When deployments were last paused or resumed.
+    #[serde(rename = "changed_at", deserialize_with = "Option::deserialize")]
+    pub changed_at: Option<String>,
+    /// Where deployments were last paused or resumed from.
+    #[serde(rename = "changed_by", deserialize_with = "Option::deserialize")]
+    pub changed_by: Option<ChangedBy>,
+    /// Why deployments were paused.
+    #[serde(rename = "reason", deserialize_with = "Option::deserialize")]
+    pub reason: Option<String>,
+}
+
+impl DeploymentsPause {
+    pub fn new(paused: bool, changed_at: Option<String>, changed_by: Option<ChangedBy>, reason: Option<String>) -> DeploymentsPause {
+        DeploymentsPause {
+            paused,
+            changed_at,
+            changed_by,
+            reason,
+        }
+    }
+}
+/// Where deployments were last paused or resumed from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum ChangedBy {
+    #[serde(rename = "socket")]
+    Socket,
+    #[serde(rename = "mqtt")]
+    Mqtt,
+    #[serde(rename = "cli")]
+    Cli,
+}
+
+impl Default for ChangedBy {
+    fn default() -> ChangedBy {
+        Self::Socket
+    }
+}
+
@@ -11,15 +11,27 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct HealthResponse {
-    /// The status of the agent.
+    /// The status of the agent, `ok` or `safe_mode`.
     #[serde(rename = "status")]
     pub status: String,
+    /// Set if no subsystem is degraded.
+    #[serde(rename = "healthy", skip_serializing_if = "Option::is_none")]
+    pub healthy: Option<bool>,
+    /// The health of the mqtt connection, syncer, poller, token, data disk and clock, each with a status of ok, degraded, disabled or unknown.
+    #[serde(rename = "subsystems", skip_serializing_if = "Option::is_none")]
+    pub subsystems: Option<serde_json::Value>,
+    /// The deprecated settings fields, flags and commands the agent was given.
+    #[serde(rename = "deprecations", skip_serializing_if = "Option::is_none")]
+    pub deprecations: Option<Vec<serde_json::Value>>,
 }
 
 impl HealthResponse {
     pub fn new(status: String) -> HealthResponse {
         HealthResponse {
             status,
+            healthy: None,
+            subsystems: None,
+            deprecations: None,
         }
     }
 }
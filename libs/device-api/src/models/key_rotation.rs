@@ -0,0 +1,26 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    /// The fingerprint of the new public key.
+    #[serde(rename = "fingerprint")]
+    pub fingerprint: String,
+}
+
+impl KeyRotation {
+    pub fn new(fingerprint: String) -> KeyRotation {
+        KeyRotation {
+            fingerprint,
+        }
+    }
+}
+
@@ -0,0 +1,46 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+/// LogLevel : The level of the agent's logs.
+/// The level of the agent's logs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum LogLevel {
+    #[serde(rename = "trace")]
+    LOG_LEVEL_TRACE,
+    #[serde(rename = "debug")]
+    LOG_LEVEL_DEBUG,
+    #[serde(rename = "info")]
+    LOG_LEVEL_INFO,
+    #[serde(rename = "warn")]
+    LOG_LEVEL_WARN,
+    #[serde(rename = "error")]
+    LOG_LEVEL_ERROR,
+
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::LOG_LEVEL_TRACE => write!(f, "trace"),
+            Self::LOG_LEVEL_DEBUG => write!(f, "debug"),
+            Self::LOG_LEVEL_INFO => write!(f, "info"),
+            Self::LOG_LEVEL_WARN => write!(f, "warn"),
+            Self::LOG_LEVEL_ERROR => write!(f, "error"),
+        }
+    }
+}
+
+impl Default for LogLevel {
+    fn default() -> LogLevel {
+        Self::LOG_LEVEL_TRACE
+    }
+}
+
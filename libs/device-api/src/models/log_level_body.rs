@@ -0,0 +1,26 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogLevelBody {
+    /// The log level to change to, one of trace, debug, info, warn or error.
+    #[serde(rename = "level")]
+    pub level: String,
+}
+
+impl LogLevelBody {
+    pub fn new(level: String) -> LogLevelBody {
+        LogLevelBody {
+            level,
+        }
+    }
+}
+
@@ -0,0 +1,29 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogLevelResponse {
+    #[serde(rename = "level")]
+    pub level: models::LogLevel,
+    /// Set if RUST_LOG pins the log filter, in which case the level can't be changed.
+    #[serde(rename = "env_filter_locked")]
+    pub env_filter_locked: bool,
+}
+
+impl LogLevelResponse {
+    pub fn new(level: models::LogLevel, env_filter_locked: bool) -> LogLevelResponse {
+        LogLevelResponse {
+            level,
+            env_filter_locked,
+        }
+    }
+}
+
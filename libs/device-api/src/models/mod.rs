@@ -2,20 +2,30 @@ pub mod api_git_commit;
 pub use self::api_git_commit::ApiGitCommit;
 pub mod api_version;
 pub use self::api_version::ApiVersion;
+pub mod config_access_list;
+pub use self::config_access_list::ConfigAccessList;
 pub mod deployment;
 pub use self::deployment::Deployment;
 pub mod deployment_activity_status;
 pub use self::deployment_activity_status::DeploymentActivityStatus;
 pub mod deployment_deployed_event;
 pub use self::deployment_deployed_event::DeploymentDeployedEvent;
+pub mod deployment_dir_release;
+pub use self::deployment_dir_release::DeploymentDirRelease;
 pub mod deployment_error_status;
 pub use self::deployment_error_status::DeploymentErrorStatus;
+pub mod deployment_list;
+pub use self::deployment_list::DeploymentList;
 pub mod deployment_removed_event;
 pub use self::deployment_removed_event::DeploymentRemovedEvent;
+pub mod deployment_state;
+pub use self::deployment_state::DeploymentState;
 pub mod deployment_status;
 pub use self::deployment_status::DeploymentStatus;
 pub mod deployment_target_status;
 pub use self::deployment_target_status::DeploymentTargetStatus;
+pub mod deployments_pause;
+pub use self::deployments_pause::DeploymentsPause;
 pub mod device;
 pub use self::device::Device;
 pub mod device_status;
@@ -30,11 +40,25 @@ pub mod git_commit;
 pub use self::git_commit::GitCommit;
 pub mod health_response;
 pub use self::health_response::HealthResponse;
+pub mod key_rotation;
+pub use self::key_rotation::KeyRotation;
+pub mod log_level;
+pub use self::log_level::LogLevel;
+pub mod log_level_body;
+pub use self::log_level_body::LogLevelBody;
+pub mod log_level_response;
+pub use self::log_level_response::LogLevelResponse;
 pub mod release;
 pub use self::release::Release;
+pub mod release_list;
+pub use self::release_list::ReleaseList;
+pub mod rollback;
+pub use self::rollback::Rollback;
 pub mod sync_device_response;
 pub use self::sync_device_response::SyncDeviceResponse;
 pub mod sync_device_result;
 pub use self::sync_device_result::SyncDeviceResult;
+pub mod sync_history;
+pub use self::sync_history::SyncHistory;
 pub mod version_response;
 pub use self::version_response::VersionResponse;
@@ -0,0 +1,26 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseList {
+    /// The releases in the deployment directory, oldest first.
+    #[serde(rename = "releases")]
+    pub releases: Vec<models::DeploymentDirRelease>,
+}
+
+impl ReleaseList {
+    pub fn new(releases: Vec<models::DeploymentDirRelease>) -> ReleaseList {
+        ReleaseList {
+            releases,
+        }
+    }
+}
+
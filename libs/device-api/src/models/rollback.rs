@@ -0,0 +1,26 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rollback {
+    /// The number of the release now live.
+    #[serde(rename = "release")]
+    pub release: i64,
+}
+
+impl Rollback {
+    pub fn new(release: i64) -> Rollback {
+        Rollback {
+            release,
+        }
+    }
+}
+
@@ -0,0 +1,30 @@
+/*
+ * Miru Agent API
+ *
+ * The API between the Miru Agent and any external client living on the same device as the Miru Agent
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyncHistory {
+    /// Counts and durations over every recorded sync.
+    #[serde(rename = "summary")]
+    pub summary: serde_json::Value,
+    /// The recent syncs with their outcome and the time spent in each phase.
+    #[serde(rename = "records")]
+    pub records: Vec<serde_json::Value>,
+}
+
+impl SyncHistory {
+    pub fn new(summary: serde_json::Value, records: Vec<serde_json::Value>) -> SyncHistory {
+        SyncHistory {
+            summary,
+            records,
+        }
+    }
+}
+
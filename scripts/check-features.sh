@@ -0,0 +1,20 @@
+#!/bin/sh
+# Builds the agent with each optional subsystem on its own, with none of them and
+# with all of them so a feature-gated path can't silently stop compiling.
+set -e
+REPO_ROOT=$(git rev-parse --show-toplevel)
+cd "$REPO_ROOT"
+
+FEATURES="mqtt server telemetry installer"
+
+check() {
+    echo "Checking features: ${1:-<none>}"
+    cargo clippy --package miru-agent --lib --bins --no-default-features \
+        --features "$1" -- -D warnings
+}
+
+check ""
+for feature in $FEATURES; do
+    check "$feature"
+done
+check "$(echo $FEATURES | tr ' ' ',')"